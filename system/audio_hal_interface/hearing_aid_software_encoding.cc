@@ -81,6 +81,9 @@ void set_remote_delay(uint16_t delay_report_ms) {
   aidl::hearing_aid::set_remote_delay(delay_report_ms);
 }
 
+// The HAL negotiates the PCM format with the audio framework on its own.
+void set_sample_rate(uint32_t sample_rate) {}
+
 // Read from the FMQ of BluetoothAudio HAL
 size_t read(uint8_t* p_buf, uint32_t len) {
   if (HalVersionManager::GetHalTransport() ==
@@ -45,6 +45,10 @@ void end_session();
 
 void set_remote_delay(uint16_t delay_report_ms);
 
+// Rate of the PCM that `read` should return, which depends on the codec the
+// hearing aids agreed on.
+void set_sample_rate(uint32_t sample_rate);
+
 // Read from the FMQ of BluetoothAudio HAL
 size_t read(uint8_t* p_buf, uint32_t len);
 
@@ -16,26 +16,184 @@
 
 #include "audio_hal_interface/hearing_aid_software_encoding.h"
 
+#include <base/logging.h>
+#include <errno.h>
+#include <grp.h>
+#include <sys/stat.h>
+
+#include <memory>
+
+#include "audio_hal_interface/hearing_aid_software_encoding_host.h"
+#include "osi/include/log.h"
+#include "udrv/include/uipc.h"
+
+#define HEARING_AID_DATA_READ_POLL_MS 10
+#define HEARING_AID_HOST_DATA_PATH "/var/run/bluetooth/audio/.hearing_aid_data"
+// Same group as the A2DP socket, so the audio server can open both.
+#define HEARING_AID_HOST_DATA_GROUP "bluetooth-audio"
+
+namespace {
+
+std::unique_ptr<tUIPC_STATE> hearing_aid_uipc = nullptr;
+bluetooth::audio::hearing_aid::StreamCallbacks stream_cb_;
+bool session_started_ = false;
+bool streaming_ = false;
+uint64_t total_bytes_read_;
+timespec data_position_;
+uint16_t remote_delay_report_ms_;
+uint32_t sample_rate_ = 16000;
+
+static void hearing_aid_data_cb([[maybe_unused]] tUIPC_CH_ID ch_id,
+                                tUIPC_EVENT event) {
+  switch (event) {
+    case UIPC_OPEN_EVT:
+      // Read directly from the media task from here on.
+      UIPC_Ioctl(*hearing_aid_uipc, UIPC_CH_ID_AV_AUDIO,
+                 UIPC_REG_REMOVE_ACTIVE_READSET, NULL);
+      UIPC_Ioctl(*hearing_aid_uipc, UIPC_CH_ID_AV_AUDIO,
+                 UIPC_SET_READ_POLL_TMO,
+                 reinterpret_cast<void*>(HEARING_AID_DATA_READ_POLL_MS));
+      break;
+
+    case UIPC_CLOSE_EVT:
+      // The audio server went away without stopping the stream.
+      if (streaming_) {
+        streaming_ = false;
+        stream_cb_.on_suspend_();
+      }
+      break;
+
+    default:
+      LOG(ERROR) << __func__ << ": event " << dump_uipc_event(event)
+                 << " not handled";
+      break;
+  }
+}
+
+// See a2dp_encoding_host.cc for why the socket is handed to the group.
+static void hearing_aid_data_path_open() {
+  UIPC_Open(*hearing_aid_uipc, UIPC_CH_ID_AV_AUDIO, hearing_aid_data_cb,
+            HEARING_AID_HOST_DATA_PATH);
+  struct group* grp = getgrnam(HEARING_AID_HOST_DATA_GROUP);
+  chmod(HEARING_AID_HOST_DATA_PATH, 0770);
+  if (grp) {
+    int res = chown(HEARING_AID_HOST_DATA_PATH, -1, grp->gr_gid);
+    if (res == -1) {
+      LOG(ERROR) << __func__ << " failed: " << strerror(errno);
+    }
+  }
+}
+
+}  // namespace
+
 namespace bluetooth {
 namespace audio {
 namespace hearing_aid {
 
-bool is_hal_enabled() { return false; }
+bool StartRequest() {
+  total_bytes_read_ = 0;
+  data_position_ = {0, 0};
+
+  if (!session_started_) {
+    LOG(ERROR) << __func__ << ": no hearing aid is ready to stream";
+    return false;
+  }
+
+  if (streaming_) {
+    return true;
+  }
+
+  hearing_aid_data_path_open();
+  if (!stream_cb_.on_resume_(true)) {
+    LOG(ERROR) << __func__ << ": hearing aids refused to start";
+    return false;
+  }
+
+  streaming_ = true;
+  return true;
+}
+
+bool StopRequest() {
+  if (!streaming_) {
+    return true;
+  }
+
+  streaming_ = false;
+  return stream_cb_.on_suspend_();
+}
+
+AudioConfig GetAudioConfig() {
+  AudioConfig config{
+      .sample_rate = sample_rate_,
+      .bits_per_sample = 16,
+      .channel_count = 2,
+  };
+  return config;
+}
+
+PresentationPosition GetPresentationPosition() {
+  PresentationPosition presentation_position{
+      .remote_delay_report_ns = remote_delay_report_ms_ * 1000000ull,
+      .total_bytes_read = total_bytes_read_,
+      .data_position = data_position_,
+  };
+  return presentation_position;
+}
+
+bool is_hal_enabled() { return hearing_aid_uipc != nullptr; }
 
 bool init(StreamCallbacks stream_cb,
           bluetooth::common::MessageLoopThread* message_loop) {
-  return false;
+  hearing_aid_uipc = UIPC_Init();
+  stream_cb_ = std::move(stream_cb);
+  session_started_ = false;
+  streaming_ = false;
+  total_bytes_read_ = 0;
+  data_position_ = {};
+  remote_delay_report_ms_ = 0;
+
+  return true;
+}
+
+void cleanup() {
+  end_session();
+
+  if (hearing_aid_uipc != nullptr) {
+    UIPC_Close(*hearing_aid_uipc, UIPC_CH_ID_ALL);
+    hearing_aid_uipc = nullptr;
+  }
 }
 
-void cleanup() {}
+void start_session() { session_started_ = true; }
 
-void start_session() {}
+void end_session() {
+  session_started_ = false;
+  streaming_ = false;
+  remote_delay_report_ms_ = 0;
+  if (hearing_aid_uipc != nullptr) {
+    UIPC_Close(*hearing_aid_uipc, UIPC_CH_ID_AV_AUDIO);
+  }
+}
+
+size_t read(uint8_t* p_buf, uint32_t len) {
+  if (hearing_aid_uipc == nullptr) {
+    return 0;
+  }
 
-void end_session() {}
+  uint32_t bytes_read =
+      UIPC_Read(*hearing_aid_uipc, UIPC_CH_ID_AV_AUDIO, p_buf, len);
+  total_bytes_read_ += bytes_read;
+  // MONOTONIC_RAW isn't affected by NTP, audio stack rely on this
+  // to get precise delay calculation.
+  clock_gettime(CLOCK_MONOTONIC_RAW, &data_position_);
+  return bytes_read;
+}
 
-size_t read(uint8_t* p_buf, uint32_t len) { return 0; }
+void set_remote_delay(uint16_t delay_report_ms) {
+  remote_delay_report_ms_ = delay_report_ms;
+}
 
-void set_remote_delay(uint16_t delay_report_ms) {}
+void set_sample_rate(uint32_t sample_rate) { sample_rate_ = sample_rate; }
 
 }  // namespace hearing_aid
 }  // namespace audio
@@ -0,0 +1,55 @@
+/*
+ * Copyright 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#pragma once
+
+#include <stdint.h>
+#include <time.h>
+
+namespace bluetooth {
+namespace audio {
+namespace hearing_aid {
+
+// Format of the PCM the audio server writes to the data socket, which the
+// stack encodes to G.722 for the hearing aids. Only the sample rate varies,
+// with the codec of the hearing aids in use.
+struct AudioConfig {
+  uint32_t sample_rate;
+  uint8_t bits_per_sample;
+  uint8_t channel_count;
+};
+
+// Invoked by audio server before starting a stream.
+AudioConfig GetAudioConfig();
+
+// Invoked by audio server when it has audio data to stream.
+bool StartRequest();
+
+// Invoked by audio server when audio streaming is done.
+bool StopRequest();
+
+struct PresentationPosition {
+  uint64_t remote_delay_report_ns;
+  uint64_t total_bytes_read;
+  timespec data_position;
+};
+
+// Invoked by audio server to check audio presentation position periodically.
+PresentationPosition GetPresentationPosition();
+
+}  // namespace hearing_aid
+}  // namespace audio
+}  // namespace bluetooth
@@ -2364,6 +2364,15 @@ static void handle_role_change(const RawAddress& bd_addr, tHCI_ROLE new_role,
     bta_dm_check_av();
   }
   bta_sys_notify_role_chg(bd_addr, new_role, hci_status);
+
+  if (bta_dm_cb.p_sec_cback) {
+    tBTA_DM_SEC sec_event;
+    memset(&sec_event, 0, sizeof(tBTA_DM_SEC));
+    sec_event.role_chg.bd_addr = bd_addr;
+    sec_event.role_chg.new_role = new_role;
+    sec_event.role_chg.hci_status = hci_status;
+    bta_dm_cb.p_sec_cback(BTA_DM_ROLE_CHG_EVT, &sec_event);
+  }
 }
 
 void BTA_dm_report_role_change(const RawAddress bd_addr, tHCI_ROLE new_role,
@@ -2372,6 +2381,51 @@ void BTA_dm_report_role_change(const RawAddress bd_addr, tHCI_ROLE new_role,
       FROM_HERE, base::Bind(handle_role_change, bd_addr, new_role, hci_status));
 }
 
+static void handle_key_size_violation(const RawAddress& bd_addr,
+                                      tBT_TRANSPORT transport,
+                                      uint8_t key_size) {
+  if (bta_dm_cb.p_sec_cback) {
+    tBTA_DM_SEC sec_event;
+    memset(&sec_event, 0, sizeof(tBTA_DM_SEC));
+    sec_event.key_size_violation.bd_addr = bd_addr;
+    sec_event.key_size_violation.transport = transport;
+    sec_event.key_size_violation.key_size = key_size;
+    bta_dm_cb.p_sec_cback(BTA_DM_KEY_SIZE_VIOLATION_EVT, &sec_event);
+  }
+}
+
+void BTA_dm_report_key_size_violation(const RawAddress bd_addr,
+                                      tBT_TRANSPORT transport,
+                                      uint8_t key_size) {
+  do_in_main_thread(FROM_HERE, base::Bind(handle_key_size_violation, bd_addr,
+                                          transport, key_size));
+}
+
+static void handle_encryption_change(const RawAddress& bd_addr,
+                                     tBT_TRANSPORT transport,
+                                     tHCI_STATUS hci_status, bool encrypted,
+                                     bool key_refresh) {
+  if (bta_dm_cb.p_sec_cback) {
+    tBTA_DM_SEC sec_event;
+    memset(&sec_event, 0, sizeof(tBTA_DM_SEC));
+    sec_event.encryption_change.bd_addr = bd_addr;
+    sec_event.encryption_change.transport = transport;
+    sec_event.encryption_change.hci_status = hci_status;
+    sec_event.encryption_change.encrypted = encrypted;
+    sec_event.encryption_change.key_refresh = key_refresh;
+    bta_dm_cb.p_sec_cback(BTA_DM_ENCRYPTION_CHANGE_EVT, &sec_event);
+  }
+}
+
+void BTA_dm_report_encryption_change(const RawAddress bd_addr,
+                                     tBT_TRANSPORT transport,
+                                     tHCI_STATUS hci_status, bool encrypted,
+                                     bool key_refresh) {
+  do_in_main_thread(FROM_HERE,
+                    base::Bind(handle_encryption_change, bd_addr, transport,
+                               hci_status, encrypted, key_refresh));
+}
+
 void handle_remote_features_complete(const RawAddress& bd_addr) {
   tBTA_DM_PEER_DEVICE* p_dev = bta_dm_find_peer_device(bd_addr);
   if (!p_dev) {
@@ -33,6 +33,7 @@
 #include "stack/btm/btm_sec.h"
 #include "stack/include/bt_octets.h"
 #include "stack/include/btm_api.h"
+#include "stack/include/btm_ble_api.h"
 #include "stack/include/btm_client_interface.h"
 #include "stack/include/btu.h"  // do_in_main_thread
 #include "types/bluetooth/uuid.h"
@@ -519,6 +520,24 @@ void BTA_DmBleConfigLocalPrivacy(bool privacy_enable) {
       FROM_HERE, base::Bind(bta_dm_ble_config_local_privacy, privacy_enable));
 }
 
+/*******************************************************************************
+ *
+ * Function         BTA_DmBleConfigStaticAddress
+ *
+ * Description      Use a static random address on the local device instead of
+ *                  the public one. Privacy is disabled.
+ *
+ * Parameters:      static_addr   - the static random address.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void BTA_DmBleConfigStaticAddress(const RawAddress& static_addr) {
+  do_in_main_thread(
+      FROM_HERE,
+      base::Bind(base::IgnoreResult(BTM_BleConfigStaticAddress), static_addr));
+}
+
 /*******************************************************************************
  *
  * Function         BTA_DmBleGetEnergyInfo
@@ -399,6 +399,7 @@ void HearingAidAudioSource::Start(const CodecConfiguration& codecConfiguration,
   if (bluetooth::audio::hearing_aid::is_hal_enabled()) {
     bluetooth::audio::hearing_aid::start_session();
     bluetooth::audio::hearing_aid::set_remote_delay(remote_delay_ms);
+    bluetooth::audio::hearing_aid::set_sample_rate(sample_rate);
   }
   localAudioReceiver = audioReceiver;
 }
@@ -41,6 +41,7 @@
 #include "types/ble_address_with_type.h"
 #include "types/bluetooth/uuid.h"
 #include "types/bt_transport.h"
+#include "types/hci_role.h"
 #include "types/raw_address.h"
 
 /*****************************************************************************
@@ -224,6 +225,9 @@ typedef enum : uint8_t {
   BTA_DM_BLE_SC_CR_LOC_OOB_EVT = 31, /* SMP SC Create Local OOB request event */
   BTA_DM_REPORT_BONDING_EVT = 32,    /*handle for pin or key missing*/
   BTA_DM_LE_ADDR_ASSOC_EVT = 33,     /* identity address association event */
+  BTA_DM_ROLE_CHG_EVT = 34,          /* ACL role change event */
+  BTA_DM_KEY_SIZE_VIOLATION_EVT = 35, /* Encryption key too short event */
+  BTA_DM_ENCRYPTION_CHANGE_EVT = 36,  /* Encryption change or key refresh */
 } tBTA_DM_SEC_EVT;
 
 /* Structure associated with BTA_DM_PIN_REQ_EVT */
@@ -388,6 +392,29 @@ typedef struct {
   RawAddress id_addr;
 } tBTA_DM_PROC_ID_ADDR;
 
+/* Structure associated with BTA_DM_ROLE_CHG_EVT */
+typedef struct {
+  RawAddress bd_addr;     /* BD address peer device. */
+  tHCI_ROLE new_role;     /* Local role on the link. */
+  tHCI_STATUS hci_status; /* Status of the role change. */
+} tBTA_DM_ROLE_CHG;
+
+/* Structure associated with BTA_DM_KEY_SIZE_VIOLATION_EVT */
+typedef struct {
+  RawAddress bd_addr;      /* BD address peer device. */
+  tBT_TRANSPORT transport; /* Transport of the link. */
+  uint8_t key_size;        /* Rejected key size, in octets. */
+} tBTA_DM_KEY_SIZE_VIOLATION;
+
+/* Structure associated with BTA_DM_ENCRYPTION_CHANGE_EVT */
+typedef struct {
+  RawAddress bd_addr;      /* BD address peer device. */
+  tBT_TRANSPORT transport; /* Transport of the link. */
+  tHCI_STATUS hci_status;  /* Status of the change. */
+  bool encrypted;          /* Whether the link is now encrypted. */
+  bool key_refresh;        /* Whether the key was refreshed. */
+} tBTA_DM_ENCRYPTION_CHANGE;
+
 /* Union of all security callback structures */
 typedef union {
   tBTA_DM_PIN_REQ pin_req;        /* PIN request. */
@@ -406,6 +433,9 @@ typedef union {
   tBTA_DM_LOC_OOB_DATA local_oob_data; /* Local OOB data generated by us */
   tBTA_DM_RC_UNPAIR delete_key_RC_to_unpair;
   tBTA_DM_PROC_ID_ADDR proc_id_addr; /* Identity address event */
+  tBTA_DM_ROLE_CHG role_chg;         /* ACL role change event */
+  tBTA_DM_KEY_SIZE_VIOLATION key_size_violation; /* Key too short event */
+  tBTA_DM_ENCRYPTION_CHANGE encryption_change;   /* Encryption change event */
 } tBTA_DM_SEC;
 
 /* Security callback */
@@ -1126,6 +1156,20 @@ extern void BTA_DmBleCsisObserve(bool observe,
  ******************************************************************************/
 extern void BTA_DmBleConfigLocalPrivacy(bool privacy_enable);
 
+/*******************************************************************************
+ *
+ * Function         BTA_DmBleConfigStaticAddress
+ *
+ * Description      Use a static random address on the local device instead of
+ *                  the public one. Privacy is disabled.
+ *
+ * Parameters:      static_addr   - the static random address.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+extern void BTA_DmBleConfigStaticAddress(const RawAddress& static_addr);
+
 /*******************************************************************************
  *
  * Function         BTA_DmBleEnableRemotePrivacy
@@ -28,4 +28,11 @@ void BTA_dm_acl_up(const RawAddress bd_addr, tBT_TRANSPORT transport);
 void BTA_dm_acl_down(const RawAddress bd_addr, tBT_TRANSPORT transport);
 void BTA_dm_report_role_change(const RawAddress bd_addr, tHCI_ROLE new_role,
                                tHCI_STATUS hci_status);
+void BTA_dm_report_key_size_violation(const RawAddress bd_addr,
+                                      tBT_TRANSPORT transport,
+                                      uint8_t key_size);
+void BTA_dm_report_encryption_change(const RawAddress bd_addr,
+                                     tBT_TRANSPORT transport,
+                                     tHCI_STATUS hci_status, bool encrypted,
+                                     bool key_refresh);
 void BTA_dm_notify_remote_features_complete(const RawAddress bd_addr);
@@ -222,6 +222,14 @@ void invoke_address_consolidate_cb(RawAddress main_bd_addr,
                                    RawAddress secondary_bd_addr);
 void invoke_le_address_associate_cb(RawAddress main_bd_addr,
                                     RawAddress secondary_bd_addr);
+void invoke_acl_role_changed_cb(bt_status_t status, RawAddress bd_addr,
+                                uint8_t new_role);
+void invoke_key_size_violation_cb(RawAddress bd_addr, int transport,
+                                  uint8_t key_size);
+void invoke_encryption_change_cb(RawAddress bd_addr, int transport,
+                                 bt_hci_error_code_t hci_status, bool encrypted,
+                                 bool key_refresh);
+void invoke_auth_failure_cb(RawAddress bd_addr, bt_hci_error_code_t hci_reason);
 void invoke_acl_state_changed_cb(bt_status_t status, RawAddress bd_addr,
                                  bt_acl_state_t state, int transport_link_type,
                                  bt_hci_error_code_t hci_reason);
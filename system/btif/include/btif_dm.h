@@ -74,6 +74,14 @@ void btif_dm_generate_local_oob_data(tBT_TRANSPORT transport);
 
 void btif_dm_clear_event_filter();
 
+/* Overrides the LE address policy applied when the stack is enabled, which
+ * otherwise only depends on the privacy property. |static_address| is used when
+ * it isn't empty and privacy is disabled. A zero |rpa_timeout_s| keeps the
+ * default rotation times. Has no effect once the stack is enabled. */
+void btif_dm_set_le_address_policy(bool privacy_enabled,
+                                   const RawAddress& static_address,
+                                   uint32_t rpa_timeout_s);
+
 /*callout for reading SMP properties from Text file*/
 bool btif_dm_get_smp_config(tBTE_APPL_CFG* p_cfg);
 
@@ -893,6 +893,50 @@ void invoke_le_address_associate_cb(RawAddress main_bd_addr,
                      },
                      main_bd_addr, secondary_bd_addr));
 }
+void invoke_acl_role_changed_cb(bt_status_t status, RawAddress bd_addr,
+                                uint8_t new_role) {
+  do_in_jni_thread(FROM_HERE,
+                   base::BindOnce(
+                       [](bt_status_t status, RawAddress bd_addr,
+                          uint8_t new_role) {
+                         HAL_CBACK(bt_hal_cbacks, acl_role_changed_cb, status,
+                                   &bd_addr, new_role);
+                       },
+                       status, bd_addr, new_role));
+}
+void invoke_key_size_violation_cb(RawAddress bd_addr, int transport,
+                                  uint8_t key_size) {
+  do_in_jni_thread(FROM_HERE,
+                   base::BindOnce(
+                       [](RawAddress bd_addr, int transport, uint8_t key_size) {
+                         HAL_CBACK(bt_hal_cbacks, key_size_violation_cb,
+                                   &bd_addr, transport, key_size);
+                       },
+                       bd_addr, transport, key_size));
+}
+void invoke_encryption_change_cb(RawAddress bd_addr, int transport,
+                                 bt_hci_error_code_t hci_status, bool encrypted,
+                                 bool key_refresh) {
+  do_in_jni_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress bd_addr, int transport, bt_hci_error_code_t hci_status,
+             bool encrypted, bool key_refresh) {
+            HAL_CBACK(bt_hal_cbacks, encryption_change_cb, &bd_addr, transport,
+                      hci_status, encrypted, key_refresh);
+          },
+          bd_addr, transport, hci_status, encrypted, key_refresh));
+}
+void invoke_auth_failure_cb(RawAddress bd_addr,
+                            bt_hci_error_code_t hci_reason) {
+  do_in_jni_thread(FROM_HERE,
+                   base::BindOnce(
+                       [](RawAddress bd_addr, bt_hci_error_code_t hci_reason) {
+                         HAL_CBACK(bt_hal_cbacks, auth_failure_cb, &bd_addr,
+                                   hci_reason);
+                       },
+                       bd_addr, hci_reason));
+}
 void invoke_acl_state_changed_cb(bt_status_t status, RawAddress bd_addr,
                                  bt_acl_state_t state, int transport_link_type,
                                  bt_hci_error_code_t hci_reason) {
@@ -50,6 +50,7 @@
 #include <unistd.h>
 
 #include <mutex>
+#include <optional>
 
 #ifdef OS_ANDROID
 #include <android/sysprop/BluetoothProperties.sysprop.h>
@@ -80,6 +81,7 @@
 #include "device/include/interop.h"
 #include "gd/common/lru_cache.h"
 #include "internal_include/stack_config.h"
+#include "main/shim/acl_api.h"
 #include "main/shim/dumpsys.h"
 #include "main/shim/shim.h"
 #include "osi/include/allocator.h"
@@ -239,6 +241,14 @@ static size_t btif_num_bond_events = 0;
 static size_t btif_events_start_index = 0;
 static size_t btif_events_end_index = 0;
 
+/* Set through btif_dm_set_le_address_policy and applied by BTIF_dm_enable */
+typedef struct {
+  bool privacy_enabled;
+  RawAddress static_address;
+  uint32_t rpa_timeout_s;
+} btif_dm_le_address_policy_t;
+static std::optional<btif_dm_le_address_policy_t> le_address_policy;
+
 /******************************************************************************
  *  Static functions
  *****************************************************************************/
@@ -1147,6 +1157,8 @@ static void btif_dm_auth_cmpl_evt(tBTA_DM_AUTH_CMPL* p_auth_cmpl) {
       case HCI_ERR_UNSPECIFIED:
         BTIF_TRACE_DEBUG(" %s() Authentication fail reason %d", __func__,
                          p_auth_cmpl->fail_reason);
+        invoke_auth_failure_cb(bd_addr, static_cast<bt_hci_error_code_t>(
+                                            p_auth_cmpl->fail_reason));
         if (pairing_cb.autopair_attempts == 1) {
           /* Create the Bond once again */
           BTIF_TRACE_WARNING("%s() auto pair failed. Reinitiate Bond",
@@ -1630,8 +1642,21 @@ void BTIF_dm_enable() {
     ble_privacy_enabled = false;
   }
 #endif
-  LOG_INFO("%s BLE Privacy: %d", __func__, ble_privacy_enabled);
-  BTA_DmBleConfigLocalPrivacy(ble_privacy_enabled);
+  if (le_address_policy) {
+    ble_privacy_enabled = le_address_policy->privacy_enabled;
+    bluetooth::shim::ACL_SetLeRpaTimeout(
+        std::chrono::seconds(le_address_policy->rpa_timeout_s));
+  }
+
+  if (le_address_policy && !ble_privacy_enabled &&
+      !le_address_policy->static_address.IsEmpty()) {
+    LOG_INFO("%s BLE static address: %s", __func__,
+             le_address_policy->static_address.ToString().c_str());
+    BTA_DmBleConfigStaticAddress(le_address_policy->static_address);
+  } else {
+    LOG_INFO("%s BLE Privacy: %d", __func__, ble_privacy_enabled);
+    BTA_DmBleConfigLocalPrivacy(ble_privacy_enabled);
+  }
 
   /* for each of the enabled services in the mask, trigger the profile
    * enable */
@@ -1910,6 +1935,28 @@ static void btif_dm_upstreams_evt(uint16_t event, char* p_param) {
                                      p_data->proc_id_addr.id_addr);
       break;
 
+    case BTA_DM_ROLE_CHG_EVT:
+      invoke_acl_role_changed_cb(p_data->role_chg.hci_status == HCI_SUCCESS
+                                     ? BT_STATUS_SUCCESS
+                                     : BT_STATUS_FAIL,
+                                 p_data->role_chg.bd_addr,
+                                 p_data->role_chg.new_role);
+      break;
+
+    case BTA_DM_KEY_SIZE_VIOLATION_EVT:
+      invoke_key_size_violation_cb(p_data->key_size_violation.bd_addr,
+                                   p_data->key_size_violation.transport,
+                                   p_data->key_size_violation.key_size);
+      break;
+
+    case BTA_DM_ENCRYPTION_CHANGE_EVT: {
+      const tBTA_DM_ENCRYPTION_CHANGE& change = p_data->encryption_change;
+      invoke_encryption_change_cb(
+          change.bd_addr, change.transport,
+          static_cast<bt_hci_error_code_t>(change.hci_status), change.encrypted,
+          change.key_refresh);
+    } break;
+
     default:
       BTIF_TRACE_WARNING("%s: unhandled event (%d)", __func__, event);
       break;
@@ -3481,3 +3528,10 @@ void btif_dm_clear_event_filter() {
   LOG_VERBOSE("%s: called", __func__);
   bta_dm_clear_event_filter();
 }
+
+void btif_dm_set_le_address_policy(bool privacy_enabled,
+                                   const RawAddress& static_address,
+                                   uint32_t rpa_timeout_s) {
+  le_address_policy = btif_dm_le_address_policy_t{
+      privacy_enabled, static_address, rpa_timeout_s};
+}
@@ -112,6 +112,8 @@ struct btif_hf_cb_t {
   RawAddress connected_bda;
   bthf_connection_state_t state;
   tBTA_AG_PEER_FEAT peer_feat;
+  tBTA_AG_PEER_CODEC peer_codecs;
+  tBTA_AG_PEER_CODEC codec_override;
   int num_active;
   int num_held;
   bthf_call_state_t call_setup_state;
@@ -254,9 +256,18 @@ static void reset_control_block(btif_hf_cb_t* hf_cb) {
   hf_cb->is_initiator = false;
   hf_cb->connected_bda = RawAddress::kEmpty;
   hf_cb->peer_feat = 0;
+  hf_cb->peer_codecs = 0;
+  hf_cb->codec_override = BTM_SCO_CODEC_NONE;
   clear_phone_state_multihf(hf_cb);
 }
 
+/* Codec to offer the headset for SCO, unless overridden by SetCodecOverride */
+static tBTA_AG_PEER_CODEC preferred_codec(const btif_hf_cb_t& hf_cb) {
+  if (hf_cb.codec_override != BTM_SCO_CODEC_NONE) return hf_cb.codec_override;
+  return (hf_cb.peer_codecs & BTM_SCO_CODEC_MSBC) ? BTM_SCO_CODEC_MSBC
+                                                 : BTM_SCO_CODEC_CVSD;
+}
+
 /**
  * Check if Service Level Connection (SLC) is established for bd_addr
  *
@@ -541,7 +552,8 @@ static void btif_hf_upstreams_evt(uint16_t event, char* p_param) {
       we should set the BTA AG Codec to mSBC. This would trigger a +BCS to mSBC
       at the time
       of SCO connection establishment */
-      if (p_data->val.num & BTM_SCO_CODEC_MSBC) {
+      btif_hf_cb[idx].peer_codecs = p_data->val.num;
+      if (preferred_codec(btif_hf_cb[idx]) == BTM_SCO_CODEC_MSBC) {
         BTIF_TRACE_EVENT("%s: btif_hf override-Preferred Codec to MSBC",
                          __func__);
         BTA_AgSetCodec(btif_hf_cb[idx].handle, BTM_SCO_CODEC_MSBC);
@@ -747,6 +759,8 @@ class HeadsetInterface : Interface {
   bt_status_t SetScoAllowed(bool value) override;
   bt_status_t SendBsir(bool value, RawAddress* bd_addr) override;
   bt_status_t SetActiveDevice(RawAddress* active_device_addr) override;
+  bt_status_t SetCodecOverride(bthf_wbs_config_t codec,
+                               RawAddress* bd_addr) override;
 };
 
 bt_status_t HeadsetInterface::Init(Callbacks* callbacks, int max_hf_clients,
@@ -1447,6 +1461,36 @@ bt_status_t HeadsetInterface::SetActiveDevice(RawAddress* active_device_addr) {
   return BT_STATUS_SUCCESS;
 }
 
+bt_status_t HeadsetInterface::SetCodecOverride(bthf_wbs_config_t codec,
+                                               RawAddress* bd_addr) {
+  CHECK_BTHF_INIT();
+  int idx = btif_hf_idx_by_bdaddr(bd_addr);
+  if ((idx < 0) || (idx >= BTA_AG_MAX_NUM_CLIENTS)) {
+    BTIF_TRACE_ERROR("%s: Invalid index %d", __func__, idx);
+    return BT_STATUS_FAIL;
+  }
+  if (!IsSlcConnected(bd_addr)) {
+    LOG(ERROR) << ": SLC not connected for " << *bd_addr;
+    return BT_STATUS_NOT_READY;
+  }
+
+  tBTA_AG_PEER_CODEC override = BTM_SCO_CODEC_NONE;
+  if (codec == BTHF_WBS_YES) {
+    if (!(btif_hf_cb[idx].peer_codecs & BTM_SCO_CODEC_MSBC)) {
+      LOG(ERROR) << __func__ << ": " << *bd_addr << " doesn't support mSBC";
+      return BT_STATUS_UNSUPPORTED;
+    }
+    override = BTM_SCO_CODEC_MSBC;
+  } else if (codec == BTHF_WBS_NO) {
+    override = BTM_SCO_CODEC_CVSD;
+  }
+
+  btif_hf_cb[idx].codec_override = override;
+  /* Takes effect with the codec negotiation of the next audio connection */
+  BTA_AgSetCodec(btif_hf_cb[idx].handle, preferred_codec(btif_hf_cb[idx]));
+  return BT_STATUS_SUCCESS;
+}
+
 /*******************************************************************************
  *
  * Function         btif_hf_execute_service
@@ -83,6 +83,15 @@ void generate_local_oob_data_callback(tBT_TRANSPORT transport,
                                       bt_oob_data_t oob_data) {}
 void switch_buffer_size_callback(bool is_low_latency_buffer_size) {}
 void switch_codec_callback(bool is_low_latency_buffer_size) {}
+void acl_role_changed_callback(bt_status_t status, RawAddress* remote_bd_addr,
+                               uint8_t new_role) {}
+void key_size_violation_callback(RawAddress* remote_bd_addr, int transport,
+                                 uint8_t key_size) {}
+void encryption_change_callback(RawAddress* remote_bd_addr, int transport,
+                                bt_hci_error_code_t hci_status, bool encrypted,
+                                bool key_refresh) {}
+void auth_failure_callback(RawAddress* remote_bd_addr,
+                           bt_hci_error_code_t hci_reason) {}
 #undef TESTCB
 
 bt_callbacks_t callbacks = {
@@ -106,6 +115,10 @@ bt_callbacks_t callbacks = {
     .generate_local_oob_data_cb = generate_local_oob_data_callback,
     .switch_buffer_size_cb = switch_buffer_size_callback,
     .switch_codec_cb = switch_codec_callback,
+    .acl_role_changed_cb = acl_role_changed_callback,
+    .key_size_violation_cb = key_size_violation_callback,
+    .encryption_change_cb = encryption_change_callback,
+    .auth_failure_cb = auth_failure_callback,
 };
 
 }  // namespace
@@ -182,7 +182,9 @@ class Controller : public Module {
   static const ModuleFactory Factory;
 
   static constexpr uint64_t kDefaultEventMask = 0x3dbfffffffffffff;
-  static constexpr uint64_t kDefaultLeEventMask = 0x000000004d02fe7f;
+  // Includes the LE Path Loss Threshold and LE Transmit Power Reporting events, which only come
+  // once the host turns the reports on for a connection.
+  static constexpr uint64_t kDefaultLeEventMask = 0x00000001cd02fe7f;
 
  protected:
   void ListDependencies(ModuleList* list) const override;
@@ -1,141 +1,424 @@
 use crate::dbus_iface::{
-    export_bluetooth_callback_dbus_obj, export_bluetooth_connection_callback_dbus_obj,
-    export_bluetooth_gatt_callback_dbus_obj, export_bluetooth_manager_callback_dbus_obj,
-    export_suspend_callback_dbus_obj,
+    export_adv_monitor_callback_dbus_obj, export_bluetooth_callback_dbus_obj,
+    export_bluetooth_connection_callback_dbus_obj,
+    export_bluetooth_discovery_session_callback_dbus_obj, export_bluetooth_gatt_callback_dbus_obj,
+    export_bluetooth_link_policy_callback_dbus_obj, export_bluetooth_manager_callback_dbus_obj,
+    export_bluetooth_map_callback_dbus_obj, export_bluetooth_opp_callback_dbus_obj,
+    export_bluetooth_pan_callback_dbus_obj, export_bluetooth_pbap_callback_dbus_obj,
+    export_bluetooth_security_callback_dbus_obj, export_distance_measurement_callback_dbus_obj,
+    export_link_diagnostics_callback_dbus_obj, export_merged_discovery_callback_dbus_obj,
+    export_suspend_callback_dbus_obj, export_throughput_test_callback_dbus_obj,
+};
+use crate::gatt_decode;
+use crate::{print_info, print_warn};
+use crate::{ClientContext, ClientEvent, EventCategory, ADAPTER_SERVICE, MANAGER_SERVICE};
+use bt_topshim::btif::{
+    BtAclRole, BtBondState, BtSspVariant, BtStatus, BtTransport, RawAddress, Uuid128Bit,
 };
-use crate::ClientContext;
-use crate::{console_yellow, print_info};
-use bt_topshim::btif::{BtBondState, BtSspVariant};
 use bt_topshim::profiles::gatt::GattStatus;
+use bt_topshim::profiles::pan::BtPanConnectionState;
+use btstack::beacon::{Beacon, BeaconType};
 use btstack::bluetooth::{
-    BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
+    AdapterState, BluetoothDevice, FoundDeviceInfo, IBluetooth, IBluetoothCallback,
+    IBluetoothConnectionCallback, IBluetoothDiscoverySessionCallback, IBluetoothLinkPolicyCallback,
+    IBluetoothSecurityCallback, PairingRejectedReason, ProfileConnectResult,
+    ProfileConnectionReason, ProfileConnectionState,
+};
+use btstack::bluetooth_adv_monitor::IAdvMonitorCallback;
+use btstack::bluetooth_gatt::{
+    BluetoothGattCharacteristic, BluetoothGattService, GattNotification, IBluetoothGattCallback,
+    LePhy,
+};
+use btstack::bluetooth_map::{IBluetoothMapCallback, MapEvent, MapMessage, MapStatus};
+use btstack::bluetooth_opp::{IBluetoothOppCallback, OppTransfer, OppTransferStatus};
+use btstack::bluetooth_pan::IBluetoothPanCallback;
+use btstack::bluetooth_pbap::{IBluetoothPbapCallback, PbapPullStatus};
+use btstack::distance_measurement::{
+    DistanceMeasurementMethod, DistanceMeasurementResult, IBluetoothDistanceMeasurementCallback,
+};
+use btstack::gatt_status::GattStatusDisplay;
+use btstack::link_diagnostics::{
+    IBluetoothLinkDiagnosticsCallback, PathLossZone, PingStats, TxPowerReport, TxPowerSource,
+    TX_POWER_UNAVAILABLE, TX_POWER_UNMANAGED,
 };
-use btstack::bluetooth_gatt::{BluetoothGattService, IBluetoothGattCallback, LePhy};
 use btstack::suspend::ISuspendCallback;
+use btstack::throughput_test::{IBluetoothThroughputTestCallback, ThroughputTestResult};
+use btstack::uuid::{Profile, UuidHelper};
 use btstack::RPCProxy;
 use dbus::nonblock::SyncConnection;
+use dbus::strings::BusName;
 use dbus_crossroads::Crossroads;
-use dbus_projection::DisconnectWatcher;
-use manager_service::iface_bluetooth_manager::IBluetoothManagerCallback;
+use dbus_macros::RPCProxy;
+use dbus_projection::{CallbackLifetime, DisconnectWatcher};
+use manager_service::iface_bluetooth_manager::{
+    IBluetoothManagerCallback, IMergedDiscoveryCallback, MergedFoundDevice,
+};
 use std::sync::{Arc, Mutex};
 
+// Prints an event unless its category has been muted with `events mute`.
+macro_rules! print_event {
+    ( $context:expr, $category:expr, $($arg:tt)* ) => {
+        if $context.lock().unwrap().is_event_shown($category) {
+            print_info!($($arg)*);
+        }
+    };
+}
+
+// Major device classes (Assigned Numbers, 2.8.2) of devices without a display or keypad, which
+// come with a fixed PIN.
+const HEADLESS_MAJOR_CLASSES: [u32; 6] = [0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
+const MAJOR_CLASS_PERIPHERAL: u32 = 0x05;
+const MINOR_CLASS_KEYBOARD: u32 = 0x40;
+
+// Gets the PINs to try in turn with a device of class `cod` that asks for one, the ones most
+// commonly used by headsets, car kits and the like, or none if the user has to enter it.
+fn default_pins(cod: u32) -> Vec<String> {
+    let major = (cod >> 8) & 0x1f;
+    let keyboard = major == MAJOR_CLASS_PERIPHERAL && cod & MINOR_CLASS_KEYBOARD != 0;
+    if HEADLESS_MAJOR_CLASSES.contains(&major) && !keyboard {
+        vec![String::from("0000"), String::from("1234")]
+    } else {
+        vec![]
+    }
+}
+
 /// Callback context for manager interface callbacks.
-pub(crate) struct BtManagerCallback {
+#[derive(RPCProxy)]
+#[rpc_proxy(
+    export = "export_bluetooth_manager_callback_dbus_obj",
+    proxy_trait = "manager_service::RPCProxy"
+)]
+pub struct BtManagerCallback {
     objpath: String,
     context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
 }
 
 impl BtManagerCallback {
-    pub(crate) fn new(
+    pub fn new(
         objpath: String,
         context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
     ) -> Self {
-        Self { objpath, context, dbus_connection, dbus_crossroads }
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(MANAGER_SERVICE), disconnect_watcher),
+        }
     }
 }
 
 impl IBluetoothManagerCallback for BtManagerCallback {
     fn on_hci_device_changed(&self, hci_interface: i32, present: bool) {
-        print_info!("hci{} present = {}", hci_interface, present);
+        print_event!(
+            self.context,
+            EventCategory::Manager,
+            "hci{} present = {}",
+            hci_interface,
+            present
+        );
 
         if present {
             self.context.lock().unwrap().adapters.entry(hci_interface).or_insert(false);
         } else {
             self.context.lock().unwrap().adapters.remove(&hci_interface);
         }
+
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::HciDeviceChanged { hci_interface, present });
     }
 
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool) {
         self.context.lock().unwrap().set_adapter_enabled(hci_interface, enabled);
     }
+
+    fn on_shutdown_complete(&self) {
+        print_event!(self.context, EventCategory::Manager, "Bluetooth shutdown complete");
+        self.context.lock().unwrap().emit_event(ClientEvent::ShutdownComplete);
+    }
+
+    fn on_adapter_recovered(&self, hci_interface: i32, crash_count: u32) {
+        print_event!(
+            self.context,
+            EventCategory::Manager,
+            "hci{} recovered from a crash (crash count: {})",
+            hci_interface,
+            crash_count
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::AdapterRecovered { hci_interface, crash_count });
+    }
+}
+
+/// Callback context for the discovery the manager merges across adapters.
+#[derive(RPCProxy)]
+#[rpc_proxy(
+    export = "export_merged_discovery_callback_dbus_obj",
+    proxy_trait = "manager_service::RPCProxy"
+)]
+pub struct BtMergedDiscoveryCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtMergedDiscoveryCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(MANAGER_SERVICE), disconnect_watcher),
+        }
+    }
 }
 
-impl manager_service::RPCProxy for BtManagerCallback {
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
+impl IMergedDiscoveryCallback for BtMergedDiscoveryCallback {
+    fn on_merged_device_found(&self, device: MergedFoundDevice) {
+        let adapters: Vec<String> =
+            device.hci_interfaces.iter().map(|hci| format!("hci{}", hci)).collect();
+        print_event!(
+            self.context,
+            EventCategory::Discovery,
+            "Merged discovery found {} ({}) on {}, strongest on hci{} (rssi: {})",
+            device.address,
+            device.name,
+            adapters.join(", "),
+            device.hci_interface,
+            device.rssi
+        );
+    }
+
+    fn on_merged_discovering_changed(&self, discovering: bool) {
+        print_event!(
+            self.context,
+            EventCategory::Discovery,
+            "Merged discovering = {}",
+            discovering
+        );
     }
+}
+
+/// Callback container for one discovery session.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_discovery_session_callback_dbus_obj")]
+pub struct BtDiscoverySessionCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
 
-    fn get_object_id(&self) -> String {
-        self.objpath.clone()
+impl BtDiscoverySessionCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
+}
 
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
+impl IBluetoothDiscoverySessionCallback for BtDiscoverySessionCallback {
+    fn on_session_device_found(&self, session_id: u32, remote_device: BluetoothDevice) {
+        print_event!(
+            self.context,
+            EventCategory::Discovery,
+            "Discovery session {} found [{}] ({})",
+            session_id,
+            remote_device.address,
+            remote_device.name
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::SessionDeviceFound { session_id, device: remote_device });
     }
 
-    fn export_for_rpc(self: Box<Self>) {
-        let cr = self.dbus_crossroads.clone();
-        export_bluetooth_manager_callback_dbus_obj(
-            self.get_object_id(),
-            self.dbus_connection.clone(),
-            &mut cr.lock().unwrap(),
-            Arc::new(Mutex::new(self)),
-            Arc::new(Mutex::new(DisconnectWatcher::new())),
+    fn on_session_device_lost(&self, session_id: u32, remote_device: BluetoothDevice) {
+        print_event!(
+            self.context,
+            EventCategory::Discovery,
+            "Discovery session {} lost [{}]",
+            session_id,
+            remote_device.address
         );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::SessionDeviceLost { session_id, device: remote_device });
+    }
+
+    fn on_discovery_session_ended(&self, session_id: u32) {
+        print_info!("Discovery session {} ended as the adapter turned off", session_id);
+        self.context.lock().unwrap().emit_event(ClientEvent::DiscoverySessionEnded(session_id));
     }
 }
 
 /// Callback container for adapter interface callbacks.
-pub(crate) struct BtCallback {
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_callback_dbus_obj")]
+pub struct BtCallback {
     objpath: String,
     context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
 }
 
 impl BtCallback {
-    pub(crate) fn new(
+    pub fn new(
         objpath: String,
         context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
     ) -> Self {
-        Self { objpath, context, dbus_connection, dbus_crossroads }
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
 }
 
 impl IBluetoothCallback for BtCallback {
+    fn on_adapter_state_changed(&self, state: AdapterState) {
+        print_event!(self.context, EventCategory::Adapter, "Adapter state changed to {:?}", state);
+        self.context.lock().unwrap().adapter_state = state;
+        self.context.lock().unwrap().emit_event(ClientEvent::AdapterStateChanged(state));
+    }
+
     fn on_address_changed(&self, addr: String) {
-        print_info!("Address changed to {}", &addr);
-        self.context.lock().unwrap().adapter_address = Some(addr);
+        print_event!(self.context, EventCategory::Adapter, "Address changed to {}", &addr);
+        self.context.lock().unwrap().adapter_address = Some(addr.clone());
+        self.context.lock().unwrap().emit_event(ClientEvent::AddressChanged(addr));
     }
 
     fn on_name_changed(&self, name: String) {
-        print_info!("Name changed to {}", &name);
+        print_event!(self.context, EventCategory::Adapter, "Name changed to {}", &name);
+        self.context.lock().unwrap().emit_event(ClientEvent::NameChanged(name));
     }
 
     fn on_discoverable_changed(&self, discoverable: bool) {
-        print_info!("Discoverable changed to {}", &discoverable);
+        print_event!(
+            self.context,
+            EventCategory::Adapter,
+            "Discoverable changed to {}",
+            &discoverable
+        );
+        self.context.lock().unwrap().emit_event(ClientEvent::DiscoverableChanged(discoverable));
     }
 
-    fn on_device_found(&self, remote_device: BluetoothDevice) {
-        self.context
-            .lock()
-            .unwrap()
-            .found_devices
-            .entry(remote_device.address.clone())
-            .or_insert(remote_device.clone());
+    fn on_device_found(&self, remote_device: BluetoothDevice, info: FoundDeviceInfo) {
+        {
+            let mut context = self.context.lock().unwrap();
+            context.found_devices.entry(remote_device.address).or_insert(remote_device.clone());
+            context.found_rssi.add_reading(remote_device.address, info.rssi);
+        }
 
-        print_info!("Found device: {:?}", remote_device);
+        print_event!(
+            self.context,
+            EventCategory::Discovery,
+            "Found device: {:?} (class: 0x{:06x}, type: {:?}, rssi: {})",
+            remote_device,
+            info.class_of_device,
+            info.device_type,
+            info.rssi
+        );
+        self.context.lock().unwrap().emit_event(ClientEvent::DeviceFound(remote_device, info));
     }
 
     fn on_device_cleared(&self, remote_device: BluetoothDevice) {
-        match self.context.lock().unwrap().found_devices.remove(&remote_device.address) {
-            Some(_) => print_info!("Removed device: {:?}", remote_device),
-            None => (),
+        let removed = {
+            let mut context = self.context.lock().unwrap();
+            context.found_rssi.remove(&remote_device.address);
+            context.found_devices.remove(&remote_device.address).is_some()
         };
+        if removed {
+            print_event!(
+                self.context,
+                EventCategory::Discovery,
+                "Removed device: {:?}",
+                remote_device
+            );
+        }
+
+        self.context.lock().unwrap().emit_event(ClientEvent::DeviceCleared(remote_device));
+    }
+
+    fn on_remote_name_fetched(&self, remote_device: BluetoothDevice, success: bool) {
+        if success {
+            if let Some(device) =
+                self.context.lock().unwrap().found_devices.get_mut(&remote_device.address)
+            {
+                device.name = remote_device.name.clone();
+            }
+            print_event!(
+                self.context,
+                EventCategory::Discovery,
+                "Fetched the name of {}: {}",
+                remote_device.address.to_string(),
+                remote_device.name
+            );
+        } else {
+            print_event!(
+                self.context,
+                EventCategory::Discovery,
+                "Couldn't fetch the name of {}",
+                remote_device.address.to_string()
+            );
+        }
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::RemoteNameFetched { device: remote_device, success });
     }
 
     fn on_discovering_changed(&self, discovering: bool) {
         self.context.lock().unwrap().discovering_state = discovering;
 
-        print_info!("Discovering: {}", discovering);
+        print_event!(self.context, EventCategory::Discovery, "Discovering: {}", discovering);
+        self.context.lock().unwrap().emit_event(ClientEvent::DiscoveringChanged(discovering));
+    }
+
+    fn on_connected_count_changed(&self, count: u32) {
+        print_event!(self.context, EventCategory::Connection, "Connected devices: {}", count);
+        self.context.lock().unwrap().emit_event(ClientEvent::ConnectedCountChanged(count));
     }
 
     fn on_ssp_request(
@@ -147,7 +430,9 @@ impl IBluetoothCallback for BtCallback {
     ) {
         match variant {
             BtSspVariant::PasskeyNotification => {
-                print_info!(
+                print_event!(
+                    self.context,
+                    EventCategory::Bonding,
                     "Device [{}: {}] would like to pair, enter passkey on remote device: {:06}",
                     &remote_device.address,
                     &remote_device.name,
@@ -177,26 +462,178 @@ impl IBluetoothCallback for BtCallback {
                 }));
             }
             BtSspVariant::PasskeyEntry => {
-                println!("Got PasskeyEntry but it is not supported...");
+                print_warn!("Got PasskeyEntry but it is not supported...");
             }
             BtSspVariant::PasskeyConfirmation => {
-                println!("Got PasskeyConfirmation but there's nothing to do...");
+                print_warn!("Got PasskeyConfirmation but there's nothing to do...");
+            }
+        }
+    }
+
+    fn on_pin_request(&self, remote_device: BluetoothDevice, cod: u32, min_16_digit: bool) {
+        self.context.lock().unwrap().emit_event(ClientEvent::PinRequest {
+            device: remote_device.clone(),
+            cod,
+            min_16_digit,
+        });
+
+        // Devices we are bonding with that can't take a PIN are tried with the usual defaults.
+        let bonding = self.context.lock().unwrap().bonding_attempt.as_ref().cloned();
+        if !min_16_digit && bonding.map_or(false, |bd| bd.address == remote_device.address) {
+            let pin = {
+                let mut context = self.context.lock().unwrap();
+                let pins = context.default_pins.get_or_insert_with(|| default_pins(cod));
+                if pins.is_empty() {
+                    None
+                } else {
+                    Some(pins.remove(0))
+                }
+            };
+
+            if let Some(pin) = pin {
+                print_event!(
+                    self.context,
+                    EventCategory::Bonding,
+                    "Device [{}: {}] asked for a PIN, trying {}",
+                    &remote_device.address,
+                    &remote_device.name,
+                    pin
+                );
+                let rd = remote_device.clone();
+                self.context.lock().unwrap().run_callback(Box::new(move |context| {
+                    let status = context.lock().unwrap().adapter_dbus.as_ref().unwrap().set_pin(
+                        rd.clone(),
+                        true,
+                        pin.clone().into_bytes(),
+                    );
+                    if status != BtStatus::Success {
+                        print_warn!("Failed to set PIN: {:?}", status);
+                    }
+                }));
+                return;
             }
         }
+
+        print_event!(
+            self.context,
+            EventCategory::Bonding,
+            "Device [{}: {}] would like to pair, enter its {}PIN with: bond pin {} <pin>",
+            &remote_device.address,
+            &remote_device.name,
+            if min_16_digit { "16-digit " } else { "" },
+            &remote_device.address
+        );
+        self.context.lock().unwrap().pin_request = Some(remote_device);
+    }
+
+    fn on_pin_display(&self, remote_device: BluetoothDevice, pincode: String) {
+        print_event!(
+            self.context,
+            EventCategory::Bonding,
+            "Device [{}: {}] would like to pair, type {} on it followed by Enter",
+            &remote_device.address,
+            &remote_device.name,
+            pincode
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::PinDisplay { device: remote_device, pincode });
+    }
+
+    fn on_pairing_rejected(&self, remote_device: BluetoothDevice, reason: PairingRejectedReason) {
+        let why = match reason {
+            PairingRejectedReason::LegacyPairing => "it uses legacy pairing",
+            PairingRejectedReason::SecureConnectionsUnsupported => {
+                "it doesn't support Secure Connections"
+            }
+        };
+        print_event!(
+            self.context,
+            EventCategory::Bonding,
+            "Refused to pair with device [{}: {}]: {}, and Secure Connections Only is on",
+            &remote_device.address,
+            &remote_device.name,
+            why
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::PairingRejected { device: remote_device, reason });
+    }
+
+    fn on_encryption_key_size_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        key_size: u8,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Bonding,
+            "Rejected the {}-octet encryption key of device [{}: {}] on {:?}",
+            key_size,
+            &remote_device.address,
+            &remote_device.name,
+            transport
+        );
+        self.context.lock().unwrap().emit_event(ClientEvent::EncryptionKeySizeRejected {
+            device: remote_device,
+            transport,
+            key_size,
+        });
     }
 
     fn on_bond_state_changed(&self, status: u32, address: String, state: u32) {
-        print_info!("Bonding state changed: [{}] state: {}, Status = {}", address, state, status);
+        print_event!(
+            self.context,
+            EventCategory::Bonding,
+            "Bonding state changed: [{}] state: {}, Status = {}",
+            address,
+            state,
+            status
+        );
+        self.context.lock().unwrap().emit_event(ClientEvent::BondStateChanged {
+            status,
+            address: address.clone(),
+            state,
+        });
 
         // Clear bonding attempt if bonding fails or succeeds
         match BtBondState::from(state) {
             BtBondState::NotBonded | BtBondState::Bonded => {
-                let bonding_attempt =
-                    self.context.lock().unwrap().bonding_attempt.as_ref().cloned();
+                let mut context = self.context.lock().unwrap();
+                if context.pin_request.as_ref().map_or(false, |d| address == d.address.to_string())
+                {
+                    context.pin_request = None;
+                }
+
+                let bonding_attempt = context.bonding_attempt.as_ref().cloned();
                 match bonding_attempt {
                     Some(bd) => {
-                        if &address == &bd.address {
-                            self.context.lock().unwrap().bonding_attempt = None;
+                        if address == bd.address.to_string() {
+                            let more_pins =
+                                context.default_pins.as_ref().map_or(false, |p| !p.is_empty());
+                            if BtBondState::from(state) == BtBondState::NotBonded && more_pins {
+                                // The last default PIN was wrong, try the next one.
+                                context.run_callback(Box::new(move |context| {
+                                    let status = context
+                                        .lock()
+                                        .unwrap()
+                                        .adapter_dbus
+                                        .as_ref()
+                                        .unwrap()
+                                        .create_bond(bd.clone(), BtTransport::Auto);
+                                    if status != BtStatus::Success {
+                                        print_warn!("Failed to bond again: {:?}", status);
+                                        context.lock().unwrap().bonding_attempt = None;
+                                        context.lock().unwrap().default_pins = None;
+                                    }
+                                }));
+                            } else {
+                                context.bonding_attempt = None;
+                                context.default_pins = None;
+                            }
                         }
                     }
                     None => (),
@@ -204,119 +641,436 @@ impl IBluetoothCallback for BtCallback {
             }
             BtBondState::Bonding => (),
         }
+    }
+}
 
-        // If bonded, we should also automatically connect all enabled profiles
-        if BtBondState::Bonded == state.into() {
-            self.context.lock().unwrap().connect_all_enabled_profiles(BluetoothDevice {
-                address,
-                name: String::from("Classic device"),
-            });
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_connection_callback_dbus_obj")]
+pub struct BtConnectionCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtConnectionCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
         }
     }
 }
 
-impl RPCProxy for BtCallback {
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
+impl IBluetoothConnectionCallback for BtConnectionCallback {
+    fn on_device_connected(&self, remote_device: BluetoothDevice) {
+        print_event!(
+            self.context,
+            EventCategory::Connection,
+            "Connected: [{}]: {}",
+            remote_device.address,
+            remote_device.name
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .connected_devices
+            .insert(remote_device.address, remote_device.clone());
+        self.context.lock().unwrap().emit_event(ClientEvent::DeviceConnected(remote_device));
     }
 
-    fn get_object_id(&self) -> String {
-        self.objpath.clone()
+    fn on_device_disconnected(&self, remote_device: BluetoothDevice) {
+        print_event!(
+            self.context,
+            EventCategory::Connection,
+            "Disconnected: [{}]: {}",
+            remote_device.address,
+            remote_device.name
+        );
+        self.context.lock().unwrap().connected_devices.remove(&remote_device.address);
+        self.context.lock().unwrap().emit_event(ClientEvent::DeviceDisconnected(remote_device));
     }
 
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
+    fn on_profile_connection_state_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    ) {
+        if reason == ProfileConnectionReason::None {
+            print_event!(
+                self.context,
+                EventCategory::Connection,
+                "[{}]: {:?} {:?}",
+                remote_device.address,
+                profile,
+                state
+            );
+        } else {
+            print_event!(
+                self.context,
+                EventCategory::Connection,
+                "[{}]: {:?} {:?} ({:?})",
+                remote_device.address,
+                profile,
+                state,
+                reason
+            );
+        }
+        self.context.lock().unwrap().emit_event(ClientEvent::ProfileConnectionStateChanged {
+            device: remote_device,
+            profile,
+            state,
+            reason,
+        });
     }
 
-    fn export_for_rpc(self: Box<Self>) {
-        let cr = self.dbus_crossroads.clone();
-        export_bluetooth_callback_dbus_obj(
-            self.get_object_id(),
-            self.dbus_connection.clone(),
-            &mut cr.lock().unwrap(),
-            Arc::new(Mutex::new(self)),
-            Arc::new(Mutex::new(DisconnectWatcher::new())),
-        );
+    fn on_connect_all_complete(
+        &self,
+        remote_device: BluetoothDevice,
+        results: Vec<ProfileConnectResult>,
+    ) {
+        let failed: Vec<String> = results
+            .iter()
+            .filter(|r| r.status != BtStatus::Success)
+            .map(|r| format!("{:?}", r.profile))
+            .collect();
+        if failed.is_empty() {
+            print_event!(
+                self.context,
+                EventCategory::Connection,
+                "[{}]: Connected {} profile(s)",
+                remote_device.address,
+                results.len()
+            );
+        } else {
+            print_event!(
+                self.context,
+                EventCategory::Connection,
+                "[{}]: Failed to connect {}",
+                remote_device.address,
+                failed.join(", ")
+            );
+        }
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::ConnectAllComplete { device: remote_device, results });
     }
 }
 
-pub(crate) struct BtConnectionCallback {
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_security_callback_dbus_obj")]
+pub struct BtSecurityCallback {
     objpath: String,
-    _context: Arc<Mutex<ClientContext>>,
+    context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
 }
 
-impl BtConnectionCallback {
-    pub(crate) fn new(
+impl BtSecurityCallback {
+    pub fn new(
         objpath: String,
-        _context: Arc<Mutex<ClientContext>>,
+        context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
     ) -> Self {
-        Self { objpath, _context, dbus_connection, dbus_crossroads }
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
 }
 
-impl IBluetoothConnectionCallback for BtConnectionCallback {
-    fn on_device_connected(&self, remote_device: BluetoothDevice) {
-        print_info!("Connected: [{}]: {}", remote_device.address, remote_device.name);
+impl IBluetoothSecurityCallback for BtSecurityCallback {
+    fn on_authentication_failed(&self, remote_device: BluetoothDevice, hci_reason: u8) {
+        print_event!(
+            self.context,
+            EventCategory::Security,
+            "[{}]: Authentication failed: {:#04x}",
+            remote_device.address,
+            hci_reason
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::AuthenticationFailed { device: remote_device, hci_reason });
     }
 
-    fn on_device_disconnected(&self, remote_device: BluetoothDevice) {
-        print_info!("Disconnected: [{}]: {}", remote_device.address, remote_device.name);
+    fn on_encryption_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+        encrypted: bool,
+    ) {
+        if hci_status == 0 {
+            print_event!(
+                self.context,
+                EventCategory::Security,
+                "[{}]: {:?} link {}",
+                remote_device.address,
+                transport,
+                if encrypted { "encrypted" } else { "no longer encrypted" }
+            );
+        } else {
+            print_event!(
+                self.context,
+                EventCategory::Security,
+                "[{}]: {:?} encryption change failed: {:#04x}",
+                remote_device.address,
+                transport,
+                hci_status
+            );
+        }
+        self.context.lock().unwrap().emit_event(ClientEvent::EncryptionChanged {
+            device: remote_device,
+            transport,
+            hci_status,
+            encrypted,
+        });
+    }
+
+    fn on_encryption_key_refreshed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+    ) {
+        if hci_status == 0 {
+            print_event!(
+                self.context,
+                EventCategory::Security,
+                "[{}]: {:?} encryption key refreshed",
+                remote_device.address,
+                transport
+            );
+        } else {
+            print_event!(
+                self.context,
+                EventCategory::Security,
+                "[{}]: {:?} encryption key refresh failed: {:#04x}",
+                remote_device.address,
+                transport,
+                hci_status
+            );
+        }
+        self.context.lock().unwrap().emit_event(ClientEvent::EncryptionKeyRefreshed {
+            device: remote_device,
+            transport,
+            hci_status,
+        });
     }
-}
 
-impl RPCProxy for BtConnectionCallback {
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
+    fn on_address_spoofing_suspected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Security,
+            "[{}: {}]: Failed to use the key of its bond on {:?}, the address may be spoofed",
+            remote_device.address,
+            remote_device.name,
+            transport
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::AddressSpoofingSuspected { device: remote_device, transport });
     }
 
-    fn get_object_id(&self) -> String {
-        self.objpath.clone()
+    fn on_unbonded_connection_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Security,
+            "[{}: {}]: Dropped its {:?} connection, it isn't bonded",
+            remote_device.address,
+            remote_device.name,
+            transport
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::ConnectionRejected { device: remote_device, transport });
     }
+}
 
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_link_policy_callback_dbus_obj")]
+pub struct BtLinkPolicyCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtLinkPolicyCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
+}
 
-    fn export_for_rpc(self: Box<Self>) {
-        let cr = self.dbus_crossroads.clone();
-        export_bluetooth_connection_callback_dbus_obj(
-            self.get_object_id(),
-            self.dbus_connection.clone(),
-            &mut cr.lock().unwrap(),
-            Arc::new(Mutex::new(self)),
-            Arc::new(Mutex::new(DisconnectWatcher::new())),
-        );
+impl IBluetoothLinkPolicyCallback for BtLinkPolicyCallback {
+    fn on_role_changed(&self, device: BluetoothDevice, role: BtAclRole, status: BtStatus) {
+        if status == BtStatus::Success {
+            print_event!(
+                self.context,
+                EventCategory::Connection,
+                "Role changed: [{}]: {:?}",
+                device.address,
+                role
+            );
+        } else {
+            print_event!(
+                self.context,
+                EventCategory::Connection,
+                "Role switch failed: [{}]: {:?}, still {:?}",
+                device.address,
+                status,
+                role
+            );
+        }
+        self.context.lock().unwrap().emit_event(ClientEvent::RoleChanged { device, role, status });
     }
 }
 
-pub(crate) struct BtGattCallback {
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_gatt_callback_dbus_obj")]
+pub struct BtGattCallback {
     objpath: String,
+    /// App UUID of the GATT client the callback was registered with.
+    app_uuid: Uuid128Bit,
     context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
 }
 
 impl BtGattCallback {
-    pub(crate) fn new(
+    pub fn new(
         objpath: String,
+        app_uuid: Uuid128Bit,
         context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
     ) -> Self {
-        Self { objpath, context, dbus_connection, dbus_crossroads }
+        Self {
+            objpath,
+            app_uuid,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
+    }
+
+    // The stack only calls back clients that are registered.
+    fn client_id(&self) -> i32 {
+        self.context.lock().unwrap().gatt_clients.get(&self.app_uuid).copied().unwrap_or(0)
+    }
+}
+
+// Lists the attributes of `services`, one per line and indented under the one they belong to,
+// with what they can be used for.
+fn format_services(services: &[BluetoothGattService]) -> String {
+    let names =
+        |names: Vec<&str>| if names.is_empty() { String::from("none") } else { names.join(", ") };
+    let mut lines = vec![];
+    for service in services {
+        lines.push(format!(
+            "  Service {} (handle {}){}",
+            UuidHelper::to_string(&service.uuid),
+            service.instance_id,
+            if service.service_type == 0 { "" } else { ", secondary" }
+        ));
+        for included in &service.included_services {
+            lines.push(format!(
+                "    Included service {} (handle {})",
+                UuidHelper::to_string(&included.uuid),
+                included.instance_id
+            ));
+        }
+        for characteristic in &service.characteristics {
+            lines.push(format!(
+                "    Characteristic {} (handle {}): properties: {}; permissions: {}",
+                UuidHelper::to_string(&characteristic.uuid),
+                characteristic.instance_id,
+                names(BluetoothGattCharacteristic::property_names(characteristic.properties)),
+                names(BluetoothGattCharacteristic::permission_names(characteristic.permissions))
+            ));
+            for descriptor in &characteristic.descriptors {
+                // Descriptors have no properties to guess their permissions from.
+                let permissions = match descriptor.permissions {
+                    0 => String::from("unknown"),
+                    p => names(BluetoothGattCharacteristic::permission_names(p)),
+                };
+                lines.push(format!(
+                    "      Descriptor {} (handle {}): permissions: {}",
+                    UuidHelper::to_string(&descriptor.uuid),
+                    descriptor.instance_id,
+                    permissions
+                ));
+            }
+        }
     }
+    lines.iter().map(|line| format!("\n{}", line)).collect()
 }
 
 impl IBluetoothGattCallback for BtGattCallback {
     fn on_client_registered(&self, status: i32, client_id: i32) {
-        print_info!("GATT Client registered status = {}, client_id = {}", status, client_id);
-        self.context.lock().unwrap().gatt_client_id = Some(client_id);
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "GATT Client registered status = {}, client_id = {}",
+            GattStatusDisplay(status),
+            client_id
+        );
+        let app_uuid = self.app_uuid;
+        let mut context = self.context.lock().unwrap();
+        if status == 0 {
+            context.gatt_clients.insert(app_uuid, client_id);
+        }
+        context.emit_event(ClientEvent::GattClientRegistered { app_uuid, status, client_id });
     }
 
     fn on_client_connection_state(
@@ -324,194 +1078,988 @@ impl IBluetoothGattCallback for BtGattCallback {
         status: i32,
         client_id: i32,
         connected: bool,
-        addr: String,
+        addr: RawAddress,
     ) {
-        print_info!(
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
             "GATT Client connection state = {}, client_id = {}, connected = {}, addr = {}",
-            status,
+            GattStatusDisplay(status),
             client_id,
             connected,
             addr
         );
+        self.context.lock().unwrap().emit_event(ClientEvent::GattClientConnectionState {
+            status,
+            client_id,
+            connected,
+            addr,
+        });
     }
 
-    fn on_phy_update(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
-        print_info!(
-            "Phy updated: addr = {}, tx_phy = {:?}, rx_phy = {:?}, status = {:?}",
+    fn on_phy_update(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "Phy updated: addr = {}, tx_phy = {:?}, rx_phy = {:?}, status = {}",
             addr,
             tx_phy,
             rx_phy,
-            status
+            GattStatusDisplay::from(status)
         );
     }
 
-    fn on_phy_read(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
-        print_info!(
-            "Phy read: addr = {}, tx_phy = {:?}, rx_phy = {:?}, status = {:?}",
+    fn on_phy_read(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "Phy read: addr = {}, tx_phy = {:?}, rx_phy = {:?}, status = {}",
             addr,
             tx_phy,
             rx_phy,
-            status
+            GattStatusDisplay::from(status)
         );
     }
 
-    fn on_search_complete(&self, addr: String, services: Vec<BluetoothGattService>, status: i32) {
-        print_info!(
-            "GATT DB Search complete: addr = {}, services = {:?}, status = {}",
+    fn on_search_complete(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        status: i32,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "GATT DB Search complete: addr = {}, status = {}{}",
             addr,
-            services,
-            status
+            GattStatusDisplay(status),
+            format_services(&services)
         );
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattSearchComplete {
+            client_id,
+            addr,
+            status,
+            services,
+        });
     }
 
-    fn on_characteristic_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {
-        print_info!(
+    fn on_characteristic_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
             "GATT Characteristic read: addr = {}, status = {}, handle = {}, value = {:?}",
             addr,
-            status,
+            GattStatusDisplay(status),
             handle,
             value
         );
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattCharacteristicRead {
+            client_id,
+            addr,
+            status,
+            handle,
+            value,
+        });
     }
 
-    fn on_characteristic_write(&self, addr: String, status: i32, handle: i32) {
-        print_info!(
+    fn on_characteristic_write(&self, addr: RawAddress, status: i32, handle: i32) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
             "GATT Characteristic write: addr = {}, status = {}, handle = {}",
             addr,
-            status,
+            GattStatusDisplay(status),
             handle
         );
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattCharacteristicWrite {
+            client_id,
+            addr,
+            status,
+            handle,
+        });
     }
 
-    fn on_execute_write(&self, addr: String, status: i32) {
-        print_info!("GATT execute write addr = {}, status = {}", addr, status);
+    fn on_execute_write(&self, addr: RawAddress, status: i32) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "GATT execute write addr = {}, status = {}",
+            addr,
+            GattStatusDisplay(status)
+        );
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattExecuteWrite {
+            client_id,
+            addr,
+            status,
+        });
     }
 
-    fn on_descriptor_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {
-        print_info!(
+    fn on_descriptor_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
             "GATT Descriptor read: addr = {}, status = {}, handle = {}, value = {:?}",
             addr,
-            status,
+            GattStatusDisplay(status),
             handle,
             value
         );
     }
 
-    fn on_descriptor_write(&self, addr: String, status: i32, handle: i32) {
-        print_info!(
+    fn on_descriptor_write(&self, addr: RawAddress, status: i32, handle: i32) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
             "GATT Descriptor write: addr = {}, status = {}, handle = {}",
             addr,
-            status,
+            GattStatusDisplay(status),
             handle
         );
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattDescriptorWrite {
+            client_id,
+            addr,
+            status,
+            handle,
+        });
     }
 
-    fn on_notify(&self, addr: String, handle: i32, value: Vec<u8>) {
-        print_info!("GATT Notification: addr = {}, handle = {}, value = {:?}", addr, handle, value);
+    fn on_notify(&self, addr: RawAddress, handle: i32, value: Arc<[u8]>) {
+        let decoded = self
+            .context
+            .lock()
+            .unwrap()
+            .decoded_characteristics
+            .get(&(addr, handle))
+            .and_then(|uuid| gatt_decode::decode(uuid, &value));
+        match decoded {
+            Some(decoded) => print_event!(
+                self.context,
+                EventCategory::Gatt,
+                "GATT Notification: addr = {}, handle = {}, {}",
+                addr,
+                handle,
+                decoded
+            ),
+            None => print_event!(
+                self.context,
+                EventCategory::Gatt,
+                "GATT Notification: addr = {}, handle = {}, value = {:?}",
+                addr,
+                handle,
+                value
+            ),
+        }
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattNotify {
+            client_id,
+            addr,
+            handle,
+            value,
+        });
     }
 
-    fn on_read_remote_rssi(&self, addr: String, rssi: i32, status: i32) {
-        print_info!("Remote RSSI read: addr = {}, rssi = {}, status = {}", addr, rssi, status);
+    fn on_notify_batch(&self, addr: RawAddress, notifications: Vec<GattNotification>) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "GATT Notification batch: addr = {}, count = {}",
+            addr,
+            notifications.len()
+        );
+
+        let client_id = self.client_id();
+        let mut context = self.context.lock().unwrap();
+        for notification in notifications {
+            context.emit_event(ClientEvent::GattNotify {
+                client_id,
+                addr,
+                handle: notification.handle,
+                value: notification.value.into(),
+            });
+        }
     }
 
-    fn on_configure_mtu(&self, addr: String, mtu: i32, status: i32) {
-        print_info!("MTU configured: addr = {}, mtu = {}, status = {}", addr, mtu, status);
+    fn on_read_remote_rssi(&self, addr: RawAddress, rssi: i32, status: i32) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "Remote RSSI read: addr = {}, rssi = {}, status = {}",
+            addr,
+            rssi,
+            GattStatusDisplay(status)
+        );
+        let client_id = self.client_id();
+        self.context.lock().unwrap().emit_event(ClientEvent::GattRemoteRssiRead {
+            client_id,
+            addr,
+            rssi,
+            status,
+        });
+    }
+
+    fn on_configure_mtu(&self, addr: RawAddress, mtu: i32, status: i32) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "MTU configured: addr = {}, mtu = {}, status = {}",
+            addr,
+            mtu,
+            GattStatusDisplay(status)
+        );
     }
 
     fn on_connection_updated(
         &self,
-        addr: String,
+        addr: RawAddress,
         interval: i32,
         latency: i32,
         timeout: i32,
         status: i32,
     ) {
-        print_info!(
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
             "Connection updated: addr = {}, interval = {}, latency = {}, timeout = {}, status = {}",
             addr,
             interval,
             latency,
             timeout,
+            GattStatusDisplay(status)
+        );
+    }
+
+    fn on_service_changed(&self, addr: RawAddress) {
+        print_event!(self.context, EventCategory::Gatt, "Service changed for {}", addr,);
+        let client_id = self.client_id();
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::GattServiceChanged { client_id, addr });
+    }
+
+    fn on_gatt_db_updated(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        database_hash: Vec<u8>,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "GATT DB updated: addr = {}, services = {:?}, database hash = {:02x?}",
+            addr,
+            services,
+            database_hash
+        );
+    }
+
+    fn on_authorization_request(&self, addr: RawAddress, handle: i32) {
+        print_event!(
+            self.context,
+            EventCategory::Gatt,
+            "GATT server authorization requested: addr = {}, handle = {}, answer with \
+             `gatt authorize {} {} <allow|deny>`",
+            addr,
+            handle,
+            addr,
+            handle
+        );
+    }
+}
+
+/// Callback context for object push interface callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_opp_callback_dbus_obj")]
+pub struct BtOppCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtOppCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
+    }
+}
+
+impl IBluetoothOppCallback for BtOppCallback {
+    fn on_incoming_transfer(&self, transfer: OppTransfer) {
+        print_event!(
+            self.context,
+            EventCategory::Opp,
+            "Incoming transfer {} from [{}]: {}, {} ({} bytes). Use 'opp accept {}' or \
+             'opp reject {}'",
+            transfer.id,
+            transfer.device.address,
+            transfer.device.name,
+            transfer.file_name,
+            transfer.total_bytes,
+            transfer.id,
+            transfer.id
+        );
+        self.context.lock().unwrap().emit_event(ClientEvent::OppIncomingTransfer(transfer));
+    }
+
+    fn on_transfer_progress(&self, transfer_id: i32, bytes_transferred: u64, total_bytes: u64) {
+        print_event!(
+            self.context,
+            EventCategory::Opp,
+            "Transfer {}: {}/{} bytes",
+            transfer_id,
+            bytes_transferred,
+            total_bytes
+        );
+    }
+
+    fn on_transfer_complete(&self, transfer_id: i32, status: OppTransferStatus) {
+        print_event!(
+            self.context,
+            EventCategory::Opp,
+            "Transfer {} complete: {:?}",
+            transfer_id,
             status
         );
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::OppTransferComplete { transfer_id, status });
     }
+}
+
+/// Callback context for phone book access interface callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_pbap_callback_dbus_obj")]
+pub struct BtPbapCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
 
-    fn on_service_changed(&self, addr: String) {
-        print_info!("Service changed for {}", addr,);
+impl BtPbapCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
 }
 
-impl RPCProxy for BtGattCallback {
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
+impl IBluetoothPbapCallback for BtPbapCallback {
+    fn on_phonebook_pull_complete(
+        &self,
+        request_id: i32,
+        status: PbapPullStatus,
+        phonebook_size: u32,
+        vcards: Vec<String>,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Pbap,
+            "Phone book pull {} complete: {:?}, {} entries, phone book size {}",
+            request_id,
+            status,
+            vcards.len(),
+            phonebook_size
+        );
+
+        // Only show who the entries are, the full vCards are delivered to subscribers.
+        for vcard in vcards.iter() {
+            let name = vcard.lines().find_map(|line| line.strip_prefix("FN:")).unwrap_or("");
+            print_event!(self.context, EventCategory::Pbap, "  {}", name);
+        }
+
+        self.context.lock().unwrap().emit_event(ClientEvent::PhonebookPullComplete {
+            request_id,
+            status,
+            vcards,
+        });
     }
+}
 
-    fn get_object_id(&self) -> String {
-        self.objpath.clone()
+/// Callback context for message access interface callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_map_callback_dbus_obj")]
+pub struct BtMapCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtMapCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
+}
 
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
+impl IBluetoothMapCallback for BtMapCallback {
+    fn on_connection_state_changed(&self, device: BluetoothDevice, connected: bool) {
+        print_event!(
+            self.context,
+            EventCategory::Map,
+            "Message access to {} ({}) {}",
+            device.address,
+            device.name,
+            if connected { "connected" } else { "disconnected" }
+        );
     }
 
-    fn export_for_rpc(self: Box<Self>) {
-        let cr = self.dbus_crossroads.clone();
-        export_bluetooth_gatt_callback_dbus_obj(
-            self.get_object_id(),
-            self.dbus_connection.clone(),
-            &mut cr.lock().unwrap(),
-            Arc::new(Mutex::new(self)),
-            Arc::new(Mutex::new(DisconnectWatcher::new())),
+    fn on_message_event(&self, device: BluetoothDevice, event: MapEvent) {
+        print_event!(
+            self.context,
+            EventCategory::Map,
+            "Message event from {}: {} {} in {} ({})",
+            device.address,
+            event.event_type,
+            event.handle,
+            event.folder,
+            event.msg_type
         );
+
+        self.context.lock().unwrap().emit_event(ClientEvent::MapMessageEvent {
+            address: device.address.to_string(),
+            event,
+        });
+    }
+
+    fn on_messages_listing(&self, request_id: i32, status: MapStatus, messages: Vec<MapMessage>) {
+        print_event!(
+            self.context,
+            EventCategory::Map,
+            "Messages listing {}: {:?}, {} messages",
+            request_id,
+            status,
+            messages.len()
+        );
+
+        for message in messages.iter() {
+            print_event!(
+                self.context,
+                EventCategory::Map,
+                "  {} {} {} <{}> {}{}",
+                message.handle,
+                message.datetime,
+                message.sender_name,
+                message.sender_addressing,
+                message.subject,
+                if message.read { "" } else { " (unread)" }
+            );
+        }
+
+        self.context.lock().unwrap().emit_event(ClientEvent::MapMessagesListing {
+            request_id,
+            status,
+            messages,
+        });
+    }
+
+    fn on_message(&self, request_id: i32, status: MapStatus, message: String) {
+        print_event!(self.context, EventCategory::Map, "Message {}: {:?}", request_id, status);
+        for line in message.lines() {
+            print_event!(self.context, EventCategory::Map, "  {}", line);
+        }
+
+        self.context.lock().unwrap().emit_event(ClientEvent::MapMessage {
+            request_id,
+            status,
+            message,
+        });
     }
 }
 
-/// Callback container for suspend interface callbacks.
-pub(crate) struct SuspendCallback {
+/// Callback context for PAN interface callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_bluetooth_pan_callback_dbus_obj")]
+pub struct BtPanCallback {
     objpath: String,
+    context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
 }
 
-impl SuspendCallback {
-    pub(crate) fn new(
+impl BtPanCallback {
+    pub fn new(
         objpath: String,
+        context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
     ) -> Self {
-        Self { objpath, dbus_connection, dbus_crossroads }
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
 }
 
-impl ISuspendCallback for SuspendCallback {
-    // TODO(b/224606285): Implement suspend utils in btclient.
-    fn on_callback_registered(&self, _callback_id: u32) {}
-    fn on_suspend_ready(&self, _suspend_id: u32) {}
-    fn on_resumed(&self, _suspend_id: u32) {}
+impl IBluetoothPanCallback for BtPanCallback {
+    fn on_connection_state_changed(
+        &self,
+        device: BluetoothDevice,
+        state: BtPanConnectionState,
+        interface_name: String,
+    ) {
+        print_event!(
+            self.context,
+            EventCategory::Pan,
+            "PAN to {} ({}) is {:?} on interface {}",
+            device.address,
+            device.name,
+            state,
+            interface_name
+        );
+
+        self.context.lock().unwrap().emit_event(ClientEvent::PanConnectionState {
+            address: device.address.to_string(),
+            state,
+            interface_name,
+        });
+    }
+}
+
+/// Callback context for link diagnostics callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_link_diagnostics_callback_dbus_obj")]
+pub struct BtLinkDiagnosticsCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtLinkDiagnosticsCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
+    }
+}
+
+// Pings are started by the user, so their results are always printed.
+impl IBluetoothLinkDiagnosticsCallback for BtLinkDiagnosticsCallback {
+    fn on_ping_reply(&self, device: BluetoothDevice, sequence: u32, rtt_us: u64) {
+        print_info!(
+            "Echo response from {}: seq {} time {}.{:03} ms",
+            device.address,
+            sequence,
+            rtt_us / 1000,
+            rtt_us % 1000
+        );
+    }
+
+    fn on_ping_lost(&self, device: BluetoothDevice, sequence: u32) {
+        print_info!("No echo response from {}: seq {}", device.address, sequence);
+    }
+
+    fn on_ping_complete(&self, device: BluetoothDevice, stats: PingStats) {
+        let ms = |us: u64| format!("{}.{:03}", us / 1000, us % 1000);
+        let lost = match stats.sent {
+            0 => 0,
+            sent => 100 * (sent - stats.received) / sent,
+        };
+        print_info!(
+            "{} sent, {} received, {}% loss to {}, rtt min/avg/max {}/{}/{} ms",
+            stats.sent,
+            stats.received,
+            lost,
+            device.address,
+            ms(stats.min_rtt_us),
+            ms(stats.avg_rtt_us),
+            ms(stats.max_rtt_us)
+        );
+
+        self.context.lock().unwrap().emit_event(ClientEvent::PingComplete { device, stats });
+    }
+
+    fn on_tx_power_reported(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        report: TxPowerReport,
+    ) {
+        let level = |dbm: i32| match dbm {
+            TX_POWER_UNAVAILABLE => String::from("unknown"),
+            TX_POWER_UNMANAGED => String::from("not managed"),
+            dbm => format!("{} dBm", dbm),
+        };
+        if status != BtStatus::Success {
+            print_info!("Can't read the TX power of {}: {:?}", device.address, status);
+        } else {
+            match report.source {
+                TxPowerSource::LocalRead => print_info!(
+                    "TX power to {} on {:?}: {}, max {}",
+                    device.address,
+                    report.phy,
+                    level(report.tx_power),
+                    level(report.max_tx_power)
+                ),
+                source => print_info!(
+                    "{} TX power to {} on {:?} changed by {} to {}{}",
+                    if source == TxPowerSource::LocalChange { "Local" } else { "Remote" },
+                    device.address,
+                    report.phy,
+                    level(report.delta),
+                    level(report.tx_power),
+                    match (report.at_min, report.at_max) {
+                        (true, _) => " (min)",
+                        (_, true) => " (max)",
+                        _ => "",
+                    }
+                ),
+            }
+        }
+
+        self.context.lock().unwrap().emit_event(ClientEvent::TxPowerReported {
+            device,
+            status,
+            report,
+        });
+    }
+
+    fn on_path_loss_zone_entered(
+        &self,
+        device: BluetoothDevice,
+        zone: PathLossZone,
+        path_loss: u32,
+    ) {
+        print_info!("Path loss to {} is {:?}: {} dB", device.address, zone, path_loss);
+
+        self.context.lock().unwrap().emit_event(ClientEvent::PathLossZoneEntered {
+            device,
+            zone,
+            path_loss,
+        });
+    }
+}
+
+/// Callback context for throughput test callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_throughput_test_callback_dbus_obj")]
+pub struct BtThroughputTestCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtThroughputTestCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
+    }
+}
+
+impl IBluetoothThroughputTestCallback for BtThroughputTestCallback {
+    fn on_throughput_test_complete(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        result: ThroughputTestResult,
+    ) {
+        let kbps = result.throughput_bps / 1000;
+        print_info!(
+            "Throughput test with {} done ({:?}): {} bytes in {} packets over {}.{:03} s, {} lost, \
+             {}.{:03} kbps",
+            device.address,
+            status,
+            result.bytes,
+            result.packets,
+            result.duration_ms / 1000,
+            result.duration_ms % 1000,
+            result.lost_packets,
+            kbps,
+            result.throughput_bps % 1000
+        );
+
+        self.context.lock().unwrap().emit_event(ClientEvent::ThroughputTestComplete {
+            device,
+            status,
+            result,
+        });
+    }
+}
+
+/// Callback context for distance measurement callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_distance_measurement_callback_dbus_obj")]
+pub struct BtDistanceMeasurementCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
 }
 
-impl RPCProxy for SuspendCallback {
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
+impl BtDistanceMeasurementCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
+}
 
-    fn get_object_id(&self) -> String {
-        self.objpath.clone()
+impl IBluetoothDistanceMeasurementCallback for BtDistanceMeasurementCallback {
+    fn on_distance_measurement_started(
+        &self,
+        device: BluetoothDevice,
+        method: DistanceMeasurementMethod,
+    ) {
+        print_info!("Measuring the distance to {} with {:?}", device.address, method);
     }
 
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
+    fn on_distance_measurement_stopped(&self, device: BluetoothDevice, status: BtStatus) {
+        print_info!("Distance measurement of {} stopped: {:?}", device.address, status);
+
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::DistanceMeasurementStopped { device, status });
     }
 
-    fn export_for_rpc(self: Box<Self>) {
-        let cr = self.dbus_crossroads.clone();
-        export_suspend_callback_dbus_obj(
-            self.get_object_id(),
-            self.dbus_connection.clone(),
-            &mut cr.lock().unwrap(),
-            Arc::new(Mutex::new(self)),
-            Arc::new(Mutex::new(DisconnectWatcher::new())),
+    fn on_distance_measurement(&self, device: BluetoothDevice, result: DistanceMeasurementResult) {
+        let m = |cm: u32| format!("{}.{:02}", cm / 100, cm % 100);
+        print_info!(
+            "Distance to {}: {} m +/- {} m (rssi {} dBm)",
+            device.address,
+            m(result.distance_cm),
+            m(result.error_cm),
+            result.rssi
         );
+
+        self.context.lock().unwrap().emit_event(ClientEvent::DistanceMeasured { device, result });
+    }
+}
+
+/// Describes the fields of `beacon` that its type sets.
+fn describe_beacon(beacon: &Beacon) -> String {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    match beacon.beacon_type {
+        BeaconType::IBeacon => format!(
+            "iBeacon {} major {} minor {} measured power {} dBm",
+            UuidHelper::to_string(&beacon.id),
+            beacon.major,
+            beacon.minor,
+            beacon.tx_power
+        ),
+        BeaconType::EddystoneUid => format!(
+            "Eddystone-UID namespace {} instance {} TX power {} dBm",
+            hex(&beacon.id[..10]),
+            hex(&beacon.id[10..]),
+            beacon.tx_power
+        ),
+        BeaconType::EddystoneUrl => {
+            format!("Eddystone-URL {} TX power {} dBm", beacon.url, beacon.tx_power)
+        }
+        BeaconType::EddystoneTlm => format!(
+            "Eddystone-TLM battery {} mV temperature {:.1} °C {} advertisements in {} s",
+            beacon.battery_voltage,
+            beacon.temperature as f64 / 256.0,
+            beacon.adv_count,
+            beacon.uptime / 10
+        ),
+    }
+}
+
+/// Callback context for the events of one advertisement monitor.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_adv_monitor_callback_dbus_obj")]
+pub struct BtAdvMonitorCallback {
+    objpath: String,
+    context: Arc<Mutex<ClientContext>>,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl BtAdvMonitorCallback {
+    pub fn new(
+        objpath: String,
+        context: Arc<Mutex<ClientContext>>,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            context,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
     }
 }
+
+impl IAdvMonitorCallback for BtAdvMonitorCallback {
+    fn on_device_found(&self, monitor_id: u32, device: BluetoothDevice) {
+        print_event!(
+            self.context,
+            EventCategory::AdvMonitor,
+            "Monitor {} found {} ({}, {:?})",
+            monitor_id,
+            device.address,
+            device.name,
+            device.icon
+        );
+
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::AdvMonitorDeviceFound { monitor_id, device });
+    }
+
+    fn on_device_lost(&self, monitor_id: u32, device: BluetoothDevice) {
+        print_event!(
+            self.context,
+            EventCategory::AdvMonitor,
+            "Monitor {} lost {} ({})",
+            monitor_id,
+            device.address,
+            device.name
+        );
+
+        self.context
+            .lock()
+            .unwrap()
+            .emit_event(ClientEvent::AdvMonitorDeviceLost { monitor_id, device });
+    }
+
+    fn on_beacon_received(&self, monitor_id: u32, device: BluetoothDevice, beacon: Beacon) {
+        print_event!(
+            self.context,
+            EventCategory::AdvMonitor,
+            "Monitor {}: {} ({}) {}",
+            monitor_id,
+            device.address,
+            device.name,
+            describe_beacon(&beacon)
+        );
+
+        self.context.lock().unwrap().emit_event(ClientEvent::AdvMonitorBeacon {
+            monitor_id,
+            device,
+            beacon,
+        });
+    }
+}
+
+/// Callback container for suspend interface callbacks.
+#[derive(RPCProxy)]
+#[rpc_proxy(export = "export_suspend_callback_dbus_obj")]
+pub struct SuspendCallback {
+    objpath: String,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    lifetime: CallbackLifetime,
+}
+
+impl SuspendCallback {
+    pub fn new(
+        objpath: String,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+    ) -> Self {
+        Self {
+            objpath,
+            dbus_connection,
+            dbus_crossroads,
+            lifetime: CallbackLifetime::new(BusName::from(ADAPTER_SERVICE), disconnect_watcher),
+        }
+    }
+}
+
+impl ISuspendCallback for SuspendCallback {
+    // TODO(b/224606285): Implement suspend utils in btclient.
+    fn on_callback_registered(&self, _callback_id: u32) {}
+    fn on_suspend_ready(&self, _suspend_id: u32) {}
+    fn on_resumed(&self, _suspend_id: u32) {}
+}
@@ -1,24 +1,86 @@
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fmt::{Display, Formatter, Result};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
-
-use crate::callbacks::BtGattCallback;
-use crate::ClientContext;
-use crate::{console_red, console_yellow, print_error, print_info};
-use bt_topshim::btif::BtTransport;
-use btstack::bluetooth::{BluetoothDevice, IBluetooth};
-use btstack::bluetooth_gatt::IBluetoothGatt;
+use std::time::Duration;
+
+use bt_topshim::btif::{BtAclRole, BtStatus, BtTransport, RawAddress, Uuid128Bit};
+use btstack::bluetooth::{
+    AdapterState, BluetoothDevice, ClassicScanType, IBluetooth, IBluetoothLinkPolicy,
+};
+use btstack::bluetooth_adv_monitor::{AdvMonitor, AdvMonitorPattern, IBluetoothAdvMonitor};
+use btstack::bluetooth_fast_pair::IBluetoothFastPair;
+use btstack::bluetooth_gatt::{ConnectionPriority, GattWriteType, IBluetoothGatt, LePhy, ScanType};
+use btstack::bluetooth_map::IBluetoothMap;
+use btstack::bluetooth_opp::IBluetoothOpp;
+use btstack::bluetooth_pan::IBluetoothPan;
+use btstack::bluetooth_pbap::{IBluetoothPbap, PbapPhonebook};
+use btstack::bluetooth_provisioning::IBluetoothProvisioning;
+use btstack::coex::{IBluetoothCoex, WifiChannel};
+use btstack::device_type::DeviceType;
+use btstack::distance_measurement::{DistanceMeasurementMethod, IBluetoothDistanceMeasurement};
+use btstack::link_diagnostics::IBluetoothLinkDiagnostics;
+use btstack::throughput_test::{
+    IBluetoothThroughputTest, ThroughputDirection, ThroughputTransport,
+};
 use btstack::uuid::{Profile, UuidHelper};
-use manager_service::iface_bluetooth_manager::IBluetoothManager;
+use floss_client::callbacks::{BtAdvMonitorCallback, BtDiscoverySessionCallback};
+use floss_client::device_report::{self, ClientDeviceInfo};
+use floss_client::gatt_decode;
+use floss_client::proximity::{DistanceBucket, PathLossModel};
+use floss_client::requests::{self, GattCharacteristic, RequestError};
+use floss_client::state;
+use floss_client::watch;
+use floss_client::{print_error, print_info};
+use floss_client::{ClientContext, EventCategory, GATT_CLIENT_APP_UUID};
+use manager_service::iface_bluetooth_manager::{IBluetoothManager, API_VERSION};
 
 const INDENT_CHAR: &str = " ";
 const BAR1_CHAR: &str = "=";
 const BAR2_CHAR: &str = "-";
 const MAX_MENU_CHAR_WIDTH: usize = 72;
-const GATT_CLIENT_APP_UUID: &str = "12345678123456781234567812345678";
+
+// How long commands wait for the stack to report the outcome of a request.
+const ADAPTER_TOGGLE_TIMEOUT: Duration = Duration::from_secs(10);
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+const GATT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_PING_COUNT: u32 = 4;
+const DEFAULT_PING_SIZE: u32 = 44;
+const DEFAULT_THROUGHPUT_PAYLOAD_SIZE: u32 = 244;
+const DEFAULT_THROUGHPUT_TEST_SECS: u32 = 10;
+const DEFAULT_DISTANCE_INTERVAL_MS: u32 = 1000;
+const DEFAULT_DEVICE_LOST_SECS: u32 = 10;
+
+// Daemon API versions needed by the subcommands which call methods older daemons don't have.
+// Subcommands not listed here work with any daemon.
+const SUBCOMMAND_API_VERSIONS: &[(&str, &str, u32)] = &[
+    ("gatt", "auto-elevate-security", 1),
+    ("floss", "virtual-controller", 2),
+    ("discovery", "merged", 3),
+    ("gatt", "request-timeout", 4),
+    ("device", "fetch-name", 5),
+    ("device", "tx-power", 6),
+    ("device", "path-loss", 6),
+    ("distance", "start", 7),
+    ("distance", "stop", 7),
+    ("distance", "methods", 7),
+    ("coex", "wifi", 8),
+    ("coex", "latency", 8),
+    ("coex", "state", 8),
+    ("discovery", "session", 9),
+    ("security", "bonded-only", 10),
+    ("floss", "reload-config", 11),
+    ("device", "show", 12),
+    ("gatt", "server-authorization", 13),
+    ("gatt", "authorize", 13),
+];
 
 type CommandFunction = fn(&mut CommandHandler, &Vec<String>);
 
+type PendingRequest = Pin<Box<dyn Future<Output = ()>>>;
+
 fn _noop(_handler: &mut CommandHandler, _args: &Vec<String>) {
     // Used so we can add options with no direct function
     // e.g. help and quit
@@ -33,6 +95,9 @@ pub struct CommandOption {
 pub(crate) struct CommandHandler {
     context: Arc<Mutex<ClientContext>>,
     command_options: HashMap<String, CommandOption>,
+
+    /// Request started by the current command that the command waits on before returning.
+    pending: Option<PendingRequest>,
 }
 
 struct DisplayList<T>(Vec<T>);
@@ -53,12 +118,63 @@ where
     F: FnMut(),
 {
     if args.len() < min_len {
-        println!("Usage: {}", msg);
+        print_error!("Usage: {}", msg);
     } else {
         action();
     }
 }
 
+// Parses a string of hex digits such as "0a1b" into bytes.
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// Formats bytes the way `parse_hex_bytes` reads them.
+fn format_hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Prints why a request was rejected, if it was.
+fn report_status(action: &str, status: BtStatus) {
+    if status != BtStatus::Success {
+        print_error!("Failed to {}: {:?}", action, status);
+    }
+}
+
+// Parses a 128-bit UUID, with or without dashes, or a 16-bit one such as "2a37".
+fn parse_uuid(uuid: &str) -> Option<Uuid128Bit> {
+    if uuid.len() == 4 {
+        return u16::from_str_radix(uuid, 16).ok().map(gatt_decode::from_uuid16);
+    }
+    parse_hex_bytes(&uuid.replace("-", ""))?.try_into().ok()
+}
+
+// Parses a GATT attribute handle, in decimal or with a 0x prefix in hex.
+fn parse_handle(handle: &str) -> Option<i32> {
+    let handle = match handle.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+        None => handle.parse::<u16>().ok()?,
+    };
+    // Handle 0 is reserved.
+    if handle == 0 {
+        None
+    } else {
+        Some(handle as i32)
+    }
+}
+
+// Parses a value to write, either in hex or as text after "utf8:".
+fn parse_write_value(value: &str) -> Option<Vec<u8>> {
+    match value.strip_prefix("utf8:") {
+        Some(text) => Some(text.as_bytes().to_vec()),
+        None => parse_hex_bytes(value.strip_prefix("0x").unwrap_or(value)),
+    }
+}
+
 fn wrap_help_text(text: &str, max: usize, indent: usize) -> String {
     let remaining_count = std::cmp::max(
         // real_max
@@ -86,7 +202,12 @@ fn build_commands() -> HashMap<String, CommandOption> {
         CommandOption {
             description: String::from(
                 "Enable/Disable/Show default bluetooth adapter. (e.g. adapter enable)\n
-                 Discoverable On/Off (e.g. adapter discoverable on)",
+                 Discoverable On/Off (e.g. adapter discoverable on)\n
+                 List all HCI devices and their controllers (e.g. adapter list)\n
+                 Choose the default adapter of the next sessions (e.g. adapter select 1)\n
+                 Show the radio activity for power accounting (e.g. adapter activity)\n
+                 Vendor debug events of the controller On/Off (e.g. adapter diagnostics on)\n
+                 Show the calls clients made when the daemon traces them (e.g. adapter api-trace)",
             ),
             function_pointer: CommandHandler::cmd_adapter,
         },
@@ -94,28 +215,95 @@ fn build_commands() -> HashMap<String, CommandOption> {
     command_options.insert(
         String::from("bond"),
         CommandOption {
-            description: String::from("Creates a bond with a device."),
+            description: String::from(
+                "Creates a bond with a device. (e.g. bond add <address>)\n
+                 Answers the PIN request of a legacy device (e.g. bond pin <address> 0000), or \
+                 rejects it (e.g. bond pin <address> reject)\n
+                 Exports the bonds, encrypted, for other units (e.g. bond export <path> \
+                 <passphrase>), or imports them for the next adapter start (e.g. bond import \
+                 <path> <passphrase>)",
+            ),
             function_pointer: CommandHandler::cmd_bond,
         },
     );
     command_options.insert(
         String::from("device"),
         CommandOption {
-            description: String::from("Take action on a remote device. (i.e. info)"),
+            description: String::from(
+                "Take action on a remote device. (i.e. info)\n
+                 Name a device for this client, so that commands can take the name instead of \
+                 its address (e.g. device alias <address> <alias>). Without an alias, removes it; \
+                 without an address, lists them\n
+                 Check a BR/EDR link with L2CAP echo requests, like l2ping \
+                 (e.g. device ping <address> [<count> [<size>]])\n
+                 Ask a BR/EDR device for its name again (e.g. device fetch-name <address>)\n
+                 Read the TX power of an LE link on a PHY, or follow its changes \
+                 (e.g. device tx-power <address> [1m|2m|coded|on|off])\n
+                 Report the path loss of an LE link leaving a range of dB, or stop \
+                 (e.g. device path-loss <address> <low> <high> [<hysteresis>], \
+                 device path-loss <address> off)\n
+                 Print what happens to one device: connections, bonding, RSSI, battery level and \
+                 GATT service changes, until unwatched \
+                 (e.g. device watch <address>, device unwatch <address>)\n
+                 Print everything known about a device: bond and connection state, UUIDs, \
+                 recent RSSIs and battery level (e.g. device show <address>)",
+            ),
             function_pointer: CommandHandler::cmd_device,
         },
     );
+    command_options.insert(
+        String::from("adv-monitor"),
+        CommandOption {
+            description: String::from(
+                "Report devices whose advertisements match a pattern. \
+                 Use: adv-monitor add <ad type> <offset> <hex content> \
+                 [<rssi high> <rssi low> <found ms> <lost ms>] or adv-monitor remove <id>",
+            ),
+            function_pointer: CommandHandler::cmd_adv_monitor,
+        },
+    );
     command_options.insert(
         String::from("discovery"),
         CommandOption {
-            description: String::from("Start and stop device discovery. (e.g. discovery start)"),
+            description: String::from(
+                "Start and stop device discovery. (e.g. discovery start)\n
+                 Show or set the path loss model estimating the distance of found devices, from \
+                 their RSSI at 1 m and the path loss exponent (e.g. discovery path-loss -59 2.0)\n
+                 Start and stop discovery on all enabled adapters at once, with the devices they \
+                 find merged by the manager (e.g. discovery merged start)\n
+                 Start a discovery session reporting devices found and lost when not seen for \
+                 some seconds, and stop it by ID (e.g. discovery session start 10)",
+            ),
             function_pointer: CommandHandler::cmd_discovery,
         },
     );
+    command_options.insert(
+        String::from("events"),
+        CommandOption {
+            description: String::from(
+                "Mute or show events printed by callbacks. \
+                 Use: events <mute|show> <manager|adapter|discovery|bonding|connection|\
+                 gatt|opp|pbap|map|pan|adv-monitor|security|all>",
+            ),
+            function_pointer: CommandHandler::cmd_events,
+        },
+    );
+    command_options.insert(
+        String::from("fast-pair"),
+        CommandOption {
+            description: String::from(
+                "Let phones pair through Google Fast Pair. \
+                 Use: fast-pair <provision <model id> <anti-spoofing key>|status|clear-keys>",
+            ),
+            function_pointer: CommandHandler::cmd_fast_pair,
+        },
+    );
     command_options.insert(
         String::from("floss"),
         CommandOption {
-            description: String::from("Enable or disable Floss for dogfood."),
+            description: String::from(
+                "Enable or disable Floss, reload its config, or attach a virtual controller.",
+            ),
             function_pointer: CommandHandler::cmd_floss,
         },
     );
@@ -140,15 +328,151 @@ fn build_commands() -> HashMap<String, CommandOption> {
             function_pointer: CommandHandler::cmd_help,
         },
     );
+    command_options.insert(
+        String::from("le-address"),
+        CommandOption {
+            description: String::from(
+                "Show or choose the address used on LE. Changes apply the next time the adapter \
+                 is enabled. \
+                 Use: le-address [static <address>|privacy <on [rpa timeout secs]|off>]",
+            ),
+            function_pointer: CommandHandler::cmd_le_address,
+        },
+    );
+    command_options.insert(
+        String::from("link-policy"),
+        CommandOption {
+            description: String::from(
+                "Show or switch the local role, and control sniff mode, on a BR/EDR link. \
+                 Use: link-policy <role <address> [central|peripheral]|sniff <address> <on|off>|\
+                 ssr <address> <max latency> <min remote timeout> <min local timeout>>",
+            ),
+            function_pointer: CommandHandler::cmd_link_policy,
+        },
+    );
     command_options.insert(
         String::from("list"),
         CommandOption {
             description: String::from(
-                "List bonded or found remote devices. Use: list <bonded|found>",
+                "List bonded, found, connected or blocked remote devices. Found devices can be \
+                 sorted by RSSI with their estimated distance. \
+                 Use: list <bonded|found [rssi]|connected|blocked>",
             ),
             function_pointer: CommandHandler::cmd_list_devices,
         },
     );
+    command_options.insert(
+        String::from("map"),
+        CommandOption {
+            description: String::from(
+                "Read messages from a phone and get notified of new ones. \
+                 Use: map <connect <address>|disconnect <address>|\
+                 list <address> <folder> [offset] [count]|read <address> <handle>>",
+            ),
+            function_pointer: CommandHandler::cmd_map,
+        },
+    );
+    command_options.insert(
+        String::from("opp"),
+        CommandOption {
+            description: String::from(
+                "Push files to a remote device and answer incoming pushes. \
+                 Use: opp <send <address> <path>|accept <id>|reject <id>|cancel <id>|list>",
+            ),
+            function_pointer: CommandHandler::cmd_opp,
+        },
+    );
+    command_options.insert(
+        String::from("pan"),
+        CommandOption {
+            description: String::from(
+                "Tether through a phone or share our network. \
+                 Use: pan <connect <address>|disconnect <address>|nap <on|off>|interface>",
+            ),
+            function_pointer: CommandHandler::cmd_pan,
+        },
+    );
+    command_options.insert(
+        String::from("pbap"),
+        CommandOption {
+            description: String::from(
+                "Download contacts or call history from a phone. \
+                 Use: pbap pull <address> <contacts|incoming|outgoing|missed|combined> \
+                 [offset] [count]",
+            ),
+            function_pointer: CommandHandler::cmd_pbap,
+        },
+    );
+    command_options.insert(
+        String::from("scan-params"),
+        CommandOption {
+            description: String::from(
+                "Show or tune the inquiry, page and LE discovery scans, in slots of 0.625ms. \
+                 Use: scan-params inquiry|page [<interval> <window> [standard|interlaced]] or \
+                 scan-params le [<interval> <window> [active|passive]]",
+            ),
+            function_pointer: CommandHandler::cmd_scan_params,
+        },
+    );
+    command_options.insert(
+        String::from("security"),
+        CommandOption {
+            description: String::from(
+                "Show or change the pairing security policy. With sc-only on, new pairings must \
+                 use Secure Connections. With bonded-only on, connections from devices that \
+                 aren't bonded are dropped. Keys shorter than min-key-size octets are rejected. \
+                 key-size shows the key size of a link. Use: security [sc-only <on|off> | \
+                 bonded-only <on|off> | min-key-size <7-16> | key-size <address> [bredr|le]]",
+            ),
+            function_pointer: CommandHandler::cmd_security,
+        },
+    );
+    command_options.insert(
+        String::from("source"),
+        CommandOption {
+            description: String::from(
+                "Run commands from a file. Use: source <file>. In the file, \
+                 'wait-for <event> <timeout secs>' waits for an event such as adapter-ready.",
+            ),
+            function_pointer: _noop,
+        },
+    );
+    command_options.insert(
+        String::from("throughput"),
+        CommandOption {
+            description: String::from(
+                "Measure the throughput of a link by streaming packets to or from a device, \
+                 over GATT to a characteristic value handle or over LE CoC to a PSM. \
+                 Use: throughput <start <address> <gatt|coc> <tx|rx> <handle|psm> \
+                 [<payload size> [<seconds>]]|stop <address>>",
+            ),
+            function_pointer: CommandHandler::cmd_throughput,
+        },
+    );
+    command_options.insert(
+        String::from("distance"),
+        CommandOption {
+            description: String::from(
+                "Measure the distance to a connected device at an interval, experimentally. \
+                 Methods: auto, rssi (an estimate from the RSSI of the link) and cs (LE Channel \
+                 Sounding, not supported yet). \
+                 Use: distance <start <address> [<interval ms> [<method>]]|stop <address>|methods>",
+            ),
+            function_pointer: CommandHandler::cmd_distance,
+        },
+    );
+    command_options.insert(
+        String::from("coex"),
+        CommandOption {
+            description: String::from(
+                "Tell the stack about Wi-Fi, as the connectivity manager does, to avoid its \
+                 channels and leave it the antenna. Channels are given as <MHz>/<bandwidth MHz>, \
+                 e.g. 2437/20. \
+                 Use: coex <wifi <channel>[,<channel>...]|none|latency <on|off>|state>",
+            ),
+            function_pointer: CommandHandler::cmd_coex,
+        },
+    );
     command_options.insert(
         String::from("quit"),
         CommandOption {
@@ -162,31 +486,142 @@ fn build_commands() -> HashMap<String, CommandOption> {
 impl CommandHandler {
     /// Creates a new CommandHandler.
     pub fn new(context: Arc<Mutex<ClientContext>>) -> CommandHandler {
-        CommandHandler { context, command_options: build_commands() }
+        CommandHandler { context, command_options: build_commands(), pending: None }
     }
 
-    /// Entry point for command and arguments
-    pub fn process_cmd_line(&mut self, command: &String, args: &Vec<String>) {
+    /// Entry point for command and arguments. Resolves once the command is done, including any
+    /// request it waits on.
+    pub async fn process_cmd_line(&mut self, command: &String, args: &Vec<String>) {
         // Ignore empty line
         match &command[0..] {
             "" => {}
+            _ if !self.is_supported_by_daemon(command, args) => {}
             _ => match self.command_options.get(command) {
                 Some(cmd) => (cmd.function_pointer)(self, &args),
                 None => {
-                    println!("'{}' is an invalid command!", command);
+                    print_error!("'{}' is an invalid command!", command);
                     self.cmd_help(&args);
                 }
             },
         };
+
+        if let Some(pending) = self.pending.take() {
+            pending.await;
+        }
     }
 
-    //  Common message for when the adapter isn't ready
-    fn adapter_not_ready(&self) {
-        let adapter_idx = self.context.lock().unwrap().default_adapter;
+    // Makes the current command wait for `request`, printing why it failed if it does.
+    fn wait_for_request<T, F>(&mut self, action: &'static str, request: F)
+    where
+        F: Future<Output = std::result::Result<T, RequestError>> + 'static,
+    {
+        self.pending = Some(Box::pin(async move {
+            if let Err(e) = request.await {
+                print_error!("Failed to {}: {}", action, e);
+            }
+        }));
+    }
+
+    // Checks that the daemons are recent enough for the subcommand, so that it fails with an error
+    // that says why instead of with an unknown method. Daemons which can't be reached are left to
+    // fail on their own.
+    fn is_supported_by_daemon(&self, command: &String, args: &Vec<String>) -> bool {
+        let api_version = match self.context.lock().unwrap().api_version {
+            Some(version) => version,
+            None => return true,
+        };
+        let subcommand = args.first().map(String::as_str).unwrap_or("");
+        let needed = SUBCOMMAND_API_VERSIONS
+            .iter()
+            .find(|(cmd, sub, _)| cmd == command && *sub == subcommand)
+            .map_or(0, |(_, _, version)| *version);
+        if api_version >= needed {
+            return true;
+        }
+
         print_error!(
-            "Default adapter {} is not enabled. Enable the adapter before using this command.",
-            adapter_idx
+            "'{} {}' is not supported by daemon version {}, it needs version {}",
+            command,
+            subcommand,
+            api_version,
+            needed
         );
+        false
+    }
+
+    //  Common message for when the adapter isn't ready
+    fn adapter_not_ready(&self) {
+        let (adapter_idx, state) = {
+            let context = self.context.lock().unwrap();
+            (context.default_adapter, context.adapter_state)
+        };
+
+        match state {
+            AdapterState::TurningOn | AdapterState::TurningOff | AdapterState::Recovering => {
+                print_error!(
+                    "Default adapter {} is busy ({:?}). Try again once it has settled.",
+                    adapter_idx,
+                    state
+                );
+            }
+            _ => print_error!(
+                "Default adapter {} is not enabled. Enable the adapter before using this command.",
+                adapter_idx
+            ),
+        }
+    }
+
+    // Parses a Bluetooth address such as "00:11:22:33:44:55", or the alias given to one with
+    // `device alias`, printing an error if it is neither.
+    fn parse_address(&self, addr: &str) -> Option<RawAddress> {
+        let address = RawAddress::from_string(addr)
+            .or_else(|| self.context.lock().unwrap().resolve_alias(addr));
+        if address.is_none() {
+            print_error!("Invalid address '{}'", addr);
+        }
+        address
+    }
+
+    // Lists the device aliases, or gives one to a device, replacing the one it had. Without an
+    // alias, removes the one of the device.
+    fn device_alias(&mut self, args: &[String]) {
+        let address = match args.first() {
+            Some(address) => match self.parse_address(address) {
+                Some(address) => address,
+                None => return,
+            },
+            None => {
+                let context = self.context.lock().unwrap();
+                let mut aliases: Vec<(&String, &RawAddress)> = context.aliases.iter().collect();
+                aliases.sort_by(|a, b| a.0.cmp(b.0));
+                for (alias, address) in aliases {
+                    print_info!("[{:17}] {}", address, alias);
+                }
+                return;
+            }
+        };
+
+        let alias = args.get(1);
+        if let Some(alias) = alias {
+            if !state::is_valid_alias(alias) {
+                print_error!(
+                    "Invalid alias '{}', it must be a single word and not an address",
+                    alias
+                );
+                return;
+            }
+        }
+
+        let mut context = self.context.lock().unwrap();
+        context.aliases.retain(|_, aliased| *aliased != address);
+        match alias {
+            Some(alias) => {
+                context.aliases.insert(alias.clone(), address);
+                print_info!("{} is now {}", address, alias);
+            }
+            None => print_info!("Removed the alias of {}", address),
+        }
+        context.save_state();
     }
 
     fn cmd_help(&mut self, args: &Vec<String>) {
@@ -202,7 +637,7 @@ impl CommandHandler {
                     );
                 }
                 None => {
-                    println!("'{}' is an invalid command!", args[0]);
+                    print_error!("'{}' is an invalid command!", args[0]);
                     self.cmd_help(&vec![]);
                 }
             }
@@ -240,18 +675,30 @@ impl CommandHandler {
 
     fn cmd_adapter(&mut self, args: &Vec<String>) {
         if !self.context.lock().unwrap().manager_dbus.get_floss_enabled() {
-            println!("Floss is not enabled. First run, `floss enable`");
+            print_error!("Floss is not enabled. First run, `floss enable`");
             return;
         }
 
         let default_adapter = self.context.lock().unwrap().default_adapter;
-        enforce_arg_len(args, 1, "adapter <enable|disable|show|discoverable>", || {
+        let usage = "adapter <enable|disable|show|discoverable|list|select <hci>|activity|\
+                     diagnostics <on|off>>";
+        enforce_arg_len(args, 1, usage, || {
             match &args[0][0..] {
                 "enable" => {
-                    self.context.lock().unwrap().manager_dbus.start(default_adapter);
+                    let request = requests::enable_adapter(
+                        self.context.clone(),
+                        default_adapter,
+                        ADAPTER_TOGGLE_TIMEOUT,
+                    );
+                    self.wait_for_request("enable adapter", request);
                 }
                 "disable" => {
-                    self.context.lock().unwrap().manager_dbus.stop(default_adapter);
+                    let request = requests::disable_adapter(
+                        self.context.clone(),
+                        default_adapter,
+                        ADAPTER_TOGGLE_TIMEOUT,
+                    );
+                    self.wait_for_request("disable adapter", request);
                 }
                 "show" => {
                     if !self.context.lock().unwrap().adapter_ready {
@@ -259,20 +706,19 @@ impl CommandHandler {
                         return;
                     }
 
-                    let enabled = self.context.lock().unwrap().enabled;
+                    let state = self.context.lock().unwrap().adapter_state;
                     let address = match self.context.lock().unwrap().adapter_address.as_ref() {
                         Some(x) => x.clone(),
                         None => String::from(""),
                     };
                     let context = self.context.lock().unwrap();
                     let adapter_dbus = context.adapter_dbus.as_ref().unwrap();
-                    let name = adapter_dbus.get_name();
+                    let props = adapter_dbus.get_adapter_properties();
                     let uuids = adapter_dbus.get_uuids();
-                    let is_discoverable = adapter_dbus.get_discoverable();
-                    let discoverable_timeout = adapter_dbus.get_discoverable_timeout();
-                    let cod = adapter_dbus.get_bluetooth_class();
                     let multi_adv_supported = adapter_dbus.is_multi_advertisement_supported();
                     let le_ext_adv_supported = adapter_dbus.is_le_extended_advertising_supported();
+                    let caps = adapter_dbus.get_adapter_capabilities();
+                    let controller = adapter_dbus.get_controller_info();
                     let uuid_helper = UuidHelper::new();
                     let enabled_profiles = uuid_helper.get_enabled_profiles();
                     let connected_profiles: Vec<Profile> = enabled_profiles
@@ -281,13 +727,65 @@ impl CommandHandler {
                         .cloned()
                         .collect();
                     print_info!("Address: {}", address);
-                    print_info!("Name: {}", name);
-                    print_info!("State: {}", if enabled { "enabled" } else { "disabled" });
-                    print_info!("Discoverable: {}", is_discoverable);
-                    print_info!("DiscoverableTimeout: {}s", discoverable_timeout);
-                    print_info!("Class: {:#06x}", cod);
+                    print_info!("Name: {}", props.name);
+                    print_info!("State: {:?}", state);
+                    print_info!("Discoverable: {}", props.discoverable);
+                    print_info!("DiscoverableTimeout: {}s", props.discoverable_timeout);
+                    print_info!("Class: {:#06x}", props.bluetooth_class);
+                    print_info!("Discovering: {}", props.discovering);
+                    print_info!("Connected devices: {}", props.connected_count);
                     print_info!("IsMultiAdvertisementSupported: {}", multi_adv_supported);
                     print_info!("IsLeExtendedAdvertisingSupported: {}", le_ext_adv_supported);
+                    print_info!(
+                        "LE: 2M PHY: {}, Coded PHY: {}, periodic advertising: {}, CIS central: {}, \
+                         BIS: {}",
+                        caps.le_2m_phy_supported,
+                        caps.le_coded_phy_supported,
+                        caps.le_periodic_advertising_supported,
+                        caps.le_connected_isochronous_stream_central_supported,
+                        caps.le_isochronous_broadcast_supported
+                    );
+                    print_info!(
+                        "Advertising sets: {} (max data length {}); advertising filters: {}",
+                        caps.max_advertising_sets,
+                        caps.max_advertising_data_length,
+                        caps.max_advertising_filters
+                    );
+                    print_info!(
+                        "ACL buffers: {} BR/EDR, {} LE; accept list size: {}; max connections: {}",
+                        caps.acl_buffer_count_classic,
+                        caps.acl_buffer_count_le,
+                        caps.le_accept_list_size,
+                        caps.max_connections
+                    );
+                    print_info!(
+                        "Controller: manufacturer {:#06x}, HCI {} revision {:#06x}, LMP {} \
+                         subversion {:#06x}",
+                        controller.manufacturer,
+                        controller.hci_version,
+                        controller.hci_revision,
+                        controller.lmp_version,
+                        controller.lmp_subversion
+                    );
+                    print_info!(
+                        "Firmware: {}",
+                        if controller.firmware_version.is_empty() {
+                            "unknown"
+                        } else {
+                            &controller.firmware_version
+                        }
+                    );
+                    print_info!(
+                        "Vendor diagnostics: {}",
+                        match (
+                            controller.vendor_diagnostics_supported,
+                            controller.vendor_diagnostics_enabled
+                        ) {
+                            (false, _) => "unsupported",
+                            (true, true) => "on",
+                            (true, false) => "off",
+                        }
+                    );
                     print_info!("Connected profiles: {:?}", connected_profiles);
                     print_info!(
                         "Uuids: {}",
@@ -299,9 +797,155 @@ impl CommandHandler {
                         )
                     );
                 }
+                "select" => {
+                    let hci_interface = match args.get(1).map(|hci| hci.parse::<i32>()) {
+                        Some(Ok(hci_interface)) => hci_interface,
+                        _ => {
+                            print_error!("usage: adapter select <hci>");
+                            return;
+                        }
+                    };
+                    let mut context = self.context.lock().unwrap();
+                    let adapters = context.manager_dbus.get_adapter_details();
+                    if !adapters.iter().any(|adapter| adapter.hci_interface == hci_interface) {
+                        print_error!("There is no hci{}", hci_interface);
+                        return;
+                    }
+                    context.selected_adapter = hci_interface;
+                    context.save_state();
+                    print_info!(
+                        "hci{} will be the default adapter from the next start of btclient",
+                        hci_interface
+                    );
+                }
+                "list" => {
+                    let adapters = self.context.lock().unwrap().manager_dbus.get_adapter_details();
+                    for adapter in adapters {
+                        // Negative values mean the kernel didn't report them.
+                        let hex_or_unknown = |value: i32| match value {
+                            v if v < 0 => String::from("unknown"),
+                            v => format!("{:#06x}", v),
+                        };
+                        print_info!(
+                            "hci{}{}: {} {:04x}:{:04x}, enabled: {}",
+                            adapter.hci_interface,
+                            if adapter.hci_interface == default_adapter {
+                                " (default)"
+                            } else {
+                                ""
+                            },
+                            adapter.bus,
+                            adapter.vendor_id,
+                            adapter.product_id,
+                            adapter.enabled
+                        );
+                        print_info!(
+                            "    Manufacturer: {}, HCI version: {}, revision: {}",
+                            hex_or_unknown(adapter.manufacturer),
+                            match adapter.hci_version {
+                                v if v < 0 => String::from("unknown"),
+                                v => v.to_string(),
+                            },
+                            hex_or_unknown(adapter.hci_revision)
+                        );
+                        print_info!("    Features: {}", DisplayList(adapter.features));
+                    }
+                }
+                "activity" => {
+                    if !self.context.lock().unwrap().adapter_ready {
+                        self.adapter_not_ready();
+                        return;
+                    }
+
+                    let info = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_ref()
+                        .unwrap()
+                        .get_activity_energy_info();
+                    if info.controller_reported {
+                        print_info!(
+                            "Controller: state {}, tx {} ms, rx {} ms, idle {} ms, energy {}",
+                            info.controller_state,
+                            info.controller_tx_time_ms,
+                            info.controller_rx_time_ms,
+                            info.controller_idle_time_ms,
+                            info.controller_energy_used
+                        );
+                    } else {
+                        print_info!("Controller: no activity reported yet");
+                    }
+                    print_info!(
+                        "Scanning: {} ms, radio on for {} ms",
+                        info.scan_time_ms,
+                        info.scan_radio_time_ms
+                    );
+                    print_info!(
+                        "Advertising: {} ms, air time {} ms",
+                        info.advertising_time_ms,
+                        info.advertising_airtime_ms
+                    );
+                    for device in info.devices {
+                        print_info!(
+                            "{}: sent {} bytes, received {} bytes",
+                            device.address.to_string(),
+                            device.tx_bytes,
+                            device.rx_bytes
+                        );
+                    }
+                }
+                "api-trace" => {
+                    if !self.context.lock().unwrap().adapter_ready {
+                        self.adapter_not_ready();
+                        return;
+                    }
+
+                    let trace = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_ref()
+                        .unwrap()
+                        .dump_api_trace();
+                    if trace.is_empty() {
+                        print_info!("No API trace, the daemon traces calls with --api-trace");
+                    } else {
+                        print!("{}", trace);
+                    }
+                }
+                "diagnostics" => {
+                    if !self.context.lock().unwrap().adapter_ready {
+                        self.adapter_not_ready();
+                        return;
+                    }
+
+                    let enabled = match args.get(1).map(|arg| &arg[0..]) {
+                        Some("on") => true,
+                        Some("off") => false,
+                        _ => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+
+                    if !self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_mut()
+                        .unwrap()
+                        .set_vendor_diagnostics_enabled(enabled)
+                    {
+                        print_error!("Failed to turn {} the vendor diagnostics", args[1]);
+                    }
+                }
                 "discoverable" => match &args[1][0..] {
                     "on" => {
-                        let discoverable = self
+                        let status = self
                             .context
                             .lock()
                             .unwrap()
@@ -309,13 +953,10 @@ impl CommandHandler {
                             .as_ref()
                             .unwrap()
                             .set_discoverable(true, 60);
-                        print_info!(
-                            "Set discoverable for 60s: {}",
-                            if discoverable { "succeeded" } else { "failed" }
-                        );
+                        print_info!("Set discoverable for 60s: {:?}", status);
                     }
                     "off" => {
-                        let discoverable = self
+                        let status = self
                             .context
                             .lock()
                             .unwrap()
@@ -323,15 +964,12 @@ impl CommandHandler {
                             .as_ref()
                             .unwrap()
                             .set_discoverable(false, 60);
-                        print_info!(
-                            "Turn discoverable off: {}",
-                            if discoverable { "succeeded" } else { "failed" }
-                        );
+                        print_info!("Turn discoverable off: {:?}", status);
                     }
-                    _ => println!("Invalid argument for adapter discoverable '{}'", args[1]),
+                    _ => print_error!("Invalid argument for adapter discoverable '{}'", args[1]),
                 },
                 _ => {
-                    println!("Invalid argument '{}'", args[0]);
+                    print_error!("Invalid argument '{}'", args[0]);
                 }
             }
         });
@@ -348,20 +986,148 @@ impl CommandHandler {
     }
 
     fn cmd_discovery(&mut self, args: &Vec<String>) {
+        // Merged discovery is run by the manager, so it doesn't need the default adapter.
+        if args.first().map(String::as_str) == Some("merged") {
+            self.cmd_merged_discovery(&args[1..].to_vec());
+            return;
+        }
+
         if !self.context.lock().unwrap().adapter_ready {
             self.adapter_not_ready();
             return;
         }
 
-        enforce_arg_len(args, 1, "discovery <start|stop>", || match &args[0][0..] {
+        if args.first().map(String::as_str) == Some("session") {
+            self.cmd_discovery_session(&args[1..].to_vec());
+            return;
+        }
+
+        let usage = "discovery <start|stop|merged <start|stop>|session <start [<lost secs>]|stop \
+                     <id>>|path-loss [<rssi at 1 m> <exponent>]>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
             "start" => {
-                self.context.lock().unwrap().adapter_dbus.as_ref().unwrap().start_discovery();
+                let request = requests::start_discovery(self.context.clone(), DISCOVERY_TIMEOUT);
+                self.wait_for_request("start discovery", request);
             }
             "stop" => {
-                self.context.lock().unwrap().adapter_dbus.as_ref().unwrap().cancel_discovery();
+                let request = requests::cancel_discovery(self.context.clone(), DISCOVERY_TIMEOUT);
+                self.wait_for_request("stop discovery", request);
+            }
+            "path-loss" => {
+                if args.len() > 1 {
+                    let model = match (args.get(1), args.get(2)) {
+                        (Some(power), Some(exponent)) => {
+                            match (power.parse::<i32>(), exponent.parse::<f64>()) {
+                                (Ok(power), Ok(exponent)) => PathLossModel::new(power, exponent),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    };
+                    match model {
+                        Some(model) => self.context.lock().unwrap().path_loss = model,
+                        None => {
+                            print_error!("Expected an RSSI in dBm and an exponent from 1 to 6");
+                            return;
+                        }
+                    }
+                }
+
+                let model = self.context.lock().unwrap().path_loss;
+                print_info!(
+                    "Path loss model: {} dBm at 1 m, exponent {}",
+                    model.measured_power,
+                    model.exponent
+                );
             }
             _ => {
-                println!("Invalid argument '{}'", args[0]);
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_discovery_session(&mut self, args: &Vec<String>) {
+        let usage = "discovery session <start [<lost secs>]|stop <id>>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "start" => {
+                let lost_secs = match args.get(1).map(|s| s.parse::<u32>()) {
+                    None => DEFAULT_DEVICE_LOST_SECS,
+                    Some(Ok(secs)) => secs,
+                    Some(Err(_)) => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                let mut context = self.context.lock().unwrap();
+                context.discovery_session_callback_count += 1;
+                let objpath = format!(
+                    "/org/chromium/bluetooth/client/{}/discovery_session_callback{}",
+                    context.default_adapter, context.discovery_session_callback_count
+                );
+                let callback = BtDiscoverySessionCallback::new(
+                    objpath,
+                    self.context.clone(),
+                    context.dbus_connection.clone(),
+                    context.dbus_crossroads.clone(),
+                    context.disconnect_watcher.clone(),
+                );
+
+                let id = context
+                    .adapter_dbus
+                    .as_mut()
+                    .unwrap()
+                    .start_discovery_session(Box::new(callback), lost_secs.saturating_mul(1000));
+                if id == 0 {
+                    print_error!("Failed to start a discovery session, lost time is 1 to 600 s");
+                } else {
+                    print_info!("Started discovery session {}", id);
+                }
+            }
+            "stop" => {
+                let id = match args.get(1).and_then(|s| s.parse::<u32>().ok()) {
+                    Some(id) => id,
+                    None => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                let stopped = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .adapter_dbus
+                    .as_mut()
+                    .unwrap()
+                    .stop_discovery_session(id);
+                if !stopped {
+                    print_error!("No discovery session {}", id);
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_merged_discovery(&mut self, args: &Vec<String>) {
+        let usage = "discovery merged <start|stop>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "start" => {
+                if self.context.lock().unwrap().manager_dbus.start_merged_discovery() {
+                    print_info!("Merged discovery started");
+                } else {
+                    print_error!("No adapter is enabled");
+                }
+            }
+            "stop" => {
+                if !self.context.lock().unwrap().manager_dbus.cancel_merged_discovery() {
+                    print_error!("Merged discovery isn't running");
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
             }
         });
     }
@@ -372,11 +1138,17 @@ impl CommandHandler {
             return;
         }
 
-        enforce_arg_len(args, 2, "bond <add|remove|cancel> <address>", || match &args[0][0..] {
+        let usage = "bond <add|remove|cancel> <address> | bond <export|import> <path> <passphrase>";
+        enforce_arg_len(args, 2, usage, || match &args[0][0..] {
             "add" => {
+                let address = match self.parse_address(&args[1]) {
+                    Some(address) => address,
+                    None => return,
+                };
                 let device = BluetoothDevice {
-                    address: String::from(&args[1]),
+                    address,
                     name: String::from("Classic Device"),
+                    ..Default::default()
                 };
 
                 let bonding_attempt =
@@ -390,7 +1162,7 @@ impl CommandHandler {
                     return;
                 }
 
-                let success = self
+                let status = self
                     .context
                     .lock()
                     .unwrap()
@@ -399,53 +1171,174 @@ impl CommandHandler {
                     .unwrap()
                     .create_bond(device.clone(), BtTransport::Auto);
 
-                if success {
+                if status == BtStatus::Success {
                     self.context.lock().unwrap().bonding_attempt = Some(device);
+                    self.context.lock().unwrap().default_pins = None;
+                } else {
+                    report_status("create a bond", status);
+                }
+            }
+            "pin" => {
+                if args.len() < 3 {
+                    print_error!("Usage: bond pin <address> <pin|reject>");
+                    return;
                 }
+                let address = match self.parse_address(&args[1]) {
+                    Some(address) => address,
+                    None => return,
+                };
+
+                let pending = self.context.lock().unwrap().pin_request.take();
+                let device = match pending {
+                    Some(device) if device.address == address => device,
+                    pending => {
+                        self.context.lock().unwrap().pin_request = pending;
+                        print_error!("No PIN was requested by [{}]", args[1]);
+                        return;
+                    }
+                };
+
+                let accept = args[2] != "reject";
+                let pin = if accept { args[2].clone().into_bytes() } else { vec![] };
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .adapter_dbus
+                    .as_ref()
+                    .unwrap()
+                    .set_pin(device, accept, pin);
+                report_status("set the PIN", status);
             }
             "remove" => {
+                let address = match self.parse_address(&args[1]) {
+                    Some(address) => address,
+                    None => return,
+                };
                 let device = BluetoothDevice {
-                    address: String::from(&args[1]),
+                    address,
                     name: String::from("Classic Device"),
+                    ..Default::default()
                 };
 
-                self.context.lock().unwrap().adapter_dbus.as_ref().unwrap().remove_bond(device);
+                let status =
+                    self.context.lock().unwrap().adapter_dbus.as_ref().unwrap().remove_bond(device);
+                report_status("remove the bond", status);
             }
             "cancel" => {
+                let address = match self.parse_address(&args[1]) {
+                    Some(address) => address,
+                    None => return,
+                };
                 let device = BluetoothDevice {
-                    address: String::from(&args[1]),
+                    address,
                     name: String::from("Classic Device"),
+                    ..Default::default()
                 };
 
-                self.context
+                let status = self
+                    .context
                     .lock()
                     .unwrap()
                     .adapter_dbus
                     .as_ref()
                     .unwrap()
                     .cancel_bond_process(device);
+                report_status("cancel bonding", status);
+            }
+            "export" | "import" => {
+                if args.len() < 3 {
+                    print_error!("Usage: bond {} <path> <passphrase>", args[0]);
+                    return;
+                }
+                let (path, passphrase) = (&args[1], args[2].clone());
+
+                // The file is read and written here, with the permissions of whoever runs the
+                // client, the daemon only sees the archive.
+                if args[0] == "export" {
+                    let archive = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .provisioning_dbus
+                        .as_mut()
+                        .unwrap()
+                        .export_bonds(passphrase);
+                    if archive.is_empty() {
+                        print_error!("Failed to export the bonds");
+                        return;
+                    }
+                    match std::fs::write(path, archive) {
+                        Ok(()) => print_info!("Exported the bonds to {}", path),
+                        Err(e) => print_error!("Failed to write {}: {}", path, e),
+                    }
+                    return;
+                }
+
+                let archive = match std::fs::read(path) {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        print_error!("Failed to read {}: {}", path, e);
+                        return;
+                    }
+                };
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .provisioning_dbus
+                    .as_mut()
+                    .unwrap()
+                    .import_bonds(archive, passphrase);
+
+                match status {
+                    BtStatus::Success => {
+                        print_info!("Imported the bonds, restart the adapter to use them")
+                    }
+                    BtStatus::AuthFailure => {
+                        print_error!("Wrong passphrase, or not a bond archive")
+                    }
+                    status => report_status("import the bonds", status),
+                }
             }
             _ => {
-                println!("Invalid argument '{}'", args[0]);
+                print_error!("Invalid argument '{}'", args[0]);
             }
         });
     }
 
     fn cmd_device(&mut self, args: &Vec<String>) {
+        // Aliases are only known to the client, so they don't need the adapter.
+        if args.first().map(String::as_str) == Some("alias") {
+            self.device_alias(&args[1..]);
+            return;
+        }
+
         if !self.context.lock().unwrap().adapter_ready {
             self.adapter_not_ready();
             return;
         }
 
-        enforce_arg_len(args, 2, "device <connect|disconnect|info|set-alias> <address>", || {
-            match &args[0][0..] {
+        enforce_arg_len(
+            args,
+            2,
+            "device <connect|disconnect|disconnect-link|force-disconnect|info|set-alias|block|\
+             unblock|enable-profile|disable-profile|ping|ping-cancel|fetch-name|tx-power|\
+             path-loss|watch|unwatch|show> <address>, or \
+             device alias [<address> [<alias>]]",
+            || match &args[0][0..] {
                 "connect" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
                     let device = BluetoothDevice {
-                        address: String::from(&args[1]),
+                        address,
                         name: String::from("Classic Device"),
+                        ..Default::default()
                     };
 
-                    let success = self
+                    let status = self
                         .context
                         .lock()
                         .unwrap()
@@ -454,19 +1347,24 @@ impl CommandHandler {
                         .unwrap()
                         .connect_all_enabled_profiles(device.clone());
 
-                    if success {
-                        println!("Connecting to {}", &device.address);
+                    if status == BtStatus::Success {
+                        print_info!("Connecting to {}", &device.address);
                     } else {
-                        println!("Can't connect to {}", &device.address);
+                        print_error!("Can't connect to {}: {:?}", &device.address, status);
                     }
                 }
                 "disconnect" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
                     let device = BluetoothDevice {
-                        address: String::from(&args[1]),
+                        address,
                         name: String::from("Classic Device"),
+                        ..Default::default()
                     };
 
-                    let success = self
+                    let status = self
                         .context
                         .lock()
                         .unwrap()
@@ -475,16 +1373,66 @@ impl CommandHandler {
                         .unwrap()
                         .disconnect_all_enabled_profiles(device.clone());
 
-                    if success {
-                        println!("Disconnecting from {}", &device.address);
+                    if status == BtStatus::Success {
+                        print_info!("Disconnecting from {}", &device.address);
                     } else {
-                        println!("Can't disconnect from {}", &device.address);
+                        print_error!("Can't disconnect from {}: {:?}", &device.address, status);
                     }
                 }
+                "disconnect-link" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let transport = match args.get(2).map(|t| t.as_str()) {
+                        None | Some("auto") => BtTransport::Auto,
+                        Some("bredr") => BtTransport::Bredr,
+                        Some("le") => BtTransport::Le,
+                        Some(t) => {
+                            print_error!("Unknown transport '{}', expected auto, bredr or le", t);
+                            return;
+                        }
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let status = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_mut()
+                        .unwrap()
+                        .disconnect(device, transport);
+                    report_status("disconnect link", status);
+                }
+                "force-disconnect" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let status = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_mut()
+                        .unwrap()
+                        .disconnect_all_profiles(device);
+                    report_status("force disconnect", status);
+                }
                 "info" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
                     let device = BluetoothDevice {
-                        address: String::from(&args[1]),
+                        address,
                         name: String::from("Classic Device"),
+                        ..Default::default()
                     };
 
                     let (name, alias, device_type, class, bonded, connected, uuids) = {
@@ -501,14 +1449,38 @@ impl CommandHandler {
 
                         (name, alias, device_type, class, bonded, connected, uuids)
                     };
+                    let (version, features) = {
+                        let ctx = self.context.lock().unwrap();
+                        let adapter = ctx.adapter_dbus.as_ref().unwrap();
+
+                        (
+                            adapter.get_remote_version_info(device.clone()),
+                            adapter.get_remote_supported_features(device.clone()),
+                        )
+                    };
 
                     print_info!("Address: {}", &device.address);
                     print_info!("Name: {}", name);
                     print_info!("Alias: {}", alias);
                     print_info!("Type: {:?}", device_type);
                     print_info!("Class: {}", class);
+                    print_info!("Icon: {:?}", DeviceType::from_class_of_device(class));
                     print_info!("Bonded: {}", bonded);
                     print_info!("Connected: {}", connected);
+                    if version.valid {
+                        print_info!(
+                            "Version: LMP {}, subversion {:#06x}, manufacturer {:#06x}",
+                            version.lmp_version,
+                            version.lmp_subversion,
+                            version.manufacturer
+                        );
+                    }
+                    if !features.lmp_features.is_empty() {
+                        print_info!("LMP features: {}", format_hex_bytes(&features.lmp_features));
+                    }
+                    if !features.le_features.is_empty() {
+                        print_info!("LE features: {}", format_hex_bytes(&features.le_features));
+                    }
                     print_info!(
                         "Uuids: {}",
                         DisplayList(
@@ -521,12 +1493,16 @@ impl CommandHandler {
                 }
                 "set-alias" => {
                     if args.len() < 3 {
-                        println!("usage: device set-alias <address> <new-alias>");
+                        print_error!("usage: device set-alias <address> <new-alias>");
                         return;
                     }
                     let new_alias = &args[2];
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
                     let device =
-                        BluetoothDevice { address: String::from(&args[1]), name: String::from("") };
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
                     let old_alias = self
                         .context
                         .lock()
@@ -535,130 +1511,2044 @@ impl CommandHandler {
                         .as_ref()
                         .unwrap()
                         .get_remote_alias(device.clone());
-                    println!("Updating alias for {}: {} -> {}", &args[1], old_alias, new_alias);
-                    self.context
+                    print_info!("Updating alias for {}: {} -> {}", &args[1], old_alias, new_alias);
+                    let status = self
+                        .context
                         .lock()
                         .unwrap()
                         .adapter_dbus
                         .as_mut()
                         .unwrap()
                         .set_remote_alias(device.clone(), new_alias.clone());
+                    report_status("set the alias", status);
+                }
+                "block" | "unblock" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let block = args[0] == "block";
+                    let success = {
+                        let mut ctx = self.context.lock().unwrap();
+                        let adapter = ctx.adapter_dbus.as_mut().unwrap();
+                        if block {
+                            adapter.block_device(device.clone())
+                        } else {
+                            adapter.unblock_device(device.clone())
+                        }
+                    };
+
+                    if success {
+                        print_info!("{} {}", if block { "Blocked" } else { "Unblocked" }, &args[1]);
+                    } else {
+                        print_error!("Can't {} {}", &args[0], &args[1]);
+                    }
+                }
+                "enable-profile" | "disable-profile" => {
+                    if args.len() < 3 {
+                        print_error!("usage: device {} <address> <profile>", &args[0]);
+                        return;
+                    }
+
+                    // Profiles are named after their enum variants, e.g. A2dpSink or Hfp.
+                    let profile = match UuidHelper::new()
+                        .profiles
+                        .values()
+                        .find(|p| format!("{:?}", p).eq_ignore_ascii_case(&args[2]))
+                    {
+                        Some(profile) => *profile,
+                        None => {
+                            print_error!("Unknown profile '{}'", &args[2]);
+                            return;
+                        }
+                    };
+
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+                    let enabled = args[0] == "enable-profile";
+                    let success = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_mut()
+                        .unwrap()
+                        .set_profile_enabled(device, profile, enabled);
+
+                    if success {
+                        print_info!(
+                            "{:?} {} for {}",
+                            profile,
+                            if enabled { "enabled" } else { "disabled" },
+                            &args[1]
+                        );
+                    } else {
+                        print_error!("Can't {} {:?} for {}", &args[0], profile, &args[1]);
+                    }
+                }
+                "ping" | "ping-cancel" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let mut ctx = self.context.lock().unwrap();
+                    let diagnostics = ctx.link_diagnostics_dbus.as_mut().unwrap();
+                    if args[0] == "ping-cancel" {
+                        if !diagnostics.cancel_ping(device) {
+                            print_error!("{} isn't being pinged", &args[1]);
+                        }
+                        return;
+                    }
+
+                    let parse = |index: usize, default: u32| match args.get(index) {
+                        Some(arg) => arg.parse::<u32>().ok(),
+                        None => Some(default),
+                    };
+                    let (count, size) =
+                        match (parse(2, DEFAULT_PING_COUNT), parse(3, DEFAULT_PING_SIZE)) {
+                            (Some(count), Some(size)) => (count, size),
+                            _ => {
+                                print_error!("usage: device ping <address> [<count> [<size>]]");
+                                return;
+                            }
+                        };
+                    if diagnostics.ping(device, count, size) {
+                        print_info!("Pinging {} with {} bytes of data", &args[1], size);
+                    } else {
+                        print_error!(
+                            "Can't ping {}: no BR/EDR link, already pinging, or count above 1000 \
+                             or size above 600",
+                            &args[1]
+                        );
+                    }
+                }
+                "fetch-name" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let fetching = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_mut()
+                        .unwrap()
+                        .fetch_remote_name(device);
+                    if !fetching {
+                        print_error!("Can't fetch the name of {}: LE only device", &args[1]);
+                    }
+                }
+                "tx-power" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let mut ctx = self.context.lock().unwrap();
+                    let diagnostics = ctx.link_diagnostics_dbus.as_mut().unwrap();
+                    let done = match args.get(2).map(String::as_str) {
+                        Some("on") => diagnostics.set_tx_power_reporting(device, true),
+                        Some("off") => diagnostics.set_tx_power_reporting(device, false),
+                        phy => {
+                            let phy = match phy {
+                                None | Some("1m") => LePhy::Phy1m,
+                                Some("2m") => LePhy::Phy2m,
+                                Some("coded") => LePhy::PhyCoded,
+                                Some(_) => {
+                                    print_error!(
+                                        "usage: device tx-power <address> [1m|2m|coded|on|off]"
+                                    );
+                                    return;
+                                }
+                            };
+                            diagnostics.read_tx_power(device, phy)
+                        }
+                    };
+                    if !done {
+                        print_error!(
+                            "Can't get the TX power of {}: no LE link, or no LE Power Control",
+                            &args[1]
+                        );
+                    }
+                }
+                "path-loss" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device =
+                        BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                    let mut ctx = self.context.lock().unwrap();
+                    let diagnostics = ctx.link_diagnostics_dbus.as_mut().unwrap();
+                    if args.get(2).map(String::as_str) == Some("off") {
+                        if !diagnostics.clear_path_loss_range(device) {
+                            print_error!("Can't stop the path loss reports of {}", &args[1]);
+                        }
+                        return;
+                    }
+
+                    let parse = |index: usize, default: Option<u32>| match args.get(index) {
+                        Some(arg) => arg.parse::<u32>().ok(),
+                        None => default,
+                    };
+                    let (low, high, hysteresis) =
+                        match (parse(2, None), parse(3, None), parse(4, Some(0))) {
+                            (Some(low), Some(high), Some(hysteresis)) => (low, high, hysteresis),
+                            _ => {
+                                print_error!(
+                                    "usage: device path-loss <address> <low> <high> \
+                                     [<hysteresis>], or device path-loss <address> off"
+                                );
+                                return;
+                            }
+                        };
+                    if !diagnostics.set_path_loss_range(device, low, high, hysteresis) {
+                        print_error!(
+                            "Can't follow the path loss of {}: no LE link, no LE Path Loss \
+                             Monitoring, or low above high, high above 254 or hysteresis above \
+                             half the range",
+                            &args[1]
+                        );
+                    }
+                }
+                "show" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    let device = BluetoothDevice { address, ..Default::default() };
+
+                    let (info, client) = {
+                        let ctx = self.context.lock().unwrap();
+                        let info =
+                            ctx.adapter_dbus.as_ref().unwrap().get_remote_device_info(device);
+                        let client = ClientDeviceInfo {
+                            alias: ctx
+                                .aliases
+                                .iter()
+                                .find(|(_, aliased)| **aliased == address)
+                                .map(|(alias, _)| alias.clone()),
+                            battery_level: ctx.battery_levels.get(&address).copied(),
+                        };
+                        (info, client)
+                    };
+                    for line in device_report::describe(&info, &client) {
+                        print_info!("{}", line);
+                    }
+                }
+                "watch" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    if !watch::start(self.context.clone(), address) {
+                        print_error!("Already watching {}", &args[1]);
+                    }
+                }
+                "unwatch" => {
+                    let address = match self.parse_address(&args[1]) {
+                        Some(address) => address,
+                        None => return,
+                    };
+                    if !watch::stop(&self.context, &address) {
+                        print_error!("Not watching {}", &args[1]);
+                    }
                 }
                 _ => {
-                    println!("Invalid argument '{}'", args[0]);
+                    print_error!("Invalid argument '{}'", args[0]);
                 }
-            }
-        });
+            },
+        );
     }
 
-    fn cmd_floss(&mut self, args: &Vec<String>) {
-        enforce_arg_len(args, 1, "floss <enable|disable>", || match &args[0][0..] {
-            "enable" => {
-                self.context.lock().unwrap().manager_dbus.set_floss_enabled(true);
-            }
-            "disable" => {
-                self.context.lock().unwrap().manager_dbus.set_floss_enabled(false);
-            }
-            "show" => {
-                print_info!(
-                    "Floss enabled: {}",
-                    self.context.lock().unwrap().manager_dbus.get_floss_enabled()
-                );
+    fn cmd_events(&mut self, args: &Vec<String>) {
+        if args.is_empty() {
+            let context = self.context.lock().unwrap();
+            for category in EventCategory::ALL.iter() {
+                let state = if context.is_event_shown(*category) { "shown" } else { "muted" };
+                print_info!("{}: {}", category.name(), state);
             }
-            _ => {
-                println!("Invalid argument '{}'", args[0]);
+            return;
+        }
+
+        enforce_arg_len(args, 2, "events <mute|show> <category|all>", || {
+            let shown = match &args[0][0..] {
+                "mute" => false,
+                "show" => true,
+                _ => {
+                    print_error!("Invalid argument '{}'", args[0]);
+                    return;
+                }
+            };
+
+            let categories = match &args[1][0..] {
+                "all" => EventCategory::ALL.to_vec(),
+                name => match EventCategory::from_name(name) {
+                    Some(category) => vec![category],
+                    None => {
+                        print_error!("Invalid event category '{}'", name);
+                        return;
+                    }
+                },
+            };
+
+            let mut context = self.context.lock().unwrap();
+            for category in categories {
+                context.set_event_shown(category, shown);
             }
         });
     }
 
-    fn cmd_gatt(&mut self, args: &Vec<String>) {
+    fn cmd_opp(&mut self, args: &Vec<String>) {
         if !self.context.lock().unwrap().adapter_ready {
             self.adapter_not_ready();
             return;
         }
 
-        enforce_arg_len(args, 1, "gatt <commands>", || match &args[0][0..] {
-            "register-client" => {
-                let dbus_connection = self.context.lock().unwrap().dbus_connection.clone();
-                let dbus_crossroads = self.context.lock().unwrap().dbus_crossroads.clone();
-
-                self.context.lock().unwrap().gatt_dbus.as_mut().unwrap().register_client(
-                    String::from(GATT_CLIENT_APP_UUID),
-                    Box::new(BtGattCallback::new(
-                        String::from("/org/chromium/bluetooth/client/bluetooth_gatt_callback"),
-                        self.context.clone(),
-                        dbus_connection,
-                        dbus_crossroads,
-                    )),
-                    false,
-                );
-            }
-            "client-connect" => {
-                if args.len() < 2 {
-                    println!("usage: gatt client-connect <addr>");
+        let usage = "opp <send <address> <path>|accept <id>|reject <id>|cancel <id>|list>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "send" => {
+                if args.len() < 3 {
+                    print_error!("Usage: {}", usage);
                     return;
                 }
 
-                let client_id = self.context.lock().unwrap().gatt_client_id;
-                if client_id.is_none() {
-                    println!("GATT client is not yet registered.");
-                    return;
-                }
+                let address = match self.parse_address(&args[1]) {
+                    Some(address) => address,
+                    None => return,
+                };
+                let device = BluetoothDevice {
+                    address,
+                    name: String::from("Classic Device"),
+                    ..Default::default()
+                };
 
-                let addr = String::from(&args[1]);
-                self.context.lock().unwrap().gatt_dbus.as_ref().unwrap().client_connect(
-                    client_id.unwrap(),
+                // The adapter daemon opens the file, so relative paths have to be resolved here.
+                let path = match std::fs::canonicalize(&args[2]) {
+                    Ok(path) => path.to_string_lossy().to_string(),
+                    Err(e) => {
+                        print_error!("Can't find {}: {}", &args[2], e);
+                        return;
+                    }
+                };
+
+                let id = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .opp_dbus
+                    .as_mut()
+                    .unwrap()
+                    .send_file(device, path.clone());
+
+                if id < 0 {
+                    print_error!("Failed to send {} to {}", path, &args[1]);
+                } else {
+                    print_info!("Sending {} to {} as transfer {}", path, &args[1], id);
+                }
+            }
+            "accept" | "reject" | "cancel" => {
+                if args.len() < 2 {
+                    print_error!("Usage: {}", usage);
+                    return;
+                }
+
+                let id = match args[1].parse::<i32>() {
+                    Ok(id) => id,
+                    Err(_) => {
+                        print_error!("Invalid transfer id '{}'", args[1]);
+                        return;
+                    }
+                };
+
+                let mut context = self.context.lock().unwrap();
+                let opp = context.opp_dbus.as_mut().unwrap();
+                let success = match &args[0][0..] {
+                    "accept" => opp.accept_transfer(id, true),
+                    "reject" => opp.accept_transfer(id, false),
+                    _ => opp.cancel_transfer(id),
+                };
+
+                if !success {
+                    print_error!("Can't {} transfer {}", &args[0], id);
+                }
+            }
+            "list" => {
+                let transfers =
+                    self.context.lock().unwrap().opp_dbus.as_ref().unwrap().get_transfers();
+                for transfer in transfers {
+                    print_info!(
+                        "{}: {} [{}] {} ({} bytes)",
+                        transfer.id,
+                        if transfer.outgoing { "to" } else { "from" },
+                        transfer.device.address,
+                        transfer.file_path,
+                        transfer.total_bytes
+                    );
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_throughput(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "throughput <start <address> <gatt|coc> <tx|rx> <handle|psm> [<payload size> \
+                     [<seconds>]]|stop <address>>";
+        enforce_arg_len(args, 2, usage, || {
+            let address = match self.parse_address(&args[1]) {
+                Some(address) => address,
+                None => return,
+            };
+            let device = BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+            match &args[0][0..] {
+                "start" => {
+                    let transport = match args.get(2).map(|arg| &arg[0..]) {
+                        Some("gatt") => ThroughputTransport::Gatt,
+                        Some("coc") => ThroughputTransport::LeCoc,
+                        _ => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+                    let direction = match args.get(3).map(|arg| &arg[0..]) {
+                        Some("tx") => ThroughputDirection::Transmit,
+                        Some("rx") => ThroughputDirection::Receive,
+                        _ => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+                    // PSMs are 16 bits like handles, and written the same ways.
+                    let target = args.get(4).and_then(|arg| parse_handle(arg));
+                    let parse = |index: usize, default: u32| match args.get(index) {
+                        Some(arg) => arg.parse::<u32>().ok(),
+                        None => Some(default),
+                    };
+                    let (target, payload_size, secs) = match (
+                        target,
+                        parse(5, DEFAULT_THROUGHPUT_PAYLOAD_SIZE),
+                        parse(6, DEFAULT_THROUGHPUT_TEST_SECS),
+                    ) {
+                        (Some(target), Some(payload_size), Some(secs)) => {
+                            (target, payload_size, secs)
+                        }
+                        _ => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+
+                    let started = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .throughput_test_dbus
+                        .as_mut()
+                        .unwrap()
+                        .start_throughput_test(
+                            device,
+                            transport,
+                            direction,
+                            target,
+                            payload_size,
+                            secs.saturating_mul(1000),
+                        );
+                    if started {
+                        print_info!("Throughput test with {} started for {} s", &args[1], secs);
+                    } else {
+                        print_error!(
+                            "Can't start a throughput test with {}: one is already running, or \
+                             the payload size or duration is out of range",
+                            &args[1]
+                        );
+                    }
+                }
+                "stop" => {
+                    let mut context = self.context.lock().unwrap();
+                    if !context.throughput_test_dbus.as_mut().unwrap().stop_throughput_test(device)
+                    {
+                        print_error!("No throughput test with {} is running", &args[1]);
+                    }
+                }
+                _ => print_error!("Invalid argument '{}'", args[0]),
+            }
+        });
+    }
+
+    fn cmd_distance(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "distance <start <address> [<interval ms> [<method>]]|stop <address>|methods>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "methods" => {
+                let methods = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .distance_measurement_dbus
+                    .as_ref()
+                    .unwrap()
+                    .get_supported_methods();
+                print_info!("Supported methods: {:?}", methods);
+            }
+            "start" | "stop" => {
+                let address = match args.get(1).and_then(|arg| self.parse_address(arg)) {
+                    Some(address) => address,
+                    None => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+                let device =
+                    BluetoothDevice { address, name: String::from(""), ..Default::default() };
+
+                let mut context = self.context.lock().unwrap();
+                let distance = context.distance_measurement_dbus.as_mut().unwrap();
+                if args[0] == "stop" {
+                    if !distance.stop_distance_measurement(device) {
+                        print_error!("The distance to {} isn't being measured", &args[1]);
+                    }
+                    return;
+                }
+
+                let interval_ms = match args.get(2) {
+                    Some(arg) => arg.parse::<u32>().ok(),
+                    None => Some(DEFAULT_DISTANCE_INTERVAL_MS),
+                };
+                let method = match args.get(3).map(|arg| &arg[0..]) {
+                    None | Some("auto") => Some(DistanceMeasurementMethod::Auto),
+                    Some("rssi") => Some(DistanceMeasurementMethod::Rssi),
+                    Some("cs") => Some(DistanceMeasurementMethod::ChannelSounding),
+                    Some(_) => None,
+                };
+                let (interval_ms, method) = match (interval_ms, method) {
+                    (Some(interval_ms), Some(method)) => (interval_ms, method),
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+                if !distance.start_distance_measurement(device, method, interval_ms) {
+                    print_error!(
+                        "Can't measure the distance to {}: already measuring it, method not \
+                         supported, or interval out of 200 to 60000 ms",
+                        &args[1]
+                    );
+                }
+            }
+            _ => print_error!("Invalid argument '{}'", args[0]),
+        });
+    }
+
+    fn cmd_coex(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "coex <wifi <channel>[,<channel>...]|none|latency <on|off>|state>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "wifi" => {
+                let channels: Option<Vec<WifiChannel>> = match args.get(1).map(|arg| &arg[0..]) {
+                    Some("none") => Some(vec![]),
+                    Some(arg) => arg
+                        .split(',')
+                        .map(|channel| {
+                            let (frequency, bandwidth) = channel.split_once('/')?;
+                            Some(WifiChannel {
+                                frequency_mhz: frequency.parse().ok()?,
+                                bandwidth_mhz: bandwidth.parse().ok()?,
+                            })
+                        })
+                        .collect(),
+                    None => None,
+                };
+                let channels = match channels {
+                    Some(channels) => channels,
+                    None => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                if !self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .coex_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_wifi_channels(channels)
+                {
+                    print_error!("Wi-Fi channels are 20, 40, 80, 160 or 320 MHz wide");
+                }
+            }
+            "latency" => {
+                let active = match args.get(1).map(|arg| &arg[0..]) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+                self.context
+                    .lock()
+                    .unwrap()
+                    .coex_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_latency_sensitive(active);
+            }
+            "state" => {
+                let state =
+                    self.context.lock().unwrap().coex_dbus.as_ref().unwrap().get_coex_state();
+                let channels: Vec<String> = state
+                    .wifi_channels
+                    .iter()
+                    .map(|channel| format!("{}/{}", channel.frequency_mhz, channel.bandwidth_mhz))
+                    .collect();
+                print_info!("Wi-Fi channels: {}", channels.join(","));
+                print_info!("Latency-sensitive: {}", state.latency_sensitive);
+                print_info!("BR/EDR channel map: {}", format_hex_bytes(&state.bredr_channel_map));
+                print_info!("LE channel map: {}", format_hex_bytes(&state.le_channel_map));
+                print_info!("Classification applied: {}", state.channel_classification_applied);
+                print_info!("Scan limit: {}%", state.scan_limit_percent);
+            }
+            _ => print_error!("Invalid argument '{}'", args[0]),
+        });
+    }
+
+    fn cmd_pan(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "pan <connect <address>|disconnect <address>|nap <on|off>|interface>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "connect" | "disconnect" => {
+                let address = match args.get(1) {
+                    Some(address) => match self.parse_address(address) {
+                        Some(address) => address,
+                        None => return,
+                    },
+                    None => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+                let device = BluetoothDevice {
+                    address,
+                    name: String::from("Classic Device"),
+                    ..Default::default()
+                };
+
+                let mut context = self.context.lock().unwrap();
+                let pan = context.pan_dbus.as_mut().unwrap();
+                let success = if args[0] == "connect" {
+                    pan.connect(device.clone())
+                } else {
+                    pan.disconnect(device.clone())
+                };
+
+                if !success {
+                    print_error!("Failed to {} PAN to {}", args[0], device.address);
+                }
+            }
+            "nap" => {
+                let enabled = match args.get(1).map(|arg| &arg[0..]) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                if !self.context.lock().unwrap().pan_dbus.as_mut().unwrap().set_nap_enabled(enabled)
+                {
+                    print_error!("Failed to turn {} the network access point", args[1]);
+                }
+            }
+            "interface" => {
+                let name =
+                    self.context.lock().unwrap().pan_dbus.as_ref().unwrap().get_interface_name();
+                if name.is_empty() {
+                    print_info!("PAN is not enabled yet");
+                } else {
+                    print_info!("PAN traffic goes through {}", name);
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_le_address(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "le-address [static <address>|privacy <on [rpa timeout secs]|off>]";
+        let mut context = self.context.lock().unwrap();
+        let adapter = context.adapter_dbus.as_mut().unwrap();
+        let status = match args.get(0).map(|arg| &arg[0..]) {
+            None => {
+                let features = adapter.get_le_privacy_features();
+                print_info!("Own address type: {:?}", adapter.get_le_own_address_type());
+                print_info!("Privacy enabled: {}", features.privacy_enabled);
+                print_info!(
+                    "LL privacy: {}, RPA offload: {}, resolving list size: {}",
+                    features.ll_privacy_supported,
+                    features.rpa_offload_supported,
+                    features.resolving_list_size
+                );
+                return;
+            }
+            Some("static") => match args.get(1) {
+                Some(address) => adapter.set_le_static_address(address.clone()),
+                None => {
+                    print_error!("Usage: {}", usage);
+                    return;
+                }
+            },
+            Some("privacy") => {
+                let timeout = match args.get(2).map(|arg| arg.parse::<u32>()) {
+                    Some(Ok(timeout)) => timeout,
+                    Some(Err(_)) => {
+                        print_error!("Invalid RPA timeout '{}'", args[2]);
+                        return;
+                    }
+                    None => 0,
+                };
+                match args.get(1).map(|arg| &arg[0..]) {
+                    Some("on") => adapter.set_le_privacy(true, timeout),
+                    Some("off") => adapter.set_le_privacy(false, 0),
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                }
+            }
+            Some(arg) => {
+                print_error!("Invalid argument '{}'", arg);
+                return;
+            }
+        };
+
+        report_status("set the LE address", status);
+        if status == BtStatus::Success {
+            print_info!("Takes effect the next time the adapter is enabled");
+        }
+    }
+
+    fn cmd_link_policy(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "link-policy <role <address> [central|peripheral]|sniff <address> <on|off>|\
+                     ssr <address> <max latency> <min remote timeout> <min local timeout>>";
+        let address = match args.get(1) {
+            Some(address) => match self.parse_address(address) {
+                Some(address) => address,
+                None => return,
+            },
+            None => {
+                print_error!("Usage: {}", usage);
+                return;
+            }
+        };
+        let device = BluetoothDevice { address, ..Default::default() };
+
+        let mut context = self.context.lock().unwrap();
+        let link_policy = context.link_policy_dbus.as_mut().unwrap();
+        match &args[0][0..] {
+            "role" => {
+                let role = match args.get(2).map(|arg| &arg[0..]) {
+                    None => {
+                        print_info!("Role: {:?}", link_policy.get_link_role(device));
+                        return;
+                    }
+                    Some("central") => BtAclRole::Central,
+                    Some("peripheral") => BtAclRole::Peripheral,
+                    Some(arg) => {
+                        print_error!("Invalid role '{}'", arg);
+                        return;
+                    }
+                };
+                report_status("switch roles", link_policy.request_role_switch(device, role));
+            }
+            "sniff" => {
+                let enabled = match args.get(2).map(|arg| &arg[0..]) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+                report_status(
+                    "change the sniff mode policy",
+                    link_policy.set_sniff_mode_enabled(device, enabled),
+                );
+            }
+            "ssr" => {
+                let values: Vec<Option<u16>> =
+                    args.iter().skip(2).map(|arg| arg.parse::<u16>().ok()).collect();
+                let status = match values[..] {
+                    [Some(max_latency), Some(min_remote_timeout), Some(min_local_timeout)] => {
+                        link_policy.set_sniff_subrating(
+                            device,
+                            max_latency,
+                            min_remote_timeout,
+                            min_local_timeout,
+                        )
+                    }
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+                report_status("set sniff subrating", status);
+            }
+            arg => print_error!("Invalid argument '{}'", arg),
+        }
+    }
+
+    fn cmd_scan_params(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        // Without an interval and window, the current parameters are shown.
+        let values = match (args.get(1), args.get(2)) {
+            (Some(interval), Some(window)) => {
+                match (interval.parse::<u16>(), window.parse::<u16>()) {
+                    (Ok(interval), Ok(window)) => Some((interval, window)),
+                    _ => {
+                        print_error!("Invalid interval or window");
+                        return;
+                    }
+                }
+            }
+            _ => None,
+        };
+        let scan_type = args.get(3).map(|arg| arg.as_str());
+
+        let mut context = self.context.lock().unwrap();
+        let adapter_dbus = context.adapter_dbus.as_mut().unwrap();
+        enforce_arg_len(
+            args,
+            1,
+            "scan-params <inquiry|page|le> [<interval> <window> [type]]",
+            || match &args[0][0..] {
+                "inquiry" | "page" => {
+                    let inquiry = args[0] == "inquiry";
+                    let mut params = if inquiry {
+                        adapter_dbus.get_inquiry_scan_params()
+                    } else {
+                        adapter_dbus.get_page_scan_params()
+                    };
+                    let (interval, window) = match values {
+                        Some(values) => values,
+                        None => {
+                            print_info!(
+                                "{} scan: interval {}, window {}, {:?}",
+                                args[0],
+                                params.interval,
+                                params.window,
+                                params.scan_type
+                            );
+                            return;
+                        }
+                    };
+
+                    params.interval = interval;
+                    params.window = window;
+                    params.scan_type = match scan_type {
+                        None => params.scan_type,
+                        Some("standard") => ClassicScanType::Standard,
+                        Some("interlaced") => ClassicScanType::Interlaced,
+                        Some(other) => {
+                            print_error!("Invalid scan type '{}'", other);
+                            return;
+                        }
+                    };
+                    let status = if inquiry {
+                        adapter_dbus.set_inquiry_scan_params(params)
+                    } else {
+                        adapter_dbus.set_page_scan_params(params)
+                    };
+                    print_info!("Set {} scan parameters: {:?}", args[0], status);
+                }
+                "le" => {
+                    let mut params = adapter_dbus.get_le_scan_params();
+                    let (interval, window) = match values {
+                        Some(values) => values,
+                        None => {
+                            print_info!(
+                                "le scan: interval {}, window {}, {:?}",
+                                params.interval,
+                                params.window,
+                                params.scan_type
+                            );
+                            return;
+                        }
+                    };
+
+                    params.interval = interval;
+                    params.window = window;
+                    params.scan_type = match scan_type {
+                        None => params.scan_type,
+                        Some("active") => ScanType::Active,
+                        Some("passive") => ScanType::Passive,
+                        Some(other) => {
+                            print_error!("Invalid scan type '{}'", other);
+                            return;
+                        }
+                    };
+                    let status = adapter_dbus.set_le_scan_params(params);
+                    print_info!("Set le scan parameters: {:?}", status);
+                }
+                _ => print_error!("Invalid argument '{}'", args[0]),
+            },
+        );
+    }
+
+    fn cmd_security(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "Usage: security [sc-only <on|off> | bonded-only <on|off> | \
+                     min-key-size <7-16> | key-size <address> [bredr|le]]";
+        // Resolved before taking the context, which aliases are looked up in.
+        let key_size_address = match (args.get(0).map(|arg| &arg[0..]), args.get(1)) {
+            (Some("key-size"), Some(address)) => match self.parse_address(address) {
+                Some(address) => Some(address),
+                None => return,
+            },
+            _ => None,
+        };
+        let mut context = self.context.lock().unwrap();
+        let adapter = context.adapter_dbus.as_mut().unwrap();
+        match (args.get(0).map(|arg| &arg[0..]), args.get(1).map(|arg| &arg[0..])) {
+            (None, _) => {
+                print_info!("Secure Connections Only: {}", adapter.get_secure_connections_only());
+                print_info!("Bonded devices only: {}", adapter.get_bonded_only_connections());
+                print_info!(
+                    "Minimum encryption key size: {} octets",
+                    adapter.get_min_encryption_key_size()
+                );
+            }
+            (Some("sc-only"), Some("on")) => report_status(
+                "set the Secure Connections Only mode",
+                adapter.set_secure_connections_only(true),
+            ),
+            (Some("sc-only"), Some("off")) => report_status(
+                "set the Secure Connections Only mode",
+                adapter.set_secure_connections_only(false),
+            ),
+            (Some("bonded-only"), Some("on")) => report_status(
+                "only accept connections from bonded devices",
+                adapter.set_bonded_only_connections(true),
+            ),
+            (Some("bonded-only"), Some("off")) => report_status(
+                "accept connections from all devices",
+                adapter.set_bonded_only_connections(false),
+            ),
+            (Some("min-key-size"), Some(size)) => match size.parse::<u8>() {
+                Ok(key_size) => report_status(
+                    "set the minimum encryption key size",
+                    adapter.set_min_encryption_key_size(key_size),
+                ),
+                Err(_) => print_error!("Invalid key size '{}'", size),
+            },
+            (Some("key-size"), Some(_)) => {
+                let address = key_size_address.unwrap();
+                let transport = match args.get(2).map(|arg| &arg[0..]) {
+                    None | Some("bredr") => BtTransport::Bredr,
+                    Some("le") => BtTransport::Le,
+                    Some(other) => {
+                        print_error!("Invalid transport '{}'", other);
+                        return;
+                    }
+                };
+                let device =
+                    BluetoothDevice { address, name: String::from(""), ..Default::default() };
+                match adapter.get_encryption_key_size(device, transport.clone()) {
+                    0 => print_info!("The {:?} link isn't encrypted", transport),
+                    key_size => print_info!("Encryption key size: {} octets", key_size),
+                }
+            }
+            _ => print_error!("{}", usage),
+        }
+    }
+
+    fn cmd_pbap(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "pbap pull <address> <contacts|incoming|outgoing|missed|combined> \
+                     [offset] [count]";
+        enforce_arg_len(args, 3, usage, || match &args[0][0..] {
+            "pull" => {
+                let address = match self.parse_address(&args[1]) {
+                    Some(address) => address,
+                    None => return,
+                };
+                let device = BluetoothDevice {
+                    address,
+                    name: String::from("Classic Device"),
+                    ..Default::default()
+                };
+
+                let phonebook = match &args[2][0..] {
+                    "contacts" => PbapPhonebook::Contacts,
+                    "incoming" => PbapPhonebook::IncomingCalls,
+                    "outgoing" => PbapPhonebook::OutgoingCalls,
+                    "missed" => PbapPhonebook::MissedCalls,
+                    "combined" => PbapPhonebook::CombinedCalls,
+                    _ => {
+                        print_error!("Invalid phone book '{}'", args[2]);
+                        return;
+                    }
+                };
+
+                // Without a count, pull the whole phone book.
+                let offset = args.get(3).map_or(Ok(0), |arg| arg.parse::<u16>());
+                let count = args.get(4).map_or(Ok(u16::MAX), |arg| arg.parse::<u16>());
+                let (offset, count) = match (offset, count) {
+                    (Ok(offset), Ok(count)) => (offset, count),
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                let id = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .pbap_dbus
+                    .as_mut()
+                    .unwrap()
+                    .pull_phonebook(device, phonebook, offset, count);
+
+                if id < 0 {
+                    print_error!("Failed to pull {:?} from {}", phonebook, &args[1]);
+                } else {
+                    print_info!("Pulling {:?} from {} as request {}", phonebook, &args[1], id);
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_map(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "map <connect <address>|disconnect <address>|\
+                     list <address> <folder> [offset] [count]|read <address> <handle>>";
+        enforce_arg_len(args, 2, usage, || {
+            let address = match self.parse_address(&args[1]) {
+                Some(address) => address,
+                None => return,
+            };
+            let device = BluetoothDevice {
+                address,
+                name: String::from("Classic Device"),
+                ..Default::default()
+            };
+
+            match &args[0][0..] {
+                "connect" => {
+                    if !self.context.lock().unwrap().map_dbus.as_mut().unwrap().connect(device) {
+                        print_error!("Failed to connect message access to {}", &args[1]);
+                    }
+                }
+                "disconnect" => {
+                    if !self.context.lock().unwrap().map_dbus.as_mut().unwrap().disconnect(device) {
+                        print_error!("No message access session to {}", &args[1]);
+                    }
+                }
+                "list" => {
+                    let folder = match args.get(2) {
+                        Some(folder) => folder.clone(),
+                        None => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+
+                    let offset = args.get(3).map_or(Ok(0), |arg| arg.parse::<u16>());
+                    let count = args.get(4).map_or(Ok(10), |arg| arg.parse::<u16>());
+                    let (offset, count) = match (offset, count) {
+                        (Ok(offset), Ok(count)) => (offset, count),
+                        _ => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+
+                    let id = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .map_dbus
+                        .as_mut()
+                        .unwrap()
+                        .get_messages_listing(device, folder, offset, count);
+
+                    if id < 0 {
+                        print_error!("Failed to list messages of {}", &args[1]);
+                    } else {
+                        print_info!("Listing messages of {} as request {}", &args[1], id);
+                    }
+                }
+                "read" => {
+                    let handle = match args.get(2) {
+                        Some(handle) => handle.clone(),
+                        None => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    };
+
+                    let id = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .map_dbus
+                        .as_mut()
+                        .unwrap()
+                        .get_message(device, handle);
+
+                    if id < 0 {
+                        print_error!("Failed to read message from {}", &args[1]);
+                    } else {
+                        print_info!("Reading message from {} as request {}", &args[1], id);
+                    }
+                }
+                _ => {
+                    print_error!("Invalid argument '{}'", args[0]);
+                }
+            }
+        });
+    }
+
+    fn cmd_adv_monitor(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "adv-monitor add <ad type> <offset> <hex content> \
+                     [<rssi high> <rssi low> <found ms> <lost ms>] or adv-monitor remove <id>";
+        enforce_arg_len(args, 2, usage, || match &args[0][0..] {
+            "add" => {
+                let pattern = match (
+                    args.get(1).and_then(|arg| arg.parse::<u8>().ok()),
+                    args.get(2).and_then(|arg| arg.parse::<u8>().ok()),
+                    args.get(3).and_then(|arg| parse_hex_bytes(arg)),
+                ) {
+                    (Some(ad_type), Some(offset), Some(content)) => {
+                        AdvMonitorPattern { ad_type, offset, content }
+                    }
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                // Without thresholds any advertisement reports the device, and losing it takes
+                // the longest silence a controller can watch for.
+                let monitor = if args.len() > 4 {
+                    let numbers: Vec<Option<i64>> =
+                        args[4..].iter().map(|arg| arg.parse::<i64>().ok()).collect();
+                    match &numbers[..] {
+                        [Some(high), Some(low), Some(found), Some(lost)] => AdvMonitor {
+                            patterns: vec![pattern],
+                            rssi_high_threshold: *high as i32,
+                            found_timeout: *found as u32,
+                            rssi_low_threshold: *low as i32,
+                            lost_timeout: *lost as u32,
+                        },
+                        _ => {
+                            print_error!("Usage: {}", usage);
+                            return;
+                        }
+                    }
+                } else {
+                    AdvMonitor {
+                        patterns: vec![pattern],
+                        rssi_high_threshold: -127,
+                        found_timeout: 0,
+                        rssi_low_threshold: -127,
+                        lost_timeout: u16::MAX.into(),
+                    }
+                };
+
+                let mut context = self.context.lock().unwrap();
+                context.adv_monitor_callback_count += 1;
+                let objpath = format!(
+                    "/org/chromium/bluetooth/client/{}/adv_monitor_callback{}",
+                    context.default_adapter, context.adv_monitor_callback_count
+                );
+                let callback = BtAdvMonitorCallback::new(
+                    objpath,
+                    self.context.clone(),
+                    context.dbus_connection.clone(),
+                    context.dbus_crossroads.clone(),
+                    context.disconnect_watcher.clone(),
+                );
+
+                let adv_monitor = context.adv_monitor_dbus.as_mut().unwrap();
+                let id = adv_monitor.add_monitor(monitor, Box::new(callback));
+                if id == 0 {
+                    print_error!("Invalid monitor");
+                } else {
+                    print_info!(
+                        "Added monitor {} (offloaded: {})",
+                        id,
+                        adv_monitor.is_monitor_offloaded(id)
+                    );
+                }
+            }
+            "remove" => {
+                let id = match args[1].parse::<u32>() {
+                    Ok(id) => id,
+                    Err(_) => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                if !self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .adv_monitor_dbus
+                    .as_mut()
+                    .unwrap()
+                    .remove_monitor(id)
+                {
+                    print_error!("No monitor {}", id);
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_fast_pair(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        let usage = "fast-pair <provision <model id> <anti-spoofing key>|status|clear-keys>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "provision" => {
+                // Both are printed in hex by the Fast Pair console, the key after base64 decoding.
+                let model_id = args
+                    .get(1)
+                    .and_then(|arg| u32::from_str_radix(arg.trim_start_matches("0x"), 16).ok());
+                let key = args.get(2).and_then(|arg| parse_hex_bytes(arg));
+                let (model_id, key) = match (model_id, key) {
+                    (Some(model_id), Some(key)) => (model_id, key),
+                    _ => {
+                        print_error!("Usage: {}", usage);
+                        return;
+                    }
+                };
+
+                if !self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .fast_pair_dbus
+                    .as_mut()
+                    .unwrap()
+                    .provision(model_id, key)
+                {
+                    print_error!("Invalid model ID or anti-spoofing key");
+                }
+            }
+            "status" => {
+                let context = self.context.lock().unwrap();
+                let fast_pair = context.fast_pair_dbus.as_ref().unwrap();
+                print_info!("Provisioned: {}", fast_pair.is_provisioned());
+                print_info!("Account keys: {}", fast_pair.get_account_key_count());
+            }
+            "clear-keys" => {
+                self.context.lock().unwrap().fast_pair_dbus.as_mut().unwrap().clear_account_keys();
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_floss(&mut self, args: &Vec<String>) {
+        let usage = "floss <enable|disable|show|version|reload-config|\
+                     virtual-controller [<ip:port>|off]>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
+            "enable" => {
+                self.context.lock().unwrap().manager_dbus.set_floss_enabled(true);
+            }
+            "disable" => {
+                self.context.lock().unwrap().manager_dbus.set_floss_enabled(false);
+            }
+            "show" => {
+                print_info!(
+                    "Floss enabled: {}",
+                    self.context.lock().unwrap().manager_dbus.get_floss_enabled()
+                );
+            }
+            "version" => {
+                let api_version = {
+                    let mut context = self.context.lock().unwrap();
+                    context.update_api_version();
+                    context.api_version
+                };
+                match api_version {
+                    Some(version) => print_info!("Daemon API version: {}", version),
+                    None => print_error!("The manager can't be reached"),
+                }
+                print_info!("Client API version: {}", API_VERSION);
+            }
+            "reload-config" => {
+                let mut context = self.context.lock().unwrap();
+                let hci_interface = context.selected_adapter;
+                if context.manager_dbus.reload_config(hci_interface) {
+                    print_info!("Reloading the runtime config of hci{}", hci_interface);
+                } else {
+                    print_error!("hci{} isn't on", hci_interface);
+                }
+            }
+            "virtual-controller" => {
+                let mut context = self.context.lock().unwrap();
+                let server = match args.get(1).map(String::as_str) {
+                    None => {
+                        let server = context.manager_dbus.get_virtual_controller();
+                        match &server[..] {
+                            "" => print_info!("No virtual controller"),
+                            _ => print_info!("Virtual controller: {}", server),
+                        }
+                        return;
+                    }
+                    Some("off") => String::new(),
+                    Some(server) => server.to_string(),
+                };
+
+                if !context.manager_dbus.set_virtual_controller(server.clone()) {
+                    print_error!("Invalid virtual controller server '{}'", server);
+                }
+            }
+            _ => {
+                print_error!("Invalid argument '{}'", args[0]);
+            }
+        });
+    }
+
+    fn cmd_gatt(&mut self, args: &Vec<String>) {
+        if !self.context.lock().unwrap().adapter_ready {
+            self.adapter_not_ready();
+            return;
+        }
+
+        enforce_arg_len(args, 1, "gatt <commands>", || match &args[0][0..] {
+            "register-client" => {
+                let request = requests::register_gatt_client(
+                    self.context.clone(),
+                    GATT_CLIENT_APP_UUID,
+                    GATT_TIMEOUT,
+                );
+                self.wait_for_request("register GATT client", request);
+            }
+            "client-connect" => {
+                if args.len() < 2 {
+                    print_error!(
+                        "usage: gatt client-connect <addr> [direct] [opportunistic] \
+                         [transport=<auto|bredr|le>]"
+                    );
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                // Without `direct` the device is put on the background connection list and gets
+                // reconnected whenever it shows up.
+                let mut is_direct = false;
+                let mut opportunistic = false;
+                let mut transport = BtTransport::Le;
+                for option in &args[2..] {
+                    match &option[..] {
+                        "direct" => is_direct = true,
+                        "opportunistic" => opportunistic = true,
+                        "transport=auto" => transport = BtTransport::Auto,
+                        "transport=bredr" => transport = BtTransport::Bredr,
+                        "transport=le" => transport = BtTransport::Le,
+                        _ => {
+                            print_error!("Invalid option '{}'", option);
+                            return;
+                        }
+                    }
+                }
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+
+                // Background connections complete whenever the device shows up, so only direct
+                // connections are waited on.
+                if is_direct {
+                    let request = requests::gatt_connect(
+                        self.context.clone(),
+                        client_id.unwrap(),
+                        addr,
+                        transport,
+                        opportunistic,
+                        GATT_TIMEOUT,
+                    );
+                    self.wait_for_request("connect", request);
+                    return;
+                }
+
+                let status =
+                    self.context.lock().unwrap().gatt_dbus.as_ref().unwrap().client_connect(
+                        client_id.unwrap(),
+                        addr,
+                        is_direct,
+                        transport,
+                        opportunistic,
+                        1,
+                    );
+                report_status("connect", status);
+            }
+            "client-disconnect" => {
+                if args.len() < 2 {
+                    print_error!("usage: gatt client-disconnect <addr>");
+                    return;
+                }
+
+                let client_id = match self.context.lock().unwrap().gatt_client_id() {
+                    Some(client_id) => client_id,
+                    None => {
+                        print_error!("GATT client is not yet registered.");
+                        return;
+                    }
+                };
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let request =
+                    requests::gatt_disconnect(self.context.clone(), client_id, addr, GATT_TIMEOUT);
+                self.wait_for_request("disconnect", request);
+            }
+            "mtu" => {
+                if args.len() < 2 {
+                    print_error!("usage: gatt mtu <addr> [size]");
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                if args.len() < 3 {
+                    let mtu = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .gatt_dbus
+                        .as_ref()
+                        .unwrap()
+                        .get_mtu(client_id.unwrap(), addr);
+                    if mtu == 0 {
+                        print_error!("Not connected to {}", addr);
+                    } else {
+                        print_info!("MTU of {}: {}", addr, mtu);
+                    }
+                    return;
+                }
+
+                let mtu = match args[2].parse::<i32>() {
+                    Ok(mtu) => mtu,
+                    Err(_) => {
+                        print_error!("Invalid MTU '{}'", args[2]);
+                        return;
+                    }
+                };
+
+                let status =
+                    self.context.lock().unwrap().gatt_dbus.as_ref().unwrap().configure_mtu(
+                        client_id.unwrap(),
+                        addr,
+                        mtu,
+                    );
+                report_status("configure the MTU", status);
+            }
+            "batch-notifications" => {
+                if args.len() < 2 {
+                    print_error!(
+                        "usage: gatt batch-notifications <interval ms, 0 to stop batching>"
+                    );
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let interval_ms = match args[1].parse::<u32>() {
+                    Ok(interval_ms) => interval_ms,
+                    Err(_) => {
+                        print_error!("Invalid interval '{}'", args[1]);
+                        return;
+                    }
+                };
+
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_notification_batching(client_id.unwrap(), interval_ms);
+                report_status("set notification batching", status);
+            }
+            "auto-elevate-security" => {
+                let enabled = match args.get(1).map(|arg| &arg[0..]) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        print_error!("usage: gatt auto-elevate-security <on|off>");
+                        return;
+                    }
+                };
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_auto_elevate_security(client_id.unwrap(), enabled);
+                report_status("set security auto-elevation", status);
+            }
+            "request-timeout" => {
+                let timeout_ms = args.get(1).and_then(|arg| arg.parse::<u32>().ok());
+                let disconnect = match args.get(2).map(|arg| &arg[0..]) {
+                    None => Some(false),
+                    Some("disconnect") => Some(true),
+                    Some(_) => None,
+                };
+                if timeout_ms.is_none() || disconnect.is_none() {
+                    print_error!(
+                        "usage: gatt request-timeout <timeout ms, 0 to wait> [disconnect]"
+                    );
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let status =
+                    self.context.lock().unwrap().gatt_dbus.as_mut().unwrap().set_request_timeout(
+                        client_id.unwrap(),
+                        timeout_ms.unwrap(),
+                        disconnect.unwrap(),
+                    );
+                report_status("set request timeout", status);
+            }
+            "priority" => {
+                if args.len() < 3 {
+                    print_error!("usage: gatt priority <addr> <high|balanced|low-power>");
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let priority = match &args[2][..] {
+                    "high" => ConnectionPriority::High,
+                    "balanced" => ConnectionPriority::Balanced,
+                    "low-power" => ConnectionPriority::LowPower,
+                    _ => {
+                        print_error!("Invalid priority '{}'", args[2]);
+                        return;
+                    }
+                };
+
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .request_connection_priority(client_id.unwrap(), addr, priority);
+                report_status("request the connection priority", status);
+            }
+            "server-authorization" => {
+                let enabled = match args.get(1).map(|arg| &arg[0..]) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        print_error!("usage: gatt server-authorization <on|off>");
+                        return;
+                    }
+                };
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_server_authorization(client_id.unwrap(), enabled);
+                report_status("set server authorization", status);
+            }
+            "authorize" => {
+                if args.len() < 4 {
+                    print_error!("usage: gatt authorize <addr> <handle> <allow|deny>");
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let handle = match parse_handle(&args[2]) {
+                    Some(handle) => handle,
+                    None => {
+                        print_error!("Invalid handle '{}'", args[2]);
+                        return;
+                    }
+                };
+                let allowed = match &args[3][..] {
+                    "allow" => true,
+                    "deny" => false,
+                    _ => {
+                        print_error!("Invalid answer '{}'", args[3]);
+                        return;
+                    }
+                };
+
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .send_authorization_response(client_id.unwrap(), addr, handle, allowed);
+                report_status("answer the authorization request", status);
+            }
+            "write" => {
+                const USAGE: &str = "usage: gatt write <addr> <handle|uuid> <hex|utf8:text> \
+                                     [--type cmd|req|prepared]";
+                if args.len() < 4 {
+                    print_error!("{}", USAGE);
+                    return;
+                }
+
+                let client_id = match self.context.lock().unwrap().gatt_client_id() {
+                    Some(client_id) => client_id,
+                    None => {
+                        print_error!("GATT client is not yet registered.");
+                        return;
+                    }
+                };
+
+                // Text values may contain spaces, so everything but the options is the value.
+                let mut write_type = GattWriteType::Write;
+                let mut value = vec![];
+                let mut options = args[3..].iter();
+                while let Some(arg) = options.next() {
+                    if arg != "--type" {
+                        value.push(&arg[..]);
+                        continue;
+                    }
+                    write_type = match options.next().map(|arg| &arg[..]) {
+                        Some("cmd") => GattWriteType::WriteNoRsp,
+                        Some("req") => GattWriteType::Write,
+                        Some("prepared") => GattWriteType::WritePrepare,
+                        _ => {
+                            print_error!("{}", USAGE);
+                            return;
+                        }
+                    };
+                }
+                if value.is_empty() {
+                    print_error!("{}", USAGE);
+                    return;
+                }
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let characteristic = match (parse_handle(&args[2]), parse_uuid(&args[2])) {
+                    (Some(handle), _) => GattCharacteristic::Handle(handle),
+                    (None, Some(uuid)) => GattCharacteristic::Uuid(uuid),
+                    (None, None) => {
+                        print_error!("Invalid handle or UUID '{}'", args[2]);
+                        return;
+                    }
+                };
+                let value = value.join(" ");
+                let value = match parse_write_value(&value) {
+                    Some(value) => value,
+                    None => {
+                        print_error!("Invalid value '{}'", value);
+                        return;
+                    }
+                };
+
+                let len = value.len();
+                let request = requests::gatt_write_characteristic(
+                    self.context.clone(),
+                    client_id,
                     addr,
-                    false,
-                    2,
-                    false,
-                    1,
+                    characteristic,
+                    write_type,
+                    value,
+                    GATT_TIMEOUT,
                 );
+                self.wait_for_request("write the characteristic", async move {
+                    let handle = request.await?;
+                    print_info!("Wrote {} bytes to handle {} of {}", len, handle, addr);
+                    Ok::<(), RequestError>(())
+                });
+            }
+            "subscribe" | "unsubscribe" => {
+                let subscribe = args[0] == "subscribe";
+                let raw = args.get(3).map(|arg| &arg[..]) == Some("--raw");
+                if args.len() < 3 || args.len() > 4 || (args.len() == 4 && !raw) {
+                    if subscribe {
+                        print_error!("usage: gatt subscribe <addr> <uuid> [--raw]");
+                    } else {
+                        print_error!("usage: gatt unsubscribe <addr> <uuid>");
+                    }
+                    return;
+                }
+
+                let client_id = match self.context.lock().unwrap().gatt_client_id() {
+                    Some(client_id) => client_id,
+                    None => {
+                        print_error!("GATT client is not yet registered.");
+                        return;
+                    }
+                };
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let uuid = match parse_uuid(&args[2]) {
+                    Some(uuid) => uuid,
+                    None => {
+                        print_error!("Invalid UUID '{}'", args[2]);
+                        return;
+                    }
+                };
+
+                let context = self.context.clone();
+                if subscribe {
+                    let request = requests::gatt_subscribe(
+                        context.clone(),
+                        client_id,
+                        addr,
+                        uuid,
+                        GATT_TIMEOUT,
+                    );
+                    self.wait_for_request("subscribe", async move {
+                        let handle = request.await?;
+                        if !raw {
+                            context
+                                .lock()
+                                .unwrap()
+                                .decoded_characteristics
+                                .insert((addr, handle), uuid);
+                        }
+                        print_info!("Subscribed to handle {} of {}", handle, addr);
+                        Ok::<(), RequestError>(())
+                    });
+                } else {
+                    let request = requests::gatt_unsubscribe(
+                        context.clone(),
+                        client_id,
+                        addr,
+                        uuid,
+                        GATT_TIMEOUT,
+                    );
+                    self.wait_for_request("unsubscribe", async move {
+                        let handle = request.await?;
+                        context.lock().unwrap().decoded_characteristics.remove(&(addr, handle));
+                        print_info!("Unsubscribed from handle {} of {}", handle, addr);
+                        Ok::<(), RequestError>(())
+                    });
+                }
+            }
+            "read-by-uuid" => {
+                if args.len() < 3 {
+                    print_error!("usage: gatt read-by-uuid <addr> <uuid>");
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let uuid = match parse_uuid(&args[2]) {
+                    Some(uuid) => uuid,
+                    None => {
+                        print_error!("Invalid UUID '{}'", args[2]);
+                        return;
+                    }
+                };
+                let status = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .read_using_characteristic_uuid(client_id.unwrap(), addr, uuid, 1, 0xffff, 0);
+                report_status("read the characteristic", status);
+            }
+            "write-by-uuid" => {
+                if args.len() < 4 {
+                    print_error!("usage: gatt write-by-uuid <addr> <uuid> <hex value>");
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id();
+                if client_id.is_none() {
+                    print_error!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let value = match parse_hex_bytes(&args[3]) {
+                    Some(value) => value,
+                    None => {
+                        print_error!("Invalid value '{}'", args[3]);
+                        return;
+                    }
+                };
+
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let uuid = match parse_uuid(&args[2]) {
+                    Some(uuid) => uuid,
+                    None => {
+                        print_error!("Invalid UUID '{}'", args[2]);
+                        return;
+                    }
+                };
+                self.context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .write_using_characteristic_uuid(
+                        client_id.unwrap(),
+                        addr,
+                        uuid,
+                        1,
+                        0xffff,
+                        GattWriteType::Write,
+                        0,
+                        value,
+                    );
             }
             "client-read-phy" => {
                 if args.len() < 2 {
-                    println!("usage: gatt client-read-phy <addr>");
+                    print_error!("usage: gatt client-read-phy <addr>");
                     return;
                 }
 
-                let client_id = self.context.lock().unwrap().gatt_client_id;
+                let client_id = self.context.lock().unwrap().gatt_client_id();
                 if client_id.is_none() {
-                    println!("GATT client is not yet registered.");
+                    print_error!("GATT client is not yet registered.");
                     return;
                 }
 
-                let addr = String::from(&args[1]);
-                self.context
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let status = self
+                    .context
                     .lock()
                     .unwrap()
                     .gatt_dbus
                     .as_mut()
                     .unwrap()
                     .client_read_phy(client_id.unwrap(), addr);
+                report_status("read the PHY", status);
             }
             "client-discover-services" => {
                 if args.len() < 2 {
-                    println!("usage: gatt client-discover-services <addr>");
+                    print_error!("usage: gatt client-discover-services <addr>");
                     return;
                 }
 
-                let client_id = self.context.lock().unwrap().gatt_client_id;
+                let client_id = self.context.lock().unwrap().gatt_client_id();
                 if client_id.is_none() {
-                    println!("GATT client is not yet registered.");
+                    print_error!("GATT client is not yet registered.");
                     return;
                 }
 
-                let addr = String::from(&args[1]);
-                self.context
+                let addr = match self.parse_address(&args[1]) {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                let status = self
+                    .context
                     .lock()
                     .unwrap()
                     .gatt_dbus
-                    .as_ref()
+                    .as_mut()
                     .unwrap()
                     .discover_services(client_id.unwrap(), addr);
+                report_status("discover services", status);
+            }
+            "set-device-info" => {
+                if args.len() < 3 {
+                    print_error!("usage: gatt set-device-info <manufacturer> <model>");
+                    return;
+                }
+
+                let manufacturer = String::from(&args[1]);
+                let model = String::from(&args[2]);
+                self.context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_device_information(manufacturer, model);
+            }
+            "set-appearance" => {
+                if args.len() < 2 {
+                    print_error!("usage: gatt set-appearance <hex value>");
+                    return;
+                }
+
+                let appearance = match u16::from_str_radix(args[1].trim_start_matches("0x"), 16) {
+                    Ok(appearance) => appearance,
+                    Err(_) => {
+                        print_error!("Invalid appearance '{}'", args[1]);
+                        return;
+                    }
+                };
+
+                self.context.lock().unwrap().gatt_dbus.as_mut().unwrap().set_appearance(appearance);
             }
             _ => {
-                println!("Invalid argument '{}'", args[0]);
+                print_error!("Invalid argument '{}'", args[0]);
             }
         });
     }
@@ -674,7 +3564,8 @@ impl CommandHandler {
             return;
         }
 
-        enforce_arg_len(args, 1, "list <bonded|found>", || match &args[0][0..] {
+        let usage = "list <bonded|found [rssi]|connected|blocked>";
+        enforce_arg_len(args, 1, usage, || match &args[0][0..] {
             "bonded" => {
                 print_info!("Known bonded devices:");
                 let devices = self
@@ -691,12 +3582,57 @@ impl CommandHandler {
             }
             "found" => {
                 print_info!("Devices found in most recent discovery session:");
-                for (key, val) in self.context.lock().unwrap().found_devices.iter() {
+                let context = self.context.lock().unwrap();
+                if args.get(1).map(|arg| &arg[0..]) != Some("rssi") {
+                    for (key, val) in context.found_devices.iter() {
+                        print_info!("[{:17}] {}", key, val.name);
+                    }
+                    return;
+                }
+
+                let mut addresses: Vec<RawAddress> =
+                    context.found_devices.keys().cloned().collect();
+                context.found_rssi.sort_by_rssi(&mut addresses);
+                for address in addresses {
+                    let name = &context.found_devices[&address].name;
+                    match context.found_rssi.get(&address) {
+                        Some(rssi) => {
+                            let distance = context.path_loss.distance(rssi);
+                            print_info!(
+                                "[{:17}] {} ({:.0} dBm, ~{:.1} m, {})",
+                                address,
+                                name,
+                                rssi,
+                                distance,
+                                DistanceBucket::from_distance(distance)
+                            );
+                        }
+                        None => print_info!("[{:17}] {} (no RSSI)", address, name),
+                    }
+                }
+            }
+            "connected" => {
+                print_info!("Connected devices:");
+                for (key, val) in self.context.lock().unwrap().connected_devices.iter() {
                     print_info!("[{:17}] {}", key, val.name);
                 }
             }
+            "blocked" => {
+                print_info!("Blocked devices:");
+                let devices = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .adapter_dbus
+                    .as_ref()
+                    .unwrap()
+                    .get_blocked_devices();
+                for device in devices.iter() {
+                    print_info!("[{:17}] {}", device.address, device.name);
+                }
+            }
             _ => {
-                println!("Invalid argument '{}'", args[0]);
+                print_error!("Invalid argument '{}'", args[0]);
             }
         });
     }
@@ -1,42 +1,159 @@
 //! Convenient functions to print messages to console.
+//!
+//! Messages printed with [`print_info`], [`print_warn`] and [`print_error`] are prefixed with a
+//! UTC timestamp and their level. Colors can be turned off with [`set_color_enabled`], e.g. when
+//! the output is captured to a file.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+static INFO_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables ANSI colors in console output.
+pub fn set_color_enabled(enabled: bool) {
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Enables or disables printing of [`print_info`] messages. Warnings and errors are always
+/// printed.
+pub fn set_info_enabled(enabled: bool) {
+    INFO_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Wraps `text` in the ANSI escape sequence `code`, unless colors are disabled.
+pub fn colorize<T: fmt::Display>(code: &str, text: T) -> String {
+    if COLOR_ENABLED.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Severity of a console message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn tag(&self) -> &'static str {
+        match self {
+            Level::Info => "btclient:info",
+            Level::Warn => "btclient:warn",
+            Level::Error => "btclient:error",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Level::Info => "1;33",
+            Level::Warn => "1;35",
+            Level::Error => "1;31",
+        }
+    }
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn format_timestamp(millis: u128) -> String {
+    let secs = (millis / 1000) as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let time = secs.rem_euclid(86400);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        millis % 1000
+    )
+}
+
+/// Returns the current time in ISO 8601 format (UTC, millisecond precision).
+pub fn timestamp() -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    format_timestamp(millis)
+}
+
+/// Prints a message with the timestamp and level prefix. Use the `print_*` macros instead.
+#[doc(hidden)]
+pub fn print(level: Level, args: fmt::Arguments) {
+    if level == Level::Info && !INFO_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    println!("{} {}: {}", timestamp(), colorize(level.color(), level.tag()), args);
+}
 
 #[macro_export]
 macro_rules! console_blue {
     ( $text:expr ) => {
-        format!("\x1b[1;34m{}\x1b[0m", $text).as_str()
+        $crate::console::colorize("1;34", $text).as_str()
     };
 }
 
 #[macro_export]
 macro_rules! console_yellow {
     ( $text:expr ) => {
-        format!("\x1b[1;33m{}\x1b[0m", $text).as_str()
+        $crate::console::colorize("1;33", $text).as_str()
     };
 }
 
 #[macro_export]
 macro_rules! console_red {
     ( $text:expr ) => {
-        format!("\x1b[1;31m{}\x1b[0m", $text).as_str()
+        $crate::console::colorize("1;31", $text).as_str()
     };
 }
 
 #[macro_export]
 macro_rules! print_info {
     ( $($arg:tt)* ) => {
-        {
-            print!("{}: ", console_yellow!("btclient:info"));
-            println!($($arg)*);
-        }
+        $crate::console::print($crate::console::Level::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! print_warn {
+    ( $($arg:tt)* ) => {
+        $crate::console::print($crate::console::Level::Warn, format_args!($($arg)*))
     };
 }
 
 #[macro_export]
 macro_rules! print_error {
     ( $($arg:tt)* ) => {
-        {
-            print!("{}: ", console_red!("btclient:error"));
-            println!($($arg)*);
-        }
+        $crate::console::print($crate::console::Level::Error, format_args!($($arg)*))
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!("1970-01-01T00:00:00.000Z", format_timestamp(0));
+        assert_eq!("2000-02-29T23:59:59.999Z", format_timestamp(951868799999));
+        assert_eq!("2022-03-14T01:02:03.004Z", format_timestamp(1647219723004));
+    }
+}
@@ -1,31 +1,78 @@
 //! D-Bus proxy implementations of the APIs.
 
-use bt_topshim::btif::{BtDeviceType, BtSspVariant, BtTransport, Uuid128Bit};
+use bt_topshim::btif::{
+    BtAclRole, BtAddrType, BtDeviceType, BtSspVariant, BtStatus, BtTransport, RawAddress,
+    Uuid128Bit,
+};
 use bt_topshim::profiles::gatt::GattStatus;
+use bt_topshim::profiles::pan::BtPanConnectionState;
 
+use btstack::activity::{ActivityEnergyInfo, DeviceActivity};
+use btstack::beacon::{Beacon, BeaconType};
 use btstack::bluetooth::{
-    BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
+    AdapterCapabilities, AdapterProperties, AdapterState, BluetoothDevice, ClassicScanParams,
+    ClassicScanType, ControllerInfo, FoundDeviceInfo, IBluetooth, IBluetoothCallback,
+    IBluetoothConnectionCallback, IBluetoothDiscoverySessionCallback, IBluetoothLinkPolicy,
+    IBluetoothLinkPolicyCallback, IBluetoothSecurityCallback, LePrivacyFeatures, LeScanParams,
+    PairingRejectedReason, ProfileConnectResult, ProfileConnectionReason, ProfileConnectionState,
+    RemoteDeviceInfo, RemoteFeatures, RemoteVersionInfo, RssiSample,
+};
+use btstack::bluetooth_adv_monitor::{
+    AdvMonitor, AdvMonitorPattern, IAdvMonitorCallback, IBluetoothAdvMonitor,
 };
+use btstack::bluetooth_fast_pair::IBluetoothFastPair;
+
 use btstack::bluetooth_gatt::{
-    BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService,
-    GattWriteRequestStatus, GattWriteType, IBluetoothGatt, IBluetoothGattCallback,
-    IScannerCallback, LePhy, ScanFilter, ScanSettings,
+    BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService, ConnectionPriority,
+    GattNotification, GattWriteRequestStatus, GattWriteType, IBluetoothGatt,
+    IBluetoothGattCallback, IScannerCallback, LePhy, RSSISettings, ScanFilter, ScanSettings,
+    ScanType,
+};
+
+use btstack::bluetooth_map::{
+    IBluetoothMap, IBluetoothMapCallback, MapEvent, MapMessage, MapStatus,
+};
+
+use btstack::bluetooth_opp::{
+    IBluetoothOpp, IBluetoothOppCallback, OppTransfer, OppTransferStatus,
+};
+
+use btstack::bluetooth_pan::{IBluetoothPan, IBluetoothPanCallback};
+
+use btstack::bluetooth_pbap::{
+    IBluetoothPbap, IBluetoothPbapCallback, PbapPhonebook, PbapPullStatus,
+};
+use btstack::bluetooth_provisioning::IBluetoothProvisioning;
+use btstack::coex::{CoexState, IBluetoothCoex, WifiChannel};
+use btstack::device_type::DeviceType;
+use btstack::distance_measurement::{
+    DistanceMeasurementMethod, DistanceMeasurementResult, IBluetoothDistanceMeasurement,
+    IBluetoothDistanceMeasurementCallback,
+};
+
+use btstack::link_diagnostics::{
+    IBluetoothLinkDiagnostics, IBluetoothLinkDiagnosticsCallback, PathLossZone, PingStats,
+    TxPowerReport, TxPowerSource,
 };
 
 use btstack::suspend::{ISuspend, ISuspendCallback, SuspendType};
 
+use btstack::throughput_test::{
+    IBluetoothThroughputTest, IBluetoothThroughputTestCallback, ThroughputDirection,
+    ThroughputTestResult, ThroughputTransport,
+};
+
 use btstack::uuid::Profile;
 use dbus::arg::{AppendAll, RefArg};
 use dbus::nonblock::SyncConnection;
 
 use dbus_projection::{impl_dbus_arg_enum, DisconnectWatcher};
 
-use dbus_macros::{
-    dbus_method, dbus_propmap, generate_dbus_exporter, generate_dbus_interface_client,
-};
+use dbus_macros::{dbus_method, dbus_propmap, generate_dbus_exporter};
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterDetails, AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    IMergedDiscoveryCallback, MergedFoundDevice,
 };
 
 use num_traits::{FromPrimitive, ToPrimitive};
@@ -39,15 +86,37 @@ fn make_object_path(idx: i32, name: &str) -> dbus::Path {
     dbus::Path::new(format!("/org/chromium/bluetooth/hci{}/{}", idx, name)).unwrap()
 }
 
+impl_dbus_arg_enum!(AdapterState);
+impl_dbus_arg_enum!(BeaconType);
+impl_dbus_arg_enum!(BtAclRole);
+impl_dbus_arg_enum!(BtAddrType);
 impl_dbus_arg_enum!(BtDeviceType);
+impl_dbus_arg_enum!(BtPanConnectionState);
 impl_dbus_arg_enum!(BtSspVariant);
+impl_dbus_arg_enum!(BtStatus);
 impl_dbus_arg_enum!(BtTransport);
+impl_dbus_arg_enum!(ClassicScanType);
+impl_dbus_arg_enum!(ConnectionPriority);
+impl_dbus_arg_enum!(DeviceType);
+impl_dbus_arg_enum!(DistanceMeasurementMethod);
 impl_dbus_arg_enum!(GattStatus);
 impl_dbus_arg_enum!(GattWriteRequestStatus);
 impl_dbus_arg_enum!(GattWriteType);
 impl_dbus_arg_enum!(LePhy);
+impl_dbus_arg_enum!(MapStatus);
+impl_dbus_arg_enum!(OppTransferStatus);
+impl_dbus_arg_enum!(PairingRejectedReason);
+impl_dbus_arg_enum!(PathLossZone);
+impl_dbus_arg_enum!(PbapPhonebook);
+impl_dbus_arg_enum!(PbapPullStatus);
 impl_dbus_arg_enum!(Profile);
+impl_dbus_arg_enum!(ProfileConnectionReason);
+impl_dbus_arg_enum!(ProfileConnectionState);
+impl_dbus_arg_enum!(ScanType);
 impl_dbus_arg_enum!(SuspendType);
+impl_dbus_arg_enum!(ThroughputDirection);
+impl_dbus_arg_enum!(ThroughputTransport);
+impl_dbus_arg_enum!(TxPowerSource);
 
 // Represents Uuid128Bit as an array in D-Bus.
 impl DBusArg for Uuid128Bit {
@@ -59,7 +128,13 @@ impl DBusArg for Uuid128Bit {
         _remote: Option<dbus::strings::BusName<'static>>,
         _disconnect_watcher: Option<Arc<std::sync::Mutex<DisconnectWatcher>>>,
     ) -> Result<[u8; 16], Box<dyn std::error::Error>> {
-        return Ok(data.try_into().unwrap());
+        let len = data.len();
+        match data.try_into() {
+            Ok(uuid) => Ok(uuid),
+            Err(_) => {
+                Err(Box::new(DBusArgError::new(format!("UUID must be 16 bytes, found {}", len))))
+            }
+        }
     }
 
     fn to_dbus(data: [u8; 16]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
@@ -67,6 +142,27 @@ impl DBusArg for Uuid128Bit {
     }
 }
 
+// Represents RawAddress as a string in D-Bus, e.g. "00:11:22:33:44:55".
+impl DBusArg for RawAddress {
+    type DBusType = String;
+
+    fn from_dbus(
+        data: String,
+        _conn: Option<Arc<SyncConnection>>,
+        _remote: Option<dbus::strings::BusName<'static>>,
+        _disconnect_watcher: Option<Arc<std::sync::Mutex<DisconnectWatcher>>>,
+    ) -> Result<RawAddress, Box<dyn std::error::Error>> {
+        match RawAddress::from_string(data.clone()) {
+            Some(addr) => Ok(addr),
+            None => Err(Box::new(DBusArgError::new(format!("Invalid address {}", data)))),
+        }
+    }
+
+    fn to_dbus(data: RawAddress) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(data.to_string())
+    }
+}
+
 #[dbus_propmap(BluetoothGattDescriptor)]
 pub struct BluetoothGattDescriptorDBus {
     uuid: Uuid128Bit,
@@ -94,12 +190,243 @@ pub struct BluetoothGattServiceDBus {
     pub included_services: Vec<BluetoothGattService>,
 }
 
+#[dbus_propmap(GattNotification)]
+pub struct GattNotificationDBus {
+    handle: i32,
+    value: Vec<u8>,
+    timestamp_us: u64,
+}
+
+#[dbus_propmap(RSSISettings)]
+pub struct RSSISettingsDBus {
+    low_threshold: i32,
+    high_threshold: i32,
+}
+
+#[dbus_propmap(ScanSettings)]
+struct ScanSettingsDBus {
+    interval: i32,
+    window: i32,
+    scan_type: ScanType,
+    rssi_settings: RSSISettings,
+}
+
+#[dbus_propmap(ScanFilter)]
+struct ScanFilterDBus {}
+
 #[dbus_propmap(BluetoothDevice)]
 pub struct BluetoothDeviceDBus {
+    address: RawAddress,
+    name: String,
+    address_type: BtAddrType,
+    transport: BtTransport,
+    icon: DeviceType,
+}
+
+#[dbus_propmap(ProfileConnectResult)]
+pub struct ProfileConnectResultDBus {
+    profile: Profile,
+    status: BtStatus,
+}
+
+#[dbus_propmap(OppTransfer)]
+pub struct OppTransferDBus {
+    id: i32,
+    device: BluetoothDevice,
+    outgoing: bool,
+    file_name: String,
+    file_path: String,
+    mime_type: String,
+    total_bytes: u64,
+}
+
+#[dbus_propmap(PingStats)]
+pub struct PingStatsDBus {
+    sent: u32,
+    received: u32,
+    min_rtt_us: u64,
+    avg_rtt_us: u64,
+    max_rtt_us: u64,
+}
+
+#[dbus_propmap(DistanceMeasurementResult)]
+pub struct DistanceMeasurementResultDBus {
+    method: DistanceMeasurementMethod,
+    distance_cm: u32,
+    error_cm: u32,
+    rssi: i32,
+}
+
+#[dbus_propmap(TxPowerReport)]
+pub struct TxPowerReportDBus {
+    source: TxPowerSource,
+    phy: LePhy,
+    tx_power: i32,
+    max_tx_power: i32,
+    at_min: bool,
+    at_max: bool,
+    delta: i32,
+}
+
+#[dbus_propmap(ThroughputTestResult)]
+pub struct ThroughputTestResultDBus {
+    bytes: u64,
+    packets: u32,
+    lost_packets: u32,
+    duration_ms: u64,
+    throughput_bps: u64,
+}
+
+#[dbus_propmap(MapMessage)]
+pub struct MapMessageDBus {
+    handle: String,
+    subject: String,
+    datetime: String,
+    sender_name: String,
+    sender_addressing: String,
+    msg_type: String,
+    read: bool,
+}
+
+#[dbus_propmap(MapEvent)]
+pub struct MapEventDBus {
+    event_type: String,
+    handle: String,
+    folder: String,
+    msg_type: String,
+}
+
+#[dbus_propmap(AdapterCapabilities)]
+pub struct AdapterCapabilitiesDBus {
+    le_extended_advertising_supported: bool,
+    le_2m_phy_supported: bool,
+    le_coded_phy_supported: bool,
+    le_periodic_advertising_supported: bool,
+    le_connected_isochronous_stream_central_supported: bool,
+    le_isochronous_broadcast_supported: bool,
+    max_advertising_sets: u32,
+    max_advertising_data_length: u32,
+    max_advertising_filters: u32,
+    acl_buffer_count_classic: u32,
+    acl_buffer_count_le: u32,
+    le_accept_list_size: u32,
+    max_connections: u32,
+}
+
+#[dbus_propmap(AdapterProperties)]
+pub struct AdapterPropertiesDBus {
     address: String,
     name: String,
+    bluetooth_class: u32,
+    discoverable: bool,
+    discoverable_timeout: u32,
+    discovering: bool,
+    connected_count: u32,
+}
+
+#[dbus_propmap(ClassicScanParams)]
+pub struct ClassicScanParamsDBus {
+    interval: u16,
+    window: u16,
+    scan_type: ClassicScanType,
+}
+
+#[dbus_propmap(LeScanParams)]
+pub struct LeScanParamsDBus {
+    interval: u16,
+    window: u16,
+    scan_type: ScanType,
+}
+
+#[dbus_propmap(LePrivacyFeatures)]
+pub struct LePrivacyFeaturesDBus {
+    ll_privacy_supported: bool,
+    rpa_offload_supported: bool,
+    resolving_list_size: u32,
+    privacy_enabled: bool,
+}
+
+#[dbus_propmap(RemoteVersionInfo)]
+pub struct RemoteVersionInfoDBus {
+    valid: bool,
+    lmp_version: u8,
+    manufacturer: u16,
+    lmp_subversion: u16,
 }
 
+#[dbus_propmap(RemoteFeatures)]
+pub struct RemoteFeaturesDBus {
+    lmp_features: Vec<u8>,
+    le_features: Vec<u8>,
+}
+
+#[dbus_propmap(FoundDeviceInfo)]
+pub struct FoundDeviceInfoDBus {
+    class_of_device: u32,
+    device_type: BtDeviceType,
+    rssi: i32,
+    uuids: Vec<Uuid128Bit>,
+}
+
+#[dbus_propmap(RssiSample)]
+pub struct RssiSampleDBus {
+    rssi: i32,
+    age_ms: u64,
+}
+
+#[dbus_propmap(RemoteDeviceInfo)]
+pub struct RemoteDeviceInfoDBus {
+    known: bool,
+    device: BluetoothDevice,
+    alias: String,
+    device_type: BtDeviceType,
+    class_of_device: u32,
+    bond_state: u32,
+    uuids: Vec<Uuid128Bit>,
+    rssi_history: Vec<RssiSample>,
+    last_seen_ms: u64,
+    connection_state: u32,
+    connected_ms: u64,
+    link_role: BtAclRole,
+    bredr_key_size: u8,
+    le_key_size: u8,
+}
+
+#[dbus_propmap(DeviceActivity)]
+pub struct DeviceActivityDBus {
+    address: RawAddress,
+    tx_bytes: u64,
+    rx_bytes: u64,
+}
+
+#[dbus_propmap(ActivityEnergyInfo)]
+pub struct ActivityEnergyInfoDBus {
+    controller_reported: bool,
+    controller_state: u32,
+    controller_tx_time_ms: u64,
+    controller_rx_time_ms: u64,
+    controller_idle_time_ms: u64,
+    controller_energy_used: u64,
+    scan_time_ms: u64,
+    scan_radio_time_ms: u64,
+    advertising_time_ms: u64,
+    advertising_airtime_ms: u64,
+    devices: Vec<DeviceActivity>,
+}
+
+#[dbus_propmap(ControllerInfo)]
+pub struct ControllerInfoDBus {
+    manufacturer: u16,
+    hci_version: u8,
+    hci_revision: u16,
+    lmp_version: u8,
+    lmp_subversion: u16,
+    firmware_version: String,
+    vendor_diagnostics_supported: bool,
+    vendor_diagnostics_enabled: bool,
+}
+
+#[derive(Clone)]
 struct ClientDBusProxy {
     conn: Arc<SyncConnection>,
     bus_name: String,
@@ -124,11 +451,38 @@ impl ClientDBusProxy {
         member: &str,
         args: A,
     ) -> Result<(T,), dbus::Error> {
-        let proxy = self.create_proxy();
         // We know that all APIs return immediately, so we can block on it for simplicity.
-        return futures::executor::block_on(async {
-            proxy.method_call(self.interface.clone(), member, args).await
-        });
+        return futures::executor::block_on(self.async_method_withresult(member, args));
+    }
+
+    /// Calls a method without blocking the caller and returns the dbus result.
+    async fn async_method_withresult<
+        A: AppendAll,
+        T: 'static + dbus::arg::Arg + for<'z> dbus::arg::Get<'z>,
+    >(
+        &self,
+        member: &str,
+        args: A,
+    ) -> Result<(T,), dbus::Error> {
+        let proxy = self.create_proxy();
+        proxy.method_call(self.interface.clone(), member, args).await
+    }
+
+    async fn async_method<
+        A: AppendAll,
+        T: 'static + dbus::arg::Arg + for<'z> dbus::arg::Get<'z>,
+    >(
+        &self,
+        member: &str,
+        args: A,
+    ) -> T {
+        let (ret,): (T,) = self.async_method_withresult(member, args).await.unwrap();
+        ret
+    }
+
+    async fn async_method_noreturn<A: AppendAll>(&self, member: &str, args: A) {
+        // See `method_noreturn` for why the result is typed as bool and never unwrapped.
+        let _: Result<(bool,), _> = self.async_method_withresult(member, args).await;
     }
 
     fn method<A: AppendAll, T: 'static + dbus::arg::Arg + for<'z> dbus::arg::Get<'z>>(
@@ -153,17 +507,10 @@ impl ClientDBusProxy {
 struct IBluetoothCallbackDBus {}
 
 impl btstack::RPCProxy for IBluetoothCallbackDBus {
-    // Dummy implementations just to satisfy impl RPCProxy requirements.
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
-    }
+    // Dummy implementation just to satisfy impl RPCProxy requirements.
     fn get_object_id(&self) -> String {
         String::from("")
     }
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
-    }
-    fn export_for_rpc(self: Box<Self>) {}
 }
 
 #[generate_dbus_exporter(
@@ -171,6 +518,9 @@ impl btstack::RPCProxy for IBluetoothCallbackDBus {
     "org.chromium.bluetooth.BluetoothCallback"
 )]
 impl IBluetoothCallback for IBluetoothCallbackDBus {
+    #[dbus_method("OnAdapterStateChanged")]
+    fn on_adapter_state_changed(&self, state: AdapterState) {}
+
     #[dbus_method("OnAddressChanged")]
     fn on_address_changed(&self, addr: String) {}
 
@@ -181,14 +531,20 @@ impl IBluetoothCallback for IBluetoothCallbackDBus {
     fn on_discoverable_changed(&self, discoverable: bool) {}
 
     #[dbus_method("OnDeviceFound")]
-    fn on_device_found(&self, remote_device: BluetoothDevice) {}
+    fn on_device_found(&self, remote_device: BluetoothDevice, info: FoundDeviceInfo) {}
 
     #[dbus_method("OnDeviceCleared")]
     fn on_device_cleared(&self, remote_device: BluetoothDevice) {}
 
+    #[dbus_method("OnRemoteNameFetched")]
+    fn on_remote_name_fetched(&self, remote_device: BluetoothDevice, success: bool) {}
+
     #[dbus_method("OnDiscoveringChanged")]
     fn on_discovering_changed(&self, discovering: bool) {}
 
+    #[dbus_method("OnConnectedCountChanged")]
+    fn on_connected_count_changed(&self, count: u32) {}
+
     #[dbus_method("OnSspRequest")]
     fn on_ssp_request(
         &self,
@@ -199,6 +555,24 @@ impl IBluetoothCallback for IBluetoothCallbackDBus {
     ) {
     }
 
+    #[dbus_method("OnPinRequest")]
+    fn on_pin_request(&self, remote_device: BluetoothDevice, cod: u32, min_16_digit: bool) {}
+
+    #[dbus_method("OnPinDisplay")]
+    fn on_pin_display(&self, remote_device: BluetoothDevice, pincode: String) {}
+
+    #[dbus_method("OnPairingRejected")]
+    fn on_pairing_rejected(&self, remote_device: BluetoothDevice, reason: PairingRejectedReason) {}
+
+    #[dbus_method("OnEncryptionKeySizeRejected")]
+    fn on_encryption_key_size_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        key_size: u8,
+    ) {
+    }
+
     #[dbus_method("OnBondStateChanged")]
     fn on_bond_state_changed(&self, status: u32, address: String, state: u32) {}
 }
@@ -207,17 +581,10 @@ impl IBluetoothCallback for IBluetoothCallbackDBus {
 struct IBluetoothConnectionCallbackDBus {}
 
 impl btstack::RPCProxy for IBluetoothConnectionCallbackDBus {
-    // Dummy implementations just to satisfy impl RPCProxy requirements.
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
-    }
+    // Dummy implementation just to satisfy impl RPCProxy requirements.
     fn get_object_id(&self) -> String {
         String::from("")
     }
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
-    }
-    fn export_for_rpc(self: Box<Self>) {}
 }
 
 #[generate_dbus_exporter(
@@ -230,229 +597,172 @@ impl IBluetoothConnectionCallback for IBluetoothConnectionCallbackDBus {
 
     #[dbus_method("OnDeviceDisconnected")]
     fn on_device_disconnected(&self, remote_device: BluetoothDevice) {}
-}
-
-pub(crate) struct BluetoothDBus {
-    client_proxy: ClientDBusProxy,
-}
-
-impl BluetoothDBus {
-    pub(crate) fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothDBus {
-        BluetoothDBus {
-            client_proxy: ClientDBusProxy {
-                conn: conn.clone(),
-                bus_name: String::from("org.chromium.bluetooth"),
-                objpath: make_object_path(index, "adapter"),
-                interface: String::from("org.chromium.bluetooth.Bluetooth"),
-            },
-        }
-    }
-}
-
-#[generate_dbus_interface_client]
-impl IBluetooth for BluetoothDBus {
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RegisterConnectionCallback")]
-    fn register_connection_callback(
-        &mut self,
-        callback: Box<dyn IBluetoothConnectionCallback + Send>,
-    ) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("UnregisterConnectionCallback")]
-    fn unregister_connection_callback(&mut self, id: u32) -> bool {
-        dbus_generated!()
-    }
-
-    fn enable(&mut self) -> bool {
-        // Not implemented by server
-        true
-    }
-
-    fn disable(&mut self) -> bool {
-        // Not implemented by server
-        true
-    }
-
-    #[dbus_method("GetAddress")]
-    fn get_address(&self) -> String {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetUuids")]
-    fn get_uuids(&self) -> Vec<Uuid128Bit> {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetName")]
-    fn get_name(&self) -> String {
-        dbus_generated!()
-    }
-
-    #[dbus_method("SetName")]
-    fn set_name(&self, name: String) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetBluetoothClass")]
-    fn get_bluetooth_class(&self) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("SetBluetoothClass")]
-    fn set_bluetooth_class(&self, cod: u32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetDiscoverable")]
-    fn get_discoverable(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetDiscoverableTimeout")]
-    fn get_discoverable_timeout(&self) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("SetDiscoverable")]
-    fn set_discoverable(&self, mode: bool, duration: u32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("IsMultiAdvertisementSupported")]
-    fn is_multi_advertisement_supported(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("IsLeExtendedAdvertisingSupported")]
-    fn is_le_extended_advertising_supported(&self) -> bool {
-        dbus_generated!()
-    }
 
-    #[dbus_method("StartDiscovery")]
-    fn start_discovery(&self) -> bool {
-        dbus_generated!()
+    #[dbus_method("OnProfileConnectionStateChanged")]
+    fn on_profile_connection_state_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    ) {
     }
 
-    #[dbus_method("CancelDiscovery")]
-    fn cancel_discovery(&self) -> bool {
-        dbus_generated!()
+    #[dbus_method("OnConnectAllComplete")]
+    fn on_connect_all_complete(
+        &self,
+        remote_device: BluetoothDevice,
+        results: Vec<ProfileConnectResult>,
+    ) {
     }
+}
 
-    #[dbus_method("IsDiscovering")]
-    fn is_discovering(&self) -> bool {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct IBluetoothSecurityCallbackDBus {}
 
-    #[dbus_method("GetDiscoveryEndMillis")]
-    fn get_discovery_end_millis(&self) -> u64 {
-        dbus_generated!()
+impl btstack::RPCProxy for IBluetoothSecurityCallbackDBus {
+    // Dummy implementation just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("CreateBond")]
-    fn create_bond(&self, device: BluetoothDevice, transport: BtTransport) -> bool {
-        dbus_generated!()
-    }
+#[generate_dbus_exporter(
+    export_bluetooth_security_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothSecurityCallback"
+)]
+impl IBluetoothSecurityCallback for IBluetoothSecurityCallbackDBus {
+    #[dbus_method("OnAuthenticationFailed")]
+    fn on_authentication_failed(&self, remote_device: BluetoothDevice, hci_reason: u8) {}
 
-    #[dbus_method("CancelBondProcess")]
-    fn cancel_bond_process(&self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
+    #[dbus_method("OnEncryptionChanged")]
+    fn on_encryption_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+        encrypted: bool,
+    ) {
     }
 
-    #[dbus_method("RemoveBond")]
-    fn remove_bond(&self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
+    #[dbus_method("OnEncryptionKeyRefreshed")]
+    fn on_encryption_key_refreshed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+    ) {
     }
 
-    #[dbus_method("GetBondedDevices")]
-    fn get_bonded_devices(&self) -> Vec<BluetoothDevice> {
-        dbus_generated!()
+    #[dbus_method("OnAddressSpoofingSuspected")]
+    fn on_address_spoofing_suspected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    ) {
     }
 
-    #[dbus_method("GetBondState")]
-    fn get_bond_state(&self, device: BluetoothDevice) -> u32 {
-        dbus_generated!()
+    #[dbus_method("OnUnbondedConnectionRejected")]
+    fn on_unbonded_connection_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    ) {
     }
+}
 
-    #[dbus_method("SetPin")]
-    fn set_pin(&self, device: BluetoothDevice, accept: bool, pin_code: Vec<u8>) -> bool {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct IBluetoothDiscoverySessionCallbackDBus {}
 
-    #[dbus_method("SetPasskey")]
-    fn set_passkey(&self, device: BluetoothDevice, accept: bool, passkey: Vec<u8>) -> bool {
-        dbus_generated!()
+impl btstack::RPCProxy for IBluetoothDiscoverySessionCallbackDBus {
+    // Placeholder implementation just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("SetPairingConfirmation")]
-    fn set_pairing_confirmation(&self, device: BluetoothDevice, accept: bool) -> bool {
-        dbus_generated!()
-    }
+#[generate_dbus_exporter(
+    export_bluetooth_discovery_session_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothDiscoverySessionCallback"
+)]
+impl IBluetoothDiscoverySessionCallback for IBluetoothDiscoverySessionCallbackDBus {
+    #[dbus_method("OnSessionDeviceFound")]
+    fn on_session_device_found(&self, session_id: u32, remote_device: BluetoothDevice) {}
 
-    #[dbus_method("GetRemoteName")]
-    fn get_remote_name(&self, device: BluetoothDevice) -> String {
-        dbus_generated!()
-    }
+    #[dbus_method("OnSessionDeviceLost")]
+    fn on_session_device_lost(&self, session_id: u32, remote_device: BluetoothDevice) {}
 
-    #[dbus_method("GetRemoteType")]
-    fn get_remote_type(&self, device: BluetoothDevice) -> BtDeviceType {
-        dbus_generated!()
-    }
+    #[dbus_method("OnDiscoverySessionEnded")]
+    fn on_discovery_session_ended(&self, session_id: u32) {}
+}
 
-    #[dbus_method("GetRemoteAlias")]
-    fn get_remote_alias(&self, device: BluetoothDevice) -> String {
-        dbus_generated!()
-    }
+#[derive(Clone)]
+pub struct BluetoothDBus {
+    client_proxy: ClientDBusProxy,
+}
 
-    #[dbus_method("SetRemoteAlias")]
-    fn set_remote_alias(&mut self, device: BluetoothDevice, new_alias: String) {
-        dbus_generated!()
+impl BluetoothDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothDBus {
+        BluetoothDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "adapter"),
+                interface: String::from("org.chromium.bluetooth.Bluetooth"),
+            },
+        }
     }
+}
 
-    #[dbus_method("GetRemoteClass")]
-    fn get_remote_class(&self, device: BluetoothDevice) -> u32 {
-        dbus_generated!()
+btstack::bluetooth_dbus!(client BluetoothDBus, {
+    fn enable(&mut self) -> bool {
+        // Not implemented by server
+        true
     }
 
-    #[dbus_method("GetConnectionState")]
-    fn get_connection_state(&self, device: BluetoothDevice) -> u32 {
-        dbus_generated!()
+    fn disable(&mut self) -> bool {
+        // Not implemented by server
+        true
     }
+});
 
-    #[dbus_method("GetProfileConnectionState")]
-    fn get_profile_connection_state(&self, profile: Profile) -> u32 {
-        dbus_generated!()
-    }
+#[derive(Clone)]
+pub struct BluetoothLinkPolicyDBus {
+    client_proxy: ClientDBusProxy,
+}
 
-    #[dbus_method("GetRemoteUuids")]
-    fn get_remote_uuids(&self, device: BluetoothDevice) -> Vec<Uuid128Bit> {
-        dbus_generated!()
+impl BluetoothLinkPolicyDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothLinkPolicyDBus {
+        BluetoothLinkPolicyDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "link_policy"),
+                interface: String::from("org.chromium.bluetooth.BluetoothLinkPolicy"),
+            },
+        }
     }
+}
 
-    #[dbus_method("FetchRemoteUuids")]
-    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
-    }
+btstack::bluetooth_link_policy_dbus!(client BluetoothLinkPolicyDBus);
 
-    #[dbus_method("SdpSearch")]
-    fn sdp_search(&self, device: BluetoothDevice, uuid: Uuid128Bit) -> bool {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct IBluetoothLinkPolicyCallbackDBus {}
 
-    #[dbus_method("ConnectAllEnabledProfiles")]
-    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
+impl btstack::RPCProxy for IBluetoothLinkPolicyCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("DisconnectAllEnabledProfiles")]
-    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
-    }
+#[generate_dbus_exporter(
+    export_bluetooth_link_policy_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothLinkPolicyCallback"
+)]
+impl IBluetoothLinkPolicyCallback for IBluetoothLinkPolicyCallbackDBus {
+    #[dbus_method("OnRoleChanged")]
+    fn on_role_changed(&self, device: BluetoothDevice, role: BtAclRole, status: BtStatus) {}
 }
 
 #[dbus_propmap(AdapterWithEnabled)]
@@ -461,12 +771,35 @@ pub struct AdapterWithEnabledDbus {
     enabled: bool,
 }
 
-pub(crate) struct BluetoothManagerDBus {
+#[dbus_propmap(AdapterDetails)]
+pub struct AdapterDetailsDbus {
+    hci_interface: i32,
+    enabled: bool,
+    bus: String,
+    vendor_id: u32,
+    product_id: u32,
+    manufacturer: i32,
+    hci_version: i32,
+    hci_revision: i32,
+    features: Vec<String>,
+}
+
+#[dbus_propmap(MergedFoundDevice)]
+pub struct MergedFoundDeviceDbus {
+    address: String,
+    name: String,
+    hci_interface: i32,
+    rssi: i32,
+    hci_interfaces: Vec<i32>,
+}
+
+#[derive(Clone)]
+pub struct BluetoothManagerDBus {
     client_proxy: ClientDBusProxy,
 }
 
 impl BluetoothManagerDBus {
-    pub(crate) fn new(conn: Arc<SyncConnection>) -> BluetoothManagerDBus {
+    pub fn new(conn: Arc<SyncConnection>) -> BluetoothManagerDBus {
         BluetoothManagerDBus {
             client_proxy: ClientDBusProxy {
                 conn: conn.clone(),
@@ -477,65 +810,34 @@ impl BluetoothManagerDBus {
         }
     }
 
-    pub(crate) fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         let result: Result<(bool,), _> = self.client_proxy.method_withresult("GetFlossEnabled", ());
         return result.is_ok();
     }
-}
 
-#[generate_dbus_interface_client]
-impl IBluetoothManager for BluetoothManagerDBus {
-    #[dbus_method("Start")]
-    fn start(&mut self, hci_interface: i32) {
-        dbus_generated!()
+    /// Gets the API version of the daemons without failing on daemons that are too old to have
+    /// `GetApiVersion`, which are version 0. Returns None if the manager can't be reached.
+    pub fn negotiate_api_version(&self) -> Option<u32> {
+        let result: Result<(u32,), dbus::Error> =
+            self.client_proxy.method_withresult("GetApiVersion", ());
+        match result {
+            Ok((version,)) => Some(version),
+            Err(e) if e.name() == Some("org.freedesktop.DBus.Error.UnknownMethod") => Some(0),
+            Err(_) => None,
+        }
     }
+}
 
-    #[dbus_method("Stop")]
-    fn stop(&mut self, hci_interface: i32) {
-        dbus_generated!()
-    }
+manager_service::bluetooth_manager_dbus!(client BluetoothManagerDBus);
 
-    #[dbus_method("GetAdapterEnabled")]
-    fn get_adapter_enabled(&mut self, hci_interface: i32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothManagerCallback + Send>) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetFlossEnabled")]
-    fn get_floss_enabled(&mut self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("SetFlossEnabled")]
-    fn set_floss_enabled(&mut self, enabled: bool) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetAvailableAdapters")]
-    fn get_available_adapters(&mut self) -> Vec<AdapterWithEnabled> {
-        dbus_generated!()
-    }
-}
-
-#[allow(dead_code)]
-struct IBluetoothManagerCallbackDBus {}
+#[allow(dead_code)]
+struct IBluetoothManagerCallbackDBus {}
 
 impl manager_service::RPCProxy for IBluetoothManagerCallbackDBus {
     // Placeholder implementations just to satisfy impl RPCProxy requirements.
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
-    }
     fn get_object_id(&self) -> String {
         String::from("")
     }
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
-    }
-    fn export_for_rpc(self: Box<Self>) {}
 }
 
 #[generate_dbus_exporter(
@@ -548,14 +850,43 @@ impl IBluetoothManagerCallback for IBluetoothManagerCallbackDBus {
 
     #[dbus_method("OnHciEnabledChanged")]
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool) {}
+
+    #[dbus_method("OnShutdownComplete")]
+    fn on_shutdown_complete(&self) {}
+
+    #[dbus_method("OnAdapterRecovered")]
+    fn on_adapter_recovered(&self, hci_interface: i32, crash_count: u32) {}
+}
+
+#[allow(dead_code)]
+struct IMergedDiscoveryCallbackDBus {}
+
+impl manager_service::RPCProxy for IMergedDiscoveryCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
+    }
 }
 
-pub(crate) struct BluetoothGattDBus {
+#[generate_dbus_exporter(
+    export_merged_discovery_callback_dbus_obj,
+    "org.chromium.bluetooth.MergedDiscoveryCallback"
+)]
+impl IMergedDiscoveryCallback for IMergedDiscoveryCallbackDBus {
+    #[dbus_method("OnMergedDeviceFound")]
+    fn on_merged_device_found(&self, device: MergedFoundDevice) {}
+
+    #[dbus_method("OnMergedDiscoveringChanged")]
+    fn on_merged_discovering_changed(&self, discovering: bool) {}
+}
+
+#[derive(Clone)]
+pub struct BluetoothGattDBus {
     client_proxy: ClientDBusProxy,
 }
 
 impl BluetoothGattDBus {
-    pub(crate) fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothGattDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothGattDBus {
         BluetoothGattDBus {
             client_proxy: ClientDBusProxy {
                 conn: conn.clone(),
@@ -567,327 +898,652 @@ impl BluetoothGattDBus {
     }
 }
 
-#[generate_dbus_interface_client]
-impl IBluetoothGatt for BluetoothGattDBus {
-    fn register_scanner(&self, _callback: Box<dyn IScannerCallback + Send>) {
-        // TODO(b/200066804): implement
-    }
+btstack::bluetooth_gatt_dbus!(client BluetoothGattDBus);
 
-    fn unregister_scanner(&self, _scanner_id: i32) {
-        // TODO(b/200066804): implement
-    }
+#[allow(dead_code)]
+struct IBluetoothGattCallbackDBus {}
 
-    fn start_scan(&self, _scanner_id: i32, _settings: ScanSettings, _filters: Vec<ScanFilter>) {
-        // TODO(b/200066804): implement
+impl btstack::RPCProxy for IBluetoothGattCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    fn stop_scan(&self, _scanner_id: i32) {
-        // TODO(b/200066804): implement
-    }
+#[generate_dbus_exporter(
+    export_bluetooth_gatt_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothGattCallback"
+)]
+impl IBluetoothGattCallback for IBluetoothGattCallbackDBus {
+    #[dbus_method("OnClientRegistered")]
+    fn on_client_registered(&self, status: i32, client_id: i32) {}
 
-    #[dbus_method("RegisterClient")]
-    fn register_client(
-        &mut self,
-        app_uuid: String,
-        callback: Box<dyn IBluetoothGattCallback + Send>,
-        eatt_support: bool,
+    #[dbus_method("OnClientConnectionState")]
+    fn on_client_connection_state(
+        &self,
+        status: i32,
+        client_id: i32,
+        connected: bool,
+        addr: RawAddress,
     ) {
-        dbus_generated!()
     }
 
-    #[dbus_method("UnregisterClient")]
-    fn unregister_client(&mut self, client_id: i32) {
-        dbus_generated!()
-    }
+    #[dbus_method("OnPhyUpdate")]
+    fn on_phy_update(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {}
+
+    #[dbus_method("OnPhyRead")]
+    fn on_phy_read(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {}
 
-    #[dbus_method("ClientConnect")]
-    fn client_connect(
+    #[dbus_method("OnSearchComplete")]
+    fn on_search_complete(
         &self,
-        client_id: i32,
-        addr: String,
-        is_direct: bool,
-        transport: i32,
-        opportunistic: bool,
-        phy: i32,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        status: i32,
     ) {
-        dbus_generated!()
     }
 
-    #[dbus_method("ClientDisconnect")]
-    fn client_disconnect(&self, client_id: i32, addr: String) {
-        dbus_generated!()
+    #[dbus_method("OnCharacteristicRead")]
+    fn on_characteristic_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>) {
     }
 
-    #[dbus_method("ClientSetPreferredPhy")]
-    fn client_set_preferred_phy(
+    #[dbus_method("OnCharacteristicWrite")]
+    fn on_characteristic_write(&self, addr: RawAddress, status: i32, handle: i32) {}
+
+    #[dbus_method("OnExecuteWrite")]
+    fn on_execute_write(&self, addr: RawAddress, status: i32) {}
+
+    #[dbus_method("OnDescriptorRead")]
+    fn on_descriptor_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>) {}
+
+    #[dbus_method("OnDescriptorWrite")]
+    fn on_descriptor_write(&self, addr: RawAddress, status: i32, handle: i32) {}
+
+    #[dbus_method("OnNotify")]
+    fn on_notify(&self, addr: RawAddress, handle: i32, value: Arc<[u8]>) {}
+
+    #[dbus_method("OnNotifyBatch")]
+    fn on_notify_batch(&self, addr: RawAddress, notifications: Vec<GattNotification>) {}
+
+    #[dbus_method("OnReadRemoteRssi")]
+    fn on_read_remote_rssi(&self, addr: RawAddress, rssi: i32, status: i32) {}
+
+    #[dbus_method("OnConfigureMtu")]
+    fn on_configure_mtu(&self, addr: RawAddress, mtu: i32, status: i32) {}
+
+    #[dbus_method("OnConnectionUpdated")]
+    fn on_connection_updated(
         &self,
-        client_id: i32,
-        addr: String,
-        tx_phy: LePhy,
-        rx_phy: LePhy,
-        phy_options: i32,
+        addr: RawAddress,
+        interval: i32,
+        latency: i32,
+        timeout: i32,
+        status: i32,
     ) {
-        dbus_generated!()
     }
 
-    #[dbus_method("ClientReadPhy")]
-    fn client_read_phy(&mut self, client_id: i32, addr: String) {
-        dbus_generated!()
+    #[dbus_method("OnServiceChanged")]
+    fn on_service_changed(&self, addr: RawAddress) {}
+
+    #[dbus_method("OnGattDbUpdated")]
+    fn on_gatt_db_updated(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        database_hash: Vec<u8>,
+    ) {
     }
 
-    #[dbus_method("RefreshDevice")]
-    fn refresh_device(&self, client_id: i32, addr: String) {
-        dbus_generated!()
+    #[dbus_method("OnAuthorizationRequest")]
+    fn on_authorization_request(&self, addr: RawAddress, handle: i32) {}
+}
+
+#[allow(dead_code)]
+struct IScannerCallbackDBus {}
+
+impl btstack::RPCProxy for IScannerCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
+
+#[generate_dbus_exporter(
+    export_scanner_callback_dbus_obj,
+    "org.chromium.bluetooth.ScannerCallback"
+)]
+impl IScannerCallback for IScannerCallbackDBus {
+    #[dbus_method("OnScannerRegistered")]
+    fn on_scanner_registered(&self, status: i32, scanner_id: i32) {}
+}
+
+#[derive(Clone)]
+pub struct SuspendDBus {
+    client_proxy: ClientDBusProxy,
+}
 
-    #[dbus_method("DiscoverServices")]
-    fn discover_services(&self, client_id: i32, addr: String) {
-        dbus_generated!()
+impl SuspendDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> SuspendDBus {
+        SuspendDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "suspend"),
+                interface: String::from("org.chromium.bluetooth.Suspend"),
+            },
+        }
     }
+}
 
-    #[dbus_method("DiscoverServiceByUuid")]
-    fn discover_service_by_uuid(&self, client_id: i32, addr: String, uuid: String) {
-        dbus_generated!()
+btstack::suspend_dbus!(client SuspendDBus);
+
+#[allow(dead_code)]
+struct ISuspendCallbackDBus {}
+
+impl btstack::RPCProxy for ISuspendCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("ReadCharacteristic")]
-    fn read_characteristic(&self, client_id: i32, addr: String, handle: i32, auth_req: i32) {
-        dbus_generated!()
+#[generate_dbus_exporter(
+    export_suspend_callback_dbus_obj,
+    "org.chromium.bluetooth.SuspendCallback"
+)]
+impl ISuspendCallback for ISuspendCallbackDBus {
+    #[dbus_method("OnCallbackRegistered")]
+    fn on_callback_registered(&self, callback_id: u32) {}
+    #[dbus_method("OnSuspendReady")]
+    fn on_suspend_ready(&self, suspend_id: u32) {}
+    #[dbus_method("OnResumed")]
+    fn on_resumed(&self, suspend_id: u32) {}
+}
+
+#[derive(Clone)]
+pub struct BluetoothOppDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothOppDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothOppDBus {
+        BluetoothOppDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "opp"),
+                interface: String::from("org.chromium.bluetooth.BluetoothOpp"),
+            },
+        }
     }
+}
 
-    #[dbus_method("ReadUsingCharacteristicUuid")]
-    fn read_using_characteristic_uuid(
-        &self,
-        client_id: i32,
-        addr: String,
-        uuid: String,
-        start_handle: i32,
-        end_handle: i32,
-        auth_req: i32,
-    ) {
-        dbus_generated!()
+btstack::bluetooth_opp_dbus!(client BluetoothOppDBus);
+
+#[allow(dead_code)]
+struct IBluetoothOppCallbackDBus {}
+
+impl btstack::RPCProxy for IBluetoothOppCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("WriteCharacteristic")]
-    fn write_characteristic(
-        &self,
-        client_id: i32,
-        addr: String,
-        handle: i32,
-        write_type: GattWriteType,
-        auth_req: i32,
-        value: Vec<u8>,
-    ) -> GattWriteRequestStatus {
-        dbus_generated!()
+#[generate_dbus_exporter(
+    export_bluetooth_opp_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothOppCallback"
+)]
+impl IBluetoothOppCallback for IBluetoothOppCallbackDBus {
+    #[dbus_method("OnIncomingTransfer")]
+    fn on_incoming_transfer(&self, transfer: OppTransfer) {}
+    #[dbus_method("OnTransferProgress")]
+    fn on_transfer_progress(&self, transfer_id: i32, bytes_transferred: u64, total_bytes: u64) {}
+    #[dbus_method("OnTransferComplete")]
+    fn on_transfer_complete(&self, transfer_id: i32, status: OppTransferStatus) {}
+}
+
+#[derive(Clone)]
+pub struct BluetoothPbapDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothPbapDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothPbapDBus {
+        BluetoothPbapDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "pbap"),
+                interface: String::from("org.chromium.bluetooth.BluetoothPbap"),
+            },
+        }
     }
+}
+
+btstack::bluetooth_pbap_dbus!(client BluetoothPbapDBus);
+
+#[allow(dead_code)]
+struct IBluetoothPbapCallbackDBus {}
 
-    #[dbus_method("ReadDescriptor")]
-    fn read_descriptor(&self, client_id: i32, addr: String, handle: i32, auth_req: i32) {
-        dbus_generated!()
+impl btstack::RPCProxy for IBluetoothPbapCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("WriteDescriptor")]
-    fn write_descriptor(
+#[generate_dbus_exporter(
+    export_bluetooth_pbap_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothPbapCallback"
+)]
+impl IBluetoothPbapCallback for IBluetoothPbapCallbackDBus {
+    #[dbus_method("OnPhonebookPullComplete")]
+    fn on_phonebook_pull_complete(
         &self,
-        client_id: i32,
-        addr: String,
-        handle: i32,
-        auth_req: i32,
-        value: Vec<u8>,
+        request_id: i32,
+        status: PbapPullStatus,
+        phonebook_size: u32,
+        vcards: Vec<String>,
     ) {
-        dbus_generated!()
     }
+}
+
+#[derive(Clone)]
+pub struct BluetoothMapDBus {
+    client_proxy: ClientDBusProxy,
+}
 
-    #[dbus_method("RegisterForNotification")]
-    fn register_for_notification(&self, client_id: i32, addr: String, handle: i32, enable: bool) {
-        dbus_generated!()
+impl BluetoothMapDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothMapDBus {
+        BluetoothMapDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "map"),
+                interface: String::from("org.chromium.bluetooth.BluetoothMap"),
+            },
+        }
     }
+}
+
+btstack::bluetooth_map_dbus!(client BluetoothMapDBus);
+
+#[allow(dead_code)]
+struct IBluetoothMapCallbackDBus {}
 
-    #[dbus_method("BeginReliableWrite")]
-    fn begin_reliable_write(&mut self, client_id: i32, addr: String) {
-        dbus_generated!()
+impl btstack::RPCProxy for IBluetoothMapCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
+
+#[generate_dbus_exporter(
+    export_bluetooth_map_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothMapCallback"
+)]
+impl IBluetoothMapCallback for IBluetoothMapCallbackDBus {
+    #[dbus_method("OnConnectionStateChanged")]
+    fn on_connection_state_changed(&self, device: BluetoothDevice, connected: bool) {}
+    #[dbus_method("OnMessageEvent")]
+    fn on_message_event(&self, device: BluetoothDevice, event: MapEvent) {}
+    #[dbus_method("OnMessagesListing")]
+    fn on_messages_listing(&self, request_id: i32, status: MapStatus, messages: Vec<MapMessage>) {}
+    #[dbus_method("OnMessage")]
+    fn on_message(&self, request_id: i32, status: MapStatus, message: String) {}
+}
 
-    #[dbus_method("EndReliableWrite")]
-    fn end_reliable_write(&mut self, client_id: i32, addr: String, execute: bool) {
-        dbus_generated!()
+#[derive(Clone)]
+pub struct BluetoothFastPairDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothFastPairDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothFastPairDBus {
+        BluetoothFastPairDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "fast_pair"),
+                interface: String::from("org.chromium.bluetooth.BluetoothFastPair"),
+            },
+        }
     }
+}
 
-    #[dbus_method("ReadRemoteRssi")]
-    fn read_remote_rssi(&self, client_id: i32, addr: String) {
-        dbus_generated!()
+btstack::bluetooth_fast_pair_dbus!(client BluetoothFastPairDBus);
+
+#[derive(Clone)]
+pub struct BluetoothProvisioningDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothProvisioningDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothProvisioningDBus {
+        BluetoothProvisioningDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "provisioning"),
+                interface: String::from("org.chromium.bluetooth.BluetoothProvisioning"),
+            },
+        }
     }
+}
+
+btstack::bluetooth_provisioning_dbus!(client BluetoothProvisioningDBus);
+
+#[dbus_propmap(WifiChannel)]
+pub struct WifiChannelDBus {
+    frequency_mhz: u32,
+    bandwidth_mhz: u32,
+}
+
+#[dbus_propmap(CoexState)]
+pub struct CoexStateDBus {
+    wifi_channels: Vec<WifiChannel>,
+    latency_sensitive: bool,
+    bredr_channel_map: Vec<u8>,
+    le_channel_map: Vec<u8>,
+    scan_limit_percent: u32,
+    channel_classification_applied: bool,
+}
 
-    #[dbus_method("ConfigureMtu")]
-    fn configure_mtu(&self, client_id: i32, addr: String, mtu: i32) {
-        dbus_generated!()
+#[derive(Clone)]
+pub struct BluetoothCoexDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothCoexDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothCoexDBus {
+        BluetoothCoexDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "coex"),
+                interface: String::from("org.chromium.bluetooth.Coex"),
+            },
+        }
     }
+}
 
-    #[dbus_method("ConnectionParameterUpdate")]
-    fn connection_parameter_update(
-        &self,
-        client_id: i32,
-        addr: String,
-        min_interval: i32,
-        max_interval: i32,
-        latency: i32,
-        timeout: i32,
-        min_ce_len: u16,
-        max_ce_len: u16,
-    ) {
-        dbus_generated!()
+btstack::bluetooth_coex_dbus!(client BluetoothCoexDBus);
+
+#[derive(Clone)]
+pub struct BluetoothPanDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothPanDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothPanDBus {
+        BluetoothPanDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "pan"),
+                interface: String::from("org.chromium.bluetooth.BluetoothPan"),
+            },
+        }
     }
 }
 
+btstack::bluetooth_pan_dbus!(client BluetoothPanDBus);
+
 #[allow(dead_code)]
-struct IBluetoothGattCallbackDBus {}
+struct IBluetoothPanCallbackDBus {}
 
-impl btstack::RPCProxy for IBluetoothGattCallbackDBus {
+impl btstack::RPCProxy for IBluetoothPanCallbackDBus {
     // Placeholder implementations just to satisfy impl RPCProxy requirements.
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
-    }
     fn get_object_id(&self) -> String {
         String::from("")
     }
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
-    }
-    fn export_for_rpc(self: Box<Self>) {}
 }
 
 #[generate_dbus_exporter(
-    export_bluetooth_gatt_callback_dbus_obj,
-    "org.chromium.bluetooth.BluetoothGattCallback"
+    export_bluetooth_pan_callback_dbus_obj,
+    "org.chromium.bluetooth.BluetoothPanCallback"
 )]
-impl IBluetoothGattCallback for IBluetoothGattCallbackDBus {
-    #[dbus_method("OnClientRegistered")]
-    fn on_client_registered(&self, status: i32, client_id: i32) {}
-
-    #[dbus_method("OnClientConnectionState")]
-    fn on_client_connection_state(
+impl IBluetoothPanCallback for IBluetoothPanCallbackDBus {
+    #[dbus_method("OnConnectionStateChanged")]
+    fn on_connection_state_changed(
         &self,
-        status: i32,
-        client_id: i32,
-        connected: bool,
-        addr: String,
+        device: BluetoothDevice,
+        state: BtPanConnectionState,
+        interface_name: String,
     ) {
     }
+}
 
-    #[dbus_method("OnPhyUpdate")]
-    fn on_phy_update(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {}
+#[dbus_propmap(AdvMonitorPattern)]
+pub struct AdvMonitorPatternDBus {
+    ad_type: u8,
+    offset: u8,
+    content: Vec<u8>,
+}
 
-    #[dbus_method("OnPhyRead")]
-    fn on_phy_read(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {}
+#[dbus_propmap(AdvMonitor)]
+pub struct AdvMonitorDBus {
+    patterns: Vec<AdvMonitorPattern>,
+    rssi_high_threshold: i32,
+    found_timeout: u32,
+    rssi_low_threshold: i32,
+    lost_timeout: u32,
+}
 
-    #[dbus_method("OnSearchComplete")]
-    fn on_search_complete(&self, addr: String, services: Vec<BluetoothGattService>, status: i32) {}
+#[dbus_propmap(Beacon)]
+pub struct BeaconDBus {
+    beacon_type: BeaconType,
+    id: Uuid128Bit,
+    major: u16,
+    minor: u16,
+    tx_power: i32,
+    url: String,
+    battery_voltage: u16,
+    temperature: i32,
+    adv_count: u32,
+    uptime: u32,
+}
 
-    #[dbus_method("OnCharacteristicRead")]
-    fn on_characteristic_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {}
+#[derive(Clone)]
+pub struct BluetoothAdvMonitorDBus {
+    client_proxy: ClientDBusProxy,
+}
 
-    #[dbus_method("OnCharacteristicWrite")]
-    fn on_characteristic_write(&self, addr: String, status: i32, handle: i32) {}
+impl BluetoothAdvMonitorDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothAdvMonitorDBus {
+        BluetoothAdvMonitorDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "adv_monitor"),
+                interface: String::from("org.chromium.bluetooth.BluetoothAdvMonitor"),
+            },
+        }
+    }
+}
 
-    #[dbus_method("OnExecuteWrite")]
-    fn on_execute_write(&self, addr: String, status: i32) {}
+btstack::bluetooth_adv_monitor_dbus!(client BluetoothAdvMonitorDBus);
 
-    #[dbus_method("OnDescriptorRead")]
-    fn on_descriptor_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {}
+#[allow(dead_code)]
+struct IAdvMonitorCallbackDBus {}
 
-    #[dbus_method("OnDescriptorWrite")]
-    fn on_descriptor_write(&self, addr: String, status: i32, handle: i32) {}
+impl btstack::RPCProxy for IAdvMonitorCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
+    }
+}
 
-    #[dbus_method("OnNotify")]
-    fn on_notify(&self, addr: String, handle: i32, value: Vec<u8>) {}
+#[generate_dbus_exporter(
+    export_adv_monitor_callback_dbus_obj,
+    "org.chromium.bluetooth.AdvMonitorCallback"
+)]
+impl IAdvMonitorCallback for IAdvMonitorCallbackDBus {
+    #[dbus_method("OnDeviceFound")]
+    fn on_device_found(&self, monitor_id: u32, device: BluetoothDevice) {}
 
-    #[dbus_method("OnReadRemoteRssi")]
-    fn on_read_remote_rssi(&self, addr: String, rssi: i32, status: i32) {}
+    #[dbus_method("OnDeviceLost")]
+    fn on_device_lost(&self, monitor_id: u32, device: BluetoothDevice) {}
 
-    #[dbus_method("OnConfigureMtu")]
-    fn on_configure_mtu(&self, addr: String, mtu: i32, status: i32) {}
+    #[dbus_method("OnBeaconReceived")]
+    fn on_beacon_received(&self, monitor_id: u32, device: BluetoothDevice, beacon: Beacon) {}
+}
 
-    #[dbus_method("OnConnectionUpdated")]
-    fn on_connection_updated(
+#[derive(Clone)]
+pub struct BluetoothLinkDiagnosticsDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothLinkDiagnosticsDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothLinkDiagnosticsDBus {
+        BluetoothLinkDiagnosticsDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "link_diagnostics"),
+                interface: String::from("org.chromium.bluetooth.LinkDiagnostics"),
+            },
+        }
+    }
+}
+
+btstack::link_diagnostics_dbus!(client BluetoothLinkDiagnosticsDBus);
+
+#[allow(dead_code)]
+struct IBluetoothLinkDiagnosticsCallbackDBus {}
+
+impl btstack::RPCProxy for IBluetoothLinkDiagnosticsCallbackDBus {
+    // Placeholder implementation just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
+    }
+}
+
+#[generate_dbus_exporter(
+    export_link_diagnostics_callback_dbus_obj,
+    "org.chromium.bluetooth.LinkDiagnosticsCallback"
+)]
+impl IBluetoothLinkDiagnosticsCallback for IBluetoothLinkDiagnosticsCallbackDBus {
+    #[dbus_method("OnPingReply")]
+    fn on_ping_reply(&self, device: BluetoothDevice, sequence: u32, rtt_us: u64) {}
+
+    #[dbus_method("OnPingLost")]
+    fn on_ping_lost(&self, device: BluetoothDevice, sequence: u32) {}
+
+    #[dbus_method("OnPingComplete")]
+    fn on_ping_complete(&self, device: BluetoothDevice, stats: PingStats) {}
+
+    #[dbus_method("OnTxPowerReported")]
+    fn on_tx_power_reported(
         &self,
-        addr: String,
-        interval: i32,
-        latency: i32,
-        timeout: i32,
-        status: i32,
+        device: BluetoothDevice,
+        status: BtStatus,
+        report: TxPowerReport,
     ) {
     }
 
-    #[dbus_method("OnServiceChanged")]
-    fn on_service_changed(&self, addr: String) {}
+    #[dbus_method("OnPathLossZoneEntered")]
+    fn on_path_loss_zone_entered(
+        &self,
+        device: BluetoothDevice,
+        zone: PathLossZone,
+        path_loss: u32,
+    ) {
+    }
 }
 
-pub(crate) struct SuspendDBus {
+#[derive(Clone)]
+pub struct BluetoothThroughputTestDBus {
     client_proxy: ClientDBusProxy,
 }
 
-impl SuspendDBus {
-    pub(crate) fn new(conn: Arc<SyncConnection>, index: i32) -> SuspendDBus {
-        SuspendDBus {
+impl BluetoothThroughputTestDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothThroughputTestDBus {
+        BluetoothThroughputTestDBus {
             client_proxy: ClientDBusProxy {
                 conn: conn.clone(),
                 bus_name: String::from("org.chromium.bluetooth"),
-                objpath: make_object_path(index, "suspend"),
-                interface: String::from("org.chromium.bluetooth.Suspend"),
+                objpath: make_object_path(index, "throughput_test"),
+                interface: String::from("org.chromium.bluetooth.ThroughputTest"),
             },
         }
     }
 }
 
-#[generate_dbus_interface_client]
-impl ISuspend for SuspendDBus {
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, _callback: Box<dyn ISuspendCallback + Send>) -> bool {
-        dbus_generated!()
-    }
+btstack::throughput_test_dbus!(client BluetoothThroughputTestDBus);
+
+#[allow(dead_code)]
+struct IBluetoothThroughputTestCallbackDBus {}
 
-    #[dbus_method("UnregisterCallback")]
-    fn unregister_callback(&mut self, _callback_id: u32) -> bool {
-        dbus_generated!()
+impl btstack::RPCProxy for IBluetoothThroughputTestCallbackDBus {
+    // Placeholder implementation just to satisfy impl RPCProxy requirements.
+    fn get_object_id(&self) -> String {
+        String::from("")
     }
+}
 
-    #[dbus_method("Suspend")]
-    fn suspend(&self, _suspend_type: SuspendType) -> u32 {
-        dbus_generated!()
+#[generate_dbus_exporter(
+    export_throughput_test_callback_dbus_obj,
+    "org.chromium.bluetooth.ThroughputTestCallback"
+)]
+impl IBluetoothThroughputTestCallback for IBluetoothThroughputTestCallbackDBus {
+    #[dbus_method("OnThroughputTestComplete")]
+    fn on_throughput_test_complete(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        result: ThroughputTestResult,
+    ) {
     }
+}
 
-    #[dbus_method("Resume")]
-    fn resume(&self) -> bool {
-        dbus_generated!()
+#[derive(Clone)]
+pub struct BluetoothDistanceMeasurementDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothDistanceMeasurementDBus {
+    pub fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothDistanceMeasurementDBus {
+        BluetoothDistanceMeasurementDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "distance_measurement"),
+                interface: String::from("org.chromium.bluetooth.DistanceMeasurement"),
+            },
+        }
     }
 }
 
+btstack::distance_measurement_dbus!(client BluetoothDistanceMeasurementDBus);
+
 #[allow(dead_code)]
-struct ISuspendCallbackDBus {}
+struct IBluetoothDistanceMeasurementCallbackDBus {}
 
-impl btstack::RPCProxy for ISuspendCallbackDBus {
-    // Placeholder implementations just to satisfy impl RPCProxy requirements.
-    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-        0
-    }
+impl btstack::RPCProxy for IBluetoothDistanceMeasurementCallbackDBus {
+    // Placeholder implementation just to satisfy impl RPCProxy requirements.
     fn get_object_id(&self) -> String {
         String::from("")
     }
-    fn unregister(&mut self, _id: u32) -> bool {
-        false
-    }
-    fn export_for_rpc(self: Box<Self>) {}
 }
 
 #[generate_dbus_exporter(
-    export_suspend_callback_dbus_obj,
-    "org.chromium.bluetooth.SuspendCallback"
+    export_distance_measurement_callback_dbus_obj,
+    "org.chromium.bluetooth.DistanceMeasurementCallback"
 )]
-impl ISuspendCallback for ISuspendCallbackDBus {
-    #[dbus_method("OnCallbackRegistered")]
-    fn on_callback_registered(&self, callback_id: u32) {}
-    #[dbus_method("OnSuspendReady")]
-    fn on_suspend_ready(&self, suspend_id: u32) {}
-    #[dbus_method("OnResumed")]
-    fn on_resumed(&self, suspend_id: u32) {}
+impl IBluetoothDistanceMeasurementCallback for IBluetoothDistanceMeasurementCallbackDBus {
+    #[dbus_method("OnDistanceMeasurementStarted")]
+    fn on_distance_measurement_started(
+        &self,
+        device: BluetoothDevice,
+        method: DistanceMeasurementMethod,
+    ) {
+    }
+
+    #[dbus_method("OnDistanceMeasurementStopped")]
+    fn on_distance_measurement_stopped(&self, device: BluetoothDevice, status: BtStatus) {}
+
+    #[dbus_method("OnDistanceMeasurement")]
+    fn on_distance_measurement(&self, device: BluetoothDevice, result: DistanceMeasurementResult) {}
 }
@@ -0,0 +1,154 @@
+//! `device show`: everything known about one device in a single view.
+//!
+//! Most of it comes from the adapter's cache through `IBluetooth::get_remote_device_info`. The
+//! client adds what only it knows: the alias it gave the device and the battery level it last
+//! read over GATT.
+
+use bt_topshim::btif::{BtBondState, Uuid128Bit};
+use btstack::bluetooth::{RemoteDeviceInfo, RssiSample};
+use btstack::device_type::DeviceType;
+use btstack::uuid::UuidHelper;
+
+/// What the client knows about a device on top of the adapter's [`RemoteDeviceInfo`].
+#[derive(Debug, Default)]
+pub struct ClientDeviceInfo {
+    pub alias: Option<String>,
+    /// Percentage last read or notified over GATT.
+    pub battery_level: Option<u8>,
+}
+
+// Formats a duration in milliseconds the way people read it, e.g. "1h 2m" or "3.5 s".
+fn format_age(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..=59 => format!("{:.1} s", ms as f64 / 1000.0),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs / 60 % 60),
+    }
+}
+
+// Lists the RSSIs newest first.
+fn format_rssi_history(history: &[RssiSample]) -> String {
+    if history.is_empty() {
+        return String::from("unknown");
+    }
+    history
+        .iter()
+        .rev()
+        .map(|sample| format!("{} dBm ({} ago)", sample.rssi, format_age(sample.age_ms)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn format_uuid(helper: &UuidHelper, uuid: &Uuid128Bit) -> String {
+    match helper.is_known_profile(uuid) {
+        Some(profile) => format!("{} {:?}", UuidHelper::to_string(uuid), profile),
+        None => UuidHelper::to_string(uuid),
+    }
+}
+
+/// Describes a device, one line per fact.
+pub fn describe(info: &RemoteDeviceInfo, client: &ClientDeviceInfo) -> Vec<String> {
+    let device = &info.device;
+    if !info.known {
+        return vec![format!("{} isn't known to the adapter", device.address)];
+    }
+
+    let mut lines = vec![
+        format!("Address: {} ({:?})", device.address, device.address_type),
+        format!("Name: {}", device.name),
+        format!("Alias: {}", info.alias),
+    ];
+    if let Some(alias) = &client.alias {
+        lines.push(format!("Client alias: {}", alias));
+    }
+    lines.push(format!("Type: {:?}, transport {:?}", info.device_type, device.transport));
+    lines.push(format!(
+        "Class: {:#08x} ({:?})",
+        info.class_of_device,
+        DeviceType::from_class_of_device(info.class_of_device)
+    ));
+    lines.push(format!("Bond state: {:?}", BtBondState::from(info.bond_state)));
+
+    if info.connection_state == 0 {
+        lines.push(String::from("Connected: no"));
+    } else {
+        lines.push(format!(
+            "Connected: yes, for {}, role {:?}",
+            format_age(info.connected_ms),
+            info.link_role
+        ));
+        lines.push(format!(
+            "Encryption key size: BR/EDR {}, LE {}",
+            info.bredr_key_size, info.le_key_size
+        ));
+    }
+
+    lines.push(format!("Last seen: {} ago", format_age(info.last_seen_ms)));
+    lines.push(format!("RSSI: {}", format_rssi_history(&info.rssi_history)));
+    lines.push(match client.battery_level {
+        Some(level) => format!("Battery: {}%", level),
+        None => String::from("Battery: unknown"),
+    });
+
+    lines.push(format!("UUIDs: {}", info.uuids.len()));
+    let helper = UuidHelper::new();
+    lines.extend(info.uuids.iter().map(|uuid| format!("  {}", format_uuid(&helper, uuid))));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bt_topshim::btif::{BtAclRole, BtDeviceType, RawAddress};
+    use btstack::bluetooth::BluetoothDevice;
+
+    fn device() -> BluetoothDevice {
+        BluetoothDevice {
+            address: RawAddress::from_string("00:11:22:33:44:55").unwrap(),
+            name: String::from("Headset"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(1500), "1.5 s");
+        assert_eq!(format_age(125_000), "2m 5s");
+        assert_eq!(format_age(3_720_000), "1h 2m");
+    }
+
+    #[test]
+    fn test_describe_unknown_device() {
+        let info = RemoteDeviceInfo::unknown(device());
+        assert_eq!(
+            describe(&info, &ClientDeviceInfo::default()),
+            vec![String::from("00:11:22:33:44:55 isn't known to the adapter")]
+        );
+    }
+
+    #[test]
+    fn test_describe_connected_device() {
+        let info = RemoteDeviceInfo {
+            known: true,
+            device_type: BtDeviceType::Bredr,
+            bond_state: BtBondState::Bonded as u32,
+            rssi_history: vec![
+                RssiSample { rssi: -70, age_ms: 4000 },
+                RssiSample { rssi: -60, age_ms: 1000 },
+            ],
+            connection_state: 1,
+            connected_ms: 61_000,
+            link_role: BtAclRole::Central,
+            bredr_key_size: 16,
+            ..RemoteDeviceInfo::unknown(device())
+        };
+        let client = ClientDeviceInfo { alias: None, battery_level: Some(80) };
+        let lines = describe(&info, &client);
+
+        assert!(lines.contains(&String::from("Bond state: Bonded")));
+        assert!(lines.contains(&String::from("Connected: yes, for 1m 1s, role Central")));
+        assert!(lines.contains(&String::from("RSSI: -60 dBm (1.0 s ago), -70 dBm (4.0 s ago)")));
+        assert!(lines.contains(&String::from("Battery: 80%")));
+    }
+}
@@ -14,7 +14,7 @@ use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
-use crate::console_blue;
+use floss_client::console_blue;
 
 #[derive(Helper)]
 struct BtHelper {
@@ -0,0 +1,60 @@
+//! Entry point for fuzzing the conversion of D-Bus arguments. Only built with the `fuzzing`
+//! feature, see the stack's fuzz/ for the targets.
+//!
+//! The conversions are generated by the same `dbus_projection` macros the daemons use, so this
+//! covers how they handle arguments of an unexpected type or shape.
+
+use dbus::arg::{Get, Iter};
+use dbus::Message;
+use std::sync::Arc;
+
+use bt_topshim::btif::RawAddress;
+use btstack::beacon::Beacon;
+use btstack::bluetooth::{AdapterCapabilities, BluetoothDevice, FoundDeviceInfo};
+use btstack::bluetooth_adv_monitor::AdvMonitor;
+use btstack::bluetooth_gatt::{BluetoothGattService, GattNotification};
+use btstack::bluetooth_map::{MapEvent, MapMessage};
+use btstack::bluetooth_opp::OppTransfer;
+
+use crate::dbus_arg::DBusArg;
+
+// Converts the argument at `args` to `T`. Conversion errors are expected and ignored.
+fn convert<'a, T: DBusArg>(args: &mut Iter<'a>)
+where
+    T::DBusType: Get<'a>,
+{
+    if let Some(arg) = args.get::<T::DBusType>() {
+        let _ = T::from_dbus(arg, None, None, None);
+    }
+}
+
+/// Parses `data` as a marshalled D-Bus message and converts each of its arguments to every
+/// structure the stack passes over D-Bus that could have that D-Bus type.
+pub fn convert_dbus_message(data: &[u8]) {
+    let message = match Message::demarshal(data) {
+        Ok(message) => message,
+        Err(_) => return,
+    };
+
+    let mut args = message.iter_init();
+    loop {
+        convert::<RawAddress>(&mut args);
+        convert::<Arc<[u8]>>(&mut args);
+        convert::<BluetoothDevice>(&mut args);
+        convert::<Vec<BluetoothDevice>>(&mut args);
+        convert::<FoundDeviceInfo>(&mut args);
+        convert::<AdapterCapabilities>(&mut args);
+        convert::<BluetoothGattService>(&mut args);
+        convert::<Vec<BluetoothGattService>>(&mut args);
+        convert::<Vec<GattNotification>>(&mut args);
+        convert::<OppTransfer>(&mut args);
+        convert::<Vec<MapMessage>>(&mut args);
+        convert::<MapEvent>(&mut args);
+        convert::<AdvMonitor>(&mut args);
+        convert::<Beacon>(&mut args);
+
+        if !args.next() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Readable forms of the values of standard GATT characteristics.
+//!
+//! Notifications of characteristics subscribed to with `gatt subscribe` are printed through
+//! [`decode`] when their format is known, instead of as raw bytes.
+
+use std::convert::TryInto;
+
+use bt_topshim::btif::Uuid128Bit;
+
+// The Bluetooth Base UUID, 00000000-0000-1000-8000-00805F9B34FB, which 16-bit UUIDs are
+// shortened from.
+const BASE_UUID: Uuid128Bit = [0, 0, 0, 0, 0, 0, 0x10, 0, 0x80, 0, 0, 0x80, 0x5f, 0x9b, 0x34, 0xfb];
+
+pub const BATTERY_LEVEL: u16 = 0x2a19;
+const TEMPERATURE_MEASUREMENT: u16 = 0x2a1c;
+const HEART_RATE_MEASUREMENT: u16 = 0x2a37;
+const TEMPERATURE: u16 = 0x2a6e;
+
+/// Expands a 16-bit UUID into the full UUID.
+pub fn from_uuid16(uuid16: u16) -> Uuid128Bit {
+    let mut uuid = BASE_UUID;
+    uuid[2..4].copy_from_slice(&uuid16.to_be_bytes());
+    uuid
+}
+
+// Gets the 16-bit form of `uuid`, if it has one.
+fn to_uuid16(uuid: &Uuid128Bit) -> Option<u16> {
+    if uuid[0..2] != [0, 0] || uuid[4..] != BASE_UUID[4..] {
+        return None;
+    }
+    Some(u16::from_be_bytes([uuid[2], uuid[3]]))
+}
+
+/// Gets a readable form of `value` if the characteristic `uuid` has a known format. Malformed
+/// values aren't decoded.
+pub fn decode(uuid: &Uuid128Bit, value: &[u8]) -> Option<String> {
+    match to_uuid16(uuid)? {
+        BATTERY_LEVEL => decode_battery_level(value),
+        TEMPERATURE_MEASUREMENT => decode_temperature_measurement(value),
+        HEART_RATE_MEASUREMENT => decode_heart_rate_measurement(value),
+        TEMPERATURE => decode_temperature(value),
+        _ => None,
+    }
+}
+
+/// Gets the percentage in `value` if the characteristic `uuid` is a Battery Level.
+pub fn battery_level(uuid: &Uuid128Bit, value: &[u8]) -> Option<u8> {
+    match (to_uuid16(uuid)?, value) {
+        (BATTERY_LEVEL, [level]) if *level <= 100 => Some(*level),
+        _ => None,
+    }
+}
+
+fn decode_battery_level(value: &[u8]) -> Option<String> {
+    battery_level(&from_uuid16(BATTERY_LEVEL), value)
+        .map(|level| format!("battery level {}%", level))
+}
+
+// Takes a little-endian u16 off the front of `value`.
+fn take_u16(value: &mut &[u8]) -> Option<u16> {
+    let bytes: &[u8] = value;
+    if bytes.len() < 2 {
+        return None;
+    }
+    *value = &bytes[2..];
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+// Heart Rate Service 3.1: flags, the rate and optional fields the flags tell about.
+fn decode_heart_rate_measurement(value: &[u8]) -> Option<String> {
+    let (&flags, mut rest) = value.split_first()?;
+
+    let rate = if flags & 0x01 != 0 {
+        take_u16(&mut rest)?
+    } else {
+        let (&rate, tail) = rest.split_first()?;
+        rest = tail;
+        rate as u16
+    };
+    let mut text = format!("heart rate {} bpm", rate);
+
+    // The contact status is only meaningful if the sensor supports detecting it.
+    if flags & 0x06 == 0x04 {
+        text.push_str(", no sensor contact");
+    }
+    if flags & 0x08 != 0 {
+        text.push_str(&format!(", energy expended {} kJ", take_u16(&mut rest)?));
+    }
+    if flags & 0x10 != 0 {
+        // RR-intervals are in units of 1/1024 s.
+        let mut intervals = vec![];
+        while !rest.is_empty() {
+            intervals.push(format!("{} ms", take_u16(&mut rest)? as u32 * 1000 / 1024));
+        }
+        text.push_str(&format!(", RR intervals [{}]", intervals.join(", ")));
+    }
+    Some(text)
+}
+
+// Health Thermometer Service 3.1: flags, then the temperature as an IEEE 11073-20601 FLOAT,
+// followed by a time stamp and the temperature type, which aren't decoded.
+fn decode_temperature_measurement(value: &[u8]) -> Option<String> {
+    let (&flags, rest) = value.split_first()?;
+    let raw = u32::from_le_bytes(rest.get(0..4)?.try_into().ok()?);
+    let unit = if flags & 0x01 != 0 { "°F" } else { "°C" };
+
+    // Mantissas from 0x7ffffe to 0x800002 stand for NaN, NRes, +INF, -INF or are reserved.
+    if (0x7ffffe..=0x800002).contains(&(raw & 0xffffff)) {
+        return Some(String::from("temperature unavailable"));
+    }
+    let mantissa = (((raw << 8) as i32) >> 8) as f64;
+    let exponent = (raw >> 24) as i8 as i32;
+    let temperature = if exponent < 0 {
+        mantissa / 10f64.powi(-exponent)
+    } else {
+        mantissa * 10f64.powi(exponent)
+    };
+    Some(format!("temperature {} {}", temperature, unit))
+}
+
+// Environmental Sensing Service: a signed temperature in units of 0.01 °C.
+fn decode_temperature(value: &[u8]) -> Option<String> {
+    match value {
+        [0x00, 0x80] => Some(String::from("temperature unavailable")),
+        [low, high] => {
+            Some(format!("temperature {:.2} °C", i16::from_le_bytes([*low, *high]) as f64 / 100.0))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid16() {
+        let uuid = from_uuid16(HEART_RATE_MEASUREMENT);
+        assert_eq!(uuid[0..4], [0, 0, 0x2a, 0x37]);
+        assert_eq!(to_uuid16(&uuid), Some(HEART_RATE_MEASUREMENT));
+        assert_eq!(to_uuid16(&[1; 16]), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let decode16 = |uuid16, value: &[u8]| decode(&from_uuid16(uuid16), value);
+
+        assert_eq!(decode16(BATTERY_LEVEL, &[87]), Some(String::from("battery level 87%")));
+        assert_eq!(battery_level(&from_uuid16(BATTERY_LEVEL), &[87]), Some(87));
+        assert_eq!(battery_level(&from_uuid16(TEMPERATURE), &[87]), None);
+        assert_eq!(decode16(BATTERY_LEVEL, &[101]), None);
+
+        assert_eq!(
+            decode16(HEART_RATE_MEASUREMENT, &[0x00, 72]),
+            Some(String::from("heart rate 72 bpm"))
+        );
+        assert_eq!(
+            decode16(HEART_RATE_MEASUREMENT, &[0x1d, 0x2c, 0x01, 0x0f, 0x00, 0x00, 0x04]),
+            Some(String::from(
+                "heart rate 300 bpm, no sensor contact, energy expended 15 kJ, \
+                 RR intervals [1000 ms]"
+            ))
+        );
+        assert_eq!(decode16(HEART_RATE_MEASUREMENT, &[0x01, 72]), None);
+
+        // 366 * 10^-1 °C.
+        assert_eq!(
+            decode16(TEMPERATURE_MEASUREMENT, &[0x00, 0x6e, 0x01, 0x00, 0xff]),
+            Some(String::from("temperature 36.6 °C"))
+        );
+        assert_eq!(
+            decode16(TEMPERATURE_MEASUREMENT, &[0x01, 0xff, 0xff, 0x7f, 0x00]),
+            Some(String::from("temperature unavailable"))
+        );
+
+        assert_eq!(
+            decode16(TEMPERATURE, &[0x3c, 0xf6]),
+            Some(String::from("temperature -25.00 °C"))
+        );
+
+        assert_eq!(decode16(0x2a00, &[0x41]), None);
+    }
+}
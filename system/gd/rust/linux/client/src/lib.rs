@@ -0,0 +1,1368 @@
+//! Floss client library.
+//!
+//! Wraps the D-Bus proxies, callback registration and cached adapter state used by btclient so
+//! that other Rust programs can control the Bluetooth stack without going through the REPL.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use dbus::channel::MatchingReceiver;
+use dbus::message::MatchRule;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::BusName;
+use dbus_crossroads::Crossroads;
+use dbus_projection::{DisconnectWatcher, EventStamp};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::callbacks::{
+    BtCallback, BtConnectionCallback, BtDistanceMeasurementCallback, BtGattCallback,
+    BtLinkDiagnosticsCallback, BtLinkPolicyCallback, BtManagerCallback, BtMapCallback,
+    BtMergedDiscoveryCallback, BtOppCallback, BtPanCallback, BtPbapCallback, BtSecurityCallback,
+    BtThroughputTestCallback, SuspendCallback,
+};
+use crate::dbus_iface::{
+    BluetoothAdvMonitorDBus, BluetoothCoexDBus, BluetoothDBus, BluetoothDistanceMeasurementDBus,
+    BluetoothFastPairDBus, BluetoothGattDBus, BluetoothLinkDiagnosticsDBus,
+    BluetoothLinkPolicyDBus, BluetoothManagerDBus, BluetoothMapDBus, BluetoothOppDBus,
+    BluetoothPanDBus, BluetoothPbapDBus, BluetoothProvisioningDBus, BluetoothThroughputTestDBus,
+    SuspendDBus,
+};
+use crate::proximity::{PathLossModel, RssiTracker};
+use crate::state::ClientState;
+use bt_topshim::btif::{BtAclRole, BtStatus, BtTransport, RawAddress, Uuid128Bit};
+use bt_topshim::profiles::pan::BtPanConnectionState;
+use btstack::beacon::Beacon;
+use btstack::bluetooth::{
+    AdapterState, BluetoothDevice, FoundDeviceInfo, IBluetooth, IBluetoothLinkPolicy,
+    PairingRejectedReason, ProfileConnectResult, ProfileConnectionReason, ProfileConnectionState,
+};
+use btstack::bluetooth_gatt::{BluetoothGattService, IBluetoothGatt};
+use btstack::bluetooth_map::{IBluetoothMap, MapEvent, MapMessage, MapStatus};
+use btstack::bluetooth_opp::{IBluetoothOpp, OppTransfer, OppTransferStatus};
+use btstack::bluetooth_pan::IBluetoothPan;
+use btstack::bluetooth_pbap::{IBluetoothPbap, PbapPullStatus};
+use btstack::distance_measurement::{DistanceMeasurementResult, IBluetoothDistanceMeasurement};
+use btstack::link_diagnostics::{
+    IBluetoothLinkDiagnostics, PathLossZone, PingStats, TxPowerReport,
+};
+use btstack::suspend::ISuspend;
+use btstack::throughput_test::{IBluetoothThroughputTest, ThroughputTestResult};
+use btstack::uuid::Profile;
+use manager_service::iface_bluetooth_manager::{IBluetoothManager, API_VERSION};
+
+pub mod callbacks;
+pub mod console;
+mod dbus_arg;
+pub mod dbus_iface;
+pub mod device_report;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod gatt_decode;
+pub mod proximity;
+pub mod requests;
+pub mod state;
+pub mod watch;
+
+/// D-Bus service name of the manager daemon (btmanagerd).
+pub const MANAGER_SERVICE: &str = "org.chromium.bluetooth.Manager";
+
+/// D-Bus service name of the adapter daemon (btadapterd).
+pub const ADAPTER_SERVICE: &str = "org.chromium.bluetooth";
+
+/// App UUID used when registering the client's GATT client. Users of the library registering
+/// GATT clients of their own pick other app UUIDs.
+pub const GATT_CLIENT_APP_UUID: Uuid128Bit = [
+    0x12, 0x34, 0x56, 0x78, 0x12, 0x34, 0x56, 0x78, 0x12, 0x34, 0x56, 0x78, 0x12, 0x34, 0x56, 0x78,
+];
+
+/// Number of events buffered for each subscriber. Subscribers that fall further behind miss the
+/// oldest events.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Events reported by the stack to clients of this library.
+///
+/// Subscribe with [`ClientContext::subscribe_events`] to receive them as a stream.
+#[derive(Clone, Debug)]
+pub enum ClientEvent {
+    /// An HCI device has appeared or disappeared.
+    HciDeviceChanged {
+        hci_interface: i32,
+        present: bool,
+    },
+    /// An HCI device was enabled or disabled.
+    HciEnabledChanged {
+        hci_interface: i32,
+        enabled: bool,
+    },
+    /// All adapters have been stopped following a shutdown request.
+    ShutdownComplete,
+    /// The manager restarted an adapter after it crashed.
+    AdapterRecovered {
+        hci_interface: i32,
+        crash_count: u32,
+    },
+    /// The default adapter has registered its callbacks and is ready to be used.
+    AdapterReady {
+        address: String,
+    },
+    /// The default adapter moved to another lifecycle state.
+    AdapterStateChanged(AdapterState),
+    AddressChanged(String),
+    NameChanged(String),
+    DiscoverableChanged(bool),
+    DiscoveringChanged(bool),
+    /// The number of remote devices with an ACL connection changed.
+    ConnectedCountChanged(u32),
+    DeviceFound(BluetoothDevice, FoundDeviceInfo),
+    DeviceCleared(BluetoothDevice),
+    /// A discovery session found a device, or found it again after losing it.
+    SessionDeviceFound {
+        session_id: u32,
+        device: BluetoothDevice,
+    },
+    /// A discovery session hasn't seen a device it found for its lost timeout.
+    SessionDeviceLost {
+        session_id: u32,
+        device: BluetoothDevice,
+    },
+    /// A discovery session ended because the adapter turned off.
+    DiscoverySessionEnded(u32),
+    /// A name request for a device completed, with the name in `device` if `success`.
+    RemoteNameFetched {
+        device: BluetoothDevice,
+        success: bool,
+    },
+    PinRequest {
+        device: BluetoothDevice,
+        cod: u32,
+        min_16_digit: bool,
+    },
+    /// A keyboard is to be given a PIN by the user.
+    PinDisplay {
+        device: BluetoothDevice,
+        pincode: String,
+    },
+    /// The security policy refused to pair with a device.
+    PairingRejected {
+        device: BluetoothDevice,
+        reason: PairingRejectedReason,
+    },
+    /// A link was dropped, or an LE pairing failed, over a too short encryption key.
+    EncryptionKeySizeRejected {
+        device: BluetoothDevice,
+        transport: BtTransport,
+        key_size: u8,
+    },
+    AuthenticationFailed {
+        device: BluetoothDevice,
+        hci_reason: u8,
+    },
+    EncryptionChanged {
+        device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+        encrypted: bool,
+    },
+    EncryptionKeyRefreshed {
+        device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+    },
+    /// A device using the address of a bonded device failed to use the key of the bond.
+    AddressSpoofingSuspected {
+        device: BluetoothDevice,
+        transport: BtTransport,
+    },
+    /// The link from a device that isn't bonded was dropped under the bonded only policy.
+    ConnectionRejected {
+        device: BluetoothDevice,
+        transport: BtTransport,
+    },
+    BondStateChanged {
+        status: u32,
+        address: String,
+        state: u32,
+    },
+    DeviceConnected(BluetoothDevice),
+    DeviceDisconnected(BluetoothDevice),
+    ProfileConnectionStateChanged {
+        device: BluetoothDevice,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    },
+    /// The stack is done connecting the profiles of a device.
+    ConnectAllComplete {
+        device: BluetoothDevice,
+        results: Vec<ProfileConnectResult>,
+    },
+    RoleChanged {
+        device: BluetoothDevice,
+        role: BtAclRole,
+        status: BtStatus,
+    },
+    GattClientRegistered {
+        app_uuid: Uuid128Bit,
+        status: i32,
+        client_id: i32,
+    },
+    GattClientConnectionState {
+        status: i32,
+        client_id: i32,
+        connected: bool,
+        addr: RawAddress,
+    },
+    GattNotify {
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        value: Arc<[u8]>,
+    },
+    GattSearchComplete {
+        client_id: i32,
+        addr: RawAddress,
+        status: i32,
+        services: Vec<BluetoothGattService>,
+    },
+    GattCharacteristicRead {
+        client_id: i32,
+        addr: RawAddress,
+        status: i32,
+        handle: i32,
+        value: Arc<[u8]>,
+    },
+    GattCharacteristicWrite {
+        client_id: i32,
+        addr: RawAddress,
+        status: i32,
+        handle: i32,
+    },
+    GattDescriptorWrite {
+        client_id: i32,
+        addr: RawAddress,
+        status: i32,
+        handle: i32,
+    },
+    GattExecuteWrite {
+        client_id: i32,
+        addr: RawAddress,
+        status: i32,
+    },
+    GattRemoteRssiRead {
+        client_id: i32,
+        addr: RawAddress,
+        rssi: i32,
+        status: i32,
+    },
+    /// The remote server says its services changed, and they are discovered again.
+    GattServiceChanged {
+        client_id: i32,
+        addr: RawAddress,
+    },
+    /// A remote device wants to push an object and waits for it to be accepted.
+    OppIncomingTransfer(OppTransfer),
+    OppTransferComplete {
+        transfer_id: i32,
+        status: OppTransferStatus,
+    },
+    PhonebookPullComplete {
+        request_id: i32,
+        status: PbapPullStatus,
+        vcards: Vec<String>,
+    },
+    MapMessageEvent {
+        address: String,
+        event: MapEvent,
+    },
+    MapMessagesListing {
+        request_id: i32,
+        status: MapStatus,
+        messages: Vec<MapMessage>,
+    },
+    MapMessage {
+        request_id: i32,
+        status: MapStatus,
+        message: String,
+    },
+    PanConnectionState {
+        address: String,
+        state: BtPanConnectionState,
+        interface_name: String,
+    },
+    AdvMonitorDeviceFound {
+        monitor_id: u32,
+        device: BluetoothDevice,
+    },
+    AdvMonitorDeviceLost {
+        monitor_id: u32,
+        device: BluetoothDevice,
+    },
+    AdvMonitorBeacon {
+        monitor_id: u32,
+        device: BluetoothDevice,
+        beacon: Beacon,
+    },
+    /// All the echo requests of a `device ping` were sent.
+    PingComplete {
+        device: BluetoothDevice,
+        stats: PingStats,
+    },
+    /// A TX power level read with `device tx-power`, or a change of one being reported.
+    TxPowerReported {
+        device: BluetoothDevice,
+        status: BtStatus,
+        report: TxPowerReport,
+    },
+    /// The path loss to a device left or entered the range of `device path-loss`.
+    PathLossZoneEntered {
+        device: BluetoothDevice,
+        zone: PathLossZone,
+        path_loss: u32,
+    },
+    /// A `distance start` measured the distance to a device.
+    DistanceMeasured {
+        device: BluetoothDevice,
+        result: DistanceMeasurementResult,
+    },
+    /// The measurements of a `distance start` ended.
+    DistanceMeasurementStopped {
+        device: BluetoothDevice,
+        status: BtStatus,
+    },
+    /// A `throughput start` ended.
+    ThroughputTestComplete {
+        device: BluetoothDevice,
+        status: BtStatus,
+        result: ThroughputTestResult,
+    },
+}
+
+impl ClientEvent {
+    /// Short name of the event kind, as used by the `wait-for` script directive.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientEvent::HciDeviceChanged { .. } => "hci-device-changed",
+            ClientEvent::HciEnabledChanged { .. } => "hci-enabled-changed",
+            ClientEvent::ShutdownComplete => "shutdown-complete",
+            ClientEvent::AdapterRecovered { .. } => "adapter-recovered",
+            ClientEvent::AdapterReady { .. } => "adapter-ready",
+            ClientEvent::AdapterStateChanged(_) => "adapter-state-changed",
+            ClientEvent::AddressChanged(_) => "address-changed",
+            ClientEvent::NameChanged(_) => "name-changed",
+            ClientEvent::DiscoverableChanged(_) => "discoverable-changed",
+            ClientEvent::DiscoveringChanged(_) => "discovering-changed",
+            ClientEvent::ConnectedCountChanged(_) => "connected-count-changed",
+            ClientEvent::DeviceFound(..) => "device-found",
+            ClientEvent::DeviceCleared(_) => "device-cleared",
+            ClientEvent::SessionDeviceFound { .. } => "session-device-found",
+            ClientEvent::SessionDeviceLost { .. } => "session-device-lost",
+            ClientEvent::DiscoverySessionEnded(_) => "discovery-session-ended",
+            ClientEvent::RemoteNameFetched { .. } => "remote-name-fetched",
+            ClientEvent::PinRequest { .. } => "pin-request",
+            ClientEvent::PinDisplay { .. } => "pin-display",
+            ClientEvent::PairingRejected { .. } => "pairing-rejected",
+            ClientEvent::EncryptionKeySizeRejected { .. } => "key-size-rejected",
+            ClientEvent::AuthenticationFailed { .. } => "authentication-failed",
+            ClientEvent::EncryptionChanged { .. } => "encryption-changed",
+            ClientEvent::EncryptionKeyRefreshed { .. } => "encryption-key-refreshed",
+            ClientEvent::AddressSpoofingSuspected { .. } => "address-spoofing-suspected",
+            ClientEvent::ConnectionRejected { .. } => "connection-rejected",
+            ClientEvent::BondStateChanged { .. } => "bond-state-changed",
+            ClientEvent::DeviceConnected(_) => "device-connected",
+            ClientEvent::DeviceDisconnected(_) => "device-disconnected",
+            ClientEvent::ProfileConnectionStateChanged { .. } => "profile-connection-state-changed",
+            ClientEvent::ConnectAllComplete { .. } => "connect-all-complete",
+            ClientEvent::RoleChanged { .. } => "role-changed",
+            ClientEvent::GattClientRegistered { .. } => "gatt-client-registered",
+            ClientEvent::GattClientConnectionState { .. } => "gatt-client-connection-state",
+            ClientEvent::GattNotify { .. } => "gatt-notify",
+            ClientEvent::GattSearchComplete { .. } => "gatt-search-complete",
+            ClientEvent::GattCharacteristicRead { .. } => "gatt-characteristic-read",
+            ClientEvent::GattCharacteristicWrite { .. } => "gatt-characteristic-write",
+            ClientEvent::GattDescriptorWrite { .. } => "gatt-descriptor-write",
+            ClientEvent::GattExecuteWrite { .. } => "gatt-execute-write",
+            ClientEvent::GattRemoteRssiRead { .. } => "gatt-remote-rssi-read",
+            ClientEvent::GattServiceChanged { .. } => "gatt-service-changed",
+            ClientEvent::OppIncomingTransfer(_) => "opp-incoming-transfer",
+            ClientEvent::OppTransferComplete { .. } => "opp-transfer-complete",
+            ClientEvent::PhonebookPullComplete { .. } => "phonebook-pull-complete",
+            ClientEvent::MapMessageEvent { .. } => "map-message-event",
+            ClientEvent::MapMessagesListing { .. } => "map-messages-listing",
+            ClientEvent::MapMessage { .. } => "map-message",
+            ClientEvent::PanConnectionState { .. } => "pan-connection-state",
+            ClientEvent::AdvMonitorDeviceFound { .. } => "adv-monitor-device-found",
+            ClientEvent::AdvMonitorDeviceLost { .. } => "adv-monitor-device-lost",
+            ClientEvent::AdvMonitorBeacon { .. } => "adv-monitor-beacon",
+            ClientEvent::PingComplete { .. } => "ping-complete",
+            ClientEvent::TxPowerReported { .. } => "tx-power-reported",
+            ClientEvent::PathLossZoneEntered { .. } => "path-loss-zone-entered",
+            ClientEvent::DistanceMeasured { .. } => "distance-measured",
+            ClientEvent::DistanceMeasurementStopped { .. } => "distance-measurement-stopped",
+            ClientEvent::ThroughputTestComplete { .. } => "throughput-test-complete",
+        }
+    }
+}
+
+/// Groups of events that the client's callbacks print, which can be muted separately.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EventCategory {
+    /// HCI devices appearing, being enabled and shutting down.
+    Manager,
+    /// Changes to the adapter's address, name and discoverability.
+    Adapter,
+    /// Discovery sessions and the devices they find.
+    Discovery,
+    /// Pairing requests and bond state changes.
+    Bonding,
+    /// ACL and profile connections and disconnections.
+    Connection,
+    /// GATT client events.
+    Gatt,
+    /// Object push requests and transfer progress.
+    Opp,
+    /// Phone book pulls.
+    Pbap,
+    /// Message access sessions, message events and listings.
+    Map,
+    /// PAN connections and the network interface they use.
+    Pan,
+    /// Devices found and lost by advertisement monitors.
+    AdvMonitor,
+    /// Authentication failures, encryption changes and suspected address spoofing.
+    Security,
+}
+
+impl EventCategory {
+    pub const ALL: [EventCategory; 12] = [
+        EventCategory::Manager,
+        EventCategory::Adapter,
+        EventCategory::Discovery,
+        EventCategory::Bonding,
+        EventCategory::Connection,
+        EventCategory::Gatt,
+        EventCategory::Opp,
+        EventCategory::Pbap,
+        EventCategory::Map,
+        EventCategory::Pan,
+        EventCategory::AdvMonitor,
+        EventCategory::Security,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventCategory::Manager => "manager",
+            EventCategory::Adapter => "adapter",
+            EventCategory::Discovery => "discovery",
+            EventCategory::Bonding => "bonding",
+            EventCategory::Connection => "connection",
+            EventCategory::Gatt => "gatt",
+            EventCategory::Opp => "opp",
+            EventCategory::Pbap => "pbap",
+            EventCategory::Map => "map",
+            EventCategory::Pan => "pan",
+            EventCategory::AdvMonitor => "adv-monitor",
+            EventCategory::Security => "security",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EventCategory> {
+        EventCategory::ALL.iter().find(|c| c.name() == name).cloned()
+    }
+}
+
+/// Context structure for the client. Used to keep track details about the active adapter and its
+/// state.
+pub struct ClientContext {
+    /// List of adapters and whether they are enabled.
+    pub adapters: HashMap<i32, bool>,
+
+    // TODO(abps) - Change once we have multi-adapter support.
+    /// The default adapter is also the active adapter. Defaults to 0.
+    pub default_adapter: i32,
+
+    /// Adapter chosen with `adapter select`, which becomes the default one in the next session.
+    pub selected_adapter: i32,
+
+    /// Current adapter is enabled?
+    pub enabled: bool,
+
+    /// Current adapter is ready to be used?
+    pub adapter_ready: bool,
+
+    /// Lifecycle state last reported by the current adapter.
+    pub adapter_state: AdapterState,
+
+    /// Current adapter address if known.
+    pub adapter_address: Option<String>,
+
+    /// Currently active bonding attempt. If it is not none, we are currently attempting to bond
+    /// this device.
+    pub bonding_attempt: Option<BluetoothDevice>,
+
+    /// PINs left to try for the device being bonded, once it asked for a PIN it can neither show
+    /// nor take from the user.
+    pub default_pins: Option<Vec<String>>,
+
+    /// Device waiting for the user to enter a PIN.
+    pub pin_request: Option<BluetoothDevice>,
+
+    /// Is adapter discovering?
+    pub discovering_state: bool,
+
+    /// Devices found in current discovery session. List should be cleared when a new discovery
+    /// session starts so that previous results don't pollute current search.
+    pub found_devices: HashMap<RawAddress, BluetoothDevice>,
+
+    /// Names given to devices with `device alias`, usable wherever commands take an address.
+    pub aliases: HashMap<String, RawAddress>,
+
+    /// Smoothed RSSI of the found devices, and the model turning it into a distance for
+    /// `list found rssi`.
+    pub found_rssi: RssiTracker,
+    pub path_loss: PathLossModel,
+
+    /// Devices currently connected to the adapter. Seeded from the adapter once it is ready and
+    /// kept up to date by connection callbacks.
+    pub connected_devices: HashMap<RawAddress, BluetoothDevice>,
+
+    /// IDs of the GATT clients registered by this process, by app UUID.
+    pub gatt_clients: HashMap<Uuid128Bit, i32>,
+
+    /// UUIDs of the subscribed characteristics whose notifications are printed decoded, by
+    /// device address and handle.
+    pub decoded_characteristics: HashMap<(RawAddress, i32), Uuid128Bit>,
+
+    /// Tasks printing the events of the devices watched with `device watch`.
+    pub device_watches: HashMap<RawAddress, JoinHandle<()>>,
+
+    /// Battery levels last read or notified over GATT, by device. Only the Battery Level
+    /// characteristics in `decoded_characteristics` are followed.
+    pub battery_levels: HashMap<RawAddress, u8>,
+
+    /// Event categories that callbacks don't print. Events are still delivered to subscribers.
+    pub muted_events: HashSet<EventCategory>,
+
+    /// Proxy for manager interface.
+    pub manager_dbus: BluetoothManagerDBus,
+
+    /// API version of the daemons, or None while the manager can't be reached.
+    pub api_version: Option<u32>,
+
+    /// Proxy for adapter interface. Only exists when the default adapter is enabled.
+    pub adapter_dbus: Option<BluetoothDBus>,
+
+    /// Proxy for link policy interface.
+    pub link_policy_dbus: Option<BluetoothLinkPolicyDBus>,
+
+    /// Proxy for GATT interface.
+    pub gatt_dbus: Option<BluetoothGattDBus>,
+
+    /// Proxy for suspend interface.
+    pub suspend_dbus: Option<SuspendDBus>,
+
+    /// Proxy for object push interface.
+    pub opp_dbus: Option<BluetoothOppDBus>,
+
+    /// Proxy for phone book access interface.
+    pub pbap_dbus: Option<BluetoothPbapDBus>,
+
+    /// Proxy for message access interface.
+    pub map_dbus: Option<BluetoothMapDBus>,
+
+    /// Proxy for PAN interface.
+    pub pan_dbus: Option<BluetoothPanDBus>,
+
+    /// Proxy for link diagnostics interface.
+    pub link_diagnostics_dbus: Option<BluetoothLinkDiagnosticsDBus>,
+
+    /// Proxy for throughput test interface.
+    pub throughput_test_dbus: Option<BluetoothThroughputTestDBus>,
+
+    /// Proxy for distance measurement interface.
+    pub distance_measurement_dbus: Option<BluetoothDistanceMeasurementDBus>,
+
+    /// Proxy for Fast Pair provider interface.
+    pub fast_pair_dbus: Option<BluetoothFastPairDBus>,
+
+    /// Proxy for bond provisioning interface.
+    pub provisioning_dbus: Option<BluetoothProvisioningDBus>,
+
+    /// Proxy for Wi-Fi coexistence interface.
+    pub coex_dbus: Option<BluetoothCoexDBus>,
+
+    /// Proxy for advertisement monitor interface.
+    pub adv_monitor_dbus: Option<BluetoothAdvMonitorDBus>,
+
+    /// Number of advertisement monitor callbacks created, so each gets its own object path.
+    pub adv_monitor_callback_count: u32,
+
+    /// Number of discovery session callbacks created, so each gets its own object path.
+    pub discovery_session_callback_count: u32,
+
+    /// Channel to send actions to take in the foreground
+    fg: mpsc::Sender<ForegroundActions>,
+
+    /// Sender side of the event stream. Receivers are handed out by `subscribe_events`.
+    events: broadcast::Sender<ClientEvent>,
+
+    /// Stamp of the last callback delivery that emitted events.
+    last_event_stamp: Option<EventStamp>,
+
+    /// Internal DBus connection object.
+    pub dbus_connection: Arc<SyncConnection>,
+
+    /// Internal DBus crossroads object.
+    pub dbus_crossroads: Arc<Mutex<Crossroads>>,
+
+    /// Notifies exported callback objects when the service holding them goes away.
+    pub disconnect_watcher: Arc<Mutex<DisconnectWatcher>>,
+
+    /// File the state is saved to between sessions, if it is kept at all.
+    state_path: Option<PathBuf>,
+}
+
+impl ClientContext {
+    pub fn new(
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        tx: mpsc::Sender<ForegroundActions>,
+    ) -> ClientContext {
+        // Manager interface is almost always available but adapter interface
+        // requires that the specific adapter is enabled.
+        let manager_dbus = BluetoothManagerDBus::new(dbus_connection.clone());
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        ClientContext {
+            adapters: HashMap::new(),
+            default_adapter: 0,
+            selected_adapter: 0,
+            enabled: false,
+            adapter_ready: false,
+            adapter_state: AdapterState::Off,
+            adapter_address: None,
+            bonding_attempt: None,
+            default_pins: None,
+            pin_request: None,
+            discovering_state: false,
+            found_devices: HashMap::new(),
+            aliases: HashMap::new(),
+            found_rssi: RssiTracker::new(),
+            path_loss: PathLossModel::default(),
+            connected_devices: HashMap::new(),
+            gatt_clients: HashMap::new(),
+            decoded_characteristics: HashMap::new(),
+            device_watches: HashMap::new(),
+            battery_levels: HashMap::new(),
+            muted_events: HashSet::new(),
+            manager_dbus,
+            api_version: None,
+            adapter_dbus: None,
+            link_policy_dbus: None,
+            gatt_dbus: None,
+            suspend_dbus: None,
+            opp_dbus: None,
+            pbap_dbus: None,
+            map_dbus: None,
+            pan_dbus: None,
+            link_diagnostics_dbus: None,
+            throughput_test_dbus: None,
+            distance_measurement_dbus: None,
+            fast_pair_dbus: None,
+            provisioning_dbus: None,
+            coex_dbus: None,
+            adv_monitor_dbus: None,
+            adv_monitor_callback_count: 0,
+            discovery_session_callback_count: 0,
+            fg: tx,
+            events,
+            last_event_stamp: None,
+            dbus_connection,
+            dbus_crossroads,
+            disconnect_watcher: Arc::new(Mutex::new(DisconnectWatcher::new())),
+            state_path: None,
+        }
+    }
+
+    /// Restores the state saved by the previous session in `path`, and saves it there from now
+    /// on.
+    pub fn load_state(&mut self, path: PathBuf) {
+        match ClientState::load(&path) {
+            Ok(state) => {
+                self.default_adapter = state.default_adapter;
+                self.selected_adapter = state.default_adapter;
+                self.aliases = state.aliases;
+                self.found_devices = state
+                    .found_devices
+                    .into_iter()
+                    .map(|(address, name)| {
+                        (address, BluetoothDevice { address, name, ..Default::default() })
+                    })
+                    .collect();
+            }
+            Err(e) => print_warn!("Can't read the saved state from {}: {}", path.display(), e),
+        }
+        self.state_path = Some(path);
+    }
+
+    /// Saves the found devices, aliases and selected adapter for the next session, if
+    /// `load_state` was called.
+    pub fn save_state(&self) {
+        let path = match &self.state_path {
+            Some(path) => path,
+            None => return,
+        };
+        let state = ClientState {
+            default_adapter: self.selected_adapter,
+            aliases: self.aliases.clone(),
+            found_devices: self
+                .found_devices
+                .iter()
+                .map(|(address, device)| (*address, device.name.clone()))
+                .collect(),
+        };
+        if let Err(e) = state.save(path) {
+            print_warn!("Can't save the state to {}: {}", path.display(), e);
+        }
+    }
+
+    /// Returns the address of the device called `alias` with `device alias`.
+    pub fn resolve_alias(&self, alias: &str) -> Option<RawAddress> {
+        self.aliases.get(alias).cloned()
+    }
+
+    /// Returns a stream of [`ClientEvent`]s emitted from now on. Dropping the receiver
+    /// unsubscribes it.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ClientEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns whether callbacks should print events of `category`.
+    pub fn is_event_shown(&self, category: EventCategory) -> bool {
+        !self.muted_events.contains(&category)
+    }
+
+    pub fn set_event_shown(&mut self, category: EventCategory, shown: bool) {
+        if shown {
+            self.muted_events.remove(&category);
+        } else {
+            self.muted_events.insert(category);
+        }
+    }
+
+    /// Returns the ID of the client's own GATT client, the one registered with
+    /// [`GATT_CLIENT_APP_UUID`], if it is registered.
+    pub fn gatt_client_id(&self) -> Option<i32> {
+        self.gatt_clients.get(&GATT_CLIENT_APP_UUID).copied()
+    }
+
+    // Keeps the battery level carried by `event`, if it is the value of a Battery Level.
+    fn note_battery_level(&mut self, event: &ClientEvent) {
+        let (addr, handle, value) = match event {
+            ClientEvent::GattNotify { addr, handle, value, .. }
+            | ClientEvent::GattCharacteristicRead { addr, handle, value, status: 0, .. } => {
+                (addr, handle, value)
+            }
+            _ => return,
+        };
+        let level = self
+            .decoded_characteristics
+            .get(&(*addr, *handle))
+            .and_then(|uuid| gatt_decode::battery_level(uuid, value));
+        if let Some(level) = level {
+            self.battery_levels.insert(*addr, level);
+        }
+    }
+
+    /// Sends the event to all subscribers.
+    pub(crate) fn emit_event(&mut self, event: ClientEvent) {
+        // Events from the same callback share its stamp, so a gap is only reported once.
+        let stamp = dbus_projection::current_event_stamp();
+        if let Some(stamp) = stamp.filter(|stamp| Some(*stamp) != self.last_event_stamp) {
+            if stamp.missed > 0 {
+                print_warn!("Missed {} events before event #{}", stamp.missed, stamp.sequence);
+            }
+            self.last_event_stamp = Some(stamp);
+        }
+
+        self.note_battery_level(&event);
+
+        // Only fails if nobody is subscribed at the moment.
+        let _ = self.events.send(event);
+    }
+
+    // Sets required values for the adapter when enabling or disabling
+    pub fn set_adapter_enabled(&mut self, hci_interface: i32, enabled: bool) {
+        if self.is_event_shown(EventCategory::Manager) {
+            print_info!("hci{} enabled = {}", hci_interface, enabled);
+        }
+
+        self.adapters.entry(hci_interface).and_modify(|v| *v = enabled).or_insert(enabled);
+
+        // When the default adapter's state is updated, we need to modify a few more things.
+        // Only do this if we're not repeating the previous state.
+        let prev_enabled = self.enabled;
+        let default_adapter = self.default_adapter;
+        if hci_interface == default_adapter && prev_enabled != enabled {
+            self.enabled = enabled;
+            self.adapter_ready = false;
+            if enabled {
+                self.create_adapter_proxy(hci_interface);
+            } else {
+                self.adapter_dbus = None;
+                self.adapter_state = AdapterState::Off;
+                self.connected_devices.clear();
+            }
+        }
+
+        self.emit_event(ClientEvent::HciEnabledChanged { hci_interface, enabled });
+    }
+
+    // Creates adapter proxy, registers callbacks and initializes address.
+    fn create_adapter_proxy(&mut self, idx: i32) {
+        let conn = self.dbus_connection.clone();
+
+        let dbus = BluetoothDBus::new(conn.clone(), idx);
+        self.adapter_dbus = Some(dbus);
+
+        self.link_policy_dbus = Some(BluetoothLinkPolicyDBus::new(conn.clone(), idx));
+
+        let gatt_dbus = BluetoothGattDBus::new(conn.clone(), idx);
+        self.gatt_dbus = Some(gatt_dbus);
+
+        self.suspend_dbus = Some(SuspendDBus::new(conn.clone(), idx));
+
+        self.opp_dbus = Some(BluetoothOppDBus::new(conn.clone(), idx));
+
+        self.pbap_dbus = Some(BluetoothPbapDBus::new(conn.clone(), idx));
+
+        self.map_dbus = Some(BluetoothMapDBus::new(conn.clone(), idx));
+
+        self.pan_dbus = Some(BluetoothPanDBus::new(conn.clone(), idx));
+
+        self.link_diagnostics_dbus = Some(BluetoothLinkDiagnosticsDBus::new(conn.clone(), idx));
+
+        self.throughput_test_dbus = Some(BluetoothThroughputTestDBus::new(conn.clone(), idx));
+
+        self.distance_measurement_dbus =
+            Some(BluetoothDistanceMeasurementDBus::new(conn.clone(), idx));
+
+        self.fast_pair_dbus = Some(BluetoothFastPairDBus::new(conn.clone(), idx));
+
+        self.provisioning_dbus = Some(BluetoothProvisioningDBus::new(conn.clone(), idx));
+
+        self.coex_dbus = Some(BluetoothCoexDBus::new(conn.clone(), idx));
+
+        self.adv_monitor_dbus = Some(BluetoothAdvMonitorDBus::new(conn.clone(), idx));
+
+        // Trigger callback registration in the foreground
+        let fg = self.fg.clone();
+        tokio::spawn(async move {
+            let adapter = String::from(format!("adapter{}", idx));
+            let _ = fg.send(ForegroundActions::RegisterAdapterCallback(adapter)).await;
+        });
+    }
+
+    // Foreground-only: Updates the adapter address.
+    pub fn update_adapter_address(&mut self) -> String {
+        let address = self.adapter_dbus.as_ref().unwrap().get_address();
+        self.adapter_address = Some(address.clone());
+
+        address
+    }
+
+    /// Asks the manager for the API version of the daemons, telling if they differ from ours.
+    pub fn update_api_version(&mut self) {
+        self.api_version = self.manager_dbus.negotiate_api_version();
+        match self.api_version {
+            Some(version) if version < API_VERSION => print_warn!(
+                "Daemon API version {} is older than the client's {}, newer commands are disabled",
+                version,
+                API_VERSION
+            ),
+            Some(version) if version > API_VERSION => print_info!(
+                "Daemon API version {} is newer than the client's {}",
+                version,
+                API_VERSION
+            ),
+            _ => {}
+        }
+    }
+
+    pub fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) {
+        let fg = self.fg.clone();
+        tokio::spawn(async move {
+            let _ = fg.send(ForegroundActions::ConnectAllEnabledProfiles(device)).await;
+        });
+    }
+
+    // Marks cached adapter state as stale when btadapterd goes away. The callbacks registered on
+    // it are gone as well and need to be registered again once the service comes back.
+    fn on_adapter_service_lost(&mut self) {
+        print_info!("Adapter service disconnected");
+        self.adapter_ready = false;
+        self.discovering_state = false;
+        self.found_devices.clear();
+        self.found_rssi.clear();
+        self.connected_devices.clear();
+        self.bonding_attempt = None;
+        self.default_pins = None;
+        self.pin_request = None;
+    }
+
+    pub fn run_callback(&mut self, callback: Box<dyn Fn(Arc<Mutex<ClientContext>>) + Send>) {
+        let fg = self.fg.clone();
+        tokio::spawn(async move {
+            let _ = fg.send(ForegroundActions::RunCallback(callback)).await;
+        });
+    }
+}
+
+/// Actions to take on the foreground loop. This allows us to queue actions in
+/// callbacks that get run in the foreground context.
+pub enum ForegroundActions {
+    ConnectAllEnabledProfiles(BluetoothDevice), // Connect all enabled profiles for this device
+    RunCallback(Box<dyn Fn(Arc<Mutex<ClientContext>>) + Send>), // Run callback in foreground
+    RegisterAdapterCallback(String),            // Register callbacks for this adapter
+}
+
+/// Connects to the D-Bus system bus and sets up Crossroads for receiving callbacks.
+///
+/// Must be called from within a tokio runtime.
+pub fn connect_dbus(
+) -> Result<(Arc<SyncConnection>, Arc<Mutex<Crossroads>>), Box<dyn std::error::Error>> {
+    // Connect to D-Bus system bus.
+    let (resource, conn) = dbus_tokio::connection::new_system_sync()?;
+
+    // The `resource` is a task that should be spawned onto a tokio compatible
+    // reactor ASAP. If the resource ever finishes, we lost connection to D-Bus.
+    tokio::spawn(async {
+        let err = resource.await;
+        panic!("Lost connection to D-Bus: {}", err);
+    });
+
+    // Sets up Crossroads for receiving callbacks.
+    let cr = Arc::new(Mutex::new(Crossroads::new()));
+    cr.lock().unwrap().set_async_support(Some((
+        conn.clone(),
+        Box::new(|x| {
+            tokio::spawn(x);
+        }),
+    )));
+    let cr_clone = cr.clone();
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            cr_clone.lock().unwrap().handle_message(msg, conn).unwrap();
+            true
+        }),
+    );
+
+    Ok((conn, cr))
+}
+
+/// Creates the client context, registers the manager callback and creates the adapter proxies
+/// if the default adapter is already enabled. The state of the previous session is restored
+/// from `state_path`, if given; see [`ClientContext::load_state`].
+///
+/// The returned receiver must be drained with [`handle_foreground_action`] (or
+/// [`run_foreground`]) for adapter callback registration to happen.
+pub async fn create_client(
+    conn: Arc<SyncConnection>,
+    cr: Arc<Mutex<Crossroads>>,
+    state_path: Option<PathBuf>,
+) -> (Arc<Mutex<ClientContext>>, mpsc::Receiver<ForegroundActions>) {
+    // Accept foreground actions with mpsc
+    let (tx, rx) = mpsc::channel::<ForegroundActions>(10);
+
+    // Create the context needed for handling commands
+    let context = Arc::new(Mutex::new(ClientContext::new(conn.clone(), cr.clone(), tx)));
+    if let Some(path) = state_path {
+        context.lock().unwrap().load_state(path);
+    }
+
+    // Check if manager interface is valid. We only print some help text before failing on the
+    // first actual access to the interface (so we can also capture the actual reason the
+    // interface isn't valid).
+    if !context.lock().unwrap().manager_dbus.is_valid() {
+        print_warn!("Bluetooth manager doesn't seem to be working correctly.");
+        print_warn!("Check if service is running.");
+    }
+    context.lock().unwrap().update_api_version();
+
+    register_manager_callback(context.clone());
+
+    // Check if the default adapter is enabled. If yes, we should create the adapter proxy
+    // right away.
+    let default_adapter = context.lock().unwrap().default_adapter;
+    if context.lock().unwrap().manager_dbus.get_adapter_enabled(default_adapter) {
+        context.lock().unwrap().set_adapter_enabled(default_adapter, true);
+    }
+
+    let disconnect_watcher = context.lock().unwrap().disconnect_watcher.clone();
+    disconnect_watcher.lock().unwrap().setup_watch(conn.clone()).await;
+    watch_services(context.clone()).await;
+
+    (context, rx)
+}
+
+/// Registers the manager callback on btmanagerd.
+fn register_manager_callback(context: Arc<Mutex<ClientContext>>) {
+    let dbus_connection = context.lock().unwrap().dbus_connection.clone();
+    let dbus_crossroads = context.lock().unwrap().dbus_crossroads.clone();
+    let disconnect_watcher = context.lock().unwrap().disconnect_watcher.clone();
+
+    // TODO: Registering the callback should be done when btmanagerd is ready (detect with
+    // ObjectManager).
+    context.lock().unwrap().manager_dbus.register_callback(Box::new(BtManagerCallback::new(
+        String::from("/org/chromium/bluetooth/client/bluetooth_manager_callback"),
+        context.clone(),
+        dbus_connection.clone(),
+        dbus_crossroads.clone(),
+        disconnect_watcher.clone(),
+    )));
+
+    // Merged discovery came with API version 3.
+    if context.lock().unwrap().api_version.map_or(false, |version| version >= 3) {
+        context.lock().unwrap().manager_dbus.register_merged_discovery_callback(Box::new(
+            BtMergedDiscoveryCallback::new(
+                String::from("/org/chromium/bluetooth/client/merged_discovery_callback"),
+                context.clone(),
+                dbus_connection,
+                dbus_crossroads,
+                disconnect_watcher,
+            ),
+        ));
+    }
+}
+
+/// Registers the client's GATT client. The client id is delivered via the GATT callback.
+pub fn register_gatt_client(context: Arc<Mutex<ClientContext>>) {
+    register_gatt_app(context, GATT_CLIENT_APP_UUID);
+}
+
+/// Registers a GATT client with `app_uuid`, which gets its own callback and connections. Once
+/// registered, its id is in [`ClientContext::gatt_clients`].
+pub fn register_gatt_app(context: Arc<Mutex<ClientContext>>, app_uuid: Uuid128Bit) {
+    let callback = new_gatt_callback(context.clone(), app_uuid);
+    context.lock().unwrap().gatt_dbus.as_mut().unwrap().register_client(app_uuid, callback, false);
+}
+
+// Creates the GATT callback object that is exported when registering the GATT client with
+// `app_uuid`. Each client has its own object path.
+fn new_gatt_callback(
+    context: Arc<Mutex<ClientContext>>,
+    app_uuid: Uuid128Bit,
+) -> Box<BtGattCallback> {
+    let dbus_connection = context.lock().unwrap().dbus_connection.clone();
+    let dbus_crossroads = context.lock().unwrap().dbus_crossroads.clone();
+    let disconnect_watcher = context.lock().unwrap().disconnect_watcher.clone();
+
+    let mut objpath = String::from("/org/chromium/bluetooth/client/bluetooth_gatt_callback");
+    if app_uuid != GATT_CLIENT_APP_UUID {
+        objpath += "_";
+        objpath.extend(app_uuid.iter().map(|b| format!("{:02x}", b)));
+    }
+
+    Box::new(BtGattCallback::new(
+        objpath,
+        app_uuid,
+        context,
+        dbus_connection,
+        dbus_crossroads,
+        disconnect_watcher,
+    ))
+}
+
+/// Watches btmanagerd and btadapterd coming and going on the bus. When either of them restarts,
+/// the callbacks registered on it are registered again and the cached state is refreshed.
+async fn watch_services(context: Arc<Mutex<ClientContext>>) {
+    let conn = context.lock().unwrap().dbus_connection.clone();
+    let fg = context.lock().unwrap().fg.clone();
+    let mr = MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged");
+
+    conn.add_match_no_cb(&mr.match_str()).await.unwrap();
+    conn.start_receive(
+        mr,
+        Box::new(move |msg, _conn| {
+            // The args are "name", "old owner", "new owner".
+            let (name, old, new) = msg.get3::<String, String, String>();
+            let (name, old, new) = match (name, old, new) {
+                (Some(name), Some(old), Some(new)) => (name, old, new),
+                _ => return true,
+            };
+
+            let appeared = old.is_empty() && !new.is_empty();
+            let disappeared = !old.is_empty() && new.is_empty();
+
+            // This runs on the D-Bus dispatch path, so defer anything that touches the context
+            // (and may make blocking D-Bus calls) to the foreground.
+            let action: Box<dyn Fn(Arc<Mutex<ClientContext>>) + Send> =
+                match (name.as_str(), appeared, disappeared) {
+                    (MANAGER_SERVICE, true, _) => Box::new(|context| {
+                        print_info!("Manager service connected");
+                        context.lock().unwrap().update_api_version();
+                        register_manager_callback(context.clone());
+
+                        let adapters =
+                            context.lock().unwrap().manager_dbus.get_available_adapters();
+                        for adapter in adapters {
+                            context
+                                .lock()
+                                .unwrap()
+                                .set_adapter_enabled(adapter.hci_interface, adapter.enabled);
+                        }
+                    }),
+                    (MANAGER_SERVICE, _, true) => Box::new(|context| {
+                        print_info!("Manager service disconnected");
+                        context.lock().unwrap().api_version = None;
+                    }),
+                    (ADAPTER_SERVICE, true, _) => Box::new(|context| {
+                        print_info!("Adapter service connected");
+                        let gatt_apps: Vec<Uuid128Bit> = context
+                            .lock()
+                            .unwrap()
+                            .gatt_clients
+                            .drain()
+                            .map(|(app, _)| app)
+                            .collect();
+                        let (enabled, ready, default_adapter) = {
+                            let c = context.lock().unwrap();
+                            (c.enabled, c.adapter_ready, c.default_adapter)
+                        };
+
+                        // The manager may already have re-enabled the adapter for us.
+                        if !enabled || ready {
+                            return;
+                        }
+
+                        // Recreate the proxies, which registers the adapter callbacks again.
+                        context.lock().unwrap().create_adapter_proxy(default_adapter);
+                        for app_uuid in gatt_apps {
+                            register_gatt_app(context.clone(), app_uuid);
+                        }
+                    }),
+                    (ADAPTER_SERVICE, _, true) => Box::new(|context| {
+                        context.lock().unwrap().on_adapter_service_lost();
+                    }),
+                    _ => return true,
+                };
+
+            let fg = fg.clone();
+            tokio::spawn(async move {
+                let _ = fg.send(ForegroundActions::RunCallback(action)).await;
+            });
+
+            true
+        }),
+    );
+}
+
+/// Runs a single foreground action. Must not be called from a D-Bus callback since the actions
+/// make blocking D-Bus calls.
+pub fn handle_foreground_action(context: Arc<Mutex<ClientContext>>, action: ForegroundActions) {
+    match action {
+        ForegroundActions::ConnectAllEnabledProfiles(device) => {
+            if context.lock().unwrap().adapter_ready {
+                let status = context
+                    .lock()
+                    .unwrap()
+                    .adapter_dbus
+                    .as_mut()
+                    .unwrap()
+                    .connect_all_enabled_profiles(device.clone());
+                if status != BtStatus::Success {
+                    print_warn!("Failed to connect profiles of {}: {:?}", device.address, status);
+                }
+            } else {
+                print_warn!("Adapter isn't ready to connect profiles.");
+            }
+        }
+        ForegroundActions::RunCallback(callback) => {
+            callback(context.clone());
+        }
+        // Once adapter is ready, register callbacks, get the address and mark it as ready
+        ForegroundActions::RegisterAdapterCallback(adapter) => {
+            let cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/bluetooth_callback", adapter);
+            let conn_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/bluetooth_conn_callback", adapter);
+            let link_policy_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/link_policy_callback", adapter);
+            let security_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/security_callback", adapter);
+            let suspend_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/suspend_callback", adapter);
+            let opp_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/opp_callback", adapter);
+            let pbap_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/pbap_callback", adapter);
+            let map_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/map_callback", adapter);
+            let pan_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/pan_callback", adapter);
+            let link_diagnostics_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/link_diagnostics_callback", adapter);
+            let throughput_test_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/throughput_test_callback", adapter);
+            let distance_measurement_cb_objpath: String =
+                format!("/org/chromium/bluetooth/client/{}/distance_measurement_callback", adapter);
+
+            let dbus_connection = context.lock().unwrap().dbus_connection.clone();
+            let dbus_crossroads = context.lock().unwrap().dbus_crossroads.clone();
+            let disconnect_watcher = context.lock().unwrap().disconnect_watcher.clone();
+
+            context.lock().unwrap().adapter_dbus.as_mut().unwrap().register_callback(Box::new(
+                BtCallback::new(
+                    cb_objpath.clone(),
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                ),
+            ));
+            context.lock().unwrap().adapter_dbus.as_mut().unwrap().register_connection_callback(
+                Box::new(BtConnectionCallback::new(
+                    conn_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                )),
+            );
+            context.lock().unwrap().adapter_dbus.as_mut().unwrap().register_security_callback(
+                Box::new(BtSecurityCallback::new(
+                    security_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                )),
+            );
+            context
+                .lock()
+                .unwrap()
+                .link_policy_dbus
+                .as_mut()
+                .unwrap()
+                .register_link_policy_callback(Box::new(BtLinkPolicyCallback::new(
+                    link_policy_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                )));
+
+            // When adapter is ready, Suspend API is also ready. Register as an observer.
+            // TODO(b/224606285): Implement suspend debug utils in btclient.
+            context.lock().unwrap().suspend_dbus.as_mut().unwrap().register_callback(Box::new(
+                SuspendCallback::new(
+                    suspend_cb_objpath,
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                ),
+            ));
+
+            context.lock().unwrap().opp_dbus.as_mut().unwrap().register_callback(Box::new(
+                BtOppCallback::new(
+                    opp_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                ),
+            ));
+
+            context.lock().unwrap().pbap_dbus.as_mut().unwrap().register_callback(Box::new(
+                BtPbapCallback::new(
+                    pbap_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                ),
+            ));
+
+            context.lock().unwrap().map_dbus.as_mut().unwrap().register_callback(Box::new(
+                BtMapCallback::new(
+                    map_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                ),
+            ));
+
+            context.lock().unwrap().pan_dbus.as_mut().unwrap().register_callback(Box::new(
+                BtPanCallback::new(
+                    pan_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                ),
+            ));
+
+            context.lock().unwrap().link_diagnostics_dbus.as_mut().unwrap().register_callback(
+                Box::new(BtLinkDiagnosticsCallback::new(
+                    link_diagnostics_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                )),
+            );
+
+            context.lock().unwrap().throughput_test_dbus.as_mut().unwrap().register_callback(
+                Box::new(BtThroughputTestCallback::new(
+                    throughput_test_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                )),
+            );
+
+            context.lock().unwrap().distance_measurement_dbus.as_mut().unwrap().register_callback(
+                Box::new(BtDistanceMeasurementCallback::new(
+                    distance_measurement_cb_objpath,
+                    context.clone(),
+                    dbus_connection.clone(),
+                    dbus_crossroads.clone(),
+                    disconnect_watcher.clone(),
+                )),
+            );
+
+            let adapter_state =
+                context.lock().unwrap().adapter_dbus.as_ref().unwrap().get_adapter_state();
+            context.lock().unwrap().adapter_state = adapter_state;
+
+            // Connections made before we attached won't be reported by the connection callback.
+            let connected_devices =
+                context.lock().unwrap().adapter_dbus.as_ref().unwrap().get_connected_devices();
+            context.lock().unwrap().connected_devices =
+                connected_devices.into_iter().map(|device| (device.address, device)).collect();
+
+            context.lock().unwrap().adapter_ready = true;
+            let adapter_address = context.lock().unwrap().update_adapter_address();
+            print_info!("Adapter {} is ready", adapter_address);
+            context
+                .lock()
+                .unwrap()
+                .emit_event(ClientEvent::AdapterReady { address: adapter_address });
+        }
+    }
+}
+
+/// Processes foreground actions for as long as the client is alive. Useful for programs that do
+/// not need to interleave their own work with the foreground loop.
+pub async fn run_foreground(
+    context: Arc<Mutex<ClientContext>>,
+    mut rx: mpsc::Receiver<ForegroundActions>,
+) {
+    while let Some(action) = rx.recv().await {
+        handle_foreground_action(context.clone(), action);
+    }
+}
@@ -0,0 +1,107 @@
+//! `--monitor` mode: prints every event the daemons report, without starting the shell.
+//!
+//! All the client's callbacks are registered as usual, along with a GATT client once the adapter
+//! is ready, but instead of the shell's messages each [`ClientEvent`] is printed as one line
+//! with a timestamp, either as text or, with `--json`, as a JSON object:
+//!
+//! ```text
+//! {"timestamp":"2022-03-14T01:02:03.004Z","event":"name-changed","details":"NameChanged(\"x\")"}
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+use floss_client::console::{self, timestamp};
+use floss_client::{print_warn, ClientContext, ClientEvent, EventCategory, ForegroundActions};
+
+// Escapes `text` for use inside a JSON string.
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_event(timestamp: &str, event: &ClientEvent, json: bool) -> String {
+    let details = format!("{:?}", event);
+    if json {
+        format!(
+            "{{\"timestamp\":\"{}\",\"event\":\"{}\",\"details\":\"{}\"}}",
+            timestamp,
+            event.name(),
+            escape_json(&details)
+        )
+    } else {
+        format!("{} {}: {}", timestamp, event.name(), details)
+    }
+}
+
+/// Prints events until the client stops. Runs the foreground actions itself, so `rx` must not be
+/// drained elsewhere.
+pub async fn run_monitor(
+    context: Arc<Mutex<ClientContext>>,
+    mut rx: mpsc::Receiver<ForegroundActions>,
+    json: bool,
+) {
+    let mut events = {
+        let mut context = context.lock().unwrap();
+        // The events are printed here instead, in a single format.
+        for category in EventCategory::ALL.iter() {
+            context.set_event_shown(*category, false);
+        }
+        context.subscribe_events()
+    };
+    console::set_info_enabled(false);
+
+    loop {
+        tokio::select! {
+            action = rx.recv() => match action {
+                Some(action) => floss_client::handle_foreground_action(context.clone(), action),
+                None => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    println!("{}", format_event(&timestamp(), &event, json));
+
+                    // GATT callbacks are only delivered to registered clients.
+                    let registered = context.lock().unwrap().gatt_client_id().is_some();
+                    if let (ClientEvent::AdapterReady { .. }, false) = (&event, registered) {
+                        floss_client::register_gatt_client(context.clone());
+                    }
+                }
+                Err(RecvError::Lagged(count)) => print_warn!("Missed {} events", count),
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_event() {
+        let event = ClientEvent::NameChanged(String::from("a \"b\"\n"));
+        assert_eq!(
+            format_event("T", &event, false),
+            "T name-changed: NameChanged(\"a \\\"b\\\"\\n\")"
+        );
+        assert_eq!(
+            format_event("T", &event, true),
+            "{\"timestamp\":\"T\",\"event\":\"name-changed\",\
+             \"details\":\"NameChanged(\\\"a \\\\\\\"b\\\\\\\"\\\\n\\\")\"}"
+        );
+    }
+}
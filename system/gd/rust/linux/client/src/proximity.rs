@@ -0,0 +1,177 @@
+//! Rough distance of found devices, to pick the one on the desk out of a crowded scan.
+//!
+//! The RSSI of each device is smoothed over the discovery results, since single readings jump
+//! around by several dB, and turned into a distance with a log-distance path loss model. It is
+//! only good enough to tell apart a device next to the adapter from one across the room.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result};
+
+use bt_topshim::btif::RawAddress;
+
+/// Weight of a new reading in the smoothed RSSI.
+const SMOOTHING_FACTOR: f64 = 0.3;
+
+/// Upper bounds, in meters, of the immediate and near buckets.
+const IMMEDIATE_DISTANCE: f64 = 0.5;
+const NEAR_DISTANCE: f64 = 3.0;
+
+/// Path loss exponents accepted by `PathLossModel::new`, from free space (2) to heavily
+/// obstructed indoor spaces.
+const MIN_EXPONENT: f64 = 1.0;
+const MAX_EXPONENT: f64 = 6.0;
+
+/// Log-distance path loss model: the RSSI drops by `10 * exponent` dB for each tenfold distance
+/// from `measured_power`, the RSSI at 1 m.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathLossModel {
+    pub measured_power: i32,
+    pub exponent: f64,
+}
+
+impl Default for PathLossModel {
+    /// A typical phone or peripheral advertising at 0 dBm, in free space.
+    fn default() -> Self {
+        PathLossModel { measured_power: -59, exponent: 2.0 }
+    }
+}
+
+impl PathLossModel {
+    /// Returns None if `exponent` is out of the range of real environments.
+    pub fn new(measured_power: i32, exponent: f64) -> Option<PathLossModel> {
+        if !(MIN_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+            return None;
+        }
+
+        Some(PathLossModel { measured_power, exponent })
+    }
+
+    /// Estimated distance in meters of a device received at `rssi` dBm.
+    pub fn distance(&self, rssi: f64) -> f64 {
+        10f64.powf((self.measured_power as f64 - rssi) / (10.0 * self.exponent))
+    }
+}
+
+/// How close a device is, as far as its RSSI can tell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DistanceBucket {
+    Immediate,
+    Near,
+    Far,
+}
+
+impl DistanceBucket {
+    pub fn from_distance(distance: f64) -> DistanceBucket {
+        if distance < IMMEDIATE_DISTANCE {
+            DistanceBucket::Immediate
+        } else if distance < NEAR_DISTANCE {
+            DistanceBucket::Near
+        } else {
+            DistanceBucket::Far
+        }
+    }
+}
+
+impl Display for DistanceBucket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let name = match self {
+            DistanceBucket::Immediate => "immediate",
+            DistanceBucket::Near => "near",
+            DistanceBucket::Far => "far",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Smoothed RSSI of the devices found in the current discovery session.
+#[derive(Default)]
+pub struct RssiTracker {
+    rssi: HashMap<RawAddress, f64>,
+}
+
+impl RssiTracker {
+    pub fn new() -> RssiTracker {
+        RssiTracker::default()
+    }
+
+    /// Adds a reading of `rssi` dBm for `address`. Readings of 0 mean the RSSI is unknown and are
+    /// ignored.
+    pub fn add_reading(&mut self, address: RawAddress, rssi: i32) {
+        if rssi == 0 {
+            return;
+        }
+
+        let rssi = rssi as f64;
+        self.rssi
+            .entry(address)
+            .and_modify(|smoothed| *smoothed += SMOOTHING_FACTOR * (rssi - *smoothed))
+            .or_insert(rssi);
+    }
+
+    pub fn get(&self, address: &RawAddress) -> Option<f64> {
+        self.rssi.get(address).copied()
+    }
+
+    pub fn remove(&mut self, address: &RawAddress) {
+        self.rssi.remove(address);
+    }
+
+    pub fn clear(&mut self) {
+        self.rssi.clear();
+    }
+
+    /// Sorts `addresses` from the strongest to the weakest smoothed RSSI. Devices without a
+    /// reading go last.
+    pub fn sort_by_rssi(&self, addresses: &mut [RawAddress]) {
+        addresses.sort_by(|a, b| {
+            let rssi = |address| self.get(address).unwrap_or(f64::NEG_INFINITY);
+            rssi(b).partial_cmp(&rssi(a)).unwrap()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(last: u8) -> RawAddress {
+        RawAddress { val: [0, 0, 0, 0, 0, last] }
+    }
+
+    #[test]
+    fn test_path_loss_model() {
+        let model = PathLossModel::default();
+        assert!((model.distance(-59.0) - 1.0).abs() < 1e-9);
+        assert!((model.distance(-79.0) - 10.0).abs() < 1e-9);
+
+        let indoor = PathLossModel::new(-59, 4.0).unwrap();
+        assert!((indoor.distance(-79.0) - 10f64.sqrt()).abs() < 1e-9);
+
+        assert_eq!(PathLossModel::new(-59, 0.5), None);
+        assert_eq!(PathLossModel::new(-59, 7.0), None);
+
+        assert_eq!(DistanceBucket::from_distance(model.distance(-50.0)), DistanceBucket::Immediate);
+        assert_eq!(DistanceBucket::from_distance(model.distance(-60.0)), DistanceBucket::Near);
+        assert_eq!(DistanceBucket::from_distance(model.distance(-75.0)), DistanceBucket::Far);
+    }
+
+    #[test]
+    fn test_smoothing_and_sorting() {
+        let mut tracker = RssiTracker::new();
+        tracker.add_reading(address(1), -80);
+        tracker.add_reading(address(1), -70);
+        assert!((tracker.get(&address(1)).unwrap() - -77.0).abs() < 1e-9);
+
+        // Unknown RSSI doesn't count as a reading.
+        tracker.add_reading(address(1), 0);
+        assert!((tracker.get(&address(1)).unwrap() - -77.0).abs() < 1e-9);
+
+        tracker.add_reading(address(2), -40);
+        let mut addresses = vec![address(3), address(1), address(2)];
+        tracker.sort_by_rssi(&mut addresses);
+        assert_eq!(addresses, vec![address(2), address(1), address(3)]);
+
+        tracker.remove(&address(2));
+        assert_eq!(tracker.get(&address(2)), None);
+    }
+}
@@ -0,0 +1,513 @@
+//! Requests that resolve once the stack reports their outcome.
+//!
+//! Most stack APIs only say whether a request was accepted and report the result later through a
+//! callback. The functions here make the D-Bus call without blocking, then wait on the
+//! [`ClientEvent`] stream for the callback that completes the request, so callers can simply
+//! `await` the result.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+use crate::gatt_decode::from_uuid16;
+use crate::{new_gatt_callback, ClientContext, ClientEvent};
+use bt_topshim::btif::{BtBondState, BtStatus, BtTransport, RawAddress, Uuid128Bit};
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_gatt::{BluetoothGattCharacteristic, GattWriteRequestStatus, GattWriteType};
+use btstack::gatt_status::GattStatusDisplay;
+
+// The Client Characteristic Configuration descriptor, which turns notifications and indications
+// on and off.
+const CLIENT_CHARACTERISTIC_CONFIG: u16 = 0x2902;
+
+/// Why a request didn't complete.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The interface the request needs isn't available, e.g. the adapter isn't enabled.
+    NotReady,
+    /// The stack rejected the request.
+    Rejected(BtStatus),
+    /// The stack accepted the request but reported that it failed with this status.
+    Failed(i32),
+    /// The stack accepted the GATT request but reported that it failed with this GATT status.
+    GattFailed(i32),
+    /// The remote device doesn't have what the request is for.
+    NotFound,
+    /// The stack didn't report the outcome in time.
+    Timeout,
+    /// The client stopped delivering events.
+    Closed,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::NotReady => write!(f, "not ready"),
+            RequestError::Rejected(status) => write!(f, "rejected: {:?}", status),
+            RequestError::Failed(status) => write!(f, "failed with status {}", status),
+            RequestError::GattFailed(status) => {
+                write!(f, "failed with status {}", GattStatusDisplay(*status))
+            }
+            RequestError::NotFound => write!(f, "not found"),
+            RequestError::Timeout => write!(f, "timed out"),
+            RequestError::Closed => write!(f, "client stopped"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+fn check_status(status: BtStatus) -> Result<(), RequestError> {
+    match status {
+        BtStatus::Success => Ok(()),
+        status => Err(RequestError::Rejected(status)),
+    }
+}
+
+fn check_write_status(status: GattWriteRequestStatus) -> Result<(), RequestError> {
+    match status {
+        GattWriteRequestStatus::Success => Ok(()),
+        GattWriteRequestStatus::Fail => Err(RequestError::Rejected(BtStatus::Fail)),
+        GattWriteRequestStatus::Busy => Err(RequestError::Rejected(BtStatus::Busy)),
+    }
+}
+
+/// Waits for the first event for which `matcher` returns a result, for the outcomes the requests
+/// here don't cover.
+pub async fn wait_for<T, F>(
+    events: &mut broadcast::Receiver<ClientEvent>,
+    timeout: Duration,
+    mut matcher: F,
+) -> Result<T, RequestError>
+where
+    F: FnMut(&ClientEvent) -> Option<Result<T, RequestError>>,
+{
+    let wait = async {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Some(result) = matcher(&event) {
+                        return result;
+                    }
+                }
+                // Events we missed can't be recovered, keep looking at the newer ones.
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return Err(RequestError::Closed),
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, wait).await.unwrap_or(Err(RequestError::Timeout))
+}
+
+/// Enables the adapter and resolves once the manager reports it enabled.
+pub async fn enable_adapter(
+    context: Arc<Mutex<ClientContext>>,
+    hci_interface: i32,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    set_adapter_enabled(context, hci_interface, true, timeout).await
+}
+
+/// Disables the adapter and resolves once the manager reports it disabled.
+pub async fn disable_adapter(
+    context: Arc<Mutex<ClientContext>>,
+    hci_interface: i32,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    set_adapter_enabled(context, hci_interface, false, timeout).await
+}
+
+async fn set_adapter_enabled(
+    context: Arc<Mutex<ClientContext>>,
+    hci_interface: i32,
+    enabled: bool,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let (manager, mut events) = {
+        let context = context.lock().unwrap();
+
+        // The manager doesn't report anything if the adapter is already in the requested state.
+        if context.adapters.get(&hci_interface) == Some(&enabled) {
+            return Ok(());
+        }
+
+        (context.manager_dbus.clone(), context.subscribe_events())
+    };
+
+    if enabled {
+        manager.start_async(hci_interface).await;
+    } else {
+        manager.stop_async(hci_interface).await;
+    }
+
+    wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::HciEnabledChanged { hci_interface: hci, enabled: e }
+            if *hci == hci_interface && *e == enabled =>
+        {
+            Some(Ok(()))
+        }
+        _ => None,
+    })
+    .await
+}
+
+/// Starts discovery and resolves once the adapter reports that it is discovering.
+pub async fn start_discovery(
+    context: Arc<Mutex<ClientContext>>,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    set_discovering(context, true, timeout).await
+}
+
+/// Cancels discovery and resolves once the adapter reports that it stopped discovering.
+pub async fn cancel_discovery(
+    context: Arc<Mutex<ClientContext>>,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    set_discovering(context, false, timeout).await
+}
+
+async fn set_discovering(
+    context: Arc<Mutex<ClientContext>>,
+    discovering: bool,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let (adapter, mut events) = {
+        let context = context.lock().unwrap();
+        (context.adapter_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    let status = if discovering {
+        adapter.start_discovery_async().await
+    } else {
+        adapter.cancel_discovery_async().await
+    };
+    check_status(status)?;
+
+    wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::DiscoveringChanged(d) if *d == discovering => Some(Ok(())),
+        _ => None,
+    })
+    .await
+}
+
+/// Bonds with `device` and resolves once bonding finishes. Pairing requests that come up in the
+/// meantime still have to be answered for the bond to complete.
+pub async fn create_bond(
+    context: Arc<Mutex<ClientContext>>,
+    device: BluetoothDevice,
+    transport: BtTransport,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let (adapter, mut events) = {
+        let context = context.lock().unwrap();
+        (context.adapter_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    let address = device.address.to_string();
+    check_status(adapter.create_bond_async(device, transport).await)?;
+
+    wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::BondStateChanged { status, address: a, state } if *a == address => {
+            match BtBondState::from(*state) {
+                BtBondState::Bonded => Some(Ok(())),
+                BtBondState::NotBonded => Some(Err(RequestError::Failed(*status as i32))),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+    .await
+}
+
+/// Registers a GATT client with `app_uuid` and resolves to its client id, right away if it is
+/// already registered. The GATT requests below take this id, so each client of the process has
+/// its own connections.
+pub async fn register_gatt_client(
+    context: Arc<Mutex<ClientContext>>,
+    app_uuid: Uuid128Bit,
+    timeout: Duration,
+) -> Result<i32, RequestError> {
+    let (gatt, mut events) = {
+        let context = context.lock().unwrap();
+        if let Some(client_id) = context.gatt_clients.get(&app_uuid) {
+            return Ok(*client_id);
+        }
+        (context.gatt_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    let callback = new_gatt_callback(context.clone(), app_uuid);
+    check_status(gatt.register_client_async(app_uuid, callback, false).await)?;
+
+    wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::GattClientRegistered { app_uuid: a, status: 0, client_id }
+            if *a == app_uuid =>
+        {
+            Some(Ok(*client_id))
+        }
+        ClientEvent::GattClientRegistered { app_uuid: a, status, .. } if *a == app_uuid => {
+            Some(Err(RequestError::GattFailed(*status)))
+        }
+        _ => None,
+    })
+    .await
+}
+
+/// Connects the GATT client `client_id` to `addr` directly and resolves once connected.
+pub async fn gatt_connect(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    transport: BtTransport,
+    opportunistic: bool,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let (gatt, mut events) = {
+        let context = context.lock().unwrap();
+        (context.gatt_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    check_status(
+        gatt.client_connect_async(client_id, addr, true, transport, opportunistic, 1).await,
+    )?;
+
+    wait_for_gatt_connection_state(&mut events, client_id, addr, true, timeout).await
+}
+
+/// Disconnects the GATT client `client_id` from `addr` and resolves once disconnected.
+pub async fn gatt_disconnect(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let (gatt, mut events) = {
+        let context = context.lock().unwrap();
+        (context.gatt_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    check_status(gatt.client_disconnect_async(client_id, addr).await)?;
+
+    wait_for_gatt_connection_state(&mut events, client_id, addr, false, timeout).await
+}
+
+/// The characteristic a GATT request is for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GattCharacteristic {
+    Handle(i32),
+    /// The first characteristic with this UUID, looked up by the remote server.
+    Uuid(Uuid128Bit),
+}
+
+/// Reads the characteristic `handle` of the connected device `addr` and resolves to its value.
+pub async fn gatt_read_characteristic(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    handle: i32,
+    timeout: Duration,
+) -> Result<Vec<u8>, RequestError> {
+    let (gatt, mut events) = {
+        let context = context.lock().unwrap();
+        (context.gatt_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    check_status(gatt.read_characteristic_async(client_id, addr, handle, 0).await)?;
+
+    wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::GattCharacteristicRead {
+            client_id: id,
+            addr: a,
+            status,
+            handle: h,
+            value,
+        } if *id == client_id && *a == addr && *h == handle => match status {
+            0 => Some(Ok(value.to_vec())),
+            status => Some(Err(RequestError::GattFailed(*status))),
+        },
+        _ => None,
+    })
+    .await
+}
+
+/// Writes `value` to a characteristic of the connected device `addr` and resolves to the handle
+/// written once the device acknowledges it. A prepared write is executed right after it's queued
+/// on the device.
+pub async fn gatt_write_characteristic(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    characteristic: GattCharacteristic,
+    write_type: GattWriteType,
+    value: Vec<u8>,
+    timeout: Duration,
+) -> Result<i32, RequestError> {
+    let (gatt, mut events) = {
+        let context = context.lock().unwrap();
+        (context.gatt_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    let prepared = matches!(write_type, GattWriteType::WritePrepare);
+    let status = match characteristic {
+        GattCharacteristic::Handle(handle) => {
+            gatt.write_characteristic_async(client_id, addr, handle, write_type, 0, value).await
+        }
+        GattCharacteristic::Uuid(uuid) => {
+            gatt.write_using_characteristic_uuid_async(
+                client_id, addr, uuid, 1, 0xffff, write_type, 0, value,
+            )
+            .await
+        }
+    };
+    check_write_status(status)?;
+
+    // The connection handles one request at a time, so the next write reported for the device
+    // is this one.
+    let handle = wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::GattCharacteristicWrite { client_id: id, addr: a, status, handle }
+            if *id == client_id && *a == addr =>
+        {
+            match (*status, characteristic) {
+                (_, GattCharacteristic::Handle(h)) if h != *handle => None,
+                (0, _) => Some(Ok(*handle)),
+                (status, _) => Some(Err(RequestError::GattFailed(status))),
+            }
+        }
+        _ => None,
+    })
+    .await?;
+
+    if prepared {
+        check_status(gatt.end_reliable_write_async(client_id, addr, true).await)?;
+        wait_for(&mut events, timeout, |event| match event {
+            ClientEvent::GattExecuteWrite { client_id: id, addr: a, status }
+                if *id == client_id && *a == addr =>
+            {
+                match status {
+                    0 => Some(Ok(())),
+                    status => Some(Err(RequestError::GattFailed(*status))),
+                }
+            }
+            _ => None,
+        })
+        .await?;
+    }
+
+    Ok(handle)
+}
+
+/// Subscribes to the first characteristic with `uuid` on the connected device `addr` and
+/// resolves to its handle once the device has turned on notifications, or indications if that's
+/// all the characteristic supports. Services are discovered first to find the characteristic.
+pub async fn gatt_subscribe(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    uuid: Uuid128Bit,
+    timeout: Duration,
+) -> Result<i32, RequestError> {
+    set_gatt_subscription(context, client_id, addr, uuid, true, timeout).await
+}
+
+/// Undoes `gatt_subscribe` and resolves to the characteristic's handle once the device has turned
+/// notifications and indications off.
+pub async fn gatt_unsubscribe(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    uuid: Uuid128Bit,
+    timeout: Duration,
+) -> Result<i32, RequestError> {
+    set_gatt_subscription(context, client_id, addr, uuid, false, timeout).await
+}
+
+async fn set_gatt_subscription(
+    context: Arc<Mutex<ClientContext>>,
+    client_id: i32,
+    addr: RawAddress,
+    uuid: Uuid128Bit,
+    subscribe: bool,
+    timeout: Duration,
+) -> Result<i32, RequestError> {
+    let (gatt, mut events) = {
+        let context = context.lock().unwrap();
+        (context.gatt_dbus.clone().ok_or(RequestError::NotReady)?, context.subscribe_events())
+    };
+
+    check_status(gatt.discover_services_async(client_id, addr).await)?;
+    let services = wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::GattSearchComplete { client_id: id, addr: a, status, services }
+            if *id == client_id && *a == addr =>
+        {
+            match status {
+                0 => Some(Ok(services.clone())),
+                status => Some(Err(RequestError::GattFailed(*status))),
+            }
+        }
+        _ => None,
+    })
+    .await?;
+
+    let characteristic = services
+        .iter()
+        .flat_map(|service| service.characteristics.iter())
+        .find(|characteristic| characteristic.uuid == uuid)
+        .ok_or(RequestError::NotFound)?;
+    let config = characteristic
+        .descriptors
+        .iter()
+        .find(|descriptor| descriptor.uuid == from_uuid16(CLIENT_CHARACTERISTIC_CONFIG))
+        .ok_or(RequestError::NotFound)?;
+
+    let properties = characteristic.properties;
+    let value = if !subscribe {
+        vec![0x00, 0x00]
+    } else if properties & BluetoothGattCharacteristic::PROPERTY_NOTIFY != 0 {
+        vec![0x01, 0x00]
+    } else if properties & BluetoothGattCharacteristic::PROPERTY_INDICATE != 0 {
+        vec![0x02, 0x00]
+    } else {
+        return Err(RequestError::NotFound);
+    };
+
+    let handle = characteristic.instance_id;
+    check_status(gatt.register_for_notification_async(client_id, addr, handle, subscribe).await)?;
+    check_status(gatt.write_descriptor_async(client_id, addr, config.instance_id, 0, value).await)?;
+    wait_for(&mut events, timeout, |event| match event {
+        ClientEvent::GattDescriptorWrite { client_id: id, addr: a, status, handle: h }
+            if *id == client_id && *a == addr && *h == config.instance_id =>
+        {
+            match status {
+                0 => Some(Ok(handle)),
+                status => Some(Err(RequestError::GattFailed(*status))),
+            }
+        }
+        _ => None,
+    })
+    .await
+}
+
+async fn wait_for_gatt_connection_state(
+    events: &mut broadcast::Receiver<ClientEvent>,
+    client_id: i32,
+    addr: RawAddress,
+    connected: bool,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    wait_for(events, timeout, |event| match event {
+        ClientEvent::GattClientConnectionState { status, client_id: id, connected: c, addr: a }
+            if *id == client_id && *a == addr =>
+        {
+            if *c == connected {
+                Some(Ok(()))
+            } else {
+                Some(Err(RequestError::GattFailed(*status)))
+            }
+        }
+        _ => None,
+    })
+    .await
+}
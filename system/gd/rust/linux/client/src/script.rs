@@ -0,0 +1,161 @@
+//! Runs btclient commands from a file.
+//!
+//! Each line of a script is a command as it would be typed in the shell. Blank lines and lines
+//! starting with `#` are ignored. `wait-for <event> <timeout secs>` pauses the script until the
+//! stack reports the event (see [`ClientEvent::name`]) or fails it when the timeout expires:
+//!
+//! ```text
+//! adapter enable
+//! wait-for adapter-ready 10
+//! discovery start
+//! wait-for device-found 30
+//! ```
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tokio::sync::mpsc;
+
+use crate::command_handler::CommandHandler;
+use floss_client::{print_info, ClientContext, ClientEvent, ForegroundActions};
+
+#[derive(Debug, PartialEq)]
+enum ScriptLine {
+    Command(String, Vec<String>),
+    WaitFor { event: String, timeout: Duration },
+}
+
+fn parse_script(contents: &str) -> Result<Vec<ScriptLine>, String> {
+    let mut lines = vec![];
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let words: Vec<String> = line.split_whitespace().map(String::from).collect();
+        match words[0].as_str() {
+            "wait-for" => {
+                if words.len() != 3 {
+                    return Err(format!("line {}: usage: wait-for <event> <timeout>", i + 1));
+                }
+
+                let secs = words[2]
+                    .parse::<u64>()
+                    .map_err(|_| format!("line {}: invalid timeout '{}'", i + 1, words[2]))?;
+                lines.push(ScriptLine::WaitFor {
+                    event: words[1].clone(),
+                    timeout: Duration::from_secs(secs),
+                });
+            }
+            "source" => return Err(format!("line {}: scripts can't source other scripts", i + 1)),
+            _ => lines.push(ScriptLine::Command(words[0].clone(), words[1..].to_vec())),
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Runs the script at `path` on the foreground loop. Foreground actions keep being handled while
+/// the script waits for events.
+pub(crate) async fn run_script(
+    handler: &mut CommandHandler,
+    path: &str,
+    context: Arc<Mutex<ClientContext>>,
+    rx: &mut mpsc::Receiver<ForegroundActions>,
+) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let lines = parse_script(&contents).map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut events = context.lock().unwrap().subscribe_events();
+
+    for line in lines {
+        match line {
+            ScriptLine::Command(cmd, args) => {
+                if cmd == "quit" {
+                    break;
+                }
+
+                // Only events caused by this command should satisfy the following wait-for.
+                loop {
+                    match events.try_recv() {
+                        Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                        Err(_) => break,
+                    }
+                }
+
+                print_info!("> {} {}", cmd, args.join(" "));
+                handler.process_cmd_line(&cmd, &args).await;
+            }
+            ScriptLine::WaitFor { event, timeout } => {
+                wait_for_event(&event, timeout, context.clone(), rx, &mut events).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn wait_for_event(
+    event: &str,
+    timeout: Duration,
+    context: Arc<Mutex<ClientContext>>,
+    rx: &mut mpsc::Receiver<ForegroundActions>,
+    events: &mut broadcast::Receiver<ClientEvent>,
+) -> Result<(), String> {
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = &mut deadline => {
+                return Err(format!("Timed out waiting for {}", event));
+            }
+            m = rx.recv() => match m {
+                Some(action) => floss_client::handle_foreground_action(context.clone(), action),
+                None => return Err(String::from("Client stopped")),
+            },
+            e = events.recv() => match e {
+                Ok(e) if e.name() == event => return Ok(()),
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return Err(String::from("Client stopped")),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let lines = parse_script(
+            "# Bond with a device\n\
+             adapter enable\n\
+             \n\
+             wait-for adapter-ready 10\n\
+             bond 11:22:33:44:55:66\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            lines,
+            vec![
+                ScriptLine::Command(String::from("adapter"), vec![String::from("enable")]),
+                ScriptLine::WaitFor {
+                    event: String::from("adapter-ready"),
+                    timeout: Duration::from_secs(10)
+                },
+                ScriptLine::Command(String::from("bond"), vec![String::from("11:22:33:44:55:66")]),
+            ]
+        );
+
+        assert!(parse_script("wait-for adapter-ready").is_err());
+        assert!(parse_script("wait-for adapter-ready soon").is_err());
+        assert!(parse_script("source other.txt").is_err());
+    }
+}
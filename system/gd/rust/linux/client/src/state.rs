@@ -0,0 +1,166 @@
+//! What btclient remembers between sessions.
+//!
+//! The devices found last, the aliases given to devices and the adapter to use are kept in a
+//! small text file, one entry per line, so that a new session can refer to devices found by the
+//! previous one. The file is a cache: entries that can't be read are skipped, and a missing file
+//! is the same as an empty one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bt_topshim::btif::RawAddress;
+
+/// Name of the file under the cache directory.
+const STATE_FILE: &str = "btclient/state";
+
+/// State saved by one session for the next.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientState {
+    /// Adapter to use, as chosen with `adapter select`.
+    pub default_adapter: i32,
+
+    /// Addresses of the devices by alias.
+    pub aliases: HashMap<String, RawAddress>,
+
+    /// Names of the devices found, by address.
+    pub found_devices: HashMap<RawAddress, String>,
+}
+
+/// Where the state is kept: `$XDG_CACHE_HOME/btclient/state`, or `~/.cache/btclient/state`.
+pub fn default_state_path() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join(STATE_FILE)),
+        None => {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join(STATE_FILE))
+        }
+    }
+}
+
+/// Whether `alias` can name a device: a single word that can't be taken for an address.
+pub fn is_valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+        && !alias.chars().any(char::is_whitespace)
+        && RawAddress::from_string(alias).is_none()
+}
+
+impl ClientState {
+    /// Reads the state from `path`, which is empty if the file doesn't exist yet.
+    pub fn load(path: &Path) -> io::Result<ClientState> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(ClientState::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ClientState::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the state to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.serialize())
+    }
+
+    /// Parses lines of the form "adapter <index>", "alias <address> <alias>" and
+    /// "device <address> <name>", where the device name runs to the end of the line.
+    pub fn parse(contents: &str) -> ClientState {
+        let mut state = ClientState::default();
+        for line in contents.lines() {
+            let mut fields = line.splitn(3, ' ');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some("adapter"), Some(index), None) => {
+                    if let Ok(index) = index.parse::<i32>() {
+                        state.default_adapter = index;
+                    }
+                }
+                (Some("alias"), Some(address), Some(alias)) if is_valid_alias(alias) => {
+                    if let Some(address) = RawAddress::from_string(address) {
+                        state.aliases.insert(alias.to_string(), address);
+                    }
+                }
+                (Some("device"), Some(address), name) => {
+                    if let Some(address) = RawAddress::from_string(address) {
+                        state.found_devices.insert(address, name.unwrap_or("").to_string());
+                    }
+                }
+                _ => (),
+            }
+        }
+        state
+    }
+
+    /// Formats the state the way `parse` reads it, sorted so that the file doesn't change
+    /// between saves of the same state.
+    pub fn serialize(&self) -> String {
+        let mut aliases: Vec<String> = self
+            .aliases
+            .iter()
+            .map(|(alias, address)| format!("alias {} {}\n", address.to_string(), alias))
+            .collect();
+        aliases.sort();
+        let mut devices: Vec<String> = self
+            .found_devices
+            .iter()
+            // Names are kept on a single line.
+            .map(|(address, name)| {
+                format!("device {} {}\n", address.to_string(), name.replace('\n', " "))
+            })
+            .collect();
+        devices.sort();
+
+        format!("adapter {}\n{}{}", self.default_adapter, aliases.concat(), devices.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        let keyboard = RawAddress::from_string("00:11:22:33:44:55").unwrap();
+        let phone = RawAddress::from_string("AA:BB:CC:DD:EE:FF").unwrap();
+        let state = ClientState {
+            default_adapter: 1,
+            aliases: HashMap::from([
+                (String::from("kbd"), keyboard),
+                (String::from("phone"), phone),
+            ]),
+            found_devices: HashMap::from([
+                (keyboard, String::from("Keyboard K380")),
+                (phone, String::from("")),
+            ]),
+        };
+
+        assert_eq!(ClientState::parse(&state.serialize()), state);
+    }
+
+    #[test]
+    fn test_state_skips_bad_lines() {
+        let state = ClientState::parse(
+            "adapter one\nalias 00:11:22:33:44:55 two words\nalias 00:11:22:33:44:55 \
+             00:11:22:33:44:66\ndevice 00:11:22:33:44\nunknown line\n\
+             alias 00:11:22:33:44:55 kbd\n",
+        );
+
+        assert_eq!(state.default_adapter, 0);
+        assert_eq!(state.found_devices.len(), 0);
+        assert_eq!(
+            state.aliases,
+            HashMap::from([(
+                String::from("kbd"),
+                RawAddress::from_string("00:11:22:33:44:55").unwrap()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_is_valid_alias() {
+        assert!(is_valid_alias("headphones"));
+        assert!(!is_valid_alias(""));
+        assert!(!is_valid_alias("my headphones"));
+        assert!(!is_valid_alias("00:11:22:33:44:55"));
+    }
+}
@@ -0,0 +1,270 @@
+//! `device watch`: a focused stream of what happens to one device.
+//!
+//! The client's callbacks print the events of all devices, which makes following a single one
+//! hard. A watch picks the events about its device out of the [`ClientEvent`] stream and prints
+//! each as one line: connections and profile states, bonding, the RSSI of the link polled while
+//! it is up, the battery level if the device has a Battery Service, and changes of its GATT
+//! services.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::gatt_decode::{self, BATTERY_LEVEL};
+use crate::requests;
+use crate::{print_info, print_warn, ClientContext, ClientEvent};
+use bt_topshim::btif::{BtBondState, RawAddress};
+use btstack::gatt_status::GattStatusDisplay;
+
+/// How often the RSSI of a connected watched device is read.
+const RSSI_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long subscribing to the battery level may take, service discovery included.
+const BATTERY_SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Picks the events about one device and describes them.
+pub struct DeviceWatch {
+    address: RawAddress,
+    /// Handle of the Battery Level characteristic of the device, once subscribed to.
+    battery_handle: Option<i32>,
+}
+
+impl DeviceWatch {
+    pub fn new(address: RawAddress) -> DeviceWatch {
+        DeviceWatch { address, battery_handle: None }
+    }
+
+    pub fn set_battery_handle(&mut self, handle: i32) {
+        self.battery_handle = Some(handle);
+    }
+
+    fn is_watched(&self, addr: &str) -> bool {
+        RawAddress::from_string(addr) == Some(self.address)
+    }
+
+    /// Returns a line describing `event` if it is about the watched device.
+    pub fn describe(&self, event: &ClientEvent) -> Option<String> {
+        match event {
+            ClientEvent::DeviceConnected(device) if device.address == self.address => {
+                Some(String::from("Connected"))
+            }
+            ClientEvent::DeviceDisconnected(device) if device.address == self.address => {
+                Some(String::from("Disconnected"))
+            }
+            ClientEvent::ProfileConnectionStateChanged { device, profile, state, .. }
+                if device.address == self.address =>
+            {
+                Some(format!("{:?} {:?}", profile, state))
+            }
+            ClientEvent::GattClientConnectionState { addr, connected, status, .. }
+                if *addr == self.address =>
+            {
+                let state = if *connected { "connected" } else { "disconnected" };
+                Some(format!("GATT {}, status = {}", state, GattStatusDisplay(*status)))
+            }
+            ClientEvent::BondStateChanged { address, state, status }
+                if self.is_watched(address) =>
+            {
+                Some(format!("Bond state {:?}, status = {}", BtBondState::from(*state), status))
+            }
+            ClientEvent::GattRemoteRssiRead { addr, rssi, status: 0, .. }
+                if *addr == self.address =>
+            {
+                Some(format!("RSSI {} dBm", rssi))
+            }
+            ClientEvent::GattNotify { addr, handle, value, .. }
+            | ClientEvent::GattCharacteristicRead { addr, handle, value, status: 0, .. }
+                if *addr == self.address && self.battery_handle == Some(*handle) =>
+            {
+                gatt_decode::decode(&gatt_decode::from_uuid16(BATTERY_LEVEL), value)
+            }
+            ClientEvent::GattServiceChanged { addr, .. } if *addr == self.address => {
+                Some(String::from("GATT services changed"))
+            }
+            ClientEvent::GattSearchComplete { addr, status: 0, services, .. }
+                if *addr == self.address =>
+            {
+                Some(format!("{} GATT services discovered", services.len()))
+            }
+            _ => None,
+        }
+    }
+
+    // Whether `event` says the client's GATT client connected to the watched device.
+    fn is_gatt_connected(&self, event: &ClientEvent, client_id: Option<i32>) -> bool {
+        match event {
+            ClientEvent::GattClientConnectionState { client_id: id, addr, connected, status } => {
+                Some(*id) == client_id && *connected && *status == 0 && *addr == self.address
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Starts watching `address`. Returns false if it is already watched.
+pub fn start(context: Arc<Mutex<ClientContext>>, address: RawAddress) -> bool {
+    if context.lock().unwrap().device_watches.contains_key(&address) {
+        return false;
+    }
+
+    let task = tokio::spawn(run(context.clone(), address));
+    context.lock().unwrap().device_watches.insert(address, task);
+    true
+}
+
+/// Stops watching `address`. Returns false if it wasn't watched.
+pub fn stop(context: &Arc<Mutex<ClientContext>>, address: &RawAddress) -> bool {
+    let task: Option<JoinHandle<()>> = context.lock().unwrap().device_watches.remove(address);
+    match task {
+        Some(task) => {
+            task.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+// Subscribes to the battery level of `address` in the background, sending its handle to `tx`.
+fn subscribe_battery(
+    context: &Arc<Mutex<ClientContext>>,
+    address: RawAddress,
+    tx: &mpsc::Sender<i32>,
+) {
+    let client_id = match context.lock().unwrap().gatt_client_id() {
+        Some(client_id) => client_id,
+        None => return,
+    };
+
+    let context = context.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let uuid = gatt_decode::from_uuid16(BATTERY_LEVEL);
+        match requests::gatt_subscribe(
+            context.clone(),
+            client_id,
+            address,
+            uuid,
+            BATTERY_SUBSCRIBE_TIMEOUT,
+        )
+        .await
+        {
+            Ok(handle) => {
+                context.lock().unwrap().decoded_characteristics.insert((address, handle), uuid);
+                let _ = tx.send(handle).await;
+            }
+            Err(e) => print_info!("[{}] No battery level: {}", address, e),
+        }
+    });
+}
+
+// Reads the RSSI of the link to `address`, if there is one. The result comes back as an event.
+async fn poll_rssi(context: &Arc<Mutex<ClientContext>>, address: RawAddress) {
+    let (client_id, gatt) = {
+        let context = context.lock().unwrap();
+        if !context.connected_devices.contains_key(&address) {
+            return;
+        }
+        match (context.gatt_client_id(), context.gatt_dbus.clone()) {
+            (Some(client_id), Some(gatt)) => (client_id, gatt),
+            _ => return,
+        }
+    };
+    let _ = gatt.read_remote_rssi_async(client_id, address).await;
+}
+
+async fn run(context: Arc<Mutex<ClientContext>>, address: RawAddress) {
+    let mut events = context.lock().unwrap().subscribe_events();
+    let mut watch = DeviceWatch::new(address);
+    let (battery_tx, mut battery_rx) = mpsc::channel(1);
+    let mut poll = tokio::time::interval(RSSI_POLL_INTERVAL);
+
+    print_info!("[{}] Watching", address);
+    if context.lock().unwrap().connected_devices.contains_key(&address) {
+        subscribe_battery(&context, address, &battery_tx);
+    }
+
+    loop {
+        tokio::select! {
+            _ = poll.tick() => poll_rssi(&context, address).await,
+            Some(handle) = battery_rx.recv() => {
+                watch.set_battery_handle(handle);
+                print_info!("[{}] Following the battery level", address);
+            }
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Some(line) = watch.describe(&event) {
+                        print_info!("[{}] {}", address, line);
+                    }
+                    let client_id = context.lock().unwrap().gatt_client_id();
+                    if watch.is_gatt_connected(&event, client_id) {
+                        subscribe_battery(&context, address, &battery_tx);
+                    }
+                }
+                Err(RecvError::Lagged(count)) => {
+                    print_warn!("[{}] Missed {} events", address, count);
+                }
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use btstack::bluetooth::BluetoothDevice;
+
+    const WATCHED: &str = "00:11:22:33:44:55";
+    const OTHER: &str = "66:77:88:99:AA:BB";
+
+    fn device(address: &str) -> BluetoothDevice {
+        BluetoothDevice {
+            address: RawAddress::from_string(address).unwrap(),
+            name: String::from("Device"),
+            ..Default::default()
+        }
+    }
+
+    fn watch() -> DeviceWatch {
+        DeviceWatch::new(RawAddress::from_string(WATCHED).unwrap())
+    }
+
+    #[test]
+    fn test_describe_only_the_watched_device() {
+        let watch = watch();
+        assert_eq!(
+            watch.describe(&ClientEvent::DeviceConnected(device(WATCHED))),
+            Some(String::from("Connected"))
+        );
+        assert_eq!(watch.describe(&ClientEvent::DeviceConnected(device(OTHER))), None);
+
+        let rssi = |addr: &str, status| ClientEvent::GattRemoteRssiRead {
+            client_id: 1,
+            addr: RawAddress::from_string(addr).unwrap(),
+            rssi: -60,
+            status,
+        };
+        assert_eq!(watch.describe(&rssi(WATCHED, 0)), Some(String::from("RSSI -60 dBm")));
+        assert_eq!(watch.describe(&rssi(WATCHED, 0x85)), None);
+        assert_eq!(watch.describe(&rssi(OTHER, 0)), None);
+        assert_eq!(watch.describe(&ClientEvent::DiscoveringChanged(true)), None);
+    }
+
+    #[test]
+    fn test_describe_battery_level() {
+        let mut watch = watch();
+        let notify = ClientEvent::GattNotify {
+            client_id: 1,
+            addr: RawAddress::from_string(WATCHED).unwrap(),
+            handle: 42,
+            value: Arc::from(vec![87u8]),
+        };
+        // Notifications only make sense once the handle is known to be the battery level.
+        assert_eq!(watch.describe(&notify), None);
+        watch.set_battery_handle(42);
+        assert_eq!(watch.describe(&notify), Some(String::from("battery level 87%")));
+    }
+}
@@ -9,7 +9,10 @@ use std::path::Path;
 use syn::parse::Parser;
 use syn::punctuated::Punctuated;
 use syn::token::Comma;
-use syn::{Expr, FnArg, ImplItem, ItemImpl, ItemStruct, Meta, Pat, ReturnType, Type};
+use syn::{
+    Attribute, Expr, FnArg, ImplItem, ItemImpl, ItemStruct, ItemTrait, Meta, Pat, ReturnType,
+    TraitItem, Type,
+};
 
 use crate::proc_macro::TokenStream;
 
@@ -31,6 +34,11 @@ fn debug_output_to_file(gen: &proc_macro2::TokenStream, filename: String) {
     file.write_all(gen.to_string().as_bytes()).unwrap();
 }
 
+/// Whether the attribute is `#[dbus_method]`, by any path.
+fn is_dbus_method(attr: &Attribute) -> bool {
+    matches!(attr.path.segments.last(), Some(segment) if segment.ident == "dbus_method")
+}
+
 /// Marks a method to be projected to a D-Bus method and specifies the D-Bus method name.
 #[proc_macro_attribute]
 pub fn dbus_method(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -81,7 +89,7 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
             }
 
             let attr = &method.attrs[0];
-            if !attr.path.get_ident().unwrap().to_string().eq("dbus_method") {
+            if !is_dbus_method(attr) {
                 continue;
             }
 
@@ -103,9 +111,11 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
             let mut make_args = quote! {};
             let mut dbus_input_vars = quote! {};
             let mut dbus_input_types = quote! {};
+            let mut arg_count = 0usize;
 
             for input in method.sig.inputs {
                 if let FnArg::Typed(ref typed) = input {
+                    arg_count += 1;
                     let arg_type = &typed.ty;
                     if let Pat::Ident(pat_ident) = &*typed.pat {
                         let ident = pat_ident.ident.clone();
@@ -170,12 +180,31 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
 
                 let conn_clone = conn.clone();
                 let dc_watcher_clone = disconnect_watcher.clone();
+                let stamp_tracker_clone = stamp_tracker.clone();
                 let handle_method = move |ctx: &mut dbus_crossroads::Context,
                                           obj: &mut #obj_type,
                                           #dbus_input_args |
                       -> Result<(#output_type), dbus_crossroads::MethodErr> {
+                    dbus_projection::trace_incoming_call(ctx.message(), &[#arg_names]);
                     #make_args
-                    let ret = obj.lock().unwrap().#method_name(#method_args);
+                    let stamp = stamp_tracker_clone.lock().unwrap().track(
+                        ctx.path(),
+                        ctx.message(),
+                        #arg_count,
+                    );
+                    let ret = dbus_projection::call_exported_method(
+                        concat!(#dbus_iface_name, ".", #dbus_method_name),
+                        &*obj,
+                        |obj| {
+                            dbus_projection::with_event_stamp(stamp, || {
+                                obj.#method_name(#method_args)
+                            })
+                        },
+                    );
+                    let ret = match ret {
+                        Ok(ret) => ret,
+                        Err(e) => return Err(dbus_crossroads::MethodErr::failed(&e)),
+                    };
                     #ret
                 };
                 ibuilder.method(
@@ -188,6 +217,17 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
         }
     }
 
+    // Callback method calls may come with an event stamp after their arguments.
+    let stamp_tracker = if register_methods.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            let stamp_tracker = std::sync::Arc::new(std::sync::Mutex::new(
+                dbus_projection::EventStampTracker::default(),
+            ));
+        }
+    };
+
     let gen = quote! {
         #ori_item
 
@@ -203,6 +243,7 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
                 cr: &mut dbus_crossroads::Crossroads,
                 disconnect_watcher: std::sync::Arc<std::sync::Mutex<dbus_projection::DisconnectWatcher>>,
             ) -> dbus_crossroads::IfaceToken<#obj_type> {
+                #stamp_tracker
                 cr.register(#dbus_iface_name, |ibuilder| {
                     #register_methods
                 })
@@ -218,6 +259,103 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
     gen.into()
 }
 
+/// Generates a macro that projects an interface trait to D-Bus, so that the D-Bus method names
+/// and the signatures are written once, next to the trait, for both sides.
+///
+/// Example:
+///   `#[dbus_interface(foo_dbus, "org.example.FooInterface")]` on `pub trait IFoo`, with
+///   `#[dbus_method("DoBar")]` on its methods.
+///
+/// This generates a macro called `foo_dbus`, exported from the crate of the trait, that implements
+/// `IFoo` for a struct:
+///   * `foo_dbus!(exporter FooDBus, export_foo_dbus_obj)` with
+///     [`generate_dbus_exporter`](macro@generate_dbus_exporter), for the service;
+///   * `foo_dbus!(client FooDBus)` with
+///     [`generate_dbus_interface_client`](macro@generate_dbus_interface_client), for the clients.
+///
+/// Methods of the trait without `#[dbus_method]` aren't projected. Their implementation, if the
+/// trait has none, is given in braces after the arguments, e.g. `foo_dbus!(client FooDBus, { fn
+/// baz(&self) {} })`.
+///
+/// The implementation is expanded where the macro is called, so the trait and the types of its
+/// method signatures must be in scope there, as well as `DBusArg`.
+#[proc_macro_attribute]
+pub fn dbus_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = Punctuated::<Expr, Comma>::parse_separated_nonempty.parse(attr.clone()).unwrap();
+
+    let macro_ident = if let Expr::Path(p) = &args[0] {
+        p.path.get_ident().unwrap()
+    } else {
+        panic!("macro name must be specified");
+    };
+
+    let dbus_iface_name = if let Expr::Lit(lit) = &args[1] {
+        lit
+    } else {
+        panic!("D-Bus interface name must be specified");
+    };
+
+    let mut ast: ItemTrait = syn::parse(item.clone()).unwrap();
+    let trait_ident = ast.ident.clone();
+
+    let mut methods = quote! {};
+
+    // The trait keeps its methods, without the `#[dbus_method]` that only the generated
+    // implementations need.
+    for item in &mut ast.items {
+        if let TraitItem::Method(method) = item {
+            let attr = match method.attrs.iter().position(is_dbus_method) {
+                Some(index) => method.attrs.remove(index),
+                None => continue,
+            };
+
+            let dbus_method_name = if let Meta::List(meta_list) = attr.parse_meta().unwrap() {
+                meta_list.nested[0].clone()
+            } else {
+                panic!("D-Bus method name must be specified");
+            };
+
+            let sig = &method.sig;
+            methods = quote! {
+                #methods
+
+                #[::dbus_macros::dbus_method(#dbus_method_name)]
+                #sig {
+                    ::dbus_projection::dbus_generated!()
+                }
+            };
+        }
+    }
+
+    let gen = quote! {
+        #ast
+
+        #[macro_export]
+        macro_rules! #macro_ident {
+            (exporter $obj:ident, $export_fn:ident $(, { $($extra:tt)* })?) => {
+                #[::dbus_macros::generate_dbus_exporter($export_fn, #dbus_iface_name)]
+                impl #trait_ident for $obj {
+                    $($($extra)*)?
+
+                    #methods
+                }
+            };
+            (client $obj:ident $(, { $($extra:tt)* })?) => {
+                #[::dbus_macros::generate_dbus_interface_client]
+                impl #trait_ident for $obj {
+                    $($($extra)*)?
+
+                    #methods
+                }
+            };
+        }
+    };
+
+    debug_output_to_file(&gen, format!("out-{}.rs", macro_ident));
+
+    gen.into()
+}
+
 /// Generates a client implementation of a D-Bus interface.
 ///
 /// Example:
@@ -235,6 +373,9 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
 
     let mut methods = quote! {};
 
+    // Non-blocking variants of the D-Bus methods, generated as inherent `<method>_async` methods.
+    let mut async_methods = quote! {};
+
     // Iterate on every methods of a trait impl
     for item in ast.items {
         if let ImplItem::Method(method) = item {
@@ -250,7 +391,7 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
             }
 
             let attr = &method.attrs[0];
-            if !attr.path.get_ident().unwrap().to_string().eq("dbus_method") {
+            if !is_dbus_method(attr) {
                 continue;
             }
 
@@ -268,6 +409,8 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
 
             let mut input_list = quote! {};
 
+            let mut async_inputs = quote! {};
+
             let mut object_conversions = quote! {};
 
             // Iterate on every parameter of a method to build a tuple, e.g.
@@ -278,6 +421,11 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
                     if let Pat::Ident(pat_ident) = &*typed.pat {
                         let ident = pat_ident.ident.clone();
 
+                        async_inputs = quote! {
+                            #async_inputs
+                            #ident: #arg_type,
+                        };
+
                         let is_box = if let Type::Path(type_path) = &**arg_type {
                             if type_path.path.segments[0].ident.to_string().eq("Box") {
                                 true
@@ -344,6 +492,23 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
                 }
             };
 
+            let async_body = match &method.sig.output {
+                ReturnType::Default => {
+                    quote! {
+                        self.client_proxy.async_method_noreturn(#dbus_method_name, #input_tuple).await
+                    }
+                }
+                _ => {
+                    quote! {
+                        let ret: #output_as_dbus_arg::DBusType = self.client_proxy.async_method(
+                            #dbus_method_name,
+                            #input_tuple,
+                        ).await;
+                        #output_as_dbus_arg::from_dbus(ret, None, None, None).unwrap()
+                    }
+                }
+            };
+
             // Assemble the method body. May have object conversions if there is a param that is
             // a proxy object (`Box<dyn>` type).
             let body = quote! {
@@ -365,6 +530,20 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
 
                 #generated_method
             };
+
+            // The async variant only needs a shared reference since it doesn't block on the
+            // proxy, so callers can clone the proxy out of a lock before awaiting.
+            let async_ident = format_ident!("{}_async", sig.ident);
+            let output = &sig.output;
+            async_methods = quote! {
+                #async_methods
+
+                pub async fn #async_ident(&self, #async_inputs) #output {
+                    #object_conversions
+
+                    #async_body
+                }
+            };
         }
     }
 
@@ -372,6 +551,10 @@ pub fn generate_dbus_interface_client(_attr: TokenStream, item: TokenStream) ->
         impl #trait_path for #struct_path {
             #methods
         }
+
+        impl #struct_path {
+            #async_methods
+        }
     };
 
     debug_output_to_file(
@@ -554,7 +737,7 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
 
             let attr = &method.attrs[0];
-            if !attr.path.get_ident().unwrap().to_string().eq("dbus_method") {
+            if !is_dbus_method(attr) {
                 continue;
             }
 
@@ -572,14 +755,31 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
             let method_sig = method.sig.clone();
 
             let mut method_args = quote! {};
+            let mut arg_refs = quote! {};
+            let mut arg_names = quote! {};
+            let mut make_args = quote! {};
 
             for input in method.sig.inputs {
                 if let FnArg::Typed(ref typed) = input {
                     if let Pat::Ident(pat_ident) = &*typed.pat {
                         let ident = pat_ident.ident.clone();
+                        let ident_string = ident.to_string();
+
+                        make_args = quote! {
+                            #make_args
+                            let #ident = DBusArg::to_dbus(#ident).unwrap();
+                        };
 
                         method_args = quote! {
-                            #method_args DBusArg::to_dbus(#ident).unwrap(),
+                            #method_args #ident,
+                        };
+
+                        arg_refs = quote! {
+                            #arg_refs &#ident,
+                        };
+
+                        arg_names = quote! {
+                            #arg_names #ident_string,
                         };
                     }
                 }
@@ -589,9 +789,10 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
                 #method_impls
                 #[allow(unused_variables)]
                 #method_sig {
-                    let remote__ = self.remote.clone();
+                    let remote__ = self.lifetime.remote().clone();
                     let objpath__ = self.objpath.clone();
                     let conn__ = self.conn.clone();
+                    let stamp__ = self.event_stamp.lock().unwrap().take();
                     tokio::spawn(async move {
                         let proxy = dbus::nonblock::Proxy::new(
                             remote__,
@@ -599,11 +800,31 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
                             std::time::Duration::from_secs(2),
                             conn__,
                         );
-                        let future: dbus::nonblock::MethodReply<()> = proxy.method_call(
-                            #dbus_iface_name,
-                            #dbus_method_name,
-                            (#method_args),
-                        );
+                        // The D-Bus values don't all implement Send, so they are gone before the
+                        // await.
+                        let future: dbus::nonblock::MethodReply<()> = {
+                            #make_args
+                            dbus_projection::trace_callback(
+                                &proxy.destination,
+                                &proxy.path,
+                                #dbus_iface_name,
+                                #dbus_method_name,
+                                &[#arg_names],
+                                &(#arg_refs),
+                            );
+                            match stamp__ {
+                                Some((sequence__, timestamp_us__)) => proxy.method_call(
+                                    #dbus_iface_name,
+                                    #dbus_method_name,
+                                    (#method_args sequence__, timestamp_us__,),
+                                ),
+                                None => proxy.method_call(
+                                    #dbus_iface_name,
+                                    #dbus_method_name,
+                                    (#method_args),
+                                ),
+                            }
+                        };
                         let _result = future.await;
                     });
                 }
@@ -615,19 +836,17 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
         #ori_item
 
         impl RPCProxy for #self_ty {
-            fn register_disconnect(&mut self, _disconnect_callback: Box<dyn Fn(u32) + Send>) -> u32 { 0 }
             fn get_object_id(&self) -> String {
                 String::from("")
             }
-            fn unregister(&mut self, _id: u32) -> bool { false }
-            fn export_for_rpc(self: Box<Self>) {}
         }
 
         struct #struct_ident {
             conn: std::sync::Arc<dbus::nonblock::SyncConnection>,
-            remote: dbus::strings::BusName<'static>,
             objpath: Path<'static>,
-            disconnect_watcher: std::sync::Arc<std::sync::Mutex<DisconnectWatcher>>,
+            lifetime: dbus_projection::CallbackLifetime,
+            // Sequence number and timestamp sent along with the next method call.
+            event_stamp: std::sync::Mutex<Option<(u64, u64)>>,
         }
 
         impl #trait_ for #struct_ident {
@@ -636,7 +855,7 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
 
         impl RPCProxy for #struct_ident {
             fn register_disconnect(&mut self, disconnect_callback: Box<dyn Fn(u32) + Send>) -> u32 {
-                return self.disconnect_watcher.lock().unwrap().add(self.remote.clone(), disconnect_callback);
+                self.lifetime.watch(disconnect_callback)
             }
 
             fn get_object_id(&self) -> String {
@@ -644,9 +863,13 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
 
             fn unregister(&mut self, id: u32) -> bool {
-                self.disconnect_watcher.lock().unwrap().remove(self.remote.clone(), id)
+                self.lifetime.unwatch(id)
             }
             fn export_for_rpc(self: Box<Self>) {}
+
+            fn set_event_stamp(&self, sequence: u64, timestamp_us: u64) {
+                *self.event_stamp.lock().unwrap() = Some((sequence, timestamp_us));
+            }
         }
 
         impl DBusArg for Box<dyn #trait_ + Send> {
@@ -660,9 +883,12 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
             ) -> Result<Box<dyn #trait_ + Send>, Box<dyn std::error::Error>> {
                 Ok(Box::new(#struct_ident {
                     conn: conn__.unwrap(),
-                    remote: remote__.unwrap(),
                     objpath: objpath__,
-                    disconnect_watcher: disconnect_watcher__.unwrap(),
+                    lifetime: dbus_projection::CallbackLifetime::new(
+                        remote__.unwrap(),
+                        disconnect_watcher__.unwrap(),
+                    ),
+                    event_stamp: std::sync::Mutex::new(None),
                 }))
             }
 
@@ -678,6 +904,87 @@ pub fn dbus_proxy_obj(attr: TokenStream, item: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// Implements `RPCProxy` for a callback object that a D-Bus client exports to the service.
+///
+/// The struct needs an `objpath: String`, a `lifetime: dbus_projection::CallbackLifetime` naming
+/// the service, and the `dbus_connection` and `dbus_crossroads` to export on. The name of the
+/// function exporting the object is given with `#[rpc_proxy(export = "export_fn")]`, and an
+/// `RPCProxy` trait other than the one in scope with `proxy_trait = "path::RPCProxy"`. The
+/// exported object is dropped once the service leaves the bus, so that it can be exported again.
+#[proc_macro_derive(RPCProxy, attributes(rpc_proxy))]
+pub fn derive_rpc_proxy(item: TokenStream) -> TokenStream {
+    let ast: ItemStruct = syn::parse(item).unwrap();
+    let ident = ast.ident;
+
+    let mut export: Option<syn::Path> = None;
+    let mut proxy_trait: syn::Path = syn::parse_quote!(RPCProxy);
+    for attr in ast.attrs.iter().filter(|attr| attr.path.is_ident("rpc_proxy")) {
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            _ => panic!("rpc_proxy expects a list of name = \"value\" pairs"),
+        };
+        for nested in list.nested {
+            let (name, value) = match nested {
+                syn::NestedMeta::Meta(Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(value),
+                    ..
+                })) => (path, value),
+                _ => panic!("rpc_proxy expects a list of name = \"value\" pairs"),
+            };
+            if name.is_ident("export") {
+                export = Some(value.parse().unwrap());
+            } else if name.is_ident("proxy_trait") {
+                proxy_trait = value.parse().unwrap();
+            } else {
+                panic!("unknown rpc_proxy attribute");
+            }
+        }
+    }
+    let export = export.expect("the export function must be given with #[rpc_proxy(export = ..)]");
+
+    let gen = quote! {
+        impl #proxy_trait for #ident {
+            fn register_disconnect(&mut self, f: Box<dyn Fn(u32) + Send>) -> u32 {
+                self.lifetime.watch(f)
+            }
+
+            fn get_object_id(&self) -> String {
+                self.objpath.clone()
+            }
+
+            fn unregister(&mut self, id: u32) -> bool {
+                self.lifetime.unwatch(id)
+            }
+
+            fn export_for_rpc(mut self: Box<Self>) {
+                let cr = self.dbus_crossroads.clone();
+                let path = dbus::Path::new(self.objpath.clone()).unwrap();
+                self.lifetime.watch(Box::new(move |_| {
+                    cr.lock()
+                        .unwrap()
+                        .remove::<std::sync::Arc<std::sync::Mutex<Box<#ident>>>>(&path);
+                }));
+
+                let cr = self.dbus_crossroads.clone();
+                #export(
+                    self.objpath.clone(),
+                    self.dbus_connection.clone(),
+                    &mut cr.lock().unwrap(),
+                    std::sync::Arc::new(std::sync::Mutex::new(self)),
+                    std::sync::Arc::new(std::sync::Mutex::new(
+                        dbus_projection::DisconnectWatcher::new(),
+                    )),
+                );
+            }
+        }
+    };
+
+    debug_output_to_file(&gen, format!("out-{}.rs", ident.to_string()));
+
+    gen.into()
+}
+
 /// Generates the definition of `DBusArg` trait required for D-Bus projection.
 ///
 /// Due to Rust orphan rule, `DBusArg` trait needs to be defined locally in the crate that wants to
@@ -763,7 +1070,12 @@ pub fn generate_dbus_arg(_item: TokenStream) -> TokenStream {
                 let mut val = iter.next();
                 while !key.is_none() && !val.is_none() {
                     let k = key.unwrap().as_str().unwrap().to_string();
-                    let v = dbus::arg::Variant(val.unwrap().box_clone());
+                    // Values of dictionaries nested in a message are variants already.
+                    let mut v = val.unwrap().box_clone();
+                    if v.arg_type() == dbus::arg::ArgType::Variant {
+                        v = v.as_static_inner(0).unwrap().box_clone();
+                    }
+                    let v = dbus::arg::Variant(v);
                     map.insert(k, v);
                     key = iter.next();
                     val = iter.next();
@@ -793,6 +1105,42 @@ pub fn generate_dbus_arg(_item: TokenStream) -> TokenStream {
             }
         }
 
+        // Likewise for dictionaries, whose D-Bus iterators alternate between keys and values.
+        impl<K, V> RefArgToRust for std::collections::HashMap<K, V>
+        where
+            K: 'static + Eq + std::hash::Hash + RefArgToRust<RustType = K>,
+            V: 'static + RefArgToRust<RustType = V>,
+        {
+            type RustType = std::collections::HashMap<K, V>;
+            fn ref_arg_to_rust(
+                arg: &(dyn dbus::arg::RefArg + 'static),
+                name: String,
+            ) -> Result<Self::RustType, Box<dyn Error>> {
+                let mut map: std::collections::HashMap<K, V> = std::collections::HashMap::new();
+                let mut iter = match arg.as_iter() {
+                    None => {
+                        return Err(Box::new(DBusArgError::new(String::from(format!(
+                            "{} is not iterable",
+                            name,
+                        )))))
+                    }
+                    Some(item) => item,
+                };
+                let mut key = iter.next();
+                let mut val = iter.next();
+                while !key.is_none() && !val.is_none() {
+                    let k = key.unwrap().box_clone();
+                    let k = <K as RefArgToRust>::ref_arg_to_rust(&k, name.clone() + " key")?;
+                    let v = val.unwrap().box_clone();
+                    let v = <V as RefArgToRust>::ref_arg_to_rust(&v, name.clone() + " value")?;
+                    map.insert(k, v);
+                    key = iter.next();
+                    val = iter.next();
+                }
+                return Ok(map);
+            }
+        }
+
         pub(crate) trait DBusArg {
             type DBusType;
 
@@ -835,6 +1183,24 @@ pub fn generate_dbus_arg(_item: TokenStream) -> TokenStream {
             }
         }
 
+        // Shared byte arrays are passed without copying them into a `Vec<u8>` first.
+        impl DBusArg for Arc<[u8]> {
+            type DBusType = dbus_projection::DBusBytes;
+
+            fn from_dbus(
+                data: dbus_projection::DBusBytes,
+                _conn: Option<Arc<dbus::nonblock::SyncConnection>>,
+                _remote: Option<BusName<'static>>,
+                _disconnect_watcher: Option<Arc<Mutex<DisconnectWatcher>>>,
+            ) -> Result<Arc<[u8]>, Box<dyn Error>> {
+                return Ok(data.0);
+            }
+
+            fn to_dbus(data: Arc<[u8]>) -> Result<dbus_projection::DBusBytes, Box<dyn Error>> {
+                return Ok(dbus_projection::DBusBytes(data));
+            }
+        }
+
         impl<T: DBusArg> DBusArg for Vec<T> {
             type DBusType = Vec<T::DBusType>;
 
@@ -866,6 +1232,87 @@ pub fn generate_dbus_arg(_item: TokenStream) -> TokenStream {
                 Ok(list)
             }
         }
+
+        impl<K, V> DBusArg for std::collections::HashMap<K, V>
+        where
+            K: Eq + std::hash::Hash + DBusArg,
+            V: DBusArg,
+            K::DBusType: Eq + std::hash::Hash,
+        {
+            type DBusType = std::collections::HashMap<K::DBusType, V::DBusType>;
+
+            fn from_dbus(
+                data: std::collections::HashMap<K::DBusType, V::DBusType>,
+                conn: Option<Arc<dbus::nonblock::SyncConnection>>,
+                remote: Option<BusName<'static>>,
+                disconnect_watcher: Option<Arc<Mutex<DisconnectWatcher>>>,
+            ) -> Result<std::collections::HashMap<K, V>, Box<dyn Error>> {
+                let mut map = std::collections::HashMap::new();
+                for (key, val) in data {
+                    let k = K::from_dbus(
+                        key,
+                        conn.clone(),
+                        remote.clone(),
+                        disconnect_watcher.clone(),
+                    )?;
+                    let v = V::from_dbus(
+                        val,
+                        conn.clone(),
+                        remote.clone(),
+                        disconnect_watcher.clone(),
+                    )?;
+                    map.insert(k, v);
+                }
+                Ok(map)
+            }
+
+            fn to_dbus(
+                data: std::collections::HashMap<K, V>,
+            ) -> Result<std::collections::HashMap<K::DBusType, V::DBusType>, Box<dyn Error>> {
+                let mut map = std::collections::HashMap::new();
+                for (key, val) in data {
+                    map.insert(K::to_dbus(key)?, V::to_dbus(val)?);
+                }
+                Ok(map)
+            }
+        }
+
+        // D-Bus has no optional type, so an option is sent as a dictionary which holds the value
+        // under "optional_value" if there is one and is empty otherwise.
+        impl<T: DBusArg> DBusArg for Option<T>
+        where
+            T::DBusType: 'static + dbus::arg::RefArg + RefArgToRust<RustType = T::DBusType>,
+        {
+            type DBusType = PropMap;
+
+            fn from_dbus(
+                data: PropMap,
+                conn: Option<Arc<dbus::nonblock::SyncConnection>>,
+                remote: Option<BusName<'static>>,
+                disconnect_watcher: Option<Arc<Mutex<DisconnectWatcher>>>,
+            ) -> Result<Option<T>, Box<dyn Error>> {
+                let value = match data.get("optional_value") {
+                    Some(value) => value,
+                    None => return Ok(None),
+                };
+                let value = <T::DBusType as RefArgToRust>::ref_arg_to_rust(
+                    dbus::arg::RefArg::as_static_inner(value, 0).unwrap(),
+                    String::from("optional_value"),
+                )?;
+                Ok(Some(T::from_dbus(value, conn, remote, disconnect_watcher)?))
+            }
+
+            fn to_dbus(data: Option<T>) -> Result<PropMap, Box<dyn Error>> {
+                let mut map: PropMap = std::collections::HashMap::new();
+                if let Some(value) = data {
+                    map.insert(
+                        String::from("optional_value"),
+                        dbus::arg::Variant(Box::new(T::to_dbus(value)?)),
+                    );
+                }
+                Ok(map)
+            }
+        }
     };
 
     debug_output_to_file(&gen, format!("out-generate_dbus_arg.rs"));
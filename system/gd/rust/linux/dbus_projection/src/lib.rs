@@ -45,12 +45,18 @@
 //!   passing in the object path, D-Bus connection, Crossroads object, the Rust object to be
 //!   projected, and a [`DisconnectWatcher`](DisconnectWatcher) object.
 
+use dbus::arg::{Append, AppendAll, Arg, ArgType, Get, Iter, IterAppend};
 use dbus::channel::MatchingReceiver;
 use dbus::message::MatchRule;
 use dbus::nonblock::SyncConnection;
-use dbus::strings::BusName;
+use dbus::strings::{BusName, Path};
+use dbus::{Message, Signature};
 
+use std::any::Any;
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 /// A D-Bus "NameOwnerChanged" handler that continuously monitors client disconnects.
@@ -116,16 +122,13 @@ impl DisconnectWatcher {
                 // disconnected. So call the registered callbacks to be notified of this client
                 // disconnect.
                 let addr = BusName::new(addr.unwrap()).unwrap().into_static();
-                if !callbacks_map.lock().unwrap().contains_key(&addr) {
-                    return true;
-                }
-
-                for (id, callback) in callbacks_map.lock().unwrap()[&addr].iter() {
+                // The callbacks are taken out first, so that they can drop objects that forget
+                // their own callbacks.
+                let callbacks = callbacks_map.lock().unwrap().remove(&addr);
+                for (id, callback) in callbacks.iter().flatten() {
                     callback(*id);
                 }
 
-                callbacks_map.lock().unwrap().remove(&addr);
-
                 true
             }),
         );
@@ -135,24 +138,302 @@ impl DisconnectWatcher {
     ///
     /// If the callback can be removed, the callback will be called before being removed.
     pub fn remove(&mut self, address: BusName<'static>, target_id: u32) -> bool {
-        if !self.callbacks.lock().unwrap().contains_key(&address) {
-            return false;
-        }
-
-        match self.callbacks.lock().unwrap().get(&address).and_then(|m| m.get(&target_id)) {
+        match self.take(&address, target_id) {
             Some(cb) => {
                 cb(target_id);
-                let _ = self
-                    .callbacks
-                    .lock()
-                    .unwrap()
-                    .get_mut(&address)
-                    .and_then(|m| m.remove(&target_id));
                 true
             }
             None => false,
         }
     }
+
+    /// Removes callback by id if owned by the specific busname, without calling it.
+    pub fn forget(&mut self, address: BusName<'static>, target_id: u32) -> bool {
+        self.take(&address, target_id).is_some()
+    }
+
+    fn take(&mut self, address: &BusName<'static>, id: u32) -> Option<Box<dyn Fn(u32) + Send>> {
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let by_id = callbacks.get_mut(address)?;
+        let callback = by_id.remove(&id);
+        if by_id.is_empty() {
+            callbacks.remove(address);
+        }
+        callback
+    }
+}
+
+/// Disconnect observers of an object that another D-Bus client owns, such as a callback.
+///
+/// Any number of observers can be added. They are all called when the owner leaves the bus, and
+/// the ones still registered when the object is dropped are removed from the
+/// [`DisconnectWatcher`] without being called, so that they don't outlive the object.
+pub struct CallbackLifetime {
+    remote: BusName<'static>,
+    watcher: Arc<Mutex<DisconnectWatcher>>,
+    ids: Vec<u32>,
+}
+
+impl CallbackLifetime {
+    pub fn new(remote: BusName<'static>, watcher: Arc<Mutex<DisconnectWatcher>>) -> Self {
+        CallbackLifetime { remote, watcher, ids: vec![] }
+    }
+
+    /// Bus name of the owner of the object.
+    pub fn remote(&self) -> &BusName<'static> {
+        &self.remote
+    }
+
+    /// Adds an observer that is called with the returned id when the owner leaves the bus.
+    pub fn watch(&mut self, callback: Box<dyn Fn(u32) + Send>) -> u32 {
+        let id = self.watcher.lock().unwrap().add(self.remote.clone(), callback);
+        self.ids.push(id);
+        id
+    }
+
+    /// Removes the observer `id`, calling it like [`DisconnectWatcher::remove`] does.
+    pub fn unwatch(&mut self, id: u32) -> bool {
+        self.ids.retain(|watched| *watched != id);
+        self.watcher.lock().unwrap().remove(self.remote.clone(), id)
+    }
+}
+
+impl Drop for CallbackLifetime {
+    fn drop(&mut self) {
+        if let Ok(mut watcher) = self.watcher.lock() {
+            for id in self.ids.drain(..) {
+                watcher.forget(self.remote.clone(), id);
+            }
+        }
+    }
+}
+
+/// Where a callback delivery stands among the deliveries of its interface.
+///
+/// Proxies generated by [`dbus_proxy_obj`](dbus_macros::dbus_proxy_obj) send the sequence number
+/// and timestamp of a delivery as two trailing `u64` arguments of the method call, which receivers
+/// that don't expect them ignore. Objects exported by
+/// [`generate_dbus_exporter`](dbus_macros::generate_dbus_exporter) can get them with
+/// [`current_event_stamp`] while they handle the call.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EventStamp {
+    /// Starts at 1 when the service starts and goes up by one with every delivery.
+    pub sequence: u64,
+    /// When the delivery was made, in microseconds since the Unix epoch. Never goes backwards.
+    pub timestamp_us: u64,
+    /// How many deliveries the object didn't get since the previous one it got.
+    pub missed: u64,
+}
+
+thread_local! {
+    static CURRENT_EVENT_STAMP: Cell<Option<EventStamp>> = Cell::new(None);
+}
+
+/// Gets the stamp of the callback method call being handled on this thread, if it has one.
+pub fn current_event_stamp() -> Option<EventStamp> {
+    CURRENT_EVENT_STAMP.with(|stamp| stamp.get())
+}
+
+/// Keeps the last sequence number each exported object got, to tell how many deliveries it missed.
+#[derive(Default)]
+pub struct EventStampTracker {
+    last_sequences: HashMap<Path<'static>, u64>,
+}
+
+impl EventStampTracker {
+    /// Reads the stamp following the `arg_count` arguments of the method call `msg` made on the
+    /// object at `path`.
+    pub fn track(
+        &mut self,
+        path: &Path<'static>,
+        msg: &Message,
+        arg_count: usize,
+    ) -> Option<EventStamp> {
+        let mut iter = msg.iter_init();
+        for _ in 0..arg_count {
+            if !iter.next() {
+                return None;
+            }
+        }
+        let sequence: u64 = iter.read().ok()?;
+        let timestamp_us: u64 = iter.read().ok()?;
+
+        // A lower sequence number means that the service restarted.
+        let missed = match self.last_sequences.insert(path.clone(), sequence) {
+            Some(last) if sequence > last => sequence - last - 1,
+            _ => 0,
+        };
+        Some(EventStamp { sequence, timestamp_us, missed })
+    }
+}
+
+/// Runs `f` with `stamp` as the current event stamp of the thread.
+pub fn with_event_stamp<R, F: FnOnce() -> R>(stamp: Option<EventStamp>, f: F) -> R {
+    let previous = CURRENT_EVENT_STAMP.with(|current| current.replace(stamp));
+    let result = f();
+    CURRENT_EVENT_STAMP.with(|current| current.set(previous));
+    result
+}
+
+/// Runs `f`, the body of a call to the D-Bus method `name`, and turns a panic in it into an error
+/// for the caller. Exported objects are called through this, so a panicking method or callback
+/// implementation fails that one call instead of taking the whole process down with it.
+pub fn catch_method_panic<R, F: FnOnce() -> R>(name: &str, f: F) -> Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = format!("{} panicked: {}", name, panic_message(&*payload));
+        log::error!("{}", message);
+        message
+    })
+}
+
+/// Locks `obj` and runs `f` on it, the body of a call to the D-Bus method `name`, the way
+/// [`catch_method_panic`] does.
+///
+/// The panic is caught while the lock is still held, so the guard is dropped once unwinding is
+/// over and doesn't poison the mutex: the object keeps serving the next calls and the rest of the
+/// program can still lock it. A mutex poisoned by a panic elsewhere is locked all the same.
+pub fn call_exported_method<T: ?Sized, R, F: FnOnce(&mut T) -> R>(
+    name: &str,
+    obj: &Mutex<T>,
+    f: F,
+) -> Result<R, String> {
+    let mut guard = obj.lock().unwrap_or_else(|e| e.into_inner());
+    catch_method_panic(name, || f(&mut guard))
+}
+
+/// Which way an API call went.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiCallDirection {
+    /// A client called a method of an exported object.
+    Incoming,
+    /// The service called a method of a callback object of a client.
+    Callback,
+}
+
+/// An API call, as given to the tracer set with [`set_api_tracer`].
+#[derive(Clone, Debug)]
+pub struct ApiCall {
+    pub direction: ApiCallDirection,
+    /// Unique bus name of the client.
+    pub peer: String,
+    pub path: String,
+    pub interface: String,
+    pub method: String,
+    /// Name and value of each argument. Values are shown as D-Bus data, e.g. structures as maps.
+    pub args: Vec<(String, String)>,
+}
+
+/// Gets every API call made through the projection while set.
+pub type ApiTracer = Box<dyn Fn(ApiCall) + Send>;
+
+static API_TRACER: Mutex<Option<ApiTracer>> = Mutex::new(None);
+static API_TRACE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Sets the function that gets every method call of exported objects and of callback proxies,
+/// or stops tracing with None. Calls aren't formatted unless a tracer is set.
+pub fn set_api_tracer(tracer: Option<ApiTracer>) {
+    let mut current = API_TRACER.lock().unwrap();
+    API_TRACE_ENABLED.store(tracer.is_some(), Ordering::Relaxed);
+    *current = tracer;
+}
+
+fn format_args(msg: &Message, arg_names: &[&str]) -> Vec<(String, String)> {
+    let mut iter = msg.iter_init();
+    let mut args = vec![];
+    for name in arg_names {
+        match iter.get_refarg() {
+            Some(value) => args.push((name.to_string(), format!("{:?}", value))),
+            None => break,
+        }
+        iter.next();
+    }
+    args
+}
+
+fn trace_api_call(direction: ApiCallDirection, peer: String, msg: &Message, arg_names: &[&str]) {
+    if let Some(tracer) = API_TRACER.lock().unwrap().as_ref() {
+        tracer(ApiCall {
+            direction,
+            peer,
+            path: msg.path().map(|path| path.to_string()).unwrap_or_default(),
+            interface: msg.interface().map(|iface| iface.to_string()).unwrap_or_default(),
+            method: msg.member().map(|member| member.to_string()).unwrap_or_default(),
+            args: format_args(msg, arg_names),
+        });
+    }
+}
+
+/// Traces the method call `msg` a client made of an exported object, whose arguments are named
+/// `arg_names`. Called by the exporters of
+/// [`generate_dbus_exporter`](dbus_macros::generate_dbus_exporter).
+pub fn trace_incoming_call(msg: &Message, arg_names: &[&str]) {
+    if !API_TRACE_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let peer = msg.sender().map(|sender| sender.to_string()).unwrap_or_default();
+    trace_api_call(ApiCallDirection::Incoming, peer, msg, arg_names);
+}
+
+/// Traces the call of `method` of the callback object at `path` of the client `remote`, with
+/// the arguments `args` named `arg_names`. Called by the proxies of
+/// [`dbus_proxy_obj`](dbus_macros::dbus_proxy_obj).
+pub fn trace_callback<A: AppendAll>(
+    remote: &BusName<'static>,
+    path: &Path<'static>,
+    interface: &str,
+    method: &str,
+    arg_names: &[&str],
+    args: &A,
+) {
+    if !API_TRACE_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut msg = match Message::new_method_call(remote.clone(), path.clone(), interface, method) {
+        Ok(msg) => msg,
+        Err(_) => return,
+    };
+    args.append(&mut IterAppend::new(&mut msg));
+    trace_api_call(ApiCallDirection::Callback, remote.to_string(), &msg, arg_names);
+}
+
+/// Returns the message a panic was started with, from the payload `catch_unwind` returned.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "(no message)"
+    }
+}
+
+/// A byte array shared with the rest of the program, e.g. a GATT value.
+///
+/// It is the D-Bus type that `Arc<[u8]>` is projected to. Appending it to a message copies the
+/// bytes straight into the message, and reading it copies them straight out of the message, so
+/// there are no intermediate `Vec<u8>`s on either side.
+#[derive(Clone, Debug)]
+pub struct DBusBytes(pub Arc<[u8]>);
+
+impl Arg for DBusBytes {
+    const ARG_TYPE: ArgType = ArgType::Array;
+
+    fn signature() -> Signature<'static> {
+        <Vec<u8> as Arg>::signature()
+    }
+}
+
+impl Append for DBusBytes {
+    fn append_by_ref(&self, i: &mut IterAppend) {
+        let bytes: &[u8] = &self.0;
+        bytes.append_by_ref(i);
+    }
+}
+
+impl<'a> Get<'a> for DBusBytes {
+    fn get(i: &mut Iter<'a>) -> Option<Self> {
+        <&[u8]>::get(i).map(|bytes| DBusBytes(Arc::from(bytes)))
+    }
 }
 
 /// Implements `DBusArg` for an enum.
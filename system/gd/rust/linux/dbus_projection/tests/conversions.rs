@@ -2,8 +2,10 @@ use core::any::Any;
 
 use dbus_macros::{dbus_propmap, generate_dbus_arg};
 
-use dbus::arg::{Arg, ArgType, IterAppend, RefArg};
-use dbus::Signature;
+use dbus::arg::{Append, Arg, ArgType, Get, IterAppend, RefArg};
+use dbus::{Message, Signature};
+
+use std::collections::HashMap;
 
 generate_dbus_arg!();
 
@@ -37,6 +39,24 @@ struct SomeStructDBus {
     recursive: Vec<SomeStruct>,
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+struct MapStruct {
+    counts: HashMap<String, u32>,
+    others: HashMap<u32, OtherStruct>,
+    alias: Option<String>,
+    missing: Option<String>,
+    some_struct: Option<SomeStruct>,
+}
+
+#[dbus_propmap(MapStruct)]
+struct MapStructDBus {
+    counts: HashMap<String, u32>,
+    others: HashMap<u32, OtherStruct>,
+    alias: Option<String>,
+    missing: Option<String>,
+    some_struct: Option<SomeStruct>,
+}
+
 // Pretends to be a D-Bus dictionary.
 #[derive(Debug)]
 struct FakeDictionary {
@@ -45,7 +65,7 @@ struct FakeDictionary {
 
 impl RefArg for FakeDictionary {
     fn arg_type(&self) -> ArgType {
-        todo!()
+        ArgType::Array
     }
     fn signature(&self) -> dbus::Signature<'static> {
         todo!()
@@ -85,6 +105,17 @@ impl Arg for FakeDictionary {
 mod tests {
     use super::*;
 
+    // Sends `data` through a D-Bus message and back.
+    fn round_trip<T: DBusArg>(data: T) -> T
+    where
+        T::DBusType: Arg + Append + for<'a> Get<'a>,
+    {
+        let message = Message::new_method_call("org.example.Test", "/", "org.example.Test", "Test")
+            .unwrap()
+            .append1(T::to_dbus(data).unwrap());
+        T::from_dbus(message.read1().unwrap(), None, None, None).unwrap()
+    }
+
     #[test]
     fn test_dbus_propmap_error() {
         let data_dbus = String::from("some data");
@@ -173,4 +204,52 @@ mod tests {
         };
         assert_eq!(expected_struct, result_struct);
     }
+
+    #[test]
+    fn test_dbus_map_round_trip() {
+        let mut counts = HashMap::new();
+        counts.insert(String::from("a"), 1);
+        counts.insert(String::from("b"), 2);
+        assert_eq!(round_trip(counts.clone()), counts);
+
+        let mut others = HashMap::new();
+        others.insert(7, OtherStruct { address: String::from("aa:bb:cc:dd:ee:ff") });
+        assert_eq!(round_trip(others.clone()), others);
+
+        assert_eq!(round_trip(HashMap::<String, Vec<u8>>::new()), HashMap::new());
+    }
+
+    #[test]
+    fn test_dbus_option_round_trip() {
+        assert_eq!(round_trip(Some(5u32)), Some(5));
+        assert_eq!(round_trip(None::<u32>), None);
+        assert_eq!(round_trip(Some(vec![String::from("foo")])), Some(vec![String::from("foo")]));
+
+        let other = OtherStruct { address: String::from("xx") };
+        assert_eq!(round_trip(Some(other.clone())), Some(other));
+    }
+
+    #[test]
+    fn test_dbus_nested_round_trip() {
+        let some_struct = SomeStruct {
+            name: String::from("foo"),
+            number: 100,
+            other_struct: OtherStruct { address: String::from("aa:bb:cc:dd:ee:ff") },
+            bytes: vec![1, 2, 3],
+            nested: vec![vec![String::from("string a")], vec![]],
+            recursive: vec![SomeStruct { name: String::from("bar"), ..Default::default() }],
+        };
+        assert_eq!(round_trip(some_struct.clone()), some_struct);
+
+        let mut data = MapStruct {
+            counts: HashMap::new(),
+            others: HashMap::new(),
+            alias: Some(String::from("alias")),
+            missing: None,
+            some_struct: Some(some_struct),
+        };
+        data.counts.insert(String::from("a"), 1);
+        data.others.insert(7, OtherStruct { address: String::from("xx") });
+        assert_eq!(round_trip(data.clone()), data);
+    }
 }
@@ -0,0 +1,38 @@
+use dbus_projection::call_exported_method;
+
+use std::sync::{Arc, Mutex};
+
+trait ICounter {
+    fn add(&mut self, value: u32) -> u32;
+}
+
+struct Counter {
+    total: u32,
+}
+
+impl ICounter for Counter {
+    fn add(&mut self, value: u32) -> u32 {
+        if value == 0 {
+            panic!("nothing to add");
+        }
+        self.total += value;
+        self.total
+    }
+}
+
+#[test]
+fn test_call_after_caught_panic() {
+    // The way exported objects are shared between the stack and the D-Bus projection.
+    let obj: Arc<Mutex<Box<dyn ICounter + Send>>> =
+        Arc::new(Mutex::new(Box::new(Counter { total: 0 })));
+
+    assert_eq!(call_exported_method("Counter.Add", &*obj, |obj| obj.add(1)), Ok(1));
+    assert_eq!(
+        call_exported_method("Counter.Add", &*obj, |obj| obj.add(0)),
+        Err(String::from("Counter.Add panicked: nothing to add"))
+    );
+
+    assert!(!obj.is_poisoned());
+    assert_eq!(call_exported_method("Counter.Add", &*obj, |obj| obj.add(2)), Ok(3));
+    assert_eq!(obj.lock().unwrap().add(3), 6);
+}
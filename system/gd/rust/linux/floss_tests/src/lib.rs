@@ -0,0 +1,201 @@
+//! End-to-end tests of the Floss daemons.
+//!
+//! The tests go through the client library and D-Bus like any other client, so they cover the
+//! IPC and callback plumbing along with the stack. btmanagerd is asked to attach a controller
+//! emulated by rootcanal, the adapter on it is enabled, and it then deals with a [`peer::Peer`]
+//! on a second controller of the same rootcanal.
+//!
+//! The tests need the daemons running, with Floss enabled and no other adapter in use, and
+//! rootcanal listening at [`ROOTCANAL_ENV`]. Where it isn't set they pass without doing anything,
+//! so that `cargo test` can still run everywhere else.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use floss_client::requests;
+use floss_client::{ClientContext, ClientEvent};
+use manager_service::iface_bluetooth_manager::IBluetoothManager;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+pub mod peer;
+
+/// Environment variable with the address of the HCI port of rootcanal, e.g. "127.0.0.1:6402".
+pub const ROOTCANAL_ENV: &str = "FLOSS_TEST_ROOTCANAL";
+
+/// Time given to each step of a test.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// First API version of the manager that attaches virtual controllers.
+const VIRTUAL_CONTROLLER_API_VERSION: u32 = 2;
+
+pub type TestResult<T> = Result<T, Box<dyn Error>>;
+
+/// Records the events of a client, so tests can check the order the callbacks came in.
+pub struct EventLog {
+    events: Arc<Mutex<Vec<ClientEvent>>>,
+    task: JoinHandle<()>,
+}
+
+impl EventLog {
+    pub fn start(context: &Arc<Mutex<ClientContext>>) -> EventLog {
+        let mut receiver = context.lock().unwrap().subscribe_events();
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let log = events.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => log.lock().unwrap().push(event),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        eprintln!("Event log missed {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        EventLog { events, task }
+    }
+
+    /// Names of the events recorded so far, as given by [`ClientEvent::name`].
+    pub fn names(&self) -> Vec<&'static str> {
+        self.events.lock().unwrap().iter().map(ClientEvent::name).collect()
+    }
+
+    /// Panics unless the events named in `expected` were recorded in this order. Other events may
+    /// come in between.
+    pub fn assert_in_order(&self, expected: &[&str]) {
+        let names = self.names();
+        assert!(is_in_order(&names, expected), "Expected {:?} in order, got {:?}", expected, names);
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn is_in_order(names: &[&str], expected: &[&str]) -> bool {
+    let mut names = names.iter();
+    expected.iter().all(|expected| names.any(|name| name == expected))
+}
+
+/// An adapter on a virtual controller, enabled and ready, and the client driving it.
+pub struct TestBed {
+    /// The rootcanal serving the controllers.
+    pub rootcanal: SocketAddr,
+    pub hci_interface: i32,
+    pub context: Arc<Mutex<ClientContext>>,
+    /// Events since the adapter was enabled.
+    pub events: EventLog,
+    previous_controller: String,
+    foreground: JoinHandle<()>,
+}
+
+impl TestBed {
+    /// Attaches a virtual controller and enables the adapter on it. Returns None if the tests
+    /// aren't set up to run here.
+    pub async fn start() -> TestResult<Option<TestBed>> {
+        let rootcanal = match std::env::var(ROOTCANAL_ENV) {
+            Ok(rootcanal) => rootcanal.parse::<SocketAddr>()?,
+            Err(_) => {
+                eprintln!("{} isn't set, skipping", ROOTCANAL_ENV);
+                return Ok(None);
+            }
+        };
+
+        let (conn, cr) = floss_client::connect_dbus()?;
+        let (context, mut rx) = floss_client::create_client(conn, cr, None).await;
+        let foreground = {
+            let context = context.clone();
+            tokio::spawn(async move {
+                while let Some(action) = rx.recv().await {
+                    floss_client::handle_foreground_action(context.clone(), action);
+                }
+            })
+        };
+
+        let (api_version, mut manager, mut events) = {
+            let context = context.lock().unwrap();
+            (context.api_version, context.manager_dbus.clone(), context.subscribe_events())
+        };
+        match api_version {
+            Some(version) if version >= VIRTUAL_CONTROLLER_API_VERSION => {}
+            version => {
+                return Err(
+                    format!("btmanagerd version {:?} can't attach controllers", version).into()
+                )
+            }
+        }
+
+        let previous_controller = manager.get_virtual_controller();
+        if !manager.set_virtual_controller(rootcanal.to_string()) {
+            return Err(format!("btmanagerd rejected {}", rootcanal).into());
+        }
+
+        let hci_interface = requests::wait_for(&mut events, TIMEOUT, |event| match event {
+            ClientEvent::HciDeviceChanged { hci_interface, present: true } => {
+                Some(Ok(*hci_interface))
+            }
+            _ => None,
+        })
+        .await?;
+
+        let log = EventLog::start(&context);
+        let already_enabled = {
+            let mut context = context.lock().unwrap();
+            context.default_adapter = hci_interface;
+            context.adapters.get(&hci_interface) == Some(&true)
+        };
+        if already_enabled {
+            // The manager brought it up before it was the default adapter, which the proxies
+            // follow.
+            context.lock().unwrap().set_adapter_enabled(hci_interface, true);
+        } else {
+            requests::enable_adapter(context.clone(), hci_interface, TIMEOUT).await?;
+        }
+        requests::wait_for(&mut events, TIMEOUT, |event| match event {
+            ClientEvent::AdapterReady { .. } => Some(Ok(())),
+            _ => None,
+        })
+        .await?;
+
+        Ok(Some(TestBed {
+            rootcanal,
+            hci_interface,
+            context,
+            events: log,
+            previous_controller,
+            foreground,
+        }))
+    }
+
+    /// Disables the adapter and gives the manager back the virtual controller it had before.
+    pub async fn stop(self) -> TestResult<()> {
+        requests::disable_adapter(self.context.clone(), self.hci_interface, TIMEOUT).await?;
+
+        let mut manager = self.context.lock().unwrap().manager_dbus.clone();
+        manager.set_virtual_controller(self.previous_controller.clone());
+        self.foreground.abort();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_in_order() {
+        let names = ["device-found", "device-found", "bond-state-changed", "device-connected"];
+        assert!(is_in_order(&names, &["device-found", "device-connected"]));
+        assert!(is_in_order(&names, &["device-found", "device-found"]));
+        assert!(is_in_order(&names, &[]));
+        assert!(!is_in_order(&names, &["device-connected", "bond-state-changed"]));
+        assert!(!is_in_order(&names, &["device-found", "device-found", "device-found"]));
+    }
+}
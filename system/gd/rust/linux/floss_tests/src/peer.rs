@@ -0,0 +1,519 @@
+//! A minimal HCI host playing the remote device of the tests.
+//!
+//! rootcanal only emulates plain advertisers, so the device the stack talks to is a second
+//! controller of the same rootcanal, driven over HCI by this host. It is discoverable and
+//! connectable on both transports, pairs with Just Works, and serves a GATT database with a single
+//! readable characteristic. Whatever else it is asked is declined, which is enough for the stack
+//! to move on.
+
+use bt_topshim::btif::RawAddress;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+/// Name of the peer, on both transports.
+pub const PEER_NAME: &str = "Floss test peer";
+
+/// The GATT database of the peer: a primary service holding one characteristic, which can be
+/// read.
+pub const SERVICE_HANDLE: u16 = 0x0001;
+pub const CHARACTERISTIC_HANDLE: u16 = 0x0002;
+pub const VALUE_HANDLE: u16 = 0x0003;
+pub const SERVICE_UUID16: u16 = 0xfff0;
+pub const CHARACTERISTIC_UUID16: u16 = 0xfff1;
+pub const CHARACTERISTIC_VALUE: &[u8] = b"floss";
+
+const H4_COMMAND: u8 = 0x01;
+const H4_ACL: u8 = 0x02;
+const H4_EVENT: u8 = 0x04;
+
+const OP_ACCEPT_CONNECTION_REQUEST: u16 = 0x0409;
+const OP_LINK_KEY_REQUEST_REPLY: u16 = 0x040b;
+const OP_LINK_KEY_REQUEST_NEGATIVE_REPLY: u16 = 0x040c;
+const OP_IO_CAPABILITY_REQUEST_REPLY: u16 = 0x042b;
+const OP_USER_CONFIRMATION_REQUEST_REPLY: u16 = 0x042c;
+const OP_SET_EVENT_MASK: u16 = 0x0c01;
+const OP_RESET: u16 = 0x0c03;
+const OP_WRITE_LOCAL_NAME: u16 = 0x0c13;
+const OP_WRITE_SCAN_ENABLE: u16 = 0x0c1a;
+const OP_WRITE_CLASS_OF_DEVICE: u16 = 0x0c24;
+const OP_WRITE_EXTENDED_INQUIRY_RESPONSE: u16 = 0x0c52;
+const OP_WRITE_SIMPLE_PAIRING_MODE: u16 = 0x0c56;
+const OP_READ_BD_ADDR: u16 = 0x1009;
+const OP_LE_SET_ADVERTISING_PARAMETERS: u16 = 0x2006;
+const OP_LE_SET_ADVERTISING_DATA: u16 = 0x2008;
+const OP_LE_SET_ADVERTISING_ENABLE: u16 = 0x200a;
+
+const EVT_CONNECTION_REQUEST: u8 = 0x04;
+const EVT_DISCONNECTION_COMPLETE: u8 = 0x05;
+const EVT_COMMAND_COMPLETE: u8 = 0x0e;
+const EVT_LINK_KEY_REQUEST: u8 = 0x17;
+const EVT_LINK_KEY_NOTIFICATION: u8 = 0x18;
+const EVT_IO_CAPABILITY_REQUEST: u8 = 0x31;
+const EVT_USER_CONFIRMATION_REQUEST: u8 = 0x33;
+const EVT_LE_META: u8 = 0x3e;
+const LE_CONNECTION_COMPLETE: u8 = 0x01;
+const LE_ENHANCED_CONNECTION_COMPLETE: u8 = 0x0a;
+
+/// Events of the default mask along with the Secure Simple Pairing ones.
+const EVENT_MASK: [u8; 8] = [0xff, 0xff, 0xfb, 0xff, 0x07, 0xf8, 0xbf, 0x3d];
+/// Computer, uncategorized.
+const CLASS_OF_DEVICE: [u8; 3] = [0x00, 0x01, 0x00];
+const IO_CAPABILITY_NO_INPUT_NO_OUTPUT: u8 = 0x03;
+const AUTH_DEDICATED_BONDING: u8 = 0x02;
+const ROLE_PERIPHERAL: u8 = 0x01;
+const SCAN_INQUIRY_AND_PAGE: u8 = 0x03;
+/// Connectable undirected advertising every 100 ms on all channels, from the public address.
+const ADVERTISING_PARAMETERS: [u8; 15] =
+    [0xa0, 0x00, 0xa0, 0x00, 0x00, 0x00, 0x00, 0, 0, 0, 0, 0, 0, 0x07, 0x00];
+
+const AD_TYPE_FLAGS: u8 = 0x01;
+const AD_TYPE_COMPLETE_NAME: u8 = 0x09;
+const AD_FLAG_LE_GENERAL_DISCOVERABLE: u8 = 0x02;
+
+/// ACL packet boundary flags of the first packet of an L2CAP PDU.
+const ACL_FIRST_NON_FLUSHABLE: u16 = 0x0000;
+const ACL_FIRST_FLUSHABLE: u16 = 0x2000;
+
+const L2CAP_SIGNALING_CID: u16 = 0x0001;
+const L2CAP_ATT_CID: u16 = 0x0004;
+const L2CAP_LE_SIGNALING_CID: u16 = 0x0005;
+const L2CAP_SMP_CID: u16 = 0x0006;
+
+const L2CAP_COMMAND_REJECT: u8 = 0x01;
+const L2CAP_CONNECTION_REQUEST: u8 = 0x02;
+const L2CAP_CONNECTION_RESPONSE: u8 = 0x03;
+const L2CAP_ECHO_REQUEST: u8 = 0x08;
+const L2CAP_ECHO_RESPONSE: u8 = 0x09;
+const L2CAP_INFORMATION_REQUEST: u8 = 0x0a;
+const L2CAP_INFORMATION_RESPONSE: u8 = 0x0b;
+const L2CAP_PSM_NOT_SUPPORTED: u16 = 0x0002;
+const L2CAP_INFORMATION_NOT_SUPPORTED: u16 = 0x0001;
+
+const SMP_PAIRING_REQUEST: u8 = 0x01;
+const SMP_PAIRING_FAILED: u8 = 0x05;
+const SMP_PAIRING_NOT_SUPPORTED: u8 = 0x05;
+
+const ATT_ERROR_RESPONSE: u8 = 0x01;
+const ATT_EXCHANGE_MTU_REQUEST: u8 = 0x02;
+const ATT_EXCHANGE_MTU_RESPONSE: u8 = 0x03;
+const ATT_READ_BY_TYPE_REQUEST: u8 = 0x08;
+const ATT_READ_BY_TYPE_RESPONSE: u8 = 0x09;
+const ATT_READ_REQUEST: u8 = 0x0a;
+const ATT_READ_RESPONSE: u8 = 0x0b;
+const ATT_READ_BY_GROUP_TYPE_REQUEST: u8 = 0x10;
+const ATT_READ_BY_GROUP_TYPE_RESPONSE: u8 = 0x11;
+const ATT_HANDLE_VALUE_CONFIRMATION: u8 = 0x1e;
+const ATT_COMMAND_FLAG: u8 = 0x40;
+const ATT_DEFAULT_MTU: u16 = 23;
+
+const ATT_INVALID_HANDLE: u8 = 0x01;
+const ATT_REQUEST_NOT_SUPPORTED: u8 = 0x06;
+const ATT_ATTRIBUTE_NOT_FOUND: u8 = 0x0a;
+
+const GATT_PRIMARY_SERVICE: u16 = 0x2800;
+const GATT_CHARACTERISTIC: u16 = 0x2803;
+const GATT_PROPERTY_READ: u8 = 0x02;
+
+/// The controller didn't answer as expected.
+fn controller_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn att_error(request: u8, handle: u16, error: u8) -> Vec<u8> {
+    let handle = handle.to_le_bytes();
+    vec![ATT_ERROR_RESPONSE, request, handle[0], handle[1], error]
+}
+
+/// Answers an ATT PDU sent to the peer's GATT server, or returns None if it doesn't take an
+/// answer.
+fn att_response(pdu: &[u8]) -> Option<Vec<u8>> {
+    let (&opcode, params) = pdu.split_first()?;
+    let u16_at = |i: usize| params.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    // Requests by type carry a 16 bit or a 128 bit UUID after the handle range.
+    let uuid16 = || if params.len() == 6 { u16_at(4) } else { None };
+
+    match opcode {
+        ATT_EXCHANGE_MTU_REQUEST => {
+            let mtu = ATT_DEFAULT_MTU.to_le_bytes();
+            Some(vec![ATT_EXCHANGE_MTU_RESPONSE, mtu[0], mtu[1]])
+        }
+        ATT_READ_BY_GROUP_TYPE_REQUEST => {
+            let (start, end) = (u16_at(0)?, u16_at(2)?);
+            if uuid16() != Some(GATT_PRIMARY_SERVICE) || start > SERVICE_HANDLE || end < start {
+                return Some(att_error(opcode, start, ATT_ATTRIBUTE_NOT_FOUND));
+            }
+
+            let mut response = vec![ATT_READ_BY_GROUP_TYPE_RESPONSE, 6];
+            response.extend_from_slice(&SERVICE_HANDLE.to_le_bytes());
+            response.extend_from_slice(&VALUE_HANDLE.to_le_bytes());
+            response.extend_from_slice(&SERVICE_UUID16.to_le_bytes());
+            Some(response)
+        }
+        ATT_READ_BY_TYPE_REQUEST => {
+            let (start, end) = (u16_at(0)?, u16_at(2)?);
+            let range = start..=end;
+            if uuid16() != Some(GATT_CHARACTERISTIC) || !range.contains(&CHARACTERISTIC_HANDLE) {
+                return Some(att_error(opcode, start, ATT_ATTRIBUTE_NOT_FOUND));
+            }
+
+            let mut response = vec![ATT_READ_BY_TYPE_RESPONSE, 7];
+            response.extend_from_slice(&CHARACTERISTIC_HANDLE.to_le_bytes());
+            response.push(GATT_PROPERTY_READ);
+            response.extend_from_slice(&VALUE_HANDLE.to_le_bytes());
+            response.extend_from_slice(&CHARACTERISTIC_UUID16.to_le_bytes());
+            Some(response)
+        }
+        ATT_READ_REQUEST => match u16_at(0)? {
+            VALUE_HANDLE => Some([&[ATT_READ_RESPONSE], CHARACTERISTIC_VALUE].concat()),
+            handle => Some(att_error(opcode, handle, ATT_INVALID_HANDLE)),
+        },
+        // Commands, responses, notifications, indications and confirmations.
+        opcode
+            if opcode & ATT_COMMAND_FLAG != 0
+                || opcode % 2 == 1
+                || opcode == ATT_HANDLE_VALUE_CONFIRMATION =>
+        {
+            None
+        }
+        opcode => Some(att_error(opcode, 0, ATT_REQUEST_NOT_SUPPORTED)),
+    }
+}
+
+/// Answers an L2CAP signaling command, or returns None if it doesn't take an answer. No channel
+/// is ever opened.
+fn signaling_response(command: &[u8]) -> Option<Vec<u8>> {
+    if command.len() < 4 {
+        return None;
+    }
+
+    let (code, identifier, data) = (command[0], command[1], &command[4..]);
+    let data: Vec<u8> = match code {
+        L2CAP_CONNECTION_REQUEST if data.len() >= 4 => {
+            // No destination channel, the source channel, the result and no further status.
+            let result = L2CAP_PSM_NOT_SUPPORTED.to_le_bytes();
+            vec![0, 0, data[2], data[3], result[0], result[1], 0, 0]
+        }
+        L2CAP_ECHO_REQUEST => data.to_vec(),
+        L2CAP_INFORMATION_REQUEST if data.len() >= 2 => {
+            let result = L2CAP_INFORMATION_NOT_SUPPORTED.to_le_bytes();
+            vec![data[0], data[1], result[0], result[1]]
+        }
+        // Responses and rejects.
+        code if code % 2 == 1 => return None,
+        _ => vec![0, 0],
+    };
+
+    let code = match code {
+        L2CAP_CONNECTION_REQUEST => L2CAP_CONNECTION_RESPONSE,
+        L2CAP_ECHO_REQUEST => L2CAP_ECHO_RESPONSE,
+        L2CAP_INFORMATION_REQUEST => L2CAP_INFORMATION_RESPONSE,
+        _ => L2CAP_COMMAND_REJECT,
+    };
+    let len = (data.len() as u16).to_le_bytes();
+    Some([&[code, identifier, len[0], len[1]], &data[..]].concat())
+}
+
+enum Packet {
+    Event(u8, Vec<u8>),
+    Acl(u16, Vec<u8>),
+}
+
+struct Host {
+    stream: TcpStream,
+    link_keys: HashMap<[u8; 6], [u8; 16]>,
+    le_connections: HashSet<u16>,
+}
+
+impl Host {
+    async fn read_packet(&mut self) -> io::Result<Packet> {
+        match self.stream.read_u8().await? {
+            H4_EVENT => {
+                let mut header = [0; 2];
+                self.stream.read_exact(&mut header).await?;
+                let mut params = vec![0; header[1] as usize];
+                self.stream.read_exact(&mut params).await?;
+                Ok(Packet::Event(header[0], params))
+            }
+            H4_ACL => {
+                let mut header = [0; 4];
+                self.stream.read_exact(&mut header).await?;
+                let handle = u16::from_le_bytes([header[0], header[1]]) & 0x0fff;
+                let mut data = vec![0; u16::from_le_bytes([header[2], header[3]]) as usize];
+                self.stream.read_exact(&mut data).await?;
+                Ok(Packet::Acl(handle, data))
+            }
+            packet_type => {
+                Err(controller_error(format!("Unexpected H4 packet type {:#04x}", packet_type)))
+            }
+        }
+    }
+
+    async fn send_command(&mut self, opcode: u16, params: &[u8]) -> io::Result<()> {
+        let opcode = opcode.to_le_bytes();
+        let header = [H4_COMMAND, opcode[0], opcode[1], params.len() as u8];
+        self.stream.write_all(&[&header, params].concat()).await
+    }
+
+    /// Sends a command and waits until it completes. Returns its return parameters, status
+    /// first.
+    async fn command(&mut self, opcode: u16, params: &[u8]) -> io::Result<Vec<u8>> {
+        self.send_command(opcode, params).await?;
+        loop {
+            // Number of command packets, opcode, then the return parameters.
+            let params = match self.read_packet().await? {
+                Packet::Event(EVT_COMMAND_COMPLETE, params) if params.len() >= 4 => params,
+                _ => continue,
+            };
+            if u16::from_le_bytes([params[1], params[2]]) != opcode {
+                continue;
+            }
+
+            return match params[3] {
+                0 => Ok(params[3..].to_vec()),
+                status => Err(controller_error(format!(
+                    "Command {:#06x} failed with status {:#04x}",
+                    opcode, status
+                ))),
+            };
+        }
+    }
+
+    async fn send_l2cap(&mut self, handle: u16, cid: u16, payload: &[u8]) -> io::Result<()> {
+        let flags = match self.le_connections.contains(&handle) {
+            true => ACL_FIRST_NON_FLUSHABLE,
+            false => ACL_FIRST_FLUSHABLE,
+        };
+        let mut packet = vec![H4_ACL];
+        packet.extend_from_slice(&(handle | flags).to_le_bytes());
+        packet.extend_from_slice(&(payload.len() as u16 + 4).to_le_bytes());
+        packet.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        packet.extend_from_slice(&cid.to_le_bytes());
+        packet.extend_from_slice(payload);
+        self.stream.write_all(&packet).await
+    }
+
+    /// Brings the controller up as the peer. Returns its address.
+    async fn setup(&mut self) -> io::Result<RawAddress> {
+        self.command(OP_RESET, &[]).await?;
+
+        // The address comes least significant byte first, after the status.
+        let bd_addr = self.command(OP_READ_BD_ADDR, &[]).await?;
+        let bd_addr = bd_addr.get(1..7).ok_or_else(|| controller_error("No address".into()))?;
+        let mut address = RawAddress { val: [0; 6] };
+        for (i, byte) in bd_addr.iter().enumerate() {
+            address.val[5 - i] = *byte;
+        }
+
+        self.command(OP_SET_EVENT_MASK, &EVENT_MASK).await?;
+        self.command(OP_WRITE_SIMPLE_PAIRING_MODE, &[0x01]).await?;
+
+        let name = PEER_NAME.as_bytes();
+        let mut local_name = [0; 248];
+        local_name[..name.len()].copy_from_slice(name);
+        self.command(OP_WRITE_LOCAL_NAME, &local_name).await?;
+
+        // Not FEC protected, then the name as the only EIR structure.
+        let mut eir = [0; 241];
+        eir[1] = name.len() as u8 + 1;
+        eir[2] = AD_TYPE_COMPLETE_NAME;
+        eir[3..3 + name.len()].copy_from_slice(name);
+        self.command(OP_WRITE_EXTENDED_INQUIRY_RESPONSE, &eir).await?;
+
+        self.command(OP_WRITE_CLASS_OF_DEVICE, &CLASS_OF_DEVICE).await?;
+        self.command(OP_WRITE_SCAN_ENABLE, &[SCAN_INQUIRY_AND_PAGE]).await?;
+
+        let mut adv_data = vec![2, AD_TYPE_FLAGS, AD_FLAG_LE_GENERAL_DISCOVERABLE];
+        adv_data.extend_from_slice(&[name.len() as u8 + 1, AD_TYPE_COMPLETE_NAME]);
+        adv_data.extend_from_slice(name);
+        let mut params = [0; 32];
+        params[0] = adv_data.len() as u8;
+        params[1..1 + adv_data.len()].copy_from_slice(&adv_data);
+        self.command(OP_LE_SET_ADVERTISING_PARAMETERS, &ADVERTISING_PARAMETERS).await?;
+        self.command(OP_LE_SET_ADVERTISING_DATA, &params).await?;
+        self.command(OP_LE_SET_ADVERTISING_ENABLE, &[0x01]).await?;
+
+        Ok(address)
+    }
+
+    async fn on_event(&mut self, code: u8, params: &[u8]) -> io::Result<()> {
+        // Most events start with the address of the remote device.
+        let mut address = [0; 6];
+        if let Some(bytes) = params.get(..6) {
+            address.copy_from_slice(bytes);
+        }
+
+        match code {
+            EVT_CONNECTION_REQUEST => {
+                let params = [&address[..], &[ROLE_PERIPHERAL]].concat();
+                self.send_command(OP_ACCEPT_CONNECTION_REQUEST, &params).await
+            }
+            EVT_LINK_KEY_REQUEST => match self.link_keys.get(&address) {
+                Some(key) => {
+                    let params = [&address[..], &key[..]].concat();
+                    self.send_command(OP_LINK_KEY_REQUEST_REPLY, &params).await
+                }
+                None => self.send_command(OP_LINK_KEY_REQUEST_NEGATIVE_REPLY, &address).await,
+            },
+            EVT_LINK_KEY_NOTIFICATION if params.len() >= 22 => {
+                let mut key = [0; 16];
+                key.copy_from_slice(&params[6..22]);
+                self.link_keys.insert(address, key);
+                Ok(())
+            }
+            EVT_IO_CAPABILITY_REQUEST => {
+                let reply = [IO_CAPABILITY_NO_INPUT_NO_OUTPUT, 0x00, AUTH_DEDICATED_BONDING];
+                let params = [&address[..], &reply[..]].concat();
+                self.send_command(OP_IO_CAPABILITY_REQUEST_REPLY, &params).await
+            }
+            EVT_USER_CONFIRMATION_REQUEST => {
+                self.send_command(OP_USER_CONFIRMATION_REQUEST_REPLY, &address).await
+            }
+            EVT_LE_META if params.len() >= 4 => {
+                let subevent = params[0];
+                let connected = (subevent == LE_CONNECTION_COMPLETE
+                    || subevent == LE_ENHANCED_CONNECTION_COMPLETE)
+                    && params[1] == 0;
+                if connected {
+                    self.le_connections.insert(u16::from_le_bytes([params[2], params[3]]));
+                }
+                Ok(())
+            }
+            // Advertising stops on connection, so it is turned back on once the link is gone.
+            EVT_DISCONNECTION_COMPLETE if params.len() >= 3 => {
+                let handle = u16::from_le_bytes([params[1], params[2]]);
+                if self.le_connections.remove(&handle) {
+                    self.send_command(OP_LE_SET_ADVERTISING_ENABLE, &[0x01]).await?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn on_acl(&mut self, handle: u16, data: &[u8]) -> io::Result<()> {
+        if data.len() < 4 {
+            return Ok(());
+        }
+
+        let cid = u16::from_le_bytes([data[2], data[3]]);
+        let payload = &data[4..];
+        let response = match cid {
+            L2CAP_ATT_CID => att_response(payload),
+            L2CAP_SIGNALING_CID | L2CAP_LE_SIGNALING_CID => signaling_response(payload),
+            L2CAP_SMP_CID if payload.first() == Some(&SMP_PAIRING_REQUEST) => {
+                Some(vec![SMP_PAIRING_FAILED, SMP_PAIRING_NOT_SUPPORTED])
+            }
+            _ => None,
+        };
+
+        match response {
+            Some(response) => self.send_l2cap(handle, cid, &response).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn run(&mut self) -> io::Result<()> {
+        loop {
+            match self.read_packet().await? {
+                Packet::Event(code, params) => self.on_event(code, &params).await?,
+                Packet::Acl(handle, data) => self.on_acl(handle, &data).await?,
+            }
+        }
+    }
+}
+
+/// The remote device of the tests. Dropping it removes its controller.
+pub struct Peer {
+    pub address: RawAddress,
+    task: JoinHandle<()>,
+}
+
+impl Peer {
+    /// Adds a controller to the rootcanal at `server` and starts playing the peer on it.
+    pub async fn start(server: SocketAddr) -> io::Result<Peer> {
+        let mut host = Host {
+            stream: TcpStream::connect(server).await?,
+            link_keys: HashMap::new(),
+            le_connections: HashSet::new(),
+        };
+        let address = host.setup().await?;
+
+        let task = tokio::spawn(async move {
+            if let Err(e) = host.run().await {
+                eprintln!("Test peer stopped: {}", e);
+            }
+        });
+
+        Ok(Peer { address, task })
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_att_response() {
+        // Primary service discovery, then characteristic discovery.
+        assert_eq!(
+            att_response(&[0x10, 0x01, 0x00, 0xff, 0xff, 0x00, 0x28]).unwrap(),
+            vec![0x11, 6, 0x01, 0x00, 0x03, 0x00, 0xf0, 0xff]
+        );
+        assert_eq!(
+            att_response(&[0x10, 0x04, 0x00, 0xff, 0xff, 0x00, 0x28]).unwrap(),
+            vec![0x01, 0x10, 0x04, 0x00, 0x0a]
+        );
+        assert_eq!(
+            att_response(&[0x08, 0x01, 0x00, 0x03, 0x00, 0x03, 0x28]).unwrap(),
+            vec![0x09, 7, 0x02, 0x00, 0x02, 0x03, 0x00, 0xf1, 0xff]
+        );
+        // Database hash, which the peer doesn't have.
+        assert_eq!(
+            att_response(&[0x08, 0x01, 0x00, 0xff, 0xff, 0x2a, 0x2b]).unwrap(),
+            vec![0x01, 0x08, 0x01, 0x00, 0x0a]
+        );
+
+        assert_eq!(att_response(&[0x0a, 0x03, 0x00]).unwrap(), b"\x0bfloss".to_vec());
+        assert_eq!(att_response(&[0x0a, 0x02, 0x00]).unwrap(), vec![0x01, 0x0a, 0x02, 0x00, 0x01]);
+
+        // Writes aren't supported, write commands and confirmations aren't answered.
+        assert_eq!(
+            att_response(&[0x12, 0x03, 0x00, 0x01]).unwrap(),
+            vec![0x01, 0x12, 0x00, 0x00, 0x06]
+        );
+        assert_eq!(att_response(&[0x52, 0x03, 0x00, 0x01]), None);
+        assert_eq!(att_response(&[0x1e]), None);
+    }
+
+    #[test]
+    fn test_signaling_response() {
+        // SDP connection refused, information request and echo answered, unknown rejected.
+        assert_eq!(
+            signaling_response(&[0x02, 0x07, 0x04, 0x00, 0x01, 0x00, 0x40, 0x00]).unwrap(),
+            vec![0x03, 0x07, 0x08, 0x00, 0x00, 0x00, 0x40, 0x00, 0x02, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            signaling_response(&[0x0a, 0x02, 0x02, 0x00, 0x02, 0x00]).unwrap(),
+            vec![0x0b, 0x02, 0x04, 0x00, 0x02, 0x00, 0x01, 0x00]
+        );
+        assert_eq!(
+            signaling_response(&[0x08, 0x03, 0x01, 0x00, 0xaa]).unwrap(),
+            vec![0x09, 0x03, 0x01, 0x00, 0xaa]
+        );
+        assert_eq!(
+            signaling_response(&[0x12, 0x04, 0x00, 0x00]).unwrap(),
+            vec![0x01, 0x04, 0x02, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(signaling_response(&[0x13, 0x04, 0x02, 0x00, 0x00, 0x00]), None);
+    }
+}
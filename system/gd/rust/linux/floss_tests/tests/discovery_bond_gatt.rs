@@ -0,0 +1,80 @@
+//! Finds the peer, bonds with it, and reads its characteristic over GATT.
+
+use bt_topshim::btif::{BtTransport, Uuid128Bit};
+use btstack::bluetooth::IBluetooth;
+use floss_client::requests;
+use floss_client::ClientEvent;
+use floss_tests::peer::{Peer, CHARACTERISTIC_VALUE, VALUE_HANDLE};
+use floss_tests::{TestBed, TIMEOUT};
+
+/// App UUID of the GATT client of the test.
+const APP_UUID: Uuid128Bit = [
+    0xf1, 0x05, 0x5e, 0x57, 0x00, 0x01, 0x40, 0x00, 0x80, 0x00, 0x00, 0x80, 0x5f, 0x9b, 0x34, 0xfb,
+];
+
+#[tokio::test(flavor = "multi_thread")]
+async fn discovery_bond_and_gatt_read() {
+    let bed = match TestBed::start().await.unwrap() {
+        Some(bed) => bed,
+        None => return,
+    };
+    let peer = Peer::start(bed.rootcanal).await.unwrap();
+    let context = bed.context.clone();
+
+    let mut events = context.lock().unwrap().subscribe_events();
+    requests::start_discovery(context.clone(), TIMEOUT).await.unwrap();
+    let device = requests::wait_for(&mut events, TIMEOUT, |event| match event {
+        ClientEvent::DeviceFound(device, _) if device.address == peer.address => {
+            Some(Ok(device.clone()))
+        }
+        _ => None,
+    })
+    .await
+    .unwrap();
+    requests::cancel_discovery(context.clone(), TIMEOUT).await.unwrap();
+
+    // The client only confirms the Just Works pairing of the device it is bonding with.
+    context.lock().unwrap().bonding_attempt = Some(device.clone());
+    requests::create_bond(context.clone(), device.clone(), BtTransport::Bredr, TIMEOUT)
+        .await
+        .unwrap();
+
+    let client_id =
+        requests::register_gatt_client(context.clone(), APP_UUID, TIMEOUT).await.unwrap();
+    requests::gatt_connect(
+        context.clone(),
+        client_id,
+        peer.address,
+        BtTransport::Le,
+        false,
+        TIMEOUT,
+    )
+    .await
+    .unwrap();
+    let value = requests::gatt_read_characteristic(
+        context.clone(),
+        client_id,
+        peer.address,
+        VALUE_HANDLE as i32,
+        TIMEOUT,
+    )
+    .await
+    .unwrap();
+    assert_eq!(value, CHARACTERISTIC_VALUE);
+    requests::gatt_disconnect(context.clone(), client_id, peer.address, TIMEOUT).await.unwrap();
+
+    bed.events.assert_in_order(&[
+        "discovering-changed",
+        "device-found",
+        "discovering-changed",
+        "bond-state-changed",
+        "gatt-client-registered",
+        "gatt-client-connection-state",
+        "gatt-characteristic-read",
+        "gatt-client-connection-state",
+    ]);
+
+    // Leave nothing behind for the next run.
+    context.lock().unwrap().adapter_dbus.as_ref().unwrap().remove_bond(device);
+    bed.stop().await.unwrap();
+}
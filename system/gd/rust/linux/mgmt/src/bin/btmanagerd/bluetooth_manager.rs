@@ -1,31 +1,81 @@
 use log::{error, info, warn};
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterDetails, AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    IMergedDiscoveryCallback, API_VERSION,
 };
 
+use manager_service::RPCProxy;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::process::Command;
 use std::sync::atomic::Ordering;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
 
-use crate::{config_util, state_machine, ManagerContext};
+use crate::merged_discovery::MergedDiscovery;
+use crate::{config_util, state_machine, virtual_controller, ManagerContext};
 
 const BLUEZ_INIT_TARGET: &str = "bluetoothd";
 
+/// Time given to callbacks to receive `on_shutdown_complete` before the manager exits.
+const SHUTDOWN_EXIT_DELAY: Duration = Duration::from_millis(500);
+
+/// What to do once all adapters have stopped for a shutdown request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ShutdownMode {
+    /// Only notify callbacks.
+    Prepare,
+    /// Notify callbacks and exit the manager.
+    Exit,
+}
+
 /// Implementation of IBluetoothManager.
 pub struct BluetoothManager {
     manager_context: ManagerContext,
     callbacks: HashMap<u32, Box<dyn IBluetoothManagerCallback + Send>>,
     cached_devices: HashMap<i32, bool>,
+    shutdown: Option<ShutdownMode>,
+    /// Server of the virtual controller and the task relaying it.
+    virtual_controller: Option<(SocketAddr, JoinHandle<()>)>,
+    merged_discovery: MergedDiscovery,
 }
 
 impl BluetoothManager {
-    pub(crate) fn new(manager_context: ManagerContext) -> BluetoothManager {
+    pub(crate) fn new(
+        manager_context: ManagerContext,
+        merged_discovery: MergedDiscovery,
+    ) -> BluetoothManager {
         BluetoothManager {
             manager_context,
             callbacks: HashMap::new(),
             cached_devices: HashMap::new(),
+            shutdown: None,
+            virtual_controller: None,
+            merged_discovery,
+        }
+    }
+
+    /// Brings back the virtual controller saved in the config, if any.
+    pub(crate) fn restore_virtual_controller(&mut self) {
+        let server = match config_util::get_virtual_controller() {
+            Some(server) => server,
+            None => return,
+        };
+
+        match server.parse::<SocketAddr>() {
+            Ok(server) => self.start_virtual_controller(Some(server)),
+            Err(_) => warn!("Ignoring invalid virtual controller server {}", server),
+        }
+    }
+
+    fn start_virtual_controller(&mut self, server: Option<SocketAddr>) {
+        if let Some((previous, task)) = self.virtual_controller.take() {
+            info!("Removing virtual controller at {}", previous);
+            task.abort();
         }
+
+        self.virtual_controller = server.map(|server| (server, virtual_controller::start(server)));
     }
 
     pub(crate) fn callback_hci_device_change(&mut self, hci_device: i32, present: bool) {
@@ -56,10 +106,67 @@ impl BluetoothManager {
         for (_, callback) in &self.callbacks {
             callback.on_hci_enabled_changed(hci_device, enabled);
         }
+        self.merged_discovery.adapter_enabled_changed(hci_device, enabled);
+
+        if !enabled {
+            self.check_shutdown_complete();
+        }
+    }
+
+    pub(crate) fn callback_adapter_recovered(&mut self, hci_device: i32, crash_count: u32) {
+        info!("hci{} recovered after crash #{}", hci_device, crash_count);
+        for (_, callback) in &self.callbacks {
+            callback.on_adapter_recovered(hci_device, crash_count);
+        }
+    }
+
+    fn start_shutdown(&mut self, mode: ShutdownMode) {
+        info!("Shutdown requested: {:?}", mode);
+        self.shutdown = Some(mode);
+
+        // Stop the adapters directly through the state machine so that the saved enabled state
+        // is left untouched for the next boot.
+        for (hci_interface, _) in self.cached_devices.iter().filter(|(_, enabled)| **enabled) {
+            self.manager_context.proxy.stop_bluetooth(*hci_interface);
+        }
+
+        self.check_shutdown_complete();
+    }
+
+    fn check_shutdown_complete(&mut self) {
+        if self.shutdown.is_none() || self.cached_devices.values().any(|enabled| *enabled) {
+            return;
+        }
+
+        info!("All adapters stopped, shutdown complete");
+        for (_, callback) in &self.callbacks {
+            callback.on_shutdown_complete();
+        }
+
+        if self.shutdown.take() == Some(ShutdownMode::Exit) {
+            // Callbacks are sent asynchronously so give them a moment to go out.
+            tokio::spawn(async move {
+                sleep(SHUTDOWN_EXIT_DELAY).await;
+                std::process::exit(0);
+            });
+        }
+    }
+
+    /// Makes the manager forget `callback` if its client goes away. Returns its id.
+    fn watch_callback_disconnect<T: RPCProxy + ?Sized>(&self, callback: &mut Box<T>) -> u32 {
+        let tx = self.manager_context.proxy.get_tx();
+
+        callback.register_disconnect(Box::new(move |cb_id| {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _result = tx.send(state_machine::Message::CallbackDisconnected(cb_id)).await;
+            });
+        }))
     }
 
     pub(crate) fn callback_disconnected(&mut self, id: u32) {
         self.callbacks.remove(&id);
+        self.merged_discovery.remove_callback(id);
     }
 
     pub(crate) fn get_floss_enabled_internal(&mut self) -> bool {
@@ -107,15 +214,7 @@ impl IBluetoothManager for BluetoothManager {
     }
 
     fn register_callback(&mut self, mut callback: Box<dyn IBluetoothManagerCallback + Send>) {
-        let tx = self.manager_context.proxy.get_tx();
-
-        let id = callback.register_disconnect(Box::new(move |cb_id| {
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                let _result = tx.send(state_machine::Message::CallbackDisconnected(cb_id)).await;
-            });
-        }));
-
+        let id = self.watch_callback_disconnect(&mut callback);
         self.callbacks.insert(id, callback);
     }
 
@@ -156,4 +255,85 @@ impl IBluetoothManager for BluetoothManager {
 
         adapters
     }
+
+    fn get_adapter_details(&mut self) -> Vec<AdapterDetails> {
+        config_util::list_hci_devices()
+            .into_iter()
+            .map(|hci_interface| AdapterDetails {
+                enabled: *self.cached_devices.get(&hci_interface).unwrap_or(&false),
+                ..config_util::get_hci_device_details(hci_interface)
+            })
+            .collect()
+    }
+
+    fn prepare_shutdown(&mut self) {
+        self.start_shutdown(ShutdownMode::Prepare);
+    }
+
+    fn shutdown(&mut self) {
+        self.start_shutdown(ShutdownMode::Exit);
+    }
+
+    fn get_api_version(&mut self) -> u32 {
+        API_VERSION
+    }
+
+    fn reload_config(&mut self, hci_interface: i32) -> bool {
+        info!("Reloading the config of {}", hci_interface);
+        self.manager_context.proxy.reload_config(hci_interface)
+    }
+
+    fn set_virtual_controller(&mut self, server: String) -> bool {
+        let parsed = match &server[..] {
+            "" => None,
+            _ => match server.parse::<SocketAddr>() {
+                Ok(parsed) => Some(parsed),
+                Err(_) => {
+                    warn!("Invalid virtual controller server {}", server);
+                    return false;
+                }
+            },
+        };
+
+        if !config_util::write_virtual_controller(parsed.as_ref().map(|_| &server[..])) {
+            error!("Config is not successfully modified");
+        }
+
+        self.start_virtual_controller(parsed);
+        true
+    }
+
+    fn get_virtual_controller(&mut self) -> String {
+        self.virtual_controller.as_ref().map_or(String::new(), |(server, _)| server.to_string())
+    }
+
+    fn register_merged_discovery_callback(
+        &mut self,
+        mut callback: Box<dyn IMergedDiscoveryCallback + Send>,
+    ) -> u32 {
+        let id = self.watch_callback_disconnect(&mut callback);
+        self.merged_discovery.add_callback(id, callback);
+        id
+    }
+
+    fn unregister_merged_discovery_callback(&mut self, callback_id: u32) -> bool {
+        match self.merged_discovery.remove_callback(callback_id) {
+            Some(mut callback) => callback.unregister(callback_id),
+            None => false,
+        }
+    }
+
+    fn start_merged_discovery(&mut self) -> bool {
+        let enabled = self
+            .cached_devices
+            .iter()
+            .filter(|(_, enabled)| **enabled)
+            .map(|(hci_interface, _)| *hci_interface)
+            .collect();
+        self.merged_discovery.start(enabled)
+    }
+
+    fn cancel_merged_discovery(&mut self) -> bool {
+        self.merged_discovery.cancel()
+    }
 }
@@ -1,10 +1,11 @@
 use dbus::arg::RefArg;
 use dbus::strings::Path;
-use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
-use dbus_projection::{dbus_generated, DisconnectWatcher};
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+use dbus_projection::DisconnectWatcher;
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterDetails, AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    IMergedDiscoveryCallback, MergedFoundDevice,
 };
 use manager_service::RPCProxy;
 
@@ -16,46 +17,35 @@ pub struct AdapterWithEnabledDbus {
     enabled: bool,
 }
 
-/// D-Bus projection of IBluetoothManager.
-struct BluetoothManagerDBus {}
-
-#[generate_dbus_exporter(export_bluetooth_manager_dbus_obj, "org.chromium.bluetooth.Manager")]
-impl IBluetoothManager for BluetoothManagerDBus {
-    #[dbus_method("Start")]
-    fn start(&mut self, hci_interface: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("Stop")]
-    fn stop(&mut self, hci_interface: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetAdapterEnabled")]
-    fn get_adapter_enabled(&mut self, hci_interface: i32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothManagerCallback + Send>) {
-        dbus_generated!()
-    }
+#[dbus_propmap(AdapterDetails)]
+pub struct AdapterDetailsDbus {
+    hci_interface: i32,
+    enabled: bool,
+    bus: String,
+    vendor_id: u32,
+    product_id: u32,
+    manufacturer: i32,
+    hci_version: i32,
+    hci_revision: i32,
+    features: Vec<String>,
+}
 
-    #[dbus_method("GetFlossEnabled")]
-    fn get_floss_enabled(&mut self) -> bool {
-        dbus_generated!()
-    }
+#[dbus_propmap(MergedFoundDevice)]
+pub struct MergedFoundDeviceDbus {
+    address: String,
+    name: String,
+    hci_interface: i32,
+    rssi: i32,
+    hci_interfaces: Vec<i32>,
+}
 
-    #[dbus_method("SetFlossEnabled")]
-    fn set_floss_enabled(&mut self, enabled: bool) {
-        dbus_generated!()
-    }
+/// D-Bus projection of IBluetoothManager.
+struct BluetoothManagerDBus {}
 
-    #[dbus_method("GetAvailableAdapters")]
-    fn get_available_adapters(&mut self) -> Vec<AdapterWithEnabled> {
-        dbus_generated!()
-    }
-}
+manager_service::bluetooth_manager_dbus!(
+    exporter BluetoothManagerDBus,
+    export_bluetooth_manager_dbus_obj
+);
 
 /// D-Bus projection of IBluetoothManagerCallback.
 struct BluetoothManagerCallbackDBus {}
@@ -67,4 +57,22 @@ impl IBluetoothManagerCallback for BluetoothManagerCallbackDBus {
 
     #[dbus_method("OnHciEnabledChanged")]
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool) {}
+
+    #[dbus_method("OnShutdownComplete")]
+    fn on_shutdown_complete(&self) {}
+
+    #[dbus_method("OnAdapterRecovered")]
+    fn on_adapter_recovered(&self, hci_interface: i32, crash_count: u32) {}
+}
+
+/// D-Bus projection of IMergedDiscoveryCallback.
+struct MergedDiscoveryCallbackDBus {}
+
+#[dbus_proxy_obj(MergedDiscoveryCallback, "org.chromium.bluetooth.MergedDiscoveryCallback")]
+impl IMergedDiscoveryCallback for MergedDiscoveryCallbackDBus {
+    #[dbus_method("OnMergedDeviceFound")]
+    fn on_merged_device_found(&self, device: MergedFoundDevice) {}
+
+    #[dbus_method("OnMergedDiscoveringChanged")]
+    fn on_merged_discovering_changed(&self, discovering: bool) {}
 }
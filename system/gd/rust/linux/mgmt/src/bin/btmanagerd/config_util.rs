@@ -1,9 +1,14 @@
 use log::LevelFilter;
+use manager_service::iface_bluetooth_manager::AdapterDetails;
 use serde_json::{Map, Value};
+use std::path::Path;
 
 // Directory for Bluetooth hci devices
 pub const HCI_DEVICES_DIR: &str = "/sys/class/bluetooth";
 
+// Directory for the kernel's debug information about hci devices
+const HCI_DEBUGFS_DIR: &str = "/sys/kernel/debug/bluetooth";
+
 // File to store the Bluetooth daemon to use (bluez or floss)
 const BLUETOOTH_DAEMON_CURRENT: &str = "/var/lib/bluetooth/bluetooth-daemon.current";
 
@@ -110,6 +115,48 @@ fn modify_hci_n_enabled_internal(config: String, n: i32, enabled: bool) -> Optio
     }
 }
 
+/// Returns the server of the virtual controller to bring up, if one is set.
+pub fn get_virtual_controller() -> Option<String> {
+    get_virtual_controller_internal(read_config().ok()?)
+}
+
+fn get_virtual_controller_internal(config: String) -> Option<String> {
+    serde_json::from_str::<Value>(config.as_str())
+        .ok()?
+        .get("virtual_controller")?
+        .as_str()
+        .map(String::from)
+}
+
+/// Saves the server of the virtual controller, or removes it if `server` is None.
+pub fn write_virtual_controller(server: Option<&str>) -> bool {
+    if !fix_config_file_format() {
+        false
+    } else {
+        match read_config()
+            .ok()
+            .and_then(|config| write_virtual_controller_internal(config, server))
+        {
+            Some(s) => std::fs::write(BTMANAGERD_CONF, s).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+fn write_virtual_controller_internal(config: String, server: Option<&str>) -> Option<String> {
+    let mut o = serde_json::from_str::<Value>(config.as_str()).ok()?;
+    let map = o.as_object_mut()?;
+    match server {
+        Some(server) => {
+            map.insert("virtual_controller".to_string(), Value::String(server.to_string()));
+        }
+        None => {
+            map.remove("virtual_controller");
+        }
+    }
+    serde_json::ser::to_string_pretty(&o).ok()
+}
+
 pub fn list_hci_devices() -> Vec<i32> {
     hci_devices_string_to_int(list_hci_devices_string())
 }
@@ -130,6 +177,81 @@ fn hci_devices_string_to_int(devices: Vec<String>) -> Vec<i32> {
         .collect()
 }
 
+/// Power-cycles the USB device behind hci N by deauthorizing and reauthorizing it, which makes the
+/// kernel re-enumerate the controller. Returns false if hci N isn't backed by a USB device or the
+/// power cycle couldn't be requested.
+pub fn power_cycle_hci_n(n: i32) -> bool {
+    // The hci device links to the USB interface, whose parent is the USB device itself.
+    let authorized = match std::fs::canonicalize(format!("{}/hci{}/device/..", HCI_DEVICES_DIR, n))
+    {
+        Ok(path) => path.join("authorized"),
+        Err(_) => return false,
+    };
+
+    if !authorized.exists() {
+        return false;
+    }
+
+    std::fs::write(&authorized, "0").is_ok() && std::fs::write(&authorized, "1").is_ok()
+}
+
+fn read_attribute(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn parse_hex_id(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok()
+}
+
+fn bus_from_subsystem(subsystem: &str) -> &'static str {
+    match subsystem {
+        "usb" => "usb",
+        "serial" | "tty" => "uart",
+        "sdio" => "sdio",
+        "pci" => "pci",
+        _ => "unknown",
+    }
+}
+
+fn parse_features(features: &str) -> Vec<String> {
+    features.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).map(String::from).collect()
+}
+
+/// Describes the controller of hci N from what sysfs and debugfs expose about it.
+pub fn get_hci_device_details(n: i32) -> AdapterDetails {
+    let device = Path::new(HCI_DEVICES_DIR).join(format!("hci{}", n)).join("device");
+    let subsystem = std::fs::read_link(device.join("subsystem"))
+        .ok()
+        .and_then(|p| p.file_name().and_then(|f| f.to_str()).map(String::from));
+    let bus = bus_from_subsystem(subsystem.as_deref().unwrap_or(""));
+
+    // USB ids are on the USB device, which is the parent of the interface hci N is bound to.
+    let (vendor, product) = match bus {
+        "usb" => (device.join("../idVendor"), device.join("../idProduct")),
+        _ => (device.join("vendor"), device.join("device")),
+    };
+    let read_id = |path: &Path| read_attribute(path).and_then(|v| parse_hex_id(&v)).unwrap_or(0);
+
+    let debugfs = Path::new(HCI_DEBUGFS_DIR).join(format!("hci{}", n));
+    let read_number = |name: &str| {
+        read_attribute(&debugfs.join(name)).and_then(|v| v.parse::<i32>().ok()).unwrap_or(-1)
+    };
+
+    AdapterDetails {
+        hci_interface: n,
+        enabled: false,
+        bus: bus.to_string(),
+        vendor_id: read_id(&vendor),
+        product_id: read_id(&product),
+        manufacturer: read_number("manufacturer"),
+        hci_version: read_number("hci_version"),
+        hci_revision: read_number("hci_revision"),
+        features: std::fs::read_to_string(debugfs.join("features"))
+            .map(|f| parse_features(&f))
+            .unwrap_or_default(),
+    }
+}
+
 pub fn list_pid_files(pid_dir: &str) -> Vec<String> {
     match std::fs::read_dir(pid_dir) {
         Ok(entries) => entries
@@ -213,6 +335,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn modify_virtual_controller() {
+        let config = "{\"hci0\":\n{\"enabled\": true}}".to_string();
+        assert_eq!(get_virtual_controller_internal(config.clone()), None);
+
+        let modified_string =
+            write_virtual_controller_internal(config, Some("127.0.0.1:6402")).unwrap();
+        assert_eq!(
+            get_virtual_controller_internal(modified_string.clone()).as_deref(),
+            Some("127.0.0.1:6402")
+        );
+        assert_eq!(is_hci_n_enabled_internal_wrapper(modified_string.clone(), 0), true);
+
+        let modified_string = write_virtual_controller_internal(modified_string, None).unwrap();
+        assert_eq!(get_virtual_controller_internal(modified_string), None);
+    }
+
     #[test]
     fn test_hci_devices_string_to_int_none() {
         assert_eq!(hci_devices_string_to_int(vec!["somethingelse".to_string()]), Vec::<i32>::new());
@@ -230,4 +369,28 @@ mod tests {
             vec![0, 1]
         );
     }
+
+    #[test]
+    fn test_parse_hex_id() {
+        assert_eq!(parse_hex_id("8087"), Some(0x8087));
+        assert_eq!(parse_hex_id("0x02d0\n"), Some(0x02d0));
+        assert_eq!(parse_hex_id(""), None);
+        assert_eq!(parse_hex_id("usb"), None);
+    }
+
+    #[test]
+    fn test_bus_from_subsystem() {
+        assert_eq!(bus_from_subsystem("usb"), "usb");
+        assert_eq!(bus_from_subsystem("serial"), "uart");
+        assert_eq!(bus_from_subsystem("tty"), "uart");
+        assert_eq!(bus_from_subsystem("virtio"), "unknown");
+    }
+
+    #[test]
+    fn test_parse_features() {
+        assert_eq!(
+            parse_features(" 0: 0xbf 0xfe 0x0f\n 1: 0x0f 0x00 0x00\nLE: 0xff 0x49\n\n"),
+            vec!["0: 0xbf 0xfe 0x0f", "1: 0x0f 0x00 0x00", "LE: 0xff 0x49"]
+        );
+    }
 }
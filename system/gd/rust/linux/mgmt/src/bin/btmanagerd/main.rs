@@ -2,9 +2,12 @@ mod bluetooth_manager;
 mod bluetooth_manager_dbus;
 mod config_util;
 mod dbus_arg;
+mod merged_discovery;
 mod state_machine;
+mod virtual_controller;
 
 use crate::bluetooth_manager::BluetoothManager;
+use crate::merged_discovery::MergedDiscovery;
 use dbus::channel::MatchingReceiver;
 use dbus::message::MatchRule;
 use dbus_crossroads::Crossroads;
@@ -87,7 +90,13 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     cr.set_object_manager_support(Some(conn.clone()));
     cr.insert("/", &[cr.object_manager()], {});
 
-    let bluetooth_manager = Arc::new(Mutex::new(Box::new(BluetoothManager::new(manager_context))));
+    // Shared so that merged discovery can add the objects adapters call back into as they come.
+    let cr = Arc::new(Mutex::new(cr));
+    let merged_discovery = MergedDiscovery::new(conn.clone(), cr.clone());
+
+    let bluetooth_manager =
+        Arc::new(Mutex::new(Box::new(BluetoothManager::new(manager_context, merged_discovery))));
+    bluetooth_manager.lock().unwrap().restore_virtual_controller();
 
     // Set up the disconnect watcher to monitor client disconnects.
     let disconnect_watcher = Arc::new(Mutex::new(DisconnectWatcher::new()));
@@ -98,7 +107,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     bluetooth_manager_dbus::export_bluetooth_manager_dbus_obj(
         "/org/chromium/bluetooth/Manager",
         conn.clone(),
-        &mut cr,
+        &mut cr.lock().unwrap(),
         bluetooth_manager.clone(),
         disconnect_watcher.clone(),
     );
@@ -107,7 +116,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     conn.start_receive(
         MatchRule::new_method_call(),
         Box::new(move |msg, conn| {
-            cr.handle_message(msg, conn).unwrap();
+            cr.lock().unwrap().handle_message(msg, conn).unwrap();
             true
         }),
     );
@@ -0,0 +1,437 @@
+//! Discovery merged across adapters.
+//!
+//! With more than one controller, each adapter daemon discovers on its own and reports to its own
+//! callbacks, so a device in range of two controllers is found twice and clients have to follow
+//! every adapter. Merged discovery has the manager start discovery on all enabled adapters and
+//! listen to them itself, as a client of each btadapterd. Devices are kept by address, so clients
+//! get a single stream of found devices, each telling which adapters heard it.
+
+use dbus::arg::{prop_cast, PropMap};
+use dbus::nonblock::{Proxy, SyncConnection};
+use dbus::Path;
+use dbus_crossroads::{Crossroads, IfaceToken};
+use log::{debug, info, warn};
+use manager_service::iface_bluetooth_manager::{IMergedDiscoveryCallback, MergedFoundDevice};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration};
+
+const ADAPTER_SERVICE: &str = "org.chromium.bluetooth";
+const ADAPTER_INTERFACE: &str = "org.chromium.bluetooth.Bluetooth";
+const ADAPTER_CALLBACK_INTERFACE: &str = "org.chromium.bluetooth.BluetoothCallback";
+
+/// Time given to an adapter daemon to answer a method call.
+const ADAPTER_CALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An adapter that was just enabled may not be on the bus or able to discover yet, so starting
+/// discovery on it is tried this many times, this far apart.
+const START_ATTEMPTS: u32 = 5;
+const START_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+fn adapter_path(hci_interface: i32) -> Path<'static> {
+    Path::new(format!("/org/chromium/bluetooth/hci{}/adapter", hci_interface)).unwrap()
+}
+
+/// Path of the callback object registered on the adapter `hci_interface`.
+fn callback_path(hci_interface: i32) -> Path<'static> {
+    Path::new(format!("/org/chromium/bluetooth/Manager/merged_discovery/hci{}", hci_interface))
+        .unwrap()
+}
+
+/// Cancels discovery on `hci_interface` if `cancel`, and removes the callback registered on it.
+async fn release_adapter(
+    conn: Arc<SyncConnection>,
+    hci_interface: i32,
+    cancel: bool,
+    callback_id: Option<u32>,
+) {
+    let proxy =
+        Proxy::new(ADAPTER_SERVICE, adapter_path(hci_interface), ADAPTER_CALL_TIMEOUT, conn);
+    if cancel {
+        let result: Result<(u32,), dbus::Error> =
+            proxy.method_call(ADAPTER_INTERFACE, "CancelDiscovery", ()).await;
+        if let Err(e) = result {
+            debug!("Merged discovery: hci{}: CancelDiscovery failed: {}", hci_interface, e);
+        }
+    }
+    if let Some(callback_id) = callback_id {
+        let result: Result<(bool,), dbus::Error> =
+            proxy.method_call(ADAPTER_INTERFACE, "UnregisterCallback", (callback_id,)).await;
+        if let Err(e) = result {
+            debug!("Merged discovery: hci{}: UnregisterCallback failed: {}", hci_interface, e);
+        }
+    }
+}
+
+/// Data of the callback object of an adapter.
+struct AdapterCallback {
+    hci_interface: i32,
+    discovery: MergedDiscovery,
+}
+
+/// Discovery on one adapter.
+struct AdapterDiscovery {
+    /// Id of the callback registered on the adapter, once it is.
+    callback_id: Option<u32>,
+    /// Whether the adapter said it is discovering.
+    discovering: bool,
+}
+
+/// Devices found since merged discovery started, by address.
+#[derive(Default)]
+struct FoundDevices(HashMap<String, MergedFoundDevice>);
+
+impl FoundDevices {
+    /// Adds a device `hci_interface` found. Returns it if it is worth telling the callbacks about,
+    /// which is when it is new, from another adapter, or named for the first time.
+    fn add(
+        &mut self,
+        hci_interface: i32,
+        address: String,
+        name: String,
+        rssi: i32,
+    ) -> Option<MergedFoundDevice> {
+        let device = match self.0.get_mut(&address) {
+            Some(device) => device,
+            None => {
+                let device = MergedFoundDevice {
+                    address: address.clone(),
+                    name,
+                    hci_interface,
+                    rssi,
+                    hci_interfaces: vec![hci_interface],
+                };
+                self.0.insert(address, device.clone());
+                return Some(device);
+            }
+        };
+
+        // An RSSI of 0 is unknown, so any other is stronger.
+        if rssi != 0 && (device.rssi == 0 || rssi > device.rssi) {
+            device.rssi = rssi;
+            device.hci_interface = hci_interface;
+        }
+
+        let mut changed = false;
+        if !device.hci_interfaces.contains(&hci_interface) {
+            device.hci_interfaces.push(hci_interface);
+            changed = true;
+        }
+        if device.name.is_empty() && !name.is_empty() {
+            device.name = name;
+            changed = true;
+        }
+
+        if changed {
+            Some(device.clone())
+        } else {
+            None
+        }
+    }
+}
+
+struct State {
+    callbacks: HashMap<u32, Box<dyn IMergedDiscoveryCallback + Send>>,
+    /// Adapters discovery was started on and that haven't stopped discovering yet. Merged
+    /// discovery runs as long as there is one.
+    adapters: HashMap<i32, AdapterDiscovery>,
+    devices: FoundDevices,
+}
+
+/// Runs merged discovery.
+///
+/// The callback objects are served from the same `Crossroads` as the manager API, which is locked
+/// while their methods run. So that they can lock the state, the state is never held while
+/// locking the `Crossroads`.
+#[derive(Clone)]
+pub struct MergedDiscovery {
+    conn: Arc<SyncConnection>,
+    cr: Arc<Mutex<Crossroads>>,
+    callback_iface: IfaceToken<AdapterCallback>,
+    state: Arc<Mutex<State>>,
+}
+
+impl MergedDiscovery {
+    pub fn new(conn: Arc<SyncConnection>, cr: Arc<Mutex<Crossroads>>) -> MergedDiscovery {
+        let callback_iface = cr.lock().unwrap().register(ADAPTER_CALLBACK_INTERFACE, |b| {
+            // Adapters stamp their callbacks with trailing arguments, which are left unread. The
+            // methods of the interface not needed here are answered with an error, which adapters
+            // ignore.
+            b.method(
+                "OnDeviceFound",
+                ("remote_device", "info"),
+                (),
+                |_ctx, data: &mut AdapterCallback, (device, info): (PropMap, PropMap)| {
+                    data.discovery.device_found(data.hci_interface, &device, &info);
+                    Ok(())
+                },
+            );
+            b.method(
+                "OnDiscoveringChanged",
+                ("discovering",),
+                (),
+                |_ctx, data: &mut AdapterCallback, (discovering,): (bool,)| {
+                    data.discovery.discovering_changed(data.hci_interface, discovering);
+                    Ok(())
+                },
+            );
+        });
+
+        MergedDiscovery {
+            conn,
+            cr,
+            callback_iface,
+            state: Arc::new(Mutex::new(State {
+                callbacks: HashMap::new(),
+                adapters: HashMap::new(),
+                devices: FoundDevices::default(),
+            })),
+        }
+    }
+
+    pub fn add_callback(&self, id: u32, callback: Box<dyn IMergedDiscoveryCallback + Send>) {
+        self.state.lock().unwrap().callbacks.insert(id, callback);
+    }
+
+    pub fn remove_callback(&self, id: u32) -> Option<Box<dyn IMergedDiscoveryCallback + Send>> {
+        self.state.lock().unwrap().callbacks.remove(&id)
+    }
+
+    /// Starts discovery on `hci_interfaces`. Returns false if there are none.
+    pub fn start(&self, hci_interfaces: Vec<i32>) -> bool {
+        if hci_interfaces.is_empty() {
+            return false;
+        }
+        for hci_interface in hci_interfaces.iter() {
+            self.export_callback(*hci_interface);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.adapters.is_empty() {
+            info!("Starting merged discovery on hci{:?}", hci_interfaces);
+            state.devices = FoundDevices::default();
+            for callback in state.callbacks.values() {
+                callback.on_merged_discovering_changed(true);
+            }
+        }
+        for hci_interface in hci_interfaces {
+            self.start_on(&mut state, hci_interface);
+        }
+        true
+    }
+
+    /// Stops discovery on all adapters. Returns false if merged discovery wasn't running.
+    pub fn cancel(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.adapters.is_empty() {
+            return false;
+        }
+
+        info!("Cancelling merged discovery");
+        let hci_interfaces: Vec<i32> = state.adapters.keys().cloned().collect();
+        for hci_interface in hci_interfaces {
+            self.stop_on(&mut state, hci_interface, true);
+        }
+        true
+    }
+
+    /// Joins adapters enabled while merged discovery runs, and forgets adapters that go away.
+    pub fn adapter_enabled_changed(&self, hci_interface: i32, enabled: bool) {
+        if !self.is_discovering() {
+            return;
+        }
+        if enabled {
+            self.export_callback(hci_interface);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // The enabled adapter may have been added while the state wasn't held.
+        if enabled && !state.adapters.is_empty() {
+            self.start_on(&mut state, hci_interface);
+        } else if !enabled && state.adapters.remove(&hci_interface).is_some() {
+            // The daemon is gone, and its callback with it.
+            Self::check_done(&mut state);
+        }
+    }
+
+    fn is_discovering(&self) -> bool {
+        !self.state.lock().unwrap().adapters.is_empty()
+    }
+
+    fn export_callback(&self, hci_interface: i32) {
+        let path = callback_path(hci_interface);
+        let mut cr = self.cr.lock().unwrap();
+        if !cr.has_interface(&path, self.callback_iface) {
+            let data = AdapterCallback { hci_interface, discovery: self.clone() };
+            cr.insert(path, &[self.callback_iface], data);
+        }
+    }
+
+    fn start_on(&self, state: &mut State, hci_interface: i32) {
+        if state.adapters.contains_key(&hci_interface) {
+            return;
+        }
+        state
+            .adapters
+            .insert(hci_interface, AdapterDiscovery { callback_id: None, discovering: false });
+
+        let discovery = self.clone();
+        tokio::spawn(async move {
+            let proxy = Proxy::new(
+                ADAPTER_SERVICE,
+                adapter_path(hci_interface),
+                ADAPTER_CALL_TIMEOUT,
+                discovery.conn.clone(),
+            );
+
+            for attempt in 1..=START_ATTEMPTS {
+                if attempt > 1 {
+                    sleep(START_RETRY_DELAY).await;
+                }
+                match discovery.start_adapter_discovery(&proxy, hci_interface).await {
+                    Ok(()) => return,
+                    Err(e) => debug!("Merged discovery: hci{}: {}", hci_interface, e),
+                }
+            }
+
+            warn!("Merged discovery: failed to start discovery on hci{}", hci_interface);
+            let mut state = discovery.state.lock().unwrap();
+            discovery.stop_on(&mut state, hci_interface, false);
+        });
+    }
+
+    /// Registers the callback of `hci_interface` if it isn't yet, and starts discovery on it.
+    async fn start_adapter_discovery(
+        &self,
+        proxy: &Proxy<'static, Arc<SyncConnection>>,
+        hci_interface: i32,
+    ) -> Result<(), String> {
+        let registered = match self.state.lock().unwrap().adapters.get(&hci_interface) {
+            Some(adapter) => adapter.callback_id.is_some(),
+            // Cancelled in the meantime.
+            None => return Ok(()),
+        };
+
+        if !registered {
+            let (callback_id,): (u32,) = proxy
+                .method_call(ADAPTER_INTERFACE, "RegisterCallback", (callback_path(hci_interface),))
+                .await
+                .map_err(|e| format!("RegisterCallback failed: {}", e))?;
+
+            let mut state = self.state.lock().unwrap();
+            match state.adapters.get_mut(&hci_interface) {
+                Some(adapter) => adapter.callback_id = Some(callback_id),
+                None => {
+                    // Cancelled while registering.
+                    tokio::spawn(release_adapter(
+                        self.conn.clone(),
+                        hci_interface,
+                        false,
+                        Some(callback_id),
+                    ));
+                    return Ok(());
+                }
+            }
+        }
+
+        let (status,): (u32,) = proxy
+            .method_call(ADAPTER_INTERFACE, "StartDiscovery", ())
+            .await
+            .map_err(|e| format!("StartDiscovery failed: {}", e))?;
+        match status {
+            0 => Ok(()),
+            status => Err(format!("StartDiscovery failed with status {}", status)),
+        }
+    }
+
+    /// Stops following `hci_interface`, cancelling its discovery if `cancel`.
+    fn stop_on(&self, state: &mut State, hci_interface: i32, cancel: bool) {
+        let adapter = match state.adapters.remove(&hci_interface) {
+            Some(adapter) => adapter,
+            None => return,
+        };
+
+        tokio::spawn(release_adapter(
+            self.conn.clone(),
+            hci_interface,
+            cancel,
+            adapter.callback_id,
+        ));
+        Self::check_done(state);
+    }
+
+    /// Ends merged discovery once no adapter is left.
+    fn check_done(state: &mut State) {
+        if !state.adapters.is_empty() {
+            return;
+        }
+
+        info!("Merged discovery done, {} devices found", state.devices.0.len());
+        for callback in state.callbacks.values() {
+            callback.on_merged_discovering_changed(false);
+        }
+    }
+
+    fn device_found(&self, hci_interface: i32, device: &PropMap, info: &PropMap) {
+        let address = match prop_cast::<String>(device, "address") {
+            Some(address) => address.clone(),
+            None => return,
+        };
+        let name = prop_cast::<String>(device, "name").cloned().unwrap_or_default();
+        let rssi = prop_cast::<i32>(info, "rssi").cloned().unwrap_or(0);
+
+        let mut state = self.state.lock().unwrap();
+        if !state.adapters.contains_key(&hci_interface) {
+            return;
+        }
+        if let Some(device) = state.devices.add(hci_interface, address, name, rssi) {
+            for callback in state.callbacks.values() {
+                callback.on_merged_device_found(device.clone());
+            }
+        }
+    }
+
+    fn discovering_changed(&self, hci_interface: i32, discovering: bool) {
+        let mut state = self.state.lock().unwrap();
+        let adapter = match state.adapters.get_mut(&hci_interface) {
+            Some(adapter) => adapter,
+            None => return,
+        };
+
+        if discovering {
+            adapter.discovering = true;
+        } else if adapter.discovering {
+            // Discovery ran its course on this adapter, or another client cancelled it.
+            debug!("Merged discovery: hci{} stopped discovering", hci_interface);
+            self.stop_on(&mut state, hci_interface, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_found_devices_are_merged() {
+        let mut devices = FoundDevices::default();
+        let address = || String::from("11:22:33:44:55:66");
+
+        let device = devices.add(0, address(), String::new(), -70).unwrap();
+        assert_eq!((device.hci_interface, device.rssi, device.hci_interfaces), (0, -70, vec![0]));
+        // The same adapter again, with nothing new.
+        assert_eq!(devices.add(0, address(), String::new(), -80), None);
+
+        // Another adapter, which hears it better.
+        let device = devices.add(1, address(), String::new(), -50).unwrap();
+        assert_eq!(
+            (device.hci_interface, device.rssi, device.hci_interfaces),
+            (1, -50, vec![0, 1])
+        );
+        assert_eq!(devices.add(1, address(), String::new(), 0), None);
+
+        let device = devices.add(0, address(), String::from("Speaker"), -75).unwrap();
+        assert_eq!((device.name.as_str(), device.hci_interface), ("Speaker", 1));
+        assert_eq!(devices.add(0, address(), String::from("Other"), -75), None);
+    }
+}
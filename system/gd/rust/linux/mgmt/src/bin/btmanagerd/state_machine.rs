@@ -8,6 +8,7 @@ use regex::Regex;
 use std::cmp;
 use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::io::unix::AsyncFd;
 use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
@@ -49,6 +50,7 @@ pub enum Message {
     HciDeviceChange(inotify::EventMask, Option<String>),
     CallbackDisconnected(u32),
     CommandTimeout(),
+    ReloadConfig(i32),
 }
 
 pub struct StateMachineContext {
@@ -89,6 +91,13 @@ const COMMAND_TIMEOUT_DURATION: Duration = Duration::from_secs(3);
 /// /sys/class/bluetooth to become available.
 const HCI_DEVICE_SLEEP_MAX_SECONDS: u64 = 64;
 
+/// A crash within this long of the stack starting counts towards the crash loop limit.
+const CRASH_LOOP_DURATION: Duration = Duration::from_secs(30);
+
+/// Number of crashes in a row, each shortly after the stack started, the manager recovers from
+/// before leaving the adapter off.
+const MAX_RECOVERY_ATTEMPTS: u32 = 3;
+
 impl StateMachineProxy {
     pub fn start_bluetooth(&self, hci_interface: i32) {
         let tx = self.tx.clone();
@@ -112,6 +121,20 @@ impl StateMachineProxy {
         });
     }
 
+    /// Asks the stack running on `hci_interface` to reload its runtime config. Returns false if
+    /// the adapter isn't on.
+    pub fn reload_config(&self, hci_interface: i32) -> bool {
+        if self.get_state() != State::On {
+            return false;
+        }
+
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(Message::ReloadConfig(hci_interface)).await;
+        });
+        true
+    }
+
     pub fn get_state(&self) -> State {
         // This assumes that self.state is never locked for a long period, i.e. never lock() and
         // await for something else without unlocking. Otherwise this function will block.
@@ -362,6 +385,7 @@ pub async fn mainloop(
                     prev_state = *context.state_machine.state.lock().unwrap();
                 }
                 let hci;
+                let mut recovered = None;
 
                 match action {
                     AdapterStateActions::StartBluetooth(i) => {
@@ -396,6 +420,8 @@ pub async fn mainloop(
                             }
                             false => warn!("unexpected BluetoothStarted pid{} hci{}", pid, hci),
                         }
+
+                        recovered = context.state_machine.take_recovery();
                     }
                     AdapterStateActions::BluetoothStopped(i) => {
                         next_state = State::Off;
@@ -420,6 +446,10 @@ pub async fn mainloop(
                         .unwrap()
                         .callback_hci_enabled_change(hci, next_state == State::On);
                 }
+
+                if let Some(crash_count) = recovered {
+                    bluetooth_manager.lock().unwrap().callback_adapter_recovered(hci, crash_count);
+                }
             }
 
             // Monitored pid directory has a change
@@ -471,6 +501,9 @@ pub async fn mainloop(
                     match get_hci_index_from_device(&fname) {
                         Some(hci) => {
                             bluetooth_manager.lock().unwrap().callback_hci_device_change(hci, true);
+                            if context.state_machine.action_on_hci_device_added(hci) {
+                                command_timeout.reset(COMMAND_TIMEOUT_DURATION);
+                            }
                         }
                         _ => (),
                     }
@@ -506,6 +539,10 @@ pub async fn mainloop(
                     _ => command_timeout.reset(COMMAND_TIMEOUT_DURATION),
                 }
             }
+
+            Message::ReloadConfig(hci) => {
+                context.state_machine.action_reload_config(hci);
+            }
         }
     }
 }
@@ -513,6 +550,12 @@ pub async fn mainloop(
 pub trait ProcessManager {
     fn start(&mut self, hci_interface: String);
     fn stop(&mut self, hci_interface: String);
+
+    /// Power-cycles the controller of `hci_interface`. Returns true if the controller is going
+    /// away and will show up again as a new HCI device.
+    fn power_cycle(&mut self, hci_interface: String) -> bool {
+        hci_interface.parse::<i32>().map_or(false, config_util::power_cycle_hci_n)
+    }
 }
 
 pub enum Invoker {
@@ -613,6 +656,16 @@ struct ManagerStateMachine {
     process_manager: Box<dyn ProcessManager + Send>,
     hci_interface: i32,
     bluetooth_pid: i32,
+    /// When the running stack reported that it started.
+    started_at: Option<Instant>,
+    /// Number of times the stack crashed since the manager started.
+    crash_count: u32,
+    /// Number of crashes in a row that happened shortly after the stack started.
+    consecutive_crashes: u32,
+    /// Whether the stack is being restarted after a crash.
+    recovering: bool,
+    /// Whether the restart is held until the power-cycled controller is back.
+    waiting_for_hci: bool,
 }
 
 impl ManagerStateMachine {
@@ -643,6 +696,11 @@ impl ManagerStateMachine {
             process_manager: process_manager,
             hci_interface: 0,
             bluetooth_pid: 0,
+            started_at: None,
+            crash_count: 0,
+            consecutive_crashes: 0,
+            recovering: false,
+            waiting_for_hci: false,
         }
     }
 
@@ -653,6 +711,7 @@ impl ManagerStateMachine {
             State::Off => {
                 *state = State::TurningOn;
                 self.hci_interface = hci_interface;
+                self.consecutive_crashes = 0;
                 self.process_manager.start(format!("{}", hci_interface));
                 true
             }
@@ -680,6 +739,8 @@ impl ManagerStateMachine {
             }
             State::TurningOn => {
                 *state = State::Off;
+                self.recovering = false;
+                self.waiting_for_hci = false;
                 self.process_manager.stop(self.hci_interface.to_string());
                 false
             }
@@ -688,6 +749,26 @@ impl ManagerStateMachine {
         }
     }
 
+    /// Sends SIGHUP to the running stack, which makes it reload its runtime config. Returns false
+    /// if the stack on `hci_interface` isn't running.
+    pub fn action_reload_config(&mut self, hci_interface: i32) -> bool {
+        if *self.state.lock().unwrap() != State::On
+            || self.hci_interface != hci_interface
+            || self.bluetooth_pid <= 0
+        {
+            warn!("Not reloading the config of hci{}, which isn't running", hci_interface);
+            return false;
+        }
+
+        match signal::kill(Pid::from_raw(self.bluetooth_pid), Signal::SIGHUP) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to signal pid {}: {}", self.bluetooth_pid, e);
+                false
+            }
+        }
+    }
+
     /// Returns true if the event is expected.
     pub fn action_on_bluetooth_started(&mut self, pid: i32, hci_interface: i32) -> bool {
         let mut state = self.state.lock().unwrap();
@@ -703,12 +784,15 @@ impl ManagerStateMachine {
         }
         *state = State::On;
         self.bluetooth_pid = pid;
+        self.started_at = Some(Instant::now());
         true
     }
 
     /// Returns true if the event is expected.
-    /// If unexpected, Bluetooth probably crashed;
-    /// start the timer for restart timeout
+    /// If unexpected, Bluetooth probably crashed, e.g. after a fatal controller error. The
+    /// controller is power-cycled and the stack restarted, which reloads the bonded devices from
+    /// the config flushed before the crash; start the timer for restart timeout. After too many
+    /// crashes in a row the adapter is left off instead, which returns true.
     pub fn action_on_bluetooth_stopped(&mut self) -> bool {
         let mut state = self.state.lock().unwrap();
 
@@ -718,9 +802,31 @@ impl ManagerStateMachine {
                 true
             }
             State::On => {
-                warn!("Bluetooth stopped unexpectedly, try restarting");
+                self.crash_count += 1;
+                let crash_loop =
+                    self.started_at.take().map_or(true, |t| t.elapsed() < CRASH_LOOP_DURATION);
+                self.consecutive_crashes =
+                    if crash_loop { self.consecutive_crashes + 1 } else { 1 };
+
+                if self.consecutive_crashes > MAX_RECOVERY_ATTEMPTS {
+                    error!(
+                        "hci{} crashed {} times in a row, leaving it off",
+                        self.hci_interface, self.consecutive_crashes
+                    );
+                    *state = State::Off;
+                    self.recovering = false;
+                    return true;
+                }
+
+                warn!("Bluetooth stopped unexpectedly (crash #{}), recovering", self.crash_count);
                 *state = State::TurningOn;
-                self.process_manager.start(format!("{}", self.hci_interface));
+                self.recovering = true;
+                if self.process_manager.power_cycle(self.hci_interface.to_string()) {
+                    info!("Waiting for hci{} to come back", self.hci_interface);
+                    self.waiting_for_hci = true;
+                } else {
+                    self.process_manager.start(format!("{}", self.hci_interface));
+                }
                 false
             }
             State::TurningOn | State::Off => {
@@ -738,6 +844,7 @@ impl ManagerStateMachine {
             State::TurningOn => {
                 info!("Restarting bluetooth {}", self.hci_interface);
                 *state = State::TurningOn;
+                self.waiting_for_hci = false;
                 self.process_manager.stop(format! {"{}", self.hci_interface});
                 self.process_manager.start(format! {"{}", self.hci_interface});
                 StateMachineTimeoutActions::RetryStart
@@ -750,6 +857,31 @@ impl ManagerStateMachine {
             _ => StateMachineTimeoutActions::Noop,
         }
     }
+
+    /// Returns true if the stack is restarted because the power-cycled controller is back.
+    pub fn action_on_hci_device_added(&mut self, hci_interface: i32) -> bool {
+        let state = self.state.lock().unwrap();
+        if !self.waiting_for_hci
+            || *state != State::TurningOn
+            || hci_interface != self.hci_interface
+        {
+            return false;
+        }
+
+        self.waiting_for_hci = false;
+        self.process_manager.start(format!("{}", self.hci_interface));
+        true
+    }
+
+    /// Returns the crash count if the stack has just come back on after a crash.
+    pub fn take_recovery(&mut self) -> Option<u32> {
+        if !self.recovering || *self.state.lock().unwrap() != State::On {
+            return None;
+        }
+
+        self.recovering = false;
+        Some(self.crash_count)
+    }
 }
 
 #[cfg(test)]
@@ -765,11 +897,12 @@ mod tests {
 
     struct MockProcessManager {
         last_command: VecDeque<ExecutedCommand>,
+        can_power_cycle: bool,
     }
 
     impl MockProcessManager {
         fn new() -> MockProcessManager {
-            MockProcessManager { last_command: VecDeque::new() }
+            MockProcessManager { last_command: VecDeque::new(), can_power_cycle: false }
         }
 
         fn expect_start(&mut self) {
@@ -791,6 +924,10 @@ mod tests {
             let stop = self.last_command.pop_front().expect("Should expect stop event");
             assert_eq!(stop, ExecutedCommand::Stop);
         }
+
+        fn power_cycle(&mut self, _: String) -> bool {
+            self.can_power_cycle
+        }
     }
 
     impl Drop for MockProcessManager {
@@ -854,6 +991,22 @@ mod tests {
         })
     }
 
+    #[test]
+    fn reload_config_needs_running_stack() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            let mut state_machine = ManagerStateMachine::new(Box::new(process_manager));
+            assert_eq!(state_machine.action_reload_config(0), false);
+            state_machine.action_start_bluetooth(0);
+            assert_eq!(state_machine.action_reload_config(0), false);
+            // No pid is known for the stack, so there is nothing to signal.
+            state_machine.action_on_bluetooth_started(0, 0);
+            assert_eq!(state_machine.action_reload_config(0), false);
+            assert_eq!(state_machine.action_reload_config(1), false);
+        })
+    }
+
     #[test]
     fn turningon_bluetooth_different_hci_started() {
         tokio::runtime::Runtime::new().unwrap().block_on(async {
@@ -927,6 +1080,48 @@ mod tests {
         })
     }
 
+    #[test]
+    fn on_bluetooth_stopped_waits_for_power_cycle() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.can_power_cycle = true;
+            process_manager.expect_start();
+            // Expect to start again once the controller is back
+            process_manager.expect_start();
+            let mut state_machine = ManagerStateMachine::new(Box::new(process_manager));
+            state_machine.action_start_bluetooth(0);
+            state_machine.action_on_bluetooth_started(0, 0);
+            assert_eq!(state_machine.action_on_bluetooth_stopped(), false);
+            assert_eq!(*state_machine.state.lock().unwrap(), State::TurningOn);
+            assert_eq!(state_machine.action_on_hci_device_added(1), false);
+            assert_eq!(state_machine.action_on_hci_device_added(0), true);
+            assert_eq!(state_machine.take_recovery(), None);
+            state_machine.action_on_bluetooth_started(0, 0);
+            assert_eq!(state_machine.take_recovery(), Some(1));
+            assert_eq!(state_machine.take_recovery(), None);
+        })
+    }
+
+    #[test]
+    fn crash_loop_should_off() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            for _ in 0..=MAX_RECOVERY_ATTEMPTS {
+                process_manager.expect_start();
+            }
+            let mut state_machine = ManagerStateMachine::new(Box::new(process_manager));
+            state_machine.action_start_bluetooth(0);
+            for _ in 0..MAX_RECOVERY_ATTEMPTS {
+                state_machine.action_on_bluetooth_started(0, 0);
+                assert_eq!(state_machine.action_on_bluetooth_stopped(), false);
+            }
+            state_machine.action_on_bluetooth_started(0, 0);
+            assert_eq!(state_machine.action_on_bluetooth_stopped(), true);
+            assert_eq!(*state_machine.state.lock().unwrap(), State::Off);
+            assert_eq!(state_machine.take_recovery(), None);
+        })
+    }
+
     #[test]
     fn turningoff_bluetooth_down_should_off() {
         tokio::runtime::Runtime::new().unwrap().block_on(async {
@@ -0,0 +1,186 @@
+//! Virtual controllers, for running the stack without Bluetooth hardware.
+//!
+//! rootcanal emulates a controller and the peers around it, and serves HCI over TCP with H4
+//! framing. The manager relays that connection to the kernel's vhci driver, which makes a new HCI
+//! device out of it. The device is found, started and used by btadapterd like any other, so
+//! pairing, GATT and everything else run through the whole stack against the emulated peers.
+
+use log::{info, warn};
+use nix::fcntl::OFlag;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+const VHCI_PATH: &str = "/dev/vhci";
+
+/// Packet the vhci driver takes to create an HCI device, which it answers with the same packet
+/// type, the device type and the index of the device.
+const VHCI_VENDOR_PACKET: u8 = 0xff;
+const VHCI_CREATE_PRIMARY: u8 = 0x00;
+
+/// Largest packet the vhci driver hands out: ACL data with a 16 bit length.
+const VHCI_MAX_PACKET_LEN: usize = 1 + 4 + 0xffff;
+
+/// Time to wait before connecting again once the server can't be reached or goes away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+const H4_COMMAND: u8 = 0x01;
+const H4_ACL: u8 = 0x02;
+const H4_SCO: u8 = 0x03;
+const H4_EVENT: u8 = 0x04;
+const H4_ISO: u8 = 0x05;
+
+/// Returns the length of the H4 packet starting `buf`, packet type included, or None if its header
+/// isn't all there yet.
+fn h4_packet_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    let u8_at = |i: usize| buf.get(i).map(|&b| b as usize);
+    let u16_at = |i: usize| buf.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+
+    let (header_len, payload_len) = match buf.first() {
+        None => return Ok(None),
+        Some(&H4_COMMAND) | Some(&H4_SCO) => (3, u8_at(3)),
+        Some(&H4_ACL) => (4, u16_at(3)),
+        Some(&H4_EVENT) => (2, u8_at(2)),
+        // The two top bits of the ISO length are reserved.
+        Some(&H4_ISO) => (4, u16_at(3).map(|len| len & 0x3fff)),
+        Some(packet_type) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown H4 packet type {:#04x}", packet_type),
+            ))
+        }
+    };
+
+    Ok(payload_len.map(|len| 1 + header_len + len))
+}
+
+fn open_vhci() -> io::Result<AsyncFd<File>> {
+    let vhci = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(VHCI_PATH)?;
+    AsyncFd::new(vhci)
+}
+
+/// Reads one packet. The vhci driver never splits or merges them.
+async fn read_vhci(vhci: &AsyncFd<File>, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        let mut guard = vhci.readable().await?;
+        if let Ok(result) = guard.try_io(|inner| inner.get_ref().read(buf)) {
+            return result;
+        }
+    }
+}
+
+/// Writes one whole packet.
+async fn write_vhci(vhci: &AsyncFd<File>, packet: &[u8]) -> io::Result<()> {
+    loop {
+        let mut guard = vhci.writable().await?;
+        if let Ok(result) = guard.try_io(|inner| inner.get_ref().write(packet)) {
+            return match result? {
+                len if len == packet.len() => Ok(()),
+                _ => Err(io::Error::new(io::ErrorKind::WriteZero, "Short write to vhci")),
+            };
+        }
+    }
+}
+
+/// Relays HCI between `server` and a new vhci device until either side goes away. The device is
+/// removed along with the file of the driver, when this returns.
+async fn relay(server: SocketAddr) -> io::Result<()> {
+    let mut stream = TcpStream::connect(server).await?;
+    let vhci = open_vhci()?;
+    write_vhci(&vhci, &[VHCI_VENDOR_PACKET, VHCI_CREATE_PRIMARY]).await?;
+
+    let (mut from_server, mut to_server) = stream.split();
+
+    // The stream has no packet boundaries, so packets are only handed to the driver once whole.
+    let server_to_vhci = async {
+        let mut pending = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let len = from_server.read(&mut buf).await?;
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Server disconnected"));
+            }
+
+            pending.extend_from_slice(&buf[..len]);
+            while let Some(packet_len) = h4_packet_len(&pending)? {
+                if pending.len() < packet_len {
+                    break;
+                }
+
+                let packet: Vec<u8> = pending.drain(..packet_len).collect();
+                write_vhci(&vhci, &packet).await?;
+            }
+        }
+    };
+
+    let vhci_to_server = async {
+        let mut buf = vec![0; VHCI_MAX_PACKET_LEN];
+        loop {
+            let len = read_vhci(&vhci, &mut buf).await?;
+            match &buf[..len] {
+                [] => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "vhci closed"));
+                }
+                [VHCI_VENDOR_PACKET, _, index_lo, index_hi] => {
+                    let index = u16::from_le_bytes([*index_lo, *index_hi]);
+                    info!("Virtual controller at {} is hci{}", server, index);
+                }
+                packet => to_server.write_all(packet).await?,
+            }
+        }
+    };
+
+    tokio::select! {
+        result = server_to_vhci => result,
+        result = vhci_to_server => result,
+    }
+}
+
+/// Starts relaying the virtual controller served at `server`, connecting again whenever the
+/// connection is lost. Aborting the returned task removes the HCI device.
+pub fn start(server: SocketAddr) -> JoinHandle<()> {
+    info!("Starting virtual controller at {}", server);
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = relay(server).await {
+                warn!("Virtual controller at {} stopped: {}", server, e);
+            }
+            sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_h4_packet_len() {
+        // Headers that aren't whole yet.
+        assert_eq!(h4_packet_len(&[]).unwrap(), None);
+        assert_eq!(h4_packet_len(&[H4_EVENT, 0x0e]).unwrap(), None);
+        assert_eq!(h4_packet_len(&[H4_ACL, 0x01, 0x20, 0x10]).unwrap(), None);
+
+        // Command Complete, with trailing bytes of the next packet.
+        assert_eq!(
+            h4_packet_len(&[H4_EVENT, 0x0e, 0x04, 0x01, 0x03, 0x0c, 0x00, 0x02]).unwrap(),
+            Some(7)
+        );
+        assert_eq!(h4_packet_len(&[H4_COMMAND, 0x03, 0x0c, 0x00]).unwrap(), Some(4));
+        assert_eq!(h4_packet_len(&[H4_ACL, 0x01, 0x20, 0x00, 0x01]).unwrap(), Some(261));
+        assert_eq!(h4_packet_len(&[H4_SCO, 0x01, 0x00, 0x3c]).unwrap(), Some(64));
+        assert_eq!(h4_packet_len(&[H4_ISO, 0x01, 0x00, 0x10, 0x40]).unwrap(), Some(21));
+
+        assert!(h4_packet_len(&[0x06, 0x00]).is_err());
+    }
+}
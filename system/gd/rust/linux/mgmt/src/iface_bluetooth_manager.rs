@@ -1,37 +1,175 @@
+use dbus_macros::dbus_interface;
+
 use crate::RPCProxy;
 
+/// Version of the D-Bus APIs of btmanagerd and btadapterd, which is raised whenever methods are
+/// added to them. Clients use it to tell what a daemon supports before calling into it. Daemons
+/// from before `get_api_version` don't report a version and count as version 0.
+pub const API_VERSION: u32 = 13;
+
 #[derive(Debug, Default)]
 pub struct AdapterWithEnabled {
     pub hci_interface: i32,
     pub enabled: bool,
 }
 
+/// What the kernel knows about the controller behind an HCI device.
+///
+/// The controller's version and features are read from debugfs, so they are only known if debugfs
+/// is mounted and the kernel has brought the controller up at least once.
+#[derive(Debug, Default)]
+pub struct AdapterDetails {
+    pub hci_interface: i32,
+    pub enabled: bool,
+    /// Bus the controller is attached to: "usb", "uart", "sdio", "pci", or "unknown".
+    pub bus: String,
+    /// USB, SDIO or PCI vendor and product ids, or 0 if the bus doesn't have them.
+    pub vendor_id: u32,
+    pub product_id: u32,
+    /// Company identifier assigned by the Bluetooth SIG, or -1 if unknown.
+    pub manufacturer: i32,
+    /// HCI version, or -1 if unknown.
+    pub hci_version: i32,
+    /// HCI revision, which usually identifies the firmware build, or -1 if unknown.
+    pub hci_revision: i32,
+    /// LMP and LE feature pages as reported by the kernel, e.g. "0: 0xff 0xfe ...".
+    pub features: Vec<String>,
+}
+
+/// A device found by merged discovery, which may have been heard by several adapters.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MergedFoundDevice {
+    pub address: String,
+    /// Name of the device, or an empty string until an adapter learns it.
+    pub name: String,
+    /// Adapter that heard the device with the strongest signal, and that signal in dBm. The RSSI
+    /// is 0 if no adapter knows it.
+    pub hci_interface: i32,
+    pub rssi: i32,
+    /// Every adapter that found the device, in the order they found it.
+    pub hci_interfaces: Vec<i32>,
+}
+
 /// Bluetooth stack management API.
+#[dbus_interface(bluetooth_manager_dbus, "org.chromium.bluetooth.Manager")]
 pub trait IBluetoothManager {
     /// Starts the Bluetooth stack.
+    #[dbus_method("Start")]
     fn start(&mut self, hci_interface: i32);
 
     /// Stops the Bluetooth stack.
+    #[dbus_method("Stop")]
     fn stop(&mut self, hci_interface: i32);
 
     /// Returns whether an adapter is enabled.
+    #[dbus_method("GetAdapterEnabled")]
     fn get_adapter_enabled(&mut self, hci_interface: i32) -> bool;
 
     /// Registers a callback to the Bluetooth manager state.
+    #[dbus_method("RegisterCallback")]
     fn register_callback(&mut self, callback: Box<dyn IBluetoothManagerCallback + Send>);
 
     /// Returns whether Floss is enabled.
+    #[dbus_method("GetFlossEnabled")]
     fn get_floss_enabled(&mut self) -> bool;
 
     /// Enables/disables Floss.
+    #[dbus_method("SetFlossEnabled")]
     fn set_floss_enabled(&mut self, enabled: bool);
 
     /// Returns a list of available HCI devices and if they are enabled.
+    #[dbus_method("GetAvailableAdapters")]
     fn get_available_adapters(&mut self) -> Vec<AdapterWithEnabled>;
+
+    /// Same as `get_available_adapters`, but also describes the controller of each device.
+    #[dbus_method("GetAdapterDetails")]
+    fn get_adapter_details(&mut self) -> Vec<AdapterDetails>;
+
+    /// Gracefully stops all running adapters without changing their saved enabled state. Each
+    /// adapter disconnects its devices and flushes its config before stopping.
+    ///
+    /// Callbacks are notified with `on_shutdown_complete` once every adapter is off.
+    #[dbus_method("PrepareShutdown")]
+    fn prepare_shutdown(&mut self);
+
+    /// Same as `prepare_shutdown`, but the manager also exits once the shutdown completes.
+    #[dbus_method("Shutdown")]
+    fn shutdown(&mut self);
+
+    /// Returns the [`API_VERSION`] the daemons were built with.
+    #[dbus_method("GetApiVersion")]
+    fn get_api_version(&mut self) -> u32;
+
+    /// Adds an HCI device backed by the virtual controller rootcanal serves at `server`, an
+    /// "ip:port" such as "127.0.0.1:6402", in place of the one set before. An empty `server`
+    /// removes the device. The choice is kept across restarts of the manager.
+    ///
+    /// Since API version 2. Returns false if `server` isn't an address.
+    #[dbus_method("SetVirtualController")]
+    fn set_virtual_controller(&mut self, server: String) -> bool;
+
+    /// Returns the server given to `set_virtual_controller`, or an empty string.
+    ///
+    /// Since API version 2.
+    #[dbus_method("GetVirtualController")]
+    fn get_virtual_controller(&mut self) -> String;
+
+    /// Adds a callback to be told about the devices found by merged discovery. Returns the id to
+    /// remove it with.
+    ///
+    /// Since API version 3.
+    #[dbus_method("RegisterMergedDiscoveryCallback")]
+    fn register_merged_discovery_callback(
+        &mut self,
+        callback: Box<dyn IMergedDiscoveryCallback + Send>,
+    ) -> u32;
+
+    /// Removes a callback added with `register_merged_discovery_callback`.
+    ///
+    /// Since API version 3.
+    #[dbus_method("UnregisterMergedDiscoveryCallback")]
+    fn unregister_merged_discovery_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Starts discovery on every enabled adapter, and on adapters enabled while it runs. The
+    /// devices they find are reported once to the merged discovery callbacks, rather than once per
+    /// adapter, and again only when another adapter finds them or their name is learned. It ends
+    /// once every adapter has stopped discovering.
+    ///
+    /// Since API version 3. Returns false if no adapter is enabled.
+    #[dbus_method("StartMergedDiscovery")]
+    fn start_merged_discovery(&mut self) -> bool;
+
+    /// Stops discovery on the adapters merged discovery started it on. Returns false if it wasn't
+    /// running.
+    ///
+    /// Since API version 3.
+    #[dbus_method("CancelMergedDiscovery")]
+    fn cancel_merged_discovery(&mut self) -> bool;
+
+    /// Makes the stack on `hci_interface` read its runtime config again, the same as sending it
+    /// SIGHUP.
+    ///
+    /// Since API version 11. Returns false if the adapter isn't on.
+    #[dbus_method("ReloadConfig")]
+    fn reload_config(&mut self, hci_interface: i32) -> bool;
 }
 
 /// Interface of Bluetooth Manager callbacks.
 pub trait IBluetoothManagerCallback: RPCProxy {
     fn on_hci_device_changed(&self, hci_interface: i32, present: bool);
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool);
+    fn on_shutdown_complete(&self);
+
+    /// Sent when an adapter is back on after the manager recovered it from a stack or controller
+    /// crash. `crash_count` is the number of crashes since the manager started.
+    fn on_adapter_recovered(&self, hci_interface: i32, crash_count: u32);
+}
+
+/// Interface of merged discovery callbacks.
+pub trait IMergedDiscoveryCallback: RPCProxy {
+    /// `device` was found for the first time, by another adapter, or with a name.
+    fn on_merged_device_found(&self, device: MergedFoundDevice);
+
+    /// Merged discovery started or ended.
+    fn on_merged_discovering_changed(&self, discovering: bool);
 }
@@ -5,14 +5,24 @@ pub mod iface_bluetooth_manager;
 /// Signifies that the object may be a proxy to a remote RPC object.
 pub trait RPCProxy {
     /// Registers disconnect observer that will be notified when the remote object is disconnected.
-    fn register_disconnect(&mut self, f: Box<dyn Fn(u32) + Send>) -> u32;
+    /// Any number of observers can be registered, and each gets its own id. Objects that never
+    /// disconnect, such as local ones, keep the default which registers nothing.
+    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
+        0
+    }
 
     /// Returns the ID of the object. For example this would be an object path in D-Bus RPC.
     fn get_object_id(&self) -> String;
 
-    /// Unregisters callback with this id.
-    fn unregister(&mut self, id: u32) -> bool;
+    /// Unregisters the disconnect observer with this id, returning whether it was registered.
+    fn unregister(&mut self, _id: u32) -> bool {
+        false
+    }
 
     /// Makes this object available for remote call.
-    fn export_for_rpc(self: Box<Self>);
+    fn export_for_rpc(self: Box<Self>) {}
+
+    /// Attaches the sequence number and timestamp of a callback delivery to the next call made on
+    /// the object. Proxies that can't send them along ignore them.
+    fn set_event_stamp(&self, _sequence: u64, _timestamp_us: u64) {}
 }
@@ -12,6 +12,7 @@ fn main() {
     println!("cargo:rerun-if-changed={}", target_dir.into_string().unwrap());
 
     // A few dynamic links
+    println!("cargo:rustc-link-lib=dylib=crypto");
     println!("cargo:rustc-link-lib=dylib=flatbuffers");
     println!("cargo:rustc-link-lib=dylib=protobuf");
     println!("cargo:rustc-link-lib=dylib=resolv");
@@ -0,0 +1,32 @@
+use btstack::advertising_privacy::{IAdvertisingPrivacy, IAdvertisingPrivacyCallback};
+use btstack::RPCProxy;
+
+use crate::dbus_arg::DBusArg;
+
+use dbus_macros::{dbus_method, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, DisconnectWatcher};
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use std::sync::Arc;
+
+#[allow(dead_code)]
+struct IAdvertisingPrivacyDBus {}
+
+btstack::advertising_privacy_dbus!(
+    exporter IAdvertisingPrivacyDBus,
+    export_advertising_privacy_dbus_obj
+);
+
+#[allow(dead_code)]
+struct AdvertisingPrivacyCallbackDBus {}
+
+#[dbus_proxy_obj(AdvertisingPrivacyCallback, "org.chromium.bluetooth.AdvertisingPrivacyCallback")]
+impl IAdvertisingPrivacyCallback for AdvertisingPrivacyCallbackDBus {
+    #[dbus_method("OnAdvertisingAddressChanged")]
+    fn on_advertising_address_changed(&self, advertiser_id: i32, address: String) {
+        dbus_generated!()
+    }
+}
@@ -1,10 +1,21 @@
 extern crate bt_shim;
 
-use bt_topshim::btif::{BtDeviceType, BtSspVariant, BtTransport, Uuid128Bit};
+use bt_topshim::btif::{
+    BtAclRole, BtAddrType, BtDeviceType, BtSspVariant, BtStatus, BtTransport, RawAddress,
+    Uuid128Bit,
+};
 
+use btstack::activity::{ActivityEnergyInfo, DeviceActivity};
 use btstack::bluetooth::{
-    BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
+    AdapterCapabilities, AdapterProperties, AdapterState, BluetoothDevice, ClassicScanParams,
+    ClassicScanType, ControllerInfo, FoundDeviceInfo, IBluetooth, IBluetoothCallback,
+    IBluetoothConnectionCallback, IBluetoothDiscoverySessionCallback, IBluetoothLinkPolicy,
+    IBluetoothLinkPolicyCallback, IBluetoothSecurityCallback, LePrivacyFeatures, LeScanParams,
+    PairingRejectedReason, ProfileConnectResult, ProfileConnectionReason, ProfileConnectionState,
+    RemoteDeviceInfo, RemoteFeatures, RemoteVersionInfo, RssiSample,
 };
+use btstack::bluetooth_gatt::ScanType;
+use btstack::device_type::DeviceType;
 use btstack::uuid::Profile;
 use btstack::RPCProxy;
 
@@ -13,7 +24,7 @@ use dbus::arg::RefArg;
 use dbus::nonblock::SyncConnection;
 use dbus::strings::Path;
 
-use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
 
 use dbus_projection::DisconnectWatcher;
 use dbus_projection::{dbus_generated, impl_dbus_arg_enum};
@@ -24,10 +35,170 @@ use std::sync::Arc;
 
 use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
 
+// Represents RawAddress as a string in D-Bus, e.g. "00:11:22:33:44:55".
+impl DBusArg for RawAddress {
+    type DBusType = String;
+
+    fn from_dbus(
+        data: String,
+        _conn: Option<Arc<SyncConnection>>,
+        _remote: Option<dbus::strings::BusName<'static>>,
+        _disconnect_watcher: Option<Arc<std::sync::Mutex<DisconnectWatcher>>>,
+    ) -> Result<RawAddress, Box<dyn std::error::Error>> {
+        match RawAddress::from_string(data.clone()) {
+            Some(addr) => Ok(addr),
+            None => Err(Box::new(DBusArgError::new(format!("Invalid address {}", data)))),
+        }
+    }
+
+    fn to_dbus(data: RawAddress) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(data.to_string())
+    }
+}
+
 #[dbus_propmap(BluetoothDevice)]
 pub struct BluetoothDeviceDBus {
+    address: RawAddress,
+    name: String,
+    address_type: BtAddrType,
+    transport: BtTransport,
+    icon: DeviceType,
+}
+
+#[dbus_propmap(ProfileConnectResult)]
+pub struct ProfileConnectResultDBus {
+    profile: Profile,
+    status: BtStatus,
+}
+
+#[dbus_propmap(AdapterCapabilities)]
+pub struct AdapterCapabilitiesDBus {
+    le_extended_advertising_supported: bool,
+    le_2m_phy_supported: bool,
+    le_coded_phy_supported: bool,
+    le_periodic_advertising_supported: bool,
+    le_connected_isochronous_stream_central_supported: bool,
+    le_isochronous_broadcast_supported: bool,
+    max_advertising_sets: u32,
+    max_advertising_data_length: u32,
+    max_advertising_filters: u32,
+    acl_buffer_count_classic: u32,
+    acl_buffer_count_le: u32,
+    le_accept_list_size: u32,
+    max_connections: u32,
+}
+
+#[dbus_propmap(AdapterProperties)]
+pub struct AdapterPropertiesDBus {
     address: String,
     name: String,
+    bluetooth_class: u32,
+    discoverable: bool,
+    discoverable_timeout: u32,
+    discovering: bool,
+    connected_count: u32,
+}
+
+#[dbus_propmap(ClassicScanParams)]
+pub struct ClassicScanParamsDBus {
+    interval: u16,
+    window: u16,
+    scan_type: ClassicScanType,
+}
+
+#[dbus_propmap(LeScanParams)]
+pub struct LeScanParamsDBus {
+    interval: u16,
+    window: u16,
+    scan_type: ScanType,
+}
+
+#[dbus_propmap(LePrivacyFeatures)]
+pub struct LePrivacyFeaturesDBus {
+    ll_privacy_supported: bool,
+    rpa_offload_supported: bool,
+    resolving_list_size: u32,
+    privacy_enabled: bool,
+}
+
+#[dbus_propmap(RemoteVersionInfo)]
+pub struct RemoteVersionInfoDBus {
+    valid: bool,
+    lmp_version: u8,
+    manufacturer: u16,
+    lmp_subversion: u16,
+}
+
+#[dbus_propmap(RemoteFeatures)]
+pub struct RemoteFeaturesDBus {
+    lmp_features: Vec<u8>,
+    le_features: Vec<u8>,
+}
+
+#[dbus_propmap(FoundDeviceInfo)]
+pub struct FoundDeviceInfoDBus {
+    class_of_device: u32,
+    device_type: BtDeviceType,
+    rssi: i32,
+    uuids: Vec<Uuid128Bit>,
+}
+
+#[dbus_propmap(RssiSample)]
+pub struct RssiSampleDBus {
+    rssi: i32,
+    age_ms: u64,
+}
+
+#[dbus_propmap(RemoteDeviceInfo)]
+pub struct RemoteDeviceInfoDBus {
+    known: bool,
+    device: BluetoothDevice,
+    alias: String,
+    device_type: BtDeviceType,
+    class_of_device: u32,
+    bond_state: u32,
+    uuids: Vec<Uuid128Bit>,
+    rssi_history: Vec<RssiSample>,
+    last_seen_ms: u64,
+    connection_state: u32,
+    connected_ms: u64,
+    link_role: BtAclRole,
+    bredr_key_size: u8,
+    le_key_size: u8,
+}
+
+#[dbus_propmap(DeviceActivity)]
+pub struct DeviceActivityDBus {
+    address: RawAddress,
+    tx_bytes: u64,
+    rx_bytes: u64,
+}
+
+#[dbus_propmap(ActivityEnergyInfo)]
+pub struct ActivityEnergyInfoDBus {
+    controller_reported: bool,
+    controller_state: u32,
+    controller_tx_time_ms: u64,
+    controller_rx_time_ms: u64,
+    controller_idle_time_ms: u64,
+    controller_energy_used: u64,
+    scan_time_ms: u64,
+    scan_radio_time_ms: u64,
+    advertising_time_ms: u64,
+    advertising_airtime_ms: u64,
+    devices: Vec<DeviceActivity>,
+}
+
+#[dbus_propmap(ControllerInfo)]
+pub struct ControllerInfoDBus {
+    manufacturer: u16,
+    hci_version: u8,
+    hci_revision: u16,
+    lmp_version: u8,
+    lmp_subversion: u16,
+    firmware_version: String,
+    vendor_diagnostics_supported: bool,
+    vendor_diagnostics_enabled: bool,
 }
 
 #[allow(dead_code)]
@@ -35,6 +206,10 @@ struct BluetoothCallbackDBus {}
 
 #[dbus_proxy_obj(BluetoothCallback, "org.chromium.bluetooth.BluetoothCallback")]
 impl IBluetoothCallback for BluetoothCallbackDBus {
+    #[dbus_method("OnAdapterStateChanged")]
+    fn on_adapter_state_changed(&self, state: AdapterState) {
+        dbus_generated!()
+    }
     #[dbus_method("OnAddressChanged")]
     fn on_address_changed(&self, addr: String) {
         dbus_generated!()
@@ -48,17 +223,25 @@ impl IBluetoothCallback for BluetoothCallbackDBus {
         dbus_generated!()
     }
     #[dbus_method("OnDeviceFound")]
-    fn on_device_found(&self, remote_device: BluetoothDevice) {
+    fn on_device_found(&self, remote_device: BluetoothDevice, info: FoundDeviceInfo) {
         dbus_generated!()
     }
     #[dbus_method("OnDeviceCleared")]
     fn on_device_cleared(&self, remote_device: BluetoothDevice) {
         dbus_generated!()
     }
+    #[dbus_method("OnRemoteNameFetched")]
+    fn on_remote_name_fetched(&self, remote_device: BluetoothDevice, success: bool) {
+        dbus_generated!()
+    }
     #[dbus_method("OnDiscoveringChanged")]
     fn on_discovering_changed(&self, discovering: bool) {
         dbus_generated!()
     }
+    #[dbus_method("OnConnectedCountChanged")]
+    fn on_connected_count_changed(&self, count: u32) {
+        dbus_generated!()
+    }
     #[dbus_method("OnSspRequest")]
     fn on_ssp_request(
         &self,
@@ -69,16 +252,46 @@ impl IBluetoothCallback for BluetoothCallbackDBus {
     ) {
         dbus_generated!()
     }
+    #[dbus_method("OnPinRequest")]
+    fn on_pin_request(&self, remote_device: BluetoothDevice, cod: u32, min_16_digit: bool) {
+        dbus_generated!()
+    }
+    #[dbus_method("OnPinDisplay")]
+    fn on_pin_display(&self, remote_device: BluetoothDevice, pincode: String) {
+        dbus_generated!()
+    }
+    #[dbus_method("OnPairingRejected")]
+    fn on_pairing_rejected(&self, remote_device: BluetoothDevice, reason: PairingRejectedReason) {
+        dbus_generated!()
+    }
+    #[dbus_method("OnEncryptionKeySizeRejected")]
+    fn on_encryption_key_size_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        key_size: u8,
+    ) {
+        dbus_generated!()
+    }
     #[dbus_method("OnBondStateChanged")]
     fn on_bond_state_changed(&self, status: u32, address: String, state: u32) {
         dbus_generated!()
     }
 }
 
+impl_dbus_arg_enum!(AdapterState);
+impl_dbus_arg_enum!(BtAclRole);
+impl_dbus_arg_enum!(BtAddrType);
 impl_dbus_arg_enum!(BtDeviceType);
 impl_dbus_arg_enum!(BtSspVariant);
+impl_dbus_arg_enum!(BtStatus);
 impl_dbus_arg_enum!(BtTransport);
+impl_dbus_arg_enum!(ClassicScanType);
+impl_dbus_arg_enum!(DeviceType);
+impl_dbus_arg_enum!(PairingRejectedReason);
 impl_dbus_arg_enum!(Profile);
+impl_dbus_arg_enum!(ProfileConnectionReason);
+impl_dbus_arg_enum!(ProfileConnectionState);
 
 #[allow(dead_code)]
 struct BluetoothConnectionCallbackDBus {}
@@ -94,214 +307,133 @@ impl IBluetoothConnectionCallback for BluetoothConnectionCallbackDBus {
     fn on_device_disconnected(&self, remote_device: BluetoothDevice) {
         dbus_generated!()
     }
-}
-
-#[allow(dead_code)]
-struct IBluetoothDBus {}
-
-#[generate_dbus_exporter(export_bluetooth_dbus_obj, "org.chromium.bluetooth.Bluetooth")]
-impl IBluetooth for IBluetoothDBus {
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RegisterConnectionCallback")]
-    fn register_connection_callback(
-        &mut self,
-        callback: Box<dyn IBluetoothConnectionCallback + Send>,
-    ) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("UnregisterConnectionCallback")]
-    fn unregister_connection_callback(&mut self, id: u32) -> bool {
-        dbus_generated!()
-    }
-
-    // Not exposed over D-Bus. The stack is automatically enabled when the daemon starts.
-    fn enable(&mut self) -> bool {
-        dbus_generated!()
-    }
-
-    // Not exposed over D-Bus. The stack is automatically disabled when the daemon exits.
-    // TODO(b/189495858): Handle shutdown properly when SIGTERM is received.
-    fn disable(&mut self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetAddress")]
-    fn get_address(&self) -> String {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetUuids")]
-    fn get_uuids(&self) -> Vec<Uuid128Bit> {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetName")]
-    fn get_name(&self) -> String {
-        dbus_generated!()
-    }
 
-    #[dbus_method("SetName")]
-    fn set_name(&self, name: String) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetBluetoothClass")]
-    fn get_bluetooth_class(&self) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("SetBluetoothClass")]
-    fn set_bluetooth_class(&self, cod: u32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetDiscoverable")]
-    fn get_discoverable(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetDiscoverableTimeout")]
-    fn get_discoverable_timeout(&self) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("SetDiscoverable")]
-    fn set_discoverable(&self, mode: bool, duration: u32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("IsMultiAdvertisementSupported")]
-    fn is_multi_advertisement_supported(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("IsLeExtendedAdvertisingSupported")]
-    fn is_le_extended_advertising_supported(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("StartDiscovery")]
-    fn start_discovery(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("CancelDiscovery")]
-    fn cancel_discovery(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("IsDiscovering")]
-    fn is_discovering(&self) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("GetDiscoveryEndMillis")]
-    fn get_discovery_end_millis(&self) -> u64 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("CreateBond")]
-    fn create_bond(&self, device: BluetoothDevice, transport: BtTransport) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("CancelBondProcess")]
-    fn cancel_bond_process(&self, device: BluetoothDevice) -> bool {
+    #[dbus_method("OnProfileConnectionStateChanged")]
+    fn on_profile_connection_state_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    ) {
         dbus_generated!()
     }
 
-    #[dbus_method("RemoveBond")]
-    fn remove_bond(&self, device: BluetoothDevice) -> bool {
+    #[dbus_method("OnConnectAllComplete")]
+    fn on_connect_all_complete(
+        &self,
+        remote_device: BluetoothDevice,
+        results: Vec<ProfileConnectResult>,
+    ) {
         dbus_generated!()
     }
+}
 
-    #[dbus_method("GetBondedDevices")]
-    fn get_bonded_devices(&self) -> Vec<BluetoothDevice> {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct BluetoothSecurityCallbackDBus {}
 
-    #[dbus_method("GetBondState")]
-    fn get_bond_state(&self, device: BluetoothDevice) -> u32 {
+#[dbus_proxy_obj(BluetoothSecurityCallback, "org.chromium.bluetooth.BluetoothSecurityCallback")]
+impl IBluetoothSecurityCallback for BluetoothSecurityCallbackDBus {
+    #[dbus_method("OnAuthenticationFailed")]
+    fn on_authentication_failed(&self, remote_device: BluetoothDevice, hci_reason: u8) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetPin")]
-    fn set_pin(&self, device: BluetoothDevice, accept: bool, pin_code: Vec<u8>) -> bool {
+    #[dbus_method("OnEncryptionChanged")]
+    fn on_encryption_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+        encrypted: bool,
+    ) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetPasskey")]
-    fn set_passkey(&self, device: BluetoothDevice, accept: bool, passkey: Vec<u8>) -> bool {
+    #[dbus_method("OnEncryptionKeyRefreshed")]
+    fn on_encryption_key_refreshed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+    ) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetPairingConfirmation")]
-    fn set_pairing_confirmation(&self, device: BluetoothDevice, accept: bool) -> bool {
+    #[dbus_method("OnAddressSpoofingSuspected")]
+    fn on_address_spoofing_suspected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    ) {
         dbus_generated!()
     }
 
-    #[dbus_method("GetRemoteName")]
-    fn get_remote_name(&self, _device: BluetoothDevice) -> String {
+    #[dbus_method("OnUnbondedConnectionRejected")]
+    fn on_unbonded_connection_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    ) {
         dbus_generated!()
     }
+}
 
-    #[dbus_method("GetRemoteType")]
-    fn get_remote_type(&self, _device: BluetoothDevice) -> BtDeviceType {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct BluetoothDiscoverySessionCallbackDBus {}
 
-    #[dbus_method("GetRemoteAlias")]
-    fn get_remote_alias(&self, _device: BluetoothDevice) -> String {
+#[dbus_proxy_obj(
+    BluetoothDiscoverySessionCallback,
+    "org.chromium.bluetooth.BluetoothDiscoverySessionCallback"
+)]
+impl IBluetoothDiscoverySessionCallback for BluetoothDiscoverySessionCallbackDBus {
+    #[dbus_method("OnSessionDeviceFound")]
+    fn on_session_device_found(&self, session_id: u32, remote_device: BluetoothDevice) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetRemoteAlias")]
-    fn set_remote_alias(&mut self, _device: BluetoothDevice, new_alias: String) {
+    #[dbus_method("OnSessionDeviceLost")]
+    fn on_session_device_lost(&self, session_id: u32, remote_device: BluetoothDevice) {
         dbus_generated!()
     }
 
-    #[dbus_method("GetRemoteClass")]
-    fn get_remote_class(&self, _device: BluetoothDevice) -> u32 {
+    #[dbus_method("OnDiscoverySessionEnded")]
+    fn on_discovery_session_ended(&self, session_id: u32) {
         dbus_generated!()
     }
+}
 
-    #[dbus_method("GetConnectionState")]
-    fn get_connection_state(&self, device: BluetoothDevice) -> u32 {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct IBluetoothDBus {}
 
-    #[dbus_method("GetProfileConnectionState")]
-    fn get_profile_connection_state(&self, profile: Profile) -> u32 {
+btstack::bluetooth_dbus!(exporter IBluetoothDBus, export_bluetooth_dbus_obj, {
+    // Not exposed over D-Bus. The stack is automatically enabled when the daemon starts.
+    fn enable(&mut self) -> bool {
         dbus_generated!()
     }
 
-    #[dbus_method("GetRemoteUuids")]
-    fn get_remote_uuids(&self, device: BluetoothDevice) -> Vec<Uuid128Bit> {
+    // Not exposed over D-Bus. The stack is automatically disabled when the daemon exits.
+    // TODO(b/189495858): Handle shutdown properly when SIGTERM is received.
+    fn disable(&mut self) -> bool {
         dbus_generated!()
     }
+});
 
-    #[dbus_method("FetchRemoteUuids")]
-    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct BluetoothLinkPolicyCallbackDBus {}
 
-    #[dbus_method("SdpSearch")]
-    fn sdp_search(&self, device: BluetoothDevice, uuid: Uuid128Bit) -> bool {
+#[dbus_proxy_obj(BluetoothLinkPolicyCallback, "org.chromium.bluetooth.BluetoothLinkPolicyCallback")]
+impl IBluetoothLinkPolicyCallback for BluetoothLinkPolicyCallbackDBus {
+    #[dbus_method("OnRoleChanged")]
+    fn on_role_changed(&self, device: BluetoothDevice, role: BtAclRole, status: BtStatus) {
         dbus_generated!()
     }
+}
 
-    #[dbus_method("ConnectAllEnabledProfiles")]
-    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct IBluetoothLinkPolicyDBus {}
 
-    #[dbus_method("DisconnectAllEnabledProfiles")]
-    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
-        dbus_generated!()
-    }
-}
+btstack::bluetooth_link_policy_dbus!(
+    exporter IBluetoothLinkPolicyDBus,
+    export_bluetooth_link_policy_dbus_obj
+);
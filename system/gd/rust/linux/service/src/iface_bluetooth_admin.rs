@@ -0,0 +1,45 @@
+use bt_topshim::btif::Uuid128Bit;
+
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_admin::{IBluetoothAdmin, IBluetoothAdminPolicyCallback, PolicyEffect};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, DisconnectWatcher};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+#[dbus_propmap(PolicyEffect)]
+pub struct PolicyEffectDBus {
+    service_blocked: Vec<Uuid128Bit>,
+    affected: bool,
+}
+
+#[allow(dead_code)]
+struct IBluetoothAdminDBus {}
+
+btstack::bluetooth_admin_dbus!(exporter IBluetoothAdminDBus, export_bluetooth_admin_dbus_obj);
+
+#[allow(dead_code)]
+struct AdminPolicyCallbackDBus {}
+
+#[dbus_proxy_obj(AdminPolicyCallback, "org.chromium.bluetooth.AdminPolicyCallback")]
+impl IBluetoothAdminPolicyCallback for AdminPolicyCallbackDBus {
+    #[dbus_method("OnServiceAllowlistChanged")]
+    fn on_service_allowlist_changed(&self, allowlist: Vec<Uuid128Bit>) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnDevicePolicyEffectChanged")]
+    fn on_device_policy_effect_changed(&self, device: BluetoothDevice, effect: PolicyEffect) {
+        dbus_generated!()
+    }
+}
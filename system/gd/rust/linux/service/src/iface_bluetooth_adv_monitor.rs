@@ -0,0 +1,83 @@
+use bt_topshim::btif::Uuid128Bit;
+
+use btstack::beacon::{Beacon, BeaconType};
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_adv_monitor::{
+    AdvMonitor, AdvMonitorPattern, IAdvMonitorCallback, IBluetoothAdvMonitor,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+#[dbus_propmap(AdvMonitorPattern)]
+pub struct AdvMonitorPatternDBus {
+    ad_type: u8,
+    offset: u8,
+    content: Vec<u8>,
+}
+
+#[dbus_propmap(AdvMonitor)]
+pub struct AdvMonitorDBus {
+    patterns: Vec<AdvMonitorPattern>,
+    rssi_high_threshold: i32,
+    found_timeout: u32,
+    rssi_low_threshold: i32,
+    lost_timeout: u32,
+}
+
+impl_dbus_arg_enum!(BeaconType);
+
+#[dbus_propmap(Beacon)]
+pub struct BeaconDBus {
+    beacon_type: BeaconType,
+    id: Uuid128Bit,
+    major: u16,
+    minor: u16,
+    tx_power: i32,
+    url: String,
+    battery_voltage: u16,
+    temperature: i32,
+    adv_count: u32,
+    uptime: u32,
+}
+
+#[allow(dead_code)]
+struct AdvMonitorCallbackDBus {}
+
+#[dbus_proxy_obj(AdvMonitorCallback, "org.chromium.bluetooth.AdvMonitorCallback")]
+impl IAdvMonitorCallback for AdvMonitorCallbackDBus {
+    #[dbus_method("OnDeviceFound")]
+    fn on_device_found(&self, monitor_id: u32, device: BluetoothDevice) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnDeviceLost")]
+    fn on_device_lost(&self, monitor_id: u32, device: BluetoothDevice) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnBeaconReceived")]
+    fn on_beacon_received(&self, monitor_id: u32, device: BluetoothDevice, beacon: Beacon) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothAdvMonitorDBus {}
+
+btstack::bluetooth_adv_monitor_dbus!(
+    exporter IBluetoothAdvMonitorDBus,
+    export_bluetooth_adv_monitor_dbus_obj
+);
@@ -0,0 +1,16 @@
+use btstack::bluetooth_fast_pair::IBluetoothFastPair;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use std::sync::Arc;
+
+use crate::dbus_arg::DBusArg;
+
+#[allow(dead_code)]
+struct IBluetoothFastPairDBus {}
+
+btstack::bluetooth_fast_pair_dbus!(
+    exporter IBluetoothFastPairDBus,
+    export_bluetooth_fast_pair_dbus_obj
+);
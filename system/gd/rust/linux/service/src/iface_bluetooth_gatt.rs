@@ -1,9 +1,11 @@
-use bt_topshim::{btif::Uuid128Bit, profiles::gatt::GattStatus};
+use bt_topshim::btif::{BtStatus, BtTransport, RawAddress, Uuid128Bit};
+use bt_topshim::profiles::gatt::GattStatus;
 
 use btstack::bluetooth_gatt::{
-    BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService,
-    GattWriteRequestStatus, GattWriteType, IBluetoothGatt, IBluetoothGattCallback,
-    IScannerCallback, LePhy, RSSISettings, ScanFilter, ScanSettings, ScanType,
+    BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService, ConnectionPriority,
+    GattNotification, GattWriteRequestStatus, GattWriteType, IBluetoothGatt,
+    IBluetoothGattCallback, IScannerCallback, LePhy, RSSISettings, ScanFilter, ScanSettings,
+    ScanType,
 };
 use btstack::RPCProxy;
 
@@ -12,7 +14,7 @@ use dbus::arg::RefArg;
 use dbus::nonblock::SyncConnection;
 use dbus::strings::Path;
 
-use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
 
 use dbus_projection::DisconnectWatcher;
 use dbus_projection::{dbus_generated, impl_dbus_arg_enum};
@@ -40,70 +42,80 @@ impl IBluetoothGattCallback for BluetoothGattCallbackDBus {
         status: i32,
         client_id: i32,
         connected: bool,
-        addr: String,
+        addr: RawAddress,
     ) {
         dbus_generated!()
     }
 
     #[dbus_method("OnPhyUpdate")]
-    fn on_phy_update(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
+    fn on_phy_update(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
         dbus_generated!()
     }
 
     #[dbus_method("OnPhyRead")]
-    fn on_phy_read(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
+    fn on_phy_read(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
         dbus_generated!()
     }
 
     #[dbus_method("OnSearchComplete")]
-    fn on_search_complete(&self, addr: String, services: Vec<BluetoothGattService>, status: i32) {
+    fn on_search_complete(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        status: i32,
+    ) {
         dbus_generated!()
     }
 
     #[dbus_method("OnCharacteristicRead")]
-    fn on_characteristic_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {
+    fn on_characteristic_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>) {
         dbus_generated!()
     }
 
     #[dbus_method("OnCharacteristicWrite")]
-    fn on_characteristic_write(&self, addr: String, status: i32, handle: i32) {
+    fn on_characteristic_write(&self, addr: RawAddress, status: i32, handle: i32) {
         dbus_generated!()
     }
 
     #[dbus_method("OnExecuteWrite")]
-    fn on_execute_write(&self, addr: String, status: i32) {
+    fn on_execute_write(&self, addr: RawAddress, status: i32) {
         dbus_generated!()
     }
 
     #[dbus_method("OnDescriptorRead")]
-    fn on_descriptor_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {
+    fn on_descriptor_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>) {
         dbus_generated!()
     }
 
     #[dbus_method("OnDescriptorWrite")]
-    fn on_descriptor_write(&self, addr: String, status: i32, handle: i32) {
+    fn on_descriptor_write(&self, addr: RawAddress, status: i32, handle: i32) {
         dbus_generated!()
     }
 
     #[dbus_method("OnNotify")]
-    fn on_notify(&self, addr: String, handle: i32, value: Vec<u8>) {
+    fn on_notify(&self, addr: RawAddress, handle: i32, value: Arc<[u8]>) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnNotifyBatch")]
+    fn on_notify_batch(&self, addr: RawAddress, notifications: Vec<GattNotification>) {
         dbus_generated!()
     }
 
     #[dbus_method("OnReadRemoteRssi")]
-    fn on_read_remote_rssi(&self, addr: String, rssi: i32, status: i32) {
+    fn on_read_remote_rssi(&self, addr: RawAddress, rssi: i32, status: i32) {
         dbus_generated!()
     }
 
     #[dbus_method("OnConfigureMtu")]
-    fn on_configure_mtu(&self, addr: String, mtu: i32, status: i32) {
+    fn on_configure_mtu(&self, addr: RawAddress, mtu: i32, status: i32) {
         dbus_generated!()
     }
 
     #[dbus_method("OnConnectionUpdated")]
     fn on_connection_updated(
         &self,
-        addr: String,
+        addr: RawAddress,
         interval: i32,
         latency: i32,
         timeout: i32,
@@ -113,7 +125,22 @@ impl IBluetoothGattCallback for BluetoothGattCallbackDBus {
     }
 
     #[dbus_method("OnServiceChanged")]
-    fn on_service_changed(&self, addr: String) {
+    fn on_service_changed(&self, addr: RawAddress) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnGattDbUpdated")]
+    fn on_gatt_db_updated(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        database_hash: Vec<u8>,
+    ) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnAuthorizationRequest")]
+    fn on_authorization_request(&self, addr: RawAddress, handle: i32) {
         dbus_generated!()
     }
 }
@@ -128,7 +155,13 @@ impl DBusArg for Uuid128Bit {
         _remote: Option<dbus::strings::BusName<'static>>,
         _disconnect_watcher: Option<Arc<std::sync::Mutex<DisconnectWatcher>>>,
     ) -> Result<[u8; 16], Box<dyn std::error::Error>> {
-        return Ok(data.try_into().unwrap());
+        let len = data.len();
+        match data.try_into() {
+            Ok(uuid) => Ok(uuid),
+            Err(_) => {
+                Err(Box::new(DBusArgError::new(format!("UUID must be 16 bytes, found {}", len))))
+            }
+        }
     }
 
     fn to_dbus(data: [u8; 16]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
@@ -174,6 +207,13 @@ pub struct BluetoothGattServiceDBus {
     included_services: Vec<BluetoothGattService>,
 }
 
+#[dbus_propmap(GattNotification)]
+pub struct GattNotificationDBus {
+    handle: i32,
+    value: Vec<u8>,
+    timestamp_us: u64,
+}
+
 #[dbus_propmap(RSSISettings)]
 pub struct RSSISettingsDBus {
     low_threshold: i32,
@@ -188,6 +228,7 @@ struct ScanSettingsDBus {
     rssi_settings: RSSISettings,
 }
 
+impl_dbus_arg_enum!(ConnectionPriority);
 impl_dbus_arg_enum!(GattStatus);
 impl_dbus_arg_enum!(GattWriteRequestStatus);
 impl_dbus_arg_enum!(GattWriteType);
@@ -200,178 +241,4 @@ struct ScanFilterDBus {}
 #[allow(dead_code)]
 struct IBluetoothGattDBus {}
 
-#[generate_dbus_exporter(export_bluetooth_gatt_dbus_obj, "org.chromium.bluetooth.BluetoothGatt")]
-impl IBluetoothGatt for IBluetoothGattDBus {
-    #[dbus_method("RegisterScanner")]
-    fn register_scanner(&self, callback: Box<dyn IScannerCallback + Send>) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("UnregisterScanner")]
-    fn unregister_scanner(&self, scanner_id: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("StartScan")]
-    fn start_scan(&self, scanner_id: i32, settings: ScanSettings, filters: Vec<ScanFilter>) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("StopScan")]
-    fn stop_scan(&self, scanner_id: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RegisterClient")]
-    fn register_client(
-        &mut self,
-        app_uuid: String,
-        callback: Box<dyn IBluetoothGattCallback + Send>,
-        eatt_support: bool,
-    ) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("UnregisterClient")]
-    fn unregister_client(&mut self, client_id: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ClientConnect")]
-    fn client_connect(
-        &self,
-        client_id: i32,
-        addr: String,
-        is_direct: bool,
-        transport: i32,
-        opportunistic: bool,
-        phy: i32,
-    ) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ClientDisconnect")]
-    fn client_disconnect(&self, client_id: i32, addr: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ClientSetPreferredPhy")]
-    fn client_set_preferred_phy(
-        &self,
-        client_id: i32,
-        addr: String,
-        tx_phy: LePhy,
-        rx_phy: LePhy,
-        phy_options: i32,
-    ) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ClientReadPhy")]
-    fn client_read_phy(&mut self, client_id: i32, addr: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RefreshDevice")]
-    fn refresh_device(&self, client_id: i32, addr: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("DiscoverServices")]
-    fn discover_services(&self, client_id: i32, addr: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("DiscoverServiceByUuid")]
-    fn discover_service_by_uuid(&self, client_id: i32, addr: String, uuid: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ReadCharacteristic")]
-    fn read_characteristic(&self, client_id: i32, addr: String, handle: i32, auth_req: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ReadUsingCharacteristicUuid")]
-    fn read_using_characteristic_uuid(
-        &self,
-        client_id: i32,
-        addr: String,
-        uuid: String,
-        start_handle: i32,
-        end_handle: i32,
-        auth_req: i32,
-    ) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("WriteCharacteristic")]
-    fn write_characteristic(
-        &self,
-        client_id: i32,
-        addr: String,
-        handle: i32,
-        write_type: GattWriteType,
-        auth_req: i32,
-        value: Vec<u8>,
-    ) -> GattWriteRequestStatus {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ReadDescriptor")]
-    fn read_descriptor(&self, client_id: i32, addr: String, handle: i32, auth_req: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("WriteDescriptor")]
-    fn write_descriptor(
-        &self,
-        client_id: i32,
-        addr: String,
-        handle: i32,
-        auth_req: i32,
-        value: Vec<u8>,
-    ) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("RegisterForNotification")]
-    fn register_for_notification(&self, client_id: i32, addr: String, handle: i32, enable: bool) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("BeginReliableWrite")]
-    fn begin_reliable_write(&mut self, client_id: i32, addr: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("EndReliableWrite")]
-    fn end_reliable_write(&mut self, client_id: i32, addr: String, execute: bool) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ReadRemoteRssi")]
-    fn read_remote_rssi(&self, client_id: i32, addr: String) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ConfigureMtu")]
-    fn configure_mtu(&self, client_id: i32, addr: String, mtu: i32) {
-        dbus_generated!()
-    }
-
-    #[dbus_method("ConnectionParameterUpdate")]
-    fn connection_parameter_update(
-        &self,
-        client_id: i32,
-        addr: String,
-        min_interval: i32,
-        max_interval: i32,
-        latency: i32,
-        timeout: i32,
-        min_ce_len: u16,
-        max_ce_len: u16,
-    ) {
-        dbus_generated!()
-    }
-}
+btstack::bluetooth_gatt_dbus!(exporter IBluetoothGattDBus, export_bluetooth_gatt_dbus_obj);
@@ -0,0 +1,71 @@
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_map::{
+    IBluetoothMap, IBluetoothMapCallback, MapEvent, MapMessage, MapStatus,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+impl_dbus_arg_enum!(MapStatus);
+
+#[dbus_propmap(MapMessage)]
+pub struct MapMessageDBus {
+    handle: String,
+    subject: String,
+    datetime: String,
+    sender_name: String,
+    sender_addressing: String,
+    msg_type: String,
+    read: bool,
+}
+
+#[dbus_propmap(MapEvent)]
+pub struct MapEventDBus {
+    event_type: String,
+    handle: String,
+    folder: String,
+    msg_type: String,
+}
+
+#[allow(dead_code)]
+struct BluetoothMapCallbackDBus {}
+
+#[dbus_proxy_obj(BluetoothMapCallback, "org.chromium.bluetooth.BluetoothMapCallback")]
+impl IBluetoothMapCallback for BluetoothMapCallbackDBus {
+    #[dbus_method("OnConnectionStateChanged")]
+    fn on_connection_state_changed(&self, device: BluetoothDevice, connected: bool) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnMessageEvent")]
+    fn on_message_event(&self, device: BluetoothDevice, event: MapEvent) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnMessagesListing")]
+    fn on_messages_listing(&self, request_id: i32, status: MapStatus, messages: Vec<MapMessage>) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnMessage")]
+    fn on_message(&self, request_id: i32, status: MapStatus, message: String) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothMapDBus {}
+
+btstack::bluetooth_map_dbus!(exporter IBluetoothMapDBus, export_bluetooth_map_dbus_obj);
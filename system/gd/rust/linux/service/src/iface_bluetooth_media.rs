@@ -1,16 +1,27 @@
 use bt_topshim::profiles::a2dp::{A2dpCodecConfig, PresentationPosition};
-use bt_topshim::profiles::hfp::HfpCodecCapability;
-use btstack::bluetooth_media::{BluetoothAudioDevice, IBluetoothMedia, IBluetoothMediaCallback};
+use bt_topshim::profiles::avrcp::{
+    AvrcpFolderInfo, AvrcpListItem, AvrcpMediaPlayerInfo, AvrcpSongInfo,
+};
+use bt_topshim::profiles::hearing_aid::HearingAidAudioConfig;
+use bt_topshim::profiles::hfp::{
+    BthfAudioState, BthfCallState, CallInfo, HfpCodecCapability, HfpCodecId,
+};
+use btstack::bluetooth_media::{
+    AudioCategory, BluetoothAudioDevice, IBluetoothMedia, IBluetoothMediaCallback,
+};
 use btstack::RPCProxy;
 
 use dbus::arg::RefArg;
 use dbus::nonblock::SyncConnection;
 use dbus::strings::Path;
 
-use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
 
-use dbus_projection::DisconnectWatcher;
-use dbus_projection::{dbus_generated, impl_dbus_arg_from_into};
+use dbus_projection::{
+    dbus_generated, impl_dbus_arg_enum, impl_dbus_arg_from_into, DisconnectWatcher,
+};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
 
 use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
 
@@ -43,6 +54,52 @@ pub struct BluetoothAudioDeviceDBus {
 }
 
 impl_dbus_arg_from_into!(HfpCodecCapability, i32);
+impl_dbus_arg_enum!(AudioCategory);
+impl_dbus_arg_enum!(BthfAudioState);
+impl_dbus_arg_enum!(BthfCallState);
+impl_dbus_arg_enum!(HfpCodecId);
+
+#[dbus_propmap(CallInfo)]
+pub struct CallInfoDBus {
+    index: u32,
+    incoming: bool,
+    state: BthfCallState,
+    multi_party: bool,
+    number: String,
+}
+
+#[dbus_propmap(AvrcpSongInfo)]
+pub struct AvrcpSongInfoDBus {
+    media_id: String,
+    title: String,
+    artist: String,
+    album: String,
+    genre: String,
+    track_number: u32,
+    total_tracks: u32,
+    duration_ms: u32,
+}
+
+#[dbus_propmap(AvrcpFolderInfo)]
+pub struct AvrcpFolderInfoDBus {
+    media_id: String,
+    name: String,
+    is_playable: bool,
+}
+
+#[dbus_propmap(AvrcpListItem)]
+pub struct AvrcpListItemDBus {
+    is_folder: bool,
+    folder: AvrcpFolderInfo,
+    song: AvrcpSongInfo,
+}
+
+#[dbus_propmap(AvrcpMediaPlayerInfo)]
+pub struct AvrcpMediaPlayerInfoDBus {
+    id: u16,
+    name: String,
+    browsing_supported: bool,
+}
 
 #[dbus_proxy_obj(BluetoothMediaCallback, "org.chromium.bluetooth.BluetoothMediaCallback")]
 impl IBluetoothMediaCallback for BluetoothMediaCallbackDBus {
@@ -65,88 +122,75 @@ impl IBluetoothMediaCallback for BluetoothMediaCallbackDBus {
     fn on_absolute_volume_changed(&self, volume: i32) {
         dbus_generated!()
     }
-}
-
-#[allow(dead_code)]
-struct IBluetoothMediaDBus {}
-
-#[dbus_propmap(PresentationPosition)]
-pub struct PresentationPositionDBus {
-    remote_delay_report_ns: u64,
-    total_bytes_read: u64,
-    data_position_sec: i64,
-    data_position_nsec: i32,
-}
-
-#[generate_dbus_exporter(export_bluetooth_media_dbus_obj, "org.chromium.bluetooth.BluetoothMedia")]
-impl IBluetoothMedia for IBluetoothMediaDBus {
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothMediaCallback + Send>) -> bool {
-        dbus_generated!()
-    }
 
-    #[dbus_method("Initialize")]
-    fn initialize(&mut self) -> bool {
+    #[dbus_method("OnAudioConnectionState")]
+    fn on_audio_connection_state(
+        &self,
+        addr: String,
+        state: BthfAudioState,
+        codec: HfpCodecId,
+        sco_handle: u16,
+    ) {
         dbus_generated!()
     }
 
-    #[dbus_method("Cleanup")]
-    fn cleanup(&mut self) -> bool {
+    #[dbus_method("OnHearingAidAdded")]
+    fn on_hearing_aid_added(&self, addr: String, name: String) {
         dbus_generated!()
     }
 
-    #[dbus_method("Connect")]
-    fn connect(&mut self, device: String) {
+    #[dbus_method("OnHearingAidRemoved")]
+    fn on_hearing_aid_removed(&self, addr: String) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetActiveDevice")]
-    fn set_active_device(&mut self, device: String) {
+    #[dbus_method("OnAvrcpPlayItem")]
+    fn on_avrcp_play_item(&self, player_id: u16, now_playing: bool, media_id: String) {
         dbus_generated!()
     }
 
-    #[dbus_method("Disconnect")]
-    fn disconnect(&mut self, device: String) {
+    #[dbus_method("OnActiveDeviceChanged")]
+    fn on_active_device_changed(&self, category: AudioCategory, addr: String) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetAudioConfig")]
-    fn set_audio_config(
-        &mut self,
-        sample_rate: i32,
-        bits_per_sample: i32,
-        channel_mode: i32,
-    ) -> bool {
+    #[dbus_method("OnAudioConfigChanged")]
+    fn on_audio_config_changed(&self, addr: String, config: A2dpCodecConfig) {
         dbus_generated!()
     }
 
-    #[dbus_method("SetVolume")]
-    fn set_volume(&mut self, volume: i32) {
+    #[dbus_method("OnHfpWidebandSpeechChanged")]
+    fn on_hfp_wideband_speech_changed(&self, enabled: bool) {
         dbus_generated!()
     }
 
-    #[dbus_method("StartAudioRequest")]
-    fn start_audio_request(&mut self) {
+    #[dbus_method("OnHfpInbandRingtoneChanged")]
+    fn on_hfp_inband_ringtone_changed(&self, enabled: bool) {
         dbus_generated!()
     }
 
-    #[dbus_method("StopAudioRequest")]
-    fn stop_audio_request(&mut self) {
+    #[dbus_method("OnHfpVoiceRecognitionChanged")]
+    fn on_hfp_voice_recognition_changed(&self, addr: String, active: bool) {
         dbus_generated!()
     }
+}
 
-    #[dbus_method("StartScoCall")]
-    fn start_sco_call(&mut self, device: String) {
-        dbus_generated!()
-    }
+#[allow(dead_code)]
+struct IBluetoothMediaDBus {}
 
-    #[dbus_method("StopScoCall")]
-    fn stop_sco_call(&mut self, device: String) {
-        dbus_generated!()
-    }
+#[dbus_propmap(PresentationPosition)]
+pub struct PresentationPositionDBus {
+    remote_delay_report_ns: u64,
+    total_bytes_read: u64,
+    data_position_sec: i64,
+    data_position_nsec: i32,
+}
 
-    #[dbus_method("GetPresentationPosition")]
-    fn get_presentation_position(&mut self) -> PresentationPosition {
-        dbus_generated!()
-    }
+#[dbus_propmap(HearingAidAudioConfig)]
+pub struct HearingAidAudioConfigDBus {
+    sample_rate: u32,
+    bits_per_sample: u8,
+    channel_count: u8,
 }
+
+btstack::bluetooth_media_dbus!(exporter IBluetoothMediaDBus, export_bluetooth_media_dbus_obj);
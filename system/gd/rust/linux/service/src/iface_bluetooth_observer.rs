@@ -0,0 +1,22 @@
+use bt_topshim::btif::{BtDeviceType, Uuid128Bit};
+
+use btstack::bluetooth::{
+    AdapterState, BluetoothDevice, IBluetoothCallback, IBluetoothConnectionCallback,
+};
+use btstack::bluetooth_observer::IBluetoothObserver;
+use btstack::uuid::Profile;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use std::sync::Arc;
+
+use crate::dbus_arg::DBusArg;
+
+#[allow(dead_code)]
+struct IBluetoothObserverDBus {}
+
+btstack::bluetooth_observer_dbus!(
+    exporter IBluetoothObserverDBus,
+    export_bluetooth_observer_dbus_obj
+);
@@ -0,0 +1,58 @@
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_opp::{
+    IBluetoothOpp, IBluetoothOppCallback, OppTransfer, OppTransferStatus,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+impl_dbus_arg_enum!(OppTransferStatus);
+
+#[dbus_propmap(OppTransfer)]
+pub struct OppTransferDBus {
+    id: i32,
+    device: BluetoothDevice,
+    outgoing: bool,
+    file_name: String,
+    file_path: String,
+    mime_type: String,
+    total_bytes: u64,
+}
+
+#[allow(dead_code)]
+struct BluetoothOppCallbackDBus {}
+
+#[dbus_proxy_obj(BluetoothOppCallback, "org.chromium.bluetooth.BluetoothOppCallback")]
+impl IBluetoothOppCallback for BluetoothOppCallbackDBus {
+    #[dbus_method("OnIncomingTransfer")]
+    fn on_incoming_transfer(&self, transfer: OppTransfer) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnTransferProgress")]
+    fn on_transfer_progress(&self, transfer_id: i32, bytes_transferred: u64, total_bytes: u64) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnTransferComplete")]
+    fn on_transfer_complete(&self, transfer_id: i32, status: OppTransferStatus) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothOppDBus {}
+
+btstack::bluetooth_opp_dbus!(exporter IBluetoothOppDBus, export_bluetooth_opp_dbus_obj);
@@ -0,0 +1,41 @@
+use bt_topshim::profiles::pan::BtPanConnectionState;
+
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_pan::{IBluetoothPan, IBluetoothPanCallback};
+use btstack::RPCProxy;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError};
+
+impl_dbus_arg_enum!(BtPanConnectionState);
+
+#[allow(dead_code)]
+struct BluetoothPanCallbackDBus {}
+
+#[dbus_proxy_obj(BluetoothPanCallback, "org.chromium.bluetooth.BluetoothPanCallback")]
+impl IBluetoothPanCallback for BluetoothPanCallbackDBus {
+    #[dbus_method("OnConnectionStateChanged")]
+    fn on_connection_state_changed(
+        &self,
+        device: BluetoothDevice,
+        state: BtPanConnectionState,
+        interface_name: String,
+    ) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothPanDBus {}
+
+btstack::bluetooth_pan_dbus!(exporter IBluetoothPanDBus, export_bluetooth_pan_dbus_obj);
@@ -0,0 +1,43 @@
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_pbap::{
+    IBluetoothPbap, IBluetoothPbapCallback, PbapPhonebook, PbapPullStatus,
+};
+use btstack::RPCProxy;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError};
+
+impl_dbus_arg_enum!(PbapPhonebook);
+impl_dbus_arg_enum!(PbapPullStatus);
+
+#[allow(dead_code)]
+struct BluetoothPbapCallbackDBus {}
+
+#[dbus_proxy_obj(BluetoothPbapCallback, "org.chromium.bluetooth.BluetoothPbapCallback")]
+impl IBluetoothPbapCallback for BluetoothPbapCallbackDBus {
+    #[dbus_method("OnPhonebookPullComplete")]
+    fn on_phonebook_pull_complete(
+        &self,
+        request_id: i32,
+        status: PbapPullStatus,
+        phonebook_size: u32,
+        vcards: Vec<String>,
+    ) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothPbapDBus {}
+
+btstack::bluetooth_pbap_dbus!(exporter IBluetoothPbapDBus, export_bluetooth_pbap_dbus_obj);
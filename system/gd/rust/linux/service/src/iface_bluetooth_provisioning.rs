@@ -0,0 +1,18 @@
+use bt_topshim::btif::BtStatus;
+
+use btstack::bluetooth_provisioning::IBluetoothProvisioning;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use std::sync::Arc;
+
+use crate::dbus_arg::DBusArg;
+
+#[allow(dead_code)]
+struct IBluetoothProvisioningDBus {}
+
+btstack::bluetooth_provisioning_dbus!(
+    exporter IBluetoothProvisioningDBus,
+    export_bluetooth_provisioning_dbus_obj
+);
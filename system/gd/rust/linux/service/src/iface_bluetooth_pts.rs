@@ -0,0 +1,15 @@
+use bt_topshim::btif::BtTransport;
+
+use btstack::bluetooth_pts::IBluetoothPts;
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use std::sync::Arc;
+
+use crate::dbus_arg::DBusArg;
+
+#[allow(dead_code)]
+struct IBluetoothPtsDBus {}
+
+btstack::bluetooth_pts_dbus!(exporter IBluetoothPtsDBus, export_bluetooth_pts_dbus_obj);
@@ -0,0 +1,38 @@
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_reconnect::{IBluetoothReconnect, IBluetoothReconnectCallback};
+use btstack::RPCProxy;
+
+use crate::dbus_arg::DBusArg;
+
+use dbus_macros::{dbus_method, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, DisconnectWatcher};
+
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use std::sync::Arc;
+
+#[allow(dead_code)]
+struct IBluetoothReconnectDBus {}
+
+btstack::bluetooth_reconnect_dbus!(
+    exporter IBluetoothReconnectDBus,
+    export_bluetooth_reconnect_dbus_obj
+);
+
+#[allow(dead_code)]
+struct ReconnectCallbackDBus {}
+
+#[dbus_proxy_obj(ReconnectCallback, "org.chromium.bluetooth.ReconnectCallback")]
+impl IBluetoothReconnectCallback for ReconnectCallbackDBus {
+    #[dbus_method("OnReconnectAttempt")]
+    fn on_reconnect_attempt(&self, device: BluetoothDevice, attempt: u32) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnReconnectGaveUp")]
+    fn on_reconnect_gave_up(&self, device: BluetoothDevice, attempts: u32) {
+        dbus_generated!()
+    }
+}
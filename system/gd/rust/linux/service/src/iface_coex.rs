@@ -0,0 +1,32 @@
+use btstack::coex::{CoexState, IBluetoothCoex, WifiChannel};
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::dbus_propmap;
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+#[dbus_propmap(WifiChannel)]
+pub struct WifiChannelDBus {
+    frequency_mhz: u32,
+    bandwidth_mhz: u32,
+}
+
+#[dbus_propmap(CoexState)]
+pub struct CoexStateDBus {
+    wifi_channels: Vec<WifiChannel>,
+    latency_sensitive: bool,
+    bredr_channel_map: Vec<u8>,
+    le_channel_map: Vec<u8>,
+    scan_limit_percent: u32,
+    channel_classification_applied: bool,
+}
+
+#[allow(dead_code)]
+struct IBluetoothCoexDBus {}
+
+btstack::bluetooth_coex_dbus!(exporter IBluetoothCoexDBus, export_bluetooth_coex_dbus_obj);
@@ -0,0 +1,64 @@
+use bt_topshim::btif::BtStatus;
+use btstack::bluetooth::BluetoothDevice;
+use btstack::distance_measurement::{
+    DistanceMeasurementMethod, DistanceMeasurementResult, IBluetoothDistanceMeasurement,
+    IBluetoothDistanceMeasurementCallback,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+impl_dbus_arg_enum!(DistanceMeasurementMethod);
+
+#[dbus_propmap(DistanceMeasurementResult)]
+pub struct DistanceMeasurementResultDBus {
+    method: DistanceMeasurementMethod,
+    distance_cm: u32,
+    error_cm: u32,
+    rssi: i32,
+}
+
+#[allow(dead_code)]
+struct IBluetoothDistanceMeasurementDBus {}
+
+btstack::distance_measurement_dbus!(
+    exporter IBluetoothDistanceMeasurementDBus,
+    export_distance_measurement_dbus_obj
+);
+
+#[allow(dead_code)]
+struct DistanceMeasurementCallbackDBus {}
+
+#[dbus_proxy_obj(DistanceMeasurementCallback, "org.chromium.bluetooth.DistanceMeasurementCallback")]
+impl IBluetoothDistanceMeasurementCallback for DistanceMeasurementCallbackDBus {
+    #[dbus_method("OnDistanceMeasurementStarted")]
+    fn on_distance_measurement_started(
+        &self,
+        device: BluetoothDevice,
+        method: DistanceMeasurementMethod,
+    ) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnDistanceMeasurementStopped")]
+    fn on_distance_measurement_stopped(&self, device: BluetoothDevice, status: BtStatus) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnDistanceMeasurement")]
+    fn on_distance_measurement(&self, device: BluetoothDevice, result: DistanceMeasurementResult) {
+        dbus_generated!()
+    }
+}
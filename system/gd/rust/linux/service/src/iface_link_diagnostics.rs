@@ -0,0 +1,94 @@
+use bt_topshim::btif::BtStatus;
+use btstack::bluetooth::BluetoothDevice;
+use btstack::bluetooth_gatt::LePhy;
+use btstack::link_diagnostics::{
+    IBluetoothLinkDiagnostics, IBluetoothLinkDiagnosticsCallback, PathLossZone, PingStats,
+    TxPowerReport, TxPowerSource,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+#[dbus_propmap(PingStats)]
+pub struct PingStatsDBus {
+    sent: u32,
+    received: u32,
+    min_rtt_us: u64,
+    avg_rtt_us: u64,
+    max_rtt_us: u64,
+}
+
+impl_dbus_arg_enum!(PathLossZone);
+impl_dbus_arg_enum!(TxPowerSource);
+
+#[dbus_propmap(TxPowerReport)]
+pub struct TxPowerReportDBus {
+    source: TxPowerSource,
+    phy: LePhy,
+    tx_power: i32,
+    max_tx_power: i32,
+    at_min: bool,
+    at_max: bool,
+    delta: i32,
+}
+
+#[allow(dead_code)]
+struct IBluetoothLinkDiagnosticsDBus {}
+
+btstack::link_diagnostics_dbus!(
+    exporter IBluetoothLinkDiagnosticsDBus,
+    export_link_diagnostics_dbus_obj
+);
+
+#[allow(dead_code)]
+struct LinkDiagnosticsCallbackDBus {}
+
+#[dbus_proxy_obj(LinkDiagnosticsCallback, "org.chromium.bluetooth.LinkDiagnosticsCallback")]
+impl IBluetoothLinkDiagnosticsCallback for LinkDiagnosticsCallbackDBus {
+    #[dbus_method("OnPingReply")]
+    fn on_ping_reply(&self, device: BluetoothDevice, sequence: u32, rtt_us: u64) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnPingLost")]
+    fn on_ping_lost(&self, device: BluetoothDevice, sequence: u32) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnPingComplete")]
+    fn on_ping_complete(&self, device: BluetoothDevice, stats: PingStats) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnTxPowerReported")]
+    fn on_tx_power_reported(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        report: TxPowerReport,
+    ) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnPathLossZoneEntered")]
+    fn on_path_loss_zone_entered(
+        &self,
+        device: BluetoothDevice,
+        zone: PathLossZone,
+        path_loss: u32,
+    ) {
+        dbus_generated!()
+    }
+}
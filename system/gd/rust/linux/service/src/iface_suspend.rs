@@ -3,7 +3,7 @@ use btstack::RPCProxy;
 
 use crate::dbus_arg::{DBusArg, DBusArgError};
 
-use dbus_macros::{dbus_method, dbus_proxy_obj, generate_dbus_exporter};
+use dbus_macros::{dbus_method, dbus_proxy_obj};
 
 use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
 
@@ -19,28 +19,7 @@ impl_dbus_arg_enum!(SuspendType);
 #[allow(dead_code)]
 struct ISuspendDBus {}
 
-#[generate_dbus_exporter(export_suspend_dbus_obj, "org.chromium.bluetooth.Suspend")]
-impl ISuspend for ISuspendDBus {
-    #[dbus_method("RegisterCallback")]
-    fn register_callback(&mut self, callback: Box<dyn ISuspendCallback + Send>) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("UnregisterCallback")]
-    fn unregister_callback(&mut self, callback_id: u32) -> bool {
-        dbus_generated!()
-    }
-
-    #[dbus_method("Suspend")]
-    fn suspend(&self, suspend_type: SuspendType) -> u32 {
-        dbus_generated!()
-    }
-
-    #[dbus_method("Resume")]
-    fn resume(&self) -> bool {
-        dbus_generated!()
-    }
-}
+btstack::suspend_dbus!(exporter ISuspendDBus, export_suspend_dbus_obj);
 
 #[allow(dead_code)]
 struct SuspendCallbackDBus {}
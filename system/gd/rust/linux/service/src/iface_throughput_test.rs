@@ -0,0 +1,57 @@
+use bt_topshim::btif::BtStatus;
+use btstack::bluetooth::BluetoothDevice;
+use btstack::throughput_test::{
+    IBluetoothThroughputTest, IBluetoothThroughputTestCallback, ThroughputDirection,
+    ThroughputTestResult, ThroughputTransport,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+impl_dbus_arg_enum!(ThroughputDirection);
+impl_dbus_arg_enum!(ThroughputTransport);
+
+#[dbus_propmap(ThroughputTestResult)]
+pub struct ThroughputTestResultDBus {
+    bytes: u64,
+    packets: u32,
+    lost_packets: u32,
+    duration_ms: u64,
+    throughput_bps: u64,
+}
+
+#[allow(dead_code)]
+struct IBluetoothThroughputTestDBus {}
+
+btstack::throughput_test_dbus!(
+    exporter IBluetoothThroughputTestDBus,
+    export_throughput_test_dbus_obj
+);
+
+#[allow(dead_code)]
+struct ThroughputTestCallbackDBus {}
+
+#[dbus_proxy_obj(ThroughputTestCallback, "org.chromium.bluetooth.ThroughputTestCallback")]
+impl IBluetoothThroughputTestCallback for ThroughputTestCallbackDBus {
+    #[dbus_method("OnThroughputTestComplete")]
+    fn on_throughput_test_complete(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        result: ThroughputTestResult,
+    ) {
+        dbus_generated!()
+    }
+}
@@ -2,29 +2,80 @@ use dbus::{channel::MatchingReceiver, message::MatchRule};
 use dbus_crossroads::Crossroads;
 use dbus_tokio::connection;
 use futures::future;
-use log::LevelFilter;
+use log::{error, info, warn};
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
 use std::error::Error;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use syslog::{BasicLogger, Facility, Formatter3164};
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{sleep, Instant};
 
-use bt_topshim::{btif::get_btinterface, topstack};
+use bt_topshim::{btif::get_btinterface, topstack, watchdog};
 use btstack::{
+    activity::ActivityTracker,
+    advertising_privacy::AdvertisingPrivacy,
+    api_trace::{self, ApiCallKind, ApiTrace, TracedCall},
+    audio_control,
     bluetooth::{get_bt_dispatcher, Bluetooth, IBluetooth},
+    bluetooth_admin::BluetoothAdmin,
+    bluetooth_adv_monitor::BluetoothAdvMonitor,
+    bluetooth_fast_pair::BluetoothFastPair,
     bluetooth_gatt::BluetoothGatt,
+    bluetooth_map::BluetoothMap,
     bluetooth_media::BluetoothMedia,
+    bluetooth_observer::BluetoothObserver,
+    bluetooth_opp::BluetoothOpp,
+    bluetooth_pan::BluetoothPan,
+    bluetooth_pbap::BluetoothPbap,
+    bluetooth_provisioning::{self, BluetoothProvisioning},
+    bluetooth_reconnect::BluetoothReconnect,
+    coex::BluetoothCoex,
+    device_store::{self, DeviceStore},
+    distance_measurement::BluetoothDistanceMeasurement,
+    link_diagnostics::BluetoothLinkDiagnostics,
+    link_users::LinkUsers,
+    radio_arbiter::RadioArbiter,
+    runtime_config::RuntimeConfig,
     suspend::Suspend,
-    Stack,
+    throughput_test::BluetoothThroughputTest,
+    Message, Stack,
 };
-use dbus_projection::DisconnectWatcher;
+use dbus_projection::{ApiCall, ApiCallDirection, DisconnectWatcher};
 
 mod dbus_arg;
+mod iface_advertising_privacy;
 mod iface_bluetooth;
+mod iface_bluetooth_admin;
+mod iface_bluetooth_adv_monitor;
+mod iface_bluetooth_fast_pair;
 mod iface_bluetooth_gatt;
+mod iface_bluetooth_map;
 mod iface_bluetooth_media;
+mod iface_bluetooth_observer;
+mod iface_bluetooth_opp;
+mod iface_bluetooth_pan;
+mod iface_bluetooth_pbap;
+mod iface_bluetooth_provisioning;
+#[cfg(feature = "pts")]
+mod iface_bluetooth_pts;
+mod iface_bluetooth_reconnect;
+mod iface_coex;
+mod iface_distance_measurement;
+mod iface_link_diagnostics;
 mod iface_suspend;
+mod iface_throughput_test;
 
 const DBUS_SERVICE_NAME: &str = "org.chromium.bluetooth";
 
+/// How long to wait for the stack to turn off on SIGTERM before exiting anyway.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often to check whether the stack has turned off during shutdown.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Check command line arguments for target hci adapter (--hci=N). If no adapter
 /// is set, default to 0.
 fn get_adapter_index(args: &Vec<String>) -> i32 {
@@ -40,10 +91,147 @@ fn get_adapter_index(args: &Vec<String>) -> i32 {
     0
 }
 
+/// Imports devices paired under BlueZ into the Floss config and exits (--import-bluez). The
+/// adapter daemon must not be running, otherwise it will overwrite the config on its next flush.
+fn import_bluez_devices() -> Result<(), Box<dyn Error>> {
+    let mut store = DeviceStore::load(device_store::DEFAULT_CONFIG_PATH)?;
+    let count = device_store::import_bluez(&mut store, device_store::BLUEZ_STORAGE_DIR)?;
+    store.save()?;
+
+    println!("Imported {} device(s) into {}", count, device_store::DEFAULT_CONFIG_PATH);
+    Ok(())
+}
+
+/// Makes the watchdog abort the daemon when a call into libbluetooth or an expected callback
+/// misses its deadline (--watchdog-recovery). The manager then restarts the adapter the same way
+/// it recovers from a crash.
+fn enable_watchdog_recovery(args: &Vec<String>) {
+    if !args.iter().any(|arg| arg == "--watchdog-recovery") {
+        return;
+    }
+
+    watchdog::set_recovery_handler(Box::new(|operation| {
+        error!("{} missed its deadline, aborting so the adapter can be restarted", operation);
+        std::process::abort();
+    }));
+}
+
+/// Writes every D-Bus method call and callback to the API trace (--api-trace), for bug reports.
+fn enable_api_trace(args: &Vec<String>) {
+    if !args.iter().any(|arg| arg == "--api-trace") {
+        return;
+    }
+
+    let trace = Mutex::new(ApiTrace::new(api_trace::API_TRACE_PATH));
+    dbus_projection::set_api_tracer(Some(Box::new(move |call: ApiCall| {
+        let kind = match call.direction {
+            ApiCallDirection::Incoming => ApiCallKind::Call,
+            ApiCallDirection::Callback => ApiCallKind::Callback,
+        };
+        trace.lock().unwrap().record(&TracedCall {
+            kind,
+            peer: call.peer,
+            path: call.path,
+            interface: call.interface,
+            method: call.method,
+            args: call.args,
+        });
+    })));
+    info!("Tracing the API calls to {}", api_trace::API_TRACE_PATH);
+}
+
 fn make_object_name(idx: i32, name: &str) -> String {
     String::from(format!("/org/chromium/bluetooth/hci{}/{}", idx, name))
 }
 
+/// Blocks `signal` and returns a signalfd it can be read from instead. This has to be done before
+/// any thread is started, so that they all inherit the mask and none of them takes the default
+/// action. The signal is left to its default action if it can't be watched.
+fn watch_signal(signal: Signal) -> Option<SignalFd> {
+    let mut mask = SigSet::empty();
+    mask.add(signal);
+    let result = mask
+        .thread_block()
+        .and_then(|()| SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC));
+    match result {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            error!("Failed to watch for {:?}: {}", signal, e);
+            let _ = mask.thread_unblock();
+            None
+        }
+    }
+}
+
+/// Waits for the next signal on `fd`. Returns false if it can't be read anymore.
+async fn recv_signal(fd: &mut AsyncFd<SignalFd>) -> bool {
+    loop {
+        let mut guard = match fd.readable_mut().await {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("Failed to poll for signals: {}", e);
+                return false;
+            }
+        };
+        match guard.get_inner_mut().read_signal() {
+            Ok(Some(_)) => return true,
+            Ok(None) => guard.clear_ready(),
+            Err(e) => {
+                error!("Failed to read a signal: {}", e);
+                return false;
+            }
+        }
+    }
+}
+
+/// Waits for SIGTERM (sent by the manager when stopping the adapter) and exits only after the
+/// adapter has been brought down cleanly.
+async fn handle_sigterm(sigterm: SignalFd, bluetooth: Arc<Mutex<Box<Bluetooth>>>) {
+    let mut sigterm = match AsyncFd::new(sigterm) {
+        Ok(fd) => fd,
+        Err(e) => {
+            error!("Failed to watch for SIGTERM: {}", e);
+            return;
+        }
+    };
+    if !recv_signal(&mut sigterm).await {
+        return;
+    }
+
+    info!("Received SIGTERM, shutting down");
+    if bluetooth.lock().unwrap().prepare_shutdown() {
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !bluetooth.lock().unwrap().is_off() {
+            if Instant::now() >= deadline {
+                warn!("Timed out waiting for the stack to turn off");
+                break;
+            }
+            sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+    } else {
+        warn!("Failed to disable the stack before exiting");
+    }
+
+    std::process::exit(0);
+}
+
+/// Applies the runtime config, then again each time SIGHUP arrives.
+async fn handle_sighup(sighup: Option<SignalFd>, tx: Sender<Message>) {
+    let _ = tx.send(Message::ReloadRuntimeConfig).await;
+    let mut sighup = match sighup.map(AsyncFd::new) {
+        Some(Ok(fd)) => fd,
+        Some(Err(e)) => {
+            error!("Failed to watch for SIGHUP: {}", e);
+            return;
+        }
+        None => return,
+    };
+    while recv_signal(&mut sighup).await {
+        info!("Received SIGHUP, reloading the runtime config");
+        let _ = tx.send(Message::ReloadRuntimeConfig).await;
+    }
+}
+
 /// Runs the Bluetooth daemon serving D-Bus IPC.
 fn main() -> Result<(), Box<dyn Error>> {
     let formatter = Formatter3164 {
@@ -55,26 +243,80 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let logger = syslog::unix(formatter).expect("could not connect to syslog");
     let _ = log::set_boxed_logger(Box::new(BasicLogger::new(logger)))
-        .map(|()| log::set_max_level(LevelFilter::Info));
+        .map(|()| RuntimeConfig::load_default().apply_log_level());
+
+    if std::env::args().any(|arg| arg == "--import-bluez") {
+        return import_bluez_devices();
+    }
+
+    // Before libbluetooth and the runtime start their threads.
+    let sigterm = watch_signal(Signal::SIGTERM);
+    let sighup = watch_signal(Signal::SIGHUP);
+
+    // libbluetooth isn't holding its config yet, so bonds imported for provisioning can go in.
+    match bluetooth_provisioning::apply_pending_bonds() {
+        Ok(0) => (),
+        Ok(count) => info!("Added {} provisioned bond(s)", count),
+        Err(e) => warn!("Failed to add the provisioned bonds: {}", e),
+    }
 
     let (tx, rx) = Stack::create_channel();
 
     let intf = Arc::new(Mutex::new(get_btinterface().unwrap()));
     let suspend = Arc::new(Mutex::new(Box::new(Suspend::new(tx.clone()))));
-    let bluetooth_gatt = Arc::new(Mutex::new(Box::new(BluetoothGatt::new(intf.clone()))));
+    let activity = Arc::new(Mutex::new(ActivityTracker::new()));
+    let link_users = Arc::new(Mutex::new(LinkUsers::new()));
+    let radio = Arc::new(Mutex::new(RadioArbiter::new(tx.clone())));
+    let bluetooth_gatt = Arc::new(Mutex::new(Box::new(BluetoothGatt::new(
+        tx.clone(),
+        intf.clone(),
+        activity.clone(),
+        link_users.clone(),
+        radio.clone(),
+    ))));
     let bluetooth_media =
         Arc::new(Mutex::new(Box::new(BluetoothMedia::new(tx.clone(), intf.clone()))));
     let bluetooth = Arc::new(Mutex::new(Box::new(Bluetooth::new(
         tx.clone(),
         intf.clone(),
         bluetooth_media.clone(),
+        activity.clone(),
+        link_users,
+        radio.clone(),
     ))));
+    let bluetooth_admin = Arc::new(Mutex::new(Box::new(BluetoothAdmin::new(tx.clone()))));
+    let bluetooth_opp = Arc::new(Mutex::new(Box::new(BluetoothOpp::new(tx.clone(), intf.clone()))));
+    let bluetooth_pbap =
+        Arc::new(Mutex::new(Box::new(BluetoothPbap::new(tx.clone(), intf.clone()))));
+    let bluetooth_map = Arc::new(Mutex::new(Box::new(BluetoothMap::new(tx.clone(), intf.clone()))));
+    let bluetooth_pan = Arc::new(Mutex::new(Box::new(BluetoothPan::new(tx.clone(), intf.clone()))));
+    let advertising_privacy = Arc::new(Mutex::new(Box::new(AdvertisingPrivacy::new(tx.clone()))));
+    let bluetooth_fast_pair = Arc::new(Mutex::new(Box::new(BluetoothFastPair::new(
+        activity,
+        radio.clone(),
+        advertising_privacy.clone(),
+    ))));
+    let bluetooth_adv_monitor =
+        Arc::new(Mutex::new(Box::new(BluetoothAdvMonitor::new(tx.clone(), radio.clone()))));
+    let bluetooth_observer =
+        Arc::new(Mutex::new(Box::new(BluetoothObserver::new(bluetooth.clone()))));
+    let bluetooth_provisioning = Arc::new(Mutex::new(Box::new(BluetoothProvisioning::new())));
+    let bluetooth_reconnect = Arc::new(Mutex::new(Box::new(BluetoothReconnect::new(tx.clone()))));
+    let coex = Arc::new(Mutex::new(Box::new(BluetoothCoex::new(intf.clone(), radio))));
+    let distance_measurement =
+        Arc::new(Mutex::new(Box::new(BluetoothDistanceMeasurement::new(tx.clone()))));
+    let link_diagnostics =
+        Arc::new(Mutex::new(Box::new(BluetoothLinkDiagnostics::new(tx.clone(), intf.clone()))));
+    let throughput_test =
+        Arc::new(Mutex::new(Box::new(BluetoothThroughputTest::new(tx.clone(), intf.clone()))));
 
     // Args don't include arg[0] which is the binary name
     let all_args = std::env::args().collect::<Vec<String>>();
     let args = all_args[1..].to_vec();
 
     let adapter_index = get_adapter_index(&args);
+    enable_watchdog_recovery(&args);
+    enable_api_trace(&args);
 
     topstack::get_runtime().block_on(async {
         // Connect to D-Bus system bus.
@@ -99,13 +341,26 @@ fn main() -> Result<(), Box<dyn Error>> {
             }),
         )));
 
-        // Run the stack main dispatch loop.
-        topstack::get_runtime().spawn(Stack::dispatch(
+        // Run the stack main dispatch loop on its own thread.
+        Stack::spawn_dispatch_thread(Stack::dispatch(
             rx,
+            advertising_privacy.clone(),
             bluetooth.clone(),
+            bluetooth_admin.clone(),
+            bluetooth_adv_monitor.clone(),
+            bluetooth_fast_pair.clone(),
             bluetooth_gatt.clone(),
+            bluetooth_map.clone(),
             bluetooth_media.clone(),
+            bluetooth_opp.clone(),
+            bluetooth_pan.clone(),
+            bluetooth_pbap.clone(),
+            bluetooth_reconnect.clone(),
+            coex.clone(),
+            distance_measurement.clone(),
+            link_diagnostics.clone(),
             suspend.clone(),
+            throughput_test.clone(),
         ));
 
         // Set up the disconnect watcher to monitor client disconnects.
@@ -120,6 +375,20 @@ fn main() -> Result<(), Box<dyn Error>> {
             bluetooth.clone(),
             disconnect_watcher.clone(),
         );
+        iface_bluetooth::export_bluetooth_link_policy_dbus_obj(
+            make_object_name(adapter_index, "link_policy"),
+            conn.clone(),
+            &mut cr,
+            bluetooth.clone(),
+            disconnect_watcher.clone(),
+        );
+        iface_bluetooth_observer::export_bluetooth_observer_dbus_obj(
+            make_object_name(adapter_index, "observer"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_observer,
+            disconnect_watcher.clone(),
+        );
         // Register D-Bus method handlers of IBluetoothGatt.
         iface_bluetooth_gatt::export_bluetooth_gatt_dbus_obj(
             make_object_name(adapter_index, "gatt"),
@@ -129,6 +398,14 @@ fn main() -> Result<(), Box<dyn Error>> {
             disconnect_watcher.clone(),
         );
 
+        iface_bluetooth_admin::export_bluetooth_admin_dbus_obj(
+            make_object_name(adapter_index, "admin"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_admin.clone(),
+            disconnect_watcher.clone(),
+        );
+
         iface_bluetooth_media::export_bluetooth_media_dbus_obj(
             make_object_name(adapter_index, "media"),
             conn.clone(),
@@ -137,6 +414,110 @@ fn main() -> Result<(), Box<dyn Error>> {
             disconnect_watcher.clone(),
         );
 
+        iface_bluetooth_opp::export_bluetooth_opp_dbus_obj(
+            make_object_name(adapter_index, "opp"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_opp.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_pan::export_bluetooth_pan_dbus_obj(
+            make_object_name(adapter_index, "pan"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_pan.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_fast_pair::export_bluetooth_fast_pair_dbus_obj(
+            make_object_name(adapter_index, "fast_pair"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_fast_pair.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_adv_monitor::export_bluetooth_adv_monitor_dbus_obj(
+            make_object_name(adapter_index, "adv_monitor"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_adv_monitor.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_pbap::export_bluetooth_pbap_dbus_obj(
+            make_object_name(adapter_index, "pbap"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_pbap.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_map::export_bluetooth_map_dbus_obj(
+            make_object_name(adapter_index, "map"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_map.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_provisioning::export_bluetooth_provisioning_dbus_obj(
+            make_object_name(adapter_index, "provisioning"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_provisioning,
+            disconnect_watcher.clone(),
+        );
+
+        iface_bluetooth_reconnect::export_bluetooth_reconnect_dbus_obj(
+            make_object_name(adapter_index, "reconnect"),
+            conn.clone(),
+            &mut cr,
+            bluetooth_reconnect.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_link_diagnostics::export_link_diagnostics_dbus_obj(
+            make_object_name(adapter_index, "link_diagnostics"),
+            conn.clone(),
+            &mut cr,
+            link_diagnostics,
+            disconnect_watcher.clone(),
+        );
+
+        iface_throughput_test::export_throughput_test_dbus_obj(
+            make_object_name(adapter_index, "throughput_test"),
+            conn.clone(),
+            &mut cr,
+            throughput_test.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_distance_measurement::export_distance_measurement_dbus_obj(
+            make_object_name(adapter_index, "distance_measurement"),
+            conn.clone(),
+            &mut cr,
+            distance_measurement.clone(),
+            disconnect_watcher.clone(),
+        );
+
+        iface_coex::export_bluetooth_coex_dbus_obj(
+            make_object_name(adapter_index, "coex"),
+            conn.clone(),
+            &mut cr,
+            coex,
+            disconnect_watcher.clone(),
+        );
+
+        iface_advertising_privacy::export_advertising_privacy_dbus_obj(
+            make_object_name(adapter_index, "advertising_privacy"),
+            conn.clone(),
+            &mut cr,
+            advertising_privacy.clone(),
+            disconnect_watcher.clone(),
+        );
+
         iface_suspend::export_suspend_dbus_obj(
             make_object_name(adapter_index, "suspend"),
             conn.clone(),
@@ -145,20 +526,55 @@ fn main() -> Result<(), Box<dyn Error>> {
             disconnect_watcher.clone(),
         );
 
+        #[cfg(feature = "pts")]
+        iface_bluetooth_pts::export_bluetooth_pts_dbus_obj(
+            make_object_name(adapter_index, "pts"),
+            conn.clone(),
+            &mut cr,
+            Arc::new(Mutex::new(Box::new(btstack::bluetooth_pts::BluetoothPts::new(intf.clone())))),
+            disconnect_watcher.clone(),
+        );
+
         // Hold locks and initialize all interfaces. This must be done AFTER DBus is
         // initialized so DBus can properly enforce user policies.
         {
             intf.lock().unwrap().initialize(get_bt_dispatcher(tx.clone()), args);
 
             bluetooth_media.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_admin.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_opp.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_pbap.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_map.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_pan.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_fast_pair.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_fast_pair.lock().unwrap().set_gatt(bluetooth_gatt.clone());
+            bluetooth_adv_monitor.lock().unwrap().set_adapter(bluetooth.clone());
+            bluetooth_adv_monitor.lock().unwrap().set_gatt(bluetooth_gatt.clone());
+            bluetooth_reconnect.lock().unwrap().set_adapter(bluetooth.clone());
+            advertising_privacy.lock().unwrap().set_adapter(bluetooth.clone());
+            advertising_privacy.lock().unwrap().set_gatt(bluetooth_gatt.clone());
+            throughput_test.lock().unwrap().set_gatt(bluetooth_gatt.clone());
+            distance_measurement.lock().unwrap().set_gatt(bluetooth_gatt.clone());
 
             let mut bluetooth = bluetooth.lock().unwrap();
             bluetooth.init_profiles();
             bluetooth.enable();
 
             bluetooth_gatt.lock().unwrap().init_profiles(tx.clone());
+            bluetooth_pan.lock().unwrap().init_profiles();
         }
 
+        // Tear down connections and flush the config when asked to stop.
+        if let Some(sigterm) = sigterm {
+            tokio::spawn(handle_sigterm(sigterm, bluetooth.clone()));
+        }
+
+        // Pick up changes to the runtime config when asked to.
+        tokio::spawn(handle_sighup(sighup, tx.clone()));
+
+        // Let the audio server run the media streams without D-Bus.
+        tokio::spawn(audio_control::serve(bluetooth_media.clone()));
+
         // Start listening on DBus after exporting interfaces and initializing
         // all bluetooth objects.
         conn.start_receive(
@@ -0,0 +1,9 @@
+#![no_main]
+
+//! D-Bus messages sent to the daemons, converted to the stack's structures.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    floss_client::fuzzing::convert_dbus_message(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+//! Decrypted writes to the Fast Pair GATT characteristics.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    btstack::fuzzing::parse_fast_pair_block(data);
+});
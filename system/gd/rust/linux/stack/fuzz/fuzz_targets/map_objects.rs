@@ -0,0 +1,9 @@
+#![no_main]
+
+//! Message listings and event reports received from MAP servers.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    btstack::fuzzing::parse_map_objects(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+//! OBEX packets received from OPP, MAP and PBAP peers.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    btstack::fuzzing::parse_obex_packet(data);
+});
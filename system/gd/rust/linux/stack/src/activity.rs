@@ -0,0 +1,238 @@
+//! Accounting of the radio activity the stack causes, so that power tooling can attribute battery
+//! drain to Bluetooth.
+//!
+//! The controller reports how long its radio transmitted, received and idled, if it supports the
+//! activity energy info command. The stack adds what only it knows: which devices the traffic was
+//! with, and how much of the time it had the radio scanning and advertising.
+
+use bt_topshim::btif::{BtActivityEnergyInfo, RawAddress};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Length of a scan or advertising interval unit.
+const SLOT: Duration = Duration::from_micros(625);
+
+/// Air time of one byte on the LE 1M PHY.
+const BYTE_AIRTIME: Duration = Duration::from_micros(8);
+
+/// Bytes of a legacy advertising PDU besides the advertising data: preamble, access address,
+/// header, advertiser address and CRC.
+const ADV_PDU_OVERHEAD: usize = 16;
+
+/// Traffic with a remote device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceActivity {
+    pub address: RawAddress,
+    /// Bytes of attribute values sent to and received from the device by GATT clients.
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+}
+
+/// Radio activity since the stack started. Times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ActivityEnergyInfo {
+    /// Whether the controller has reported its activity. The controller fields are 0 if not.
+    pub controller_reported: bool,
+    /// Controller state at the time of the report: 1 if active, 2 if scanning, 3 if idle.
+    pub controller_state: u32,
+    pub controller_tx_time_ms: u64,
+    pub controller_rx_time_ms: u64,
+    pub controller_idle_time_ms: u64,
+    /// Product of mA, V and ms, as reported by the controller.
+    pub controller_energy_used: u64,
+
+    /// Time spent discovering, and how much of it the radio was listening according to the scan
+    /// window and interval.
+    pub scan_time_ms: u64,
+    pub scan_radio_time_ms: u64,
+
+    /// Time spent advertising by any advertising set, and an estimate of the air time of the
+    /// advertising packets sent in it.
+    pub advertising_time_ms: u64,
+    pub advertising_airtime_ms: u64,
+
+    pub devices: Vec<DeviceActivity>,
+}
+
+struct Scan {
+    started: Instant,
+    duty_cycle: f64,
+}
+
+struct Advertising {
+    started: Instant,
+    /// Air time per advertising interval.
+    airtime_per_event: Duration,
+    interval: Duration,
+}
+
+impl Advertising {
+    fn airtime(&self, duration: Duration) -> Duration {
+        self.airtime_per_event.mul_f64(duration.as_secs_f64() / self.interval.as_secs_f64())
+    }
+}
+
+/// Collects radio activity reported by the parts of the stack that cause it.
+#[derive(Default)]
+pub struct ActivityTracker {
+    devices: HashMap<RawAddress, DeviceActivity>,
+
+    scan: Option<Scan>,
+    scan_time: Duration,
+    scan_radio_time: Duration,
+
+    advertising: HashMap<u8, Advertising>,
+    advertising_time: Duration,
+    advertising_airtime: Duration,
+
+    controller: Option<BtActivityEnergyInfo>,
+}
+
+impl ActivityTracker {
+    pub fn new() -> Self {
+        ActivityTracker::default()
+    }
+
+    pub fn add_tx_bytes(&mut self, address: RawAddress, bytes: usize) {
+        self.device(address).tx_bytes += bytes as u64;
+    }
+
+    pub fn add_rx_bytes(&mut self, address: RawAddress, bytes: usize) {
+        self.device(address).rx_bytes += bytes as u64;
+    }
+
+    fn device(&mut self, address: RawAddress) -> &mut DeviceActivity {
+        self.devices.entry(address).or_insert(DeviceActivity { address, ..Default::default() })
+    }
+
+    /// Starts counting scan time at `now`, with the radio listening for `window` of each
+    /// `interval`, both in slots.
+    pub fn scan_started(&mut self, window: u16, interval: u16, now: Instant) {
+        self.scan_stopped(now);
+        let duty_cycle =
+            if interval == 0 { 1.0 } else { window.min(interval) as f64 / interval as f64 };
+        self.scan = Some(Scan { started: now, duty_cycle });
+    }
+
+    pub fn scan_stopped(&mut self, now: Instant) {
+        if let Some(scan) = self.scan.take() {
+            let duration = now.saturating_duration_since(scan.started);
+            self.scan_time += duration;
+            self.scan_radio_time += duration.mul_f64(scan.duty_cycle);
+        }
+    }
+
+    /// Starts counting the advertising of set `adv_id` at `now`. The set sends `data_len` bytes of
+    /// legacy advertising data on each of its `channels` every `interval` slots.
+    pub fn advertising_started(
+        &mut self,
+        adv_id: u8,
+        interval: u32,
+        channels: u32,
+        data_len: usize,
+        now: Instant,
+    ) {
+        self.advertising_stopped(adv_id, now);
+        let pdu_airtime = BYTE_AIRTIME * (ADV_PDU_OVERHEAD + data_len) as u32;
+        self.advertising.insert(
+            adv_id,
+            Advertising {
+                started: now,
+                airtime_per_event: pdu_airtime * channels,
+                interval: SLOT * interval.max(1),
+            },
+        );
+    }
+
+    pub fn advertising_stopped(&mut self, adv_id: u8, now: Instant) {
+        if let Some(advertising) = self.advertising.remove(&adv_id) {
+            let duration = now.saturating_duration_since(advertising.started);
+            self.advertising_time += duration;
+            self.advertising_airtime += advertising.airtime(duration);
+        }
+    }
+
+    /// Keeps the latest activity reported by the controller. Failed reports are dropped.
+    pub fn controller_activity_received(&mut self, info: BtActivityEnergyInfo) {
+        if info.status == 0 && info.ctrl_state != 0 {
+            self.controller = Some(info);
+        }
+    }
+
+    /// Gets the activity until `now`, including scanning and advertising that is still going on.
+    pub fn get_activity_energy_info(&self, now: Instant) -> ActivityEnergyInfo {
+        let mut info = ActivityEnergyInfo::default();
+
+        if let Some(controller) = self.controller.as_ref() {
+            info.controller_reported = true;
+            info.controller_state = controller.ctrl_state as u32;
+            info.controller_tx_time_ms = controller.tx_time;
+            info.controller_rx_time_ms = controller.rx_time;
+            info.controller_idle_time_ms = controller.idle_time;
+            info.controller_energy_used = controller.energy_used;
+        }
+
+        let (mut scan_time, mut scan_radio_time) = (self.scan_time, self.scan_radio_time);
+        if let Some(scan) = self.scan.as_ref() {
+            let duration = now.saturating_duration_since(scan.started);
+            scan_time += duration;
+            scan_radio_time += duration.mul_f64(scan.duty_cycle);
+        }
+        info.scan_time_ms = scan_time.as_millis() as u64;
+        info.scan_radio_time_ms = scan_radio_time.as_millis() as u64;
+
+        let (mut advertising_time, mut advertising_airtime) =
+            (self.advertising_time, self.advertising_airtime);
+        for advertising in self.advertising.values() {
+            let duration = now.saturating_duration_since(advertising.started);
+            advertising_time += duration;
+            advertising_airtime += advertising.airtime(duration);
+        }
+        info.advertising_time_ms = advertising_time.as_millis() as u64;
+        info.advertising_airtime_ms = advertising_airtime.as_millis() as u64;
+
+        info.devices = self.devices.values().cloned().collect();
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_and_advertising_time() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new();
+
+        tracker.scan_started(18, 72, start);
+        tracker.scan_stopped(start + Duration::from_secs(4));
+        tracker.scan_started(18, 18, start + Duration::from_secs(10));
+
+        // 100 ms interval with 15 bytes of data: 3 channels * 31 bytes * 8 us per 100 ms.
+        tracker.advertising_started(1, 160, 3, 15, start);
+
+        let info = tracker.get_activity_energy_info(start + Duration::from_secs(12));
+        assert_eq!(info.scan_time_ms, 6000);
+        assert_eq!(info.scan_radio_time_ms, 3000);
+        assert_eq!(info.advertising_time_ms, 12000);
+        assert_eq!(info.advertising_airtime_ms, 89);
+        assert!(!info.controller_reported);
+
+        tracker.advertising_stopped(1, start + Duration::from_secs(12));
+        let info = tracker.get_activity_energy_info(start + Duration::from_secs(20));
+        assert_eq!(info.advertising_time_ms, 12000);
+    }
+
+    #[test]
+    fn test_device_traffic() {
+        let address = RawAddress { val: [1, 2, 3, 4, 5, 6] };
+        let mut tracker = ActivityTracker::new();
+        tracker.add_tx_bytes(address, 20);
+        tracker.add_rx_bytes(address, 5);
+        tracker.add_tx_bytes(address, 1);
+
+        let info = tracker.get_activity_energy_info(Instant::now());
+        assert_eq!(info.devices, vec![DeviceActivity { address, tx_bytes: 21, rx_bytes: 5 }]);
+    }
+}
@@ -0,0 +1,282 @@
+//! Resolvable private addresses of the advertising sets (IAdvertisingPrivacy).
+//!
+//! With LE privacy, each advertising set uses its own resolvable private address, which the
+//! controller or the native stack is expected to replace every RPA timeout. Some controllers only
+//! replace it when the set is started, so an advertising set that stays up keeps a trackable
+//! address. Owners of advertising sets add them here: their address is read again every timeout,
+//! a set whose address didn't change is restarted to get a new one, and callbacks are told of
+//! each address a set advertises with, which is mainly of use for debugging.
+
+use bt_topshim::btif::RawAddress;
+use bt_topshim::profiles::gatt::{AdvertiseParameters, GattAdvInbandCallbacks};
+use dbus_macros::dbus_interface;
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::bluetooth::{Bluetooth, IBluetooth};
+use crate::bluetooth_gatt::BluetoothGatt;
+use crate::callbacks::Callbacks;
+use crate::{Message, RPCProxy};
+
+/// RPA timeout of the native stack when none was set with `IBluetooth::set_le_privacy`.
+const DEFAULT_RPA_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Slack given to the controller past the timeout before an unchanged address counts as stale.
+const ROTATION_SLACK: Duration = Duration::from_secs(30);
+
+/// Address type of random addresses in the own address callbacks.
+const ADDRESS_TYPE_RANDOM: u8 = 0x01;
+
+/// Defines the advertising privacy API.
+#[dbus_interface(advertising_privacy_dbus, "org.chromium.bluetooth.AdvertisingPrivacy")]
+pub trait IAdvertisingPrivacy {
+    /// Adds a callback to be told of the addresses the advertising sets use. It is first told of
+    /// the current address of each set.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IAdvertisingPrivacyCallback + Send>) -> u32;
+
+    /// Removes a callback added with `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+}
+
+/// Advertising address events.
+pub trait IAdvertisingPrivacyCallback: RPCProxy {
+    /// The advertising set `advertiser_id` now advertises with `address`.
+    fn on_advertising_address_changed(&self, advertiser_id: i32, address: String);
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum AdvertisingPrivacyActions {
+    /// Time to read the addresses of the sets again.
+    Check,
+}
+
+struct AdvertisingSet {
+    /// Parameters the set was started with, to restart it.
+    params: AdvertiseParameters,
+    address: Option<RawAddress>,
+    /// The address before the last rotation, which peers may still be using.
+    previous_address: Option<RawAddress>,
+    /// When `address` was first read.
+    since: Instant,
+}
+
+/// Whether an address read `age` after it was first seen should have been replaced.
+fn is_stale(address_type: u8, address: &RawAddress, age: Duration, rpa_timeout: Duration) -> bool {
+    // Only resolvable private addresses rotate, and their two most significant bits are 0b01.
+    address_type == ADDRESS_TYPE_RANDOM
+        && address.val[0] >> 6 == 0b01
+        && age >= rpa_timeout + ROTATION_SLACK
+}
+
+/// Implementation of the advertising privacy API.
+pub struct AdvertisingPrivacy {
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    gatt: Option<Arc<Mutex<Box<BluetoothGatt>>>>,
+    callbacks: Callbacks<dyn IAdvertisingPrivacyCallback + Send>,
+    sets: HashMap<u8, AdvertisingSet>,
+    /// RPA timeout, or None if privacy is off or the controller rotates addresses itself.
+    rpa_timeout: Option<Duration>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl AdvertisingPrivacy {
+    pub fn new(tx: Sender<Message>) -> AdvertisingPrivacy {
+        AdvertisingPrivacy {
+            tx: tx.clone(),
+            adapter: None,
+            gatt: None,
+            callbacks: Callbacks::new(tx, Message::AdvertisingPrivacyCallbackDisconnected),
+            sets: HashMap::new(),
+            rpa_timeout: None,
+            timer: None,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub fn set_gatt(&mut self, gatt: Arc<Mutex<Box<BluetoothGatt>>>) {
+        self.gatt = Some(gatt);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    /// Starts following the address of the advertising set `adv_id`, started with `params`.
+    pub(crate) fn add_set(&mut self, adv_id: u8, params: AdvertiseParameters) {
+        self.sets.insert(
+            adv_id,
+            AdvertisingSet { params, address: None, previous_address: None, since: Instant::now() },
+        );
+        self.read_address(adv_id);
+
+        if self.timer.is_none() {
+            let txl = self.tx.clone();
+            self.timer = Some(tokio::spawn(async move {
+                let _ =
+                    txl.send(Message::AdvertisingPrivacy(AdvertisingPrivacyActions::Check)).await;
+            }));
+        }
+    }
+
+    /// Stops following the advertising set `adv_id`, which was unregistered.
+    pub(crate) fn remove_set(&mut self, adv_id: u8) {
+        self.sets.remove(&adv_id);
+        if self.sets.is_empty() {
+            if let Some(timer) = self.timer.take() {
+                timer.abort();
+            }
+        }
+    }
+
+    /// Addresses peers may know the advertising set `adv_id` by, the current one first.
+    pub(crate) fn get_addresses(&self, adv_id: u8) -> Vec<RawAddress> {
+        self.sets
+            .get(&adv_id)
+            .map(|set| set.address.iter().chain(set.previous_address.iter()).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn dispatch_advertising_privacy_actions(&mut self, action: AdvertisingPrivacyActions) {
+        match action {
+            AdvertisingPrivacyActions::Check => self.check(),
+        }
+    }
+
+    pub fn dispatch_advertiser_inband_callbacks(&mut self, cb: GattAdvInbandCallbacks) {
+        if let GattAdvInbandCallbacks::GetAddressCallback(adv_id, address_type, address) = cb {
+            self.address_read(adv_id, address_type, address);
+        }
+    }
+
+    /// Reads the addresses of all sets, and checks again after the RPA timeout.
+    fn check(&mut self) {
+        self.timer = None;
+        if self.sets.is_empty() {
+            return;
+        }
+
+        self.rpa_timeout = self.adapter.as_ref().and_then(|adapter| {
+            let adapter = adapter.lock().unwrap();
+            let features = adapter.get_le_privacy_features();
+            if !features.privacy_enabled || features.rpa_offload_supported {
+                return None;
+            }
+            Some(match adapter.get_le_rpa_timeout() {
+                0 => DEFAULT_RPA_TIMEOUT,
+                timeout => Duration::from_secs(timeout.into()),
+            })
+        });
+        let rpa_timeout = match self.rpa_timeout {
+            Some(rpa_timeout) => rpa_timeout,
+            None => return,
+        };
+
+        let ids: Vec<u8> = self.sets.keys().cloned().collect();
+        for adv_id in ids {
+            self.read_address(adv_id);
+        }
+
+        let txl = self.tx.clone();
+        self.timer = Some(tokio::spawn(async move {
+            time::sleep(rpa_timeout).await;
+            let _ = txl.send(Message::AdvertisingPrivacy(AdvertisingPrivacyActions::Check)).await;
+        }));
+    }
+
+    /// Handles the own address of an advertising set, which comes both as an advertiser callback
+    /// and as an inband one.
+    pub(crate) fn address_read(&mut self, adv_id: u8, address_type: u8, address: RawAddress) {
+        let set = match self.sets.get_mut(&adv_id) {
+            Some(set) => set,
+            None => return,
+        };
+
+        if set.address != Some(address) {
+            set.previous_address = set.address.replace(address);
+            set.since = Instant::now();
+            debug!("Advertising set {} uses {}", adv_id, address.to_string());
+            self.callbacks.for_all_callbacks(|callback| {
+                callback.on_advertising_address_changed(adv_id.into(), address.to_string());
+            });
+            return;
+        }
+
+        let rpa_timeout = match self.rpa_timeout {
+            Some(rpa_timeout) => rpa_timeout,
+            None => return,
+        };
+        if !is_stale(address_type, &address, set.since.elapsed(), rpa_timeout) {
+            return;
+        }
+
+        // Setting the parameters again makes the native stack pick a new address for the set,
+        // which it can only do while the set is disabled.
+        info!("Advertising set {} kept its address past the RPA timeout, restarting it", adv_id);
+        let params = set.params.clone();
+        set.since = Instant::now();
+        if let Some(gatt) = &self.gatt {
+            if let Some(gatt) = gatt.lock().unwrap().get_gatt() {
+                gatt.advertiser.enable(adv_id, false, 0, 0);
+                gatt.advertiser.set_parameters(adv_id, params);
+                gatt.advertiser.enable(adv_id, true, 0, 0);
+                gatt.advertiser.get_own_address(adv_id);
+            }
+        }
+    }
+
+    fn read_address(&self, adv_id: u8) {
+        if let Some(gatt) = &self.gatt {
+            if let Some(gatt) = gatt.lock().unwrap().get_gatt() {
+                gatt.advertiser.get_own_address(adv_id);
+            }
+        }
+    }
+}
+
+impl IAdvertisingPrivacy for AdvertisingPrivacy {
+    fn register_callback(&mut self, callback: Box<dyn IAdvertisingPrivacyCallback + Send>) -> u32 {
+        for (adv_id, set) in self.sets.iter() {
+            if let Some(address) = set.address {
+                callback.on_advertising_address_changed((*adv_id).into(), address.to_string());
+            }
+        }
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stale() {
+        let rpa = RawAddress::from_string("4A:11:22:33:44:55").unwrap();
+        let timeout = Duration::from_secs(60);
+        let late = timeout + ROTATION_SLACK;
+
+        assert!(is_stale(ADDRESS_TYPE_RANDOM, &rpa, late, timeout));
+        assert!(!is_stale(ADDRESS_TYPE_RANDOM, &rpa, timeout, timeout));
+        // Public, static random and non-resolvable addresses never rotate.
+        assert!(!is_stale(0x00, &rpa, late, timeout));
+        let static_random = RawAddress::from_string("CA:11:22:33:44:55").unwrap();
+        assert!(!is_stale(ADDRESS_TYPE_RANDOM, &static_random, late, timeout));
+        let non_resolvable = RawAddress::from_string("0A:11:22:33:44:55").unwrap();
+        assert!(!is_stale(ADDRESS_TYPE_RANDOM, &non_resolvable, late, timeout));
+    }
+}
@@ -0,0 +1,186 @@
+//! Trace of the API calls, for bug reports.
+//!
+//! When turned on, every method call a client makes and every callback the stack sends is
+//! written to a file with its arguments, so that what a client asked for can be reconstructed
+//! from a field report. PINs, passkeys and keys are left out. The file is kept small by moving it
+//! aside once it is full, so the trace holds the latest calls only.
+
+use log::debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const API_TRACE_PATH: &str = "/var/lib/bluetooth/floss_api_trace.log";
+
+/// Size at which the trace is moved to `<path>.old`, replacing the previous one.
+const MAX_API_TRACE_SIZE: u64 = 1024 * 1024;
+
+/// Words that mark an argument holding a secret, e.g. "pin_code" or "link_key".
+const SECRET_ARG_WORDS: [&str; 5] = ["key", "pin", "passkey", "password", "secret"];
+
+/// Which way a traced call went.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiCallKind {
+    /// A client called the stack.
+    Call,
+    /// The stack called a callback of a client.
+    Callback,
+}
+
+/// A call of `interface.method` made by or to the client `peer`, on the object at `path`.
+#[derive(Clone, Debug)]
+pub struct TracedCall {
+    pub kind: ApiCallKind,
+    pub peer: String,
+    pub path: String,
+    pub interface: String,
+    pub method: String,
+    /// Name and value of each argument.
+    pub args: Vec<(String, String)>,
+}
+
+fn is_secret(arg_name: &str) -> bool {
+    arg_name.split('_').any(|word| SECRET_ARG_WORDS.contains(&word))
+}
+
+/// Formats `call`, made `time_ms` milliseconds after the Unix epoch, as a line of the trace.
+fn format_call(time_ms: u128, call: &TracedCall) -> String {
+    let args: Vec<String> = call
+        .args
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_secret(name) { "<redacted>" } else { value };
+            format!("{}={}", name, value)
+        })
+        .collect();
+    let kind = match call.kind {
+        ApiCallKind::Call => "call",
+        ApiCallKind::Callback => "callback",
+    };
+
+    format!(
+        "{}.{:03} {} {} {} {}.{}({})\n",
+        time_ms / 1000,
+        time_ms % 1000,
+        kind,
+        call.peer,
+        call.path,
+        call.interface,
+        call.method,
+        args.join(", ")
+    )
+}
+
+fn old_trace_path(path: &Path) -> PathBuf {
+    let mut old = path.as_os_str().to_owned();
+    old.push(".old");
+    PathBuf::from(old)
+}
+
+/// Writes the trace to its file.
+pub struct ApiTrace {
+    path: PathBuf,
+    max_size: u64,
+    file: Option<File>,
+    size: u64,
+}
+
+impl ApiTrace {
+    pub fn new<P: Into<PathBuf>>(path: P) -> ApiTrace {
+        ApiTrace::with_max_size(path, MAX_API_TRACE_SIZE)
+    }
+
+    fn with_max_size<P: Into<PathBuf>>(path: P, max_size: u64) -> ApiTrace {
+        ApiTrace { path: path.into(), max_size, file: None, size: 0 }
+    }
+
+    /// Adds `call` to the trace. Calls that can't be written are dropped, since the trace must
+    /// not get in the way of the calls themselves.
+    pub fn record(&mut self, call: &TracedCall) {
+        let time_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let line = format_call(time_ms, call);
+        if let Err(e) = self.write(line.as_bytes()) {
+            debug!("Failed to write the API trace: {}", e);
+            self.file = None;
+        }
+    }
+
+    fn write(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.file.is_some() && self.size + line.len() as u64 > self.max_size {
+            self.file = None;
+            fs::rename(&self.path, old_trace_path(&self.path))?;
+        }
+
+        if self.file.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+
+        self.file.as_mut().unwrap().write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Returns the trace written at `path`, oldest calls first, or an empty string if there is none.
+pub fn read_api_trace(path: &Path) -> String {
+    let old = fs::read_to_string(old_trace_path(path)).unwrap_or_default();
+    let current = fs::read_to_string(path).unwrap_or_default();
+    old + &current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(method: &str, args: &[(&str, &str)]) -> TracedCall {
+        TracedCall {
+            kind: ApiCallKind::Call,
+            peer: String::from(":1.42"),
+            path: String::from("/org/chromium/bluetooth/hci0/adapter"),
+            interface: String::from("org.chromium.bluetooth.Bluetooth"),
+            method: String::from(method),
+            args: args.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_format_call_redacts_secrets() {
+        let line = format_call(
+            1_700_000_000_042,
+            &call("SetPin", &[("device", "{\"name\": \"Keyboard\"}"), ("pin_code", "[1, 2]")]),
+        );
+        assert_eq!(
+            line,
+            "1700000000.042 call :1.42 /org/chromium/bluetooth/hci0/adapter \
+             org.chromium.bluetooth.Bluetooth.SetPin(device={\"name\": \"Keyboard\"}, \
+             pin_code=<redacted>)\n"
+        );
+
+        assert!(is_secret("passkey"));
+        assert!(is_secret("link_key"));
+        assert!(!is_secret("keyboard"));
+        assert!(!is_secret("device"));
+    }
+
+    #[test]
+    fn test_trace_rotates() {
+        let dir = std::env::temp_dir().join(format!("floss_api_trace_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("trace.log");
+
+        let mut trace = ApiTrace::with_max_size(&path, 250);
+        for method in ["First", "Second", "Third"] {
+            trace.record(&call(method, &[]));
+        }
+
+        let old = fs::read_to_string(old_trace_path(&path)).unwrap();
+        let dump = read_api_trace(&path);
+        assert!(old.contains(".First()") && old.contains(".Second()"));
+        assert!(dump.starts_with(&old) && dump.ends_with(".Third()\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
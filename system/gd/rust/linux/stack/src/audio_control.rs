@@ -0,0 +1,304 @@
+//! Control socket through which the platform audio server runs the media streams, so that it
+//! doesn't have to link against the daemon or talk D-Bus.
+//!
+//! The audio itself doesn't go through this socket. A2DP PCM is written to the data socket of the
+//! audio HAL and HFP audio goes through the kernel SCO socket; this one only starts and stops the
+//! streams and agrees on their format.
+//!
+//! Each request is a line of ASCII, answered by a line that starts with `ok`, followed by the
+//! result if there is one, or with `error` followed by the reason:
+//!
+//! ```text
+//! start <a2dp|hfp>
+//! stop <a2dp|hfp>
+//! format <a2dp|hfp>                                    -> ok <sample rate> <bits> <channels>
+//! format a2dp <sample rate> <bits> <channels>
+//! ```
+//!
+//! HFP streams are with the active HFP device, and its format follows the negotiated codec.
+
+use bt_topshim::btif::RawAddress;
+use bt_topshim::profiles::a2dp::{
+    A2dpCodecBitsPerSample, A2dpCodecChannelMode, A2dpCodecConfig, A2dpCodecSampleRate,
+};
+use bt_topshim::profiles::hfp::HfpCodecId;
+
+use log::{info, warn};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::bluetooth_media::{AudioCategory, BluetoothMedia, IBluetoothMedia};
+
+/// Path of the control socket, next to the data sockets of the audio HAL.
+pub const AUDIO_CONTROL_PATH: &str = "/var/run/bluetooth/audio/.control";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioStream {
+    A2dp,
+    Hfp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub bits_per_sample: u8,
+    pub channels: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ControlRequest {
+    Start(AudioStream),
+    Stop(AudioStream),
+    GetFormat(AudioStream),
+    SetFormat(AudioStream, AudioFormat),
+}
+
+const SAMPLE_RATES: &[(u32, A2dpCodecSampleRate)] = &[
+    (16000, A2dpCodecSampleRate::RATE_16000),
+    (24000, A2dpCodecSampleRate::RATE_24000),
+    (44100, A2dpCodecSampleRate::RATE_44100),
+    (48000, A2dpCodecSampleRate::RATE_48000),
+    (88200, A2dpCodecSampleRate::RATE_88200),
+    (96000, A2dpCodecSampleRate::RATE_96000),
+    (176400, A2dpCodecSampleRate::RATE_176400),
+    (192000, A2dpCodecSampleRate::RATE_192000),
+];
+
+const BITS_PER_SAMPLE: &[(u8, A2dpCodecBitsPerSample)] = &[
+    (16, A2dpCodecBitsPerSample::SAMPLE_16),
+    (24, A2dpCodecBitsPerSample::SAMPLE_24),
+    (32, A2dpCodecBitsPerSample::SAMPLE_32),
+];
+
+const CHANNELS: &[(u8, A2dpCodecChannelMode)] =
+    &[(1, A2dpCodecChannelMode::MODE_MONO), (2, A2dpCodecChannelMode::MODE_STEREO)];
+
+impl AudioFormat {
+    /// Gets the PCM format the stack expects for an A2DP codec configuration.
+    fn from_a2dp_config(config: &A2dpCodecConfig) -> Option<AudioFormat> {
+        let sample_rate =
+            SAMPLE_RATES.iter().find(|(_, flag)| flag.bits() == config.sample_rate)?.0;
+        let bits_per_sample =
+            BITS_PER_SAMPLE.iter().find(|(_, flag)| flag.bits() == config.bits_per_sample)?.0;
+        let channels = CHANNELS.iter().find(|(_, flag)| flag.bits() == config.channel_mode)?.0;
+        Some(AudioFormat { sample_rate, bits_per_sample, channels })
+    }
+
+    /// Gets the `(sample rate, bits per sample, channel mode)` flags of the A2DP audio config.
+    fn to_a2dp_flags(self) -> Option<(i32, i32, i32)> {
+        let sample_rate = SAMPLE_RATES.iter().find(|(rate, _)| *rate == self.sample_rate)?.1;
+        let bits = BITS_PER_SAMPLE.iter().find(|(bits, _)| *bits == self.bits_per_sample)?.1;
+        let channel_mode = CHANNELS.iter().find(|(channels, _)| *channels == self.channels)?.1;
+        Some((sample_rate.bits(), bits.bits(), channel_mode.bits()))
+    }
+
+    /// Gets the format of the audio of SCO calls with `codec`.
+    fn from_hfp_codec(codec: HfpCodecId) -> Option<AudioFormat> {
+        let sample_rate = match codec {
+            HfpCodecId::Cvsd => 8000,
+            HfpCodecId::Msbc => 16000,
+            HfpCodecId::Lc3Swb => 32000,
+            HfpCodecId::None => return None,
+        };
+        Some(AudioFormat { sample_rate, bits_per_sample: 16, channels: 1 })
+    }
+}
+
+impl ControlRequest {
+    pub fn parse(line: &str) -> Result<ControlRequest, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let stream = match words.get(1) {
+            Some(&"a2dp") => AudioStream::A2dp,
+            Some(&"hfp") => AudioStream::Hfp,
+            Some(other) => return Err(format!("unknown stream '{}'", other)),
+            None => return Err(String::from("missing stream")),
+        };
+
+        match (words[0], &words[2..]) {
+            ("start", []) => Ok(ControlRequest::Start(stream)),
+            ("stop", []) => Ok(ControlRequest::Stop(stream)),
+            ("format", []) => Ok(ControlRequest::GetFormat(stream)),
+            ("format", [sample_rate, bits_per_sample, channels]) => {
+                let format = match (sample_rate.parse(), bits_per_sample.parse(), channels.parse())
+                {
+                    (Ok(sample_rate), Ok(bits_per_sample), Ok(channels)) => {
+                        AudioFormat { sample_rate, bits_per_sample, channels }
+                    }
+                    _ => return Err(String::from("invalid format")),
+                };
+                Ok(ControlRequest::SetFormat(stream, format))
+            }
+            _ => Err(format!("invalid request '{}'", line)),
+        }
+    }
+}
+
+fn format_reply(result: Result<Option<AudioFormat>, String>) -> String {
+    match result {
+        Ok(None) => String::from("ok"),
+        Ok(Some(format)) => {
+            format!("ok {} {} {}", format.sample_rate, format.bits_per_sample, format.channels)
+        }
+        Err(reason) => format!("error {}", reason),
+    }
+}
+
+fn active_device(media: &BluetoothMedia, category: AudioCategory) -> Result<String, String> {
+    match media.get_active_device(category) {
+        device if device.is_empty() => Err(String::from("no active device")),
+        device => Ok(device),
+    }
+}
+
+fn handle_request(
+    media: &mut BluetoothMedia,
+    request: ControlRequest,
+) -> Result<Option<AudioFormat>, String> {
+    if !media.is_initialized() {
+        return Err(String::from("media is not initialized"));
+    }
+
+    match request {
+        ControlRequest::Start(AudioStream::A2dp) => media.start_audio_request(),
+        ControlRequest::Stop(AudioStream::A2dp) => media.stop_audio_request(),
+        ControlRequest::Start(AudioStream::Hfp) => {
+            let device = active_device(media, AudioCategory::Hfp)?;
+            media.start_sco_call(device);
+        }
+        ControlRequest::Stop(AudioStream::Hfp) => {
+            let device = active_device(media, AudioCategory::Hfp)?;
+            media.stop_sco_call(device);
+        }
+        ControlRequest::GetFormat(AudioStream::A2dp) => {
+            let device = active_device(media, AudioCategory::A2dp)?;
+            return RawAddress::from_string(device)
+                .and_then(|addr| media.get_a2dp_codec_config(&addr))
+                .and_then(|config| AudioFormat::from_a2dp_config(&config))
+                .map(Some)
+                .ok_or_else(|| String::from("codec not configured"));
+        }
+        ControlRequest::GetFormat(AudioStream::Hfp) => {
+            let device = active_device(media, AudioCategory::Hfp)?;
+            return AudioFormat::from_hfp_codec(media.get_sco_codec(device))
+                .map(Some)
+                .ok_or_else(|| String::from("codec not negotiated"));
+        }
+        ControlRequest::SetFormat(AudioStream::A2dp, format) => {
+            let (sample_rate, bits_per_sample, channel_mode) =
+                format.to_a2dp_flags().ok_or_else(|| String::from("unsupported format"))?;
+            if !media.set_audio_config(sample_rate, bits_per_sample, channel_mode) {
+                return Err(String::from("format rejected"));
+            }
+        }
+        ControlRequest::SetFormat(AudioStream::Hfp, _) => {
+            return Err(String::from("the HFP format follows the codec"));
+        }
+    }
+    Ok(None)
+}
+
+async fn handle_connection(stream: UnixStream, media: Arc<Mutex<Box<BluetoothMedia>>>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let result = ControlRequest::parse(&line)
+            .and_then(|request| handle_request(&mut media.lock().unwrap(), request));
+        let reply = format_reply(result) + "\n";
+        if writer.write_all(reply.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Listens on `AUDIO_CONTROL_PATH` and serves the audio server for as long as the daemon runs.
+pub async fn serve(media: Arc<Mutex<Box<BluetoothMedia>>>) {
+    // A previous run may have left its socket behind.
+    let _ = fs::remove_file(AUDIO_CONTROL_PATH);
+    let listener = match UnixListener::bind(AUDIO_CONTROL_PATH) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Failed to listen on {}: {}", AUDIO_CONTROL_PATH, e);
+            return;
+        }
+    };
+
+    // The audio server is expected to share a group with the daemon, like for the data sockets.
+    if let Err(e) = fs::set_permissions(AUDIO_CONTROL_PATH, fs::Permissions::from_mode(0o770)) {
+        warn!("Failed to set the permissions of {}: {}", AUDIO_CONTROL_PATH, e);
+    }
+    info!("Audio control listening on {}", AUDIO_CONTROL_PATH);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_connection(stream, media.clone()));
+            }
+            Err(e) => {
+                warn!("Failed to accept an audio control connection: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ControlRequest::parse("start a2dp"),
+            Ok(ControlRequest::Start(AudioStream::A2dp))
+        );
+        assert_eq!(
+            ControlRequest::parse(" stop  hfp "),
+            Ok(ControlRequest::Stop(AudioStream::Hfp))
+        );
+        assert_eq!(
+            ControlRequest::parse("format a2dp 48000 16 2"),
+            Ok(ControlRequest::SetFormat(
+                AudioStream::A2dp,
+                AudioFormat { sample_rate: 48000, bits_per_sample: 16, channels: 2 }
+            ))
+        );
+        assert_eq!(
+            ControlRequest::parse("format hfp"),
+            Ok(ControlRequest::GetFormat(AudioStream::Hfp))
+        );
+
+        assert!(ControlRequest::parse("").is_err());
+        assert!(ControlRequest::parse("start").is_err());
+        assert!(ControlRequest::parse("start sco").is_err());
+        assert!(ControlRequest::parse("start a2dp now").is_err());
+        assert!(ControlRequest::parse("format a2dp 48000 16").is_err());
+        assert!(ControlRequest::parse("format a2dp 48000 16 -1").is_err());
+    }
+
+    #[test]
+    fn test_a2dp_format() {
+        let format = AudioFormat { sample_rate: 44100, bits_per_sample: 24, channels: 2 };
+        let (sample_rate, bits_per_sample, channel_mode) = format.to_a2dp_flags().unwrap();
+        assert_eq!(sample_rate, A2dpCodecSampleRate::RATE_44100.bits());
+
+        let config =
+            A2dpCodecConfig { sample_rate, bits_per_sample, channel_mode, ..Default::default() };
+        assert_eq!(AudioFormat::from_a2dp_config(&config), Some(format));
+
+        assert_eq!(AudioFormat { sample_rate: 8000, ..format }.to_a2dp_flags(), None);
+    }
+
+    #[test]
+    fn test_format_reply() {
+        assert_eq!(format_reply(Ok(None)), "ok");
+        assert_eq!(
+            format_reply(Ok(AudioFormat::from_hfp_codec(HfpCodecId::Msbc))),
+            "ok 16000 16 1"
+        );
+        assert_eq!(format_reply(Err(String::from("no active device"))), "error no active device");
+    }
+}
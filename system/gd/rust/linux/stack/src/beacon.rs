@@ -0,0 +1,219 @@
+//! Decoding of the beacon frames found in advertisements.
+//!
+//! Beacons put everything they have to say in their advertising data, in one of two common
+//! formats: Apple's iBeacon, in manufacturer specific data, and Google's Eddystone, in the service
+//! data of its own service UUID. Eddystone beacons usually rotate between several frame types, so
+//! consecutive advertisements of one beacon carry different frames.
+
+use bt_topshim::btif::Uuid128Bit;
+
+use crate::bluetooth_adv_monitor::ad_structures;
+
+const AD_TYPE_SERVICE_DATA_16: u8 = 0x16;
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xff;
+
+/// Apple's company identifier, and the type and length that start the rest of an iBeacon.
+const IBEACON_PREFIX: [u8; 4] = [0x4c, 0x00, 0x02, 0x15];
+/// Company identifier, type and length, proximity UUID, major, minor and measured power.
+const IBEACON_LEN: usize = 25;
+
+/// Eddystone service UUID, little endian.
+const EDDYSTONE_UUID: [u8; 2] = [0xaa, 0xfe];
+
+const EDDYSTONE_FRAME_UID: u8 = 0x00;
+const EDDYSTONE_FRAME_URL: u8 = 0x10;
+const EDDYSTONE_FRAME_TLM: u8 = 0x20;
+
+/// Frame type, TX power, and a 10 byte namespace with a 6 byte instance. Some beacons leave out
+/// the two reserved bytes that follow.
+const EDDYSTONE_UID_LEN: usize = 18;
+/// Frame type, TX power, URL scheme, and up to 17 bytes of encoded URL.
+const EDDYSTONE_URL_MIN_LEN: usize = 3;
+const EDDYSTONE_URL_MAX_LEN: usize = 20;
+/// Frame type, version, battery voltage, temperature, advertising PDU count and time since boot.
+const EDDYSTONE_TLM_LEN: usize = 14;
+/// Only unencrypted telemetry can be decoded.
+const EDDYSTONE_TLM_VERSION: u8 = 0x00;
+
+const EDDYSTONE_URL_SCHEMES: [&str; 4] = ["http://www.", "https://www.", "http://", "https://"];
+const EDDYSTONE_URL_EXPANSIONS: [&str; 14] = [
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net",
+    ".info", ".biz", ".gov",
+];
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum BeaconType {
+    IBeacon = 0,
+    EddystoneUid,
+    EddystoneUrl,
+    EddystoneTlm,
+}
+
+/// A decoded beacon frame. Only the fields of its type are set, the others are 0 or empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Beacon {
+    pub beacon_type: BeaconType,
+    /// iBeacon proximity UUID, or Eddystone-UID namespace followed by its instance.
+    pub id: Uuid128Bit,
+    /// iBeacon major and minor numbers.
+    pub major: u16,
+    pub minor: u16,
+    /// Calibrated TX power in dBm, as received at 1 m for iBeacon and at 0 m for Eddystone.
+    pub tx_power: i32,
+    /// Eddystone-URL, expanded.
+    pub url: String,
+    /// Eddystone-TLM battery voltage in mV, or 0 if the beacon isn't powered by a battery.
+    pub battery_voltage: u16,
+    /// Eddystone-TLM temperature in 1/256 °C, or -32768 if the beacon has no sensor.
+    pub temperature: i32,
+    /// Eddystone-TLM count of advertising PDUs, and time in 0.1 s, since the beacon booted.
+    pub adv_count: u32,
+    pub uptime: u32,
+}
+
+impl Beacon {
+    fn new(beacon_type: BeaconType) -> Beacon {
+        Beacon {
+            beacon_type,
+            id: [0; 16],
+            major: 0,
+            minor: 0,
+            tx_power: 0,
+            url: String::new(),
+            battery_voltage: 0,
+            temperature: 0,
+            adv_count: 0,
+            uptime: 0,
+        }
+    }
+}
+
+fn parse_ibeacon(data: &[u8]) -> Option<Beacon> {
+    if data.len() != IBEACON_LEN || !data.starts_with(&IBEACON_PREFIX) {
+        return None;
+    }
+
+    let mut beacon = Beacon::new(BeaconType::IBeacon);
+    beacon.id.copy_from_slice(&data[4..20]);
+    beacon.major = u16::from_be_bytes([data[20], data[21]]);
+    beacon.minor = u16::from_be_bytes([data[22], data[23]]);
+    beacon.tx_power = data[24] as i8 as i32;
+    Some(beacon)
+}
+
+/// Expands an Eddystone-URL, or returns None if it uses reserved codes.
+fn expand_url(scheme: u8, encoded: &[u8]) -> Option<String> {
+    let mut url = EDDYSTONE_URL_SCHEMES.get(scheme as usize)?.to_string();
+    for &byte in encoded {
+        match EDDYSTONE_URL_EXPANSIONS.get(byte as usize) {
+            Some(expansion) => url.push_str(expansion),
+            None if (0x21..0x7f).contains(&byte) => url.push(byte as char),
+            None => return None,
+        }
+    }
+    Some(url)
+}
+
+/// Decodes an Eddystone frame from the service data following the Eddystone UUID.
+fn parse_eddystone(frame: &[u8]) -> Option<Beacon> {
+    match *frame.first()? {
+        EDDYSTONE_FRAME_UID if frame.len() >= EDDYSTONE_UID_LEN => {
+            let mut beacon = Beacon::new(BeaconType::EddystoneUid);
+            beacon.tx_power = frame[1] as i8 as i32;
+            beacon.id.copy_from_slice(&frame[2..18]);
+            Some(beacon)
+        }
+        EDDYSTONE_FRAME_URL
+            if (EDDYSTONE_URL_MIN_LEN..=EDDYSTONE_URL_MAX_LEN).contains(&frame.len()) =>
+        {
+            let mut beacon = Beacon::new(BeaconType::EddystoneUrl);
+            beacon.tx_power = frame[1] as i8 as i32;
+            beacon.url = expand_url(frame[2], &frame[3..])?;
+            Some(beacon)
+        }
+        EDDYSTONE_FRAME_TLM
+            if frame.len() == EDDYSTONE_TLM_LEN && frame[1] == EDDYSTONE_TLM_VERSION =>
+        {
+            let mut beacon = Beacon::new(BeaconType::EddystoneTlm);
+            beacon.battery_voltage = u16::from_be_bytes([frame[2], frame[3]]);
+            beacon.temperature = i16::from_be_bytes([frame[4], frame[5]]) as i32;
+            beacon.adv_count = u32::from_be_bytes([frame[6], frame[7], frame[8], frame[9]]);
+            beacon.uptime = u32::from_be_bytes([frame[10], frame[11], frame[12], frame[13]]);
+            Some(beacon)
+        }
+        _ => None,
+    }
+}
+
+/// Decodes the beacon frames in `adv_data`. Malformed frames are left out.
+pub(crate) fn parse_beacons(adv_data: &[u8]) -> Vec<Beacon> {
+    ad_structures(adv_data)
+        .into_iter()
+        .filter_map(|(ad_type, data)| match ad_type {
+            AD_TYPE_MANUFACTURER_DATA => parse_ibeacon(data),
+            AD_TYPE_SERVICE_DATA_16 if data.starts_with(&EDDYSTONE_UUID) => {
+                parse_eddystone(&data[2..])
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ibeacon() {
+        let mut adv_data = vec![0x02, 0x01, 0x06, 0x1a, 0xff];
+        adv_data.extend_from_slice(&IBEACON_PREFIX);
+        adv_data.extend((0..16).map(|i| i as u8));
+        adv_data.extend_from_slice(&[0x00, 0x01, 0x12, 0x34, 0xc5]);
+
+        let beacons = parse_beacons(&adv_data);
+        assert_eq!(beacons.len(), 1);
+        assert_eq!(beacons[0].beacon_type, BeaconType::IBeacon);
+        assert_eq!(beacons[0].id[15], 15);
+        assert_eq!((beacons[0].major, beacons[0].minor), (1, 0x1234));
+        assert_eq!(beacons[0].tx_power, -59);
+
+        // Truncated, or another company's data.
+        assert!(parse_beacons(&[0x05, 0xff, 0x4c, 0x00, 0x02, 0x15]).is_empty());
+        adv_data[5] = 0x4d;
+        assert!(parse_beacons(&adv_data).is_empty());
+    }
+
+    #[test]
+    fn test_parse_eddystone() {
+        let mut uid = vec![0x15, 0x16, 0xaa, 0xfe, 0x00, 0xee];
+        uid.extend([0xab; 10].iter().chain([0xcd; 6].iter()));
+        let beacons = parse_beacons(&uid);
+        assert_eq!(beacons[0].beacon_type, BeaconType::EddystoneUid);
+        assert_eq!(beacons[0].tx_power, -18);
+        assert_eq!((beacons[0].id[9], beacons[0].id[10]), (0xab, 0xcd));
+
+        let url = [0x0c, 0x16, 0xaa, 0xfe, 0x10, 0xeb, 0x03, b'g', b'o', b'o', b'.', b'g', b'l'];
+        assert_eq!(parse_beacons(&url)[0].url, "https://goo.gl");
+        let url = [0x0a, 0x16, 0xaa, 0xfe, 0x10, 0xeb, 0x00, b'a', b'b', b'c', 0x07];
+        assert_eq!(parse_beacons(&url)[0].url, "http://www.abc.com");
+        // Reserved scheme, or reserved expansion code.
+        assert!(parse_beacons(&[0x06, 0x16, 0xaa, 0xfe, 0x10, 0xeb, 0x04]).is_empty());
+        assert!(parse_beacons(&[0x07, 0x16, 0xaa, 0xfe, 0x10, 0xeb, 0x00, 0x0e]).is_empty());
+
+        let tlm = [
+            0x11, 0x16, 0xaa, 0xfe, 0x20, 0x00, 0x0b, 0xb8, 0x17, 0x80, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0x02, 0x00,
+        ];
+        let beacons = parse_beacons(&tlm);
+        assert_eq!(beacons[0].beacon_type, BeaconType::EddystoneTlm);
+        assert_eq!(beacons[0].battery_voltage, 3000);
+        assert_eq!(beacons[0].temperature, 0x1780);
+        assert_eq!((beacons[0].adv_count, beacons[0].uptime), (256, 512));
+
+        // Encrypted telemetry.
+        let mut etlm = tlm;
+        etlm[5] = 0x01;
+        assert!(parse_beacons(&etlm).is_empty());
+    }
+}
@@ -1,21 +1,26 @@
 //! Anything related to the adapter API (IBluetooth).
 
 use bt_topshim::btif::{
-    BaseCallbacks, BaseCallbacksDispatcher, BluetoothInterface, BluetoothProperty, BtAclState,
-    BtBondState, BtDeviceType, BtDiscoveryState, BtHciErrorCode, BtPinCode, BtPropertyType,
-    BtScanMode, BtSspVariant, BtState, BtStatus, BtTransport, RawAddress, Uuid, Uuid128Bit,
+    BaseCallbacks, BaseCallbacksDispatcher, BluetoothInterface, BluetoothProperty, BtAclRole,
+    BtAclState, BtActivityEnergyInfo, BtAddrType, BtBondState, BtDeviceType, BtDiscoveryState,
+    BtHciErrorCode, BtPinCode, BtPropertyType, BtScanMode, BtSspVariant, BtState, BtStatus,
+    BtTransport, RawAddress, Uuid, Uuid128Bit,
 };
 use bt_topshim::{
-    profiles::hid_host::{HHCallbacksDispatcher, HidHost},
+    controller::Controller,
+    crypto,
+    profiles::hid_host::{BthhConnectionState, HHCallbacks, HHCallbacksDispatcher, HidHost},
     profiles::sdp::{BtSdpRecord, Sdp, SdpCallbacks, SdpCallbacksDispatcher},
     topstack,
 };
 
 use btif_macros::{btif_callback, btif_callbacks_dispatcher};
 
-use log::{debug, warn};
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
 use num_traits::cast::ToPrimitive;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -24,178 +29,964 @@ use tokio::sync::mpsc::Sender;
 use tokio::task::JoinHandle;
 use tokio::time;
 
+use crate::activity::{ActivityEnergyInfo, ActivityTracker};
+use crate::api_trace;
+use crate::bluetooth_adv_monitor::AdvMonitorActions;
+use crate::bluetooth_fast_pair::FastPairActions;
+use crate::bluetooth_gatt::{GattActions, ScanType};
 use crate::bluetooth_media::{BluetoothMedia, IBluetoothMedia, MediaActions};
+use crate::bluetooth_opp::OppActions;
+use crate::bluetooth_reconnect::ReconnectActions;
+use crate::callbacks::Callbacks;
+use crate::coex::CoexActions;
+use crate::device_store::{self, DeviceStore, StoredDevice};
+use crate::device_type::DeviceType;
+use crate::interop::{InteropDatabase, InteropFeature};
+use crate::link_users::LinkUsers;
+use crate::radio_arbiter::{RadioActivity, RadioArbiter, RadioGrant, RadioUser, ScanDuty};
+use crate::rate_limiter::RateLimiter;
+use crate::runtime_config::RuntimeConfig;
 use crate::uuid::{Profile, UuidHelper};
-use crate::{BluetoothCallbackType, Message, RPCProxy};
+use crate::vendor::{self, VendorCommand};
+use crate::{queue_message, BluetoothCallbackType, Message, RPCProxy};
 
 const DEFAULT_DISCOVERY_TIMEOUT_MS: u64 = 12800;
-const MIN_ADV_INSTANCES_FOR_MULTI_ADV: u8 = 5;
+pub(crate) const MIN_ADV_INSTANCES_FOR_MULTI_ADV: u8 = 5;
+
+/// Number of ACL links the stack can hold at once, i.e. MAX_L2CAP_LINKS in bt_target.h.
+const MAX_ACL_CONNECTIONS: u32 = 16;
+
+/// How long to hold off SDP for devices with the `DelaySdp` interop workaround.
+const INTEROP_SDP_DELAY: Duration = Duration::from_millis(1000);
+
+/// Length of the PINs generated for keyboards, unless they ask for a 16-digit one.
+const KEYBOARD_PIN_LENGTH: usize = 6;
+
+/// Random bytes below this are taken as PIN digits, the largest multiple of 10 a byte holds.
+/// Bytes at or above it would make the low digits more likely, so they are drawn again.
+const PIN_DIGIT_BYTE_LIMIT: u8 = 250;
+
+// Class of device bits telling keyboards apart (Assigned Numbers, 2.8.2).
+const COD_MAJOR_CLASS_MASK: u32 = 0x1f00;
+const COD_MAJOR_CLASS_PERIPHERAL: u32 = 0x0500;
+const COD_MINOR_CLASS_KEYBOARD: u32 = 0x40;
+
+/// HCI error a bond fails with when the native stack refuses to pair, which in Secure Connections
+/// Only mode is what happens to devices that don't support Secure Connections.
+const HCI_ERR_PAIRING_NOT_ALLOWED: i32 = 0x18;
+
+// HCI errors of a device that couldn't prove it holds the link key of a bond.
+const HCI_ERR_AUTH_FAILURE: BtHciErrorCode = 0x05;
+const HCI_ERR_KEY_MISSING: BtHciErrorCode = 0x06;
+
+/// Time a newly bonded device gets to connect its own profiles before we connect the others, so
+/// that both sides don't race to connect the same profiles.
+const CONNECT_AFTER_BOND_DELAY: Duration = Duration::from_secs(2);
+
+/// Time between connecting two profiles of a device.
+const PROFILE_CONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time the profiles of a device get to connect before the ones that didn't are tried again.
+const PROFILE_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of times each profile is tried by `connect_all_enabled_profiles`.
+const PROFILE_CONNECT_ATTEMPTS: u32 = 3;
+
+/// Profiles that `connect_all_enabled_profiles` connects, in order.
+const CONNECTABLE_PROFILES: [Profile; 5] =
+    [Profile::Hfp, Profile::A2dpSink, Profile::HearingAid, Profile::Hid, Profile::Hogp];
 
 /// Devices that were last seen longer than this duration are considered stale
 /// if they haven't already bonded or connected. Once this duration expires, the
 /// clear event should be sent to clients.
 const FOUND_DEVICE_FRESHNESS: Duration = Duration::from_secs(30);
 
+/// Most devices kept in the found devices cache. Past this the least recently seen device that
+/// isn't connected is cleared, so a long LE observation doesn't grow the cache without bound.
+const MAX_FOUND_DEVICES: usize = 512;
+
+/// Range of the time a discovery session waits for a device before reporting it lost.
+const MIN_DEVICE_LOST_TIMEOUT_MS: u32 = 1000;
+const MAX_DEVICE_LOST_TIMEOUT_MS: u32 = 600_000;
+
+/// How often devices are checked for being lost while discovery sessions are running.
+const DEVICE_LOST_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of device found callbacks sent for each device per second, unless changed through
+/// `IBluetooth::set_device_found_rate_limit`.
+const DEFAULT_DEVICE_FOUND_RATE_LIMIT: u32 = 2;
+const DEVICE_FOUND_RATE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Least time between remote name requests. Each one pages the device if there is no link, which
+/// holds up inquiry and other pages meanwhile.
+const NAME_REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Most devices found without a name that wait for a name request.
+const MAX_QUEUED_NAME_REQUESTS: usize = 32;
+
+/// RSSIs kept per device for `get_remote_device_info`.
+const RSSI_HISTORY_LEN: usize = 10;
+
+/// Longest RPA timeout the controller accepts, in seconds (LE Set Resolvable Private Address
+/// Timeout).
+pub(crate) const MAX_RPA_TIMEOUT: u32 = 0xA1B8;
+
+// Floss config keys of the LE address policy, applied each time the adapter is enabled.
+const KEY_LE_PRIVACY: &str = "LePrivacy";
+const KEY_LE_RPA_TIMEOUT: &str = "LeRpaTimeout";
+const KEY_LE_STATIC_ADDRESS: &str = "LeStaticAddress";
+
+// Floss config key of the Secure Connections Only policy, applied each time the adapter is on.
+const KEY_SECURE_CONNECTIONS_ONLY: &str = "SecureConnectionsOnly";
+
+// Floss config key of the policy dropping connections from devices that aren't bonded.
+const KEY_BONDED_ONLY_CONNECTIONS: &str = "BondedOnlyConnections";
+
+// Floss config key of the minimum encryption key size, applied each time the adapter is on.
+const KEY_MIN_ENCRYPTION_KEY_SIZE: &str = "MinEncryptionKeySize";
+
+/// Shortest encryption key the native stack accepts, in octets, and the default minimum. Shorter
+/// keys are what KNOB-style attacks downgrade links to.
+pub const MIN_ENCRYPTION_KEY_SIZE: u8 = 7;
+
+/// Longest possible encryption key, in octets.
+pub const MAX_ENCRYPTION_KEY_SIZE: u8 = 16;
+
 /// Defines the adapter API.
+///
+/// Methods that start an operation return a [`BtStatus`] as soon as the request is accepted or
+/// rejected, and accepted operations report their outcome through callbacks. `NotReady` means the
+/// adapter or its profiles aren't up yet, `Busy` that a conflicting operation is in progress and
+/// `InvalidParam` that the device isn't in a state the request applies to, e.g. not bonded.
+#[dbus_interface(bluetooth_dbus, "org.chromium.bluetooth.Bluetooth")]
 pub trait IBluetooth {
-    /// Adds a callback from a client who wishes to observe adapter events.
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>);
+    /// Adds a callback from a client who wishes to observe adapter events. Returns the ID to
+    /// unregister it with.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) -> u32;
+
+    /// Removes a callback added by `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
 
     /// Adds a callback from a client who wishes to observe connection events.
+    #[dbus_method("RegisterConnectionCallback")]
     fn register_connection_callback(
         &mut self,
         callback: Box<dyn IBluetoothConnectionCallback + Send>,
     ) -> u32;
 
     /// Removes registered callback.
+    #[dbus_method("UnregisterConnectionCallback")]
     fn unregister_connection_callback(&mut self, callback_id: u32) -> bool;
 
+    /// Sends the callback added by `register_callback` the events that lead to the current state
+    /// of the adapter, for clients that register once it is already up: the adapter state and, if
+    /// it is on, its address, name and discoverability, whether it is discovering, a bond state
+    /// change for every bonded device and the number of connected devices. Returns false if there
+    /// is no such callback.
+    #[dbus_method("ReplayAdapterState")]
+    fn replay_adapter_state(&self, callback_id: u32) -> bool;
+
+    /// Sends the callback added by `register_connection_callback` a connection for every connected
+    /// device and a state change for every profile that isn't disconnected. Returns false if
+    /// there is no such callback.
+    #[dbus_method("ReplayConnectionState")]
+    fn replay_connection_state(&self, callback_id: u32) -> bool;
+
+    /// Adds a callback from a client who wishes to observe security events, e.g. to raise alerts.
+    #[dbus_method("RegisterSecurityCallback")]
+    fn register_security_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothSecurityCallback + Send>,
+    ) -> u32;
+
+    /// Removes a callback added by `register_security_callback`.
+    #[dbus_method("UnregisterSecurityCallback")]
+    fn unregister_security_callback(&mut self, callback_id: u32) -> bool;
+
     /// Enables the adapter.
     ///
-    /// Returns true if the request is accepted.
+    /// Returns true if the request is accepted. Enabling an adapter that is already on or turning
+    /// on is accepted without doing anything, while enabling one that is turning off or recovering
+    /// is rejected.
     fn enable(&mut self) -> bool;
 
     /// Disables the adapter.
     ///
-    /// Returns true if the request is accepted.
+    /// Returns true if the request is accepted. Disabling an adapter that is recovering is
+    /// rejected.
     fn disable(&mut self) -> bool;
 
+    /// Returns where the adapter is in its lifecycle. Commands that need the adapter, such as
+    /// discovery or bonding, are rejected unless it is [`AdapterState::On`].
+    #[dbus_method("GetAdapterState")]
+    fn get_adapter_state(&self) -> AdapterState;
+
     /// Returns the Bluetooth address of the local adapter.
+    #[dbus_method("GetAddress")]
     fn get_address(&self) -> String;
 
     /// Gets supported UUIDs by the local adapter.
+    #[dbus_method("GetUuids")]
     fn get_uuids(&self) -> Vec<Uuid128Bit>;
 
     /// Gets the local adapter name.
+    #[dbus_method("GetName")]
     fn get_name(&self) -> String;
 
     /// Sets the local adapter name.
-    fn set_name(&self, name: String) -> bool;
+    #[dbus_method("SetName")]
+    fn set_name(&self, name: String) -> BtStatus;
 
     /// Gets the bluetooth class.
+    #[dbus_method("GetBluetoothClass")]
     fn get_bluetooth_class(&self) -> u32;
 
     /// Sets the bluetooth class.
-    fn set_bluetooth_class(&self, cod: u32) -> bool;
+    #[dbus_method("SetBluetoothClass")]
+    fn set_bluetooth_class(&self, cod: u32) -> BtStatus;
 
     /// Returns whether the adapter is discoverable.
+    #[dbus_method("GetDiscoverable")]
     fn get_discoverable(&self) -> bool;
 
     /// Returns the adapter discoverable timeout.
+    #[dbus_method("GetDiscoverableTimeout")]
     fn get_discoverable_timeout(&self) -> u32;
 
     /// Sets discoverability. If discoverable, limits the duration with given value.
-    fn set_discoverable(&self, mode: bool, duration: u32) -> bool;
+    #[dbus_method("SetDiscoverable")]
+    fn set_discoverable(&self, mode: bool, duration: u32) -> BtStatus;
 
     /// Returns whether multi-advertisement is supported.
     /// A minimum number of 5 advertising instances is required for multi-advertisment support.
+    #[dbus_method("IsMultiAdvertisementSupported")]
     fn is_multi_advertisement_supported(&self) -> bool;
 
     /// Returns whether LE extended advertising is supported.
+    #[dbus_method("IsLeExtendedAdvertisingSupported")]
     fn is_le_extended_advertising_supported(&self) -> bool;
 
+    /// Returns what the local controller supports. Everything is reported as unsupported until
+    /// the adapter is on.
+    #[dbus_method("GetAdapterCapabilities")]
+    fn get_adapter_capabilities(&self) -> AdapterCapabilities;
+
+    /// Returns the current value of every property in `AdapterProperties`.
+    #[dbus_method("GetAdapterProperties")]
+    fn get_adapter_properties(&self) -> AdapterProperties;
+
     /// Starts BREDR Inquiry.
-    fn start_discovery(&self) -> bool;
+    #[dbus_method("StartDiscovery")]
+    fn start_discovery(&self) -> BtStatus;
 
     /// Cancels BREDR Inquiry.
-    fn cancel_discovery(&self) -> bool;
+    #[dbus_method("CancelDiscovery")]
+    fn cancel_discovery(&self) -> BtStatus;
 
     /// Checks if discovery is started.
+    #[dbus_method("IsDiscovering")]
     fn is_discovering(&self) -> bool;
 
     /// Checks when discovery ends in milliseconds from now.
+    #[dbus_method("GetDiscoveryEndMillis")]
     fn get_discovery_end_millis(&self) -> u64;
 
+    /// Starts a discovery session, which keeps discovery running until the session is stopped
+    /// and tells `callback` when a device is found and when it hasn't been seen for
+    /// `lost_timeout_ms`. Returns the session ID, or 0 if the adapter isn't on or
+    /// `lost_timeout_ms` isn't between 1 second and 10 minutes.
+    #[dbus_method("StartDiscoverySession")]
+    fn start_discovery_session(
+        &mut self,
+        callback: Box<dyn IBluetoothDiscoverySessionCallback + Send>,
+        lost_timeout_ms: u32,
+    ) -> u32;
+
+    /// Stops a session started by `start_discovery_session`. Discovery is cancelled once the
+    /// last session stops. Returns false if there was no such session.
+    #[dbus_method("StopDiscoverySession")]
+    fn stop_discovery_session(&mut self, session_id: u32) -> bool;
+
+    /// Limits how many times per second `on_device_found` is sent for the same device, 0 for no
+    /// limit. Updates over the limit are folded into one callback with the latest properties,
+    /// sent once the second is over.
+    #[dbus_method("SetDeviceFoundRateLimit")]
+    fn set_device_found_rate_limit(&mut self, limit: u32) -> bool;
+
+    /// Returns the current limit set by `set_device_found_rate_limit`.
+    #[dbus_method("GetDeviceFoundRateLimit")]
+    fn get_device_found_rate_limit(&self) -> u32;
+
+    /// Sets the inquiry scan run while the adapter is discoverable, to trade how quickly it is
+    /// found against power. Returns `InvalidParam` if the parameters are outside the ranges of
+    /// the spec. The parameters apply right away if the adapter is on, and again each time it is
+    /// enabled until the daemon exits.
+    #[dbus_method("SetInquiryScanParams")]
+    fn set_inquiry_scan_params(&mut self, params: ClassicScanParams) -> BtStatus;
+
+    /// Returns the inquiry scan parameters, the stack defaults unless they were set.
+    #[dbus_method("GetInquiryScanParams")]
+    fn get_inquiry_scan_params(&self) -> ClassicScanParams;
+
+    /// Sets the page scan run while the adapter is connectable, i.e. how quickly peers can
+    /// connect to it. Applied the same way as `set_inquiry_scan_params`.
+    #[dbus_method("SetPageScanParams")]
+    fn set_page_scan_params(&mut self, params: ClassicScanParams) -> BtStatus;
+
+    /// Returns the page scan parameters, the stack defaults unless they were set.
+    #[dbus_method("GetPageScanParams")]
+    fn get_page_scan_params(&self) -> ClassicScanParams;
+
+    /// Sets the LE scan run during discovery. Takes effect from the next discovery on.
+    #[dbus_method("SetLeScanParams")]
+    fn set_le_scan_params(&mut self, params: LeScanParams) -> BtStatus;
+
+    /// Returns the LE discovery scan parameters, the stack defaults unless they were set.
+    #[dbus_method("GetLeScanParams")]
+    fn get_le_scan_params(&self) -> LeScanParams;
+
+    /// Returns what the controller supports for LE privacy. Everything is reported as
+    /// unsupported until the adapter is on.
+    #[dbus_method("GetLePrivacyFeatures")]
+    fn get_le_privacy_features(&self) -> LePrivacyFeatures;
+
+    /// Uses `address`, a static random address, on LE instead of the public or a private one.
+    /// Returns `InvalidParam` if it isn't a static random address.
+    ///
+    /// The native stack can't change its LE address policy once it's running, so like
+    /// `set_le_privacy` this is saved and takes effect the next time the adapter is enabled.
+    #[dbus_method("SetLeStaticAddress")]
+    fn set_le_static_address(&mut self, address: String) -> BtStatus;
+
+    /// Enables LE privacy with the resolvable private address rotated every `rpa_timeout`
+    /// seconds, 0 for the default of 7 to 15 minutes, or disables it and uses the public
+    /// address. Saved and applied from the next time the adapter is enabled.
+    #[dbus_method("SetLePrivacy")]
+    fn set_le_privacy(&mut self, enabled: bool, rpa_timeout: u32) -> BtStatus;
+
+    /// Returns the type of the address the adapter currently uses on LE, `Unknown` while it is
+    /// off.
+    #[dbus_method("GetLeOwnAddressType")]
+    fn get_le_own_address_type(&self) -> BtAddrType;
+
+    /// Requires Secure Connections, on BR/EDR and LE, for all new pairings. Legacy pairing
+    /// attempts are then rejected and reported with `IBluetoothCallback::on_pairing_rejected`.
+    /// Existing bonds are kept. Returns `Unsupported` if the adapter is on and its controller
+    /// doesn't support Secure Connections.
+    ///
+    /// The policy is saved and applied again each time the adapter is enabled.
+    #[dbus_method("SetSecureConnectionsOnly")]
+    fn set_secure_connections_only(&mut self, enabled: bool) -> BtStatus;
+
+    /// Returns whether new pairings are required to use Secure Connections.
+    #[dbus_method("GetSecureConnectionsOnly")]
+    fn get_secure_connections_only(&self) -> bool;
+
+    /// Only lets devices that are bonded connect, for deployments that mustn't talk to arbitrary
+    /// peers. A link that comes up from any other device is dropped before its profiles connect,
+    /// and reported with `IBluetoothSecurityCallback::on_unbonded_connection_rejected`. Links
+    /// this host asks for, to bond with a device or for a GATT client, are kept.
+    ///
+    /// The native stack accepts the link before the adapter can tell who it is from, so the
+    /// device still sees a short connection. The policy is kept across restarts.
+    #[dbus_method("SetBondedOnlyConnections")]
+    fn set_bonded_only_connections(&mut self, enabled: bool) -> BtStatus;
+
+    /// Returns whether connections from devices that aren't bonded are dropped.
+    #[dbus_method("GetBondedOnlyConnections")]
+    fn get_bonded_only_connections(&self) -> bool;
+
+    /// Sets the shortest encryption key, in octets, accepted when a BR/EDR link is encrypted or an
+    /// LE device pairs. Links with shorter keys are disconnected and LE pairings failed, and both
+    /// are reported with `IBluetoothCallback::on_encryption_key_size_rejected`. Returns
+    /// `InvalidParam` unless `key_size` is between 7 and 16.
+    ///
+    /// The minimum is saved and applied again each time the adapter is enabled.
+    #[dbus_method("SetMinEncryptionKeySize")]
+    fn set_min_encryption_key_size(&mut self, key_size: u8) -> BtStatus;
+
+    /// Returns the shortest encryption key accepted, in octets.
+    #[dbus_method("GetMinEncryptionKeySize")]
+    fn get_min_encryption_key_size(&self) -> u8;
+
+    /// Returns the size of the key encrypting the link to a device on `transport`, in octets, or
+    /// 0 if the link isn't up or isn't encrypted.
+    #[dbus_method("GetEncryptionKeySize")]
+    fn get_encryption_key_size(&self, device: BluetoothDevice, transport: BtTransport) -> u8;
+
     /// Initiates pairing to a remote device. Triggers connection if not already started.
-    fn create_bond(&self, device: BluetoothDevice, transport: BtTransport) -> bool;
+    #[dbus_method("CreateBond")]
+    fn create_bond(&self, device: BluetoothDevice, transport: BtTransport) -> BtStatus;
 
     /// Cancels any pending bond attempt on given device.
-    fn cancel_bond_process(&self, device: BluetoothDevice) -> bool;
+    #[dbus_method("CancelBondProcess")]
+    fn cancel_bond_process(&self, device: BluetoothDevice) -> BtStatus;
 
     /// Removes pairing for given device.
-    fn remove_bond(&self, device: BluetoothDevice) -> bool;
+    #[dbus_method("RemoveBond")]
+    fn remove_bond(&self, device: BluetoothDevice) -> BtStatus;
 
     /// Returns a list of known bonded devices.
+    #[dbus_method("GetBondedDevices")]
     fn get_bonded_devices(&self) -> Vec<BluetoothDevice>;
 
     /// Gets the bond state of a single device.
+    #[dbus_method("GetBondState")]
     fn get_bond_state(&self, device: BluetoothDevice) -> u32;
 
     /// Set pin on bonding device.
-    fn set_pin(&self, device: BluetoothDevice, accept: bool, pin_code: Vec<u8>) -> bool;
+    #[dbus_method("SetPin")]
+    fn set_pin(&self, device: BluetoothDevice, accept: bool, pin_code: Vec<u8>) -> BtStatus;
 
     /// Set passkey on bonding device.
-    fn set_passkey(&self, device: BluetoothDevice, accept: bool, passkey: Vec<u8>) -> bool;
+    #[dbus_method("SetPasskey")]
+    fn set_passkey(&self, device: BluetoothDevice, accept: bool, passkey: Vec<u8>) -> BtStatus;
 
     /// Confirm that a pairing should be completed on a bonding device.
-    fn set_pairing_confirmation(&self, device: BluetoothDevice, accept: bool) -> bool;
+    #[dbus_method("SetPairingConfirmation")]
+    fn set_pairing_confirmation(&self, device: BluetoothDevice, accept: bool) -> BtStatus;
 
     /// Gets the name of the remote device.
+    #[dbus_method("GetRemoteName")]
     fn get_remote_name(&self, device: BluetoothDevice) -> String;
 
+    /// Asks a BR/EDR device for its name, e.g. one that discovery found without. The adapter
+    /// already does so for such devices, one at a time, so the request waits for the one in
+    /// progress. The outcome is reported with `IBluetoothCallback::on_remote_name_fetched`.
+    ///
+    /// Returns false if the adapter is not on or the device is LE only.
+    #[dbus_method("FetchRemoteName")]
+    fn fetch_remote_name(&mut self, device: BluetoothDevice) -> bool;
+
     /// Gets the type of the remote device.
+    #[dbus_method("GetRemoteType")]
     fn get_remote_type(&self, device: BluetoothDevice) -> BtDeviceType;
 
     /// Gets the alias of the remote device.
+    #[dbus_method("GetRemoteAlias")]
     fn get_remote_alias(&self, device: BluetoothDevice) -> String;
 
     /// Sets the alias of the remote device.
-    fn set_remote_alias(&mut self, device: BluetoothDevice, new_alias: String);
+    #[dbus_method("SetRemoteAlias")]
+    fn set_remote_alias(&mut self, device: BluetoothDevice, new_alias: String) -> BtStatus;
 
     /// Gets the class of the remote device.
+    #[dbus_method("GetRemoteClass")]
     fn get_remote_class(&self, device: BluetoothDevice) -> u32;
 
-    /// Gets the connection state of a single device.
+    /// Returns the versions the remote device reported when it connected.
+    #[dbus_method("GetRemoteVersionInfo")]
+    fn get_remote_version_info(&self, device: BluetoothDevice) -> RemoteVersionInfo;
+
+    /// Returns the features the remote device reported on each transport it is connected on.
+    #[dbus_method("GetRemoteSupportedFeatures")]
+    fn get_remote_supported_features(&self, device: BluetoothDevice) -> RemoteFeatures;
+
+    /// Returns everything the adapter has cached about a device: its properties, bond and
+    /// connection state, and the RSSIs it was last seen with.
+    #[dbus_method("GetRemoteDeviceInfo")]
+    fn get_remote_device_info(&self, device: BluetoothDevice) -> RemoteDeviceInfo;
+
+    /// Returns the devices that currently have an ACL connection to the adapter.
+    #[dbus_method("GetConnectedDevices")]
+    fn get_connected_devices(&self) -> Vec<BluetoothDevice>;
+
+    /// Gets the connection state of a single device. Returns 0 if the device isn't connected;
+    /// otherwise the value is non-zero and has the encryption state of the link in its upper bits.
+    #[dbus_method("GetConnectionState")]
     fn get_connection_state(&self, device: BluetoothDevice) -> u32;
 
     /// Gets the connection state of a specific profile.
+    #[dbus_method("GetProfileConnectionState")]
     fn get_profile_connection_state(&self, profile: Profile) -> u32;
 
     /// Returns the cached UUIDs of a remote device.
+    #[dbus_method("GetRemoteUuids")]
     fn get_remote_uuids(&self, device: BluetoothDevice) -> Vec<Uuid128Bit>;
 
     /// Triggers SDP to get UUIDs of a remote device.
-    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> bool;
+    #[dbus_method("FetchRemoteUuids")]
+    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> BtStatus;
 
     /// Triggers SDP and searches for a specific UUID on a remote device.
-    fn sdp_search(&self, device: BluetoothDevice, uuid: Uuid128Bit) -> bool;
+    #[dbus_method("SdpSearch")]
+    fn sdp_search(&self, device: BluetoothDevice, uuid: Uuid128Bit) -> BtStatus;
 
     /// Connect all profiles supported by device and enabled on adapter.
-    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool;
+    ///
+    /// The profiles are connected one after the other and retried a few times, waiting for SDP
+    /// first if the UUIDs of the device aren't known yet. Once done, the outcome for each profile
+    /// is reported by `IBluetoothConnectionCallback::on_connect_all_complete`.
+    #[dbus_method("ConnectAllEnabledProfiles")]
+    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> BtStatus;
 
     /// Disconnect all profiles supported by device and enabled on adapter.
-    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool;
+    #[dbus_method("DisconnectAllEnabledProfiles")]
+    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> BtStatus;
+
+    /// Drops the link to a device on `transport`, or on both with `Auto`, along with whatever
+    /// runs over it. Returns `RemoteDeviceDown` if there is no such link.
+    ///
+    /// The LE link is kept while GATT clients are connected over it, since they share it with
+    /// whoever asked for the disconnection. `Busy` is returned if that was the only link.
+    #[dbus_method("Disconnect")]
+    fn disconnect(&mut self, device: BluetoothDevice, transport: BtTransport) -> BtStatus;
+
+    /// Disconnects every profile of a device, enabled or not, and then drops its links on both
+    /// transports without waiting for the profiles to finish.
+    #[dbus_method("DisconnectAllProfiles")]
+    fn disconnect_all_profiles(&mut self, device: BluetoothDevice) -> BtStatus;
+
+    /// Enables or disables a profile for a single device. Disabled profiles aren't connected by
+    /// `connect_all_enabled_profiles` and incoming connections on them are rejected. Existing
+    /// connections are kept. The setting is kept across restarts.
+    #[dbus_method("SetProfileEnabled")]
+    fn set_profile_enabled(
+        &mut self,
+        device: BluetoothDevice,
+        profile: Profile,
+        enabled: bool,
+    ) -> bool;
+
+    /// Returns false if the profile was disabled for this device with `set_profile_enabled`.
+    #[dbus_method("GetProfileEnabled")]
+    fn get_profile_enabled(&self, device: BluetoothDevice, profile: Profile) -> bool;
+
+    /// Blocks a remote device. Incoming connections and pairing attempts from it are rejected
+    /// until it is unblocked. The block is kept across restarts.
+    #[dbus_method("BlockDevice")]
+    fn block_device(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Removes a remote device from the block list.
+    #[dbus_method("UnblockDevice")]
+    fn unblock_device(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Returns the list of blocked devices.
+    #[dbus_method("GetBlockedDevices")]
+    fn get_blocked_devices(&self) -> Vec<BluetoothDevice>;
+
+    /// Returns the radio activity since the stack started, and asks the controller for a new
+    /// report of its own activity, which is included from the next call on.
+    #[dbus_method("GetActivityEnergyInfo")]
+    fn get_activity_energy_info(&self) -> ActivityEnergyInfo;
+
+    /// Returns the versions and firmware build of the local controller. Everything is 0 or empty
+    /// until the adapter is on.
+    #[dbus_method("GetControllerInfo")]
+    fn get_controller_info(&self) -> ControllerInfo;
+
+    /// Turns the vendor specific debug events of the controller on or off. They are turned on
+    /// again each time the adapter comes up, until turned off.
+    ///
+    /// Returns false if the adapter is off or the stack doesn't know how to for its controller.
+    #[dbus_method("SetVendorDiagnosticsEnabled")]
+    fn set_vendor_diagnostics_enabled(&mut self, enabled: bool) -> bool;
+
+    /// Returns the latest API calls and callbacks with their arguments, one per line, if the
+    /// daemon was started with --api-trace. Secrets such as PINs and keys are redacted.
+    #[dbus_method("DumpApiTrace")]
+    fn dump_api_trace(&self) -> String;
+}
+
+/// Lifecycle of the adapter, reported to clients through
+/// `IBluetoothCallback::on_adapter_state_changed`.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum AdapterState {
+    Off = 0,
+    TurningOn,
+    On,
+    TurningOff,
+    /// The stack is being restarted after a controller failure.
+    Recovering,
+}
+
+/// Capabilities of the local controller, so that clients can check for a feature before using it.
+#[derive(Clone, Debug, Default)]
+pub struct AdapterCapabilities {
+    pub le_extended_advertising_supported: bool,
+    pub le_2m_phy_supported: bool,
+    pub le_coded_phy_supported: bool,
+    pub le_periodic_advertising_supported: bool,
+    pub le_connected_isochronous_stream_central_supported: bool,
+    pub le_isochronous_broadcast_supported: bool,
+    pub max_advertising_sets: u32,
+    pub max_advertising_data_length: u32,
+    /// Number of advertisement filters the controller can apply while scanning.
+    pub max_advertising_filters: u32,
+    /// Number of ACL data packets the controller can buffer, for BR/EDR and LE.
+    pub acl_buffer_count_classic: u32,
+    pub acl_buffer_count_le: u32,
+    pub le_accept_list_size: u32,
+    /// Number of ACL connections the stack can hold at once.
+    pub max_connections: u32,
+}
+
+/// The adapter properties that have change callbacks in `IBluetoothCallback`, read in one call so
+/// clients can set up their initial state without a round trip per property.
+#[derive(Clone, Debug, Default)]
+pub struct AdapterProperties {
+    pub address: String,
+    pub name: String,
+    pub bluetooth_class: u32,
+    pub discoverable: bool,
+    pub discoverable_timeout: u32,
+    pub discovering: bool,
+    /// Number of remote devices with an ACL connection.
+    pub connected_count: u32,
+}
+
+/// Connection state of a profile with a remote device, as reported by
+/// `IBluetoothConnectionCallback::on_profile_connection_state_changed`.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum ProfileConnectionState {
+    Disconnected = 0,
+    Connecting,
+    Connected,
+    Disconnecting,
+}
+
+/// Why a profile connection changed state.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum ProfileConnectionReason {
+    /// A regular transition, requested by either side.
+    None = 0,
+    /// The connection attempt ended before the profile connected.
+    ConnectFailed,
+    /// The connection was dropped because the profile is blocked by policy or disabled for the
+    /// device.
+    NotAllowed,
+}
+
+/// Why a pairing attempt was refused by the security policy, as reported by
+/// `IBluetoothCallback::on_pairing_rejected`.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum PairingRejectedReason {
+    /// The device asked for legacy PIN pairing.
+    LegacyPairing = 0,
+    /// The device doesn't support Secure Connections.
+    SecureConnectionsUnsupported,
+}
+
+/// Outcome of connecting a profile through `IBluetooth::connect_all_enabled_profiles`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileConnectResult {
+    pub profile: Profile,
+    /// `Success` if the profile connected, `Fail` if it didn't after all the attempts.
+    pub status: BtStatus,
+}
+
+impl Default for ProfileConnectResult {
+    fn default() -> Self {
+        ProfileConnectResult { profile: Profile::Hfp, status: BtStatus::Fail }
+    }
+}
+
+/// A `connect_all_enabled_profiles` in progress.
+struct ConnectAllAttempt {
+    /// Profiles to connect, none until the UUIDs of the device are known.
+    profiles: Vec<Profile>,
+    /// Profiles left to try in the current round.
+    round: VecDeque<Profile>,
+    /// Number of rounds started.
+    attempts: u32,
+    next_step: Option<JoinHandle<()>>,
+}
+
+impl ConnectAllAttempt {
+    fn cancel(&mut self) {
+        if let Some(step) = self.next_step.take() {
+            step.abort();
+        }
+    }
+}
+
+/// A session started with `IBluetooth::start_discovery_session`.
+struct DiscoverySession {
+    lost_timeout: Duration,
+    /// Devices reported found to the session and not lost since.
+    found: HashSet<RawAddress>,
+}
+
+/// Whether a BR/EDR scan listens on one train of hopping frequencies per window (standard) or
+/// on both trains back to back (interlaced), which finds peers faster for the same window.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum ClassicScanType {
+    Standard = 0,
+    Interlaced,
+}
+
+impl Default for ClassicScanType {
+    fn default() -> Self {
+        ClassicScanType::Interlaced
+    }
+}
+
+/// Parameters of the inquiry or page scan of the controller: it scans for `window` out of every
+/// `interval`, both in slots of 0.625 ms.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClassicScanParams {
+    pub interval: u16,
+    pub window: u16,
+    pub scan_type: ClassicScanType,
+}
+
+impl ClassicScanParams {
+    /// BTM_DEFAULT_DISC_INTERVAL and BTM_DEFAULT_DISC_WINDOW. The stack switches both scans to
+    /// interlaced when the controller supports it.
+    pub(crate) fn default_inquiry() -> ClassicScanParams {
+        ClassicScanParams {
+            interval: 0x0800,
+            window: 0x0012,
+            scan_type: ClassicScanType::Interlaced,
+        }
+    }
+
+    /// BTM_DEFAULT_CONN_INTERVAL and BTM_DEFAULT_CONN_WINDOW.
+    pub(crate) fn default_page() -> ClassicScanParams {
+        ClassicScanParams {
+            interval: 0x0400,
+            window: 0x0012,
+            scan_type: ClassicScanType::Interlaced,
+        }
+    }
+
+    /// Ranges of the Write Inquiry/Page Scan Activity commands.
+    pub(crate) fn is_valid(&self) -> bool {
+        (0x0012..=0x1000).contains(&self.interval)
+            && (0x0011..=0x1000).contains(&self.window)
+            && self.window <= self.interval
+    }
+}
+
+/// Parameters of the LE scan run during discovery, in the same units as `ClassicScanParams`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeScanParams {
+    pub interval: u16,
+    pub window: u16,
+    pub scan_type: ScanType,
+}
+
+impl Default for LeScanParams {
+    /// BTM_BLE_GAP_DISC_SCAN_INT and BTM_BLE_GAP_DISC_SCAN_WIN.
+    fn default() -> Self {
+        LeScanParams { interval: 18, window: 18, scan_type: ScanType::Active }
+    }
+}
+
+impl LeScanParams {
+    /// Ranges of the LE Set Scan Parameters command.
+    pub(crate) fn is_valid(&self) -> bool {
+        (0x0004..=0x4000).contains(&self.interval)
+            && (0x0004..=0x4000).contains(&self.window)
+            && self.window <= self.interval
+    }
+}
+
+/// Identifies the local controller and its firmware, for bug reports.
+#[derive(Clone, Debug, Default)]
+pub struct ControllerInfo {
+    /// Company identifier of the controller manufacturer.
+    pub manufacturer: u16,
+    pub hci_version: u8,
+    pub hci_revision: u16,
+    pub lmp_version: u8,
+    pub lmp_subversion: u16,
+    /// Firmware build read with a vendor specific command, or empty if the stack doesn't know one
+    /// for this manufacturer or the controller didn't answer yet.
+    pub firmware_version: String,
+    /// Whether the stack can turn on the vendor specific debug events of the controller, and
+    /// whether they are on.
+    pub vendor_diagnostics_supported: bool,
+    pub vendor_diagnostics_enabled: bool,
+}
+
+/// Versions reported by the controller of a connected remote device, to tell which firmware an
+/// interop issue is with. `valid` is false if the device isn't connected or they weren't read
+/// yet.
+#[derive(Clone, Debug, Default)]
+pub struct RemoteVersionInfo {
+    pub valid: bool,
+    pub lmp_version: u8,
+    /// Company identifier of the controller manufacturer.
+    pub manufacturer: u16,
+    pub lmp_subversion: u16,
+}
+
+/// Feature masks sent by the controller of a connected remote device. A mask is empty while the
+/// device isn't connected on its transport.
+#[derive(Clone, Debug, Default)]
+pub struct RemoteFeatures {
+    /// Page 0 of the LMP features, from the BR/EDR link.
+    pub lmp_features: Vec<u8>,
+    /// Link Layer features, from the LE link.
+    pub le_features: Vec<u8>,
+}
+
+/// What the controller supports for LE privacy, and whether the stack is using it.
+#[derive(Clone, Debug, Default)]
+pub struct LePrivacyFeatures {
+    /// The controller resolves private addresses itself (LL privacy).
+    pub ll_privacy_supported: bool,
+    /// The controller generates our resolvable private addresses without the host.
+    pub rpa_offload_supported: bool,
+    /// Number of identities the resolving list of the controller can hold.
+    pub resolving_list_size: u32,
+    /// The adapter uses resolvable private addresses.
+    pub privacy_enabled: bool,
+}
+
+/// Whether `address` can be used as a static random address: the two most significant bits are
+/// set and the other bits are neither all 0 nor all 1.
+pub(crate) fn is_static_random_address(address: &RawAddress) -> bool {
+    let val = &address.val;
+    let random_bits_are =
+        |first: u8, rest: u8| val[0] & 0x3f == first && val[1..].iter().all(|b| *b == rest);
+    val[0] & 0xc0 == 0xc0 && !random_bits_are(0, 0) && !random_bits_are(0x3f, 0xff)
 }
 
 /// Serializable device used in various apis.
+///
+/// A dual-mode peer is a single device, even if it was first seen through an LE address that
+/// differs from its BR/EDR one.
 #[derive(Clone, Debug, Default)]
 pub struct BluetoothDevice {
-    pub address: String,
+    pub address: RawAddress,
     pub name: String,
+    /// LE address type of `address`, or `Unknown` when the stack hasn't stored one.
+    pub address_type: BtAddrType,
+    /// Transport the device is reachable on. `Auto` for dual-mode devices and for devices of
+    /// unknown type.
+    pub transport: BtTransport,
+    /// What the device says it is, for showing an icon.
+    pub icon: DeviceType,
 }
 
 impl BluetoothDevice {
-    pub(crate) fn new(address: String, name: String) -> BluetoothDevice {
-        BluetoothDevice { address, name }
+    pub(crate) fn new(address: RawAddress, name: String) -> BluetoothDevice {
+        BluetoothDevice {
+            address,
+            name,
+            address_type: BtAddrType::Unknown,
+            transport: BtTransport::Auto,
+            icon: DeviceType::Unknown,
+        }
     }
 
     pub(crate) fn from_properties(in_properties: &Vec<BluetoothProperty>) -> BluetoothDevice {
-        let mut address = String::from("");
-        let mut name = String::from("");
+        let mut device = BluetoothDevice::new(RawAddress::default(), String::from(""));
 
         for prop in in_properties {
             match &prop {
                 BluetoothProperty::BdAddr(bdaddr) => {
-                    address = bdaddr.to_string();
+                    device.address = *bdaddr;
                 }
                 BluetoothProperty::BdName(bdname) => {
-                    name = bdname.clone();
+                    device.name = bdname.clone();
+                }
+                BluetoothProperty::TypeOfDevice(device_type) => {
+                    device.set_device_type(device_type);
+                }
+                BluetoothProperty::ClassOfDevice(cod) => {
+                    device.icon = DeviceType::from_class_of_device(*cod);
                 }
                 _ => {}
             }
         }
 
-        BluetoothDevice::new(address, name)
+        device
+    }
+
+    fn set_device_type(&mut self, device_type: &BtDeviceType) {
+        self.transport = match device_type {
+            BtDeviceType::Bredr => BtTransport::Bredr,
+            BtDeviceType::Ble => BtTransport::Le,
+            BtDeviceType::Dual | BtDeviceType::Unknown => BtTransport::Auto,
+        };
+
+        // BR/EDR addresses are always public.
+        if self.transport == BtTransport::Bredr {
+            self.address_type = BtAddrType::Public;
+        }
+    }
+}
+
+/// Properties of a device found by discovery, taken from its inquiry result or advertisement.
+///
+/// libbluetooth doesn't report TX power, manufacturer data or the LE address type along with
+/// found devices, so those aren't available here.
+#[derive(Clone, Debug)]
+pub struct FoundDeviceInfo {
+    pub class_of_device: u32,
+    pub device_type: BtDeviceType,
+    /// RSSI in dBm, or 0 if unknown.
+    pub rssi: i32,
+    pub uuids: Vec<Uuid128Bit>,
+}
+
+/// An RSSI reported for a device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RssiSample {
+    /// RSSI in dBm.
+    pub rssi: i32,
+    /// How long ago it was reported, in milliseconds.
+    pub age_ms: u64,
+}
+
+/// Everything the adapter has cached about a remote device, gathered by
+/// `IBluetooth::get_remote_device_info` for triage.
+#[derive(Clone, Debug)]
+pub struct RemoteDeviceInfo {
+    /// Whether the adapter knows the device at all, as a bonded or found device. If not, the rest
+    /// is empty.
+    pub known: bool,
+    pub device: BluetoothDevice,
+    pub alias: String,
+    pub device_type: BtDeviceType,
+    pub class_of_device: u32,
+    /// A `BtBondState`, as returned by `get_bond_state`.
+    pub bond_state: u32,
+    pub uuids: Vec<Uuid128Bit>,
+    /// The last RSSIs reported by discovery, oldest first.
+    pub rssi_history: Vec<RssiSample>,
+    /// How long ago the device was last seen, in milliseconds.
+    pub last_seen_ms: u64,
+    /// The connection state as returned by `get_connection_state`, and how long the device has
+    /// been connected, in milliseconds, or 0 if it isn't.
+    pub connection_state: u32,
+    pub connected_ms: u64,
+    /// Local role on the BR/EDR link.
+    pub link_role: BtAclRole,
+    /// Encryption key size of the link on each transport, or 0 if it isn't encrypted.
+    pub bredr_key_size: u8,
+    pub le_key_size: u8,
+}
+
+impl RemoteDeviceInfo {
+    /// Returns the info of a device the adapter doesn't know.
+    pub fn unknown(device: BluetoothDevice) -> RemoteDeviceInfo {
+        RemoteDeviceInfo {
+            known: false,
+            device,
+            alias: String::new(),
+            device_type: BtDeviceType::Unknown,
+            class_of_device: 0,
+            bond_state: BtBondState::NotBonded as u32,
+            uuids: vec![],
+            rssi_history: vec![],
+            last_seen_ms: 0,
+            connection_state: 0,
+            connected_ms: 0,
+            link_role: BtAclRole::Unknown,
+            bredr_key_size: 0,
+            le_key_size: 0,
+        }
     }
 }
 
@@ -206,6 +997,10 @@ struct BluetoothDeviceContext {
     pub info: BluetoothDevice,
     pub last_seen: Instant,
     pub properties: HashMap<BtPropertyType, BluetoothProperty>,
+    /// When the last RSSIs were reported, and what they were, oldest first.
+    pub rssi_history: VecDeque<(Instant, i32)>,
+    /// When the ACL came up, while the device is connected.
+    pub connected_at: Option<Instant>,
 }
 
 impl BluetoothDeviceContext {
@@ -222,6 +1017,8 @@ impl BluetoothDeviceContext {
             info,
             last_seen,
             properties: HashMap::new(),
+            rssi_history: VecDeque::new(),
+            connected_at: None,
         };
         device.update_properties(properties);
         device
@@ -231,11 +1028,23 @@ impl BluetoothDeviceContext {
         for prop in in_properties {
             match &prop {
                 BluetoothProperty::BdAddr(bdaddr) => {
-                    self.info.address = bdaddr.to_string();
+                    self.info.address = *bdaddr;
                 }
                 BluetoothProperty::BdName(bdname) => {
                     self.info.name = bdname.clone();
                 }
+                BluetoothProperty::TypeOfDevice(device_type) => {
+                    self.info.set_device_type(device_type);
+                }
+                BluetoothProperty::ClassOfDevice(cod) => {
+                    self.info.icon = DeviceType::from_class_of_device(*cod);
+                }
+                BluetoothProperty::RemoteRssi(rssi) => {
+                    if self.rssi_history.len() == RSSI_HISTORY_LEN {
+                        self.rssi_history.pop_front();
+                    }
+                    self.rssi_history.push_back((Instant::now(), i32::from(*rssi)));
+                }
                 _ => {}
             }
 
@@ -243,14 +1052,85 @@ impl BluetoothDeviceContext {
         }
     }
 
+    /// Fills in the LE address type once the stack has stored one for this device.
+    pub(crate) fn update_address_type(&mut self, intf: &BluetoothInterface) {
+        if self.info.transport == BtTransport::Bredr
+            || self.info.address_type != BtAddrType::Unknown
+        {
+            return;
+        }
+
+        self.info.address_type = intf.get_remote_addr_type(&self.info.address);
+    }
+
+    /// Folds the properties of another identity of the same device into this one.
+    pub(crate) fn merge(&mut self, other: BluetoothDeviceContext) {
+        if self.info.name.is_empty() {
+            self.info.name = other.info.name;
+        }
+
+        if self.bond_state == BtBondState::NotBonded {
+            self.bond_state = other.bond_state;
+        }
+
+        if self.acl_state == BtAclState::Disconnected {
+            self.acl_state = other.acl_state;
+            self.connected_at = other.connected_at;
+        }
+
+        if self.rssi_history.is_empty() {
+            self.rssi_history = other.rssi_history;
+        }
+
+        if other.last_seen > self.last_seen {
+            self.last_seen = other.last_seen;
+        }
+
+        for (prop_type, prop) in other.properties {
+            // Our own addresses and device type win.
+            if prop_type == BtPropertyType::BdAddr || prop_type == BtPropertyType::TypeOfDevice {
+                continue;
+            }
+            self.properties.entry(prop_type).or_insert(prop);
+        }
+    }
+
     /// Mark this device as seen.
     pub(crate) fn seen(&mut self) {
         self.last_seen = Instant::now();
     }
+
+    pub(crate) fn found_device_info(&self) -> FoundDeviceInfo {
+        let mut info = FoundDeviceInfo {
+            class_of_device: 0,
+            device_type: BtDeviceType::Unknown,
+            rssi: 0,
+            uuids: vec![],
+        };
+
+        for prop in self.properties.values() {
+            match prop {
+                BluetoothProperty::ClassOfDevice(cod) => info.class_of_device = *cod,
+                BluetoothProperty::TypeOfDevice(device_type) => {
+                    info.device_type = device_type.clone()
+                }
+                BluetoothProperty::RemoteRssi(rssi) => info.rssi = i32::from(*rssi),
+                BluetoothProperty::Uuids(uuids) => {
+                    info.uuids = uuids.iter().map(|uuid| uuid.uu).collect()
+                }
+                _ => {}
+            }
+        }
+
+        info
+    }
 }
 
 /// The interface for adapter callbacks registered through `IBluetooth::register_callback`.
 pub trait IBluetoothCallback: RPCProxy {
+    /// When the adapter moves to another lifecycle state.
+    fn on_adapter_state_changed(&self, state: AdapterState);
+
     /// When any of the adapter local address is changed.
     fn on_address_changed(&self, addr: String);
 
@@ -260,15 +1140,23 @@ pub trait IBluetoothCallback: RPCProxy {
     /// When the adapter's discoverable mode is changed.
     fn on_discoverable_changed(&self, discoverable: bool);
 
-    /// When a device is found via discovery.
-    fn on_device_found(&self, remote_device: BluetoothDevice);
+    /// When a device is found via discovery, along with the properties discovery reported for it.
+    fn on_device_found(&self, remote_device: BluetoothDevice, info: FoundDeviceInfo);
 
     /// When a device is cleared from discovered devices cache.
     fn on_device_cleared(&self, remote_device: BluetoothDevice);
 
+    /// When a name request for a device completes, with the name it got if `success`. Requests
+    /// made with `IBluetooth::fetch_remote_name` are always reported, those the adapter makes for
+    /// devices discovery found without a name only if they got one.
+    fn on_remote_name_fetched(&self, remote_device: BluetoothDevice, success: bool);
+
     /// When the discovery state is changed.
     fn on_discovering_changed(&self, discovering: bool);
 
+    /// When the number of remote devices with an ACL connection changes.
+    fn on_connected_count_changed(&self, count: u32);
+
     /// When there is a pairing/bonding process and requires agent to display the event to UI.
     fn on_ssp_request(
         &self,
@@ -278,41 +1166,224 @@ pub trait IBluetoothCallback: RPCProxy {
         passkey: u32,
     );
 
+    /// When a device pairing with legacy pairing asks for a PIN, which is given to it with
+    /// `IBluetooth::set_pin`. The PIN must be 16 digits long if `min_16_digit` is set.
+    fn on_pin_request(&self, remote_device: BluetoothDevice, cod: u32, min_16_digit: bool);
+
+    /// When a keyboard pairing with legacy pairing has to be given a PIN, which the user types on
+    /// it and which the adapter already answered with.
+    fn on_pin_display(&self, remote_device: BluetoothDevice, pincode: String);
+
+    /// When a pairing attempt was refused because the device can't meet the Secure Connections
+    /// Only policy set with `IBluetooth::set_secure_connections_only`.
+    fn on_pairing_rejected(&self, remote_device: BluetoothDevice, reason: PairingRejectedReason);
+
+    /// When the link to a device on `transport` was disconnected, or its LE pairing failed,
+    /// because its encryption key of `key_size` octets is shorter than the minimum set with
+    /// `IBluetooth::set_min_encryption_key_size`.
+    fn on_encryption_key_size_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        key_size: u8,
+    );
+
     /// When a bonding attempt has completed.
     fn on_bond_state_changed(&self, status: u32, device_address: String, state: u32);
 }
 
+/// The interface for the callback of a session started with
+/// `IBluetooth::start_discovery_session`.
+pub trait IBluetoothDiscoverySessionCallback: RPCProxy {
+    /// When a device is found for the first time in the session, or again after it was lost.
+    fn on_session_device_found(&self, session_id: u32, remote_device: BluetoothDevice);
+
+    /// When a device found in the session wasn't seen for the lost timeout of the session, or
+    /// was cleared from the found devices cache.
+    fn on_session_device_lost(&self, session_id: u32, remote_device: BluetoothDevice);
+
+    /// When the session ended without being stopped, because the adapter turned off.
+    fn on_discovery_session_ended(&self, session_id: u32);
+}
+
 pub trait IBluetoothConnectionCallback: RPCProxy {
     /// Notification sent when a remote device completes HCI connection.
     fn on_device_connected(&self, remote_device: BluetoothDevice);
 
     /// Notification sent when a remote device completes HCI disconnection.
     fn on_device_disconnected(&self, remote_device: BluetoothDevice);
+
+    /// Notification sent when a profile (A2DP, HFP, HID, PAN or hearing aid) connects to or
+    /// disconnects from a remote device, independently of its ACL connection.
+    fn on_profile_connection_state_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    );
+
+    /// Notification sent when `IBluetooth::connect_all_enabled_profiles` is done with a device,
+    /// with the outcome for each profile it tried to connect.
+    fn on_connect_all_complete(
+        &self,
+        remote_device: BluetoothDevice,
+        results: Vec<ProfileConnectResult>,
+    );
+}
+
+/// Security events of remote devices, for daemons monitoring the adapter.
+///
+/// HCI status and reason codes are the error codes of the Core Specification, Vol 1, Part F, with
+/// 0 for success.
+pub trait IBluetoothSecurityCallback: RPCProxy {
+    /// Notification sent when a device failed to authenticate, either while pairing or with the
+    /// key of an existing bond.
+    fn on_authentication_failed(&self, remote_device: BluetoothDevice, hci_reason: u8);
+
+    /// Notification sent when the encryption of the link to a device on `transport` was turned
+    /// on or off, or failed to be.
+    fn on_encryption_changed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+        encrypted: bool,
+    );
+
+    /// Notification sent when the encryption key of the link to a device on `transport` was
+    /// refreshed, or failed to be.
+    fn on_encryption_key_refreshed(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+        hci_status: u8,
+    );
+
+    /// Notification sent when a device using the address of a bonded device couldn't prove it
+    /// holds the key of the bond, as a device spoofing the address would. This is also what
+    /// happens when the bonded device itself has lost the bond.
+    fn on_address_spoofing_suspected(&self, remote_device: BluetoothDevice, transport: BtTransport);
+
+    /// Notification sent when the link from a device that isn't bonded was dropped on
+    /// `transport`, as set with `IBluetooth::set_bonded_only_connections`.
+    fn on_unbonded_connection_rejected(
+        &self,
+        remote_device: BluetoothDevice,
+        transport: BtTransport,
+    );
+}
+
+/// Controls the link policy of BR/EDR links, for remote devices that only work well when the
+/// adapter takes a given role or stays out of sniff mode.
+///
+/// Changes apply to the current link only and are dropped when it disconnects.
+#[dbus_interface(bluetooth_link_policy_dbus, "org.chromium.bluetooth.BluetoothLinkPolicy")]
+pub trait IBluetoothLinkPolicy {
+    /// Adds a callback to be notified of role changes.
+    #[dbus_method("RegisterLinkPolicyCallback")]
+    fn register_link_policy_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothLinkPolicyCallback + Send>,
+    ) -> u32;
+
+    /// Removes registered callback.
+    #[dbus_method("UnregisterLinkPolicyCallback")]
+    fn unregister_link_policy_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Gets the local role on the BR/EDR link to a device, or `Unknown` if it isn't connected.
+    #[dbus_method("GetLinkRole")]
+    fn get_link_role(&self, device: BluetoothDevice) -> BtAclRole;
+
+    /// Asks for the local role on the BR/EDR link to a device to be switched to `role`. The result
+    /// is reported by `on_role_changed`, unless the adapter already has that role.
+    #[dbus_method("RequestRoleSwitch")]
+    fn request_role_switch(&mut self, device: BluetoothDevice, role: BtAclRole) -> BtStatus;
+
+    /// Allows or forbids the BR/EDR link to a device to enter sniff mode.
+    #[dbus_method("SetSniffModeEnabled")]
+    fn set_sniff_mode_enabled(&mut self, device: BluetoothDevice, enabled: bool) -> BtStatus;
+
+    /// Sets the sniff subrating parameters of the BR/EDR link to a device, in slots of 0.625
+    /// ms. They are sent to the controller the next time the link is active.
+    #[dbus_method("SetSniffSubrating")]
+    fn set_sniff_subrating(
+        &mut self,
+        device: BluetoothDevice,
+        max_latency: u16,
+        min_remote_timeout: u16,
+        min_local_timeout: u16,
+    ) -> BtStatus;
+}
+
+/// The interface for callbacks registered through `IBluetoothLinkPolicy`.
+pub trait IBluetoothLinkPolicyCallback: RPCProxy {
+    /// When the local role on the BR/EDR link to a device changes, or a role switch fails, in
+    /// which case `role` is the role that was kept.
+    fn on_role_changed(&self, device: BluetoothDevice, role: BtAclRole, status: BtStatus);
 }
 
 /// Implementation of the adapter API.
 pub struct Bluetooth {
     intf: Arc<Mutex<BluetoothInterface>>,
 
-    bonded_devices: HashMap<String, BluetoothDeviceContext>,
+    activity: Arc<Mutex<ActivityTracker>>,
+    adapter_state: AdapterState,
+    blocked_devices: HashSet<RawAddress>,
+    bonded_devices: HashMap<RawAddress, BluetoothDeviceContext>,
+    /// Other addresses of a known device (the BR/EDR address or LE identity address reported by
+    /// address consolidation), mapped to the address the device is kept under.
+    address_aliases: HashMap<RawAddress, RawAddress>,
     bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
-    callbacks: HashMap<u32, Box<dyn IBluetoothCallback + Send>>,
-    connection_callbacks: HashMap<u32, Box<dyn IBluetoothConnectionCallback + Send>>,
+    callbacks: Callbacks<dyn IBluetoothCallback + Send>,
+    connection_callbacks: Callbacks<dyn IBluetoothConnectionCallback + Send>,
+    link_policy_callbacks: Callbacks<dyn IBluetoothLinkPolicyCallback + Send>,
+    security_callbacks: Callbacks<dyn IBluetoothSecurityCallback + Send>,
+    /// Callbacks of the discovery sessions, whose IDs are the session IDs, and the sessions.
+    discovery_session_callbacks: Callbacks<dyn IBluetoothDiscoverySessionCallback + Send>,
+    discovery_sessions: HashMap<u32, DiscoverySession>,
+    controller: Option<Controller>,
+    /// Firmware build of the controller, once it answered the vendor command reading it.
+    firmware_version: String,
+    vendor_diagnostics_enabled: bool,
     discovering_started: Instant,
+    device_found_limiter: RateLimiter<RawAddress>,
+    device_found_flush: Option<JoinHandle<()>>,
+    floss_config: DeviceStore,
     hh: Option<HidHost>,
+    interop: InteropDatabase,
     is_connectable: bool,
     is_discovering: bool,
+    /// GATT clients keeping LE links up.
+    link_users: Arc<Mutex<LinkUsers>>,
     local_address: Option<RawAddress>,
     properties: HashMap<BtPropertyType, BluetoothProperty>,
+    radio: Arc<Mutex<RadioArbiter>>,
+    /// Last state reported for the profiles that aren't disconnected.
+    profile_states: HashMap<(RawAddress, Profile), ProfileConnectionState>,
     profiles_ready: bool,
-    found_devices: HashMap<String, BluetoothDeviceContext>,
+    found_devices: HashMap<RawAddress, BluetoothDeviceContext>,
     freshness_check: Option<JoinHandle<()>>,
+    /// Devices waiting for a remote name request, the one whose request is outstanding, and when
+    /// the last one completed.
+    name_requests: VecDeque<RawAddress>,
+    name_request_pending: Option<RawAddress>,
+    name_request_timer: Option<JoinHandle<()>>,
+    last_name_request: Instant,
+    /// Devices queued for a name request since discovery started, which aren't queued again.
+    names_requested: HashSet<RawAddress>,
+    /// Devices whose name was asked for through `IBluetooth::fetch_remote_name`.
+    name_fetches: HashSet<RawAddress>,
+    // Scan parameters set through `IBluetooth`, or None to leave the stack defaults.
+    inquiry_scan_params: Option<ClassicScanParams>,
+    page_scan_params: Option<ClassicScanParams>,
+    le_scan_params: Option<LeScanParams>,
     sdp: Option<Sdp>,
     state: BtState,
     tx: Sender<Message>,
     uuid_helper: UuidHelper,
-    /// Used to delay connection until we have SDP results.
-    wait_to_connect: bool,
+    /// Devices that `connect_all_enabled_profiles` is connecting.
+    connect_all_attempts: HashMap<RawAddress, ConnectAllAttempt>,
 }
 
 impl Bluetooth {
@@ -321,27 +1392,81 @@ impl Bluetooth {
         tx: Sender<Message>,
         intf: Arc<Mutex<BluetoothInterface>>,
         bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
+        activity: Arc<Mutex<ActivityTracker>>,
+        link_users: Arc<Mutex<LinkUsers>>,
+        radio: Arc<Mutex<RadioArbiter>>,
     ) -> Bluetooth {
+        let floss_config = DeviceStore::load(device_store::FLOSS_CONFIG_PATH).unwrap_or_else(|e| {
+            warn!("Failed to load {}: {}", device_store::FLOSS_CONFIG_PATH, e);
+            DeviceStore::new(device_store::FLOSS_CONFIG_PATH)
+        });
+        let blocked_devices = floss_config
+            .get_devices()
+            .into_iter()
+            .filter(|d| d.blocked)
+            .map(|d| d.address)
+            .collect();
+
         Bluetooth {
+            activity,
+            adapter_state: AdapterState::Off,
+            blocked_devices,
             bonded_devices: HashMap::new(),
-            callbacks: HashMap::new(),
-            connection_callbacks: HashMap::new(),
+            address_aliases: HashMap::new(),
+            callbacks: Callbacks::new(tx.clone(), |id| {
+                Message::BluetoothCallbackDisconnected(id, BluetoothCallbackType::Adapter)
+            }),
+            connection_callbacks: Callbacks::new(tx.clone(), |id| {
+                Message::BluetoothCallbackDisconnected(id, BluetoothCallbackType::Connection)
+            }),
+            link_policy_callbacks: Callbacks::new(tx.clone(), |id| {
+                Message::BluetoothCallbackDisconnected(id, BluetoothCallbackType::LinkPolicy)
+            }),
+            security_callbacks: Callbacks::new(tx.clone(), |id| {
+                Message::BluetoothCallbackDisconnected(id, BluetoothCallbackType::Security)
+            }),
+            discovery_session_callbacks: Callbacks::new(tx.clone(), |id| {
+                Message::BluetoothCallbackDisconnected(id, BluetoothCallbackType::DiscoverySession)
+            }),
+            discovery_sessions: HashMap::new(),
+            controller: None,
+            firmware_version: String::new(),
+            vendor_diagnostics_enabled: false,
             hh: None,
             bluetooth_media,
             discovering_started: Instant::now(),
+            device_found_limiter: RateLimiter::new(
+                DEFAULT_DEVICE_FOUND_RATE_LIMIT,
+                DEVICE_FOUND_RATE_PERIOD,
+            ),
+            device_found_flush: None,
+            floss_config,
             intf,
+            interop: InteropDatabase::load_default(),
             is_connectable: false,
             is_discovering: false,
+            link_users,
             local_address: None,
             properties: HashMap::new(),
+            radio,
+            profile_states: HashMap::new(),
             profiles_ready: false,
             found_devices: HashMap::new(),
             freshness_check: None,
+            name_requests: VecDeque::new(),
+            name_request_pending: None,
+            name_request_timer: None,
+            last_name_request: Instant::now(),
+            names_requested: HashSet::new(),
+            name_fetches: HashSet::new(),
+            inquiry_scan_params: None,
+            page_scan_params: None,
+            le_scan_params: None,
             sdp: None,
             state: BtState::Off,
             tx,
             uuid_helper: UuidHelper::new(),
-            wait_to_connect: false,
+            connect_all_attempts: HashMap::new(),
         }
     }
 
@@ -350,10 +1475,7 @@ impl Bluetooth {
         self.hh = Some(HidHost::new(&self.intf.lock().unwrap()));
         self.hh.as_mut().unwrap().initialize(HHCallbacksDispatcher {
             dispatch: Box::new(move |cb| {
-                let txl = hhtx.clone();
-                topstack::get_runtime().spawn(async move {
-                    let _ = txl.send(Message::HidHost(cb)).await;
-                });
+                queue_message(&hhtx, Message::HidHost(cb));
             }),
         });
 
@@ -361,10 +1483,7 @@ impl Bluetooth {
         self.sdp = Some(Sdp::new(&self.intf.lock().unwrap()));
         self.sdp.as_mut().unwrap().initialize(SdpCallbacksDispatcher {
             dispatch: Box::new(move |cb| {
-                let txl = sdptx.clone();
-                topstack::get_runtime().spawn(async move {
-                    let _ = txl.send(Message::Sdp(cb)).await;
-                });
+                queue_message(&sdptx, Message::Sdp(cb));
             }),
         });
 
@@ -381,18 +1500,14 @@ impl Bluetooth {
     }
 
     fn for_all_callbacks<F: Fn(&Box<dyn IBluetoothCallback + Send>)>(&self, f: F) {
-        for (_, callback) in self.callbacks.iter() {
-            f(&callback);
-        }
+        self.callbacks.for_all_callbacks(f);
     }
 
     fn for_all_connection_callbacks<F: Fn(&Box<dyn IBluetoothConnectionCallback + Send>)>(
         &self,
         f: F,
     ) {
-        for (_, callback) in self.connection_callbacks.iter() {
-            f(&callback);
-        }
+        self.connection_callbacks.for_all_callbacks(f);
     }
 
     pub fn get_connectable(&self) -> bool {
@@ -418,31 +1533,830 @@ impl Bluetooth {
         )) == 0
     }
 
-    pub(crate) fn callback_disconnected(&mut self, id: u32, cb_type: BluetoothCallbackType) {
-        match cb_type {
-            BluetoothCallbackType::Adapter => {
-                self.callbacks.remove(&id);
-            }
-            BluetoothCallbackType::Connection => {
-                self.connection_callbacks.remove(&id);
+    fn apply_inquiry_scan_params(&self) {
+        if let Some(params) = self.inquiry_scan_params.as_ref() {
+            self.intf.lock().unwrap().set_inquiry_scan_params(
+                params.interval,
+                params.window,
+                params.scan_type == ClassicScanType::Interlaced,
+            );
+        }
+    }
+
+    fn apply_page_scan_params(&self) {
+        if let Some(params) = self.page_scan_params.as_ref() {
+            self.intf.lock().unwrap().set_page_scan_params(
+                params.interval,
+                params.window,
+                params.scan_type == ClassicScanType::Interlaced,
+            );
+        }
+    }
+
+    fn apply_le_scan_params(&self) {
+        // While discovering, the scan shares the radio and runs with what it was granted.
+        let granted = {
+            let radio = self.radio.lock().unwrap();
+            match radio.get_grant(RadioUser::Discovery, RadioActivity::Scan) {
+                Some(_) => radio.get_scan_duty(),
+                None => None,
             }
         };
+        let params = match (granted, self.le_scan_params.as_ref()) {
+            (Some(duty), params) => LeScanParams {
+                interval: duty.interval,
+                window: duty.window,
+                scan_type: params.cloned().unwrap_or_default().scan_type,
+            },
+            (None, Some(params)) => params.clone(),
+            (None, None) => return,
+        };
+        self.intf.lock().unwrap().set_le_scan_params(
+            params.interval,
+            params.window,
+            params.scan_type == ScanType::Active,
+        );
     }
 
-    fn get_remote_device_if_found(&self, address: &str) -> Option<&BluetoothDeviceContext> {
-        self.bonded_devices.get(address).or_else(|| self.found_devices.get(address))
+    /// Asks the radio for the LE scan of discovery, with the parameters set through `IBluetooth`.
+    fn request_discovery_scan(&self) {
+        let params = self.le_scan_params.clone().unwrap_or_default();
+        let duty = ScanDuty { interval: params.interval, window: params.window };
+        let grant = self.radio.lock().unwrap().request_scan(RadioUser::Discovery, duty);
+        if grant == RadioGrant::Downgraded {
+            info!("Discovery scans less than {:?} while other scans or connections run", duty);
+        }
+        self.apply_le_scan_params();
     }
 
-    fn get_remote_device_if_found_mut(
-        &mut self,
-        address: &str,
-    ) -> Option<&mut BluetoothDeviceContext> {
-        match self.bonded_devices.get_mut(address) {
-            None => self.found_devices.get_mut(address),
+    /// Handles a change of what the radio grants discovery.
+    pub(crate) fn radio_grant_changed(&mut self, activity: RadioActivity, grant: RadioGrant) {
+        if activity != RadioActivity::Scan || !self.is_discovering {
+            return;
+        }
+
+        debug!("Discovery scan is now {:?}", grant);
+        self.apply_le_scan_params();
+    }
+
+    /// Passes the LE address policy saved with `set_le_privacy` or `set_le_static_address` to the
+    /// native stack, which otherwise enables privacy. Must be done before enabling it.
+    fn apply_le_address_policy(&self) {
+        let privacy = self.floss_config.get_adapter_value(KEY_LE_PRIVACY);
+        let static_address = self
+            .floss_config
+            .get_adapter_value(KEY_LE_STATIC_ADDRESS)
+            .and_then(|address| RawAddress::from_string(address));
+        if privacy.is_none() && static_address.is_none() {
+            return;
+        }
+
+        let rpa_timeout = self.get_le_rpa_timeout();
+        self.intf.lock().unwrap().set_le_address_policy(
+            privacy.as_deref() == Some("1"),
+            static_address,
+            rpa_timeout,
+        );
+    }
+
+    /// RPA timeout set with `set_le_privacy`, in seconds, or 0 for the default of the native stack.
+    pub(crate) fn get_le_rpa_timeout(&self) -> u32 {
+        self.floss_config
+            .get_adapter_value(KEY_LE_RPA_TIMEOUT)
+            .and_then(|timeout| timeout.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Saves the LE address policy to the Floss config.
+    fn store_le_address_policy(
+        &mut self,
+        privacy: bool,
+        rpa_timeout: u32,
+        static_address: Option<RawAddress>,
+    ) -> BtStatus {
+        let privacy = if privacy { "1" } else { "0" };
+        let config = &mut self.floss_config;
+        config.set_adapter_value(KEY_LE_PRIVACY, Some(privacy.to_string()));
+        config.set_adapter_value(
+            KEY_LE_RPA_TIMEOUT,
+            if rpa_timeout != 0 { Some(rpa_timeout.to_string()) } else { None },
+        );
+        config.set_adapter_value(
+            KEY_LE_STATIC_ADDRESS,
+            static_address.map(|address| address.to_string().to_lowercase()),
+        );
+
+        match config.save() {
+            Ok(()) => BtStatus::Success,
+            Err(e) => {
+                warn!("Failed to save {}: {}", device_store::FLOSS_CONFIG_PATH, e);
+                BtStatus::Fail
+            }
+        }
+    }
+
+    fn is_secure_connections_only(&self) -> bool {
+        self.floss_config.get_adapter_value(KEY_SECURE_CONNECTIONS_ONLY).as_deref() == Some("1")
+    }
+
+    fn is_bonded_only_connections(&self) -> bool {
+        self.floss_config.get_adapter_value(KEY_BONDED_ONLY_CONNECTIONS).as_deref() == Some("1")
+    }
+
+    /// Returns true if the link from `address` that just came up has to be dropped under the
+    /// bonded only policy. Bonding with the device, connecting all its profiles, or a GATT client
+    /// connecting to it means this host asked for the link; a device pairing with us from scratch
+    /// has no bond and isn't bonding yet when its link comes up.
+    fn rejects_connection(&self, address: &RawAddress) -> bool {
+        self.is_bonded_only_connections()
+            && !self.bonded_devices.contains_key(address)
+            && !self.is_bonding(address)
+            && !self.connect_all_attempts.contains_key(address)
+            && !self.link_users.lock().unwrap().is_connecting(address)
+    }
+
+    /// Passes the Secure Connections Only policy to the native stack, which resets it whenever it
+    /// is enabled.
+    fn apply_secure_connections_only(&self) {
+        if !self.is_secure_connections_only() {
+            return;
+        }
+
+        if !self.intf.lock().unwrap().set_secure_connections_only(true) {
+            warn!("Secure Connections Only mode isn't supported by the controller");
+        }
+    }
+
+    fn min_encryption_key_size(&self) -> u8 {
+        self.floss_config
+            .get_adapter_value(KEY_MIN_ENCRYPTION_KEY_SIZE)
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(MIN_ENCRYPTION_KEY_SIZE)
+    }
+
+    /// Passes the minimum encryption key size to the native stack, which goes back to the
+    /// default whenever it is enabled.
+    fn apply_min_encryption_key_size(&self) {
+        let key_size = self.min_encryption_key_size();
+        if key_size != MIN_ENCRYPTION_KEY_SIZE {
+            self.intf.lock().unwrap().set_min_encryption_key_size(key_size);
+        }
+    }
+
+    /// Manufacturer of the controller, once the stack is up.
+    fn manufacturer(&self) -> Option<u16> {
+        self.controller.as_ref().map(|controller| controller.get_local_version().manufacturer)
+    }
+
+    fn send_vendor_command(&self, command: VendorCommand) {
+        if !self.intf.lock().unwrap().send_vendor_command(command.opcode, &command.params) {
+            warn!("Unable to send vendor command {:#06x}", command.opcode);
+        }
+    }
+
+    fn read_firmware_version(&mut self) {
+        self.firmware_version.clear();
+        if let Some(command) = self.manufacturer().and_then(vendor::firmware_version_command) {
+            self.send_vendor_command(command);
+        }
+    }
+
+    /// Sends the commands turning the debug events of the controller on or off. Returns false if
+    /// there are none for the controller.
+    fn apply_vendor_diagnostics(&self, enabled: bool) -> bool {
+        match self.manufacturer().and_then(|m| vendor::diagnostics_commands(m, enabled)) {
+            Some(commands) => {
+                commands.into_iter().for_each(|command| self.send_vendor_command(command));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_activity_energy_info_supported(&self) -> bool {
+        match self.properties.get(&BtPropertyType::LocalLeFeatures) {
+            Some(BluetoothProperty::LocalLeFeatures(llf)) => {
+                llf.activity_energy_info_supported != 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets the device a security event is about, which may not have been found.
+    fn security_event_device(&self, addr: &RawAddress) -> BluetoothDevice {
+        self.get_remote_device_if_found(addr)
+            .map(|d| d.info.clone())
+            .unwrap_or_else(|| BluetoothDevice::new(*addr, String::from("")))
+    }
+
+    /// Reports a suspected spoofing of the address of a bonded device that failed to use the key
+    /// of the bond.
+    fn check_address_spoofing(&self, addr: &RawAddress, transport: BtTransport) {
+        let bonded = self
+            .bonded_devices
+            .get(&self.main_address(addr))
+            .map_or(false, |d| d.bond_state == BtBondState::Bonded);
+        if !bonded {
+            return;
+        }
+
+        warn!("[{}]: Bonded device failed to use its key, possibly spoofed", addr.to_string());
+        let device = self.security_event_device(addr);
+        self.security_callbacks.for_all_callbacks(|callback| {
+            callback.on_address_spoofing_suspected(device.clone(), transport.clone());
+        });
+    }
+
+    fn set_adapter_state(&mut self, state: AdapterState) {
+        if self.adapter_state == state {
+            return;
+        }
+
+        debug!("Adapter state {:?} -> {:?}", self.adapter_state, state);
+        self.adapter_state = state;
+        self.for_all_callbacks(|callback| {
+            callback.on_adapter_state_changed(state);
+        });
+    }
+
+    /// Returns true if the adapter can take commands, logging why `action` is rejected otherwise.
+    fn check_adapter_on(&self, action: &str) -> bool {
+        if self.adapter_state != AdapterState::On {
+            warn!("Can't {} while the adapter is {:?}", action, self.adapter_state);
+            return false;
+        }
+
+        true
+    }
+
+    /// Drops the links to `address` on `transport`, or on both with `Auto`. Unless `force` is set,
+    /// the LE link is kept while GATT clients use it.
+    fn disconnect_links(
+        &self,
+        address: &RawAddress,
+        transport: BtTransport,
+        force: bool,
+    ) -> BtStatus {
+        let transports = match transport {
+            BtTransport::Auto => vec![BtTransport::Bredr, BtTransport::Le],
+            transport => vec![transport],
+        };
+
+        let intf = self.intf.lock().unwrap();
+        let mut disconnected = false;
+        let mut kept = false;
+        for transport in transports {
+            if transport == BtTransport::Le && !force {
+                let users = self.link_users.lock().unwrap().count(address);
+                if users > 0 {
+                    info!(
+                        "[{}]: Keeping the LE link used by {} GATT clients",
+                        address.to_string(),
+                        users
+                    );
+                    kept = true;
+                    continue;
+                }
+            }
+
+            disconnected |= intf.disconnect_acl(address, transport);
+        }
+
+        if disconnected {
+            BtStatus::Success
+        } else if kept {
+            BtStatus::Busy
+        } else {
+            BtStatus::RemoteDeviceDown
+        }
+    }
+
+    /// Returns true if a bond with the device is in progress.
+    fn is_bonding(&self, address: &RawAddress) -> bool {
+        self.get_remote_device_if_found(address)
+            .map_or(false, |d| d.bond_state == BtBondState::Bonding)
+    }
+
+    /// Returns true once the stack has finished turning off.
+    pub fn is_off(&self) -> bool {
+        self.state == BtState::Off
+    }
+
+    /// Prepares the adapter for the daemon to exit: stops discovery, disconnects all connected
+    /// devices and disables the stack. Disabling also tears down any scanning or advertising and
+    /// flushes the config to disk.
+    ///
+    /// Returns true if the stack is turning off or already off.
+    pub fn prepare_shutdown(&mut self) -> bool {
+        if self.state == BtState::Off {
+            return true;
+        }
+
+        if self.is_discovering {
+            self.cancel_discovery();
+        }
+
+        for device in self.get_connected_devices() {
+            self.disconnect_all_enabled_profiles(device);
+        }
+
+        self.disable()
+    }
+
+    pub(crate) fn is_blocked(&self, addr: &RawAddress) -> bool {
+        self.blocked_devices.contains(addr)
+    }
+
+    /// Returns false if the device opted out of being reconnected when its link is lost.
+    pub(crate) fn is_reconnect_enabled(&self, addr: &RawAddress) -> bool {
+        self.floss_config.get_device(addr).map_or(true, |stored| !stored.no_reconnect)
+    }
+
+    pub(crate) fn set_reconnect_enabled(&mut self, addr: RawAddress, enabled: bool) -> bool {
+        self.update_stored_device(addr, |stored| stored.no_reconnect = !enabled)
+    }
+
+    /// Updates the blocked flag of a device in the Floss config and saves it.
+    fn store_blocked(&mut self, addr: RawAddress, blocked: bool) -> bool {
+        self.update_stored_device(addr, |stored| stored.blocked = blocked)
+    }
+
+    /// Applies `update` to the Floss config entry of a device and saves the config. Entries left
+    /// with nothing but defaults are removed.
+    fn update_stored_device<F: FnOnce(&mut StoredDevice)>(
+        &mut self,
+        addr: RawAddress,
+        update: F,
+    ) -> bool {
+        let mut stored = self
+            .floss_config
+            .get_device(&addr)
+            .unwrap_or(StoredDevice { address: addr, ..Default::default() });
+        update(&mut stored);
+
+        if stored == (StoredDevice { address: addr, ..Default::default() }) {
+            self.floss_config.remove_device(&addr);
+        } else {
+            self.floss_config.set_device(&stored);
+        }
+
+        match self.floss_config.save() {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to save {}: {}", device_store::FLOSS_CONFIG_PATH, e);
+                false
+            }
+        }
+    }
+
+    /// Returns whether `profile` is enabled on the adapter and allowed by policy.
+    pub fn is_profile_enabled(&self, profile: &Profile) -> bool {
+        self.uuid_helper.is_profile_enabled(profile)
+    }
+
+    /// Returns whether `profile` is enabled on the adapter and hasn't been disabled for the
+    /// device at `addr`.
+    pub fn is_profile_enabled_for_device(&self, addr: &RawAddress, profile: &Profile) -> bool {
+        self.is_profile_enabled(profile) && !self.get_disabled_profiles(addr).contains(profile)
+    }
+
+    /// Returns the profiles disabled for a device with `set_profile_enabled`.
+    fn get_disabled_profiles(&self, addr: &RawAddress) -> HashSet<Profile> {
+        self.floss_config.get_device(addr).map_or(HashSet::new(), |stored| {
+            stored
+                .disabled_profiles
+                .iter()
+                .filter_map(|uuid| UuidHelper::from_string(uuid.as_str()))
+                .filter_map(|uuid| self.uuid_helper.is_known_profile(&uuid).cloned())
+                .collect()
+        })
+    }
+
+    /// Enables only the profiles allowed by `allowed_services` (all default profiles if empty)
+    /// and disconnects any profile connection that is no longer allowed.
+    pub fn toggle_enabled_profiles(&mut self, allowed_services: &HashSet<Uuid128Bit>) {
+        self.uuid_helper.apply_allowlist(allowed_services);
+
+        for device in self.get_connected_devices() {
+            let mut addr = device.address;
+
+            let blocked: HashSet<Profile> = self
+                .get_remote_uuids(device.clone())
+                .iter()
+                .filter_map(|uuid| self.uuid_helper.is_known_profile(uuid).cloned())
+                .filter(|p| !self.uuid_helper.is_profile_enabled(p))
+                .collect();
+
+            if blocked.contains(&Profile::Hid) || blocked.contains(&Profile::Hogp) {
+                if let Some(hh) = self.hh.as_ref() {
+                    hh.disconnect(&mut addr);
+                }
+            }
+
+            if blocked.iter().any(|p| {
+                matches!(p, Profile::A2dpSink | Profile::A2dpSource | Profile::Hfp | Profile::Hsp)
+            }) {
+                let txl = self.tx.clone();
+                let address = device.address.to_string();
+                topstack::get_runtime().spawn(async move {
+                    let _ = txl.send(Message::Media(MediaActions::Disconnect(address))).await;
+                });
+            }
+        }
+    }
+
+    pub fn dispatch_hid_host_callbacks(&mut self, cb: HHCallbacks) {
+        if let HHCallbacks::ConnectionState(mut addr, state) = cb {
+            let device = BluetoothDevice::new(addr, String::from(""));
+            let hid_profiles: Vec<Profile> = self
+                .get_remote_uuids(device)
+                .iter()
+                .filter_map(|uuid| self.uuid_helper.is_known_profile(uuid))
+                .filter(|p| matches!(p, Profile::Hid | Profile::Hogp))
+                .cloned()
+                .collect();
+            // The HID host serves both HID over BR/EDR and over GATT.
+            let profile = match hid_profiles.contains(&Profile::Hid) {
+                false if hid_profiles.contains(&Profile::Hogp) => Profile::Hogp,
+                _ => Profile::Hid,
+            };
+
+            let state = match state {
+                BthhConnectionState::Connected => ProfileConnectionState::Connected,
+                BthhConnectionState::Connecting => ProfileConnectionState::Connecting,
+                BthhConnectionState::Disconnected => ProfileConnectionState::Disconnected,
+                BthhConnectionState::Disconnecting => ProfileConnectionState::Disconnecting,
+                BthhConnectionState::Unknown => return,
+            };
+
+            // Reject HID connections from devices whose HID profiles are blocked by policy.
+            if state == ProfileConnectionState::Connected
+                && !hid_profiles.iter().all(|p| self.is_profile_enabled_for_device(&addr, p))
+            {
+                info!("[{}]: HID connection is not allowed", addr.to_string());
+                self.hh.as_ref().unwrap().disconnect(&mut addr);
+                self.update_profile_connection_state(
+                    addr,
+                    profile,
+                    ProfileConnectionState::Disconnected,
+                    ProfileConnectionReason::NotAllowed,
+                );
+                return;
+            }
+
+            self.update_profile_connection_state(
+                addr,
+                profile,
+                state,
+                ProfileConnectionReason::None,
+            );
+        }
+    }
+
+    /// Reports the new state of a profile connection to the connection callbacks.
+    ///
+    /// Repeated states are dropped, so profiles can forward every state they get. A connection
+    /// that goes from connecting straight to disconnected is reported as failed. Once a profile
+    /// is reported disconnected, a late disconnection of it isn't reported again.
+    pub(crate) fn update_profile_connection_state(
+        &mut self,
+        addr: RawAddress,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    ) {
+        let addr = self.main_address(&addr);
+        let previous = match state {
+            ProfileConnectionState::Disconnected => self.profile_states.remove(&(addr, profile)),
+            _ => self.profile_states.insert((addr, profile), state),
+        };
+        if previous == Some(state)
+            || (previous.is_none()
+                && state == ProfileConnectionState::Disconnected
+                && reason == ProfileConnectionReason::None)
+        {
+            return;
+        }
+
+        let reason = match (previous, state, reason) {
+            (
+                Some(ProfileConnectionState::Connecting),
+                ProfileConnectionState::Disconnected,
+                ProfileConnectionReason::None,
+            ) => ProfileConnectionReason::ConnectFailed,
+            _ => reason,
+        };
+
+        let device = match self.get_remote_device_if_found(&addr) {
+            Some(context) => context.info.clone(),
+            None => BluetoothDevice::new(addr, String::from("")),
+        };
+        debug!("[{}]: {:?} {:?} ({:?})", addr.to_string(), profile, state, reason);
+        self.for_all_connection_callbacks(|callback| {
+            callback.on_profile_connection_state_changed(device.clone(), profile, state, reason);
+        });
+
+        if state == ProfileConnectionState::Connected {
+            let done = match self.connect_all_attempts.get(&addr) {
+                Some(attempt) => {
+                    !attempt.profiles.is_empty()
+                        && attempt.profiles.iter().all(|p| self.is_profile_connected(&addr, p))
+                }
+                None => false,
+            };
+            if done {
+                self.finish_connect_all(&addr);
+            }
+        }
+    }
+
+    fn is_profile_connected(&self, addr: &RawAddress, profile: &Profile) -> bool {
+        self.profile_states.get(&(*addr, *profile)) == Some(&ProfileConnectionState::Connected)
+    }
+
+    /// Gets the profiles of a device that `connect_all_enabled_profiles` should connect, or None
+    /// if its UUIDs aren't known yet.
+    fn get_profiles_to_connect(&self, addr: &RawAddress) -> Option<Vec<Profile>> {
+        let known: HashSet<Profile> = self
+            .get_remote_uuids(BluetoothDevice::new(*addr, String::from("")))
+            .iter()
+            .filter_map(|uuid| self.uuid_helper.is_known_profile(uuid))
+            .cloned()
+            .collect();
+        if known.is_empty() {
+            return None;
+        }
+
+        let disabled = self.get_disabled_profiles(addr);
+        Some(
+            CONNECTABLE_PROFILES
+                .iter()
+                .filter(|p| known.contains(*p))
+                .filter(|p| self.uuid_helper.is_profile_enabled(p) && !disabled.contains(*p))
+                // HID over GATT is only used by devices without BR/EDR HID.
+                .filter(|p| **p != Profile::Hogp || !known.contains(&Profile::Hid))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Starts connecting the enabled profiles of a device after `delay`, dropping any connection
+    /// of them already in progress.
+    fn start_connect_all(&mut self, addr: RawAddress, delay: Duration) {
+        let addr = self.main_address(&addr);
+        self.cancel_connect_all(&addr);
+
+        let profiles = match self.get_profiles_to_connect(&addr) {
+            Some(profiles) => profiles,
+            None => {
+                // Try again once SDP is completed.
+                debug!("[{}]: Waiting for UUIDs to connect profiles", addr.to_string());
+                let attempt = ConnectAllAttempt {
+                    profiles: vec![],
+                    round: VecDeque::new(),
+                    attempts: 0,
+                    next_step: None,
+                };
+                self.connect_all_attempts.insert(addr, attempt);
+                return;
+            }
+        };
+
+        let nothing_to_connect = profiles.is_empty();
+        let attempt =
+            ConnectAllAttempt { profiles, round: VecDeque::new(), attempts: 0, next_step: None };
+        self.connect_all_attempts.insert(addr, attempt);
+        if nothing_to_connect {
+            self.finish_connect_all(&addr);
+        } else {
+            self.schedule_connect_all_step(addr, delay);
+        }
+    }
+
+    fn cancel_connect_all(&mut self, addr: &RawAddress) {
+        if let Some(mut attempt) = self.connect_all_attempts.remove(&self.main_address(addr)) {
+            attempt.cancel();
+        }
+    }
+
+    fn schedule_connect_all_step(&mut self, addr: RawAddress, delay: Duration) {
+        let attempt = match self.connect_all_attempts.get_mut(&addr) {
+            Some(attempt) => attempt,
+            None => return,
+        };
+
+        let txl = self.tx.clone();
+        attempt.next_step = Some(topstack::get_runtime().spawn(async move {
+            time::sleep(delay).await;
+            let _ = txl.send(Message::ConnectAllStep(addr)).await;
+        }));
+    }
+
+    /// Connects the next profile of a `connect_all_enabled_profiles` in progress. Rounds of
+    /// attempts go through the profiles that aren't connected yet, one at a time.
+    pub(crate) fn connect_all_step(&mut self, addr: RawAddress) {
+        let mut next = None;
+        let mut finished = false;
+        if let Some(attempt) = self.connect_all_attempts.get_mut(&addr) {
+            attempt.next_step = None;
+            let profile_states = &self.profile_states;
+            let pending: Vec<Profile> = attempt
+                .profiles
+                .iter()
+                .filter(|p| {
+                    profile_states.get(&(addr, **p)) != Some(&ProfileConnectionState::Connected)
+                })
+                .cloned()
+                .collect();
+
+            if attempt.round.is_empty() {
+                if pending.is_empty() || attempt.attempts >= PROFILE_CONNECT_ATTEMPTS {
+                    finished = true;
+                } else {
+                    attempt.attempts += 1;
+                    attempt.round = pending.iter().cloned().collect();
+                }
+            }
+
+            while let Some(profile) = attempt.round.pop_front() {
+                if pending.contains(&profile) {
+                    next = Some((profile, attempt.round.is_empty()));
+                    break;
+                }
+            }
+
+            if next.is_none() && !finished {
+                // Everything in the round connected since the last step.
+                finished = pending.is_empty();
+            }
+        }
+
+        if finished {
+            self.finish_connect_all(&addr);
+            return;
+        }
+
+        match next {
+            Some((profile, last)) => {
+                self.connect_profile(addr, profile);
+                let delay = if last { PROFILE_CONNECT_TIMEOUT } else { PROFILE_CONNECT_INTERVAL };
+                self.schedule_connect_all_step(addr, delay);
+            }
+            None => self.schedule_connect_all_step(addr, PROFILE_CONNECT_TIMEOUT),
+        }
+    }
+
+    fn connect_profile(&mut self, mut addr: RawAddress, profile: Profile) {
+        // Leave the peer alone if it is connecting the profile itself.
+        if self.profile_states.get(&(addr, profile)) == Some(&ProfileConnectionState::Connecting) {
+            debug!("[{}]: {:?} is already connecting", addr.to_string(), profile);
+            return;
+        }
+
+        debug!("[{}]: Connecting {:?}", addr.to_string(), profile);
+        match profile {
+            Profile::Hid | Profile::Hogp => {
+                self.hh.as_ref().unwrap().connect(&mut addr);
+            }
+            _ => {
+                let action = MediaActions::ConnectProfile(addr.to_string(), profile);
+                queue_message(&self.tx, Message::Media(action));
+            }
+        }
+    }
+
+    fn finish_connect_all(&mut self, addr: &RawAddress) {
+        let mut attempt = match self.connect_all_attempts.remove(addr) {
+            Some(attempt) => attempt,
+            None => return,
+        };
+        attempt.cancel();
+
+        let results: Vec<ProfileConnectResult> = attempt
+            .profiles
+            .iter()
+            .map(|p| ProfileConnectResult {
+                profile: *p,
+                status: match self.is_profile_connected(addr, p) {
+                    true => BtStatus::Success,
+                    false => BtStatus::Fail,
+                },
+            })
+            .collect();
+        info!("[{}]: Connected profiles: {:?}", addr.to_string(), results);
+
+        let device = match self.get_remote_device_if_found(addr) {
+            Some(context) => context.info.clone(),
+            None => BluetoothDevice::new(*addr, String::from("")),
+        };
+        self.for_all_connection_callbacks(|callback| {
+            callback.on_connect_all_complete(device.clone(), results.clone());
+        });
+    }
+
+    pub(crate) fn callback_disconnected(&mut self, id: u32, cb_type: BluetoothCallbackType) {
+        match cb_type {
+            BluetoothCallbackType::Adapter => {
+                self.callbacks.remove_callback(id);
+            }
+            BluetoothCallbackType::Connection => {
+                self.connection_callbacks.remove_callback(id);
+            }
+            BluetoothCallbackType::LinkPolicy => {
+                self.link_policy_callbacks.remove_callback(id);
+            }
+            BluetoothCallbackType::Security => {
+                self.security_callbacks.remove_callback(id);
+            }
+            BluetoothCallbackType::DiscoverySession => {
+                self.stop_discovery_session(id);
+            }
+        };
+    }
+
+    /// Gets the address a device is kept under, which differs from `address` when it is another
+    /// identity of a consolidated device.
+    fn main_address(&self, address: &RawAddress) -> RawAddress {
+        self.address_aliases.get(address).cloned().unwrap_or(*address)
+    }
+
+    /// Gets the addresses of all the identities of a device, the one it is kept under first.
+    fn identity_addresses(&self, address: &RawAddress) -> Vec<RawAddress> {
+        let main = self.main_address(address);
+        let secondary = self.address_aliases.iter().filter(|(_, m)| **m == main).map(|(a, _)| *a);
+        std::iter::once(main).chain(secondary).collect()
+    }
+
+    fn get_remote_device_if_found(&self, address: &RawAddress) -> Option<&BluetoothDeviceContext> {
+        let address = self.main_address(address);
+        self.bonded_devices.get(&address).or_else(|| self.found_devices.get(&address))
+    }
+
+    fn get_remote_device_if_found_mut(
+        &mut self,
+        address: &RawAddress,
+    ) -> Option<&mut BluetoothDeviceContext> {
+        let address = self.main_address(address);
+        match self.bonded_devices.get_mut(&address) {
+            None => self.found_devices.get_mut(&address),
             some => some,
         }
     }
 
+    /// Merges the `secondary` identity of a device into the one kept under `main`, so that
+    /// clients see a single device.
+    fn consolidate_device(
+        &mut self,
+        main: RawAddress,
+        secondary: RawAddress,
+        transport: BtTransport,
+    ) {
+        if main == secondary {
+            return;
+        }
+
+        self.address_aliases.insert(secondary, main);
+
+        let found_secondary = self.found_devices.remove(&secondary);
+        let secondary_device = self.bonded_devices.remove(&secondary).or(found_secondary);
+
+        if self.get_remote_device_if_found(&main).is_none() {
+            self.found_devices.insert(
+                main,
+                BluetoothDeviceContext::new(
+                    BtBondState::NotBonded,
+                    BtAclState::Disconnected,
+                    BluetoothDevice::new(main, String::from("")),
+                    Instant::now(),
+                    vec![],
+                ),
+            );
+        }
+
+        // If the merge made the device bonded, move it into the bonded list.
+        let bonded =
+            secondary_device.as_ref().map_or(false, |d| d.bond_state == BtBondState::Bonded);
+        if bonded && !self.bonded_devices.contains_key(&main) {
+            if let Some(device) = self.found_devices.remove(&main) {
+                self.bonded_devices.insert(main, device);
+            }
+        }
+
+        let intf = self.intf.clone();
+        let device = self.get_remote_device_if_found_mut(&main).unwrap();
+        device.info.transport = transport;
+        device.update_address_type(&intf.lock().unwrap());
+
+        if let Some(secondary_device) = secondary_device {
+            let cleared = secondary_device.info.clone();
+            device.merge(secondary_device);
+
+            self.for_all_callbacks(|callback| {
+                callback.on_device_cleared(cleared.clone());
+            });
+        }
+    }
+
     fn get_remote_device_property(
         &self,
         device: &BluetoothDevice,
@@ -457,24 +2371,19 @@ impl Bluetooth {
         device: &BluetoothDevice,
         property_type: BtPropertyType,
         property: BluetoothProperty,
-    ) -> Result<(), ()> {
+    ) -> BtStatus {
         let remote_device = match self.get_remote_device_if_found_mut(&device.address) {
             Some(d) => d,
             None => {
-                return Err(());
+                return BtStatus::InvalidParam;
             }
         };
 
-        let mut addr = RawAddress::from_string(device.address.clone());
-        if addr.is_none() {
-            return Err(());
-        }
-        let addr = addr.as_mut().unwrap();
+        let mut addr = device.address;
 
         // TODO: Determine why a callback isn't invoked to do this.
         remote_device.properties.insert(property_type, property.clone());
-        self.intf.lock().unwrap().set_remote_device_property(addr, property);
-        Ok(())
+        BtStatus::from(self.intf.lock().unwrap().set_remote_device_property(&mut addr, property))
     }
 
     /// Check whether found devices are still fresh. If they're outside the
@@ -483,6 +2392,8 @@ impl Bluetooth {
         // Drop previous joinhandle
         self.freshness_check = None;
 
+        self.check_lost_devices();
+
         // A found device is considered fresh if:
         // * It was last seen less than |FOUND_DEVICE_FRESHNESS| ago.
         // * It is currently connected.
@@ -503,18 +2414,228 @@ impl Bluetooth {
         self.found_devices.retain(|_, d| is_fresh(d, &now));
 
         for d in stale_devices {
-            self.for_all_callbacks(|callback| {
-                callback.on_device_cleared(d.clone());
+            self.found_device_cleared(d);
+        }
+
+        self.schedule_freshness_check();
+    }
+
+    /// Queues a freshness check unless one is already queued. It is queued while there are found
+    /// devices, and more often while discovery sessions wait for devices to be lost.
+    fn schedule_freshness_check(&mut self) {
+        if self.freshness_check.is_some()
+            || (self.found_devices.is_empty() && self.discovery_sessions.is_empty())
+        {
+            return;
+        }
+
+        let delay = match self.discovery_sessions.is_empty() {
+            true => FOUND_DEVICE_FRESHNESS,
+            false => DEVICE_LOST_CHECK_INTERVAL,
+        };
+        let txl = self.tx.clone();
+        self.freshness_check = Some(tokio::spawn(async move {
+            time::sleep(delay).await;
+            let _ = txl.send(Message::DeviceFreshnessCheck).await;
+        }));
+    }
+
+    /// Tells clients a device was removed from the found devices cache.
+    fn found_device_cleared(&mut self, device: BluetoothDevice) {
+        self.device_found_limiter.remove(&device.address);
+        self.name_requests.retain(|address| *address != device.address);
+
+        for (id, session) in self.discovery_sessions.iter_mut() {
+            if session.found.remove(&device.address) {
+                self.discovery_session_callbacks.call_by_id(*id, |callback| {
+                    callback.on_session_device_lost(*id, device.clone());
+                });
+            }
+        }
+
+        self.for_all_callbacks(|callback| {
+            callback.on_device_cleared(device.clone());
+        });
+    }
+
+    /// Makes room in the found devices cache by clearing the device seen the longest ago that
+    /// isn't connected.
+    fn evict_found_device(&mut self) {
+        let oldest = self
+            .found_devices
+            .values()
+            .filter(|d| d.acl_state != BtAclState::Connected)
+            .min_by_key(|d| d.last_seen)
+            .map(|d| d.info.address);
+
+        if let Some(device) = oldest.and_then(|address| self.found_devices.remove(&address)) {
+            debug!(
+                "[{}]: Cleared from the full found devices cache",
+                device.info.address.to_string()
+            );
+            self.found_device_cleared(device.info);
+        }
+    }
+
+    /// Tells the discovery sessions that haven't reported `address` found yet that it is.
+    fn report_session_device_found(&mut self, address: &RawAddress) {
+        let device = match self.found_devices.get(address) {
+            Some(d) => d.info.clone(),
+            None => return,
+        };
+
+        for (id, session) in self.discovery_sessions.iter_mut() {
+            if session.found.insert(*address) {
+                self.discovery_session_callbacks.call_by_id(*id, |callback| {
+                    callback.on_session_device_found(*id, device.clone());
+                });
+            }
+        }
+    }
+
+    /// Tells the discovery sessions about the devices they found that weren't seen for their lost
+    /// timeout. Connected devices are never lost.
+    fn check_lost_devices(&mut self) {
+        let now = Instant::now();
+        let (bonded_devices, found_devices) = (&self.bonded_devices, &self.found_devices);
+        for (id, session) in self.discovery_sessions.iter_mut() {
+            let lost: Vec<BluetoothDevice> = session
+                .found
+                .iter()
+                .filter_map(|address| {
+                    match bonded_devices.get(address).or(found_devices.get(address)) {
+                        Some(d) if d.acl_state == BtAclState::Connected => None,
+                        Some(d) if now < d.last_seen + session.lost_timeout => None,
+                        Some(d) => Some(d.info.clone()),
+                        None => Some(BluetoothDevice::new(*address, String::new())),
+                    }
+                })
+                .collect();
+
+            for device in lost {
+                session.found.remove(&device.address);
+                self.discovery_session_callbacks.call_by_id(*id, |callback| {
+                    callback.on_session_device_lost(*id, device);
+                });
+            }
+        }
+    }
+
+    /// Ends the discovery sessions when the adapter turns off.
+    fn end_discovery_sessions(&mut self) {
+        for (id, _) in self.discovery_sessions.drain() {
+            self.discovery_session_callbacks.call_by_id(id, |callback| {
+                callback.on_discovery_session_ended(id);
             });
+            self.discovery_session_callbacks.remove_callback(id);
         }
+    }
 
-        // If we have any fresh devices remaining, re-queue a freshness check.
-        if self.found_devices.len() > 0 {
-            let txl = self.tx.clone();
+    /// Sends the device found callbacks held back by rate limiting whose period is over.
+    pub(crate) fn flush_found_devices(&mut self) {
+        self.device_found_flush = None;
+
+        for address in self.device_found_limiter.take_due(Instant::now()) {
+            if let Some(device) = self.found_devices.get(&address) {
+                let info = device.found_device_info();
+                self.for_all_callbacks(|callback| {
+                    callback.on_device_found(device.info.clone(), info.clone());
+                });
+            }
+        }
+
+        self.schedule_found_devices_flush();
+    }
+
+    /// Applies a reloaded runtime config: the interop database is read again, and the discovery
+    /// scan changes if the config sets it.
+    pub(crate) fn apply_runtime_config(&mut self, config: &RuntimeConfig) {
+        self.interop = InteropDatabase::load_default();
+
+        if config.le_scan_interval.is_none() && config.le_scan_window.is_none() {
+            return;
+        }
+        let current = self.get_le_scan_params();
+        let params = LeScanParams {
+            interval: config.le_scan_interval.unwrap_or(current.interval),
+            window: config.le_scan_window.unwrap_or(current.window),
+            ..current
+        };
+        if params != current {
+            self.set_le_scan_params(params);
+        }
+    }
+
+    /// Queues a name request for a device found without a name, unless it already had one.
+    fn queue_name_request(&mut self, address: RawAddress) {
+        let wants_name = self.found_devices.get(&address).map_or(false, |device| {
+            device.info.name.is_empty()
+                && matches!(
+                    device.found_device_info().device_type,
+                    BtDeviceType::Bredr | BtDeviceType::Dual
+                )
+        });
+        if !wants_name
+            || self.name_requests.len() >= MAX_QUEUED_NAME_REQUESTS
+            || !self.names_requested.insert(address)
+        {
+            return;
+        }
+
+        self.name_requests.push_back(address);
+        self.schedule_name_request();
+    }
+
+    /// Sends the next name request once the interval since the last one is over.
+    fn schedule_name_request(&mut self) {
+        if self.name_request_pending.is_some()
+            || self.name_request_timer.is_some()
+            || self.name_requests.is_empty()
+        {
+            return;
+        }
+
+        let delay = NAME_REQUEST_INTERVAL.saturating_sub(self.last_name_request.elapsed());
+        let txl = self.tx.clone();
+        self.name_request_timer = Some(tokio::spawn(async move {
+            time::sleep(delay).await;
+            let _ = txl.send(Message::RemoteNameRequest).await;
+        }));
+    }
+
+    /// Sends the name request of the first device in the queue.
+    pub(crate) fn send_name_request(&mut self) {
+        self.name_request_timer = None;
+        if self.name_request_pending.is_some() || self.adapter_state != AdapterState::On {
+            return;
+        }
+
+        while let Some(address) = self.name_requests.pop_front() {
+            // Discovery may have reported the name since the device was queued.
+            let named = self
+                .get_remote_device_if_found(&address)
+                .map_or(false, |device| !device.info.name.is_empty());
+            if named && !self.name_fetches.contains(&address) {
+                continue;
+            }
+
+            debug!("[{}]: Requesting the remote name", address.to_string());
+            self.name_request_pending = Some(address);
+            self.intf.lock().unwrap().read_remote_name(&address);
+            return;
+        }
+    }
+
+    fn schedule_found_devices_flush(&mut self) {
+        if self.device_found_flush.is_some() {
+            return;
+        }
 
-            self.freshness_check = Some(tokio::spawn(async move {
-                time::sleep(FOUND_DEVICE_FRESHNESS).await;
-                let _ = txl.send(Message::DeviceFreshnessCheck).await;
+        if let Some(due) = self.device_found_limiter.next_due() {
+            let txl = self.tx.clone();
+            self.device_found_flush = Some(tokio::spawn(async move {
+                time::sleep(due.saturating_duration_since(Instant::now())).await;
+                let _ = txl.send(Message::FoundDevicesFlush).await;
             }));
         }
     }
@@ -539,6 +2660,18 @@ pub(crate) trait BtifBluetoothCallbacks {
     #[btif_callback(DiscoveryState)]
     fn discovery_state(&mut self, state: BtDiscoveryState);
 
+    #[btif_callback(RemoteName)]
+    fn remote_name(&mut self, addr: RawAddress, success: bool, name: String);
+
+    #[btif_callback(PinRequest)]
+    fn pin_request(
+        &mut self,
+        remote_addr: RawAddress,
+        remote_name: String,
+        cod: u32,
+        min_16_digit: bool,
+    );
+
     #[btif_callback(SspRequest)]
     fn ssp_request(
         &mut self,
@@ -576,6 +2709,37 @@ pub(crate) trait BtifBluetoothCallbacks {
         link_type: BtTransport,
         hci_reason: BtHciErrorCode,
     );
+
+    #[btif_callback(AddressConsolidate)]
+    fn address_consolidate(&mut self, main_addr: RawAddress, secondary_addr: RawAddress);
+
+    #[btif_callback(LeAddressAssociate)]
+    fn le_address_associate(&mut self, main_addr: RawAddress, secondary_addr: RawAddress);
+
+    #[btif_callback(AclRoleChanged)]
+    fn acl_role_changed(&mut self, status: BtStatus, addr: RawAddress, role: BtAclRole);
+
+    #[btif_callback(KeySizeViolation)]
+    fn key_size_violation(&mut self, addr: RawAddress, transport: BtTransport, key_size: u8);
+
+    #[btif_callback(EncryptionChange)]
+    fn encryption_change(
+        &mut self,
+        addr: RawAddress,
+        transport: BtTransport,
+        hci_status: BtHciErrorCode,
+        encrypted: bool,
+        key_refresh: bool,
+    );
+
+    #[btif_callback(AuthFailure)]
+    fn auth_failure(&mut self, addr: RawAddress, hci_reason: BtHciErrorCode);
+
+    #[btif_callback(EnergyInfo)]
+    fn energy_info(&mut self, info: BtActivityEnergyInfo);
+
+    #[btif_callback(VendorCommandComplete)]
+    fn vendor_command_complete(&mut self, opcode: u16, params: Vec<u8>);
 }
 
 #[btif_callbacks_dispatcher(Bluetooth, dispatch_sdp_callbacks, SdpCallbacks)]
@@ -591,13 +2755,23 @@ pub(crate) trait BtifSdpCallbacks {
     );
 }
 
+/// Makes up a PIN of `length` decimal digits, each as likely as the others.
+fn generate_pin(length: usize) -> String {
+    let mut pin = String::with_capacity(length);
+    while pin.len() < length {
+        let digits = crypto::random_bytes(length - pin.len())
+            .into_iter()
+            .filter(|b| *b < PIN_DIGIT_BYTE_LIMIT)
+            .map(|b| char::from(b'0' + b % 10));
+        pin.extend(digits);
+    }
+    pin
+}
+
 pub fn get_bt_dispatcher(tx: Sender<Message>) -> BaseCallbacksDispatcher {
     BaseCallbacksDispatcher {
         dispatch: Box::new(move |cb| {
-            let txl = tx.clone();
-            topstack::get_runtime().spawn(async move {
-                let _ = txl.send(Message::Base(cb)).await;
-            });
+            queue_message(&tx, Message::Base(cb));
         }),
     }
 }
@@ -613,11 +2787,52 @@ impl BtifBluetoothCallbacks for Bluetooth {
         }
 
         if self.state == BtState::On {
+            // The controller interface can only be taken once and its values are read from the
+            // controller when the stack comes up.
+            if self.controller.is_none() {
+                self.controller = Some(Controller::new());
+            }
+
             self.bluetooth_media.lock().unwrap().initialize();
+
+            let txl = self.tx.clone();
+            topstack::get_runtime().spawn(async move {
+                let _ = txl.send(Message::Gatt(GattActions::RegisterLocalServices)).await;
+                let _ = txl.send(Message::FastPair(FastPairActions::Start)).await;
+                let _ = txl.send(Message::AdvMonitor(AdvMonitorActions::Start)).await;
+                let _ = txl.send(Message::Opp(OppActions::StartServer)).await;
+                let _ = txl.send(Message::Coex(CoexActions::AdapterOn)).await;
+            });
+
+            self.apply_inquiry_scan_params();
+            self.apply_page_scan_params();
+            self.apply_le_scan_params();
+            self.apply_secure_connections_only();
+            self.apply_min_encryption_key_size();
+
+            self.read_firmware_version();
+            if self.vendor_diagnostics_enabled {
+                self.apply_vendor_diagnostics(true);
+            }
+
+            self.set_adapter_state(AdapterState::On);
         }
 
         if self.state == BtState::Off {
+            let txl = self.tx.clone();
+            topstack::get_runtime().spawn(async move {
+                let _ = txl.send(Message::FastPair(FastPairActions::Stop)).await;
+                let _ = txl.send(Message::AdvMonitor(AdvMonitorActions::Stop)).await;
+                let _ = txl.send(Message::Reconnect(ReconnectActions::Stop)).await;
+                let _ = txl.send(Message::Coex(CoexActions::AdapterOff)).await;
+            });
+
             self.properties.clear();
+            for (_, mut attempt) in self.connect_all_attempts.drain() {
+                attempt.cancel();
+            }
+            self.end_discovery_sessions();
+            self.set_adapter_state(AdapterState::Off);
         } else {
             // Trigger properties update
             self.intf.lock().unwrap().get_adapter_properties();
@@ -649,17 +2864,17 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 }
                 BluetoothProperty::AdapterBondedDevices(bondlist) => {
                     for addr in bondlist.iter() {
-                        let address = addr.to_string();
+                        queue_message(&self.tx, Message::Gatt(GattActions::SetBonded(*addr, true)));
 
                         // Update bonded state if already in the list. Otherwise create a new
                         // context with empty properties and name.
                         self.bonded_devices
-                            .entry(address.clone())
+                            .entry(*addr)
                             .and_modify(|d| d.bond_state = BtBondState::Bonded)
                             .or_insert(BluetoothDeviceContext::new(
                                 BtBondState::Bonded,
                                 BtAclState::Disconnected,
-                                BluetoothDevice::new(address.clone(), "".to_string()),
+                                BluetoothDevice::new(*addr, "".to_string()),
                                 Instant::now(),
                                 vec![],
                             ));
@@ -671,11 +2886,30 @@ impl BtifBluetoothCallbacks for Bluetooth {
                     });
                 }
                 BluetoothProperty::AdapterScanMode(mode) => {
+                    let discoverable = *mode == BtScanMode::ConnectableDiscoverable;
                     self.for_all_callbacks(|callback| {
-                        callback
-                            .on_discoverable_changed(*mode == BtScanMode::ConnectableDiscoverable);
+                        callback.on_discoverable_changed(discoverable);
+                    });
+
+                    // The stack writes its own scan activity when the scan mode changes.
+                    self.apply_inquiry_scan_params();
+                    self.apply_page_scan_params();
+
+                    let txl = self.tx.clone();
+                    topstack::get_runtime().spawn(async move {
+                        let _ = txl
+                            .send(Message::FastPair(FastPairActions::DiscoverableChanged(
+                                discoverable,
+                            )))
+                            .await;
                     });
                 }
+                BluetoothProperty::LocalLeFeatures(llf) => {
+                    self.radio
+                        .lock()
+                        .unwrap()
+                        .set_max_advertising_sets(llf.max_adv_instance.into());
+                }
                 _ => {}
             }
 
@@ -684,13 +2918,29 @@ impl BtifBluetoothCallbacks for Bluetooth {
     }
 
     fn device_found(&mut self, _n: i32, properties: Vec<BluetoothProperty>) {
-        let device = BluetoothDevice::from_properties(&properties);
-        let address = device.address.clone();
+        let mut device = BluetoothDevice::from_properties(&properties);
+        let address = self.main_address(&device.address);
+
+        // Another identity of a consolidated device keeps the address the device is known under.
+        let properties = match address == device.address {
+            true => properties,
+            false => {
+                device.address = address;
+                properties
+                    .into_iter()
+                    .filter(|p| !matches!(p, BluetoothProperty::BdAddr(_)))
+                    .collect()
+            }
+        };
 
         if let Some(existing) = self.found_devices.get_mut(&address) {
             existing.update_properties(properties);
             existing.seen();
         } else {
+            if self.found_devices.len() >= MAX_FOUND_DEVICES {
+                self.evict_found_device();
+            }
+
             let device_with_props = BluetoothDeviceContext::new(
                 BtBondState::NotBonded,
                 BtAclState::Disconnected,
@@ -698,13 +2948,31 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 Instant::now(),
                 properties,
             );
-            self.found_devices.insert(address.clone(), device_with_props);
+            self.found_devices.insert(address, device_with_props);
+        }
+
+        let intf = self.intf.clone();
+        if let Some(device) = self.found_devices.get_mut(&address) {
+            device.update_address_type(&intf.lock().unwrap());
+        }
+        self.queue_name_request(address);
+
+        // Sessions aren't rate limited: they are only told when a device is found or lost.
+        self.report_session_device_found(&address);
+        self.schedule_freshness_check();
+
+        // During discovery a device can be reported many times a second; the update that goes
+        // over the limit is sent later by `flush_found_devices`.
+        if !self.device_found_limiter.allow(&address, Instant::now()) {
+            self.schedule_found_devices_flush();
+            return;
         }
 
         let device = self.found_devices.get(&address).unwrap();
+        let info = device.found_device_info();
 
         self.for_all_callbacks(|callback| {
-            callback.on_device_found(device.info.clone());
+            callback.on_device_found(device.info.clone(), info.clone());
         });
     }
 
@@ -720,6 +2988,17 @@ impl BtifBluetoothCallbacks for Bluetooth {
         self.is_discovering = &state == &BtDiscoveryState::Started;
         if self.is_discovering {
             self.discovering_started = Instant::now();
+            self.names_requested.clear();
+            let params = self.le_scan_params.clone().unwrap_or_default();
+            self.activity.lock().unwrap().scan_started(
+                params.window,
+                params.interval,
+                self.discovering_started,
+            );
+        } else {
+            self.activity.lock().unwrap().scan_stopped(Instant::now());
+            self.radio.lock().unwrap().release(RadioUser::Discovery, RadioActivity::Scan);
+            self.apply_le_scan_params();
         }
 
         self.for_all_callbacks(|callback| {
@@ -732,6 +3011,39 @@ impl BtifBluetoothCallbacks for Bluetooth {
         if !is_discovering && self.freshness_check.is_none() {
             self.trigger_freshness_check();
         }
+
+        // Discovery times out on its own; discovery sessions keep it going until they stop.
+        let sessions = self.discovery_sessions.len();
+        if !is_discovering && sessions > 0 && self.start_discovery() != BtStatus::Success {
+            warn!("Failed to restart discovery for {} sessions", sessions);
+        }
+    }
+
+    fn remote_name(&mut self, addr: RawAddress, success: bool, name: String) {
+        if self.name_request_pending == Some(addr) {
+            self.name_request_pending = None;
+            self.last_name_request = Instant::now();
+        }
+
+        let success = success && !name.is_empty();
+        let address = self.main_address(&addr);
+        let device = match self.get_remote_device_if_found_mut(&address) {
+            Some(device) => {
+                if success {
+                    device.update_properties(vec![BluetoothProperty::BdName(name.clone())]);
+                }
+                device.info.clone()
+            }
+            None => BluetoothDevice::new(address, name),
+        };
+        debug!("[{}]: Remote name request done, success: {}", address.to_string(), success);
+
+        if self.name_fetches.remove(&address) || success {
+            self.for_all_callbacks(|callback| {
+                callback.on_remote_name_fetched(device.clone(), success);
+            });
+        }
+        self.schedule_name_request();
     }
 
     fn ssp_request(
@@ -742,11 +3054,27 @@ impl BtifBluetoothCallbacks for Bluetooth {
         variant: BtSspVariant,
         passkey: u32,
     ) {
+        if self.is_blocked(&remote_addr) {
+            info!("[{}]: Rejecting pairing from blocked device", remote_addr.to_string());
+            self.intf.lock().unwrap().ssp_reply(&remote_addr, variant, 0, 0);
+            return;
+        }
+
+        // A verified Fast Pair seeker confirms the passkey on behalf of the user.
+        if variant == BtSspVariant::PasskeyConfirmation {
+            let txl = self.tx.clone();
+            topstack::get_runtime().spawn(async move {
+                let _ = txl
+                    .send(Message::FastPair(FastPairActions::SspRequest(remote_addr, passkey)))
+                    .await;
+            });
+        }
+
         // Currently this supports many agent because we accept many callbacks.
         // TODO: We need a way to select the default agent.
         self.for_all_callbacks(|callback| {
             callback.on_ssp_request(
-                BluetoothDevice::new(remote_addr.to_string(), remote_name.clone()),
+                BluetoothDevice::new(remote_addr, remote_name.clone()),
                 cod,
                 variant.clone(),
                 passkey,
@@ -754,26 +3082,96 @@ impl BtifBluetoothCallbacks for Bluetooth {
         });
     }
 
+    fn pin_request(
+        &mut self,
+        remote_addr: RawAddress,
+        remote_name: String,
+        cod: u32,
+        min_16_digit: bool,
+    ) {
+        if self.is_blocked(&remote_addr) {
+            info!("[{}]: Rejecting pairing from blocked device", remote_addr.to_string());
+            let mut pin_code: BtPinCode = BtPinCode { pin: [0; 16] };
+            self.intf.lock().unwrap().pin_reply(&remote_addr, 0, 0, &mut pin_code);
+            return;
+        }
+
+        let device = BluetoothDevice::new(remote_addr, remote_name);
+        if self.is_secure_connections_only() {
+            info!("[{}]: Rejecting legacy pairing", remote_addr.to_string());
+            let mut pin_code: BtPinCode = BtPinCode { pin: [0; 16] };
+            self.intf.lock().unwrap().pin_reply(&remote_addr, 0, 0, &mut pin_code);
+            self.for_all_callbacks(|callback| {
+                callback.on_pairing_rejected(device.clone(), PairingRejectedReason::LegacyPairing);
+            });
+            return;
+        }
+
+        let keyboard = cod & COD_MAJOR_CLASS_MASK == COD_MAJOR_CLASS_PERIPHERAL
+            && cod & COD_MINOR_CLASS_KEYBOARD != 0;
+        if keyboard {
+            // Keyboards take the PIN from the user, so one is made up for them to type.
+            let length = if min_16_digit { 16 } else { KEYBOARD_PIN_LENGTH };
+            let pincode = generate_pin(length);
+
+            let mut pin_code: BtPinCode = BtPinCode { pin: [0; 16] };
+            pin_code.pin[..length].copy_from_slice(pincode.as_bytes());
+            let status =
+                self.intf.lock().unwrap().pin_reply(&remote_addr, 1, length as u8, &mut pin_code);
+            if BtStatus::from(status) != BtStatus::Success {
+                warn!("[{}]: Failed to answer the PIN request", remote_addr.to_string());
+                return;
+            }
+
+            self.for_all_callbacks(|callback| {
+                callback.on_pin_display(device.clone(), pincode.clone());
+            });
+            return;
+        }
+
+        self.for_all_callbacks(|callback| {
+            callback.on_pin_request(device.clone(), cod, min_16_digit);
+        });
+    }
+
     fn bond_state(
         &mut self,
         status: BtStatus,
         addr: RawAddress,
         bond_state: BtBondState,
-        _fail_reason: i32,
+        fail_reason: i32,
     ) {
-        let address = addr.to_string();
+        let address = self.main_address(&addr);
+        let mut newly_bonded = false;
+
+        // Looked up before a failed bond drops the device from the bonded list.
+        let rejected_device = if bond_state == BtBondState::NotBonded
+            && fail_reason == HCI_ERR_PAIRING_NOT_ALLOWED
+            && self.is_secure_connections_only()
+        {
+            let device = self.get_remote_device_if_found(&address).map(|d| d.info.clone());
+            Some(device.unwrap_or_else(|| BluetoothDevice::new(address, String::from(""))))
+        } else {
+            None
+        };
+
+        if bond_state == BtBondState::Bonding && self.is_blocked(&addr) {
+            info!("[{}]: Cancelling bonding with blocked device", address.to_string());
+            self.intf.lock().unwrap().cancel_bond(&addr);
+        }
 
         // Easy case of not bonded -- we remove the device from the bonded list and change the bond
         // state in the found list (in case it was previously bonding).
         if &bond_state == &BtBondState::NotBonded {
             self.bonded_devices.remove(&address);
-            self.found_devices
-                .entry(address.clone())
-                .and_modify(|d| d.bond_state = bond_state.clone());
+            self.address_aliases.retain(|_, main| main != &address);
+            self.found_devices.entry(address).and_modify(|d| d.bond_state = bond_state.clone());
         }
         // We will only insert into the bonded list after bonding is complete
         else if &bond_state == &BtBondState::Bonded && !self.bonded_devices.contains_key(&address)
         {
+            newly_bonded = true;
+
             // We either need to construct a new BluetoothDeviceContext or grab it from the found
             // devices map
             let device = match self.found_devices.remove(&address) {
@@ -784,28 +3182,51 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 None => BluetoothDeviceContext::new(
                     bond_state.clone(),
                     BtAclState::Disconnected,
-                    BluetoothDevice::new(address.clone(), "".to_string()),
+                    BluetoothDevice::new(address, "".to_string()),
                     Instant::now(),
                     vec![],
                 ),
             };
 
-            self.bonded_devices.insert(address.clone(), device);
+            self.bonded_devices.insert(address, device);
         } else {
             // If we're bonding, we need to update the found devices list
-            self.found_devices
-                .entry(address.clone())
-                .and_modify(|d| d.bond_state = bond_state.clone());
+            self.found_devices.entry(address).and_modify(|d| d.bond_state = bond_state.clone());
+        }
+
+        match bond_state {
+            BtBondState::Bonded | BtBondState::NotBonded => {
+                let bonded = bond_state == BtBondState::Bonded;
+                queue_message(&self.tx, Message::Gatt(GattActions::SetBonded(address, bonded)));
+            }
+            _ => {}
         }
 
         // Send bond state changed notifications
         self.for_all_callbacks(|callback| {
             callback.on_bond_state_changed(
                 status.to_u32().unwrap(),
-                address.clone(),
+                address.to_string(),
                 bond_state.to_u32().unwrap(),
             );
         });
+
+        if let Some(device) = rejected_device {
+            self.for_all_callbacks(|callback| {
+                callback.on_pairing_rejected(
+                    device.clone(),
+                    PairingRejectedReason::SecureConnectionsUnsupported,
+                );
+            });
+        }
+
+        match bond_state {
+            BtBondState::Bonded if newly_bonded && self.profiles_ready => {
+                self.start_connect_all(address, CONNECT_AFTER_BOND_DELAY);
+            }
+            BtBondState::NotBonded => self.cancel_connect_all(&address),
+            _ => {}
+        }
     }
 
     fn remote_device_properties_changed(
@@ -815,15 +3236,16 @@ impl BtifBluetoothCallbacks for Bluetooth {
         _num_properties: i32,
         properties: Vec<BluetoothProperty>,
     ) {
-        let address = addr.to_string();
+        let address = self.main_address(&addr);
+        let intf = self.intf.clone();
         let device = match self.get_remote_device_if_found_mut(&address) {
             None => {
                 self.found_devices.insert(
-                    address.clone(),
+                    address,
                     BluetoothDeviceContext::new(
                         BtBondState::NotBonded,
                         BtAclState::Disconnected,
-                        BluetoothDevice::new(address.clone(), String::from("")),
+                        BluetoothDevice::new(address, String::from("")),
                         Instant::now(),
                         vec![],
                     ),
@@ -836,13 +3258,28 @@ impl BtifBluetoothCallbacks for Bluetooth {
 
         match device {
             Some(d) => {
+                let uuids_changed =
+                    properties.iter().any(|p| matches!(p, BluetoothProperty::Uuids(_)));
                 d.update_properties(properties);
+                d.update_address_type(&intf.lock().unwrap());
                 d.seen();
 
                 let info = d.info.clone();
-                let uuids = self.get_remote_uuids(info.clone());
-                if self.wait_to_connect && uuids.len() > 0 {
-                    self.connect_all_enabled_profiles(info);
+                if uuids_changed {
+                    let txl = self.tx.clone();
+                    let device = info.clone();
+                    topstack::get_runtime().spawn(async move {
+                        let _ = txl.send(Message::RemoteUuidsChanged(device)).await;
+                    });
+                }
+
+                // A connection waiting for SDP results can go on.
+                let waiting = self
+                    .connect_all_attempts
+                    .get(&address)
+                    .map_or(false, |attempt| attempt.profiles.is_empty());
+                if waiting && !self.get_remote_uuids(info.clone()).is_empty() {
+                    self.start_connect_all(info.address, Duration::from_millis(0));
                 }
             }
             None => (),
@@ -854,23 +3291,23 @@ impl BtifBluetoothCallbacks for Bluetooth {
         status: BtStatus,
         addr: RawAddress,
         state: BtAclState,
-        _link_type: BtTransport,
-        _hci_reason: BtHciErrorCode,
+        link_type: BtTransport,
+        hci_reason: BtHciErrorCode,
     ) {
         if status != BtStatus::Success {
             warn!("Connection to [{}] failed. Status: {:?}", addr.to_string(), status);
             return;
         }
 
-        let address = addr.to_string();
+        let address = self.main_address(&addr);
         let device = match self.get_remote_device_if_found_mut(&address) {
             None => {
                 self.found_devices.insert(
-                    address.clone(),
+                    address,
                     BluetoothDeviceContext::new(
                         BtBondState::NotBonded,
                         BtAclState::Disconnected,
-                        BluetoothDevice::new(address.clone(), String::from("")),
+                        BluetoothDevice::new(address, String::from("")),
                         Instant::now(),
                         vec![],
                     ),
@@ -881,21 +3318,52 @@ impl BtifBluetoothCallbacks for Bluetooth {
             some => some,
         };
 
+        let mut changed = false;
         match device {
             Some(found) => {
                 // Only notify if there's been a change in state
                 let prev_state = &found.acl_state;
                 if prev_state != &state {
+                    changed = true;
                     let device = found.info.clone();
                     found.acl_state = state.clone();
+                    found.connected_at = match state {
+                        BtAclState::Connected => Some(Instant::now()),
+                        BtAclState::Disconnected => None,
+                    };
 
                     match state {
+                        BtAclState::Connected if self.is_blocked(&addr) => {
+                            info!(
+                                "[{}]: Dropping connection from blocked device",
+                                address.to_string()
+                            );
+                            self.disconnect_all_profiles(device);
+                        }
+                        BtAclState::Connected if self.rejects_connection(&address) => {
+                            info!(
+                                "[{}]: Dropping {:?} connection from device that isn't bonded",
+                                address.to_string(),
+                                link_type
+                            );
+                            self.disconnect_all_profiles(device.clone());
+                            self.security_callbacks.for_all_callbacks(|callback| {
+                                callback.on_unbonded_connection_rejected(
+                                    device.clone(),
+                                    link_type.clone(),
+                                );
+                            });
+                        }
                         BtAclState::Connected => {
+                            let action = ReconnectActions::Connected(device.address);
+                            queue_message(&self.tx, Message::Reconnect(action));
                             self.for_all_connection_callbacks(|callback| {
                                 callback.on_device_connected(device.clone());
                             });
                         }
                         BtAclState::Disconnected => {
+                            let action = ReconnectActions::Disconnected(device.clone(), hci_reason);
+                            queue_message(&self.tx, Message::Reconnect(action));
                             self.for_all_connection_callbacks(|callback| {
                                 callback.on_device_disconnected(device.clone());
                             });
@@ -905,65 +3373,244 @@ impl BtifBluetoothCallbacks for Bluetooth {
             }
             None => (),
         };
+
+        if changed {
+            let count = self.get_connected_devices().len() as u32;
+            self.for_all_callbacks(|callback| {
+                callback.on_connected_count_changed(count);
+            });
+        }
+    }
+
+    fn address_consolidate(&mut self, main_addr: RawAddress, secondary_addr: RawAddress) {
+        debug!(
+            "[{}]: Consolidating dual-mode identity {}",
+            main_addr.to_string(),
+            secondary_addr.to_string()
+        );
+        self.consolidate_device(main_addr, secondary_addr, BtTransport::Auto);
+    }
+
+    fn le_address_associate(&mut self, main_addr: RawAddress, secondary_addr: RawAddress) {
+        debug!(
+            "[{}]: Associating LE identity address {}",
+            main_addr.to_string(),
+            secondary_addr.to_string()
+        );
+        self.consolidate_device(main_addr, secondary_addr, BtTransport::Le);
+    }
+
+    fn acl_role_changed(&mut self, status: BtStatus, addr: RawAddress, role: BtAclRole) {
+        debug!("[{}]: Role changed to {:?}: {:?}", addr.to_string(), role, status);
+
+        let device = self
+            .get_remote_device_if_found(&addr)
+            .map(|d| d.info.clone())
+            .unwrap_or_else(|| BluetoothDevice::new(addr, String::from("")));
+        self.link_policy_callbacks.for_all_callbacks(|callback| {
+            callback.on_role_changed(device.clone(), role, status.clone());
+        });
+    }
+
+    fn key_size_violation(&mut self, addr: RawAddress, transport: BtTransport, key_size: u8) {
+        warn!(
+            "[{}]: Rejected encryption key of {} octets on {:?}",
+            addr.to_string(),
+            key_size,
+            transport
+        );
+
+        let device = self
+            .get_remote_device_if_found(&addr)
+            .map(|d| d.info.clone())
+            .unwrap_or_else(|| BluetoothDevice::new(addr, String::from("")));
+        self.for_all_callbacks(|callback| {
+            callback.on_encryption_key_size_rejected(device.clone(), transport.clone(), key_size);
+        });
+    }
+
+    fn encryption_change(
+        &mut self,
+        addr: RawAddress,
+        transport: BtTransport,
+        hci_status: BtHciErrorCode,
+        encrypted: bool,
+        key_refresh: bool,
+    ) {
+        debug!(
+            "[{}]: Encryption {} on {:?}: status {:#04x}, encrypted {}",
+            addr.to_string(),
+            if key_refresh { "key refresh" } else { "change" },
+            transport,
+            hci_status,
+            encrypted
+        );
+
+        let device = self.security_event_device(&addr);
+        self.security_callbacks.for_all_callbacks(|callback| {
+            if key_refresh {
+                callback.on_encryption_key_refreshed(device.clone(), transport.clone(), hci_status);
+            } else {
+                callback.on_encryption_changed(
+                    device.clone(),
+                    transport.clone(),
+                    hci_status,
+                    encrypted,
+                );
+            }
+        });
+
+        // LE links of bonded devices are encrypted with the bond's key without authenticating
+        // first, so a missing key only shows here. BR/EDR ones are checked by `auth_failure`.
+        if transport == BtTransport::Le
+            && (hci_status == HCI_ERR_KEY_MISSING || hci_status == HCI_ERR_AUTH_FAILURE)
+        {
+            self.check_address_spoofing(&addr, transport);
+        }
+    }
+
+    fn auth_failure(&mut self, addr: RawAddress, hci_reason: BtHciErrorCode) {
+        warn!("[{}]: Authentication failed: {:#04x}", addr.to_string(), hci_reason);
+
+        let device = self.security_event_device(&addr);
+        self.security_callbacks.for_all_callbacks(|callback| {
+            callback.on_authentication_failed(device.clone(), hci_reason);
+        });
+
+        if hci_reason == HCI_ERR_KEY_MISSING || hci_reason == HCI_ERR_AUTH_FAILURE {
+            self.check_address_spoofing(&addr, BtTransport::Bredr);
+        }
+    }
+
+    fn energy_info(&mut self, info: BtActivityEnergyInfo) {
+        self.activity.lock().unwrap().controller_activity_received(info);
+    }
+
+    fn vendor_command_complete(&mut self, opcode: u16, params: Vec<u8>) {
+        if let Some(version) = vendor::parse_firmware_version(opcode, &params) {
+            info!("Controller firmware: {}", version);
+            self.firmware_version = version;
+        } else if params.first() != Some(&0) {
+            warn!("Vendor command {:#06x} failed with status {:?}", opcode, params.first());
+        }
     }
 }
 
 // TODO: Add unit tests for this implementation
 impl IBluetooth for Bluetooth {
-    fn register_callback(&mut self, mut callback: Box<dyn IBluetoothCallback + Send>) {
-        let tx = self.tx.clone();
-
-        let id = callback.register_disconnect(Box::new(move |cb_id| {
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                let _result = tx
-                    .send(Message::BluetoothCallbackDisconnected(
-                        cb_id,
-                        BluetoothCallbackType::Adapter,
-                    ))
-                    .await;
-            });
-        }));
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
 
-        self.callbacks.insert(id, callback);
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.callbacks.remove_callback(callback_id)
     }
 
     fn register_connection_callback(
         &mut self,
-        mut callback: Box<dyn IBluetoothConnectionCallback + Send>,
+        callback: Box<dyn IBluetoothConnectionCallback + Send>,
     ) -> u32 {
-        let tx = self.tx.clone();
-
-        let id = callback.register_disconnect(Box::new(move |cb_id| {
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                let _ = tx
-                    .send(Message::BluetoothCallbackDisconnected(
-                        cb_id,
-                        BluetoothCallbackType::Connection,
-                    ))
-                    .await;
-            });
-        }));
+        self.connection_callbacks.add_callback(callback)
+    }
 
-        self.connection_callbacks.insert(id, callback);
+    fn unregister_connection_callback(&mut self, callback_id: u32) -> bool {
+        self.connection_callbacks.remove_callback(callback_id)
+    }
 
-        id
+    fn replay_adapter_state(&self, callback_id: u32) -> bool {
+        self.callbacks.call_by_id(callback_id, |callback| {
+            callback.on_adapter_state_changed(self.adapter_state);
+            if self.adapter_state != AdapterState::On {
+                return;
+            }
+
+            callback.on_address_changed(self.get_address());
+            callback.on_name_changed(self.get_name());
+            callback.on_discoverable_changed(self.get_discoverable());
+            callback.on_discovering_changed(self.is_discovering);
+            for device in self.bonded_devices.values() {
+                if device.bond_state == BtBondState::Bonded {
+                    callback.on_bond_state_changed(
+                        BtStatus::Success.to_u32().unwrap(),
+                        device.info.address.to_string(),
+                        BtBondState::Bonded.to_u32().unwrap(),
+                    );
+                }
+            }
+            callback.on_connected_count_changed(self.get_connected_devices().len() as u32);
+        })
     }
 
-    fn unregister_connection_callback(&mut self, callback_id: u32) -> bool {
-        match self.connection_callbacks.get_mut(&callback_id) {
-            Some(cb) => cb.unregister(callback_id),
-            None => false,
-        }
+    fn replay_connection_state(&self, callback_id: u32) -> bool {
+        self.connection_callbacks.call_by_id(callback_id, |callback| {
+            for device in self.get_connected_devices() {
+                callback.on_device_connected(device);
+            }
+            for ((addr, profile), state) in self.profile_states.iter() {
+                let device = match self.get_remote_device_if_found(addr) {
+                    Some(context) => context.info.clone(),
+                    None => BluetoothDevice::new(*addr, String::from("")),
+                };
+                callback.on_profile_connection_state_changed(
+                    device,
+                    *profile,
+                    *state,
+                    ProfileConnectionReason::None,
+                );
+            }
+        })
+    }
+
+    fn register_security_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothSecurityCallback + Send>,
+    ) -> u32 {
+        self.security_callbacks.add_callback(callback)
+    }
+
+    fn unregister_security_callback(&mut self, callback_id: u32) -> bool {
+        self.security_callbacks.remove_callback(callback_id)
     }
 
     fn enable(&mut self) -> bool {
-        self.intf.lock().unwrap().enable() == 0
+        match self.adapter_state {
+            AdapterState::Off => {}
+            AdapterState::TurningOn | AdapterState::On => return true,
+            state => {
+                warn!("Can't enable the adapter while it is {:?}", state);
+                return false;
+            }
+        }
+
+        self.apply_le_address_policy();
+        if self.intf.lock().unwrap().enable() != 0 {
+            return false;
+        }
+
+        self.set_adapter_state(AdapterState::TurningOn);
+        true
     }
 
     fn disable(&mut self) -> bool {
-        self.intf.lock().unwrap().disable() == 0
+        match self.adapter_state {
+            AdapterState::TurningOn | AdapterState::On => {}
+            AdapterState::Off | AdapterState::TurningOff => return true,
+            AdapterState::Recovering => {
+                warn!("Can't disable the adapter while it is recovering");
+                return false;
+            }
+        }
+
+        if self.intf.lock().unwrap().disable() != 0 {
+            return false;
+        }
+
+        self.set_adapter_state(AdapterState::TurningOff);
+        true
+    }
+
+    fn get_adapter_state(&self) -> AdapterState {
+        self.adapter_state
     }
 
     fn get_address(&self) -> String {
@@ -995,8 +3642,14 @@ impl IBluetooth for Bluetooth {
         }
     }
 
-    fn set_name(&self, name: String) -> bool {
-        self.intf.lock().unwrap().set_adapter_property(BluetoothProperty::BdName(name)) == 0
+    fn set_name(&self, name: String) -> BtStatus {
+        if !self.check_adapter_on("set the name") {
+            return BtStatus::NotReady;
+        }
+
+        BtStatus::from(
+            self.intf.lock().unwrap().set_adapter_property(BluetoothProperty::BdName(name)),
+        )
     }
 
     fn get_bluetooth_class(&self) -> u32 {
@@ -1009,8 +3662,14 @@ impl IBluetooth for Bluetooth {
         }
     }
 
-    fn set_bluetooth_class(&self, cod: u32) -> bool {
-        self.intf.lock().unwrap().set_adapter_property(BluetoothProperty::ClassOfDevice(cod)) == 0
+    fn set_bluetooth_class(&self, cod: u32) -> BtStatus {
+        if !self.check_adapter_on("set the class of device") {
+            return BtStatus::NotReady;
+        }
+
+        BtStatus::from(
+            self.intf.lock().unwrap().set_adapter_property(BluetoothProperty::ClassOfDevice(cod)),
+        )
     }
 
     fn get_discoverable(&self) -> bool {
@@ -1026,119 +3685,423 @@ impl IBluetooth for Bluetooth {
         }
     }
 
-    fn get_discoverable_timeout(&self) -> u32 {
-        match self.properties.get(&BtPropertyType::AdapterDiscoverableTimeout) {
-            Some(prop) => match prop {
-                BluetoothProperty::AdapterDiscoverableTimeout(timeout) => timeout.clone(),
-                _ => 0,
-            },
-            _ => 0,
+    fn get_discoverable_timeout(&self) -> u32 {
+        match self.properties.get(&BtPropertyType::AdapterDiscoverableTimeout) {
+            Some(prop) => match prop {
+                BluetoothProperty::AdapterDiscoverableTimeout(timeout) => timeout.clone(),
+                _ => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    fn set_discoverable(&self, mode: bool, duration: u32) -> BtStatus {
+        if !self.check_adapter_on("change discoverability") {
+            return BtStatus::NotReady;
+        }
+
+        self.intf
+            .lock()
+            .unwrap()
+            .set_adapter_property(BluetoothProperty::AdapterDiscoverableTimeout(duration));
+        BtStatus::from(self.intf.lock().unwrap().set_adapter_property(
+            BluetoothProperty::AdapterScanMode(if mode {
+                BtScanMode::ConnectableDiscoverable
+            } else {
+                if self.is_connectable {
+                    BtScanMode::Connectable
+                } else {
+                    BtScanMode::None_
+                }
+            }),
+        ))
+    }
+
+    fn is_multi_advertisement_supported(&self) -> bool {
+        match self.properties.get(&BtPropertyType::LocalLeFeatures) {
+            Some(prop) => match prop {
+                BluetoothProperty::LocalLeFeatures(llf) => {
+                    llf.max_adv_instance >= MIN_ADV_INSTANCES_FOR_MULTI_ADV
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn is_le_extended_advertising_supported(&self) -> bool {
+        match self.properties.get(&BtPropertyType::LocalLeFeatures) {
+            Some(prop) => match prop {
+                BluetoothProperty::LocalLeFeatures(llf) => llf.le_extended_advertising_supported,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn get_adapter_properties(&self) -> AdapterProperties {
+        AdapterProperties {
+            address: self.get_address(),
+            name: self.get_name(),
+            bluetooth_class: self.get_bluetooth_class(),
+            discoverable: self.get_discoverable(),
+            discoverable_timeout: self.get_discoverable_timeout(),
+            discovering: self.is_discovering(),
+            connected_count: self.get_connected_devices().len() as u32,
+        }
+    }
+
+    fn get_adapter_capabilities(&self) -> AdapterCapabilities {
+        let mut caps = AdapterCapabilities::default();
+        if self.adapter_state != AdapterState::On {
+            return caps;
+        }
+
+        if let Some(BluetoothProperty::LocalLeFeatures(llf)) =
+            self.properties.get(&BtPropertyType::LocalLeFeatures)
+        {
+            caps.le_extended_advertising_supported = llf.le_extended_advertising_supported;
+            caps.le_2m_phy_supported = llf.le_2m_phy_supported;
+            caps.le_coded_phy_supported = llf.le_coded_phy_supported;
+            caps.le_periodic_advertising_supported = llf.le_periodic_advertising_supported;
+            caps.le_connected_isochronous_stream_central_supported =
+                llf.le_connected_isochronous_stream_central_supported;
+            caps.le_isochronous_broadcast_supported = llf.le_isochronous_broadcast_supported;
+            caps.max_advertising_sets = llf.max_adv_instance.into();
+            caps.max_advertising_data_length = llf.le_maximum_advertising_data_length.into();
+            caps.max_advertising_filters = llf.max_adv_filter_supported.into();
+        }
+
+        if let Some(controller) = self.controller.as_ref() {
+            caps.acl_buffer_count_classic = controller.get_acl_buffer_count_classic().into();
+            caps.acl_buffer_count_le = controller.get_acl_buffer_count_ble().into();
+            caps.le_accept_list_size = controller.get_ble_acceptlist_size().into();
+        }
+
+        caps.max_connections = MAX_ACL_CONNECTIONS;
+        caps
+    }
+
+    fn start_discovery(&self) -> BtStatus {
+        if !self.check_adapter_on("start discovery") {
+            return BtStatus::NotReady;
+        }
+
+        self.request_discovery_scan();
+        let status = BtStatus::from(self.intf.lock().unwrap().start_discovery());
+        if status != BtStatus::Success && !self.is_discovering {
+            self.radio.lock().unwrap().release(RadioUser::Discovery, RadioActivity::Scan);
+        }
+        status
+    }
+
+    fn cancel_discovery(&self) -> BtStatus {
+        BtStatus::from(self.intf.lock().unwrap().cancel_discovery())
+    }
+
+    fn start_discovery_session(
+        &mut self,
+        callback: Box<dyn IBluetoothDiscoverySessionCallback + Send>,
+        lost_timeout_ms: u32,
+    ) -> u32 {
+        if !self.check_adapter_on("start a discovery session") {
+            return 0;
+        }
+
+        if !(MIN_DEVICE_LOST_TIMEOUT_MS..=MAX_DEVICE_LOST_TIMEOUT_MS).contains(&lost_timeout_ms) {
+            warn!("Invalid discovery session lost timeout: {} ms", lost_timeout_ms);
+            return 0;
+        }
+
+        let id = self.discovery_session_callbacks.add_callback(callback);
+        self.discovery_sessions.insert(
+            id,
+            DiscoverySession {
+                lost_timeout: Duration::from_millis(lost_timeout_ms.into()),
+                found: HashSet::new(),
+            },
+        );
+
+        // A queued check may be waiting for the longer freshness period.
+        if let Some(check) = self.freshness_check.take() {
+            check.abort();
+        }
+        self.schedule_freshness_check();
+
+        if !self.is_discovering && self.start_discovery() != BtStatus::Success {
+            warn!("Discovery session {} couldn't start discovery yet", id);
+        }
+        id
+    }
+
+    fn stop_discovery_session(&mut self, session_id: u32) -> bool {
+        if self.discovery_sessions.remove(&session_id).is_none() {
+            return false;
+        }
+
+        self.discovery_session_callbacks.remove_callback(session_id);
+        if self.discovery_sessions.is_empty() && self.is_discovering {
+            self.cancel_discovery();
+        }
+        true
+    }
+
+    fn is_discovering(&self) -> bool {
+        self.is_discovering
+    }
+
+    fn get_discovery_end_millis(&self) -> u64 {
+        if !self.is_discovering {
+            return 0;
+        }
+
+        let elapsed_ms = self.discovering_started.elapsed().as_millis() as u64;
+        if elapsed_ms >= DEFAULT_DISCOVERY_TIMEOUT_MS {
+            0
+        } else {
+            DEFAULT_DISCOVERY_TIMEOUT_MS - elapsed_ms
+        }
+    }
+
+    fn set_device_found_rate_limit(&mut self, limit: u32) -> bool {
+        self.device_found_limiter.set_limit(limit);
+        true
+    }
+
+    fn get_device_found_rate_limit(&self) -> u32 {
+        self.device_found_limiter.get_limit()
+    }
+
+    fn set_inquiry_scan_params(&mut self, params: ClassicScanParams) -> BtStatus {
+        if !params.is_valid() {
+            warn!("Invalid inquiry scan parameters {:?}", params);
+            return BtStatus::InvalidParam;
+        }
+
+        self.inquiry_scan_params = Some(params);
+        if self.adapter_state == AdapterState::On {
+            self.apply_inquiry_scan_params();
+        }
+        BtStatus::Success
+    }
+
+    fn get_inquiry_scan_params(&self) -> ClassicScanParams {
+        self.inquiry_scan_params.clone().unwrap_or_else(ClassicScanParams::default_inquiry)
+    }
+
+    fn set_page_scan_params(&mut self, params: ClassicScanParams) -> BtStatus {
+        if !params.is_valid() {
+            warn!("Invalid page scan parameters {:?}", params);
+            return BtStatus::InvalidParam;
+        }
+
+        self.page_scan_params = Some(params);
+        if self.adapter_state == AdapterState::On {
+            self.apply_page_scan_params();
+        }
+        BtStatus::Success
+    }
+
+    fn get_page_scan_params(&self) -> ClassicScanParams {
+        self.page_scan_params.clone().unwrap_or_else(ClassicScanParams::default_page)
+    }
+
+    fn set_le_scan_params(&mut self, params: LeScanParams) -> BtStatus {
+        if !params.is_valid() {
+            warn!("Invalid LE scan parameters {:?}", params);
+            return BtStatus::InvalidParam;
+        }
+
+        self.le_scan_params = Some(params);
+        if self.is_discovering {
+            self.request_discovery_scan();
+        } else if self.adapter_state == AdapterState::On {
+            self.apply_le_scan_params();
+        }
+        BtStatus::Success
+    }
+
+    fn get_le_scan_params(&self) -> LeScanParams {
+        self.le_scan_params.clone().unwrap_or_default()
+    }
+
+    fn get_le_privacy_features(&self) -> LePrivacyFeatures {
+        let mut features = LePrivacyFeatures::default();
+        if self.adapter_state != AdapterState::On {
+            return features;
+        }
+
+        if let Some(BluetoothProperty::LocalLeFeatures(llf)) =
+            self.properties.get(&BtPropertyType::LocalLeFeatures)
+        {
+            features.rpa_offload_supported = llf.rpa_offload_supported != 0;
+            features.privacy_enabled = llf.local_privacy_enabled != 0;
+        }
+
+        if let Some(controller) = self.controller.as_ref() {
+            features.ll_privacy_supported = controller.supports_ble_privacy();
+            features.resolving_list_size = controller.get_ble_resolving_list_max_size().into();
+        }
+        features
+    }
+
+    fn set_le_static_address(&mut self, address: String) -> BtStatus {
+        let address = match RawAddress::from_string(address.clone()) {
+            Some(address) if is_static_random_address(&address) => address,
+            _ => {
+                warn!("{} isn't a static random address", address);
+                return BtStatus::InvalidParam;
+            }
+        };
+
+        self.store_le_address_policy(false, 0, Some(address))
+    }
+
+    fn set_le_privacy(&mut self, enabled: bool, rpa_timeout: u32) -> BtStatus {
+        if rpa_timeout > MAX_RPA_TIMEOUT {
+            warn!("Invalid RPA timeout {}", rpa_timeout);
+            return BtStatus::InvalidParam;
         }
+
+        self.store_le_address_policy(enabled, if enabled { rpa_timeout } else { 0 }, None)
     }
 
-    fn set_discoverable(&self, mode: bool, duration: u32) -> bool {
-        self.intf
-            .lock()
-            .unwrap()
-            .set_adapter_property(BluetoothProperty::AdapterDiscoverableTimeout(duration));
-        self.intf.lock().unwrap().set_adapter_property(BluetoothProperty::AdapterScanMode(
-            if mode {
-                BtScanMode::ConnectableDiscoverable
-            } else {
-                if self.is_connectable {
-                    BtScanMode::Connectable
-                } else {
-                    BtScanMode::None_
-                }
-            },
-        )) == 0
+    fn get_le_own_address_type(&self) -> BtAddrType {
+        if self.adapter_state != AdapterState::On {
+            return BtAddrType::Unknown;
+        }
+
+        self.intf.lock().unwrap().get_le_own_address_type()
     }
 
-    fn is_multi_advertisement_supported(&self) -> bool {
-        match self.properties.get(&BtPropertyType::LocalLeFeatures) {
-            Some(prop) => match prop {
-                BluetoothProperty::LocalLeFeatures(llf) => {
-                    llf.max_adv_instance >= MIN_ADV_INSTANCES_FOR_MULTI_ADV
-                }
-                _ => false,
-            },
-            _ => false,
+    fn set_secure_connections_only(&mut self, enabled: bool) -> BtStatus {
+        if self.adapter_state == AdapterState::On
+            && !self.intf.lock().unwrap().set_secure_connections_only(enabled)
+        {
+            warn!("Secure Connections Only mode isn't supported by the controller");
+            return BtStatus::Unsupported;
+        }
+
+        let config = &mut self.floss_config;
+        config.set_adapter_value(
+            KEY_SECURE_CONNECTIONS_ONLY,
+            if enabled { Some(String::from("1")) } else { None },
+        );
+        match config.save() {
+            Ok(()) => BtStatus::Success,
+            Err(e) => {
+                warn!("Failed to save {}: {}", device_store::FLOSS_CONFIG_PATH, e);
+                BtStatus::Fail
+            }
         }
     }
 
-    fn is_le_extended_advertising_supported(&self) -> bool {
-        match self.properties.get(&BtPropertyType::LocalLeFeatures) {
-            Some(prop) => match prop {
-                BluetoothProperty::LocalLeFeatures(llf) => llf.le_extended_advertising_supported,
-                _ => false,
-            },
-            _ => false,
+    fn get_secure_connections_only(&self) -> bool {
+        self.is_secure_connections_only()
+    }
+
+    fn set_bonded_only_connections(&mut self, enabled: bool) -> BtStatus {
+        let config = &mut self.floss_config;
+        config.set_adapter_value(
+            KEY_BONDED_ONLY_CONNECTIONS,
+            if enabled { Some(String::from("1")) } else { None },
+        );
+        match config.save() {
+            Ok(()) => BtStatus::Success,
+            Err(e) => {
+                warn!("Failed to save {}: {}", device_store::FLOSS_CONFIG_PATH, e);
+                BtStatus::Fail
+            }
         }
     }
 
-    fn start_discovery(&self) -> bool {
-        self.intf.lock().unwrap().start_discovery() == 0
+    fn get_bonded_only_connections(&self) -> bool {
+        self.is_bonded_only_connections()
     }
 
-    fn cancel_discovery(&self) -> bool {
-        self.intf.lock().unwrap().cancel_discovery() == 0
+    fn set_min_encryption_key_size(&mut self, key_size: u8) -> BtStatus {
+        if key_size < MIN_ENCRYPTION_KEY_SIZE || key_size > MAX_ENCRYPTION_KEY_SIZE {
+            return BtStatus::InvalidParam;
+        }
+
+        if self.adapter_state == AdapterState::On {
+            self.intf.lock().unwrap().set_min_encryption_key_size(key_size);
+        }
+
+        let config = &mut self.floss_config;
+        config.set_adapter_value(
+            KEY_MIN_ENCRYPTION_KEY_SIZE,
+            if key_size != MIN_ENCRYPTION_KEY_SIZE { Some(key_size.to_string()) } else { None },
+        );
+        match config.save() {
+            Ok(()) => BtStatus::Success,
+            Err(e) => {
+                warn!("Failed to save {}: {}", device_store::FLOSS_CONFIG_PATH, e);
+                BtStatus::Fail
+            }
+        }
     }
 
-    fn is_discovering(&self) -> bool {
-        self.is_discovering
+    fn get_min_encryption_key_size(&self) -> u8 {
+        self.min_encryption_key_size()
     }
 
-    fn get_discovery_end_millis(&self) -> u64 {
-        if !self.is_discovering {
+    fn get_encryption_key_size(&self, device: BluetoothDevice, transport: BtTransport) -> u8 {
+        if self.adapter_state != AdapterState::On {
             return 0;
         }
 
-        let elapsed_ms = self.discovering_started.elapsed().as_millis() as u64;
-        if elapsed_ms >= DEFAULT_DISCOVERY_TIMEOUT_MS {
-            0
-        } else {
-            DEFAULT_DISCOVERY_TIMEOUT_MS - elapsed_ms
-        }
+        self.intf.lock().unwrap().get_encryption_key_size(&device.address, transport)
     }
 
-    fn create_bond(&self, device: BluetoothDevice, transport: BtTransport) -> bool {
-        let addr = RawAddress::from_string(device.address.clone());
+    fn create_bond(&self, device: BluetoothDevice, transport: BtTransport) -> BtStatus {
+        if !self.check_adapter_on("create a bond") {
+            return BtStatus::NotReady;
+        }
 
-        if addr.is_none() {
-            warn!("Can't create bond. Address {} is not valid", device.address);
-            return false;
+        let address = self.main_address(&device.address);
+        if self.bonded_devices.get(&address).map_or(false, |d| d.bond_state == BtBondState::Bonded)
+        {
+            warn!("Can't create a bond. Device {} is already bonded.", address.to_string());
+            return BtStatus::InvalidParam;
         }
 
-        let address = addr.unwrap();
+        // The stack pairs with one device at a time.
+        if let Some(bonding) = self
+            .bonded_devices
+            .values()
+            .chain(self.found_devices.values())
+            .find(|d| d.bond_state == BtBondState::Bonding)
+        {
+            warn!(
+                "Can't create a bond. Already bonding with {}.",
+                bonding.info.address.to_string()
+            );
+            return BtStatus::Busy;
+        }
 
         // BREDR connection won't work when Inquiry is in progress.
         self.cancel_discovery();
 
-        self.intf.lock().unwrap().create_bond(&address, transport) == 0
+        BtStatus::from(self.intf.lock().unwrap().create_bond(&device.address, transport))
     }
 
-    fn cancel_bond_process(&self, device: BluetoothDevice) -> bool {
-        let addr = RawAddress::from_string(device.address.clone());
-
-        if addr.is_none() {
-            warn!("Can't cancel bond. Address {} is not valid.", device.address);
-            return false;
+    fn cancel_bond_process(&self, device: BluetoothDevice) -> BtStatus {
+        if !self.is_bonding(&device.address) {
+            warn!("Can't cancel bonding. Device {} isn't bonding.", device.address.to_string());
+            return BtStatus::InvalidParam;
         }
 
-        let address = addr.unwrap();
-        self.intf.lock().unwrap().cancel_bond(&address) == 0
+        BtStatus::from(self.intf.lock().unwrap().cancel_bond(&device.address))
     }
 
-    fn remove_bond(&self, device: BluetoothDevice) -> bool {
-        let addr = RawAddress::from_string(device.address.clone());
-
-        if addr.is_none() {
-            warn!("Can't remove bond. Address {} is not valid.", device.address);
-            return false;
+    fn remove_bond(&self, device: BluetoothDevice) -> BtStatus {
+        if !self.bonded_devices.contains_key(&self.main_address(&device.address)) {
+            warn!("Can't remove bond. Device {} isn't bonded.", device.address.to_string());
+            return BtStatus::InvalidParam;
         }
 
-        let address = addr.unwrap();
-        self.intf.lock().unwrap().remove_bond(&address) == 0
+        BtStatus::from(self.intf.lock().unwrap().remove_bond(&device.address))
     }
 
     fn get_bonded_devices(&self) -> Vec<BluetoothDevice> {
@@ -1152,95 +4115,70 @@ impl IBluetooth for Bluetooth {
     }
 
     fn get_bond_state(&self, device: BluetoothDevice) -> u32 {
-        match self.bonded_devices.get(&device.address) {
+        match self.bonded_devices.get(&self.main_address(&device.address)) {
             Some(device) => device.bond_state.to_u32().unwrap(),
             None => BtBondState::NotBonded.to_u32().unwrap(),
         }
     }
 
-    fn set_pin(&self, device: BluetoothDevice, accept: bool, pin_code: Vec<u8>) -> bool {
-        let addr = RawAddress::from_string(device.address.clone());
-
-        if addr.is_none() {
-            warn!("Can't set pin. Address {} is not valid.", device.address);
-            return false;
-        }
-
-        let is_bonding = match self.found_devices.get(&device.address) {
-            Some(d) => d.bond_state == BtBondState::Bonding,
-            None => false,
-        };
-
-        if !is_bonding {
-            warn!("Can't set pin. Device {} isn't bonding.", device.address);
-            return false;
+    fn set_pin(&self, device: BluetoothDevice, accept: bool, pin_code: Vec<u8>) -> BtStatus {
+        if !self.is_bonding(&device.address) {
+            warn!("Can't set pin. Device {} isn't bonding.", device.address.to_string());
+            return BtStatus::InvalidParam;
         }
 
         let mut btpin: BtPinCode = BtPinCode { pin: [0; 16] };
-        btpin.pin.copy_from_slice(pin_code.as_slice());
+        if pin_code.len() > btpin.pin.len() {
+            warn!("Can't set pin. A pin is at most {} bytes long.", btpin.pin.len());
+            return BtStatus::InvalidParam;
+        }
+        btpin.pin[..pin_code.len()].copy_from_slice(pin_code.as_slice());
 
-        self.intf.lock().unwrap().pin_reply(
-            &addr.unwrap(),
+        BtStatus::from(self.intf.lock().unwrap().pin_reply(
+            &device.address,
             accept as u8,
             pin_code.len() as u8,
             &mut btpin,
-        ) == 0
+        ))
     }
 
-    fn set_passkey(&self, device: BluetoothDevice, accept: bool, passkey: Vec<u8>) -> bool {
-        let addr = RawAddress::from_string(device.address.clone());
-
-        if addr.is_none() {
-            warn!("Can't set passkey. Address {} is not valid.", device.address);
-            return false;
-        }
-
-        let is_bonding = match self.found_devices.get(&device.address) {
-            Some(d) => d.bond_state == BtBondState::Bonding,
-            None => false,
-        };
-
-        if !is_bonding {
-            warn!("Can't set passkey. Device {} isn't bonding.", device.address);
-            return false;
+    fn set_passkey(&self, device: BluetoothDevice, accept: bool, passkey: Vec<u8>) -> BtStatus {
+        if !self.is_bonding(&device.address) {
+            warn!("Can't set passkey. Device {} isn't bonding.", device.address.to_string());
+            return BtStatus::InvalidParam;
         }
 
         let mut tmp: [u8; 4] = [0; 4];
+        if passkey.len() != tmp.len() {
+            warn!("Can't set passkey. A passkey is {} bytes long.", tmp.len());
+            return BtStatus::InvalidParam;
+        }
         tmp.copy_from_slice(passkey.as_slice());
         let passkey = u32::from_ne_bytes(tmp);
 
-        self.intf.lock().unwrap().ssp_reply(
-            &addr.unwrap(),
+        BtStatus::from(self.intf.lock().unwrap().ssp_reply(
+            &device.address,
             BtSspVariant::PasskeyEntry,
             accept as u8,
             passkey,
-        ) == 0
+        ))
     }
 
-    fn set_pairing_confirmation(&self, device: BluetoothDevice, accept: bool) -> bool {
-        let addr = RawAddress::from_string(device.address.clone());
-
-        if addr.is_none() {
-            warn!("Can't set pairing confirmation. Address {} is not valid.", device.address);
-            return false;
-        }
-
-        let is_bonding = match self.found_devices.get(&device.address) {
-            Some(d) => d.bond_state == BtBondState::Bonding,
-            None => false,
-        };
-
-        if !is_bonding {
-            warn!("Can't set pairing confirmation. Device {} isn't bonding.", device.address);
-            return false;
+    fn set_pairing_confirmation(&self, device: BluetoothDevice, accept: bool) -> BtStatus {
+        if !self.is_bonding(&device.address) {
+            warn!(
+                "Can't set pairing confirmation. Device {} isn't bonding.",
+                device.address.to_string()
+            );
+            return BtStatus::InvalidParam;
         }
 
-        self.intf.lock().unwrap().ssp_reply(
-            &addr.unwrap(),
+        BtStatus::from(self.intf.lock().unwrap().ssp_reply(
+            &device.address,
             BtSspVariant::PasskeyConfirmation,
             accept as u8,
             0,
-        ) == 0
+        ))
     }
 
     fn get_remote_name(&self, device: BluetoothDevice) -> String {
@@ -1250,6 +4188,23 @@ impl IBluetooth for Bluetooth {
         }
     }
 
+    fn fetch_remote_name(&mut self, device: BluetoothDevice) -> bool {
+        if self.adapter_state != AdapterState::On
+            || self.get_remote_type(device.clone()) == BtDeviceType::Ble
+        {
+            return false;
+        }
+
+        let address = self.main_address(&device.address);
+        self.name_fetches.insert(address);
+        if self.name_request_pending != Some(address) {
+            self.name_requests.retain(|queued| *queued != address);
+            self.name_requests.push_front(address);
+            self.schedule_name_request();
+        }
+        true
+    }
+
     fn get_remote_type(&self, device: BluetoothDevice) -> BtDeviceType {
         match self.get_remote_device_property(&device, &BtPropertyType::TypeOfDevice) {
             Some(BluetoothProperty::TypeOfDevice(device_type)) => return device_type,
@@ -1264,12 +4219,12 @@ impl IBluetooth for Bluetooth {
         }
     }
 
-    fn set_remote_alias(&mut self, device: BluetoothDevice, new_alias: String) {
-        let _ = self.set_remote_device_property(
+    fn set_remote_alias(&mut self, device: BluetoothDevice, new_alias: String) -> BtStatus {
+        self.set_remote_device_property(
             &device,
             BtPropertyType::RemoteFriendlyName,
             BluetoothProperty::RemoteFriendlyName(new_alias),
-        );
+        )
     }
 
     fn get_remote_class(&self, device: BluetoothDevice) -> u32 {
@@ -1279,15 +4234,73 @@ impl IBluetooth for Bluetooth {
         }
     }
 
-    fn get_connection_state(&self, device: BluetoothDevice) -> u32 {
-        let addr = RawAddress::from_string(device.address.clone());
+    fn get_remote_version_info(&self, device: BluetoothDevice) -> RemoteVersionInfo {
+        // The version comes from whichever link is up.
+        let intf = self.intf.lock().unwrap();
+        self.identity_addresses(&device.address)
+            .iter()
+            .find_map(|addr| intf.get_remote_version(addr))
+            .map_or(RemoteVersionInfo::default(), |(lmp_version, manufacturer, lmp_subversion)| {
+                RemoteVersionInfo { valid: true, lmp_version, manufacturer, lmp_subversion }
+            })
+    }
 
-        if addr.is_none() {
-            warn!("Can't check connection state. Address {} is not valid.", device.address);
-            return 0;
+    fn get_remote_supported_features(&self, device: BluetoothDevice) -> RemoteFeatures {
+        let intf = self.intf.lock().unwrap();
+        let mut features = RemoteFeatures::default();
+        for addr in self.identity_addresses(&device.address) {
+            if features.lmp_features.is_empty() {
+                features.lmp_features = intf.get_remote_features(&addr);
+            }
+            if features.le_features.is_empty() {
+                features.le_features = intf.get_remote_le_features(&addr);
+            }
+        }
+        features
+    }
+
+    fn get_remote_device_info(&self, device: BluetoothDevice) -> RemoteDeviceInfo {
+        let context = match self.get_remote_device_if_found(&device.address) {
+            Some(context) => context,
+            None => return RemoteDeviceInfo::unknown(device),
+        };
+
+        let now = Instant::now();
+        let age_ms = |at: &Instant| now.duration_since(*at).as_millis() as u64;
+        let found = context.found_device_info();
+        RemoteDeviceInfo {
+            known: true,
+            device: context.info.clone(),
+            alias: self.get_remote_alias(device.clone()),
+            device_type: found.device_type,
+            class_of_device: found.class_of_device,
+            bond_state: context.bond_state.to_u32().unwrap(),
+            uuids: found.uuids,
+            rssi_history: context
+                .rssi_history
+                .iter()
+                .map(|(at, rssi)| RssiSample { rssi: *rssi, age_ms: age_ms(at) })
+                .collect(),
+            last_seen_ms: age_ms(&context.last_seen),
+            connection_state: self.get_connection_state(device.clone()),
+            connected_ms: context.connected_at.as_ref().map_or(0, age_ms),
+            link_role: self.get_link_role(device.clone()),
+            bredr_key_size: self.get_encryption_key_size(device.clone(), BtTransport::Bredr),
+            le_key_size: self.get_encryption_key_size(device, BtTransport::Le),
         }
+    }
 
-        self.intf.lock().unwrap().get_connection_state(&addr.unwrap())
+    fn get_connected_devices(&self) -> Vec<BluetoothDevice> {
+        self.bonded_devices
+            .values()
+            .chain(self.found_devices.values())
+            .filter(|d| d.acl_state == BtAclState::Connected)
+            .map(|d| d.info.clone())
+            .collect()
+    }
+
+    fn get_connection_state(&self, device: BluetoothDevice) -> u32 {
+        self.intf.lock().unwrap().get_connection_state(&device.address)
     }
 
     fn get_profile_connection_state(&self, profile: Profile) -> u32 {
@@ -1312,50 +4325,58 @@ impl IBluetooth for Bluetooth {
         }
     }
 
-    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> bool {
+    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> BtStatus {
         if self.get_remote_device_if_found(&device.address).is_none() {
-            warn!("Won't fetch UUIDs on unknown device {}", device.address);
-            return false;
+            warn!("Won't fetch UUIDs on unknown device {}", device.address.to_string());
+            return BtStatus::InvalidParam;
         }
 
-        let addr = RawAddress::from_string(device.address.clone());
-        if addr.is_none() {
-            warn!("Can't fetch UUIDs. Address {} is not valid.", device.address);
-            return false;
+        let mut addr = device.address;
+        let name = self.get_remote_device_if_found(&device.address).map(|d| d.info.name.clone());
+        if self.interop.is_match(InteropFeature::DelaySdp, &addr, name.as_deref()) {
+            debug!("[{}]: Delaying SDP for interop", device.address.to_string());
+            let intf = self.intf.clone();
+            topstack::get_runtime().spawn(async move {
+                time::sleep(INTEROP_SDP_DELAY).await;
+                intf.lock().unwrap().get_remote_services(&mut addr, BtTransport::Auto);
+            });
+            return BtStatus::Success;
         }
-        self.intf.lock().unwrap().get_remote_services(&mut addr.unwrap(), BtTransport::Auto) == 0
+
+        BtStatus::from(self.intf.lock().unwrap().get_remote_services(&mut addr, BtTransport::Auto))
     }
 
-    fn sdp_search(&self, device: BluetoothDevice, uuid: Uuid128Bit) -> bool {
+    fn sdp_search(&self, device: BluetoothDevice, uuid: Uuid128Bit) -> BtStatus {
         if self.sdp.is_none() {
             warn!("SDP is not initialized. Can't do SDP search.");
-            return false;
-        }
-
-        let addr = RawAddress::from_string(device.address.clone());
-        if addr.is_none() {
-            warn!("Can't SDP search. Address {} is not valid.", device.address);
-            return false;
+            return BtStatus::NotReady;
         }
 
+        let mut addr = device.address;
         let uu = Uuid { uu: uuid };
-        self.sdp.as_ref().unwrap().sdp_search(&mut addr.unwrap(), &uu) == BtStatus::Success
+        self.sdp.as_ref().unwrap().sdp_search(&mut addr, &uu)
     }
 
-    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
+    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> BtStatus {
         // Profile init must be complete before this api is callable
-        if !self.profiles_ready {
-            return false;
+        if !self.profiles_ready || !self.check_adapter_on("connect profiles") {
+            return BtStatus::NotReady;
         }
 
-        let addr = RawAddress::from_string(device.address.clone());
-        if addr.is_none() {
-            warn!("Can't connect profiles on invalid address [{}]", &device.address);
-            return false;
+        self.start_connect_all(device.address, Duration::from_millis(0));
+        BtStatus::Success
+    }
+
+    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> BtStatus {
+        // No need to retry connection as we are going to disconnect all enabled profiles.
+        self.cancel_connect_all(&device.address);
+
+        if !self.profiles_ready {
+            return BtStatus::NotReady;
         }
 
-        // Check all remote uuids to see if they match enabled profiles and connect them.
-        let mut has_enabled_uuids = false;
+        let mut addr = device.address;
+
         let uuids = self.get_remote_uuids(device.clone());
         for uuid in uuids.iter() {
             match self.uuid_helper.is_known_profile(uuid) {
@@ -1363,78 +4384,252 @@ impl IBluetooth for Bluetooth {
                     if self.uuid_helper.is_profile_enabled(&p) {
                         match p {
                             Profile::Hid | Profile::Hogp => {
-                                self.hh.as_ref().unwrap().connect(&mut addr.unwrap());
+                                self.hh.as_ref().unwrap().disconnect(&mut addr);
                             }
 
-                            Profile::A2dpSink | Profile::A2dpSource => {
+                            Profile::A2dpSink | Profile::A2dpSource | Profile::HearingAid => {
                                 let txl = self.tx.clone();
-                                let address = device.address.clone();
+                                let address = device.address.to_string();
                                 topstack::get_runtime().spawn(async move {
                                     let _ = txl
-                                        .send(Message::Media(MediaActions::Connect(address)))
+                                        .send(Message::Media(MediaActions::Disconnect(address)))
                                         .await;
                                 });
                             }
+
                             // We don't connect most profiles
                             _ => (),
                         }
                     }
-                    has_enabled_uuids = true;
                 }
                 _ => {}
             }
         }
 
-        // If SDP isn't completed yet, we wait for it to complete and retry the connection again.
-        // Otherwise, this connection request is done, no retry is required.
-        self.wait_to_connect = !has_enabled_uuids;
-        return true;
+        BtStatus::Success
     }
 
-    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
-        // No need to retry connection as we are going to disconnect all enabled profiles.
-        self.wait_to_connect = false;
+    fn disconnect(&mut self, device: BluetoothDevice, transport: BtTransport) -> BtStatus {
+        if !self.check_adapter_on("disconnect") {
+            return BtStatus::NotReady;
+        }
+
+        self.disconnect_links(&device.address, transport, false)
+    }
+
+    fn disconnect_all_profiles(&mut self, device: BluetoothDevice) -> BtStatus {
+        self.cancel_connect_all(&device.address);
 
         if !self.profiles_ready {
+            return BtStatus::NotReady;
+        }
+
+        let mut addr = device.address;
+        self.hh.as_ref().unwrap().disconnect(&mut addr);
+        queue_message(&self.tx, Message::Media(MediaActions::Disconnect(addr.to_string())));
+
+        if !self.check_adapter_on("disconnect") {
+            return BtStatus::NotReady;
+        }
+
+        // Everything goes, GATT clients included.
+        self.disconnect_links(&device.address, BtTransport::Auto, true)
+    }
+
+    fn set_profile_enabled(
+        &mut self,
+        device: BluetoothDevice,
+        profile: Profile,
+        enabled: bool,
+    ) -> bool {
+        let addr = device.address;
+
+        let uuid = match self.uuid_helper.get_profile_uuid(&profile) {
+            Some(uuid) => *uuid,
+            None => return false,
+        };
+
+        if self.get_disabled_profiles(&addr).contains(&profile) != enabled {
+            return true;
+        }
+
+        self.update_stored_device(addr, |stored| {
+            stored.disabled_profiles.retain(|s| UuidHelper::from_string(s.as_str()) != Some(uuid));
+            if !enabled {
+                stored.disabled_profiles.insert(UuidHelper::to_string(&uuid).to_lowercase());
+            }
+        })
+    }
+
+    fn get_profile_enabled(&self, device: BluetoothDevice, profile: Profile) -> bool {
+        !self.get_disabled_profiles(&device.address).contains(&profile)
+    }
+
+    fn block_device(&mut self, device: BluetoothDevice) -> bool {
+        let addr = device.address;
+
+        if !self.blocked_devices.insert(addr) {
+            return true;
+        }
+
+        if self.get_remote_device_if_found(&addr).map_or(false, |d| {
+            d.acl_state == BtAclState::Connected || d.bond_state == BtBondState::Bonding
+        }) {
+            self.intf.lock().unwrap().cancel_bond(&addr);
+            self.disconnect_all_profiles(device);
+        }
+
+        self.store_blocked(addr, true)
+    }
+
+    fn unblock_device(&mut self, device: BluetoothDevice) -> bool {
+        let addr = device.address;
+
+        if !self.blocked_devices.remove(&addr) {
+            return false;
+        }
+
+        self.store_blocked(addr, false)
+    }
+
+    fn get_blocked_devices(&self) -> Vec<BluetoothDevice> {
+        self.blocked_devices
+            .iter()
+            .map(|address| {
+                let name = self
+                    .get_remote_device_if_found(address)
+                    .map(|d| d.info.name.clone())
+                    .unwrap_or_default();
+                BluetoothDevice::new(*address, name)
+            })
+            .collect()
+    }
+
+    fn get_activity_energy_info(&self) -> ActivityEnergyInfo {
+        if self.state == BtState::On && self.is_activity_energy_info_supported() {
+            self.intf.lock().unwrap().read_energy_info();
+        }
+        self.activity.lock().unwrap().get_activity_energy_info(Instant::now())
+    }
+
+    fn get_controller_info(&self) -> ControllerInfo {
+        let controller = match self.controller.as_ref() {
+            Some(controller) if self.adapter_state == AdapterState::On => controller,
+            _ => return ControllerInfo::default(),
+        };
+
+        let version = controller.get_local_version();
+        ControllerInfo {
+            manufacturer: version.manufacturer,
+            hci_version: version.hci_version,
+            hci_revision: version.hci_revision,
+            lmp_version: version.lmp_version,
+            lmp_subversion: version.lmp_subversion,
+            firmware_version: self.firmware_version.clone(),
+            vendor_diagnostics_supported: vendor::diagnostics_commands(version.manufacturer, true)
+                .is_some(),
+            vendor_diagnostics_enabled: self.vendor_diagnostics_enabled,
+        }
+    }
+
+    fn set_vendor_diagnostics_enabled(&mut self, enabled: bool) -> bool {
+        if !self.check_adapter_on("set vendor diagnostics") {
             return false;
         }
 
-        let addr = RawAddress::from_string(device.address.clone());
-        if addr.is_none() {
-            warn!("Can't connect profiles on invalid address [{}]", &device.address);
+        if !self.apply_vendor_diagnostics(enabled) {
+            warn!("The stack has no diagnostics commands for this controller");
             return false;
         }
+        self.vendor_diagnostics_enabled = enabled;
+        true
+    }
 
-        let uuids = self.get_remote_uuids(device.clone());
-        for uuid in uuids.iter() {
-            match self.uuid_helper.is_known_profile(uuid) {
-                Some(p) => {
-                    if self.uuid_helper.is_profile_enabled(&p) {
-                        match p {
-                            Profile::Hid | Profile::Hogp => {
-                                self.hh.as_ref().unwrap().disconnect(&mut addr.unwrap());
-                            }
+    fn dump_api_trace(&self) -> String {
+        api_trace::read_api_trace(Path::new(api_trace::API_TRACE_PATH))
+    }
+}
 
-                            Profile::A2dpSink | Profile::A2dpSource => {
-                                let txl = self.tx.clone();
-                                let address = device.address.clone();
-                                topstack::get_runtime().spawn(async move {
-                                    let _ = txl
-                                        .send(Message::Media(MediaActions::Disconnect(address)))
-                                        .await;
-                                });
-                            }
+impl IBluetoothLinkPolicy for Bluetooth {
+    fn register_link_policy_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothLinkPolicyCallback + Send>,
+    ) -> u32 {
+        self.link_policy_callbacks.add_callback(callback)
+    }
 
-                            // We don't connect most profiles
-                            _ => (),
-                        }
-                    }
-                }
-                _ => {}
-            }
+    fn unregister_link_policy_callback(&mut self, callback_id: u32) -> bool {
+        self.link_policy_callbacks.remove_callback(callback_id)
+    }
+
+    fn get_link_role(&self, device: BluetoothDevice) -> BtAclRole {
+        if self.adapter_state != AdapterState::On {
+            return BtAclRole::Unknown;
+        }
+
+        self.intf.lock().unwrap().get_acl_role(&device.address)
+    }
+
+    fn request_role_switch(&mut self, device: BluetoothDevice, role: BtAclRole) -> BtStatus {
+        if !self.check_adapter_on("switch roles") {
+            return BtStatus::NotReady;
+        }
+
+        match role {
+            BtAclRole::Central | BtAclRole::Peripheral => {}
+            BtAclRole::Unknown => return BtStatus::InvalidParam,
+        }
+
+        let intf = self.intf.lock().unwrap();
+        if intf.get_acl_role(&device.address) == role {
+            return BtStatus::Success;
+        }
+
+        if intf.switch_acl_role(&device.address, role) {
+            BtStatus::Success
+        } else {
+            BtStatus::RemoteDeviceDown
+        }
+    }
+
+    fn set_sniff_mode_enabled(&mut self, device: BluetoothDevice, enabled: bool) -> BtStatus {
+        if !self.check_adapter_on("change the sniff mode policy") {
+            return BtStatus::NotReady;
+        }
+
+        if self.intf.lock().unwrap().set_sniff_mode_enabled(&device.address, enabled) {
+            BtStatus::Success
+        } else {
+            BtStatus::RemoteDeviceDown
+        }
+    }
+
+    fn set_sniff_subrating(
+        &mut self,
+        device: BluetoothDevice,
+        max_latency: u16,
+        min_remote_timeout: u16,
+        min_local_timeout: u16,
+    ) -> BtStatus {
+        if !self.check_adapter_on("set sniff subrating") {
+            return BtStatus::NotReady;
+        }
+
+        if max_latency == 0xFFFF {
+            return BtStatus::InvalidParam;
         }
 
-        return true;
+        let set = self.intf.lock().unwrap().set_sniff_subrating(
+            &device.address,
+            max_latency,
+            min_remote_timeout,
+            min_local_timeout,
+        );
+        if set {
+            BtStatus::Success
+        } else {
+            BtStatus::RemoteDeviceDown
+        }
     }
 }
 
@@ -1453,3 +4648,17 @@ impl BtifSdpCallbacks for Bluetooth {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_pin() {
+        for length in [KEYBOARD_PIN_LENGTH, 16] {
+            let pin = generate_pin(length);
+            assert_eq!(pin.len(), length);
+            assert!(pin.chars().all(|c| c.is_ascii_digit()), "{} isn't all digits", pin);
+        }
+    }
+}
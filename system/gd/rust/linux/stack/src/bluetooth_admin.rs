@@ -0,0 +1,197 @@
+//! Admin policy API (IBluetoothAdmin).
+
+use bt_topshim::btif::{RawAddress, Uuid128Bit};
+use dbus_macros::dbus_interface;
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
+
+use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::callbacks::Callbacks;
+use crate::{Message, RPCProxy};
+
+/// Defines the admin policy API.
+///
+/// Enterprise policy uses this API to restrict the services (profile UUIDs) that devices are
+/// allowed to use. Connections to services outside of the allowlist are rejected.
+#[dbus_interface(bluetooth_admin_dbus, "org.chromium.bluetooth.BluetoothAdmin")]
+pub trait IBluetoothAdmin {
+    /// Checks whether the given service is allowed by the policy.
+    #[dbus_method("IsServiceAllowed")]
+    fn is_service_allowed(&self, service: Uuid128Bit) -> bool;
+
+    /// Sets the list of allowed services. An empty list allows all services.
+    ///
+    /// Returns true if the allowlist is applied.
+    #[dbus_method("SetAllowedServices")]
+    fn set_allowed_services(&mut self, services: Vec<Uuid128Bit>) -> bool;
+
+    /// Returns the list of allowed services. An empty list means all services are allowed.
+    #[dbus_method("GetAllowedServices")]
+    fn get_allowed_services(&self) -> Vec<Uuid128Bit>;
+
+    /// Returns how the current policy affects the given device.
+    #[dbus_method("GetDevicePolicyEffect")]
+    fn get_device_policy_effect(&self, device: BluetoothDevice) -> PolicyEffect;
+
+    /// Adds an observer to policy changes.
+    ///
+    /// Returns an id identifying the callback.
+    #[dbus_method("RegisterAdminPolicyCallback")]
+    fn register_admin_policy_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothAdminPolicyCallback + Send>,
+    ) -> u32;
+
+    /// Removes an observer to policy changes.
+    ///
+    /// Returns true if the callback can be removed, false if `callback_id` is not recognized.
+    #[dbus_method("UnregisterAdminPolicyCallback")]
+    fn unregister_admin_policy_callback(&mut self, callback_id: u32) -> bool;
+}
+
+/// Admin policy events.
+pub trait IBluetoothAdminPolicyCallback: RPCProxy {
+    /// Triggered when the service allowlist is changed.
+    fn on_service_allowlist_changed(&self, allowlist: Vec<Uuid128Bit>);
+
+    /// Triggered when the policy effect on a device is changed, either because of a new
+    /// allowlist or because the services of the device are updated.
+    fn on_device_policy_effect_changed(&self, device: BluetoothDevice, effect: PolicyEffect);
+}
+
+/// Describes how the admin policy affects a device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PolicyEffect {
+    /// Services of the device that are blocked by the policy.
+    pub service_blocked: Vec<Uuid128Bit>,
+    /// True if any of the services of the device is blocked.
+    pub affected: bool,
+}
+
+/// Implementation of the admin policy API.
+pub struct BluetoothAdmin {
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    allowed_services: HashSet<Uuid128Bit>,
+    callbacks: Callbacks<dyn IBluetoothAdminPolicyCallback + Send>,
+    device_policy_effects: HashMap<RawAddress, PolicyEffect>,
+}
+
+impl BluetoothAdmin {
+    pub fn new(tx: Sender<Message>) -> BluetoothAdmin {
+        BluetoothAdmin {
+            adapter: None,
+            allowed_services: HashSet::new(),
+            callbacks: Callbacks::new(tx, Message::AdminCallbackDisconnected),
+            device_policy_effects: HashMap::new(),
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    fn compute_policy_effect(&self, device: &BluetoothDevice) -> PolicyEffect {
+        let uuids = match &self.adapter {
+            Some(adapter) => adapter.lock().unwrap().get_remote_uuids(device.clone()),
+            None => vec![],
+        };
+
+        let service_blocked: Vec<Uuid128Bit> =
+            uuids.into_iter().filter(|uuid| !self.is_service_allowed(*uuid)).collect();
+        let affected = !service_blocked.is_empty();
+
+        PolicyEffect { service_blocked, affected }
+    }
+
+    /// Recomputes the policy effect on `device` and notifies observers if it changed.
+    fn update_policy_effect(&mut self, device: BluetoothDevice) {
+        let effect = self.compute_policy_effect(&device);
+        let prev = self.device_policy_effects.get(&device.address).cloned().unwrap_or_default();
+        if prev == effect {
+            return;
+        }
+
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_device_policy_effect_changed(device.clone(), effect.clone());
+        });
+
+        if effect.affected {
+            self.device_policy_effects.insert(device.address, effect);
+        } else {
+            self.device_policy_effects.remove(&device.address);
+        }
+    }
+
+    pub(crate) fn on_remote_uuids_changed(&mut self, device: BluetoothDevice) {
+        self.update_policy_effect(device);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+}
+
+impl IBluetoothAdmin for BluetoothAdmin {
+    fn is_service_allowed(&self, service: Uuid128Bit) -> bool {
+        self.allowed_services.is_empty() || self.allowed_services.contains(&service)
+    }
+
+    fn set_allowed_services(&mut self, services: Vec<Uuid128Bit>) -> bool {
+        let adapter = match &self.adapter {
+            Some(adapter) => adapter.clone(),
+            None => {
+                warn!("Adapter is not ready. Can't apply service allowlist.");
+                return false;
+            }
+        };
+
+        let allowed_services: HashSet<Uuid128Bit> = services.into_iter().collect();
+        if allowed_services == self.allowed_services {
+            return true;
+        }
+
+        self.allowed_services = allowed_services;
+        adapter.lock().unwrap().toggle_enabled_profiles(&self.allowed_services);
+
+        let allowlist = self.get_allowed_services();
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_service_allowlist_changed(allowlist.clone());
+        });
+
+        let devices = adapter.lock().unwrap().get_bonded_devices();
+        for device in devices {
+            self.update_policy_effect(device);
+        }
+
+        // Devices that are no longer bonded may still have a stale effect cached.
+        let stale: Vec<RawAddress> = self.device_policy_effects.keys().cloned().collect();
+        for address in stale {
+            self.update_policy_effect(BluetoothDevice::new(address, String::from("")));
+        }
+
+        true
+    }
+
+    fn get_allowed_services(&self) -> Vec<Uuid128Bit> {
+        let mut services: Vec<Uuid128Bit> = self.allowed_services.iter().cloned().collect();
+        services.sort();
+        services
+    }
+
+    fn get_device_policy_effect(&self, device: BluetoothDevice) -> PolicyEffect {
+        self.compute_policy_effect(&device)
+    }
+
+    fn register_admin_policy_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothAdminPolicyCallback + Send>,
+    ) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_admin_policy_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+}
@@ -0,0 +1,946 @@
+//! Advertisement monitors (IBluetoothAdvMonitor).
+//!
+//! Watches the advertisements of nearby devices in the background, for presence detection such as
+//! unlocking the system when its owner's phone comes close. A monitor picks advertisements by
+//! their content and reports a device as found once its RSSI stays at or above a high threshold
+//! for a while, and as lost once it stays below a low threshold, or isn't heard at all, for a
+//! while.
+//!
+//! Monitors are handed to the advertisement packet content filters (APCF) of the controller when
+//! it has room for them, so that the controller applies the thresholds and timeouts itself.
+//! Otherwise the stack scans at a low duty cycle and tracks the devices on its own.
+
+use bt_topshim::bindings::root::bluetooth::Uuid;
+use bt_topshim::btif::RawAddress;
+use bt_topshim::profiles::gatt::{
+    AdvertisingTrackInfo, ApcfCommand, Gatt, GattFilterParam, GattScannerCallbacks,
+    GattScannerInbandCallbacks,
+};
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::beacon::{parse_beacons, Beacon};
+use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::bluetooth_gatt::{parse_uuid_string, BluetoothGatt};
+use crate::callbacks::Callbacks;
+use crate::device_type::DeviceType;
+use crate::radio_arbiter::{RadioActivity, RadioArbiter, RadioGrant, RadioUser, ScanDuty};
+use crate::{Message, RPCProxy};
+
+/// App UUID of the scanner shared by all monitors.
+const ADV_MONITOR_SCANNER_UUID: &str = "3e0c5b2a9d7f4c81b6a24f10d8e57c39";
+
+// Background scanning runs 30 ms out of every 1.28 s, in units of 0.625 ms.
+const SCAN_INTERVAL: u16 = 0x0800;
+const SCAN_WINDOW: u16 = 0x0030;
+
+/// How often the devices tracked by the stack are checked for having gone silent.
+const SILENCE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Valid range of the RSSI thresholds, in dBm.
+const MIN_RSSI_THRESHOLD: i32 = -127;
+const MAX_RSSI_THRESHOLD: i32 = 20;
+
+/// The content of an AD structure is at most 254 bytes long, as its length takes a byte and
+/// includes the AD type.
+const MAX_AD_CONTENT_LEN: usize = 254;
+
+const AD_TYPE_SHORTENED_LOCAL_NAME: u8 = 0x08;
+const AD_TYPE_COMPLETE_LOCAL_NAME: u8 = 0x09;
+const AD_TYPE_APPEARANCE: u8 = 0x19;
+
+// Advertisement packet content filtering. Filter 0 is kept for letting every advertisement
+// through, which the other users of scanning rely on once filtering is enabled.
+const APCF_ACTION_ADD: u8 = 0;
+const APCF_ACTION_DELETE: u8 = 1;
+const APCF_FILTER_TYPE_AD_TYPE: u8 = 0x07;
+const APCF_FEATURE_AD_TYPE: u16 = 1 << 7;
+const APCF_LOGIC_OR: u8 = 0;
+const APCF_ALLOW_ALL_INDEX: u8 = 0;
+/// Longest pattern the controller can match, offset included.
+const APCF_MAX_DATA_LEN: usize = 29;
+/// Number of devices the controller tracks for each filter.
+const APCF_TRACKED_DEVICES: u16 = 8;
+/// RSSI threshold that lets every advertisement through.
+const APCF_LOWEST_RSSI: u8 = 129;
+
+// Delivery modes of the filters.
+const APCF_DELIVERY_IMMEDIATE: u8 = 0;
+const APCF_DELIVERY_ON_FOUND: u8 = 1;
+
+// Advertiser states in the tracking events of the controller.
+const ADVERTISER_STATE_FOUND: u8 = 0;
+const ADVERTISER_STATE_LOST: u8 = 1;
+
+/// An AD structure to look for in advertisements.
+#[derive(Clone, Debug, Default)]
+pub struct AdvMonitorPattern {
+    pub ad_type: u8,
+    /// Where `content` starts in the data of the AD structure.
+    pub offset: u8,
+    pub content: Vec<u8>,
+}
+
+/// What to look for and when to report a device.
+#[derive(Clone, Debug, Default)]
+pub struct AdvMonitor {
+    /// Advertisements that match any of the patterns are considered.
+    pub patterns: Vec<AdvMonitorPattern>,
+    /// A device is found once its RSSI stays at or above `rssi_high_threshold` dBm for
+    /// `found_timeout` ms.
+    pub rssi_high_threshold: i32,
+    pub found_timeout: u32,
+    /// A found device is lost once its RSSI stays below `rssi_low_threshold` dBm, or it isn't
+    /// heard at all, for `lost_timeout` ms.
+    pub rssi_low_threshold: i32,
+    pub lost_timeout: u32,
+}
+
+impl AdvMonitorPattern {
+    fn is_valid(&self) -> bool {
+        !self.content.is_empty() && self.offset as usize + self.content.len() <= MAX_AD_CONTENT_LEN
+    }
+
+    fn matches(&self, adv_data: &[u8]) -> bool {
+        ad_structures(adv_data).iter().any(|(ad_type, data)| {
+            *ad_type == self.ad_type
+                && data.get(self.offset as usize..).map_or(false, |d| d.starts_with(&self.content))
+        })
+    }
+
+    /// The controller only matches AD data from its start, so the bytes before the offset are
+    /// masked out.
+    fn to_apcf_command(&self) -> ApcfCommand {
+        let mut data = vec![0; self.offset as usize];
+        data.extend_from_slice(&self.content);
+        let mut data_mask = vec![0; self.offset as usize];
+        data_mask.resize(data.len(), 0xff);
+
+        ApcfCommand {
+            type_: APCF_FILTER_TYPE_AD_TYPE,
+            address: RawAddress { val: [0; 6] }.into(),
+            addr_type: 0,
+            uuid: Uuid { uu: [0; 16] }.into(),
+            uuid_mask: Uuid { uu: [0; 16] }.into(),
+            name: vec![],
+            company: 0,
+            company_mask: 0,
+            ad_type: self.ad_type,
+            data,
+            data_mask,
+            irk: [0; 16],
+        }
+    }
+}
+
+impl AdvMonitor {
+    fn is_valid(&self) -> bool {
+        let rssi_range = MIN_RSSI_THRESHOLD..=MAX_RSSI_THRESHOLD;
+        !self.patterns.is_empty()
+            && self.patterns.iter().all(|pattern| pattern.is_valid())
+            && rssi_range.contains(&self.rssi_high_threshold)
+            && rssi_range.contains(&self.rssi_low_threshold)
+            && self.rssi_low_threshold <= self.rssi_high_threshold
+            && self.found_timeout <= u16::MAX.into()
+            && self.lost_timeout <= u16::MAX.into()
+    }
+
+    fn matches(&self, adv_data: &[u8]) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(adv_data))
+    }
+
+    fn can_offload(&self) -> bool {
+        self.patterns
+            .iter()
+            .all(|pattern| pattern.offset as usize + pattern.content.len() <= APCF_MAX_DATA_LEN)
+    }
+
+    fn found_timeout(&self) -> Duration {
+        Duration::from_millis(self.found_timeout.into())
+    }
+
+    fn lost_timeout(&self) -> Duration {
+        Duration::from_millis(self.lost_timeout.into())
+    }
+
+    /// Updates the state of a device the monitor matched on an advertisement received at `now`.
+    fn on_sample(&self, state: &mut DeviceState, rssi: i32, now: Instant) -> Option<Presence> {
+        state.last_seen = now;
+
+        if rssi >= self.rssi_high_threshold {
+            state.low_since = None;
+            let since = *state.high_since.get_or_insert(now);
+            if !state.found && now - since >= self.found_timeout() {
+                state.found = true;
+                return Some(Presence::Found);
+            }
+        } else if rssi < self.rssi_low_threshold {
+            state.high_since = None;
+            let since = *state.low_since.get_or_insert(now);
+            if state.found && now - since >= self.lost_timeout() {
+                state.found = false;
+                return Some(Presence::Lost);
+            }
+        } else {
+            // In between the thresholds, neither the found nor the lost condition holds.
+            state.high_since = None;
+            state.low_since = None;
+        }
+
+        None
+    }
+
+    /// Reports a found device as lost if nothing was heard from it for too long.
+    fn on_silence(&self, state: &mut DeviceState, now: Instant) -> Option<Presence> {
+        if state.found && now - state.last_seen >= self.lost_timeout() {
+            state.found = false;
+            return Some(Presence::Lost);
+        }
+        None
+    }
+
+    fn filter_param(&self) -> GattFilterParam {
+        GattFilterParam {
+            feat_seln: APCF_FEATURE_AD_TYPE,
+            list_logic_type: APCF_LOGIC_OR.into(),
+            filt_logic_type: APCF_LOGIC_OR,
+            rssi_high_thres: self.rssi_high_threshold as i8 as u8,
+            rssi_low_thres: self.rssi_low_threshold as i8 as u8,
+            delay_mode: APCF_DELIVERY_ON_FOUND,
+            found_timeout: self.found_timeout as u16,
+            lost_timeout: self.lost_timeout as u16,
+            found_timeout_count: 1,
+            num_of_tracking_entries: APCF_TRACKED_DEVICES,
+        }
+    }
+}
+
+/// Parameters of the filter that lets every advertisement through.
+fn allow_all_filter_param() -> GattFilterParam {
+    GattFilterParam {
+        feat_seln: 0,
+        list_logic_type: APCF_LOGIC_OR.into(),
+        filt_logic_type: APCF_LOGIC_OR,
+        rssi_high_thres: APCF_LOWEST_RSSI,
+        rssi_low_thres: 0,
+        delay_mode: APCF_DELIVERY_IMMEDIATE,
+        found_timeout: 0,
+        lost_timeout: 0,
+        found_timeout_count: 0,
+        num_of_tracking_entries: 0,
+    }
+}
+
+/// Splits advertising data into the type and data of its AD structures. Stops at the first
+/// structure that doesn't fit.
+pub(crate) fn ad_structures(adv_data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut structures = vec![];
+    let mut rest = adv_data;
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = len as usize;
+        if len == 0 || len > tail.len() {
+            break;
+        }
+        structures.push((tail[0], &tail[1..len]));
+        rest = &tail[len..];
+    }
+    structures
+}
+
+/// Returns the local name advertised in `adv_data`, or an empty string.
+fn advertised_name(adv_data: &[u8]) -> String {
+    let structures = ad_structures(adv_data);
+    [AD_TYPE_COMPLETE_LOCAL_NAME, AD_TYPE_SHORTENED_LOCAL_NAME]
+        .iter()
+        .find_map(|ad_type| structures.iter().find(|(t, _)| t == ad_type))
+        .map(|(_, name)| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_default()
+}
+
+/// Returns the GAP Appearance advertised in `adv_data`, if any.
+fn advertised_appearance(adv_data: &[u8]) -> Option<u16> {
+    ad_structures(adv_data).iter().find(|(t, _)| *t == AD_TYPE_APPEARANCE).and_then(|(_, data)| {
+        match data {
+            [low, high] => Some(u16::from_le_bytes([*low, *high])),
+            _ => None,
+        }
+    })
+}
+
+/// Change in the presence of a device.
+#[derive(Debug, PartialEq)]
+enum Presence {
+    Found,
+    Lost,
+}
+
+/// What the stack knows of a device that a monitor matched.
+struct DeviceState {
+    name: String,
+    icon: DeviceType,
+    found: bool,
+    last_seen: Instant,
+    /// Since when the RSSI has been at or above the high threshold.
+    high_since: Option<Instant>,
+    /// Since when the RSSI has been below the low threshold.
+    low_since: Option<Instant>,
+    /// Last beacon frame of each type heard from the device.
+    beacons: Vec<Beacon>,
+}
+
+impl DeviceState {
+    fn new(now: Instant) -> DeviceState {
+        DeviceState {
+            name: String::new(),
+            icon: DeviceType::Unknown,
+            found: false,
+            last_seen: now,
+            high_since: None,
+            low_since: None,
+            beacons: vec![],
+        }
+    }
+
+    /// Takes the name and appearance of the device from its advertisement, where it has them.
+    fn update_advertised(&mut self, adv_data: &[u8]) {
+        let name = advertised_name(adv_data);
+        if !name.is_empty() {
+            self.name = name;
+        }
+        if let Some(appearance) = advertised_appearance(adv_data) {
+            self.icon = DeviceType::from_appearance(appearance);
+        }
+    }
+
+    fn to_device(&self, address: RawAddress) -> BluetoothDevice {
+        let mut device = BluetoothDevice::new(address, self.name.clone());
+        device.icon = self.icon;
+        device
+    }
+
+    /// Keeps the frames of `beacons`, and returns those that differ from the last frame of their
+    /// type.
+    fn update_beacons(&mut self, beacons: &[Beacon]) -> Vec<Beacon> {
+        let mut updated = vec![];
+        for beacon in beacons {
+            match self.beacons.iter_mut().find(|b| b.beacon_type == beacon.beacon_type) {
+                Some(last) if last == beacon => continue,
+                Some(last) => *last = beacon.clone(),
+                None => self.beacons.push(beacon.clone()),
+            }
+            updated.push(beacon.clone());
+        }
+        updated
+    }
+}
+
+struct Monitor {
+    monitor: AdvMonitor,
+    /// Controller filter applying the monitor, if it is offloaded.
+    filter_index: Option<u8>,
+    /// Devices matched by the monitor, tracked by the stack when it isn't offloaded. Offloaded
+    /// monitors only keep the devices the controller reported as found.
+    devices: HashMap<RawAddress, DeviceState>,
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum AdvMonitorActions {
+    /// The adapter is enabled, so the monitors can start scanning.
+    Start,
+    /// The adapter is disabled, which took down our scanner and filters.
+    Stop,
+    /// Time to look for tracked devices that went silent.
+    CheckSilence,
+}
+
+/// Defines the advertisement monitor API.
+#[dbus_interface(bluetooth_adv_monitor_dbus, "org.chromium.bluetooth.BluetoothAdvMonitor")]
+pub trait IBluetoothAdvMonitor {
+    /// Starts reporting the devices that `monitor` matches to `callback`, whenever the adapter is
+    /// enabled, until the monitor is removed or its callback goes away.
+    ///
+    /// Returns the ID of the monitor, or 0 if `monitor` isn't valid.
+    #[dbus_method("AddMonitor")]
+    fn add_monitor(
+        &mut self,
+        monitor: AdvMonitor,
+        callback: Box<dyn IAdvMonitorCallback + Send>,
+    ) -> u32;
+
+    /// Stops a monitor. Returns false if `monitor_id` is not recognized.
+    #[dbus_method("RemoveMonitor")]
+    fn remove_monitor(&mut self, monitor_id: u32) -> bool;
+
+    /// Returns whether the controller applies the monitor rather than the stack.
+    #[dbus_method("IsMonitorOffloaded")]
+    fn is_monitor_offloaded(&self, monitor_id: u32) -> bool;
+}
+
+/// Presence events of the devices that a monitor matches.
+pub trait IAdvMonitorCallback: RPCProxy {
+    /// The RSSI of `device` stayed at or above the high threshold for the found timeout.
+    fn on_device_found(&self, monitor_id: u32, device: BluetoothDevice);
+
+    /// `device` went below the low threshold or silent for the lost timeout.
+    fn on_device_lost(&self, monitor_id: u32, device: BluetoothDevice);
+
+    /// `device`, while found, advertised a beacon frame unlike the last one of its type. The
+    /// frames already heard follow `on_device_found`. Devices of offloaded monitors only report
+    /// the frames of the advertisement that got them found.
+    fn on_beacon_received(&self, monitor_id: u32, device: BluetoothDevice, beacon: Beacon);
+}
+
+/// Implementation of the advertisement monitor API.
+pub struct BluetoothAdvMonitor {
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    gatt: Option<Arc<Mutex<Box<BluetoothGatt>>>>,
+    callbacks: Callbacks<dyn IAdvMonitorCallback + Send>,
+    monitors: HashMap<u32, Monitor>,
+    radio: Arc<Mutex<RadioArbiter>>,
+
+    /// Whether the adapter is enabled.
+    started: bool,
+    scanner_id: Option<u8>,
+    /// Whether we asked for a scanner and wait for it to be registered.
+    registering: bool,
+    silence_check: Option<JoinHandle<()>>,
+}
+
+impl BluetoothAdvMonitor {
+    pub fn new(tx: Sender<Message>, radio: Arc<Mutex<RadioArbiter>>) -> BluetoothAdvMonitor {
+        BluetoothAdvMonitor {
+            tx: tx.clone(),
+            adapter: None,
+            gatt: None,
+            callbacks: Callbacks::new(tx, Message::AdvMonitorCallbackDisconnected),
+            monitors: HashMap::new(),
+            radio,
+            started: false,
+            scanner_id: None,
+            registering: false,
+            silence_check: None,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub fn set_gatt(&mut self, gatt: Arc<Mutex<Box<BluetoothGatt>>>) {
+        self.gatt = Some(gatt);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.remove_monitor(id)
+    }
+
+    pub fn dispatch_adv_monitor_actions(&mut self, action: AdvMonitorActions) {
+        match action {
+            AdvMonitorActions::Start => {
+                self.started = true;
+                self.update_scanner();
+            }
+
+            AdvMonitorActions::Stop => {
+                self.started = false;
+                self.scanner_id = None;
+                self.registering = false;
+                self.radio.lock().unwrap().release(RadioUser::AdvMonitor, RadioActivity::Scan);
+                self.silence_check = None;
+
+                // The devices can't be heard anymore.
+                let ids: Vec<u32> = self.monitors.keys().cloned().collect();
+                for id in ids {
+                    let monitor = self.monitors.get_mut(&id).unwrap();
+                    monitor.filter_index = None;
+                    let found: Vec<BluetoothDevice> = monitor
+                        .devices
+                        .drain()
+                        .filter(|(_, state)| state.found)
+                        .map(|(addr, state)| state.to_device(addr))
+                        .collect();
+                    for device in found {
+                        self.report(id, device, Presence::Lost);
+                    }
+                }
+            }
+
+            AdvMonitorActions::CheckSilence => self.check_silence(),
+        }
+    }
+
+    pub fn dispatch_scanner_callbacks(&mut self, cb: GattScannerCallbacks) {
+        match cb {
+            GattScannerCallbacks::OnScannerRegistered(uuid, scanner_id, status) => {
+                let ours = parse_uuid_string(ADV_MONITOR_SCANNER_UUID).unwrap();
+                if uuid.uu != ours.uu || !self.registering {
+                    return;
+                }
+
+                self.registering = false;
+                if status != 0 {
+                    warn!("Failed to register the advertisement monitor scanner: {}", status);
+                    return;
+                }
+
+                self.scanner_id = Some(scanner_id);
+                self.radio.lock().unwrap().request_scan(
+                    RadioUser::AdvMonitor,
+                    ScanDuty { interval: SCAN_INTERVAL, window: SCAN_WINDOW },
+                );
+                self.apply_scan_duty();
+                self.with_gatt(|gatt| gatt.scanner.start_scan());
+
+                let ids: Vec<u32> = self.monitors.keys().cloned().collect();
+                for id in ids {
+                    self.offload(scanner_id, id);
+                }
+                self.update_scanner();
+            }
+
+            GattScannerCallbacks::OnScanResult(_, _, addr, _, _, _, _, rssi, _, adv_data) => {
+                self.on_scan_result(addr, rssi.into(), &adv_data);
+            }
+
+            GattScannerCallbacks::OnTrackAdvFoundLost(info) => self.on_track_adv_found_lost(info),
+
+            _ => {}
+        }
+    }
+
+    pub fn dispatch_scanner_inband_callbacks(&mut self, cb: GattScannerInbandCallbacks) {
+        match cb {
+            GattScannerInbandCallbacks::FilterConfigCallback(
+                filter_index,
+                _filter_type,
+                _available_space,
+                action,
+                status,
+            ) => {
+                if action != APCF_ACTION_ADD || status == 0 || filter_index == APCF_ALLOW_ALL_INDEX
+                {
+                    return;
+                }
+
+                // The controller ran out of room for patterns, so the stack tracks the devices.
+                for monitor in self.monitors.values_mut() {
+                    if monitor.filter_index == Some(filter_index) {
+                        warn!("Failed to offload a monitor to filter {}: {}", filter_index, status);
+                        monitor.filter_index = None;
+                        monitor.devices.clear();
+                    }
+                }
+                self.release_filter(filter_index);
+            }
+
+            GattScannerInbandCallbacks::FilterParamSetupCallback(_, _, action, status) => {
+                if status != 0 {
+                    warn!("Failed to set up a scan filter (action {}): {}", action, status);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn with_gatt<F: FnOnce(&mut Gatt)>(&self, f: F) {
+        if let Some(gatt) = &self.gatt {
+            if let Some(gatt) = gatt.lock().unwrap().get_gatt() {
+                f(gatt);
+            }
+        }
+    }
+
+    /// Handles a change of what the radio grants the background scan.
+    pub(crate) fn radio_grant_changed(&mut self, activity: RadioActivity, grant: RadioGrant) {
+        if activity == RadioActivity::Scan && self.scanner_id.is_some() {
+            debug!("Advertisement monitor scan is now {:?}", grant);
+            self.apply_scan_duty();
+        }
+    }
+
+    /// Sets the scan parameters of the scanner to those the radio scans with.
+    fn apply_scan_duty(&self) {
+        let scanner_id = match self.scanner_id {
+            Some(scanner_id) => scanner_id,
+            None => return,
+        };
+        let duty = self
+            .radio
+            .lock()
+            .unwrap()
+            .get_scan_duty()
+            .unwrap_or(ScanDuty { interval: SCAN_INTERVAL, window: SCAN_WINDOW });
+        self.with_gatt(|gatt| {
+            gatt.scanner.set_scan_parameters(scanner_id, duty.interval, duty.window)
+        });
+    }
+
+    /// Number of filters the controller can apply, 0 if it can't filter advertisements.
+    fn get_filter_count(&self) -> u8 {
+        match &self.adapter {
+            Some(adapter) => {
+                let count =
+                    adapter.lock().unwrap().get_adapter_capabilities().max_advertising_filters;
+                count.min(u8::MAX.into()) as u8
+            }
+            None => 0,
+        }
+    }
+
+    /// Brings the scanner and the filters in line with the monitors.
+    fn update_scanner(&mut self) {
+        if !self.started {
+            return;
+        }
+
+        if self.monitors.is_empty() {
+            if let Some(scanner_id) = self.scanner_id.take() {
+                debug!("No advertisement monitor left, stopping the scan");
+                self.with_gatt(|gatt| {
+                    gatt.scanner.stop_scan();
+                    gatt.scanner.unregister(scanner_id);
+                });
+                self.radio.lock().unwrap().release(RadioUser::AdvMonitor, RadioActivity::Scan);
+            }
+            return;
+        }
+
+        if self.scanner_id.is_none() && !self.registering {
+            self.registering = true;
+            let uuid = parse_uuid_string(ADV_MONITOR_SCANNER_UUID).unwrap();
+            self.with_gatt(|gatt| gatt.scanner.register_scanner(uuid));
+        }
+    }
+
+    /// Hands a monitor to the controller if it fits a free filter. Monitors the controller
+    /// couldn't take stay with the stack until the scanner is registered again.
+    fn offload(&mut self, scanner_id: u8, id: u32) {
+        if !self.monitors.get(&id).map_or(false, |m| m.monitor.can_offload()) {
+            return;
+        }
+
+        let count = self.get_filter_count();
+        let free_index = (1..count)
+            .find(|index| !self.monitors.values().any(|m| m.filter_index == Some(*index)));
+        let filter_index = match free_index {
+            Some(index) => index,
+            None => return,
+        };
+
+        let first = !self.monitors.values().any(|m| m.filter_index.is_some());
+        let monitor = self.monitors.get_mut(&id).unwrap();
+        let commands: Vec<ApcfCommand> =
+            monitor.monitor.patterns.iter().map(|pattern| pattern.to_apcf_command()).collect();
+        let param = monitor.monitor.filter_param();
+        monitor.filter_index = Some(filter_index);
+        monitor.devices.clear();
+
+        info!("Offloading advertisement monitor {} to filter {}", id, filter_index);
+        self.with_gatt(|gatt| {
+            if first {
+                let allow_all = allow_all_filter_param();
+                gatt.scanner.scan_filter_setup(
+                    scanner_id,
+                    APCF_ACTION_ADD,
+                    APCF_ALLOW_ALL_INDEX,
+                    allow_all,
+                );
+                gatt.scanner.scan_filter_enable();
+            }
+            gatt.scanner.scan_filter_add(filter_index, commands);
+            gatt.scanner.scan_filter_setup(scanner_id, APCF_ACTION_ADD, filter_index, param);
+        });
+    }
+
+    /// Deletes a filter whose monitor is gone, and stops filtering once no filter is left.
+    fn release_filter(&mut self, filter_index: u8) {
+        let scanner_id = match self.scanner_id {
+            Some(scanner_id) => scanner_id,
+            None => return,
+        };
+
+        let last = !self.monitors.values().any(|m| m.filter_index.is_some());
+        self.with_gatt(|gatt| {
+            gatt.scanner.scan_filter_setup(
+                scanner_id,
+                APCF_ACTION_DELETE,
+                filter_index,
+                allow_all_filter_param(),
+            );
+            gatt.scanner.scan_filter_clear(filter_index);
+            if last {
+                gatt.scanner.scan_filter_setup(
+                    scanner_id,
+                    APCF_ACTION_DELETE,
+                    APCF_ALLOW_ALL_INDEX,
+                    allow_all_filter_param(),
+                );
+                gatt.scanner.scan_filter_disable();
+            }
+        });
+    }
+
+    fn on_scan_result(&mut self, addr: RawAddress, rssi: i32, adv_data: &[u8]) {
+        let now = Instant::now();
+        let beacons = parse_beacons(adv_data);
+        let mut changes = vec![];
+        for (id, monitor) in self.monitors.iter_mut() {
+            if monitor.filter_index.is_some() || !monitor.monitor.matches(adv_data) {
+                continue;
+            }
+
+            let state = monitor.devices.entry(addr).or_insert_with(|| DeviceState::new(now));
+            state.update_advertised(adv_data);
+            let updated = state.update_beacons(&beacons);
+            match monitor.monitor.on_sample(state, rssi, now) {
+                Some(Presence::Found) => changes.push((
+                    *id,
+                    state.to_device(addr),
+                    Some(Presence::Found),
+                    state.beacons.clone(),
+                )),
+                presence if presence.is_some() || (state.found && !updated.is_empty()) => {
+                    changes.push((*id, state.to_device(addr), presence, updated))
+                }
+                _ => {}
+            }
+        }
+
+        for (id, device, presence, beacons) in changes {
+            if let Some(presence) = presence {
+                self.report(id, device.clone(), presence);
+            }
+            self.report_beacons(id, device, beacons);
+        }
+        self.schedule_silence_check();
+    }
+
+    fn on_track_adv_found_lost(&mut self, info: AdvertisingTrackInfo) {
+        let addr: RawAddress = info.advertiser_address.into();
+        let entry =
+            self.monitors.iter_mut().find(|(_, m)| m.filter_index == Some(info.filter_index));
+        let (id, monitor) = match entry {
+            Some((id, monitor)) => (*id, monitor),
+            None => return,
+        };
+
+        let presence = match info.advertiser_state {
+            ADVERTISER_STATE_FOUND => {
+                let mut state = DeviceState::new(Instant::now());
+                // What the advertisement itself says wins over the scan response.
+                state.update_advertised(&info.scan_response);
+                state.update_advertised(&info.adv_packet);
+                state.found = true;
+                state.update_beacons(&parse_beacons(&info.adv_packet));
+                state.update_beacons(&parse_beacons(&info.scan_response));
+                let (device, beacons) = (state.to_device(addr), state.beacons.clone());
+                if monitor.devices.insert(addr, state).is_some() {
+                    return;
+                }
+                (device, Presence::Found, beacons)
+            }
+            ADVERTISER_STATE_LOST => match monitor.devices.remove(&addr) {
+                Some(state) => (state.to_device(addr), Presence::Lost, vec![]),
+                None => return,
+            },
+            state => {
+                debug!("Unknown advertiser state {} of {}", state, addr.to_string());
+                return;
+            }
+        };
+
+        let (device, presence, beacons) = presence;
+        self.report(id, device.clone(), presence);
+        self.report_beacons(id, device, beacons);
+    }
+
+    /// Reports found devices that went silent and forgets the others.
+    fn check_silence(&mut self) {
+        self.silence_check = None;
+
+        let now = Instant::now();
+        let mut changes = vec![];
+        for (id, monitor) in self.monitors.iter_mut() {
+            if monitor.filter_index.is_some() {
+                continue;
+            }
+
+            let lost_timeout = monitor.monitor.lost_timeout();
+            for (addr, state) in monitor.devices.iter_mut() {
+                if let Some(presence) = monitor.monitor.on_silence(state, now) {
+                    changes.push((*id, state.to_device(*addr), presence));
+                }
+            }
+            monitor.devices.retain(|_, state| state.found || now - state.last_seen < lost_timeout);
+        }
+
+        for (id, device, presence) in changes {
+            self.report(id, device, presence);
+        }
+        self.schedule_silence_check();
+    }
+
+    fn schedule_silence_check(&mut self) {
+        let tracking =
+            self.monitors.values().any(|m| m.filter_index.is_none() && !m.devices.is_empty());
+        if !tracking || self.silence_check.is_some() {
+            return;
+        }
+
+        let txl = self.tx.clone();
+        self.silence_check = Some(tokio::spawn(async move {
+            time::sleep(SILENCE_CHECK_INTERVAL).await;
+            let _ = txl.send(Message::AdvMonitor(AdvMonitorActions::CheckSilence)).await;
+        }));
+    }
+
+    fn report(&self, id: u32, device: BluetoothDevice, presence: Presence) {
+        debug!("Advertisement monitor {}: {} is {:?}", id, device.address.to_string(), presence);
+        self.callbacks.call_by_id(id, |callback| match presence {
+            Presence::Found => callback.on_device_found(id, device),
+            Presence::Lost => callback.on_device_lost(id, device),
+        });
+    }
+
+    fn report_beacons(&self, id: u32, device: BluetoothDevice, beacons: Vec<Beacon>) {
+        self.callbacks.call_by_id(id, |callback| {
+            for beacon in beacons {
+                callback.on_beacon_received(id, device.clone(), beacon);
+            }
+        });
+    }
+}
+
+impl IBluetoothAdvMonitor for BluetoothAdvMonitor {
+    fn add_monitor(
+        &mut self,
+        monitor: AdvMonitor,
+        callback: Box<dyn IAdvMonitorCallback + Send>,
+    ) -> u32 {
+        if !monitor.is_valid() {
+            warn!("Rejecting invalid advertisement monitor {:?}", monitor);
+            return 0;
+        }
+
+        let id = self.callbacks.add_callback(callback);
+        self.monitors.insert(id, Monitor { monitor, filter_index: None, devices: HashMap::new() });
+        match self.scanner_id {
+            Some(scanner_id) => self.offload(scanner_id, id),
+            None => self.update_scanner(),
+        }
+        id
+    }
+
+    fn remove_monitor(&mut self, monitor_id: u32) -> bool {
+        let monitor = match self.monitors.remove(&monitor_id) {
+            Some(monitor) => monitor,
+            None => return false,
+        };
+
+        self.callbacks.remove_callback(monitor_id);
+        if let Some(filter_index) = monitor.filter_index {
+            self.release_filter(filter_index);
+        }
+        self.update_scanner();
+        true
+    }
+
+    fn is_monitor_offloaded(&self, monitor_id: u32) -> bool {
+        self.monitors.get(&monitor_id).map_or(false, |m| m.filter_index.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> AdvMonitor {
+        AdvMonitor {
+            patterns: vec![AdvMonitorPattern {
+                ad_type: 0xff,
+                offset: 2,
+                content: vec![0x01, 0x02],
+            }],
+            rssi_high_threshold: -60,
+            found_timeout: 2000,
+            rssi_low_threshold: -80,
+            lost_timeout: 5000,
+        }
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        let monitor = monitor();
+        assert!(monitor.is_valid());
+
+        // Flags, then manufacturer data with the pattern after the company ID.
+        assert!(monitor.matches(&[0x02, 0x01, 0x06, 0x05, 0xff, 0xe0, 0x00, 0x01, 0x02]));
+        assert!(!monitor.matches(&[0x05, 0xff, 0x01, 0x02, 0xe0, 0x00]));
+        assert!(!monitor.matches(&[0x05, 0x16, 0xe0, 0x00, 0x01, 0x02]));
+        // A truncated structure ends the data.
+        assert!(!monitor.matches(&[0x09, 0xff, 0xe0, 0x00, 0x01, 0x02]));
+
+        assert_eq!(advertised_name(&[0x02, 0x01, 0x06, 0x04, 0x09, b'a', b'b', b'c']), "abc");
+        assert_eq!(advertised_name(&[0x02, 0x01, 0x06]), "");
+
+        let mut invalid = monitor.clone();
+        invalid.rssi_low_threshold = -50;
+        assert!(!invalid.is_valid());
+        invalid = monitor.clone();
+        invalid.patterns.clear();
+        assert!(!invalid.is_valid());
+    }
+
+    #[test]
+    fn test_found_and_lost() {
+        let monitor = monitor();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut state = DeviceState::new(start);
+
+        // Found only once the RSSI stayed at or above the high threshold for the found timeout.
+        assert_eq!(monitor.on_sample(&mut state, -50, at(0)), None);
+        assert_eq!(monitor.on_sample(&mut state, -70, at(1000)), None);
+        assert_eq!(monitor.on_sample(&mut state, -60, at(1500)), None);
+        assert_eq!(monitor.on_sample(&mut state, -55, at(3000)), None);
+        assert_eq!(monitor.on_sample(&mut state, -55, at(3500)), Some(Presence::Found));
+        assert_eq!(monitor.on_sample(&mut state, -55, at(4000)), None);
+
+        // Values in between the thresholds keep the device found.
+        assert_eq!(monitor.on_sample(&mut state, -85, at(5000)), None);
+        assert_eq!(monitor.on_sample(&mut state, -70, at(8000)), None);
+        assert_eq!(monitor.on_sample(&mut state, -85, at(9000)), None);
+        assert_eq!(monitor.on_sample(&mut state, -85, at(14000)), Some(Presence::Lost));
+
+        // A found device that goes silent is lost after the lost timeout.
+        state = DeviceState::new(start);
+        let mut no_delay = monitor.clone();
+        no_delay.found_timeout = 0;
+        assert_eq!(no_delay.on_sample(&mut state, -60, at(0)), Some(Presence::Found));
+        assert_eq!(no_delay.on_silence(&mut state, at(4999)), None);
+        assert_eq!(no_delay.on_silence(&mut state, at(5000)), Some(Presence::Lost));
+        assert_eq!(no_delay.on_silence(&mut state, at(6000)), None);
+    }
+
+    #[test]
+    fn test_apcf_command() {
+        let command = monitor().patterns[0].to_apcf_command();
+        assert_eq!(command.ad_type, 0xff);
+        assert_eq!(command.data, vec![0x00, 0x00, 0x01, 0x02]);
+        assert_eq!(command.data_mask, vec![0x00, 0x00, 0xff, 0xff]);
+
+        let mut long = monitor();
+        long.patterns[0].offset = 28;
+        assert!(long.is_valid());
+        assert!(!long.can_offload());
+    }
+}
@@ -0,0 +1,1291 @@
+//! Google Fast Pair provider (IBluetoothFastPair).
+//!
+//! Lets a seeker, usually a phone, offer to pair with us as soon as it sees our advertisement.
+//! The seeker proves it knows the model registered on the Fast Pair console by deriving a key
+//! from our anti-spoofing key through the Key-based Pairing characteristic. That key protects
+//! the comparison of the BR/EDR pairing passkey and the account key the seeker writes afterwards,
+//! which lets the other devices of the same account recognize us later.
+
+use btif_macros::{btif_callback, btif_callbacks_dispatcher};
+
+use bt_topshim::bindings::root::bluetooth::Uuid;
+use bt_topshim::btif::{BtStatus, BtTransport, RawAddress};
+use bt_topshim::crypto;
+use bt_topshim::profiles::gatt::{
+    AdvertiseParameters, BtGattDbElement, BtGattResponse, BtGattValue, Gatt, GattAdvCallbacks,
+    GattServerCallbacks, GattStatus, PeriodicAdvertisingParameters,
+};
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::cmp::min;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::activity::ActivityTracker;
+use crate::advertising_privacy::AdvertisingPrivacy;
+use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::bluetooth_gatt::{parse_uuid_string, read_at_offset, BluetoothGatt, GattDbElementType};
+use crate::device_store::{from_hex_array, parse_ini, to_hex, to_ini, Section};
+use crate::gatt_authorization::Authorization;
+use crate::radio_arbiter::{RadioActivity, RadioArbiter, RadioGrant, RadioUser};
+
+/// Where the provisioning and the account keys are kept.
+const FAST_PAIR_CONFIG_PATH: &str = "/var/lib/bluetooth/fast_pair.conf";
+const CONFIG_SECTION: &str = "Provider";
+const KEY_MODEL_ID: &str = "ModelId";
+const KEY_ANTI_SPOOFING_KEY: &str = "AntiSpoofingKey";
+const KEY_ACCOUNT_KEYS: &str = "AccountKeys";
+
+/// App UUID of the GATT server hosting the Fast Pair service.
+const FAST_PAIR_SERVER_UUID: &str = "8c1f5e3a62d94b0e9a7d3f2b61c04e95";
+
+const FAST_PAIR_SERVICE: &str = "0000fe2c00001000800000805f9b34fb";
+const MODEL_ID_CHARACTERISTIC: &str = "fe2c1233836648148eb001de32100bea";
+const KEY_BASED_PAIRING_CHARACTERISTIC: &str = "fe2c1234836648148eb001de32100bea";
+const PASSKEY_CHARACTERISTIC: &str = "fe2c1235836648148eb001de32100bea";
+const ACCOUNT_KEY_CHARACTERISTIC: &str = "fe2c1236836648148eb001de32100bea";
+const CLIENT_CHARACTERISTIC_CONFIG: &str = "0000290200001000800000805f9b34fb";
+
+const GATT_CHAR_PROP_READ: u8 = 0x02;
+const GATT_CHAR_PROP_WRITE: u8 = 0x08;
+const GATT_CHAR_PROP_NOTIFY: u8 = 0x10;
+const GATT_PERM_READ: u16 = 0x01;
+const GATT_PERM_WRITE: u16 = 0x10;
+
+/// Model IDs are 24 bits long.
+const MAX_MODEL_ID: u32 = 0xFF_FFFF;
+
+// Types of the encrypted 16 byte messages.
+const MESSAGE_KEY_BASED_PAIRING_REQUEST: u8 = 0x00;
+const MESSAGE_KEY_BASED_PAIRING_RESPONSE: u8 = 0x01;
+pub(crate) const MESSAGE_SEEKER_PASSKEY: u8 = 0x02;
+const MESSAGE_PROVIDER_PASSKEY: u8 = 0x03;
+const MESSAGE_ACCOUNT_KEY: u8 = 0x04;
+
+// Flags of a Key-based Pairing request. The spec numbers bits from the most significant one.
+const FLAG_INITIATE_BONDING: u8 = 1 << 6;
+const FLAG_SEEKER_ADDRESS: u8 = 1 << 4;
+
+/// Length of a Key-based Pairing write that carries the public key of the seeker.
+const PUBLIC_KEY_REQUEST_LEN: usize = 16 + 64;
+
+/// Seekers are required to keep this many account keys, drop the oldest past that.
+const MAX_ACCOUNT_KEYS: usize = 5;
+
+/// Requests with a public key that fail before the provider ignores them until the adapter is
+/// restarted, which keeps the anti-spoofing key from being brute forced.
+const MAX_FAILED_REQUESTS: u32 = 10;
+
+/// Identifies our advertising set in the advertiser callbacks.
+const ADVERTISER_REG_ID: i32 = 0x4650;
+
+// Connectable, scannable legacy advertising every 100ms on all channels of the 1M PHY.
+const ADV_EVENT_PROPERTIES: u16 = 0x13;
+const ADV_INTERVAL: u32 = 160;
+const ADV_CHANNEL_MAP: u8 = 0x07;
+const ADV_PHY_1M: u8 = 1;
+/// Leaves the choice of the address to the native stack, which uses a random one with privacy.
+const ADV_OWN_ADDRESS_DEFAULT: i8 = -1;
+
+const AD_TYPE_SERVICE_DATA_16: u8 = 0x16;
+const AD_TYPE_TX_POWER_LEVEL: u8 = 0x0A;
+const FAST_PAIR_SERVICE_16: [u8; 2] = [0x2C, 0xFE];
+
+/// Flags byte of the service data advertised while not discoverable.
+const ACCOUNT_DATA_FLAGS: u8 = 0x00;
+/// Length and type field of the salt following the account key filter.
+const ACCOUNT_DATA_SALT_FIELD: u8 = 0x11;
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum FastPairActions {
+    /// The adapter is enabled, so the provider can start serving seekers.
+    Start,
+    /// The adapter is disabled, which took down our server and advertisement.
+    Stop,
+    /// The adapter became discoverable or stopped being so.
+    DiscoverableChanged(bool),
+    /// A remote device asks to confirm the passkey of a pairing in progress.
+    SspRequest(RawAddress, u32),
+    /// A client answered whether the device of a connection may write the characteristic at a
+    /// handle.
+    Authorized(i32, i32, bool),
+}
+
+/// Defines the Fast Pair provider API.
+#[dbus_interface(bluetooth_fast_pair_dbus, "org.chromium.bluetooth.BluetoothFastPair")]
+pub trait IBluetoothFastPair {
+    /// Sets the model ID and the base64-decoded anti-spoofing private key that the Fast Pair
+    /// console issued for this device. The provider starts serving seekers right away.
+    ///
+    /// Returns false if the model ID doesn't fit 24 bits or the key isn't 32 bytes long.
+    #[dbus_method("Provision")]
+    fn provision(&mut self, model_id: u32, anti_spoofing_key: Vec<u8>) -> bool;
+
+    /// Returns whether a model ID and anti-spoofing key were provisioned.
+    #[dbus_method("IsProvisioned")]
+    fn is_provisioned(&self) -> bool;
+
+    /// Returns the number of account keys that seekers wrote.
+    #[dbus_method("GetAccountKeyCount")]
+    fn get_account_key_count(&self) -> u32;
+
+    /// Forgets all account keys, so that seekers treat us as a device never seen before.
+    #[dbus_method("ClearAccountKeys")]
+    fn clear_account_keys(&mut self);
+}
+
+/// Values issued by the Fast Pair console.
+#[derive(Clone)]
+struct Provisioning {
+    model_id: u32,
+    anti_spoofing_key: [u8; 32],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Characteristic {
+    ModelId,
+    KeyBasedPairing,
+    Passkey,
+    AccountKey,
+}
+
+/// A seeker connected to the Fast Pair service.
+struct Session {
+    address: RawAddress,
+    /// Key shared with the seeker after a successful Key-based Pairing request.
+    key: Option<[u8; 16]>,
+    /// BR/EDR address the seeker pairs from, which differs from the LE one it connects with.
+    seeker_address: Option<RawAddress>,
+    /// Passkey of the pairing in progress and the one the seeker wrote, compared once both are
+    /// known.
+    ssp_passkey: Option<(RawAddress, u32)>,
+    seeker_passkey: Option<u32>,
+    /// Account key writes waiting for a client to authorize them.
+    pending_writes: Vec<PendingWrite>,
+}
+
+/// A write of a seeker, kept until it can be answered.
+struct PendingWrite {
+    trans_id: i32,
+    handle: i32,
+    need_rsp: bool,
+    value: Vec<u8>,
+}
+
+impl Session {
+    fn new(address: RawAddress) -> Session {
+        Session {
+            address,
+            key: None,
+            seeker_address: None,
+            ssp_passkey: None,
+            seeker_passkey: None,
+            pending_writes: vec![],
+        }
+    }
+}
+
+/// Content of a decrypted Key-based Pairing request.
+#[derive(Debug)]
+pub(crate) struct KeyBasedPairingRequest {
+    flags: u8,
+    provider_address: [u8; 6],
+    seeker_address: Option<RawAddress>,
+}
+
+/// Parses a decrypted Key-based Pairing request, or returns None if `block` isn't one.
+pub(crate) fn parse_key_based_pairing_request(block: &[u8; 16]) -> Option<KeyBasedPairingRequest> {
+    if block[0] != MESSAGE_KEY_BASED_PAIRING_REQUEST {
+        return None;
+    }
+
+    let flags = block[1];
+    let seeker_address = if flags & (FLAG_INITIATE_BONDING | FLAG_SEEKER_ADDRESS) != 0 {
+        RawAddress::from_bytes(&block[8..14])
+    } else {
+        None
+    };
+
+    Some(KeyBasedPairingRequest {
+        flags,
+        provider_address: block[2..8].try_into().unwrap(),
+        seeker_address,
+    })
+}
+
+/// Parses a decrypted passkey message of `message_type`.
+pub(crate) fn parse_passkey(block: &[u8; 16], message_type: u8) -> Option<u32> {
+    if block[0] != message_type {
+        return None;
+    }
+
+    Some(u32::from_be_bytes([0, block[1], block[2], block[3]]))
+}
+
+/// Builds a message of `message_type` carrying `payload`, padded with a random salt.
+fn new_message(message_type: u8, payload: &[u8]) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[0] = message_type;
+    block[1..1 + payload.len()].copy_from_slice(payload);
+    block[1 + payload.len()..].copy_from_slice(&crypto::random_bytes(15 - payload.len()));
+    block
+}
+
+/// Service data advertised while discoverable.
+fn model_id_service_data(model_id: u32) -> Vec<u8> {
+    model_id.to_be_bytes()[1..].to_vec()
+}
+
+/// Service data advertised while not discoverable, which lets seekers recognize us if one of
+/// `filter` matches their account keys.
+fn account_key_service_data(filter: &[u8], salt: u8) -> Vec<u8> {
+    if filter.is_empty() {
+        return vec![ACCOUNT_DATA_FLAGS, 0];
+    }
+
+    // Length of the filter and its type, which asks seekers to show a notification.
+    let mut data = vec![ACCOUNT_DATA_FLAGS, (filter.len() as u8) << 4];
+    data.extend_from_slice(filter);
+    data.extend_from_slice(&[ACCOUNT_DATA_SALT_FIELD, salt]);
+    data
+}
+
+/// Returns the size of the account key filter for `key_count` keys.
+fn account_key_filter_len(key_count: usize) -> usize {
+    if key_count == 0 {
+        0
+    } else {
+        key_count * 6 / 5 + 3
+    }
+}
+
+/// Sets the bits of `filter` selected by the hash of an account key and the salt.
+fn add_to_account_key_filter(filter: &mut [u8], hash: &[u8; 32]) {
+    let bits = filter.len() as u32 * 8;
+    for chunk in hash.chunks(4) {
+        let bit = (u32::from_be_bytes(chunk.try_into().unwrap()) % bits) as usize;
+        filter[bit / 8] |= 1 << (bit % 8);
+    }
+}
+
+/// Wraps `service_data` into the advertising data, followed by the transmit power that seekers
+/// use to estimate how close we are.
+fn advertising_data(service_data: &[u8], tx_power: i8) -> Vec<u8> {
+    let mut data = vec![(service_data.len() + 3) as u8, AD_TYPE_SERVICE_DATA_16];
+    data.extend_from_slice(&FAST_PAIR_SERVICE_16);
+    data.extend_from_slice(service_data);
+    data.extend_from_slice(&[2, AD_TYPE_TX_POWER_LEVEL, tx_power as u8]);
+    data
+}
+
+fn fast_pair_service() -> Vec<BtGattDbElement> {
+    let characteristic = |uuid, properties, permissions| BtGattDbElement {
+        uuid: parse_uuid_string(uuid).unwrap(),
+        type_: GattDbElementType::Characteristic as u32,
+        properties,
+        permissions,
+        ..Default::default()
+    };
+    let client_config = || BtGattDbElement {
+        uuid: parse_uuid_string(CLIENT_CHARACTERISTIC_CONFIG).unwrap(),
+        type_: GattDbElementType::Descriptor as u32,
+        permissions: GATT_PERM_READ | GATT_PERM_WRITE,
+        ..Default::default()
+    };
+    let write_notify = GATT_CHAR_PROP_WRITE | GATT_CHAR_PROP_NOTIFY;
+
+    vec![
+        BtGattDbElement {
+            uuid: parse_uuid_string(FAST_PAIR_SERVICE).unwrap(),
+            type_: GattDbElementType::PrimaryService as u32,
+            ..Default::default()
+        },
+        characteristic(MODEL_ID_CHARACTERISTIC, GATT_CHAR_PROP_READ, GATT_PERM_READ),
+        characteristic(KEY_BASED_PAIRING_CHARACTERISTIC, write_notify, GATT_PERM_WRITE),
+        client_config(),
+        characteristic(PASSKEY_CHARACTERISTIC, write_notify, GATT_PERM_WRITE),
+        client_config(),
+        characteristic(ACCOUNT_KEY_CHARACTERISTIC, GATT_CHAR_PROP_WRITE, GATT_PERM_WRITE),
+    ]
+}
+
+fn load_config(path: &str) -> (Option<Provisioning>, Vec<[u8; 16]>) {
+    let sections = match fs::read_to_string(path) {
+        Ok(contents) => parse_ini(&contents),
+        Err(_) => return (None, vec![]),
+    };
+
+    let section = match sections.iter().find(|s| s.name == CONFIG_SECTION) {
+        Some(section) => section,
+        None => return (None, vec![]),
+    };
+
+    let model_id = section.get(KEY_MODEL_ID).and_then(|v| u32::from_str_radix(v, 16).ok());
+    let anti_spoofing_key = section.get(KEY_ANTI_SPOOFING_KEY).and_then(from_hex_array::<32>);
+    let provisioning = match (model_id, anti_spoofing_key) {
+        (Some(model_id), Some(anti_spoofing_key)) => {
+            Some(Provisioning { model_id, anti_spoofing_key })
+        }
+        _ => None,
+    };
+
+    let account_keys = section
+        .get(KEY_ACCOUNT_KEYS)
+        .map(|v| v.split(',').filter_map(|key| from_hex_array::<16>(key.trim())).collect())
+        .unwrap_or_default();
+
+    (provisioning, account_keys)
+}
+
+/// Implementation of the Fast Pair provider API.
+pub struct BluetoothFastPair {
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    gatt: Option<Arc<Mutex<Box<BluetoothGatt>>>>,
+    config_path: String,
+
+    provisioning: Option<Provisioning>,
+    /// Most recently written last.
+    account_keys: Vec<[u8; 16]>,
+
+    started: bool,
+    discoverable: bool,
+    failed_requests: u32,
+
+    server_if: Option<i32>,
+    handles: HashMap<i32, Characteristic>,
+    sessions: HashMap<i32, Session>,
+
+    advertiser_id: Option<u8>,
+    /// Whether the advertising set was asked for, and didn't fail or stop since.
+    advertising: bool,
+    tx_power: i8,
+
+    activity: Arc<Mutex<ActivityTracker>>,
+    radio: Arc<Mutex<RadioArbiter>>,
+    /// Follows the address we advertise with, which seekers may put in their requests instead of
+    /// our public one.
+    privacy: Arc<Mutex<Box<AdvertisingPrivacy>>>,
+}
+
+impl BluetoothFastPair {
+    pub fn new(
+        activity: Arc<Mutex<ActivityTracker>>,
+        radio: Arc<Mutex<RadioArbiter>>,
+        privacy: Arc<Mutex<Box<AdvertisingPrivacy>>>,
+    ) -> BluetoothFastPair {
+        let (provisioning, account_keys) = load_config(FAST_PAIR_CONFIG_PATH);
+
+        BluetoothFastPair {
+            adapter: None,
+            gatt: None,
+            config_path: String::from(FAST_PAIR_CONFIG_PATH),
+            provisioning,
+            account_keys,
+            started: false,
+            discoverable: false,
+            failed_requests: 0,
+            server_if: None,
+            handles: HashMap::new(),
+            sessions: HashMap::new(),
+            advertiser_id: None,
+            advertising: false,
+            tx_power: 0,
+            activity,
+            radio,
+            privacy,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub fn set_gatt(&mut self, gatt: Arc<Mutex<Box<BluetoothGatt>>>) {
+        self.gatt = Some(gatt);
+    }
+
+    pub fn dispatch_fast_pair_actions(&mut self, action: FastPairActions) {
+        match action {
+            FastPairActions::Start => {
+                self.started = true;
+                self.failed_requests = 0;
+                self.discoverable = match &self.adapter {
+                    Some(adapter) => adapter.lock().unwrap().get_discoverable(),
+                    None => false,
+                };
+                self.start_provider();
+            }
+
+            FastPairActions::Stop => {
+                self.started = false;
+                self.reset();
+            }
+
+            FastPairActions::DiscoverableChanged(discoverable) => {
+                if self.discoverable != discoverable {
+                    self.discoverable = discoverable;
+                    self.update_advertising_data();
+                }
+            }
+
+            FastPairActions::SspRequest(addr, passkey) => self.on_ssp_request(addr, passkey),
+
+            FastPairActions::Authorized(conn_id, handle, allowed) => {
+                self.on_authorized(conn_id, handle, allowed)
+            }
+        }
+    }
+
+    /// Returns whether `cb` is about our server rather than one of the GATT API.
+    pub(crate) fn owns_gatt_server_callback(&self, cb: &GattServerCallbacks) -> bool {
+        let ours = |server_if: &i32| self.server_if == Some(*server_if);
+        let connected = |conn_id: &i32| self.sessions.contains_key(conn_id);
+
+        match cb {
+            GattServerCallbacks::RegisterServer(_, _, uuid) => {
+                uuid.uu == parse_uuid_string(FAST_PAIR_SERVER_UUID).unwrap().uu
+            }
+            GattServerCallbacks::Connection(_, server_if, _, _)
+            | GattServerCallbacks::ServiceAdded(_, server_if, _, _)
+            | GattServerCallbacks::ServiceStopped(_, server_if, _)
+            | GattServerCallbacks::ServiceDeleted(_, server_if, _) => ours(server_if),
+            GattServerCallbacks::RequestReadCharacteristic(conn_id, ..)
+            | GattServerCallbacks::RequestReadDescriptor(conn_id, ..)
+            | GattServerCallbacks::RequestWriteCharacteristic(conn_id, ..)
+            | GattServerCallbacks::RequestWriteDescriptor(conn_id, ..)
+            | GattServerCallbacks::RequestExecWrite(conn_id, ..)
+            | GattServerCallbacks::IndicationSent(conn_id, _)
+            | GattServerCallbacks::Congestion(conn_id, _)
+            | GattServerCallbacks::MtuChanged(conn_id, _)
+            | GattServerCallbacks::PhyUpdated(conn_id, ..)
+            | GattServerCallbacks::ConnUpdated(conn_id, ..) => connected(conn_id),
+            GattServerCallbacks::ResponseConfirmation(..) => false,
+        }
+    }
+
+    pub fn dispatch_advertiser_callbacks(&mut self, cb: GattAdvCallbacks) {
+        // Other advertiser events only matter to whoever changes the set, which we never do.
+        if let GattAdvCallbacks::OnAdvertisingSetStarted(reg_id, adv_id, tx_power, status) = cb {
+            if reg_id != ADVERTISER_REG_ID {
+                return;
+            }
+
+            if status != 0 {
+                warn!("Fast Pair advertising failed to start, status {}", status);
+                self.advertising = false;
+                self.radio.lock().unwrap().release(RadioUser::FastPair, RadioActivity::Advertise);
+                return;
+            }
+
+            if !self.started || !self.advertising {
+                self.with_gatt(|gatt| gatt.advertiser.unregister(adv_id));
+                return;
+            }
+
+            self.advertiser_id = Some(adv_id);
+            self.tx_power = tx_power;
+            // The controller may have picked another power than the one we asked for.
+            self.update_advertising_data();
+            self.privacy.lock().unwrap().add_set(adv_id, self.get_advertise_parameters());
+        }
+    }
+
+    fn start_provider(&mut self) {
+        if !self.started || self.provisioning.is_none() {
+            return;
+        }
+
+        let uuid = parse_uuid_string(FAST_PAIR_SERVER_UUID).unwrap();
+        self.with_gatt(|gatt| {
+            let status = gatt.server.register_server(&uuid, false);
+            if status != BtStatus::Success {
+                warn!("Failed to register the Fast Pair server: {:?}", status);
+            }
+        });
+
+        let grant = self.radio.lock().unwrap().request_advertising(RadioUser::FastPair);
+        if grant == RadioGrant::Deferred {
+            info!("Fast Pair advertising waits for a free advertising set");
+        } else {
+            self.advertising = true;
+            self.start_advertising();
+        }
+    }
+
+    /// Handles a change of what the radio grants our advertising.
+    pub(crate) fn radio_grant_changed(&mut self, activity: RadioActivity, grant: RadioGrant) {
+        if activity != RadioActivity::Advertise || !self.started {
+            return;
+        }
+
+        match grant {
+            RadioGrant::Deferred if self.advertising => {
+                info!("Fast Pair advertising makes room for other advertising sets");
+                self.stop_advertising();
+            }
+            RadioGrant::Granted if !self.advertising => {
+                self.advertising = true;
+                self.start_advertising();
+            }
+            _ => {}
+        }
+    }
+
+    fn get_advertise_parameters(&self) -> AdvertiseParameters {
+        AdvertiseParameters {
+            advertising_event_properties: ADV_EVENT_PROPERTIES,
+            min_interval: ADV_INTERVAL,
+            max_interval: ADV_INTERVAL,
+            channel_map: ADV_CHANNEL_MAP,
+            tx_power: self.tx_power,
+            primary_advertising_phy: ADV_PHY_1M,
+            secondary_advertising_phy: ADV_PHY_1M,
+            scan_request_notification_enable: 0,
+            own_address_type: ADV_OWN_ADDRESS_DEFAULT,
+        }
+    }
+
+    fn start_advertising(&self) {
+        let params = self.get_advertise_parameters();
+        let data = self.get_advertising_data();
+
+        self.with_gatt(|gatt| {
+            gatt.advertiser.start_advertising_set(
+                ADVERTISER_REG_ID,
+                params,
+                data,
+                vec![],
+                PeriodicAdvertisingParameters::default(),
+                vec![],
+                0,
+                0,
+            );
+        });
+    }
+
+    /// Stops the advertising set, or drops it once it starts if it hasn't yet.
+    fn stop_advertising(&mut self) {
+        self.advertising = false;
+        if let Some(adv_id) = self.advertiser_id.take() {
+            self.with_gatt(|gatt| gatt.advertiser.unregister(adv_id));
+            self.activity.lock().unwrap().advertising_stopped(adv_id, Instant::now());
+            self.privacy.lock().unwrap().remove_set(adv_id);
+        }
+    }
+
+    fn stop_provider(&mut self) {
+        let server_if = self.server_if;
+        let advertiser_id = self.advertiser_id;
+        self.with_gatt(|gatt| {
+            if let Some(server_if) = server_if {
+                gatt.server.unregister_server(server_if);
+            }
+            if let Some(adv_id) = advertiser_id {
+                gatt.advertiser.unregister(adv_id);
+            }
+        });
+
+        self.reset();
+    }
+
+    /// Forgets everything that only lives as long as the native stack does.
+    fn reset(&mut self) {
+        self.server_if = None;
+        self.handles.clear();
+        self.sessions.clear();
+        if let Some(adv_id) = self.advertiser_id.take() {
+            self.activity.lock().unwrap().advertising_stopped(adv_id, Instant::now());
+            self.privacy.lock().unwrap().remove_set(adv_id);
+        }
+        self.advertising = false;
+        self.radio.lock().unwrap().release(RadioUser::FastPair, RadioActivity::Advertise);
+    }
+
+    fn with_gatt<F: FnOnce(&mut Gatt)>(&self, f: F) {
+        if let Some(gatt) = &self.gatt {
+            if let Some(gatt) = gatt.lock().unwrap().get_gatt() {
+                f(gatt);
+            }
+        }
+    }
+
+    fn get_advertising_data(&self) -> Vec<u8> {
+        let provisioning = match &self.provisioning {
+            Some(provisioning) => provisioning,
+            None => return vec![],
+        };
+
+        let service_data = if self.discoverable {
+            model_id_service_data(provisioning.model_id)
+        } else {
+            let salt = crypto::random_bytes(1)[0];
+            let mut filter = vec![0; account_key_filter_len(self.account_keys.len())];
+            for key in self.account_keys.iter() {
+                let mut input = key.to_vec();
+                input.push(salt);
+                add_to_account_key_filter(&mut filter, &crypto::sha256(&input));
+            }
+            account_key_service_data(&filter, salt)
+        };
+
+        advertising_data(&service_data, self.tx_power)
+    }
+
+    fn update_advertising_data(&mut self) {
+        if let Some(adv_id) = self.advertiser_id {
+            let data = self.get_advertising_data();
+            // The air time of the advertising depends on how long the data is.
+            self.activity.lock().unwrap().advertising_started(
+                adv_id,
+                ADV_INTERVAL,
+                ADV_CHANNEL_MAP.count_ones(),
+                data.len(),
+                Instant::now(),
+            );
+            self.with_gatt(|gatt| gatt.advertiser.set_data(adv_id, false, data));
+        }
+    }
+
+    fn save_config(&self) -> io::Result<()> {
+        let mut section = Section { name: String::from(CONFIG_SECTION), entries: vec![] };
+        if let Some(provisioning) = &self.provisioning {
+            section.set(KEY_MODEL_ID, format!("{:06x}", provisioning.model_id));
+            section.set(KEY_ANTI_SPOOFING_KEY, to_hex(&provisioning.anti_spoofing_key));
+        }
+        if !self.account_keys.is_empty() {
+            let keys: Vec<String> = self.account_keys.iter().map(|key| to_hex(key)).collect();
+            section.set(KEY_ACCOUNT_KEYS, keys.join(","));
+        }
+
+        let tmp = format!("{}.tmp", self.config_path);
+        fs::write(&tmp, to_ini(&vec![section]))?;
+        fs::rename(&tmp, &self.config_path)
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save_config() {
+            warn!("Failed to save Fast Pair config to {}: {}", self.config_path, e);
+        }
+    }
+
+    fn get_public_address(&self) -> Option<RawAddress> {
+        let adapter = self.adapter.as_ref()?;
+        let address = adapter.lock().unwrap().get_address();
+        RawAddress::from_string(address)
+    }
+
+    fn get_handle(&self, characteristic: Characteristic) -> Option<i32> {
+        self.handles.iter().find(|(_, c)| **c == characteristic).map(|(handle, _)| *handle)
+    }
+
+    fn notify(&self, conn_id: i32, characteristic: Characteristic, value: &[u8]) {
+        let (server_if, handle) = match (self.server_if, self.get_handle(characteristic)) {
+            (Some(server_if), Some(handle)) => (server_if, handle),
+            _ => return,
+        };
+
+        self.with_gatt(|gatt| {
+            gatt.server.send_indication(server_if, handle, conn_id, 0, value);
+        });
+    }
+
+    fn send_response(
+        &self,
+        conn_id: i32,
+        trans_id: i32,
+        handle: i32,
+        offset: i32,
+        result: Result<&[u8], GattStatus>,
+    ) {
+        let mut value = BtGattValue::default();
+        let status = match result {
+            Ok(data) => {
+                let len = min(data.len(), value.value.len());
+                value.value[..len].copy_from_slice(&data[..len]);
+                value.len = len as u16;
+                GattStatus::Success
+            }
+            Err(status) => status,
+        };
+
+        value.handle = handle as u16;
+        value.offset = offset as u16;
+        let response = BtGattResponse { attr_value: value };
+        self.with_gatt(|gatt| {
+            gatt.server.send_response(conn_id, trans_id, status as i32, &response);
+        });
+    }
+
+    fn on_key_based_pairing(&mut self, conn_id: i32, value: &[u8]) {
+        let provisioning = match &self.provisioning {
+            Some(provisioning) => provisioning.clone(),
+            None => return,
+        };
+
+        let with_public_key = value.len() == PUBLIC_KEY_REQUEST_LEN;
+        let candidates: Vec<[u8; 16]> = if with_public_key {
+            if self.failed_requests >= MAX_FAILED_REQUESTS {
+                debug!("Ignoring Key-based Pairing request after too many failures");
+                return;
+            }
+
+            let public_key: [u8; 64] = value[16..].try_into().unwrap();
+            match crypto::ecdh_p256(&provisioning.anti_spoofing_key, &public_key) {
+                Some(secret) => vec![crypto::sha256(&secret)[..16].try_into().unwrap()],
+                None => vec![],
+            }
+        } else if value.len() == 16 {
+            self.account_keys.clone()
+        } else {
+            debug!("Ignoring Key-based Pairing request of {} bytes", value.len());
+            return;
+        };
+
+        let le_addresses = match self.advertiser_id {
+            Some(adv_id) => self.privacy.lock().unwrap().get_addresses(adv_id),
+            None => vec![],
+        };
+        let public_address = self.get_public_address();
+        let own_addresses: Vec<[u8; 6]> =
+            public_address.iter().chain(le_addresses.iter()).map(|addr| addr.val).collect();
+
+        let encrypted: [u8; 16] = value[..16].try_into().unwrap();
+        let found = candidates.iter().find_map(|key| {
+            let request =
+                parse_key_based_pairing_request(&crypto::aes128_decrypt(key, &encrypted))?;
+            if own_addresses.contains(&request.provider_address) {
+                Some((*key, request))
+            } else {
+                None
+            }
+        });
+
+        let (key, request) = match found {
+            Some(found) => found,
+            None => {
+                if with_public_key {
+                    self.failed_requests += 1;
+                }
+                debug!("Key-based Pairing request on connection {} didn't decrypt", conn_id);
+                return;
+            }
+        };
+
+        let session = match self.sessions.get_mut(&conn_id) {
+            Some(session) => session,
+            None => return,
+        };
+        info!("[{}]: Fast Pair seeker verified", session.address.to_string());
+        session.key = Some(key);
+        session.seeker_address = request.seeker_address;
+        session.ssp_passkey = None;
+        session.seeker_passkey = None;
+
+        let public_address = match public_address {
+            Some(addr) => addr,
+            None => return,
+        };
+        let response = new_message(MESSAGE_KEY_BASED_PAIRING_RESPONSE, &public_address.val);
+        self.notify(
+            conn_id,
+            Characteristic::KeyBasedPairing,
+            &crypto::aes128_encrypt(&key, &response),
+        );
+
+        if request.flags & FLAG_INITIATE_BONDING != 0 {
+            if let (Some(addr), Some(adapter)) = (request.seeker_address, &self.adapter) {
+                let device = BluetoothDevice::new(addr, String::from(""));
+                adapter.lock().unwrap().create_bond(device, BtTransport::Bredr);
+            }
+        }
+    }
+
+    fn on_passkey(&mut self, conn_id: i32, value: &[u8]) {
+        let session = match self.sessions.get_mut(&conn_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let (key, encrypted): ([u8; 16], [u8; 16]) = match (session.key, value.try_into()) {
+            (Some(key), Ok(encrypted)) => (key, encrypted),
+            _ => return,
+        };
+
+        match parse_passkey(&crypto::aes128_decrypt(&key, &encrypted), MESSAGE_SEEKER_PASSKEY) {
+            Some(passkey) => session.seeker_passkey = Some(passkey),
+            None => return,
+        }
+
+        self.compare_passkeys(conn_id);
+    }
+
+    fn on_ssp_request(&mut self, addr: RawAddress, passkey: u32) {
+        // Only a verified seeker can take over the confirmation, and only for the device it
+        // told us about if it did.
+        let conn_id = self
+            .sessions
+            .iter()
+            .find(|(_, s)| s.key.is_some() && s.seeker_address.map_or(true, |a| a == addr))
+            .map(|(conn_id, _)| *conn_id);
+
+        if let Some(conn_id) = conn_id {
+            let session = self.sessions.get_mut(&conn_id).unwrap();
+            session.seeker_address = Some(addr);
+            session.ssp_passkey = Some((addr, passkey));
+            self.compare_passkeys(conn_id);
+        }
+    }
+
+    fn compare_passkeys(&mut self, conn_id: i32) {
+        let session = match self.sessions.get_mut(&conn_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let ((addr, passkey), seeker_passkey, key) =
+            match (session.ssp_passkey, session.seeker_passkey, session.key) {
+                (Some(ssp), Some(seeker_passkey), Some(key)) => (ssp, seeker_passkey, key),
+                _ => return,
+            };
+        session.ssp_passkey = None;
+        session.seeker_passkey = None;
+
+        let matched = passkey == seeker_passkey;
+        if !matched {
+            warn!("[{}]: Fast Pair passkey mismatch, rejecting pairing", addr.to_string());
+        }
+
+        if let Some(adapter) = &self.adapter {
+            let device = BluetoothDevice::new(addr, String::from(""));
+            adapter.lock().unwrap().set_pairing_confirmation(device, matched);
+        }
+
+        let message = new_message(MESSAGE_PROVIDER_PASSKEY, &passkey.to_be_bytes()[1..]);
+        self.notify(conn_id, Characteristic::Passkey, &crypto::aes128_encrypt(&key, &message));
+    }
+
+    fn on_account_key(&mut self, conn_id: i32, value: &[u8]) {
+        let session = match self.sessions.get(&conn_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let (key, encrypted): ([u8; 16], [u8; 16]) = match (session.key, value.try_into()) {
+            (Some(key), Ok(encrypted)) => (key, encrypted),
+            _ => return,
+        };
+
+        let account_key = crypto::aes128_decrypt(&key, &encrypted);
+        if account_key[0] != MESSAGE_ACCOUNT_KEY {
+            return;
+        }
+
+        info!("[{}]: Fast Pair account key added", session.address.to_string());
+        self.account_keys.retain(|k| *k != account_key);
+        self.account_keys.push(account_key);
+        if self.account_keys.len() > MAX_ACCOUNT_KEYS {
+            self.account_keys.remove(0);
+        }
+
+        self.persist();
+        self.update_advertising_data();
+    }
+
+    fn on_authorized(&mut self, conn_id: i32, handle: i32, allowed: bool) {
+        let writes: Vec<PendingWrite> = match self.sessions.get_mut(&conn_id) {
+            Some(session) => {
+                let (writes, others) = std::mem::take(&mut session.pending_writes)
+                    .into_iter()
+                    .partition(|write| write.handle == handle);
+                session.pending_writes = others;
+                writes
+            }
+            None => return,
+        };
+
+        for write in writes {
+            if allowed {
+                self.write_characteristic(conn_id, write, Characteristic::AccountKey);
+            } else if write.need_rsp {
+                let result = Err(GattStatus::InsufAuthorization);
+                self.send_response(conn_id, write.trans_id, write.handle, 0, result);
+            }
+        }
+    }
+
+    fn write_characteristic(
+        &mut self,
+        conn_id: i32,
+        write: PendingWrite,
+        characteristic: Characteristic,
+    ) {
+        // Writes that don't decrypt are ignored rather than rejected, so they don't tell an
+        // attacker anything.
+        if write.need_rsp {
+            let result = Ok(&write.value[..]);
+            self.send_response(conn_id, write.trans_id, write.handle, 0, result);
+        }
+
+        match characteristic {
+            Characteristic::KeyBasedPairing => self.on_key_based_pairing(conn_id, &write.value),
+            Characteristic::Passkey => self.on_passkey(conn_id, &write.value),
+            Characteristic::AccountKey => self.on_account_key(conn_id, &write.value),
+            Characteristic::ModelId => (),
+        }
+    }
+}
+
+#[btif_callbacks_dispatcher(BluetoothFastPair, dispatch_gatt_server_callbacks, GattServerCallbacks)]
+pub(crate) trait BtifFastPairGattCallbacks {
+    #[btif_callback(RegisterServer)]
+    fn register_server_cb(&mut self, status: i32, server_if: i32, app_uuid: Uuid);
+
+    #[btif_callback(ServiceAdded)]
+    fn service_added_cb(
+        &mut self,
+        status: i32,
+        server_if: i32,
+        elements: Vec<BtGattDbElement>,
+        count: usize,
+    );
+
+    #[btif_callback(Connection)]
+    fn connection_cb(&mut self, conn_id: i32, server_if: i32, connected: i32, addr: RawAddress);
+
+    #[btif_callback(RequestReadCharacteristic)]
+    fn request_read_characteristic_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        is_long: bool,
+    );
+
+    #[btif_callback(RequestReadDescriptor)]
+    fn request_read_descriptor_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        is_long: bool,
+    );
+
+    #[btif_callback(RequestWriteCharacteristic)]
+    fn request_write_characteristic_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        need_rsp: bool,
+        is_prep: bool,
+        value: Vec<u8>,
+        len: usize,
+    );
+
+    #[btif_callback(RequestWriteDescriptor)]
+    fn request_write_descriptor_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        need_rsp: bool,
+        is_prep: bool,
+        value: Vec<u8>,
+        len: usize,
+    );
+}
+
+impl BtifFastPairGattCallbacks for BluetoothFastPair {
+    fn register_server_cb(&mut self, status: i32, server_if: i32, _app_uuid: Uuid) {
+        if status != GattStatus::Success as i32 {
+            warn!("Fast Pair server was not registered, status {}", status);
+            return;
+        }
+
+        if !self.started {
+            self.with_gatt(|gatt| {
+                gatt.server.unregister_server(server_if);
+            });
+            return;
+        }
+
+        self.server_if = Some(server_if);
+        self.with_gatt(|gatt| {
+            let status = gatt.server.add_service(server_if, &fast_pair_service());
+            if status != BtStatus::Success {
+                warn!("Failed to add the Fast Pair service: {:?}", status);
+            }
+        });
+    }
+
+    fn service_added_cb(
+        &mut self,
+        status: i32,
+        _server_if: i32,
+        elements: Vec<BtGattDbElement>,
+        _count: usize,
+    ) {
+        if status != GattStatus::Success as i32 {
+            warn!("Fast Pair service was not added, status {}", status);
+            return;
+        }
+
+        let characteristics = [
+            (MODEL_ID_CHARACTERISTIC, Characteristic::ModelId),
+            (KEY_BASED_PAIRING_CHARACTERISTIC, Characteristic::KeyBasedPairing),
+            (PASSKEY_CHARACTERISTIC, Characteristic::Passkey),
+            (ACCOUNT_KEY_CHARACTERISTIC, Characteristic::AccountKey),
+        ];
+        for elem in elements.iter() {
+            for (uuid, characteristic) in characteristics.iter() {
+                if elem.uuid.uu == parse_uuid_string(*uuid).unwrap().uu {
+                    self.handles.insert(elem.attribute_handle as i32, *characteristic);
+                }
+            }
+        }
+    }
+
+    fn connection_cb(&mut self, conn_id: i32, _server_if: i32, connected: i32, addr: RawAddress) {
+        if connected == 0 {
+            self.sessions.remove(&conn_id);
+            if let Some(gatt) = &self.gatt {
+                gatt.lock().unwrap().server_disconnected(conn_id);
+            }
+            return;
+        }
+
+        self.sessions.insert(conn_id, Session::new(addr));
+
+        // The random address rotates, so seekers may have seen a different one than we know.
+        if let Some(adv_id) = self.advertiser_id {
+            self.with_gatt(|gatt| gatt.advertiser.get_own_address(adv_id));
+        }
+    }
+
+    fn request_read_characteristic_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        _addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        _is_long: bool,
+    ) {
+        let model_id = match (self.handles.get(&handle), &self.provisioning) {
+            (Some(Characteristic::ModelId), Some(provisioning)) => {
+                model_id_service_data(provisioning.model_id)
+            }
+            _ => {
+                self.send_response(
+                    conn_id,
+                    trans_id,
+                    handle,
+                    offset,
+                    Err(GattStatus::ReadNotPermit),
+                );
+                return;
+            }
+        };
+
+        self.send_response(conn_id, trans_id, handle, offset, read_at_offset(&model_id, offset));
+    }
+
+    fn request_read_descriptor_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        _addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        _is_long: bool,
+    ) {
+        // Notifications are sent regardless of the client configuration.
+        self.send_response(conn_id, trans_id, handle, offset, read_at_offset(&[0, 0], offset));
+    }
+
+    fn request_write_characteristic_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        need_rsp: bool,
+        is_prep: bool,
+        value: Vec<u8>,
+        _len: usize,
+    ) {
+        let characteristic = match self.handles.get(&handle) {
+            Some(c) if *c != Characteristic::ModelId && !is_prep && offset == 0 => *c,
+            _ => {
+                if need_rsp {
+                    self.send_response(
+                        conn_id,
+                        trans_id,
+                        handle,
+                        offset,
+                        Err(GattStatus::WriteNotPermit),
+                    );
+                }
+                return;
+            }
+        };
+
+        let write = PendingWrite { trans_id, handle, need_rsp, value };
+        // Only account keys are sensitive: the rest of the exchange has to work with devices
+        // that aren't bonded yet, and is protected by the anti-spoofing key.
+        let authorization = match (&self.gatt, characteristic) {
+            (Some(gatt), Characteristic::AccountKey) => {
+                gatt.lock().unwrap().authorize_server_request(conn_id, addr, handle)
+            }
+            _ => Authorization::Allowed,
+        };
+
+        match authorization {
+            Authorization::Allowed => self.write_characteristic(conn_id, write, characteristic),
+            Authorization::Denied => {
+                if need_rsp {
+                    let result = Err(GattStatus::InsufAuthorization);
+                    self.send_response(conn_id, trans_id, handle, offset, result);
+                }
+            }
+            Authorization::Pending => {
+                if let Some(session) = self.sessions.get_mut(&conn_id) {
+                    session.pending_writes.push(write);
+                }
+            }
+        }
+    }
+
+    fn request_write_descriptor_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        _addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        need_rsp: bool,
+        _is_prep: bool,
+        value: Vec<u8>,
+        _len: usize,
+    ) {
+        if need_rsp {
+            self.send_response(conn_id, trans_id, handle, offset, Ok(&value[..]));
+        }
+    }
+}
+
+impl IBluetoothFastPair for BluetoothFastPair {
+    fn provision(&mut self, model_id: u32, anti_spoofing_key: Vec<u8>) -> bool {
+        if model_id > MAX_MODEL_ID {
+            warn!("Fast Pair model ID {:#x} is longer than 24 bits", model_id);
+            return false;
+        }
+
+        let anti_spoofing_key: [u8; 32] = match anti_spoofing_key.try_into() {
+            Ok(key) => key,
+            Err(key) => {
+                warn!("Fast Pair anti-spoofing key is {} bytes long instead of 32", key.len());
+                return false;
+            }
+        };
+
+        self.stop_provider();
+        self.provisioning = Some(Provisioning { model_id, anti_spoofing_key });
+        self.persist();
+        self.start_provider();
+        true
+    }
+
+    fn is_provisioned(&self) -> bool {
+        self.provisioning.is_some()
+    }
+
+    fn get_account_key_count(&self) -> u32 {
+        self.account_keys.len() as u32
+    }
+
+    fn clear_account_keys(&mut self) {
+        self.account_keys.clear();
+        self.persist();
+        self.update_advertising_data();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_based_pairing_request() {
+        let mut block = [0u8; 16];
+        block[1] = FLAG_INITIATE_BONDING;
+        block[2..8].copy_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        block[8..14].copy_from_slice(&[0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]);
+
+        let request = parse_key_based_pairing_request(&block).unwrap();
+        assert_eq!(request.provider_address, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        assert_eq!(request.seeker_address.unwrap().to_string(), "AA:BB:CC:DD:EE:FF");
+
+        // Without the flags the remaining bytes are salt.
+        block[1] = 0;
+        assert!(parse_key_based_pairing_request(&block).unwrap().seeker_address.is_none());
+
+        block[0] = MESSAGE_SEEKER_PASSKEY;
+        assert!(parse_key_based_pairing_request(&block).is_none());
+    }
+
+    #[test]
+    fn test_parse_passkey() {
+        let mut block = [0u8; 16];
+        block[0] = MESSAGE_SEEKER_PASSKEY;
+        block[1..4].copy_from_slice(&[0x01, 0xE2, 0x40]);
+
+        assert_eq!(parse_passkey(&block, MESSAGE_SEEKER_PASSKEY), Some(123456));
+        assert_eq!(parse_passkey(&block, MESSAGE_PROVIDER_PASSKEY), None);
+    }
+
+    #[test]
+    fn test_advertising_data() {
+        assert_eq!(
+            advertising_data(&model_id_service_data(0x0A0B0C), -10),
+            vec![0x06, 0x16, 0x2C, 0xFE, 0x0A, 0x0B, 0x0C, 0x02, 0x0A, 0xF6]
+        );
+
+        assert_eq!(account_key_service_data(&[], 0x42), vec![0x00, 0x00]);
+        assert_eq!(
+            account_key_service_data(&[0x01, 0x02, 0x03, 0x04], 0x42),
+            vec![0x00, 0x40, 0x01, 0x02, 0x03, 0x04, 0x11, 0x42]
+        );
+    }
+
+    #[test]
+    fn test_account_key_filter() {
+        assert_eq!(account_key_filter_len(0), 0);
+        assert_eq!(account_key_filter_len(1), 4);
+        assert_eq!(account_key_filter_len(5), 9);
+
+        let mut filter = vec![0; 4];
+        let mut hash = [0u8; 32];
+        // Bits 0, 9, 31 and 32 % 32 = 0.
+        hash[0..4].copy_from_slice(&0u32.to_be_bytes());
+        hash[4..8].copy_from_slice(&9u32.to_be_bytes());
+        hash[8..12].copy_from_slice(&31u32.to_be_bytes());
+        hash[12..16].copy_from_slice(&32u32.to_be_bytes());
+        add_to_account_key_filter(&mut filter, &hash);
+        assert_eq!(filter, vec![0x01, 0x02, 0x00, 0x80]);
+    }
+}
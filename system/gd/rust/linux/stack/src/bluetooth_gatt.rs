@@ -3,36 +3,272 @@
 use btif_macros::{btif_callback, btif_callbacks_dispatcher};
 
 use bt_topshim::bindings::root::bluetooth::Uuid;
-use bt_topshim::btif::{BluetoothInterface, RawAddress, Uuid128Bit};
+use bt_topshim::btif::{BluetoothInterface, BtStatus, BtTransport, RawAddress, Uuid128Bit};
 use bt_topshim::profiles::gatt::{
-    BtGattDbElement, BtGattNotifyParams, BtGattReadParams, Gatt, GattClientCallbacks,
-    GattClientCallbacksDispatcher, GattScannerCallbacksDispatcher, GattServerCallbacksDispatcher,
+    BtGattDbElement, BtGattNotifyParams, BtGattReadParams, BtGattResponse, BtGattValue, Gatt,
+    GattAdvCallbacksDispatcher, GattAdvInbandCallbacksDispatcher, GattClientCallbacks,
+    GattClientCallbacksDispatcher, GattScannerCallbacksDispatcher,
+    GattScannerInbandCallbacksDispatcher, GattServerCallbacks, GattServerCallbacksDispatcher,
     GattStatus,
 };
-use bt_topshim::topstack;
 
+use dbus_macros::dbus_interface;
 use log::{debug, warn};
 use num_traits::cast::{FromPrimitive, ToPrimitive};
-use std::collections::HashSet;
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::Sender;
-
-use crate::{Message, RPCProxy};
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::activity::ActivityTracker;
+use crate::bluetooth_fast_pair::FastPairActions;
+use crate::device_store::{from_hex_array, parse_ini, to_hex, to_ini, Section};
+use crate::gatt_authorization::{Authorization, GattAuthorization};
+use crate::interop::{InteropDatabase, InteropFeature};
+use crate::link_users::LinkUsers;
+use crate::radio_arbiter::{RadioActivity, RadioArbiter, RadioUser};
+use crate::{queue_message, Message, RPCProxy};
+
+// Bits of the PHY mask passed to `client_connect`.
+pub(crate) const LE_PHY_1M_MASK: i32 = 1 << 0;
+const LE_PHY_2M_MASK: i32 = 1 << 1;
+
+/// ATT MTU of a connection before any MTU exchange.
+const DEFAULT_ATT_MTU: i32 = 23;
+
+/// Longest attribute value allowed by ATT.
+const GATT_MAX_ATTR_LEN: usize = 512;
+
+// Bytes of a PDU that aren't available to the attribute value.
+const ATT_WRITE_HEADER_LEN: i32 = 3;
+const ATT_PREPARE_WRITE_HEADER_LEN: i32 = 5;
+const ATT_READ_BY_TYPE_HEADER_LEN: i32 = 4;
+
+/// App UUID of the GATT server that hosts the services of the stack itself.
+const LOCAL_SERVER_UUID: &str = "3c4d0e1a7b2f4c58a6e1d09b8f2c5e71";
+
+// Device Information Service and the characteristics of it that we serve.
+const DEVICE_INFORMATION_SERVICE: &str = "0000180a00001000800000805f9b34fb";
+const MANUFACTURER_NAME_STRING: &str = "00002a2900001000800000805f9b34fb";
+const MODEL_NUMBER_STRING: &str = "00002a2400001000800000805f9b34fb";
+
+const GATT_CHAR_PROP_READ: u8 = 0x02;
+const GATT_PERM_READ: u16 = 0x01;
+
+/// Appearance of a generic computer, from the Bluetooth Assigned Numbers.
+const APPEARANCE_GENERIC_COMPUTER: u16 = 0x0080;
+
+/// Where the firmware reports the vendor and name of the product we run on.
+const DMI_ID_PATH: &str = "/sys/class/dmi/id";
+
+/// Database Hash characteristic of the Generic Attribute service (0x2B2A).
+const DATABASE_HASH_UUID: Uuid128Bit = [
+    0x00, 0x00, 0x2B, 0x2A, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0x80, 0x5F, 0x9B, 0x34, 0xFB,
+];
+const DATABASE_HASH_LEN: usize = 16;
+
+/// Where the services of bonded devices are kept, so they aren't discovered again on every
+/// connection while the Database Hash of the device stays the same.
+const GATT_CACHE_PATH: &str = "/var/lib/bluetooth/floss_gatt_cache.conf";
+const KEY_DATABASE_HASH: &str = "DatabaseHash";
+const KEY_ATTRIBUTES: &str = "Attributes";
+
+/// Number of notifications a batch is delivered at even if its interval isn't over yet.
+const MAX_NOTIFICATION_BATCH_LEN: usize = 256;
+
+// Authentication requirements of GATT requests (GATT_AUTH_REQ_*), which make the native stack
+// encrypt the link, pairing first if needed, before sending the request.
+const GATT_AUTH_REQ_NO_MITM: i32 = 1;
+const GATT_AUTH_REQ_MITM: i32 = 2;
+
+/// How long an operation waits for the native stack when its client didn't set a timeout. The
+/// native stack gives up on a server after the 30 seconds of an ATT transaction, so this only
+/// frees the queue of a connection if the callback never comes.
+const OPERATION_BACKSTOP_TIMEOUT: Duration = Duration::from_secs(35);
 
 struct Client {
     id: Option<i32>,
     uuid: Uuid128Bit,
     callback: Box<dyn IBluetoothGattCallback + Send>,
+    // ID of the observer of the callback's disconnection.
+    disconnect_watch: u32,
     is_congested: bool,
 
     // Queued on_characteristic_write callback.
-    congestion_queue: Vec<(String, i32, i32)>,
+    congestion_queue: Vec<(RawAddress, i32, i32)>,
+
+    // How often notifications are delivered through on_notify_batch, if they are batched.
+    notification_batch_interval: Option<Duration>,
+
+    // Whether reads and writes failing for lack of security are sent again with it raised.
+    auto_elevate_security: bool,
+
+    // How long requests wait for their response before failing, and whether the server is then
+    // disconnected.
+    request_timeout: Option<Duration>,
+    disconnect_on_timeout: bool,
+
+    // Whether the client is asked to authorize the requests to the services the stack hosts.
+    authorizes_server_requests: bool,
+}
+
+// A request to the remote GATT server. The native stack rejects a request while another one is
+// outstanding on the connection, so they are queued and sent one at a time.
+enum GattOperation {
+    ReadCharacteristic {
+        handle: i32,
+        auth_req: i32,
+    },
+    // Only the first MTU - 4 bytes of the value fit in a Read By Type response, so long values are
+    // read again by handle.
+    ReadUsingCharacteristicUuid {
+        uuid: Uuid,
+        start_handle: i32,
+        end_handle: i32,
+        auth_req: i32,
+    },
+    // Looks up the handle with a read by UUID, then writes to it.
+    WriteUsingCharacteristicUuid {
+        uuid: Uuid,
+        start_handle: i32,
+        end_handle: i32,
+        write_type: GattWriteType,
+        auth_req: i32,
+        value: Vec<u8>,
+    },
+    WriteCharacteristic {
+        handle: i32,
+        write_type: GattWriteType,
+        auth_req: i32,
+        value: Vec<u8>,
+    },
+    ReadDescriptor {
+        handle: i32,
+        auth_req: i32,
+    },
+    WriteDescriptor {
+        handle: i32,
+        auth_req: i32,
+        value: Vec<u8>,
+    },
+    ExecuteWrite {
+        execute: bool,
+    },
+    // Reads the Database Hash of the remote server: by UUID when it connects, and by handle once
+    // its services are discovered again after a change.
+    ReadDatabaseHash {
+        handle: Option<i32>,
+    },
+}
+
+// The native callbacks completing operations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OperationCallback {
+    ReadCharacteristic,
+    WriteCharacteristic,
+    ReadDescriptor,
+    WriteDescriptor,
+    ExecuteWrite,
+}
+
+impl GattOperation {
+    /// Raises the authentication requirement of a read or write to at least `auth_req`. Returns
+    /// false if the operation has none or it is already as high.
+    fn raise_auth_req(&mut self, level: i32) -> bool {
+        let auth_req = match self {
+            GattOperation::ReadCharacteristic { auth_req, .. }
+            | GattOperation::ReadUsingCharacteristicUuid { auth_req, .. }
+            | GattOperation::WriteUsingCharacteristicUuid { auth_req, .. }
+            | GattOperation::WriteCharacteristic { auth_req, .. }
+            | GattOperation::ReadDescriptor { auth_req, .. }
+            | GattOperation::WriteDescriptor { auth_req, .. } => auth_req,
+            GattOperation::ExecuteWrite { .. } | GattOperation::ReadDatabaseHash { .. } => {
+                return false
+            }
+        };
+
+        if *auth_req >= level {
+            return false;
+        }
+        *auth_req = level;
+        true
+    }
+
+    /// The callback the native stack completes the operation with. Accesses by UUID start with a
+    /// read, whose result tells which handle to go on with.
+    fn callback(&self) -> OperationCallback {
+        match self {
+            GattOperation::ReadCharacteristic { .. }
+            | GattOperation::ReadUsingCharacteristicUuid { .. }
+            | GattOperation::WriteUsingCharacteristicUuid { .. }
+            | GattOperation::ReadDatabaseHash { .. } => OperationCallback::ReadCharacteristic,
+            GattOperation::WriteCharacteristic { .. } => OperationCallback::WriteCharacteristic,
+            GattOperation::ReadDescriptor { .. } => OperationCallback::ReadDescriptor,
+            GattOperation::WriteDescriptor { .. } => OperationCallback::WriteDescriptor,
+            GattOperation::ExecuteWrite { .. } => OperationCallback::ExecuteWrite,
+        }
+    }
+
+    /// Handle of the attribute the operation reads or writes, and whether it writes. Accesses by
+    /// UUID don't have one.
+    fn accessed_handle(&self) -> Option<(i32, bool)> {
+        match self {
+            GattOperation::ReadCharacteristic { handle, .. }
+            | GattOperation::ReadDescriptor { handle, .. } => Some((*handle, false)),
+            GattOperation::WriteCharacteristic { handle, .. }
+            | GattOperation::WriteDescriptor { handle, .. } => Some((*handle, true)),
+            _ => None,
+        }
+    }
 }
 
 struct Connection {
     conn_id: i32,
     address: String,
     client_id: i32,
+    mtu: i32,
+
+    // The request waiting for its callback, followed by the ones not sent yet.
+    outstanding_operation: Option<GattOperation>,
+    queued_operations: Vec<GattOperation>,
+
+    // Notifications waiting for the next on_notify_batch.
+    pending_notifications: Vec<GattNotification>,
+
+    // The services found by the last discovery, until the remote server reports a change.
+    services: Option<Vec<BluetoothGattService>>,
+
+    // Handles the client has enabled notifications or indications on.
+    subscribed_handles: HashSet<u16>,
+
+    // Whether the services are being discovered again after a change, so the result goes to
+    // on_gatt_db_updated.
+    rediscovering: bool,
+
+    // The Database Hash of the remote server, if it has one and it was read.
+    database_hash: Option<Vec<u8>>,
+
+    // Whether the Database Hash is being read after connecting, and whether discovery waits for
+    // it to tell if the cached services can be used.
+    hash_pending: bool,
+    discovery_requested: bool,
+
+    // Whether the outstanding operation was sent again with raised security, which is only done
+    // once.
+    security_retried: bool,
+
+    // Timer of the outstanding operation, and the number of the last operation timed, which tells
+    // a timer that fires late from the current one.
+    operation_timer: Option<JoinHandle<()>>,
+    operation_sequence: u32,
+
+    // Responses still to come for operations that timed out, which no one waits for anymore.
+    late_responses: u32,
 }
 
 struct ContextMap {
@@ -61,11 +297,11 @@ impl ContextMap {
             .find(|client| client.id.is_some() && client.id.unwrap() == client_id)
     }
 
-    fn get_address_by_conn_id(&self, conn_id: i32) -> Option<String> {
-        match self.connections.iter().find(|conn| conn.conn_id == conn_id) {
-            None => None,
-            Some(conn) => Some(conn.address.clone()),
-        }
+    fn get_address_by_conn_id(&self, conn_id: i32) -> Option<RawAddress> {
+        self.connections
+            .iter()
+            .find(|conn| conn.conn_id == conn_id)
+            .and_then(|conn| RawAddress::from_string(conn.address.clone()))
     }
 
     fn get_client_by_conn_id(&self, conn_id: i32) -> Option<&Client> {
@@ -84,7 +320,12 @@ impl ContextMap {
         self.get_by_client_id_mut(client_id)
     }
 
-    fn add(&mut self, uuid: &Uuid128Bit, callback: Box<dyn IBluetoothGattCallback + Send>) {
+    fn add(
+        &mut self,
+        uuid: &Uuid128Bit,
+        callback: Box<dyn IBluetoothGattCallback + Send>,
+        disconnect_watch: u32,
+    ) {
         if self.get_by_uuid(uuid).is_some() {
             return;
         }
@@ -93,13 +334,30 @@ impl ContextMap {
             id: None,
             uuid: uuid.clone(),
             callback,
+            disconnect_watch,
             is_congested: false,
             congestion_queue: vec![],
+            notification_batch_interval: None,
+            auto_elevate_security: false,
+            request_timeout: None,
+            disconnect_on_timeout: false,
+            authorizes_server_requests: false,
         });
     }
 
     fn remove(&mut self, id: i32) {
-        self.clients.retain(|client| !(client.id.is_some() && client.id.unwrap() == id));
+        self.remove_where(|client| client.id == Some(id));
+    }
+
+    fn remove_by_uuid(&mut self, uuid: &Uuid128Bit) {
+        self.remove_where(|client| client.uuid == *uuid);
+    }
+
+    fn remove_where<F: Fn(&Client) -> bool>(&mut self, f: F) {
+        for client in self.clients.iter_mut().filter(|client| f(client)) {
+            client.callback.unregister(client.disconnect_watch);
+        }
+        self.clients.retain(|client| !f(client));
     }
 
     fn set_client_id(&mut self, uuid: &Uuid128Bit, id: i32) {
@@ -116,10 +374,160 @@ impl ContextMap {
             return;
         }
 
-        self.connections.push(Connection { conn_id, address: address.clone(), client_id });
+        self.connections.push(Connection {
+            conn_id,
+            address: address.clone(),
+            client_id,
+            mtu: DEFAULT_ATT_MTU,
+            outstanding_operation: None,
+            queued_operations: vec![],
+            pending_notifications: vec![],
+            services: None,
+            subscribed_handles: HashSet::new(),
+            rediscovering: false,
+            database_hash: None,
+            hash_pending: false,
+            discovery_requested: false,
+            security_retried: false,
+            operation_timer: None,
+            operation_sequence: 0,
+            late_responses: 0,
+        });
+    }
+
+    fn set_mtu(&mut self, conn_id: i32, mtu: i32) {
+        if let Some(conn) = self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            conn.mtu = mtu;
+        }
+    }
+
+    fn get_mtu(&self, client_id: i32, address: &String) -> Option<i32> {
+        self.connections
+            .iter()
+            .find(|conn| conn.client_id == client_id && conn.address == *address)
+            .map(|conn| conn.mtu)
+    }
+
+    fn get_mtu_by_conn_id(&self, conn_id: i32) -> Option<i32> {
+        self.connections.iter().find(|conn| conn.conn_id == conn_id).map(|conn| conn.mtu)
+    }
+
+    fn queue_operation(&mut self, conn_id: i32, op: GattOperation) {
+        if let Some(conn) = self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            conn.queued_operations.push(op);
+        }
+    }
+
+    /// Takes the next operation to send, if the previous one has completed.
+    fn next_operation(&mut self, conn_id: i32) -> Option<GattOperation> {
+        match self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            Some(conn)
+                if conn.outstanding_operation.is_none() && !conn.queued_operations.is_empty() =>
+            {
+                Some(conn.queued_operations.remove(0))
+            }
+            _ => None,
+        }
+    }
+
+    fn set_outstanding_operation(&mut self, conn_id: i32, op: GattOperation) {
+        if let Some(conn) = self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            conn.outstanding_operation = Some(op);
+        }
+    }
+
+    /// Takes the outstanding operation that `callback` completes. An operation completing with
+    /// another callback is left outstanding, and the callback it expects is returned as the error.
+    fn take_outstanding_operation(
+        &mut self,
+        conn_id: i32,
+        callback: OperationCallback,
+    ) -> Result<Option<GattOperation>, OperationCallback> {
+        let conn = match self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            Some(conn) => conn,
+            None => return Ok(None),
+        };
+        match &conn.outstanding_operation {
+            Some(op) if op.callback() != callback => return Err(op.callback()),
+            _ => {}
+        }
+        if let Some(timer) = conn.operation_timer.take() {
+            timer.abort();
+        }
+        Ok(conn.outstanding_operation.take())
+    }
+
+    /// Counts off a response to an operation that timed out. Returns false if the response is
+    /// for the outstanding operation instead.
+    fn take_late_response(&mut self, conn_id: i32) -> bool {
+        match self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            Some(conn) if conn.late_responses > 0 => {
+                conn.late_responses -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn set_security_retried(&mut self, conn_id: i32, retried: bool) {
+        if let Some(conn) = self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            conn.security_retried = retried;
+        }
+    }
+
+    /// Queues a notification for the next batch and returns how many are queued now.
+    fn queue_notification(&mut self, conn_id: i32, notification: GattNotification) -> usize {
+        match self.connections.iter_mut().find(|conn| conn.conn_id == conn_id) {
+            Some(conn) => {
+                conn.pending_notifications.push(notification);
+                conn.pending_notifications.len()
+            }
+            None => 0,
+        }
+    }
+
+    fn take_pending_notifications(&mut self, conn_id: i32) -> Vec<GattNotification> {
+        self.connections
+            .iter_mut()
+            .find(|conn| conn.conn_id == conn_id)
+            .map(|conn| std::mem::take(&mut conn.pending_notifications))
+            .unwrap_or_default()
+    }
+
+    fn get_connection_mut(&mut self, conn_id: i32) -> Option<&mut Connection> {
+        self.connections.iter_mut().find(|conn| conn.conn_id == conn_id)
+    }
+
+    fn set_subscribed(&mut self, conn_id: i32, handle: u16, subscribed: bool) {
+        if let Some(conn) = self.get_connection_mut(conn_id) {
+            if subscribed {
+                conn.subscribed_handles.insert(handle);
+            } else {
+                conn.subscribed_handles.remove(&handle);
+            }
+        }
+    }
+
+    /// Drops the cached services of the connection and tells whether they should be discovered
+    /// again, which is when the client has subscriptions that may have moved.
+    fn invalidate_services(&mut self, conn_id: i32) -> bool {
+        match self.get_connection_mut(conn_id) {
+            Some(conn) => {
+                conn.services = None;
+                conn.database_hash = None;
+                conn.rediscovering = !conn.subscribed_handles.is_empty();
+                conn.rediscovering
+            }
+            None => false,
+        }
     }
 
     fn remove_connection(&mut self, _client_id: i32, conn_id: i32) {
+        for conn in self.connections.iter_mut().filter(|conn| conn.conn_id == conn_id) {
+            if let Some(timer) = conn.operation_timer.take() {
+                timer.abort();
+            }
+        }
         self.connections.retain(|conn| conn.conn_id != conn_id);
     }
 
@@ -136,129 +544,293 @@ impl ContextMap {
 }
 
 /// Defines the GATT API.
+///
+/// Client methods return a [`BtStatus`] telling whether the request was accepted; results come
+/// back through [`IBluetoothGattCallback`]. Methods that act on a connection return
+/// `RemoteDeviceDown` if the client isn't connected to the device.
+#[dbus_interface(bluetooth_gatt_dbus, "org.chromium.bluetooth.BluetoothGatt")]
 pub trait IBluetoothGatt {
+    #[dbus_method("RegisterScanner")]
     fn register_scanner(&self, callback: Box<dyn IScannerCallback + Send>);
 
+    #[dbus_method("UnregisterScanner")]
     fn unregister_scanner(&self, scanner_id: i32);
 
+    #[dbus_method("StartScan")]
     fn start_scan(&self, scanner_id: i32, settings: ScanSettings, filters: Vec<ScanFilter>);
+    #[dbus_method("StopScan")]
     fn stop_scan(&self, scanner_id: i32);
 
-    /// Registers a GATT Client.
+    /// Registers a GATT Client. A process may register several, each with its own `app_uuid` and
+    /// `callback`, and they are unregistered when their callback goes away.
+    ///
+    /// Returns `Busy` if a client with `app_uuid` is already registered.
+    #[dbus_method("RegisterClient")]
     fn register_client(
         &mut self,
-        app_uuid: String,
+        app_uuid: Uuid128Bit,
         callback: Box<dyn IBluetoothGattCallback + Send>,
         eatt_support: bool,
-    );
+    ) -> BtStatus;
 
     /// Unregisters a GATT Client.
-    fn unregister_client(&mut self, client_id: i32);
+    #[dbus_method("UnregisterClient")]
+    fn unregister_client(&mut self, client_id: i32) -> BtStatus;
 
     /// Initiates a GATT connection to a peer device.
+    ///
+    /// * `is_direct` - Connect right away if true. Otherwise the device is added to the background
+    ///   connection list and is (re)connected whenever it is seen advertising, until
+    ///   `client_disconnect` is called.
+    /// * `transport` - Transport to connect over. `Auto` lets the stack pick based on the device
+    ///   type.
+    /// * `opportunistic` - Only use an existing link to the device and never create one. The
+    ///   connection doesn't keep the link up on its own.
+    ///
+    /// Clients connecting to a device that is already connected share its link, which then stays
+    /// up until the last of their connections closes.
+    /// * `phy` - Mask of the LE PHYs to initiate the connection on (1M = 1, 2M = 2, Coded = 4).
+    #[dbus_method("ClientConnect")]
     fn client_connect(
         &self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         is_direct: bool,
-        transport: i32,
+        transport: BtTransport,
         opportunistic: bool,
         phy: i32,
-    );
+    ) -> BtStatus;
 
     /// Disconnects a GATT connection.
-    fn client_disconnect(&self, client_id: i32, addr: String);
+    #[dbus_method("ClientDisconnect")]
+    fn client_disconnect(&self, client_id: i32, addr: RawAddress) -> BtStatus;
 
     /// Sets preferred PHY.
+    #[dbus_method("ClientSetPreferredPhy")]
     fn client_set_preferred_phy(
         &self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         tx_phy: LePhy,
         rx_phy: LePhy,
         phy_options: i32,
-    );
+    ) -> BtStatus;
 
     /// Reads the PHY used by a peer.
-    fn client_read_phy(&mut self, client_id: i32, addr: String);
+    #[dbus_method("ClientReadPhy")]
+    fn client_read_phy(&mut self, client_id: i32, addr: RawAddress) -> BtStatus;
 
     /// Clears the attribute cache of a device.
-    fn refresh_device(&self, client_id: i32, addr: String);
+    #[dbus_method("RefreshDevice")]
+    fn refresh_device(&self, client_id: i32, addr: RawAddress) -> BtStatus;
 
     /// Enumerates all GATT services on a connected device.
-    fn discover_services(&self, client_id: i32, addr: String);
+    #[dbus_method("DiscoverServices")]
+    fn discover_services(&mut self, client_id: i32, addr: RawAddress) -> BtStatus;
 
     /// Search a GATT service on a connected device based on a UUID.
-    fn discover_service_by_uuid(&self, client_id: i32, addr: String, uuid: String);
+    #[dbus_method("DiscoverServiceByUuid")]
+    fn discover_service_by_uuid(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+    ) -> BtStatus;
 
     /// Reads a characteristic on a remote device.
-    fn read_characteristic(&self, client_id: i32, addr: String, handle: i32, auth_req: i32);
+    ///
+    /// Values longer than a single PDU are read in chunks and reported as a whole.
+    #[dbus_method("ReadCharacteristic")]
+    fn read_characteristic(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        auth_req: i32,
+    ) -> BtStatus;
 
-    /// Reads a characteristic on a remote device.
+    /// Reads the first characteristic with the given UUID between `start_handle` and
+    /// `end_handle`. Long values are completed with a read by handle and reported as a whole.
+    #[dbus_method("ReadUsingCharacteristicUuid")]
     fn read_using_characteristic_uuid(
-        &self,
+        &mut self,
         client_id: i32,
-        addr: String,
-        uuid: String,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
         start_handle: i32,
         end_handle: i32,
         auth_req: i32,
-    );
+    ) -> BtStatus;
 
     /// Writes a remote characteristic.
+    ///
+    /// Values longer than a single PDU are written in chunks with Prepare Write. This isn't
+    /// possible without a response, so `WriteNoRsp` fails for those. Values longer than 512 bytes
+    /// always fail.
+    #[dbus_method("WriteCharacteristic")]
     fn write_characteristic(
-        &self,
+        &mut self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         handle: i32,
         write_type: GattWriteType,
         auth_req: i32,
         value: Vec<u8>,
     ) -> GattWriteRequestStatus;
 
+    /// Writes the first characteristic with the given UUID found between `start_handle` and
+    /// `end_handle`, without requiring a prior service discovery.
+    ///
+    /// The handle is looked up with a read by UUID, and the result of the write is reported
+    /// through `on_characteristic_write`.
+    #[dbus_method("WriteUsingCharacteristicUuid")]
+    fn write_using_characteristic_uuid(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+        start_handle: i32,
+        end_handle: i32,
+        write_type: GattWriteType,
+        auth_req: i32,
+        value: Vec<u8>,
+    ) -> GattWriteRequestStatus;
+
     /// Reads the descriptor for a given characteristic.
-    fn read_descriptor(&self, client_id: i32, addr: String, handle: i32, auth_req: i32);
+    #[dbus_method("ReadDescriptor")]
+    fn read_descriptor(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        auth_req: i32,
+    ) -> BtStatus;
 
     /// Writes a remote descriptor for a given characteristic.
+    #[dbus_method("WriteDescriptor")]
     fn write_descriptor(
-        &self,
+        &mut self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         handle: i32,
         auth_req: i32,
         value: Vec<u8>,
-    );
+    ) -> BtStatus;
 
     /// Registers to receive notifications or indications for a given characteristic.
-    fn register_for_notification(&self, client_id: i32, addr: String, handle: i32, enable: bool);
+    #[dbus_method("RegisterForNotification")]
+    fn register_for_notification(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        enable: bool,
+    ) -> BtStatus;
 
     /// Begins reliable write.
-    fn begin_reliable_write(&mut self, client_id: i32, addr: String);
+    #[dbus_method("BeginReliableWrite")]
+    fn begin_reliable_write(&mut self, client_id: i32, addr: RawAddress) -> BtStatus;
 
     /// Ends reliable write.
-    fn end_reliable_write(&mut self, client_id: i32, addr: String, execute: bool);
+    #[dbus_method("EndReliableWrite")]
+    fn end_reliable_write(&mut self, client_id: i32, addr: RawAddress, execute: bool) -> BtStatus;
 
     /// Requests RSSI for a given remote device.
-    fn read_remote_rssi(&self, client_id: i32, addr: String);
+    #[dbus_method("ReadRemoteRssi")]
+    fn read_remote_rssi(&self, client_id: i32, addr: RawAddress) -> BtStatus;
 
     /// Configures the MTU of a given connection.
-    fn configure_mtu(&self, client_id: i32, addr: String, mtu: i32);
+    #[dbus_method("ConfigureMtu")]
+    fn configure_mtu(&self, client_id: i32, addr: RawAddress, mtu: i32) -> BtStatus;
+
+    /// Returns the current ATT MTU of a given connection, or 0 if there is no such connection.
+    #[dbus_method("GetMtu")]
+    fn get_mtu(&self, client_id: i32, addr: RawAddress) -> i32;
+
+    /// Delivers the notifications and indications the client receives in batches through
+    /// `on_notify_batch`, one batch per connection every `interval_ms`, instead of one
+    /// `on_notify` each. An interval of 0 goes back to delivering them one by one.
+    #[dbus_method("SetNotificationBatching")]
+    fn set_notification_batching(&mut self, client_id: i32, interval_ms: u32) -> BtStatus;
+
+    /// Makes reads and writes of the client that fail with Insufficient Authentication or
+    /// Insufficient Encryption be sent once more, with the authentication requirement raised so
+    /// that the link is encrypted first, pairing with the device if it isn't bonded. Only the
+    /// outcome of the second attempt is reported. Off by default.
+    #[dbus_method("SetAutoElevateSecurity")]
+    fn set_auto_elevate_security(&mut self, client_id: i32, enabled: bool) -> BtStatus;
+
+    /// Fails the requests of the client that the remote server doesn't answer within
+    /// `timeout_ms`, with `GattStatus::RequestTimeout` in the callback they would have completed
+    /// with, so that the requests queued behind them aren't held up. The native stack can't take
+    /// back a request it sent, so its response is dropped if it comes later, and if `disconnect`
+    /// the unresponsive server is disconnected as well. A timeout of 0, the default, waits as
+    /// long as the native stack does, which gives up on the server after 30 seconds.
+    #[dbus_method("SetRequestTimeout")]
+    fn set_request_timeout(
+        &mut self,
+        client_id: i32,
+        timeout_ms: u32,
+        disconnect: bool,
+    ) -> BtStatus;
 
     /// Requests a connection parameter update.
+    #[dbus_method("ConnectionParameterUpdate")]
     fn connection_parameter_update(
         &self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         min_interval: i32,
         max_interval: i32,
         latency: i32,
         timeout: i32,
         min_ce_len: u16,
         max_ce_len: u16,
-    );
+    ) -> BtStatus;
+
+    /// Asks for the LE connection parameters of `priority` on the link to `addr`, which the
+    /// client must be connected to. Requesting `Balanced` releases an earlier request. When
+    /// several clients of the same device have a request, `High` wins over `LowPower`. The
+    /// outcome is reported through `on_connection_updated`.
+    #[dbus_method("RequestConnectionPriority")]
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        priority: ConnectionPriority,
+    ) -> BtStatus;
+
+    /// Makes the client decide whether remote devices may read the Device Information Service
+    /// and write Fast Pair account keys, through `on_authorization_request`. The first such
+    /// request on a connection waits for an answer from `send_authorization_response`, which
+    /// holds for the rest of the connection. The requests are served without asking while no
+    /// client authorizes them. Off by default.
+    #[dbus_method("SetServerAuthorization")]
+    fn set_server_authorization(&mut self, client_id: i32, enabled: bool) -> BtStatus;
+
+    /// Answers the `on_authorization_request` about `handle` for `addr`. Fails if nothing waits
+    /// for that answer.
+    #[dbus_method("SendAuthorizationResponse")]
+    fn send_authorization_response(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        allowed: bool,
+    ) -> BtStatus;
+
+    /// Sets the manufacturer name and model number served by the Device Information Service of
+    /// the stack. They default to what the firmware reports about the product.
+    #[dbus_method("SetDeviceInformation")]
+    fn set_device_information(&mut self, manufacturer: String, model: String);
+
+    /// Sets the appearance served by the GAP service, as listed in the Bluetooth Assigned
+    /// Numbers. Defaults to a generic computer.
+    #[dbus_method("SetAppearance")]
+    fn set_appearance(&mut self, appearance: u16);
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 /// Represents a GATT Descriptor.
 pub struct BluetoothGattDescriptor {
     pub uuid: Uuid128Bit,
@@ -272,7 +844,7 @@ impl BluetoothGattDescriptor {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 /// Represents a GATT Characteristic.
 pub struct BluetoothGattCharacteristic {
     pub uuid: Uuid128Bit,
@@ -294,6 +866,112 @@ impl BluetoothGattCharacteristic {
     pub const PROPERTY_SIGNED_WRITE: i32 = 0x40;
     pub const PROPERTY_EXTENDED_PROPS: i32 = 0x80;
 
+    // Permissions are those of the native stack (GATT_PERM_*). The ones of remote attributes
+    // aren't sent over the air: they are first guessed from the properties, and corrected when
+    // the server refuses an access for want of encryption or authentication.
+    pub const PERMISSION_READ: i32 = 0x01;
+    pub const PERMISSION_READ_ENCRYPTED: i32 = 0x02;
+    pub const PERMISSION_READ_ENCRYPTED_MITM: i32 = 0x04;
+    pub const PERMISSION_WRITE: i32 = 0x10;
+    pub const PERMISSION_WRITE_ENCRYPTED: i32 = 0x20;
+    pub const PERMISSION_WRITE_ENCRYPTED_MITM: i32 = 0x40;
+    pub const PERMISSION_WRITE_SIGNED: i32 = 0x80;
+    pub const PERMISSION_WRITE_SIGNED_MITM: i32 = 0x100;
+
+    const READ_PERMISSIONS: i32 = BluetoothGattCharacteristic::PERMISSION_READ
+        | BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED
+        | BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED_MITM;
+    const WRITE_PERMISSIONS: i32 = BluetoothGattCharacteristic::PERMISSION_WRITE
+        | BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED
+        | BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED_MITM;
+
+    /// Names of the bits set in `properties`, in the order of the bits.
+    pub fn property_names(properties: i32) -> Vec<&'static str> {
+        [
+            (BluetoothGattCharacteristic::PROPERTY_BROADCAST, "broadcast"),
+            (BluetoothGattCharacteristic::PROPERTY_READ, "read"),
+            (BluetoothGattCharacteristic::PROPERTY_WRITE_NO_RESPONSE, "write-without-response"),
+            (BluetoothGattCharacteristic::PROPERTY_WRITE, "write"),
+            (BluetoothGattCharacteristic::PROPERTY_NOTIFY, "notify"),
+            (BluetoothGattCharacteristic::PROPERTY_INDICATE, "indicate"),
+            (BluetoothGattCharacteristic::PROPERTY_SIGNED_WRITE, "signed-write"),
+            (BluetoothGattCharacteristic::PROPERTY_EXTENDED_PROPS, "extended-properties"),
+        ]
+        .iter()
+        .filter(|(bit, _)| properties & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+    }
+
+    /// Names of the bits set in `permissions`, of characteristics or descriptors, in the order
+    /// of the bits.
+    pub fn permission_names(permissions: i32) -> Vec<&'static str> {
+        [
+            (BluetoothGattCharacteristic::PERMISSION_READ, "read"),
+            (BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED, "read-encrypted"),
+            (BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED_MITM, "read-authenticated"),
+            (BluetoothGattCharacteristic::PERMISSION_WRITE, "write"),
+            (BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED, "write-encrypted"),
+            (BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED_MITM, "write-authenticated"),
+            (BluetoothGattCharacteristic::PERMISSION_WRITE_SIGNED, "write-signed"),
+            (
+                BluetoothGattCharacteristic::PERMISSION_WRITE_SIGNED_MITM,
+                "write-signed-authenticated",
+            ),
+        ]
+        .iter()
+        .filter(|(bit, _)| permissions & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+    }
+
+    /// The permissions a remote characteristic with `properties` has at least, before any access
+    /// to it was refused.
+    fn permissions_from_properties(properties: i32) -> i32 {
+        let mut permissions = 0;
+        if properties & BluetoothGattCharacteristic::PROPERTY_READ != 0 {
+            permissions |= BluetoothGattCharacteristic::PERMISSION_READ;
+        }
+        if properties
+            & (BluetoothGattCharacteristic::PROPERTY_WRITE
+                | BluetoothGattCharacteristic::PROPERTY_WRITE_NO_RESPONSE)
+            != 0
+        {
+            permissions |= BluetoothGattCharacteristic::PERMISSION_WRITE;
+        }
+        if properties & BluetoothGattCharacteristic::PROPERTY_SIGNED_WRITE != 0 {
+            permissions |= BluetoothGattCharacteristic::PERMISSION_WRITE_SIGNED;
+        }
+        permissions
+    }
+
+    /// Permissions of an attribute that had `permissions`, once a read of it, or a write if
+    /// `write`, failed with `status`. They don't change for other failures.
+    fn permissions_after_failure(permissions: i32, write: bool, status: i32) -> i32 {
+        let (all, encrypted, authenticated) = if write {
+            (
+                BluetoothGattCharacteristic::WRITE_PERMISSIONS,
+                BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED,
+                BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED_MITM,
+            )
+        } else {
+            (
+                BluetoothGattCharacteristic::READ_PERMISSIONS,
+                BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED,
+                BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED_MITM,
+            )
+        };
+        let required = match GattStatus::from_i32(status) {
+            Some(GattStatus::ReadNotPermit) if !write => 0,
+            Some(GattStatus::WriteNotPermit) if write => 0,
+            // Encryption was enough if the link was encrypted without MITM protection already.
+            Some(GattStatus::InsufEncryption) if permissions & authenticated == 0 => encrypted,
+            Some(GattStatus::InsufAuthentication) => authenticated,
+            _ => return permissions,
+        };
+        (permissions & !all) | required
+    }
+
     fn new(
         uuid: Uuid128Bit,
         instance_id: i32,
@@ -317,7 +995,16 @@ impl BluetoothGattCharacteristic {
     }
 }
 
-#[derive(Debug, Default)]
+/// A notification or indication delivered as part of `IBluetoothGattCallback::on_notify_batch`.
+#[derive(Clone, Debug, Default)]
+pub struct GattNotification {
+    pub handle: i32,
+    pub value: Vec<u8>,
+    /// When the notification was received, in microseconds since the Unix epoch.
+    pub timestamp_us: u64,
+}
+
+#[derive(Clone, Debug, Default)]
 /// Represents a GATT Service.
 pub struct BluetoothGattService {
     pub uuid: Uuid128Bit,
@@ -350,46 +1037,55 @@ pub trait IBluetoothGattCallback: RPCProxy {
         status: i32,
         client_id: i32,
         connected: bool,
-        addr: String,
+        addr: RawAddress,
     );
 
     /// When there is a change of PHY.
-    fn on_phy_update(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus);
+    fn on_phy_update(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus);
 
     /// The completion of IBluetoothGatt::read_phy.
-    fn on_phy_read(&self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus);
+    fn on_phy_read(&self, addr: RawAddress, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus);
 
     /// When GATT db is available.
-    fn on_search_complete(&self, addr: String, services: Vec<BluetoothGattService>, status: i32);
+    fn on_search_complete(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        status: i32,
+    );
 
     /// The completion of IBluetoothGatt::read_characteristic.
-    fn on_characteristic_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>);
+    fn on_characteristic_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>);
 
     /// The completion of IBluetoothGatt::write_characteristic.
-    fn on_characteristic_write(&self, addr: String, status: i32, handle: i32);
+    fn on_characteristic_write(&self, addr: RawAddress, status: i32, handle: i32);
 
     /// When a reliable write is completed.
-    fn on_execute_write(&self, addr: String, status: i32);
+    fn on_execute_write(&self, addr: RawAddress, status: i32);
 
     /// The completion of IBluetoothGatt::read_descriptor.
-    fn on_descriptor_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>);
+    fn on_descriptor_read(&self, addr: RawAddress, status: i32, handle: i32, value: Arc<[u8]>);
 
     /// The completion of IBluetoothGatt::write_descriptor.
-    fn on_descriptor_write(&self, addr: String, status: i32, handle: i32);
+    fn on_descriptor_write(&self, addr: RawAddress, status: i32, handle: i32);
 
     /// When notification or indication is received.
-    fn on_notify(&self, addr: String, handle: i32, value: Vec<u8>);
+    fn on_notify(&self, addr: RawAddress, handle: i32, value: Arc<[u8]>);
+
+    /// The notifications and indications received from `addr` since the previous batch, oldest
+    /// first, when batching is enabled through IBluetoothGatt::set_notification_batching.
+    fn on_notify_batch(&self, addr: RawAddress, notifications: Vec<GattNotification>);
 
     /// The completion of IBluetoothGatt::read_remote_rssi.
-    fn on_read_remote_rssi(&self, addr: String, rssi: i32, status: i32);
+    fn on_read_remote_rssi(&self, addr: RawAddress, rssi: i32, status: i32);
 
     /// The completion of IBluetoothGatt::configure_mtu.
-    fn on_configure_mtu(&self, addr: String, mtu: i32, status: i32);
+    fn on_configure_mtu(&self, addr: RawAddress, mtu: i32, status: i32);
 
     /// When a connection parameter changes.
     fn on_connection_updated(
         &self,
-        addr: String,
+        addr: RawAddress,
         interval: i32,
         latency: i32,
         timeout: i32,
@@ -397,19 +1093,33 @@ pub trait IBluetoothGattCallback: RPCProxy {
     );
 
     /// When there is an addition, removal, or change of a GATT service.
-    fn on_service_changed(&self, addr: String);
+    fn on_service_changed(&self, addr: RawAddress);
+
+    /// When the services of a device were discovered again after a change, because the client
+    /// had subscriptions on it. `database_hash` is empty if the device doesn't expose one.
+    fn on_gatt_db_updated(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        database_hash: Vec<u8>,
+    );
+
+    /// When `addr` first reads or writes the sensitive characteristic at `handle` of a service
+    /// the stack hosts on a connection, if the client authorizes server requests. The request
+    /// waits for `send_authorization_response`.
+    fn on_authorization_request(&self, addr: RawAddress, handle: i32);
 }
 
 /// Interface for scanner callbacks to clients, passed to `IBluetoothGatt::register_scanner`.
-pub trait IScannerCallback {
+pub trait IScannerCallback: RPCProxy {
     /// When the `register_scanner` request is done.
     fn on_scanner_registered(&self, status: i32, scanner_id: i32);
 }
 
 #[derive(Debug, FromPrimitive, ToPrimitive)]
 #[repr(u8)]
-/// GATT write type.
-enum GattDbElementType {
+/// Type of an attribute in a GATT database.
+pub(crate) enum GattDbElementType {
     PrimaryService = 0,
     SecondaryService = 1,
     IncludedService = 2,
@@ -417,7 +1127,7 @@ enum GattDbElementType {
     Descriptor = 4,
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Clone, Debug, FromPrimitive, ToPrimitive)]
 #[repr(u8)]
 /// GATT write type.
 pub enum GattWriteType {
@@ -433,7 +1143,7 @@ impl Default for GattWriteType {
     }
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Clone, Debug, FromPrimitive, PartialEq, ToPrimitive)]
 #[repr(u8)]
 /// Represents LE PHY.
 pub enum LePhy {
@@ -443,7 +1153,53 @@ pub enum LePhy {
     PhyCoded = 3,
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+/// Presets of LE connection parameters, with the same values as Android's
+/// CONNECTION_PRIORITY_*.
+pub enum ConnectionPriority {
+    Balanced = 0,
+    /// Short connection interval, for input devices and audio.
+    High = 1,
+    /// Long connection interval and some peripheral latency, to save power on both sides.
+    LowPower = 2,
+}
+
+impl Default for ConnectionPriority {
+    fn default() -> Self {
+        ConnectionPriority::Balanced
+    }
+}
+
+/// Supervision timeout of every `ConnectionPriority`, in units of 10 ms.
+pub(crate) const CONNECTION_PRIORITY_TIMEOUT: i32 = 500;
+
+impl ConnectionPriority {
+    /// Returns the minimum and maximum connection interval, in units of 1.25 ms, and the
+    /// peripheral latency of the preset.
+    pub(crate) fn parameters(&self) -> (i32, i32, i32) {
+        match self {
+            ConnectionPriority::Balanced => (24, 40, 0),
+            ConnectionPriority::High => (9, 12, 0),
+            ConnectionPriority::LowPower => (80, 100, 2),
+        }
+    }
+
+    /// Picks the priority to apply to a link out of what its clients requested.
+    fn combine<'a, I: IntoIterator<Item = &'a ConnectionPriority>>(requests: I) -> Self {
+        let mut combined = ConnectionPriority::Balanced;
+        for priority in requests {
+            match priority {
+                ConnectionPriority::High => return ConnectionPriority::High,
+                ConnectionPriority::LowPower => combined = ConnectionPriority::LowPower,
+                ConnectionPriority::Balanced => {}
+            }
+        }
+        combined
+    }
+}
+
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
 #[repr(u32)]
 /// Scan type configuration.
 pub enum ScanType {
@@ -478,47 +1234,1030 @@ pub struct ScanSettings {
 #[derive(Debug, Default)]
 pub struct ScanFilter {}
 
-/// Implementation of the GATT API (IBluetoothGatt).
-pub struct BluetoothGatt {
-    intf: Arc<Mutex<BluetoothInterface>>,
-    gatt: Option<Gatt>,
+/// Actions that `BluetoothGatt` takes on behalf of the stack.
+pub enum GattActions {
+    /// Hosts the services of the stack once the adapter is enabled.
+    RegisterLocalServices,
+    /// Delivers the notifications batched on a connection.
+    FlushNotifications(i32),
+    /// Tells whether a device is bonded. Only the services of bonded devices are cached.
+    SetBonded(RawAddress, bool),
+    /// The callback of the client with this app UUID went away, so the client goes too.
+    ClientCallbackDisconnected(Uuid128Bit),
+    /// The operation with this number, sent on a connection, got no response in time.
+    RequestTimeout(i32, u32),
+}
 
-    context_map: ContextMap,
-    reliable_queue: HashSet<String>,
+// Services hosted by the stack itself because many peers expect them on any device. The GAP
+// service is served by the native stack, so only its appearance is kept here.
+struct LocalServices {
+    server_if: Option<i32>,
+    manufacturer: String,
+    model: String,
+    appearance: u16,
+
+    // Value handles of the Device Information Service characteristics once it is added.
+    manufacturer_handle: Option<i32>,
+    model_handle: Option<i32>,
+
+    // Reads waiting for the clients to authorize them.
+    pending_reads: Vec<PendingRead>,
 }
 
-impl BluetoothGatt {
-    /// Constructs a new IBluetoothGatt implementation.
-    pub fn new(intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothGatt {
-        BluetoothGatt {
-            intf: intf,
-            gatt: None,
-            context_map: ContextMap::new(),
-            reliable_queue: HashSet::new(),
+// A read of the local services, kept until it can be answered.
+struct PendingRead {
+    conn_id: i32,
+    trans_id: i32,
+    address: RawAddress,
+    handle: i32,
+    offset: i32,
+}
+
+impl LocalServices {
+    fn new() -> LocalServices {
+        LocalServices {
+            server_if: None,
+            manufacturer: read_dmi_id("sys_vendor"),
+            model: read_dmi_id("product_name"),
+            appearance: APPEARANCE_GENERIC_COMPUTER,
+            manufacturer_handle: None,
+            model_handle: None,
+            pending_reads: vec![],
         }
     }
 
-    pub fn init_profiles(&mut self, tx: Sender<Message>) {
-        self.gatt = Gatt::new(&self.intf.lock().unwrap());
-        self.gatt.as_mut().unwrap().initialize(
-            GattClientCallbacksDispatcher {
-                dispatch: Box::new(move |cb| {
-                    let tx_clone = tx.clone();
-                    topstack::get_runtime().spawn(async move {
-                        let _ = tx_clone.send(Message::GattClient(cb)).await;
-                    });
-                }),
-            },
-            GattServerCallbacksDispatcher {
-                dispatch: Box::new(move |cb| {
-                    // TODO(b/193685149): Implement the callbacks
-                    debug!("received Gatt server callback: {:?}", cb);
-                }),
-            },
-            GattScannerCallbacksDispatcher {
-                dispatch: Box::new(move |cb| {
-                    debug!("received Gatt scanner callback: {:?}", cb);
-                }),
+    fn get_value(&self, handle: i32) -> Option<&str> {
+        if self.manufacturer_handle == Some(handle) {
+            Some(&self.manufacturer)
+        } else if self.model_handle == Some(handle) {
+            Some(&self.model)
+        } else {
+            None
+        }
+    }
+}
+
+fn read_dmi_id(name: &str) -> String {
+    fs::read_to_string(format!("{}/{}", DMI_ID_PATH, name))
+        .map(|value| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn device_information_service() -> Vec<BtGattDbElement> {
+    let characteristic = |uuid| BtGattDbElement {
+        uuid: parse_uuid_string(uuid).unwrap(),
+        type_: GattDbElementType::Characteristic as u32,
+        properties: GATT_CHAR_PROP_READ,
+        permissions: GATT_PERM_READ,
+        ..Default::default()
+    };
+
+    vec![
+        BtGattDbElement {
+            uuid: parse_uuid_string(DEVICE_INFORMATION_SERVICE).unwrap(),
+            type_: GattDbElementType::PrimaryService as u32,
+            ..Default::default()
+        },
+        characteristic(MANUFACTURER_NAME_STRING),
+        characteristic(MODEL_NUMBER_STRING),
+    ]
+}
+
+/// Returns the write type to use for a value of `len` bytes on a connection with `mtu`, or None if
+/// it can't be written. Writes of a reliable write are all prepared.
+///
+/// Long writes are chunked by the native stack, which has no way to be given the offset of a
+/// Prepare Write Request: a `Write` of a value that doesn't fit a Write Request is sent as Prepare
+/// Write Requests of `mtu - 5` bytes at increasing offsets followed by an Execute Write Request,
+/// and fails as a whole if any of them does. A write without response is a single Write Command
+/// and a write of a reliable write a single Prepare Write Request, so their values have to fit.
+fn write_type_for_len(
+    write_type: GattWriteType,
+    len: usize,
+    mtu: i32,
+    reliable: bool,
+) -> Option<GattWriteType> {
+    if len > GATT_MAX_ATTR_LEN {
+        warn!("Value of {} bytes is too long to write", len);
+        return None;
+    }
+
+    let (write_type, max_len) = match write_type {
+        _ if reliable => (GattWriteType::WritePrepare, mtu - ATT_PREPARE_WRITE_HEADER_LEN),
+        GattWriteType::WriteNoRsp => (write_type, mtu - ATT_WRITE_HEADER_LEN),
+        GattWriteType::WritePrepare => (write_type, mtu - ATT_PREPARE_WRITE_HEADER_LEN),
+        write_type => return Some(write_type),
+    };
+    if len as i32 > max_len {
+        warn!("Value of {} bytes doesn't fit a single {:?}", len, write_type);
+        return None;
+    }
+    Some(write_type)
+}
+
+/// Returns the part of `value` that a read starting at `offset` gets.
+pub(crate) fn read_at_offset(value: &[u8], offset: i32) -> Result<&[u8], GattStatus> {
+    if offset < 0 || offset as usize > value.len() {
+        return Err(GattStatus::InvalidOffset);
+    }
+
+    Ok(&value[offset as usize..])
+}
+
+/// Implementation of the GATT API (IBluetoothGatt).
+// Services of a device along with the Database Hash they were discovered for.
+struct CachedDatabase {
+    hash: Vec<u8>,
+    services: Vec<BluetoothGattService>,
+}
+
+// The cached services of bonded devices, saved to a config file of their own.
+struct GattCache {
+    path: String,
+    databases: HashMap<RawAddress, CachedDatabase>,
+}
+
+impl GattCache {
+    fn load(path: &str) -> GattCache {
+        let sections = match fs::read_to_string(path) {
+            Ok(contents) => parse_ini(&contents),
+            Err(_) => vec![],
+        };
+
+        let databases = sections
+            .iter()
+            .filter_map(|section| {
+                let address = RawAddress::from_string(section.name.clone())?;
+                let hash = section.get(KEY_DATABASE_HASH).and_then(from_hex_array::<16>)?;
+                let services = services_from_string(section.get(KEY_ATTRIBUTES)?)?;
+                Some((address, CachedDatabase { hash: hash.to_vec(), services }))
+            })
+            .collect();
+
+        GattCache { path: String::from(path), databases }
+    }
+
+    fn get(&self, address: &RawAddress) -> Option<&CachedDatabase> {
+        self.databases.get(address)
+    }
+
+    fn set(&mut self, address: RawAddress, database: CachedDatabase) {
+        self.databases.insert(address, database);
+        self.save_or_warn();
+    }
+
+    fn remove(&mut self, address: &RawAddress) {
+        if self.databases.remove(address).is_some() {
+            self.save_or_warn();
+        }
+    }
+
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            warn!("Failed to save {}: {}", self.path, e);
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let mut addresses: Vec<&RawAddress> = self.databases.keys().collect();
+        addresses.sort_by_key(|address| address.to_string());
+        let sections = addresses
+            .iter()
+            .map(|address| {
+                let database = &self.databases[address];
+                Section {
+                    name: address.to_string().to_lowercase(),
+                    entries: vec![
+                        (String::from(KEY_DATABASE_HASH), to_hex(&database.hash)),
+                        (String::from(KEY_ATTRIBUTES), services_to_string(&database.services)),
+                    ],
+                }
+            })
+            .collect();
+
+        let tmp = format!("{}.tmp", self.path);
+        fs::write(&tmp, to_ini(&sections))?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+// Writes the attributes of `services` as comma separated entries of their kind, handle, UUID and
+// service type or properties, in the order of the database.
+//
+// Permissions aren't kept, as they are only guessed, and are guessed again when read back.
+fn services_to_string(services: &[BluetoothGattService]) -> String {
+    let mut entries = vec![];
+    for service in services {
+        entries.push(format!(
+            "s:{}:{}:{}",
+            service.instance_id,
+            to_hex(&service.uuid),
+            service.service_type
+        ));
+        for included in &service.included_services {
+            entries.push(format!(
+                "i:{}:{}:{}",
+                included.instance_id,
+                to_hex(&included.uuid),
+                included.service_type
+            ));
+        }
+        for characteristic in &service.characteristics {
+            entries.push(format!(
+                "c:{}:{}:{}",
+                characteristic.instance_id,
+                to_hex(&characteristic.uuid),
+                characteristic.properties
+            ));
+            for descriptor in &characteristic.descriptors {
+                entries.push(format!(
+                    "d:{}:{}:0",
+                    descriptor.instance_id,
+                    to_hex(&descriptor.uuid)
+                ));
+            }
+        }
+    }
+
+    entries.join(",")
+}
+
+fn services_from_string(s: &str) -> Option<Vec<BluetoothGattService>> {
+    let mut services: Vec<BluetoothGattService> = vec![];
+    for entry in s.split(',').filter(|entry| !entry.is_empty()) {
+        let fields: Vec<&str> = entry.split(':').collect();
+        if fields.len() != 4 {
+            return None;
+        }
+
+        let handle: i32 = fields[1].parse().ok()?;
+        let uuid = from_hex_array::<16>(fields[2])?;
+        let value: i32 = fields[3].parse().ok()?;
+        match fields[0] {
+            "s" => services.push(BluetoothGattService::new(uuid, handle, value)),
+            "i" => services
+                .last_mut()?
+                .included_services
+                .push(BluetoothGattService::new(uuid, handle, value)),
+            "c" => services.last_mut()?.characteristics.push(BluetoothGattCharacteristic::new(
+                uuid,
+                handle,
+                value,
+                BluetoothGattCharacteristic::permissions_from_properties(value),
+            )),
+            "d" => services
+                .last_mut()?
+                .characteristics
+                .last_mut()?
+                .descriptors
+                .push(BluetoothGattDescriptor::new(uuid, handle, 0)),
+            _ => return None,
+        }
+    }
+
+    Some(services)
+}
+
+/// Corrects the permissions of the characteristic or descriptor at `handle` in `services` after
+/// a read of it, or a write if `write`, failed with `status`.
+fn record_access_failure(
+    services: &mut [BluetoothGattService],
+    handle: i32,
+    write: bool,
+    status: i32,
+) {
+    let permissions = services
+        .iter_mut()
+        .flat_map(|service| service.characteristics.iter_mut())
+        .find_map(|characteristic| {
+            if characteristic.instance_id == handle {
+                return Some(&mut characteristic.permissions);
+            }
+            characteristic
+                .descriptors
+                .iter_mut()
+                .find(|descriptor| descriptor.instance_id == handle)
+                .map(|descriptor| &mut descriptor.permissions)
+        });
+    if let Some(permissions) = permissions {
+        *permissions =
+            BluetoothGattCharacteristic::permissions_after_failure(*permissions, write, status);
+    }
+}
+
+/// Keeps the permissions learned on the attributes of `old` in the same attributes of `new`, so
+/// that discovering the services again doesn't forget them.
+fn keep_permissions(old: &[BluetoothGattService], new: &mut [BluetoothGattService]) {
+    let mut learned = HashMap::new();
+    for characteristic in old.iter().flat_map(|service| service.characteristics.iter()) {
+        learned
+            .insert((characteristic.instance_id, characteristic.uuid), characteristic.permissions);
+        for descriptor in &characteristic.descriptors {
+            learned.insert((descriptor.instance_id, descriptor.uuid), descriptor.permissions);
+        }
+    }
+
+    for characteristic in new.iter_mut().flat_map(|service| service.characteristics.iter_mut()) {
+        if let Some(permissions) = learned.get(&(characteristic.instance_id, characteristic.uuid)) {
+            characteristic.permissions = *permissions;
+        }
+        for descriptor in characteristic.descriptors.iter_mut() {
+            if let Some(permissions) = learned.get(&(descriptor.instance_id, descriptor.uuid)) {
+                descriptor.permissions = *permissions;
+            }
+        }
+    }
+}
+
+pub struct BluetoothGatt {
+    intf: Arc<Mutex<BluetoothInterface>>,
+    gatt: Option<Gatt>,
+    tx: Sender<Message>,
+
+    context_map: ContextMap,
+    reliable_queue: HashSet<RawAddress>,
+    interop: InteropDatabase,
+    local_services: LocalServices,
+
+    // Connection priorities requested by each client, and the one applied to each link.
+    connection_priorities: HashMap<RawAddress, HashMap<i32, ConnectionPriority>>,
+    applied_priorities: HashMap<RawAddress, ConnectionPriority>,
+
+    bonded_devices: HashSet<RawAddress>,
+    gatt_cache: GattCache,
+    server_authorization: GattAuthorization,
+    activity: Arc<Mutex<ActivityTracker>>,
+    link_users: Arc<Mutex<LinkUsers>>,
+    radio: Arc<Mutex<RadioArbiter>>,
+}
+
+impl BluetoothGatt {
+    /// Constructs a new IBluetoothGatt implementation.
+    pub fn new(
+        tx: Sender<Message>,
+        intf: Arc<Mutex<BluetoothInterface>>,
+        activity: Arc<Mutex<ActivityTracker>>,
+        link_users: Arc<Mutex<LinkUsers>>,
+        radio: Arc<Mutex<RadioArbiter>>,
+    ) -> BluetoothGatt {
+        BluetoothGatt {
+            intf: intf,
+            gatt: None,
+            tx,
+            context_map: ContextMap::new(),
+            reliable_queue: HashSet::new(),
+            interop: InteropDatabase::load_default(),
+            local_services: LocalServices::new(),
+            connection_priorities: HashMap::new(),
+            applied_priorities: HashMap::new(),
+            bonded_devices: HashSet::new(),
+            gatt_cache: GattCache::load(GATT_CACHE_PATH),
+            server_authorization: GattAuthorization::new(),
+            activity,
+            link_users,
+            radio,
+        }
+    }
+
+    /// Gives the parts of the stack that host their own server or advertise access to GATT.
+    pub(crate) fn get_gatt(&mut self) -> Option<&mut Gatt> {
+        self.gatt.as_mut()
+    }
+
+    /// Reads the interop database again, for workarounds added in the field.
+    pub(crate) fn reload_interop(&mut self) {
+        self.interop = InteropDatabase::load_default();
+    }
+
+    pub fn dispatch_gatt_actions(&mut self, action: GattActions) {
+        match action {
+            GattActions::RegisterLocalServices => {
+                // Registrations don't survive the native stack going down with the adapter.
+                self.local_services.server_if = None;
+                self.local_services.manufacturer_handle = None;
+                self.local_services.model_handle = None;
+                self.local_services.pending_reads.clear();
+                self.server_authorization.clear();
+
+                let uuid = parse_uuid_string(LOCAL_SERVER_UUID).unwrap();
+                let status = self.gatt.as_ref().unwrap().server.register_server(&uuid, false);
+                if status != BtStatus::Success {
+                    warn!("Failed to register the local GATT server: {:?}", status);
+                }
+
+                self.intf.lock().unwrap().set_le_appearance(self.local_services.appearance);
+            }
+            GattActions::FlushNotifications(conn_id) => self.flush_notifications(conn_id),
+            GattActions::RequestTimeout(conn_id, sequence) => {
+                self.request_timed_out(conn_id, sequence)
+            }
+            GattActions::SetBonded(address, bonded) => {
+                if bonded {
+                    self.bonded_devices.insert(address);
+                } else {
+                    self.bonded_devices.remove(&address);
+                    self.gatt_cache.remove(&address);
+                }
+            }
+            GattActions::ClientCallbackDisconnected(app_uuid) => {
+                match self.context_map.get_by_uuid(&app_uuid).map(|client| client.id) {
+                    Some(Some(client_id)) => {
+                        self.unregister_client(client_id);
+                    }
+                    // The native client is unregistered once its registration completes.
+                    Some(None) => self.context_map.remove_by_uuid(&app_uuid),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Delivers the notifications batched on `conn_id`, if there are any.
+    fn flush_notifications(&mut self, conn_id: i32) {
+        let notifications = self.context_map.take_pending_notifications(conn_id);
+        if notifications.is_empty() {
+            return;
+        }
+
+        let address = self.context_map.get_address_by_conn_id(conn_id);
+        let client = self.context_map.get_client_by_conn_id(conn_id);
+        if let (Some(address), Some(client)) = (address, client) {
+            client.callback.on_notify_batch(address, notifications);
+        }
+    }
+
+    /// Updates the connection parameters of the link to `addr` to what its clients requested.
+    fn apply_connection_priority(&mut self, addr: &RawAddress) -> BtStatus {
+        let priority = ConnectionPriority::combine(
+            self.connection_priorities.get(addr).into_iter().flat_map(|requests| requests.values()),
+        );
+        let applied = self.applied_priorities.get(addr).cloned().unwrap_or_default();
+        if priority == applied {
+            return BtStatus::Success;
+        }
+
+        let (min_interval, max_interval, latency) = priority.parameters();
+        let status = self.gatt.as_ref().unwrap().client.conn_parameter_update(
+            addr,
+            min_interval,
+            max_interval,
+            latency,
+            CONNECTION_PRIORITY_TIMEOUT,
+            0,
+            0,
+        );
+        if status == BtStatus::Success {
+            self.applied_priorities.insert(*addr, priority);
+        }
+        status
+    }
+
+    /// Drops the connection priority requests of `client_id`, on the link to `addr` or on all
+    /// links if None.
+    fn release_connection_priorities(&mut self, client_id: i32, addr: Option<RawAddress>) {
+        let addrs: Vec<RawAddress> = match addr {
+            Some(addr) => vec![addr],
+            None => self.connection_priorities.keys().cloned().collect(),
+        };
+
+        for addr in addrs {
+            let released = match self.connection_priorities.get_mut(&addr) {
+                Some(requests) => requests.remove(&client_id).is_some(),
+                None => false,
+            };
+            if self.connection_priorities.get(&addr).map_or(false, |r| r.is_empty()) {
+                self.connection_priorities.remove(&addr);
+            }
+
+            // Other clients may still be using the link.
+            let address = addr.to_string();
+            if !self.context_map.connections.iter().any(|conn| conn.address == address) {
+                self.connection_priorities.remove(&addr);
+                self.applied_priorities.remove(&addr);
+            } else if released {
+                self.apply_connection_priority(&addr);
+            }
+        }
+    }
+
+    fn avoid_2m_phy(&self, address: &RawAddress) -> bool {
+        self.interop.is_match(InteropFeature::Avoid2mPhy, address, None)
+    }
+
+    /// Returns the write type to use for a value of `len` bytes, or None if it can't be written.
+    fn resolve_write_type(
+        &self,
+        conn_id: i32,
+        addr: &RawAddress,
+        write_type: GattWriteType,
+        len: usize,
+    ) -> Option<GattWriteType> {
+        let mtu = self.context_map.get_mtu_by_conn_id(conn_id).unwrap();
+        write_type_for_len(write_type, len, mtu, self.reliable_queue.contains(addr))
+    }
+
+    fn queue_operation(&mut self, conn_id: i32, op: GattOperation) {
+        self.context_map.queue_operation(conn_id, op);
+        self.send_next_operation(conn_id);
+    }
+
+    /// Sends queued operations until one is accepted by the native stack.
+    fn send_next_operation(&mut self, conn_id: i32) {
+        while let Some(op) = self.context_map.next_operation(conn_id) {
+            match self.send_operation(conn_id, op) {
+                Ok(()) => break,
+                Err(op) => self.report_operation_failure(conn_id, op),
+            }
+        }
+    }
+
+    /// Sends `op` to the native stack, which reports its completion via a callback. The operation
+    /// is given back if it couldn't be sent.
+    fn send_operation(&mut self, conn_id: i32, op: GattOperation) -> Result<(), GattOperation> {
+        let client = &self.gatt.as_ref().unwrap().client;
+        let status = match &op {
+            // The native stack continues reads by handle with Read Blob until the whole value is
+            // read.
+            GattOperation::ReadCharacteristic { handle, auth_req } => {
+                client.read_characteristic(conn_id, *handle as u16, *auth_req)
+            }
+            GattOperation::ReadUsingCharacteristicUuid {
+                uuid,
+                start_handle,
+                end_handle,
+                auth_req,
+            }
+            | GattOperation::WriteUsingCharacteristicUuid {
+                uuid,
+                start_handle,
+                end_handle,
+                auth_req,
+                ..
+            } => client.read_using_characteristic_uuid(
+                conn_id,
+                uuid,
+                *start_handle as u16,
+                *end_handle as u16,
+                *auth_req,
+            ),
+            GattOperation::WriteCharacteristic { handle, write_type, auth_req, value } => client
+                .write_characteristic(
+                    conn_id,
+                    *handle as u16,
+                    write_type.to_i32().unwrap(),
+                    *auth_req,
+                    value,
+                ),
+            GattOperation::ReadDescriptor { handle, auth_req } => {
+                client.read_descriptor(conn_id, *handle as u16, *auth_req)
+            }
+            GattOperation::WriteDescriptor { handle, auth_req, value } => {
+                client.write_descriptor(conn_id, *handle as u16, *auth_req, value)
+            }
+            GattOperation::ExecuteWrite { execute } => {
+                client.execute_write(conn_id, if *execute { 1 } else { 0 })
+            }
+            GattOperation::ReadDatabaseHash { handle: Some(handle) } => {
+                client.read_characteristic(conn_id, *handle as u16, 0)
+            }
+            GattOperation::ReadDatabaseHash { handle: None } => client
+                .read_using_characteristic_uuid(
+                    conn_id,
+                    &Uuid { uu: DATABASE_HASH_UUID },
+                    0x0001,
+                    0xFFFF,
+                    0,
+                ),
+        };
+
+        if status != BtStatus::Success {
+            warn!("Failed to send GATT operation on connection {}: {:?}", conn_id, status);
+            return Err(op);
+        }
+
+        match &op {
+            GattOperation::WriteCharacteristic { value, .. }
+            | GattOperation::WriteDescriptor { value, .. } => {
+                self.count_traffic(conn_id, value.len(), 0)
+            }
+            _ => {}
+        }
+        self.context_map.set_outstanding_operation(conn_id, op);
+        self.start_request_timer(conn_id);
+        Ok(())
+    }
+
+    /// Takes the operation outstanding on `conn_id` that `callback` completes. Returns None if the
+    /// outstanding operation completes with another callback, which means `callback` isn't one
+    /// of its responses and has to be dropped.
+    fn take_completed_operation(
+        &mut self,
+        conn_id: i32,
+        callback: OperationCallback,
+    ) -> Option<Option<GattOperation>> {
+        match self.context_map.take_outstanding_operation(conn_id, callback) {
+            Ok(op) => Some(op),
+            Err(expected) => {
+                warn!(
+                    "Dropping {:?} callback on connection {}, expected {:?}",
+                    callback, conn_id, expected
+                );
+                None
+            }
+        }
+    }
+
+    /// Times the operation just sent on `conn_id`, with the timeout of its client or else
+    /// `OPERATION_BACKSTOP_TIMEOUT`.
+    fn start_request_timer(&mut self, conn_id: i32) {
+        let timeout = self
+            .context_map
+            .get_client_by_conn_id(conn_id)
+            .and_then(|client| client.request_timeout)
+            .unwrap_or(OPERATION_BACKSTOP_TIMEOUT);
+        let conn = match self.context_map.get_connection_mut(conn_id) {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        conn.operation_sequence = conn.operation_sequence.wrapping_add(1);
+        let sequence = conn.operation_sequence;
+        let txl = self.tx.clone();
+        let timer = tokio::spawn(async move {
+            time::sleep(timeout).await;
+            let _ = txl.send(Message::Gatt(GattActions::RequestTimeout(conn_id, sequence))).await;
+        });
+        if let Some(previous) = conn.operation_timer.replace(timer) {
+            previous.abort();
+        }
+    }
+
+    /// Fails the operation outstanding on `conn_id` if it is the one numbered `sequence`, and
+    /// disconnects the server or goes on with the next operation.
+    fn request_timed_out(&mut self, conn_id: i32, sequence: u32) {
+        let (op, address, client_id) = match self.context_map.get_connection_mut(conn_id) {
+            Some(conn) if conn.operation_sequence == sequence => {
+                let op = match conn.outstanding_operation.take() {
+                    Some(op) => op,
+                    None => return,
+                };
+                conn.operation_timer = None;
+                conn.security_retried = false;
+                conn.late_responses += 1;
+                (op, conn.address.clone(), conn.client_id)
+            }
+            _ => return,
+        };
+
+        let disconnect = self
+            .context_map
+            .get_by_client_id(client_id)
+            .map_or(false, |client| client.disconnect_on_timeout);
+        warn!(
+            "[{}]: GATT request on connection {} got no response in time{}",
+            address,
+            conn_id,
+            if disconnect { ", disconnecting" } else { "" }
+        );
+        self.report_operation_status(conn_id, op, GattStatus::RequestTimeout);
+
+        // Queued operations are dropped along with the connection.
+        if disconnect {
+            if let Some(addr) = RawAddress::from_string(address) {
+                self.gatt.as_ref().unwrap().client.disconnect(client_id, &addr, conn_id);
+                return;
+            }
+        }
+        self.send_next_operation(conn_id);
+    }
+
+    /// Counts attribute value bytes sent and received on `conn_id` toward the device's activity.
+    fn count_traffic(&self, conn_id: i32, tx_bytes: usize, rx_bytes: usize) {
+        let address = match self.context_map.get_address_by_conn_id(conn_id) {
+            Some(address) => address,
+            None => return,
+        };
+
+        let mut activity = self.activity.lock().unwrap();
+        activity.add_tx_bytes(address, tx_bytes);
+        activity.add_rx_bytes(address, rx_bytes);
+    }
+
+    /// Updates the permissions of the attribute `op` accessed in the services of the connection
+    /// with what its failure with `status` tells about them.
+    fn record_operation_status(&mut self, conn_id: i32, status: i32, op: &Option<GattOperation>) {
+        if status == GattStatus::Success as i32 {
+            return;
+        }
+        let (handle, write) = match op.as_ref().and_then(GattOperation::accessed_handle) {
+            Some(access) => access,
+            None => return,
+        };
+        if let Some(services) =
+            self.context_map.get_connection_mut(conn_id).and_then(|conn| conn.services.as_mut())
+        {
+            record_access_failure(services, handle, write, status);
+        }
+    }
+
+    /// Sends the operation that completed with `status` again, once, if it failed because the link
+    /// isn't encrypted or authenticated and its client asked for that. The raised authentication
+    /// requirement makes the native stack encrypt the link, or pair, first. The operation is given
+    /// back unless it was sent.
+    fn retry_with_security(
+        &mut self,
+        conn_id: i32,
+        status: i32,
+        op: Option<GattOperation>,
+    ) -> Result<(), Option<GattOperation>> {
+        let retried = match self.context_map.get_connection_mut(conn_id) {
+            Some(conn) => std::mem::replace(&mut conn.security_retried, false),
+            None => return Err(op),
+        };
+        let enabled = self
+            .context_map
+            .get_client_by_conn_id(conn_id)
+            .map_or(false, |client| client.auto_elevate_security);
+        let level = match GattStatus::from_i32(status) {
+            Some(GattStatus::InsufAuthentication) => GATT_AUTH_REQ_MITM,
+            Some(GattStatus::InsufEncryption) => GATT_AUTH_REQ_NO_MITM,
+            _ => return Err(op),
+        };
+        let mut op = match op {
+            Some(op) if enabled && !retried => op,
+            op => return Err(op),
+        };
+        if !op.raise_auth_req(level) {
+            return Err(Some(op));
+        }
+
+        debug!("Retrying GATT operation on connection {} with auth_req {}", conn_id, level);
+        self.send_operation(conn_id, op).map_err(Some)?;
+        self.context_map.set_security_retried(conn_id, true);
+        Ok(())
+    }
+
+    /// Reports an operation that couldn't be sent through the callback it would have completed
+    /// with.
+    fn report_operation_failure(&mut self, conn_id: i32, op: GattOperation) {
+        self.report_operation_status(conn_id, op, GattStatus::Error);
+    }
+
+    /// Completes an operation that got no response with `status`.
+    fn report_operation_status(&mut self, conn_id: i32, op: GattOperation, status: GattStatus) {
+        // The stack reads the Database Hash for itself, there is no client callback for it.
+        if let GattOperation::ReadDatabaseHash { handle } = op {
+            self.database_hash_read(conn_id, handle.is_some(), vec![]);
+            return;
+        }
+
+        let address = self.context_map.get_address_by_conn_id(conn_id);
+        let client = self.context_map.get_client_by_conn_id(conn_id);
+        if address.is_none() || client.is_none() {
+            return;
+        }
+
+        let address = address.unwrap();
+        let callback = &client.unwrap().callback;
+        let status = status.to_i32().unwrap();
+        match op {
+            GattOperation::ReadCharacteristic { handle, .. } => {
+                callback.on_characteristic_read(address, status, handle, Arc::from(vec![]))
+            }
+            GattOperation::ReadUsingCharacteristicUuid { .. } => {
+                callback.on_characteristic_read(address, status, 0, Arc::from(vec![]))
+            }
+            GattOperation::WriteUsingCharacteristicUuid { .. } => {
+                callback.on_characteristic_write(address, status, 0)
+            }
+            GattOperation::WriteCharacteristic { handle, .. } => {
+                callback.on_characteristic_write(address, status, handle)
+            }
+            GattOperation::ReadDescriptor { handle, .. } => {
+                callback.on_descriptor_read(address, status, handle, Arc::from(vec![]))
+            }
+            GattOperation::WriteDescriptor { handle, .. } => {
+                callback.on_descriptor_write(address, status, handle)
+            }
+            GattOperation::ExecuteWrite { .. } => callback.on_execute_write(address, status),
+            GattOperation::ReadDatabaseHash { .. } => {}
+        }
+    }
+
+    /// Takes the Database Hash read from the remote server, which is empty if it couldn't be read.
+    fn database_hash_read(&mut self, conn_id: i32, after_discovery: bool, hash: Vec<u8>) {
+        let discovery_requested = match self.context_map.get_connection_mut(conn_id) {
+            Some(conn) => {
+                conn.database_hash =
+                    if hash.len() == DATABASE_HASH_LEN { Some(hash.clone()) } else { None };
+                conn.hash_pending = false;
+                std::mem::take(&mut conn.discovery_requested)
+            }
+            None => return,
+        };
+
+        if after_discovery {
+            self.cache_services(conn_id);
+            self.report_gatt_db_updated(conn_id, hash);
+        } else if discovery_requested {
+            let status = self.discover_all_services(conn_id);
+            if status != BtStatus::Success {
+                warn!("Failed to discover services on connection {}: {:?}", conn_id, status);
+                let address = self.context_map.get_address_by_conn_id(conn_id);
+                let client = self.context_map.get_client_by_conn_id(conn_id);
+                if let (Some(address), Some(client)) = (address, client) {
+                    let status = GattStatus::Error.to_i32().unwrap();
+                    client.callback.on_search_complete(address, vec![], status);
+                }
+            }
+        }
+    }
+
+    /// Discovers the services of the remote server, unless they are cached for the Database Hash
+    /// it has now.
+    fn discover_all_services(&mut self, conn_id: i32) -> BtStatus {
+        let conn = match self.context_map.get_connection_mut(conn_id) {
+            Some(conn) => conn,
+            None => return BtStatus::RemoteDeviceDown,
+        };
+
+        if conn.hash_pending {
+            conn.discovery_requested = true;
+            return BtStatus::Success;
+        }
+
+        let address = match RawAddress::from_string(conn.address.clone()) {
+            Some(address) => address,
+            None => return BtStatus::RemoteDeviceDown,
+        };
+        let (bonded_devices, gatt_cache) = (&self.bonded_devices, &self.gatt_cache);
+        let cached = Some(address)
+            .filter(|address| bonded_devices.contains(address))
+            .and_then(|address| gatt_cache.get(&address))
+            .filter(|cached| Some(&cached.hash) == conn.database_hash.as_ref())
+            .map(|cached| cached.services.clone());
+        let mut services = match cached {
+            Some(services) => services,
+            None => return self.gatt.as_ref().unwrap().client.search_service(conn_id, None),
+        };
+        if let Some(old) = &conn.services {
+            keep_permissions(old, &mut services);
+        }
+
+        debug!("Database Hash of {} hasn't changed, using the cached services", conn.address);
+        conn.services = Some(services.clone());
+        if let Some(client) = self.context_map.get_client_by_conn_id(conn_id) {
+            client.callback.on_search_complete(address, services, 0);
+        }
+
+        BtStatus::Success
+    }
+
+    /// Keeps the services of a bonded device for its next connections, as long as they can be
+    /// checked against its Database Hash.
+    fn cache_services(&mut self, conn_id: i32) {
+        let conn = match self.context_map.connections.iter().find(|conn| conn.conn_id == conn_id) {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        let address = match RawAddress::from_string(conn.address.clone()) {
+            Some(address) if self.bonded_devices.contains(&address) => address,
+            _ => return,
+        };
+
+        match (&conn.database_hash, &conn.services) {
+            (Some(hash), Some(services)) => self
+                .gatt_cache
+                .set(address, CachedDatabase { hash: hash.clone(), services: services.clone() }),
+            _ => self.gatt_cache.remove(&address),
+        }
+    }
+
+    /// Delivers the services discovered again after a change, along with the Database Hash if
+    /// the remote server has one.
+    fn report_gatt_db_updated(&self, conn_id: i32, database_hash: Vec<u8>) {
+        let conn = self.context_map.connections.iter().find(|conn| conn.conn_id == conn_id);
+        let address = self.context_map.get_address_by_conn_id(conn_id);
+        let client = self.context_map.get_client_by_conn_id(conn_id);
+        if let (Some(conn), Some(address), Some(client)) = (conn, address, client) {
+            client.callback.on_gatt_db_updated(
+                address,
+                conn.services.clone().unwrap_or_default(),
+                database_hash,
+            );
+        }
+    }
+
+    /// Tells whether `addr` may use the sensitive characteristic at `handle` of a service the
+    /// stack hosts, on server connection `conn_id`. The clients that authorize server requests
+    /// are asked the first time, and the request then waits for their answer.
+    pub(crate) fn authorize_server_request(
+        &mut self,
+        conn_id: i32,
+        addr: RawAddress,
+        handle: i32,
+    ) -> Authorization {
+        if let Some(authorization) = self.server_authorization.get(conn_id, handle) {
+            return authorization;
+        }
+
+        let mut asked = false;
+        for client in self.context_map.clients.iter() {
+            if client.id.is_some() && client.authorizes_server_requests {
+                client.callback.on_authorization_request(addr, handle);
+                asked = true;
+            }
+        }
+        if !asked {
+            return Authorization::Allowed;
+        }
+
+        self.server_authorization.ask(conn_id, addr, handle);
+        Authorization::Pending
+    }
+
+    /// Forgets the authorizations of server connection `conn_id`, which is closed.
+    pub(crate) fn server_disconnected(&mut self, conn_id: i32) {
+        self.server_authorization.disconnected(conn_id);
+        self.local_services.pending_reads.retain(|read| read.conn_id != conn_id);
+    }
+
+    fn respond_local_read(&self, read: PendingRead, allowed: bool) {
+        let result = match self.local_services.get_value(read.handle) {
+            Some(_) if !allowed => Err(GattStatus::InsufAuthorization),
+            Some(value) => read_at_offset(value.as_bytes(), read.offset),
+            None => Err(GattStatus::InvalidHandle),
+        };
+
+        let mut value = BtGattValue::default();
+        let status = match result {
+            Ok(data) => {
+                // The native stack trims the value to what fits the MTU of the connection.
+                let len = min(data.len(), value.value.len());
+                value.value[..len].copy_from_slice(&data[..len]);
+                value.len = len as u16;
+                GattStatus::Success
+            }
+            Err(status) => {
+                debug!(
+                    "[{}]: Rejecting read of handle {}: {:?}",
+                    read.address.to_string(),
+                    read.handle,
+                    status
+                );
+                status
+            }
+        };
+
+        value.handle = read.handle as u16;
+        value.offset = read.offset as u16;
+        let response = BtGattResponse { attr_value: value };
+        self.gatt.as_ref().unwrap().server.send_response(
+            read.conn_id,
+            read.trans_id,
+            status as i32,
+            &response,
+        );
+    }
+
+    pub fn init_profiles(&mut self, tx: Sender<Message>) {
+        self.gatt = Gatt::new(&self.intf.lock().unwrap());
+        let tx_server = tx.clone();
+        let tx_scanner = tx.clone();
+        let tx_scanner_inband = tx.clone();
+        let tx_adv = tx.clone();
+        let tx_adv_inband = tx.clone();
+        self.gatt.as_mut().unwrap().initialize(
+            GattClientCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    queue_message(&tx, Message::GattClient(cb));
+                }),
+            },
+            GattServerCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    queue_message(&tx_server, Message::GattServer(cb));
+                }),
+            },
+            GattScannerCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    queue_message(&tx_scanner, Message::GattScanner(cb));
+                }),
+            },
+            GattScannerInbandCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    queue_message(&tx_scanner_inband, Message::GattScannerInband(cb));
+                }),
+            },
+            GattAdvCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    queue_message(&tx_adv, Message::GattAdvertiser(cb));
+                }),
+            },
+            GattAdvInbandCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    queue_message(&tx_adv_inband, Message::GattAdvertiserInband(cb));
+                }),
             },
         );
     }
@@ -526,7 +2265,7 @@ impl BluetoothGatt {
 
 // Temporary util that covers only basic string conversion.
 // TODO(b/193685325): Implement more UUID utils by using Uuid from gd/hci/uuid.h with cxx.
-fn parse_uuid_string<T: Into<String>>(uuid: T) -> Option<Uuid> {
+pub(crate) fn parse_uuid_string<T: Into<String>>(uuid: T) -> Option<Uuid> {
     let uuid = uuid.into();
 
     if uuid.len() != 32 {
@@ -574,238 +2313,324 @@ impl IBluetoothGatt for BluetoothGatt {
 
     fn register_client(
         &mut self,
-        app_uuid: String,
+        app_uuid: Uuid128Bit,
         callback: Box<dyn IBluetoothGattCallback + Send>,
         eatt_support: bool,
-    ) {
-        let uuid = parse_uuid_string(app_uuid).unwrap();
-        self.context_map.add(&uuid.uu, callback);
-        self.gatt.as_ref().unwrap().client.register_client(&uuid, eatt_support);
+    ) -> BtStatus {
+        // Clients of a process are told apart by their app UUID when their registration completes,
+        // so each needs its own.
+        if self.context_map.get_by_uuid(&app_uuid).is_some() {
+            warn!("GATT client {:?} is already registered", app_uuid);
+            return BtStatus::Busy;
+        }
+
+        let mut callback = callback;
+        let tx = self.tx.clone();
+        let disconnect_watch = callback.register_disconnect(Box::new(move |_| {
+            let message = Message::Gatt(GattActions::ClientCallbackDisconnected(app_uuid));
+            queue_message(&tx, message);
+        }));
+
+        self.context_map.add(&app_uuid, callback, disconnect_watch);
+        let status = self
+            .gatt
+            .as_ref()
+            .unwrap()
+            .client
+            .register_client(&Uuid { uu: app_uuid }, eatt_support);
+
+        // No registration callback comes to drop the client then, and its UUID would stay taken.
+        if status != BtStatus::Success {
+            warn!("Failed to register GATT client {:?}: {:?}", app_uuid, status);
+            self.context_map.remove_by_uuid(&app_uuid);
+        }
+        status
     }
 
-    fn unregister_client(&mut self, client_id: i32) {
+    fn unregister_client(&mut self, client_id: i32) -> BtStatus {
         self.context_map.remove(client_id);
-        self.gatt.as_ref().unwrap().client.unregister_client(client_id);
+        self.release_connection_priorities(client_id, None);
+        self.link_users.lock().unwrap().remove_client(client_id);
+        self.gatt.as_ref().unwrap().client.unregister_client(client_id)
     }
 
     fn client_connect(
         &self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         is_direct: bool,
-        transport: i32,
+        transport: BtTransport,
         opportunistic: bool,
         phy: i32,
-    ) {
-        let address = match RawAddress::from_string(addr.clone()) {
-            None => return,
-            Some(addr) => addr,
+    ) -> BtStatus {
+        let phy = if self.avoid_2m_phy(&addr) {
+            match phy & !LE_PHY_2M_MASK {
+                0 => LE_PHY_1M_MASK,
+                mask => mask,
+            }
+        } else {
+            phy
         };
 
+        if transport != BtTransport::Bredr {
+            self.link_users.lock().unwrap().connecting(addr, client_id, opportunistic);
+            // Background connections only use the radio while scans leave it free anyway.
+            if is_direct {
+                self.radio.lock().unwrap().request_connection(RadioUser::GattConnection(addr));
+            }
+        }
+
         self.gatt.as_ref().unwrap().client.connect(
             client_id,
-            &address,
+            &addr,
             is_direct,
-            transport,
+            transport.to_i32().unwrap(),
             opportunistic,
             phy,
-        );
+        )
     }
 
-    fn client_disconnect(&self, client_id: i32, address: String) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &address);
+    fn client_disconnect(&self, client_id: i32, addr: RawAddress) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
-        self.gatt.as_ref().unwrap().client.disconnect(
-            client_id,
-            &RawAddress::from_string(address).unwrap(),
-            conn_id.unwrap(),
-        );
+        self.gatt.as_ref().unwrap().client.disconnect(client_id, &addr, conn_id.unwrap())
     }
 
     fn client_set_preferred_phy(
         &self,
         client_id: i32,
-        address: String,
+        addr: RawAddress,
         tx_phy: LePhy,
         rx_phy: LePhy,
         phy_options: i32,
-    ) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &address);
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
+        let (tx_phy, rx_phy) = if self.avoid_2m_phy(&addr) {
+            let no_2m = |phy| match phy {
+                LePhy::Phy2m => LePhy::Phy1m,
+                phy => phy,
+            };
+            (no_2m(tx_phy), no_2m(rx_phy))
+        } else {
+            (tx_phy, rx_phy)
+        };
+
         self.gatt.as_ref().unwrap().client.set_preferred_phy(
-            &RawAddress::from_string(address).unwrap(),
+            &addr,
             tx_phy.to_u8().unwrap(),
             rx_phy.to_u8().unwrap(),
             phy_options as u16,
-        );
+        )
     }
 
-    fn client_read_phy(&mut self, client_id: i32, addr: String) {
-        let address = match RawAddress::from_string(addr.clone()) {
-            None => return,
-            Some(addr) => addr,
-        };
-
-        self.gatt.as_mut().unwrap().client.read_phy(client_id, &address);
+    fn client_read_phy(&mut self, client_id: i32, addr: RawAddress) -> BtStatus {
+        self.gatt.as_mut().unwrap().client.read_phy(client_id, &addr)
     }
 
-    fn refresh_device(&self, client_id: i32, addr: String) {
-        self.gatt
-            .as_ref()
-            .unwrap()
-            .client
-            .refresh(client_id, &RawAddress::from_string(addr).unwrap());
+    fn refresh_device(&self, client_id: i32, addr: RawAddress) -> BtStatus {
+        self.gatt.as_ref().unwrap().client.refresh(client_id, &addr)
     }
 
-    fn discover_services(&self, client_id: i32, addr: String) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    fn discover_services(&mut self, client_id: i32, addr: RawAddress) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
-        self.gatt.as_ref().unwrap().client.search_service(conn_id.unwrap(), None);
+        self.discover_all_services(conn_id.unwrap())
     }
 
-    fn discover_service_by_uuid(&self, client_id: i32, addr: String, uuid: String) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    fn discover_service_by_uuid(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
-        let uuid = parse_uuid_string(uuid);
-        if uuid.is_none() {
-            return;
-        }
-
-        self.gatt.as_ref().unwrap().client.search_service(conn_id.unwrap(), uuid);
+        self.gatt.as_ref().unwrap().client.search_service(conn_id.unwrap(), Some(Uuid { uu: uuid }))
     }
 
-    fn read_characteristic(&self, client_id: i32, addr: String, handle: i32, auth_req: i32) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    fn read_characteristic(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        auth_req: i32,
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
         // TODO(b/200065274): Perform check on restricted handles.
 
-        self.gatt.as_ref().unwrap().client.read_characteristic(
+        self.queue_operation(
             conn_id.unwrap(),
-            handle as u16,
-            auth_req,
+            GattOperation::ReadCharacteristic { handle, auth_req },
         );
+
+        BtStatus::Success
     }
 
     fn read_using_characteristic_uuid(
-        &self,
+        &mut self,
         client_id: i32,
-        addr: String,
-        uuid: String,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
         start_handle: i32,
         end_handle: i32,
         auth_req: i32,
-    ) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
-        }
-
-        let uuid = parse_uuid_string(uuid);
-        if uuid.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
         // TODO(b/200065274): Perform check on restricted handles.
 
-        self.gatt.as_ref().unwrap().client.read_using_characteristic_uuid(
+        self.queue_operation(
             conn_id.unwrap(),
-            &uuid.unwrap(),
-            start_handle as u16,
-            end_handle as u16,
-            auth_req,
+            GattOperation::ReadUsingCharacteristicUuid {
+                uuid: Uuid { uu: uuid },
+                start_handle,
+                end_handle,
+                auth_req,
+            },
         );
+
+        BtStatus::Success
     }
 
     fn write_characteristic(
-        &self,
+        &mut self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         handle: i32,
-        mut write_type: GattWriteType,
+        write_type: GattWriteType,
         auth_req: i32,
         value: Vec<u8>,
     ) -> GattWriteRequestStatus {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
             return GattWriteRequestStatus::Fail;
         }
 
-        if self.reliable_queue.contains(&addr) {
-            write_type = GattWriteType::WritePrepare;
-        }
+        let conn_id = conn_id.unwrap();
+        let write_type = match self.resolve_write_type(conn_id, &addr, write_type, value.len()) {
+            Some(write_type) => write_type,
+            None => return GattWriteRequestStatus::Fail,
+        };
 
         // TODO(b/200065274): Perform check on restricted handles.
 
-        // TODO(b/200070162): Handle concurrent write characteristic.
-
-        self.gatt.as_ref().unwrap().client.write_characteristic(
-            conn_id.unwrap(),
-            handle as u16,
-            write_type.to_i32().unwrap(),
-            auth_req,
-            &value,
+        self.queue_operation(
+            conn_id,
+            GattOperation::WriteCharacteristic { handle, write_type, auth_req, value },
         );
 
         return GattWriteRequestStatus::Success;
     }
 
-    fn read_descriptor(&self, client_id: i32, addr: String, handle: i32, auth_req: i32) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    fn write_using_characteristic_uuid(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+        start_handle: i32,
+        end_handle: i32,
+        write_type: GattWriteType,
+        auth_req: i32,
+        value: Vec<u8>,
+    ) -> GattWriteRequestStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return GattWriteRequestStatus::Fail;
         }
 
-        // TODO(b/200065274): Perform check on restricted handles.
+        let conn_id = conn_id.unwrap();
+        let write_type = match self.resolve_write_type(conn_id, &addr, write_type, value.len()) {
+            Some(write_type) => write_type,
+            None => return GattWriteRequestStatus::Fail,
+        };
 
-        self.gatt.as_ref().unwrap().client.read_descriptor(
-            conn_id.unwrap(),
-            handle as u16,
-            auth_req,
+        self.queue_operation(
+            conn_id,
+            GattOperation::WriteUsingCharacteristicUuid {
+                uuid: Uuid { uu: uuid },
+                start_handle,
+                end_handle,
+                write_type,
+                auth_req,
+                value,
+            },
         );
+
+        GattWriteRequestStatus::Success
+    }
+
+    fn read_descriptor(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        auth_req: i32,
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
+        if conn_id.is_none() {
+            return BtStatus::RemoteDeviceDown;
+        }
+
+        // TODO(b/200065274): Perform check on restricted handles.
+
+        self.queue_operation(conn_id.unwrap(), GattOperation::ReadDescriptor { handle, auth_req });
+
+        BtStatus::Success
     }
 
     fn write_descriptor(
-        &self,
+        &mut self,
         client_id: i32,
-        addr: String,
+        addr: RawAddress,
         handle: i32,
         auth_req: i32,
         value: Vec<u8>,
-    ) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
         // TODO(b/200065274): Perform check on restricted handles.
 
-        self.gatt.as_ref().unwrap().client.write_descriptor(
+        self.queue_operation(
             conn_id.unwrap(),
-            handle as u16,
-            auth_req,
-            &value,
+            GattOperation::WriteDescriptor { handle, auth_req, value },
         );
+
+        BtStatus::Success
     }
 
-    fn register_for_notification(&self, client_id: i32, addr: String, handle: i32, enable: bool) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    fn register_for_notification(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        enable: bool,
+    ) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
         // TODO(b/200065274): Perform check on restricted handles.
@@ -813,74 +2638,209 @@ impl IBluetoothGatt for BluetoothGatt {
         if enable {
             self.gatt.as_ref().unwrap().client.register_for_notification(
                 client_id,
-                &RawAddress::from_string(addr).unwrap(),
+                &addr,
                 handle as u16,
-            );
+            )
         } else {
             self.gatt.as_ref().unwrap().client.deregister_for_notification(
                 client_id,
-                &RawAddress::from_string(addr).unwrap(),
+                &addr,
                 handle as u16,
-            );
+            )
         }
     }
 
-    fn begin_reliable_write(&mut self, _client_id: i32, addr: String) {
+    fn begin_reliable_write(&mut self, _client_id: i32, addr: RawAddress) -> BtStatus {
         self.reliable_queue.insert(addr);
+
+        BtStatus::Success
     }
 
-    fn end_reliable_write(&mut self, client_id: i32, addr: String, execute: bool) {
+    fn end_reliable_write(&mut self, client_id: i32, addr: RawAddress, execute: bool) -> BtStatus {
         self.reliable_queue.remove(&addr);
 
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
         }
 
-        self.gatt
-            .as_ref()
-            .unwrap()
-            .client
-            .execute_write(conn_id.unwrap(), if execute { 1 } else { 0 });
+        self.queue_operation(conn_id.unwrap(), GattOperation::ExecuteWrite { execute });
+
+        BtStatus::Success
     }
 
-    fn read_remote_rssi(&self, client_id: i32, addr: String) {
-        self.gatt
-            .as_ref()
-            .unwrap()
-            .client
-            .read_remote_rssi(client_id, &RawAddress::from_string(addr).unwrap());
+    fn read_remote_rssi(&self, client_id: i32, addr: RawAddress) -> BtStatus {
+        self.gatt.as_ref().unwrap().client.read_remote_rssi(client_id, &addr)
     }
 
-    fn configure_mtu(&self, client_id: i32, addr: String, mtu: i32) {
-        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr);
+    fn configure_mtu(&self, client_id: i32, addr: RawAddress, mtu: i32) -> BtStatus {
+        let conn_id = self.context_map.get_conn_id_from_address(client_id, &addr.to_string());
         if conn_id.is_none() {
-            return;
+            return BtStatus::RemoteDeviceDown;
+        }
+
+        self.gatt.as_ref().unwrap().client.configure_mtu(conn_id.unwrap(), mtu)
+    }
+
+    fn get_mtu(&self, client_id: i32, addr: RawAddress) -> i32 {
+        self.context_map.get_mtu(client_id, &addr.to_string()).unwrap_or(0)
+    }
+
+    fn set_notification_batching(&mut self, client_id: i32, interval_ms: u32) -> BtStatus {
+        match self.context_map.get_by_client_id_mut(client_id) {
+            Some(client) => {
+                client.notification_batch_interval = match interval_ms {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms.into())),
+                };
+            }
+            None => return BtStatus::InvalidParam,
+        }
+
+        // Don't hold on to what was batched under the previous setting.
+        let conn_ids: Vec<i32> = self
+            .context_map
+            .connections
+            .iter()
+            .filter(|conn| conn.client_id == client_id)
+            .map(|conn| conn.conn_id)
+            .collect();
+        for conn_id in conn_ids {
+            self.flush_notifications(conn_id);
+        }
+
+        BtStatus::Success
+    }
+
+    fn set_auto_elevate_security(&mut self, client_id: i32, enabled: bool) -> BtStatus {
+        match self.context_map.get_by_client_id_mut(client_id) {
+            Some(client) => {
+                client.auto_elevate_security = enabled;
+                BtStatus::Success
+            }
+            None => BtStatus::InvalidParam,
+        }
+    }
+
+    fn set_request_timeout(
+        &mut self,
+        client_id: i32,
+        timeout_ms: u32,
+        disconnect: bool,
+    ) -> BtStatus {
+        match self.context_map.get_by_client_id_mut(client_id) {
+            Some(client) => {
+                client.request_timeout = match timeout_ms {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms.into())),
+                };
+                client.disconnect_on_timeout = disconnect;
+                BtStatus::Success
+            }
+            None => BtStatus::InvalidParam,
         }
-
-        self.gatt.as_ref().unwrap().client.configure_mtu(conn_id.unwrap(), mtu);
     }
 
     fn connection_parameter_update(
         &self,
         _client_id: i32,
-        addr: String,
+        addr: RawAddress,
         min_interval: i32,
         max_interval: i32,
         latency: i32,
         timeout: i32,
         min_ce_len: u16,
         max_ce_len: u16,
-    ) {
+    ) -> BtStatus {
         self.gatt.as_ref().unwrap().client.conn_parameter_update(
-            &RawAddress::from_string(addr).unwrap(),
+            &addr,
             min_interval,
             max_interval,
             latency,
             timeout,
             min_ce_len,
             max_ce_len,
-        );
+        )
+    }
+
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        priority: ConnectionPriority,
+    ) -> BtStatus {
+        if self.context_map.get_conn_id_from_address(client_id, &addr.to_string()).is_none() {
+            return BtStatus::RemoteDeviceDown;
+        }
+
+        let requests = self.connection_priorities.entry(addr).or_insert_with(HashMap::new);
+        match priority {
+            ConnectionPriority::Balanced => {
+                requests.remove(&client_id);
+            }
+            _ => {
+                requests.insert(client_id, priority);
+            }
+        }
+        self.apply_connection_priority(&addr)
+    }
+
+    fn set_device_information(&mut self, manufacturer: String, model: String) {
+        // Values are read on every request, so no need to touch the service itself.
+        self.local_services.manufacturer = manufacturer;
+        self.local_services.model = model;
+    }
+
+    fn set_server_authorization(&mut self, client_id: i32, enabled: bool) -> BtStatus {
+        match self.context_map.get_by_client_id_mut(client_id) {
+            Some(client) => {
+                client.authorizes_server_requests = enabled;
+                BtStatus::Success
+            }
+            None => BtStatus::InvalidParam,
+        }
+    }
+
+    fn send_authorization_response(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        allowed: bool,
+    ) -> BtStatus {
+        match self.context_map.get_by_client_id(client_id) {
+            Some(client) if client.authorizes_server_requests => (),
+            _ => return BtStatus::InvalidParam,
+        }
+
+        let conn_ids = self.server_authorization.answer(addr, handle, allowed);
+        if conn_ids.is_empty() {
+            return BtStatus::Fail;
+        }
+
+        let (reads, others): (Vec<PendingRead>, Vec<PendingRead>) =
+            std::mem::take(&mut self.local_services.pending_reads)
+                .into_iter()
+                .partition(|read| read.handle == handle && conn_ids.contains(&read.conn_id));
+        self.local_services.pending_reads = others;
+        for read in reads {
+            self.respond_local_read(read, allowed);
+        }
+
+        // The Fast Pair provider answers the requests made to its own server.
+        for conn_id in conn_ids {
+            let action = FastPairActions::Authorized(conn_id, handle, allowed);
+            queue_message(&self.tx, Message::FastPair(action));
+        }
+
+        BtStatus::Success
+    }
+
+    fn set_appearance(&mut self, appearance: u16) {
+        self.local_services.appearance = appearance;
+        if self.local_services.server_if.is_some() {
+            self.intf.lock().unwrap().set_le_appearance(appearance);
+        }
     }
 }
 
@@ -965,7 +2925,7 @@ pub(crate) trait BtifGattClientCallbacks {
     );
 
     #[btif_callback(ServiceChanged)]
-    fn service_changed_cb(&self, conn_id: i32);
+    fn service_changed_cb(&mut self, conn_id: i32);
 
     #[btif_callback(ReadPhy)]
     fn read_phy_cb(&mut self, client_id: i32, addr: RawAddress, tx_phy: u8, rx_phy: u8, status: u8);
@@ -973,21 +2933,44 @@ pub(crate) trait BtifGattClientCallbacks {
 
 impl BtifGattClientCallbacks for BluetoothGatt {
     fn register_client_cb(&mut self, status: i32, client_id: i32, app_uuid: Uuid) {
-        self.context_map.set_client_id(&app_uuid.uu, client_id);
+        let success = status == GattStatus::Success as i32;
+        if success {
+            self.context_map.set_client_id(&app_uuid.uu, client_id);
+        }
 
         let client = self.context_map.get_by_uuid(&app_uuid.uu);
         if client.is_none() {
             warn!("Warning: Client not registered for UUID {:?}", app_uuid.uu);
+            // Its callback went away while it was being registered.
+            if success {
+                self.gatt.as_ref().unwrap().client.unregister_client(client_id);
+            }
             return;
         }
 
         let callback = &client.unwrap().callback;
         callback.on_client_registered(status, client_id);
+
+        // Leave the app UUID free for trying again.
+        if !success {
+            self.context_map.remove_by_uuid(&app_uuid.uu);
+        }
     }
 
     fn connect_cb(&mut self, conn_id: i32, status: i32, client_id: i32, addr: RawAddress) {
+        self.link_users.lock().unwrap().connected(addr, client_id, status == 0);
+        self.radio.lock().unwrap().release(RadioUser::GattConnection(addr), RadioActivity::Connect);
         if status == 0 {
             self.context_map.add_connection(client_id, conn_id, &addr.to_string());
+
+            // The services of bonded devices are cached, the Database Hash tells whether they
+            // can be used instead of discovering them again.
+            if self.bonded_devices.contains(&addr) {
+                if let Some(conn) = self.context_map.get_connection_mut(conn_id) {
+                    conn.hash_pending = true;
+                }
+                self.queue_operation(conn_id, GattOperation::ReadDatabaseHash { handle: None });
+            }
         }
 
         let client = self.context_map.get_by_client_id(client_id);
@@ -1002,12 +2985,16 @@ impl BtifGattClientCallbacks for BluetoothGatt {
                 None => false,
                 Some(gatt_status) => gatt_status == GattStatus::Success,
             },
-            addr.to_string(),
+            addr,
         );
     }
 
     fn disconnect_cb(&mut self, conn_id: i32, status: i32, client_id: i32, addr: RawAddress) {
+        // Notifications received before the disconnection are still delivered.
+        self.flush_notifications(conn_id);
         self.context_map.remove_connection(client_id, conn_id);
+        self.release_connection_priorities(client_id, Some(addr));
+        self.link_users.lock().unwrap().disconnected(addr, client_id);
         let client = self.context_map.get_by_client_id(client_id);
         if client.is_none() {
             return;
@@ -1020,7 +3007,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
                 None => false,
                 Some(gatt_status) => gatt_status == GattStatus::Success,
             },
-            addr.to_string(),
+            addr,
         );
     }
 
@@ -1031,25 +3018,59 @@ impl BtifGattClientCallbacks for BluetoothGatt {
 
     fn register_for_notification_cb(
         &mut self,
-        _conn_id: i32,
-        _registered: i32,
-        _status: i32,
-        _handle: u16,
+        conn_id: i32,
+        registered: i32,
+        status: i32,
+        handle: u16,
     ) {
-        // No-op.
+        if status == GattStatus::Success.to_i32().unwrap() {
+            self.context_map.set_subscribed(conn_id, handle, registered != 0);
+        }
     }
 
     fn notify_cb(&mut self, conn_id: i32, data: BtGattNotifyParams) {
+        self.count_traffic(conn_id, 0, data.len as usize);
+
         let client = self.context_map.get_client_by_conn_id(conn_id);
         if client.is_none() {
             return;
         }
 
-        client.unwrap().callback.on_notify(
-            RawAddress { val: data.bda.address }.to_string(),
-            data.handle as i32,
-            data.value[0..data.len as usize].to_vec(),
-        );
+        let client = client.unwrap();
+        let interval = match client.notification_batch_interval {
+            Some(interval) => interval,
+            None => {
+                client.callback.on_notify(
+                    RawAddress { val: data.bda.address },
+                    data.handle as i32,
+                    Arc::from(&data.value[0..data.len as usize]),
+                );
+                return;
+            }
+        };
+
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_micros() as u64)
+            .unwrap_or(0);
+        let notification = GattNotification {
+            handle: data.handle as i32,
+            value: data.value[0..data.len as usize].to_vec(),
+            timestamp_us,
+        };
+
+        match self.context_map.queue_notification(conn_id, notification) {
+            // The first notification of a batch starts its interval.
+            1 => {
+                let txl = self.tx.clone();
+                tokio::spawn(async move {
+                    time::sleep(interval).await;
+                    let _ = txl.send(Message::Gatt(GattActions::FlushNotifications(conn_id))).await;
+                });
+            }
+            len if len >= MAX_NOTIFICATION_BATCH_LEN => self.flush_notifications(conn_id),
+            _ => {}
+        }
     }
 
     fn read_characteristic_cb(&mut self, conn_id: i32, status: i32, data: BtGattReadParams) {
@@ -1058,17 +3079,82 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
-        let client = self.context_map.get_client_by_conn_id(conn_id);
-        if client.is_none() {
+        let address = address.unwrap();
+        self.count_traffic(conn_id, 0, data.value.len as usize);
+        if self.context_map.take_late_response(conn_id) {
+            self.send_next_operation(conn_id);
             return;
         }
+        let op = match self.take_completed_operation(conn_id, OperationCallback::ReadCharacteristic)
+        {
+            Some(op) => op,
+            None => return,
+        };
+        self.record_operation_status(conn_id, status, &op);
+        let op = match self.retry_with_security(conn_id, status, op) {
+            Ok(()) => return,
+            Err(op) => op,
+        };
+        let success = status == GattStatus::Success.to_i32().unwrap();
+        match op {
+            Some(GattOperation::ReadDatabaseHash { handle }) => {
+                let hash = if success {
+                    data.value.value[0..data.value.len as usize].to_vec()
+                } else {
+                    vec![]
+                };
+                self.database_hash_read(conn_id, handle.is_some(), hash);
+                self.send_next_operation(conn_id);
+                return;
+            }
+            Some(GattOperation::ReadUsingCharacteristicUuid { auth_req, .. }) if success => {
+                // A value that fills the whole response may have been cut short, read it again
+                // by handle to get all of it. The partial value is reported if that can't start.
+                let mtu = self.context_map.get_mtu_by_conn_id(conn_id).unwrap();
+                if data.value.len as i32 >= mtu - ATT_READ_BY_TYPE_HEADER_LEN {
+                    let read =
+                        GattOperation::ReadCharacteristic { handle: data.handle as i32, auth_req };
+                    if self.send_operation(conn_id, read).is_ok() {
+                        return;
+                    }
+                }
+            }
+            Some(GattOperation::WriteUsingCharacteristicUuid {
+                write_type,
+                auth_req,
+                value,
+                ..
+            }) => {
+                if success {
+                    let write = GattOperation::WriteCharacteristic {
+                        handle: data.handle as i32,
+                        write_type,
+                        auth_req,
+                        value,
+                    };
+                    if let Err(write) = self.send_operation(conn_id, write) {
+                        self.report_operation_failure(conn_id, write);
+                    }
+                } else if let Some(client) = self.context_map.get_client_by_conn_id(conn_id) {
+                    client.callback.on_characteristic_write(address, status, 0);
+                }
 
-        client.unwrap().callback.on_characteristic_read(
-            address.unwrap().to_string(),
-            status,
-            data.handle as i32,
-            data.value.value[0..data.value.len as usize].to_vec(),
-        );
+                self.send_next_operation(conn_id);
+                return;
+            }
+            _ => {}
+        }
+
+        if let Some(client) = self.context_map.get_client_by_conn_id(conn_id) {
+            client.callback.on_characteristic_read(
+                address,
+                status,
+                data.handle as i32,
+                Arc::from(&data.value.value[0..data.value.len as usize]),
+            );
+        }
+
+        self.send_next_operation(conn_id);
     }
 
     fn write_characteristic_cb(
@@ -1084,8 +3170,19 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
-        // TODO(b/200070162): Design how to handle concurrent write characteristic to the same
-        // peer.
+        if self.context_map.take_late_response(conn_id) {
+            self.send_next_operation(conn_id);
+            return;
+        }
+        let op =
+            match self.take_completed_operation(conn_id, OperationCallback::WriteCharacteristic) {
+                Some(op) => op,
+                None => return,
+            };
+        self.record_operation_status(conn_id, status, &op);
+        if self.retry_with_security(conn_id, status, op).is_ok() {
+            return;
+        }
 
         let client = self.context_map.get_client_by_conn_id_mut(conn_id);
         if client.is_none() {
@@ -1099,15 +3196,12 @@ impl BtifGattClientCallbacks for BluetoothGatt {
                 status = GattStatus::Success.to_i32().unwrap();
             }
 
-            client.congestion_queue.push((address.unwrap().to_string(), status, handle as i32));
-            return;
+            client.congestion_queue.push((address.unwrap(), status, handle as i32));
+        } else {
+            client.callback.on_characteristic_write(address.unwrap(), status, handle as i32);
         }
 
-        client.callback.on_characteristic_write(
-            address.unwrap().to_string(),
-            status,
-            handle as i32,
-        );
+        self.send_next_operation(conn_id);
     }
 
     fn read_descriptor_cb(&mut self, conn_id: i32, status: i32, data: BtGattReadParams) {
@@ -1116,17 +3210,33 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
+        self.count_traffic(conn_id, 0, data.value.len as usize);
+        if self.context_map.take_late_response(conn_id) {
+            self.send_next_operation(conn_id);
+            return;
+        }
+        let op = match self.take_completed_operation(conn_id, OperationCallback::ReadDescriptor) {
+            Some(op) => op,
+            None => return,
+        };
+        self.record_operation_status(conn_id, status, &op);
+        if self.retry_with_security(conn_id, status, op).is_ok() {
+            return;
+        }
+
         let client = self.context_map.get_client_by_conn_id(conn_id);
         if client.is_none() {
             return;
         }
 
         client.unwrap().callback.on_descriptor_read(
-            address.unwrap().to_string(),
+            address.unwrap(),
             status,
             data.handle as i32,
-            data.value.value[0..data.value.len as usize].to_vec(),
+            Arc::from(&data.value.value[0..data.value.len as usize]),
         );
+
+        self.send_next_operation(conn_id);
     }
 
     fn write_descriptor_cb(
@@ -1142,16 +3252,27 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
+        if self.context_map.take_late_response(conn_id) {
+            self.send_next_operation(conn_id);
+            return;
+        }
+        let op = match self.take_completed_operation(conn_id, OperationCallback::WriteDescriptor) {
+            Some(op) => op,
+            None => return,
+        };
+        self.record_operation_status(conn_id, status, &op);
+        if self.retry_with_security(conn_id, status, op).is_ok() {
+            return;
+        }
+
         let client = self.context_map.get_client_by_conn_id(conn_id);
         if client.is_none() {
             return;
         }
 
-        client.unwrap().callback.on_descriptor_write(
-            address.unwrap().to_string(),
-            status,
-            handle as i32,
-        );
+        client.unwrap().callback.on_descriptor_write(address.unwrap(), status, handle as i32);
+
+        self.send_next_operation(conn_id);
     }
 
     fn execute_write_cb(&mut self, conn_id: i32, status: i32) {
@@ -1160,12 +3281,22 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
+        if self.context_map.take_late_response(conn_id) {
+            self.send_next_operation(conn_id);
+            return;
+        }
+        if self.take_completed_operation(conn_id, OperationCallback::ExecuteWrite).is_none() {
+            return;
+        }
+
         let client = self.context_map.get_client_by_conn_id(conn_id);
         if client.is_none() {
             return;
         }
 
-        client.unwrap().callback.on_execute_write(address.unwrap().to_string(), status);
+        client.unwrap().callback.on_execute_write(address.unwrap(), status);
+
+        self.send_next_operation(conn_id);
     }
 
     fn read_remote_rssi_cb(&mut self, client_id: i32, addr: RawAddress, rssi: i32, status: i32) {
@@ -1174,10 +3305,14 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
-        client.unwrap().callback.on_read_remote_rssi(addr.to_string(), rssi, status);
+        client.unwrap().callback.on_read_remote_rssi(addr, rssi, status);
     }
 
     fn configure_mtu_cb(&mut self, conn_id: i32, status: i32, mtu: i32) {
+        if GattStatus::from_i32(status) == Some(GattStatus::Success) {
+            self.context_map.set_mtu(conn_id, mtu);
+        }
+
         let client = self.context_map.get_client_by_conn_id(conn_id);
         if client.is_none() {
             return;
@@ -1202,7 +3337,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
         client.is_congested = congested;
         if !client.is_congested {
             for callback in client.congestion_queue.iter() {
-                client.callback.on_characteristic_write(callback.0.clone(), callback.1, callback.2);
+                client.callback.on_characteristic_write(callback.0, callback.1, callback.2);
             }
             client.congestion_queue.clear();
         }
@@ -1214,8 +3349,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
-        let client = self.context_map.get_client_by_conn_id(conn_id);
-        if client.is_none() {
+        if self.context_map.get_client_by_conn_id(conn_id).is_none() {
             return;
         }
 
@@ -1238,7 +3372,9 @@ impl BtifGattClientCallbacks for BluetoothGatt {
                             elem.uuid.uu,
                             elem.id as i32,
                             elem.properties as i32,
-                            0,
+                            BluetoothGattCharacteristic::permissions_from_properties(
+                                elem.properties as i32,
+                            ),
                         )),
                         None => {
                             // TODO(b/193685325): Log error.
@@ -1283,7 +3419,34 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             }
         }
 
-        client.unwrap().callback.on_search_complete(address.unwrap().to_string(), db_out, 0);
+        let rediscovering = match self.context_map.get_connection_mut(conn_id) {
+            Some(conn) => {
+                if let Some(old) = &conn.services {
+                    keep_permissions(old, &mut db_out);
+                }
+                conn.services = Some(db_out.clone());
+                std::mem::take(&mut conn.rediscovering)
+            }
+            None => false,
+        };
+
+        if !rediscovering {
+            self.cache_services(conn_id);
+            let client = self.context_map.get_client_by_conn_id(conn_id).unwrap();
+            client.callback.on_search_complete(address.unwrap(), db_out, 0);
+            return;
+        }
+
+        let hash_handle = db_out
+            .iter()
+            .flat_map(|service| service.characteristics.iter())
+            .find(|characteristic| characteristic.uuid == DATABASE_HASH_UUID)
+            .map(|characteristic| characteristic.instance_id);
+        match hash_handle {
+            Some(handle) => self
+                .queue_operation(conn_id, GattOperation::ReadDatabaseHash { handle: Some(handle) }),
+            None => self.database_hash_read(conn_id, true, vec![]),
+        }
     }
 
     fn phy_updated_cb(&mut self, conn_id: i32, tx_phy: u8, rx_phy: u8, status: u8) {
@@ -1319,7 +3482,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
         }
 
         client.unwrap().callback.on_phy_read(
-            addr.to_string(),
+            addr,
             LePhy::from_u8(tx_phy).unwrap(),
             LePhy::from_u8(rx_phy).unwrap(),
             GattStatus::from_u8(status).unwrap(),
@@ -1353,7 +3516,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
         );
     }
 
-    fn service_changed_cb(&self, conn_id: i32) {
+    fn service_changed_cb(&mut self, conn_id: i32) {
         let address = self.context_map.get_address_by_conn_id(conn_id);
         if address.is_none() {
             return;
@@ -1365,6 +3528,128 @@ impl BtifGattClientCallbacks for BluetoothGatt {
         }
 
         client.unwrap().callback.on_service_changed(address.unwrap());
+
+        // The handles the client subscribed to may have moved, so it gets the new database
+        // without having to ask for it.
+        if self.context_map.invalidate_services(conn_id) {
+            let status = self.gatt.as_ref().unwrap().client.search_service(conn_id, None);
+            if status != BtStatus::Success {
+                warn!("Failed to discover services again on connection {}: {:?}", conn_id, status);
+                if let Some(conn) = self.context_map.get_connection_mut(conn_id) {
+                    conn.rediscovering = false;
+                }
+            }
+        }
+    }
+}
+
+#[btif_callbacks_dispatcher(BluetoothGatt, dispatch_gatt_server_callbacks, GattServerCallbacks)]
+pub(crate) trait BtifGattServerCallbacks {
+    #[btif_callback(RegisterServer)]
+    fn register_server_cb(&mut self, status: i32, server_if: i32, app_uuid: Uuid);
+
+    #[btif_callback(ServiceAdded)]
+    fn service_added_cb(
+        &mut self,
+        status: i32,
+        server_if: i32,
+        elements: Vec<BtGattDbElement>,
+        count: usize,
+    );
+
+    #[btif_callback(Connection)]
+    fn connection_cb(&mut self, conn_id: i32, server_if: i32, connected: i32, addr: RawAddress);
+
+    #[btif_callback(RequestReadCharacteristic)]
+    fn request_read_characteristic_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        is_long: bool,
+    );
+}
+
+impl BtifGattServerCallbacks for BluetoothGatt {
+    fn register_server_cb(&mut self, status: i32, server_if: i32, app_uuid: Uuid) {
+        if app_uuid.uu != parse_uuid_string(LOCAL_SERVER_UUID).unwrap().uu {
+            return;
+        }
+
+        if status != GattStatus::Success as i32 {
+            warn!("Local GATT server was not registered, status {}", status);
+            return;
+        }
+
+        self.local_services.server_if = Some(server_if);
+        let status = self
+            .gatt
+            .as_ref()
+            .unwrap()
+            .server
+            .add_service(server_if, &device_information_service());
+        if status != BtStatus::Success {
+            warn!("Failed to add the Device Information Service: {:?}", status);
+        }
+    }
+
+    fn service_added_cb(
+        &mut self,
+        status: i32,
+        server_if: i32,
+        elements: Vec<BtGattDbElement>,
+        _count: usize,
+    ) {
+        if self.local_services.server_if != Some(server_if) {
+            return;
+        }
+
+        if status != GattStatus::Success as i32 {
+            warn!("Device Information Service was not added, status {}", status);
+            return;
+        }
+
+        let manufacturer = parse_uuid_string(MANUFACTURER_NAME_STRING).unwrap();
+        let model = parse_uuid_string(MODEL_NUMBER_STRING).unwrap();
+        for elem in elements.iter() {
+            if elem.uuid.uu == manufacturer.uu {
+                self.local_services.manufacturer_handle = Some(elem.attribute_handle as i32);
+            } else if elem.uuid.uu == model.uu {
+                self.local_services.model_handle = Some(elem.attribute_handle as i32);
+            }
+        }
+    }
+
+    fn connection_cb(&mut self, conn_id: i32, server_if: i32, connected: i32, _addr: RawAddress) {
+        if self.local_services.server_if == Some(server_if) && connected == 0 {
+            self.server_disconnected(conn_id);
+        }
+    }
+
+    fn request_read_characteristic_cb(
+        &mut self,
+        conn_id: i32,
+        trans_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        offset: i32,
+        _is_long: bool,
+    ) {
+        let read = PendingRead { conn_id, trans_id, address: addr, handle, offset };
+        // Every characteristic of the Device Information Service is sensitive, since together
+        // they tell what the host is.
+        if self.local_services.get_value(handle).is_none() {
+            self.respond_local_read(read, true);
+            return;
+        }
+
+        match self.authorize_server_request(conn_id, addr, handle) {
+            Authorization::Allowed => self.respond_local_read(read, true),
+            Authorization::Denied => self.respond_local_read(read, false),
+            Authorization::Pending => self.local_services.pending_reads.push(read),
+        }
     }
 }
 
@@ -1387,24 +3672,31 @@ mod tests {
             _status: i32,
             _client_id: i32,
             _connected: bool,
-            _addr: String,
+            _addr: RawAddress,
         ) {
         }
 
         fn on_phy_update(
             &self,
-            _addr: String,
+            _addr: RawAddress,
             _tx_phy: LePhy,
             _rx_phy: LePhy,
             _status: GattStatus,
         ) {
         }
 
-        fn on_phy_read(&self, _addr: String, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+        fn on_phy_read(
+            &self,
+            _addr: RawAddress,
+            _tx_phy: LePhy,
+            _rx_phy: LePhy,
+            _status: GattStatus,
+        ) {
+        }
 
         fn on_search_complete(
             &self,
-            _addr: String,
+            _addr: RawAddress,
             _services: Vec<BluetoothGattService>,
             _status: i32,
         ) {
@@ -1412,30 +3704,39 @@ mod tests {
 
         fn on_characteristic_read(
             &self,
-            _addr: String,
+            _addr: RawAddress,
             _status: i32,
             _handle: i32,
-            _value: Vec<u8>,
+            _value: Arc<[u8]>,
         ) {
         }
 
-        fn on_characteristic_write(&self, _addr: String, _status: i32, _handle: i32) {}
+        fn on_characteristic_write(&self, _addr: RawAddress, _status: i32, _handle: i32) {}
+
+        fn on_execute_write(&self, _addr: RawAddress, _status: i32) {}
 
-        fn on_execute_write(&self, _addr: String, _status: i32) {}
+        fn on_descriptor_read(
+            &self,
+            _addr: RawAddress,
+            _status: i32,
+            _handle: i32,
+            _value: Arc<[u8]>,
+        ) {
+        }
 
-        fn on_descriptor_read(&self, _addr: String, _status: i32, _handle: i32, _value: Vec<u8>) {}
+        fn on_descriptor_write(&self, _addr: RawAddress, _status: i32, _handle: i32) {}
 
-        fn on_descriptor_write(&self, _addr: String, _status: i32, _handle: i32) {}
+        fn on_notify(&self, _addr: RawAddress, _handle: i32, _value: Arc<[u8]>) {}
 
-        fn on_notify(&self, _addr: String, _handle: i32, _value: Vec<u8>) {}
+        fn on_notify_batch(&self, _addr: RawAddress, _notifications: Vec<GattNotification>) {}
 
-        fn on_read_remote_rssi(&self, _addr: String, _rssi: i32, _status: i32) {}
+        fn on_read_remote_rssi(&self, _addr: RawAddress, _rssi: i32, _status: i32) {}
 
-        fn on_configure_mtu(&self, _addr: String, _mtu: i32, _status: i32) {}
+        fn on_configure_mtu(&self, _addr: RawAddress, _mtu: i32, _status: i32) {}
 
         fn on_connection_updated(
             &self,
-            _addr: String,
+            _addr: RawAddress,
             _interval: i32,
             _latency: i32,
             _timeout: i32,
@@ -1443,23 +3744,23 @@ mod tests {
         ) {
         }
 
-        fn on_service_changed(&self, _addr: String) {}
-    }
+        fn on_service_changed(&self, _addr: RawAddress) {}
 
-    impl RPCProxy for TestBluetoothGattCallback {
-        fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
-            0
+        fn on_gatt_db_updated(
+            &self,
+            _addr: RawAddress,
+            _services: Vec<BluetoothGattService>,
+            _database_hash: Vec<u8>,
+        ) {
         }
 
+        fn on_authorization_request(&self, _addr: RawAddress, _handle: i32) {}
+    }
+
+    impl RPCProxy for TestBluetoothGattCallback {
         fn get_object_id(&self) -> String {
             self.id.clone()
         }
-
-        fn unregister(&mut self, _id: u32) -> bool {
-            false
-        }
-
-        fn export_for_rpc(self: Box<Self>) {}
     }
 
     use super::*;
@@ -1485,7 +3786,7 @@ mod tests {
         // Add client 1.
         let callback1 = Box::new(TestBluetoothGattCallback::new(String::from("Callback 1")));
         let uuid1 = parse_uuid_string("00000000000000000000000000000001").unwrap().uu;
-        map.add(&uuid1, callback1);
+        map.add(&uuid1, callback1, 0);
         let found = map.get_by_uuid(&uuid1);
         assert!(found.is_some());
         assert_eq!("Callback 1", found.unwrap().callback.get_object_id());
@@ -1493,7 +3794,7 @@ mod tests {
         // Add client 2.
         let callback2 = Box::new(TestBluetoothGattCallback::new(String::from("Callback 2")));
         let uuid2 = parse_uuid_string("00000000000000000000000000000002").unwrap().uu;
-        map.add(&uuid2, callback2);
+        map.add(&uuid2, callback2, 0);
         let found = map.get_by_uuid(&uuid2);
         assert!(found.is_some());
         assert_eq!("Callback 2", found.unwrap().callback.get_object_id());
@@ -1507,6 +3808,10 @@ mod tests {
         map.remove(3);
         let found = map.get_by_uuid(&uuid1);
         assert!(found.is_none());
+
+        // Client 2 never got an ID.
+        map.remove_by_uuid(&uuid2);
+        assert!(map.get_by_uuid(&uuid2).is_none());
     }
 
     #[test]
@@ -1524,5 +3829,251 @@ mod tests {
         let found = map.get_conn_id_from_address(client_id, &String::from("11:22:33:44:55:66"));
         assert!(found.is_some());
         assert_eq!(4, found.unwrap());
+
+        let address = String::from("aa:bb:cc:dd:ee:ff");
+        assert_eq!(Some(DEFAULT_ATT_MTU), map.get_mtu(client_id, &address));
+        map.set_mtu(3, 517);
+        assert_eq!(Some(517), map.get_mtu(client_id, &address));
+        assert_eq!(None, map.get_mtu(2, &address));
+
+        assert_eq!(Some(517), map.get_mtu_by_conn_id(3));
+        assert_eq!(Some(DEFAULT_ATT_MTU), map.get_mtu_by_conn_id(4));
+
+        map.queue_operation(3, GattOperation::ReadCharacteristic { handle: 1, auth_req: 0 });
+        map.queue_operation(3, GattOperation::ExecuteWrite { execute: true });
+        let op = map.next_operation(3);
+        assert!(matches!(op, Some(GattOperation::ReadCharacteristic { handle: 1, .. })));
+
+        // Nothing else is sent until the outstanding operation completes, and only the callback
+        // it expects completes it.
+        map.set_outstanding_operation(3, op.unwrap());
+        assert!(map.next_operation(3).is_none());
+        assert!(matches!(
+            map.take_outstanding_operation(3, OperationCallback::WriteDescriptor),
+            Err(OperationCallback::ReadCharacteristic)
+        ));
+        assert!(map.next_operation(3).is_none());
+        assert!(matches!(
+            map.take_outstanding_operation(3, OperationCallback::ReadCharacteristic),
+            Ok(Some(GattOperation::ReadCharacteristic { handle: 1, .. }))
+        ));
+        let op = map.next_operation(3);
+        assert!(matches!(op, Some(GattOperation::ExecuteWrite { execute: true })));
+        assert!(map.next_operation(3).is_none());
+        assert!(map.next_operation(4).is_none());
+
+        // Responses to operations that timed out are dropped before any other.
+        map.get_connection_mut(4).unwrap().late_responses = 1;
+        assert!(map.take_late_response(4));
+        assert!(!map.take_late_response(4));
+        assert!(!map.take_late_response(3));
+    }
+
+    #[test]
+    fn test_services_to_string() {
+        let uuid = |n: u8| [n; 16];
+        let permissions = BluetoothGattCharacteristic::permissions_from_properties(0x12);
+        let mut characteristic = BluetoothGattCharacteristic::new(uuid(3), 3, 0x12, permissions);
+        characteristic.descriptors.push(BluetoothGattDescriptor::new(uuid(4), 4, 0));
+        let mut service = BluetoothGattService::new(uuid(1), 1, 0);
+        service.included_services.push(BluetoothGattService::new(uuid(2), 2, 1));
+        service.characteristics.push(characteristic);
+        let services = vec![service, BluetoothGattService::new(uuid(5), 5, 0)];
+
+        let s = services_to_string(&services);
+        let parsed = services_from_string(&s).unwrap();
+        assert_eq!(format!("{:?}", services), format!("{:?}", parsed));
+        assert_eq!(s, services_to_string(&parsed));
+
+        assert!(services_from_string("").unwrap().is_empty());
+        assert!(services_from_string("c:3:0303:18").is_none());
+        assert!(services_from_string(&format!("d:4:{}:0", to_hex(&uuid(4)))).is_none());
+    }
+
+    #[test]
+    fn test_characteristic_permissions() {
+        type C = BluetoothGattCharacteristic;
+
+        assert_eq!(C::property_names(0x1a), vec!["read", "write", "notify"]);
+        assert_eq!(C::permissions_from_properties(0x1a), C::PERMISSION_READ | C::PERMISSION_WRITE);
+        assert_eq!(
+            C::permissions_from_properties(0x44),
+            C::PERMISSION_WRITE | C::PERMISSION_WRITE_SIGNED
+        );
+
+        let permissions = C::PERMISSION_READ | C::PERMISSION_WRITE;
+        let insuf_encryption = GattStatus::InsufEncryption as i32;
+        let insuf_authentication = GattStatus::InsufAuthentication as i32;
+        let encrypted = C::permissions_after_failure(permissions, false, insuf_encryption);
+        assert_eq!(encrypted, C::PERMISSION_READ_ENCRYPTED | C::PERMISSION_WRITE);
+        assert_eq!(C::permission_names(encrypted), vec!["read-encrypted", "write"]);
+
+        let authenticated = C::permissions_after_failure(encrypted, true, insuf_authentication);
+        assert_eq!(
+            authenticated,
+            C::PERMISSION_READ_ENCRYPTED | C::PERMISSION_WRITE_ENCRYPTED_MITM
+        );
+        // Encryption doesn't lower a requirement of authentication.
+        assert_eq!(
+            C::permissions_after_failure(authenticated, true, insuf_encryption),
+            authenticated
+        );
+        assert_eq!(
+            C::permissions_after_failure(authenticated, true, GattStatus::WriteNotPermit as i32),
+            C::PERMISSION_READ_ENCRYPTED
+        );
+        assert_eq!(
+            C::permissions_after_failure(permissions, false, GattStatus::Busy as i32),
+            permissions
+        );
+    }
+
+    #[test]
+    fn test_record_access_failure() {
+        let uuid = |n: u8| [n; 16];
+        let mut characteristic = BluetoothGattCharacteristic::new(
+            uuid(3),
+            3,
+            0x02,
+            BluetoothGattCharacteristic::PERMISSION_READ,
+        );
+        characteristic.descriptors.push(BluetoothGattDescriptor::new(uuid(4), 4, 0));
+        let mut service = BluetoothGattService::new(uuid(1), 1, 0);
+        service.characteristics.push(characteristic);
+        let mut services = vec![service];
+
+        record_access_failure(&mut services, 3, false, GattStatus::InsufAuthentication as i32);
+        record_access_failure(&mut services, 4, true, GattStatus::InsufEncryption as i32);
+        let characteristic = &services[0].characteristics[0];
+        assert_eq!(
+            characteristic.permissions,
+            BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED_MITM
+        );
+        assert_eq!(
+            characteristic.descriptors[0].permissions,
+            BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED
+        );
+
+        // Discovering the services again keeps what was learned.
+        let mut rediscovered = services.clone();
+        rediscovered[0].characteristics[0].permissions =
+            BluetoothGattCharacteristic::PERMISSION_READ;
+        rediscovered[0].characteristics[0].descriptors[0].permissions = 0;
+        keep_permissions(&services, &mut rediscovered);
+        assert_eq!(format!("{:?}", rediscovered), format!("{:?}", services));
+    }
+
+    #[test]
+    fn test_context_map_invalidate_services() {
+        let mut map = ContextMap::new();
+        map.add_connection(1, 3, &String::from("aa:bb:cc:dd:ee:ff"));
+        map.get_connection_mut(3).unwrap().services = Some(vec![]);
+
+        // Without subscriptions the services are only dropped.
+        assert!(!map.invalidate_services(3));
+        assert!(map.get_connection_mut(3).unwrap().services.is_none());
+
+        map.set_subscribed(3, 10, true);
+        assert!(map.invalidate_services(3));
+        assert!(map.get_connection_mut(3).unwrap().rediscovering);
+
+        map.set_subscribed(3, 10, false);
+        assert!(!map.invalidate_services(3));
+        assert!(!map.invalidate_services(4));
+    }
+
+    #[test]
+    fn test_read_at_offset() {
+        let value = "Model".as_bytes();
+        assert_eq!(value, read_at_offset(value, 0).ok().unwrap());
+        assert_eq!("el".as_bytes(), read_at_offset(value, 3).ok().unwrap());
+        assert!(read_at_offset(value, 5).ok().unwrap().is_empty());
+        assert!(matches!(read_at_offset(value, 6), Err(GattStatus::InvalidOffset)));
+        assert!(matches!(read_at_offset(value, -1), Err(GattStatus::InvalidOffset)));
+    }
+
+    #[test]
+    fn test_combine_connection_priorities() {
+        use ConnectionPriority::*;
+        assert_eq!(ConnectionPriority::combine(&[]), Balanced);
+        assert_eq!(ConnectionPriority::combine(&[Balanced, LowPower]), LowPower);
+        assert_eq!(ConnectionPriority::combine(&[LowPower, High, Balanced]), High);
+    }
+
+    #[test]
+    fn test_raise_auth_req() {
+        let mut op = GattOperation::ReadCharacteristic { handle: 3, auth_req: 0 };
+        assert!(op.raise_auth_req(GATT_AUTH_REQ_NO_MITM));
+        assert!(!op.raise_auth_req(GATT_AUTH_REQ_NO_MITM));
+        assert!(op.raise_auth_req(GATT_AUTH_REQ_MITM));
+        assert!(matches!(
+            op,
+            GattOperation::ReadCharacteristic { auth_req: GATT_AUTH_REQ_MITM, .. }
+        ));
+
+        let mut op = GattOperation::ExecuteWrite { execute: true };
+        assert!(!op.raise_auth_req(GATT_AUTH_REQ_MITM));
+    }
+
+    #[test]
+    fn test_write_type_for_len() {
+        let mtu = DEFAULT_ATT_MTU;
+
+        // Long values are left to the native stack to chunk, up to the longest attribute value.
+        assert!(matches!(
+            write_type_for_len(GattWriteType::Write, 20, mtu, false),
+            Some(GattWriteType::Write)
+        ));
+        assert!(matches!(
+            write_type_for_len(GattWriteType::Write, 512, mtu, false),
+            Some(GattWriteType::Write)
+        ));
+        assert!(write_type_for_len(GattWriteType::Write, 513, mtu, false).is_none());
+
+        // The others go out in a single PDU.
+        assert!(matches!(
+            write_type_for_len(GattWriteType::WriteNoRsp, 20, mtu, false),
+            Some(GattWriteType::WriteNoRsp)
+        ));
+        assert!(write_type_for_len(GattWriteType::WriteNoRsp, 21, mtu, false).is_none());
+        assert!(matches!(
+            write_type_for_len(GattWriteType::WritePrepare, 18, mtu, false),
+            Some(GattWriteType::WritePrepare)
+        ));
+        assert!(write_type_for_len(GattWriteType::WritePrepare, 19, mtu, false).is_none());
+
+        // Every write of a reliable write is prepared.
+        assert!(matches!(
+            write_type_for_len(GattWriteType::Write, 18, mtu, true),
+            Some(GattWriteType::WritePrepare)
+        ));
+        assert!(write_type_for_len(GattWriteType::Write, 19, mtu, true).is_none());
+        assert!(matches!(
+            write_type_for_len(GattWriteType::Write, 512, 517, true),
+            Some(GattWriteType::WritePrepare)
+        ));
+    }
+
+    #[test]
+    fn test_operation_callbacks() {
+        let read_by_uuid = GattOperation::ReadUsingCharacteristicUuid {
+            uuid: Uuid { uu: DATABASE_HASH_UUID },
+            start_handle: 1,
+            end_handle: 0xffff,
+            auth_req: 0,
+        };
+        assert_eq!(read_by_uuid.callback(), OperationCallback::ReadCharacteristic);
+        assert_eq!(
+            GattOperation::ReadDatabaseHash { handle: None }.callback(),
+            OperationCallback::ReadCharacteristic
+        );
+        assert_eq!(
+            GattOperation::WriteDescriptor { handle: 4, auth_req: 0, value: vec![1, 0] }.callback(),
+            OperationCallback::WriteDescriptor
+        );
+        assert_eq!(
+            GattOperation::ExecuteWrite { execute: false }.callback(),
+            OperationCallback::ExecuteWrite
+        );
     }
 }
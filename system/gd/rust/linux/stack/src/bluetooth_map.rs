@@ -0,0 +1,718 @@
+//! Message Access Profile client (IBluetoothMap).
+//!
+//! A MAS (Message Access Server) session is kept open for every connected device to list and
+//! read its messages. While it is open, the device reports new messages and other changes to the
+//! local MNS (Message Notification Server), which listens once the first device is connected.
+
+use bt_topshim::btif::{BluetoothInterface, BtStatus, RawAddress, Uuid};
+use bt_topshim::profiles::socket::{self, BtSocket, SocketType, SOCK_FLAG_ENCRYPT};
+use bt_topshim::topstack;
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::callbacks::Callbacks;
+use crate::obex::{self, Header, ObexClient, ObexError, Packet};
+use crate::uuid::{Profile, UuidHelper, MAS, MNS};
+use crate::{Message, RPCProxy};
+
+/// OBEX target of the Message Access service.
+const MAS_TARGET: [u8; 16] = [
+    0xbb, 0x58, 0x2b, 0x40, 0x42, 0x0c, 0x11, 0xdb, 0xb0, 0xde, 0x08, 0x00, 0x20, 0x0c, 0x9a, 0x66,
+];
+
+/// OBEX target of the Message Notification service.
+const MNS_TARGET: [u8; 16] = [
+    0xbb, 0x58, 0x2b, 0x41, 0x42, 0x0c, 0x11, 0xdb, 0xb0, 0xde, 0x08, 0x00, 0x20, 0x0c, 0x9a, 0x66,
+];
+
+/// Name of the MNS service record.
+const MNS_SERVICE_NAME: &str = "MAP MNS";
+
+const MSG_LISTING_TYPE: &str = "x-bt/MAP-msg-listing";
+const MESSAGE_TYPE: &str = "x-bt/message";
+const NOTIFICATION_REGISTRATION_TYPE: &str = "x-bt/MAP-NotificationRegistration";
+
+// Application parameter tags.
+const TAG_MAX_LIST_COUNT: u8 = 0x01;
+const TAG_LIST_START_OFFSET: u8 = 0x02;
+const TAG_ATTACHMENT: u8 = 0x0a;
+const TAG_NOTIFICATION_STATUS: u8 = 0x0e;
+const TAG_CHARSET: u8 = 0x14;
+
+const CHARSET_UTF8: u8 = 0x01;
+
+/// Folders are looked up relative to this one, e.g. "inbox" or "sent".
+const MESSAGE_ROOT: [&str; 2] = ["telecom", "msg"];
+
+/// Defines the Message Access client API.
+#[dbus_interface(bluetooth_map_dbus, "org.chromium.bluetooth.BluetoothMap")]
+pub trait IBluetoothMap {
+    /// Adds an observer to message events.
+    ///
+    /// Returns an id identifying the callback.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothMapCallback + Send>) -> u32;
+
+    /// Removes an observer to message events.
+    ///
+    /// Returns true if the callback can be removed, false if `callback_id` is not recognized.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Opens a message access session to `device` and registers for its notifications.
+    ///
+    /// Returns true if connecting started. The result is reported by
+    /// `on_connection_state_changed`.
+    #[dbus_method("Connect")]
+    fn connect(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Closes the message access session to `device`.
+    #[dbus_method("Disconnect")]
+    fn disconnect(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Lists up to `max_count` messages of `folder` (e.g. "inbox"), newest first, starting at
+    /// `offset`.
+    ///
+    /// Returns the id of the request reported back in `on_messages_listing`, or -1 if `device`
+    /// isn't connected.
+    #[dbus_method("GetMessagesListing")]
+    fn get_messages_listing(
+        &mut self,
+        device: BluetoothDevice,
+        folder: String,
+        offset: u16,
+        max_count: u16,
+    ) -> i32;
+
+    /// Reads the message with `handle`, as reported in a listing or an event.
+    ///
+    /// Returns the id of the request reported back in `on_message`, or -1 if `device` isn't
+    /// connected.
+    #[dbus_method("GetMessage")]
+    fn get_message(&mut self, device: BluetoothDevice, handle: String) -> i32;
+}
+
+/// Message access events.
+pub trait IBluetoothMapCallback: RPCProxy {
+    /// Triggered when a message access session opens or closes.
+    fn on_connection_state_changed(&self, device: BluetoothDevice, connected: bool);
+
+    /// Triggered when a connected device reports a change to its messages, such as a new
+    /// message arriving.
+    fn on_message_event(&self, device: BluetoothDevice, event: MapEvent);
+
+    /// Triggered when a `get_messages_listing` request completes.
+    fn on_messages_listing(&self, request_id: i32, status: MapStatus, messages: Vec<MapMessage>);
+
+    /// Triggered when a `get_message` request completes. `message` is in bMessage format.
+    fn on_message(&self, request_id: i32, status: MapStatus, message: String);
+}
+
+/// An entry of a message listing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapMessage {
+    pub handle: String,
+    pub subject: String,
+    /// Time the message was received, as YYYYMMDDTHHMMSS.
+    pub datetime: String,
+    pub sender_name: String,
+    /// Phone number or email address of the sender.
+    pub sender_addressing: String,
+    /// SMS_GSM, SMS_CDMA, MMS or EMAIL.
+    pub msg_type: String,
+    pub read: bool,
+}
+
+/// An event report from a device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapEvent {
+    /// NewMessage, MessageDeleted, ReadStatusChanged, etc.
+    pub event_type: String,
+    pub handle: String,
+    pub folder: String,
+    pub msg_type: String,
+}
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum MapStatus {
+    Success = 0,
+    NotFound,
+    Failed,
+}
+
+/// Requests to the MAS session of a device.
+enum MasRequest {
+    GetListing(i32, String, u16, u16),
+    GetMessage(i32, String),
+    Disconnect,
+}
+
+/// Actions that `BluetoothMap` takes on behalf of its session tasks.
+pub enum MapActions {
+    /// The MAS session of a device is open and registered for notifications.
+    Connected(RawAddress),
+    /// The MAS session of a device ended.
+    Disconnected(RawAddress),
+    /// The MNS listening socket was closed.
+    ServerStopped,
+    /// A device connected to the MNS.
+    MnsAccepted(RawAddress, RawFd),
+    Event(RawAddress, MapEvent),
+    MessagesListing(i32, MapStatus, Vec<MapMessage>),
+    Message(i32, MapStatus, String),
+}
+
+/// Implementation of the Message Access client API.
+pub struct BluetoothMap {
+    intf: Arc<Mutex<BluetoothInterface>>,
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    socket: Option<BtSocket>,
+    mns_running: bool,
+    callbacks: Callbacks<dyn IBluetoothMapCallback + Send>,
+    sessions: HashMap<RawAddress, Sender<MasRequest>>,
+    next_request_id: i32,
+}
+
+impl BluetoothMap {
+    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothMap {
+        BluetoothMap {
+            intf,
+            tx: tx.clone(),
+            adapter: None,
+            socket: None,
+            mns_running: false,
+            callbacks: Callbacks::new(tx, Message::MapCallbackDisconnected),
+            sessions: HashMap::new(),
+            next_request_id: 1,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_map_actions(&mut self, action: MapActions) {
+        match action {
+            MapActions::Connected(addr) => {
+                let device = self.get_device(&addr);
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_connection_state_changed(device.clone(), true);
+                });
+            }
+
+            MapActions::Disconnected(addr) => {
+                self.sessions.remove(&addr);
+                let device = self.get_device(&addr);
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_connection_state_changed(device.clone(), false);
+                });
+            }
+
+            MapActions::ServerStopped => {
+                debug!("MNS server socket closed");
+                self.mns_running = false;
+            }
+
+            MapActions::MnsAccepted(addr, fd) => {
+                // Takes ownership of the fd so it is closed when the session ends.
+                let stream = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+
+                // Only devices we opened a MAS session to have a reason to notify us.
+                if !self.sessions.contains_key(&addr) {
+                    info!("[{}]: Rejecting MNS connection without MAS session", addr.to_string());
+                    return;
+                }
+
+                let tx = self.tx.clone();
+                topstack::get_runtime().spawn(async move {
+                    if let Err(e) = serve_mns(stream, addr, tx).await {
+                        debug!("[{}]: MNS session ended: {}", addr.to_string(), e);
+                    }
+                });
+            }
+
+            MapActions::Event(addr, event) => {
+                let device = self.get_device(&addr);
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_message_event(device.clone(), event.clone());
+                });
+            }
+
+            MapActions::MessagesListing(id, status, messages) => {
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_messages_listing(id, status, messages.clone());
+                });
+            }
+
+            MapActions::Message(id, status, message) => {
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_message(id, status, message.clone());
+                });
+            }
+        }
+    }
+
+    fn get_socket(&mut self) -> &BtSocket {
+        if self.socket.is_none() {
+            self.socket = Some(BtSocket::new(&self.intf.lock().unwrap()));
+        }
+
+        self.socket.as_ref().unwrap()
+    }
+
+    fn get_device(&self, addr: &RawAddress) -> BluetoothDevice {
+        let device = BluetoothDevice::new(*addr, String::from(""));
+        match &self.adapter {
+            Some(adapter) => {
+                let name = adapter.lock().unwrap().get_remote_name(device.clone());
+                BluetoothDevice::new(device.address, name)
+            }
+            None => device,
+        }
+    }
+
+    fn is_allowed(&self, addr: &RawAddress) -> bool {
+        match &self.adapter {
+            Some(adapter) => {
+                adapter.lock().unwrap().is_profile_enabled_for_device(addr, &Profile::Map)
+            }
+            None => false,
+        }
+    }
+
+    fn start_mns(&mut self) {
+        if self.mns_running {
+            return;
+        }
+
+        let (status, fd) = self.get_socket().listen(
+            SocketType::Rfcomm,
+            String::from(MNS_SERVICE_NAME),
+            Some(Uuid { uu: UuidHelper::from_string(MNS).unwrap() }),
+            0,
+            SOCK_FLAG_ENCRYPT,
+        );
+
+        let fd = match (&status, fd) {
+            (BtStatus::Success, Some(fd)) => fd,
+            _ => {
+                warn!("Failed to listen for MNS connections: {:?}", status);
+                return;
+            }
+        };
+
+        self.mns_running = true;
+        let tx = self.tx.clone();
+
+        std::thread::spawn(move || {
+            // Owns the fd so that it is closed when the thread exits.
+            let listener = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+
+            let result: std::io::Result<()> = socket::read_channel(fd).and_then(|channel| {
+                info!("MNS listening on channel {}", channel);
+                loop {
+                    let (signal, accepted) = socket::read_connection_complete(fd)?;
+                    if let Some(accepted) = accepted {
+                        let _ = tx.blocking_send(Message::Map(MapActions::MnsAccepted(
+                            signal.addr,
+                            accepted,
+                        )));
+                    }
+                }
+            });
+
+            debug!("MNS stopped: {:?}", result);
+            drop(listener);
+            let _ = tx.blocking_send(Message::Map(MapActions::ServerStopped));
+        });
+    }
+
+    fn send_request(
+        &mut self,
+        device: &BluetoothDevice,
+        make: impl FnOnce(i32) -> MasRequest,
+    ) -> i32 {
+        let session = match self.sessions.get(&device.address) {
+            Some(session) => session.clone(),
+            None => {
+                warn!("[{}]: No message access session", device.address);
+                return -1;
+            }
+        };
+
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+
+        let request = make(id);
+        topstack::get_runtime().spawn(async move {
+            let _ = session.send(request).await;
+        });
+
+        id
+    }
+}
+
+impl IBluetoothMap for BluetoothMap {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothMapCallback + Send>) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn connect(&mut self, device: BluetoothDevice) -> bool {
+        let addr = device.address;
+
+        if self.sessions.contains_key(&addr) {
+            return true;
+        }
+
+        if !self.is_allowed(&addr) {
+            info!("[{}]: MAP is not allowed.", device.address);
+            return false;
+        }
+
+        // Notifications are only sent once the MNS is there to receive them.
+        self.start_mns();
+
+        let (status, fd) = self.get_socket().connect(
+            addr,
+            SocketType::Rfcomm,
+            Some(Uuid { uu: UuidHelper::from_string(MAS).unwrap() }),
+            0,
+            SOCK_FLAG_ENCRYPT,
+        );
+
+        let fd = match (&status, fd) {
+            (BtStatus::Success, Some(fd)) => fd,
+            _ => {
+                warn!("[{}]: Failed to connect MAP: {:?}", device.address, status);
+                return false;
+            }
+        };
+
+        let (session_tx, session_rx) = mpsc::channel(10);
+        self.sessions.insert(addr, session_tx);
+
+        let tx = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            if let Err(e) = run_mas_session(fd, addr, session_rx, tx.clone()).await {
+                warn!("[{}]: MAS session failed: {}", addr.to_string(), e);
+            }
+            let _ = tx.send(Message::Map(MapActions::Disconnected(addr))).await;
+        });
+
+        true
+    }
+
+    fn disconnect(&mut self, device: BluetoothDevice) -> bool {
+        self.send_request(&device, |_| MasRequest::Disconnect) >= 0
+    }
+
+    fn get_messages_listing(
+        &mut self,
+        device: BluetoothDevice,
+        folder: String,
+        offset: u16,
+        max_count: u16,
+    ) -> i32 {
+        self.send_request(&device, |id| MasRequest::GetListing(id, folder, offset, max_count))
+    }
+
+    fn get_message(&mut self, device: BluetoothDevice, handle: String) -> i32 {
+        self.send_request(&device, |id| MasRequest::GetMessage(id, handle))
+    }
+}
+
+fn status_from_error(e: &ObexError) -> MapStatus {
+    match e {
+        ObexError::Response(obex::RSP_NOT_FOUND) => MapStatus::NotFound,
+        _ => MapStatus::Failed,
+    }
+}
+
+async fn run_mas_session(
+    fd: RawFd,
+    addr: RawAddress,
+    mut requests: mpsc::Receiver<MasRequest>,
+    tx: Sender<Message>,
+) -> Result<(), ObexError> {
+    let mut client = ObexClient::new(obex::wait_for_connection(fd).await?);
+    client.connect(Some(MAS_TARGET.to_vec())).await?;
+
+    client.set_path("").await?;
+    for folder in MESSAGE_ROOT.iter() {
+        client.set_path(folder).await?;
+    }
+
+    // The body of a notification registration is a single filler byte.
+    let register = client
+        .start_request(obex::OP_PUT | obex::FINAL_BIT)
+        .with_header(Header::Type(String::from(NOTIFICATION_REGISTRATION_TYPE)))
+        .with_header(Header::AppParameters(obex::encode_app_parameters(&[(
+            TAG_NOTIFICATION_STATUS,
+            vec![1],
+        )])))
+        .with_header(Header::EndOfBody(vec![0x30]));
+    let response = client.send(&register).await?;
+    if response.code != obex::RSP_SUCCESS {
+        // Listing and reading still work without notifications.
+        warn!("[{}]: Notification registration failed: {:#04x}", addr.to_string(), response.code);
+    }
+
+    let _ = tx.send(Message::Map(MapActions::Connected(addr))).await;
+
+    while let Some(request) = requests.recv().await {
+        match request {
+            MasRequest::GetListing(id, folder, offset, max_count) => {
+                let request = client
+                    .start_request(obex::OP_GET | obex::FINAL_BIT)
+                    .with_header(Header::Name(folder))
+                    .with_header(Header::Type(String::from(MSG_LISTING_TYPE)))
+                    .with_header(Header::AppParameters(obex::encode_app_parameters(&[
+                        (TAG_MAX_LIST_COUNT, max_count.to_be_bytes().to_vec()),
+                        (TAG_LIST_START_OFFSET, offset.to_be_bytes().to_vec()),
+                    ])));
+
+                let (status, messages) = match client.get(request).await {
+                    Ok((_, listing)) => {
+                        (MapStatus::Success, parse_listing(&String::from_utf8_lossy(&listing)))
+                    }
+                    Err(e) => (status_from_error(&e), vec![]),
+                };
+                let _ =
+                    tx.send(Message::Map(MapActions::MessagesListing(id, status, messages))).await;
+            }
+
+            MasRequest::GetMessage(id, handle) => {
+                let request = client
+                    .start_request(obex::OP_GET | obex::FINAL_BIT)
+                    .with_header(Header::Name(handle))
+                    .with_header(Header::Type(String::from(MESSAGE_TYPE)))
+                    .with_header(Header::AppParameters(obex::encode_app_parameters(&[
+                        (TAG_ATTACHMENT, vec![0]),
+                        (TAG_CHARSET, vec![CHARSET_UTF8]),
+                    ])));
+
+                let (status, message) = match client.get(request).await {
+                    Ok((_, message)) => {
+                        (MapStatus::Success, String::from_utf8_lossy(&message).to_string())
+                    }
+                    Err(e) => (status_from_error(&e), String::new()),
+                };
+                let _ = tx.send(Message::Map(MapActions::Message(id, status, message))).await;
+            }
+
+            MasRequest::Disconnect => break,
+        }
+    }
+
+    let _ = client.disconnect().await;
+    Ok(())
+}
+
+async fn serve_mns(
+    stream: std::os::unix::net::UnixStream,
+    addr: RawAddress,
+    tx: Sender<Message>,
+) -> Result<(), ObexError> {
+    let mut stream = obex::into_async_stream(stream)?;
+
+    let request = obex::read_packet(&mut stream, 4).await?;
+    if request.code != obex::OP_CONNECT {
+        obex::write_packet(&mut stream, &Packet::new(obex::RSP_BAD_REQUEST)).await?;
+        return Err(ObexError::InvalidPacket(String::from("expected CONNECT")));
+    }
+
+    let response = Packet::connect(obex::RSP_SUCCESS, obex::DEFAULT_MAX_PACKET_LEN)
+        .with_header(Header::ConnectionId(1))
+        .with_header(Header::Who(MNS_TARGET.to_vec()));
+    obex::write_packet(&mut stream, &response).await?;
+
+    // Every event report is a PUT, possibly split over several packets.
+    let mut report = vec![];
+    loop {
+        let packet = match obex::read_packet(&mut stream, 0).await {
+            Ok(packet) => packet,
+            // The device closes the transport when it is done with us.
+            Err(ObexError::Io(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        match packet.code {
+            obex::OP_DISCONNECT => {
+                obex::write_packet(&mut stream, &Packet::new(obex::RSP_SUCCESS)).await?;
+                return Ok(());
+            }
+            code if code & !obex::FINAL_BIT == obex::OP_PUT => {
+                report.extend_from_slice(&packet.body().0);
+                if code & obex::FINAL_BIT == 0 {
+                    obex::write_packet(&mut stream, &Packet::new(obex::RSP_CONTINUE)).await?;
+                    continue;
+                }
+
+                obex::write_packet(&mut stream, &Packet::new(obex::RSP_SUCCESS)).await?;
+                for event in parse_event_report(&String::from_utf8_lossy(&report)) {
+                    let _ = tx.send(Message::Map(MapActions::Event(addr, event))).await;
+                }
+                report.clear();
+            }
+            _ => {
+                obex::write_packet(&mut stream, &Packet::new(obex::RSP_BAD_REQUEST)).await?;
+            }
+        }
+    }
+}
+
+/// Returns the attributes of every `<name .../>` element in `xml`.
+///
+/// Listings and event reports are flat lists of elements, so this is all the XML parsing MAP
+/// needs.
+fn parse_elements(xml: &str, name: &str) -> Vec<HashMap<String, String>> {
+    let mut elements = vec![];
+    let open = format!("<{}", name);
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // Skip elements that only start with `name`, e.g. <msg-listing> when looking for <msg>.
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+            continue;
+        }
+
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let mut attributes = HashMap::new();
+        let mut tag = &rest[..end];
+        while let Some(eq) = tag.find('=') {
+            let key = tag[..eq].trim().to_string();
+            let value = tag[eq + 1..].trim_start();
+            let quote = match value.chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => break,
+            };
+            let value = &value[1..];
+            let close = match value.find(quote) {
+                Some(close) => close,
+                None => break,
+            };
+
+            attributes.insert(key, unescape_xml(&value[..close]));
+            tag = &value[close + 1..];
+        }
+
+        elements.push(attributes);
+        rest = &rest[end..];
+    }
+
+    elements
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub(crate) fn parse_listing(xml: &str) -> Vec<MapMessage> {
+    parse_elements(xml, "msg")
+        .into_iter()
+        .map(|mut attributes| {
+            let mut take = |key: &str| attributes.remove(key).unwrap_or_default();
+            MapMessage {
+                handle: take("handle"),
+                subject: take("subject"),
+                datetime: take("datetime"),
+                sender_name: take("sender_name"),
+                sender_addressing: take("sender_addressing"),
+                msg_type: take("type"),
+                read: take("read") == "yes",
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn parse_event_report(xml: &str) -> Vec<MapEvent> {
+    parse_elements(xml, "event")
+        .into_iter()
+        .map(|mut attributes| {
+            let mut take = |key: &str| attributes.remove(key).unwrap_or_default();
+            MapEvent {
+                event_type: take("type"),
+                handle: take("handle"),
+                folder: take("folder"),
+                msg_type: take("msg_type"),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() {
+        let xml = r#"<?xml version="1.0"?>
+            <MAP-msg-listing version="1.0">
+              <msg handle="20000100001" subject="Fish &amp; chips?" datetime="20220314T130510"
+                   sender_name="Jamie" sender_addressing="+1-987-6543210" type="SMS_GSM"
+                   read="no"/>
+              <msg handle='20000100002' type='MMS' read='yes' />
+            </MAP-msg-listing>"#;
+
+        let messages = parse_listing(xml);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0],
+            MapMessage {
+                handle: String::from("20000100001"),
+                subject: String::from("Fish & chips?"),
+                datetime: String::from("20220314T130510"),
+                sender_name: String::from("Jamie"),
+                sender_addressing: String::from("+1-987-6543210"),
+                msg_type: String::from("SMS_GSM"),
+                read: false,
+            }
+        );
+        assert_eq!(messages[1].handle, "20000100002");
+        assert_eq!(messages[1].msg_type, "MMS");
+        assert!(messages[1].read);
+    }
+
+    #[test]
+    fn test_parse_event_report() {
+        let xml = r#"<MAP-event-report version="1.0">
+              <event type="NewMessage" handle="12345678" folder="telecom/msg/inbox"
+                     msg_type="SMS_GSM"/>
+            </MAP-event-report>"#;
+
+        assert_eq!(
+            parse_event_report(xml),
+            vec![MapEvent {
+                event_type: String::from("NewMessage"),
+                handle: String::from("12345678"),
+                folder: String::from("telecom/msg/inbox"),
+                msg_type: String::from("SMS_GSM"),
+            }]
+        );
+    }
+}
@@ -3,18 +3,27 @@
 use bt_topshim::btif::{BluetoothInterface, RawAddress};
 use bt_topshim::profiles::a2dp::{
     A2dp, A2dpCallbacks, A2dpCallbacksDispatcher, A2dpCodecBitsPerSample, A2dpCodecChannelMode,
-    A2dpCodecConfig, A2dpCodecSampleRate, BtavConnectionState, PresentationPosition,
+    A2dpCodecConfig, A2dpCodecIndex, A2dpCodecPriority, A2dpCodecSampleRate, BtavConnectionState,
+    PresentationPosition,
+};
+use bt_topshim::profiles::avrcp::{
+    Avrcp, AvrcpCallbacks, AvrcpCallbacksDispatcher, AvrcpListItem, AvrcpMediaPlayerInfo,
+    AvrcpSongInfo,
+};
+use bt_topshim::profiles::hearing_aid::{
+    BthaConnectionState, HearingAid, HearingAidAudioConfig, HearingAidCallbacks,
+    HearingAidCallbacksDispatcher,
 };
-use bt_topshim::profiles::avrcp::{Avrcp, AvrcpCallbacks, AvrcpCallbacksDispatcher};
 use bt_topshim::profiles::hfp::{
-    BthfAudioState, BthfConnectionState, Hfp, HfpCallbacks, HfpCallbacksDispatcher,
-    HfpCodecCapability,
+    BthfAudioState, BthfCallState, BthfConnectionState, CallInfo, DeviceStatus, Hfp, HfpCallbacks,
+    HfpCallbacksDispatcher, HfpCodecCapability, HfpCodecId,
 };
 
 use bt_topshim::topstack;
 
+use dbus_macros::dbus_interface;
 use log::{info, warn};
-use num_traits::cast::ToPrimitive;
+use num_traits::cast::{FromPrimitive, ToPrimitive};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::sync::Arc;
@@ -24,40 +33,157 @@ use tokio::sync::mpsc::Sender;
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 
-use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
-use crate::Message;
+use crate::bluetooth::{
+    Bluetooth, BluetoothDevice, IBluetooth, ProfileConnectionReason, ProfileConnectionState,
+};
+use crate::callbacks::Callbacks;
+use crate::uuid::{Profile, UuidHelper, HEARING_AID};
+use crate::{queue_message, Message, RPCProxy};
 
 const DEFAULT_PROFILE_DISCOVERY_TIMEOUT_SEC: u64 = 5;
 
+/// Highest signal strength and battery level of the HFP indicators.
+const HFP_INDICATOR_MAX: u32 = 5;
+
+#[dbus_interface(bluetooth_media_dbus, "org.chromium.bluetooth.BluetoothMedia")]
 pub trait IBluetoothMedia {
-    ///
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothMediaCallback + Send>) -> bool;
+    /// Adds an observer to media events. Returns the ID to unregister it with.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothMediaCallback + Send>) -> u32;
+
+    /// Removes an observer added by `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
 
     /// initializes media (both A2dp and AVRCP) stack
+    #[dbus_method("Initialize")]
     fn initialize(&mut self) -> bool;
 
     /// clean up media stack
+    #[dbus_method("Cleanup")]
     fn cleanup(&mut self) -> bool;
 
+    #[dbus_method("Connect")]
     fn connect(&mut self, device: String);
+    /// Makes the device the active one of every audio category it is connected with, replacing
+    /// the device that was active until then.
+    #[dbus_method("SetActiveDevice")]
     fn set_active_device(&mut self, device: String);
+    /// Returns the address of the active device of an audio category, or an empty string if there
+    /// is none.
+    #[dbus_method("GetActiveDevice")]
+    fn get_active_device(&self, category: AudioCategory) -> String;
+    #[dbus_method("Disconnect")]
     fn disconnect(&mut self, device: String);
+    #[dbus_method("SetAudioConfig")]
     fn set_audio_config(
         &mut self,
         sample_rate: i32,
         bits_per_sample: i32,
         channel_mode: i32,
     ) -> bool;
+    #[dbus_method("SetVolume")]
     fn set_volume(&mut self, volume: i32);
+    #[dbus_method("StartAudioRequest")]
     fn start_audio_request(&mut self);
+    #[dbus_method("StopAudioRequest")]
     fn stop_audio_request(&mut self);
+    #[dbus_method("GetPresentationPosition")]
     fn get_presentation_position(&mut self) -> PresentationPosition;
 
+    /// Returns whether the platform can have the controller encode A2DP audio.
+    #[dbus_method("IsA2dpOffloadSupported")]
+    fn is_a2dp_offload_supported(&mut self) -> bool;
+    /// Sets the order in which A2DP codecs are preferred, as `A2dpCodecIndex` source codecs, most
+    /// preferred first. Codecs that aren't listed keep their default priority below the listed
+    /// ones. Connected devices switch to the most preferred codec they support.
+    #[dbus_method("SetA2dpCodecPriorities")]
+    fn set_a2dp_codec_priorities(&mut self, codecs: Vec<i32>) -> bool;
+    /// Forces the codec and audio format of the stream with the device. Fields of the config left
+    /// at 0 are negotiated. `on_audio_config_changed` reports the configuration that results.
+    #[dbus_method("SetA2dpCodecConfig")]
+    fn set_a2dp_codec_config(&mut self, device: String, config: A2dpCodecConfig) -> bool;
+
+    #[dbus_method("StartScoCall")]
     fn start_sco_call(&mut self, device: String);
+    #[dbus_method("StopScoCall")]
     fn stop_sco_call(&mut self, device: String);
+    /// Forces the codec of the next SCO calls with the device, for testing. `HfpCodecId::None`
+    /// goes back to negotiating the best codec both sides support.
+    #[dbus_method("SetScoCodecOverride")]
+    fn set_sco_codec_override(&mut self, device: String, codec: HfpCodecId) -> bool;
+    /// Returns the codec negotiated for SCO calls with the device.
+    #[dbus_method("GetScoCodec")]
+    fn get_sco_codec(&mut self, device: String) -> HfpCodecId;
+    /// Allows or keeps HFP devices from using wideband speech (mSBC). Disabling it holds every
+    /// device to CVSD, replacing any codec set with `set_sco_codec_override`.
+    #[dbus_method("SetHfpWidebandSpeech")]
+    fn set_hfp_wideband_speech(&mut self, enabled: bool);
+    #[dbus_method("GetHfpWidebandSpeech")]
+    fn get_hfp_wideband_speech(&self) -> bool;
+    /// Sets whether ringtones are sent over SCO to the HFP devices, instead of each device playing
+    /// its own. Disabled by default.
+    #[dbus_method("SetHfpInbandRingtone")]
+    fn set_hfp_inband_ringtone(&mut self, enabled: bool);
+    #[dbus_method("GetHfpInbandRingtone")]
+    fn get_hfp_inband_ringtone(&self) -> bool;
+    /// Starts or stops voice recognition with a HFP device that supports it. The audio of the
+    /// session goes through the SCO connection, which is up to the caller to start.
+    #[dbus_method("SetHfpVoiceRecognition")]
+    fn set_hfp_voice_recognition(&mut self, device: String, active: bool) -> bool;
+
+    /// Reports the calls of the telephony service to the HFP devices: how many are active and
+    /// held, and the state of the one being set up with its number. Until this is first called,
+    /// calls stand for a single active call while SCO is connected.
+    #[dbus_method("PhoneStateChanged")]
+    fn phone_state_changed(
+        &mut self,
+        num_active: u32,
+        num_held: u32,
+        call_setup_state: BthfCallState,
+        number: String,
+    );
+    /// Sets the calls listed to HFP devices asking for the current calls (+CLCC). It should be
+    /// kept in line with `phone_state_changed`.
+    #[dbus_method("ClccResponse")]
+    fn clcc_response(&mut self, calls: Vec<CallInfo>);
+    /// Sets the network operator reported to HFP devices (+COPS).
+    #[dbus_method("SetHfpOperatorName")]
+    fn set_hfp_operator_name(&mut self, name: String);
+    /// Sets the signal strength shown by HFP devices, from 0 to 5.
+    #[dbus_method("SetHfpSignalStrength")]
+    fn set_hfp_signal_strength(&mut self, signal: u32) -> bool;
+    /// Sets the battery level of the audio gateway shown by HFP devices, from 0 to 5.
+    #[dbus_method("SetHfpBatteryLevel")]
+    fn set_hfp_battery_level(&mut self, level: u32) -> bool;
+
+    /// Sets the volume of the connected hearing aids, from -128 (muted) to 0.
+    #[dbus_method("SetHearingAidVolume")]
+    fn set_hearing_aid_volume(&mut self, volume: i32);
+    /// Starts streaming the PCM written to the hearing aid data socket.
+    #[dbus_method("StartHearingAidAudioRequest")]
+    fn start_hearing_aid_audio_request(&mut self) -> bool;
+    #[dbus_method("StopHearingAidAudioRequest")]
+    fn stop_hearing_aid_audio_request(&mut self) -> bool;
+    /// Returns the PCM format the hearing aid data socket expects.
+    #[dbus_method("GetHearingAidAudioConfig")]
+    fn get_hearing_aid_audio_config(&mut self) -> HearingAidAudioConfig;
+    #[dbus_method("GetHearingAidPresentationPosition")]
+    fn get_hearing_aid_presentation_position(&mut self) -> PresentationPosition;
+
+    /// Sets the queue remote controllers see as the now playing list, and which of its tracks
+    /// is playing.
+    #[dbus_method("SetNowPlayingList")]
+    fn set_now_playing_list(&mut self, current_media_id: String, songs: Vec<AvrcpSongInfo>);
+    /// Sets the media players remote controllers can browse, and the one their commands go to.
+    #[dbus_method("SetMediaPlayers")]
+    fn set_media_players(&mut self, addressed_player: u16, players: Vec<AvrcpMediaPlayerInfo>);
+    /// Sets the contents of a folder of the media players. Their top folder has an empty id.
+    #[dbus_method("SetFolderItems")]
+    fn set_folder_items(&mut self, media_id: String, items: Vec<AvrcpListItem>);
 }
 
-pub trait IBluetoothMediaCallback {
+pub trait IBluetoothMediaCallback: RPCProxy {
     /// Triggered when a Bluetooth audio device is ready to be used. This should
     /// only be triggered once for a device and send an event to clients. If the
     /// device supports both HFP and A2DP, both should be ready when this is
@@ -72,6 +198,90 @@ pub trait IBluetoothMediaCallback {
 
     ///
     fn on_absolute_volume_changed(&self, volume: i32);
+
+    /// Triggered when the SCO audio connection of a HFP device changes state. The SCO handle is
+    /// only valid once connected.
+    fn on_audio_connection_state(
+        &self,
+        addr: String,
+        state: BthfAudioState,
+        codec: HfpCodecId,
+        sco_handle: u16,
+    );
+
+    /// Triggered when hearing aids are connected and ready to stream. The two sides of a
+    /// binaural set are reported once, with the address of the side that connected first.
+    fn on_hearing_aid_added(&self, addr: String, name: String);
+
+    /// Triggered when the last connected side of a set of hearing aids is disconnected.
+    fn on_hearing_aid_removed(&self, addr: String);
+
+    /// Triggered when a remote controller picks an item to play while browsing, either from the
+    /// now playing list or from a folder of the player.
+    fn on_avrcp_play_item(&self, player_id: u16, now_playing: bool, media_id: String);
+
+    /// Triggered when the active device of an audio category changes. The address is empty when
+    /// the active device disconnected, until another one is made active.
+    fn on_active_device_changed(&self, category: AudioCategory, addr: String);
+
+    /// Triggered when the A2DP codec configuration with a device is negotiated, or changes after
+    /// `set_a2dp_codec_priorities` or `set_a2dp_codec_config`.
+    fn on_audio_config_changed(&self, addr: String, config: A2dpCodecConfig);
+
+    /// Triggered when `set_hfp_wideband_speech` changes whether wideband speech is allowed.
+    fn on_hfp_wideband_speech_changed(&self, enabled: bool);
+
+    /// Triggered when `set_hfp_inband_ringtone` changes whether ringtones are sent over SCO.
+    fn on_hfp_inband_ringtone_changed(&self, enabled: bool);
+
+    /// Triggered when voice recognition starts or stops with a HFP device, whether the device or
+    /// `set_hfp_voice_recognition` asked for it.
+    fn on_hfp_voice_recognition_changed(&self, addr: String, active: bool);
+}
+
+/// Kinds of audio that are routed to a single device at a time.
+///
+/// The first device to connect in a category becomes its active device. After that, the active
+/// device only changes through `IBluetoothMedia::set_active_device` or when it disconnects.
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, Hash, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum AudioCategory {
+    /// Audio streamed to headphones and speakers over A2DP, with the adapter as source.
+    A2dp = 0,
+    /// Calls handled over HFP, with the adapter as audio gateway.
+    Hfp,
+}
+
+/// State of the phone the audio gateway reports to HFP devices.
+struct TelephonyState {
+    // Whether a telephony service reports the calls. Without one, SCO stands for an active call,
+    // which some headsets need to see before they send audio.
+    reported: bool,
+    num_active: u32,
+    num_held: u32,
+    call_setup_state: BthfCallState,
+    calls: Vec<CallInfo>,
+    operator_name: String,
+    status: DeviceStatus,
+}
+
+impl TelephonyState {
+    fn new() -> TelephonyState {
+        TelephonyState {
+            reported: false,
+            num_active: 0,
+            num_held: 0,
+            call_setup_state: BthfCallState::Idle,
+            calls: vec![],
+            operator_name: String::from(""),
+            status: DeviceStatus {
+                network_available: true,
+                roaming: false,
+                signal: HFP_INDICATOR_MAX,
+                battery: HFP_INDICATOR_MAX,
+            },
+        }
+    }
 }
 
 /// Serializable device used in.
@@ -98,14 +308,15 @@ impl BluetoothAudioDevice {
 /// Actions that `BluetoothMedia` can take on behalf of the stack.
 pub enum MediaActions {
     Connect(String),
+    /// Connects one of A2DP, HFP and hearing aid.
+    ConnectProfile(String, Profile),
     Disconnect(String),
 }
 
 pub struct BluetoothMedia {
     intf: Arc<Mutex<BluetoothInterface>>,
     initialized: bool,
-    callbacks: Arc<Mutex<Vec<(u32, Box<dyn IBluetoothMediaCallback + Send>)>>>,
-    callback_last_id: u32,
+    callbacks: Arc<Mutex<Callbacks<dyn IBluetoothMediaCallback + Send>>>,
     tx: Sender<Message>,
     adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
     a2dp: Option<A2dp>,
@@ -113,10 +324,24 @@ pub struct BluetoothMedia {
     a2dp_states: HashMap<RawAddress, BtavConnectionState>,
     hfp: Option<Hfp>,
     hfp_states: HashMap<RawAddress, BthfConnectionState>,
+    hfp_codecs: HashMap<RawAddress, HfpCodecId>,
+    hfp_wideband_speech: bool,
+    hfp_inband_ringtone: bool,
+    telephony: TelephonyState,
+    hearing_aid: Option<HearingAid>,
+    hearing_aid_states: HashMap<RawAddress, BthaConnectionState>,
+    hearing_aid_hi_sync_ids: HashMap<RawAddress, u64>,
+    // Address each connected set of hearing aids was reported with, by HiSyncId.
+    hearing_aid_sets: HashMap<u64, RawAddress>,
     selectable_caps: HashMap<RawAddress, Vec<A2dpCodecConfig>>,
+    // Set through `set_a2dp_codec_priorities`, and given to each device that connects.
+    a2dp_codec_priorities: Vec<A2dpCodecConfig>,
+    // Codec configuration last negotiated with each connected device.
+    a2dp_codec_configs: HashMap<RawAddress, A2dpCodecConfig>,
     hfp_caps: HashMap<RawAddress, HfpCodecCapability>,
     device_added_tasks: Arc<Mutex<HashMap<RawAddress, Option<JoinHandle<()>>>>>,
     absolute_volume: bool,
+    active_devices: HashMap<AudioCategory, RawAddress>,
 }
 
 impl BluetoothMedia {
@@ -124,8 +349,10 @@ impl BluetoothMedia {
         BluetoothMedia {
             intf,
             initialized: false,
-            callbacks: Arc::new(Mutex::new(vec![])),
-            callback_last_id: 0,
+            callbacks: Arc::new(Mutex::new(Callbacks::new(
+                tx.clone(),
+                Message::MediaCallbackDisconnected,
+            ))),
             tx,
             adapter: None,
             a2dp: None,
@@ -133,10 +360,21 @@ impl BluetoothMedia {
             a2dp_states: HashMap::new(),
             hfp: None,
             hfp_states: HashMap::new(),
+            hfp_codecs: HashMap::new(),
+            hfp_wideband_speech: true,
+            hfp_inband_ringtone: false,
+            telephony: TelephonyState::new(),
+            hearing_aid: None,
+            hearing_aid_states: HashMap::new(),
+            hearing_aid_hi_sync_ids: HashMap::new(),
+            hearing_aid_sets: HashMap::new(),
             selectable_caps: HashMap::new(),
+            a2dp_codec_priorities: vec![],
+            a2dp_codec_configs: HashMap::new(),
             hfp_caps: HashMap::new(),
             device_added_tasks: Arc::new(Mutex::new(HashMap::new())),
             absolute_volume: false,
+            active_devices: HashMap::new(),
         }
     }
 
@@ -144,6 +382,16 @@ impl BluetoothMedia {
         self.adapter = Some(adapter);
     }
 
+    /// Whether the profiles are up, which they are from when the adapter is first enabled.
+    pub(crate) fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Gets the codec configuration negotiated with a connected A2DP device.
+    pub(crate) fn get_a2dp_codec_config(&self, addr: &RawAddress) -> Option<A2dpCodecConfig> {
+        self.a2dp_codec_configs.get(addr).copied()
+    }
+
     pub fn dispatch_a2dp_callbacks(&mut self, cb: A2dpCallbacks) {
         match cb {
             A2dpCallbacks::ConnectionState(addr, state) => {
@@ -154,12 +402,24 @@ impl BluetoothMedia {
                 }
                 match state {
                     BtavConnectionState::Connected => {
+                        if !self.is_profile_allowed(addr, Profile::A2dpSink) {
+                            info!("[{}]: a2dp is not allowed.", addr.to_string());
+                            self.a2dp.as_mut().unwrap().disconnect(addr);
+                            self.notify_connection_rejected(addr, Profile::A2dpSink);
+                            return;
+                        }
                         info!("[{}]: a2dp connected.", addr.to_string());
                         self.notify_media_capability_added(addr);
                         self.a2dp_states.insert(addr, state);
+                        self.apply_a2dp_codec_priorities(addr);
+                        self.activate_if_none(AudioCategory::A2dp, addr);
                     }
                     BtavConnectionState::Disconnected => match self.a2dp_states.remove(&addr) {
-                        Some(_) => self.notify_media_capability_removed(addr),
+                        Some(_) => {
+                            self.a2dp_codec_configs.remove(&addr);
+                            self.deactivate(AudioCategory::A2dp, addr);
+                            self.notify_media_capability_removed(addr);
+                        }
                         None => {
                             warn!("[{}]: Unknown address a2dp disconnected.", addr.to_string());
                         }
@@ -168,10 +428,22 @@ impl BluetoothMedia {
                         self.a2dp_states.insert(addr, state);
                     }
                 }
+
+                let state = match state {
+                    BtavConnectionState::Disconnected => ProfileConnectionState::Disconnected,
+                    BtavConnectionState::Connecting => ProfileConnectionState::Connecting,
+                    BtavConnectionState::Connected => ProfileConnectionState::Connected,
+                    BtavConnectionState::Disconnecting => ProfileConnectionState::Disconnecting,
+                };
+                self.notify_profile_connection_state(addr, Profile::A2dpSink, state);
             }
             A2dpCallbacks::AudioState(_addr, _state) => {}
-            A2dpCallbacks::AudioConfig(addr, _config, _local_caps, selectable_caps) => {
+            A2dpCallbacks::AudioConfig(addr, config, _local_caps, selectable_caps) => {
                 self.selectable_caps.insert(addr, selectable_caps);
+                self.a2dp_codec_configs.insert(addr, config);
+                self.for_all_callbacks(|callback| {
+                    callback.on_audio_config_changed(addr.to_string(), config);
+                });
             }
             A2dpCallbacks::MandatoryCodecPreferred(_addr) => {}
         }
@@ -190,12 +462,20 @@ impl BluetoothMedia {
                     callback.on_absolute_volume_changed(i32::from(volume));
                 });
             }
+            AvrcpCallbacks::AvrcpPlayItem(player_id, now_playing, media_id) => {
+                self.for_all_callbacks(|callback| {
+                    callback.on_avrcp_play_item(player_id, now_playing, media_id.clone());
+                });
+            }
         }
     }
 
     pub fn dispatch_media_actions(&mut self, action: MediaActions) {
         match action {
             MediaActions::Connect(address) => self.connect(address),
+            MediaActions::ConnectProfile(address, profile) => {
+                self.connect_profile(address, profile)
+            }
             MediaActions::Disconnect(address) => self.disconnect(address),
         }
     }
@@ -208,24 +488,51 @@ impl BluetoothMedia {
                 {
                     return;
                 }
+                // HFP is only usable once the service level connection is up.
+                let profile_state = match state {
+                    BthfConnectionState::Disconnected => ProfileConnectionState::Disconnected,
+                    BthfConnectionState::Connecting | BthfConnectionState::Connected => {
+                        ProfileConnectionState::Connecting
+                    }
+                    BthfConnectionState::SlcConnected => ProfileConnectionState::Connected,
+                    BthfConnectionState::Disconnecting => ProfileConnectionState::Disconnecting,
+                };
+
                 match state {
                     BthfConnectionState::Connected => {
+                        if !self.is_profile_allowed(addr, Profile::Hfp) {
+                            info!("[{}]: hfp is not allowed.", addr.to_string());
+                            self.hfp.as_mut().unwrap().disconnect(addr);
+                            self.notify_connection_rejected(addr, Profile::Hfp);
+                            return;
+                        }
                         info!("[{}]: hfp connected.", addr.to_string());
                     }
                     BthfConnectionState::SlcConnected => {
                         info!("[{}]: hfp slc connected.", addr.to_string());
-                        // TODO(b/214148074): Support WBS
-                        self.hfp_caps.insert(addr, HfpCodecCapability::CVSD);
+                        // The codec is negotiated while setting up the SLC.
+                        let cap = match self.hfp_codecs.get(&addr) {
+                            Some(HfpCodecId::Msbc) => {
+                                HfpCodecCapability::CVSD | HfpCodecCapability::MSBC
+                            }
+                            _ => HfpCodecCapability::CVSD,
+                        };
+                        self.hfp_caps.insert(addr, cap);
+                        self.apply_hfp_settings(addr);
                         self.notify_media_capability_added(addr);
+                        self.activate_if_none(AudioCategory::Hfp, addr);
                     }
                     BthfConnectionState::Disconnected => {
                         info!("[{}]: hfp disconnected.", addr.to_string());
+                        self.hfp_codecs.remove(&addr);
+                        self.deactivate(AudioCategory::Hfp, addr);
                         match self.hfp_states.remove(&addr) {
                             Some(_) => self.notify_media_capability_removed(addr),
                             None => {
                                 warn!("[{}] Unknown address hfp disconnected.", addr.to_string())
                             }
                         }
+                        self.notify_profile_connection_state(addr, Profile::Hfp, profile_state);
                         return;
                     }
                     BthfConnectionState::Connecting => {
@@ -237,6 +544,7 @@ impl BluetoothMedia {
                 }
 
                 self.hfp_states.insert(addr, state);
+                self.notify_profile_connection_state(addr, Profile::Hfp, profile_state);
             }
             HfpCallbacks::AudioState(state, addr) => {
                 if self.hfp_states.get(&addr).is_none()
@@ -248,9 +556,11 @@ impl BluetoothMedia {
                 match state {
                     BthfAudioState::Connected => {
                         info!("[{}]: hfp audio connected.", addr.to_string());
+                        self.set_sco_call(addr, true);
                     }
                     BthfAudioState::Disconnected => {
                         info!("[{}]: hfp audio disconnected.", addr.to_string());
+                        self.set_sco_call(addr, false);
                     }
                     BthfAudioState::Connecting => {
                         info!("[{}]: hfp audio connecting.", addr.to_string());
@@ -259,6 +569,186 @@ impl BluetoothMedia {
                         info!("[{}]: hfp audio disconnecting.", addr.to_string());
                     }
                 }
+
+                let codec = *self.hfp_codecs.get(&addr).unwrap_or(&HfpCodecId::None);
+                let sco_handle = self.hfp.as_mut().unwrap().get_sco_handle(addr);
+                self.for_all_callbacks(|callback| {
+                    callback.on_audio_connection_state(addr.to_string(), state, codec, sco_handle);
+                });
+            }
+            HfpCallbacks::CodecNegotiated(codec, addr) => {
+                info!("[{}]: hfp codec {:?}.", addr.to_string(), codec);
+                self.hfp_codecs.insert(addr, codec);
+            }
+            HfpCallbacks::VoiceRecognition(active, addr) => {
+                let accepted =
+                    self.hfp_states.get(&addr) == Some(&BthfConnectionState::SlcConnected);
+                info!(
+                    "[{}]: hfp voice recognition {} (accepted: {}).",
+                    addr.to_string(),
+                    active,
+                    accepted
+                );
+                self.hfp.as_mut().unwrap().voice_recognition_response(addr, accepted);
+                if accepted {
+                    self.for_all_callbacks(|callback| {
+                        callback.on_hfp_voice_recognition_changed(addr.to_string(), active);
+                    });
+                }
+            }
+            HfpCallbacks::IndicatorsQuery(addr) => {
+                let telephony = &self.telephony;
+                self.hfp.as_mut().unwrap().cind_response(
+                    addr,
+                    telephony.status,
+                    telephony.num_active,
+                    telephony.num_held,
+                    telephony.call_setup_state,
+                );
+            }
+            HfpCallbacks::OperatorQuery(addr) => {
+                self.hfp.as_mut().unwrap().cops_response(addr, &self.telephony.operator_name);
+            }
+            HfpCallbacks::CurrentCallsQuery(addr) => {
+                self.hfp.as_mut().unwrap().clcc_response(addr, &self.telephony.calls);
+            }
+        }
+    }
+
+    /// Makes the SCO connection with the device stand for a call, unless a telephony service
+    /// reports the actual calls.
+    fn set_sco_call(&mut self, addr: RawAddress, connected: bool) {
+        let telephony = &mut self.telephony;
+        if telephony.reported {
+            return;
+        }
+
+        telephony.num_active = if connected { 1 } else { 0 };
+        telephony.calls = if connected {
+            vec![CallInfo {
+                index: 1,
+                incoming: false,
+                state: BthfCallState::Active,
+                multi_party: false,
+                number: String::from(""),
+            }]
+        } else {
+            vec![]
+        };
+        self.hfp.as_mut().unwrap().phone_state_change(
+            addr,
+            telephony.num_active,
+            0,
+            BthfCallState::Idle,
+            "",
+        );
+    }
+
+    /// Gives a device whose SLC just came up the settings that differ from what it was told
+    /// while setting it up.
+    fn apply_hfp_settings(&mut self, addr: RawAddress) {
+        let hfp = self.hfp.as_mut().unwrap();
+        if !self.hfp_wideband_speech {
+            hfp.set_codec_override(addr, HfpCodecId::Cvsd);
+        }
+        if self.hfp_inband_ringtone {
+            hfp.set_inband_ringtone(addr, true);
+        }
+    }
+
+    fn notify_hfp_device_status(&mut self) {
+        for addr in self.slc_connected_devices() {
+            self.hfp.as_mut().unwrap().device_status_notification(addr, self.telephony.status);
+        }
+    }
+
+    fn slc_connected_devices(&self) -> Vec<RawAddress> {
+        self.hfp_states
+            .iter()
+            .filter(|(_, state)| **state == BthfConnectionState::SlcConnected)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    pub fn dispatch_hearing_aid_callbacks(&mut self, cb: HearingAidCallbacks) {
+        match cb {
+            HearingAidCallbacks::ConnectionState(state, addr) => {
+                if self.hearing_aid_states.get(&addr) == Some(&state) {
+                    return;
+                }
+                let hi_sync_id = *self.hearing_aid_hi_sync_ids.get(&addr).unwrap_or(&0);
+                let profile_state = match state {
+                    BthaConnectionState::Disconnected => ProfileConnectionState::Disconnected,
+                    BthaConnectionState::Connecting => ProfileConnectionState::Connecting,
+                    BthaConnectionState::Connected => ProfileConnectionState::Connected,
+                    BthaConnectionState::Disconnecting => ProfileConnectionState::Disconnecting,
+                };
+
+                match state {
+                    BthaConnectionState::Connected => {
+                        if !self.is_profile_allowed(addr, Profile::HearingAid) {
+                            info!("[{}]: hearing aid is not allowed.", addr.to_string());
+                            self.hearing_aid.as_mut().unwrap().disconnect(addr);
+                            self.notify_connection_rejected(addr, Profile::HearingAid);
+                            return;
+                        }
+                        info!("[{}]: hearing aid connected.", addr.to_string());
+                        // The other side of a binaural set streams along with this one, so
+                        // clients only see the set once.
+                        if !self.hearing_aid_sets.contains_key(&hi_sync_id) {
+                            self.hearing_aid_sets.insert(hi_sync_id, addr);
+                            let name = self.adapter_get_remote_name(addr);
+                            self.for_all_callbacks(|callback| {
+                                callback.on_hearing_aid_added(addr.to_string(), name.clone());
+                            });
+                        }
+                    }
+                    BthaConnectionState::Disconnected => {
+                        info!("[{}]: hearing aid disconnected.", addr.to_string());
+                        if self.hearing_aid_states.remove(&addr).is_none() {
+                            warn!(
+                                "[{}]: Unknown address hearing aid disconnected.",
+                                addr.to_string()
+                            );
+                            return;
+                        }
+                        let set_connected = self.hearing_aid_states.iter().any(|(other, state)| {
+                            *state == BthaConnectionState::Connected
+                                && self.hearing_aid_hi_sync_ids.get(other) == Some(&hi_sync_id)
+                        });
+                        if !set_connected {
+                            if let Some(reported) = self.hearing_aid_sets.remove(&hi_sync_id) {
+                                self.for_all_callbacks(|callback| {
+                                    callback.on_hearing_aid_removed(reported.to_string());
+                                });
+                            }
+                        }
+                        self.notify_profile_connection_state(
+                            addr,
+                            Profile::HearingAid,
+                            profile_state,
+                        );
+                        return;
+                    }
+                    BthaConnectionState::Connecting => {
+                        info!("[{}]: hearing aid connecting.", addr.to_string());
+                    }
+                    BthaConnectionState::Disconnecting => {
+                        info!("[{}]: hearing aid disconnecting.", addr.to_string());
+                    }
+                }
+
+                self.hearing_aid_states.insert(addr, state);
+                self.notify_profile_connection_state(addr, Profile::HearingAid, profile_state);
+            }
+            HearingAidCallbacks::DeviceAvailable(capabilities, hi_sync_id, addr) => {
+                info!(
+                    "[{}]: hearing aid available, capabilities {:?}, HiSyncId {:016x}.",
+                    addr.to_string(),
+                    capabilities,
+                    hi_sync_id
+                );
+                self.hearing_aid_hi_sync_ids.insert(addr, hi_sync_id);
             }
         }
     }
@@ -268,14 +758,14 @@ impl BluetoothMedia {
         fn dedup_added_cb(
             device_added_tasks: Arc<Mutex<HashMap<RawAddress, Option<JoinHandle<()>>>>>,
             addr: RawAddress,
-            callbacks: Arc<Mutex<Vec<(u32, Box<dyn IBluetoothMediaCallback + Send>)>>>,
+            callbacks: Arc<Mutex<Callbacks<dyn IBluetoothMediaCallback + Send>>>,
             device: BluetoothAudioDevice,
             is_delayed: bool,
         ) -> bool {
             // Closure used to lock and trigger the device added callbacks.
             let trigger_device_added = || {
-                for callback in &*callbacks.lock().unwrap() {
-                    callback.1.on_bluetooth_audio_device_added(device.clone());
+                for callback in callbacks.lock().unwrap().iter() {
+                    callback.on_bluetooth_audio_device_added(device.clone());
                 }
             };
             let mut guard = device_added_tasks.lock().unwrap();
@@ -376,15 +866,154 @@ impl BluetoothMedia {
         }
     }
 
+    /// Returns whether the device is connected and ready to be used for `category`.
+    fn is_connected_for(&self, category: AudioCategory, addr: &RawAddress) -> bool {
+        match category {
+            AudioCategory::A2dp => {
+                self.a2dp_states.get(addr) == Some(&BtavConnectionState::Connected)
+            }
+            AudioCategory::Hfp => {
+                self.hfp_states.get(addr) == Some(&BthfConnectionState::SlcConnected)
+            }
+        }
+    }
+
+    fn apply_a2dp_codec_priorities(&self, addr: RawAddress) {
+        if self.a2dp_codec_priorities.is_empty() {
+            return;
+        }
+
+        let status =
+            self.a2dp.as_ref().unwrap().config_codec(addr, self.a2dp_codec_priorities.clone());
+        if status != 0 {
+            warn!("[{}]: Failed to set A2DP codec priorities: {}", addr.to_string(), status);
+        }
+    }
+
+    /// Makes the device the active one of `category` in the stack and tells clients about it.
+    fn activate(&mut self, category: AudioCategory, addr: RawAddress) {
+        if self.active_devices.get(&category) == Some(&addr) {
+            return;
+        }
+
+        let status = match category {
+            AudioCategory::A2dp => {
+                self.a2dp.as_mut().unwrap().set_active_device(addr);
+                0
+            }
+            AudioCategory::Hfp => self.hfp.as_mut().unwrap().set_active_device(addr),
+        };
+        if status != 0 {
+            warn!("[{}]: Failed to make active for {:?}: {}", addr.to_string(), category, status);
+            return;
+        }
+
+        info!("[{}]: Active device for {:?}.", addr.to_string(), category);
+        self.active_devices.insert(category, addr);
+        self.for_all_callbacks(|callback| {
+            callback.on_active_device_changed(category, addr.to_string());
+        });
+    }
+
+    fn activate_if_none(&mut self, category: AudioCategory, addr: RawAddress) {
+        if !self.active_devices.contains_key(&category) {
+            self.activate(category, addr);
+        }
+    }
+
+    /// Forgets the active device of `category` if it is the device, which is disconnecting.
+    fn deactivate(&mut self, category: AudioCategory, addr: RawAddress) {
+        if self.active_devices.get(&category) != Some(&addr) {
+            return;
+        }
+
+        self.active_devices.remove(&category);
+        self.for_all_callbacks(|callback| {
+            callback.on_active_device_changed(category, String::from(""));
+        });
+    }
+
+    fn connect_profile(&mut self, device: String, profile: Profile) {
+        let addr = match RawAddress::from_string(device.clone()) {
+            Some(addr) => addr,
+            None => {
+                warn!("Invalid device string {}", device);
+                return;
+            }
+        };
+        if !self.is_profile_allowed(addr, profile) {
+            return;
+        }
+
+        match profile {
+            Profile::A2dpSink => self.a2dp.as_mut().unwrap().connect(addr),
+            Profile::Hfp => self.hfp.as_mut().unwrap().connect(addr),
+            Profile::HearingAid => self.hearing_aid.as_mut().unwrap().connect(addr),
+            _ => warn!("[{}]: {:?} is not a media profile", device, profile),
+        };
+    }
+
+    fn notify_profile_connection_state(
+        &self,
+        addr: RawAddress,
+        profile: Profile,
+        state: ProfileConnectionState,
+    ) {
+        if let Some(adapter) = &self.adapter {
+            adapter.lock().unwrap().update_profile_connection_state(
+                addr,
+                profile,
+                state,
+                ProfileConnectionReason::None,
+            );
+        }
+    }
+
+    fn notify_connection_rejected(&self, addr: RawAddress, profile: Profile) {
+        if let Some(adapter) = &self.adapter {
+            adapter.lock().unwrap().update_profile_connection_state(
+                addr,
+                profile,
+                ProfileConnectionState::Disconnected,
+                ProfileConnectionReason::NotAllowed,
+            );
+        }
+    }
+
     fn for_all_callbacks<F: Fn(&Box<dyn IBluetoothMediaCallback + Send>)>(&self, f: F) {
-        for callback in &*self.callbacks.lock().unwrap() {
-            f(&callback.1);
+        self.callbacks.lock().unwrap().for_all_callbacks(f);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.lock().unwrap().remove_callback(id)
+    }
+
+    /// Returns whether `profile` is allowed by policy and hasn't been disabled for the device.
+    fn is_profile_allowed(&self, addr: RawAddress, profile: Profile) -> bool {
+        match &self.adapter {
+            Some(adapter) => adapter.lock().unwrap().is_profile_enabled_for_device(&addr, &profile),
+            None => true,
+        }
+    }
+
+    fn adapter_has_remote_uuid(&self, addr: RawAddress, uuid: &str) -> bool {
+        let uuid = match UuidHelper::from_string(uuid) {
+            Some(uuid) => uuid,
+            None => return false,
+        };
+        match &self.adapter {
+            Some(adapter) => adapter
+                .lock()
+                .unwrap()
+                .get_remote_uuids(BluetoothDevice::new(addr, "".to_string()))
+                .contains(&uuid),
+            None => false,
         }
     }
 
     fn adapter_get_remote_name(&self, addr: RawAddress) -> String {
         let device = BluetoothDevice::new(
-            addr.to_string(),
+            addr,
             // get_remote_name needs a BluetoothDevice just for its address, the
             // name field is unused so construct one with a fake name.
             "Classic Device".to_string(),
@@ -414,13 +1043,18 @@ impl BluetoothMedia {
     }
 }
 
+// Whether `codec_type` is an `A2dpCodecIndex` the adapter can encode with as a source.
+fn is_source_codec(codec_type: i32) -> bool {
+    match A2dpCodecIndex::from_i32(codec_type) {
+        Some(index) => index < A2dpCodecIndex::SRC_MAX,
+        None => false,
+    }
+}
+
 fn get_a2dp_dispatcher(tx: Sender<Message>) -> A2dpCallbacksDispatcher {
     A2dpCallbacksDispatcher {
         dispatch: Box::new(move |cb| {
-            let txl = tx.clone();
-            topstack::get_runtime().spawn(async move {
-                let _ = txl.send(Message::A2dp(cb)).await;
-            });
+            queue_message(&tx, Message::A2dp(cb));
         }),
     }
 }
@@ -428,10 +1062,15 @@ fn get_a2dp_dispatcher(tx: Sender<Message>) -> A2dpCallbacksDispatcher {
 fn get_avrcp_dispatcher(tx: Sender<Message>) -> AvrcpCallbacksDispatcher {
     AvrcpCallbacksDispatcher {
         dispatch: Box::new(move |cb| {
-            let txl = tx.clone();
-            topstack::get_runtime().spawn(async move {
-                let _ = txl.send(Message::Avrcp(cb)).await;
-            });
+            queue_message(&tx, Message::Avrcp(cb));
+        }),
+    }
+}
+
+fn get_hearing_aid_dispatcher(tx: Sender<Message>) -> HearingAidCallbacksDispatcher {
+    HearingAidCallbacksDispatcher {
+        dispatch: Box::new(move |cb| {
+            queue_message(&tx, Message::HearingAid(cb));
         }),
     }
 }
@@ -439,19 +1078,18 @@ fn get_avrcp_dispatcher(tx: Sender<Message>) -> AvrcpCallbacksDispatcher {
 fn get_hfp_dispatcher(tx: Sender<Message>) -> HfpCallbacksDispatcher {
     HfpCallbacksDispatcher {
         dispatch: Box::new(move |cb| {
-            let txl = tx.clone();
-            topstack::get_runtime().spawn(async move {
-                let _ = txl.send(Message::Hfp(cb)).await;
-            });
+            queue_message(&tx, Message::Hfp(cb));
         }),
     }
 }
 
 impl IBluetoothMedia for BluetoothMedia {
-    fn register_callback(&mut self, callback: Box<dyn IBluetoothMediaCallback + Send>) -> bool {
-        self.callback_last_id += 1;
-        self.callbacks.lock().unwrap().push((self.callback_last_id, callback));
-        true
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothMediaCallback + Send>) -> u32 {
+        self.callbacks.lock().unwrap().add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
     }
 
     fn initialize(&mut self) -> bool {
@@ -475,13 +1113,27 @@ impl IBluetoothMedia for BluetoothMedia {
         self.hfp = Some(Hfp::new(&self.intf.lock().unwrap()));
         self.hfp.as_mut().unwrap().initialize(hfp_dispatcher);
 
+        // Hearing Aid
+        let hearing_aid_dispatcher = get_hearing_aid_dispatcher(self.tx.clone());
+        self.hearing_aid = Some(HearingAid::new(&self.intf.lock().unwrap()));
+        self.hearing_aid.as_mut().unwrap().initialize(hearing_aid_dispatcher);
+
         true
     }
 
     fn connect(&mut self, device: String) {
         if let Some(addr) = RawAddress::from_string(device.clone()) {
-            self.a2dp.as_mut().unwrap().connect(addr);
-            self.hfp.as_mut().unwrap().connect(addr);
+            if self.is_profile_allowed(addr, Profile::A2dpSink) {
+                self.a2dp.as_mut().unwrap().connect(addr);
+            }
+            if self.is_profile_allowed(addr, Profile::Hfp) {
+                self.hfp.as_mut().unwrap().connect(addr);
+            }
+            if self.is_profile_allowed(addr, Profile::HearingAid)
+                && self.adapter_has_remote_uuid(addr, HEARING_AID)
+            {
+                self.hearing_aid.as_mut().unwrap().connect(addr);
+            }
         } else {
             warn!("Invalid device string {}", device);
         }
@@ -492,17 +1144,39 @@ impl IBluetoothMedia for BluetoothMedia {
     }
 
     fn set_active_device(&mut self, device: String) {
-        if let Some(addr) = RawAddress::from_string(device.clone()) {
-            self.a2dp.as_mut().unwrap().set_active_device(addr);
-        } else {
-            warn!("Invalid device string {}", device);
+        let addr = match RawAddress::from_string(device.clone()) {
+            Some(addr) => addr,
+            None => {
+                warn!("Invalid device string {}", device);
+                return;
+            }
+        };
+
+        let categories: Vec<AudioCategory> = [AudioCategory::A2dp, AudioCategory::Hfp]
+            .iter()
+            .cloned()
+            .filter(|category| self.is_connected_for(*category, &addr))
+            .collect();
+        if categories.is_empty() {
+            warn!("[{}]: Can't make a device that isn't connected active.", device);
+        }
+
+        for category in categories {
+            self.activate(category, addr);
         }
     }
 
+    fn get_active_device(&self, category: AudioCategory) -> String {
+        self.active_devices.get(&category).map_or(String::from(""), |addr| addr.to_string())
+    }
+
     fn disconnect(&mut self, device: String) {
         if let Some(addr) = RawAddress::from_string(device.clone()) {
             self.a2dp.as_mut().unwrap().disconnect(addr);
             self.hfp.as_mut().unwrap().disconnect(addr);
+            if self.hearing_aid_states.contains_key(&addr) {
+                self.hearing_aid.as_mut().unwrap().disconnect(addr);
+            }
         } else {
             warn!("Invalid device string {}", device);
         }
@@ -564,6 +1238,146 @@ impl IBluetoothMedia for BluetoothMedia {
         }
     }
 
+    fn set_sco_codec_override(&mut self, device: String, codec: HfpCodecId) -> bool {
+        if !self.hfp_wideband_speech && codec != HfpCodecId::Cvsd {
+            warn!("[{}]: Only CVSD is allowed with wideband speech disabled.", device);
+            return false;
+        }
+        if let Some(addr) = RawAddress::from_string(device.clone()) {
+            match self.hfp.as_mut().unwrap().set_codec_override(addr, codec) {
+                0 => true,
+                x => {
+                    warn!("[{}]: Failed to force codec {:?}: {}", device, codec, x);
+                    false
+                }
+            }
+        } else {
+            warn!("Invalid device string {}", device);
+            false
+        }
+    }
+
+    fn get_sco_codec(&mut self, device: String) -> HfpCodecId {
+        match RawAddress::from_string(device) {
+            Some(addr) => *self.hfp_codecs.get(&addr).unwrap_or(&HfpCodecId::None),
+            None => HfpCodecId::None,
+        }
+    }
+
+    fn set_hfp_wideband_speech(&mut self, enabled: bool) {
+        if self.hfp_wideband_speech == enabled {
+            return;
+        }
+        self.hfp_wideband_speech = enabled;
+
+        // Takes effect from the next SCO connection.
+        let codec = if enabled { HfpCodecId::None } else { HfpCodecId::Cvsd };
+        for addr in self.slc_connected_devices() {
+            self.hfp.as_mut().unwrap().set_codec_override(addr, codec);
+        }
+        self.for_all_callbacks(|callback| {
+            callback.on_hfp_wideband_speech_changed(enabled);
+        });
+    }
+
+    fn get_hfp_wideband_speech(&self) -> bool {
+        self.hfp_wideband_speech
+    }
+
+    fn set_hfp_inband_ringtone(&mut self, enabled: bool) {
+        if self.hfp_inband_ringtone == enabled {
+            return;
+        }
+        self.hfp_inband_ringtone = enabled;
+
+        for addr in self.slc_connected_devices() {
+            self.hfp.as_mut().unwrap().set_inband_ringtone(addr, enabled);
+        }
+        self.for_all_callbacks(|callback| {
+            callback.on_hfp_inband_ringtone_changed(enabled);
+        });
+    }
+
+    fn get_hfp_inband_ringtone(&self) -> bool {
+        self.hfp_inband_ringtone
+    }
+
+    fn set_hfp_voice_recognition(&mut self, device: String, active: bool) -> bool {
+        let addr = match RawAddress::from_string(device.clone()) {
+            Some(addr) => addr,
+            None => {
+                warn!("Invalid device string {}", device);
+                return false;
+            }
+        };
+
+        let hfp = self.hfp.as_mut().unwrap();
+        let status = if active {
+            hfp.start_voice_recognition(addr)
+        } else {
+            hfp.stop_voice_recognition(addr)
+        };
+        if status != 0 {
+            warn!("[{}]: Failed to set voice recognition to {}: {}", device, active, status);
+            return false;
+        }
+
+        self.for_all_callbacks(|callback| {
+            callback.on_hfp_voice_recognition_changed(device.clone(), active);
+        });
+        true
+    }
+
+    fn phone_state_changed(
+        &mut self,
+        num_active: u32,
+        num_held: u32,
+        call_setup_state: BthfCallState,
+        number: String,
+    ) {
+        self.telephony.reported = true;
+        self.telephony.num_active = num_active;
+        self.telephony.num_held = num_held;
+        self.telephony.call_setup_state = call_setup_state;
+
+        for addr in self.slc_connected_devices() {
+            self.hfp.as_mut().unwrap().phone_state_change(
+                addr,
+                num_active,
+                num_held,
+                call_setup_state,
+                &number,
+            );
+        }
+    }
+
+    fn clcc_response(&mut self, calls: Vec<CallInfo>) {
+        self.telephony.reported = true;
+        self.telephony.calls = calls;
+    }
+
+    fn set_hfp_operator_name(&mut self, name: String) {
+        self.telephony.operator_name = name;
+    }
+
+    fn set_hfp_signal_strength(&mut self, signal: u32) -> bool {
+        if signal > HFP_INDICATOR_MAX {
+            return false;
+        }
+        self.telephony.status.signal = signal;
+        self.notify_hfp_device_status();
+        true
+    }
+
+    fn set_hfp_battery_level(&mut self, level: u32) -> bool {
+        if level > HFP_INDICATOR_MAX {
+            return false;
+        }
+        self.telephony.status.battery = level;
+        self.notify_hfp_device_status();
+        true
+    }
+
     fn get_presentation_position(&mut self) -> PresentationPosition {
         let position = self.a2dp.as_mut().unwrap().get_presentation_position();
         PresentationPosition {
@@ -573,4 +1387,103 @@ impl IBluetoothMedia for BluetoothMedia {
             data_position_nsec: position.data_position_nsec,
         }
     }
+
+    fn set_hearing_aid_volume(&mut self, volume: i32) {
+        match i8::try_from(volume) {
+            Ok(val) if val <= 0 => self.hearing_aid.as_mut().unwrap().set_volume(val),
+            _ => warn!("Invalid hearing aid volume {}", volume),
+        };
+    }
+
+    fn start_hearing_aid_audio_request(&mut self) -> bool {
+        self.hearing_aid.as_mut().unwrap().start_audio_request()
+    }
+
+    fn stop_hearing_aid_audio_request(&mut self) -> bool {
+        self.hearing_aid.as_mut().unwrap().stop_audio_request()
+    }
+
+    fn get_hearing_aid_audio_config(&mut self) -> HearingAidAudioConfig {
+        self.hearing_aid.as_ref().unwrap().get_audio_config()
+    }
+
+    fn get_hearing_aid_presentation_position(&mut self) -> PresentationPosition {
+        self.hearing_aid.as_ref().unwrap().get_presentation_position()
+    }
+
+    fn is_a2dp_offload_supported(&mut self) -> bool {
+        self.a2dp.as_ref().map_or(false, |a2dp| a2dp.is_offload_supported())
+    }
+
+    fn set_a2dp_codec_priorities(&mut self, codecs: Vec<i32>) -> bool {
+        let mut priorities = vec![];
+        for (i, codec) in codecs.iter().enumerate() {
+            if !is_source_codec(*codec) || codecs[..i].contains(codec) {
+                warn!("Invalid A2DP codec priorities {:?}", codecs);
+                return false;
+            }
+            priorities.push(A2dpCodecConfig {
+                codec_type: *codec,
+                codec_priority: A2dpCodecPriority::Highest as i32 - i as i32,
+                ..Default::default()
+            });
+        }
+        self.a2dp_codec_priorities = priorities;
+
+        for (addr, state) in self.a2dp_states.iter() {
+            if *state == BtavConnectionState::Connected {
+                self.apply_a2dp_codec_priorities(*addr);
+            }
+        }
+        true
+    }
+
+    fn set_a2dp_codec_config(&mut self, device: String, config: A2dpCodecConfig) -> bool {
+        let addr = match RawAddress::from_string(device.clone()) {
+            Some(addr) => addr,
+            None => {
+                warn!("Invalid device string {}", device);
+                return false;
+            }
+        };
+
+        if !is_source_codec(config.codec_type)
+            || !A2dpCodecSampleRate::validate_bits(config.sample_rate)
+            || !A2dpCodecBitsPerSample::validate_bits(config.bits_per_sample)
+            || !A2dpCodecChannelMode::validate_bits(config.channel_mode)
+        {
+            warn!("[{}]: Invalid A2DP codec config {:?}", device, config);
+            return false;
+        }
+
+        if !self.is_connected_for(AudioCategory::A2dp, &addr) {
+            warn!("[{}]: Can't configure the codec of a device that isn't connected.", device);
+            return false;
+        }
+
+        let config =
+            A2dpCodecConfig { codec_priority: A2dpCodecPriority::Highest as i32, ..config };
+        match self.a2dp.as_ref().unwrap().config_codec(addr, vec![config]) {
+            0 => true,
+            x => {
+                warn!("[{}]: Failed to configure A2DP codec: {}", device, x);
+                false
+            }
+        }
+    }
+
+    fn set_now_playing_list(&mut self, current_media_id: String, songs: Vec<AvrcpSongInfo>) {
+        self.avrcp.as_mut().unwrap().set_now_playing_list(current_media_id, songs);
+    }
+
+    fn set_media_players(&mut self, addressed_player: u16, players: Vec<AvrcpMediaPlayerInfo>) {
+        if !players.iter().any(|player| player.id == addressed_player) {
+            warn!("Addressed player {} is not one of the media players", addressed_player);
+        }
+        self.avrcp.as_mut().unwrap().set_media_players(addressed_player, players);
+    }
+
+    fn set_folder_items(&mut self, media_id: String, items: Vec<AvrcpListItem>) {
+        self.avrcp.as_mut().unwrap().set_folder_items(media_id, items);
+    }
 }
@@ -0,0 +1,203 @@
+//! Read-only view of the adapter (IBluetoothObserver).
+//!
+//! Status bar widgets and metrics collectors only need to know what the adapter is doing. This
+//! interface gives them the queries and events of `IBluetooth` without anything that changes the
+//! adapter or answers for it, so a D-Bus policy can let unprivileged processes reach it and keep
+//! them away from the rest.
+
+use bt_topshim::btif::{BtDeviceType, Uuid128Bit};
+use dbus_macros::dbus_interface;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use crate::bluetooth::{
+    AdapterState, Bluetooth, BluetoothDevice, IBluetooth, IBluetoothCallback,
+    IBluetoothConnectionCallback,
+};
+use crate::uuid::Profile;
+
+/// Defines the observer API.
+#[dbus_interface(bluetooth_observer_dbus, "org.chromium.bluetooth.BluetoothObserver")]
+pub trait IBluetoothObserver {
+    /// Adds a callback to be notified of changes to the adapter. It gets the same events as the
+    /// callbacks of `IBluetooth`, pairing requests included, though nothing here can answer them.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) -> u32;
+
+    /// Removes a callback added with `register_callback`. Callbacks added through other
+    /// interfaces can't be removed.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Adds a callback to be notified of devices connecting and disconnecting.
+    #[dbus_method("RegisterConnectionCallback")]
+    fn register_connection_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothConnectionCallback + Send>,
+    ) -> u32;
+
+    /// Removes a callback added with `register_connection_callback`.
+    #[dbus_method("UnregisterConnectionCallback")]
+    fn unregister_connection_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Sends the current adapter state to a callback added with `register_callback`, as if it
+    /// just changed.
+    #[dbus_method("ReplayAdapterState")]
+    fn replay_adapter_state(&self, callback_id: u32) -> bool;
+
+    #[dbus_method("GetAdapterState")]
+    fn get_adapter_state(&self) -> AdapterState;
+
+    #[dbus_method("GetAddress")]
+    fn get_address(&self) -> String;
+
+    #[dbus_method("GetName")]
+    fn get_name(&self) -> String;
+
+    #[dbus_method("GetDiscoverable")]
+    fn get_discoverable(&self) -> bool;
+
+    #[dbus_method("IsDiscovering")]
+    fn is_discovering(&self) -> bool;
+
+    #[dbus_method("GetBondedDevices")]
+    fn get_bonded_devices(&self) -> Vec<BluetoothDevice>;
+
+    #[dbus_method("GetBondState")]
+    fn get_bond_state(&self, device: BluetoothDevice) -> u32;
+
+    #[dbus_method("GetConnectedDevices")]
+    fn get_connected_devices(&self) -> Vec<BluetoothDevice>;
+
+    #[dbus_method("GetConnectionState")]
+    fn get_connection_state(&self, device: BluetoothDevice) -> u32;
+
+    #[dbus_method("GetProfileConnectionState")]
+    fn get_profile_connection_state(&self, profile: Profile) -> u32;
+
+    #[dbus_method("GetRemoteName")]
+    fn get_remote_name(&self, device: BluetoothDevice) -> String;
+
+    #[dbus_method("GetRemoteAlias")]
+    fn get_remote_alias(&self, device: BluetoothDevice) -> String;
+
+    #[dbus_method("GetRemoteType")]
+    fn get_remote_type(&self, device: BluetoothDevice) -> BtDeviceType;
+
+    #[dbus_method("GetRemoteClass")]
+    fn get_remote_class(&self, device: BluetoothDevice) -> u32;
+
+    #[dbus_method("GetRemoteUuids")]
+    fn get_remote_uuids(&self, device: BluetoothDevice) -> Vec<Uuid128Bit>;
+}
+
+/// Implementation of the observer API on top of the adapter.
+pub struct BluetoothObserver {
+    adapter: Arc<Mutex<Box<Bluetooth>>>,
+    // IDs of the callbacks registered here, the only ones observers may remove or replay to. The
+    // adapter never reuses IDs, so the ones of callbacks that went away since can stay.
+    callbacks: HashSet<u32>,
+    connection_callbacks: HashSet<u32>,
+}
+
+impl BluetoothObserver {
+    pub fn new(adapter: Arc<Mutex<Box<Bluetooth>>>) -> BluetoothObserver {
+        BluetoothObserver {
+            adapter,
+            callbacks: HashSet::new(),
+            connection_callbacks: HashSet::new(),
+        }
+    }
+}
+
+impl IBluetoothObserver for BluetoothObserver {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) -> u32 {
+        let id = self.adapter.lock().unwrap().register_callback(callback);
+        self.callbacks.insert(id);
+        id
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.callbacks.remove(&callback_id)
+            && self.adapter.lock().unwrap().unregister_callback(callback_id)
+    }
+
+    fn register_connection_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothConnectionCallback + Send>,
+    ) -> u32 {
+        let id = self.adapter.lock().unwrap().register_connection_callback(callback);
+        self.connection_callbacks.insert(id);
+        id
+    }
+
+    fn unregister_connection_callback(&mut self, callback_id: u32) -> bool {
+        self.connection_callbacks.remove(&callback_id)
+            && self.adapter.lock().unwrap().unregister_connection_callback(callback_id)
+    }
+
+    fn replay_adapter_state(&self, callback_id: u32) -> bool {
+        self.callbacks.contains(&callback_id)
+            && self.adapter.lock().unwrap().replay_adapter_state(callback_id)
+    }
+
+    fn get_adapter_state(&self) -> AdapterState {
+        self.adapter.lock().unwrap().get_adapter_state()
+    }
+
+    fn get_address(&self) -> String {
+        self.adapter.lock().unwrap().get_address()
+    }
+
+    fn get_name(&self) -> String {
+        self.adapter.lock().unwrap().get_name()
+    }
+
+    fn get_discoverable(&self) -> bool {
+        self.adapter.lock().unwrap().get_discoverable()
+    }
+
+    fn is_discovering(&self) -> bool {
+        self.adapter.lock().unwrap().is_discovering()
+    }
+
+    fn get_bonded_devices(&self) -> Vec<BluetoothDevice> {
+        self.adapter.lock().unwrap().get_bonded_devices()
+    }
+
+    fn get_bond_state(&self, device: BluetoothDevice) -> u32 {
+        self.adapter.lock().unwrap().get_bond_state(device)
+    }
+
+    fn get_connected_devices(&self) -> Vec<BluetoothDevice> {
+        self.adapter.lock().unwrap().get_connected_devices()
+    }
+
+    fn get_connection_state(&self, device: BluetoothDevice) -> u32 {
+        self.adapter.lock().unwrap().get_connection_state(device)
+    }
+
+    fn get_profile_connection_state(&self, profile: Profile) -> u32 {
+        self.adapter.lock().unwrap().get_profile_connection_state(profile)
+    }
+
+    fn get_remote_name(&self, device: BluetoothDevice) -> String {
+        self.adapter.lock().unwrap().get_remote_name(device)
+    }
+
+    fn get_remote_alias(&self, device: BluetoothDevice) -> String {
+        self.adapter.lock().unwrap().get_remote_alias(device)
+    }
+
+    fn get_remote_type(&self, device: BluetoothDevice) -> BtDeviceType {
+        self.adapter.lock().unwrap().get_remote_type(device)
+    }
+
+    fn get_remote_class(&self, device: BluetoothDevice) -> u32 {
+        self.adapter.lock().unwrap().get_remote_class(device)
+    }
+
+    fn get_remote_uuids(&self, device: BluetoothDevice) -> Vec<Uuid128Bit> {
+        self.adapter.lock().unwrap().get_remote_uuids(device)
+    }
+}
@@ -0,0 +1,673 @@
+//! Object Push Profile (IBluetoothOpp).
+//!
+//! Files are pushed over OBEX on an RFCOMM socket. The server side listens on the OPP UUID, which
+//! makes libbluetooth publish the Object Push service record, and asks the registered callbacks
+//! to confirm every incoming object before its body is received.
+
+use bt_topshim::btif::{BluetoothInterface, BtStatus, RawAddress, Uuid};
+use bt_topshim::profiles::socket::{self, BtSocket, SocketType, SOCK_FLAG_ENCRYPT};
+use bt_topshim::topstack;
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::callbacks::Callbacks;
+use crate::obex::{self, Header, ObexClient, ObexError, Packet};
+use crate::uuid::{Profile, UuidHelper, OBEX_OBJECT_PUSH};
+use crate::{Message, RPCProxy};
+
+/// Where accepted incoming objects are stored.
+const DOWNLOAD_DIR: &str = "/var/lib/bluetooth/opp";
+
+/// Name of the Object Push service record.
+const SERVICE_NAME: &str = "OBEX Object Push";
+
+/// How long an incoming object waits for `accept_transfer` before it is rejected.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// MIME type used when the type of an outgoing file can't be guessed from its extension.
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Defines the Object Push API.
+#[dbus_interface(bluetooth_opp_dbus, "org.chromium.bluetooth.BluetoothOpp")]
+pub trait IBluetoothOpp {
+    /// Adds an observer to transfer events.
+    ///
+    /// Returns an id identifying the callback.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothOppCallback + Send>) -> u32;
+
+    /// Removes an observer to transfer events.
+    ///
+    /// Returns true if the callback can be removed, false if `callback_id` is not recognized.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Pushes the file at `path` to `device`.
+    ///
+    /// Returns the id of the transfer, or -1 if it can't be started.
+    #[dbus_method("SendFile")]
+    fn send_file(&mut self, device: BluetoothDevice, path: String) -> i32;
+
+    /// Accepts or rejects an incoming transfer reported by `on_incoming_transfer`.
+    ///
+    /// Returns false if the transfer isn't waiting for confirmation.
+    #[dbus_method("AcceptTransfer")]
+    fn accept_transfer(&mut self, transfer_id: i32, accept: bool) -> bool;
+
+    /// Cancels an ongoing transfer in either direction.
+    ///
+    /// Returns false if `transfer_id` is not recognized.
+    #[dbus_method("CancelTransfer")]
+    fn cancel_transfer(&mut self, transfer_id: i32) -> bool;
+
+    /// Returns all transfers that haven't completed yet.
+    #[dbus_method("GetTransfers")]
+    fn get_transfers(&self) -> Vec<OppTransfer>;
+}
+
+/// Object Push events.
+pub trait IBluetoothOppCallback: RPCProxy {
+    /// Triggered when a remote device wants to push an object. The transfer starts once it is
+    /// confirmed with `accept_transfer`.
+    fn on_incoming_transfer(&self, transfer: OppTransfer);
+
+    /// Triggered as the body of an object is sent or received.
+    fn on_transfer_progress(&self, transfer_id: i32, bytes_transferred: u64, total_bytes: u64);
+
+    /// Triggered when a transfer ends, successfully or not.
+    fn on_transfer_complete(&self, transfer_id: i32, status: OppTransferStatus);
+}
+
+/// Describes a transfer in either direction.
+#[derive(Clone, Debug, Default)]
+pub struct OppTransfer {
+    pub id: i32,
+    pub device: BluetoothDevice,
+    /// True if the object is pushed to `device`.
+    pub outgoing: bool,
+    /// Name of the object as sent over OBEX.
+    pub file_name: String,
+    /// Local file the object is read from or written to.
+    pub file_path: String,
+    pub mime_type: String,
+    /// Size of the object, or 0 if the remote device didn't tell.
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum OppTransferStatus {
+    Success = 0,
+    /// The receiving side didn't accept the object.
+    Rejected,
+    Cancelled,
+    Failed,
+}
+
+/// Actions that `BluetoothOpp` takes on behalf of the stack and its transfer tasks.
+pub enum OppActions {
+    /// Starts listening for incoming pushes once the adapter is enabled.
+    StartServer,
+    /// The listening socket was closed, usually because the adapter was disabled.
+    ServerStopped,
+    /// A remote device connected to the server socket.
+    Accepted(RawAddress, RawFd),
+    /// The remote device of an incoming transfer announced the object. Carries the name, where
+    /// it will be stored, the MIME type and the size of the object, and where to send the
+    /// user's decision.
+    IncomingObject(i32, String, PathBuf, String, u64, oneshot::Sender<bool>),
+    Progress(i32, u64, u64),
+    Complete(i32, OppTransferStatus),
+}
+
+struct TransferState {
+    info: OppTransfer,
+    cancelled: Arc<AtomicBool>,
+    /// Set while an incoming transfer waits for confirmation.
+    confirmation: Option<oneshot::Sender<bool>>,
+}
+
+/// Implementation of the Object Push API.
+pub struct BluetoothOpp {
+    intf: Arc<Mutex<BluetoothInterface>>,
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    socket: Option<BtSocket>,
+    server_running: bool,
+    callbacks: Callbacks<dyn IBluetoothOppCallback + Send>,
+    transfers: HashMap<i32, TransferState>,
+    next_transfer_id: i32,
+}
+
+impl BluetoothOpp {
+    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothOpp {
+        BluetoothOpp {
+            intf,
+            tx: tx.clone(),
+            adapter: None,
+            socket: None,
+            server_running: false,
+            callbacks: Callbacks::new(tx, Message::OppCallbackDisconnected),
+            transfers: HashMap::new(),
+            next_transfer_id: 1,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_opp_actions(&mut self, action: OppActions) {
+        match action {
+            OppActions::StartServer => self.start_server(),
+
+            OppActions::ServerStopped => {
+                debug!("OPP server socket closed");
+                self.server_running = false;
+            }
+
+            OppActions::Accepted(addr, fd) => self.on_accepted(addr, fd),
+
+            OppActions::IncomingObject(id, name, path, mime_type, total_bytes, confirmation) => {
+                let transfer = match self.transfers.get_mut(&id) {
+                    Some(transfer) => transfer,
+                    None => return,
+                };
+
+                transfer.info.file_name = name;
+                transfer.info.file_path = path.to_string_lossy().to_string();
+                transfer.info.mime_type = mime_type;
+                transfer.info.total_bytes = total_bytes;
+                transfer.confirmation = Some(confirmation);
+
+                let info = transfer.info.clone();
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_incoming_transfer(info.clone());
+                });
+            }
+
+            OppActions::Progress(id, bytes_transferred, total_bytes) => {
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_transfer_progress(id, bytes_transferred, total_bytes);
+                });
+            }
+
+            OppActions::Complete(id, status) => {
+                if self.transfers.remove(&id).is_none() {
+                    return;
+                }
+
+                info!("OPP transfer {} completed: {:?}", id, status);
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_transfer_complete(id, status);
+                });
+            }
+        }
+    }
+
+    fn get_socket(&mut self) -> &BtSocket {
+        if self.socket.is_none() {
+            self.socket = Some(BtSocket::new(&self.intf.lock().unwrap()));
+        }
+
+        self.socket.as_ref().unwrap()
+    }
+
+    fn is_allowed(&self, addr: &RawAddress) -> bool {
+        match &self.adapter {
+            Some(adapter) => adapter
+                .lock()
+                .unwrap()
+                .is_profile_enabled_for_device(addr, &Profile::ObexObjectPush),
+            None => false,
+        }
+    }
+
+    fn opp_uuid() -> Uuid {
+        Uuid { uu: UuidHelper::from_string(OBEX_OBJECT_PUSH).unwrap() }
+    }
+
+    fn start_server(&mut self) {
+        if self.server_running {
+            return;
+        }
+
+        let (status, fd) = self.get_socket().listen(
+            SocketType::Rfcomm,
+            String::from(SERVICE_NAME),
+            Some(BluetoothOpp::opp_uuid()),
+            0,
+            SOCK_FLAG_ENCRYPT,
+        );
+
+        let fd = match (&status, fd) {
+            (BtStatus::Success, Some(fd)) => fd,
+            _ => {
+                warn!("Failed to listen for OPP connections: {:?}", status);
+                return;
+            }
+        };
+
+        self.server_running = true;
+        let tx = self.tx.clone();
+
+        // Accepting blocks on the listening socket, so it gets its own thread for its lifetime.
+        std::thread::spawn(move || {
+            // Owns the fd so that it is closed when the thread exits.
+            let listener = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+
+            let result: std::io::Result<()> = socket::read_channel(fd).and_then(|channel| {
+                info!("OPP server listening on channel {}", channel);
+                loop {
+                    let (signal, accepted) = socket::read_connection_complete(fd)?;
+                    match accepted {
+                        Some(accepted) => {
+                            let _ = tx.blocking_send(Message::Opp(OppActions::Accepted(
+                                signal.addr,
+                                accepted,
+                            )));
+                        }
+                        None => warn!(
+                            "OPP connection from {} without a socket",
+                            signal.addr.to_string()
+                        ),
+                    }
+                }
+            });
+
+            debug!("OPP server stopped: {:?}", result);
+            drop(listener);
+            let _ = tx.blocking_send(Message::Opp(OppActions::ServerStopped));
+        });
+    }
+
+    fn new_transfer(&mut self, info: OppTransfer) -> (i32, Arc<AtomicBool>) {
+        let id = self.next_transfer_id;
+        self.next_transfer_id += 1;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.transfers.insert(
+            id,
+            TransferState {
+                info: OppTransfer { id, ..info },
+                cancelled: cancelled.clone(),
+                confirmation: None,
+            },
+        );
+
+        (id, cancelled)
+    }
+
+    fn get_device(&self, addr: &RawAddress) -> BluetoothDevice {
+        let device = BluetoothDevice::new(*addr, String::from(""));
+        match &self.adapter {
+            Some(adapter) => {
+                let name = adapter.lock().unwrap().get_remote_name(device.clone());
+                BluetoothDevice::new(device.address, name)
+            }
+            None => device,
+        }
+    }
+
+    fn on_accepted(&mut self, addr: RawAddress, fd: RawFd) {
+        // Takes ownership of the fd so it is closed when the session ends.
+        let stream = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+
+        if !self.is_allowed(&addr) {
+            info!("[{}]: OPP is not allowed.", addr.to_string());
+            return;
+        }
+
+        let device = self.get_device(&addr);
+        let (id, cancelled) =
+            self.new_transfer(OppTransfer { device, outgoing: false, ..Default::default() });
+
+        let tx = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            let status = match receive_object(stream, id, cancelled, tx.clone()).await {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!("OPP transfer {} failed: {}", id, e);
+                    OppTransferStatus::Failed
+                }
+            };
+            let _ = tx.send(Message::Opp(OppActions::Complete(id, status))).await;
+        });
+    }
+}
+
+impl IBluetoothOpp for BluetoothOpp {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothOppCallback + Send>) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn send_file(&mut self, device: BluetoothDevice, path: String) -> i32 {
+        let addr = device.address;
+
+        if !self.is_allowed(&addr) {
+            info!("[{}]: OPP is not allowed.", device.address);
+            return -1;
+        }
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Can't open {}: {}", path, e);
+                return -1;
+            }
+        };
+
+        let total_bytes = match file.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => {
+                warn!("{} is not a regular file", path);
+                return -1;
+            }
+        };
+
+        let (status, fd) = self.get_socket().connect(
+            addr,
+            SocketType::Rfcomm,
+            Some(BluetoothOpp::opp_uuid()),
+            0,
+            SOCK_FLAG_ENCRYPT,
+        );
+
+        let fd = match (&status, fd) {
+            (BtStatus::Success, Some(fd)) => fd,
+            _ => {
+                warn!("[{}]: Failed to connect OPP: {:?}", device.address, status);
+                return -1;
+            }
+        };
+
+        let file_name = Path::new(&path)
+            .file_name()
+            .map_or(String::from(""), |name| name.to_string_lossy().to_string());
+        let mime_type = guess_mime_type(&file_name);
+        let (id, cancelled) = self.new_transfer(OppTransfer {
+            device,
+            outgoing: true,
+            file_name,
+            file_path: path,
+            mime_type,
+            total_bytes,
+            ..Default::default()
+        });
+
+        let info = self.transfers[&id].info.clone();
+        let tx = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            let status = match push_object(fd, file, info, cancelled, tx.clone()).await {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!("OPP transfer {} failed: {}", id, e);
+                    OppTransferStatus::Failed
+                }
+            };
+            let _ = tx.send(Message::Opp(OppActions::Complete(id, status))).await;
+        });
+
+        id
+    }
+
+    fn accept_transfer(&mut self, transfer_id: i32, accept: bool) -> bool {
+        match self.transfers.get_mut(&transfer_id).and_then(|t| t.confirmation.take()) {
+            Some(confirmation) => confirmation.send(accept).is_ok(),
+            None => false,
+        }
+    }
+
+    fn cancel_transfer(&mut self, transfer_id: i32) -> bool {
+        match self.transfers.get_mut(&transfer_id) {
+            Some(transfer) => {
+                transfer.cancelled.store(true, Ordering::Relaxed);
+                // A transfer waiting for confirmation is cancelled by rejecting it.
+                if let Some(confirmation) = transfer.confirmation.take() {
+                    let _ = confirmation.send(false);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_transfers(&self) -> Vec<OppTransfer> {
+        self.transfers.values().map(|t| t.info.clone()).collect()
+    }
+}
+
+async fn push_object(
+    fd: RawFd,
+    mut file: File,
+    info: OppTransfer,
+    cancelled: Arc<AtomicBool>,
+    tx: Sender<Message>,
+) -> Result<OppTransferStatus, ObexError> {
+    let mut client = ObexClient::new(obex::wait_for_connection(fd).await?);
+    client.connect(None).await?;
+
+    let mut request = client
+        .start_request(obex::OP_PUT)
+        .with_header(Header::Name(info.file_name.clone()))
+        .with_header(Header::Type(info.mime_type.clone()))
+        .with_header(Header::Length(info.total_bytes.min(u32::MAX as u64) as u32));
+
+    let mut sent: u64 = 0;
+    let status = loop {
+        if cancelled.load(Ordering::Relaxed) {
+            client.abort().await?;
+            break OppTransferStatus::Cancelled;
+        }
+
+        // The first packet also carries the headers describing the object, leaving less room.
+        let mut buf = vec![0u8; client.max_body_len(&request)];
+        let len = file.read(&mut buf)?;
+
+        request = if len == 0 {
+            Packet { code: obex::OP_PUT | obex::FINAL_BIT, ..request }
+                .with_header(Header::EndOfBody(vec![]))
+        } else {
+            request.with_header(Header::Body(buf[..len].to_vec()))
+        };
+
+        let response = client.send(&request).await?;
+        request = Packet::new(obex::OP_PUT);
+
+        match response.code {
+            obex::RSP_CONTINUE if len > 0 => {
+                sent += len as u64;
+                let _ = tx
+                    .send(Message::Opp(OppActions::Progress(info.id, sent, info.total_bytes)))
+                    .await;
+            }
+            obex::RSP_SUCCESS if len == 0 => break OppTransferStatus::Success,
+            obex::RSP_FORBIDDEN | obex::RSP_NOT_ACCEPTABLE | obex::RSP_UNAUTHORIZED => {
+                break OppTransferStatus::Rejected
+            }
+            code => return Err(ObexError::Response(code)),
+        }
+    };
+
+    let _ = client.disconnect().await;
+    Ok(status)
+}
+
+async fn receive_object(
+    stream: std::os::unix::net::UnixStream,
+    id: i32,
+    cancelled: Arc<AtomicBool>,
+    tx: Sender<Message>,
+) -> Result<OppTransferStatus, ObexError> {
+    let mut stream = obex::into_async_stream(stream)?;
+
+    let request = obex::read_packet(&mut stream, 4).await?;
+    if request.code != obex::OP_CONNECT {
+        obex::write_packet(&mut stream, &Packet::new(obex::RSP_BAD_REQUEST)).await?;
+        return Err(ObexError::InvalidPacket(String::from("expected CONNECT")));
+    }
+    obex::write_packet(
+        &mut stream,
+        &Packet::connect(obex::RSP_SUCCESS, obex::DEFAULT_MAX_PACKET_LEN),
+    )
+    .await?;
+
+    // OPP pushes exactly one object per PUT. The first packet names it.
+    let first = obex::read_packet(&mut stream, 0).await?;
+    if first.code & !obex::FINAL_BIT != obex::OP_PUT {
+        obex::write_packet(&mut stream, &Packet::new(obex::RSP_BAD_REQUEST)).await?;
+        return Err(ObexError::InvalidPacket(String::from("expected PUT")));
+    }
+
+    let name = first.name().unwrap_or("").to_string();
+    let mime_type = first.mime_type().unwrap_or("").to_string();
+    let total_bytes = first.length().unwrap_or(0) as u64;
+    let path = unique_download_path(&name);
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let _ = tx
+        .send(Message::Opp(OppActions::IncomingObject(
+            id,
+            name,
+            path.clone(),
+            mime_type,
+            total_bytes,
+            reply_tx,
+        )))
+        .await;
+
+    let accepted = match tokio::time::timeout(CONFIRMATION_TIMEOUT, reply_rx).await {
+        Ok(Ok(accepted)) => accepted,
+        _ => false,
+    };
+    if !accepted {
+        obex::write_packet(&mut stream, &Packet::new(obex::RSP_FORBIDDEN)).await?;
+        return Ok(if cancelled.load(Ordering::Relaxed) {
+            OppTransferStatus::Cancelled
+        } else {
+            OppTransferStatus::Rejected
+        });
+    }
+
+    std::fs::create_dir_all(DOWNLOAD_DIR)?;
+    let mut file = File::create(&path)?;
+
+    let mut packet = first;
+    let mut received: u64 = 0;
+    loop {
+        let (body, end) = packet.body();
+        file.write_all(&body)?;
+        received += body.len() as u64;
+        if !body.is_empty() {
+            let _ = tx.send(Message::Opp(OppActions::Progress(id, received, total_bytes))).await;
+        }
+
+        if packet.code & obex::FINAL_BIT != 0 || end {
+            obex::write_packet(&mut stream, &Packet::new(obex::RSP_SUCCESS)).await?;
+            break;
+        }
+
+        if cancelled.load(Ordering::Relaxed) {
+            obex::write_packet(&mut stream, &Packet::new(obex::RSP_FORBIDDEN)).await?;
+            drop(file);
+            let _ = std::fs::remove_file(&path);
+            return Ok(OppTransferStatus::Cancelled);
+        }
+
+        obex::write_packet(&mut stream, &Packet::new(obex::RSP_CONTINUE)).await?;
+        packet = obex::read_packet(&mut stream, 0).await?;
+
+        if packet.code == obex::OP_ABORT {
+            obex::write_packet(&mut stream, &Packet::new(obex::RSP_SUCCESS)).await?;
+            drop(file);
+            let _ = std::fs::remove_file(&path);
+            return Ok(OppTransferStatus::Cancelled);
+        }
+    }
+
+    // The remote device disconnects the session, but the object is complete regardless.
+    if let Ok(packet) = obex::read_packet(&mut stream, 0).await {
+        if packet.code == obex::OP_DISCONNECT {
+            let _ = obex::write_packet(&mut stream, &Packet::new(obex::RSP_SUCCESS)).await;
+        }
+    }
+
+    Ok(OppTransferStatus::Success)
+}
+
+/// Returns the path an incoming object named `name` is stored at, without overwriting files.
+fn unique_download_path(name: &str) -> PathBuf {
+    // Never trust the remote device with the directory structure.
+    let name = Path::new(name)
+        .file_name()
+        .map_or(String::from("received"), |name| name.to_string_lossy().to_string());
+    let dir = Path::new(DOWNLOAD_DIR);
+
+    let mut path = dir.join(&name);
+    let mut count = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}", count, name));
+        count += 1;
+    }
+
+    path
+}
+
+fn guess_mime_type(file_name: &str) -> String {
+    let extension = Path::new(file_name)
+        .extension()
+        .map_or(String::from(""), |ext| ext.to_string_lossy().to_lowercase());
+
+    String::from(match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "vcf" => "text/x-vcard",
+        "vcs" => "text/x-vcalendar",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "zip" => "application/zip",
+        _ => DEFAULT_MIME_TYPE,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_mime_type() {
+        assert_eq!(guess_mime_type("photo.JPG"), "image/jpeg");
+        assert_eq!(guess_mime_type("contact.vcf"), "text/x-vcard");
+        assert_eq!(guess_mime_type("archive.tar.xz"), DEFAULT_MIME_TYPE);
+        assert_eq!(guess_mime_type("noextension"), DEFAULT_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_unique_download_path_strips_directories() {
+        let path = unique_download_path("../../etc/passwd");
+        assert_eq!(path.parent(), Some(Path::new(DOWNLOAD_DIR)));
+        assert!(path.file_name().unwrap().to_string_lossy().ends_with("passwd"));
+    }
+}
@@ -0,0 +1,305 @@
+//! Personal Area Networking (IBluetoothPan).
+//!
+//! Joins the network access point (NAP) of a remote device, usually a phone sharing its internet
+//! connection, and optionally serves a NAP of our own. Either way the traffic goes through the
+//! network interface that libbluetooth creates once PAN is enabled, which the system network
+//! manager is expected to configure.
+
+use bt_topshim::btif::{BluetoothInterface, BtStatus, RawAddress};
+use bt_topshim::profiles::pan::{
+    BtPanConnectionState, BtPanControlState, Pan, PanCallbacks, PanCallbacksDispatcher,
+    BTPAN_ROLE_PANNAP, BTPAN_ROLE_PANU,
+};
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
+
+use crate::bluetooth::{
+    Bluetooth, BluetoothDevice, IBluetooth, ProfileConnectionReason, ProfileConnectionState,
+};
+use crate::callbacks::Callbacks;
+use crate::uuid::Profile;
+use crate::{queue_message, Message, RPCProxy};
+
+/// Defines the PAN API.
+#[dbus_interface(bluetooth_pan_dbus, "org.chromium.bluetooth.BluetoothPan")]
+pub trait IBluetoothPan {
+    /// Adds an observer to PAN connection changes.
+    ///
+    /// Returns an id identifying the callback.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothPanCallback + Send>) -> u32;
+
+    /// Removes an observer to PAN connection changes.
+    ///
+    /// Returns true if the callback can be removed, false if `callback_id` is not recognized.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Joins the network access point of `device`.
+    #[dbus_method("Connect")]
+    fn connect(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Drops the PAN connection to `device`, whichever side serves the network.
+    #[dbus_method("Disconnect")]
+    fn disconnect(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Allows remote devices to join a network access point served by us. Disabled by default.
+    #[dbus_method("SetNapEnabled")]
+    fn set_nap_enabled(&mut self, enabled: bool) -> bool;
+
+    /// Returns whether remote devices can join our network access point.
+    #[dbus_method("IsNapEnabled")]
+    fn is_nap_enabled(&self) -> bool;
+
+    /// Returns the name of the network interface carrying PAN traffic, or an empty string before
+    /// PAN is enabled.
+    #[dbus_method("GetInterfaceName")]
+    fn get_interface_name(&self) -> String;
+}
+
+/// PAN connection events.
+pub trait IBluetoothPanCallback: RPCProxy {
+    /// Triggered when the PAN connection to `device` changes state. Traffic of a connected
+    /// device goes through the network interface `interface_name`.
+    fn on_connection_state_changed(
+        &self,
+        device: BluetoothDevice,
+        state: BtPanConnectionState,
+        interface_name: String,
+    );
+}
+
+/// Implementation of the PAN API.
+pub struct BluetoothPan {
+    intf: Arc<Mutex<BluetoothInterface>>,
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    pan: Option<Pan>,
+    callbacks: Callbacks<dyn IBluetoothPanCallback + Send>,
+    states: HashMap<RawAddress, BtPanConnectionState>,
+    interface_name: String,
+    nap_enabled: bool,
+    /// Whether we asked libbluetooth for different roles and wait for its answer.
+    roles_pending: bool,
+}
+
+impl BluetoothPan {
+    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothPan {
+        BluetoothPan {
+            intf,
+            tx: tx.clone(),
+            adapter: None,
+            pan: None,
+            callbacks: Callbacks::new(tx, Message::PanCallbackDisconnected),
+            states: HashMap::new(),
+            interface_name: String::new(),
+            nap_enabled: false,
+            roles_pending: false,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub fn init_profiles(&mut self) {
+        let pantx = self.tx.clone();
+        self.pan = Some(Pan::new(&self.intf.lock().unwrap()));
+        self.pan.as_mut().unwrap().initialize(PanCallbacksDispatcher {
+            dispatch: Box::new(move |cb| {
+                queue_message(&pantx, Message::Pan(cb));
+            }),
+        });
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_pan_callbacks(&mut self, cb: PanCallbacks) {
+        match cb {
+            PanCallbacks::ControlState(state, local_role, status, ifname) => {
+                debug!("PAN {:?} with roles {}: {:?}, {}", state, local_role, status, ifname);
+                if state == BtPanControlState::Enabled && !ifname.is_empty() {
+                    self.interface_name = ifname;
+                }
+
+                // libbluetooth enables all roles it was built with whenever the stack comes up,
+                // so the NAP would be open to anyone without this.
+                if self.roles_pending {
+                    self.roles_pending = false;
+                } else if local_role != self.local_roles() {
+                    self.apply_roles();
+                }
+            }
+
+            PanCallbacks::ConnectionState(state, status, addr, local_role, remote_role) => {
+                info!(
+                    "[{}]: PAN {:?} ({:?}), local role {}, remote role {}",
+                    addr.to_string(),
+                    state,
+                    status,
+                    local_role,
+                    remote_role
+                );
+
+                // A remote PAN user connects to our NAP, otherwise we joined theirs.
+                let profile =
+                    if remote_role == BTPAN_ROLE_PANU { Profile::Nap } else { Profile::Panu };
+                if state == BtPanConnectionState::Connected && !self.is_allowed(&addr, &profile) {
+                    info!("[{}]: {:?} is not allowed, disconnecting", addr.to_string(), profile);
+                    self.pan.as_ref().unwrap().disconnect(&addr);
+                    self.update_profile_connection_state(
+                        addr,
+                        profile,
+                        ProfileConnectionState::Disconnected,
+                        ProfileConnectionReason::NotAllowed,
+                    );
+                    return;
+                }
+
+                if state == BtPanConnectionState::Disconnected {
+                    self.states.remove(&addr);
+                } else {
+                    self.states.insert(addr, state);
+                }
+
+                let device = self.get_device(&addr);
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_connection_state_changed(
+                        device.clone(),
+                        state,
+                        self.interface_name.clone(),
+                    );
+                });
+
+                let profile_state = match state {
+                    BtPanConnectionState::Connected => ProfileConnectionState::Connected,
+                    BtPanConnectionState::Connecting => ProfileConnectionState::Connecting,
+                    BtPanConnectionState::Disconnected => ProfileConnectionState::Disconnected,
+                    BtPanConnectionState::Disconnecting => ProfileConnectionState::Disconnecting,
+                    BtPanConnectionState::Unknown => return,
+                };
+                self.update_profile_connection_state(
+                    addr,
+                    profile,
+                    profile_state,
+                    ProfileConnectionReason::None,
+                );
+            }
+        }
+    }
+
+    fn local_roles(&self) -> i32 {
+        if self.nap_enabled {
+            BTPAN_ROLE_PANU | BTPAN_ROLE_PANNAP
+        } else {
+            BTPAN_ROLE_PANU
+        }
+    }
+
+    fn apply_roles(&mut self) -> bool {
+        let roles = self.local_roles();
+        let status = match self.pan.as_ref() {
+            Some(pan) => pan.enable(roles),
+            None => return false,
+        };
+
+        if status != BtStatus::Success {
+            warn!("Failed to set PAN roles {}: {:?}", roles, status);
+            return false;
+        }
+
+        self.roles_pending = true;
+        true
+    }
+
+    fn get_device(&self, addr: &RawAddress) -> BluetoothDevice {
+        let device = BluetoothDevice::new(*addr, String::from(""));
+        match &self.adapter {
+            Some(adapter) => {
+                let name = adapter.lock().unwrap().get_remote_name(device.clone());
+                BluetoothDevice::new(device.address, name)
+            }
+            None => device,
+        }
+    }
+
+    fn update_profile_connection_state(
+        &self,
+        addr: RawAddress,
+        profile: Profile,
+        state: ProfileConnectionState,
+        reason: ProfileConnectionReason,
+    ) {
+        if let Some(adapter) = &self.adapter {
+            adapter.lock().unwrap().update_profile_connection_state(addr, profile, state, reason);
+        }
+    }
+
+    fn is_allowed(&self, addr: &RawAddress, profile: &Profile) -> bool {
+        match &self.adapter {
+            Some(adapter) => adapter.lock().unwrap().is_profile_enabled_for_device(addr, profile),
+            None => false,
+        }
+    }
+}
+
+impl IBluetoothPan for BluetoothPan {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothPanCallback + Send>) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn connect(&mut self, device: BluetoothDevice) -> bool {
+        let addr = device.address;
+
+        if !self.is_allowed(&addr, &Profile::Panu) {
+            info!("[{}]: PANU is not allowed.", device.address);
+            return false;
+        }
+
+        match self.pan.as_ref() {
+            Some(pan) => {
+                pan.connect(&addr, BTPAN_ROLE_PANU, BTPAN_ROLE_PANNAP) == BtStatus::Success
+            }
+            None => false,
+        }
+    }
+
+    fn disconnect(&mut self, device: BluetoothDevice) -> bool {
+        let addr = device.address;
+
+        if !self.states.contains_key(&addr) {
+            return false;
+        }
+
+        match self.pan.as_ref() {
+            Some(pan) => pan.disconnect(&addr) == BtStatus::Success,
+            None => false,
+        }
+    }
+
+    fn set_nap_enabled(&mut self, enabled: bool) -> bool {
+        if self.nap_enabled == enabled {
+            return true;
+        }
+
+        self.nap_enabled = enabled;
+        self.apply_roles()
+    }
+
+    fn is_nap_enabled(&self) -> bool {
+        self.nap_enabled
+    }
+
+    fn get_interface_name(&self) -> String {
+        self.interface_name.clone()
+    }
+}
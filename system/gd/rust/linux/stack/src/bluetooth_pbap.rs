@@ -0,0 +1,322 @@
+//! Phone Book Access Profile client (IBluetoothPbap).
+//!
+//! Pulls contacts and call history from the Phone Book Server of a remote device, usually a
+//! phone, with the PullPhoneBook function. Every pull runs its own OBEX session so that pages can
+//! be requested independently of each other.
+
+use bt_topshim::btif::{BluetoothInterface, BtStatus, RawAddress, Uuid};
+use bt_topshim::profiles::socket::{BtSocket, SocketType, SOCK_FLAG_ENCRYPT};
+use bt_topshim::topstack;
+use dbus_macros::dbus_interface;
+use log::{info, warn};
+use std::os::unix::io::RawFd;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
+
+use crate::bluetooth::{Bluetooth, BluetoothDevice};
+use crate::callbacks::Callbacks;
+use crate::obex::{self, Header, ObexClient, ObexError};
+use crate::uuid::{Profile, UuidHelper, PBAP_PSE};
+use crate::{Message, RPCProxy};
+
+/// OBEX target of the Phone Book Access service.
+const PBAP_TARGET: [u8; 16] = [
+    0x79, 0x61, 0x35, 0xf0, 0xf0, 0xc5, 0x11, 0xd8, 0x09, 0x66, 0x08, 0x00, 0x20, 0x0c, 0x9a, 0x66,
+];
+
+const PHONEBOOK_TYPE: &str = "x-bt/phonebook";
+
+// Application parameter tags.
+const TAG_MAX_LIST_COUNT: u8 = 0x04;
+const TAG_LIST_START_OFFSET: u8 = 0x05;
+const TAG_FORMAT: u8 = 0x07;
+const TAG_PHONEBOOK_SIZE: u8 = 0x08;
+
+/// vCard 3.0, which every server supports along with 2.1.
+const FORMAT_VCARD_30: u8 = 0x01;
+
+/// Defines the Phone Book Access client API.
+#[dbus_interface(bluetooth_pbap_dbus, "org.chromium.bluetooth.BluetoothPbap")]
+pub trait IBluetoothPbap {
+    /// Adds an observer to phone book events.
+    ///
+    /// Returns an id identifying the callback.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothPbapCallback + Send>) -> u32;
+
+    /// Removes an observer to phone book events.
+    ///
+    /// Returns true if the callback can be removed, false if `callback_id` is not recognized.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Pulls up to `max_count` entries of `phonebook` from `device`, starting at entry `offset`.
+    /// A `max_count` of 0 only queries the size of the phone book.
+    ///
+    /// Returns the id of the request, reported back in `on_phonebook_pull_complete`, or -1 if the
+    /// pull can't be started.
+    #[dbus_method("PullPhonebook")]
+    fn pull_phonebook(
+        &mut self,
+        device: BluetoothDevice,
+        phonebook: PbapPhonebook,
+        offset: u16,
+        max_count: u16,
+    ) -> i32;
+}
+
+/// Phone book events.
+pub trait IBluetoothPbapCallback: RPCProxy {
+    /// Triggered when a pull started with `pull_phonebook` completes. `phonebook_size` is the
+    /// total number of entries on the server, if it told, and `vcards` are the entries of the
+    /// requested page.
+    fn on_phonebook_pull_complete(
+        &self,
+        request_id: i32,
+        status: PbapPullStatus,
+        phonebook_size: u32,
+        vcards: Vec<String>,
+    );
+}
+
+/// Phone book objects of the server.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum PbapPhonebook {
+    Contacts = 0,
+    IncomingCalls,
+    OutgoingCalls,
+    MissedCalls,
+    /// Incoming, outgoing and missed calls in one list.
+    CombinedCalls,
+}
+
+impl PbapPhonebook {
+    fn object_name(&self) -> &'static str {
+        match self {
+            PbapPhonebook::Contacts => "telecom/pb.vcf",
+            PbapPhonebook::IncomingCalls => "telecom/ich.vcf",
+            PbapPhonebook::OutgoingCalls => "telecom/och.vcf",
+            PbapPhonebook::MissedCalls => "telecom/mch.vcf",
+            PbapPhonebook::CombinedCalls => "telecom/cch.vcf",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum PbapPullStatus {
+    Success = 0,
+    /// The server refused access, e.g. because the user didn't allow it on the phone.
+    Forbidden,
+    NotFound,
+    Failed,
+}
+
+/// Actions that `BluetoothPbap` takes on behalf of its pull tasks.
+pub enum PbapActions {
+    PullComplete(i32, PbapPullStatus, u32, Vec<String>),
+}
+
+/// Implementation of the Phone Book Access client API.
+pub struct BluetoothPbap {
+    intf: Arc<Mutex<BluetoothInterface>>,
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    socket: Option<BtSocket>,
+    callbacks: Callbacks<dyn IBluetoothPbapCallback + Send>,
+    next_request_id: i32,
+}
+
+impl BluetoothPbap {
+    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothPbap {
+        BluetoothPbap {
+            intf,
+            tx: tx.clone(),
+            adapter: None,
+            socket: None,
+            callbacks: Callbacks::new(tx, Message::PbapCallbackDisconnected),
+            next_request_id: 1,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_pbap_actions(&mut self, action: PbapActions) {
+        match action {
+            PbapActions::PullComplete(id, status, phonebook_size, vcards) => {
+                info!("PBAP pull {} completed: {:?}, {} entries", id, status, vcards.len());
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_phonebook_pull_complete(id, status, phonebook_size, vcards.clone());
+                });
+            }
+        }
+    }
+
+    fn is_allowed(&self, addr: &RawAddress) -> bool {
+        match &self.adapter {
+            Some(adapter) => {
+                adapter.lock().unwrap().is_profile_enabled_for_device(addr, &Profile::PbapPce)
+            }
+            None => false,
+        }
+    }
+}
+
+impl IBluetoothPbap for BluetoothPbap {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothPbapCallback + Send>) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn pull_phonebook(
+        &mut self,
+        device: BluetoothDevice,
+        phonebook: PbapPhonebook,
+        offset: u16,
+        max_count: u16,
+    ) -> i32 {
+        let addr = device.address;
+
+        if !self.is_allowed(&addr) {
+            info!("[{}]: PBAP is not allowed.", device.address);
+            return -1;
+        }
+
+        if self.socket.is_none() {
+            self.socket = Some(BtSocket::new(&self.intf.lock().unwrap()));
+        }
+
+        let (status, fd) = self.socket.as_ref().unwrap().connect(
+            addr,
+            SocketType::Rfcomm,
+            Some(Uuid { uu: UuidHelper::from_string(PBAP_PSE).unwrap() }),
+            0,
+            SOCK_FLAG_ENCRYPT,
+        );
+
+        let fd = match (&status, fd) {
+            (BtStatus::Success, Some(fd)) => fd,
+            _ => {
+                warn!("[{}]: Failed to connect PBAP: {:?}", device.address, status);
+                return -1;
+            }
+        };
+
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+
+        let tx = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            let (status, phonebook_size, vcards) = match pull(fd, phonebook, offset, max_count)
+                .await
+            {
+                Ok((phonebook_size, vcards)) => (PbapPullStatus::Success, phonebook_size, vcards),
+                Err(ObexError::Response(obex::RSP_FORBIDDEN))
+                | Err(ObexError::Response(obex::RSP_UNAUTHORIZED)) => {
+                    (PbapPullStatus::Forbidden, 0, vec![])
+                }
+                Err(ObexError::Response(obex::RSP_NOT_FOUND)) => {
+                    (PbapPullStatus::NotFound, 0, vec![])
+                }
+                Err(e) => {
+                    warn!("PBAP pull {} failed: {}", id, e);
+                    (PbapPullStatus::Failed, 0, vec![])
+                }
+            };
+
+            let _ = tx
+                .send(Message::Pbap(PbapActions::PullComplete(id, status, phonebook_size, vcards)))
+                .await;
+        });
+
+        id
+    }
+}
+
+async fn pull(
+    fd: RawFd,
+    phonebook: PbapPhonebook,
+    offset: u16,
+    max_count: u16,
+) -> Result<(u32, Vec<String>), ObexError> {
+    let mut client = ObexClient::new(obex::wait_for_connection(fd).await?);
+    client.connect(Some(PBAP_TARGET.to_vec())).await?;
+
+    let params = obex::encode_app_parameters(&[
+        (TAG_MAX_LIST_COUNT, max_count.to_be_bytes().to_vec()),
+        (TAG_LIST_START_OFFSET, offset.to_be_bytes().to_vec()),
+        (TAG_FORMAT, vec![FORMAT_VCARD_30]),
+    ]);
+    let request = client
+        .start_request(obex::OP_GET | obex::FINAL_BIT)
+        .with_header(Header::Name(String::from(phonebook.object_name())))
+        .with_header(Header::Type(String::from(PHONEBOOK_TYPE)))
+        .with_header(Header::AppParameters(params));
+
+    let (response, object) = client.get(request).await?;
+    let _ = client.disconnect().await;
+
+    // The size is only sent when it was asked for with a `max_count` of 0.
+    let phonebook_size = response
+        .app_parameters()
+        .map(obex::parse_app_parameters)
+        .and_then(|params| {
+            params.into_iter().find(|(tag, value)| *tag == TAG_PHONEBOOK_SIZE && value.len() == 2)
+        })
+        .map_or(0, |(_, value)| u16::from_be_bytes([value[0], value[1]]) as u32);
+
+    Ok((phonebook_size, split_vcards(&String::from_utf8_lossy(&object))))
+}
+
+/// Splits a vCard listing into individual vCards.
+fn split_vcards(listing: &str) -> Vec<String> {
+    let mut vcards = vec![];
+    let mut current: Option<String> = None;
+
+    for line in listing.lines() {
+        let trimmed = line.trim_end();
+        if trimmed.eq_ignore_ascii_case("BEGIN:VCARD") {
+            current = Some(String::new());
+        }
+
+        if let Some(vcard) = current.as_mut() {
+            vcard.push_str(trimmed);
+            vcard.push_str("\r\n");
+        }
+
+        if trimmed.eq_ignore_ascii_case("END:VCARD") {
+            if let Some(vcard) = current.take() {
+                vcards.push(vcard);
+            }
+        }
+    }
+
+    vcards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_vcards() {
+        let listing = "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Alice\r\nEND:VCARD\r\n\
+                       BEGIN:VCARD\nVERSION:3.0\nFN:Bob\nEND:VCARD\n\
+                       BEGIN:VCARD\r\nFN:Truncated\r\n";
+
+        let vcards = split_vcards(listing);
+        assert_eq!(vcards.len(), 2);
+        assert_eq!(vcards[0], "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Alice\r\nEND:VCARD\r\n");
+        assert_eq!(vcards[1], "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Bob\r\nEND:VCARD\r\n");
+    }
+}
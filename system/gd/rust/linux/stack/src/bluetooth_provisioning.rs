@@ -0,0 +1,206 @@
+//! Bond export and import for fleet provisioning (IBluetoothProvisioning).
+//!
+//! Labs and factories pair their peripherals once and load the same bonds on every identical unit
+//! instead of pairing each one by hand. The bonds are carried in an archive holding the device
+//! sections of `bt_config.conf`, keys and all, so the archive is encrypted with a passphrase.
+//!
+//! The archive itself goes over D-Bus: the caller reads and writes the file with its own
+//! permissions, the daemon never opens a path it is handed.
+//!
+//! libbluetooth owns its config while the daemon runs and writes it back on the way out, so
+//! imported bonds are kept aside and only merged into the config when the daemon next starts.
+
+use bt_topshim::btif::BtStatus;
+use bt_topshim::crypto;
+use dbus_macros::dbus_interface;
+use log::{info, warn};
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+
+use crate::device_store::{self, parse_ini, to_ini, DeviceStore};
+
+/// Where imported bonds wait for the next start of the daemon.
+pub const PENDING_BONDS_PATH: &str = "/var/lib/bluetooth/floss_pending_bonds.conf";
+
+/// Start of every archive, bumped whenever its layout changes. It is also authenticated along
+/// with the bonds.
+const ARCHIVE_MAGIC: &[u8; 8] = b"FLBONDS1";
+
+/// The archive is the magic, the salt of the passphrase, the AES-256-GCM nonce, the encrypted
+/// bonds and the GCM tag.
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const HEADER_LEN: usize = ARCHIVE_MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+/// PBKDF2-HMAC-SHA256 iterations turning the passphrase into the key.
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Defines the provisioning API.
+#[dbus_interface(bluetooth_provisioning_dbus, "org.chromium.bluetooth.BluetoothProvisioning")]
+pub trait IBluetoothProvisioning {
+    /// Returns an archive of the bonded devices of the adapter, encrypted with `passphrase`, or
+    /// an empty one if the passphrase is empty or the bonds can't be read.
+    ///
+    /// Bonds are read from the config libbluetooth flushed last, so one made a moment ago may be
+    /// missing until the adapter is disabled.
+    #[dbus_method("ExportBonds")]
+    fn export_bonds(&mut self, passphrase: String) -> Vec<u8>;
+
+    /// Adds the bonded devices from an archive returned by `export_bonds`, replacing the ones
+    /// already bonded under the same address. The devices show up as bonded once the adapter is
+    /// restarted.
+    ///
+    /// Returns `AuthFailure` if the passphrase is wrong or the archive was tampered with.
+    #[dbus_method("ImportBonds")]
+    fn import_bonds(&mut self, archive: Vec<u8>, passphrase: String) -> BtStatus;
+}
+
+/// Implementation of the provisioning API.
+#[derive(Default)]
+pub struct BluetoothProvisioning {}
+
+impl BluetoothProvisioning {
+    pub fn new() -> BluetoothProvisioning {
+        BluetoothProvisioning::default()
+    }
+}
+
+/// Moves the bonds imported while the daemon last ran into the libbluetooth config. Must be called
+/// before libbluetooth is initialized.
+///
+/// Returns the number of bonds added.
+pub fn apply_pending_bonds() -> io::Result<usize> {
+    let pending = DeviceStore::load(PENDING_BONDS_PATH)?.get_bonded_sections();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let mut store = DeviceStore::load(device_store::DEFAULT_CONFIG_PATH)?;
+    let count = pending.len();
+    for section in pending {
+        store.set_bonded_section(section);
+    }
+    store.save()?;
+    fs::remove_file(PENDING_BONDS_PATH)?;
+    Ok(count)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Option<[u8; KEY_LEN]> {
+    crypto::pbkdf2_hmac_sha256(passphrase.as_bytes(), salt, PBKDF2_ITERATIONS)
+}
+
+/// Encrypts `plaintext` into an archive.
+fn seal(plaintext: &[u8], passphrase: &str) -> Option<Vec<u8>> {
+    let salt = crypto::random_bytes(SALT_LEN);
+    let nonce: [u8; NONCE_LEN] = crypto::random_bytes(NONCE_LEN).try_into().ok()?;
+
+    let key = derive_key(passphrase, &salt)?;
+    // The tag comes out appended to the ciphertext.
+    let sealed = crypto::aes256_gcm_seal(&key, &nonce, ARCHIVE_MAGIC, plaintext);
+
+    let mut archive = Vec::with_capacity(HEADER_LEN + sealed.len());
+    archive.extend_from_slice(ARCHIVE_MAGIC);
+    archive.extend_from_slice(&salt);
+    archive.extend_from_slice(&nonce);
+    archive.extend_from_slice(&sealed);
+    Some(archive)
+}
+
+/// Decrypts an archive. Returns None if it isn't one, or if `passphrase` doesn't open it.
+fn open(archive: &[u8], passphrase: &str) -> Option<Vec<u8>> {
+    if archive.len() < HEADER_LEN + TAG_LEN || !archive.starts_with(ARCHIVE_MAGIC) {
+        return None;
+    }
+
+    let (salt, rest) = archive[ARCHIVE_MAGIC.len()..].split_at(SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(passphrase, salt)?;
+    crypto::aes256_gcm_open(&key, nonce.try_into().ok()?, ARCHIVE_MAGIC, sealed)
+}
+
+impl IBluetoothProvisioning for BluetoothProvisioning {
+    fn export_bonds(&mut self, passphrase: String) -> Vec<u8> {
+        if passphrase.is_empty() {
+            warn!("Refusing to export the bonds without a passphrase");
+            return vec![];
+        }
+
+        let store = match DeviceStore::load(device_store::DEFAULT_CONFIG_PATH) {
+            Ok(store) => store,
+            Err(e) => {
+                warn!("Failed to load {}: {}", device_store::DEFAULT_CONFIG_PATH, e);
+                return vec![];
+            }
+        };
+
+        let sections = store.get_bonded_sections();
+        match seal(to_ini(&sections).as_bytes(), &passphrase) {
+            Some(archive) => {
+                info!("Exported {} bond(s)", sections.len());
+                archive
+            }
+            None => {
+                warn!("Failed to encrypt the bonds");
+                vec![]
+            }
+        }
+    }
+
+    fn import_bonds(&mut self, archive: Vec<u8>, passphrase: String) -> BtStatus {
+        let sections = match open(&archive, &passphrase).map(String::from_utf8) {
+            Some(Ok(plaintext)) => parse_ini(&plaintext),
+            _ => {
+                warn!("Not a bond archive, or the passphrase is wrong");
+                return BtStatus::AuthFailure;
+            }
+        };
+
+        // Imports that haven't been applied yet are kept, later ones winning.
+        let mut pending = match DeviceStore::load(PENDING_BONDS_PATH) {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("Failed to load {}: {}", PENDING_BONDS_PATH, e);
+                return BtStatus::Fail;
+            }
+        };
+
+        let mut count = 0;
+        for section in sections {
+            if pending.set_bonded_section(section) {
+                count += 1;
+            }
+        }
+
+        if let Err(e) = pending.save() {
+            warn!("Failed to save {}: {}", PENDING_BONDS_PATH, e);
+            return BtStatus::Fail;
+        }
+
+        info!("Imported {} bond(s), added on the next adapter start", count);
+        BtStatus::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let plaintext = b"[11:22:33:44:55:66]\nLinkKey = 00112233445566778899aabbccddeeff\n";
+        let archive = seal(plaintext, "passphrase").unwrap();
+        assert!(archive.starts_with(ARCHIVE_MAGIC));
+        assert_eq!(open(&archive, "passphrase").unwrap(), plaintext.to_vec());
+
+        // Wrong passphrase, tampered or truncated archive.
+        assert!(open(&archive, "Passphrase").is_none());
+        let mut tampered = archive.clone();
+        tampered[HEADER_LEN] ^= 1;
+        assert!(open(&tampered, "passphrase").is_none());
+        assert!(open(&archive[..HEADER_LEN + TAG_LEN - 1], "passphrase").is_none());
+    }
+}
@@ -0,0 +1,202 @@
+//! Qualification helpers (IBluetoothPts).
+//!
+//! Test cases of the Profile Tuning Suite (PTS) expect the stack under test to do things it never
+//! does on its own, or to leave out things it always does. libbluetooth has test modes for most of
+//! these, read from its stack configuration, and this interface switches them at runtime so that
+//! a test run doesn't need a different configuration file and a restart for each case.
+//!
+//! Only built with the `pts` feature. None of this belongs on a production image.
+
+use bt_topshim::btif::{BluetoothInterface, BtTransport, RawAddress};
+use dbus_macros::dbus_interface;
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+
+/// Stack configuration keys of the native test modes.
+const PTS_DISABLE_SDP_ON_LE_PAIR: &str = "PTS_DisableSDPOnLEPair";
+const PTS_DISABLE_CONN_UPDATES: &str = "PTS_DisableConnUpdates";
+const PTS_SMP_OPTIONS: &str = "PTS_SmpOptions";
+
+/// Highest SMP IO capability, KeyboardDisplay.
+const SMP_IO_CAP_MAX: u8 = 4;
+
+/// Range of SMP encryption key sizes, in octets.
+const SMP_KEY_SIZE_MIN: u8 = 7;
+const SMP_KEY_SIZE_MAX: u8 = 16;
+
+/// Shortest ATT PDU: the opcode alone.
+const ATT_PDU_MIN_LEN: usize = 1;
+
+/// Defines the qualification API.
+#[dbus_interface(bluetooth_pts_dbus, "org.chromium.bluetooth.BluetoothPts")]
+pub trait IBluetoothPts {
+    /// Chooses whether service discovery runs on a device after an LE pairing that also derived
+    /// keys for BR/EDR. Enabled by default.
+    #[dbus_method("SetAutoSdpEnabled")]
+    fn set_auto_sdp_enabled(&mut self, enabled: bool);
+
+    /// Overrides what the local device offers in the SMP Pairing Request and Response of new LE
+    /// pairings, which chooses the pairing method: the `auth_req` flags, `io_cap`, the key
+    /// distribution `init_keys` and `resp_keys`, and the largest key size in octets.
+    ///
+    /// Returns false if the values can't be sent in an SMP pairing request.
+    #[dbus_method("SetLePairingOptions")]
+    fn set_le_pairing_options(
+        &mut self,
+        auth_req: u8,
+        io_cap: u8,
+        init_keys: u8,
+        resp_keys: u8,
+        max_key_size: u8,
+    ) -> bool;
+
+    /// Goes back to the pairing options of the adapter for new LE pairings.
+    #[dbus_method("ClearLePairingOptions")]
+    fn clear_le_pairing_options(&mut self);
+
+    /// Sends `pdu`, starting with the ATT opcode, over the ATT channel of the LE link to
+    /// `address` without checking that it makes sense. Responses are handled by GATT as usual.
+    ///
+    /// Returns false if there is no LE link to the device.
+    #[dbus_method("SendAttPdu")]
+    fn send_att_pdu(&mut self, address: String, pdu: Vec<u8>) -> bool;
+
+    /// Chooses whether L2CAP updates the parameters of LE connections, on its own or when the
+    /// remote device asks for it. Enabled by default.
+    #[dbus_method("SetLeConnUpdatesEnabled")]
+    fn set_le_conn_updates_enabled(&mut self, enabled: bool);
+
+    /// Sets how long, in seconds, the link to `address` on `transport` stays up once its last
+    /// L2CAP channel closes. 0xffff keeps it up until either side disconnects.
+    ///
+    /// Returns false if there is no such link.
+    #[dbus_method("SetLinkIdleTimeout")]
+    fn set_link_idle_timeout(
+        &mut self,
+        address: String,
+        transport: BtTransport,
+        timeout: u16,
+    ) -> bool;
+}
+
+/// Implementation of the qualification API.
+pub struct BluetoothPts {
+    intf: Arc<Mutex<BluetoothInterface>>,
+}
+
+impl BluetoothPts {
+    pub fn new(intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothPts {
+        BluetoothPts { intf }
+    }
+
+    fn set_config(&self, key: &str, value: &str) {
+        info!("PTS: {} = {:?}", key, value);
+        self.intf.lock().unwrap().set_pts_config(key, value);
+    }
+
+    fn set_config_flag(&self, key: &str, value: bool) {
+        self.set_config(key, if value { "true" } else { "false" });
+    }
+}
+
+/// Formats pairing options as libbluetooth reads them from `PTS_SmpOptions`: comma separated hex.
+fn format_smp_options(
+    auth_req: u8,
+    io_cap: u8,
+    init_keys: u8,
+    resp_keys: u8,
+    max_key_size: u8,
+) -> Option<String> {
+    if io_cap > SMP_IO_CAP_MAX || !(SMP_KEY_SIZE_MIN..=SMP_KEY_SIZE_MAX).contains(&max_key_size) {
+        return None;
+    }
+
+    Some(format!("{:x},{:x},{:x},{:x},{:x}", auth_req, io_cap, init_keys, resp_keys, max_key_size))
+}
+
+impl IBluetoothPts for BluetoothPts {
+    fn set_auto_sdp_enabled(&mut self, enabled: bool) {
+        self.set_config_flag(PTS_DISABLE_SDP_ON_LE_PAIR, !enabled);
+    }
+
+    fn set_le_pairing_options(
+        &mut self,
+        auth_req: u8,
+        io_cap: u8,
+        init_keys: u8,
+        resp_keys: u8,
+        max_key_size: u8,
+    ) -> bool {
+        match format_smp_options(auth_req, io_cap, init_keys, resp_keys, max_key_size) {
+            Some(options) => {
+                self.set_config(PTS_SMP_OPTIONS, &options);
+                true
+            }
+            None => {
+                warn!(
+                    "PTS: invalid pairing options, IO capability {} key size {}",
+                    io_cap, max_key_size
+                );
+                false
+            }
+        }
+    }
+
+    fn clear_le_pairing_options(&mut self) {
+        self.set_config(PTS_SMP_OPTIONS, "");
+    }
+
+    fn send_att_pdu(&mut self, address: String, pdu: Vec<u8>) -> bool {
+        let addr = match RawAddress::from_string(&address) {
+            Some(addr) => addr,
+            None => {
+                warn!("PTS: invalid address {}", address);
+                return false;
+            }
+        };
+
+        if pdu.len() < ATT_PDU_MIN_LEN {
+            warn!("[{}]: PTS: empty ATT PDU", address);
+            return false;
+        }
+
+        self.intf.lock().unwrap().send_att_pdu(&addr, &pdu)
+    }
+
+    fn set_le_conn_updates_enabled(&mut self, enabled: bool) {
+        self.set_config_flag(PTS_DISABLE_CONN_UPDATES, !enabled);
+    }
+
+    fn set_link_idle_timeout(
+        &mut self,
+        address: String,
+        transport: BtTransport,
+        timeout: u16,
+    ) -> bool {
+        match RawAddress::from_string(&address) {
+            Some(addr) => {
+                self.intf.lock().unwrap().set_link_idle_timeout(&addr, transport, timeout)
+            }
+            None => {
+                warn!("PTS: invalid address {}", address);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_smp_options() {
+        // Secure Connections with MITM and bonding, KeyboardDisplay, all keys, 16 octets.
+        assert_eq!(format_smp_options(0x0d, 4, 0xf, 0xf, 16), Some("d,4,f,f,10".to_string()));
+        assert_eq!(format_smp_options(0x01, 3, 0x0, 0x1, 7), Some("1,3,0,1,7".to_string()));
+
+        assert_eq!(format_smp_options(0x0d, 5, 0xf, 0xf, 16), None);
+        assert_eq!(format_smp_options(0x0d, 4, 0xf, 0xf, 6), None);
+        assert_eq!(format_smp_options(0x0d, 4, 0xf, 0xf, 17), None);
+    }
+}
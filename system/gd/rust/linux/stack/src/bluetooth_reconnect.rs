@@ -0,0 +1,308 @@
+//! Automatic reconnection of trusted peripherals (IBluetoothReconnect).
+//!
+//! Keyboards, mice and headphones that go out of range usually come back on their own, but many
+//! of them wait for the host to page them rather than paging it. When the link to a bonded device
+//! using one of these profiles is lost, it is connected again in the background, waiting twice as
+//! long after each attempt, until it is back or the attempts run out. Devices can opt out, and
+//! links that were closed on purpose are left alone.
+
+use bt_topshim::btif::{BtBondState, BtHciErrorCode, RawAddress};
+use dbus_macros::dbus_interface;
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::callbacks::Callbacks;
+use crate::runtime_config::RuntimeConfig;
+use crate::uuid::{Profile, UuidHelper};
+use crate::{Message, RPCProxy};
+
+/// Profiles of the devices that are reconnected.
+const RECONNECT_PROFILES: [Profile; 7] = [
+    Profile::Hid,
+    Profile::Hogp,
+    Profile::A2dpSink,
+    Profile::Hfp,
+    Profile::Hsp,
+    Profile::HearingAid,
+    Profile::LeAudio,
+];
+
+// Disconnection reasons meaning the link was lost rather than closed by either side.
+const HCI_CONNECTION_TIMEOUT: BtHciErrorCode = 0x08;
+const HCI_LMP_RESPONSE_TIMEOUT: BtHciErrorCode = 0x22;
+
+/// Wait before the first attempt, doubled after each one up to `MAX_RECONNECT_DELAY`.
+const FIRST_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+
+/// Defines the reconnection API.
+#[dbus_interface(bluetooth_reconnect_dbus, "org.chromium.bluetooth.BluetoothReconnect")]
+pub trait IBluetoothReconnect {
+    /// Adds a callback to be told about reconnection attempts.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothReconnectCallback + Send>) -> u32;
+
+    /// Removes a callback added with `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Lets the stack reconnect a device when its link is lost, which is the default, or stops
+    /// it from doing so. Disabling it also gives up an ongoing reconnection. The setting is kept
+    /// across restarts.
+    #[dbus_method("SetReconnectEnabled")]
+    fn set_reconnect_enabled(&mut self, device: BluetoothDevice, enabled: bool) -> bool;
+
+    /// Returns false if reconnection was disabled for the device with `set_reconnect_enabled`.
+    #[dbus_method("IsReconnectEnabled")]
+    fn is_reconnect_enabled(&self, device: BluetoothDevice) -> bool;
+
+    /// Returns whether the stack is trying to reconnect the device.
+    #[dbus_method("IsReconnecting")]
+    fn is_reconnecting(&self, device: BluetoothDevice) -> bool;
+}
+
+/// Reconnection events.
+pub trait IBluetoothReconnectCallback: RPCProxy {
+    /// The stack is connecting `device` again, for the `attempt`-th time since its link was lost.
+    fn on_reconnect_attempt(&self, device: BluetoothDevice, attempt: u32);
+
+    /// The device didn't come back after `attempts` attempts, and the stack stopped trying.
+    fn on_reconnect_gave_up(&self, device: BluetoothDevice, attempts: u32);
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum ReconnectActions {
+    /// The ACL link to the device went down for `reason`.
+    Disconnected(BluetoothDevice, BtHciErrorCode),
+    Connected(RawAddress),
+    /// Time for the next attempt.
+    Attempt(RawAddress),
+    /// The adapter is disabled, so there is nothing to reconnect with.
+    Stop,
+}
+
+struct Reconnection {
+    device: BluetoothDevice,
+    attempts: u32,
+    timer: JoinHandle<()>,
+}
+
+/// Implementation of the reconnection API.
+pub struct BluetoothReconnect {
+    tx: Sender<Message>,
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+    callbacks: Callbacks<dyn IBluetoothReconnectCallback + Send>,
+    reconnections: HashMap<RawAddress, Reconnection>,
+    uuid_helper: UuidHelper,
+    /// Whether lost links are reconnected at all, and how many attempts are made, as set by the
+    /// runtime config.
+    enabled: bool,
+    max_attempts: u32,
+}
+
+/// How long to wait before the attempt following `attempts` earlier ones.
+fn reconnect_delay(attempts: u32) -> Duration {
+    FIRST_RECONNECT_DELAY.saturating_mul(1 << attempts.min(16)).min(MAX_RECONNECT_DELAY)
+}
+
+impl BluetoothReconnect {
+    pub fn new(tx: Sender<Message>) -> BluetoothReconnect {
+        BluetoothReconnect {
+            tx: tx.clone(),
+            adapter: None,
+            callbacks: Callbacks::new(tx, Message::ReconnectCallbackDisconnected),
+            reconnections: HashMap::new(),
+            uuid_helper: UuidHelper::new(),
+            enabled: true,
+            max_attempts: MAX_RECONNECT_ATTEMPTS,
+        }
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    /// Applies a reloaded runtime config. Turning reconnection off gives up the ongoing ones.
+    pub(crate) fn apply_runtime_config(&mut self, config: &RuntimeConfig) {
+        self.enabled = config.reconnect_enabled;
+        self.max_attempts = config.max_reconnect_attempts.unwrap_or(MAX_RECONNECT_ATTEMPTS);
+        if !self.enabled {
+            self.stop_all();
+        }
+    }
+
+    fn stop_all(&mut self) {
+        for (_, reconnection) in self.reconnections.drain() {
+            reconnection.timer.abort();
+        }
+    }
+
+    pub fn dispatch_reconnect_actions(&mut self, action: ReconnectActions) {
+        match action {
+            ReconnectActions::Disconnected(device, reason) => {
+                if !self.enabled
+                    || (reason != HCI_CONNECTION_TIMEOUT && reason != HCI_LMP_RESPONSE_TIMEOUT)
+                {
+                    return;
+                }
+                if self.reconnections.contains_key(&device.address) || !self.is_trusted(&device) {
+                    return;
+                }
+
+                info!("[{}]: Link lost, reconnecting", device.address.to_string());
+                let timer = self.schedule_attempt(device.address, 0);
+                self.reconnections
+                    .insert(device.address, Reconnection { device, attempts: 0, timer });
+            }
+
+            ReconnectActions::Connected(address) => {
+                if let Some(reconnection) = self.reconnections.remove(&address) {
+                    reconnection.timer.abort();
+                    info!(
+                        "[{}]: Reconnected after {} attempt(s)",
+                        address.to_string(),
+                        reconnection.attempts
+                    );
+                }
+            }
+
+            ReconnectActions::Attempt(address) => self.attempt(address),
+
+            ReconnectActions::Stop => self.stop_all(),
+        }
+    }
+
+    /// Whether `device` is bonded, uses a profile worth reconnecting, and neither opted out nor
+    /// is blocked.
+    fn is_trusted(&self, device: &BluetoothDevice) -> bool {
+        let adapter = match &self.adapter {
+            Some(adapter) => adapter.lock().unwrap(),
+            None => return false,
+        };
+
+        adapter.get_bond_state(device.clone()) == BtBondState::Bonded as u32
+            && adapter.is_reconnect_enabled(&device.address)
+            && !adapter.is_blocked(&device.address)
+            && adapter.get_remote_uuids(device.clone()).iter().any(|uuid| {
+                self.uuid_helper
+                    .is_known_profile(uuid)
+                    .map_or(false, |profile| RECONNECT_PROFILES.contains(profile))
+            })
+    }
+
+    fn schedule_attempt(&self, address: RawAddress, attempts: u32) -> JoinHandle<()> {
+        let delay = reconnect_delay(attempts);
+        debug!("[{}]: Next reconnection attempt in {:?}", address.to_string(), delay);
+
+        let txl = self.tx.clone();
+        tokio::spawn(async move {
+            time::sleep(delay).await;
+            let _ = txl.send(Message::Reconnect(ReconnectActions::Attempt(address))).await;
+        })
+    }
+
+    fn attempt(&mut self, address: RawAddress) {
+        let (device, attempts) = match self.reconnections.get(&address) {
+            Some(reconnection) => (reconnection.device.clone(), reconnection.attempts),
+            None => return,
+        };
+
+        // The bond may have been removed since, or the device connected before we noticed.
+        let connected = match &self.adapter {
+            Some(adapter) => adapter.lock().unwrap().get_connection_state(device.clone()) != 0,
+            None => false,
+        };
+        if connected || !self.is_trusted(&device) {
+            self.reconnections.remove(&address);
+            return;
+        }
+
+        if attempts >= self.max_attempts {
+            info!("[{}]: Giving up reconnecting", address.to_string());
+            self.reconnections.remove(&address);
+            self.callbacks.for_all_callbacks(|callback| {
+                callback.on_reconnect_gave_up(device.clone(), attempts);
+            });
+            return;
+        }
+
+        let attempt = attempts + 1;
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_reconnect_attempt(device.clone(), attempt);
+        });
+        if let Some(adapter) = &self.adapter {
+            adapter.lock().unwrap().connect_all_enabled_profiles(device);
+        }
+
+        let timer = self.schedule_attempt(address, attempt);
+        if let Some(reconnection) = self.reconnections.get_mut(&address) {
+            reconnection.attempts = attempt;
+            reconnection.timer = timer;
+        }
+    }
+}
+
+impl IBluetoothReconnect for BluetoothReconnect {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothReconnectCallback + Send>) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn set_reconnect_enabled(&mut self, device: BluetoothDevice, enabled: bool) -> bool {
+        let adapter = match &self.adapter {
+            Some(adapter) => adapter.clone(),
+            None => return false,
+        };
+        if !adapter.lock().unwrap().set_reconnect_enabled(device.address, enabled) {
+            return false;
+        }
+
+        if !enabled {
+            if let Some(reconnection) = self.reconnections.remove(&device.address) {
+                reconnection.timer.abort();
+            }
+        }
+        true
+    }
+
+    fn is_reconnect_enabled(&self, device: BluetoothDevice) -> bool {
+        match &self.adapter {
+            Some(adapter) => adapter.lock().unwrap().is_reconnect_enabled(&device.address),
+            None => true,
+        }
+    }
+
+    fn is_reconnecting(&self, device: BluetoothDevice) -> bool {
+        self.reconnections.contains_key(&device.address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_delay() {
+        assert_eq!(reconnect_delay(0), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(1), Duration::from_secs(4));
+        assert_eq!(reconnect_delay(4), Duration::from_secs(32));
+        assert_eq!(reconnect_delay(5), MAX_RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(MAX_RECONNECT_ATTEMPTS), MAX_RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), MAX_RECONNECT_DELAY);
+    }
+}
@@ -0,0 +1,268 @@
+//! Bookkeeping for the callbacks clients register on the stack's interfaces.
+
+use bt_topshim::topstack;
+use dbus_projection::panic_message;
+use log::{error, warn};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::Sender;
+
+use crate::{queue_message, Message, RPCProxy};
+
+/// Callbacks registered on one interface, keyed by the ID handed out to the client.
+///
+/// IDs are allocated here instead of being taken from the RPC layer, so they are unique among the
+/// interface's callbacks no matter how the callback object was created. When the remote end of a
+/// callback goes away, the message built by `disconnected_message` is sent to the dispatch loop,
+/// which is expected to call [`Callbacks::remove_callback`] with the ID.
+///
+/// Every delivery made through [`Callbacks::for_all_callbacks`] is stamped with the next sequence
+/// number of the interface and the time it was made, so clients can tell when they missed some and
+/// line them up with other logs such as btsnoop.
+///
+/// Calls made through [`Callbacks::for_all_callbacks`] and [`Callbacks::call_by_id`] are isolated
+/// from each other: a panic in one is logged and doesn't reach the caller. A callback that panics
+/// [`MAX_CONSECUTIVE_FAILURES`] times in a row is dropped as if its remote end went away.
+pub struct Callbacks<T: RPCProxy + Send + ?Sized> {
+    callbacks: HashMap<u32, Entry<T>>,
+    last_id: u32,
+    tx: Sender<Message>,
+    disconnected_message: fn(u32) -> Message,
+    last_sequence: AtomicU64,
+    clock: EventClock,
+}
+
+/// Number of calls in a row a callback may panic in before it is dropped.
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+struct Entry<T: ?Sized> {
+    // ID of the disconnect observer of the callback.
+    watch_id: u32,
+    callback: Box<T>,
+    // Calls that panicked since the last one that didn't.
+    failures: AtomicU32,
+}
+
+// Gives the time in microseconds since the Unix epoch, like btsnoop does, but measured from a
+// fixed starting point with a monotonic clock so that it can't go backwards.
+struct EventClock {
+    start: Instant,
+    start_us: u64,
+}
+
+impl EventClock {
+    fn new() -> Self {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        EventClock { start: Instant::now(), start_us: since_epoch.as_micros() as u64 }
+    }
+
+    fn now_us(&self) -> u64 {
+        self.start_us + self.start.elapsed().as_micros() as u64
+    }
+}
+
+impl<T: RPCProxy + Send + ?Sized> Callbacks<T> {
+    pub fn new(tx: Sender<Message>, disconnected_message: fn(u32) -> Message) -> Self {
+        Self {
+            callbacks: HashMap::new(),
+            last_id: 0,
+            tx,
+            disconnected_message,
+            last_sequence: AtomicU64::new(0),
+            clock: EventClock::new(),
+        }
+    }
+
+    /// Stores `callback` and returns the ID allocated for it. IDs start at 1.
+    pub fn add_callback(&mut self, mut callback: Box<T>) -> u32 {
+        self.last_id += 1;
+        let id = self.last_id;
+
+        let tx = self.tx.clone();
+        let disconnected_message = self.disconnected_message;
+        let watch_id = callback.register_disconnect(Box::new(move |_| {
+            let tx = tx.clone();
+            topstack::get_runtime().spawn(async move {
+                let _ = tx.send(disconnected_message(id)).await;
+            });
+        }));
+
+        self.callbacks.insert(id, Entry { watch_id, callback, failures: AtomicU32::new(0) });
+        id
+    }
+
+    /// Removes the callback with `id` and stops watching for its disconnection. Returns false if
+    /// there was no such callback.
+    pub fn remove_callback(&mut self, id: u32) -> bool {
+        match self.callbacks.remove(&id) {
+            Some(mut entry) => {
+                entry.callback.unregister(entry.watch_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_by_id(&self, id: u32) -> Option<&Box<T>> {
+        self.callbacks.get(&id).map(|entry| &entry.callback)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Box<T>> {
+        self.callbacks.values().map(|entry| &entry.callback)
+    }
+
+    /// Calls `f` on every registered callback, as one delivery of the interface. The stamp of the
+    /// delivery goes with the first call `f` makes on each callback.
+    pub fn for_all_callbacks<F: Fn(&Box<T>)>(&self, f: F) {
+        let sequence = self.last_sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        let timestamp_us = self.clock.now_us();
+        for (id, entry) in self.callbacks.iter() {
+            entry.callback.set_event_stamp(sequence, timestamp_us);
+            self.call_isolated(*id, entry, &f);
+        }
+    }
+
+    /// Calls `f` on the callback with `id`. Returns false if there is no such callback, true
+    /// otherwise, even if `f` panicked.
+    pub fn call_by_id<F: FnOnce(&Box<T>)>(&self, id: u32, f: F) -> bool {
+        match self.callbacks.get(&id) {
+            Some(entry) => {
+                self.call_isolated(id, entry, f);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn call_isolated<F: FnOnce(&Box<T>)>(&self, id: u32, entry: &Entry<T>, f: F) {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(|| f(&entry.callback))) {
+            Ok(()) => {
+                entry.failures.store(0, Ordering::Relaxed);
+                return;
+            }
+            Err(payload) => payload,
+        };
+
+        let failures = entry.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        error!(
+            "Callback {} ({}) panicked, {} time(s) in a row: {}",
+            id,
+            entry.callback.get_object_id(),
+            failures,
+            panic_message(&*payload)
+        );
+
+        // Only the call that reaches the limit queues the removal.
+        if failures == MAX_CONSECUTIVE_FAILURES {
+            warn!("Dropping callback {} ({})", id, entry.callback.get_object_id());
+            queue_message(&self.tx, (self.disconnected_message)(id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc::channel;
+
+    struct TestCallback {
+        name: String,
+        stamps: Mutex<Vec<(u64, u64)>>,
+    }
+
+    impl TestCallback {
+        fn new(name: &str) -> Box<Self> {
+            Box::new(TestCallback { name: String::from(name), stamps: Mutex::new(vec![]) })
+        }
+    }
+
+    impl RPCProxy for TestCallback {
+        fn get_object_id(&self) -> String {
+            self.name.clone()
+        }
+
+        fn set_event_stamp(&self, sequence: u64, timestamp_us: u64) {
+            self.stamps.lock().unwrap().push((sequence, timestamp_us));
+        }
+    }
+
+    #[test]
+    fn test_add_remove_callbacks() {
+        let (tx, _rx) = channel(1);
+        let mut callbacks: Callbacks<TestCallback> =
+            Callbacks::new(tx, Message::SuspendCallbackDisconnected);
+
+        // Callbacks whose RPC layer doesn't hand out IDs still get distinct ones.
+        let id1 = callbacks.add_callback(TestCallback::new("cb1"));
+        let id2 = callbacks.add_callback(TestCallback::new("cb2"));
+        assert_ne!(id1, id2);
+        assert_eq!(callbacks.get_by_id(id2).unwrap().get_object_id(), "cb2");
+        assert_eq!(callbacks.iter().count(), 2);
+
+        assert!(callbacks.remove_callback(id1));
+        assert!(!callbacks.remove_callback(id1));
+        assert!(callbacks.get_by_id(id1).is_none());
+        assert_eq!(callbacks.iter().count(), 1);
+
+        // IDs aren't reused after a removal.
+        let id3 = callbacks.add_callback(TestCallback::new("cb3"));
+        assert_ne!(id3, id1);
+    }
+
+    #[test]
+    fn test_for_all_callbacks_stamps() {
+        let (tx, _rx) = channel(1);
+        let mut callbacks: Callbacks<TestCallback> =
+            Callbacks::new(tx, Message::SuspendCallbackDisconnected);
+        let id1 = callbacks.add_callback(TestCallback::new("cb1"));
+
+        callbacks.for_all_callbacks(|_| {});
+        let id2 = callbacks.add_callback(TestCallback::new("cb2"));
+        callbacks.for_all_callbacks(|_| {});
+
+        // Both callbacks get the same stamp for a delivery, and only see the ones made since they
+        // were added.
+        let stamps1 = callbacks.get_by_id(id1).unwrap().stamps.lock().unwrap().clone();
+        let stamps2 = callbacks.get_by_id(id2).unwrap().stamps.lock().unwrap().clone();
+        assert_eq!(stamps1.iter().map(|(sequence, _)| *sequence).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(stamps2, stamps1[1..]);
+        assert!(stamps1[0].1 <= stamps1[1].1);
+    }
+
+    #[test]
+    fn test_panicking_callbacks() {
+        let (tx, mut rx) = channel(1);
+        let mut callbacks: Callbacks<TestCallback> =
+            Callbacks::new(tx, Message::SuspendCallbackDisconnected);
+        let bad = callbacks.add_callback(TestCallback::new("bad"));
+        let good = callbacks.add_callback(TestCallback::new("good"));
+
+        // A call that doesn't panic starts the count over.
+        for _ in 1..MAX_CONSECUTIVE_FAILURES {
+            assert!(callbacks.call_by_id(bad, |_| panic!("bad callback")));
+        }
+        assert!(callbacks.call_by_id(bad, |_| {}));
+        assert!(rx.try_recv().is_err());
+
+        let delivered = Mutex::new(vec![]);
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            callbacks.for_all_callbacks(|callback| {
+                if callback.get_object_id() == "bad" {
+                    panic!("bad callback");
+                }
+                delivered.lock().unwrap().push(callback.get_object_id());
+            });
+        }
+
+        // The other callbacks got every delivery, and the one that kept panicking is on its way
+        // out.
+        assert_eq!(delivered.lock().unwrap().len(), MAX_CONSECUTIVE_FAILURES as usize);
+        assert!(matches!(rx.try_recv(), Ok(Message::SuspendCallbackDisconnected(id)) if id == bad));
+        assert!(rx.try_recv().is_err());
+        assert!(callbacks.call_by_id(good, |_| {}));
+        assert!(!callbacks.call_by_id(bad + good, |_| {}));
+    }
+}
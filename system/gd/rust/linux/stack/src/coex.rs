@@ -0,0 +1,316 @@
+//! Coexistence with Wi-Fi (IBluetoothCoex).
+//!
+//! Bluetooth and 2.4 GHz Wi-Fi share the band, and often the antenna. The platform connectivity
+//! manager knows which channels Wi-Fi is on and when something latency-sensitive, like a call,
+//! runs over it, and tells the stack here:
+//!
+//! * The Bluetooth channels under the Wi-Fi channels are marked bad in the host channel
+//!   classification, so that adaptive frequency hopping on BR/EDR links and the channel maps of
+//!   LE connections avoid them.
+//! * While Wi-Fi is latency-sensitive, LE scans listen for a small part of their interval only.
+//!
+//! The controller forgets the classification on reset, so it is sent again whenever the adapter
+//! turns on.
+
+use bt_topshim::btif::BluetoothInterface;
+use dbus_macros::dbus_interface;
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+
+use crate::radio_arbiter::RadioArbiter;
+
+/// Bandwidths of the Wi-Fi channels, in MHz.
+const WIFI_BANDWIDTHS_MHZ: [u32; 5] = [20, 40, 80, 160, 320];
+
+/// Margin kept between a Wi-Fi channel and the Bluetooth channels used, in kHz, for the edges of
+/// the Wi-Fi spectrum mask.
+const GUARD_KHZ: u32 = 1000;
+
+/// BR/EDR channels are 1 MHz wide, from 2402 MHz.
+const BREDR_CHANNELS: usize = 79;
+const BREDR_CHANNEL_HALF_WIDTH_KHZ: u32 = 500;
+
+/// AFH needs this many channels left to hop on.
+const BREDR_MIN_CHANNELS: usize = 20;
+
+/// LE data channels are 2 MHz wide, numbered around the advertising channel at 2426 MHz.
+const LE_DATA_CHANNELS: usize = 37;
+const LE_CHANNEL_HALF_WIDTH_KHZ: u32 = 1000;
+
+/// LE connections need this many channels left in their channel map.
+const LE_MIN_CHANNELS: usize = 2;
+
+/// Most of each interval scans listen for while Wi-Fi is latency-sensitive, in percent.
+const LATENCY_SENSITIVE_SCAN_PERCENT: u32 = 10;
+
+/// Defines the coexistence API.
+#[dbus_interface(bluetooth_coex_dbus, "org.chromium.bluetooth.Coex")]
+pub trait IBluetoothCoex {
+    /// Tells which channels Wi-Fi is on, replacing those told before. Bluetooth avoids the ones
+    /// in the 2.4 GHz band; an empty list clears them.
+    ///
+    /// Returns false, and keeps the channels told before, if a bandwidth isn't one Wi-Fi uses.
+    #[dbus_method("SetWifiChannels")]
+    fn set_wifi_channels(&mut self, channels: Vec<WifiChannel>) -> bool;
+
+    /// Tells whether Wi-Fi is carrying latency-sensitive traffic. While it is, scans are duty
+    /// cycled to leave it the antenna.
+    #[dbus_method("SetLatencySensitive")]
+    fn set_latency_sensitive(&mut self, active: bool);
+
+    /// Returns what the stack was told, and what it does about it.
+    #[dbus_method("GetCoexState")]
+    fn get_coex_state(&self) -> CoexState;
+}
+
+/// A channel Wi-Fi is on.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WifiChannel {
+    /// Center frequency of the whole channel, in MHz.
+    pub frequency_mhz: u32,
+    pub bandwidth_mhz: u32,
+}
+
+/// The coexistence state, for debugging.
+#[derive(Clone, Debug, Default)]
+pub struct CoexState {
+    pub wifi_channels: Vec<WifiChannel>,
+    pub latency_sensitive: bool,
+    /// Host channel classification, with a bit set for each channel that may be used: 10 octets
+    /// for BR/EDR and 5 for the LE data channels, the lowest channel in the lowest bit.
+    pub bredr_channel_map: Vec<u8>,
+    pub le_channel_map: Vec<u8>,
+    /// Most of each interval scans listen for, in percent. 100 if they aren't limited.
+    pub scan_limit_percent: u32,
+    /// Whether the controller took the classification. False while the adapter is off.
+    pub channel_classification_applied: bool,
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum CoexActions {
+    AdapterOn,
+    AdapterOff,
+}
+
+/// Center frequency of BR/EDR channel `channel`, in kHz.
+fn bredr_channel_khz(channel: usize) -> u32 {
+    (2402 + channel as u32) * 1000
+}
+
+/// Center frequency of LE data channel `channel`, in kHz. The advertising channel 38 at 2426 MHz
+/// sits between data channels 10 and 11.
+fn le_channel_khz(channel: usize) -> u32 {
+    let channel = channel as u32;
+    match channel {
+        0..=10 => (2404 + 2 * channel) * 1000,
+        _ => (2428 + 2 * (channel - 11)) * 1000,
+    }
+}
+
+/// Whether a Bluetooth channel of `half_width_khz` at `center_khz` is too close to one of
+/// `wifi_channels`.
+fn overlaps_wifi(center_khz: u32, half_width_khz: u32, wifi_channels: &[WifiChannel]) -> bool {
+    wifi_channels.iter().any(|wifi| {
+        let center = wifi.frequency_mhz * 1000;
+        let half_width = wifi.bandwidth_mhz * 500 + GUARD_KHZ;
+        center_khz + half_width_khz > center.saturating_sub(half_width)
+            && center_khz.saturating_sub(half_width_khz) < center + half_width
+    })
+}
+
+/// Builds a channel map of `count` channels with the bits of those too close to Wi-Fi cleared.
+/// When fewer than `min_channels` would be left, all channels are kept: a crowded map does more
+/// harm than the interference.
+fn channel_map<const N: usize>(
+    count: usize,
+    min_channels: usize,
+    channel_khz: fn(usize) -> u32,
+    half_width_khz: u32,
+    wifi_channels: &[WifiChannel],
+) -> [u8; N] {
+    let usable: Vec<bool> = (0..count)
+        .map(|channel| !overlaps_wifi(channel_khz(channel), half_width_khz, wifi_channels))
+        .collect();
+    let keep_all = usable.iter().filter(|used| **used).count() < min_channels;
+    if keep_all {
+        warn!("Too few channels left beside Wi-Fi {:?}, using them all", wifi_channels);
+    }
+
+    let mut map = [0u8; N];
+    for (channel, used) in usable.into_iter().enumerate() {
+        if used || keep_all {
+            map[channel / 8] |= 1 << (channel % 8);
+        }
+    }
+    map
+}
+
+fn bredr_channel_map(wifi_channels: &[WifiChannel]) -> [u8; 10] {
+    channel_map(
+        BREDR_CHANNELS,
+        BREDR_MIN_CHANNELS,
+        bredr_channel_khz,
+        BREDR_CHANNEL_HALF_WIDTH_KHZ,
+        wifi_channels,
+    )
+}
+
+fn le_channel_map(wifi_channels: &[WifiChannel]) -> [u8; 5] {
+    channel_map(
+        LE_DATA_CHANNELS,
+        LE_MIN_CHANNELS,
+        le_channel_khz,
+        LE_CHANNEL_HALF_WIDTH_KHZ,
+        wifi_channels,
+    )
+}
+
+/// Implementation of the coexistence API.
+pub struct BluetoothCoex {
+    intf: Arc<Mutex<BluetoothInterface>>,
+    radio: Arc<Mutex<RadioArbiter>>,
+    wifi_channels: Vec<WifiChannel>,
+    latency_sensitive: bool,
+    adapter_on: bool,
+    classification_applied: bool,
+}
+
+impl BluetoothCoex {
+    pub fn new(intf: Arc<Mutex<BluetoothInterface>>, radio: Arc<Mutex<RadioArbiter>>) -> Self {
+        BluetoothCoex {
+            intf,
+            radio,
+            wifi_channels: vec![],
+            latency_sensitive: false,
+            adapter_on: false,
+            classification_applied: false,
+        }
+    }
+
+    pub fn dispatch_coex_actions(&mut self, action: CoexActions) {
+        match action {
+            CoexActions::AdapterOn => {
+                self.adapter_on = true;
+                self.apply_channel_classification();
+            }
+            CoexActions::AdapterOff => {
+                self.adapter_on = false;
+                self.classification_applied = false;
+            }
+        }
+    }
+
+    fn apply_channel_classification(&mut self) {
+        if !self.adapter_on {
+            return;
+        }
+
+        let bredr_map = bredr_channel_map(&self.wifi_channels);
+        let le_map = le_channel_map(&self.wifi_channels);
+        self.classification_applied =
+            self.intf.lock().unwrap().set_host_channel_classification(&bredr_map, &le_map);
+        if !self.classification_applied {
+            warn!("Controller doesn't take the host channel classification");
+        }
+    }
+}
+
+impl IBluetoothCoex for BluetoothCoex {
+    fn set_wifi_channels(&mut self, channels: Vec<WifiChannel>) -> bool {
+        if let Some(channel) =
+            channels.iter().find(|channel| !WIFI_BANDWIDTHS_MHZ.contains(&channel.bandwidth_mhz))
+        {
+            warn!("Ignoring Wi-Fi channels with a bandwidth of {} MHz", channel.bandwidth_mhz);
+            return false;
+        }
+
+        if channels != self.wifi_channels {
+            info!("Wi-Fi is now on {:?}", channels);
+            self.wifi_channels = channels;
+            self.apply_channel_classification();
+        }
+        true
+    }
+
+    fn set_latency_sensitive(&mut self, active: bool) {
+        if active == self.latency_sensitive {
+            return;
+        }
+
+        info!("Wi-Fi latency-sensitive: {}", active);
+        self.latency_sensitive = active;
+        let limit = Some(LATENCY_SENSITIVE_SCAN_PERCENT).filter(|_| active);
+        self.radio.lock().unwrap().set_coex_scan_limit(limit);
+    }
+
+    fn get_coex_state(&self) -> CoexState {
+        CoexState {
+            wifi_channels: self.wifi_channels.clone(),
+            latency_sensitive: self.latency_sensitive,
+            bredr_channel_map: bredr_channel_map(&self.wifi_channels).to_vec(),
+            le_channel_map: le_channel_map(&self.wifi_channels).to_vec(),
+            scan_limit_percent: match self.latency_sensitive {
+                true => LATENCY_SENSITIVE_SCAN_PERCENT,
+                false => 100,
+            },
+            channel_classification_applied: self.classification_applied,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wifi(frequency_mhz: u32, bandwidth_mhz: u32) -> WifiChannel {
+        WifiChannel { frequency_mhz, bandwidth_mhz }
+    }
+
+    fn used_channels(map: &[u8], count: usize) -> Vec<usize> {
+        (0..count).filter(|channel| map[channel / 8] & (1 << (channel % 8)) != 0).collect()
+    }
+
+    #[test]
+    fn test_channel_frequencies() {
+        assert_eq!(bredr_channel_khz(0), 2_402_000);
+        assert_eq!(bredr_channel_khz(78), 2_480_000);
+        assert_eq!(le_channel_khz(0), 2_404_000);
+        assert_eq!(le_channel_khz(10), 2_424_000);
+        assert_eq!(le_channel_khz(11), 2_428_000);
+        assert_eq!(le_channel_khz(36), 2_478_000);
+    }
+
+    #[test]
+    fn test_channel_maps_avoid_wifi() {
+        // Without Wi-Fi all channels are used, and the reserved bits stay cleared.
+        assert_eq!(
+            bredr_channel_map(&[]),
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]
+        );
+        assert_eq!(le_channel_map(&[]), [0xff, 0xff, 0xff, 0xff, 0x1f]);
+
+        // Wi-Fi channel 6 spans 2427 to 2447 MHz.
+        let channel_6 = [wifi(2437, 20)];
+        let bredr: Vec<usize> = (0..BREDR_CHANNELS).filter(|c| !(24..=46).contains(c)).collect();
+        assert_eq!(used_channels(&bredr_channel_map(&channel_6), BREDR_CHANNELS), bredr);
+        let le: Vec<usize> = (0..LE_DATA_CHANNELS).filter(|c| !(11..=21).contains(c)).collect();
+        assert_eq!(used_channels(&le_channel_map(&channel_6), LE_DATA_CHANNELS), le);
+
+        // 5 GHz Wi-Fi leaves the band alone.
+        assert_eq!(le_channel_map(&[wifi(5180, 80)]), le_channel_map(&[]));
+    }
+
+    #[test]
+    fn test_channel_maps_keep_enough_channels() {
+        // With channels 1, 6 and 11 taken, too few BR/EDR channels are left for AFH.
+        let busy = [wifi(2412, 20), wifi(2437, 20), wifi(2462, 20)];
+        assert_eq!(bredr_channel_map(&busy), bredr_channel_map(&[]));
+        assert_eq!(
+            used_channels(&le_channel_map(&busy), LE_DATA_CHANNELS),
+            vec![10, 22, 34, 35, 36]
+        );
+        let crowded = [wifi(2442, 80)];
+        assert_eq!(le_channel_map(&crowded), le_channel_map(&[]));
+    }
+}
@@ -0,0 +1,684 @@
+//! Persistent store for remote device information.
+//!
+//! The store reads and writes the same INI format that libbluetooth uses for `bt_config.conf`, so
+//! entries written here are picked up by the native stack on its next start. It also knows how
+//! to import devices paired with BlueZ from `/var/lib/bluetooth/<adapter>/<device>/info`.
+//!
+//! libbluetooth keeps the config in memory while it's running and overwrites the file, so any
+//! modifications should only be made while the adapter is disabled.
+
+use bt_topshim::btif::RawAddress;
+use log::warn;
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where libbluetooth keeps its config on Linux.
+pub const DEFAULT_CONFIG_PATH: &str = "/var/lib/bluetooth/bt_config.conf";
+
+/// Where Floss keeps device settings libbluetooth doesn't know about. Unlike `bt_config.conf`,
+/// this file is owned by the Rust stack and can be written while the adapter is running.
+pub const FLOSS_CONFIG_PATH: &str = "/var/lib/bluetooth/floss_config.conf";
+
+/// Where BlueZ keeps its per-adapter storage.
+pub const BLUEZ_STORAGE_DIR: &str = "/var/lib/bluetooth";
+
+// Keys used by the native stack (see btif_storage.cc).
+const KEY_NAME: &str = "Name";
+const KEY_DEV_CLASS: &str = "DevClass";
+const KEY_DEV_TYPE: &str = "DevType";
+const KEY_ADDR_TYPE: &str = "AddrType";
+const KEY_LINK_KEY: &str = "LinkKey";
+const KEY_LINK_KEY_TYPE: &str = "LinkKeyType";
+const KEY_PIN_LENGTH: &str = "PinLength";
+const KEY_LE_PENC: &str = "LE_KEY_PENC";
+const KEY_LE_PID: &str = "LE_KEY_PID";
+
+// Floss-only keys. Comma separated list of profile UUIDs the user has disabled for this device,
+// whether connections from the device are rejected, and whether it opted out of reconnection.
+const KEY_DISABLED_PROFILES: &str = "FlossDisabledProfiles";
+const KEY_BLOCKED: &str = "FlossBlocked";
+const KEY_NO_RECONNECT: &str = "FlossNoReconnect";
+
+// Section for settings of the adapter rather than of a device, named like the one libbluetooth
+// keeps in its own config.
+const SECTION_ADAPTER: &str = "Adapter";
+
+// Values of DevType in the native stack.
+const DEV_TYPE_BREDR: u32 = 1;
+const DEV_TYPE_BLE: u32 = 2;
+const DEV_TYPE_DUAL: u32 = 3;
+
+// Values of sec_level in LE_KEY_PENC.
+const LE_SEC_UNAUTHENTICATED: u8 = 1;
+const LE_SEC_AUTHENTICATED: u8 = 2;
+
+/// BR/EDR link key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkKey {
+    pub key: [u8; 16],
+    pub key_type: u8,
+    pub pin_length: u8,
+}
+
+/// LE long term key received from the peer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LongTermKey {
+    pub key: [u8; 16],
+    pub rand: [u8; 8],
+    pub ediv: u16,
+    pub authenticated: bool,
+    pub key_size: u8,
+}
+
+impl LongTermKey {
+    /// Serializes to the layout of `tBTM_LE_PENC_KEYS`.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.key.to_vec();
+        bytes.extend_from_slice(&self.rand);
+        bytes.extend_from_slice(&self.ediv.to_le_bytes());
+        bytes.push(if self.authenticated { LE_SEC_AUTHENTICATED } else { LE_SEC_UNAUTHENTICATED });
+        bytes.push(self.key_size);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<LongTermKey> {
+        if bytes.len() != 28 {
+            return None;
+        }
+
+        let mut ltk = LongTermKey::default();
+        ltk.key.copy_from_slice(&bytes[0..16]);
+        ltk.rand.copy_from_slice(&bytes[16..24]);
+        ltk.ediv = u16::from_le_bytes([bytes[24], bytes[25]]);
+        ltk.authenticated = bytes[26] == LE_SEC_AUTHENTICATED;
+        ltk.key_size = bytes[27];
+        Some(ltk)
+    }
+}
+
+/// LE identity resolving key along with the identity address it resolves to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IdentityKey {
+    pub irk: [u8; 16],
+    pub address_type: u8,
+    pub address: RawAddress,
+}
+
+impl IdentityKey {
+    /// Serializes to the layout of `tBTM_LE_PID_KEYS`.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.irk.to_vec();
+        bytes.push(self.address_type);
+        bytes.extend_from_slice(&self.address.val);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<IdentityKey> {
+        if bytes.len() != 23 {
+            return None;
+        }
+
+        let mut key = IdentityKey::default();
+        key.irk.copy_from_slice(&bytes[0..16]);
+        key.address_type = bytes[16];
+        key.address = RawAddress::from_bytes(&bytes[17..23])?;
+        Some(key)
+    }
+}
+
+/// Everything the store knows about a single remote device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StoredDevice {
+    pub address: RawAddress,
+    pub name: Option<String>,
+    pub class_of_device: Option<u32>,
+    /// Native device type: 1 = BR/EDR, 2 = LE, 3 = dual mode.
+    pub device_type: Option<u32>,
+    /// LE address type: 0 = public, 1 = random.
+    pub address_type: Option<u8>,
+    pub link_key: Option<LinkKey>,
+    pub ltk: Option<LongTermKey>,
+    pub identity_key: Option<IdentityKey>,
+    /// Profile UUIDs (in string form) that shouldn't be connected for this device.
+    pub disabled_profiles: HashSet<String>,
+    /// Incoming connections and pairing attempts from this device are rejected.
+    pub blocked: bool,
+    /// The stack doesn't connect the device again by itself when its link is lost.
+    pub no_reconnect: bool,
+}
+
+/// A section of the config file, keeping the order and any keys we don't interpret.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Section {
+    pub(crate) name: String,
+    pub(crate) entries: Vec<(String, String)>,
+}
+
+impl Section {
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: String) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+
+    fn set_or_remove(&mut self, key: &str, value: Option<String>) {
+        match value {
+            Some(v) => self.set(key, v),
+            None => self.remove(key),
+        }
+    }
+}
+
+/// Persistent store of remote devices backed by a `bt_config.conf` style file.
+pub struct DeviceStore {
+    path: PathBuf,
+    sections: Vec<Section>,
+}
+
+impl DeviceStore {
+    /// Creates an empty store that will be saved to `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> DeviceStore {
+        DeviceStore { path: path.as_ref().to_path_buf(), sections: vec![] }
+    }
+
+    /// Loads the store from `path`. A missing file results in an empty store.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<DeviceStore> {
+        let path = path.as_ref().to_path_buf();
+        let sections = match fs::read_to_string(&path) {
+            Ok(contents) => parse_ini(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+
+        Ok(DeviceStore { path, sections })
+    }
+
+    /// Writes the store back to disk. The file is replaced atomically so a crash never leaves a
+    /// partially written config behind.
+    pub fn save(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, to_ini(&self.sections))?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// Returns all stored devices.
+    pub fn get_devices(&self) -> Vec<StoredDevice> {
+        self.sections.iter().filter_map(section_to_device).collect()
+    }
+
+    /// Returns the stored device with this address, if any.
+    pub fn get_device(&self, address: &RawAddress) -> Option<StoredDevice> {
+        self.find_section(address).and_then(section_to_device)
+    }
+
+    /// Adds or updates a device. Keys the store doesn't know about are kept as they are.
+    pub fn set_device(&mut self, device: &StoredDevice) {
+        let name = section_name(&device.address);
+        let section = match self.sections.iter().position(|s| s.name == name) {
+            Some(i) => &mut self.sections[i],
+            None => {
+                self.sections.push(Section { name, entries: vec![] });
+                self.sections.last_mut().unwrap()
+            }
+        };
+
+        section.set_or_remove(KEY_NAME, device.name.clone());
+        section.set_or_remove(KEY_DEV_CLASS, device.class_of_device.map(|c| c.to_string()));
+        section.set_or_remove(KEY_DEV_TYPE, device.device_type.map(|t| t.to_string()));
+        section.set_or_remove(KEY_ADDR_TYPE, device.address_type.map(|t| t.to_string()));
+
+        match &device.link_key {
+            Some(lk) => {
+                section.set(KEY_LINK_KEY, to_hex(&lk.key));
+                section.set(KEY_LINK_KEY_TYPE, lk.key_type.to_string());
+                section.set(KEY_PIN_LENGTH, lk.pin_length.to_string());
+            }
+            None => {
+                section.remove(KEY_LINK_KEY);
+                section.remove(KEY_LINK_KEY_TYPE);
+                section.remove(KEY_PIN_LENGTH);
+            }
+        }
+
+        section.set_or_remove(KEY_LE_PENC, device.ltk.as_ref().map(|k| to_hex(&k.to_bytes())));
+        section
+            .set_or_remove(KEY_LE_PID, device.identity_key.as_ref().map(|k| to_hex(&k.to_bytes())));
+
+        let mut disabled: Vec<&String> = device.disabled_profiles.iter().collect();
+        disabled.sort();
+        section.set_or_remove(
+            KEY_DISABLED_PROFILES,
+            if disabled.is_empty() {
+                None
+            } else {
+                Some(disabled.iter().map(|s| s.as_str()).collect::<Vec<&str>>().join(","))
+            },
+        );
+        section.set_or_remove(
+            KEY_BLOCKED,
+            if device.blocked { Some(String::from("1")) } else { None },
+        );
+        section.set_or_remove(
+            KEY_NO_RECONNECT,
+            if device.no_reconnect { Some(String::from("1")) } else { None },
+        );
+    }
+
+    /// Removes a device from the store. Returns false if it wasn't there.
+    pub fn remove_device(&mut self, address: &RawAddress) -> bool {
+        let name = section_name(address);
+        let len = self.sections.len();
+        self.sections.retain(|s| s.name != name);
+        len != self.sections.len()
+    }
+
+    /// Returns an adapter setting saved with `set_adapter_value`.
+    pub fn get_adapter_value(&self, key: &str) -> Option<String> {
+        self.sections.iter().find(|s| s.name == SECTION_ADAPTER)?.get(key).map(String::from)
+    }
+
+    /// Sets an adapter setting, or removes it if `value` is None.
+    pub fn set_adapter_value(&mut self, key: &str, value: Option<String>) {
+        match self.sections.iter_mut().find(|s| s.name == SECTION_ADAPTER) {
+            Some(section) => section.set_or_remove(key, value),
+            None => {
+                if let Some(value) = value {
+                    let entries = vec![(key.to_string(), value)];
+                    self.sections.push(Section { name: SECTION_ADAPTER.to_string(), entries });
+                }
+            }
+        }
+        self.sections.retain(|s| s.name != SECTION_ADAPTER || !s.entries.is_empty());
+    }
+
+    /// Returns the sections of the devices with a link key or an LTK, whole, including the keys
+    /// the store doesn't interpret.
+    pub(crate) fn get_bonded_sections(&self) -> Vec<Section> {
+        self.sections.iter().filter(|s| is_bonded_section(s)).cloned().collect()
+    }
+
+    /// Adds the section of a device, replacing the one of the same name. Returns false if the
+    /// section isn't the one of a bonded device.
+    pub(crate) fn set_bonded_section(&mut self, section: Section) -> bool {
+        if !is_bonded_section(&section) {
+            return false;
+        }
+
+        match self.sections.iter_mut().find(|s| s.name == section.name) {
+            Some(existing) => *existing = section,
+            None => self.sections.push(section),
+        }
+        true
+    }
+
+    fn find_section(&self, address: &RawAddress) -> Option<&Section> {
+        let name = section_name(address);
+        self.sections.iter().find(|s| s.name == name)
+    }
+}
+
+/// Imports all devices paired with BlueZ under `bluez_dir` into the store. Devices already in the
+/// store are updated with the BlueZ data.
+///
+/// Returns the number of imported devices.
+pub fn import_bluez<P: AsRef<Path>>(store: &mut DeviceStore, bluez_dir: P) -> io::Result<usize> {
+    let mut count = 0;
+
+    for adapter in fs::read_dir(bluez_dir)? {
+        let adapter = adapter?.path();
+        if !adapter.is_dir() || !is_address_dir(&adapter) {
+            continue;
+        }
+
+        for device in fs::read_dir(&adapter)? {
+            let device = device?.path();
+            if !device.is_dir() || !is_address_dir(&device) {
+                continue;
+            }
+
+            let address = device
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(RawAddress::from_string)
+                .unwrap();
+
+            let info = match fs::read_to_string(device.join("info")) {
+                Ok(info) => info,
+                Err(e) => {
+                    warn!("Skipping {}: can't read info ({})", address.to_string(), e);
+                    continue;
+                }
+            };
+
+            match bluez_info_to_device(address, &info) {
+                Some(d) => {
+                    store.set_device(&d);
+                    count += 1;
+                }
+                None => warn!("Skipping {}: not paired", address.to_string()),
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+fn is_address_dir(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).and_then(RawAddress::from_string).is_some()
+}
+
+/// Converts the contents of a BlueZ device `info` file. Returns None if the device has no keys.
+fn bluez_info_to_device(address: RawAddress, info: &str) -> Option<StoredDevice> {
+    let sections = parse_ini(info);
+    let get = |section: &str, key: &str| -> Option<String> {
+        sections.iter().find(|s| s.name == section).and_then(|s| s.get(key)).map(String::from)
+    };
+
+    let mut device = StoredDevice { address, ..Default::default() };
+
+    device.name = get("General", "Name");
+    device.class_of_device = get("General", "Class")
+        .and_then(|c| u32::from_str_radix(c.trim_start_matches("0x"), 16).ok());
+
+    let technologies = get("General", "SupportedTechnologies").unwrap_or_default();
+    let bredr = technologies.split(';').any(|t| t == "BR/EDR");
+    let le = technologies.split(';').any(|t| t == "LE");
+    device.device_type = match (bredr, le) {
+        (true, true) => Some(DEV_TYPE_DUAL),
+        (true, false) => Some(DEV_TYPE_BREDR),
+        (false, true) => Some(DEV_TYPE_BLE),
+        (false, false) => None,
+    };
+    device.address_type = match get("General", "AddressType").as_deref() {
+        Some("public") => Some(0),
+        Some("static") => Some(1),
+        _ => None,
+    };
+
+    // BlueZ and libbluetooth both store keys in the byte order they're exchanged on the air.
+    if let Some(key) = get("LinkKey", "Key").and_then(|k| from_hex_array::<16>(&k)) {
+        device.link_key = Some(LinkKey {
+            key,
+            key_type: get("LinkKey", "Type").and_then(|t| t.parse().ok()).unwrap_or(0),
+            pin_length: get("LinkKey", "PINLength").and_then(|p| p.parse().ok()).unwrap_or(0),
+        });
+    }
+
+    // Older BlueZ versions use [LongTermKey] for everything; newer ones split out the key used
+    // while we're the peripheral.
+    let ltk_section = ["LongTermKey", "PeripheralLongTermKey", "SlaveLongTermKey"]
+        .iter()
+        .copied()
+        .find(|s| get(s, "Key").is_some());
+    if let Some(section) = ltk_section {
+        if let Some(key) = get(section, "Key").and_then(|k| from_hex_array::<16>(&k)) {
+            let rand: u64 = get(section, "Rand").and_then(|r| r.parse().ok()).unwrap_or(0);
+            device.ltk = Some(LongTermKey {
+                key,
+                rand: rand.to_le_bytes(),
+                ediv: get(section, "EDiv").and_then(|e| e.parse().ok()).unwrap_or(0),
+                authenticated: get(section, "Authenticated")
+                    .and_then(|a| a.parse::<u8>().ok())
+                    .unwrap_or(0)
+                    != 0,
+                key_size: get(section, "EncSize").and_then(|e| e.parse().ok()).unwrap_or(16),
+            });
+        }
+    }
+
+    if let Some(irk) = get("IdentityResolvingKey", "Key").and_then(|k| from_hex_array::<16>(&k)) {
+        device.identity_key =
+            Some(IdentityKey { irk, address_type: device.address_type.unwrap_or(0), address });
+    }
+
+    if device.link_key.is_none() && device.ltk.is_none() {
+        return None;
+    }
+
+    Some(device)
+}
+
+fn section_name(address: &RawAddress) -> String {
+    address.to_string().to_lowercase()
+}
+
+fn section_to_device(section: &Section) -> Option<StoredDevice> {
+    let address = RawAddress::from_string(section.name.clone())?;
+
+    let link_key =
+        section.get(KEY_LINK_KEY).and_then(|k| from_hex_array::<16>(k)).map(|key| LinkKey {
+            key,
+            key_type: section.get(KEY_LINK_KEY_TYPE).and_then(|t| t.parse().ok()).unwrap_or(0),
+            pin_length: section.get(KEY_PIN_LENGTH).and_then(|p| p.parse().ok()).unwrap_or(0),
+        });
+
+    Some(StoredDevice {
+        address,
+        name: section.get(KEY_NAME).map(|n| n.to_string()),
+        class_of_device: section.get(KEY_DEV_CLASS).and_then(|c| c.parse().ok()),
+        device_type: section.get(KEY_DEV_TYPE).and_then(|t| t.parse().ok()),
+        address_type: section.get(KEY_ADDR_TYPE).and_then(|t| t.parse().ok()),
+        link_key,
+        ltk: section.get(KEY_LE_PENC).and_then(from_hex).and_then(|b| LongTermKey::from_bytes(&b)),
+        identity_key: section
+            .get(KEY_LE_PID)
+            .and_then(from_hex)
+            .and_then(|b| IdentityKey::from_bytes(&b)),
+        disabled_profiles: section
+            .get(KEY_DISABLED_PROFILES)
+            .map(|p| p.split(',').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect())
+            .unwrap_or_default(),
+        blocked: section.get(KEY_BLOCKED).map_or(false, |b| b == "1"),
+        no_reconnect: section.get(KEY_NO_RECONNECT).map_or(false, |b| b == "1"),
+    })
+}
+
+fn is_bonded_section(section: &Section) -> bool {
+    section_to_device(section).map_or(false, |d| d.link_key.is_some() || d.ltk.is_some())
+}
+
+pub(crate) fn parse_ini(contents: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = vec![];
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push(Section { name: line[1..line.len() - 1].to_string(), entries: vec![] });
+            continue;
+        }
+
+        let (key, value) = match line.find('=') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => continue,
+        };
+
+        // Keys before the first section are ignored, the same as libbluetooth does.
+        if let Some(section) = sections.last_mut() {
+            section.entries.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    sections
+}
+
+pub(crate) fn to_ini(sections: &Vec<Section>) -> String {
+    let mut out = String::new();
+    for section in sections {
+        out.push_str(&format!("[{}]\n", section.name));
+        for (key, value) in &section.entries {
+            out.push_str(&format!("{} = {}\n", key, value));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+pub(crate) fn from_hex_array<const N: usize>(s: &str) -> Option<[u8; N]> {
+    let bytes = from_hex(s)?;
+    if bytes.len() != N {
+        return None;
+    }
+
+    let mut arr = [0; N];
+    arr.copy_from_slice(&bytes);
+    Some(arr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUEZ_INFO: &str = "[General]
+Name=Keyboard
+Class=0x000540
+SupportedTechnologies=BR/EDR;LE;
+AddressType=public
+
+[LinkKey]
+Key=00112233445566778899AABBCCDDEEFF
+Type=4
+PINLength=0
+
+[LongTermKey]
+Key=FFEEDDCCBBAA99887766554433221100
+Authenticated=2
+EncSize=16
+EDiv=4660
+Rand=1
+";
+
+    #[test]
+    fn test_bluez_info_to_device() {
+        let address = RawAddress::from_string("11:22:33:44:55:66").unwrap();
+        let device = bluez_info_to_device(address, BLUEZ_INFO).unwrap();
+
+        assert_eq!(device.name, Some(String::from("Keyboard")));
+        assert_eq!(device.class_of_device, Some(0x540));
+        assert_eq!(device.device_type, Some(DEV_TYPE_DUAL));
+        assert_eq!(device.address_type, Some(0));
+
+        let link_key = device.link_key.unwrap();
+        assert_eq!(link_key.key[0], 0x00);
+        assert_eq!(link_key.key[15], 0xff);
+        assert_eq!(link_key.key_type, 4);
+
+        let ltk = device.ltk.unwrap();
+        assert_eq!(ltk.key[0], 0xff);
+        assert_eq!(ltk.ediv, 4660);
+        assert_eq!(ltk.rand, [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(ltk.authenticated);
+
+        // Devices without keys aren't paired and shouldn't be imported.
+        assert!(bluez_info_to_device(address, "[General]\nName=Speaker\n").is_none());
+    }
+
+    #[test]
+    fn test_device_round_trip() {
+        let mut store = DeviceStore { path: PathBuf::new(), sections: vec![] };
+        let address = RawAddress::from_string("11:22:33:44:55:66").unwrap();
+        let device = StoredDevice {
+            address,
+            name: Some(String::from("Mouse")),
+            ltk: Some(LongTermKey { key_size: 16, authenticated: true, ..Default::default() }),
+            identity_key: Some(IdentityKey { address, ..Default::default() }),
+            disabled_profiles: vec![String::from("0000110b-0000-1000-8000-00805f9b34fb")]
+                .into_iter()
+                .collect(),
+            blocked: true,
+            no_reconnect: true,
+            ..Default::default()
+        };
+        store.set_device(&device);
+
+        let reloaded =
+            DeviceStore { path: PathBuf::new(), sections: parse_ini(&to_ini(&store.sections)) };
+        assert_eq!(reloaded.get_device(&address), Some(device));
+
+        assert!(store.remove_device(&address));
+        assert!(store.get_device(&address).is_none());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_kept() {
+        let mut store = DeviceStore {
+            path: PathBuf::new(),
+            sections: parse_ini(
+                "[Adapter]\nAddress = 00:11:22:33:44:55\n\n[11:22:33:44:55:66]\nTimestamp = 1\n",
+            ),
+        };
+        let address = RawAddress::from_string("11:22:33:44:55:66").unwrap();
+        let mut device = store.get_device(&address).unwrap();
+        device.name = Some(String::from("Headset"));
+        store.set_device(&device);
+
+        let ini = to_ini(&store.sections);
+        assert!(ini.contains("Address = 00:11:22:33:44:55"));
+        assert!(ini.contains("Timestamp = 1"));
+        assert!(ini.contains("Name = Headset"));
+    }
+
+    #[test]
+    fn test_bonded_sections() {
+        let mut store = DeviceStore {
+            path: PathBuf::new(),
+            sections: parse_ini(concat!(
+                "[Adapter]\nAddress = 00:11:22:33:44:55\n\n",
+                "[11:22:33:44:55:66]\nName = Speaker\n\n",
+                "[11:22:33:44:55:77]\nLinkKey = 00112233445566778899aabbccddeeff\nService = 1\n",
+            )),
+        };
+        let bonded = store.get_bonded_sections();
+        assert_eq!(bonded.len(), 1);
+        assert_eq!(bonded[0].get("Service"), Some("1"));
+
+        let mut section = bonded[0].clone();
+        section.set("Name", String::from("Keyboard"));
+        assert!(store.set_bonded_section(section));
+        assert_eq!(store.get_bonded_sections()[0].get("Name"), Some("Keyboard"));
+        assert_eq!(store.sections.len(), 3);
+
+        // Neither the adapter nor devices without keys.
+        assert!(!store.set_bonded_section(store.sections[0].clone()));
+        assert!(!store.set_bonded_section(store.sections[1].clone()));
+    }
+
+    #[test]
+    fn test_adapter_values() {
+        let mut store = DeviceStore { path: PathBuf::new(), sections: vec![] };
+        store.set_adapter_value("LePrivacy", Some(String::from("1")));
+        assert_eq!(store.get_adapter_value("LePrivacy"), Some(String::from("1")));
+        assert!(store.get_devices().is_empty());
+
+        store.set_adapter_value("LePrivacy", None);
+        assert!(store.get_adapter_value("LePrivacy").is_none());
+        assert!(store.sections.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+//! What kind of device a peer is, for picking its icon.
+//!
+//! Classic devices tell in their Class of Device, LE devices in the GAP Appearance they advertise.
+//! Both are reduced here to the same few types, so that clients don't each need to know the
+//! assigned numbers. libbluetooth already turns the Appearance of devices found by discovery into
+//! a Class of Device, so devices found either way get their type from it.
+
+/// Kind of device, as far as it matters for its icon.
+#[derive(Clone, Copy, Debug, Default, FromPrimitive, ToPrimitive, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DeviceType {
+    #[default]
+    Unknown = 0,
+    Computer,
+    Tablet,
+    Phone,
+    Watch,
+    Headset,
+    Headphones,
+    Earbuds,
+    Speaker,
+    Microphone,
+    CarAudio,
+    HearingAid,
+    Keyboard,
+    Mouse,
+    Gamepad,
+    Pen,
+    RemoteControl,
+    Display,
+    Camera,
+    Printer,
+    HealthSensor,
+    Tag,
+}
+
+// Major device classes of the Class of Device.
+const COD_MAJOR_COMPUTER: u32 = 0x01;
+const COD_MAJOR_PHONE: u32 = 0x02;
+const COD_MAJOR_AUDIO_VIDEO: u32 = 0x04;
+const COD_MAJOR_PERIPHERAL: u32 = 0x05;
+const COD_MAJOR_IMAGING: u32 = 0x06;
+const COD_MAJOR_WEARABLE: u32 = 0x07;
+const COD_MAJOR_TOY: u32 = 0x08;
+const COD_MAJOR_HEALTH: u32 = 0x09;
+
+// Peripherals have a keyboard and pointing device part, and another for the rest.
+const COD_PERIPHERAL_KEYBOARD: u32 = 0x10;
+const COD_PERIPHERAL_POINTING: u32 = 0x20;
+
+// Imaging devices have a bit set for each of what they are.
+const COD_IMAGING_DISPLAY: u32 = 0x04;
+const COD_IMAGING_CAMERA: u32 = 0x08;
+const COD_IMAGING_PRINTER: u32 = 0x20;
+
+impl DeviceType {
+    /// Type of a classic device of Class of Device `cod`, as in the Baseband assigned numbers.
+    pub fn from_class_of_device(cod: u32) -> DeviceType {
+        let major = (cod >> 8) & 0x1f;
+        let minor = (cod >> 2) & 0x3f;
+        match major {
+            COD_MAJOR_COMPUTER => match minor {
+                // Wearable computer.
+                0x06 => DeviceType::Watch,
+                0x07 => DeviceType::Tablet,
+                _ => DeviceType::Computer,
+            },
+            COD_MAJOR_PHONE => DeviceType::Phone,
+            COD_MAJOR_AUDIO_VIDEO => match minor {
+                // Wearable headset and hands-free device.
+                0x01 | 0x02 => DeviceType::Headset,
+                0x04 => DeviceType::Microphone,
+                // Loudspeaker, portable audio and HiFi audio device.
+                0x05 | 0x07 | 0x0a => DeviceType::Speaker,
+                0x06 => DeviceType::Headphones,
+                0x08 => DeviceType::CarAudio,
+                // Video camera and camcorder.
+                0x0c | 0x0d => DeviceType::Camera,
+                // Video monitor, with or without loudspeaker.
+                0x0e | 0x0f => DeviceType::Display,
+                0x12 => DeviceType::Gamepad,
+                _ => DeviceType::Unknown,
+            },
+            COD_MAJOR_PERIPHERAL => {
+                if minor & COD_PERIPHERAL_KEYBOARD != 0 {
+                    DeviceType::Keyboard
+                } else if minor & COD_PERIPHERAL_POINTING != 0 {
+                    DeviceType::Mouse
+                } else {
+                    match minor & 0x0f {
+                        // Joystick and gamepad.
+                        0x01 | 0x02 => DeviceType::Gamepad,
+                        0x03 => DeviceType::RemoteControl,
+                        0x05 => DeviceType::Tablet,
+                        0x07 => DeviceType::Pen,
+                        _ => DeviceType::Unknown,
+                    }
+                }
+            }
+            COD_MAJOR_IMAGING => {
+                if minor & COD_IMAGING_PRINTER != 0 {
+                    DeviceType::Printer
+                } else if minor & COD_IMAGING_CAMERA != 0 {
+                    DeviceType::Camera
+                } else if minor & COD_IMAGING_DISPLAY != 0 {
+                    DeviceType::Display
+                } else {
+                    DeviceType::Unknown
+                }
+            }
+            COD_MAJOR_WEARABLE => match minor {
+                0x01 => DeviceType::Watch,
+                _ => DeviceType::Unknown,
+            },
+            COD_MAJOR_TOY => match minor {
+                0x05 => DeviceType::Gamepad,
+                _ => DeviceType::Unknown,
+            },
+            COD_MAJOR_HEALTH => DeviceType::HealthSensor,
+            _ => DeviceType::Unknown,
+        }
+    }
+
+    /// Type of an LE device advertising GAP Appearance `appearance`, a category in the upper 10
+    /// bits and a subcategory in the lower 6.
+    pub fn from_appearance(appearance: u16) -> DeviceType {
+        let subcategory = appearance & 0x3f;
+        match appearance >> 6 {
+            0x001 => DeviceType::Phone,
+            0x002 => match subcategory {
+                0x06 => DeviceType::Watch,
+                0x07 => DeviceType::Tablet,
+                _ => DeviceType::Computer,
+            },
+            0x003 => DeviceType::Watch,
+            0x005 => DeviceType::Display,
+            0x006 => DeviceType::RemoteControl,
+            // Tag and keyring.
+            0x008 | 0x009 => DeviceType::Tag,
+            0x00a => DeviceType::Speaker,
+            // Thermometers, heart rate, blood pressure and glucose meters, and sport sensors.
+            0x00c..=0x00e | 0x010..=0x012 => DeviceType::HealthSensor,
+            0x00f => match subcategory {
+                0x01 => DeviceType::Keyboard,
+                0x02 => DeviceType::Mouse,
+                // Joystick and gamepad.
+                0x03 | 0x04 => DeviceType::Gamepad,
+                0x05 => DeviceType::Tablet,
+                0x07 => DeviceType::Pen,
+                _ => DeviceType::Unknown,
+            },
+            0x021 => DeviceType::Speaker,
+            0x022 => DeviceType::Microphone,
+            0x025 => match subcategory {
+                0x01 => DeviceType::Earbuds,
+                0x03 => DeviceType::Headphones,
+                _ => DeviceType::Headset,
+            },
+            0x029 => DeviceType::HearingAid,
+            0x02a => DeviceType::Gamepad,
+            _ => DeviceType::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_class_of_device() {
+        assert_eq!(DeviceType::from_class_of_device(0x5a020c), DeviceType::Phone);
+        assert_eq!(DeviceType::from_class_of_device(0x24010c), DeviceType::Computer);
+        assert_eq!(DeviceType::from_class_of_device(0x240404), DeviceType::Headset);
+        assert_eq!(DeviceType::from_class_of_device(0x240418), DeviceType::Headphones);
+        assert_eq!(DeviceType::from_class_of_device(0x000540), DeviceType::Keyboard);
+        assert_eq!(DeviceType::from_class_of_device(0x0005c0), DeviceType::Keyboard);
+        assert_eq!(DeviceType::from_class_of_device(0x002580), DeviceType::Mouse);
+        assert_eq!(DeviceType::from_class_of_device(0x000508), DeviceType::Gamepad);
+        assert_eq!(DeviceType::from_class_of_device(0x040680), DeviceType::Printer);
+        assert_eq!(DeviceType::from_class_of_device(0x000704), DeviceType::Watch);
+        assert_eq!(DeviceType::from_class_of_device(0x001f00), DeviceType::Unknown);
+        assert_eq!(DeviceType::from_class_of_device(0), DeviceType::Unknown);
+    }
+
+    #[test]
+    fn test_from_appearance() {
+        assert_eq!(DeviceType::from_appearance(0x0040), DeviceType::Phone);
+        assert_eq!(DeviceType::from_appearance(0x0083), DeviceType::Computer);
+        assert_eq!(DeviceType::from_appearance(0x0087), DeviceType::Tablet);
+        assert_eq!(DeviceType::from_appearance(0x00c1), DeviceType::Watch);
+        assert_eq!(DeviceType::from_appearance(0x03c1), DeviceType::Keyboard);
+        assert_eq!(DeviceType::from_appearance(0x03c2), DeviceType::Mouse);
+        assert_eq!(DeviceType::from_appearance(0x03c4), DeviceType::Gamepad);
+        assert_eq!(DeviceType::from_appearance(0x0341), DeviceType::HealthSensor);
+        assert_eq!(DeviceType::from_appearance(0x0941), DeviceType::Earbuds);
+        assert_eq!(DeviceType::from_appearance(0x0942), DeviceType::Headset);
+        assert_eq!(DeviceType::from_appearance(0x0943), DeviceType::Headphones);
+        assert_eq!(DeviceType::from_appearance(0x0a40), DeviceType::HearingAid);
+        assert_eq!(DeviceType::from_appearance(0x0000), DeviceType::Unknown);
+        assert_eq!(DeviceType::from_appearance(0x03c0), DeviceType::Unknown);
+    }
+}
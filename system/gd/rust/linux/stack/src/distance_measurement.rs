@@ -0,0 +1,587 @@
+//! Distance measurement (IBluetoothDistanceMeasurement).
+//!
+//! An experimental ranging API for prototyping features like proximity unlock: the distance to a
+//! connected peer is measured at a set interval and each measurement is reported to the
+//! callbacks. Channel Sounding, which times the phase of tones exchanged by the controllers and
+//! gets to within tens of centimeters, has no controller interface in this stack yet, so it is
+//! only named here for clients to ask for once it does. Until then distances are estimated from
+//! the RSSI of the link with a log-distance path loss model, which at best tells a peer within
+//! reach from one across the room.
+
+use bt_topshim::btif::{BtStatus, RawAddress};
+use bt_topshim::profiles::gatt::GattStatus;
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::bluetooth::BluetoothDevice;
+use crate::bluetooth_gatt::{
+    parse_uuid_string, BluetoothGatt, BluetoothGattService, GattNotification, IBluetoothGatt,
+    IBluetoothGattCallback, LePhy,
+};
+use crate::callbacks::Callbacks;
+use crate::{queue_message, Message, RPCProxy};
+
+/// App UUID of the GATT client the RSSI of the links is read with.
+const DISTANCE_MEASUREMENT_CLIENT_UUID: &str = "b3e95d0a1c6f4a84a7d2e41f0c8b6d57";
+
+/// Shortest and longest time between two measurements. Each RSSI reading is an HCI command, so
+/// more often than this would only keep the controller busy.
+const MIN_INTERVAL_MS: u32 = 200;
+const MAX_INTERVAL_MS: u32 = 60 * 1000;
+
+/// Failed readings in a row after which the link is taken to be down.
+const MAX_FAILED_READINGS: u32 = 3;
+
+/// How long to wait for a reading. The stack drops a request it can't send, for instance while
+/// another reading is pending, without a word.
+const READING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Weight of a new reading in the smoothed RSSI and its deviation.
+const SMOOTHING_FACTOR: f64 = 0.3;
+
+/// The RSSI at 1 m of a peer transmitting at 0 dBm.
+const MEASURED_POWER: f64 = -59.0;
+
+/// How fast the RSSI drops with the distance: 2 in free space, more indoors.
+const PATH_LOSS_EXPONENT: f64 = 2.0;
+
+/// How distances are measured.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum DistanceMeasurementMethod {
+    /// The most accurate method the controller and the peer support.
+    Auto = 0,
+    /// An estimate from the RSSI of the link.
+    Rssi,
+    /// LE Channel Sounding, not supported yet.
+    ChannelSounding,
+}
+
+/// A measured distance to a peer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistanceMeasurementResult {
+    pub method: DistanceMeasurementMethod,
+    pub distance_cm: u32,
+    /// How far off `distance_cm` may be, either way.
+    pub error_cm: u32,
+    /// Smoothed RSSI of the link, in dBm, for the `Rssi` method.
+    pub rssi: i32,
+}
+
+/// Defines the distance measurement API.
+#[dbus_interface(distance_measurement_dbus, "org.chromium.bluetooth.DistanceMeasurement")]
+pub trait IBluetoothDistanceMeasurement {
+    /// Adds a callback to be given the measurements.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothDistanceMeasurementCallback + Send>,
+    ) -> u32;
+
+    /// Removes a callback added with `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Returns the methods `start_distance_measurement` can use, besides `Auto`.
+    #[dbus_method("GetSupportedMethods")]
+    fn get_supported_methods(&self) -> Vec<DistanceMeasurementMethod>;
+
+    /// Measures the distance to `device`, which has to be connected, every `interval_ms`
+    /// milliseconds with `method` until `stop_distance_measurement`, or until the link goes down.
+    ///
+    /// Returns false if `device` is already being measured, `method` isn't supported, or
+    /// `interval_ms` is below 200 or above 60000.
+    #[dbus_method("StartDistanceMeasurement")]
+    fn start_distance_measurement(
+        &mut self,
+        device: BluetoothDevice,
+        method: DistanceMeasurementMethod,
+        interval_ms: u32,
+    ) -> bool;
+
+    /// Stops measuring the distance to `device`. Returns false if it isn't being measured.
+    #[dbus_method("StopDistanceMeasurement")]
+    fn stop_distance_measurement(&mut self, device: BluetoothDevice) -> bool;
+}
+
+/// Distance measurement events.
+pub trait IBluetoothDistanceMeasurementCallback: RPCProxy {
+    /// Measurements of `device` started with `method`, which `Auto` was resolved to.
+    fn on_distance_measurement_started(
+        &self,
+        device: BluetoothDevice,
+        method: DistanceMeasurementMethod,
+    );
+
+    /// Measurements of `device` ended, with `Success` if they were stopped or a failure if the
+    /// distance couldn't be measured anymore.
+    fn on_distance_measurement_stopped(&self, device: BluetoothDevice, status: BtStatus);
+
+    /// A new measurement of the distance to `device`.
+    fn on_distance_measurement(&self, device: BluetoothDevice, result: DistanceMeasurementResult);
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum DistanceMeasurementActions {
+    /// The GATT client was registered with this status and id.
+    GattClientRegistered(i32, i32),
+    /// The RSSI of the link to the device was read, with a status that is 0 for success.
+    RssiRead(RawAddress, i32, i32),
+    /// The reading of the RSSI of the device took too long.
+    ReadingTimeout(RawAddress),
+    /// Time for the next measurement of the device.
+    Measure(RawAddress),
+}
+
+/// Hands the RSSI readings of the GATT client to the dispatch loop.
+struct DistanceGattCallback {
+    tx: Sender<Message>,
+}
+
+impl DistanceGattCallback {
+    fn send(&self, action: DistanceMeasurementActions) {
+        queue_message(&self.tx, Message::DistanceMeasurement(action));
+    }
+}
+
+impl IBluetoothGattCallback for DistanceGattCallback {
+    fn on_client_registered(&self, status: i32, client_id: i32) {
+        self.send(DistanceMeasurementActions::GattClientRegistered(status, client_id));
+    }
+
+    fn on_client_connection_state(
+        &self,
+        _status: i32,
+        _client_id: i32,
+        _connected: bool,
+        _addr: RawAddress,
+    ) {
+    }
+
+    fn on_phy_update(
+        &self,
+        _addr: RawAddress,
+        _tx_phy: LePhy,
+        _rx_phy: LePhy,
+        _status: GattStatus,
+    ) {
+    }
+
+    fn on_phy_read(&self, _addr: RawAddress, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+
+    fn on_search_complete(
+        &self,
+        _addr: RawAddress,
+        _services: Vec<BluetoothGattService>,
+        _status: i32,
+    ) {
+    }
+
+    fn on_characteristic_read(
+        &self,
+        _addr: RawAddress,
+        _status: i32,
+        _handle: i32,
+        _value: Arc<[u8]>,
+    ) {
+    }
+
+    fn on_characteristic_write(&self, _addr: RawAddress, _status: i32, _handle: i32) {}
+
+    fn on_execute_write(&self, _addr: RawAddress, _status: i32) {}
+
+    fn on_descriptor_read(&self, _addr: RawAddress, _status: i32, _handle: i32, _value: Arc<[u8]>) {
+    }
+
+    fn on_descriptor_write(&self, _addr: RawAddress, _status: i32, _handle: i32) {}
+
+    fn on_notify(&self, _addr: RawAddress, _handle: i32, _value: Arc<[u8]>) {}
+
+    fn on_notify_batch(&self, _addr: RawAddress, _notifications: Vec<GattNotification>) {}
+
+    fn on_read_remote_rssi(&self, addr: RawAddress, rssi: i32, status: i32) {
+        self.send(DistanceMeasurementActions::RssiRead(addr, rssi, status));
+    }
+
+    fn on_configure_mtu(&self, _addr: RawAddress, _mtu: i32, _status: i32) {}
+
+    fn on_connection_updated(
+        &self,
+        _addr: RawAddress,
+        _interval: i32,
+        _latency: i32,
+        _timeout: i32,
+        _status: i32,
+    ) {
+    }
+
+    fn on_service_changed(&self, _addr: RawAddress) {}
+
+    fn on_gatt_db_updated(
+        &self,
+        _addr: RawAddress,
+        _services: Vec<BluetoothGattService>,
+        _database_hash: Vec<u8>,
+    ) {
+    }
+
+    fn on_authorization_request(&self, _addr: RawAddress, _handle: i32) {}
+}
+
+impl RPCProxy for DistanceGattCallback {
+    fn get_object_id(&self) -> String {
+        String::from("distance_measurement")
+    }
+}
+
+/// Registration of the GATT client, which is kept once registered.
+#[derive(Debug, PartialEq)]
+enum GattClient {
+    Unregistered,
+    Registering,
+    Registered(i32),
+}
+
+/// Smoothed RSSI of a link and how much the readings stray from it.
+#[derive(Debug, Default)]
+struct RssiEstimator {
+    rssi: Option<f64>,
+    deviation: f64,
+}
+
+impl RssiEstimator {
+    fn add_reading(&mut self, rssi: i32) {
+        let rssi = rssi as f64;
+        match self.rssi {
+            Some(smoothed) => {
+                self.deviation += SMOOTHING_FACTOR * ((rssi - smoothed).abs() - self.deviation);
+                self.rssi = Some(smoothed + SMOOTHING_FACTOR * (rssi - smoothed));
+            }
+            None => self.rssi = Some(rssi),
+        }
+    }
+
+    /// Distance in meters at the path loss of a link received at `rssi` dBm.
+    fn distance(rssi: f64) -> f64 {
+        10f64.powf((MEASURED_POWER - rssi) / (10.0 * PATH_LOSS_EXPONENT))
+    }
+
+    fn result(&self) -> Option<DistanceMeasurementResult> {
+        let rssi = self.rssi?;
+        let distance = Self::distance(rssi);
+        // Half the spread of the distances a reading one deviation off either way would give.
+        let error =
+            (Self::distance(rssi - self.deviation) - Self::distance(rssi + self.deviation)) / 2.0;
+        Some(DistanceMeasurementResult {
+            method: DistanceMeasurementMethod::Rssi,
+            distance_cm: (distance * 100.0).round() as u32,
+            error_cm: (error * 100.0).round() as u32,
+            rssi: rssi.round() as i32,
+        })
+    }
+}
+
+struct Measurement {
+    device: BluetoothDevice,
+    interval: Duration,
+    estimator: RssiEstimator,
+    failed_readings: u32,
+    timer: Option<JoinHandle<()>>,
+}
+
+/// Implementation of the distance measurement API.
+pub struct BluetoothDistanceMeasurement {
+    tx: Sender<Message>,
+    gatt: Option<Arc<Mutex<Box<BluetoothGatt>>>>,
+    callbacks: Callbacks<dyn IBluetoothDistanceMeasurementCallback + Send>,
+    gatt_client: GattClient,
+    measurements: HashMap<RawAddress, Measurement>,
+    /// Devices waiting for their RSSI to be read. The stack reads one RSSI at a time.
+    pending_readings: VecDeque<RawAddress>,
+    /// Device whose RSSI is being read, and the timer of the reading.
+    reading: Option<(RawAddress, JoinHandle<()>)>,
+}
+
+impl BluetoothDistanceMeasurement {
+    pub fn new(tx: Sender<Message>) -> Self {
+        BluetoothDistanceMeasurement {
+            tx: tx.clone(),
+            gatt: None,
+            callbacks: Callbacks::new(tx, Message::DistanceMeasurementCallbackDisconnected),
+            gatt_client: GattClient::Unregistered,
+            measurements: HashMap::new(),
+            pending_readings: VecDeque::new(),
+            reading: None,
+        }
+    }
+
+    pub fn set_gatt(&mut self, gatt: Arc<Mutex<Box<BluetoothGatt>>>) {
+        self.gatt = Some(gatt);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_distance_measurement_actions(&mut self, action: DistanceMeasurementActions) {
+        match action {
+            DistanceMeasurementActions::GattClientRegistered(status, client_id) => {
+                if status != GattStatus::Success as i32 {
+                    warn!("Distance measurement GATT client wasn't registered, status {}", status);
+                    self.gatt_client = GattClient::Unregistered;
+                    let addresses: Vec<RawAddress> = self.measurements.keys().cloned().collect();
+                    for address in addresses {
+                        self.finish(&address, BtStatus::Fail);
+                    }
+                    return;
+                }
+                self.gatt_client = GattClient::Registered(client_id);
+                self.pending_readings.extend(self.measurements.keys());
+                self.next_reading();
+            }
+            DistanceMeasurementActions::RssiRead(address, rssi, status) => {
+                // Readings of other GATT clients come here too.
+                if !self.is_reading(&address) {
+                    return;
+                }
+                if let Some((_, timer)) = self.reading.take() {
+                    timer.abort();
+                }
+                if status != 0 {
+                    debug!("[{}]: Reading RSSI failed, status {}", address, status);
+                    self.reading_done(&address, None);
+                } else {
+                    self.reading_done(&address, Some(rssi));
+                }
+                self.next_reading();
+            }
+            DistanceMeasurementActions::ReadingTimeout(address) => {
+                if !self.is_reading(&address) {
+                    return;
+                }
+                self.reading = None;
+                debug!("[{}]: No RSSI reading", address);
+                self.reading_done(&address, None);
+                self.next_reading();
+            }
+            DistanceMeasurementActions::Measure(address) => {
+                if self.measurements.contains_key(&address) {
+                    self.pending_readings.push_back(address);
+                    self.next_reading();
+                }
+            }
+        }
+    }
+
+    fn is_reading(&self, address: &RawAddress) -> bool {
+        self.reading.as_ref().map_or(false, |(reading, _)| reading == address)
+    }
+
+    /// Reports the distance at `rssi`, or counts a failed reading, and schedules the next one.
+    fn reading_done(&mut self, address: &RawAddress, rssi: Option<i32>) {
+        let measurement = match self.measurements.get_mut(address) {
+            Some(measurement) => measurement,
+            None => return,
+        };
+        match rssi {
+            Some(rssi) => {
+                measurement.failed_readings = 0;
+                measurement.estimator.add_reading(rssi);
+                if let Some(result) = measurement.estimator.result() {
+                    let device = measurement.device.clone();
+                    self.callbacks.for_all_callbacks(|callback| {
+                        callback.on_distance_measurement(device.clone(), result.clone());
+                    });
+                }
+            }
+            None => {
+                measurement.failed_readings += 1;
+                if measurement.failed_readings >= MAX_FAILED_READINGS {
+                    self.finish(address, BtStatus::RemoteDeviceDown);
+                    return;
+                }
+            }
+        }
+        self.schedule(address);
+    }
+
+    fn schedule(&mut self, address: &RawAddress) {
+        let measurement = match self.measurements.get_mut(address) {
+            Some(measurement) => measurement,
+            None => return,
+        };
+        let txl = self.tx.clone();
+        let (address, interval) = (*address, measurement.interval);
+        measurement.timer = Some(tokio::spawn(async move {
+            time::sleep(interval).await;
+            let _ = txl
+                .send(Message::DistanceMeasurement(DistanceMeasurementActions::Measure(address)))
+                .await;
+        }));
+    }
+
+    /// Reads the RSSI of the next device waiting for it, unless a reading is still going on.
+    fn next_reading(&mut self) {
+        while self.reading.is_none() {
+            let (gatt, client_id) = match (&self.gatt, &self.gatt_client) {
+                (Some(gatt), GattClient::Registered(client_id)) => (gatt.clone(), *client_id),
+                _ => return,
+            };
+            let address = match self.pending_readings.pop_front() {
+                Some(address) => address,
+                None => return,
+            };
+            if !self.measurements.contains_key(&address) {
+                continue;
+            }
+
+            let status = gatt.lock().unwrap().read_remote_rssi(client_id, address);
+            if status != BtStatus::Success {
+                self.finish(&address, status);
+                continue;
+            }
+            let txl = self.tx.clone();
+            let timer = tokio::spawn(async move {
+                time::sleep(READING_TIMEOUT).await;
+                let _ = txl
+                    .send(Message::DistanceMeasurement(DistanceMeasurementActions::ReadingTimeout(
+                        address,
+                    )))
+                    .await;
+            });
+            self.reading = Some((address, timer));
+        }
+    }
+
+    fn finish(&mut self, address: &RawAddress, status: BtStatus) {
+        let measurement = match self.measurements.remove(address) {
+            Some(measurement) => measurement,
+            None => return,
+        };
+        if let Some(timer) = measurement.timer {
+            timer.abort();
+        }
+        self.pending_readings.retain(|pending| pending != address);
+
+        info!("[{}]: Distance measurement stopped with {:?}", address, status);
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_distance_measurement_stopped(measurement.device.clone(), status.clone());
+        });
+    }
+}
+
+impl IBluetoothDistanceMeasurement for BluetoothDistanceMeasurement {
+    fn register_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothDistanceMeasurementCallback + Send>,
+    ) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn get_supported_methods(&self) -> Vec<DistanceMeasurementMethod> {
+        vec![DistanceMeasurementMethod::Rssi]
+    }
+
+    fn start_distance_measurement(
+        &mut self,
+        device: BluetoothDevice,
+        method: DistanceMeasurementMethod,
+        interval_ms: u32,
+    ) -> bool {
+        if method == DistanceMeasurementMethod::ChannelSounding
+            || !(MIN_INTERVAL_MS..=MAX_INTERVAL_MS).contains(&interval_ms)
+            || self.measurements.contains_key(&device.address)
+        {
+            return false;
+        }
+
+        match self.gatt_client {
+            GattClient::Registered(_) | GattClient::Registering => (),
+            GattClient::Unregistered => {
+                let gatt = match &self.gatt {
+                    Some(gatt) => gatt.clone(),
+                    None => return false,
+                };
+                let callback = Box::new(DistanceGattCallback { tx: self.tx.clone() });
+                let uuid = parse_uuid_string(DISTANCE_MEASUREMENT_CLIENT_UUID).unwrap().uu;
+                if gatt.lock().unwrap().register_client(uuid, callback, false) != BtStatus::Success
+                {
+                    return false;
+                }
+                self.gatt_client = GattClient::Registering;
+            }
+        }
+
+        let address = device.address;
+        self.measurements.insert(
+            address,
+            Measurement {
+                device: device.clone(),
+                interval: Duration::from_millis(interval_ms.into()),
+                estimator: RssiEstimator::default(),
+                failed_readings: 0,
+                timer: None,
+            },
+        );
+        self.callbacks.for_all_callbacks(|callback| {
+            callback
+                .on_distance_measurement_started(device.clone(), DistanceMeasurementMethod::Rssi);
+        });
+
+        // Until the GATT client is registered, the reading waits in the queue.
+        self.pending_readings.push_back(address);
+        self.next_reading();
+        true
+    }
+
+    fn stop_distance_measurement(&mut self, device: BluetoothDevice) -> bool {
+        if !self.measurements.contains_key(&device.address) {
+            return false;
+        }
+        self.finish(&device.address, BtStatus::Success);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rssi_estimator() {
+        let mut estimator = RssiEstimator::default();
+        assert_eq!(estimator.result(), None);
+
+        estimator.add_reading(-59);
+        assert_eq!(
+            estimator.result(),
+            Some(DistanceMeasurementResult {
+                method: DistanceMeasurementMethod::Rssi,
+                distance_cm: 100,
+                error_cm: 0,
+                rssi: -59
+            })
+        );
+
+        // 10 dB weaker is about 3 m in free space, and readings that jump around widen the error.
+        let mut estimator = RssiEstimator::default();
+        estimator.add_reading(-69);
+        assert_eq!(estimator.result().unwrap().distance_cm, 316);
+        estimator.add_reading(-63);
+        estimator.add_reading(-75);
+        let result = estimator.result().unwrap();
+        assert!(result.error_cm > 0);
+        assert!(result.rssi < -66 && result.rssi > -72);
+    }
+}
@@ -0,0 +1,44 @@
+//! Entry points for fuzzing the parsers of data sent by remote devices. Only built with the
+//! `fuzzing` feature, see fuzz/ for the targets.
+//!
+//! Each function feeds its input to the parsers the stack runs on the matching kind of remote
+//! data and drops the result. Parsers may reject any input, but must not panic on it.
+
+use crate::bluetooth_fast_pair::{
+    parse_key_based_pairing_request, parse_passkey, MESSAGE_SEEKER_PASSKEY,
+};
+use crate::bluetooth_map::{parse_event_report, parse_listing};
+use crate::obex::{self, Packet};
+use std::convert::TryFrom;
+
+/// Parses `data` as an OBEX request, as received by the OPP server, and as a response, as
+/// received by the MAP and PBAP clients.
+pub fn parse_obex_packet(data: &[u8]) {
+    let request_params_len = data.first().map_or(0, |opcode| obex::request_params_len(*opcode));
+
+    // Only the response to CONNECT has params, and it has as many as the request.
+    for params_len in [request_params_len, 4] {
+        if let Ok(packet) = Packet::from_bytes(data, params_len) {
+            let _ = packet.body();
+            if let Some(params) = packet.app_parameters() {
+                let _ = obex::parse_app_parameters(params);
+            }
+        }
+    }
+}
+
+/// Parses `data` as the MAP message listing and event report objects sent by a phone.
+pub fn parse_map_objects(data: &[u8]) {
+    let xml = String::from_utf8_lossy(data);
+    let _ = parse_listing(&xml);
+    let _ = parse_event_report(&xml);
+}
+
+/// Parses `data` as the decrypted blocks a Fast Pair seeker writes to the Key-based Pairing and
+/// Passkey characteristics. Inputs of another size can't come out of the decryption.
+pub fn parse_fast_pair_block(data: &[u8]) {
+    if let Ok(block) = <&[u8; 16]>::try_from(data) {
+        let _ = parse_key_based_pairing_request(block);
+        let _ = parse_passkey(block, MESSAGE_SEEKER_PASSKEY);
+    }
+}
@@ -0,0 +1,111 @@
+//! Answers of the applications on whether remote devices may use the sensitive characteristics
+//! of the GATT services the stack hosts, the Device Information Service and Fast Pair.
+//!
+//! A GATT client can take over authorizing them. The first read or write of such a
+//! characteristic on a connection then waits until the client answers the
+//! `on_authorization_request` it gets, and the answer holds until the connection closes, so that
+//! a device is asked about once per connection rather than on every request.
+
+use bt_topshim::btif::RawAddress;
+use std::collections::HashMap;
+
+/// Whether a request of a remote device may be served.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Authorization {
+    Allowed,
+    Denied,
+    /// The request waits for the answer of the application.
+    Pending,
+}
+
+/// Authorizations of the server connections, keyed by connection and attribute handle.
+#[derive(Default)]
+pub struct GattAuthorization {
+    answers: HashMap<(i32, i32), bool>,
+    /// Questions the application didn't answer yet, with the device of the connection.
+    asked: HashMap<(i32, i32), RawAddress>,
+}
+
+impl GattAuthorization {
+    pub fn new() -> GattAuthorization {
+        GattAuthorization::default()
+    }
+
+    /// Returns what was answered for `handle` on `conn_id`, if anything.
+    pub(crate) fn get(&self, conn_id: i32, handle: i32) -> Option<Authorization> {
+        match self.answers.get(&(conn_id, handle)).copied() {
+            Some(true) => Some(Authorization::Allowed),
+            Some(false) => Some(Authorization::Denied),
+            None if self.asked.contains_key(&(conn_id, handle)) => Some(Authorization::Pending),
+            None => None,
+        }
+    }
+
+    /// The application is asked whether `address` may use `handle` on `conn_id`.
+    pub(crate) fn ask(&mut self, conn_id: i32, address: RawAddress, handle: i32) {
+        self.asked.insert((conn_id, handle), address);
+    }
+
+    /// Keeps the answer for `handle` on the connections to `address` that asked for it, and
+    /// returns them.
+    pub(crate) fn answer(&mut self, address: RawAddress, handle: i32, allowed: bool) -> Vec<i32> {
+        let conn_ids: Vec<i32> = self
+            .asked
+            .iter()
+            .filter(|((_, h), a)| *h == handle && **a == address)
+            .map(|((conn_id, _), _)| *conn_id)
+            .collect();
+        for conn_id in conn_ids.iter() {
+            self.asked.remove(&(*conn_id, handle));
+            self.answers.insert((*conn_id, handle), allowed);
+        }
+        conn_ids
+    }
+
+    /// Forgets the answers given for `conn_id`, which is closed.
+    pub(crate) fn disconnected(&mut self, conn_id: i32) {
+        self.answers.retain(|(id, _), _| *id != conn_id);
+        self.asked.retain(|(id, _), _| *id != conn_id);
+    }
+
+    /// Forgets everything, the connections having gone down with the native stack.
+    pub(crate) fn clear(&mut self) {
+        self.answers.clear();
+        self.asked.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gatt_authorization() {
+        let address = RawAddress { val: [1, 2, 3, 4, 5, 6] };
+        let other = RawAddress { val: [6, 5, 4, 3, 2, 1] };
+        let mut authorization = GattAuthorization::new();
+
+        assert_eq!(authorization.get(1, 42), None);
+        authorization.ask(1, address, 42);
+        authorization.ask(2, other, 42);
+        assert_eq!(authorization.get(1, 42), Some(Authorization::Pending));
+
+        // Answers only go to the connections of the device that asked about the handle.
+        assert!(authorization.answer(address, 43, true).is_empty());
+        assert_eq!(authorization.answer(address, 42, true), vec![1]);
+        assert_eq!(authorization.get(1, 42), Some(Authorization::Allowed));
+        assert_eq!(authorization.get(2, 42), Some(Authorization::Pending));
+        assert_eq!(authorization.answer(other, 42, false), vec![2]);
+        assert_eq!(authorization.get(2, 42), Some(Authorization::Denied));
+
+        // The answer is kept for the connection, and asked again on the next one.
+        assert!(authorization.answer(address, 42, false).is_empty());
+        assert_eq!(authorization.get(1, 42), Some(Authorization::Allowed));
+        authorization.disconnected(1);
+        assert_eq!(authorization.get(1, 42), None);
+        assert_eq!(authorization.get(2, 42), Some(Authorization::Denied));
+
+        authorization.clear();
+        assert_eq!(authorization.get(2, 42), None);
+    }
+}
@@ -0,0 +1,58 @@
+//! Readable GATT statuses.
+//!
+//! GATT callbacks carry their status as a bare number, which is either an ATT error sent by the
+//! remote server or a status of the local stack. `GattStatusDisplay` shows it with its name and
+//! what it means, so that logs and clients don't each keep a table of the assigned numbers.
+
+use bt_topshim::profiles::gatt::{AttErrorRange, GattStatus};
+use num_traits::cast::FromPrimitive;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter, Result};
+
+/// Shows a GATT status such as 5 as "InsufAuthentication (0x05, insufficient authentication)".
+pub struct GattStatusDisplay(pub i32);
+
+impl From<GattStatus> for GattStatusDisplay {
+    fn from(status: GattStatus) -> Self {
+        GattStatusDisplay(status as i32)
+    }
+}
+
+impl Display for GattStatusDisplay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let code = self.0;
+        if let Some(status) = GattStatus::from_i32(code) {
+            return write!(f, "{:?} ({:#04x}, {})", status, code, status.description());
+        }
+
+        // Unnamed codes are still sorted by who defines them.
+        let description = match u8::try_from(code).map(AttErrorRange::from_code) {
+            Ok(AttErrorRange::Application) => "application error of the server",
+            Ok(AttErrorRange::CommonProfile) => "common profile error",
+            _ => "unknown status",
+        };
+        write!(f, "{:#04x} ({})", code, description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gatt_status_display() {
+        assert_eq!(GattStatusDisplay(0).to_string(), "Success (0x00, success)");
+        assert_eq!(
+            GattStatusDisplay(5).to_string(),
+            "InsufAuthentication (0x05, insufficient authentication)"
+        );
+        assert_eq!(
+            GattStatusDisplay::from(GattStatus::Congested).to_string(),
+            "Congested (0x8f, congested)"
+        );
+        assert_eq!(GattStatusDisplay(0x9e).to_string(), "0x9e (application error of the server)");
+        assert_eq!(GattStatusDisplay(0xe0).to_string(), "0xe0 (common profile error)");
+        assert_eq!(GattStatusDisplay(0xa3).to_string(), "0xa3 (unknown status)");
+        assert_eq!(GattStatusDisplay(-1).to_string(), "0xffffffff (unknown status)");
+    }
+}
@@ -0,0 +1,174 @@
+//! Interoperability workarounds for misbehaving remote devices.
+//!
+//! The database maps device address prefixes and names to workaround flags that profile code
+//! checks before doing something a known device can't handle. Entries are read from a config
+//! file in the same format as the native `interop_database.conf`, so field issues can be
+//! mitigated by shipping a new file instead of a new stack:
+//!
+//! ```text
+//! [INTEROP_AVOID_2M_PHY]
+//! 00:1b:dc = Address_Based
+//! Some Keyboard = Name_Based
+//! ```
+
+use bt_topshim::btif::RawAddress;
+use log::{info, warn};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::device_store::parse_ini;
+
+/// Where the interop workarounds config is read from.
+pub const INTEROP_CONFIG_PATH: &str = "/etc/bluetooth/interop_database.conf";
+
+/// Workarounds that can be applied to a remote device.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum InteropFeature {
+    /// Never use the LE 2M PHY with the device.
+    Avoid2mPhy,
+    /// Wait before starting SDP with the device. Some devices fail SDP when it starts right after
+    /// the connection is established.
+    DelaySdp,
+}
+
+impl InteropFeature {
+    fn from_section_name(name: &str) -> Option<InteropFeature> {
+        match name {
+            "INTEROP_AVOID_2M_PHY" => Some(InteropFeature::Avoid2mPhy),
+            "INTEROP_DELAY_SDP" => Some(InteropFeature::DelaySdp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum DeviceMatcher {
+    /// Leading bytes of the device address.
+    AddressPrefix(Vec<u8>),
+    /// Leading characters of the device name.
+    NamePrefix(String),
+}
+
+impl DeviceMatcher {
+    fn matches(&self, addr: &RawAddress, name: Option<&str>) -> bool {
+        match self {
+            DeviceMatcher::AddressPrefix(prefix) => addr.val.starts_with(prefix),
+            DeviceMatcher::NamePrefix(prefix) => name.map_or(false, |n| n.starts_with(prefix)),
+        }
+    }
+}
+
+fn parse_address_prefix(prefix: &str) -> Option<Vec<u8>> {
+    let bytes = prefix
+        .split(':')
+        .map(|b| if b.len() == 2 { u8::from_str_radix(b, 16).ok() } else { None })
+        .collect::<Option<Vec<u8>>>()?;
+
+    if bytes.is_empty() || bytes.len() > 6 {
+        return None;
+    }
+
+    Some(bytes)
+}
+
+/// Table of devices that need workarounds.
+#[derive(Debug, Default)]
+pub struct InteropDatabase {
+    entries: Vec<(InteropFeature, DeviceMatcher)>,
+}
+
+impl InteropDatabase {
+    /// Creates an empty database.
+    pub fn new() -> InteropDatabase {
+        InteropDatabase { entries: vec![] }
+    }
+
+    /// Loads the database from [`INTEROP_CONFIG_PATH`]. A missing or unreadable file results in
+    /// an empty database.
+    pub fn load_default() -> InteropDatabase {
+        match InteropDatabase::load(INTEROP_CONFIG_PATH) {
+            Ok(db) => db,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => InteropDatabase::new(),
+            Err(e) => {
+                warn!("Failed to load {}: {}", INTEROP_CONFIG_PATH, e);
+                InteropDatabase::new()
+            }
+        }
+    }
+
+    /// Loads the database from `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<InteropDatabase> {
+        let db = InteropDatabase::parse(&fs::read_to_string(path)?);
+        info!("Loaded {} interop entries", db.entries.len());
+        Ok(db)
+    }
+
+    fn parse(contents: &str) -> InteropDatabase {
+        let mut db = InteropDatabase::new();
+
+        for section in parse_ini(contents) {
+            let feature = match InteropFeature::from_section_name(&section.name) {
+                Some(feature) => feature,
+                None => {
+                    warn!("Unknown interop feature {}", section.name);
+                    continue;
+                }
+            };
+
+            for (key, value) in section.entries {
+                let matcher = match value.as_str() {
+                    "Address_Based" => match parse_address_prefix(&key) {
+                        Some(prefix) => DeviceMatcher::AddressPrefix(prefix),
+                        None => {
+                            warn!("Invalid interop address prefix {}", key);
+                            continue;
+                        }
+                    },
+                    "Name_Based" => DeviceMatcher::NamePrefix(key),
+                    _ => {
+                        warn!("Unknown interop entry type {} for {}", value, key);
+                        continue;
+                    }
+                };
+
+                db.entries.push((feature, matcher));
+            }
+        }
+
+        db
+    }
+
+    /// Returns true if `feature` should be applied to the device with this address and name.
+    pub fn is_match(&self, feature: InteropFeature, addr: &RawAddress, name: Option<&str>) -> bool {
+        self.entries.iter().any(|(f, matcher)| *f == feature && matcher.matches(addr, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interop_match() {
+        let db = InteropDatabase::parse(
+            "[INTEROP_AVOID_2M_PHY]\n\
+             00:1b:dc = Address_Based\n\
+             Keyboard K = Name_Based\n\
+             zz:00 = Address_Based\n\
+             \n\
+             [INTEROP_UNKNOWN]\n\
+             00:11:22 = Address_Based\n",
+        );
+        assert_eq!(db.entries.len(), 2);
+
+        let matching = RawAddress::from_string("00:1B:DC:01:02:03").unwrap();
+        let other = RawAddress::from_string("00:11:22:01:02:03").unwrap();
+
+        assert!(db.is_match(InteropFeature::Avoid2mPhy, &matching, None));
+        assert!(!db.is_match(InteropFeature::DelaySdp, &matching, None));
+        assert!(!db.is_match(InteropFeature::Avoid2mPhy, &other, None));
+        assert!(db.is_match(InteropFeature::Avoid2mPhy, &other, Some("Keyboard K380")));
+        assert!(!db.is_match(InteropFeature::Avoid2mPhy, &other, Some("Mouse")));
+    }
+}
@@ -6,33 +6,92 @@
 #[macro_use]
 extern crate num_derive;
 
+pub mod activity;
+pub mod advertising_privacy;
+pub mod api_trace;
+pub mod audio_control;
+pub mod beacon;
 pub mod bluetooth;
+pub mod bluetooth_admin;
+pub mod bluetooth_adv_monitor;
+pub mod bluetooth_fast_pair;
 pub mod bluetooth_gatt;
+pub mod bluetooth_map;
 pub mod bluetooth_media;
+pub mod bluetooth_observer;
+pub mod bluetooth_opp;
+pub mod bluetooth_pan;
+pub mod bluetooth_pbap;
+pub mod bluetooth_provisioning;
+#[cfg(feature = "pts")]
+pub mod bluetooth_pts;
+pub mod bluetooth_reconnect;
+pub mod callbacks;
+pub mod coex;
+pub mod device_store;
+pub mod device_type;
+pub mod distance_measurement;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod gatt_authorization;
+pub mod gatt_status;
+pub mod interop;
+pub mod link_diagnostics;
+pub mod link_users;
+pub mod obex;
+pub mod radio_arbiter;
+pub mod rate_limiter;
+pub mod runtime_config;
 pub mod suspend;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod throughput_test;
 pub mod uuid;
+pub mod vendor;
 
-use log::debug;
+use std::future::Future;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::{Receiver, Sender};
 
-use crate::bluetooth::Bluetooth;
-use crate::bluetooth_gatt::BluetoothGatt;
+use crate::advertising_privacy::{AdvertisingPrivacy, AdvertisingPrivacyActions};
+use crate::bluetooth::{Bluetooth, BluetoothDevice};
+use crate::bluetooth_admin::BluetoothAdmin;
+use crate::bluetooth_adv_monitor::{AdvMonitorActions, BluetoothAdvMonitor};
+use crate::bluetooth_fast_pair::{BluetoothFastPair, FastPairActions};
+use crate::bluetooth_gatt::{BluetoothGatt, GattActions};
+use crate::bluetooth_map::{BluetoothMap, MapActions};
 use crate::bluetooth_media::{BluetoothMedia, MediaActions};
+use crate::bluetooth_opp::{BluetoothOpp, OppActions};
+use crate::bluetooth_pan::BluetoothPan;
+use crate::bluetooth_pbap::{BluetoothPbap, PbapActions};
+use crate::bluetooth_reconnect::{BluetoothReconnect, ReconnectActions};
+use crate::coex::{BluetoothCoex, CoexActions};
+use crate::distance_measurement::{BluetoothDistanceMeasurement, DistanceMeasurementActions};
+use crate::link_diagnostics::{BluetoothLinkDiagnostics, LinkDiagnosticsActions};
+use crate::radio_arbiter::{RadioActivity, RadioGrant, RadioUser};
+use crate::runtime_config::RuntimeConfig;
 use crate::suspend::Suspend;
+use crate::throughput_test::{BluetoothThroughputTest, ThroughputTestActions};
 use bt_topshim::{
-    btif::BaseCallbacks,
+    btif::{BaseCallbacks, RawAddress},
     profiles::{
-        a2dp::A2dpCallbacks, avrcp::AvrcpCallbacks, gatt::GattClientCallbacks,
-        gatt::GattServerCallbacks, hfp::HfpCallbacks, hid_host::HHCallbacks, sdp::SdpCallbacks,
+        a2dp::A2dpCallbacks, avrcp::AvrcpCallbacks, gatt::GattAdvCallbacks,
+        gatt::GattAdvInbandCallbacks, gatt::GattClientCallbacks, gatt::GattScannerCallbacks,
+        gatt::GattScannerInbandCallbacks, gatt::GattServerCallbacks,
+        hearing_aid::HearingAidCallbacks, hfp::HfpCallbacks, hid_host::HHCallbacks,
+        pan::PanCallbacks, sdp::SdpCallbacks,
     },
+    topstack,
 };
 
 #[derive(Clone, Debug)]
 pub enum BluetoothCallbackType {
     Adapter,
     Connection,
+    LinkPolicy,
+    Security,
+    DiscoverySession,
 }
 
 /// Message types that are sent to the stack main dispatch loop.
@@ -41,14 +100,32 @@ pub enum Message {
     A2dp(A2dpCallbacks),
     Avrcp(AvrcpCallbacks),
     Base(BaseCallbacks),
+    GattAdvertiser(GattAdvCallbacks),
+    GattAdvertiserInband(GattAdvInbandCallbacks),
     GattClient(GattClientCallbacks),
+    GattScanner(GattScannerCallbacks),
+    GattScannerInband(GattScannerInbandCallbacks),
     GattServer(GattServerCallbacks),
+    HearingAid(HearingAidCallbacks),
     HidHost(HHCallbacks),
     Hfp(HfpCallbacks),
+    Pan(PanCallbacks),
     Sdp(SdpCallbacks),
 
     // Actions within the stack
+    AdvMonitor(AdvMonitorActions),
+    AdvertisingPrivacy(AdvertisingPrivacyActions),
+    Coex(CoexActions),
+    DistanceMeasurement(DistanceMeasurementActions),
+    FastPair(FastPairActions),
+    Gatt(GattActions),
+    LinkDiagnostics(LinkDiagnosticsActions),
+    Map(MapActions),
     Media(MediaActions),
+    Opp(OppActions),
+    Pbap(PbapActions),
+    Reconnect(ReconnectActions),
+    ThroughputTest(ThroughputTestActions),
 
     // Client callback disconnections
     BluetoothCallbackDisconnected(u32, BluetoothCallbackType),
@@ -56,9 +133,81 @@ pub enum Message {
     // Update list of found devices and remove old instances.
     DeviceFreshnessCheck,
 
+    // Send device found callbacks held back by rate limiting.
+    FoundDevicesFlush,
+
+    // Read the runtime config again and apply it.
+    ReloadRuntimeConfig,
+
+    // Send the next queued remote name request.
+    RemoteNameRequest,
+
+    // Connect the next profile of a device that is connecting all its profiles.
+    ConnectAllStep(RawAddress),
+
+    // Media related
+    MediaCallbackDisconnected(u32),
+
+    // Admin policy related
+    AdminCallbackDisconnected(u32),
+    RemoteUuidsChanged(BluetoothDevice),
+
     // Suspend related
     SuspendCallbackRegistered(u32),
     SuspendCallbackDisconnected(u32),
+
+    // Object push related
+    OppCallbackDisconnected(u32),
+
+    // Phone book access related
+    PbapCallbackDisconnected(u32),
+
+    // Message access related
+    MapCallbackDisconnected(u32),
+
+    // Personal area networking related
+    PanCallbackDisconnected(u32),
+
+    // Advertisement monitor related
+    AdvMonitorCallbackDisconnected(u32),
+
+    // Reconnection related
+    ReconnectCallbackDisconnected(u32),
+
+    // Advertising privacy related
+    AdvertisingPrivacyCallbackDisconnected(u32),
+
+    // Link diagnostics related
+    LinkDiagnosticsCallbackDisconnected(u32),
+
+    // Distance measurement related
+    DistanceMeasurementCallbackDisconnected(u32),
+
+    // Throughput test related
+    ThroughputTestCallbackDisconnected(u32),
+
+    // The radio grant of a part of the stack changed because of the requests of others.
+    RadioGrantChanged(RadioUser, RadioActivity, RadioGrant),
+}
+
+/// Number of messages that can be waiting for the dispatch loop before senders have to wait.
+const DISPATCH_QUEUE_SIZE: usize = 256;
+
+/// Queues a message from libbluetooth for the dispatch loop.
+///
+/// Callbacks from libbluetooth arrive on its own thread, outside of any runtime. That thread waits
+/// for room in the queue, so callbacks are handled in the order libbluetooth reported them. If the
+/// caller is already running on a runtime, where waiting isn't allowed, the message is sent from a
+/// separate task instead.
+pub(crate) fn queue_message(tx: &Sender<Message>, message: Message) {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        let tx = tx.clone();
+        topstack::get_runtime().spawn(async move {
+            let _ = tx.send(message).await;
+        });
+    } else {
+        let _ = tx.blocking_send(message);
+    }
 }
 
 /// Umbrella class for the Bluetooth stack.
@@ -67,16 +216,50 @@ pub struct Stack {}
 impl Stack {
     /// Creates an mpsc channel for passing messages to the main dispatch loop.
     pub fn create_channel() -> (Sender<Message>, Receiver<Message>) {
-        channel::<Message>(1)
+        channel::<Message>(DISPATCH_QUEUE_SIZE)
+    }
+
+    /// Runs `dispatch` (normally [`Stack::dispatch`]) on a thread of its own.
+    ///
+    /// Handling a message can take a while, so the dispatch loop gets its own executor rather than
+    /// sharing the runtime that serves D-Bus. D-Bus handlers then don't wait behind libbluetooth
+    /// callbacks, and vice versa.
+    pub fn spawn_dispatch_thread<F>(dispatch: F) -> std::thread::JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        std::thread::Builder::new()
+            .name(String::from("bt_dispatch"))
+            .spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to build the dispatch runtime")
+                    .block_on(dispatch);
+            })
+            .expect("Failed to spawn the dispatch thread")
     }
 
     /// Runs the main dispatch loop.
     pub async fn dispatch(
         mut rx: Receiver<Message>,
+        advertising_privacy: Arc<Mutex<Box<AdvertisingPrivacy>>>,
         bluetooth: Arc<Mutex<Box<Bluetooth>>>,
+        bluetooth_admin: Arc<Mutex<Box<BluetoothAdmin>>>,
+        bluetooth_adv_monitor: Arc<Mutex<Box<BluetoothAdvMonitor>>>,
+        bluetooth_fast_pair: Arc<Mutex<Box<BluetoothFastPair>>>,
         bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
+        bluetooth_map: Arc<Mutex<Box<BluetoothMap>>>,
         bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
+        bluetooth_opp: Arc<Mutex<Box<BluetoothOpp>>>,
+        bluetooth_pan: Arc<Mutex<Box<BluetoothPan>>>,
+        bluetooth_pbap: Arc<Mutex<Box<BluetoothPbap>>>,
+        bluetooth_reconnect: Arc<Mutex<Box<BluetoothReconnect>>>,
+        coex: Arc<Mutex<Box<BluetoothCoex>>>,
+        distance_measurement: Arc<Mutex<Box<BluetoothDistanceMeasurement>>>,
+        link_diagnostics: Arc<Mutex<Box<BluetoothLinkDiagnostics>>>,
         suspend: Arc<Mutex<Box<Suspend>>>,
+        throughput_test: Arc<Mutex<Box<BluetoothThroughputTest>>>,
     ) {
         loop {
             let m = rx.recv().await;
@@ -95,36 +278,151 @@ impl Stack {
                     bluetooth_media.lock().unwrap().dispatch_avrcp_callbacks(av);
                 }
 
+                Message::Base(BaseCallbacks::L2capEchoResponse(address, data)) => {
+                    link_diagnostics.lock().unwrap().echo_response(address, data);
+                }
+
+                Message::Base(BaseCallbacks::LeTxPowerLevels(address, status, phy, tx, max_tx)) => {
+                    link_diagnostics
+                        .lock()
+                        .unwrap()
+                        .tx_power_levels(address, status, phy, tx, max_tx);
+                }
+
+                Message::Base(BaseCallbacks::LeTxPowerReport(
+                    address,
+                    reason,
+                    phy,
+                    tx,
+                    flags,
+                    delta,
+                )) => {
+                    link_diagnostics
+                        .lock()
+                        .unwrap()
+                        .tx_power_report(address, reason, phy, tx, flags, delta);
+                }
+
+                Message::Base(BaseCallbacks::LePathLoss(address, path_loss, zone)) => {
+                    link_diagnostics.lock().unwrap().path_loss(address, path_loss, zone);
+                }
+
                 Message::Base(b) => {
                     bluetooth.lock().unwrap().dispatch_base_callbacks(b);
                 }
 
+                Message::GattAdvertiser(m) => {
+                    if let GattAdvCallbacks::OnOwnAddressRead(adv_id, address_type, address) = m {
+                        let mut privacy = advertising_privacy.lock().unwrap();
+                        privacy.address_read(adv_id, address_type, address);
+                    } else {
+                        bluetooth_fast_pair.lock().unwrap().dispatch_advertiser_callbacks(m);
+                    }
+                }
+
+                Message::GattAdvertiserInband(m) => {
+                    advertising_privacy.lock().unwrap().dispatch_advertiser_inband_callbacks(m);
+                }
+
                 Message::GattClient(m) => {
                     bluetooth_gatt.lock().unwrap().dispatch_gatt_client_callbacks(m);
                 }
 
+                Message::GattScanner(m) => {
+                    bluetooth_adv_monitor.lock().unwrap().dispatch_scanner_callbacks(m);
+                }
+
+                Message::GattScannerInband(m) => {
+                    bluetooth_adv_monitor.lock().unwrap().dispatch_scanner_inband_callbacks(m);
+                }
+
                 Message::GattServer(m) => {
-                    // TODO(b/193685149): dispatch GATT server callbacks.
-                    debug!("Unhandled Message::GattServer: {:?}", m);
+                    // The Fast Pair provider hosts its own server next to the local services.
+                    let mut fast_pair = bluetooth_fast_pair.lock().unwrap();
+                    if fast_pair.owns_gatt_server_callback(&m) {
+                        fast_pair.dispatch_gatt_server_callbacks(m);
+                    } else {
+                        drop(fast_pair);
+                        bluetooth_gatt.lock().unwrap().dispatch_gatt_server_callbacks(m);
+                    }
+                }
+
+                Message::HearingAid(ha) => {
+                    bluetooth_media.lock().unwrap().dispatch_hearing_aid_callbacks(ha);
                 }
 
                 Message::Hfp(hf) => {
                     bluetooth_media.lock().unwrap().dispatch_hfp_callbacks(hf);
                 }
 
-                Message::HidHost(_h) => {
-                    // TODO(abps) - Handle hid host callbacks
-                    debug!("Received HH callback");
+                Message::HidHost(h) => {
+                    bluetooth.lock().unwrap().dispatch_hid_host_callbacks(h);
+                }
+
+                Message::Pan(p) => {
+                    bluetooth_pan.lock().unwrap().dispatch_pan_callbacks(p);
                 }
 
                 Message::Sdp(s) => {
                     bluetooth.lock().unwrap().dispatch_sdp_callbacks(s);
                 }
 
+                Message::AdvMonitor(action) => {
+                    bluetooth_adv_monitor.lock().unwrap().dispatch_adv_monitor_actions(action);
+                }
+
+                Message::AdvertisingPrivacy(action) => {
+                    let mut privacy = advertising_privacy.lock().unwrap();
+                    privacy.dispatch_advertising_privacy_actions(action);
+                }
+
+                Message::FastPair(action) => {
+                    bluetooth_fast_pair.lock().unwrap().dispatch_fast_pair_actions(action);
+                }
+
+                Message::Gatt(action) => {
+                    bluetooth_gatt.lock().unwrap().dispatch_gatt_actions(action);
+                }
+
+                Message::LinkDiagnostics(action) => {
+                    link_diagnostics.lock().unwrap().dispatch_link_diagnostics_actions(action);
+                }
+
+                Message::Map(action) => {
+                    bluetooth_map.lock().unwrap().dispatch_map_actions(action);
+                }
+
                 Message::Media(action) => {
                     bluetooth_media.lock().unwrap().dispatch_media_actions(action);
                 }
 
+                Message::Opp(action) => {
+                    bluetooth_opp.lock().unwrap().dispatch_opp_actions(action);
+                }
+
+                Message::Pbap(action) => {
+                    bluetooth_pbap.lock().unwrap().dispatch_pbap_actions(action);
+                }
+
+                Message::Reconnect(action) => {
+                    bluetooth_reconnect.lock().unwrap().dispatch_reconnect_actions(action);
+                }
+
+                Message::Coex(action) => {
+                    coex.lock().unwrap().dispatch_coex_actions(action);
+                }
+
+                Message::DistanceMeasurement(action) => {
+                    distance_measurement
+                        .lock()
+                        .unwrap()
+                        .dispatch_distance_measurement_actions(action);
+                }
+
+                Message::ThroughputTest(action) => {
+                    throughput_test.lock().unwrap().dispatch_throughput_test_actions(action);
+                }
+
                 Message::BluetoothCallbackDisconnected(id, cb_type) => {
                     bluetooth.lock().unwrap().callback_disconnected(id, cb_type);
                 }
@@ -133,6 +431,38 @@ impl Stack {
                     bluetooth.lock().unwrap().trigger_freshness_check();
                 }
 
+                Message::FoundDevicesFlush => {
+                    bluetooth.lock().unwrap().flush_found_devices();
+                }
+
+                Message::ReloadRuntimeConfig => {
+                    let config = RuntimeConfig::load_default();
+                    config.apply_log_level();
+                    bluetooth.lock().unwrap().apply_runtime_config(&config);
+                    bluetooth_gatt.lock().unwrap().reload_interop();
+                    bluetooth_reconnect.lock().unwrap().apply_runtime_config(&config);
+                }
+
+                Message::RemoteNameRequest => {
+                    bluetooth.lock().unwrap().send_name_request();
+                }
+
+                Message::ConnectAllStep(address) => {
+                    bluetooth.lock().unwrap().connect_all_step(address);
+                }
+
+                Message::AdminCallbackDisconnected(id) => {
+                    bluetooth_admin.lock().unwrap().remove_callback(id);
+                }
+
+                Message::RemoteUuidsChanged(device) => {
+                    bluetooth_admin.lock().unwrap().on_remote_uuids_changed(device);
+                }
+
+                Message::MediaCallbackDisconnected(id) => {
+                    bluetooth_media.lock().unwrap().remove_callback(id);
+                }
+
                 Message::SuspendCallbackRegistered(id) => {
                     suspend.lock().unwrap().callback_registered(id);
                 }
@@ -140,6 +470,60 @@ impl Stack {
                 Message::SuspendCallbackDisconnected(id) => {
                     suspend.lock().unwrap().remove_callback(id);
                 }
+
+                Message::OppCallbackDisconnected(id) => {
+                    bluetooth_opp.lock().unwrap().remove_callback(id);
+                }
+
+                Message::PbapCallbackDisconnected(id) => {
+                    bluetooth_pbap.lock().unwrap().remove_callback(id);
+                }
+
+                Message::MapCallbackDisconnected(id) => {
+                    bluetooth_map.lock().unwrap().remove_callback(id);
+                }
+
+                Message::PanCallbackDisconnected(id) => {
+                    bluetooth_pan.lock().unwrap().remove_callback(id);
+                }
+
+                Message::AdvMonitorCallbackDisconnected(id) => {
+                    bluetooth_adv_monitor.lock().unwrap().remove_callback(id);
+                }
+
+                Message::ReconnectCallbackDisconnected(id) => {
+                    bluetooth_reconnect.lock().unwrap().remove_callback(id);
+                }
+
+                Message::AdvertisingPrivacyCallbackDisconnected(id) => {
+                    advertising_privacy.lock().unwrap().remove_callback(id);
+                }
+
+                Message::LinkDiagnosticsCallbackDisconnected(id) => {
+                    link_diagnostics.lock().unwrap().remove_callback(id);
+                }
+
+                Message::DistanceMeasurementCallbackDisconnected(id) => {
+                    distance_measurement.lock().unwrap().remove_callback(id);
+                }
+
+                Message::ThroughputTestCallbackDisconnected(id) => {
+                    throughput_test.lock().unwrap().remove_callback(id);
+                }
+
+                Message::RadioGrantChanged(user, activity, grant) => match user {
+                    RadioUser::Discovery => {
+                        bluetooth.lock().unwrap().radio_grant_changed(activity, grant);
+                    }
+                    RadioUser::AdvMonitor => {
+                        bluetooth_adv_monitor.lock().unwrap().radio_grant_changed(activity, grant);
+                    }
+                    RadioUser::FastPair => {
+                        bluetooth_fast_pair.lock().unwrap().radio_grant_changed(activity, grant);
+                    }
+                    // Connections are always granted, whatever else uses the radio.
+                    RadioUser::GattConnection(_) => {}
+                },
             }
         }
     }
@@ -150,16 +534,53 @@ impl Stack {
 /// An object that implements RPCProxy trait signifies that the object may be a proxy to a remote
 /// RPC object. Therefore the object may be disconnected and thus should implement
 /// `register_disconnect` to let others observe the disconnection event.
+///
+/// Proxies generated by `dbus_proxy_obj`, and callbacks using `#[derive(RPCProxy)]` from
+/// `dbus_macros`, keep their observers in a `dbus_projection::CallbackLifetime`.
 pub trait RPCProxy {
     /// Registers disconnect observer that will be notified when the remote object is disconnected.
-    fn register_disconnect(&mut self, f: Box<dyn Fn(u32) + Send>) -> u32;
+    /// Any number of observers can be registered, and each gets its own id. Objects that never
+    /// disconnect, such as local ones, keep the default which registers nothing.
+    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
+        0
+    }
 
     /// Returns the ID of the object. For example this would be an object path in D-Bus RPC.
     fn get_object_id(&self) -> String;
 
-    /// Unregisters callback with this id.
-    fn unregister(&mut self, id: u32) -> bool;
+    /// Unregisters the disconnect observer with this id, returning whether it was registered.
+    fn unregister(&mut self, _id: u32) -> bool {
+        false
+    }
 
     /// Makes this object available for remote call.
-    fn export_for_rpc(self: Box<Self>);
+    fn export_for_rpc(self: Box<Self>) {}
+
+    /// Attaches the sequence number and timestamp of a callback delivery to the next call made on
+    /// the object. Proxies that can't send them along ignore them.
+    fn set_event_stamp(&self, _sequence: u64, _timestamp_us: u64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_message_keeps_order() {
+        let (tx, mut rx) = Stack::create_channel();
+
+        // libbluetooth reports callbacks from a thread that isn't running any runtime.
+        std::thread::spawn(move || {
+            for id in 0..DISPATCH_QUEUE_SIZE as u32 * 2 {
+                queue_message(&tx, Message::PanCallbackDisconnected(id));
+            }
+        });
+
+        for expected in 0..DISPATCH_QUEUE_SIZE as u32 * 2 {
+            match rx.blocking_recv() {
+                Some(Message::PanCallbackDisconnected(id)) => assert_eq!(id, expected),
+                _ => panic!("Unexpected message"),
+            }
+        }
+    }
 }
@@ -0,0 +1,597 @@
+//! Link diagnostics (IBluetoothLinkDiagnostics).
+//!
+//! A quick check of a BR/EDR link without external tools: like `l2ping`, L2CAP echo requests are
+//! sent to the device one after another, and the time each response took is reported. Devices
+//! answer echo requests in their L2CAP layer, so this works with any connected device regardless
+//! of the profiles it supports.
+//!
+//! For LE links, the TX power levels of both sides can be read and followed where the controller
+//! supports LE Power Control. The controllers adjust the levels between themselves; the host can
+//! only ask to be told when the path loss leaves a range, which at a known TX power is a range of
+//! RSSI.
+
+use bt_topshim::btif::{BluetoothInterface, BtStatus, RawAddress};
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use num_derive::{FromPrimitive, ToPrimitive};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::bluetooth::BluetoothDevice;
+use crate::bluetooth_gatt::LePhy;
+use crate::callbacks::Callbacks;
+use crate::{Message, RPCProxy};
+
+/// Most echo requests of a single ping.
+const MAX_PING_COUNT: u32 = 1000;
+
+/// Most data sent in an echo request. Devices reject requests bigger than their signalling MTU,
+/// which can be as small as 48 octets, and such requests then get no response.
+const MAX_ECHO_SIZE: u32 = 600;
+
+/// How long to wait for a response before counting the request as lost.
+const ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait between a response and the next request.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// TX power level of a report that has none, as HCI gives it.
+pub const TX_POWER_UNAVAILABLE: i32 = 127;
+
+/// TX power level reported for a remote device that doesn't manage its power.
+pub const TX_POWER_UNMANAGED: i32 = 126;
+
+/// Path loss, in dB, HCI uses for none. Thresholds have to be below it.
+const PATH_LOSS_UNUSED: u32 = 0xff;
+
+/// Defines the link diagnostics API.
+#[dbus_interface(link_diagnostics_dbus, "org.chromium.bluetooth.LinkDiagnostics")]
+pub trait IBluetoothLinkDiagnostics {
+    /// Adds a callback to be told about the responses to echo requests.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothLinkDiagnosticsCallback + Send>,
+    ) -> u32;
+
+    /// Removes a callback added with `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Starts sending `count` echo requests carrying `size` octets of data to `device`, one
+    /// second after the response to the previous one.
+    ///
+    /// Returns false if there is no BR/EDR link to the device, the device is already being
+    /// pinged, or `count` or `size` are out of range.
+    #[dbus_method("Ping")]
+    fn ping(&mut self, device: BluetoothDevice, count: u32, size: u32) -> bool;
+
+    /// Stops pinging `device` before all the requests were sent. Returns false if it wasn't
+    /// being pinged.
+    #[dbus_method("CancelPing")]
+    fn cancel_ping(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Reads the current and highest TX power levels of the LE link to `device` on `phy`, which
+    /// are reported with `on_tx_power_reported`.
+    ///
+    /// Returns false if the controller doesn't support LE Power Control or there is no LE link to
+    /// the device.
+    #[dbus_method("ReadTxPower")]
+    fn read_tx_power(&mut self, device: BluetoothDevice, phy: LePhy) -> bool;
+
+    /// Turns the reports of the changes of the local and remote TX power levels of the LE link to
+    /// `device` on or off. Returns false like `read_tx_power`.
+    #[dbus_method("SetTxPowerReporting")]
+    fn set_tx_power_reporting(&mut self, device: BluetoothDevice, enable: bool) -> bool;
+
+    /// Reports with `on_path_loss_zone_entered` when the path loss of the LE link to `device`, the
+    /// local TX power level minus the RSSI, goes below `low_db` or above `high_db`, and back. To
+    /// leave a zone, the path loss has to go `hysteresis_db` past its threshold.
+    ///
+    /// Returns false if the controller doesn't support LE Path Loss Monitoring, there is no LE
+    /// link to the device, `low_db` is above `high_db` or the hysteresis doesn't fit between them.
+    #[dbus_method("SetPathLossRange")]
+    fn set_path_loss_range(
+        &mut self,
+        device: BluetoothDevice,
+        low_db: u32,
+        high_db: u32,
+        hysteresis_db: u32,
+    ) -> bool;
+
+    /// Stops the reports turned on with `set_path_loss_range`.
+    #[dbus_method("ClearPathLossRange")]
+    fn clear_path_loss_range(&mut self, device: BluetoothDevice) -> bool;
+}
+
+/// Round trip times of the echo requests sent to a device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PingStats {
+    pub sent: u32,
+    pub received: u32,
+    /// Shortest, average and longest round trip times of the responses, in microseconds. All 0
+    /// if there was none.
+    pub min_rtt_us: u64,
+    pub avg_rtt_us: u64,
+    pub max_rtt_us: u64,
+}
+
+impl PingStats {
+    fn new(sent: u32, rtts: &[Duration]) -> PingStats {
+        let rtts_us: Vec<u64> = rtts.iter().map(|rtt| rtt.as_micros() as u64).collect();
+        let received = rtts_us.len() as u32;
+        PingStats {
+            sent,
+            received,
+            min_rtt_us: rtts_us.iter().cloned().min().unwrap_or(0),
+            avg_rtt_us: rtts_us.iter().sum::<u64>().checked_div(received.into()).unwrap_or(0),
+            max_rtt_us: rtts_us.iter().cloned().max().unwrap_or(0),
+        }
+    }
+}
+
+/// What a TX power report is about.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum TxPowerSource {
+    /// The answer to `IBluetoothLinkDiagnostics::read_tx_power`.
+    LocalRead = 0,
+    /// The local TX power level changed.
+    LocalChange,
+    /// The TX power level of the remote device changed.
+    RemoteChange,
+}
+
+/// A TX power level of an LE link, in dBm.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxPowerReport {
+    pub source: TxPowerSource,
+    pub phy: LePhy,
+    /// `TX_POWER_UNAVAILABLE` if unknown, or `TX_POWER_UNMANAGED` for a remote device that doesn't
+    /// manage its power.
+    pub tx_power: i32,
+    /// Highest level the controller can transmit at on the PHY. Only read, `TX_POWER_UNAVAILABLE`
+    /// for changes.
+    pub max_tx_power: i32,
+    /// Whether the changed level is the lowest or highest one the device can transmit at.
+    pub at_min: bool,
+    pub at_max: bool,
+    /// Difference to the level before a change, or `TX_POWER_UNAVAILABLE`.
+    pub delta: i32,
+}
+
+/// Where the path loss of an LE link is with regard to the range set with
+/// `IBluetoothLinkDiagnostics::set_path_loss_range`.
+#[derive(Clone, Copy, Debug, FromPrimitive, PartialEq, ToPrimitive)]
+#[repr(u32)]
+pub enum PathLossZone {
+    Low = 0,
+    InRange,
+    High,
+}
+
+/// PHY numbering of the LE power control commands and events.
+fn phy_to_hci(phy: &LePhy) -> Option<u8> {
+    match phy {
+        LePhy::Phy1m => Some(1),
+        LePhy::Phy2m => Some(2),
+        // Coded with S=8, the longest range.
+        LePhy::PhyCoded => Some(3),
+        LePhy::Invalid => None,
+    }
+}
+
+fn phy_from_hci(phy: u8) -> LePhy {
+    match phy {
+        1 => LePhy::Phy1m,
+        2 => LePhy::Phy2m,
+        3 | 4 => LePhy::PhyCoded,
+        _ => LePhy::Invalid,
+    }
+}
+
+/// Link diagnostics events.
+pub trait IBluetoothLinkDiagnosticsCallback: RPCProxy {
+    /// The response to echo request `sequence`, counting from 1, came after `rtt_us`
+    /// microseconds.
+    fn on_ping_reply(&self, device: BluetoothDevice, sequence: u32, rtt_us: u64);
+
+    /// There was no response to echo request `sequence` within 5 seconds.
+    fn on_ping_lost(&self, device: BluetoothDevice, sequence: u32);
+
+    /// No more requests are sent to `device`, either because all of them were, the ping was
+    /// cancelled, or the link went down.
+    fn on_ping_complete(&self, device: BluetoothDevice, stats: PingStats);
+
+    /// A TX power level of the LE link to `device`. `status` only fails for reads.
+    fn on_tx_power_reported(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        report: TxPowerReport,
+    );
+
+    /// The path loss of the LE link to `device` moved into `zone`, at `path_loss` dB, or 255 if
+    /// unknown.
+    fn on_path_loss_zone_entered(
+        &self,
+        device: BluetoothDevice,
+        zone: PathLossZone,
+        path_loss: u32,
+    );
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum LinkDiagnosticsActions {
+    /// Time for the next echo request to the device.
+    SendEcho(RawAddress),
+    /// The echo request `sequence` got no response in time.
+    EchoTimeout(RawAddress, u32),
+}
+
+struct Ping {
+    device: BluetoothDevice,
+    count: u32,
+    size: u32,
+    /// Sequence number of the last request sent.
+    sequence: u32,
+    /// When the last request was sent, if it is still waiting for its response.
+    sent_at: Option<Instant>,
+    rtts: Vec<Duration>,
+    timer: Option<JoinHandle<()>>,
+}
+
+/// Data of an echo request of `size` octets: "ABC..." like `l2ping` sends.
+fn echo_data(size: u32) -> Vec<u8> {
+    (0..size).map(|i| b'A' + (i % 40) as u8).collect()
+}
+
+/// Implementation of the link diagnostics API.
+pub struct BluetoothLinkDiagnostics {
+    tx: Sender<Message>,
+    intf: Arc<Mutex<BluetoothInterface>>,
+    callbacks: Callbacks<dyn IBluetoothLinkDiagnosticsCallback + Send>,
+    pings: HashMap<RawAddress, Ping>,
+    /// Devices whose TX power or path loss was asked for, to report them with.
+    le_devices: HashMap<RawAddress, BluetoothDevice>,
+}
+
+impl BluetoothLinkDiagnostics {
+    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> Self {
+        BluetoothLinkDiagnostics {
+            tx: tx.clone(),
+            intf,
+            callbacks: Callbacks::new(tx, Message::LinkDiagnosticsCallbackDisconnected),
+            pings: HashMap::new(),
+            le_devices: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_link_diagnostics_actions(&mut self, action: LinkDiagnosticsActions) {
+        match action {
+            LinkDiagnosticsActions::SendEcho(address) => {
+                if !self.send_echo(&address) {
+                    self.finish(&address);
+                }
+            }
+            LinkDiagnosticsActions::EchoTimeout(address, sequence) => {
+                let ping = match self.pings.get_mut(&address) {
+                    Some(ping) if ping.sequence == sequence && ping.sent_at.is_some() => ping,
+                    _ => return,
+                };
+                ping.sent_at = None;
+                let device = ping.device.clone();
+                self.callbacks.for_all_callbacks(|callback| {
+                    callback.on_ping_lost(device.clone(), sequence);
+                });
+                self.next_echo(&address);
+            }
+        }
+    }
+
+    /// Handles the data echoed back by `address`.
+    pub(crate) fn echo_response(&mut self, address: RawAddress, data: Vec<u8>) {
+        let ping = match self.pings.get_mut(&address) {
+            Some(ping) => ping,
+            None => return,
+        };
+        let rtt = match ping.sent_at.take() {
+            Some(sent_at) => sent_at.elapsed(),
+            None => return,
+        };
+        if let Some(timer) = ping.timer.take() {
+            timer.abort();
+        }
+        if data != echo_data(ping.size) {
+            debug!("[{}]: Echo response data differs from the request", address.to_string());
+        }
+
+        ping.rtts.push(rtt);
+        let (device, sequence) = (ping.device.clone(), ping.sequence);
+        let rtt_us = rtt.as_micros() as u64;
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_ping_reply(device.clone(), sequence, rtt_us);
+        });
+        self.next_echo(&address);
+    }
+
+    fn le_device(&self, address: &RawAddress) -> BluetoothDevice {
+        self.le_devices
+            .get(address)
+            .cloned()
+            .unwrap_or_else(|| BluetoothDevice::new(*address, String::from("")))
+    }
+
+    /// Handles the TX power levels read for `address`, with an HCI `status`.
+    pub(crate) fn tx_power_levels(
+        &mut self,
+        address: RawAddress,
+        status: u8,
+        phy: u8,
+        tx_power: i8,
+        max_tx_power: i8,
+    ) {
+        let (status, tx_power, max_tx_power) = match status {
+            0 => (BtStatus::Success, tx_power.into(), max_tx_power.into()),
+            _ => {
+                warn!("[{}]: Reading TX power failed, status {}", address.to_string(), status);
+                (BtStatus::Fail, TX_POWER_UNAVAILABLE, TX_POWER_UNAVAILABLE)
+            }
+        };
+        let report = TxPowerReport {
+            source: TxPowerSource::LocalRead,
+            phy: phy_from_hci(phy),
+            tx_power,
+            max_tx_power,
+            at_min: false,
+            at_max: false,
+            delta: TX_POWER_UNAVAILABLE,
+        };
+        let device = self.le_device(&address);
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_tx_power_reported(device.clone(), status.clone(), report.clone());
+        });
+    }
+
+    /// Handles a TX power change of the link to `address`.
+    pub(crate) fn tx_power_report(
+        &mut self,
+        address: RawAddress,
+        reason: u8,
+        phy: u8,
+        tx_power: i8,
+        flags: u8,
+        delta: i8,
+    ) {
+        let report = TxPowerReport {
+            // Reason 0 is a local change, 1 and 2 remote ones, either reported by the remote
+            // device or read from it.
+            source: match reason {
+                0 => TxPowerSource::LocalChange,
+                _ => TxPowerSource::RemoteChange,
+            },
+            phy: phy_from_hci(phy),
+            tx_power: tx_power.into(),
+            max_tx_power: TX_POWER_UNAVAILABLE,
+            at_min: flags & 0x01 != 0,
+            at_max: flags & 0x02 != 0,
+            delta: delta.into(),
+        };
+        let device = self.le_device(&address);
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_tx_power_reported(device.clone(), BtStatus::Success, report.clone());
+        });
+    }
+
+    /// Handles the path loss of the link to `address` moving into another zone.
+    pub(crate) fn path_loss(&mut self, address: RawAddress, path_loss: u8, zone: u8) {
+        let zone = match zone {
+            0 => PathLossZone::Low,
+            1 => PathLossZone::InRange,
+            _ => PathLossZone::High,
+        };
+        let device = self.le_device(&address);
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_path_loss_zone_entered(device.clone(), zone, path_loss.into());
+        });
+    }
+
+    /// Sends the next request to `address` after the interval, or ends the ping after the last.
+    fn next_echo(&mut self, address: &RawAddress) {
+        let ping = match self.pings.get_mut(address) {
+            Some(ping) => ping,
+            None => return,
+        };
+        if ping.sequence >= ping.count {
+            self.finish(address);
+            return;
+        }
+
+        let txl = self.tx.clone();
+        let address = *address;
+        ping.timer = Some(tokio::spawn(async move {
+            time::sleep(PING_INTERVAL).await;
+            let _ =
+                txl.send(Message::LinkDiagnostics(LinkDiagnosticsActions::SendEcho(address))).await;
+        }));
+    }
+
+    /// Sends the next echo request of the ping of `address`. Returns false if it couldn't be.
+    fn send_echo(&mut self, address: &RawAddress) -> bool {
+        let ping = match self.pings.get_mut(address) {
+            Some(ping) => ping,
+            None => return true,
+        };
+        if !self.intf.lock().unwrap().send_l2cap_echo(address, &echo_data(ping.size)) {
+            return false;
+        }
+
+        ping.sequence += 1;
+        ping.sent_at = Some(Instant::now());
+        let txl = self.tx.clone();
+        let (address, sequence) = (*address, ping.sequence);
+        ping.timer = Some(tokio::spawn(async move {
+            time::sleep(ECHO_TIMEOUT).await;
+            let _ = txl
+                .send(Message::LinkDiagnostics(LinkDiagnosticsActions::EchoTimeout(
+                    address, sequence,
+                )))
+                .await;
+        }));
+        true
+    }
+
+    fn finish(&mut self, address: &RawAddress) {
+        let ping = match self.pings.remove(address) {
+            Some(ping) => ping,
+            None => return,
+        };
+        if let Some(timer) = ping.timer {
+            timer.abort();
+        }
+
+        let stats = PingStats::new(ping.sequence, &ping.rtts);
+        info!(
+            "[{}]: Ping done, {} of {} echo requests answered",
+            address.to_string(),
+            stats.received,
+            stats.sent
+        );
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_ping_complete(ping.device.clone(), stats.clone());
+        });
+    }
+}
+
+impl IBluetoothLinkDiagnostics for BluetoothLinkDiagnostics {
+    fn register_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothLinkDiagnosticsCallback + Send>,
+    ) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn ping(&mut self, device: BluetoothDevice, count: u32, size: u32) -> bool {
+        if count == 0 || count > MAX_PING_COUNT || size > MAX_ECHO_SIZE {
+            return false;
+        }
+        let address = device.address;
+        if self.pings.contains_key(&address) {
+            return false;
+        }
+
+        self.pings.insert(
+            address,
+            Ping { device, count, size, sequence: 0, sent_at: None, rtts: vec![], timer: None },
+        );
+        if !self.send_echo(&address) {
+            self.pings.remove(&address);
+            return false;
+        }
+        true
+    }
+
+    fn cancel_ping(&mut self, device: BluetoothDevice) -> bool {
+        if !self.pings.contains_key(&device.address) {
+            return false;
+        }
+        self.finish(&device.address);
+        true
+    }
+
+    fn read_tx_power(&mut self, device: BluetoothDevice, phy: LePhy) -> bool {
+        let phy = match phy_to_hci(&phy) {
+            Some(phy) => phy,
+            None => return false,
+        };
+        if !self.intf.lock().unwrap().read_le_tx_power_levels(&device.address, phy) {
+            return false;
+        }
+        self.le_devices.insert(device.address, device);
+        true
+    }
+
+    fn set_tx_power_reporting(&mut self, device: BluetoothDevice, enable: bool) -> bool {
+        if !self.intf.lock().unwrap().set_le_tx_power_reporting(&device.address, enable) {
+            return false;
+        }
+        self.le_devices.insert(device.address, device);
+        true
+    }
+
+    fn set_path_loss_range(
+        &mut self,
+        device: BluetoothDevice,
+        low_db: u32,
+        high_db: u32,
+        hysteresis_db: u32,
+    ) -> bool {
+        if low_db > high_db || high_db >= PATH_LOSS_UNUSED || 2 * hysteresis_db > high_db - low_db {
+            return false;
+        }
+
+        // The same hysteresis on both thresholds, so that the path loss always has to get back
+        // well into the range to enter it.
+        let hysteresis = hysteresis_db as u8;
+        if !self.intf.lock().unwrap().set_le_path_loss_reporting(
+            &device.address,
+            true,
+            high_db as u8,
+            hysteresis,
+            low_db as u8,
+            hysteresis,
+        ) {
+            return false;
+        }
+        self.le_devices.insert(device.address, device);
+        true
+    }
+
+    fn clear_path_loss_range(&mut self, device: BluetoothDevice) -> bool {
+        self.intf.lock().unwrap().set_le_path_loss_reporting(&device.address, false, 0, 0, 0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping_stats() {
+        let rtts =
+            [Duration::from_micros(1500), Duration::from_micros(900), Duration::from_millis(3)];
+        assert_eq!(
+            PingStats::new(4, &rtts),
+            PingStats { sent: 4, received: 3, min_rtt_us: 900, avg_rtt_us: 1800, max_rtt_us: 3000 }
+        );
+        assert_eq!(PingStats::new(2, &[]), PingStats { sent: 2, ..Default::default() });
+    }
+
+    #[test]
+    fn test_phy_to_hci() {
+        assert_eq!(phy_to_hci(&LePhy::PhyCoded), Some(3));
+        assert_eq!(phy_to_hci(&LePhy::Invalid), None);
+        assert_eq!(phy_from_hci(4), LePhy::PhyCoded);
+        assert_eq!(phy_from_hci(0), LePhy::Invalid);
+    }
+
+    #[test]
+    fn test_echo_data() {
+        assert_eq!(echo_data(0), Vec::<u8>::new());
+        assert_eq!(&echo_data(44)[..4], b"ABCD");
+        assert_eq!(echo_data(44)[40], b'A');
+    }
+}
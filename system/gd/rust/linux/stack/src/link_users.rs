@@ -0,0 +1,106 @@
+//! Reference counts of the LE links shared by the GATT clients.
+//!
+//! Several clients, such as a battery monitor and an app, often talk to the same LE device at
+//! once. libbluetooth keeps the link up until the last of their GATT connections closes, and a
+//! client connecting while the link is up simply attaches to it. Dropping the link outright must
+//! then wait until no client uses it anymore, which is what these counts tell the adapter.
+//!
+//! Opportunistic connections don't count: they only ride on a link that others keep up.
+
+use bt_topshim::btif::RawAddress;
+use std::collections::{HashMap, HashSet};
+
+/// GATT clients using the LE link to each device.
+#[derive(Default)]
+pub struct LinkUsers {
+    users: HashMap<RawAddress, HashSet<i32>>,
+    /// Connections in progress, and those of them that were asked to be opportunistic.
+    pending: HashSet<(RawAddress, i32)>,
+    opportunistic: HashSet<(RawAddress, i32)>,
+}
+
+impl LinkUsers {
+    pub fn new() -> LinkUsers {
+        LinkUsers::default()
+    }
+
+    /// `client_id` started connecting to `address`.
+    pub(crate) fn connecting(&mut self, address: RawAddress, client_id: i32, opportunistic: bool) {
+        self.pending.insert((address, client_id));
+        if opportunistic {
+            self.opportunistic.insert((address, client_id));
+        } else {
+            self.opportunistic.remove(&(address, client_id));
+        }
+    }
+
+    /// The connection of `client_id` to `address` completed, successfully or not.
+    pub(crate) fn connected(&mut self, address: RawAddress, client_id: i32, success: bool) {
+        self.pending.remove(&(address, client_id));
+        let opportunistic = self.opportunistic.remove(&(address, client_id));
+        if success && !opportunistic {
+            self.users.entry(address).or_default().insert(client_id);
+        }
+    }
+
+    pub(crate) fn disconnected(&mut self, address: RawAddress, client_id: i32) {
+        if let Some(users) = self.users.get_mut(&address) {
+            users.remove(&client_id);
+            if users.is_empty() {
+                self.users.remove(&address);
+            }
+        }
+    }
+
+    /// Forgets everything about `client_id`, which was unregistered.
+    pub(crate) fn remove_client(&mut self, client_id: i32) {
+        self.pending.retain(|(_, id)| *id != client_id);
+        self.opportunistic.retain(|(_, id)| *id != client_id);
+        self.users.retain(|_, users| {
+            users.remove(&client_id);
+            !users.is_empty()
+        });
+    }
+
+    /// Returns how many clients keep the LE link to `address` up.
+    pub fn count(&self, address: &RawAddress) -> usize {
+        self.users.get(address).map_or(0, |users| users.len())
+    }
+
+    /// Returns whether a client is connecting to `address`, opportunistically or not, which
+    /// means a link to it coming up was asked for by this host.
+    pub fn is_connecting(&self, address: &RawAddress) -> bool {
+        self.pending.iter().any(|(pending, _)| pending == address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_users() {
+        let address = RawAddress { val: [1, 2, 3, 4, 5, 6] };
+        let mut users = LinkUsers::new();
+
+        users.connecting(address, 1, false);
+        users.connected(address, 1, true);
+        users.connecting(address, 2, false);
+        users.connected(address, 2, true);
+        assert_eq!(users.count(&address), 2);
+
+        // Opportunistic and failed connections don't hold the link.
+        users.connecting(address, 3, true);
+        assert!(users.is_connecting(&address));
+        users.connected(address, 3, true);
+        assert!(!users.is_connecting(&address));
+        users.connecting(address, 4, false);
+        users.connected(address, 4, false);
+        assert_eq!(users.count(&address), 2);
+
+        users.disconnected(address, 1);
+        assert_eq!(users.count(&address), 1);
+        users.remove_client(2);
+        assert_eq!(users.count(&address), 0);
+    }
+}
@@ -0,0 +1,592 @@
+//! OBEX (IrDA Object Exchange) packets and a minimal client session.
+//!
+//! OBEX is the session protocol underneath OPP, PBAP and MAP. A packet is an opcode (requests)
+//! or response code (responses), a 2 byte length, a few fixed fields for CONNECT and SETPATH, and
+//! a list of headers. Only what the profiles in this crate need is implemented; SRM and
+//! authentication are not supported.
+
+use bt_topshim::profiles::socket;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::os::unix::io::{FromRawFd, RawFd};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+/// Set on opcodes and response codes of the last packet of a request or response.
+pub const FINAL_BIT: u8 = 0x80;
+
+// Request opcodes, including the final bit where the spec requires it.
+pub const OP_CONNECT: u8 = 0x80;
+pub const OP_DISCONNECT: u8 = 0x81;
+pub const OP_PUT: u8 = 0x02;
+pub const OP_GET: u8 = 0x03;
+pub const OP_SETPATH: u8 = 0x85;
+pub const OP_ABORT: u8 = 0xff;
+
+// Response codes, including the final bit.
+pub const RSP_CONTINUE: u8 = 0x90;
+pub const RSP_SUCCESS: u8 = 0xa0;
+pub const RSP_BAD_REQUEST: u8 = 0xc0;
+pub const RSP_UNAUTHORIZED: u8 = 0xc1;
+pub const RSP_FORBIDDEN: u8 = 0xc3;
+pub const RSP_NOT_FOUND: u8 = 0xc4;
+pub const RSP_NOT_ACCEPTABLE: u8 = 0xc6;
+pub const RSP_ENTITY_TOO_LARGE: u8 = 0xcd;
+pub const RSP_INTERNAL_SERVER_ERROR: u8 = 0xd0;
+pub const RSP_SERVICE_UNAVAILABLE: u8 = 0xd3;
+
+/// SETPATH flag that makes the server fail instead of creating a missing folder.
+const SETPATH_DONT_CREATE: u8 = 0x02;
+
+/// OBEX version 1.0, sent in CONNECT packets.
+const OBEX_VERSION: u8 = 0x10;
+
+/// Largest packet this side accepts. The actual maximum is the smaller of this and the peer's.
+pub const DEFAULT_MAX_PACKET_LEN: u16 = 0xfffe;
+
+/// Smallest maximum packet length the spec allows a peer to announce.
+const MIN_PACKET_LEN: u16 = 255;
+
+// Length of the opcode and packet length fields.
+const PACKET_HEADER_LEN: usize = 3;
+
+// The top 2 bits of a header id tell how its value is encoded.
+const HI_UNICODE: u8 = 0x00;
+const HI_BYTES: u8 = 0x40;
+const HI_U8: u8 = 0x80;
+const HI_U32: u8 = 0xc0;
+const HI_ENCODING_MASK: u8 = 0xc0;
+
+const HI_NAME: u8 = 0x01;
+const HI_TYPE: u8 = 0x42;
+const HI_LENGTH: u8 = 0xc3;
+const HI_TARGET: u8 = 0x46;
+const HI_BODY: u8 = 0x48;
+const HI_END_OF_BODY: u8 = 0x49;
+const HI_WHO: u8 = 0x4a;
+const HI_CONNECTION_ID: u8 = 0xcb;
+const HI_APP_PARAMETERS: u8 = 0x4c;
+
+#[derive(Debug)]
+pub enum ObexError {
+    Io(io::Error),
+    /// The peer sent something that isn't a valid OBEX packet.
+    InvalidPacket(String),
+    /// The peer answered with an unexpected response code.
+    Response(u8),
+}
+
+impl Display for ObexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ObexError::Io(e) => write!(f, "{}", e),
+            ObexError::InvalidPacket(reason) => write!(f, "invalid packet: {}", reason),
+            ObexError::Response(code) => write!(f, "response {:#04x}", code),
+        }
+    }
+}
+
+impl From<io::Error> for ObexError {
+    fn from(e: io::Error) -> Self {
+        ObexError::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Header {
+    Name(String),
+    /// MIME type of the object.
+    Type(String),
+    /// Size of the object in bytes.
+    Length(u32),
+    Target(Vec<u8>),
+    Body(Vec<u8>),
+    EndOfBody(Vec<u8>),
+    Who(Vec<u8>),
+    ConnectionId(u32),
+    AppParameters(Vec<u8>),
+    /// A header this module doesn't interpret, with its raw value.
+    Unknown(u8, Vec<u8>),
+}
+
+impl Header {
+    fn id(&self) -> u8 {
+        match self {
+            Header::Name(_) => HI_NAME,
+            Header::Type(_) => HI_TYPE,
+            Header::Length(_) => HI_LENGTH,
+            Header::Target(_) => HI_TARGET,
+            Header::Body(_) => HI_BODY,
+            Header::EndOfBody(_) => HI_END_OF_BODY,
+            Header::Who(_) => HI_WHO,
+            Header::ConnectionId(_) => HI_CONNECTION_ID,
+            Header::AppParameters(_) => HI_APP_PARAMETERS,
+            Header::Unknown(id, _) => *id,
+        }
+    }
+
+    fn raw_value(&self) -> Vec<u8> {
+        match self {
+            Header::Name(name) => {
+                // Null terminated UTF-16BE. An empty name has no terminator.
+                if name.is_empty() {
+                    return vec![];
+                }
+                name.encode_utf16()
+                    .chain(std::iter::once(0))
+                    .flat_map(|c| c.to_be_bytes())
+                    .collect()
+            }
+            Header::Type(mime) => mime.bytes().chain(std::iter::once(0)).collect(),
+            Header::Length(v) | Header::ConnectionId(v) => v.to_be_bytes().to_vec(),
+            Header::Target(v)
+            | Header::Body(v)
+            | Header::EndOfBody(v)
+            | Header::Who(v)
+            | Header::AppParameters(v)
+            | Header::Unknown(_, v) => v.clone(),
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        let id = self.id();
+        let value = self.raw_value();
+
+        out.push(id);
+        match id & HI_ENCODING_MASK {
+            // Fixed size values have no length prefix.
+            HI_U8 | HI_U32 => {}
+            _ => out.extend_from_slice(&((value.len() + 3) as u16).to_be_bytes()),
+        }
+        out.extend_from_slice(&value);
+    }
+
+    fn decode(id: u8, value: &[u8]) -> Result<Header, ObexError> {
+        let u32_value = || -> u32 { u32::from_be_bytes([value[0], value[1], value[2], value[3]]) };
+
+        Ok(match id {
+            HI_NAME => {
+                if value.len() % 2 != 0 {
+                    return Err(ObexError::InvalidPacket(String::from("odd length name")));
+                }
+                let chars: Vec<u16> = value
+                    .chunks(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .take_while(|c| *c != 0)
+                    .collect();
+                Header::Name(String::from_utf16_lossy(&chars))
+            }
+            HI_TYPE => Header::Type(
+                String::from_utf8_lossy(value).trim_end_matches(char::from(0)).to_string(),
+            ),
+            HI_LENGTH => Header::Length(u32_value()),
+            HI_TARGET => Header::Target(value.to_vec()),
+            HI_BODY => Header::Body(value.to_vec()),
+            HI_END_OF_BODY => Header::EndOfBody(value.to_vec()),
+            HI_WHO => Header::Who(value.to_vec()),
+            HI_CONNECTION_ID => Header::ConnectionId(u32_value()),
+            HI_APP_PARAMETERS => Header::AppParameters(value.to_vec()),
+            _ => Header::Unknown(id, value.to_vec()),
+        })
+    }
+}
+
+/// A request or response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    /// Opcode of a request or response code of a response, including the final bit.
+    pub code: u8,
+    /// Fixed fields between the packet length and the headers: version, flags and maximum
+    /// packet length for CONNECT, flags and constants for SETPATH requests. Empty otherwise.
+    pub params: Vec<u8>,
+    pub headers: Vec<Header>,
+}
+
+impl Packet {
+    pub fn new(code: u8) -> Packet {
+        Packet { code, params: vec![], headers: vec![] }
+    }
+
+    /// Creates a CONNECT request or response announcing `max_packet_len`.
+    pub fn connect(code: u8, max_packet_len: u16) -> Packet {
+        let mut params = vec![OBEX_VERSION, 0];
+        params.extend_from_slice(&max_packet_len.to_be_bytes());
+        Packet { code, params, headers: vec![] }
+    }
+
+    pub fn with_header(mut self, header: Header) -> Packet {
+        self.headers.push(header);
+        self
+    }
+
+    /// Maximum packet length announced by a CONNECT packet.
+    pub fn max_packet_len(&self) -> Option<u16> {
+        match self.params.len() {
+            4 => Some(u16::from_be_bytes([self.params[2], self.params[3]])),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.headers.iter().find_map(|h| match h {
+            Header::Name(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn mime_type(&self) -> Option<&str> {
+        self.headers.iter().find_map(|h| match h {
+            Header::Type(mime) => Some(mime.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn length(&self) -> Option<u32> {
+        self.headers.iter().find_map(|h| match h {
+            Header::Length(len) => Some(*len),
+            _ => None,
+        })
+    }
+
+    pub fn connection_id(&self) -> Option<u32> {
+        self.headers.iter().find_map(|h| match h {
+            Header::ConnectionId(id) => Some(*id),
+            _ => None,
+        })
+    }
+
+    pub fn app_parameters(&self) -> Option<&[u8]> {
+        self.headers.iter().find_map(|h| match h {
+            Header::AppParameters(params) => Some(params.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Returns the contents of all Body and End-of-Body headers, and whether the End-of-Body
+    /// header was present.
+    pub fn body(&self) -> (Vec<u8>, bool) {
+        let mut body = vec![];
+        let mut end = false;
+        for header in self.headers.iter() {
+            match header {
+                Header::Body(data) => body.extend_from_slice(data),
+                Header::EndOfBody(data) => {
+                    body.extend_from_slice(data);
+                    end = true;
+                }
+                _ => {}
+            }
+        }
+        (body, end)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.code, 0, 0];
+        out.extend_from_slice(&self.params);
+        for header in self.headers.iter() {
+            header.encode(&mut out);
+        }
+
+        let len = (out.len() as u16).to_be_bytes();
+        out[1] = len[0];
+        out[2] = len[1];
+        out
+    }
+
+    /// Parses a complete packet. `params_len` is the length of the fixed fields following the
+    /// packet length, see `params`.
+    pub fn from_bytes(bytes: &[u8], params_len: usize) -> Result<Packet, ObexError> {
+        let invalid = |reason: &str| ObexError::InvalidPacket(String::from(reason));
+
+        if bytes.len() < PACKET_HEADER_LEN + params_len {
+            return Err(invalid("too short"));
+        }
+        if u16::from_be_bytes([bytes[1], bytes[2]]) as usize != bytes.len() {
+            return Err(invalid("length mismatch"));
+        }
+
+        let params = bytes[PACKET_HEADER_LEN..PACKET_HEADER_LEN + params_len].to_vec();
+        let mut headers = vec![];
+        let mut rest = &bytes[PACKET_HEADER_LEN + params_len..];
+
+        while !rest.is_empty() {
+            let id = rest[0];
+            let (value, len) = match id & HI_ENCODING_MASK {
+                HI_UNICODE | HI_BYTES => {
+                    if rest.len() < 3 {
+                        return Err(invalid("truncated header"));
+                    }
+                    let len = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+                    if len < 3 || len > rest.len() {
+                        return Err(invalid("bad header length"));
+                    }
+                    (&rest[3..len], len)
+                }
+                encoding => {
+                    let len = if encoding == HI_U32 { 5 } else { 2 };
+                    if rest.len() < len {
+                        return Err(invalid("truncated header"));
+                    }
+                    (&rest[1..len], len)
+                }
+            };
+
+            headers.push(Header::decode(id, value)?);
+            rest = &rest[len..];
+        }
+
+        Ok(Packet { code: bytes[0], params, headers })
+    }
+}
+
+/// Encodes the tag-length-value triplets of an Application Parameters header.
+pub fn encode_app_parameters(params: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut out = vec![];
+    for (tag, value) in params.iter() {
+        out.push(*tag);
+        out.push(value.len() as u8);
+        out.extend_from_slice(value);
+    }
+    out
+}
+
+/// Parses an Application Parameters header. A truncated last triplet is dropped.
+pub fn parse_app_parameters(data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut params = vec![];
+    let mut rest = data;
+
+    while rest.len() >= 2 {
+        let len = rest[1] as usize;
+        if rest.len() < 2 + len {
+            break;
+        }
+        params.push((rest[0], rest[2..2 + len].to_vec()));
+        rest = &rest[2 + len..];
+    }
+
+    params
+}
+
+/// Reads one packet from `stream`. See `Packet::from_bytes` for `params_len`.
+pub async fn read_packet<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    params_len: usize,
+) -> Result<Packet, ObexError> {
+    let mut bytes = vec![0u8; PACKET_HEADER_LEN];
+    stream.read_exact(&mut bytes).await?;
+
+    let len = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+    if len < PACKET_HEADER_LEN {
+        return Err(ObexError::InvalidPacket(String::from("bad packet length")));
+    }
+
+    bytes.resize(len, 0);
+    stream.read_exact(&mut bytes[PACKET_HEADER_LEN..]).await?;
+    Packet::from_bytes(&bytes, params_len)
+}
+
+/// Writes one packet to `stream`.
+pub async fn write_packet<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    packet: &Packet,
+) -> Result<(), ObexError> {
+    stream.write_all(&packet.to_bytes()).await?;
+    Ok(())
+}
+
+/// Number of params bytes that follow the packet length of requests with `opcode`.
+pub fn request_params_len(opcode: u8) -> usize {
+    match opcode {
+        OP_CONNECT => 4,
+        OP_SETPATH => 2,
+        _ => 0,
+    }
+}
+
+/// Turns a socket handed out by libbluetooth into a tokio stream.
+pub fn into_async_stream(stream: std::os::unix::net::UnixStream) -> Result<UnixStream, ObexError> {
+    stream.set_nonblocking(true)?;
+    Ok(UnixStream::from_std(stream)?)
+}
+
+/// Waits until the socket `fd` returned by `BtSocket::connect` is connected to the remote device
+/// and returns it as a stream. Takes ownership of `fd`.
+pub async fn wait_for_connection(fd: RawFd) -> Result<UnixStream, ObexError> {
+    let stream = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+
+    let stream = tokio::task::spawn_blocking(move || -> io::Result<_> {
+        socket::read_channel(fd)?;
+        let (signal, _) = socket::read_connection_complete(fd)?;
+        if signal.status != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("connection failed with status {}", signal.status),
+            ));
+        }
+        Ok(stream)
+    })
+    .await
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+
+    into_async_stream(stream)
+}
+
+/// Client side of an OBEX session.
+pub struct ObexClient<S> {
+    stream: S,
+    max_packet_len: u16,
+    connection_id: Option<u32>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> ObexClient<S> {
+    pub fn new(stream: S) -> ObexClient<S> {
+        ObexClient { stream, max_packet_len: MIN_PACKET_LEN, connection_id: None }
+    }
+
+    /// Connects the session, to the service identified by `target` if given.
+    pub async fn connect(&mut self, target: Option<Vec<u8>>) -> Result<Packet, ObexError> {
+        let mut request = Packet::connect(OP_CONNECT, DEFAULT_MAX_PACKET_LEN);
+        if let Some(target) = target {
+            request = request.with_header(Header::Target(target));
+        }
+
+        write_packet(&mut self.stream, &request).await?;
+        let response = read_packet(&mut self.stream, 4).await?;
+        if response.code != RSP_SUCCESS {
+            return Err(ObexError::Response(response.code));
+        }
+
+        self.max_packet_len = response
+            .max_packet_len()
+            .unwrap_or(MIN_PACKET_LEN)
+            .clamp(MIN_PACKET_LEN, DEFAULT_MAX_PACKET_LEN);
+        self.connection_id = response.connection_id();
+        Ok(response)
+    }
+
+    /// Sends one request packet and returns the response. The connection id of the session is
+    /// added to the first packet of every request by the caller through `start_request`.
+    pub async fn send(&mut self, request: &Packet) -> Result<Packet, ObexError> {
+        write_packet(&mut self.stream, request).await?;
+        read_packet(&mut self.stream, 0).await
+    }
+
+    /// Creates the first packet of a request, carrying the connection id if there is one.
+    pub fn start_request(&self, opcode: u8) -> Packet {
+        let packet = Packet::new(opcode);
+        match self.connection_id {
+            Some(id) => packet.with_header(Header::ConnectionId(id)),
+            None => packet,
+        }
+    }
+
+    /// Runs a GET request to completion and returns the final response along with the whole
+    /// object.
+    pub async fn get(&mut self, request: Packet) -> Result<(Packet, Vec<u8>), ObexError> {
+        let mut object = vec![];
+        let mut response = self.send(&request).await?;
+
+        loop {
+            object.extend_from_slice(&response.body().0);
+            match response.code {
+                RSP_CONTINUE => {
+                    response = self.send(&Packet::new(OP_GET | FINAL_BIT)).await?;
+                }
+                RSP_SUCCESS => return Ok((response, object)),
+                code => return Err(ObexError::Response(code)),
+            }
+        }
+    }
+
+    /// Changes the current folder of the server to the child folder `name`, or to the root
+    /// folder if `name` is empty.
+    pub async fn set_path(&mut self, name: &str) -> Result<(), ObexError> {
+        let mut request =
+            self.start_request(OP_SETPATH).with_header(Header::Name(String::from(name)));
+        request.params = vec![SETPATH_DONT_CREATE, 0];
+
+        let response = self.send(&request).await?;
+        match response.code {
+            RSP_SUCCESS => Ok(()),
+            code => Err(ObexError::Response(code)),
+        }
+    }
+
+    /// Aborts the current request.
+    pub async fn abort(&mut self) -> Result<(), ObexError> {
+        self.send(&self.start_request(OP_ABORT)).await.map(|_| ())
+    }
+
+    /// Disconnects the session. The transport is left open.
+    pub async fn disconnect(&mut self) -> Result<(), ObexError> {
+        let request = self.start_request(OP_DISCONNECT);
+        self.send(&request).await.map(|_| ())
+    }
+
+    /// Largest body that can be added to `packet` as a single Body header.
+    pub fn max_body_len(&self, packet: &Packet) -> usize {
+        // A Body header takes 3 bytes besides its value.
+        (self.max_packet_len as usize).saturating_sub(packet.to_bytes().len() + 3).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_round_trip() {
+        let packet = Packet::new(OP_PUT)
+            .with_header(Header::ConnectionId(1))
+            .with_header(Header::Name(String::from("photo.jpg")))
+            .with_header(Header::Type(String::from("image/jpeg")))
+            .with_header(Header::Length(1234))
+            .with_header(Header::Body(vec![1, 2, 3]));
+
+        let bytes = packet.to_bytes();
+        assert_eq!(bytes[0], OP_PUT);
+        assert_eq!(u16::from_be_bytes([bytes[1], bytes[2]]) as usize, bytes.len());
+        assert_eq!(Packet::from_bytes(&bytes, 0).unwrap(), packet);
+
+        let connect = Packet::connect(OP_CONNECT, 0x2000);
+        let parsed = Packet::from_bytes(&connect.to_bytes(), 4).unwrap();
+        assert_eq!(parsed.max_packet_len(), Some(0x2000));
+    }
+
+    #[test]
+    fn test_name_encoding() {
+        let mut out = vec![];
+        Header::Name(String::from("a")).encode(&mut out);
+        assert_eq!(out, vec![HI_NAME, 0x00, 0x07, 0x00, b'a', 0x00, 0x00]);
+
+        let mut out = vec![];
+        Header::Name(String::new()).encode(&mut out);
+        assert_eq!(out, vec![HI_NAME, 0x00, 0x03]);
+    }
+
+    #[test]
+    fn test_invalid_packets() {
+        assert!(Packet::from_bytes(&[OP_PUT, 0x00], 0).is_err());
+        // Length field doesn't match the packet.
+        assert!(Packet::from_bytes(&[OP_PUT, 0x00, 0x04], 0).is_err());
+        // Header length runs past the end of the packet.
+        assert!(Packet::from_bytes(&[OP_PUT, 0x00, 0x06, HI_BODY, 0x00, 0x10], 0).is_err());
+    }
+
+    #[test]
+    fn test_app_parameters() {
+        let params = vec![(0x04, vec![0x00, 0x10]), (0x07, vec![0x01])];
+        let encoded = encode_app_parameters(&params);
+        assert_eq!(encoded, vec![0x04, 0x02, 0x00, 0x10, 0x07, 0x01, 0x01]);
+        assert_eq!(parse_app_parameters(&encoded), params);
+        assert_eq!(parse_app_parameters(&encoded[..5]), vec![(0x04, vec![0x00, 0x10])]);
+    }
+
+    #[test]
+    fn test_body() {
+        let packet = Packet::new(RSP_SUCCESS)
+            .with_header(Header::Body(vec![1, 2]))
+            .with_header(Header::EndOfBody(vec![3]));
+        assert_eq!(packet.body(), (vec![1, 2, 3], true));
+    }
+}
@@ -0,0 +1,379 @@
+//! Arbitration of the LE radio between scanning, advertising and connection establishment.
+//!
+//! The controller has a single radio for all of them, and libbluetooth runs every scanner with
+//! the parameters of whichever one set them last. The parts of the stack that use the radio on
+//! behalf of clients ask for it here instead, and their requests are scheduled together:
+//!
+//! * Scans all run with the parameters of the most demanding one, so none of them gets less than
+//!   it asked for just because another scanner started after it.
+//! * While LE connections are being established, scans are duty cycled so the initiator gets
+//!   radio time. The scans that then get less than they asked for are downgraded.
+//! * Scans are also duty cycled while the platform asks for the shared antenna to be left to
+//!   Wi-Fi, see [`RadioArbiter::set_coex_scan_limit`].
+//! * Advertising sets beyond the number the controller supports are deferred until one ends.
+//!
+//! A request learns its grant when it is made. When a grant changes later because of other
+//! requests, the owner is told with a [`Message::RadioGrantChanged`].
+
+use bt_topshim::btif::RawAddress;
+use log::{debug, info};
+use std::collections::HashMap;
+use tokio::sync::mpsc::Sender;
+
+use crate::{queue_message, Message};
+
+/// Part of the stack using the radio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RadioUser {
+    /// Device discovery of the adapter.
+    Discovery,
+    AdvMonitor,
+    FastPair,
+    /// The GATT clients connecting to a device.
+    GattConnection(RawAddress),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RadioActivity {
+    Scan,
+    Advertise,
+    Connect,
+}
+
+/// LE scan interval and window, in units of 0.625ms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanDuty {
+    pub interval: u16,
+    pub window: u16,
+}
+
+impl ScanDuty {
+    /// Whether this scan listens for a larger part of the time than `other`, or as large a part
+    /// more often.
+    fn exceeds(&self, other: &ScanDuty) -> bool {
+        let ours = u32::from(self.window) * u32::from(other.interval);
+        let theirs = u32::from(other.window) * u32::from(self.interval);
+        ours > theirs || (ours == theirs && self.interval < other.interval)
+    }
+
+    fn capped(&self, percent: u32) -> ScanDuty {
+        let max_window = (u32::from(self.interval) * percent / 100).max(1) as u16;
+        ScanDuty { interval: self.interval, window: self.window.min(max_window) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RadioGrant {
+    /// The request goes ahead. Scans run with [`RadioArbiter::get_scan_duty`], which is at least
+    /// as demanding as each of them asked for.
+    Granted,
+    /// The scan goes ahead with [`RadioArbiter::get_scan_duty`], which listens less than asked.
+    Downgraded,
+    /// The request has to wait, and is granted once there is room for it.
+    Deferred,
+}
+
+/// Largest part of the scan interval scans may listen for while connections are established.
+const CONNECTING_SCAN_PERCENT: u32 = 25;
+
+struct Request {
+    /// What the scan asked for, for scan requests.
+    duty: Option<ScanDuty>,
+    /// Requests of the same activity are served in the order they were first made.
+    order: u64,
+    grant: RadioGrant,
+}
+
+/// Requests for the radio, and what each of them got.
+pub struct RadioArbiter {
+    tx: Sender<Message>,
+    requests: HashMap<(RadioUser, RadioActivity), Request>,
+    last_order: u64,
+    max_advertising_sets: usize,
+    scan_duty: Option<ScanDuty>,
+    /// Largest part of the scan interval scans may listen for, for coexistence with Wi-Fi.
+    coex_scan_percent: Option<u32>,
+}
+
+impl RadioArbiter {
+    pub fn new(tx: Sender<Message>) -> RadioArbiter {
+        RadioArbiter {
+            tx,
+            requests: HashMap::new(),
+            last_order: 0,
+            // Only known once the adapter reads the features of the controller.
+            max_advertising_sets: 1,
+            scan_duty: None,
+            coex_scan_percent: None,
+        }
+    }
+
+    /// Sets the number of advertising sets the controller can run at once.
+    pub fn set_max_advertising_sets(&mut self, count: usize) {
+        // Controllers without multiple advertising still run the legacy set.
+        self.max_advertising_sets = count.max(1);
+        self.schedule(None);
+    }
+
+    /// Caps scans at `percent` of their interval, or lifts the cap. Scans that then get less than
+    /// they asked for are downgraded, as when connections are established.
+    pub fn set_coex_scan_limit(&mut self, percent: Option<u32>) {
+        self.coex_scan_percent = percent.map(|percent| percent.clamp(1, 100));
+        self.schedule(None);
+    }
+
+    /// Asks for a scan with `duty`, replacing the previous scan request of `user`.
+    pub fn request_scan(&mut self, user: RadioUser, duty: ScanDuty) -> RadioGrant {
+        self.request(user, RadioActivity::Scan, Some(duty))
+    }
+
+    /// Asks to run an advertising set. Each user runs at most one.
+    pub fn request_advertising(&mut self, user: RadioUser) -> RadioGrant {
+        self.request(user, RadioActivity::Advertise, None)
+    }
+
+    /// Tells that `user` is establishing a connection. It is never deferred, but the scans are
+    /// duty cycled until it is released.
+    pub fn request_connection(&mut self, user: RadioUser) -> RadioGrant {
+        self.request(user, RadioActivity::Connect, None)
+    }
+
+    /// Ends the request of `user` for `activity`, if it made one.
+    pub fn release(&mut self, user: RadioUser, activity: RadioActivity) {
+        if self.requests.remove(&(user, activity)).is_some() {
+            debug!("Radio released by {:?} for {:?}", user, activity);
+            self.schedule(None);
+        }
+    }
+
+    /// Returns the parameters all scans run with, or None if there is no scan.
+    pub fn get_scan_duty(&self) -> Option<ScanDuty> {
+        self.scan_duty
+    }
+
+    /// Returns the grant of the request of `user` for `activity`, if it made one.
+    pub fn get_grant(&self, user: RadioUser, activity: RadioActivity) -> Option<RadioGrant> {
+        self.requests.get(&(user, activity)).map(|request| request.grant)
+    }
+
+    fn request(
+        &mut self,
+        user: RadioUser,
+        activity: RadioActivity,
+        duty: Option<ScanDuty>,
+    ) -> RadioGrant {
+        let key = (user, activity);
+        match self.requests.get_mut(&key) {
+            Some(request) => request.duty = duty,
+            None => {
+                self.last_order += 1;
+                let request = Request { duty, order: self.last_order, grant: RadioGrant::Deferred };
+                self.requests.insert(key, request);
+            }
+        }
+
+        self.schedule(Some(key));
+        let grant = self.requests[&key].grant;
+        debug!("Radio requested by {:?} for {:?}: {:?}", user, activity, grant);
+        grant
+    }
+
+    /// Works out the grants again, and tells the owners of the requests other than `requester`
+    /// whose grant changed. Scans are told whenever the parameters they run with change.
+    fn schedule(&mut self, requester: Option<(RadioUser, RadioActivity)>) {
+        let connecting =
+            self.requests.keys().any(|(_, activity)| *activity == RadioActivity::Connect);
+        let wanted = self.requests.values().filter_map(|request| request.duty).fold(
+            None,
+            |most: Option<ScanDuty>, duty| match most {
+                Some(most) if !duty.exceeds(&most) => Some(most),
+                _ => Some(duty),
+            },
+        );
+        let limit = match (connecting, self.coex_scan_percent) {
+            (true, Some(percent)) => Some(percent.min(CONNECTING_SCAN_PERCENT)),
+            (true, None) => Some(CONNECTING_SCAN_PERCENT),
+            (false, percent) => percent,
+        };
+        let scan_duty = match (wanted, limit) {
+            (Some(duty), Some(percent)) => Some(duty.capped(percent)),
+            (duty, _) => duty,
+        };
+        let scan_duty_changed = scan_duty != self.scan_duty;
+        if scan_duty_changed {
+            info!("Scanning with {:?}", scan_duty);
+        }
+        self.scan_duty = scan_duty;
+
+        let mut advertisers: Vec<(u64, (RadioUser, RadioActivity))> = self
+            .requests
+            .iter()
+            .filter(|((_, activity), _)| *activity == RadioActivity::Advertise)
+            .map(|(key, request)| (request.order, *key))
+            .collect();
+        advertisers.sort_unstable_by_key(|(order, _)| *order);
+        let granted_advertisers: Vec<(RadioUser, RadioActivity)> =
+            advertisers.iter().take(self.max_advertising_sets).map(|(_, key)| *key).collect();
+
+        let mut changes = vec![];
+        for (key, request) in self.requests.iter_mut() {
+            let grant = match key.1 {
+                RadioActivity::Scan => match (request.duty, scan_duty) {
+                    (Some(duty), Some(scan_duty)) if duty.exceeds(&scan_duty) => {
+                        RadioGrant::Downgraded
+                    }
+                    _ => RadioGrant::Granted,
+                },
+                RadioActivity::Advertise if granted_advertisers.contains(key) => {
+                    RadioGrant::Granted
+                }
+                RadioActivity::Advertise => RadioGrant::Deferred,
+                RadioActivity::Connect => RadioGrant::Granted,
+            };
+
+            let changed =
+                grant != request.grant || (key.1 == RadioActivity::Scan && scan_duty_changed);
+            request.grant = grant;
+            if changed && Some(*key) != requester {
+                changes.push((*key, grant));
+            }
+        }
+
+        for ((user, activity), grant) in changes {
+            debug!("Radio grant of {:?} for {:?} is now {:?}", user, activity, grant);
+            queue_message(&self.tx, Message::RadioGrantChanged(user, activity, grant));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::{channel, Receiver};
+
+    const PEER: RawAddress = RawAddress { val: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55] };
+
+    fn changes(rx: &mut Receiver<Message>) -> Vec<(RadioUser, RadioActivity, RadioGrant)> {
+        let mut changes = vec![];
+        while let Ok(message) = rx.try_recv() {
+            if let Message::RadioGrantChanged(user, activity, grant) = message {
+                changes.push((user, activity, grant));
+            }
+        }
+        changes
+    }
+
+    #[test]
+    fn test_scans_share_the_most_demanding_duty() {
+        let (tx, mut rx) = channel(16);
+        let mut arbiter = RadioArbiter::new(tx);
+        let low = ScanDuty { interval: 0x0800, window: 0x0030 };
+        let high = ScanDuty { interval: 0x0012, window: 0x0012 };
+
+        assert_eq!(arbiter.request_scan(RadioUser::AdvMonitor, low), RadioGrant::Granted);
+        assert_eq!(arbiter.get_scan_duty(), Some(low));
+
+        // The monitor now scans with the discovery's parameters instead of clobbering them.
+        assert_eq!(arbiter.request_scan(RadioUser::Discovery, high), RadioGrant::Granted);
+        assert_eq!(arbiter.get_scan_duty(), Some(high));
+        assert_eq!(
+            changes(&mut rx),
+            vec![(RadioUser::AdvMonitor, RadioActivity::Scan, RadioGrant::Granted)]
+        );
+
+        arbiter.release(RadioUser::Discovery, RadioActivity::Scan);
+        assert_eq!(arbiter.get_scan_duty(), Some(low));
+        assert_eq!(changes(&mut rx).len(), 1);
+
+        arbiter.release(RadioUser::AdvMonitor, RadioActivity::Scan);
+        assert_eq!(arbiter.get_scan_duty(), None);
+    }
+
+    #[test]
+    fn test_scans_are_duty_cycled_while_connecting() {
+        let (tx, mut rx) = channel(16);
+        let mut arbiter = RadioArbiter::new(tx);
+        let full = ScanDuty { interval: 0x0040, window: 0x0040 };
+        let low = ScanDuty { interval: 0x0800, window: 0x0030 };
+        arbiter.request_scan(RadioUser::Discovery, full);
+        arbiter.request_scan(RadioUser::AdvMonitor, low);
+        changes(&mut rx);
+
+        let connection = RadioUser::GattConnection(PEER);
+        assert_eq!(arbiter.request_connection(connection), RadioGrant::Granted);
+        assert_eq!(arbiter.get_scan_duty(), Some(ScanDuty { interval: 0x0040, window: 0x0010 }));
+
+        // The monitor still gets more than it asked for.
+        let mut got = changes(&mut rx);
+        got.sort_by_key(|(user, _, _)| *user == RadioUser::AdvMonitor);
+        assert_eq!(
+            got,
+            vec![
+                (RadioUser::Discovery, RadioActivity::Scan, RadioGrant::Downgraded),
+                (RadioUser::AdvMonitor, RadioActivity::Scan, RadioGrant::Granted),
+            ]
+        );
+
+        arbiter.release(connection, RadioActivity::Connect);
+        assert_eq!(arbiter.get_scan_duty(), Some(full));
+        assert_eq!(
+            arbiter.get_grant(RadioUser::Discovery, RadioActivity::Scan),
+            Some(RadioGrant::Granted)
+        );
+    }
+
+    #[test]
+    fn test_scans_are_capped_for_coex() {
+        let (tx, mut rx) = channel(16);
+        let mut arbiter = RadioArbiter::new(tx);
+        let full = ScanDuty { interval: 0x0040, window: 0x0040 };
+        arbiter.request_scan(RadioUser::Discovery, full);
+
+        arbiter.set_coex_scan_limit(Some(10));
+        assert_eq!(arbiter.get_scan_duty(), Some(ScanDuty { interval: 0x0040, window: 0x0006 }));
+        assert_eq!(
+            changes(&mut rx),
+            vec![(RadioUser::Discovery, RadioActivity::Scan, RadioGrant::Downgraded)]
+        );
+
+        // The tighter of the two caps applies while connecting.
+        let connection = RadioUser::GattConnection(PEER);
+        arbiter.request_connection(connection);
+        assert_eq!(arbiter.get_scan_duty(), Some(ScanDuty { interval: 0x0040, window: 0x0006 }));
+        arbiter.release(connection, RadioActivity::Connect);
+
+        arbiter.set_coex_scan_limit(None);
+        assert_eq!(arbiter.get_scan_duty(), Some(full));
+        assert_eq!(
+            arbiter.get_grant(RadioUser::Discovery, RadioActivity::Scan),
+            Some(RadioGrant::Granted)
+        );
+    }
+
+    #[test]
+    fn test_advertising_is_deferred_beyond_the_controller_sets() {
+        let (tx, mut rx) = channel(16);
+        let mut arbiter = RadioArbiter::new(tx);
+        arbiter.set_max_advertising_sets(0);
+
+        assert_eq!(arbiter.request_advertising(RadioUser::FastPair), RadioGrant::Granted);
+        assert_eq!(arbiter.request_advertising(RadioUser::AdvMonitor), RadioGrant::Deferred);
+
+        // Asking again doesn't jump the queue.
+        assert_eq!(arbiter.request_advertising(RadioUser::AdvMonitor), RadioGrant::Deferred);
+        assert!(changes(&mut rx).is_empty());
+
+        arbiter.release(RadioUser::FastPair, RadioActivity::Advertise);
+        assert_eq!(
+            changes(&mut rx),
+            vec![(RadioUser::AdvMonitor, RadioActivity::Advertise, RadioGrant::Granted)]
+        );
+
+        arbiter.request_advertising(RadioUser::FastPair);
+        arbiter.set_max_advertising_sets(2);
+        assert_eq!(
+            changes(&mut rx),
+            vec![(RadioUser::FastPair, RadioActivity::Advertise, RadioGrant::Granted)]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Coalescing of events that can be reported much more often than clients need them.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+struct Window {
+    started: Instant,
+    count: u32,
+    /// Whether an event was held back during this window.
+    pending: bool,
+}
+
+/// Allows at most `limit` events per key in each `period`, starting from the key's first event.
+///
+/// Events over the limit aren't lost: the key is marked pending and returned by
+/// [`RateLimiter::take_due`] once its window ends, so the caller can report the latest state then.
+pub struct RateLimiter<K: Eq + Hash + Clone> {
+    limit: u32,
+    period: Duration,
+    windows: HashMap<K, Window>,
+}
+
+impl<K: Eq + Hash + Clone> RateLimiter<K> {
+    /// Creates a limiter allowing `limit` events per key in each `period`. A limit of 0 allows
+    /// everything.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        RateLimiter { limit, period, windows: HashMap::new() }
+    }
+
+    pub fn get_limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Changes the limit. Events held back so far are still returned by `take_due`.
+    pub fn set_limit(&mut self, limit: u32) {
+        self.limit = limit;
+    }
+
+    /// Returns whether an event for `key` at `now` can be reported right away. If not, the key is
+    /// marked pending.
+    pub fn allow(&mut self, key: &K, now: Instant) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+
+        let period = self.period;
+        let window = self.windows.entry(key.clone()).or_insert(Window {
+            started: now,
+            count: 0,
+            pending: false,
+        });
+
+        // A new window also replaces any held back event, since this one is newer.
+        if now >= window.started + period {
+            window.started = now;
+            window.count = 0;
+            window.pending = false;
+        }
+
+        if window.count < self.limit {
+            window.count += 1;
+            return true;
+        }
+
+        window.pending = true;
+        false
+    }
+
+    /// Returns the pending keys whose window has ended by `now`. Each of them starts a new window
+    /// in which the caller's report counts as the first event.
+    pub fn take_due(&mut self, now: Instant) -> Vec<K> {
+        let period = self.period;
+        let mut due = vec![];
+
+        for (key, window) in self.windows.iter_mut() {
+            if window.pending && now >= window.started + period {
+                window.started = now;
+                window.count = 1;
+                window.pending = false;
+                due.push(key.clone());
+            }
+        }
+
+        due
+    }
+
+    /// Returns when the earliest pending key becomes due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.windows.values().filter(|w| w.pending).map(|w| w.started + self.period).min()
+    }
+
+    /// Forgets about `key`, including any event held back for it.
+    pub fn remove(&mut self, key: &K) {
+        self.windows.remove(key);
+    }
+
+    /// Forgets about all keys.
+    pub fn clear(&mut self) {
+        self.windows.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_limit_per_key() {
+        let mut limiter = RateLimiter::new(2, PERIOD);
+        let start = Instant::now();
+
+        assert!(limiter.allow(&1, start));
+        assert!(limiter.allow(&1, start));
+        assert!(!limiter.allow(&1, start));
+
+        // Other keys have their own windows.
+        assert!(limiter.allow(&2, start));
+
+        assert_eq!(limiter.next_due(), Some(start + PERIOD));
+        assert!(limiter.take_due(start).is_empty());
+        assert_eq!(limiter.take_due(start + PERIOD), vec![1]);
+        assert_eq!(limiter.next_due(), None);
+
+        // The report made by the caller counts towards the new window.
+        assert!(limiter.allow(&1, start + PERIOD));
+        assert!(!limiter.allow(&1, start + PERIOD));
+    }
+
+    #[test]
+    fn test_window_restarts() {
+        let mut limiter = RateLimiter::new(1, PERIOD);
+        let start = Instant::now();
+
+        assert!(limiter.allow(&1, start));
+        assert!(!limiter.allow(&1, start + PERIOD / 2));
+        assert!(limiter.allow(&1, start + PERIOD * 2));
+    }
+
+    #[test]
+    fn test_no_limit() {
+        let mut limiter = RateLimiter::new(0, PERIOD);
+        let start = Instant::now();
+
+        for _ in 0..10 {
+            assert!(limiter.allow(&1, start));
+        }
+        assert_eq!(limiter.next_due(), None);
+    }
+}
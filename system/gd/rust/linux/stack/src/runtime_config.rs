@@ -0,0 +1,177 @@
+//! Settings tuned in the field without a new build or a restart.
+//!
+//! The file is read when the daemon starts, and again on SIGHUP or when the manager is asked to
+//! reload it with `IBluetoothManager::reload_config`. It is written in the flat subset of TOML
+//! the other config files share, sections of `key = value` lines with optionally quoted values:
+//!
+//! ```text
+//! [Log]
+//! Level = "debug"
+//!
+//! [Discovery]
+//! # Slots of 0.625 ms.
+//! LeScanInterval = 96
+//! LeScanWindow = 48
+//!
+//! [Reconnect]
+//! Enabled = true
+//! MaxAttempts = 4
+//! ```
+//!
+//! A setting left out goes back to its default when the file is reloaded, except the discovery
+//! scan, which clients can also set and which is left as it is. Reloading also reads the interop
+//! database from [`INTEROP_CONFIG_PATH`](crate::interop::INTEROP_CONFIG_PATH) again.
+
+use log::{info, warn, LevelFilter};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::device_store::parse_ini;
+
+/// Where the runtime config is read from.
+pub const RUNTIME_CONFIG_PATH: &str = "/etc/bluetooth/btadapterd.toml";
+
+/// Log level of the daemon unless the config sets one.
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeConfig {
+    pub log_level: LevelFilter,
+    /// LE scan interval and window used by discovery, in slots of 0.625 ms.
+    pub le_scan_interval: Option<u16>,
+    pub le_scan_window: Option<u16>,
+    /// Whether devices are reconnected when their link is lost, and how many times that is tried,
+    /// or None for the stack default.
+    pub reconnect_enabled: bool,
+    pub max_reconnect_attempts: Option<u32>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            log_level: DEFAULT_LOG_LEVEL,
+            le_scan_interval: None,
+            le_scan_window: None,
+            reconnect_enabled: true,
+            max_reconnect_attempts: None,
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(section: &str, key: &str, value: &str) -> Option<T> {
+    let parsed = value.parse::<T>().ok();
+    if parsed.is_none() {
+        warn!("Invalid runtime config value {}.{} = {}", section, key, value);
+    }
+    parsed
+}
+
+impl RuntimeConfig {
+    /// Loads the config from [`RUNTIME_CONFIG_PATH`]. A missing or unreadable file results in the
+    /// defaults.
+    pub fn load_default() -> RuntimeConfig {
+        match RuntimeConfig::load(RUNTIME_CONFIG_PATH) {
+            Ok(config) => config,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => RuntimeConfig::default(),
+            Err(e) => {
+                warn!("Failed to load {}: {}", RUNTIME_CONFIG_PATH, e);
+                RuntimeConfig::default()
+            }
+        }
+    }
+
+    /// Loads the config from `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<RuntimeConfig> {
+        let config = RuntimeConfig::parse(&fs::read_to_string(path)?);
+        info!("Loaded runtime config {:?}", config);
+        Ok(config)
+    }
+
+    /// Parses the config. Unknown keys and invalid values are skipped, keeping the defaults.
+    fn parse(contents: &str) -> RuntimeConfig {
+        let mut config = RuntimeConfig::default();
+
+        for section in parse_ini(contents) {
+            for (key, value) in &section.entries {
+                let value = value.trim_matches('"');
+                let name = section.name.as_str();
+                match (name, key.as_str()) {
+                    ("Log", "Level") => {
+                        if let Some(level) = parse_value(name, key, value) {
+                            config.log_level = level;
+                        }
+                    }
+                    ("Discovery", "LeScanInterval") => {
+                        config.le_scan_interval = parse_value(name, key, value);
+                    }
+                    ("Discovery", "LeScanWindow") => {
+                        config.le_scan_window = parse_value(name, key, value);
+                    }
+                    ("Reconnect", "Enabled") => {
+                        if let Some(enabled) = parse_value(name, key, value) {
+                            config.reconnect_enabled = enabled;
+                        }
+                    }
+                    ("Reconnect", "MaxAttempts") => {
+                        config.max_reconnect_attempts = parse_value(name, key, value);
+                    }
+                    _ => warn!("Unknown runtime config key {}.{}", name, key),
+                }
+            }
+        }
+
+        config
+    }
+
+    /// Sets the log level of the daemon.
+    pub fn apply_log_level(&self) {
+        log::set_max_level(self.log_level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_runtime_config() {
+        let config = RuntimeConfig::parse(
+            "[Log]\n\
+             Level = \"debug\"\n\
+             \n\
+             # Slots of 0.625 ms.\n\
+             [Discovery]\n\
+             LeScanInterval = 96\n\
+             LeScanWindow = 48\n\
+             \n\
+             [Reconnect]\n\
+             Enabled = false\n\
+             MaxAttempts = 4\n",
+        );
+        assert_eq!(
+            config,
+            RuntimeConfig {
+                log_level: LevelFilter::Debug,
+                le_scan_interval: Some(96),
+                le_scan_window: Some(48),
+                reconnect_enabled: false,
+                max_reconnect_attempts: Some(4),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_values_keep_defaults() {
+        let config = RuntimeConfig::parse(
+            "[Log]\n\
+             Level = loud\n\
+             [Discovery]\n\
+             LeScanInterval = 0x60\n\
+             [Reconnect]\n\
+             Enabled = 1\n\
+             Unknown = 2\n",
+        );
+        assert_eq!(config, RuntimeConfig::default());
+    }
+}
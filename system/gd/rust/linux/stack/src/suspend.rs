@@ -1,8 +1,10 @@
 //! Suspend/Resume API.
 
+use dbus_macros::dbus_interface;
+
+use crate::callbacks::Callbacks;
 use crate::{Message, RPCProxy};
 use log::warn;
-use std::collections::HashMap;
 use tokio::sync::mpsc::Sender;
 
 /// Defines the Suspend/Resume API.
@@ -11,26 +13,31 @@ use tokio::sync::mpsc::Sender;
 /// which depends on the actual operating system the daemon runs on. Possible clients of this API
 /// include `btmanagerd` with Chrome OS `powerd` integration, `btmanagerd` with systemd Inhibitor
 /// interface, or any script hooked to suspend/resume events.
+#[dbus_interface(suspend_dbus, "org.chromium.bluetooth.Suspend")]
 pub trait ISuspend {
     /// Adds an observer to suspend events.
     ///
     /// Returns true if the callback can be registered.
+    #[dbus_method("RegisterCallback")]
     fn register_callback(&mut self, callback: Box<dyn ISuspendCallback + Send>) -> bool;
 
     /// Removes an observer to suspend events.
     ///
     /// Returns true if the callback can be removed, false if `callback_id` is not recognized.
+    #[dbus_method("UnregisterCallback")]
     fn unregister_callback(&mut self, callback_id: u32) -> bool;
 
     /// Prepares the stack for suspend, identified by `suspend_id`.
     ///
     /// Returns a positive number identifying the suspend if it can be started. If there is already
     /// a suspend, that active suspend id is returned.
+    #[dbus_method("Suspend")]
     fn suspend(&self, suspend_type: SuspendType) -> u32;
 
     /// Undoes previous suspend preparation identified by `suspend_id`.
     ///
     /// Returns true if suspend can be resumed, and false if there is no suspend to resume.
+    #[dbus_method("Resume")]
     fn resume(&self) -> bool;
 }
 
@@ -57,50 +64,34 @@ pub enum SuspendType {
 /// Implementation of the suspend API.
 pub struct Suspend {
     tx: Sender<Message>,
-    callbacks: HashMap<u32, Box<dyn ISuspendCallback + Send>>,
+    callbacks: Callbacks<dyn ISuspendCallback + Send>,
 }
 
 impl Suspend {
     pub fn new(tx: Sender<Message>) -> Suspend {
-        Self { tx, callbacks: HashMap::new() }
+        Self { tx: tx.clone(), callbacks: Callbacks::new(tx, Message::SuspendCallbackDisconnected) }
     }
 
     pub(crate) fn callback_registered(&mut self, id: u32) {
-        match self.callbacks.get(&id) {
-            Some(callback) => callback.on_callback_registered(id),
-            None => warn!("Suspend callback {} does not exist", id),
+        if !self.callbacks.call_by_id(id, |callback| callback.on_callback_registered(id)) {
+            warn!("Suspend callback {} does not exist", id);
         }
     }
 
     pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
-        match self.callbacks.get_mut(&id) {
-            Some(callback) => {
-                callback.unregister(id);
-                self.callbacks.remove(&id);
-                true
-            }
-            None => false,
-        }
+        self.callbacks.remove_callback(id)
     }
 }
 
 impl ISuspend for Suspend {
-    fn register_callback(&mut self, mut callback: Box<dyn ISuspendCallback + Send>) -> bool {
-        let tx = self.tx.clone();
-
-        let id = callback.register_disconnect(Box::new(move |cb_id| {
-            let tx = tx.clone();
-            tokio::spawn(async move {
-                let _result = tx.send(Message::SuspendCallbackDisconnected(cb_id)).await;
-            });
-        }));
+    fn register_callback(&mut self, callback: Box<dyn ISuspendCallback + Send>) -> bool {
+        let id = self.callbacks.add_callback(callback);
 
         let tx = self.tx.clone();
         tokio::spawn(async move {
             let _result = tx.send(Message::SuspendCallbackRegistered(id)).await;
         });
 
-        self.callbacks.insert(id, callback);
         true
     }
 
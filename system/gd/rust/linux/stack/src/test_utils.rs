@@ -0,0 +1,1718 @@
+//! Simulated adapter and GATT interfaces for tests. Only built with the `test-utils` feature.
+//!
+//! [`MockBluetooth`] and [`MockBluetoothGatt`] implement [`IBluetooth`] and [`IBluetoothGatt`]
+//! against a scripted adapter and a set of [`FakePeer`]s, reporting results through the
+//! registered callbacks synchronously, so logic built on top of the stack's interfaces can be
+//! tested without a controller or libbluetooth. For testing the stack itself, a [`FakePeer`] also
+//! produces the callbacks libbluetooth would make for it, which can be fed to the dispatch loop
+//! with [`send_base_callback`].
+
+use bt_topshim::btif::{
+    BaseCallbacks, BluetoothProperty, BtAclRole, BtAddrType, BtBondState, BtDeviceType, BtStatus,
+    BtTransport, RawAddress, Uuid, Uuid128Bit,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc::Sender;
+
+use crate::activity::ActivityEnergyInfo;
+use crate::bluetooth::{
+    is_static_random_address, AdapterCapabilities, AdapterProperties, AdapterState,
+    BluetoothDevice, ClassicScanParams, ControllerInfo, FoundDeviceInfo, IBluetooth,
+    IBluetoothCallback, IBluetoothConnectionCallback, IBluetoothDiscoverySessionCallback,
+    IBluetoothSecurityCallback, LePrivacyFeatures, LeScanParams, RemoteDeviceInfo, RemoteFeatures,
+    RemoteVersionInfo, RssiSample, MAX_ENCRYPTION_KEY_SIZE, MAX_RPA_TIMEOUT,
+    MIN_ADV_INSTANCES_FOR_MULTI_ADV, MIN_ENCRYPTION_KEY_SIZE,
+};
+use crate::bluetooth_gatt::{
+    BluetoothGattService, ConnectionPriority, GattNotification, GattWriteRequestStatus,
+    GattWriteType, IBluetoothGatt, IBluetoothGattCallback, IScannerCallback, LePhy, ScanFilter,
+    ScanSettings, CONNECTION_PRIORITY_TIMEOUT,
+};
+use crate::uuid::Profile;
+use crate::Message;
+use bt_topshim::profiles::gatt::GattStatus;
+
+/// MTU of a fresh LE connection.
+const DEFAULT_MTU: i32 = 23;
+
+/// Largest MTU a peer agrees to.
+const MAX_MTU: i32 = 517;
+
+/// A remote device the simulated adapter can discover, bond with and connect to.
+#[derive(Clone, Debug)]
+pub struct FakePeer {
+    pub address: RawAddress,
+    pub name: String,
+    pub device_type: BtDeviceType,
+    pub class_of_device: u32,
+    pub rssi: i32,
+    pub uuids: Vec<Uuid128Bit>,
+    /// Whether the peer completes bonding. If not, bonding fails with `AuthFailure`.
+    pub accepts_bond: bool,
+    /// The GATT database the peer serves.
+    pub services: Vec<BluetoothGattService>,
+    /// Values of the peer's characteristics and descriptors, by handle. Handles without a value
+    /// can't be read or written.
+    pub values: HashMap<i32, Vec<u8>>,
+    /// What the peer reports about its controller once connected.
+    pub version: RemoteVersionInfo,
+    pub features: RemoteFeatures,
+}
+
+impl FakePeer {
+    /// Creates an LE peer that accepts bonding and serves no GATT database.
+    pub fn new(address: RawAddress, name: &str) -> FakePeer {
+        FakePeer {
+            address,
+            name: name.to_string(),
+            device_type: BtDeviceType::Ble,
+            class_of_device: 0,
+            rssi: -50,
+            uuids: vec![],
+            accepts_bond: true,
+            services: vec![],
+            values: HashMap::new(),
+            version: RemoteVersionInfo::default(),
+            features: RemoteFeatures::default(),
+        }
+    }
+
+    pub fn device(&self) -> BluetoothDevice {
+        BluetoothDevice::new(self.address, self.name.clone())
+    }
+
+    pub fn found_device_info(&self) -> FoundDeviceInfo {
+        FoundDeviceInfo {
+            class_of_device: self.class_of_device,
+            device_type: self.device_type.clone(),
+            rssi: self.rssi,
+            uuids: self.uuids.clone(),
+        }
+    }
+
+    /// Returns the properties libbluetooth reports for the peer when discovery finds it.
+    pub fn properties(&self) -> Vec<BluetoothProperty> {
+        vec![
+            BluetoothProperty::BdAddr(self.address),
+            BluetoothProperty::BdName(self.name.clone()),
+            BluetoothProperty::TypeOfDevice(self.device_type.clone()),
+            BluetoothProperty::ClassOfDevice(self.class_of_device),
+            BluetoothProperty::RemoteRssi(self.rssi as i8),
+            BluetoothProperty::Uuids(self.uuids.iter().map(|uu| Uuid { uu: *uu }).collect()),
+        ]
+    }
+
+    /// Returns the callback libbluetooth makes when discovery finds the peer.
+    pub fn device_found_callback(&self) -> BaseCallbacks {
+        let properties = self.properties();
+        BaseCallbacks::DeviceFound(properties.len() as i32, properties)
+    }
+
+    /// Returns the callback libbluetooth makes when the peer's bond state changes.
+    pub fn bond_state_callback(&self, status: BtStatus, state: BtBondState) -> BaseCallbacks {
+        BaseCallbacks::BondState(status, self.address, state, 0)
+    }
+}
+
+/// Hands `callback` to the dispatch loop on `tx` as if libbluetooth had made it.
+pub async fn send_base_callback(tx: &Sender<Message>, callback: BaseCallbacks) {
+    let _ = tx.send(Message::Base(callback)).await;
+}
+
+/// How the simulated adapter behaves.
+#[derive(Clone, Debug)]
+pub struct AdapterScript {
+    pub address: RawAddress,
+    pub name: String,
+    pub class_of_device: u32,
+    pub capabilities: AdapterCapabilities,
+    pub le_privacy_features: LePrivacyFeatures,
+    /// Whether enabling the adapter succeeds. If not, it goes back to `Off` after `TurningOn`.
+    pub enable_succeeds: bool,
+}
+
+impl Default for AdapterScript {
+    fn default() -> Self {
+        AdapterScript {
+            address: RawAddress { val: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55] },
+            name: String::from("floss-test"),
+            class_of_device: 0,
+            capabilities: AdapterCapabilities::default(),
+            le_privacy_features: LePrivacyFeatures::default(),
+            enable_succeeds: true,
+        }
+    }
+}
+
+struct AdapterSim {
+    script: AdapterScript,
+    state: AdapterState,
+    discoverable: bool,
+    discoverable_timeout: u32,
+    discovering: bool,
+    device_found_rate_limit: u32,
+    inquiry_scan_params: ClassicScanParams,
+    page_scan_params: ClassicScanParams,
+    le_scan_params: LeScanParams,
+    /// Address type used on LE, and the one set to be used from the next enable on.
+    le_own_address_type: BtAddrType,
+    next_le_own_address_type: BtAddrType,
+    secure_connections_only: bool,
+    bonded_only_connections: bool,
+    min_encryption_key_size: u8,
+    peers: HashMap<RawAddress, FakePeer>,
+    bonded: HashSet<RawAddress>,
+    connected: HashSet<RawAddress>,
+    blocked: HashSet<RawAddress>,
+    aliases: HashMap<RawAddress, String>,
+    disabled_profiles: HashSet<(RawAddress, Profile)>,
+}
+
+/// A simulated [`IBluetooth`].
+///
+/// Discovery finds every peer right away and keeps going until it is cancelled. Bonding
+/// completes without pairing requests, according to the peer's `accepts_bond`.
+pub struct MockBluetooth {
+    sim: RefCell<AdapterSim>,
+    callbacks: HashMap<u32, Box<dyn IBluetoothCallback + Send>>,
+    connection_callbacks: HashMap<u32, Box<dyn IBluetoothConnectionCallback + Send>>,
+    security_callbacks: HashMap<u32, Box<dyn IBluetoothSecurityCallback + Send>>,
+    discovery_sessions: HashMap<u32, Box<dyn IBluetoothDiscoverySessionCallback + Send>>,
+    last_callback_id: u32,
+}
+
+impl MockBluetooth {
+    /// Creates a disabled adapter behaving as `script`, with `peers` in range.
+    pub fn new(script: AdapterScript, peers: Vec<FakePeer>) -> MockBluetooth {
+        MockBluetooth {
+            sim: RefCell::new(AdapterSim {
+                script,
+                state: AdapterState::Off,
+                discoverable: false,
+                discoverable_timeout: 0,
+                discovering: false,
+                device_found_rate_limit: 0,
+                inquiry_scan_params: ClassicScanParams::default_inquiry(),
+                page_scan_params: ClassicScanParams::default_page(),
+                le_scan_params: LeScanParams::default(),
+                le_own_address_type: BtAddrType::Public,
+                next_le_own_address_type: BtAddrType::Public,
+                secure_connections_only: false,
+                bonded_only_connections: false,
+                min_encryption_key_size: MIN_ENCRYPTION_KEY_SIZE,
+                peers: peers.into_iter().map(|peer| (peer.address, peer)).collect(),
+                bonded: HashSet::new(),
+                connected: HashSet::new(),
+                blocked: HashSet::new(),
+                aliases: HashMap::new(),
+                disabled_profiles: HashSet::new(),
+            }),
+            callbacks: HashMap::new(),
+            connection_callbacks: HashMap::new(),
+            security_callbacks: HashMap::new(),
+            discovery_sessions: HashMap::new(),
+            last_callback_id: 0,
+        }
+    }
+
+    /// Brings `peer` in range. It is reported found right away if discovery is running.
+    pub fn add_peer(&self, peer: FakePeer) {
+        let discovering = self.sim.borrow().discovering;
+        if discovering {
+            self.for_all_callbacks(|cb| {
+                cb.on_device_found(peer.device(), peer.found_device_info())
+            });
+        }
+        for (id, session) in self.discovery_sessions.iter() {
+            session.on_session_device_found(*id, peer.device());
+        }
+        self.sim.borrow_mut().peers.insert(peer.address, peer);
+    }
+
+    /// Takes the peer at `addr` out of range, disconnecting it first.
+    pub fn remove_peer(&self, addr: RawAddress) {
+        self.peer_disconnected(addr);
+        if let Some(peer) = self.sim.borrow_mut().peers.remove(&addr) {
+            self.for_all_callbacks(|cb| cb.on_device_cleared(peer.device()));
+            for (id, session) in self.discovery_sessions.iter() {
+                session.on_session_device_lost(*id, peer.device());
+            }
+        }
+    }
+
+    /// Simulates the peer at `addr` disconnecting on its own.
+    pub fn peer_disconnected(&self, addr: RawAddress) {
+        let device = {
+            let mut sim = self.sim.borrow_mut();
+            if !sim.connected.remove(&addr) {
+                return;
+            }
+            sim.peers.get(&addr).map(|peer| peer.device())
+        };
+
+        if let Some(device) = device {
+            for callback in self.connection_callbacks.values() {
+                callback.on_device_disconnected(device.clone());
+            }
+        }
+        self.report_connected_count();
+    }
+
+    fn report_connected_count(&self) {
+        let count = self.sim.borrow().connected.len() as u32;
+        self.for_all_callbacks(|cb| cb.on_connected_count_changed(count));
+    }
+
+    fn for_all_callbacks<F: Fn(&Box<dyn IBluetoothCallback + Send>)>(&self, f: F) {
+        for callback in self.callbacks.values() {
+            f(callback);
+        }
+    }
+
+    fn set_state(&self, state: AdapterState) {
+        self.sim.borrow_mut().state = state;
+        self.for_all_callbacks(|cb| cb.on_adapter_state_changed(state));
+    }
+
+    fn is_on(&self) -> bool {
+        self.sim.borrow().state == AdapterState::On
+    }
+
+    fn peer(&self, device: &BluetoothDevice) -> Option<FakePeer> {
+        self.sim.borrow().peers.get(&device.address).cloned()
+    }
+
+    fn next_callback_id(&mut self) -> u32 {
+        self.last_callback_id += 1;
+        self.last_callback_id
+    }
+}
+
+impl IBluetooth for MockBluetooth {
+    fn register_callback(&mut self, callback: Box<dyn IBluetoothCallback + Send>) -> u32 {
+        let id = self.next_callback_id();
+        self.callbacks.insert(id, callback);
+        id
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.callbacks.remove(&callback_id).is_some()
+    }
+
+    fn register_connection_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothConnectionCallback + Send>,
+    ) -> u32 {
+        let id = self.next_callback_id();
+        self.connection_callbacks.insert(id, callback);
+        id
+    }
+
+    fn unregister_connection_callback(&mut self, callback_id: u32) -> bool {
+        self.connection_callbacks.remove(&callback_id).is_some()
+    }
+
+    fn replay_adapter_state(&self, callback_id: u32) -> bool {
+        let callback = match self.callbacks.get(&callback_id) {
+            Some(callback) => callback,
+            None => return false,
+        };
+
+        let sim = self.sim.borrow();
+        callback.on_adapter_state_changed(sim.state);
+        if sim.state != AdapterState::On {
+            return true;
+        }
+
+        callback.on_address_changed(sim.script.address.to_string());
+        callback.on_name_changed(sim.script.name.clone());
+        callback.on_discoverable_changed(sim.discoverable);
+        callback.on_discovering_changed(sim.discovering);
+        for addr in sim.bonded.iter() {
+            callback.on_bond_state_changed(
+                BtStatus::Success as u32,
+                addr.to_string(),
+                BtBondState::Bonded as u32,
+            );
+        }
+        callback.on_connected_count_changed(sim.connected.len() as u32);
+        true
+    }
+
+    fn replay_connection_state(&self, callback_id: u32) -> bool {
+        let callback = match self.connection_callbacks.get(&callback_id) {
+            Some(callback) => callback,
+            None => return false,
+        };
+
+        for device in self.get_connected_devices() {
+            callback.on_device_connected(device);
+        }
+        true
+    }
+
+    fn register_security_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothSecurityCallback + Send>,
+    ) -> u32 {
+        let id = self.next_callback_id();
+        self.security_callbacks.insert(id, callback);
+        id
+    }
+
+    fn unregister_security_callback(&mut self, callback_id: u32) -> bool {
+        self.security_callbacks.remove(&callback_id).is_some()
+    }
+
+    fn enable(&mut self) -> bool {
+        if self.sim.borrow().state != AdapterState::Off {
+            return false;
+        }
+
+        self.set_state(AdapterState::TurningOn);
+        if self.sim.borrow().script.enable_succeeds {
+            let next = self.sim.borrow().next_le_own_address_type;
+            self.sim.borrow_mut().le_own_address_type = next;
+            self.set_state(AdapterState::On);
+        } else {
+            self.set_state(AdapterState::Off);
+        }
+        true
+    }
+
+    fn disable(&mut self) -> bool {
+        if !self.is_on() {
+            return false;
+        }
+
+        let connected: Vec<RawAddress> = self.sim.borrow().connected.iter().cloned().collect();
+        for addr in connected {
+            self.peer_disconnected(addr);
+        }
+        self.sim.borrow_mut().discovering = false;
+
+        self.set_state(AdapterState::TurningOff);
+        self.set_state(AdapterState::Off);
+        true
+    }
+
+    fn get_adapter_state(&self) -> AdapterState {
+        self.sim.borrow().state
+    }
+
+    fn get_address(&self) -> String {
+        self.sim.borrow().script.address.to_string()
+    }
+
+    fn get_uuids(&self) -> Vec<Uuid128Bit> {
+        vec![]
+    }
+
+    fn get_name(&self) -> String {
+        self.sim.borrow().script.name.clone()
+    }
+
+    fn set_name(&self, name: String) -> BtStatus {
+        self.sim.borrow_mut().script.name = name.clone();
+        self.for_all_callbacks(|cb| cb.on_name_changed(name.clone()));
+        BtStatus::Success
+    }
+
+    fn get_bluetooth_class(&self) -> u32 {
+        self.sim.borrow().script.class_of_device
+    }
+
+    fn set_bluetooth_class(&self, cod: u32) -> BtStatus {
+        self.sim.borrow_mut().script.class_of_device = cod;
+        BtStatus::Success
+    }
+
+    fn get_discoverable(&self) -> bool {
+        self.sim.borrow().discoverable
+    }
+
+    fn get_discoverable_timeout(&self) -> u32 {
+        self.sim.borrow().discoverable_timeout
+    }
+
+    fn set_discoverable(&self, mode: bool, duration: u32) -> BtStatus {
+        {
+            let mut sim = self.sim.borrow_mut();
+            sim.discoverable = mode;
+            sim.discoverable_timeout = duration;
+        }
+        self.for_all_callbacks(|cb| cb.on_discoverable_changed(mode));
+        BtStatus::Success
+    }
+
+    fn is_multi_advertisement_supported(&self) -> bool {
+        self.sim.borrow().script.capabilities.max_advertising_sets
+            >= MIN_ADV_INSTANCES_FOR_MULTI_ADV as u32
+    }
+
+    fn is_le_extended_advertising_supported(&self) -> bool {
+        self.sim.borrow().script.capabilities.le_extended_advertising_supported
+    }
+
+    fn get_adapter_capabilities(&self) -> AdapterCapabilities {
+        self.sim.borrow().script.capabilities.clone()
+    }
+
+    fn get_adapter_properties(&self) -> AdapterProperties {
+        let sim = self.sim.borrow();
+        AdapterProperties {
+            address: sim.script.address.to_string(),
+            name: sim.script.name.clone(),
+            bluetooth_class: sim.script.class_of_device,
+            discoverable: sim.discoverable,
+            discoverable_timeout: sim.discoverable_timeout,
+            discovering: sim.discovering,
+            connected_count: sim.connected.len() as u32,
+        }
+    }
+
+    fn start_discovery(&self) -> BtStatus {
+        if !self.is_on() {
+            return BtStatus::NotReady;
+        }
+
+        self.sim.borrow_mut().discovering = true;
+        self.for_all_callbacks(|cb| cb.on_discovering_changed(true));
+
+        let peers: Vec<FakePeer> = self.sim.borrow().peers.values().cloned().collect();
+        for peer in peers {
+            self.for_all_callbacks(|cb| {
+                cb.on_device_found(peer.device(), peer.found_device_info())
+            });
+        }
+        BtStatus::Success
+    }
+
+    fn cancel_discovery(&self) -> BtStatus {
+        if !self.is_on() {
+            return BtStatus::NotReady;
+        }
+
+        if std::mem::replace(&mut self.sim.borrow_mut().discovering, false) {
+            self.for_all_callbacks(|cb| cb.on_discovering_changed(false));
+        }
+        BtStatus::Success
+    }
+
+    fn is_discovering(&self) -> bool {
+        self.sim.borrow().discovering
+    }
+
+    fn get_discovery_end_millis(&self) -> u64 {
+        0
+    }
+
+    // Sessions are told about every peer in range right away, and lose peers as they're removed.
+    fn start_discovery_session(
+        &mut self,
+        callback: Box<dyn IBluetoothDiscoverySessionCallback + Send>,
+        lost_timeout_ms: u32,
+    ) -> u32 {
+        if !self.is_on() || lost_timeout_ms == 0 {
+            return 0;
+        }
+
+        let id = self.next_callback_id();
+        for peer in self.sim.borrow().peers.values() {
+            callback.on_session_device_found(id, peer.device());
+        }
+        self.discovery_sessions.insert(id, callback);
+        if !self.is_discovering() {
+            self.start_discovery();
+        }
+        id
+    }
+
+    fn stop_discovery_session(&mut self, session_id: u32) -> bool {
+        if self.discovery_sessions.remove(&session_id).is_none() {
+            return false;
+        }
+
+        if self.discovery_sessions.is_empty() {
+            self.cancel_discovery();
+        }
+        true
+    }
+
+    fn set_device_found_rate_limit(&mut self, limit: u32) -> bool {
+        self.sim.borrow_mut().device_found_rate_limit = limit;
+        true
+    }
+
+    fn get_device_found_rate_limit(&self) -> u32 {
+        self.sim.borrow().device_found_rate_limit
+    }
+
+    fn set_inquiry_scan_params(&mut self, params: ClassicScanParams) -> BtStatus {
+        if !params.is_valid() {
+            return BtStatus::InvalidParam;
+        }
+        self.sim.borrow_mut().inquiry_scan_params = params;
+        BtStatus::Success
+    }
+
+    fn get_inquiry_scan_params(&self) -> ClassicScanParams {
+        self.sim.borrow().inquiry_scan_params.clone()
+    }
+
+    fn set_page_scan_params(&mut self, params: ClassicScanParams) -> BtStatus {
+        if !params.is_valid() {
+            return BtStatus::InvalidParam;
+        }
+        self.sim.borrow_mut().page_scan_params = params;
+        BtStatus::Success
+    }
+
+    fn get_page_scan_params(&self) -> ClassicScanParams {
+        self.sim.borrow().page_scan_params.clone()
+    }
+
+    fn set_le_scan_params(&mut self, params: LeScanParams) -> BtStatus {
+        if !params.is_valid() {
+            return BtStatus::InvalidParam;
+        }
+        self.sim.borrow_mut().le_scan_params = params;
+        BtStatus::Success
+    }
+
+    fn get_le_scan_params(&self) -> LeScanParams {
+        self.sim.borrow().le_scan_params.clone()
+    }
+
+    fn get_le_privacy_features(&self) -> LePrivacyFeatures {
+        if !self.is_on() {
+            return LePrivacyFeatures::default();
+        }
+        self.sim.borrow().script.le_privacy_features.clone()
+    }
+
+    fn set_le_static_address(&mut self, address: String) -> BtStatus {
+        match RawAddress::from_string(address) {
+            Some(address) if is_static_random_address(&address) => {
+                self.sim.borrow_mut().next_le_own_address_type = BtAddrType::Random;
+                BtStatus::Success
+            }
+            _ => BtStatus::InvalidParam,
+        }
+    }
+
+    fn set_le_privacy(&mut self, enabled: bool, rpa_timeout: u32) -> BtStatus {
+        if rpa_timeout > MAX_RPA_TIMEOUT {
+            return BtStatus::InvalidParam;
+        }
+        self.sim.borrow_mut().next_le_own_address_type =
+            if enabled { BtAddrType::Random } else { BtAddrType::Public };
+        BtStatus::Success
+    }
+
+    fn get_le_own_address_type(&self) -> BtAddrType {
+        if !self.is_on() {
+            return BtAddrType::Unknown;
+        }
+        self.sim.borrow().le_own_address_type
+    }
+
+    fn set_secure_connections_only(&mut self, enabled: bool) -> BtStatus {
+        self.sim.borrow_mut().secure_connections_only = enabled;
+        BtStatus::Success
+    }
+
+    fn get_secure_connections_only(&self) -> bool {
+        self.sim.borrow().secure_connections_only
+    }
+
+    fn set_bonded_only_connections(&mut self, enabled: bool) -> BtStatus {
+        self.sim.borrow_mut().bonded_only_connections = enabled;
+        BtStatus::Success
+    }
+
+    fn get_bonded_only_connections(&self) -> bool {
+        self.sim.borrow().bonded_only_connections
+    }
+
+    fn set_min_encryption_key_size(&mut self, key_size: u8) -> BtStatus {
+        if key_size < MIN_ENCRYPTION_KEY_SIZE || key_size > MAX_ENCRYPTION_KEY_SIZE {
+            return BtStatus::InvalidParam;
+        }
+        self.sim.borrow_mut().min_encryption_key_size = key_size;
+        BtStatus::Success
+    }
+
+    fn get_min_encryption_key_size(&self) -> u8 {
+        self.sim.borrow().min_encryption_key_size
+    }
+
+    /// Connected peers use keys of the maximum size.
+    fn get_encryption_key_size(&self, device: BluetoothDevice, _transport: BtTransport) -> u8 {
+        if !self.sim.borrow().connected.contains(&device.address) {
+            return 0;
+        }
+        MAX_ENCRYPTION_KEY_SIZE
+    }
+
+    fn create_bond(&self, device: BluetoothDevice, _transport: BtTransport) -> BtStatus {
+        if !self.is_on() {
+            return BtStatus::NotReady;
+        }
+
+        let peer = match self.peer(&device) {
+            Some(peer) => peer,
+            None => return BtStatus::RemoteDeviceDown,
+        };
+        let address = device.address.to_string();
+
+        self.for_all_callbacks(|cb| {
+            cb.on_bond_state_changed(
+                BtStatus::Success as u32,
+                address.clone(),
+                BtBondState::Bonding as u32,
+            )
+        });
+
+        let (status, state) = if peer.accepts_bond {
+            self.sim.borrow_mut().bonded.insert(device.address);
+            (BtStatus::Success as u32, BtBondState::Bonded as u32)
+        } else {
+            (BtStatus::AuthFailure as u32, BtBondState::NotBonded as u32)
+        };
+        self.for_all_callbacks(|cb| cb.on_bond_state_changed(status, address.clone(), state));
+        BtStatus::Success
+    }
+
+    fn cancel_bond_process(&self, _device: BluetoothDevice) -> BtStatus {
+        BtStatus::Success
+    }
+
+    fn remove_bond(&self, device: BluetoothDevice) -> BtStatus {
+        if !self.sim.borrow_mut().bonded.remove(&device.address) {
+            return BtStatus::Fail;
+        }
+
+        let address = device.address.to_string();
+        self.for_all_callbacks(|cb| {
+            cb.on_bond_state_changed(
+                BtStatus::Success as u32,
+                address.clone(),
+                BtBondState::NotBonded as u32,
+            )
+        });
+        self.peer_disconnected(device.address);
+        BtStatus::Success
+    }
+
+    fn get_bonded_devices(&self) -> Vec<BluetoothDevice> {
+        let sim = self.sim.borrow();
+        sim.bonded.iter().filter_map(|addr| sim.peers.get(addr)).map(|p| p.device()).collect()
+    }
+
+    fn get_bond_state(&self, device: BluetoothDevice) -> u32 {
+        if self.sim.borrow().bonded.contains(&device.address) {
+            BtBondState::Bonded as u32
+        } else {
+            BtBondState::NotBonded as u32
+        }
+    }
+
+    fn set_pin(&self, _device: BluetoothDevice, _accept: bool, _pin_code: Vec<u8>) -> BtStatus {
+        BtStatus::Success
+    }
+
+    fn set_passkey(&self, _device: BluetoothDevice, _accept: bool, _passkey: Vec<u8>) -> BtStatus {
+        BtStatus::Success
+    }
+
+    fn set_pairing_confirmation(&self, _device: BluetoothDevice, _accept: bool) -> BtStatus {
+        BtStatus::Success
+    }
+
+    fn get_remote_name(&self, device: BluetoothDevice) -> String {
+        self.peer(&device).map(|peer| peer.name).unwrap_or_default()
+    }
+
+    fn fetch_remote_name(&mut self, device: BluetoothDevice) -> bool {
+        if !self.is_on() || self.get_remote_type(device.clone()) == BtDeviceType::Ble {
+            return false;
+        }
+
+        // Peers out of range don't answer.
+        let (device, success) = match self.peer(&device) {
+            Some(peer) => (peer.device(), true),
+            None => (device, false),
+        };
+        self.for_all_callbacks(|cb| cb.on_remote_name_fetched(device.clone(), success));
+        true
+    }
+
+    fn get_remote_type(&self, device: BluetoothDevice) -> BtDeviceType {
+        self.peer(&device).map(|peer| peer.device_type).unwrap_or(BtDeviceType::Unknown)
+    }
+
+    fn get_remote_alias(&self, device: BluetoothDevice) -> String {
+        match self.sim.borrow().aliases.get(&device.address) {
+            Some(alias) => alias.clone(),
+            None => self.get_remote_name(device),
+        }
+    }
+
+    fn set_remote_alias(&mut self, device: BluetoothDevice, new_alias: String) -> BtStatus {
+        self.sim.borrow_mut().aliases.insert(device.address, new_alias);
+        BtStatus::Success
+    }
+
+    fn get_remote_class(&self, device: BluetoothDevice) -> u32 {
+        self.peer(&device).map(|peer| peer.class_of_device).unwrap_or(0)
+    }
+
+    fn get_remote_version_info(&self, device: BluetoothDevice) -> RemoteVersionInfo {
+        match self.get_connection_state(device.clone()) {
+            0 => RemoteVersionInfo::default(),
+            _ => self.peer(&device).map(|peer| peer.version).unwrap_or_default(),
+        }
+    }
+
+    fn get_remote_supported_features(&self, device: BluetoothDevice) -> RemoteFeatures {
+        match self.get_connection_state(device.clone()) {
+            0 => RemoteFeatures::default(),
+            _ => self.peer(&device).map(|peer| peer.features).unwrap_or_default(),
+        }
+    }
+
+    fn get_remote_device_info(&self, device: BluetoothDevice) -> RemoteDeviceInfo {
+        let peer = match self.peer(&device) {
+            Some(peer) => peer,
+            None => return RemoteDeviceInfo::unknown(device),
+        };
+
+        let connection_state = self.get_connection_state(device.clone());
+        let key_size = self.get_encryption_key_size(device.clone(), BtTransport::Auto);
+        RemoteDeviceInfo {
+            known: true,
+            alias: self.get_remote_alias(device.clone()),
+            device_type: peer.device_type.clone(),
+            class_of_device: peer.class_of_device,
+            bond_state: self.get_bond_state(device),
+            uuids: peer.uuids.clone(),
+            rssi_history: vec![RssiSample { rssi: peer.rssi, age_ms: 0 }],
+            last_seen_ms: 0,
+            connection_state,
+            connected_ms: 0,
+            link_role: BtAclRole::Unknown,
+            bredr_key_size: key_size,
+            le_key_size: key_size,
+            device: peer.device(),
+        }
+    }
+
+    fn get_connected_devices(&self) -> Vec<BluetoothDevice> {
+        let sim = self.sim.borrow();
+        sim.connected.iter().filter_map(|addr| sim.peers.get(addr)).map(|p| p.device()).collect()
+    }
+
+    fn get_connection_state(&self, device: BluetoothDevice) -> u32 {
+        self.sim.borrow().connected.contains(&device.address) as u32
+    }
+
+    fn get_profile_connection_state(&self, _profile: Profile) -> u32 {
+        0
+    }
+
+    fn get_remote_uuids(&self, device: BluetoothDevice) -> Vec<Uuid128Bit> {
+        self.peer(&device).map(|peer| peer.uuids).unwrap_or_default()
+    }
+
+    fn fetch_remote_uuids(&self, device: BluetoothDevice) -> BtStatus {
+        match self.peer(&device) {
+            Some(_) => BtStatus::Success,
+            None => BtStatus::RemoteDeviceDown,
+        }
+    }
+
+    fn sdp_search(&self, device: BluetoothDevice, _uuid: Uuid128Bit) -> BtStatus {
+        self.fetch_remote_uuids(device)
+    }
+
+    fn connect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> BtStatus {
+        if !self.is_on() {
+            return BtStatus::NotReady;
+        }
+
+        let peer = match self.peer(&device) {
+            Some(peer) => peer,
+            None => return BtStatus::RemoteDeviceDown,
+        };
+
+        if self.sim.borrow_mut().connected.insert(device.address) {
+            for callback in self.connection_callbacks.values() {
+                callback.on_device_connected(peer.device());
+            }
+            self.report_connected_count();
+        }
+        // Peers don't have profiles of their own.
+        for callback in self.connection_callbacks.values() {
+            callback.on_connect_all_complete(peer.device(), vec![]);
+        }
+        BtStatus::Success
+    }
+
+    fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> BtStatus {
+        self.peer_disconnected(device.address);
+        BtStatus::Success
+    }
+
+    fn disconnect(&mut self, device: BluetoothDevice, _transport: BtTransport) -> BtStatus {
+        if !self.is_on() {
+            return BtStatus::NotReady;
+        }
+
+        if !self.sim.borrow().connected.contains(&device.address) {
+            return BtStatus::RemoteDeviceDown;
+        }
+
+        self.peer_disconnected(device.address);
+        BtStatus::Success
+    }
+
+    fn disconnect_all_profiles(&mut self, device: BluetoothDevice) -> BtStatus {
+        self.disconnect(device, BtTransport::Auto)
+    }
+
+    fn set_profile_enabled(
+        &mut self,
+        device: BluetoothDevice,
+        profile: Profile,
+        enabled: bool,
+    ) -> bool {
+        let mut sim = self.sim.borrow_mut();
+        if enabled {
+            sim.disabled_profiles.remove(&(device.address, profile));
+        } else {
+            sim.disabled_profiles.insert((device.address, profile));
+        }
+        true
+    }
+
+    fn get_profile_enabled(&self, device: BluetoothDevice, profile: Profile) -> bool {
+        !self.sim.borrow().disabled_profiles.contains(&(device.address, profile))
+    }
+
+    fn block_device(&mut self, device: BluetoothDevice) -> bool {
+        self.peer_disconnected(device.address);
+        self.sim.borrow_mut().blocked.insert(device.address)
+    }
+
+    fn unblock_device(&mut self, device: BluetoothDevice) -> bool {
+        self.sim.borrow_mut().blocked.remove(&device.address)
+    }
+
+    fn get_blocked_devices(&self) -> Vec<BluetoothDevice> {
+        let sim = self.sim.borrow();
+        sim.blocked
+            .iter()
+            .map(|addr| match sim.peers.get(addr) {
+                Some(peer) => peer.device(),
+                None => BluetoothDevice::new(*addr, String::new()),
+            })
+            .collect()
+    }
+
+    fn get_activity_energy_info(&self) -> ActivityEnergyInfo {
+        ActivityEnergyInfo::default()
+    }
+
+    fn get_controller_info(&self) -> ControllerInfo {
+        ControllerInfo::default()
+    }
+
+    fn set_vendor_diagnostics_enabled(&mut self, _enabled: bool) -> bool {
+        false
+    }
+
+    fn dump_api_trace(&self) -> String {
+        String::new()
+    }
+}
+
+struct GattSim {
+    peers: HashMap<RawAddress, FakePeer>,
+    last_scanner_id: i32,
+    // (client id, peer) -> MTU.
+    connections: HashMap<(i32, RawAddress), i32>,
+    // (client id, peer, handle) of enabled notifications.
+    notifications: HashSet<(i32, RawAddress, i32)>,
+    batching: HashSet<i32>,
+    auto_elevate_security: HashSet<i32>,
+}
+
+/// A simulated [`IBluetoothGatt`] client.
+///
+/// Connecting to a peer succeeds if the peer was given to the mock, reads and writes go to the
+/// peer's `values`, and notifications are sent with [`MockBluetoothGatt::notify`]. Clients that
+/// turn on batching get every notification as a batch of one.
+pub struct MockBluetoothGatt {
+    sim: RefCell<GattSim>,
+    clients: HashMap<i32, Box<dyn IBluetoothGattCallback + Send>>,
+    last_client_id: i32,
+}
+
+impl MockBluetoothGatt {
+    pub fn new(peers: Vec<FakePeer>) -> MockBluetoothGatt {
+        MockBluetoothGatt {
+            sim: RefCell::new(GattSim {
+                peers: peers.into_iter().map(|peer| (peer.address, peer)).collect(),
+                last_scanner_id: 0,
+                connections: HashMap::new(),
+                notifications: HashSet::new(),
+                batching: HashSet::new(),
+                auto_elevate_security: HashSet::new(),
+            }),
+            clients: HashMap::new(),
+            last_client_id: 0,
+        }
+    }
+
+    /// Sends a notification of `value` on `handle` from the peer at `addr` to the connected
+    /// clients that enabled it.
+    pub fn notify(&self, addr: RawAddress, handle: i32, value: Vec<u8>) {
+        let sim = self.sim.borrow();
+        for (client_id, callback) in self.clients.iter() {
+            if !sim.notifications.contains(&(*client_id, addr, handle)) {
+                continue;
+            }
+
+            if sim.batching.contains(client_id) {
+                let notification =
+                    GattNotification { handle, value: value.clone(), timestamp_us: 0 };
+                callback.on_notify_batch(addr, vec![notification]);
+            } else {
+                callback.on_notify(addr, handle, value.clone().into());
+            }
+        }
+    }
+
+    /// Simulates the peer at `addr` dropping its connections.
+    pub fn peer_disconnected(&self, addr: RawAddress) {
+        let clients: Vec<i32> = {
+            let mut sim = self.sim.borrow_mut();
+            let clients = sim.connections.keys().filter(|(_, a)| *a == addr).map(|(c, _)| *c);
+            let clients: Vec<i32> = clients.collect();
+            for client_id in clients.iter() {
+                sim.connections.remove(&(*client_id, addr));
+            }
+            sim.notifications.retain(|(_, a, _)| *a != addr);
+            clients
+        };
+
+        for client_id in clients {
+            if let Some(callback) = self.clients.get(&client_id) {
+                callback.on_client_connection_state(0, client_id, false, addr);
+            }
+        }
+    }
+
+    fn is_connected(&self, client_id: i32, addr: RawAddress) -> bool {
+        self.sim.borrow().connections.contains_key(&(client_id, addr))
+    }
+
+    // Finds the handle of the characteristic with `uuid` in the peer's database.
+    fn find_characteristic(&self, addr: RawAddress, uuid: Uuid128Bit) -> Option<i32> {
+        let sim = self.sim.borrow();
+        let peer = sim.peers.get(&addr)?;
+        peer.services
+            .iter()
+            .flat_map(|service| service.characteristics.iter())
+            .find(|characteristic| characteristic.uuid == uuid)
+            .map(|characteristic| characteristic.instance_id)
+    }
+
+    fn read_value(&self, addr: RawAddress, handle: i32) -> (i32, Vec<u8>) {
+        let sim = self.sim.borrow();
+        match sim.peers.get(&addr).and_then(|peer| peer.values.get(&handle)) {
+            Some(value) => (GattStatus::Success as i32, value.clone()),
+            None => (GattStatus::InvalidHandle as i32, vec![]),
+        }
+    }
+
+    fn write_value(&self, addr: RawAddress, handle: i32, value: Vec<u8>) -> i32 {
+        let mut sim = self.sim.borrow_mut();
+        match sim.peers.get_mut(&addr).and_then(|peer| peer.values.get_mut(&handle)) {
+            Some(stored) => {
+                *stored = value;
+                GattStatus::Success as i32
+            }
+            None => GattStatus::InvalidHandle as i32,
+        }
+    }
+}
+
+impl IBluetoothGatt for MockBluetoothGatt {
+    fn register_scanner(&self, callback: Box<dyn IScannerCallback + Send>) {
+        let scanner_id = {
+            let mut sim = self.sim.borrow_mut();
+            sim.last_scanner_id += 1;
+            sim.last_scanner_id
+        };
+        callback.on_scanner_registered(0, scanner_id);
+    }
+
+    fn unregister_scanner(&self, _scanner_id: i32) {}
+
+    fn start_scan(&self, _scanner_id: i32, _settings: ScanSettings, _filters: Vec<ScanFilter>) {}
+
+    fn stop_scan(&self, _scanner_id: i32) {}
+
+    fn register_client(
+        &mut self,
+        _app_uuid: Uuid128Bit,
+        callback: Box<dyn IBluetoothGattCallback + Send>,
+        _eatt_support: bool,
+    ) -> BtStatus {
+        self.last_client_id += 1;
+        let client_id = self.last_client_id;
+
+        callback.on_client_registered(0, client_id);
+        self.clients.insert(client_id, callback);
+        BtStatus::Success
+    }
+
+    fn unregister_client(&mut self, client_id: i32) -> BtStatus {
+        if self.clients.remove(&client_id).is_none() {
+            return BtStatus::InvalidParam;
+        }
+
+        let mut sim = self.sim.borrow_mut();
+        sim.connections.retain(|(c, _), _| *c != client_id);
+        sim.notifications.retain(|(c, _, _)| *c != client_id);
+        sim.batching.remove(&client_id);
+        sim.auto_elevate_security.remove(&client_id);
+        BtStatus::Success
+    }
+
+    fn client_connect(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        _is_direct: bool,
+        _transport: BtTransport,
+        _opportunistic: bool,
+        _phy: i32,
+    ) -> BtStatus {
+        let callback = match self.clients.get(&client_id) {
+            Some(callback) => callback,
+            None => return BtStatus::InvalidParam,
+        };
+
+        let reachable = self.sim.borrow().peers.contains_key(&addr);
+        if reachable {
+            self.sim.borrow_mut().connections.entry((client_id, addr)).or_insert(DEFAULT_MTU);
+            callback.on_client_connection_state(0, client_id, true, addr);
+        } else {
+            callback.on_client_connection_state(GattStatus::Error as i32, client_id, false, addr);
+        }
+        BtStatus::Success
+    }
+
+    fn client_disconnect(&self, client_id: i32, addr: RawAddress) -> BtStatus {
+        let callback = match self.clients.get(&client_id) {
+            Some(callback) => callback,
+            None => return BtStatus::InvalidParam,
+        };
+
+        {
+            let mut sim = self.sim.borrow_mut();
+            if sim.connections.remove(&(client_id, addr)).is_none() {
+                return BtStatus::Success;
+            }
+            sim.notifications.retain(|(c, a, _)| *c != client_id || *a != addr);
+        }
+        callback.on_client_connection_state(0, client_id, false, addr);
+        BtStatus::Success
+    }
+
+    fn client_set_preferred_phy(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        tx_phy: LePhy,
+        rx_phy: LePhy,
+        _phy_options: i32,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        self.clients[&client_id].on_phy_update(addr, tx_phy, rx_phy, GattStatus::Success);
+        BtStatus::Success
+    }
+
+    fn client_read_phy(&mut self, client_id: i32, addr: RawAddress) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        self.clients[&client_id].on_phy_read(addr, LePhy::Phy1m, LePhy::Phy1m, GattStatus::Success);
+        BtStatus::Success
+    }
+
+    fn refresh_device(&self, _client_id: i32, _addr: RawAddress) -> BtStatus {
+        BtStatus::Success
+    }
+
+    fn discover_services(&mut self, client_id: i32, addr: RawAddress) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let services = self.sim.borrow().peers[&addr].services.clone();
+        self.clients[&client_id].on_search_complete(addr, services, 0);
+        BtStatus::Success
+    }
+
+    fn discover_service_by_uuid(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let services = self.sim.borrow().peers[&addr]
+            .services
+            .iter()
+            .filter(|service| service.uuid == uuid)
+            .cloned()
+            .collect();
+        self.clients[&client_id].on_search_complete(addr, services, 0);
+        BtStatus::Success
+    }
+
+    fn read_characteristic(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        _auth_req: i32,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let (status, value) = self.read_value(addr, handle);
+        self.clients[&client_id].on_characteristic_read(addr, status, handle, value.into());
+        BtStatus::Success
+    }
+
+    fn read_using_characteristic_uuid(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+        _start_handle: i32,
+        _end_handle: i32,
+        auth_req: i32,
+    ) -> BtStatus {
+        match self.find_characteristic(addr, uuid) {
+            Some(handle) => self.read_characteristic(client_id, addr, handle, auth_req),
+            None => BtStatus::InvalidParam,
+        }
+    }
+
+    fn write_characteristic(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        _write_type: GattWriteType,
+        _auth_req: i32,
+        value: Vec<u8>,
+    ) -> GattWriteRequestStatus {
+        if !self.is_connected(client_id, addr) {
+            return GattWriteRequestStatus::Fail;
+        }
+
+        let status = self.write_value(addr, handle, value);
+        self.clients[&client_id].on_characteristic_write(addr, status, handle);
+        GattWriteRequestStatus::Success
+    }
+
+    fn write_using_characteristic_uuid(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        uuid: Uuid128Bit,
+        _start_handle: i32,
+        _end_handle: i32,
+        write_type: GattWriteType,
+        auth_req: i32,
+        value: Vec<u8>,
+    ) -> GattWriteRequestStatus {
+        match self.find_characteristic(addr, uuid) {
+            Some(handle) => {
+                self.write_characteristic(client_id, addr, handle, write_type, auth_req, value)
+            }
+            None => GattWriteRequestStatus::Fail,
+        }
+    }
+
+    fn read_descriptor(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        _auth_req: i32,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let (status, value) = self.read_value(addr, handle);
+        self.clients[&client_id].on_descriptor_read(addr, status, handle, value.into());
+        BtStatus::Success
+    }
+
+    fn write_descriptor(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        _auth_req: i32,
+        value: Vec<u8>,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let status = self.write_value(addr, handle, value);
+        self.clients[&client_id].on_descriptor_write(addr, status, handle);
+        BtStatus::Success
+    }
+
+    fn register_for_notification(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        handle: i32,
+        enable: bool,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let mut sim = self.sim.borrow_mut();
+        if enable {
+            sim.notifications.insert((client_id, addr, handle));
+        } else {
+            sim.notifications.remove(&(client_id, addr, handle));
+        }
+        BtStatus::Success
+    }
+
+    fn begin_reliable_write(&mut self, client_id: i32, addr: RawAddress) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+        BtStatus::Success
+    }
+
+    fn end_reliable_write(&mut self, client_id: i32, addr: RawAddress, _execute: bool) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        self.clients[&client_id].on_execute_write(addr, 0);
+        BtStatus::Success
+    }
+
+    fn read_remote_rssi(&self, client_id: i32, addr: RawAddress) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        let rssi = self.sim.borrow().peers[&addr].rssi;
+        self.clients[&client_id].on_read_remote_rssi(addr, rssi, 0);
+        BtStatus::Success
+    }
+
+    fn configure_mtu(&self, client_id: i32, addr: RawAddress, mtu: i32) -> BtStatus {
+        let mtu = {
+            let mut sim = self.sim.borrow_mut();
+            match sim.connections.get_mut(&(client_id, addr)) {
+                Some(current) => {
+                    *current = mtu.clamp(DEFAULT_MTU, MAX_MTU);
+                    *current
+                }
+                None => return BtStatus::NotReady,
+            }
+        };
+
+        self.clients[&client_id].on_configure_mtu(addr, mtu, 0);
+        BtStatus::Success
+    }
+
+    fn get_mtu(&self, client_id: i32, addr: RawAddress) -> i32 {
+        self.sim.borrow().connections.get(&(client_id, addr)).cloned().unwrap_or(0)
+    }
+
+    fn set_notification_batching(&mut self, client_id: i32, interval_ms: u32) -> BtStatus {
+        if !self.clients.contains_key(&client_id) {
+            return BtStatus::InvalidParam;
+        }
+
+        let mut sim = self.sim.borrow_mut();
+        if interval_ms == 0 {
+            sim.batching.remove(&client_id);
+        } else {
+            sim.batching.insert(client_id);
+        }
+        BtStatus::Success
+    }
+
+    fn set_auto_elevate_security(&mut self, client_id: i32, enabled: bool) -> BtStatus {
+        if !self.clients.contains_key(&client_id) {
+            return BtStatus::InvalidParam;
+        }
+
+        let mut sim = self.sim.borrow_mut();
+        if enabled {
+            sim.auto_elevate_security.insert(client_id);
+        } else {
+            sim.auto_elevate_security.remove(&client_id);
+        }
+        BtStatus::Success
+    }
+
+    fn set_request_timeout(
+        &mut self,
+        client_id: i32,
+        _timeout_ms: u32,
+        _disconnect: bool,
+    ) -> BtStatus {
+        // Simulated peers answer every request right away, so none can time out.
+        if self.clients.contains_key(&client_id) {
+            BtStatus::Success
+        } else {
+            BtStatus::InvalidParam
+        }
+    }
+
+    fn connection_parameter_update(
+        &self,
+        client_id: i32,
+        addr: RawAddress,
+        _min_interval: i32,
+        max_interval: i32,
+        latency: i32,
+        timeout: i32,
+        _min_ce_len: u16,
+        _max_ce_len: u16,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::NotReady;
+        }
+
+        self.clients[&client_id].on_connection_updated(addr, max_interval, latency, timeout, 0);
+        BtStatus::Success
+    }
+
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: RawAddress,
+        priority: ConnectionPriority,
+    ) -> BtStatus {
+        if !self.is_connected(client_id, addr) {
+            return BtStatus::RemoteDeviceDown;
+        }
+
+        let (min_interval, max_interval, latency) = priority.parameters();
+        self.connection_parameter_update(
+            client_id,
+            addr,
+            min_interval,
+            max_interval,
+            latency,
+            CONNECTION_PRIORITY_TIMEOUT,
+            0,
+            0,
+        )
+    }
+
+    fn set_server_authorization(&mut self, client_id: i32, _enabled: bool) -> BtStatus {
+        // Simulated peers never use the services the stack hosts, so nothing is ever asked.
+        if self.clients.contains_key(&client_id) {
+            BtStatus::Success
+        } else {
+            BtStatus::InvalidParam
+        }
+    }
+
+    fn send_authorization_response(
+        &mut self,
+        client_id: i32,
+        _addr: RawAddress,
+        _handle: i32,
+        _allowed: bool,
+    ) -> BtStatus {
+        if self.clients.contains_key(&client_id) {
+            BtStatus::Fail
+        } else {
+            BtStatus::InvalidParam
+        }
+    }
+
+    fn set_device_information(&mut self, _manufacturer: String, _model: String) {}
+
+    fn set_appearance(&mut self, _appearance: u16) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RPCProxy;
+    use std::sync::{Arc, Mutex};
+
+    const PEER_ADDR: RawAddress = RawAddress { val: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66] };
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Recorder {
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.events.lock().unwrap())
+        }
+    }
+
+    impl RPCProxy for Recorder {
+        fn get_object_id(&self) -> String {
+            String::from("recorder")
+        }
+    }
+
+    impl IBluetoothCallback for Recorder {
+        fn on_adapter_state_changed(&self, state: AdapterState) {
+            self.record(format!("state {:?}", state));
+        }
+
+        fn on_address_changed(&self, _addr: String) {}
+
+        fn on_name_changed(&self, _name: String) {}
+
+        fn on_discoverable_changed(&self, _discoverable: bool) {}
+
+        fn on_device_found(&self, remote_device: BluetoothDevice, _info: FoundDeviceInfo) {
+            self.record(format!("found {}", remote_device.name));
+        }
+
+        fn on_device_cleared(&self, _remote_device: BluetoothDevice) {}
+
+        fn on_remote_name_fetched(&self, _remote_device: BluetoothDevice, _success: bool) {}
+
+        fn on_discovering_changed(&self, discovering: bool) {
+            self.record(format!("discovering {}", discovering));
+        }
+
+        fn on_connected_count_changed(&self, _count: u32) {}
+
+        fn on_ssp_request(
+            &self,
+            _remote_device: BluetoothDevice,
+            _cod: u32,
+            _variant: bt_topshim::btif::BtSspVariant,
+            _passkey: u32,
+        ) {
+        }
+
+        fn on_pin_request(&self, _remote_device: BluetoothDevice, _cod: u32, _min_16_digit: bool) {}
+
+        fn on_pin_display(&self, _remote_device: BluetoothDevice, _pincode: String) {}
+
+        fn on_pairing_rejected(
+            &self,
+            _remote_device: BluetoothDevice,
+            _reason: crate::bluetooth::PairingRejectedReason,
+        ) {
+        }
+
+        fn on_encryption_key_size_rejected(
+            &self,
+            _remote_device: BluetoothDevice,
+            _transport: BtTransport,
+            _key_size: u8,
+        ) {
+        }
+
+        fn on_bond_state_changed(&self, status: u32, _device_address: String, state: u32) {
+            self.record(format!("bond {} {}", status, state));
+        }
+    }
+
+    impl IBluetoothDiscoverySessionCallback for Recorder {
+        fn on_session_device_found(&self, session_id: u32, remote_device: BluetoothDevice) {
+            self.record(format!("session {} found {}", session_id, remote_device.name));
+        }
+
+        fn on_session_device_lost(&self, session_id: u32, remote_device: BluetoothDevice) {
+            self.record(format!("session {} lost {}", session_id, remote_device.name));
+        }
+
+        fn on_discovery_session_ended(&self, session_id: u32) {
+            self.record(format!("session {} ended", session_id));
+        }
+    }
+
+    #[test]
+    fn test_enable_discover_bond() {
+        let recorder = Recorder::default();
+        let mut adapter =
+            MockBluetooth::new(AdapterScript::default(), vec![FakePeer::new(PEER_ADDR, "peer")]);
+        adapter.register_callback(Box::new(recorder.clone()));
+
+        assert_eq!(adapter.start_discovery(), BtStatus::NotReady);
+        assert!(adapter.enable());
+        assert_eq!(adapter.get_adapter_state(), AdapterState::On);
+        assert_eq!(recorder.take(), vec!["state TurningOn", "state On"]);
+
+        assert_eq!(adapter.start_discovery(), BtStatus::Success);
+        assert_eq!(recorder.take(), vec!["discovering true", "found peer"]);
+
+        let device = FakePeer::new(PEER_ADDR, "peer").device();
+        assert_eq!(adapter.create_bond(device.clone(), BtTransport::Le), BtStatus::Success);
+        assert_eq!(recorder.take(), vec!["bond 0 1", "bond 0 2"]);
+        assert_eq!(adapter.get_bonded_devices().len(), 1);
+        assert_eq!(adapter.get_bond_state(device), BtBondState::Bonded as u32);
+    }
+
+    #[test]
+    fn test_remote_device_info() {
+        let adapter =
+            MockBluetooth::new(AdapterScript::default(), vec![FakePeer::new(PEER_ADDR, "peer")]);
+        let info = adapter.get_remote_device_info(FakePeer::new(PEER_ADDR, "peer").device());
+        assert!(info.known);
+        assert_eq!(info.device.name, "peer");
+        assert_eq!(info.rssi_history, vec![RssiSample { rssi: -50, age_ms: 0 }]);
+        assert_eq!(info.connection_state, 0);
+
+        let unknown = BluetoothDevice::new(RawAddress::default(), String::new());
+        assert!(!adapter.get_remote_device_info(unknown).known);
+    }
+
+    #[test]
+    fn test_replay_adapter_state() {
+        let mut adapter =
+            MockBluetooth::new(AdapterScript::default(), vec![FakePeer::new(PEER_ADDR, "peer")]);
+        assert!(adapter.enable());
+        assert_eq!(adapter.start_discovery(), BtStatus::Success);
+        let device = FakePeer::new(PEER_ADDR, "peer").device();
+        assert_eq!(adapter.create_bond(device, BtTransport::Le), BtStatus::Success);
+
+        let recorder = Recorder::default();
+        let id = adapter.register_callback(Box::new(recorder.clone()));
+        assert!(recorder.take().is_empty());
+
+        assert!(adapter.replay_adapter_state(id));
+        assert_eq!(recorder.take(), vec!["state On", "discovering true", "bond 0 2"]);
+        assert!(!adapter.replay_adapter_state(id + 1));
+    }
+
+    #[test]
+    fn test_discovery_session() {
+        let recorder = Recorder::default();
+        let mut adapter =
+            MockBluetooth::new(AdapterScript::default(), vec![FakePeer::new(PEER_ADDR, "peer")]);
+        assert_eq!(adapter.start_discovery_session(Box::new(recorder.clone()), 1000), 0);
+
+        assert!(adapter.enable());
+        let id = adapter.start_discovery_session(Box::new(recorder.clone()), 1000);
+        assert_ne!(id, 0);
+        assert!(adapter.is_discovering());
+        assert_eq!(recorder.take(), vec![format!("session {} found peer", id)]);
+
+        adapter.remove_peer(PEER_ADDR);
+        assert_eq!(recorder.take(), vec![format!("session {} lost peer", id)]);
+
+        assert!(adapter.stop_discovery_session(id));
+        assert!(!adapter.is_discovering());
+        assert!(!adapter.stop_discovery_session(id));
+    }
+
+    #[test]
+    fn test_enable_failure() {
+        let recorder = Recorder::default();
+        let script = AdapterScript { enable_succeeds: false, ..Default::default() };
+        let mut adapter = MockBluetooth::new(script, vec![]);
+        adapter.register_callback(Box::new(recorder.clone()));
+
+        assert!(adapter.enable());
+        assert_eq!(adapter.get_adapter_state(), AdapterState::Off);
+        assert_eq!(recorder.take(), vec!["state TurningOn", "state Off"]);
+    }
+
+    #[test]
+    fn test_scan_params() {
+        let mut adapter = MockBluetooth::new(AdapterScript::default(), vec![]);
+        let mut params = adapter.get_page_scan_params();
+        assert_eq!(params, ClassicScanParams::default_page());
+
+        // The window can't be longer than the interval.
+        params.window = params.interval + 1;
+        assert_eq!(adapter.set_page_scan_params(params.clone()), BtStatus::InvalidParam);
+
+        params.window = params.interval;
+        assert_eq!(adapter.set_page_scan_params(params.clone()), BtStatus::Success);
+        assert_eq!(adapter.get_page_scan_params(), params);
+
+        let le_params = LeScanParams { interval: 3, ..Default::default() };
+        assert_eq!(adapter.set_le_scan_params(le_params), BtStatus::InvalidParam);
+    }
+
+    #[test]
+    fn test_le_address_policy() {
+        let mut adapter = MockBluetooth::new(AdapterScript::default(), vec![]);
+        assert_eq!(adapter.get_le_own_address_type(), BtAddrType::Unknown);
+
+        // The two most significant bits of a static address are set.
+        let status = adapter.set_le_static_address(String::from("40:11:22:33:44:55"));
+        assert_eq!(status, BtStatus::InvalidParam);
+        let status = adapter.set_le_static_address(String::from("FF:FF:FF:FF:FF:FF"));
+        assert_eq!(status, BtStatus::InvalidParam);
+        let status = adapter.set_le_static_address(String::from("C0:11:22:33:44:55"));
+        assert_eq!(status, BtStatus::Success);
+
+        assert!(adapter.enable());
+        assert_eq!(adapter.get_le_own_address_type(), BtAddrType::Random);
+
+        // Takes effect from the next enable on.
+        assert_eq!(adapter.set_le_privacy(false, 0), BtStatus::Success);
+        assert_eq!(adapter.get_le_own_address_type(), BtAddrType::Random);
+        assert_eq!(adapter.set_le_privacy(true, MAX_RPA_TIMEOUT + 1), BtStatus::InvalidParam);
+    }
+
+    #[test]
+    fn test_peer_properties() {
+        let mut peer = FakePeer::new(PEER_ADDR, "peer");
+        peer.uuids = vec![[1; 16]];
+
+        match peer.device_found_callback() {
+            BaseCallbacks::DeviceFound(count, properties) => {
+                assert_eq!(count as usize, properties.len());
+                assert!(properties
+                    .iter()
+                    .any(|p| matches!(p, BluetoothProperty::Uuids(u) if u.len() == 1)));
+            }
+            _ => panic!("Expected DeviceFound"),
+        }
+    }
+}
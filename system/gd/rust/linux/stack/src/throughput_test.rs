@@ -0,0 +1,889 @@
+//! Throughput benchmark (IBluetoothThroughputTest).
+//!
+//! Streams data to or from a device for a set time and reports how much of it got through, so
+//! that performance regressions of the stack can be measured the same way every time. Data goes
+//! over GATT, as writes without response to a characteristic of the device or as notifications
+//! from it, or over an LE credit based channel. Each packet starts with its sequence number, which
+//! lets the receiving side count the packets lost on the way; the device at the other end is
+//! expected to run a matching test or tool.
+
+use bt_topshim::btif::{BluetoothInterface, BtStatus, BtTransport, RawAddress};
+use bt_topshim::profiles::gatt::GattStatus;
+use bt_topshim::profiles::socket::{BtSocket, SocketType};
+use bt_topshim::topstack;
+use dbus_macros::dbus_interface;
+use log::{debug, info, warn};
+use std::os::unix::io::RawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::bluetooth::BluetoothDevice;
+use crate::bluetooth_gatt::{
+    parse_uuid_string, BluetoothGatt, BluetoothGattCharacteristic, BluetoothGattService,
+    GattNotification, GattWriteRequestStatus, GattWriteType, IBluetoothGatt,
+    IBluetoothGattCallback, LePhy, LE_PHY_1M_MASK,
+};
+use crate::callbacks::Callbacks;
+use crate::obex;
+use crate::{queue_message, Message, RPCProxy};
+
+/// App UUID of the GATT client the tests connect with.
+const THROUGHPUT_TEST_CLIENT_UUID: &str = "5e0c7a3d91b24f6e8d4a2c17b9f3e608";
+
+const CLIENT_CHARACTERISTIC_CONFIG: &str = "0000290200001000800000805f9b34fb";
+
+/// ATT MTU asked for, so that writes and notifications can carry the largest payloads.
+const MAX_ATT_MTU: i32 = 517;
+
+/// Octets of the sequence number at the start of each packet.
+const SEQUENCE_LEN: usize = 4;
+
+/// Most octets of a packet over GATT, the longest attribute value.
+const MAX_GATT_PAYLOAD_SIZE: u32 = 512;
+
+/// Most octets of a packet over LE CoC, the longest SDU.
+const MAX_COC_PAYLOAD_SIZE: u32 = 65535;
+
+/// Longest test.
+const MAX_DURATION_MS: u32 = 10 * 60 * 1000;
+
+/// Writes queued in the GATT client at once, so that the next one is ready as soon as the
+/// previous one is sent.
+const GATT_WRITES_QUEUED: u32 = 8;
+
+/// How data goes between the stack and the device.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum ThroughputTransport {
+    /// Writes without response to, or notifications from, a characteristic of the device.
+    Gatt = 0,
+    /// An LE credit based channel to a PSM of the device.
+    LeCoc,
+}
+
+/// Which way data goes.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum ThroughputDirection {
+    /// From the stack to the device.
+    Transmit = 0,
+    /// From the device to the stack.
+    Receive,
+}
+
+/// What a throughput test measured.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThroughputTestResult {
+    /// Octets that went through, sequence numbers included.
+    pub bytes: u64,
+    pub packets: u32,
+    /// Packets that couldn't be sent, or that are missing from the sequence the device sent.
+    pub lost_packets: u32,
+    /// How long data flowed, in milliseconds.
+    pub duration_ms: u64,
+    /// `bytes` over `duration_ms`, in bits per second.
+    pub throughput_bps: u64,
+}
+
+/// Defines the throughput test API.
+#[dbus_interface(throughput_test_dbus, "org.chromium.bluetooth.ThroughputTest")]
+pub trait IBluetoothThroughputTest {
+    /// Adds a callback to be told about the results of the tests.
+    #[dbus_method("RegisterCallback")]
+    fn register_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothThroughputTestCallback + Send>,
+    ) -> u32;
+
+    /// Removes a callback added with `register_callback`.
+    #[dbus_method("UnregisterCallback")]
+    fn unregister_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Streams packets of `payload_size` octets to or from `device` over `transport` for
+    /// `duration_ms` milliseconds, counted from when the link is set up, and reports the result
+    /// to the callbacks. `target` is the value handle of the characteristic for GATT, and the PSM
+    /// for LE CoC.
+    ///
+    /// Returns false if a test is already running, or `payload_size` or `duration_ms` are out of
+    /// range.
+    #[dbus_method("StartThroughputTest")]
+    fn start_throughput_test(
+        &mut self,
+        device: BluetoothDevice,
+        transport: ThroughputTransport,
+        direction: ThroughputDirection,
+        target: i32,
+        payload_size: u32,
+        duration_ms: u32,
+    ) -> bool;
+
+    /// Ends the test of `device` early. What it measured so far is still reported. Returns false
+    /// if no test of `device` is running.
+    #[dbus_method("StopThroughputTest")]
+    fn stop_throughput_test(&mut self, device: BluetoothDevice) -> bool;
+}
+
+/// Throughput test events.
+pub trait IBluetoothThroughputTestCallback: RPCProxy {
+    /// The test of `device` ended. `status` is `Success` if it ran for its duration or was
+    /// stopped, and tells what went wrong otherwise, in which case `result` covers the data that
+    /// went through before.
+    fn on_throughput_test_complete(
+        &self,
+        device: BluetoothDevice,
+        status: BtStatus,
+        result: ThroughputTestResult,
+    );
+}
+
+/// Events from the rest of the stack.
+#[derive(Debug)]
+pub enum ThroughputTestActions {
+    /// The GATT client of the tests was registered with this status and id.
+    GattClientRegistered(i32, i32),
+    GattConnectionState(RawAddress, bool),
+    GattMtuConfigured(RawAddress),
+    GattSearchComplete(RawAddress, i32, Vec<BluetoothGattService>),
+    GattDescriptorWritten(RawAddress, i32),
+    GattCharacteristicWritten(RawAddress, i32),
+    GattNotified(RawAddress, i32, Vec<u8>),
+    /// The test of the device ran for its duration.
+    Timeout(RawAddress),
+    /// The channel of an LE CoC test was closed, after it measured this.
+    CocDone(RawAddress, BtStatus, ThroughputTestResult),
+}
+
+/// Hands the events of the GATT client of the tests to the dispatch loop.
+struct ThroughputGattCallback {
+    tx: Sender<Message>,
+}
+
+impl ThroughputGattCallback {
+    fn send(&self, action: ThroughputTestActions) {
+        queue_message(&self.tx, Message::ThroughputTest(action));
+    }
+}
+
+impl IBluetoothGattCallback for ThroughputGattCallback {
+    fn on_client_registered(&self, status: i32, client_id: i32) {
+        self.send(ThroughputTestActions::GattClientRegistered(status, client_id));
+    }
+
+    fn on_client_connection_state(
+        &self,
+        _status: i32,
+        _client_id: i32,
+        connected: bool,
+        addr: RawAddress,
+    ) {
+        self.send(ThroughputTestActions::GattConnectionState(addr, connected));
+    }
+
+    fn on_phy_update(
+        &self,
+        _addr: RawAddress,
+        _tx_phy: LePhy,
+        _rx_phy: LePhy,
+        _status: GattStatus,
+    ) {
+    }
+
+    fn on_phy_read(&self, _addr: RawAddress, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+
+    fn on_search_complete(
+        &self,
+        addr: RawAddress,
+        services: Vec<BluetoothGattService>,
+        status: i32,
+    ) {
+        self.send(ThroughputTestActions::GattSearchComplete(addr, status, services));
+    }
+
+    fn on_characteristic_read(
+        &self,
+        _addr: RawAddress,
+        _status: i32,
+        _handle: i32,
+        _value: Arc<[u8]>,
+    ) {
+    }
+
+    fn on_characteristic_write(&self, addr: RawAddress, status: i32, _handle: i32) {
+        self.send(ThroughputTestActions::GattCharacteristicWritten(addr, status));
+    }
+
+    fn on_execute_write(&self, _addr: RawAddress, _status: i32) {}
+
+    fn on_descriptor_read(&self, _addr: RawAddress, _status: i32, _handle: i32, _value: Arc<[u8]>) {
+    }
+
+    fn on_descriptor_write(&self, addr: RawAddress, status: i32, _handle: i32) {
+        self.send(ThroughputTestActions::GattDescriptorWritten(addr, status));
+    }
+
+    fn on_notify(&self, addr: RawAddress, handle: i32, value: Arc<[u8]>) {
+        self.send(ThroughputTestActions::GattNotified(addr, handle, value.to_vec()));
+    }
+
+    fn on_notify_batch(&self, _addr: RawAddress, _notifications: Vec<GattNotification>) {}
+
+    fn on_read_remote_rssi(&self, _addr: RawAddress, _rssi: i32, _status: i32) {}
+
+    fn on_configure_mtu(&self, addr: RawAddress, _mtu: i32, _status: i32) {
+        self.send(ThroughputTestActions::GattMtuConfigured(addr));
+    }
+
+    fn on_connection_updated(
+        &self,
+        _addr: RawAddress,
+        _interval: i32,
+        _latency: i32,
+        _timeout: i32,
+        _status: i32,
+    ) {
+    }
+
+    fn on_service_changed(&self, _addr: RawAddress) {}
+
+    fn on_gatt_db_updated(
+        &self,
+        _addr: RawAddress,
+        _services: Vec<BluetoothGattService>,
+        _database_hash: Vec<u8>,
+    ) {
+    }
+
+    fn on_authorization_request(&self, _addr: RawAddress, _handle: i32) {}
+}
+
+impl RPCProxy for ThroughputGattCallback {
+    fn get_object_id(&self) -> String {
+        String::from("throughput_test")
+    }
+}
+
+/// Registration of the GATT client of the tests, which is kept once registered.
+#[derive(Debug, PartialEq)]
+enum GattClient {
+    Unregistered,
+    Registering,
+    Registered(i32),
+}
+
+/// Steps of a GATT test before data flows.
+#[derive(Debug, PartialEq)]
+enum Stage {
+    Connecting,
+    ConfiguringMtu,
+    Discovering,
+    Subscribing,
+    Running,
+}
+
+/// Counts the packets that went through in one direction.
+#[derive(Debug, Default)]
+struct PacketCounter {
+    bytes: u64,
+    packets: u32,
+    failed: u32,
+    /// Lowest and highest sequence numbers received.
+    sequences: Option<(u32, u32)>,
+}
+
+impl PacketCounter {
+    fn sent(&mut self, len: usize) {
+        self.bytes += len as u64;
+        self.packets += 1;
+    }
+
+    fn failed(&mut self) {
+        self.failed += 1;
+    }
+
+    fn received(&mut self, packet: &[u8]) {
+        self.sent(packet.len());
+        if packet.len() < SEQUENCE_LEN {
+            return;
+        }
+
+        // Packets may be handed over out of order, so the gaps are only counted at the end.
+        let sequence = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        self.sequences = Some(match self.sequences {
+            Some((lowest, highest)) => (lowest.min(sequence), highest.max(sequence)),
+            None => (sequence, sequence),
+        });
+    }
+
+    fn result(&self, elapsed: Duration) -> ThroughputTestResult {
+        let expected = self.sequences.map_or(0, |(lowest, highest)| highest - lowest + 1);
+        let duration_ms = elapsed.as_millis() as u64;
+        ThroughputTestResult {
+            bytes: self.bytes,
+            packets: self.packets,
+            lost_packets: self.failed + expected.saturating_sub(self.packets),
+            duration_ms,
+            throughput_bps: (self.bytes * 8 * 1000).checked_div(duration_ms).unwrap_or(0),
+        }
+    }
+}
+
+/// Packet `sequence` of `size` octets: the sequence number, then a counting pattern.
+fn packet(sequence: u32, size: usize) -> Vec<u8> {
+    let mut packet = sequence.to_le_bytes().to_vec();
+    packet.extend((SEQUENCE_LEN..size).map(|i| i as u8));
+    packet.truncate(size);
+    packet
+}
+
+/// Runs the transmit or receive loop of an LE CoC test on the socket `fd`, returned by
+/// `BtSocket::connect`, until `duration` passes or `stop` fires.
+async fn run_coc_test(
+    fd: RawFd,
+    direction: ThroughputDirection,
+    payload_size: usize,
+    duration: Duration,
+    mut stop: oneshot::Receiver<()>,
+) -> (BtStatus, ThroughputTestResult) {
+    let mut stream = match obex::wait_for_connection(fd).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("Throughput test channel wasn't connected: {}", e);
+            return (BtStatus::Fail, ThroughputTestResult::default());
+        }
+    };
+
+    let mut counter = PacketCounter::default();
+    let mut buf = vec![0u8; payload_size];
+    let mut sequence: u32 = 0;
+    let started = Instant::now();
+    let deadline = time::sleep(duration);
+    tokio::pin!(deadline);
+
+    let status = loop {
+        let step = async {
+            match direction {
+                ThroughputDirection::Transmit => {
+                    stream.write_all(&packet(sequence, payload_size)).await?;
+                    counter.sent(payload_size);
+                    sequence = sequence.wrapping_add(1);
+                }
+                ThroughputDirection::Receive => {
+                    // The socket doesn't keep the SDU boundaries, so the device has to send
+                    // packets of the same size.
+                    stream.read_exact(&mut buf).await?;
+                    counter.received(&buf);
+                }
+            }
+            Ok::<(), std::io::Error>(())
+        };
+
+        tokio::select! {
+            _ = &mut deadline => break BtStatus::Success,
+            _ = &mut stop => break BtStatus::Success,
+            result = step => {
+                if let Err(e) = result {
+                    debug!("Throughput test channel closed: {}", e);
+                    break BtStatus::RemoteDeviceDown;
+                }
+            }
+        }
+    };
+
+    (status, counter.result(started.elapsed()))
+}
+
+struct ThroughputTest {
+    device: BluetoothDevice,
+    transport: ThroughputTransport,
+    direction: ThroughputDirection,
+    target: i32,
+    payload_size: usize,
+    duration: Duration,
+    stage: Stage,
+    /// When data started to flow.
+    started: Option<Instant>,
+    counter: PacketCounter,
+    /// Sequence number of the next packet sent over GATT.
+    sequence: u32,
+    writes_queued: u32,
+    /// Client Characteristic Configuration descriptor written to receive notifications.
+    config_handle: Option<i32>,
+    /// Ends the channel task of an LE CoC test.
+    stop: Option<oneshot::Sender<()>>,
+    timer: Option<JoinHandle<()>>,
+}
+
+/// Implementation of the throughput test API.
+pub struct BluetoothThroughputTest {
+    tx: Sender<Message>,
+    intf: Arc<Mutex<BluetoothInterface>>,
+    gatt: Option<Arc<Mutex<Box<BluetoothGatt>>>>,
+    socket: Option<BtSocket>,
+    callbacks: Callbacks<dyn IBluetoothThroughputTestCallback + Send>,
+    gatt_client: GattClient,
+    test: Option<ThroughputTest>,
+}
+
+impl BluetoothThroughputTest {
+    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> Self {
+        BluetoothThroughputTest {
+            tx: tx.clone(),
+            intf,
+            gatt: None,
+            socket: None,
+            callbacks: Callbacks::new(tx, Message::ThroughputTestCallbackDisconnected),
+            gatt_client: GattClient::Unregistered,
+            test: None,
+        }
+    }
+
+    pub fn set_gatt(&mut self, gatt: Arc<Mutex<Box<BluetoothGatt>>>) {
+        self.gatt = Some(gatt);
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove_callback(id)
+    }
+
+    pub fn dispatch_throughput_test_actions(&mut self, action: ThroughputTestActions) {
+        match action {
+            ThroughputTestActions::GattClientRegistered(status, client_id) => {
+                if status != GattStatus::Success as i32 {
+                    warn!("Throughput test GATT client wasn't registered, status {}", status);
+                    self.gatt_client = GattClient::Unregistered;
+                    if self.is_waiting_for_gatt() {
+                        self.finish(BtStatus::Fail);
+                    }
+                    return;
+                }
+                self.gatt_client = GattClient::Registered(client_id);
+                if self.is_waiting_for_gatt() {
+                    self.gatt_connect();
+                }
+            }
+            ThroughputTestActions::GattConnectionState(address, connected) => {
+                if !self.is_testing(&address, ThroughputTransport::Gatt) {
+                    return;
+                }
+                // A disconnection while connecting is the end of a previous test of the device.
+                let connecting = self.test.as_ref().unwrap().stage == Stage::Connecting;
+                if !connected && !connecting {
+                    self.finish(BtStatus::RemoteDeviceDown);
+                } else if connected && connecting {
+                    self.test.as_mut().unwrap().stage = Stage::ConfiguringMtu;
+                    self.with_gatt(|gatt, client_id| {
+                        gatt.configure_mtu(client_id, address, MAX_ATT_MTU)
+                    });
+                }
+            }
+            ThroughputTestActions::GattMtuConfigured(address) => {
+                if !self.is_at_stage(&address, Stage::ConfiguringMtu) {
+                    return;
+                }
+                // A smaller MTU than asked for only fails the test if the packets don't fit it.
+                match self.test.as_ref().unwrap().direction {
+                    ThroughputDirection::Transmit => self.start_running(),
+                    ThroughputDirection::Receive => {
+                        self.test.as_mut().unwrap().stage = Stage::Discovering;
+                        self.with_gatt(|gatt, client_id| {
+                            gatt.discover_services(client_id, address)
+                        });
+                    }
+                }
+            }
+            ThroughputTestActions::GattSearchComplete(address, status, services) => {
+                if !self.is_at_stage(&address, Stage::Discovering) {
+                    return;
+                }
+                if status != GattStatus::Success as i32 {
+                    self.finish(BtStatus::Fail);
+                    return;
+                }
+                self.subscribe(address, &services);
+            }
+            ThroughputTestActions::GattDescriptorWritten(address, status) => {
+                if !self.is_at_stage(&address, Stage::Subscribing) {
+                    return;
+                }
+                if status != GattStatus::Success as i32 {
+                    self.finish(BtStatus::Fail);
+                    return;
+                }
+                self.start_running();
+            }
+            ThroughputTestActions::GattCharacteristicWritten(address, status) => {
+                if !self.is_at_stage(&address, Stage::Running) {
+                    return;
+                }
+                let test = self.test.as_mut().unwrap();
+                test.writes_queued = test.writes_queued.saturating_sub(1);
+                if status == GattStatus::Success as i32 {
+                    test.counter.sent(test.payload_size);
+                } else {
+                    test.counter.failed();
+                }
+                self.queue_writes();
+            }
+            ThroughputTestActions::GattNotified(address, handle, value) => {
+                if !self.is_at_stage(&address, Stage::Running) {
+                    return;
+                }
+                let test = self.test.as_mut().unwrap();
+                if test.direction == ThroughputDirection::Receive && handle == test.target {
+                    test.counter.received(&value);
+                }
+            }
+            ThroughputTestActions::Timeout(address) => {
+                if self.is_testing(&address, ThroughputTransport::Gatt) {
+                    self.finish(BtStatus::Success);
+                }
+            }
+            ThroughputTestActions::CocDone(address, status, result) => {
+                if !self.is_testing(&address, ThroughputTransport::LeCoc) {
+                    return;
+                }
+                let test = self.test.take().unwrap();
+                self.report(test.device, status, result);
+            }
+        }
+    }
+
+    fn is_testing(&self, address: &RawAddress, transport: ThroughputTransport) -> bool {
+        self.test
+            .as_ref()
+            .map_or(false, |test| test.device.address == *address && test.transport == transport)
+    }
+
+    fn is_waiting_for_gatt(&self) -> bool {
+        self.test.as_ref().map_or(false, |test| {
+            test.transport == ThroughputTransport::Gatt && test.stage == Stage::Connecting
+        })
+    }
+
+    fn is_at_stage(&self, address: &RawAddress, stage: Stage) -> bool {
+        self.is_testing(address, ThroughputTransport::Gatt)
+            && self.test.as_ref().unwrap().stage == stage
+    }
+
+    /// Calls `f` with the GATT client of the tests, and ends the test if it fails.
+    fn with_gatt<F: FnOnce(&mut BluetoothGatt, i32) -> BtStatus>(&mut self, f: F) {
+        let status = match (&self.gatt, &self.gatt_client) {
+            (Some(gatt), GattClient::Registered(client_id)) => {
+                f(&mut gatt.lock().unwrap(), *client_id)
+            }
+            _ => BtStatus::NotReady,
+        };
+        if status != BtStatus::Success {
+            debug!("Throughput test GATT request failed: {:?}", status);
+            self.finish(status);
+        }
+    }
+
+    fn gatt_connect(&mut self) {
+        let address = self.test.as_ref().unwrap().device.address;
+        self.with_gatt(|gatt, client_id| {
+            gatt.client_connect(client_id, address, true, BtTransport::Le, false, LE_PHY_1M_MASK)
+        });
+    }
+
+    /// Subscribes to the notifications of the test characteristic among `services`.
+    fn subscribe(&mut self, address: RawAddress, services: &[BluetoothGattService]) {
+        let handle = self.test.as_ref().unwrap().target;
+        let characteristic = services
+            .iter()
+            .flat_map(|service| service.characteristics.iter())
+            .find(|characteristic| characteristic.instance_id == handle);
+        let config_uuid = parse_uuid_string(CLIENT_CHARACTERISTIC_CONFIG).unwrap().uu;
+        let config = characteristic.and_then(|characteristic| {
+            characteristic.descriptors.iter().find(|descriptor| descriptor.uuid == config_uuid)
+        });
+        let (properties, config_handle) = match (characteristic, config) {
+            (Some(characteristic), Some(config)) => (characteristic.properties, config.instance_id),
+            _ => {
+                warn!("[{}]: No characteristic {} to be notified by", address, handle);
+                self.finish(BtStatus::Unsupported);
+                return;
+            }
+        };
+        let value = if properties & BluetoothGattCharacteristic::PROPERTY_NOTIFY != 0 {
+            vec![0x01, 0x00]
+        } else if properties & BluetoothGattCharacteristic::PROPERTY_INDICATE != 0 {
+            vec![0x02, 0x00]
+        } else {
+            self.finish(BtStatus::Unsupported);
+            return;
+        };
+
+        let test = self.test.as_mut().unwrap();
+        test.stage = Stage::Subscribing;
+        test.config_handle = Some(config_handle);
+        self.with_gatt(|gatt, client_id| {
+            match gatt.register_for_notification(client_id, address, handle, true) {
+                BtStatus::Success => {
+                    gatt.write_descriptor(client_id, address, config_handle, 0, value)
+                }
+                status => status,
+            }
+        });
+    }
+
+    /// Starts timing the GATT test once the link is set up.
+    fn start_running(&mut self) {
+        let test = self.test.as_mut().unwrap();
+        test.stage = Stage::Running;
+        test.started = Some(Instant::now());
+
+        let txl = self.tx.clone();
+        let (address, duration) = (test.device.address, test.duration);
+        test.timer = Some(tokio::spawn(async move {
+            time::sleep(duration).await;
+            let _ =
+                txl.send(Message::ThroughputTest(ThroughputTestActions::Timeout(address))).await;
+        }));
+
+        if test.direction == ThroughputDirection::Transmit {
+            self.queue_writes();
+        }
+    }
+
+    fn queue_writes(&mut self) {
+        let (client_id, gatt) = match (&self.gatt_client, &self.gatt) {
+            (GattClient::Registered(client_id), Some(gatt)) => (*client_id, gatt.clone()),
+            _ => return,
+        };
+        let test = self.test.as_mut().unwrap();
+        while test.writes_queued < GATT_WRITES_QUEUED {
+            let status = gatt.lock().unwrap().write_characteristic(
+                client_id,
+                test.device.address,
+                test.target,
+                GattWriteType::WriteNoRsp,
+                0,
+                packet(test.sequence, test.payload_size),
+            );
+            if !matches!(status, GattWriteRequestStatus::Success) {
+                break;
+            }
+            test.writes_queued += 1;
+            test.sequence = test.sequence.wrapping_add(1);
+        }
+
+        // Writes are only refused up front when the packets don't fit the MTU.
+        if test.writes_queued == 0 {
+            self.finish(BtStatus::Fail);
+        }
+    }
+
+    /// Ends the GATT test running, if any, and tears down its connection.
+    fn finish(&mut self, status: BtStatus) {
+        let test = match self.test.take() {
+            Some(test) => test,
+            None => return,
+        };
+        if let Some(timer) = &test.timer {
+            timer.abort();
+        }
+
+        let address = test.device.address;
+        if let (Some(gatt), GattClient::Registered(client_id)) = (&self.gatt, &self.gatt_client) {
+            let mut gatt = gatt.lock().unwrap();
+            if let Some(config_handle) = test.config_handle {
+                gatt.write_descriptor(*client_id, address, config_handle, 0, vec![0x00, 0x00]);
+                gatt.register_for_notification(*client_id, address, test.target, false);
+            }
+            gatt.client_disconnect(*client_id, address);
+        }
+
+        let result = test.counter.result(test.started.map_or(Duration::ZERO, |s| s.elapsed()));
+        self.report(test.device, status, result);
+    }
+
+    fn report(&mut self, device: BluetoothDevice, status: BtStatus, result: ThroughputTestResult) {
+        info!(
+            "[{}]: Throughput test done with {:?}: {} packets, {} lost, {} bps",
+            device.address, status, result.packets, result.lost_packets, result.throughput_bps
+        );
+        self.callbacks.for_all_callbacks(|callback| {
+            callback.on_throughput_test_complete(device.clone(), status.clone(), result.clone());
+        });
+    }
+
+    fn start_coc_test(&mut self) -> bool {
+        if self.socket.is_none() {
+            self.socket = Some(BtSocket::new(&self.intf.lock().unwrap()));
+        }
+        let test = self.test.as_mut().unwrap();
+        let address = test.device.address;
+        let (status, fd) = self.socket.as_ref().unwrap().connect(
+            address,
+            SocketType::L2capLe,
+            None,
+            test.target,
+            0,
+        );
+        let fd = match (&status, fd) {
+            (BtStatus::Success, Some(fd)) => fd,
+            _ => {
+                warn!("[{}]: Failed to connect the throughput test channel: {:?}", address, status);
+                return false;
+            }
+        };
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        test.stop = Some(stop_tx);
+        test.stage = Stage::Running;
+        let (direction, payload_size, duration) =
+            (test.direction, test.payload_size, test.duration);
+        let tx = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            let (status, result) =
+                run_coc_test(fd, direction, payload_size, duration, stop_rx).await;
+            let _ = tx
+                .send(Message::ThroughputTest(ThroughputTestActions::CocDone(
+                    address, status, result,
+                )))
+                .await;
+        });
+        true
+    }
+}
+
+impl IBluetoothThroughputTest for BluetoothThroughputTest {
+    fn register_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothThroughputTestCallback + Send>,
+    ) -> u32 {
+        self.callbacks.add_callback(callback)
+    }
+
+    fn unregister_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn start_throughput_test(
+        &mut self,
+        device: BluetoothDevice,
+        transport: ThroughputTransport,
+        direction: ThroughputDirection,
+        target: i32,
+        payload_size: u32,
+        duration_ms: u32,
+    ) -> bool {
+        let max_payload_size = match transport {
+            ThroughputTransport::Gatt => MAX_GATT_PAYLOAD_SIZE,
+            ThroughputTransport::LeCoc => MAX_COC_PAYLOAD_SIZE,
+        };
+        if self.test.is_some()
+            || payload_size < SEQUENCE_LEN as u32
+            || payload_size > max_payload_size
+            || duration_ms == 0
+            || duration_ms > MAX_DURATION_MS
+        {
+            return false;
+        }
+
+        self.test = Some(ThroughputTest {
+            device,
+            transport,
+            direction,
+            target,
+            payload_size: payload_size as usize,
+            duration: Duration::from_millis(duration_ms.into()),
+            stage: Stage::Connecting,
+            started: None,
+            counter: PacketCounter::default(),
+            sequence: 0,
+            writes_queued: 0,
+            config_handle: None,
+            stop: None,
+            timer: None,
+        });
+
+        if transport == ThroughputTransport::LeCoc {
+            if !self.start_coc_test() {
+                self.test = None;
+                return false;
+            }
+            return true;
+        }
+
+        match self.gatt_client {
+            GattClient::Registered(_) => self.gatt_connect(),
+            GattClient::Registering => (),
+            GattClient::Unregistered => {
+                let gatt = match &self.gatt {
+                    Some(gatt) => gatt.clone(),
+                    None => {
+                        self.test = None;
+                        return false;
+                    }
+                };
+                let callback = Box::new(ThroughputGattCallback { tx: self.tx.clone() });
+                let uuid = parse_uuid_string(THROUGHPUT_TEST_CLIENT_UUID).unwrap().uu;
+                if gatt.lock().unwrap().register_client(uuid, callback, false) != BtStatus::Success
+                {
+                    self.test = None;
+                    return false;
+                }
+                self.gatt_client = GattClient::Registering;
+            }
+        }
+        // Connecting may have failed already.
+        self.test.is_some()
+    }
+
+    fn stop_throughput_test(&mut self, device: BluetoothDevice) -> bool {
+        let test = match self.test.as_mut() {
+            Some(test) if test.device.address == device.address => test,
+            _ => return false,
+        };
+        match test.stop.take() {
+            // The channel task reports what it measured once it ends.
+            Some(stop) => {
+                let _ = stop.send(());
+            }
+            None => self.finish(BtStatus::Success),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet() {
+        assert_eq!(packet(0x01020304, 8), vec![0x04, 0x03, 0x02, 0x01, 4, 5, 6, 7]);
+        assert_eq!(packet(7, 4), vec![7, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_packet_counter_counts_gaps() {
+        let mut counter = PacketCounter::default();
+        for sequence in [10, 12, 11, 15] {
+            counter.received(&packet(sequence, 20));
+        }
+        assert_eq!(
+            counter.result(Duration::from_millis(500)),
+            ThroughputTestResult {
+                bytes: 80,
+                packets: 4,
+                lost_packets: 2,
+                duration_ms: 500,
+                throughput_bps: 1280
+            }
+        );
+
+        let mut counter = PacketCounter::default();
+        counter.sent(100);
+        counter.failed();
+        assert_eq!(counter.result(Duration::ZERO).lost_packets, 1);
+        assert_eq!(counter.result(Duration::ZERO).throughput_bps, 0);
+    }
+}
@@ -79,23 +79,7 @@ pub struct UuidHelper {
 
 impl UuidHelper {
     pub fn new() -> Self {
-        let enabled_profiles: HashSet<Profile> = [
-            Profile::A2dpSink,
-            Profile::A2dpSource,
-            Profile::Hsp,
-            Profile::Hfp,
-            Profile::Hid,
-            Profile::Hogp,
-            Profile::Panu,
-            Profile::PbapPce,
-            Profile::Map,
-            Profile::HearingAid,
-            Profile::VolumeControl,
-            Profile::CoordinatedSet,
-        ]
-        .iter()
-        .cloned()
-        .collect();
+        let enabled_profiles = UuidHelper::default_enabled_profiles();
 
         let profiles: HashMap<Uuid128Bit, Profile> = [
             (UuidHelper::from_string(A2DP_SINK).unwrap(), Profile::A2dpSink),
@@ -134,6 +118,29 @@ impl UuidHelper {
         UuidHelper { enabled_profiles, profiles }
     }
 
+    /// Profiles that are enabled unless restricted by policy.
+    fn default_enabled_profiles() -> HashSet<Profile> {
+        [
+            Profile::A2dpSink,
+            Profile::A2dpSource,
+            Profile::Hsp,
+            Profile::Hfp,
+            Profile::ObexObjectPush,
+            Profile::Hid,
+            Profile::Hogp,
+            Profile::Panu,
+            Profile::Nap,
+            Profile::PbapPce,
+            Profile::Map,
+            Profile::HearingAid,
+            Profile::VolumeControl,
+            Profile::CoordinatedSet,
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    }
+
     /// Checks whether a UUID corresponds to a currently enabled profile.
     pub fn is_profile_enabled(&self, profile: &Profile) -> bool {
         self.enabled_profiles.contains(profile)
@@ -144,10 +151,30 @@ impl UuidHelper {
         self.profiles.get(uuid)
     }
 
+    /// Returns the UUID of a known profile.
+    pub fn get_profile_uuid(&self, profile: &Profile) -> Option<&Uuid128Bit> {
+        self.profiles.iter().find(|(_, p)| *p == profile).map(|(uuid, _)| uuid)
+    }
+
     pub fn get_enabled_profiles(&self) -> HashSet<Profile> {
         self.enabled_profiles.clone()
     }
 
+    /// Restricts the enabled profiles to those whose UUID is in `allowed_services`. An empty
+    /// allowlist puts back all the profiles that are enabled by default.
+    pub fn apply_allowlist(&mut self, allowed_services: &HashSet<Uuid128Bit>) {
+        let profiles = &self.profiles;
+        self.enabled_profiles = UuidHelper::default_enabled_profiles()
+            .into_iter()
+            .filter(|profile| {
+                allowed_services.is_empty()
+                    || profiles
+                        .iter()
+                        .any(|(uuid, p)| p == profile && allowed_services.contains(uuid))
+            })
+            .collect();
+    }
+
     /// Converts a UUID byte array into a formatted string.
     pub fn to_string(uuid: &Uuid128Bit) -> String {
         return String::from(format!("{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
@@ -204,4 +231,28 @@ mod tests {
             });
         }
     }
+
+    #[test]
+    fn test_apply_allowlist() {
+        let mut uuidhelper = UuidHelper::new();
+        let defaults = uuidhelper.get_enabled_profiles();
+
+        let allowlist: HashSet<Uuid128Bit> =
+            [UuidHelper::from_string(HID).unwrap()].iter().cloned().collect();
+        uuidhelper.apply_allowlist(&allowlist);
+        assert!(uuidhelper.is_profile_enabled(&Profile::Hid));
+        assert!(!uuidhelper.is_profile_enabled(&Profile::Hogp));
+        assert!(!uuidhelper.is_profile_enabled(&Profile::A2dpSink));
+
+        uuidhelper.apply_allowlist(&HashSet::new());
+        assert_eq!(uuidhelper.get_enabled_profiles(), defaults);
+    }
+
+    #[test]
+    fn test_get_profile_uuid() {
+        let uuidhelper = UuidHelper::new();
+        for (uuid, profile) in uuidhelper.profiles.iter() {
+            assert_eq!(uuidhelper.get_profile_uuid(profile), Some(uuid));
+        }
+    }
 }
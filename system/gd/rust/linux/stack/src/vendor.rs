@@ -0,0 +1,137 @@
+//! Vendor specific HCI commands of the controllers the stack knows about, for what the standard
+//! commands don't tell: which firmware build runs on the controller, and how to get debug events
+//! out of it.
+//!
+//! The commands and their return parameters follow what the Linux kernel drivers of these
+//! controllers use.
+
+/// Company identifiers assigned by the Bluetooth SIG.
+pub(crate) const MANUFACTURER_INTEL: u16 = 0x0002;
+pub(crate) const MANUFACTURER_BROADCOM: u16 = 0x000f;
+pub(crate) const MANUFACTURER_REALTEK: u16 = 0x005d;
+
+const INTEL_READ_VERSION: u16 = 0xfc05;
+const INTEL_SET_DIAGNOSTIC_MODE: u16 = 0xfc43;
+const INTEL_SET_EVENT_MASK: u16 = 0xfc52;
+const BROADCOM_READ_VERBOSE_CONFIG: u16 = 0xfc79;
+const REALTEK_READ_ROM_VERSION: u16 = 0xfc6d;
+
+/// Intel event mask bits: the default events, plus the debug events with diagnostics on.
+const INTEL_EVENT_MASK_DEFAULT: u8 = 0x87;
+const INTEL_EVENT_MASK_DEBUG: u8 = 0x62;
+
+/// A vendor specific command and its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VendorCommand {
+    pub opcode: u16,
+    pub params: Vec<u8>,
+}
+
+impl VendorCommand {
+    fn new(opcode: u16, params: Vec<u8>) -> VendorCommand {
+        VendorCommand { opcode, params }
+    }
+}
+
+/// Gets the command reading the firmware build of a controller by `manufacturer`, if the stack
+/// knows one.
+pub(crate) fn firmware_version_command(manufacturer: u16) -> Option<VendorCommand> {
+    let opcode = match manufacturer {
+        MANUFACTURER_INTEL => INTEL_READ_VERSION,
+        MANUFACTURER_BROADCOM => BROADCOM_READ_VERBOSE_CONFIG,
+        MANUFACTURER_REALTEK => REALTEK_READ_ROM_VERSION,
+        _ => return None,
+    };
+    Some(VendorCommand::new(opcode, vec![]))
+}
+
+/// Describes the firmware build from the return parameters, starting with the status, of the
+/// command from `firmware_version_command`. Returns None if the command failed or the parameters
+/// don't have the expected length.
+pub(crate) fn parse_firmware_version(opcode: u16, params: &[u8]) -> Option<String> {
+    if params.first() != Some(&0) {
+        return None;
+    }
+
+    match (opcode, params.len()) {
+        // Status, hardware platform, variant and revision, firmware variant and revision, build
+        // number, week and year, patch number.
+        (INTEL_READ_VERSION, 10) => Some(format!(
+            "variant {:#04x} revision {}.{} build {} week {} {}",
+            params[2],
+            params[5] >> 4,
+            params[5] & 0x0f,
+            params[6],
+            params[7],
+            2000 + params[8] as u32,
+        )),
+        // Status, chip id, target id and an unused field, then the build number.
+        (BROADCOM_READ_VERBOSE_CONFIG, 7) => Some(format!(
+            "chip {} build {:04}",
+            params[1],
+            u16::from_le_bytes([params[5], params[6]])
+        )),
+        (REALTEK_READ_ROM_VERSION, 2) => Some(format!("ROM version {}", params[1])),
+        _ => None,
+    }
+}
+
+/// Gets the commands turning the debug events of a controller by `manufacturer` on or off, or
+/// None if the stack doesn't know how to for this manufacturer.
+pub(crate) fn diagnostics_commands(manufacturer: u16, enabled: bool) -> Option<Vec<VendorCommand>> {
+    match manufacturer {
+        MANUFACTURER_INTEL => {
+            let mode = if enabled { 0x03 } else { 0x00 };
+            let mut mask = vec![0; 8];
+            mask[0] = INTEL_EVENT_MASK_DEFAULT | if enabled { INTEL_EVENT_MASK_DEBUG } else { 0 };
+            Some(vec![
+                VendorCommand::new(INTEL_SET_DIAGNOSTIC_MODE, vec![mode; 3]),
+                VendorCommand::new(INTEL_SET_EVENT_MASK, mask),
+            ])
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_firmware_version() {
+        let intel = [0x00, 0x37, 0x11, 0x10, 0x06, 0x21, 0x05, 0x2a, 0x16, 0x00];
+        assert_eq!(
+            parse_firmware_version(INTEL_READ_VERSION, &intel),
+            Some("variant 0x11 revision 2.1 build 5 week 42 2022".to_string())
+        );
+
+        let broadcom = [0x00, 0x6b, 0x01, 0x00, 0x00, 0x9b, 0x02];
+        assert_eq!(
+            parse_firmware_version(BROADCOM_READ_VERBOSE_CONFIG, &broadcom),
+            Some("chip 107 build 0667".to_string())
+        );
+
+        assert_eq!(
+            parse_firmware_version(REALTEK_READ_ROM_VERSION, &[0x00, 0x01]),
+            Some("ROM version 1".to_string())
+        );
+
+        // Failed, truncated, or not a firmware version command.
+        assert_eq!(parse_firmware_version(REALTEK_READ_ROM_VERSION, &[0x01, 0x01]), None);
+        assert_eq!(parse_firmware_version(INTEL_READ_VERSION, &intel[..9]), None);
+        assert_eq!(parse_firmware_version(INTEL_SET_EVENT_MASK, &[0x00]), None);
+    }
+
+    #[test]
+    fn test_diagnostics_commands() {
+        let on = diagnostics_commands(MANUFACTURER_INTEL, true).unwrap();
+        assert_eq!(on[0], VendorCommand::new(INTEL_SET_DIAGNOSTIC_MODE, vec![0x03; 3]));
+        assert_eq!(on[1].params, vec![0xe7, 0, 0, 0, 0, 0, 0, 0]);
+
+        let off = diagnostics_commands(MANUFACTURER_INTEL, false).unwrap();
+        assert_eq!(off[0].params, vec![0x00; 3]);
+        assert_eq!(off[1].params, vec![0x87, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert!(diagnostics_commands(MANUFACTURER_REALTEK, true).is_none());
+    }
+}
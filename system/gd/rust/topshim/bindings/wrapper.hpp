@@ -8,4 +8,6 @@
 
 #include "hardware/bt_gatt.h"
 #include "hardware/bt_hh.h"
+#include "hardware/bt_pan.h"
 #include "hardware/bt_sdp.h"
+#include "hardware/bt_sock.h"
@@ -17,9 +17,12 @@
 #include "gd/rust/topshim/btav/btav_shim.h"
 
 #include <cstdio>
+#include <map>
 #include <memory>
+#include <mutex>
 
 #include "base/callback.h"
+#include "btif/include/btif_av.h"
 #include "gd/rust/topshim/common/utils.h"
 #include "include/hardware/avrcp/avrcp.h"
 #include "include/hardware/bluetooth.h"
@@ -31,36 +34,138 @@
 namespace rusty = ::bluetooth::topshim::rust;
 
 namespace bluetooth::avrcp {
+// Answers the remote controller from the media state last set by the clients. The AVRCP service
+// calls this on the jni thread while the setters come from Rust, hence the lock.
 class AvrcpMediaInterfaceImpl : public MediaInterface {
  public:
   void SendKeyEvent([[maybe_unused]] uint8_t key, [[maybe_unused]] KeyState state) {}
 
-  void GetSongInfo([[maybe_unused]] SongInfoCallback cb) override {}
+  void GetSongInfo(SongInfoCallback cb) override {
+    SongInfo info;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      for (const SongInfo& song : now_playing_) {
+        if (song.media_id == current_media_id_) info = song;
+      }
+    }
+    cb.Run(info);
+  }
 
   void GetPlayStatus([[maybe_unused]] PlayStatusCallback cb) override {}
 
-  void GetNowPlayingList([[maybe_unused]] NowPlayingCallback cb) override {}
+  void GetNowPlayingList(NowPlayingCallback cb) override {
+    std::string current_media_id;
+    std::vector<SongInfo> now_playing;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      current_media_id = current_media_id_;
+      now_playing = now_playing_;
+    }
+    cb.Run(current_media_id, now_playing);
+  }
 
-  void GetMediaPlayerList([[maybe_unused]] MediaListCallback cb) override {}
+  void GetMediaPlayerList(MediaListCallback cb) override {
+    uint16_t addressed_player;
+    std::vector<MediaPlayerInfo> players;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      addressed_player = addressed_player_;
+      players = players_;
+    }
+    cb.Run(addressed_player, players);
+  }
 
-  void GetFolderItems(
-      [[maybe_unused]] uint16_t player_id,
-      [[maybe_unused]] std::string media_id,
-      [[maybe_unused]] FolderItemsCallback folder_cb) override {}
+  void GetFolderItems([[maybe_unused]] uint16_t player_id, std::string media_id, FolderItemsCallback folder_cb)
+      override {
+    std::vector<ListItem> items;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      auto it = folders_.find(media_id);
+      if (it != folders_.end()) items = it->second;
+    }
+    folder_cb.Run(items);
+  }
 
-  void SetBrowsedPlayer(
-      [[maybe_unused]] uint16_t player_id, [[maybe_unused]] SetBrowsedPlayerCallback browse_cb) override {}
+  void SetBrowsedPlayer(uint16_t player_id, SetBrowsedPlayerCallback browse_cb) override {
+    bool browsable = false;
+    uint32_t num_items = 0;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      for (const MediaPlayerInfo& player : players_) {
+        if (player.id == player_id) browsable = player.browsing_supported;
+      }
+      auto it = folders_.find(kRootMediaId);
+      if (it != folders_.end()) num_items = it->second.size();
+    }
+    browse_cb.Run(browsable, kRootMediaId, num_items);
+  }
 
-  void RegisterUpdateCallback([[maybe_unused]] MediaCallbacks* callback) override {}
+  void RegisterUpdateCallback(MediaCallbacks* callback) override {
+    std::lock_guard<std::mutex> lock(mutex_);
+    callbacks_ = callback;
+  }
 
-  void UnregisterUpdateCallback([[maybe_unused]] MediaCallbacks* callback) override {}
+  void UnregisterUpdateCallback(MediaCallbacks* callback) override {
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (callbacks_ == callback) callbacks_ = nullptr;
+  }
 
-  void PlayItem(
-      [[maybe_unused]] uint16_t player_id,
-      [[maybe_unused]] bool now_playing,
-      [[maybe_unused]] std::string media_id) override {}
+  void PlayItem(uint16_t player_id, bool now_playing, std::string media_id) override {
+    rusty::avrcp_play_item(player_id, now_playing, ::rust::String(media_id));
+  }
 
   void SetActiveDevice([[maybe_unused]] const RawAddress& address) override {}
+
+  void SetNowPlayingList(std::string current_media_id, std::vector<SongInfo> now_playing) {
+    MediaCallbacks* callbacks;
+    bool track_changed;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      track_changed = current_media_id_ != current_media_id;
+      current_media_id_ = std::move(current_media_id);
+      now_playing_ = std::move(now_playing);
+      callbacks = callbacks_;
+    }
+    if (callbacks) callbacks->SendMediaUpdate(track_changed, /*play_state=*/false, /*queue=*/true);
+  }
+
+  void SetMediaPlayers(uint16_t addressed_player, std::vector<MediaPlayerInfo> players) {
+    MediaCallbacks* callbacks;
+    bool addressed_changed;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      addressed_changed = addressed_player_ != addressed_player;
+      addressed_player_ = addressed_player;
+      players_ = std::move(players);
+      callbacks = callbacks_;
+    }
+    if (callbacks)
+      callbacks->SendFolderUpdate(/*available_players=*/true, addressed_changed, /*uids_changed=*/false);
+  }
+
+  void SetFolderItems(std::string media_id, std::vector<ListItem> items) {
+    MediaCallbacks* callbacks;
+    {
+      std::lock_guard<std::mutex> lock(mutex_);
+      folders_[media_id] = std::move(items);
+      callbacks = callbacks_;
+    }
+    if (callbacks)
+      callbacks->SendFolderUpdate(/*available_players=*/false, /*addressed_players=*/false, /*uids_changed=*/true);
+  }
+
+ private:
+  // Media id of the top folder of every player.
+  static constexpr const char* kRootMediaId = "";
+
+  std::mutex mutex_;
+  MediaCallbacks* callbacks_ = nullptr;
+  std::string current_media_id_;
+  std::vector<SongInfo> now_playing_;
+  uint16_t addressed_player_ = 0;
+  std::vector<MediaPlayerInfo> players_;
+  // Contents of each folder, by the media id of the folder.
+  std::map<std::string, std::vector<ListItem>> folders_;
 };
 
 class VolumeInterfaceImpl : public VolumeInterface {
@@ -104,6 +209,40 @@ namespace internal {
 static A2dpIntf* g_a2dpif;
 static AvrcpIntf* g_avrcpif;
 
+static avrcp::SongInfo from_rust_song_info(const AvrcpSongInfo& rinfo) {
+  avrcp::SongInfo info;
+  info.media_id = std::string(rinfo.media_id);
+
+  auto add_attribute = [&info](avrcp::Attribute attribute, const std::string& value) {
+    if (!value.empty()) info.attributes.insert(avrcp::AttributeEntry(attribute, value));
+  };
+  add_attribute(avrcp::Attribute::TITLE, std::string(rinfo.title));
+  add_attribute(avrcp::Attribute::ARTIST_NAME, std::string(rinfo.artist));
+  add_attribute(avrcp::Attribute::ALBUM_NAME, std::string(rinfo.album));
+  add_attribute(avrcp::Attribute::GENRE, std::string(rinfo.genre));
+  if (rinfo.track_number) add_attribute(avrcp::Attribute::TRACK_NUMBER, std::to_string(rinfo.track_number));
+  if (rinfo.total_tracks)
+    add_attribute(avrcp::Attribute::TOTAL_NUMBER_OF_TRACKS, std::to_string(rinfo.total_tracks));
+  if (rinfo.duration_ms) add_attribute(avrcp::Attribute::PLAYING_TIME, std::to_string(rinfo.duration_ms));
+  return info;
+}
+
+static avrcp::ListItem from_rust_list_item(const AvrcpListItem& ritem) {
+  avrcp::ListItem item;
+  if (ritem.is_folder) {
+    item.type = avrcp::ListItem::FOLDER;
+    item.folder = {
+        .media_id = std::string(ritem.folder.media_id),
+        .is_playable = ritem.folder.is_playable,
+        .name = std::string(ritem.folder.name),
+    };
+  } else {
+    item.type = avrcp::ListItem::SONG;
+    item.song = from_rust_song_info(ritem.song);
+  }
+  return item;
+}
+
 static A2dpCodecConfig to_rust_codec_config(const btav_a2dp_codec_config_t& config) {
   A2dpCodecConfig rconfig = {.codec_type = static_cast<uint8_t>(config.codec_type),
                              .codec_priority = config.codec_priority,
@@ -248,6 +387,9 @@ RustPresentationPosition A2dpIntf::get_presentation_position() const {
   };
   return rposition;
 }
+bool A2dpIntf::is_offload_supported() const {
+  return btif_av_is_a2dp_offload_enabled();
+}
 
 // AVRCP
 
@@ -286,6 +428,34 @@ int AvrcpIntf::disconnect(RustRawAddress bt_addr) {
 void AvrcpIntf::set_volume(int8_t volume) {
   return mVolumeInterface.SetDeviceVolume(volume);
 }
+
+void AvrcpIntf::set_now_playing_list(::rust::String current_media_id, ::rust::Vec<AvrcpSongInfo> songs) {
+  std::vector<avrcp::SongInfo> now_playing;
+  for (const AvrcpSongInfo& song : songs) {
+    now_playing.push_back(internal::from_rust_song_info(song));
+  }
+  mAvrcpInterface.SetNowPlayingList(std::string(current_media_id), std::move(now_playing));
+}
+
+void AvrcpIntf::set_media_players(uint16_t addressed_player, ::rust::Vec<AvrcpMediaPlayerInfo> players) {
+  std::vector<avrcp::MediaPlayerInfo> infos;
+  for (const AvrcpMediaPlayerInfo& player : players) {
+    infos.push_back({
+        .id = player.id,
+        .name = std::string(player.name),
+        .browsing_supported = player.browsing_supported,
+    });
+  }
+  mAvrcpInterface.SetMediaPlayers(addressed_player, std::move(infos));
+}
+
+void AvrcpIntf::set_folder_items(::rust::String media_id, ::rust::Vec<AvrcpListItem> items) {
+  std::vector<avrcp::ListItem> list;
+  for (const AvrcpListItem& item : items) {
+    list.push_back(internal::from_rust_list_item(item));
+  }
+  mAvrcpInterface.SetFolderItems(std::string(media_id), std::move(list));
+}
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
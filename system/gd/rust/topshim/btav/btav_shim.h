@@ -29,6 +29,9 @@ namespace topshim {
 namespace rust {
 
 struct A2dpCodecConfig;
+struct AvrcpListItem;
+struct AvrcpMediaPlayerInfo;
+struct AvrcpSongInfo;
 struct RustPresentationPosition;
 struct RustRawAddress;
 
@@ -51,6 +54,7 @@ class A2dpIntf {
   bool start_audio_request() const;
   bool stop_audio_request() const;
   RustPresentationPosition get_presentation_position() const;
+  bool is_offload_supported() const;
 
  private:
   const btav_source_interface_t* intf_;
@@ -71,6 +75,11 @@ class AvrcpIntf {
   // interface for Audio server
   void set_volume(int8_t volume);
 
+  // interface for media players, served to remote controllers over the browsing channel
+  void set_now_playing_list(::rust::String current_media_id, ::rust::Vec<AvrcpSongInfo> songs);
+  void set_media_players(uint16_t addressed_player, ::rust::Vec<AvrcpMediaPlayerInfo> players);
+  void set_folder_items(::rust::String media_id, ::rust::Vec<AvrcpListItem> items);
+
  private:
   bluetooth::avrcp::ServiceInterface* intf_;
 };
@@ -19,10 +19,39 @@
 #include <algorithm>
 #include <cstdlib>
 #include <cstring>
+#include <iterator>
 #include <memory>
+#include <vector>
 
+#include "base/bind.h"
+#include "btif/include/btif_common.h"
+#include "btif/include/btif_dm.h"
+#include "btif/include/btif_storage.h"
+#include "device/include/controller.h"
+#include "gd/os/log.h"
+#include "gd/rust/topshim/common/utils.h"
+#include "internal_include/stack_config.h"
+#include "gd/hci/controller.h"
+#include "main/shim/acl_api.h"
+#include "main/shim/entry.h"
+#include "osi/include/allocator.h"
+#include "osi/include/config.h"
 #include "rust/cxx.h"
 #include "src/btif.rs.h"
+#include "stack/btm/btm_sec.h"
+#include "stack/btm/neighbor_inquiry.h"
+#include "stack/include/acl_api.h"
+#include "stack/include/btm_api.h"
+#include "stack/include/bt_hdr.h"
+#include "stack/include/btm_ble_api.h"
+#include "stack/include/btu.h"
+#include "stack/include/gap_api.h"
+#include "stack/include/gattdefs.h"
+#include "stack/include/hcidefs.h"
+#include "stack/include/hcimsgs.h"
+#include "stack/include/l2c_api.h"
+#include "stack/include/l2cdefs.h"
+#include "types/ble_address_with_type.h"
 
 namespace bluetooth {
 namespace topshim {
@@ -60,6 +89,607 @@ std::unique_ptr<InitFlags> ConvertFlags(::rust::Vec<::rust::String> flags) {
   return ret;
 }
 
+void SetGapAppearance(uint16_t appearance) {
+  do_in_main_thread(FROM_HERE, base::BindOnce(
+                                   [](uint16_t icon) {
+                                     tGAP_BLE_ATTR_VALUE value;
+                                     value.icon = icon;
+                                     GAP_BleAttrDBUpdate(GATT_UUID_GAP_ICON, &value);
+                                   },
+                                   appearance));
+}
+
+// Matches BtAddrType::Unknown.
+constexpr uint8_t kAddrTypeUnknown = 0xfe;
+
+uint8_t GetRemoteAddrType(RustRawAddress addr) {
+  tBLE_ADDR_TYPE addr_type = BLE_ADDR_PUBLIC;
+  if (!btif_storage_get_remote_addr_type(CopyFromRustAddress(addr), addr_type)) {
+    return kAddrTypeUnknown;
+  }
+
+  return addr_type;
+}
+
+// Maps the scan type set from Rust to the HCI value.
+static uint8_t ClassicScanType(bool interlaced) {
+  return interlaced ? BTM_SCAN_TYPE_INTERLACED : BTM_SCAN_TYPE_STANDARD;
+}
+
+void SetInquiryScanParams(uint16_t interval, uint16_t window, bool interlaced) {
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](uint16_t interval, uint16_t window, bool interlaced) {
+            btsnd_hcic_write_inqscan_cfg(interval, window);
+            btsnd_hcic_write_inqscan_type(ClassicScanType(interlaced));
+          },
+          interval,
+          window,
+          interlaced));
+}
+
+void SetPageScanParams(uint16_t interval, uint16_t window, bool interlaced) {
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](uint16_t interval, uint16_t window, bool interlaced) {
+            btsnd_hcic_write_pagescan_cfg(interval, window);
+            btsnd_hcic_write_pagescan_type(ClassicScanType(interlaced));
+          },
+          interval,
+          window,
+          interlaced));
+}
+
+void SetLeScanParams(uint16_t interval, uint16_t window, bool active) {
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](uint16_t interval, uint16_t window, bool active) {
+            BTM_BleSetScanParams(
+                {interval},
+                {window},
+                active ? BTM_BLE_SCAN_MODE_ACTI : BTM_BLE_SCAN_MODE_PASS,
+                base::Bind([](uint8_t status) {
+                  if (status != BTM_SUCCESS) {
+                    LOG_WARN("Failed to set the LE scan parameters: %u", status);
+                  }
+                }));
+          },
+          interval,
+          window,
+          active));
+}
+
+RustRemoteVersion GetRemoteVersion(RustRawAddress addr) {
+  RustRemoteVersion version{};
+  version.valid = BTM_ReadRemoteVersion(
+      CopyFromRustAddress(addr),
+      &version.lmp_version,
+      &version.manufacturer,
+      &version.lmp_subversion);
+  return version;
+}
+
+// Copies a features mask read from the stack, which is null when there is none.
+static ::rust::Vec<uint8_t> CopyFeatures(const uint8_t* features) {
+  ::rust::Vec<uint8_t> copy;
+  if (features != nullptr) {
+    for (int i = 0; i < HCI_FEATURE_BYTES_PER_PAGE; ++i) {
+      copy.push_back(features[i]);
+    }
+  }
+
+  return copy;
+}
+
+::rust::Vec<uint8_t> GetRemoteFeatures(RustRawAddress addr) {
+  return CopyFeatures(BTM_ReadRemoteFeatures(CopyFromRustAddress(addr)));
+}
+
+::rust::Vec<uint8_t> GetRemoteLeFeatures(RustRawAddress addr) {
+  return CopyFeatures(BTM_ReadRemoteLeFeatures(CopyFromRustAddress(addr)));
+}
+
+void SetLeAddressPolicy(bool privacy_enabled, RustRawAddress static_address, uint32_t rpa_timeout) {
+  btif_dm_set_le_address_policy(privacy_enabled, CopyFromRustAddress(static_address), rpa_timeout);
+}
+
+uint8_t GetLeOwnAddressType() {
+  return bluetooth::shim::ACL_GetLeOwnAddressType();
+}
+
+bool DisconnectAcl(RustRawAddress addr, uint8_t transport) {
+  uint16_t handle =
+      BTM_GetHCIConnHandle(CopyFromRustAddress(addr), static_cast<tBT_TRANSPORT>(transport));
+  if (handle == HCI_INVALID_HANDLE) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](uint16_t handle) {
+            acl_disconnect_from_handle(handle, HCI_ERR_PEER_USER, "Requested by client");
+          },
+          handle));
+  return true;
+}
+
+uint8_t GetAclRole(RustRawAddress addr) {
+  tHCI_ROLE role = HCI_ROLE_UNKNOWN;
+  BTM_GetRole(CopyFromRustAddress(addr), &role);
+  return role;
+}
+
+bool SwitchAclRole(RustRawAddress addr, uint8_t role) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!BTM_IsAclConnectionUp(address, BT_TRANSPORT_BR_EDR)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, tHCI_ROLE role) {
+            tHCI_ROLE current_role = HCI_ROLE_UNKNOWN;
+            BTM_GetRole(address, &current_role);
+            if (role == HCI_ROLE_CENTRAL) {
+              tBTM_STATUS status = BTM_SwitchRoleToCentral(address);
+              if (status != BTM_SUCCESS && status != BTM_CMD_STARTED) {
+                LOG_WARN("Unable to switch to central: %s", btm_status_text(status).c_str());
+                invoke_acl_role_changed_cb(BT_STATUS_FAIL, address, current_role);
+              }
+            } else if (current_role != HCI_ROLE_PERIPHERAL) {
+              bluetooth::legacy::hci::GetInterface().StartRoleSwitch(address, HCI_ROLE_PERIPHERAL);
+            }
+          },
+          address,
+          to_hci_role(role)));
+  return true;
+}
+
+bool SetSniffModeEnabled(RustRawAddress addr, bool enabled) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!BTM_IsAclConnectionUp(address, BT_TRANSPORT_BR_EDR)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, bool enabled) {
+            if (enabled) {
+              BTM_unblock_sniff_mode_for(address);
+            } else {
+              BTM_block_sniff_mode_for(address);
+            }
+          },
+          address,
+          enabled));
+  return true;
+}
+
+bool SetSniffSubrating(
+    RustRawAddress addr,
+    uint16_t max_latency,
+    uint16_t min_remote_timeout,
+    uint16_t min_local_timeout) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!BTM_IsAclConnectionUp(address, BT_TRANSPORT_BR_EDR)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address,
+             uint16_t max_latency,
+             uint16_t min_remote_timeout,
+             uint16_t min_local_timeout) {
+            tBTM_STATUS status =
+                BTM_SetSsrParams(address, max_latency, min_remote_timeout, min_local_timeout);
+            if (status != BTM_SUCCESS && status != BTM_CMD_STORED) {
+              LOG_WARN(
+                  "Unable to set the sniff subrating parameters: %s",
+                  btm_status_text(status).c_str());
+            }
+          },
+          address,
+          max_latency,
+          min_remote_timeout,
+          min_local_timeout));
+  return true;
+}
+
+bool SetSecureConnectionsOnly(bool enabled) {
+  if (enabled && !controller_get_interface()->supports_secure_connections()) {
+    return false;
+  }
+
+  do_in_main_thread(FROM_HERE, base::BindOnce(&BTM_SetSecureConnectionsOnly, enabled));
+  return true;
+}
+
+void SetMinEncryptionKeySize(uint8_t key_size) {
+  do_in_main_thread(FROM_HERE, base::BindOnce(&BTM_SetMinEncryptionKeySize, key_size));
+}
+
+uint8_t GetEncryptionKeySize(RustRawAddress addr, uint8_t transport) {
+  return BTM_GetEncryptionKeySize(
+      CopyFromRustAddress(addr), static_cast<tBT_TRANSPORT>(transport));
+}
+
+void SetPtsConfig(::rust::Str key, ::rust::Str value) {
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](std::string key, std::string value) {
+            config_t* config = stack_config_get_interface()->get_all();
+            if (config == nullptr) {
+              LOG_WARN("Stack configuration isn't loaded, ignoring %s", key.c_str());
+            } else if (value.empty()) {
+              config_remove_key(config, CONFIG_DEFAULT_SECTION, key);
+            } else {
+              config_set_string(config, CONFIG_DEFAULT_SECTION, key, value);
+            }
+          },
+          std::string(key),
+          std::string(value)));
+}
+
+bool SendAttPdu(RustRawAddress addr, ::rust::Slice<const uint8_t> pdu) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!BTM_IsAclConnectionUp(address, BT_TRANSPORT_LE)) {
+    return false;
+  }
+
+  BT_HDR* p_buf = (BT_HDR*)osi_malloc(sizeof(BT_HDR) + L2CAP_MIN_OFFSET + pdu.size());
+  p_buf->offset = L2CAP_MIN_OFFSET;
+  p_buf->len = pdu.size();
+  std::copy(pdu.begin(), pdu.end(), (uint8_t*)(p_buf + 1) + L2CAP_MIN_OFFSET);
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, BT_HDR* p_buf) {
+            if (L2CA_SendFixedChnlData(L2CAP_ATT_CID, address, p_buf) == L2CAP_DW_FAILED) {
+              LOG_WARN("Unable to send the ATT PDU");
+            }
+          },
+          address,
+          p_buf));
+  return true;
+}
+
+bool SetLinkIdleTimeout(RustRawAddress addr, uint8_t transport, uint16_t timeout) {
+  RawAddress address = CopyFromRustAddress(addr);
+  tBT_TRANSPORT bt_transport = static_cast<tBT_TRANSPORT>(transport);
+  if (!BTM_IsAclConnectionUp(address, bt_transport)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, uint16_t timeout, tBT_TRANSPORT transport) {
+            if (!L2CA_SetIdleTimeoutByBdAddr(address, timeout, transport)) {
+              LOG_WARN("Unable to set the idle timeout of the link");
+            }
+          },
+          address,
+          timeout,
+          bt_transport));
+  return true;
+}
+
+static void VendorCommandComplete(tBTM_VSC_CMPL* p_result) {
+  ::rust::Vec<uint8_t> params;
+  std::copy(
+      p_result->p_param_buf,
+      p_result->p_param_buf + p_result->param_len,
+      std::back_inserter(params));
+  vendor_command_complete_cb(p_result->opcode, std::move(params));
+}
+
+bool SendVendorCommand(uint16_t opcode, ::rust::Slice<const uint8_t> params) {
+  if ((opcode & HCI_GRP_VENDOR_SPECIFIC) != HCI_GRP_VENDOR_SPECIFIC || params.size() > UINT8_MAX) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](uint16_t opcode, std::vector<uint8_t> params) {
+            BTM_VendorSpecificCommand(
+                opcode, params.size(), params.data(), &VendorCommandComplete);
+          },
+          opcode,
+          std::vector<uint8_t>(params.begin(), params.end())));
+  return true;
+}
+
+static void L2capEchoResponse(const RawAddress& bd_addr, uint8_t* p_data, uint16_t data_len) {
+  ::rust::Vec<uint8_t> data;
+  std::copy(p_data, p_data + data_len, std::back_inserter(data));
+  l2cap_echo_response_cb(CopyToRustAddress(bd_addr), std::move(data));
+}
+
+bool SendL2capEcho(RustRawAddress addr, ::rust::Slice<const uint8_t> data) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!BTM_IsAclConnectionUp(address, BT_TRANSPORT_BR_EDR)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, std::vector<uint8_t> data) {
+            if (!L2CA_SendEcho(address, data.data(), data.size(), &L2capEchoResponse)) {
+              LOG_WARN("Unable to send the L2CAP echo request");
+            }
+          },
+          address,
+          std::vector<uint8_t>(data.begin(), data.end())));
+  return true;
+}
+
+static void RemoteNameComplete(void* p_data) {
+  auto* p_name = static_cast<tBTM_REMOTE_DEV_NAME*>(p_data);
+  ::rust::Vec<uint8_t> name;
+  if (p_name->status == BTM_SUCCESS) {
+    uint16_t length = std::min<uint16_t>(p_name->length, BD_NAME_LEN);
+    auto end = std::find(p_name->remote_bd_name, p_name->remote_bd_name + length, '\0');
+    std::copy(p_name->remote_bd_name, end, std::back_inserter(name));
+  }
+  remote_name_cb(
+      CopyToRustAddress(p_name->bd_addr), p_name->status == BTM_SUCCESS, std::move(name));
+}
+
+void ReadRemoteName(RustRawAddress addr) {
+  RawAddress address = CopyFromRustAddress(addr);
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address) {
+            tBTM_STATUS status =
+                BTM_ReadRemoteDeviceName(address, &RemoteNameComplete, BT_TRANSPORT_BR_EDR);
+            if (status != BTM_CMD_STARTED) {
+              LOG_WARN("Unable to request the remote name, status %d", status);
+              remote_name_cb(CopyToRustAddress(address), false, ::rust::Vec<uint8_t>());
+            }
+          },
+          address));
+}
+
+static void LeTxPowerReport(
+    const RawAddress& bd_addr,
+    uint8_t reason,
+    uint8_t phy,
+    int8_t tx_power,
+    uint8_t flags,
+    int8_t delta) {
+  le_tx_power_report_cb(CopyToRustAddress(bd_addr), reason, phy, tx_power, flags, delta);
+}
+
+static void LePathLoss(const RawAddress& bd_addr, uint8_t path_loss, uint8_t zone) {
+  le_path_loss_cb(CopyToRustAddress(bd_addr), path_loss, zone);
+}
+
+// Sends an LE power control command for the link to |address|, only logging its failure.
+static void SendLePowerCommand(
+    const RawAddress& address, uint16_t opcode, std::vector<uint8_t> params, const char* name) {
+  uint16_t handle = BTM_GetHCIConnHandle(address, BT_TRANSPORT_LE);
+  if (handle == HCI_INVALID_HANDLE) {
+    LOG_WARN("Unable to send %s, the link is gone", name);
+    return;
+  }
+  params[0] = handle & 0xff;
+  params[1] = handle >> 8;
+
+  btu_hcif_send_cmd_with_cb(
+      FROM_HERE,
+      opcode,
+      params.data(),
+      params.size(),
+      base::BindOnce(
+          [](const char* name, uint8_t* p, uint16_t len) {
+            if (len < 1 || p[0] != HCI_SUCCESS) {
+              LOG_WARN("%s failed, status 0x%02x", name, len < 1 ? 0xff : p[0]);
+            }
+          },
+          name));
+}
+
+bool ReadLeTxPowerLevels(RustRawAddress addr, uint8_t phy) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!bluetooth::shim::GetController()->SupportsBlePowerControlRequest() ||
+      !BTM_IsAclConnectionUp(address, BT_TRANSPORT_LE)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, uint8_t phy) {
+            uint16_t handle = BTM_GetHCIConnHandle(address, BT_TRANSPORT_LE);
+            if (handle == HCI_INVALID_HANDLE) {
+              le_tx_power_levels_cb(CopyToRustAddress(address), HCI_ERR_NO_CONNECTION, phy, 0, 0);
+              return;
+            }
+
+            uint8_t params[] = {
+                static_cast<uint8_t>(handle & 0xff), static_cast<uint8_t>(handle >> 8), phy};
+            btu_hcif_send_cmd_with_cb(
+                FROM_HERE,
+                HCI_LE_ENH_READ_TRANSMIT_POWER_LEVEL,
+                params,
+                sizeof(params),
+                base::BindOnce(
+                    [](RawAddress address, uint8_t phy, uint8_t* p, uint16_t len) {
+                      // Status, connection handle, PHY, current and max TX power levels.
+                      if (len < 6 || p[0] != HCI_SUCCESS) {
+                        uint8_t status = len < 1 ? HCI_ERR_UNSPECIFIED : p[0];
+                        le_tx_power_levels_cb(CopyToRustAddress(address), status, phy, 0, 0);
+                        return;
+                      }
+                      le_tx_power_levels_cb(
+                          CopyToRustAddress(address),
+                          HCI_SUCCESS,
+                          p[3],
+                          static_cast<int8_t>(p[4]),
+                          static_cast<int8_t>(p[5]));
+                    },
+                    address,
+                    phy));
+          },
+          address,
+          phy));
+  return true;
+}
+
+bool SetLeTxPowerReporting(RustRawAddress addr, bool enable) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!bluetooth::shim::GetController()->SupportsBlePowerControlRequest() ||
+      !BTM_IsAclConnectionUp(address, BT_TRANSPORT_LE)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address, bool enable) {
+            BTM_BleSetPowerReportCallbacks(&LeTxPowerReport, &LePathLoss);
+            // Both the local and the remote TX power changes are reported.
+            std::vector<uint8_t> params = {0, 0, enable, enable};
+            SendLePowerCommand(
+                address,
+                HCI_LE_SET_TRANSMIT_POWER_REPORTING_ENABLE,
+                std::move(params),
+                "LE Set Transmit Power Reporting Enable");
+          },
+          address,
+          enable));
+  return true;
+}
+
+bool SetLePathLossReporting(
+    RustRawAddress addr,
+    bool enable,
+    uint8_t high_threshold,
+    uint8_t high_hysteresis,
+    uint8_t low_threshold,
+    uint8_t low_hysteresis) {
+  RawAddress address = CopyFromRustAddress(addr);
+  if (!bluetooth::shim::GetController()->SupportsBlePathLossMonitoring() ||
+      !BTM_IsAclConnectionUp(address, BT_TRANSPORT_LE)) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](RawAddress address,
+             bool enable,
+             uint8_t high_threshold,
+             uint8_t high_hysteresis,
+             uint8_t low_threshold,
+             uint8_t low_hysteresis) {
+            BTM_BleSetPowerReportCallbacks(&LeTxPowerReport, &LePathLoss);
+            if (enable) {
+              // The path loss has to stay in a new zone for this many connection events before it
+              // is reported, so that a single weak packet doesn't move it.
+              constexpr uint16_t kMinTimeSpent = 8;
+              std::vector<uint8_t> params = {
+                  0,
+                  0,
+                  high_threshold,
+                  high_hysteresis,
+                  low_threshold,
+                  low_hysteresis,
+                  kMinTimeSpent & 0xff,
+                  kMinTimeSpent >> 8};
+              SendLePowerCommand(
+                  address,
+                  HCI_LE_SET_PATH_LOSS_REPORTING_PARAMS,
+                  std::move(params),
+                  "LE Set Path Loss Reporting Parameters");
+            }
+            SendLePowerCommand(
+                address,
+                HCI_LE_SET_PATH_LOSS_REPORTING_ENABLE,
+                {0, 0, enable},
+                "LE Set Path Loss Reporting Enable");
+          },
+          address,
+          enable,
+          high_threshold,
+          high_hysteresis,
+          low_threshold,
+          low_hysteresis));
+  return true;
+}
+
+// Sends a channel classification command, only logging its failure.
+static void SendChannelClassification(uint16_t opcode, std::vector<uint8_t> map, const char* name) {
+  btu_hcif_send_cmd_with_cb(
+      FROM_HERE,
+      opcode,
+      map.data(),
+      map.size(),
+      base::BindOnce(
+          [](const char* name, uint8_t* p, uint16_t len) {
+            if (len < 1 || p[0] != HCI_SUCCESS) {
+              LOG_WARN("%s failed, status 0x%02x", name, len < 1 ? 0xff : p[0]);
+            }
+          },
+          name));
+}
+
+bool SetHostChannelClassification(
+    ::rust::Slice<const uint8_t> bredr_map, ::rust::Slice<const uint8_t> le_map) {
+  constexpr size_t kBredrMapSize = 10;
+  constexpr size_t kLeMapSize = 5;
+  auto controller = bluetooth::shim::GetController();
+  if (controller == nullptr || bredr_map.size() != kBredrMapSize || le_map.size() != kLeMapSize) {
+    return false;
+  }
+  bool set_bredr =
+      controller->IsSupported(bluetooth::hci::OpCode::SET_AFH_HOST_CHANNEL_CLASSIFICATION);
+  bool set_le =
+      controller->IsSupported(bluetooth::hci::OpCode::LE_SET_HOST_CHANNEL_CLASSIFICATION);
+  if (!set_bredr && !set_le) {
+    return false;
+  }
+
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          [](bool set_bredr,
+             bool set_le,
+             std::vector<uint8_t> bredr_map,
+             std::vector<uint8_t> le_map) {
+            if (set_bredr) {
+              SendChannelClassification(
+                  HCI_SET_AFH_CHANNELS,
+                  std::move(bredr_map),
+                  "Set AFH Host Channel Classification");
+            }
+            if (set_le) {
+              SendChannelClassification(
+                  HCI_BLE_SET_HOST_CHNL_CLASS,
+                  std::move(le_map),
+                  "LE Set Host Channel Classification");
+            }
+          },
+          set_bredr,
+          set_le,
+          std::vector<uint8_t>(bredr_map.begin(), bredr_map.end()),
+          std::vector<uint8_t>(le_map.begin(), le_map.end())));
+  return true;
+}
+
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
@@ -16,6 +16,7 @@
 #ifndef GD_RUST_TOPSHIM_BTIF_BTIF_SHIM_H
 #define GD_RUST_TOPSHIM_BTIF_BTIF_SHIM_H
 
+#include <cstdint>
 #include <memory>
 
 #include "rust/cxx.h"
@@ -24,6 +25,9 @@ namespace bluetooth {
 namespace topshim {
 namespace rust {
 
+struct RustRawAddress;
+struct RustRemoteVersion;
+
 class InitFlags {
  public:
   InitFlags();
@@ -40,6 +44,119 @@ class InitFlags {
 
 std::unique_ptr<InitFlags> ConvertFlags(::rust::Vec<::rust::String> flags);
 
+// Sets the appearance characteristic of the GAP service hosted by the stack.
+void SetGapAppearance(uint16_t appearance);
+
+// Gets the LE address type stored for a remote device, or 0xfe if it is unknown.
+uint8_t GetRemoteAddrType(RustRawAddress addr);
+
+// Writes the inquiry and page scan activity and type of the controller. The interval and window
+// are in slots of 0.625 ms.
+void SetInquiryScanParams(uint16_t interval, uint16_t window, bool interlaced);
+void SetPageScanParams(uint16_t interval, uint16_t window, bool interlaced);
+
+// Sets the parameters of the LE scan run by discovery, in slots of 0.625 ms.
+void SetLeScanParams(uint16_t interval, uint16_t window, bool active);
+
+// Read what a connected remote device reported about itself. The version is marked invalid and
+// the features are empty if there is no link or the values weren't read yet.
+RustRemoteVersion GetRemoteVersion(RustRawAddress addr);
+::rust::Vec<uint8_t> GetRemoteFeatures(RustRawAddress addr);
+::rust::Vec<uint8_t> GetRemoteLeFeatures(RustRawAddress addr);
+
+// Chooses the LE address used once the stack is enabled: a resolvable private address rotated
+// every |rpa_timeout| seconds (0 for the default) with privacy, otherwise the static random
+// |static_address| or, if it is empty, the public address. Must be called before enabling.
+void SetLeAddressPolicy(bool privacy_enabled, RustRawAddress static_address, uint32_t rpa_timeout);
+
+// Gets the type of the address currently used on LE, public (0) or random (1).
+uint8_t GetLeOwnAddressType();
+
+// Disconnects the ACL link to a remote device on |transport|. Returns false if there is no such
+// link.
+bool DisconnectAcl(RustRawAddress addr, uint8_t transport);
+
+// Gets the local role on the BR/EDR link to a remote device, or 0xff if there is no such link.
+uint8_t GetAclRole(RustRawAddress addr);
+
+// Starts switching the local role on the BR/EDR link to |role|. The result is reported by the
+// ACL role changed callback. Returns false if there is no such link.
+bool SwitchAclRole(RustRawAddress addr, uint8_t role);
+
+// Change the link policy of the BR/EDR link to a remote device. Return false if there is no such
+// link.
+bool SetSniffModeEnabled(RustRawAddress addr, bool enabled);
+bool SetSniffSubrating(
+    RustRawAddress addr,
+    uint16_t max_latency,
+    uint16_t min_remote_timeout,
+    uint16_t min_local_timeout);
+
+// Requires Secure Connections for all new pairings on both transports, rejecting legacy pairing.
+// Returns false if it is being enabled but the controller doesn't support Secure Connections.
+bool SetSecureConnectionsOnly(bool enabled);
+
+// Sets the shortest encryption key, in octets, accepted on new links and LE pairings. Shorter
+// keys are reported by the key size violation callback.
+void SetMinEncryptionKeySize(uint8_t key_size);
+
+// Gets the size of the key encrypting the link to |addr| on |transport|, or 0 if the link isn't
+// encrypted.
+uint8_t GetEncryptionKeySize(RustRawAddress addr, uint8_t transport);
+
+// Sets |key| of the stack configuration, which holds the PTS test modes of libbluetooth, or
+// removes it if |value| is empty. The stack reads most of these modes when they apply, so the
+// changes take effect without restarting it.
+void SetPtsConfig(::rust::Str key, ::rust::Str value);
+
+// Sends |pdu| over the ATT channel of the LE link to |addr| as is, bypassing GATT. Responses are
+// handled by GATT as usual. Returns false if there is no such link.
+bool SendAttPdu(RustRawAddress addr, ::rust::Slice<const uint8_t> pdu);
+
+// Sets how long, in seconds, the link to |addr| on |transport| stays up with no L2CAP channels
+// open. 0xffff keeps it up until either side disconnects. Returns false if there is no such link.
+bool SetLinkIdleTimeout(RustRawAddress addr, uint8_t transport, uint16_t timeout);
+
+// Sends a vendor specific HCI command to the controller. The return parameters of its command
+// complete event are passed to vendor_command_complete_cb. Returns false if |opcode| isn't in the
+// vendor specific group or |params| don't fit in a command.
+bool SendVendorCommand(uint16_t opcode, ::rust::Slice<const uint8_t> params);
+
+// Sends an L2CAP echo request carrying |data| over the BR/EDR link to |addr|. The data echoed back
+// is passed to l2cap_echo_response_cb, unless another request was sent since. Returns false if
+// there is no such link.
+bool SendL2capEcho(RustRawAddress addr, ::rust::Slice<const uint8_t> data);
+
+// Asks the BR/EDR device at |addr| for its name, paging it if there is no link. The outcome is
+// passed to remote_name_cb, also when the request couldn't be sent because another one is in
+// progress.
+void ReadRemoteName(RustRawAddress addr);
+
+// LE Power Control of the link to |addr|. Each returns false if the controller doesn't support the
+// feature or there is no such link.
+//
+// Reads the current and maximum TX power levels on |phy|, which are passed to
+// le_tx_power_levels_cb.
+bool ReadLeTxPowerLevels(RustRawAddress addr, uint8_t phy);
+// Turns the reports of local and remote TX power changes, passed to le_tx_power_report_cb, on or
+// off.
+bool SetLeTxPowerReporting(RustRawAddress addr, bool enable);
+// Turns the reports of the path loss moving into another zone, passed to le_path_loss_cb, on or
+// off. The zones are split by the thresholds, in dB.
+bool SetLePathLossReporting(
+    RustRawAddress addr,
+    bool enable,
+    uint8_t high_threshold,
+    uint8_t high_hysteresis,
+    uint8_t low_threshold,
+    uint8_t low_hysteresis);
+
+// Tells the controller which channels the host knows to be bad, with a bit for each BR/EDR channel
+// in |bredr_map| (10 octets) and each LE data channel in |le_map| (5 octets), cleared when it is
+// bad. Returns false if the maps are the wrong size or the controller takes neither of them.
+bool SetHostChannelClassification(
+    ::rust::Slice<const uint8_t> bredr_map, ::rust::Slice<const uint8_t> le_map);
+
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
@@ -44,6 +44,43 @@ RustRawAddress ControllerIntf::read_local_addr() const {
   return CopyToRustAddress(*controller_->get_address());
 }
 
+uint16_t ControllerIntf::get_acl_buffer_count_classic() const {
+  if (!controller_) std::abort();
+  return controller_->get_acl_buffer_count_classic();
+}
+
+uint8_t ControllerIntf::get_acl_buffer_count_ble() const {
+  if (!controller_) std::abort();
+  return controller_->get_acl_buffer_count_ble();
+}
+
+uint8_t ControllerIntf::get_ble_acceptlist_size() const {
+  if (!controller_) std::abort();
+  return controller_->get_ble_acceptlist_size();
+}
+
+bool ControllerIntf::supports_ble_privacy() const {
+  if (!controller_) std::abort();
+  return controller_->supports_ble_privacy();
+}
+
+uint8_t ControllerIntf::get_ble_resolving_list_max_size() const {
+  if (!controller_) std::abort();
+  return controller_->get_ble_resolving_list_max_size();
+}
+
+RustLocalVersion ControllerIntf::get_local_version() const {
+  if (!controller_) std::abort();
+  const bt_version_t* version = controller_->get_bt_version();
+  RustLocalVersion local{};
+  local.hci_version = version->hci_version;
+  local.hci_revision = version->hci_revision;
+  local.lmp_version = version->lmp_version;
+  local.manufacturer = version->manufacturer;
+  local.lmp_subversion = version->lmp_subversion;
+  return local;
+}
+
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
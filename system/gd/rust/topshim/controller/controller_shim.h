@@ -27,6 +27,7 @@ namespace topshim {
 namespace rust {
 
 struct RustRawAddress;
+struct RustLocalVersion;
 
 class ControllerIntf {
  public:
@@ -34,6 +35,12 @@ class ControllerIntf {
   ~ControllerIntf();
 
   RustRawAddress read_local_addr() const;
+  uint16_t get_acl_buffer_count_classic() const;
+  uint8_t get_acl_buffer_count_ble() const;
+  uint8_t get_ble_acceptlist_size() const;
+  bool supports_ble_privacy() const;
+  uint8_t get_ble_resolving_list_max_size() const;
+  RustLocalVersion get_local_version() const;
 
  private:
   const controller_t* controller_;
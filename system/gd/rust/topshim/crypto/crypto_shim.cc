@@ -0,0 +1,208 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#include "gd/rust/topshim/crypto/crypto_shim.h"
+
+#include <openssl/aead.h>
+#include <openssl/aes.h>
+#include <openssl/bn.h>
+#include <openssl/digest.h>
+#include <openssl/ec.h>
+#include <openssl/ecdh.h>
+#include <openssl/evp.h>
+#include <openssl/obj_mac.h>
+#include <openssl/rand.h>
+#include <openssl/sha.h>
+
+#include <algorithm>
+#include <memory>
+
+#include "rust/cxx.h"
+#include "src/crypto.rs.h"
+
+namespace bluetooth {
+namespace topshim {
+namespace rust {
+
+namespace internal {
+
+constexpr size_t kAes128KeyLen = 16;
+constexpr size_t kAes256KeyLen = 32;
+constexpr size_t kGcmNonceLen = 12;
+constexpr size_t kP256PrivateKeyLen = 32;
+constexpr size_t kP256PublicKeyLen = 64;
+
+::rust::Vec<uint8_t> ToVec(const uint8_t* data, size_t len) {
+  ::rust::Vec<uint8_t> vec;
+  vec.reserve(len);
+  for (size_t i = 0; i < len; ++i) {
+    vec.push_back(data[i]);
+  }
+  return vec;
+}
+
+}  // namespace internal
+
+::rust::Vec<uint8_t> Sha256(::rust::Slice<const uint8_t> data) {
+  uint8_t digest[SHA256_DIGEST_LENGTH];
+  SHA256(data.data(), data.size(), digest);
+  return internal::ToVec(digest, sizeof(digest));
+}
+
+::rust::Vec<uint8_t> Aes128Encrypt(::rust::Slice<const uint8_t> key, ::rust::Slice<const uint8_t> block) {
+  if (key.size() != internal::kAes128KeyLen || block.size() != AES_BLOCK_SIZE) return {};
+
+  AES_KEY aes_key;
+  if (AES_set_encrypt_key(key.data(), 128, &aes_key) != 0) return {};
+
+  uint8_t out[AES_BLOCK_SIZE];
+  AES_encrypt(block.data(), out, &aes_key);
+  return internal::ToVec(out, sizeof(out));
+}
+
+::rust::Vec<uint8_t> Aes128Decrypt(::rust::Slice<const uint8_t> key, ::rust::Slice<const uint8_t> block) {
+  if (key.size() != internal::kAes128KeyLen || block.size() != AES_BLOCK_SIZE) return {};
+
+  AES_KEY aes_key;
+  if (AES_set_decrypt_key(key.data(), 128, &aes_key) != 0) return {};
+
+  uint8_t out[AES_BLOCK_SIZE];
+  AES_decrypt(block.data(), out, &aes_key);
+  return internal::ToVec(out, sizeof(out));
+}
+
+::rust::Vec<uint8_t> EcdhP256(::rust::Slice<const uint8_t> private_key, ::rust::Slice<const uint8_t> public_key) {
+  if (private_key.size() != internal::kP256PrivateKeyLen || public_key.size() != internal::kP256PublicKeyLen) {
+    return {};
+  }
+
+  std::unique_ptr<EC_KEY, decltype(&EC_KEY_free)> key(EC_KEY_new_by_curve_name(NID_X9_62_prime256v1), EC_KEY_free);
+  std::unique_ptr<BIGNUM, decltype(&BN_free)> priv(
+      BN_bin2bn(private_key.data(), private_key.size(), nullptr), BN_free);
+  if (!key || !priv || !EC_KEY_set_private_key(key.get(), priv.get())) return {};
+
+  // The public key is given as the X and Y coordinates, which is the uncompressed point format
+  // without its prefix. Decoding it also checks that the point is on the curve.
+  uint8_t point[1 + internal::kP256PublicKeyLen] = {POINT_CONVERSION_UNCOMPRESSED};
+  std::copy(public_key.begin(), public_key.end(), point + 1);
+
+  const EC_GROUP* group = EC_KEY_get0_group(key.get());
+  std::unique_ptr<EC_POINT, decltype(&EC_POINT_free)> peer(EC_POINT_new(group), EC_POINT_free);
+  if (!peer || !EC_POINT_oct2point(group, peer.get(), point, sizeof(point), nullptr)) return {};
+
+  uint8_t secret[internal::kP256PrivateKeyLen];
+  if (ECDH_compute_key(secret, sizeof(secret), peer.get(), key.get(), nullptr) != sizeof(secret)) return {};
+
+  return internal::ToVec(secret, sizeof(secret));
+}
+
+::rust::Vec<uint8_t> RandomBytes(size_t len) {
+  std::unique_ptr<uint8_t[]> buf(new uint8_t[len]);
+  if (RAND_bytes(buf.get(), len) != 1) return {};
+
+  return internal::ToVec(buf.get(), len);
+}
+
+::rust::Vec<uint8_t> Pbkdf2HmacSha256(
+    ::rust::Slice<const uint8_t> password, ::rust::Slice<const uint8_t> salt, uint32_t iterations, size_t len) {
+  std::unique_ptr<uint8_t[]> key(new uint8_t[len]);
+  if (!PKCS5_PBKDF2_HMAC(
+          reinterpret_cast<const char*>(password.data()),
+          password.size(),
+          salt.data(),
+          salt.size(),
+          iterations,
+          EVP_sha256(),
+          len,
+          key.get())) {
+    return {};
+  }
+
+  return internal::ToVec(key.get(), len);
+}
+
+::rust::Vec<uint8_t> Aes256GcmSeal(
+    ::rust::Slice<const uint8_t> key,
+    ::rust::Slice<const uint8_t> nonce,
+    ::rust::Slice<const uint8_t> aad,
+    ::rust::Slice<const uint8_t> plaintext) {
+  if (key.size() != internal::kAes256KeyLen || nonce.size() != internal::kGcmNonceLen) return {};
+
+  const EVP_AEAD* aead = EVP_aead_aes_256_gcm();
+  bssl::ScopedEVP_AEAD_CTX ctx;
+  if (!EVP_AEAD_CTX_init(ctx.get(), aead, key.data(), key.size(), EVP_AEAD_DEFAULT_TAG_LENGTH, nullptr)) {
+    return {};
+  }
+
+  // The tag is appended to the ciphertext.
+  size_t max_len = plaintext.size() + EVP_AEAD_max_overhead(aead);
+  std::unique_ptr<uint8_t[]> out(new uint8_t[max_len]);
+  size_t out_len = 0;
+  if (!EVP_AEAD_CTX_seal(
+          ctx.get(),
+          out.get(),
+          &out_len,
+          max_len,
+          nonce.data(),
+          nonce.size(),
+          plaintext.data(),
+          plaintext.size(),
+          aad.data(),
+          aad.size())) {
+    return {};
+  }
+
+  return internal::ToVec(out.get(), out_len);
+}
+
+bool Aes256GcmOpen(
+    ::rust::Slice<const uint8_t> key,
+    ::rust::Slice<const uint8_t> nonce,
+    ::rust::Slice<const uint8_t> aad,
+    ::rust::Slice<const uint8_t> sealed,
+    ::rust::Vec<uint8_t>& plaintext) {
+  if (key.size() != internal::kAes256KeyLen || nonce.size() != internal::kGcmNonceLen) return false;
+
+  bssl::ScopedEVP_AEAD_CTX ctx;
+  if (!EVP_AEAD_CTX_init(
+          ctx.get(), EVP_aead_aes_256_gcm(), key.data(), key.size(), EVP_AEAD_DEFAULT_TAG_LENGTH, nullptr)) {
+    return false;
+  }
+
+  // Fails if the tag doesn't match, i.e. the key is wrong or the data was tampered with.
+  std::unique_ptr<uint8_t[]> out(new uint8_t[sealed.size()]);
+  size_t out_len = 0;
+  if (!EVP_AEAD_CTX_open(
+          ctx.get(),
+          out.get(),
+          &out_len,
+          sealed.size(),
+          nonce.data(),
+          nonce.size(),
+          sealed.data(),
+          sealed.size(),
+          aad.data(),
+          aad.size())) {
+    return false;
+  }
+
+  plaintext = internal::ToVec(out.get(), out_len);
+  return true;
+}
+
+}  // namespace rust
+}  // namespace topshim
+}  // namespace bluetooth
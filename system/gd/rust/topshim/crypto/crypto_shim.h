@@ -0,0 +1,54 @@
+/*
+ * Copyright (C) 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+#ifndef GD_RUST_TOPSHIM_BTIF_BTIF_SHIM_H
+#ifndef GD_RUST_TOPSHIM_CRYPTO_CRYPTO_SHIM_H
+#define GD_RUST_TOPSHIM_CRYPTO_CRYPTO_SHIM_H
+
+#include <cstddef>
+#include <cstdint>
+
+#include "rust/cxx.h"
+
+namespace bluetooth {
+namespace topshim {
+namespace rust {
+
+// Thin wrappers around libcrypto for protocols that the Rust stack runs itself. Inputs are
+// length checked on the Rust side, failures are reported with an empty result.
+::rust::Vec<uint8_t> Sha256(::rust::Slice<const uint8_t> data);
+::rust::Vec<uint8_t> Aes128Encrypt(::rust::Slice<const uint8_t> key, ::rust::Slice<const uint8_t> block);
+::rust::Vec<uint8_t> Aes128Decrypt(::rust::Slice<const uint8_t> key, ::rust::Slice<const uint8_t> block);
+::rust::Vec<uint8_t> EcdhP256(::rust::Slice<const uint8_t> private_key, ::rust::Slice<const uint8_t> public_key);
+::rust::Vec<uint8_t> RandomBytes(size_t len);
+::rust::Vec<uint8_t> Pbkdf2HmacSha256(
+    ::rust::Slice<const uint8_t> password, ::rust::Slice<const uint8_t> salt, uint32_t iterations, size_t len);
+::rust::Vec<uint8_t> Aes256GcmSeal(
+    ::rust::Slice<const uint8_t> key,
+    ::rust::Slice<const uint8_t> nonce,
+    ::rust::Slice<const uint8_t> aad,
+    ::rust::Slice<const uint8_t> plaintext);
+bool Aes256GcmOpen(
+    ::rust::Slice<const uint8_t> key,
+    ::rust::Slice<const uint8_t> nonce,
+    ::rust::Slice<const uint8_t> aad,
+    ::rust::Slice<const uint8_t> sealed,
+    ::rust::Vec<uint8_t>& plaintext);
+
+}  // namespace rust
+}  // namespace topshim
+}  // namespace bluetooth
+
+#endif  // GD_RUST_TOPSHIM_CRYPTO_CRYPTO_SHIM_H
@@ -0,0 +1,142 @@
+/*
+ * Copyright 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#include "gd/rust/topshim/hearing_aid/hearing_aid_shim.h"
+
+#include "gd/os/log.h"
+#include "gd/rust/topshim/common/utils.h"
+#include "include/hardware/bluetooth.h"
+#include "src/profiles/hearing_aid.rs.h"
+#include "types/raw_address.h"
+
+namespace rusty = ::bluetooth::topshim::rust;
+
+namespace bluetooth {
+namespace topshim {
+namespace rust {
+namespace internal {
+static HearingAidIntf* g_hearing_aid_if;
+
+static void connection_state_cb(hearing_aid::ConnectionState state, const RawAddress& addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(addr);
+  rusty::hearing_aid_connection_state_callback(static_cast<uint32_t>(state), raddr);
+}
+
+static void device_available_cb(uint8_t capabilities, uint64_t hi_sync_id, const RawAddress& addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(addr);
+  rusty::hearing_aid_device_available_callback(capabilities, hi_sync_id, raddr);
+}
+
+}  // namespace internal
+
+class DBusHearingAidCallbacks : public hearing_aid::HearingAidCallbacks {
+ public:
+  static HearingAidCallbacks* GetInstance() {
+    static HearingAidCallbacks* instance = new DBusHearingAidCallbacks();
+    return instance;
+  }
+
+  DBusHearingAidCallbacks(){};
+
+  // hearing_aid::HearingAidCallbacks
+  void OnConnectionState(hearing_aid::ConnectionState state, const RawAddress& address) override {
+    LOG_INFO("OnConnectionState %u from %s", static_cast<uint32_t>(state), address.ToString().c_str());
+    topshim::rust::internal::connection_state_cb(state, address);
+  }
+
+  void OnDeviceAvailable(uint8_t capabilities, uint64_t hi_sync_id, const RawAddress& address) override {
+    LOG_INFO("OnDeviceAvailable from %s", address.ToString().c_str());
+    topshim::rust::internal::device_available_cb(capabilities, hi_sync_id, address);
+  }
+};
+
+void HearingAidIntf::init() {
+  intf_->Init(DBusHearingAidCallbacks::GetInstance());
+}
+
+void HearingAidIntf::connect(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  intf_->Connect(addr);
+}
+
+void HearingAidIntf::disconnect(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  intf_->Disconnect(addr);
+}
+
+void HearingAidIntf::add_to_accept_list(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  intf_->AddToAcceptlist(addr);
+}
+
+void HearingAidIntf::set_volume(int8_t volume) {
+  intf_->SetVolume(volume);
+}
+
+void HearingAidIntf::remove_device(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  intf_->RemoveDevice(addr);
+}
+
+void HearingAidIntf::cleanup() {
+  intf_->Cleanup();
+}
+
+bool HearingAidIntf::start_audio_request() {
+  return bluetooth::audio::hearing_aid::StartRequest();
+}
+
+bool HearingAidIntf::stop_audio_request() {
+  return bluetooth::audio::hearing_aid::StopRequest();
+}
+
+RustHearingAidAudioConfig HearingAidIntf::get_audio_config() const {
+  bluetooth::audio::hearing_aid::AudioConfig c = bluetooth::audio::hearing_aid::GetAudioConfig();
+  RustHearingAidAudioConfig rconfig = {
+      .sample_rate = c.sample_rate,
+      .bits_per_sample = c.bits_per_sample,
+      .channel_count = c.channel_count,
+  };
+  return rconfig;
+}
+
+RustPresentationPosition HearingAidIntf::get_presentation_position() const {
+  bluetooth::audio::hearing_aid::PresentationPosition p = bluetooth::audio::hearing_aid::GetPresentationPosition();
+  RustPresentationPosition rposition = {
+      .remote_delay_report_ns = p.remote_delay_report_ns,
+      .total_bytes_read = p.total_bytes_read,
+      .data_position_sec = p.data_position.tv_sec,
+      .data_position_nsec = static_cast<int32_t>(p.data_position.tv_nsec),
+  };
+  return rposition;
+}
+
+std::unique_ptr<HearingAidIntf> GetHearingAidProfile(const unsigned char* btif) {
+  if (internal::g_hearing_aid_if) std::abort();
+
+  const bt_interface_t* btif_ = reinterpret_cast<const bt_interface_t*>(btif);
+
+  auto hearing_aid_if = std::make_unique<HearingAidIntf>(
+      const_cast<hearing_aid::HearingAidInterface*>(reinterpret_cast<const hearing_aid::HearingAidInterface*>(
+          btif_->get_profile_interface(BT_PROFILE_HEARING_AID_ID))));
+  internal::g_hearing_aid_if = hearing_aid_if.get();
+
+  return hearing_aid_if;
+}
+
+}  // namespace rust
+}  // namespace topshim
+}  // namespace bluetooth
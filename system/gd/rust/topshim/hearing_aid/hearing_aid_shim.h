@@ -0,0 +1,59 @@
+/*
+ * Copyright 2022 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#pragma once
+
+#include <memory>
+
+#include "audio_hal_interface/hearing_aid_software_encoding_host.h"
+#include "include/hardware/bt_hearing_aid.h"
+#include "types/raw_address.h"
+
+namespace bluetooth {
+namespace topshim {
+namespace rust {
+
+struct RustHearingAidAudioConfig;
+struct RustPresentationPosition;
+struct RustRawAddress;
+
+class HearingAidIntf {
+ public:
+  HearingAidIntf(hearing_aid::HearingAidInterface* intf) : intf_(intf){};
+
+  void init();
+  void connect(RustRawAddress bt_addr);
+  void disconnect(RustRawAddress bt_addr);
+  void add_to_accept_list(RustRawAddress bt_addr);
+  void set_volume(int8_t volume);
+  void remove_device(RustRawAddress bt_addr);
+  void cleanup();
+
+  // Audio data path
+  bool start_audio_request();
+  bool stop_audio_request();
+  RustHearingAidAudioConfig get_audio_config() const;
+  RustPresentationPosition get_presentation_position() const;
+
+ private:
+  hearing_aid::HearingAidInterface* intf_;
+};
+
+std::unique_ptr<HearingAidIntf> GetHearingAidProfile(const unsigned char* btif);
+
+}  // namespace rust
+}  // namespace topshim
+}  // namespace bluetooth
@@ -21,6 +21,7 @@
 #include "gd/rust/topshim/common/utils.h"
 #include "include/hardware/bt_hf.h"
 #include "src/profiles/hfp.rs.h"
+#include "stack/include/btm_api.h"
 #include "types/raw_address.h"
 
 namespace rusty = ::bluetooth::topshim::rust;
@@ -41,6 +42,42 @@ static void audio_state_cb(bluetooth::headset::bthf_audio_state_t state, RawAddr
   rusty::hfp_audio_state_callback(state, raddr);
 }
 
+// Codec ids as assigned by the HFP specification.
+static constexpr uint32_t kCodecIdNone = 0;
+static constexpr uint32_t kCodecIdCvsd = 1;
+static constexpr uint32_t kCodecIdMsbc = 2;
+
+static void codec_cb(bluetooth::headset::bthf_wbs_config_t wbs, RawAddress* addr) {
+  uint32_t codec = kCodecIdNone;
+  if (wbs == headset::BTHF_WBS_YES) {
+    codec = kCodecIdMsbc;
+  } else if (wbs == headset::BTHF_WBS_NO) {
+    codec = kCodecIdCvsd;
+  }
+  RustRawAddress raddr = rusty::CopyToRustAddress(*addr);
+  rusty::hfp_codec_callback(codec, raddr);
+}
+
+static void voice_recognition_cb(bluetooth::headset::bthf_vr_state_t state, RawAddress* addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(*addr);
+  rusty::hfp_voice_recognition_callback(state == headset::BTHF_VR_STATE_STARTED, raddr);
+}
+
+static void indicators_query_cb(RawAddress* addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(*addr);
+  rusty::hfp_indicators_query_callback(raddr);
+}
+
+static void operator_query_cb(RawAddress* addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(*addr);
+  rusty::hfp_operator_query_callback(raddr);
+}
+
+static void current_calls_query_cb(RawAddress* addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(*addr);
+  rusty::hfp_current_calls_query_callback(raddr);
+}
+
 }  // namespace internal
 
 class DBusHeadsetCallbacks : public headset::Callbacks {
@@ -50,9 +87,7 @@ class DBusHeadsetCallbacks : public headset::Callbacks {
     return instance;
   }
 
-  DBusHeadsetCallbacks(headset::Interface* headset) : headset_(headset) {
-    call_status = 0;
-  };
+  DBusHeadsetCallbacks(headset::Interface* headset) : headset_(headset){};
 
   // headset::Callbacks
   void ConnectionStateCallback(headset::bthf_connection_state_t state, RawAddress* bd_addr) override {
@@ -64,25 +99,19 @@ class DBusHeadsetCallbacks : public headset::Callbacks {
     LOG_INFO("AudioStateCallback %u from %s", state, bd_addr->ToString().c_str());
     topshim::rust::internal::audio_state_cb(state, bd_addr);
 
-    switch (state) {
-      case headset::bthf_audio_state_t::BTHF_AUDIO_STATE_CONNECTED:
-        SetCallStatus(1, bd_addr);
-        // This triggers a +VGS command to set the speaker volume for HFP
-        // devices.
-        // TODO(b/215089433): Add a set volume API and have client to handle the
-        // set volume when start.
-        headset_->VolumeControl(headset::bthf_volume_type_t::BTHF_VOLUME_TYPE_SPK, 5, bd_addr);
-        return;
-      case headset::bthf_audio_state_t::BTHF_AUDIO_STATE_DISCONNECTED:
-        SetCallStatus(0, bd_addr);
-        return;
-      default:
-        return;
+    if (state == headset::bthf_audio_state_t::BTHF_AUDIO_STATE_CONNECTED) {
+      // This triggers a +VGS command to set the speaker volume for HFP
+      // devices.
+      // TODO(b/215089433): Add a set volume API and have client to handle the
+      // set volume when start.
+      headset_->VolumeControl(headset::bthf_volume_type_t::BTHF_VOLUME_TYPE_SPK, 5, bd_addr);
     }
   }
 
-  void VoiceRecognitionCallback(
-      [[maybe_unused]] headset::bthf_vr_state_t state, [[maybe_unused]] RawAddress* bd_addr) override {}
+  void VoiceRecognitionCallback(headset::bthf_vr_state_t state, RawAddress* bd_addr) override {
+    LOG_INFO("VoiceRecognitionCallback %u from %s", state, bd_addr->ToString().c_str());
+    topshim::rust::internal::voice_recognition_cb(state, bd_addr);
+  }
 
   void AnswerCallCallback([[maybe_unused]] RawAddress* bd_addr) override {}
 
@@ -100,47 +129,29 @@ class DBusHeadsetCallbacks : public headset::Callbacks {
   void NoiseReductionCallback(
       [[maybe_unused]] headset::bthf_nrec_t nrec, [[maybe_unused]] RawAddress* bd_addr) override {}
 
-  void WbsCallback([[maybe_unused]] headset::bthf_wbs_config_t wbs, [[maybe_unused]] RawAddress* bd_addr) override {}
+  void WbsCallback(headset::bthf_wbs_config_t wbs, RawAddress* bd_addr) override {
+    LOG_INFO("WbsCallback %u from %s", wbs, bd_addr->ToString().c_str());
+    topshim::rust::internal::codec_cb(wbs, bd_addr);
+  }
 
   void AtChldCallback([[maybe_unused]] headset::bthf_chld_type_t chld, [[maybe_unused]] RawAddress* bd_addr) override {}
 
   void AtCnumCallback([[maybe_unused]] RawAddress* bd_addr) override {}
 
+  // The stack answers the queries with the state of the calls and the network.
   void AtCindCallback(RawAddress* bd_addr) override {
-    // This is required to setup the SLC, the format of the response should be
-    // +CIND: <call>,<callsetup>,<service>,<signal>,<roam>,<battery>,<callheld>
-    LOG_WARN("Respond +CIND: 0,0,1,5,0,5,0 to AT+CIND? from %s", bd_addr->ToString().c_str());
-
-    // headset::Interface::CindResponse's parameters are similar but different
-    // from the actual CIND response. It will construct the final response for
-    // you based on the arguments you provide.
-    // CindResponse(network_service_availability, active_call_num,
-    //              held_call_num, callsetup_state, signal_strength,
-    //              roam_state, battery_level, bd_addr);
-    headset_->CindResponse(1, 0, 0, headset::BTHF_CALL_STATE_IDLE, 5, 0, 5, bd_addr);
+    LOG_INFO("AT+CIND? from %s", bd_addr->ToString().c_str());
+    topshim::rust::internal::indicators_query_cb(bd_addr);
   }
 
   void AtCopsCallback(RawAddress* bd_addr) override {
-    LOG_WARN("Respond +COPS: 0 to AT+COPS? from %s", bd_addr->ToString().c_str());
-    headset_->CopsResponse("", bd_addr);
+    LOG_INFO("AT+COPS? from %s", bd_addr->ToString().c_str());
+    topshim::rust::internal::operator_query_cb(bd_addr);
   }
 
   void AtClccCallback(RawAddress* bd_addr) override {
-    // Reply +CLCC:<idx>,<dir>,<status>,<mode>,<mprty>[,<number>,<type>] if
-    // there is an active audio connection. Simply rely OK otherwise.
-    // This is required for some headsets to start to send actual data to AG.
-    if (call_status)
-      headset_->ClccResponse(
-          /*index=*/1,
-          /*dir=*/headset::BTHF_CALL_DIRECTION_OUTGOING,
-          /*state=*/headset::BTHF_CALL_STATE_ACTIVE,
-          /*mode=*/headset::BTHF_CALL_TYPE_VOICE,
-          /*multi_party=*/headset::BTHF_CALL_MPTY_TYPE_SINGLE,
-          /*number=*/"",
-          /*type=*/headset::BTHF_CALL_ADDRTYPE_UNKNOWN,
-          bd_addr);
-
-    headset_->AtResponse(headset::BTHF_AT_RESPONSE_OK, 0, bd_addr);
+    LOG_INFO("AT+CLCC from %s", bd_addr->ToString().c_str());
+    topshim::rust::internal::current_calls_query_cb(bd_addr);
   }
 
   void UnknownAtCallback(char* at_string, RawAddress* bd_addr) override {
@@ -169,36 +180,6 @@ class DBusHeadsetCallbacks : public headset::Callbacks {
 
  private:
   headset::Interface* headset_;
-  int call_status;
-
-  void SetCallStatus(int call, RawAddress* bd_addr) {
-    if (call == call_status) return;
-
-    if (call) {
-      // This triggers a +CIEV command to set the call status for HFP
-      // devices. It is required along with the SCO establishment for some
-      // devices to provide sound.
-      headset_->PhoneStateChange(
-          /*num_active=*/1,
-          /*num_held=*/0,
-          /*call_setup_state=*/headset::bthf_call_state_t::BTHF_CALL_STATE_IDLE,
-          /*number=*/"",
-          /*type=*/(headset::bthf_call_addrtype_t)0,
-          /*name=*/"",
-          /*bd_addr=*/bd_addr);
-    } else {
-      headset_->PhoneStateChange(
-          /*num_active=*/0,
-          /*num_held=*/0,
-          /*call_setup_state=*/headset::bthf_call_state_t::BTHF_CALL_STATE_IDLE,
-          /*number=*/"",
-          /*type=*/(headset::bthf_call_addrtype_t)0,
-          /*name=*/"",
-          /*bd_addr=*/bd_addr);
-    }
-
-    call_status = call;
-  }
 };
 
 int HfpIntf::init() {
@@ -225,6 +206,125 @@ int HfpIntf::disconnect_audio(RustRawAddress bt_addr) {
   return intf_->DisconnectAudio(&addr);
 }
 
+int HfpIntf::set_codec_override(uint32_t codec, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  headset::bthf_wbs_config_t wbs = headset::BTHF_WBS_NONE;
+  switch (codec) {
+    case internal::kCodecIdNone:
+      break;
+    case internal::kCodecIdCvsd:
+      wbs = headset::BTHF_WBS_NO;
+      break;
+    case internal::kCodecIdMsbc:
+      wbs = headset::BTHF_WBS_YES;
+      break;
+    default:
+      // LC3-SWB isn't implemented by the audio gateway.
+      return BT_STATUS_UNSUPPORTED;
+  }
+  return intf_->SetCodecOverride(wbs, &addr);
+}
+
+uint16_t HfpIntf::get_sco_handle(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return BTM_ReadScoHandle(addr);
+}
+
+int HfpIntf::set_active_device(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return intf_->SetActiveDevice(&addr);
+}
+
+int HfpIntf::start_voice_recognition(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return intf_->StartVoiceRecognition(&addr);
+}
+
+int HfpIntf::stop_voice_recognition(RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return intf_->StopVoiceRecognition(&addr);
+}
+
+int HfpIntf::voice_recognition_response(bool accepted, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  // The audio gateway leaves answering AT+BVRA to the application.
+  return intf_->AtResponse(
+      accepted ? headset::BTHF_AT_RESPONSE_OK : headset::BTHF_AT_RESPONSE_ERROR, 0, &addr);
+}
+
+int HfpIntf::set_inband_ringtone(bool enabled, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return intf_->SendBsir(enabled, &addr);
+}
+
+int HfpIntf::phone_state_change(
+    uint32_t num_active, uint32_t num_held, uint32_t call_setup_state, ::rust::String number, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  std::string number_str = std::string(number);
+  return intf_->PhoneStateChange(
+      num_active,
+      num_held,
+      (headset::bthf_call_state_t)call_setup_state,
+      number_str.c_str(),
+      headset::BTHF_CALL_ADDRTYPE_UNKNOWN,
+      /*name=*/"",
+      &addr);
+}
+
+int HfpIntf::device_status_notification(
+    bool network_available, bool roaming, uint32_t signal, uint32_t battery, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return intf_->DeviceStatusNotification(
+      network_available ? headset::BTHF_NETWORK_STATE_AVAILABLE : headset::BTHF_NETWORK_STATE_NOT_AVAILABLE,
+      roaming ? headset::BTHF_SERVICE_TYPE_ROAMING : headset::BTHF_SERVICE_TYPE_HOME,
+      signal,
+      battery,
+      &addr);
+}
+
+int HfpIntf::cind_response(
+    bool network_available,
+    uint32_t num_active,
+    uint32_t num_held,
+    uint32_t call_setup_state,
+    uint32_t signal,
+    bool roaming,
+    uint32_t battery,
+    RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  // The parameters aren't in the order of the +CIND response, which
+  // CindResponse builds out of them.
+  return intf_->CindResponse(
+      network_available,
+      num_active,
+      num_held,
+      (headset::bthf_call_state_t)call_setup_state,
+      signal,
+      roaming,
+      battery,
+      &addr);
+}
+
+int HfpIntf::cops_response(::rust::String operator_name, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  return intf_->CopsResponse(std::string(operator_name).c_str(), &addr);
+}
+
+int HfpIntf::clcc_response(
+    uint32_t index, bool incoming, uint32_t state, bool multi_party, ::rust::String number, RustRawAddress bt_addr) {
+  RawAddress addr = rusty::CopyFromRustAddress(bt_addr);
+  std::string number_str = std::string(number);
+  return intf_->ClccResponse(
+      index,
+      incoming ? headset::BTHF_CALL_DIRECTION_INCOMING : headset::BTHF_CALL_DIRECTION_OUTGOING,
+      (headset::bthf_call_state_t)state,
+      headset::BTHF_CALL_TYPE_VOICE,
+      multi_party ? headset::BTHF_CALL_MPTY_TYPE_MULTI : headset::BTHF_CALL_MPTY_TYPE_SINGLE,
+      number_str.empty() ? nullptr : number_str.c_str(),
+      headset::BTHF_CALL_ADDRTYPE_UNKNOWN,
+      &addr);
+}
+
 void HfpIntf::cleanup() {}
 
 std::unique_ptr<HfpIntf> GetHfpProfile(const unsigned char* btif) {
@@ -20,6 +20,7 @@
 
 #include "btif/include/btif_hf.h"
 #include "include/hardware/bluetooth_headset_callbacks.h"
+#include "rust/cxx.h"
 #include "types/raw_address.h"
 
 namespace bluetooth {
@@ -37,6 +38,29 @@ class HfpIntf {
   int connect_audio(RustRawAddress bt_addr);
   int disconnect(RustRawAddress bt_addr);
   int disconnect_audio(RustRawAddress bt_addr);
+  int set_codec_override(uint32_t codec, RustRawAddress bt_addr);
+  uint16_t get_sco_handle(RustRawAddress bt_addr);
+  int set_active_device(RustRawAddress bt_addr);
+  int start_voice_recognition(RustRawAddress bt_addr);
+  int stop_voice_recognition(RustRawAddress bt_addr);
+  int voice_recognition_response(bool accepted, RustRawAddress bt_addr);
+  int set_inband_ringtone(bool enabled, RustRawAddress bt_addr);
+  int phone_state_change(
+      uint32_t num_active, uint32_t num_held, uint32_t call_setup_state, ::rust::String number, RustRawAddress bt_addr);
+  int device_status_notification(
+      bool network_available, bool roaming, uint32_t signal, uint32_t battery, RustRawAddress bt_addr);
+  int cind_response(
+      bool network_available,
+      uint32_t num_active,
+      uint32_t num_held,
+      uint32_t call_setup_state,
+      uint32_t signal,
+      bool roaming,
+      uint32_t battery,
+      RustRawAddress bt_addr);
+  int cops_response(::rust::String operator_name, RustRawAddress bt_addr);
+  int clcc_response(
+      uint32_t index, bool incoming, uint32_t state, bool multi_party, ::rust::String number, RustRawAddress bt_addr);
   void cleanup();
 
  private:
@@ -4,12 +4,14 @@
 
 use crate::bindings::root as bindings;
 use crate::topstack::get_dispatchers;
+use crate::watchdog;
 use num_traits::cast::{FromPrimitive, ToPrimitive};
 use std::cmp;
-use std::fmt::{Debug, Formatter, Result};
+use std::fmt::{Debug, Display, Formatter, Result};
 use std::mem;
 use std::os::raw::c_char;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::vec::Vec;
 use topshim_macros::cb_variant;
 
@@ -46,6 +48,56 @@ impl From<BtTransport> for i32 {
     }
 }
 
+impl Default for BtTransport {
+    fn default() -> Self {
+        BtTransport::Auto
+    }
+}
+
+/// LE address type of a remote device, as stored by the stack (see tBLE_ADDR_TYPE).
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BtAddrType {
+    Public = 0,
+    Random,
+    PublicId,
+    RandomId,
+    Unknown = 0xfe,
+}
+
+impl From<u8> for BtAddrType {
+    fn from(item: u8) -> Self {
+        BtAddrType::from_u8(item).unwrap_or(BtAddrType::Unknown)
+    }
+}
+
+impl Default for BtAddrType {
+    fn default() -> Self {
+        BtAddrType::Unknown
+    }
+}
+
+/// Local role on an ACL link (see tHCI_ROLE).
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BtAclRole {
+    Central = 0,
+    Peripheral,
+    Unknown = 0xff,
+}
+
+impl From<u8> for BtAclRole {
+    fn from(item: u8) -> Self {
+        BtAclRole::from_u8(item).unwrap_or(BtAclRole::Unknown)
+    }
+}
+
+impl Default for BtAclRole {
+    fn default() -> Self {
+        BtAclRole::Unknown
+    }
+}
+
 #[derive(Clone, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
 #[repr(u32)]
 pub enum BtSspVariant {
@@ -225,6 +277,16 @@ impl From<bindings::bt_status_t> for BtStatus {
     }
 }
 
+// Most of the adapter interface reports its status as a plain int.
+impl From<i32> for BtStatus {
+    fn from(item: i32) -> Self {
+        match BtStatus::from_i32(item) {
+            Some(x) => x,
+            _ => BtStatus::Unknown,
+        }
+    }
+}
+
 impl From<bindings::bt_bdname_t> for String {
     fn from(item: bindings::bt_bdname_t) -> Self {
         ascii_to_string(&item.name, item.name.len())
@@ -282,6 +344,8 @@ impl From<bindings::bt_io_cap_t> for BtIoCap {
     }
 }
 
+/// Activity reported by the controller for `read_energy_info`.
+pub type BtActivityEnergyInfo = bindings::bt_activity_energy_info;
 pub type BtHciErrorCode = u8;
 pub type BtLocalLeFeatures = bindings::bt_local_le_features_t;
 pub type BtPinCode = bindings::bt_pin_code_t;
@@ -553,6 +617,8 @@ pub enum SupportedProfiles {
     A2dp,
     Gatt,
     Sdp,
+    Socket,
+    Pan,
 }
 
 impl From<SupportedProfiles> for Vec<u8> {
@@ -563,6 +629,8 @@ impl From<SupportedProfiles> for Vec<u8> {
             SupportedProfiles::A2dp => "a2dp",
             SupportedProfiles::Gatt => "gatt",
             SupportedProfiles::Sdp => "sdp",
+            SupportedProfiles::Socket => "socket",
+            SupportedProfiles::Pan => "pan",
         }
         .bytes()
         .chain("\0".bytes())
@@ -574,7 +642,15 @@ impl From<SupportedProfiles> for Vec<u8> {
 mod ffi {
     #[derive(Debug, Copy, Clone)]
     pub struct RustRawAddress {
-        address: [u8; 6],
+        pub address: [u8; 6],
+    }
+
+    #[derive(Debug, Copy, Clone)]
+    pub struct RustRemoteVersion {
+        pub valid: bool,
+        pub lmp_version: u8,
+        pub manufacturer: u16,
+        pub lmp_subversion: u16,
     }
 
     unsafe extern "C++" {
@@ -586,6 +662,76 @@ mod ffi {
         // Convert flgas into an InitFlags object
         fn ConvertFlags(flags: Vec<String>) -> UniquePtr<InitFlags>;
         fn GetFlagsPtr(self: &InitFlags) -> *mut *const c_char;
+
+        fn SetGapAppearance(appearance: u16);
+
+        fn GetRemoteAddrType(addr: RustRawAddress) -> u8;
+
+        fn SetInquiryScanParams(interval: u16, window: u16, interlaced: bool);
+        fn SetPageScanParams(interval: u16, window: u16, interlaced: bool);
+        fn SetLeScanParams(interval: u16, window: u16, active: bool);
+
+        fn GetRemoteVersion(addr: RustRawAddress) -> RustRemoteVersion;
+        fn GetRemoteFeatures(addr: RustRawAddress) -> Vec<u8>;
+        fn GetRemoteLeFeatures(addr: RustRawAddress) -> Vec<u8>;
+
+        fn SetLeAddressPolicy(
+            privacy_enabled: bool,
+            static_address: RustRawAddress,
+            rpa_timeout: u32,
+        );
+        fn GetLeOwnAddressType() -> u8;
+
+        fn DisconnectAcl(addr: RustRawAddress, transport: u8) -> bool;
+
+        fn GetAclRole(addr: RustRawAddress) -> u8;
+        fn SwitchAclRole(addr: RustRawAddress, role: u8) -> bool;
+        fn SetSniffModeEnabled(addr: RustRawAddress, enabled: bool) -> bool;
+        fn SetSniffSubrating(
+            addr: RustRawAddress,
+            max_latency: u16,
+            min_remote_timeout: u16,
+            min_local_timeout: u16,
+        ) -> bool;
+
+        fn SetSecureConnectionsOnly(enabled: bool) -> bool;
+        fn SetMinEncryptionKeySize(key_size: u8);
+        fn GetEncryptionKeySize(addr: RustRawAddress, transport: u8) -> u8;
+
+        fn SetPtsConfig(key: &str, value: &str);
+        fn SendAttPdu(addr: RustRawAddress, pdu: &[u8]) -> bool;
+        fn SetLinkIdleTimeout(addr: RustRawAddress, transport: u8, timeout: u16) -> bool;
+        fn SendVendorCommand(opcode: u16, params: &[u8]) -> bool;
+        fn SendL2capEcho(addr: RustRawAddress, data: &[u8]) -> bool;
+        fn ReadRemoteName(addr: RustRawAddress);
+        fn ReadLeTxPowerLevels(addr: RustRawAddress, phy: u8) -> bool;
+        fn SetLeTxPowerReporting(addr: RustRawAddress, enable: bool) -> bool;
+        fn SetLePathLossReporting(
+            addr: RustRawAddress,
+            enable: bool,
+            high_threshold: u8,
+            high_hysteresis: u8,
+            low_threshold: u8,
+            low_hysteresis: u8,
+        ) -> bool;
+        fn SetHostChannelClassification(bredr_map: &[u8], le_map: &[u8]) -> bool;
+    }
+
+    extern "Rust" {
+        // Generated by cb_variant! below.
+        fn vendor_command_complete_cb(opcode: u16, params: Vec<u8>);
+        fn l2cap_echo_response_cb(addr: RustRawAddress, data: Vec<u8>);
+        fn remote_name_cb(addr: RustRawAddress, success: bool, name: Vec<u8>);
+        fn le_tx_power_levels_cb(addr: RustRawAddress, status: u8, phy: u8, current: i8, max: i8);
+        fn le_tx_power_report_cb(
+            addr: RustRawAddress,
+            reason: u8,
+            phy: u8,
+            tx_power: i8,
+            flags: u8,
+            delta: i8,
+        );
+        fn le_path_loss_cb(addr: RustRawAddress, path_loss: u8, zone: u8);
     }
 }
 
@@ -616,9 +762,9 @@ impl Default for RawAddress {
     }
 }
 
-impl ToString for RawAddress {
-    fn to_string(&self) -> String {
-        String::from(format!(
+impl Display for RawAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_fmt(format_args!(
             "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
             self.val[0], self.val[1], self.val[2], self.val[3], self.val[4], self.val[5]
         ))
@@ -636,6 +782,7 @@ impl RawAddress {
         return Some(RawAddress { val: raw });
     }
 
+    /// Parses an address in the "XX:XX:XX:XX:XX:XX" form, each octet being two hex digits.
     pub fn from_string<S: Into<String>>(addr: S) -> Option<RawAddress> {
         let addr: String = addr.into();
         let s = addr.split(':').collect::<Vec<&str>>();
@@ -646,6 +793,10 @@ impl RawAddress {
 
         let mut raw: [u8; 6] = [0; 6];
         for i in 0..s.len() {
+            if s[i].len() != 2 || !s[i].chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+
             raw[i] = match u8::from_str_radix(s[i], 16) {
                 Ok(res) => res,
                 Err(_) => {
@@ -704,11 +855,24 @@ pub enum BaseCallbacks {
     AddressConsolidate(RawAddress, RawAddress),
     LeAddressAssociate(RawAddress, RawAddress),
     AclState(BtStatus, RawAddress, BtAclState, BtTransport, BtHciErrorCode),
+    AclRoleChanged(BtStatus, RawAddress, BtAclRole),
+    KeySizeViolation(RawAddress, BtTransport, u8),
+    EncryptionChange(RawAddress, BtTransport, BtHciErrorCode, bool, bool),
+    AuthFailure(RawAddress, BtHciErrorCode),
+    EnergyInfo(BtActivityEnergyInfo),
+    VendorCommandComplete(u16, Vec<u8>),
+    L2capEchoResponse(RawAddress, Vec<u8>),
+    RemoteName(RawAddress, bool, String),
+    /// Status, PHY, and current and maximum TX power levels of an LE link, in dBm.
+    LeTxPowerLevels(RawAddress, u8, u8, i8, i8),
+    /// Reason, PHY, TX power level, flags and change of an LE Transmit Power Reporting event.
+    LeTxPowerReport(RawAddress, u8, u8, i8, u8, i8),
+    /// Path loss, in dB, and zone entered of an LE Path Loss Threshold event.
+    LePathLoss(RawAddress, u8, u8),
     // Unimplemented so far:
     // thread_evt_cb
     // dut_mode_recv_cb
     // le_test_mode_cb
-    // energy_info_cb
     // link_quality_report_cb
     // generate_local_oob_data_cb
     // switch_buffer_size_cb
@@ -721,7 +885,9 @@ pub struct BaseCallbacksDispatcher {
 
 type BaseCb = Arc<Mutex<BaseCallbacksDispatcher>>;
 
-cb_variant!(BaseCb, adapter_state_cb -> BaseCallbacks::AdapterState, u32 -> BtState);
+cb_variant!(BaseCb, adapter_state_cb -> BaseCallbacks::AdapterState, u32 -> BtState, {
+    watchdog::callback_received(ADAPTER_STATE_CALLBACK);
+});
 cb_variant!(BaseCb, adapter_properties_cb -> BaseCallbacks::AdapterProperties,
 u32 -> BtStatus, i32, *mut bindings::bt_property_t, {
     let _2 = ptr_to_vec(_2, _1 as usize);
@@ -769,6 +935,73 @@ u32 -> BtStatus, *mut FfiAddress, bindings::bt_acl_state_t -> BtAclState, i32 ->
     let _1 = unsafe { *(_1 as *const RawAddress) };
 });
 
+cb_variant!(BaseCb, acl_role_changed_cb -> BaseCallbacks::AclRoleChanged,
+u32 -> BtStatus, *mut FfiAddress, u8 -> BtAclRole, {
+    let _1 = unsafe { *(_1 as *const RawAddress) };
+});
+
+cb_variant!(BaseCb, key_size_violation_cb -> BaseCallbacks::KeySizeViolation,
+*mut FfiAddress, i32 -> BtTransport, u8, {
+    let _0 = unsafe { *(_0 as *const RawAddress) };
+});
+
+cb_variant!(BaseCb, encryption_change_cb -> BaseCallbacks::EncryptionChange,
+*mut FfiAddress, i32 -> BtTransport, bindings::bt_hci_error_code_t -> BtHciErrorCode, bool, bool, {
+    let _0 = unsafe { *(_0 as *const RawAddress) };
+});
+
+cb_variant!(BaseCb, auth_failure_cb -> BaseCallbacks::AuthFailure,
+*mut FfiAddress, bindings::bt_hci_error_code_t -> BtHciErrorCode, {
+    let _0 = unsafe { *(_0 as *const RawAddress) };
+});
+
+// The traffic per Android app uid that comes with the activity doesn't apply to Floss.
+cb_variant!(BaseCb, energy_info_cb -> BaseCallbacks::EnergyInfo,
+*mut bindings::bt_activity_energy_info, *mut bindings::bt_uid_traffic_t -> _, {
+    let _0 = unsafe { *_0 };
+});
+
+// Not part of `bt_callbacks_t`, the btif shim calls it for commands sent with
+// `BluetoothInterface::send_vendor_command`.
+cb_variant!(BaseCb, vendor_command_complete_cb -> BaseCallbacks::VendorCommandComplete,
+u16, Vec<u8>);
+
+// Also from the btif shim, for echo requests sent with `BluetoothInterface::send_l2cap_echo`.
+cb_variant!(BaseCb, l2cap_echo_response_cb -> BaseCallbacks::L2capEchoResponse,
+ffi::RustRawAddress, Vec<u8>, {
+    let _0 = RawAddress { val: _0.address };
+});
+
+// And for name requests sent with `BluetoothInterface::read_remote_name`.
+cb_variant!(BaseCb, remote_name_cb -> BaseCallbacks::RemoteName,
+ffi::RustRawAddress, bool, Vec<u8>, {
+    let _0 = RawAddress { val: _0.address };
+    let _2 = String::from_utf8_lossy(&_2).into_owned();
+});
+
+// And for LE power control through `BluetoothInterface::read_le_tx_power_levels` and the
+// reporting it turns on.
+cb_variant!(BaseCb, le_tx_power_levels_cb -> BaseCallbacks::LeTxPowerLevels,
+ffi::RustRawAddress, u8, u8, i8, i8, {
+    let _0 = RawAddress { val: _0.address };
+});
+
+cb_variant!(BaseCb, le_tx_power_report_cb -> BaseCallbacks::LeTxPowerReport,
+ffi::RustRawAddress, u8, u8, i8, u8, i8, {
+    let _0 = RawAddress { val: _0.address };
+});
+
+cb_variant!(BaseCb, le_path_loss_cb -> BaseCallbacks::LePathLoss,
+ffi::RustRawAddress, u8, u8, {
+    let _0 = RawAddress { val: _0.address };
+});
+
+/// Name the watchdog uses for the adapter state callback expected after enable and disable.
+const ADAPTER_STATE_CALLBACK: &str = "adapter_state_changed";
+
+/// How long libbluetooth may take to report the new adapter state after enable or disable.
+const ADAPTER_STATE_DEADLINE: Duration = Duration::from_secs(10);
+
 struct RawInterfaceWrapper {
     pub raw: *const bindings::bt_interface_t,
 }
@@ -780,6 +1013,8 @@ unsafe impl Send for RawInterfaceWrapper {}
 /// marked unsafe since it will need to dereference a C object. This can cause
 /// segfaults if not validated beforehand.
 ///
+/// The call is watched by the [`watchdog`](crate::watchdog) while it runs.
+///
 /// Example:
 ///     ccall!(self, foobar, arg1, arg2)
 ///     Expands to: unsafe {((*self.internal.raw).foobar.unwrap())(arg1, arg2)}
@@ -787,16 +1022,30 @@ unsafe impl Send for RawInterfaceWrapper {}
 macro_rules! ccall {
     ($self:ident,$fn_name:ident) => {
         unsafe {
+            let _watch = $crate::watch_btif_call!($fn_name);
             ((*$self.internal.raw).$fn_name.unwrap())()
         }
     };
     ($self:ident,$fn_name:ident, $($args:expr),*) => {
         unsafe {
+            let _watch = $crate::watch_btif_call!($fn_name);
             ((*$self.internal.raw).$fn_name.unwrap())($($args),*)
         }
     };
 }
 
+/// Starts watching a call named after the calling module and `fn_name`, e.g.
+/// `bt_topshim::profiles::a2dp::connect`. Used by the call macros.
+#[macro_export]
+macro_rules! watch_btif_call {
+    ($fn_name:ident) => {
+        $crate::watchdog::watch_call(
+            concat!(module_path!(), "::", stringify!($fn_name)),
+            $crate::watchdog::DEFAULT_CALL_DEADLINE,
+        )
+    };
+}
+
 /// Macro to call const functions via cxx. Expects the self object to have the
 /// cxx object to be called at `self.internal_cxx`.
 ///
@@ -805,12 +1054,14 @@ macro_rules! ccall {
 ///     Expands to: self.internal_cxx.foobar(arg1, arg2)
 #[macro_export]
 macro_rules! cxxcall {
-    ($self:expr,$fn_name:ident) => {
+    ($self:expr,$fn_name:ident) => {{
+        let _watch = $crate::watch_btif_call!($fn_name);
         $self.internal_cxx.$fn_name()
-    };
-    ($self:expr,$fn_name:ident, $($args:expr),*) => {
+    }};
+    ($self:expr,$fn_name:ident, $($args:expr),*) => {{
+        let _watch = $crate::watch_btif_call!($fn_name);
         $self.internal_cxx.$fn_name($($args),*)
-    };
+    }};
 }
 
 /// Macro to call mutable functions via cxx. Mutable functions are always
@@ -822,12 +1073,14 @@ macro_rules! cxxcall {
 ///     Expands to: self.internal_cxx.pin_mut().foobar(arg1, arg2)
 #[macro_export]
 macro_rules! mutcxxcall {
-    ($self:expr,$fn_name:ident) => {
+    ($self:expr,$fn_name:ident) => {{
+        let _watch = $crate::watch_btif_call!($fn_name);
         $self.internal_cxx.pin_mut().$fn_name()
-    };
-    ($self:expr,$fn_name:ident, $($args:expr),*) => {
+    }};
+    ($self:expr,$fn_name:ident, $($args:expr),*) => {{
+        let _watch = $crate::watch_btif_call!($fn_name);
         $self.internal_cxx.pin_mut().$fn_name($($args),*)
-    };
+    }};
 }
 
 /// Rust wrapper around `bt_interface_t`.
@@ -883,11 +1136,15 @@ impl BluetoothInterface {
             thread_evt_cb: None,
             dut_mode_recv_cb: None,
             le_test_mode_cb: None,
-            energy_info_cb: None,
+            energy_info_cb: Some(energy_info_cb),
             link_quality_report_cb: None,
             generate_local_oob_data_cb: None,
             switch_buffer_size_cb: None,
             switch_codec_cb: None,
+            acl_role_changed_cb: Some(acl_role_changed_cb),
+            key_size_violation_cb: Some(key_size_violation_cb),
+            encryption_change_cb: Some(encryption_change_cb),
+            auth_failure_cb: Some(auth_failure_cb),
         });
 
         let rawcb: *mut bindings::bt_callbacks_t = &mut *callbacks;
@@ -918,10 +1175,12 @@ impl BluetoothInterface {
     }
 
     pub fn enable(&self) -> i32 {
+        watchdog::expect_callback(ADAPTER_STATE_CALLBACK, ADAPTER_STATE_DEADLINE);
         ccall!(self, enable)
     }
 
     pub fn disable(&self) -> i32 {
+        watchdog::expect_callback(ADAPTER_STATE_CALLBACK, ADAPTER_STATE_DEADLINE);
         ccall!(self, disable)
     }
 
@@ -977,6 +1236,11 @@ impl BluetoothInterface {
         ccall!(self, cancel_discovery)
     }
 
+    /// Asks the controller for its activity, which is reported with `BaseCallbacks::EnergyInfo`.
+    pub fn read_energy_info(&self) -> i32 {
+        ccall!(self, read_energy_info)
+    }
+
     pub fn create_bond(&self, addr: &RawAddress, transport: BtTransport) -> i32 {
         let ctransport: i32 = transport.into();
         let ffi_addr = cast_to_const_ffi_address!(addr as *const RawAddress);
@@ -1025,6 +1289,234 @@ impl BluetoothInterface {
         ccall!(self, clear_event_filter)
     }
 
+    /// Sets the appearance exposed by the GAP service of the stack. There is no btif API for it
+    /// so this goes to the stack directly.
+    pub fn set_le_appearance(&self, appearance: u16) {
+        ffi::SetGapAppearance(appearance);
+    }
+
+    /// Gets the LE address type the stack stored for a remote device.
+    pub fn get_remote_addr_type(&self, addr: &RawAddress) -> BtAddrType {
+        BtAddrType::from(ffi::GetRemoteAddrType(ffi::RustRawAddress { address: addr.val }))
+    }
+
+    /// Sets the inquiry scan activity of the controller, `interval` and `window` being in slots
+    /// of 0.625 ms. Like the appearance, this has no btif API.
+    pub fn set_inquiry_scan_params(&self, interval: u16, window: u16, interlaced: bool) {
+        ffi::SetInquiryScanParams(interval, window, interlaced);
+    }
+
+    /// Sets the page scan activity of the controller, in the same units as the inquiry scan.
+    pub fn set_page_scan_params(&self, interval: u16, window: u16, interlaced: bool) {
+        ffi::SetPageScanParams(interval, window, interlaced);
+    }
+
+    /// Sets the parameters of the LE scan the stack runs during discovery. They are used from the
+    /// next discovery on.
+    pub fn set_le_scan_params(&self, interval: u16, window: u16, active: bool) {
+        ffi::SetLeScanParams(interval, window, active);
+    }
+
+    /// Gets the LMP version, manufacturer and LMP subversion a connected remote device reported,
+    /// or None if there is no link to it or they weren't read yet.
+    pub fn get_remote_version(&self, addr: &RawAddress) -> Option<(u8, u16, u16)> {
+        let version = ffi::GetRemoteVersion(ffi::RustRawAddress { address: addr.val });
+        match version.valid {
+            true => Some((version.lmp_version, version.manufacturer, version.lmp_subversion)),
+            false => None,
+        }
+    }
+
+    /// Gets page 0 of the LMP features of a remote device connected over BR/EDR. Empty if there
+    /// is no such link.
+    pub fn get_remote_features(&self, addr: &RawAddress) -> Vec<u8> {
+        ffi::GetRemoteFeatures(ffi::RustRawAddress { address: addr.val })
+    }
+
+    /// Gets the LL features of a remote device connected over LE. Empty if there is no such link
+    /// or they weren't read yet.
+    pub fn get_remote_le_features(&self, addr: &RawAddress) -> Vec<u8> {
+        ffi::GetRemoteLeFeatures(ffi::RustRawAddress { address: addr.val })
+    }
+
+    /// Chooses the address the stack uses on LE once it's enabled: with privacy, a resolvable
+    /// private address rotated every `rpa_timeout` seconds (0 keeps the default of 7 to 15
+    /// minutes); otherwise `static_address` if it is set, or the public address. The choice can't
+    /// be changed while the stack is enabled, so this must be called before `enable`.
+    pub fn set_le_address_policy(
+        &self,
+        privacy_enabled: bool,
+        static_address: Option<RawAddress>,
+        rpa_timeout: u32,
+    ) {
+        let static_address = static_address.map_or([0; 6], |address| address.val);
+        ffi::SetLeAddressPolicy(
+            privacy_enabled,
+            ffi::RustRawAddress { address: static_address },
+            rpa_timeout,
+        );
+    }
+
+    /// Gets the type of the address currently used on LE, either public or random.
+    pub fn get_le_own_address_type(&self) -> BtAddrType {
+        BtAddrType::from(ffi::GetLeOwnAddressType())
+    }
+
+    /// Drops the ACL link to `addr` on `transport`, which must be BR/EDR or LE. Returns false if
+    /// the device isn't connected on it.
+    pub fn disconnect_acl(&self, addr: &RawAddress, transport: BtTransport) -> bool {
+        ffi::DisconnectAcl(ffi::RustRawAddress { address: addr.val }, transport.to_u8().unwrap())
+    }
+
+    /// Gets the local role on the BR/EDR link to `addr`, or `Unknown` if there is no such link.
+    pub fn get_acl_role(&self, addr: &RawAddress) -> BtAclRole {
+        BtAclRole::from(ffi::GetAclRole(ffi::RustRawAddress { address: addr.val }))
+    }
+
+    /// Starts switching the local role on the BR/EDR link to `addr`. The outcome is reported by
+    /// [`BaseCallbacks::AclRoleChanged`]. Returns false if there is no such link.
+    pub fn switch_acl_role(&self, addr: &RawAddress, role: BtAclRole) -> bool {
+        ffi::SwitchAclRole(ffi::RustRawAddress { address: addr.val }, role.to_u8().unwrap())
+    }
+
+    /// Allows or forbids sniff mode on the BR/EDR link to `addr`. Returns false if there is no
+    /// such link.
+    pub fn set_sniff_mode_enabled(&self, addr: &RawAddress, enabled: bool) -> bool {
+        ffi::SetSniffModeEnabled(ffi::RustRawAddress { address: addr.val }, enabled)
+    }
+
+    /// Sets the sniff subrating parameters of the BR/EDR link to `addr`, in slots of 0.625 ms.
+    /// Returns false if there is no such link.
+    pub fn set_sniff_subrating(
+        &self,
+        addr: &RawAddress,
+        max_latency: u16,
+        min_remote_timeout: u16,
+        min_local_timeout: u16,
+    ) -> bool {
+        ffi::SetSniffSubrating(
+            ffi::RustRawAddress { address: addr.val },
+            max_latency,
+            min_remote_timeout,
+            min_local_timeout,
+        )
+    }
+
+    /// Requires Secure Connections for all new pairings on BR/EDR and LE, so legacy pairing is
+    /// rejected by the stack. Returns false if the controller doesn't support Secure Connections.
+    pub fn set_secure_connections_only(&self, enabled: bool) -> bool {
+        ffi::SetSecureConnectionsOnly(enabled)
+    }
+
+    /// Sets the shortest encryption key, in octets, accepted on new links and LE pairings.
+    /// Shorter keys are reported by [`BaseCallbacks::KeySizeViolation`].
+    pub fn set_min_encryption_key_size(&self, key_size: u8) {
+        ffi::SetMinEncryptionKeySize(key_size)
+    }
+
+    /// Gets the size of the key encrypting the link to `addr` on `transport`, in octets, or 0 if
+    /// the link isn't encrypted.
+    pub fn get_encryption_key_size(&self, addr: &RawAddress, transport: BtTransport) -> u8 {
+        ffi::GetEncryptionKeySize(
+            ffi::RustRawAddress { address: addr.val },
+            transport.to_u8().unwrap(),
+        )
+    }
+
+    /// Sets `key` of the stack configuration, which holds the PTS test modes of libbluetooth, or
+    /// removes it if `value` is empty.
+    pub fn set_pts_config(&self, key: &str, value: &str) {
+        ffi::SetPtsConfig(key, value)
+    }
+
+    /// Sends `pdu` over the ATT channel of the LE link to `addr` as is, bypassing GATT. Returns
+    /// false if there is no such link.
+    pub fn send_att_pdu(&self, addr: &RawAddress, pdu: &[u8]) -> bool {
+        ffi::SendAttPdu(ffi::RustRawAddress { address: addr.val }, pdu)
+    }
+
+    /// Sets how long, in seconds, the link to `addr` on `transport` stays up with no L2CAP
+    /// channels open. Returns false if there is no such link.
+    pub fn set_link_idle_timeout(
+        &self,
+        addr: &RawAddress,
+        transport: BtTransport,
+        timeout: u16,
+    ) -> bool {
+        ffi::SetLinkIdleTimeout(
+            ffi::RustRawAddress { address: addr.val },
+            transport.to_u8().unwrap(),
+            timeout,
+        )
+    }
+
+    /// Sends a vendor specific HCI command to the controller. Its return parameters are reported
+    /// with [`BaseCallbacks::VendorCommandComplete`]. Returns false if `opcode` isn't in the vendor
+    /// specific group or `params` don't fit in a command.
+    pub fn send_vendor_command(&self, opcode: u16, params: &[u8]) -> bool {
+        ffi::SendVendorCommand(opcode, params)
+    }
+
+    /// Sends an L2CAP echo request carrying `data` over the BR/EDR link to `addr`. The data the
+    /// device echoes back is reported with [`BaseCallbacks::L2capEchoResponse`], unless another
+    /// request was sent since. Returns false if there is no such link.
+    pub fn send_l2cap_echo(&self, addr: &RawAddress, data: &[u8]) -> bool {
+        ffi::SendL2capEcho(ffi::RustRawAddress { address: addr.val }, data)
+    }
+
+    /// Asks the BR/EDR device at `addr` for its name. Whether it answered, and the name if so,
+    /// are reported with [`BaseCallbacks::RemoteName`]. Only one request is handled at a time,
+    /// others fail right away.
+    pub fn read_remote_name(&self, addr: &RawAddress) {
+        ffi::ReadRemoteName(ffi::RustRawAddress { address: addr.val })
+    }
+
+    /// Reads the current and maximum TX power levels of the LE link to `addr` on `phy` (1M, 2M,
+    /// Coded S=8 and Coded S=2 from 1 on), reported with [`BaseCallbacks::LeTxPowerLevels`].
+    /// Returns false if the controller doesn't support LE Power Control or there is no such link.
+    pub fn read_le_tx_power_levels(&self, addr: &RawAddress, phy: u8) -> bool {
+        ffi::ReadLeTxPowerLevels(ffi::RustRawAddress { address: addr.val }, phy)
+    }
+
+    /// Turns the reports of the local and remote TX power changes of the LE link to `addr`, made
+    /// with [`BaseCallbacks::LeTxPowerReport`], on or off. Returns false like
+    /// `read_le_tx_power_levels`.
+    pub fn set_le_tx_power_reporting(&self, addr: &RawAddress, enable: bool) -> bool {
+        ffi::SetLeTxPowerReporting(ffi::RustRawAddress { address: addr.val }, enable)
+    }
+
+    /// Turns the reports of the path loss of the LE link to `addr` moving into another zone, made
+    /// with [`BaseCallbacks::LePathLoss`], on or off. The low zone ends at `low_threshold` and the
+    /// high one starts at `high_threshold`, in dB, and the path loss has to go past a threshold
+    /// by its hysteresis to leave a zone. Returns false if the controller doesn't support LE Path
+    /// Loss Monitoring or there is no such link.
+    pub fn set_le_path_loss_reporting(
+        &self,
+        addr: &RawAddress,
+        enable: bool,
+        high_threshold: u8,
+        high_hysteresis: u8,
+        low_threshold: u8,
+        low_hysteresis: u8,
+    ) -> bool {
+        ffi::SetLePathLossReporting(
+            ffi::RustRawAddress { address: addr.val },
+            enable,
+            high_threshold,
+            high_hysteresis,
+            low_threshold,
+            low_hysteresis,
+        )
+    }
+
+    /// Marks the channels the host knows to be bad, with their bits cleared in `bredr_map` for
+    /// the 79 BR/EDR channels and in `le_map` for the 37 LE data channels, so that the controller
+    /// hops around them. Each map is sent if the controller supports it; returns false if it
+    /// supports neither.
+    pub fn set_host_channel_classification(&self, bredr_map: &[u8; 10], le_map: &[u8; 5]) -> bool {
+        ffi::SetHostChannelClassification(bredr_map, le_map)
+    }
+
     pub(crate) fn get_profile_interface(
         &self,
         profile: SupportedProfiles,
@@ -1089,6 +1581,20 @@ mod tests {
         assert_eq!(std::mem::align_of::<RawAddress>(), std::mem::align_of::<FfiAddress>());
     }
 
+    #[test]
+    fn test_addr_from_string() {
+        let addr = RawAddress::from_string("00:11:22:aa:BB:cc").unwrap();
+        assert_eq!(addr.val, [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc]);
+        assert_eq!(addr.to_string(), "00:11:22:AA:BB:CC");
+
+        assert!(RawAddress::from_string("").is_none());
+        assert!(RawAddress::from_string("00:11:22:33:44").is_none());
+        assert!(RawAddress::from_string("00:11:22:33:44:55:66").is_none());
+        assert!(RawAddress::from_string("0:11:22:33:44:55").is_none());
+        assert!(RawAddress::from_string("+0:11:22:33:44:55").is_none());
+        assert!(RawAddress::from_string("00:11:22:33:44:5g").is_none());
+    }
+
     fn make_bdname_from_slice(slice: &[u8]) -> bindings::bt_bdname_t {
         // Length of slice must be less than bd_name max
         assert!(slice.len() <= 249);
@@ -4,6 +4,14 @@ mod ffi {
         address: [u8; 6],
     }
 
+    pub struct RustLocalVersion {
+        pub hci_version: u8,
+        pub hci_revision: u16,
+        pub lmp_version: u8,
+        pub manufacturer: u16,
+        pub lmp_subversion: u16,
+    }
+
     unsafe extern "C++" {
         include!("controller/controller_shim.h");
 
@@ -11,9 +19,17 @@ mod ffi {
 
         fn GetControllerInterface() -> UniquePtr<ControllerIntf>;
         fn read_local_addr(self: &ControllerIntf) -> RustRawAddress;
+        fn get_acl_buffer_count_classic(self: &ControllerIntf) -> u16;
+        fn get_acl_buffer_count_ble(self: &ControllerIntf) -> u8;
+        fn get_ble_acceptlist_size(self: &ControllerIntf) -> u8;
+        fn supports_ble_privacy(self: &ControllerIntf) -> bool;
+        fn get_ble_resolving_list_max_size(self: &ControllerIntf) -> u8;
+        fn get_local_version(self: &ControllerIntf) -> RustLocalVersion;
     }
 }
 
+pub type LocalVersion = ffi::RustLocalVersion;
+
 pub struct Controller {
     internal: cxx::UniquePtr<ffi::ControllerIntf>,
 }
@@ -29,4 +45,33 @@ impl Controller {
     pub fn read_local_addr(&mut self) -> [u8; 6] {
         self.internal.read_local_addr().address
     }
+
+    /// Number of BR/EDR ACL packets the controller can buffer.
+    pub fn get_acl_buffer_count_classic(&self) -> u16 {
+        self.internal.get_acl_buffer_count_classic()
+    }
+
+    /// Number of LE ACL packets the controller can buffer.
+    pub fn get_acl_buffer_count_ble(&self) -> u8 {
+        self.internal.get_acl_buffer_count_ble()
+    }
+
+    pub fn get_ble_acceptlist_size(&self) -> u8 {
+        self.internal.get_ble_acceptlist_size()
+    }
+
+    /// Whether the controller can resolve private addresses itself (LL privacy).
+    pub fn supports_ble_privacy(&self) -> bool {
+        self.internal.supports_ble_privacy()
+    }
+
+    /// Number of identities the controller can keep in its resolving list.
+    pub fn get_ble_resolving_list_max_size(&self) -> u8 {
+        self.internal.get_ble_resolving_list_max_size()
+    }
+
+    /// Versions the controller reported in Read Local Version Information.
+    pub fn get_local_version(&self) -> LocalVersion {
+        self.internal.get_local_version()
+    }
 }
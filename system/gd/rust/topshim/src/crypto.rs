@@ -0,0 +1,94 @@
+//! Cryptographic primitives for the protocols that the Rust stack runs itself.
+
+#[cxx::bridge(namespace = bluetooth::topshim::rust)]
+mod ffi {
+    unsafe extern "C++" {
+        include!("crypto/crypto_shim.h");
+
+        fn Sha256(data: &[u8]) -> Vec<u8>;
+        fn Aes128Encrypt(key: &[u8], block: &[u8]) -> Vec<u8>;
+        fn Aes128Decrypt(key: &[u8], block: &[u8]) -> Vec<u8>;
+        fn EcdhP256(private_key: &[u8], public_key: &[u8]) -> Vec<u8>;
+        fn RandomBytes(len: usize) -> Vec<u8>;
+        fn Pbkdf2HmacSha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8>;
+        fn Aes256GcmSeal(key: &[u8], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Vec<u8>;
+        fn Aes256GcmOpen(
+            key: &[u8],
+            nonce: &[u8],
+            aad: &[u8],
+            sealed: &[u8],
+            plaintext: &mut Vec<u8>,
+        ) -> bool;
+    }
+}
+
+fn to_array<const N: usize>(bytes: Vec<u8>) -> Option<[u8; N]> {
+    if bytes.len() != N {
+        return None;
+    }
+
+    let mut arr = [0; N];
+    arr.copy_from_slice(&bytes);
+    Some(arr)
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    to_array(ffi::Sha256(data)).expect("SHA-256 digest has the wrong length")
+}
+
+/// Encrypts a single block with AES-128 in ECB mode.
+pub fn aes128_encrypt(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+    to_array(ffi::Aes128Encrypt(key, block)).expect("Failed to encrypt with AES-128")
+}
+
+/// Decrypts a single block with AES-128 in ECB mode.
+pub fn aes128_decrypt(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+    to_array(ffi::Aes128Decrypt(key, block)).expect("Failed to decrypt with AES-128")
+}
+
+/// Computes the ECDH shared secret on the P-256 curve, which is the X coordinate of the shared
+/// point. `public_key` holds the X and Y coordinates of the remote key.
+///
+/// Returns None if either key is invalid.
+pub fn ecdh_p256(private_key: &[u8; 32], public_key: &[u8; 64]) -> Option<[u8; 32]> {
+    to_array(ffi::EcdhP256(private_key, public_key))
+}
+
+/// Returns `len` bytes from the cryptographically secure random generator.
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let bytes = ffi::RandomBytes(len);
+    assert_eq!(bytes.len(), len, "Failed to generate random bytes");
+    bytes
+}
+
+/// Derives a key from `password` with PBKDF2-HMAC-SHA256.
+pub fn pbkdf2_hmac_sha256<const N: usize>(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+) -> Option<[u8; N]> {
+    to_array(ffi::Pbkdf2HmacSha256(password, salt, iterations, N))
+}
+
+/// Encrypts and authenticates `plaintext` along with `aad` using AES-256-GCM. The 16-byte tag is
+/// appended to the ciphertext.
+pub fn aes256_gcm_seal(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let sealed = ffi::Aes256GcmSeal(key, nonce, aad, plaintext);
+    assert!(!sealed.is_empty(), "Failed to encrypt with AES-256-GCM");
+    sealed
+}
+
+/// Decrypts what `aes256_gcm_seal` returned. Returns None if the key, nonce or `aad` don't match,
+/// or if the data was tampered with.
+pub fn aes256_gcm_open(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Option<Vec<u8>> {
+    let mut plaintext = Vec::new();
+    if !ffi::Aes256GcmOpen(key, nonce, aad, sealed, &mut plaintext) {
+        return None;
+    }
+    Some(plaintext)
+}
@@ -26,6 +26,10 @@ pub mod btif;
 /// Helper module for the topshim facade.
 pub mod controller;
 
+pub mod crypto;
+
 pub mod profiles;
 
 pub mod topstack;
+
+pub mod watchdog;
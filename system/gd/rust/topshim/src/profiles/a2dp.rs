@@ -178,6 +178,7 @@ pub mod ffi {
         fn stop_audio_request(self: &A2dpIntf) -> bool;
         fn cleanup(self: &A2dpIntf);
         fn get_presentation_position(self: &A2dpIntf) -> RustPresentationPosition;
+        fn is_offload_supported(self: &A2dpIntf) -> bool;
         // A2dp sink functions
 
         unsafe fn GetA2dpSinkProfile(btif: *const u8) -> UniquePtr<A2dpSinkIntf>;
@@ -305,6 +306,12 @@ impl A2dp {
         self.internal.disconnect(addr.into());
     }
 
+    /// Sets the codec preferences for the stream with `addr`, which is reconfigured if they change
+    /// the codec or its parameters.
+    pub fn config_codec(&self, addr: RawAddress, codec_preferences: Vec<A2dpCodecConfig>) -> i32 {
+        self.internal.config_codec(addr.into(), codec_preferences)
+    }
+
     pub fn set_audio_config(&self, sample_rate: i32, bits_per_sample: i32, channel_mode: i32) {
         let config =
             A2dpCodecConfig { sample_rate, bits_per_sample, channel_mode, ..Default::default() };
@@ -321,6 +328,11 @@ impl A2dp {
     pub fn get_presentation_position(&self) -> PresentationPosition {
         self.internal.get_presentation_position()
     }
+
+    /// Whether the platform supports encoding A2DP audio in the controller.
+    pub fn is_offload_supported(&self) -> bool {
+        self.internal.is_offload_supported()
+    }
 }
 
 #[derive(Debug)]
@@ -6,6 +6,42 @@ use topshim_macros::cb_variant;
 
 #[cxx::bridge(namespace = bluetooth::topshim::rust)]
 pub mod ffi {
+    /// A track of a media player. The duration is in milliseconds, and the numbers are left
+    /// out when zero.
+    #[derive(Debug, Default, Clone)]
+    pub struct AvrcpSongInfo {
+        pub media_id: String,
+        pub title: String,
+        pub artist: String,
+        pub album: String,
+        pub genre: String,
+        pub track_number: u32,
+        pub total_tracks: u32,
+        pub duration_ms: u32,
+    }
+
+    #[derive(Debug, Default, Clone)]
+    pub struct AvrcpFolderInfo {
+        pub media_id: String,
+        pub name: String,
+        pub is_playable: bool,
+    }
+
+    /// Entry of a folder, which is either a subfolder or a track.
+    #[derive(Debug, Default, Clone)]
+    pub struct AvrcpListItem {
+        pub is_folder: bool,
+        pub folder: AvrcpFolderInfo,
+        pub song: AvrcpSongInfo,
+    }
+
+    #[derive(Debug, Default, Clone)]
+    pub struct AvrcpMediaPlayerInfo {
+        pub id: u16,
+        pub name: String,
+        pub browsing_supported: bool,
+    }
+
     unsafe extern "C++" {
         include!("btav/btav_shim.h");
 
@@ -16,18 +52,37 @@ pub mod ffi {
         fn init(self: Pin<&mut AvrcpIntf>);
         fn cleanup(self: Pin<&mut AvrcpIntf>);
         fn set_volume(self: Pin<&mut AvrcpIntf>, volume: i8);
+        fn set_now_playing_list(
+            self: Pin<&mut AvrcpIntf>,
+            current_media_id: String,
+            songs: Vec<AvrcpSongInfo>,
+        );
+        fn set_media_players(
+            self: Pin<&mut AvrcpIntf>,
+            addressed_player: u16,
+            players: Vec<AvrcpMediaPlayerInfo>,
+        );
+        fn set_folder_items(self: Pin<&mut AvrcpIntf>, media_id: String, items: Vec<AvrcpListItem>);
 
     }
     extern "Rust" {
         fn avrcp_absolute_volume_enabled(enabled: bool);
         fn avrcp_absolute_volume_update(volume: i8);
+        fn avrcp_play_item(player_id: u16, now_playing: bool, media_id: String);
     }
 }
 
+pub type AvrcpSongInfo = ffi::AvrcpSongInfo;
+pub type AvrcpFolderInfo = ffi::AvrcpFolderInfo;
+pub type AvrcpListItem = ffi::AvrcpListItem;
+pub type AvrcpMediaPlayerInfo = ffi::AvrcpMediaPlayerInfo;
+
 #[derive(Debug)]
 pub enum AvrcpCallbacks {
     AvrcpAbsoluteVolumeEnabled(bool),
     AvrcpAbsoluteVolumeUpdate(i8),
+    /// The remote asked to play an item of a player, from its now playing list or from a folder.
+    AvrcpPlayItem(u16, bool, String),
 }
 
 pub struct AvrcpCallbacksDispatcher {
@@ -48,6 +103,12 @@ cb_variant!(
     i8, {}
 );
 
+cb_variant!(
+    AvrcpCb,
+    avrcp_play_item -> AvrcpCallbacks::AvrcpPlayItem,
+    u16, bool, String, {}
+);
+
 pub struct Avrcp {
     internal: cxx::UniquePtr<ffi::AvrcpIntf>,
     _is_init: bool,
@@ -82,4 +143,19 @@ impl Avrcp {
     pub fn set_volume(&mut self, volume: i8) {
         self.internal.pin_mut().set_volume(volume);
     }
+
+    /// Sets the queue of the addressed player and which of its tracks is playing.
+    pub fn set_now_playing_list(&mut self, current_media_id: String, songs: Vec<AvrcpSongInfo>) {
+        self.internal.pin_mut().set_now_playing_list(current_media_id, songs);
+    }
+
+    /// Sets the players remote devices can browse and the one their commands go to.
+    pub fn set_media_players(&mut self, addressed_player: u16, players: Vec<AvrcpMediaPlayerInfo>) {
+        self.internal.pin_mut().set_media_players(addressed_player, players);
+    }
+
+    /// Sets the contents of a folder. The top folder of a player has an empty media id.
+    pub fn set_folder_items(&mut self, media_id: String, items: Vec<AvrcpListItem>) {
+        self.internal.pin_mut().set_folder_items(media_id, items);
+    }
 }
@@ -20,6 +20,7 @@ pub type BtGattNotifyParams = bindings::btgatt_notify_params_t;
 pub type BtGattReadParams = bindings::btgatt_read_params_t;
 pub type BtGattDbElement = bindings::btgatt_db_element_t;
 pub type BtGattResponse = bindings::btgatt_response_t;
+pub type BtGattValue = bindings::btgatt_value_t;
 pub type BtGattTestParams = bindings::btgatt_test_params_t;
 
 #[cxx::bridge(namespace = bluetooth::topshim::rust)]
@@ -36,72 +37,72 @@ pub mod ffi {
 
     #[derive(Debug, Clone)]
     pub struct RustAdvertisingTrackInfo {
-        scanner_id: u8,
-        filter_index: u8,
-        advertiser_state: u8,
-        advertiser_info_present: u8,
-        advertiser_address: RustRawAddress,
-        advertiser_address_type: u8,
-        tx_power: u8,
-        rssi: i8,
-        timestamp: u16,
-        adv_packet_len: u8,
-        adv_packet: Vec<u8>,
-        scan_response_len: u8,
-        scan_response: Vec<u8>,
+        pub scanner_id: u8,
+        pub filter_index: u8,
+        pub advertiser_state: u8,
+        pub advertiser_info_present: u8,
+        pub advertiser_address: RustRawAddress,
+        pub advertiser_address_type: u8,
+        pub tx_power: u8,
+        pub rssi: i8,
+        pub timestamp: u16,
+        pub adv_packet_len: u8,
+        pub adv_packet: Vec<u8>,
+        pub scan_response_len: u8,
+        pub scan_response: Vec<u8>,
     }
 
     // Original definition exists in C++.
     #[derive(Debug, Clone)]
     pub struct RustGattFilterParam {
-        feat_seln: u16,
-        list_logic_type: u16,
-        filt_logic_type: u8,
-        rssi_high_thres: u8,
-        rssi_low_thres: u8,
-        delay_mode: u8,
-        found_timeout: u16,
-        lost_timeout: u16,
-        found_timeout_count: u8,
-        num_of_tracking_entries: u16,
+        pub feat_seln: u16,
+        pub list_logic_type: u16,
+        pub filt_logic_type: u8,
+        pub rssi_high_thres: u8,
+        pub rssi_low_thres: u8,
+        pub delay_mode: u8,
+        pub found_timeout: u16,
+        pub lost_timeout: u16,
+        pub found_timeout_count: u8,
+        pub num_of_tracking_entries: u16,
     }
 
     // Defined in C++ and needs a translation in shim.
     #[derive(Debug, Clone)]
     pub struct RustApcfCommand {
-        type_: u8,
-        address: RustRawAddress,
-        addr_type: u8,
-        uuid: RustUuid,
-        uuid_mask: RustUuid,
-        name: Vec<u8>,
-        company: u16,
-        company_mask: u16,
-        ad_type: u8,
-        data: Vec<u8>,
-        data_mask: Vec<u8>,
-        irk: [u8; 16],
+        pub type_: u8,
+        pub address: RustRawAddress,
+        pub addr_type: u8,
+        pub uuid: RustUuid,
+        pub uuid_mask: RustUuid,
+        pub name: Vec<u8>,
+        pub company: u16,
+        pub company_mask: u16,
+        pub ad_type: u8,
+        pub data: Vec<u8>,
+        pub data_mask: Vec<u8>,
+        pub irk: [u8; 16],
     }
 
     #[derive(Debug, Clone)]
     pub struct RustAdvertiseParameters {
-        advertising_event_properties: u16,
-        min_interval: u32,
-        max_interval: u32,
-        channel_map: u8,
-        tx_power: i8,
-        primary_advertising_phy: u8,
-        secondary_advertising_phy: u8,
-        scan_request_notification_enable: u8,
-        own_address_type: i8,
-    }
-
-    #[derive(Debug, Clone)]
+        pub advertising_event_properties: u16,
+        pub min_interval: u32,
+        pub max_interval: u32,
+        pub channel_map: u8,
+        pub tx_power: i8,
+        pub primary_advertising_phy: u8,
+        pub secondary_advertising_phy: u8,
+        pub scan_request_notification_enable: u8,
+        pub own_address_type: i8,
+    }
+
+    #[derive(Debug, Clone, Default)]
     pub struct RustPeriodicAdvertisingParameters {
-        enable: u8,
-        min_interval: u16,
-        max_interval: u16,
-        periodic_advertising_properties: u16,
+        pub enable: u8,
+        pub min_interval: u16,
+        pub max_interval: u16,
+        pub periodic_advertising_properties: u16,
     }
 
     unsafe extern "C++" {
@@ -394,6 +395,18 @@ impl From<Uuid> for ffi::RustUuid {
     }
 }
 
+impl From<ffi::RustRawAddress> for RawAddress {
+    fn from(item: ffi::RustRawAddress) -> Self {
+        RawAddress { val: item.address }
+    }
+}
+
+impl From<RawAddress> for ffi::RustRawAddress {
+    fn from(item: RawAddress) -> Self {
+        ffi::RustRawAddress { address: item.val }
+    }
+}
+
 #[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
 #[repr(u32)]
 pub enum GattStatus {
@@ -437,6 +450,9 @@ pub enum GattStatus {
     DupReg = 0x90,      /* 0x90 */
     AlreadyOpen = 0x91, /* 0x91 */
     Cancel = 0x92,      /* 0x92 */
+    /// Not a status of the native stack: the Rust stack reports it for requests the remote
+    /// server didn't answer within the timeout of the client.
+    RequestTimeout = 0x94,
     /* = 0xE0 ~ 0xFC reserved for future use */
 
     /* Client Characteristic Configuration Descriptor Improperly Configured */
@@ -447,6 +463,84 @@ pub enum GattStatus {
     OutOfRange = 0xFF,
 }
 
+impl GattStatus {
+    /// Short description of the status, in the words of the Core and Supplement specifications
+    /// for the ones that are ATT errors.
+    pub fn description(&self) -> &'static str {
+        match self {
+            GattStatus::Success => "success",
+            GattStatus::InvalidHandle => "invalid attribute handle",
+            GattStatus::ReadNotPermit => "read not permitted",
+            GattStatus::WriteNotPermit => "write not permitted",
+            GattStatus::InvalidPdu => "invalid PDU",
+            GattStatus::InsufAuthentication => "insufficient authentication",
+            GattStatus::ReqNotSupported => "request not supported",
+            GattStatus::InvalidOffset => "invalid offset",
+            GattStatus::InsufAuthorization => "insufficient authorization",
+            GattStatus::PrepareQFull => "prepare queue full",
+            GattStatus::NotFound => "attribute not found",
+            GattStatus::NotLong => "attribute not long",
+            GattStatus::InsufKeySize => "encryption key size too short",
+            GattStatus::InvalidAttrLen => "invalid attribute value length",
+            GattStatus::ErrUnlikely => "unlikely error",
+            GattStatus::InsufEncryption => "insufficient encryption",
+            GattStatus::UnsupportGrpType => "unsupported group type",
+            GattStatus::InsufResource => "insufficient resources",
+            GattStatus::DatabaseOutOfSync => "database out of sync",
+            GattStatus::ValueNotAllowed => "value not allowed",
+            GattStatus::IllegalParameter => "illegal parameter",
+            GattStatus::TooShort => "value too short",
+            GattStatus::NoResources => "no resources",
+            GattStatus::InternalError => "internal error",
+            GattStatus::WrongState => "wrong state",
+            GattStatus::DbFull => "database full",
+            GattStatus::Busy => "busy",
+            GattStatus::Error => "error",
+            GattStatus::CmdStarted => "command started",
+            GattStatus::Pending => "pending",
+            GattStatus::AuthFail => "authentication failed",
+            GattStatus::More => "more to come",
+            GattStatus::InvalidCfg => "invalid configuration",
+            GattStatus::ServiceStarted => "service started",
+            GattStatus::EncryptedNoMitm => "encrypted without MITM protection",
+            GattStatus::NotEncrypted => "not encrypted",
+            GattStatus::Congested => "congested",
+            GattStatus::DupReg => "already registered",
+            GattStatus::AlreadyOpen => "already open",
+            GattStatus::Cancel => "cancelled",
+            GattStatus::RequestTimeout => "no response in time",
+            GattStatus::CccCfgErr => "CCC descriptor improperly configured",
+            GattStatus::PrcInProgress => "procedure already in progress",
+            GattStatus::OutOfRange => "value out of range",
+        }
+    }
+}
+
+/// Ranges of ATT error codes, as assigned by the Core specification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttErrorRange {
+    /// Errors defined by ATT itself.
+    Att,
+    /// Errors defined by the higher layer specification of the server, or the application on it.
+    Application,
+    /// Errors common to all profiles, defined in the Core Specification Supplement.
+    CommonProfile,
+    /// Codes reserved for future use.
+    Reserved,
+}
+
+impl AttErrorRange {
+    /// Range of the ATT error `code`.
+    pub fn from_code(code: u8) -> AttErrorRange {
+        match code {
+            0x01..=0x13 => AttErrorRange::Att,
+            0x80..=0x9f => AttErrorRange::Application,
+            0xe0..=0xff => AttErrorRange::CommonProfile,
+            _ => AttErrorRange::Reserved,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum GattClientCallbacks {
     RegisterClient(i32, i32, Uuid),
@@ -1608,6 +1702,9 @@ impl Gatt {
         gatt_client_callbacks_dispatcher: GattClientCallbacksDispatcher,
         gatt_server_callbacks_dispatcher: GattServerCallbacksDispatcher,
         gatt_scanner_callbacks_dispatcher: GattScannerCallbacksDispatcher,
+        gatt_scanner_inband_callbacks_dispatcher: GattScannerInbandCallbacksDispatcher,
+        gatt_adv_callbacks_dispatcher: GattAdvCallbacksDispatcher,
+        gatt_adv_inband_callbacks_dispatcher: GattAdvInbandCallbacksDispatcher,
     ) -> bool {
         // Register dispatcher
         if get_dispatchers()
@@ -1634,6 +1731,28 @@ impl Gatt {
             panic!("Tried to set dispatcher for GattScannerCallbacks but it already existed");
         }
 
+        if get_dispatchers().lock().unwrap().set::<GDScannerInbandCb>(Arc::new(Mutex::new(
+            gatt_scanner_inband_callbacks_dispatcher,
+        ))) {
+            panic!("Tried to set dispatcher for GattScannerInbandCallbacks but it already existed");
+        }
+
+        if get_dispatchers()
+            .lock()
+            .unwrap()
+            .set::<GDAdvCb>(Arc::new(Mutex::new(gatt_adv_callbacks_dispatcher)))
+        {
+            panic!("Tried to set dispatcher for GattAdvCallbacks but it already existed");
+        }
+
+        if get_dispatchers()
+            .lock()
+            .unwrap()
+            .set::<GDAdvInbandCb>(Arc::new(Mutex::new(gatt_adv_inband_callbacks_dispatcher)))
+        {
+            panic!("Tried to set dispatcher for GattAdvInbandCallbacks but it already existed");
+        }
+
         let mut gatt_client_callbacks = Box::new(btgatt_client_callbacks_t {
             register_client_cb: Some(gc_register_client_cb),
             open_cb: Some(gc_open_cb),
@@ -0,0 +1,210 @@
+use crate::btif::{BluetoothInterface, RawAddress};
+use crate::profiles::a2dp::PresentationPosition;
+use crate::topstack::get_dispatchers;
+
+use num_traits::cast::FromPrimitive;
+use std::sync::{Arc, Mutex};
+use topshim_macros::cb_variant;
+
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BthaConnectionState {
+    Disconnected = 0,
+    Connecting,
+    Connected,
+    Disconnecting,
+}
+
+impl From<u32> for BthaConnectionState {
+    fn from(item: u32) -> Self {
+        BthaConnectionState::from_u32(item).unwrap()
+    }
+}
+
+bitflags! {
+    /// Capabilities a hearing aid reports in its ReadOnlyProperties.
+    #[derive(Default)]
+    pub struct HearingAidCapability: u8 {
+        /// Set for the right side of a binaural set, clear for the left.
+        const SIDE_RIGHT = 0b01;
+        /// Set if the device is one of a binaural set.
+        const BINAURAL = 0b10;
+    }
+}
+
+impl From<u8> for HearingAidCapability {
+    fn from(item: u8) -> Self {
+        HearingAidCapability::from_bits_truncate(item)
+    }
+}
+
+#[cxx::bridge(namespace = bluetooth::topshim::rust)]
+pub mod ffi {
+    #[derive(Debug, Copy, Clone)]
+    pub struct RustRawAddress {
+        address: [u8; 6],
+    }
+
+    #[derive(Debug, Default)]
+    pub struct RustHearingAidAudioConfig {
+        pub sample_rate: u32,
+        pub bits_per_sample: u8,
+        pub channel_count: u8,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct RustPresentationPosition {
+        pub remote_delay_report_ns: u64,
+        pub total_bytes_read: u64,
+        pub data_position_sec: i64,
+        pub data_position_nsec: i32,
+    }
+
+    unsafe extern "C++" {
+        include!("hearing_aid/hearing_aid_shim.h");
+
+        type HearingAidIntf;
+
+        unsafe fn GetHearingAidProfile(btif: *const u8) -> UniquePtr<HearingAidIntf>;
+
+        fn init(self: Pin<&mut HearingAidIntf>);
+        fn connect(self: Pin<&mut HearingAidIntf>, bt_addr: RustRawAddress);
+        fn disconnect(self: Pin<&mut HearingAidIntf>, bt_addr: RustRawAddress);
+        fn add_to_accept_list(self: Pin<&mut HearingAidIntf>, bt_addr: RustRawAddress);
+        fn set_volume(self: Pin<&mut HearingAidIntf>, volume: i8);
+        fn remove_device(self: Pin<&mut HearingAidIntf>, bt_addr: RustRawAddress);
+        fn cleanup(self: Pin<&mut HearingAidIntf>);
+
+        fn start_audio_request(self: Pin<&mut HearingAidIntf>) -> bool;
+        fn stop_audio_request(self: Pin<&mut HearingAidIntf>) -> bool;
+        fn get_audio_config(self: &HearingAidIntf) -> RustHearingAidAudioConfig;
+        fn get_presentation_position(self: &HearingAidIntf) -> RustPresentationPosition;
+    }
+    extern "Rust" {
+        fn hearing_aid_connection_state_callback(state: u32, addr: RustRawAddress);
+        fn hearing_aid_device_available_callback(
+            capabilities: u8,
+            hi_sync_id: u64,
+            addr: RustRawAddress,
+        );
+    }
+}
+
+pub type HearingAidAudioConfig = ffi::RustHearingAidAudioConfig;
+
+impl From<RawAddress> for ffi::RustRawAddress {
+    fn from(addr: RawAddress) -> Self {
+        ffi::RustRawAddress { address: addr.val }
+    }
+}
+
+impl Into<RawAddress> for ffi::RustRawAddress {
+    fn into(self) -> RawAddress {
+        RawAddress { val: self.address }
+    }
+}
+
+#[derive(Debug)]
+pub enum HearingAidCallbacks {
+    ConnectionState(BthaConnectionState, RawAddress),
+    /// Capabilities, HiSyncId shared by both sides of a binaural set, and address.
+    DeviceAvailable(HearingAidCapability, u64, RawAddress),
+}
+
+pub struct HearingAidCallbacksDispatcher {
+    pub dispatch: Box<dyn Fn(HearingAidCallbacks) + Send>,
+}
+
+type HearingAidCb = Arc<Mutex<HearingAidCallbacksDispatcher>>;
+
+cb_variant!(
+    HearingAidCb,
+    hearing_aid_connection_state_callback -> HearingAidCallbacks::ConnectionState,
+    u32 -> BthaConnectionState, ffi::RustRawAddress -> RawAddress, {
+        let _1 = _1.into();
+    }
+);
+
+cb_variant!(
+    HearingAidCb,
+    hearing_aid_device_available_callback -> HearingAidCallbacks::DeviceAvailable,
+    u8 -> HearingAidCapability, u64, ffi::RustRawAddress -> RawAddress, {
+        let _2 = _2.into();
+    }
+);
+
+pub struct HearingAid {
+    internal: cxx::UniquePtr<ffi::HearingAidIntf>,
+    _is_init: bool,
+}
+
+// For *const u8 opaque btif
+unsafe impl Send for HearingAid {}
+
+impl HearingAid {
+    pub fn new(intf: &BluetoothInterface) -> HearingAid {
+        let hearing_aid_if: cxx::UniquePtr<ffi::HearingAidIntf>;
+        unsafe {
+            hearing_aid_if = ffi::GetHearingAidProfile(intf.as_raw_ptr());
+        }
+
+        HearingAid { internal: hearing_aid_if, _is_init: false }
+    }
+
+    pub fn initialize(&mut self, callbacks: HearingAidCallbacksDispatcher) -> bool {
+        if get_dispatchers().lock().unwrap().set::<HearingAidCb>(Arc::new(Mutex::new(callbacks))) {
+            panic!("Tried to set dispatcher for Hearing Aid callbacks while it already exists");
+        }
+        self.internal.pin_mut().init();
+        true
+    }
+
+    pub fn connect(&mut self, addr: RawAddress) {
+        self.internal.pin_mut().connect(addr.into());
+    }
+
+    pub fn disconnect(&mut self, addr: RawAddress) {
+        self.internal.pin_mut().disconnect(addr.into());
+    }
+
+    /// Lets the device reconnect by itself, e.g. once it is back in range.
+    pub fn add_to_accept_list(&mut self, addr: RawAddress) {
+        self.internal.pin_mut().add_to_accept_list(addr.into());
+    }
+
+    /// Sets the volume of all connected hearing aids, in the range [-128, 0].
+    pub fn set_volume(&mut self, volume: i8) {
+        self.internal.pin_mut().set_volume(volume);
+    }
+
+    pub fn remove_device(&mut self, addr: RawAddress) {
+        self.internal.pin_mut().remove_device(addr.into());
+    }
+
+    pub fn cleanup(&mut self) -> bool {
+        self.internal.pin_mut().cleanup();
+        true
+    }
+
+    pub fn start_audio_request(&mut self) -> bool {
+        self.internal.pin_mut().start_audio_request()
+    }
+
+    pub fn stop_audio_request(&mut self) -> bool {
+        self.internal.pin_mut().stop_audio_request()
+    }
+
+    pub fn get_audio_config(&self) -> HearingAidAudioConfig {
+        self.internal.get_audio_config()
+    }
+
+    pub fn get_presentation_position(&self) -> PresentationPosition {
+        let position = self.internal.get_presentation_position();
+        PresentationPosition {
+            remote_delay_report_ns: position.remote_delay_report_ns,
+            total_bytes_read: position.total_bytes_read,
+            data_position_sec: position.data_position_sec,
+            data_position_nsec: position.data_position_nsec,
+        }
+    }
+}
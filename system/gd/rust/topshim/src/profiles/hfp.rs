@@ -1,7 +1,7 @@
 use crate::btif::{BluetoothInterface, RawAddress};
 use crate::topstack::get_dispatchers;
 
-use num_traits::cast::FromPrimitive;
+use num_traits::cast::{FromPrimitive, ToPrimitive};
 use std::convert::{TryFrom, TryInto};
 use std::sync::{Arc, Mutex};
 use topshim_macros::cb_variant;
@@ -22,7 +22,7 @@ impl From<u32> for BthfConnectionState {
     }
 }
 
-#[derive(Debug, FromPrimitive, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
 #[repr(u32)]
 pub enum BthfAudioState {
     Disconnected = 0,
@@ -37,6 +37,70 @@ impl From<u32> for BthfAudioState {
     }
 }
 
+/// Codec of a SCO connection, with the ids assigned by the HFP specification.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum HfpCodecId {
+    /// Not negotiated yet, or back to negotiating when used as an override.
+    None = 0,
+    Cvsd = 1,
+    Msbc = 2,
+    Lc3Swb = 3,
+}
+
+impl From<u32> for HfpCodecId {
+    fn from(item: u32) -> Self {
+        HfpCodecId::from_u32(item).unwrap_or(HfpCodecId::None)
+    }
+}
+
+/// State of a call, or of the call being set up when given as the call setup state.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BthfCallState {
+    Active = 0,
+    Held,
+    Dialing,
+    Alerting,
+    Incoming,
+    Waiting,
+    /// No call is being set up.
+    Idle,
+    Disconnected,
+}
+
+impl From<u32> for BthfCallState {
+    fn from(item: u32) -> Self {
+        BthfCallState::from_u32(item).unwrap_or(BthfCallState::Idle)
+    }
+}
+
+impl Default for BthfCallState {
+    fn default() -> Self {
+        BthfCallState::Idle
+    }
+}
+
+/// A voice call the audio gateway lists to devices asking for the current calls.
+#[derive(Clone, Debug, Default)]
+pub struct CallInfo {
+    /// Starts at 1, and stays the same for as long as the call lasts.
+    pub index: u32,
+    pub incoming: bool,
+    pub state: BthfCallState,
+    pub multi_party: bool,
+    pub number: String,
+}
+
+/// Network and battery indicators of the audio gateway. Signal and battery go from 0 to 5.
+#[derive(Clone, Copy, Debug)]
+pub struct DeviceStatus {
+    pub network_available: bool,
+    pub roaming: bool,
+    pub signal: u32,
+    pub battery: u32,
+}
+
 bitflags! {
     #[derive(Default)]
     pub struct HfpCodecCapability: i32 {
@@ -79,12 +143,73 @@ pub mod ffi {
         fn connect_audio(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> i32;
         fn disconnect(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> i32;
         fn disconnect_audio(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> i32;
+        fn set_codec_override(self: Pin<&mut HfpIntf>, codec: u32, bt_addr: RustRawAddress) -> i32;
+        fn get_sco_handle(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> u16;
+        fn set_active_device(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> i32;
+        fn start_voice_recognition(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> i32;
+        fn stop_voice_recognition(self: Pin<&mut HfpIntf>, bt_addr: RustRawAddress) -> i32;
+        fn voice_recognition_response(
+            self: Pin<&mut HfpIntf>,
+            accepted: bool,
+            bt_addr: RustRawAddress,
+        ) -> i32;
+        fn set_inband_ringtone(
+            self: Pin<&mut HfpIntf>,
+            enabled: bool,
+            bt_addr: RustRawAddress,
+        ) -> i32;
+        fn phone_state_change(
+            self: Pin<&mut HfpIntf>,
+            num_active: u32,
+            num_held: u32,
+            call_setup_state: u32,
+            number: String,
+            bt_addr: RustRawAddress,
+        ) -> i32;
+        fn device_status_notification(
+            self: Pin<&mut HfpIntf>,
+            network_available: bool,
+            roaming: bool,
+            signal: u32,
+            battery: u32,
+            bt_addr: RustRawAddress,
+        ) -> i32;
+        fn cind_response(
+            self: Pin<&mut HfpIntf>,
+            network_available: bool,
+            num_active: u32,
+            num_held: u32,
+            call_setup_state: u32,
+            signal: u32,
+            roaming: bool,
+            battery: u32,
+            bt_addr: RustRawAddress,
+        ) -> i32;
+        fn cops_response(
+            self: Pin<&mut HfpIntf>,
+            operator_name: String,
+            bt_addr: RustRawAddress,
+        ) -> i32;
+        fn clcc_response(
+            self: Pin<&mut HfpIntf>,
+            index: u32,
+            incoming: bool,
+            state: u32,
+            multi_party: bool,
+            number: String,
+            bt_addr: RustRawAddress,
+        ) -> i32;
         fn cleanup(self: Pin<&mut HfpIntf>);
 
     }
     extern "Rust" {
         fn hfp_connection_state_callback(state: u32, addr: RustRawAddress);
         fn hfp_audio_state_callback(state: u32, addr: RustRawAddress);
+        fn hfp_codec_callback(codec: u32, addr: RustRawAddress);
+        fn hfp_voice_recognition_callback(started: bool, addr: RustRawAddress);
+        fn hfp_indicators_query_callback(addr: RustRawAddress);
+        fn hfp_operator_query_callback(addr: RustRawAddress);
+        fn hfp_current_calls_query_callback(addr: RustRawAddress);
     }
 }
 
@@ -104,6 +229,18 @@ impl Into<RawAddress> for ffi::RustRawAddress {
 pub enum HfpCallbacks {
     ConnectionState(BthfConnectionState, RawAddress),
     AudioState(BthfAudioState, RawAddress),
+    /// The codec the next SCO connection with the device will use.
+    CodecNegotiated(HfpCodecId, RawAddress),
+    /// The device asked to start (true) or stop (false) voice recognition. It waits for
+    /// `Hfp::voice_recognition_response`.
+    VoiceRecognition(bool, RawAddress),
+    /// The device asked for the current indicators (AT+CIND?), which answer with
+    /// `Hfp::cind_response`.
+    IndicatorsQuery(RawAddress),
+    /// The device asked for the network operator (AT+COPS?). Answer with `Hfp::cops_response`.
+    OperatorQuery(RawAddress),
+    /// The device asked for the current calls (AT+CLCC). Answer with `Hfp::clcc_response`.
+    CurrentCallsQuery(RawAddress),
 }
 
 pub struct HfpCallbacksDispatcher {
@@ -128,6 +265,46 @@ cb_variant!(
     }
 );
 
+cb_variant!(
+    HfpCb,
+    hfp_codec_callback -> HfpCallbacks::CodecNegotiated,
+    u32 -> HfpCodecId, ffi::RustRawAddress -> RawAddress, {
+        let _1 = _1.into();
+    }
+);
+
+cb_variant!(
+    HfpCb,
+    hfp_voice_recognition_callback -> HfpCallbacks::VoiceRecognition,
+    bool, ffi::RustRawAddress -> RawAddress, {
+        let _1 = _1.into();
+    }
+);
+
+cb_variant!(
+    HfpCb,
+    hfp_indicators_query_callback -> HfpCallbacks::IndicatorsQuery,
+    ffi::RustRawAddress -> RawAddress, {
+        let _0 = _0.into();
+    }
+);
+
+cb_variant!(
+    HfpCb,
+    hfp_operator_query_callback -> HfpCallbacks::OperatorQuery,
+    ffi::RustRawAddress -> RawAddress, {
+        let _0 = _0.into();
+    }
+);
+
+cb_variant!(
+    HfpCb,
+    hfp_current_calls_query_callback -> HfpCallbacks::CurrentCallsQuery,
+    ffi::RustRawAddress -> RawAddress, {
+        let _0 = _0.into();
+    }
+);
+
 pub struct Hfp {
     internal: cxx::UniquePtr<ffi::HfpIntf>,
     _is_init: bool,
@@ -170,6 +347,114 @@ impl Hfp {
         self.internal.pin_mut().disconnect_audio(addr.into())
     }
 
+    /// Forces the codec of the next SCO connections with the device, for testing.
+    /// `HfpCodecId::None` goes back to negotiating the best codec.
+    pub fn set_codec_override(&mut self, addr: RawAddress, codec: HfpCodecId) -> i32 {
+        self.internal.pin_mut().set_codec_override(codec.to_u32().unwrap(), addr.into())
+    }
+
+    /// Returns the HCI handle of the SCO connection with the device, or 0xFFFF if there is none.
+    pub fn get_sco_handle(&mut self, addr: RawAddress) -> u16 {
+        self.internal.pin_mut().get_sco_handle(addr.into())
+    }
+
+    /// Makes the device the one whose calls the audio gateway handles.
+    pub fn set_active_device(&mut self, addr: RawAddress) -> i32 {
+        self.internal.pin_mut().set_active_device(addr.into())
+    }
+
+    /// Tells the device voice recognition started on the audio gateway.
+    pub fn start_voice_recognition(&mut self, addr: RawAddress) -> i32 {
+        self.internal.pin_mut().start_voice_recognition(addr.into())
+    }
+
+    pub fn stop_voice_recognition(&mut self, addr: RawAddress) -> i32 {
+        self.internal.pin_mut().stop_voice_recognition(addr.into())
+    }
+
+    /// Answers a `HfpCallbacks::VoiceRecognition` request of the device.
+    pub fn voice_recognition_response(&mut self, addr: RawAddress, accepted: bool) -> i32 {
+        self.internal.pin_mut().voice_recognition_response(accepted, addr.into())
+    }
+
+    /// Tells the device whether the audio gateway sends its own ringtone over SCO, instead of
+    /// having the device play one.
+    pub fn set_inband_ringtone(&mut self, addr: RawAddress, enabled: bool) -> i32 {
+        self.internal.pin_mut().set_inband_ringtone(enabled, addr.into())
+    }
+
+    /// Tells the device how many calls are active and held, and which one is being set up.
+    pub fn phone_state_change(
+        &mut self,
+        addr: RawAddress,
+        num_active: u32,
+        num_held: u32,
+        call_setup_state: BthfCallState,
+        number: &str,
+    ) -> i32 {
+        self.internal.pin_mut().phone_state_change(
+            num_active,
+            num_held,
+            call_setup_state.to_u32().unwrap(),
+            number.to_string(),
+            addr.into(),
+        )
+    }
+
+    /// Updates the network and battery indicators of the device.
+    pub fn device_status_notification(&mut self, addr: RawAddress, status: DeviceStatus) -> i32 {
+        self.internal.pin_mut().device_status_notification(
+            status.network_available,
+            status.roaming,
+            status.signal,
+            status.battery,
+            addr.into(),
+        )
+    }
+
+    pub fn cind_response(
+        &mut self,
+        addr: RawAddress,
+        status: DeviceStatus,
+        num_active: u32,
+        num_held: u32,
+        call_setup_state: BthfCallState,
+    ) -> i32 {
+        self.internal.pin_mut().cind_response(
+            status.network_available,
+            num_active,
+            num_held,
+            call_setup_state.to_u32().unwrap(),
+            status.signal,
+            status.roaming,
+            status.battery,
+            addr.into(),
+        )
+    }
+
+    pub fn cops_response(&mut self, addr: RawAddress, operator_name: &str) -> i32 {
+        self.internal.pin_mut().cops_response(operator_name.to_string(), addr.into())
+    }
+
+    /// Lists the calls to the device, and ends the list.
+    pub fn clcc_response(&mut self, addr: RawAddress, calls: &[CallInfo]) -> i32 {
+        for call in calls {
+            let status = self.internal.pin_mut().clcc_response(
+                call.index,
+                call.incoming,
+                call.state.to_u32().unwrap(),
+                call.multi_party,
+                call.number.clone(),
+                addr.into(),
+            );
+            if status != 0 {
+                return status;
+            }
+        }
+        // Index 0 is the end of the list.
+        self.internal.pin_mut().clcc_response(0, false, 0, false, String::new(), addr.into())
+    }
+
     pub fn cleanup(&mut self) -> bool {
         self.internal.pin_mut().cleanup();
         true
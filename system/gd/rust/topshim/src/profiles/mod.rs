@@ -4,5 +4,8 @@ pub mod a2dp;
 pub mod avrcp;
 pub mod gatt;
 pub mod hfp;
+pub mod hearing_aid;
 pub mod hid_host;
+pub mod pan;
 pub mod sdp;
+pub mod socket;
@@ -0,0 +1,153 @@
+use crate::bindings::root as bindings;
+use crate::btif::{BluetoothInterface, BtStatus, RawAddress, SupportedProfiles};
+use crate::topstack::get_dispatchers;
+use crate::{cast_to_const_ffi_address, ccall, deref_const_ffi_address};
+
+use num_traits::cast::FromPrimitive;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
+use topshim_macros::cb_variant;
+
+/// Local or remote roles of a PAN connection. Roles passed to `Pan::enable` can be or-ed.
+pub const BTPAN_ROLE_NONE: i32 = 0;
+pub const BTPAN_ROLE_PANNAP: i32 = 1;
+pub const BTPAN_ROLE_PANU: i32 = 2;
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BtPanConnectionState {
+    Connected = 0,
+    Connecting,
+    Disconnected,
+    Disconnecting,
+    Unknown = 0xff,
+}
+
+impl From<bindings::btpan_connection_state_t> for BtPanConnectionState {
+    fn from(item: bindings::btpan_connection_state_t) -> Self {
+        BtPanConnectionState::from_u32(item).unwrap_or_else(|| BtPanConnectionState::Unknown)
+    }
+}
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BtPanControlState {
+    Enabled = 0,
+    Disabled,
+    Unknown = 0xff,
+}
+
+impl From<bindings::btpan_control_state_t> for BtPanControlState {
+    fn from(item: bindings::btpan_control_state_t) -> Self {
+        BtPanControlState::from_u32(item).unwrap_or_else(|| BtPanControlState::Unknown)
+    }
+}
+
+fn convert_ifname(ifname: *const c_char) -> String {
+    if ifname.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(ifname) }.to_string_lossy().into_owned()
+}
+
+#[derive(Debug)]
+pub enum PanCallbacks {
+    /// State, local role, status and the name of the network interface.
+    ControlState(BtPanControlState, i32, BtStatus, String),
+    /// State, status, remote address, local role and remote role.
+    ConnectionState(BtPanConnectionState, BtStatus, RawAddress, i32, i32),
+}
+
+pub struct PanCallbacksDispatcher {
+    pub dispatch: Box<dyn Fn(PanCallbacks) + Send>,
+}
+
+// Export the raw address type directly from the bindings
+type FfiAddress = bindings::RawAddress;
+
+type PanCb = Arc<Mutex<PanCallbacksDispatcher>>;
+
+cb_variant!(PanCb, control_state_cb -> PanCallbacks::ControlState,
+bindings::btpan_control_state_t -> BtPanControlState, i32,
+bindings::bt_status_t -> BtStatus, *const c_char, {
+    let _3 = convert_ifname(_3);
+});
+cb_variant!(PanCb, connection_state_cb -> PanCallbacks::ConnectionState,
+bindings::btpan_connection_state_t -> BtPanConnectionState,
+bindings::bt_status_t -> BtStatus, *const FfiAddress, i32, i32, {
+    let _2 = unsafe { deref_const_ffi_address!(_2) };
+});
+
+struct RawPanWrapper {
+    raw: *const bindings::btpan_interface_t,
+}
+
+// Pointers unsafe due to ownership but this is a static pointer so Send is ok
+unsafe impl Send for RawPanWrapper {}
+
+pub struct Pan {
+    internal: RawPanWrapper,
+    is_init: bool,
+    // Keep callback object in memory (underlying code doesn't make copy)
+    callbacks: Option<Box<bindings::btpan_callbacks_t>>,
+}
+
+impl Pan {
+    pub fn new(intf: &BluetoothInterface) -> Pan {
+        let r = intf.get_profile_interface(SupportedProfiles::Pan);
+        Pan {
+            internal: RawPanWrapper { raw: r as *const bindings::btpan_interface_t },
+            is_init: false,
+            callbacks: None,
+        }
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.is_init
+    }
+
+    pub fn initialize(&mut self, callbacks: PanCallbacksDispatcher) -> bool {
+        if get_dispatchers().lock().unwrap().set::<PanCb>(Arc::new(Mutex::new(callbacks))) {
+            panic!("Tried to set dispatcher for PanCallbacks but it already existed");
+        }
+
+        let mut callbacks = Box::new(bindings::btpan_callbacks_t {
+            size: 3 * 8,
+            control_state_cb: Some(control_state_cb),
+            connection_state_cb: Some(connection_state_cb),
+        });
+
+        let rawcb = &mut *callbacks;
+
+        let init = ccall!(self, init, rawcb);
+        self.is_init = BtStatus::from(init) == BtStatus::Success;
+        self.callbacks = Some(callbacks);
+
+        return self.is_init;
+    }
+
+    /// Enables the local `roles`, creating the network interface if needed.
+    pub fn enable(&self, roles: i32) -> BtStatus {
+        BtStatus::from(ccall!(self, enable, roles))
+    }
+
+    pub fn get_local_role(&self) -> i32 {
+        ccall!(self, get_local_role)
+    }
+
+    pub fn connect(&self, addr: &RawAddress, local_role: i32, remote_role: i32) -> BtStatus {
+        let ffi_addr = cast_to_const_ffi_address!(addr as *const RawAddress);
+        BtStatus::from(ccall!(self, connect, ffi_addr, local_role, remote_role))
+    }
+
+    pub fn disconnect(&self, addr: &RawAddress) -> BtStatus {
+        let ffi_addr = cast_to_const_ffi_address!(addr as *const RawAddress);
+        BtStatus::from(ccall!(self, disconnect, ffi_addr))
+    }
+
+    pub fn cleanup(&self) {
+        ccall!(self, cleanup)
+    }
+}
@@ -0,0 +1,241 @@
+//! Bluetooth sockets (RFCOMM and L2CAP) provided by `btsock_interface_t`.
+//!
+//! libbluetooth hands out the local end of a Unix socket pair for each Bluetooth socket. Before
+//! any data flows, it writes the channel number (an `i32`) and then a `sock_connect_signal_t`
+//! once the remote side is connected. For listening sockets, the signal of every incoming
+//! connection also carries the fd of the accepted socket as `SCM_RIGHTS` ancillary data.
+
+use crate::bindings::root as bindings;
+use crate::btif::{BluetoothInterface, BtStatus, FfiAddress, RawAddress, SupportedProfiles, Uuid};
+use crate::{cast_to_const_ffi_address, ccall};
+
+use num_traits::cast::ToPrimitive;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::io::RawFd;
+
+/// Encrypt the connection.
+pub const SOCK_FLAG_ENCRYPT: i32 = 1;
+/// Require authentication before the connection is accepted.
+pub const SOCK_FLAG_AUTH: i32 = 1 << 1;
+/// Don't create an SDP record for a listening socket.
+pub const SOCK_FLAG_NO_SDP: i32 = 1 << 2;
+/// Require MITM protection.
+pub const SOCK_FLAG_AUTH_MITM: i32 = 1 << 3;
+
+// Size of the packed `sock_connect_signal_t`.
+const CONNECT_SIGNAL_SIZE: usize = 20;
+
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq)]
+#[repr(u32)]
+pub enum SocketType {
+    Rfcomm = 1,
+    Sco = 2,
+    L2cap = 3,
+    L2capLe = 4,
+}
+
+impl From<SocketType> for bindings::btsock_type_t {
+    fn from(item: SocketType) -> Self {
+        item.to_u32().unwrap()
+    }
+}
+
+/// Contents of `sock_connect_signal_t`, sent when the remote side of a socket is connected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectionComplete {
+    pub addr: RawAddress,
+    pub channel: i32,
+    pub status: i32,
+    pub max_tx_packet_size: u16,
+    pub max_rx_packet_size: u16,
+}
+
+impl ConnectionComplete {
+    fn from_bytes(bytes: &[u8; CONNECT_SIGNAL_SIZE]) -> Option<ConnectionComplete> {
+        let size = u16::from_ne_bytes([bytes[0], bytes[1]]) as usize;
+        if size != CONNECT_SIGNAL_SIZE {
+            return None;
+        }
+
+        Some(ConnectionComplete {
+            addr: RawAddress::from_bytes(&bytes[2..8])?,
+            channel: i32::from_ne_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            status: i32::from_ne_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            max_tx_packet_size: u16::from_ne_bytes([bytes[16], bytes[17]]),
+            max_rx_packet_size: u16::from_ne_bytes([bytes[18], bytes[19]]),
+        })
+    }
+}
+
+struct RawBtSockWrapper {
+    raw: *const bindings::btsock_interface_t,
+}
+
+// Pointers unsafe due to ownership but this is a static pointer so Send is ok
+unsafe impl Send for RawBtSockWrapper {}
+
+pub struct BtSocket {
+    internal: RawBtSockWrapper,
+}
+
+impl BtSocket {
+    pub fn new(intf: &BluetoothInterface) -> BtSocket {
+        let r = intf.get_profile_interface(SupportedProfiles::Socket);
+        BtSocket { internal: RawBtSockWrapper { raw: r as *const bindings::btsock_interface_t } }
+    }
+
+    /// Listens on `uuid` or `channel`. Unless `SOCK_FLAG_NO_SDP` is given, libbluetooth also
+    /// registers a service record named `service_name`.
+    ///
+    /// Returns the fd to read the channel number and incoming connections from.
+    pub fn listen(
+        &self,
+        sock_type: SocketType,
+        service_name: String,
+        uuid: Option<Uuid>,
+        channel: i32,
+        flags: i32,
+    ) -> (BtStatus, Option<RawFd>) {
+        let name = CString::new(service_name).unwrap_or_default();
+        let uuid_ptr = uuid.as_ref().map_or(std::ptr::null(), |u| u as *const Uuid);
+        let mut sock_fd: i32 = -1;
+
+        let status = BtStatus::from(ccall!(
+            self,
+            listen,
+            bindings::btsock_type_t::from(sock_type),
+            name.as_ptr(),
+            uuid_ptr,
+            channel,
+            &mut sock_fd,
+            flags,
+            0
+        ));
+
+        (status, if sock_fd >= 0 { Some(sock_fd) } else { None })
+    }
+
+    /// Connects to `uuid` (looked up with SDP) or `channel` on the remote device.
+    ///
+    /// Returns the fd of the socket. The channel number and the connection signal have to be
+    /// read from it before any data.
+    pub fn connect(
+        &self,
+        addr: RawAddress,
+        sock_type: SocketType,
+        uuid: Option<Uuid>,
+        channel: i32,
+        flags: i32,
+    ) -> (BtStatus, Option<RawFd>) {
+        let uuid_ptr = uuid.as_ref().map_or(std::ptr::null(), |u| u as *const Uuid);
+        let mut sock_fd: i32 = -1;
+
+        let status = BtStatus::from(ccall!(
+            self,
+            connect,
+            cast_to_const_ffi_address!(&addr as *const RawAddress),
+            bindings::btsock_type_t::from(sock_type),
+            uuid_ptr,
+            channel,
+            &mut sock_fd,
+            flags,
+            0
+        ));
+
+        (status, if sock_fd >= 0 { Some(sock_fd) } else { None })
+    }
+}
+
+/// Reads the channel number libbluetooth sends first on every socket. Blocks until it arrives.
+pub fn read_channel(fd: RawFd) -> io::Result<i32> {
+    let mut buf = [0u8; 4];
+    recv_with_fd(fd, &mut buf)?;
+    Ok(i32::from_ne_bytes(buf))
+}
+
+/// Reads the next connection signal from `fd`. Blocks until it arrives.
+///
+/// For listening sockets, the returned fd is the accepted connection.
+pub fn read_connection_complete(fd: RawFd) -> io::Result<(ConnectionComplete, Option<RawFd>)> {
+    let mut buf = [0u8; CONNECT_SIGNAL_SIZE];
+    let accepted = recv_with_fd(fd, &mut buf)?;
+
+    match ConnectionComplete::from_bytes(&buf) {
+        Some(signal) => Ok((signal, accepted)),
+        None => {
+            if let Some(accepted) = accepted {
+                unsafe { libc::close(accepted) };
+            }
+            Err(io::Error::new(io::ErrorKind::InvalidData, "invalid connection signal"))
+        }
+    }
+}
+
+// Fills `buf` completely from `fd`, returning any fd passed along with the data.
+fn recv_with_fd(fd: RawFd, buf: &mut [u8]) -> io::Result<Option<RawFd>> {
+    let mut received_fd: Option<RawFd> = None;
+    let mut read = 0;
+
+    while read < buf.len() {
+        let mut iov = libc::iovec {
+            iov_base: buf[read..].as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len() - read,
+        };
+        let mut cmsg_buf =
+            vec![0u8; unsafe { libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) } as usize];
+
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = cmsg_buf.len() as _;
+
+        let n = unsafe { libc::recvmsg(fd, &mut msg, 0) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "socket closed"));
+        }
+        read += n as usize;
+
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    received_fd =
+                        Some(std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd));
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+    }
+
+    Ok(received_fd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_complete_from_bytes() {
+        let mut bytes = [0u8; CONNECT_SIGNAL_SIZE];
+        bytes[0..2].copy_from_slice(&(CONNECT_SIGNAL_SIZE as u16).to_ne_bytes());
+        bytes[2..8].copy_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        bytes[8..12].copy_from_slice(&12i32.to_ne_bytes());
+
+        let signal = ConnectionComplete::from_bytes(&bytes).unwrap();
+        assert_eq!(signal.addr, RawAddress::from_string("11:22:33:44:55:66").unwrap());
+        assert_eq!(signal.channel, 12);
+        assert_eq!(signal.status, 0);
+
+        bytes[0] = 0;
+        assert!(ConnectionComplete::from_bytes(&bytes).is_none());
+    }
+}
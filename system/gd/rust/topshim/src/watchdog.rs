@@ -0,0 +1,193 @@
+//! Watchdog for calls into libbluetooth.
+//!
+//! A call into libbluetooth that never returns, or a callback that never arrives, otherwise shows
+//! up only as a daemon that stops responding. Calls made through [`ccall`](crate::ccall) and the
+//! cxx call macros are watched automatically, and expected callbacks can be watched with
+//! [`expect_callback`]. Anything that runs past its deadline is logged once, together with the
+//! stack of the thread that started waiting for it, and the recovery handler is run if one is set.
+
+use log::error;
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a call into libbluetooth may take before it is reported.
+pub const DEFAULT_CALL_DEADLINE: Duration = Duration::from_secs(5);
+
+/// How often the watchdog looks for operations past their deadline.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs when an operation misses its deadline, with the name of the operation.
+pub type RecoveryHandler = Box<dyn Fn(&str) + Send>;
+
+lazy_static! {
+    static ref WATCHDOG: Arc<Mutex<Watchdog>> = {
+        thread::Builder::new()
+            .name(String::from("bt_watchdog"))
+            .spawn(run_watchdog)
+            .expect("Failed to spawn the watchdog thread");
+        Arc::new(Mutex::new(Watchdog::new()))
+    };
+}
+
+/// An operation being waited on.
+struct Watched {
+    name: String,
+    started: Instant,
+    deadline: Duration,
+    thread: String,
+    backtrace: Backtrace,
+    reported: bool,
+}
+
+impl Watched {
+    fn new(name: String, deadline: Duration) -> Self {
+        Watched {
+            name,
+            started: Instant::now(),
+            deadline,
+            thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+            // Only captured if backtraces are enabled through RUST_BACKTRACE or
+            // RUST_LIB_BACKTRACE, so watching stays cheap otherwise.
+            backtrace: Backtrace::capture(),
+            reported: false,
+        }
+    }
+
+    /// Marks the operation reported and returns a description of it, if it is newly overdue.
+    fn check(&mut self, now: Instant) -> Option<String> {
+        let elapsed = now.saturating_duration_since(self.started);
+        if self.reported || elapsed < self.deadline {
+            return None;
+        }
+
+        self.reported = true;
+        Some(format!(
+            "{} has been pending for {:?} (deadline {:?}), started on thread {}:\n{}",
+            self.name, elapsed, self.deadline, self.thread, self.backtrace
+        ))
+    }
+}
+
+struct Watchdog {
+    last_call_id: u64,
+    calls: HashMap<u64, Watched>,
+    callbacks: HashMap<&'static str, Watched>,
+    recovery: Option<Arc<RecoveryHandler>>,
+}
+
+impl Watchdog {
+    fn new() -> Self {
+        Watchdog {
+            last_call_id: 0,
+            calls: HashMap::new(),
+            callbacks: HashMap::new(),
+            recovery: None,
+        }
+    }
+
+    /// Returns the names and descriptions of operations that are newly past their deadline.
+    fn find_overdue(&mut self, now: Instant) -> Vec<(String, String)> {
+        self.calls
+            .values_mut()
+            .chain(self.callbacks.values_mut())
+            .filter_map(|watched| watched.check(now).map(|report| (watched.name.clone(), report)))
+            .collect()
+    }
+}
+
+fn run_watchdog() {
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        let (overdue, recovery) = {
+            let mut watchdog = WATCHDOG.lock().unwrap();
+            (watchdog.find_overdue(Instant::now()), watchdog.recovery.clone())
+        };
+
+        // The recovery handler runs without the lock held so it can use the watchdog itself.
+        for (name, report) in overdue {
+            error!("Watchdog: {}", report);
+            if let Some(recovery) = &recovery {
+                recovery(&name);
+            }
+        }
+    }
+}
+
+/// Watches a call for as long as the returned guard is alive.
+pub struct CallGuard {
+    id: u64,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        WATCHDOG.lock().unwrap().calls.remove(&self.id);
+    }
+}
+
+/// Starts watching a call named `name` that should return within `deadline`.
+pub fn watch_call(name: &str, deadline: Duration) -> CallGuard {
+    let mut watchdog = WATCHDOG.lock().unwrap();
+    watchdog.last_call_id += 1;
+    let id = watchdog.last_call_id;
+    watchdog.calls.insert(id, Watched::new(name.to_string(), deadline));
+
+    CallGuard { id }
+}
+
+/// Starts waiting for the callback `name`, which should arrive within `deadline`. Expecting a
+/// callback that is already being waited for restarts its deadline.
+pub fn expect_callback(name: &'static str, deadline: Duration) {
+    WATCHDOG.lock().unwrap().callbacks.insert(name, Watched::new(name.to_string(), deadline));
+}
+
+/// Stops waiting for the callback `name`.
+pub fn callback_received(name: &'static str) {
+    WATCHDOG.lock().unwrap().callbacks.remove(name);
+}
+
+/// Sets what to do when an operation misses its deadline, e.g. restarting the adapter. Without a
+/// handler, overdue operations are only logged.
+pub fn set_recovery_handler(handler: RecoveryHandler) {
+    WATCHDOG.lock().unwrap().recovery = Some(Arc::new(handler));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overdue_reported_once() {
+        let mut watchdog = Watchdog::new();
+        watchdog.calls.insert(1, Watched::new(String::from("enable"), Duration::from_secs(5)));
+        watchdog.callbacks.insert(
+            "adapter_state_changed",
+            Watched::new(String::from("adapter_state_changed"), Duration::from_secs(10)),
+        );
+
+        let start = Instant::now();
+        assert!(watchdog.find_overdue(start).is_empty());
+
+        let overdue = watchdog.find_overdue(start + Duration::from_secs(6));
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue[0].0, "enable");
+
+        // Only the callback is newly overdue, the call was already reported.
+        let overdue = watchdog.find_overdue(start + Duration::from_secs(11));
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue[0].0, "adapter_state_changed");
+    }
+
+    #[test]
+    fn test_call_guard_stops_watching() {
+        let guard = watch_call("test_call_guard_stops_watching", Duration::from_secs(5));
+        let id = guard.id;
+        assert!(WATCHDOG.lock().unwrap().calls.contains_key(&id));
+
+        drop(guard);
+        assert!(!WATCHDOG.lock().unwrap().calls.contains_key(&id));
+    }
+}
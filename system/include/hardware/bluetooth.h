@@ -477,6 +477,35 @@ typedef void (*acl_state_changed_callback)(bt_status_t status,
                                            int transport_link_type,
                                            bt_hci_error_code_t hci_reason);
 
+/** Bluetooth ACL role changed callback */
+/* Invoked when the local role on a BR/EDR link changes, or a requested role
+ * switch fails. |new_role| is the local role: 0 for central, 1 for
+ * peripheral. */
+typedef void (*acl_role_changed_callback)(bt_status_t status,
+                                          RawAddress* remote_bd_addr,
+                                          uint8_t new_role);
+
+/** Bluetooth encryption key size violation callback */
+/* Invoked when a link was to be encrypted, or an LE device paired, with a key
+ * shorter than the minimum key size. The link is disconnected, or the pairing
+ * fails. |key_size| is in octets. */
+typedef void (*key_size_violation_callback)(RawAddress* remote_bd_addr,
+                                            int transport, uint8_t key_size);
+
+/** Bluetooth encryption change callback */
+/* Invoked when the encryption of a link is turned on or off, or its key is
+ * refreshed, including when either fails with |hci_status|. */
+typedef void (*encryption_change_callback)(RawAddress* remote_bd_addr,
+                                           int transport,
+                                           bt_hci_error_code_t hci_status,
+                                           bool encrypted, bool key_refresh);
+
+/** Bluetooth authentication failure callback */
+/* Invoked when a remote device fails to authenticate, whether it is pairing or
+ * already bonded. */
+typedef void (*auth_failure_callback)(RawAddress* remote_bd_addr,
+                                      bt_hci_error_code_t hci_reason);
+
 /** Bluetooth link quality report callback */
 typedef void (*link_quality_report_callback)(
     uint64_t timestamp, int report_id, int rssi, int snr,
@@ -549,6 +578,10 @@ typedef struct {
   generate_local_oob_data_callback generate_local_oob_data_cb;
   switch_buffer_size_callback switch_buffer_size_cb;
   switch_codec_callback switch_codec_cb;
+  acl_role_changed_callback acl_role_changed_cb;
+  key_size_violation_callback key_size_violation_cb;
+  encryption_change_callback encryption_change_cb;
+  auth_failure_callback auth_failure_cb;
 } bt_callbacks_t;
 
 typedef void (*alarm_cb)(void* data);
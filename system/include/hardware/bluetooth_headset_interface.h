@@ -254,6 +254,18 @@ class Interface {
    * @param active_device_addr remote device address
    */
   virtual bt_status_t SetActiveDevice(RawAddress* active_device_addr) = 0;
+
+  /**
+   * Force the codec used for the next SCO connections to a headset, instead of
+   * the best codec supported by both sides. Meant for testing.
+   *
+   * @param codec BTHF_WBS_YES for mSBC, BTHF_WBS_NO for CVSD, BTHF_WBS_NONE to
+   *              go back to negotiating the codec
+   * @param bd_addr remote device address
+   * @return BT_STATUS_SUCCESS on success
+   */
+  virtual bt_status_t SetCodecOverride(bthf_wbs_config_t codec,
+                                       RawAddress* bd_addr) = 0;
 };
 
 }  // namespace headset
@@ -65,6 +65,15 @@ void bluetooth::shim::ACL_WriteData(uint16_t handle, BT_HDR* p_buf) {
   osi_free(p_buf);
 }
 
+namespace {
+// Set by ACL_SetLeRpaTimeout, zero when the default rotation times are used.
+std::chrono::seconds le_rpa_timeout{0};
+}  // namespace
+
+void bluetooth::shim::ACL_SetLeRpaTimeout(std::chrono::seconds timeout) {
+  le_rpa_timeout = timeout;
+}
+
 void bluetooth::shim::ACL_ConfigureLePrivacy(bool is_le_privacy_enabled) {
   hci::LeAddressManager::AddressPolicy address_policy =
       is_le_privacy_enabled
@@ -73,8 +82,14 @@ void bluetooth::shim::ACL_ConfigureLePrivacy(bool is_le_privacy_enabled) {
   hci::AddressWithType empty_address_with_type(
       hci::Address{}, hci::AddressType::RANDOM_DEVICE_ADDRESS);
   /* 7 minutes minimum, 15 minutes maximum for random address refreshing */
-  auto minimum_rotation_time = std::chrono::minutes(7);
-  auto maximum_rotation_time = std::chrono::minutes(15);
+  std::chrono::milliseconds minimum_rotation_time = std::chrono::minutes(7);
+  std::chrono::milliseconds maximum_rotation_time = std::chrono::minutes(15);
+  if (le_rpa_timeout.count() > 0) {
+    /* The address manager rotates at a random point between the two, which
+     * must differ */
+    maximum_rotation_time = le_rpa_timeout;
+    minimum_rotation_time = maximum_rotation_time / 2;
+  }
 
   Stack::GetInstance()
       ->GetStackManager()
@@ -84,6 +99,28 @@ void bluetooth::shim::ACL_ConfigureLePrivacy(bool is_le_privacy_enabled) {
           maximum_rotation_time);
 }
 
+void bluetooth::shim::ACL_ConfigureLeStaticAddress(
+    const RawAddress& static_address) {
+  /* Rotation times are ignored for static addresses */
+  Stack::GetInstance()
+      ->GetStackManager()
+      ->GetInstance<bluetooth::hci::AclManager>()
+      ->SetPrivacyPolicyForInitiatorAddress(
+          hci::LeAddressManager::AddressPolicy::USE_STATIC_ADDRESS,
+          hci::AddressWithType(ToGdAddress(static_address),
+                               hci::AddressType::RANDOM_DEVICE_ADDRESS),
+          std::chrono::minutes(7), std::chrono::minutes(15));
+}
+
+tBLE_ADDR_TYPE bluetooth::shim::ACL_GetLeOwnAddressType() {
+  auto address = Stack::GetInstance()
+                     ->GetStackManager()
+                     ->GetInstance<bluetooth::hci::AclManager>()
+                     ->GetLeAddressManager()
+                     ->GetCurrentAddress();
+  return static_cast<tBLE_ADDR_TYPE>(address.GetAddressType());
+}
+
 void bluetooth::shim::ACL_Disconnect(uint16_t handle, bool is_classic,
                                      tHCI_STATUS reason, std::string comment) {
   (is_classic)
@@ -16,6 +16,8 @@
 
 #pragma once
 
+#include <chrono>
+
 #include "stack/include/bt_hdr.h"
 #include "stack/include/bt_types.h"
 #include "stack/include/hci_error_code.h"
@@ -35,6 +37,14 @@ void ACL_Disconnect(uint16_t handle, bool is_classic, tHCI_STATUS reason,
                     std::string comment);
 void ACL_WriteData(uint16_t handle, BT_HDR* p_buf);
 void ACL_ConfigureLePrivacy(bool is_le_privacy_enabled);
+// Uses |static_address| as the LE address instead of the public or a private one. Like the
+// privacy configuration, this can only be done once, before any LE activity.
+void ACL_ConfigureLeStaticAddress(const RawAddress& static_address);
+// Sets how often the resolvable private address is rotated once privacy is configured. Zero
+// restores the default of 7 to 15 minutes.
+void ACL_SetLeRpaTimeout(std::chrono::seconds timeout);
+// Type of the address we currently use on LE.
+tBLE_ADDR_TYPE ACL_GetLeOwnAddressType();
 void ACL_Shutdown();
 void ACL_IgnoreAllLeConnections();
 
@@ -265,6 +265,10 @@ bt_callbacks_t bt_callbacks = {
     nullptr /* generate_local_oob_data_cb */,
     SwitchBufferSizeCallback,
     SwitchCodecCallback,
+    nullptr, /* acl_role_changed_cb */
+    nullptr, /* key_size_violation_cb */
+    nullptr, /* encryption_change_cb */
+    nullptr, /* auth_failure_cb */
 };
 
 bt_os_callouts_t bt_os_callouts = {sizeof(bt_os_callouts_t),
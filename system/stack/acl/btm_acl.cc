@@ -108,6 +108,8 @@ struct RoleChangeView {
 namespace {
 StackAclBtmAcl internal_;
 std::unique_ptr<RoleChangeView> delayed_role_change_ = nullptr;
+tBTM_BLE_TX_POWER_REPORT_CB* tx_power_report_cb_ = nullptr;
+tBTM_BLE_PATH_LOSS_CB* path_loss_cb_ = nullptr;
 const bluetooth::legacy::hci::Interface& GetLegacyHciInterface() {
   return bluetooth::legacy::hci::GetInterface();
 }
@@ -1616,6 +1618,15 @@ uint8_t* BTM_ReadRemoteFeatures(const RawAddress& addr) {
   return (p->peer_lmp_feature_pages[0]);
 }
 
+const uint8_t* BTM_ReadRemoteLeFeatures(const RawAddress& addr) {
+  const tACL_CONN* p = internal_.btm_bda_to_acl(addr, BT_TRANSPORT_LE);
+  if (p == nullptr || !p->peer_le_features_valid) {
+    return nullptr;
+  }
+
+  return p->peer_le_features;
+}
+
 /*******************************************************************************
  *
  * Function         BTM_ReadRSSI
@@ -1810,6 +1821,93 @@ void btm_read_tx_power_complete(uint8_t* p, bool is_ble) {
   }
 }
 
+void BTM_BleSetPowerReportCallbacks(tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb,
+                                    tBTM_BLE_PATH_LOSS_CB* p_path_loss_cb) {
+  tx_power_report_cb_ = p_tx_power_cb;
+  path_loss_cb_ = p_path_loss_cb;
+}
+
+/*******************************************************************************
+ *
+ * Function         btm_ble_transmit_power_reporting_evt
+ *
+ * Description      This function is called when the controller reports a
+ *                  change of the local or remote TX power of an LE
+ *                  connection, or the completion of a remote TX power read.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void btm_ble_transmit_power_reporting_evt(uint8_t* p, uint8_t evt_len) {
+  uint8_t status, reason, phy, flags;
+  uint16_t handle;
+  int8_t tx_power, delta;
+
+  if (evt_len < 8) {
+    LOG_WARN("Transmit power reporting event too short, length:%u", evt_len);
+    return;
+  }
+
+  STREAM_TO_UINT8(status, p);
+  STREAM_TO_UINT16(handle, p);
+  STREAM_TO_UINT8(reason, p);
+  STREAM_TO_UINT8(phy, p);
+  STREAM_TO_INT8(tx_power, p);
+  STREAM_TO_UINT8(flags, p);
+  STREAM_TO_INT8(delta, p);
+
+  if (status != HCI_SUCCESS) {
+    LOG_WARN("Transmit power reporting failed:%s",
+             hci_error_code_text(static_cast<tHCI_STATUS>(status)).c_str());
+    return;
+  }
+
+  tACL_CONN* p_acl = internal_.acl_get_connection_from_handle(handle);
+  if (p_acl == nullptr) {
+    LOG_WARN("Unable to find active acl");
+    return;
+  }
+
+  if (tx_power_report_cb_ != nullptr) {
+    (*tx_power_report_cb_)(p_acl->remote_addr, reason, phy, tx_power, flags,
+                           delta);
+  }
+}
+
+/*******************************************************************************
+ *
+ * Function         btm_ble_path_loss_threshold_evt
+ *
+ * Description      This function is called when the path loss of an LE
+ *                  connection crosses into another zone.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void btm_ble_path_loss_threshold_evt(uint8_t* p, uint8_t evt_len) {
+  uint16_t handle;
+  uint8_t path_loss, zone;
+
+  if (evt_len < 4) {
+    LOG_WARN("Path loss threshold event too short, length:%u", evt_len);
+    return;
+  }
+
+  STREAM_TO_UINT16(handle, p);
+  STREAM_TO_UINT8(path_loss, p);
+  STREAM_TO_UINT8(zone, p);
+
+  tACL_CONN* p_acl = internal_.acl_get_connection_from_handle(handle);
+  if (p_acl == nullptr) {
+    LOG_WARN("Unable to find active acl");
+    return;
+  }
+
+  if (path_loss_cb_ != nullptr) {
+    (*path_loss_cb_)(p_acl->remote_addr, path_loss, zone);
+  }
+}
+
 /*******************************************************************************
  *
  * Function         btm_read_rssi_timeout
@@ -809,6 +809,44 @@ bool BTM_BleConfigPrivacy(bool privacy_mode) {
   return true;
 }
 
+/*******************************************************************************
+ *
+ * Function         BTM_BleConfigStaticAddress
+ *
+ * Description      This function is called to use a static random address
+ *                  instead of the public one, with privacy disabled.
+ *
+ * Parameters       static_addr: address to use; the two most significant bits
+ *                  must be set.
+ *
+ * Returns          bool    static address set success; otherwise failed.
+ *
+ ******************************************************************************/
+bool BTM_BleConfigStaticAddress(const RawAddress& static_addr) {
+  tBTM_BLE_CB* p_cb = &btm_cb.ble_ctr_cb;
+
+  BTM_TRACE_WARNING("%s %s", __func__, static_addr.ToString().c_str());
+
+  if (!controller_get_interface()->supports_ble()) return false;
+  if ((static_addr.address[0] & BLE_RESOLVE_ADDR_MASK) !=
+      BLE_RESOLVE_ADDR_MASK) {
+    LOG_ERROR("%s is not a static random address",
+              static_addr.ToString().c_str());
+    return false;
+  }
+
+  p_cb->addr_mgnt_cb.own_addr_type = BLE_ADDR_RANDOM;
+  p_cb->addr_mgnt_cb.private_addr = static_addr;
+  p_cb->privacy_mode = BTM_PRIVACY_NONE;
+
+  tGAP_BLE_ATTR_VALUE gap_ble_attr_value;
+  gap_ble_attr_value.addr_resolution = 0;
+  GAP_BleAttrDBUpdate(GATT_UUID_GAP_CENTRAL_ADDR_RESOL, &gap_ble_attr_value);
+
+  bluetooth::shim::ACL_ConfigureLeStaticAddress(static_addr);
+  return true;
+}
+
 /*******************************************************************************
  *
  * Function          BTM_BleMaxMultiAdvInstanceCount
@@ -262,6 +262,7 @@ typedef struct tBTM_CB {
   uint64_t collision_start_time{0};
   uint32_t dev_rec_count{0}; /* Counter used for device record timestamp */
   uint8_t security_mode{0};
+  uint8_t min_enc_key_size{0}; /* shortest key size accepted on links */
   bool pairing_disabled{false};
   bool security_mode_changed{false}; /* mode changed during bonding */
   bool pin_type_changed{false};      /* pin type changed during bonding */
@@ -340,6 +341,7 @@ typedef struct tBTM_CB {
     trace_level = BT_TRACE_LEVEL_NONE; /* No traces */
 #endif
     security_mode = initial_security_mode;
+    min_enc_key_size = BTM_SEC_MIN_ENC_KEY_SIZE;
     pairing_bda = RawAddress::kAny;
     sec_dev_rec = list_new(osi_free);
 
@@ -1027,6 +1027,28 @@ const RawAddress* BTM_ReadScoBdAddr(uint16_t sco_inx) {
     return (NULL);
 }
 
+/*******************************************************************************
+ *
+ * Function         BTM_ReadScoHandle
+ *
+ * Description      This function reads the HCI handle of the SCO connection
+ *                  to a remote device.
+ *
+ * Returns          HCI handle, or HCI_INVALID_HANDLE if there is no SCO
+ *                  connected to the device
+ *
+ ******************************************************************************/
+uint16_t BTM_ReadScoHandle(const RawAddress& bda) {
+  tSCO_CONN* p = &btm_cb.sco_cb.sco_db[0];
+  for (uint16_t xx = 0; xx < BTM_MAX_SCO_LINKS; xx++, p++) {
+    if (p->state == SCO_ST_CONNECTED && p->rem_bd_known &&
+        p->esco.data.bd_addr == bda) {
+      return p->hci_handle;
+    }
+  }
+  return HCI_INVALID_HANDLE;
+}
+
 /*******************************************************************************
  *
  * Function         BTM_SetEScoMode
@@ -32,6 +32,7 @@
 #include <frameworks/proto_logging/stats/enums/bluetooth/hci/enums.pb.h>
 #include <string.h>
 
+#include "bta/include/bta_dm_acl.h"
 #include "btif/include/btif_storage.h"
 #include "common/metrics.h"
 #include "common/time_util.h"
@@ -370,6 +371,71 @@ void BTM_SetPinType(uint8_t pin_type, PIN_CODE pin_code, uint8_t pin_code_len) {
   memcpy(btm_cb.cfg.pin_code, pin_code, pin_code_len);
 }
 
+/*******************************************************************************
+ *
+ * Function         BTM_SetSecureConnectionsOnly
+ *
+ * Description      Enable or disable Secure Connections Only mode.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void BTM_SetSecureConnectionsOnly(bool enabled) {
+  BTM_TRACE_API("%s: enabled %d, sec mode %d", __func__, enabled,
+                btm_cb.security_mode);
+
+  if (enabled) {
+    if (!controller_get_interface()->supports_secure_connections()) {
+      BTM_TRACE_WARNING("%s: Secure Connections not supported", __func__);
+      return;
+    }
+    btm_cb.security_mode = BTM_SEC_MODE_SC;
+  } else if (btm_cb.security_mode == BTM_SEC_MODE_SC) {
+    btm_cb.security_mode = BTM_SEC_MODE_SP;
+  }
+}
+
+/*******************************************************************************
+ *
+ * Function         BTM_SetMinEncryptionKeySize
+ *
+ * Description      Set the shortest encryption key size accepted on links.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void BTM_SetMinEncryptionKeySize(uint8_t key_size) {
+  BTM_TRACE_API("%s: key size %d", __func__, key_size);
+
+  if (key_size < BTM_SEC_MIN_ENC_KEY_SIZE || key_size > 16) {
+    BTM_TRACE_WARNING("%s: invalid key size %d", __func__, key_size);
+    return;
+  }
+  btm_cb.min_enc_key_size = key_size;
+}
+
+/*******************************************************************************
+ *
+ * Function         BTM_GetEncryptionKeySize
+ *
+ * Description      Get the size of the key a link is encrypted with.
+ *
+ * Returns          The key size in octets, or 0 if the link isn't encrypted.
+ *
+ ******************************************************************************/
+uint8_t BTM_GetEncryptionKeySize(const RawAddress& bd_addr,
+                                 tBT_TRANSPORT transport) {
+  tBTM_SEC_DEV_REC* p_dev_rec = btm_find_dev(bd_addr);
+  if (p_dev_rec == nullptr || !BTM_IsEncrypted(bd_addr, transport)) {
+    return 0;
+  }
+
+  if (transport == BT_TRANSPORT_LE) {
+    return p_dev_rec->enc_key_size;
+  }
+  return p_dev_rec->classic_enc_key_size;
+}
+
 #define BTM_NO_AVAIL_SEC_SERVICES ((uint16_t)0xffff)
 
 /*******************************************************************************
@@ -3245,6 +3311,34 @@ void btm_sec_auth_complete(uint16_t handle, tHCI_STATUS status) {
     btm_sec_dev_rec_cback_event(p_dev_rec, btm_status, false);
 }
 
+/*******************************************************************************
+ *
+ * Function         btm_sec_check_encryption_key_size
+ *
+ * Description      This function checks the size of an encryption key against
+ *                  the minimum key size, and reports keys that are too short.
+ *
+ * Returns          true if the key size is allowed
+ *
+ ******************************************************************************/
+bool btm_sec_check_encryption_key_size(const RawAddress& bd_addr,
+                                       tBT_TRANSPORT transport,
+                                       uint8_t key_size) {
+  if (key_size < btm_cb.min_enc_key_size) {
+    LOG_WARN("Encryption key too short device:%s transport:%s key_size:%d",
+             PRIVATE_ADDRESS(bd_addr), bt_transport_text(transport).c_str(),
+             key_size);
+    BTA_dm_report_key_size_violation(bd_addr, transport, key_size);
+    return false;
+  }
+
+  if (transport == BT_TRANSPORT_BR_EDR) {
+    tBTM_SEC_DEV_REC* p_dev_rec = btm_find_dev(bd_addr);
+    if (p_dev_rec != nullptr) p_dev_rec->classic_enc_key_size = key_size;
+  }
+  return true;
+}
+
 /*******************************************************************************
  *
  * Function         btm_sec_encrypt_change
@@ -3832,6 +3926,7 @@ void btm_sec_disconnected(uint16_t handle, tHCI_REASON reason,
     p_dev_rec->sec_flags &=
         ~(BTM_SEC_AUTHENTICATED | BTM_SEC_ENCRYPTED | BTM_SEC_ROLE_SWITCHED |
           BTM_SEC_16_DIGIT_PIN_AUTHED);
+    p_dev_rec->classic_enc_key_size = 0;
 
     // Remove temporary key.
     if (p_dev_rec->bond_type == tBTM_SEC_DEV_REC::BOND_TYPE_TEMPORARY)
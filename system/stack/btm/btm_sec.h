@@ -109,6 +109,46 @@ bool BTM_IsAuthenticated(const RawAddress& bd_addr, tBT_TRANSPORT transport);
  ******************************************************************************/
 void BTM_SetPinType(uint8_t pin_type, PIN_CODE pin_code, uint8_t pin_code_len);
 
+/*******************************************************************************
+ *
+ * Function         BTM_SetSecureConnectionsOnly
+ *
+ * Description      Enable or disable Secure Connections Only mode. While it is
+ *                  enabled, new pairings on both BR/EDR and LE must use Secure
+ *                  Connections and legacy pairing requests are rejected. The
+ *                  controller must support Secure Connections.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void BTM_SetSecureConnectionsOnly(bool enabled);
+
+/*******************************************************************************
+ *
+ * Function         BTM_SetMinEncryptionKeySize
+ *
+ * Description      Set the shortest encryption key size, in octets, accepted
+ *                  on BR/EDR links and in LE pairings. Links encrypted with a
+ *                  shorter key are disconnected. Values below
+ *                  BTM_SEC_MIN_ENC_KEY_SIZE or above 16 are ignored.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void BTM_SetMinEncryptionKeySize(uint8_t key_size);
+
+/*******************************************************************************
+ *
+ * Function         BTM_GetEncryptionKeySize
+ *
+ * Description      Get the size of the key a link is encrypted with.
+ *
+ * Returns          The key size in octets, or 0 if the link isn't encrypted.
+ *
+ ******************************************************************************/
+uint8_t BTM_GetEncryptionKeySize(const RawAddress& bd_addr,
+                                 tBT_TRANSPORT transport);
+
 /*******************************************************************************
  *
  * Function         BTM_SetSecurityLevel
@@ -609,6 +649,22 @@ void btm_sec_auth_complete(uint16_t handle, tHCI_STATUS status);
 void btm_sec_encrypt_change(uint16_t handle, tHCI_STATUS status,
                             uint8_t encr_enable);
 
+/*******************************************************************************
+ *
+ * Function         btm_sec_check_encryption_key_size
+ *
+ * Description      This function is called with the size of the key a link is
+ *                  encrypted with, or the longest key an LE peer can pair
+ *                  with, to check it against the minimum key size. Keys that
+ *                  are too short are reported to the upper layer.
+ *
+ * Returns          true if the key size is allowed
+ *
+ ******************************************************************************/
+bool btm_sec_check_encryption_key_size(const RawAddress& bd_addr,
+                                       tBT_TRANSPORT transport,
+                                       uint8_t key_size);
+
 /*******************************************************************************
  *
  * Function         btm_sec_connected
@@ -406,6 +406,7 @@ struct tBTM_SEC_DEV_REC {
 
   uint8_t enc_key_size;    /* current link encryption key size */
   uint8_t get_encryption_key_size() const { return enc_key_size; }
+  uint8_t classic_enc_key_size = 0; /* key size of the BR/EDR link */
 
   tBT_DEVICE_TYPE device_type;
   bool is_device_type_br_edr() const {
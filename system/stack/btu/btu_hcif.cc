@@ -33,12 +33,14 @@
 
 #include <cstdint>
 
+#include "bta/include/bta_dm_acl.h"
 #include "btif/include/btif_config.h"
 #include "common/metrics.h"
 #include "device/include/controller.h"
 #include "main/shim/hci_layer.h"
 #include "osi/include/allocator.h"
 #include "osi/include/log.h"
+#include "stack/include/acl_api.h"
 #include "stack/include/acl_hci_link_interface.h"
 #include "stack/include/ble_acl_interface.h"
 #include "stack/include/ble_hci_link_interface.h"
@@ -374,6 +376,14 @@ void btu_hcif_process_event(UNUSED_ATTR uint8_t controller_id,
           btm_acl_process_sca_cmpl_pkt(ble_evt_len, p);
           break;
 
+        case HCI_BLE_PATH_LOSS_THRESHOLD_EVT:
+          btm_ble_path_loss_threshold_evt(p, ble_evt_len);
+          break;
+
+        case HCI_BLE_TRANSMIT_POWER_REPORTING_EVT:
+          btm_ble_transmit_power_reporting_evt(p, ble_evt_len);
+          break;
+
         case HCI_BLE_PERIODIC_ADV_SYNC_EST_EVT:
           btm_ble_process_periodic_adv_sync_est_evt(
               ble_evt_len, const_cast<const uint8_t*>(p));
@@ -1006,7 +1016,17 @@ static void btu_hcif_rmt_name_request_comp_evt(const uint8_t* p,
   btm_sec_rmt_name_request_complete(&bd_addr, p, to_hci_status_code(status));
 }
 
-constexpr uint8_t MIN_KEY_SIZE = 7;
+/* Reports the outcome of an encryption change or key refresh upwards, once the
+ * key size check is done. */
+static void btu_hcif_report_encryption_change(uint16_t handle, uint8_t status,
+                                              uint8_t encr_enable,
+                                              bool key_refresh) {
+  tBT_TRANSPORT transport =
+      BTM_IsBleConnection(handle) ? BT_TRANSPORT_LE : BT_TRANSPORT_BR_EDR;
+  BTA_dm_report_encryption_change(acl_address_from_handle(handle), transport,
+                                  static_cast<tHCI_STATUS>(status),
+                                  encr_enable != 0, key_refresh);
+}
 
 static void read_encryption_key_size_complete_after_encryption_change(uint8_t status, uint16_t handle,
                                                                       uint8_t key_size) {
@@ -1026,7 +1046,8 @@ static void read_encryption_key_size_complete_after_encryption_change(uint8_t st
     return;
   }
 
-  if (key_size < MIN_KEY_SIZE) {
+  if (!btm_sec_check_encryption_key_size(acl_address_from_handle(handle),
+                                         BT_TRANSPORT_BR_EDR, key_size)) {
     android_errorWriteLog(0x534e4554, "124301137");
     LOG(ERROR) << __func__ << " encryption key too short, disconnecting. handle: " << loghex(handle)
                << " key_size: " << +key_size;
@@ -1043,6 +1064,8 @@ static void read_encryption_key_size_complete_after_encryption_change(uint8_t st
                          1 /* enable */);
   btm_sec_encrypt_change(handle, static_cast<tHCI_STATUS>(status),
                          1 /* enable */);
+  btu_hcif_report_encryption_change(handle, status, 1 /* enable */,
+                                    false /* key_refresh */);
 }
 /*******************************************************************************
  *
@@ -1072,6 +1095,8 @@ static void btu_hcif_encryption_change_evt(uint8_t* p) {
                            encr_enable);
     btm_sec_encrypt_change(handle, static_cast<tHCI_STATUS>(status),
                            encr_enable);
+    btu_hcif_report_encryption_change(handle, status, encr_enable,
+                                      false /* key_refresh */);
   } else {
     btsnd_hcic_read_encryption_key_size(handle, base::Bind(&read_encryption_key_size_complete_after_encryption_change));
   }
@@ -1601,7 +1626,8 @@ static void read_encryption_key_size_complete_after_key_refresh(uint8_t status,
     return;
   }
 
-  if (key_size < MIN_KEY_SIZE) {
+  if (!btm_sec_check_encryption_key_size(acl_address_from_handle(handle),
+                                         BT_TRANSPORT_BR_EDR, key_size)) {
     android_errorWriteLog(0x534e4554, "124301137");
     LOG(ERROR) << __func__ << " encryption key too short, disconnecting. handle: " << loghex(handle)
                << " key_size: " << +key_size;
@@ -1614,6 +1640,8 @@ static void read_encryption_key_size_complete_after_key_refresh(uint8_t status,
 
   btm_sec_encrypt_change(handle, static_cast<tHCI_STATUS>(status),
                          1 /* enc_enable */);
+  btu_hcif_report_encryption_change(handle, status, 1 /* enc_enable */,
+                                    true /* key_refresh */);
 }
 
 static void btu_hcif_encryption_key_refresh_cmpl_evt(uint8_t* p) {
@@ -1626,6 +1654,9 @@ static void btu_hcif_encryption_key_refresh_cmpl_evt(uint8_t* p) {
   if (status != HCI_SUCCESS || BTM_IsBleConnection(handle)) {
     btm_sec_encrypt_change(handle, static_cast<tHCI_STATUS>(status),
                            (status == HCI_SUCCESS) ? 1 : 0);
+    btu_hcif_report_encryption_change(handle, status,
+                                      (status == HCI_SUCCESS) ? 1 : 0,
+                                      true /* key_refresh */);
   } else {
     btsnd_hcic_read_encryption_key_size(handle, base::Bind(&read_encryption_key_size_complete_after_key_refresh));
   }
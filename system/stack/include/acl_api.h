@@ -170,6 +170,23 @@ tBTM_STATUS BTM_ReadFailedContactCounter(const RawAddress& remote_bda,
 tBTM_STATUS BTM_ReadTxPower(const RawAddress& remote_bda,
                             tBT_TRANSPORT transport, tBTM_CMPL_CB* p_cb);
 
+/*******************************************************************************
+ *
+ * Function         BTM_BleSetPowerReportCallbacks
+ *
+ * Description      Sets the callbacks called for the LE Transmit Power
+ *                  Reporting and LE Path Loss Threshold events, which come
+ *                  for the LE connections the reports were enabled for with
+ *                  HCI_LE_Set_Transmit_Power_Reporting_Enable and
+ *                  HCI_LE_Set_Path_Loss_Reporting_Enable. Events of links
+ *                  that are gone by then are dropped.
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+void BTM_BleSetPowerReportCallbacks(tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb,
+                                    tBTM_BLE_PATH_LOSS_CB* p_path_loss_cb);
+
 /*******************************************************************************
  *
  * Function         BTM_GetNumAclLinks
@@ -76,6 +76,16 @@ typedef struct {
   int8_t tx_power;
 } tBTM_TX_POWER_RESULT;
 
+/* Callbacks for the LE Transmit Power Reporting and LE Path Loss Threshold
+ * events of a connection, registered with BTM_BleSetPowerReportCallbacks.
+ */
+typedef void(tBTM_BLE_TX_POWER_REPORT_CB)(const RawAddress& bd_addr,
+                                          uint8_t reason, uint8_t phy,
+                                          int8_t tx_power, uint8_t flags,
+                                          int8_t delta);
+typedef void(tBTM_BLE_PATH_LOSS_CB)(const RawAddress& bd_addr,
+                                    uint8_t path_loss, uint8_t zone);
+
 /* Structure returned with read link quality event (in tBTM_CMPL_CB callback
  * function) in response to BTM_ReadLinkQuality call.
  */
@@ -428,6 +428,21 @@ bool BTM_ReadRemoteVersion(const RawAddress& addr, uint8_t* lmp_version,
  ******************************************************************************/
 uint8_t* BTM_ReadRemoteFeatures(const RawAddress& addr);
 
+/*******************************************************************************
+ *
+ * Function         BTM_ReadRemoteLeFeatures
+ *
+ * Description      This function is called to read the LE features of a
+ *                  remote device, as sent in the LE Read Remote Features
+ *                  Complete event
+ *
+ * Returns          pointer to the remote LE features mask, or nullptr if
+ *                  there is no LE link or the features haven't been read
+ *                  yet. The mask is HCI_FEATURE_BYTES_PER_PAGE bytes.
+ *
+ ******************************************************************************/
+const uint8_t* BTM_ReadRemoteLeFeatures(const RawAddress& addr);
+
 /*******************************************************************************
  *
  * Function         BTM_InqDbRead
@@ -533,6 +548,19 @@ void BTM_RemoveSco(const RawAddress& bda);
  ******************************************************************************/
 const RawAddress* BTM_ReadScoBdAddr(uint16_t sco_inx);
 
+/*******************************************************************************
+ *
+ * Function         BTM_ReadScoHandle
+ *
+ * Description      This function reads the HCI handle of the SCO connection
+ *                  to a remote device.
+ *
+ * Returns          HCI handle, or HCI_INVALID_HANDLE if there is no SCO
+ *                  connected to the device
+ *
+ ******************************************************************************/
+uint16_t BTM_ReadScoHandle(const RawAddress& bda);
+
 /*******************************************************************************
  *
  * Function         BTM_SetEScoMode
@@ -283,6 +283,10 @@ inline std::string security_mode_text(const tSECURITY_MODE& security_mode) {
   }
 }
 
+/* Shortest encryption key size allowed on a link, in octets, unless a longer
+ * minimum was set with BTM_SetMinEncryptionKeySize */
+#define BTM_SEC_MIN_ENC_KEY_SIZE 7
+
 /* BTM_SEC security masks */
 enum : uint16_t {
   /* Nothing required */
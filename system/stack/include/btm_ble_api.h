@@ -474,6 +474,21 @@ extern bool BTM_ReadConnectedTransportAddress(RawAddress* remote_bda,
  ******************************************************************************/
 extern bool BTM_BleConfigPrivacy(bool enable);
 
+/*******************************************************************************
+ *
+ * Function         BTM_BleConfigStaticAddress
+ *
+ * Description      This function is called to use a static random address
+ *                  instead of the public one, with privacy disabled.
+ *
+ * Parameters       static_addr: address to use; the two most significant bits
+ *                  must be set.
+ *
+ * Returns          bool    static address set success; otherwise failed.
+ *
+ ******************************************************************************/
+extern bool BTM_BleConfigStaticAddress(const RawAddress& static_addr);
+
 /*******************************************************************************
  *
  * Function         BTM_BleLocalPrivacyEnabled
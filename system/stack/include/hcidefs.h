@@ -428,6 +428,10 @@
 #define HCI_LE_ISO_TEST_END (0x0073 | HCI_GRP_BLE_CMDS)
 #define HCI_LE_SET_HOST_FEATURE (0x0074 | HCI_GRP_BLE_CMDS)
 #define HCI_LE_READ_ISO_LINK_QUALITY (0x0075 | HCI_GRP_BLE_CMDS)
+#define HCI_LE_ENH_READ_TRANSMIT_POWER_LEVEL (0x0076 | HCI_GRP_BLE_CMDS)
+#define HCI_LE_SET_PATH_LOSS_REPORTING_PARAMS (0x0078 | HCI_GRP_BLE_CMDS)
+#define HCI_LE_SET_PATH_LOSS_REPORTING_ENABLE (0x0079 | HCI_GRP_BLE_CMDS)
+#define HCI_LE_SET_TRANSMIT_POWER_REPORTING_ENABLE (0x007A | HCI_GRP_BLE_CMDS)
 
 /* Multi adv opcode */
 #define HCI_BLE_MULTI_ADV (0x0154 | HCI_GRP_VENDOR_SPECIFIC)
@@ -610,6 +614,8 @@ constexpr uint8_t HCI_LE_STATES_INIT_CENTRAL_PERIPHERAL_BIT = 41;
 #define HCI_BLE_BIG_SYNC_EST_EVT 0x1d
 #define HCI_BLE_BIG_SYNC_LOST_EVT 0x1e
 #define HCI_BLE_REQ_PEER_SCA_CPL_EVT 0x1f
+#define HCI_BLE_PATH_LOSS_THRESHOLD_EVT 0x20
+#define HCI_BLE_TRANSMIT_POWER_REPORTING_EVT 0x21
 
 #define HCI_LE_PERIODIC_ADV_SYNC_TRANSFERE_RECEIVED_EVT 0x18
 #define HCI_LE_BIGINFO_ADVERTISING_REPORT_EVT 0x22
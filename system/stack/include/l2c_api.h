@@ -716,6 +716,31 @@ extern bool L2CA_SetChnlFlushability(uint16_t cid, bool is_flushable);
 extern bool L2CA_GetPeerFeatures(const RawAddress& bd_addr,
                                  uint32_t* p_ext_feat, uint8_t* p_chnl_mask);
 
+/* Callback of L2CA_SendEcho with the data the peer echoed back */
+typedef void(tL2CA_ECHO_RSP_CB)(const RawAddress& bd_addr, uint8_t* p_data,
+                                uint16_t data_len);
+
+/*******************************************************************************
+ *
+ *  Function         L2CA_SendEcho
+ *
+ *  Description      Sends an echo request carrying |p_data| to the peer over
+ *                   the BR/EDR signalling channel. The response is passed to
+ *                   |p_cb|. A new request replaces the one before it, whose
+ *                   response is then dropped. Responses that never come are
+ *                   not reported, so the caller keeps its own timeout.
+ *
+ *  Parameters:      BD address of the peer
+ *                   Data to be echoed and its length
+ *                   Callback of the response
+ *
+ *  Return value:    true if the request was sent, false if there is no
+ *                   BR/EDR link to the peer or the data is too long
+ *
+ ******************************************************************************/
+extern bool L2CA_SendEcho(const RawAddress& bd_addr, const uint8_t* p_data,
+                          uint16_t data_len, tL2CA_ECHO_RSP_CB* p_cb);
+
 /*******************************************************************************
  *
  *                      Fixed Channel callback prototypes
@@ -1164,6 +1164,34 @@ bool L2CA_GetPeerFeatures(const RawAddress& bd_addr, uint32_t* p_ext_feat,
   return true;
 }
 
+/*******************************************************************************
+ *
+ *  Function        L2CA_SendEcho
+ *
+ *  Description     Sends an echo request to a connected peer.
+ *
+ *  Return value:   true if the request was sent
+ *
+ ******************************************************************************/
+bool L2CA_SendEcho(const RawAddress& bd_addr, const uint8_t* p_data,
+                   uint16_t data_len, tL2CA_ECHO_RSP_CB* p_cb) {
+  if (bluetooth::shim::is_gd_l2cap_enabled()) {
+    LOG(WARNING) << __func__ << " Not supported by GD L2CAP";
+    return false;
+  }
+
+  tL2C_LCB* p_lcb = l2cu_find_lcb_by_bd_addr(bd_addr, BT_TRANSPORT_BR_EDR);
+  if (p_lcb == NULL || p_lcb->link_state != LST_CONNECTED) {
+    LOG(WARNING) << __func__ << " No BDA: " << bd_addr;
+    return false;
+  }
+
+  if (!l2cu_send_peer_echo_req(p_lcb, p_data, data_len)) return false;
+
+  p_lcb->p_echo_rsp_cb = p_cb;
+  return true;
+}
+
 /*******************************************************************************
  *
  *  Function        L2CA_RegisterFixedChannel
@@ -457,6 +457,8 @@ typedef struct t_l2c_linkcb {
 
   uint8_t signal_id;                /* Signalling channel id */
   uint8_t cur_echo_id;              /* Current id value for echo request */
+  uint8_t echo_req_id;              /* Id of the echo request sent last */
+  tL2CA_ECHO_RSP_CB* p_echo_rsp_cb; /* Called with the echo response */
   uint16_t idle_timeout;            /* Idle timeout */
  private:
   bool is_bonding_{false};          /* True - link active only for bonding */
@@ -719,6 +721,8 @@ extern void l2cu_send_peer_config_rej(tL2C_CCB* p_ccb, uint8_t* p_data,
 extern void l2cu_send_peer_disc_req(tL2C_CCB* p_ccb);
 extern void l2cu_send_peer_disc_rsp(tL2C_LCB* p_lcb, uint8_t remote_id,
                                     uint16_t local_cid, uint16_t remote_cid);
+extern bool l2cu_send_peer_echo_req(tL2C_LCB* p_lcb, const uint8_t* p_data,
+                                    uint16_t data_len);
 extern void l2cu_send_peer_echo_rsp(tL2C_LCB* p_lcb, uint8_t id,
                                     uint8_t* p_data, uint16_t data_len);
 extern void l2cu_send_peer_info_rsp(tL2C_LCB* p_lcb, uint8_t id,
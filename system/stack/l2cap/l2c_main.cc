@@ -764,6 +764,15 @@ static void process_l2cap_cmd(tL2C_LCB* p_lcb, uint8_t* p, uint16_t pkt_len) {
         l2cu_send_peer_echo_rsp(p_lcb, id, p, cmd_len);
         break;
 
+      case L2CAP_CMD_ECHO_RSP:
+        /* Responses to earlier requests are of no interest any more */
+        if (p_lcb->p_echo_rsp_cb && id == p_lcb->echo_req_id) {
+          tL2CA_ECHO_RSP_CB* p_cb = p_lcb->p_echo_rsp_cb;
+          p_lcb->p_echo_rsp_cb = NULL;
+          (*p_cb)(p_lcb->remote_bd_addr, p, cmd_len);
+        }
+        break;
+
       case L2CAP_CMD_INFO_REQ: {
         uint16_t info_type;
         if (p + 2 > p_next_cmd) {
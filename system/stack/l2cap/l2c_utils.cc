@@ -946,6 +946,64 @@ void l2cu_send_peer_disc_rsp(tL2C_LCB* p_lcb, uint8_t remote_id,
   l2c_link_check_send_pkts(p_lcb, 0, p_buf);
 }
 
+/* Largest data of an echo request or response that fits in ACL and L2CAP MTU */
+static uint16_t l2cu_max_echo_data_len(void) {
+  uint16_t acl_data_size =
+      controller_get_interface()->get_acl_data_size_classic();
+  uint16_t acl_packet_size =
+      controller_get_interface()->get_acl_packet_size_classic();
+  uint16_t maxlen = (L2CAP_CMD_BUF_SIZE > acl_packet_size)
+                        ? acl_data_size
+                        : (uint16_t)L2CAP_CMD_BUF_SIZE;
+  return maxlen - (uint16_t)(BT_HDR_SIZE + HCI_DATA_PREAMBLE_SIZE +
+                             L2CAP_PKT_OVERHEAD + L2CAP_CMD_OVERHEAD +
+                             L2CAP_ECHO_RSP_LEN);
+}
+
+/*******************************************************************************
+ *
+ * Function         l2cu_send_peer_echo_req
+ *
+ * Description      Build and send an L2CAP "echo request" message carrying
+ *                  |p_data| to the peer. Its identifier is kept so that only
+ *                  the response to the latest request is reported.
+ *
+ * Returns          true if the request was sent
+ *
+ ******************************************************************************/
+bool l2cu_send_peer_echo_req(tL2C_LCB* p_lcb, const uint8_t* p_data,
+                             uint16_t data_len) {
+  BT_HDR* p_buf;
+  uint8_t* p;
+
+  if (data_len > l2cu_max_echo_data_len()) {
+    L2CAP_TRACE_WARNING("L2CAP echo request data too long (%d)", data_len);
+    return false;
+  }
+
+  /* Create an identifier for this packet */
+  p_lcb->signal_id++;
+  l2cu_adj_id(p_lcb);
+
+  p_buf = l2cu_build_header(p_lcb, (uint16_t)(L2CAP_ECHO_REQ_LEN + data_len),
+                            L2CAP_CMD_ECHO_REQ, p_lcb->signal_id);
+  if (p_buf == NULL) {
+    L2CAP_TRACE_WARNING("L2CAP - no buffer for echo_req");
+    return false;
+  }
+
+  p = (uint8_t*)(p_buf + 1) + L2CAP_SEND_CMD_OFFSET + HCI_DATA_PREAMBLE_SIZE +
+      L2CAP_PKT_OVERHEAD + L2CAP_CMD_OVERHEAD;
+
+  if (data_len) {
+    ARRAY_TO_STREAM(p, p_data, data_len);
+  }
+
+  p_lcb->echo_req_id = p_lcb->signal_id;
+  l2c_link_check_send_pkts(p_lcb, 0, p_buf);
+  return true;
+}
+
 /*******************************************************************************
  *
  * Function         l2cu_send_peer_echo_rsp
@@ -971,17 +1029,8 @@ void l2cu_send_peer_echo_rsp(tL2C_LCB* p_lcb, uint8_t signal_id,
   } else
     p_lcb->cur_echo_id = signal_id;
 
-  uint16_t acl_data_size =
-      controller_get_interface()->get_acl_data_size_classic();
-  uint16_t acl_packet_size =
-      controller_get_interface()->get_acl_packet_size_classic();
   /* Don't return data if it does not fit in ACL and L2CAP MTU */
-  maxlen = (L2CAP_CMD_BUF_SIZE > acl_packet_size)
-               ? acl_data_size
-               : (uint16_t)L2CAP_CMD_BUF_SIZE;
-  maxlen -=
-      (uint16_t)(BT_HDR_SIZE + HCI_DATA_PREAMBLE_SIZE + L2CAP_PKT_OVERHEAD +
-                 L2CAP_CMD_OVERHEAD + L2CAP_ECHO_RSP_LEN);
+  maxlen = l2cu_max_echo_data_len();
 
   if (data_len > maxlen) data_len = 0;
 
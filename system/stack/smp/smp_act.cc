@@ -564,6 +564,15 @@ void smp_proc_pair_cmd(tSMP_CB* p_cb, tSMP_INT_DATA* p_data) {
     return;
   }
 
+  /* the key can't be longer than the peer's maximum key size */
+  if (!btm_sec_check_encryption_key_size(p_cb->pairing_bda, BT_TRANSPORT_LE,
+                                         p_cb->peer_enc_size)) {
+    tSMP_INT_DATA smp_int_data;
+    smp_int_data.status = SMP_ENC_KEY_SIZE;
+    smp_sm_event(p_cb, SMP_AUTH_CMPL_EVT, &smp_int_data);
+    return;
+  }
+
   // PTS Testing failure modes
   if (pts_test_send_authentication_complete_failure(p_cb)) return;
 
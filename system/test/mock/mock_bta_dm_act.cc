@@ -47,6 +47,8 @@ struct BTA_dm_notify_remote_features_complete
 struct BTA_dm_on_hw_off BTA_dm_on_hw_off;
 struct BTA_dm_on_hw_on BTA_dm_on_hw_on;
 struct BTA_dm_report_role_change BTA_dm_report_role_change;
+struct BTA_dm_report_key_size_violation BTA_dm_report_key_size_violation;
+struct BTA_dm_report_encryption_change BTA_dm_report_encryption_change;
 struct bta_dm_acl_up bta_dm_acl_up;
 struct bta_dm_add_ble_device bta_dm_add_ble_device;
 struct bta_dm_add_blekey bta_dm_add_blekey;
@@ -137,6 +139,21 @@ void BTA_dm_report_role_change(const RawAddress bd_addr, tHCI_ROLE new_role,
   test::mock::bta_dm_act::BTA_dm_report_role_change(bd_addr, new_role,
                                                     hci_status);
 }
+void BTA_dm_report_key_size_violation(const RawAddress bd_addr,
+                                      tBT_TRANSPORT transport,
+                                      uint8_t key_size) {
+  mock_function_count_map[__func__]++;
+  test::mock::bta_dm_act::BTA_dm_report_key_size_violation(bd_addr, transport,
+                                                           key_size);
+}
+void BTA_dm_report_encryption_change(const RawAddress bd_addr,
+                                     tBT_TRANSPORT transport,
+                                     tHCI_STATUS hci_status, bool encrypted,
+                                     bool key_refresh) {
+  mock_function_count_map[__func__]++;
+  test::mock::bta_dm_act::BTA_dm_report_encryption_change(
+      bd_addr, transport, hci_status, encrypted, key_refresh);
+}
 void bta_dm_acl_up(const RawAddress& bd_addr, tBT_TRANSPORT transport) {
   mock_function_count_map[__func__]++;
   test::mock::bta_dm_act::bta_dm_acl_up(bd_addr, transport);
@@ -143,6 +143,38 @@ struct BTA_dm_report_role_change {
 };
 extern struct BTA_dm_report_role_change BTA_dm_report_role_change;
 
+// Name: BTA_dm_report_key_size_violation
+// Params: const RawAddress bd_addr, tBT_TRANSPORT transport, uint8_t key_size
+// Return: void
+struct BTA_dm_report_key_size_violation {
+  std::function<void(const RawAddress bd_addr, tBT_TRANSPORT transport,
+                     uint8_t key_size)>
+      body{[](const RawAddress bd_addr, tBT_TRANSPORT transport,
+              uint8_t key_size) {}};
+  void operator()(const RawAddress bd_addr, tBT_TRANSPORT transport,
+                  uint8_t key_size) {
+    body(bd_addr, transport, key_size);
+  };
+};
+extern struct BTA_dm_report_key_size_violation
+    BTA_dm_report_key_size_violation;
+
+// Name: BTA_dm_report_encryption_change
+// Params: const RawAddress bd_addr, tBT_TRANSPORT transport, tHCI_STATUS
+// hci_status, bool encrypted, bool key_refresh
+// Return: void
+struct BTA_dm_report_encryption_change {
+  std::function<void(const RawAddress bd_addr, tBT_TRANSPORT transport,
+                     tHCI_STATUS hci_status, bool encrypted, bool key_refresh)>
+      body{[](const RawAddress bd_addr, tBT_TRANSPORT transport,
+              tHCI_STATUS hci_status, bool encrypted, bool key_refresh) {}};
+  void operator()(const RawAddress bd_addr, tBT_TRANSPORT transport,
+                  tHCI_STATUS hci_status, bool encrypted, bool key_refresh) {
+    body(bd_addr, transport, hci_status, encrypted, key_refresh);
+  };
+};
+extern struct BTA_dm_report_encryption_change BTA_dm_report_encryption_change;
+
 // Name: bta_dm_acl_up
 // Params: const RawAddress& bd_addr, tBT_TRANSPORT transport
 // Return: void
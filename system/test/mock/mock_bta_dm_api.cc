@@ -16,7 +16,7 @@
 
 /*
  * Generated mock file from original source file
- *   Functions generated:34
+ *   Functions generated:35
  *
  *  mockcify.pl ver 0.3.0
  */
@@ -45,6 +45,7 @@ struct BTA_DmAddBleDevice BTA_DmAddBleDevice;
 struct BTA_DmAddBleKey BTA_DmAddBleKey;
 struct BTA_DmAddDevice BTA_DmAddDevice;
 struct BTA_DmBleConfigLocalPrivacy BTA_DmBleConfigLocalPrivacy;
+struct BTA_DmBleConfigStaticAddress BTA_DmBleConfigStaticAddress;
 struct BTA_DmBleConfirmReply BTA_DmBleConfirmReply;
 struct BTA_DmBleCsisObserve BTA_DmBleCsisObserve;
 struct BTA_DmBleGetEnergyInfo BTA_DmBleGetEnergyInfo;
@@ -100,6 +101,10 @@ void BTA_DmBleConfigLocalPrivacy(bool privacy_enable) {
   mock_function_count_map[__func__]++;
   test::mock::bta_dm_api::BTA_DmBleConfigLocalPrivacy(privacy_enable);
 }
+void BTA_DmBleConfigStaticAddress(const RawAddress& static_addr) {
+  mock_function_count_map[__func__]++;
+  test::mock::bta_dm_api::BTA_DmBleConfigStaticAddress(static_addr);
+}
 void BTA_DmBleConfirmReply(const RawAddress& bd_addr, bool accept) {
   mock_function_count_map[__func__]++;
   test::mock::bta_dm_api::BTA_DmBleConfirmReply(bd_addr, accept);
@@ -16,7 +16,7 @@
 
 /*
  * Generated mock file from original source file
- *   Functions generated:34
+ *   Functions generated:35
  *
  *  mockcify.pl ver 0.3.0
  */
@@ -112,6 +112,16 @@ struct BTA_DmBleConfigLocalPrivacy {
 };
 extern struct BTA_DmBleConfigLocalPrivacy BTA_DmBleConfigLocalPrivacy;
 
+// Name: BTA_DmBleConfigStaticAddress
+// Params: const RawAddress& static_addr
+// Return: void
+struct BTA_DmBleConfigStaticAddress {
+  std::function<void(const RawAddress& static_addr)> body{
+      [](const RawAddress& static_addr) {}};
+  void operator()(const RawAddress& static_addr) { body(static_addr); };
+};
+extern struct BTA_DmBleConfigStaticAddress BTA_DmBleConfigStaticAddress;
+
 // Name: BTA_DmBleConfirmReply
 // Params: const RawAddress& bd_addr, bool accept
 // Return: void
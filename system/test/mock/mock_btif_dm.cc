@@ -130,6 +130,11 @@ void btif_dm_remove_ble_bonding_keys(void) {
 void btif_dm_remove_bond(const RawAddress bd_addr) {
   mock_function_count_map[__func__]++;
 }
+void btif_dm_set_le_address_policy(bool privacy_enabled,
+                                   const RawAddress& static_address,
+                                   uint32_t rpa_timeout_s) {
+  mock_function_count_map[__func__]++;
+}
 void btif_dm_set_oob_for_io_req(tBTM_OOB_DATA* p_has_oob_data) {
   mock_function_count_map[__func__]++;
 }
@@ -57,6 +57,17 @@ void bluetooth::shim::ACL_IgnoreLeConnectionFrom(
 void bluetooth::shim::ACL_ConfigureLePrivacy(bool is_le_privacy_enabled) {
   mock_function_count_map[__func__]++;
 }
+void bluetooth::shim::ACL_ConfigureLeStaticAddress(
+    const RawAddress& static_address) {
+  mock_function_count_map[__func__]++;
+}
+void bluetooth::shim::ACL_SetLeRpaTimeout(std::chrono::seconds timeout) {
+  mock_function_count_map[__func__]++;
+}
+tBLE_ADDR_TYPE bluetooth::shim::ACL_GetLeOwnAddressType() {
+  mock_function_count_map[__func__]++;
+  return BLE_ADDR_PUBLIC;
+}
 void bluetooth::shim::ACL_WriteData(uint16_t handle, BT_HDR* p_buf) {
   mock_function_count_map[__func__]++;
 }
@@ -83,6 +83,7 @@ struct BTM_GetRole BTM_GetRole;
 struct BTM_ReadFailedContactCounter BTM_ReadFailedContactCounter;
 struct BTM_ReadRSSI BTM_ReadRSSI;
 struct BTM_ReadTxPower BTM_ReadTxPower;
+struct BTM_BleSetPowerReportCallbacks BTM_BleSetPowerReportCallbacks;
 struct BTM_SetLinkSuperTout BTM_SetLinkSuperTout;
 struct BTM_SwitchRoleToCentral BTM_SwitchRoleToCentral;
 struct btm_remove_acl btm_remove_acl;
@@ -96,6 +97,7 @@ struct BTM_SetTraceLevel BTM_SetTraceLevel;
 struct acl_link_role_from_handle acl_link_role_from_handle;
 struct btm_handle_to_acl_index btm_handle_to_acl_index;
 struct BTM_ReadRemoteFeatures BTM_ReadRemoteFeatures;
+struct BTM_ReadRemoteLeFeatures BTM_ReadRemoteLeFeatures;
 struct ACL_RegisterClient ACL_RegisterClient;
 struct ACL_UnregisterClient ACL_UnregisterClient;
 struct BTM_ReadConnectionAddr BTM_ReadConnectionAddr;
@@ -165,6 +167,9 @@ struct btm_read_rssi_complete btm_read_rssi_complete;
 struct btm_read_rssi_timeout btm_read_rssi_timeout;
 struct btm_read_tx_power_complete btm_read_tx_power_complete;
 struct btm_read_tx_power_timeout btm_read_tx_power_timeout;
+struct btm_ble_transmit_power_reporting_evt
+    btm_ble_transmit_power_reporting_evt;
+struct btm_ble_path_loss_threshold_evt btm_ble_path_loss_threshold_evt;
 struct btm_rejectlist_role_change_device btm_rejectlist_role_change_device;
 struct btm_set_link_policy btm_set_link_policy;
 struct btm_set_packet_types_from_address btm_set_packet_types_from_address;
@@ -342,6 +347,12 @@ tBTM_STATUS BTM_ReadTxPower(const RawAddress& remote_bda,
   mock_function_count_map[__func__]++;
   return test::mock::stack_acl::BTM_ReadTxPower(remote_bda, transport, p_cb);
 }
+void BTM_BleSetPowerReportCallbacks(tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb,
+                                    tBTM_BLE_PATH_LOSS_CB* p_path_loss_cb) {
+  mock_function_count_map[__func__]++;
+  test::mock::stack_acl::BTM_BleSetPowerReportCallbacks(p_tx_power_cb,
+                                                        p_path_loss_cb);
+}
 tBTM_STATUS BTM_SetLinkSuperTout(const RawAddress& remote_bda,
                                  uint16_t timeout) {
   mock_function_count_map[__func__]++;
@@ -396,6 +407,10 @@ uint8_t* BTM_ReadRemoteFeatures(const RawAddress& addr) {
   mock_function_count_map[__func__]++;
   return test::mock::stack_acl::BTM_ReadRemoteFeatures(addr);
 }
+const uint8_t* BTM_ReadRemoteLeFeatures(const RawAddress& addr) {
+  mock_function_count_map[__func__]++;
+  return test::mock::stack_acl::BTM_ReadRemoteLeFeatures(addr);
+}
 void ACL_RegisterClient(struct acl_client_callback_s* callbacks) {
   mock_function_count_map[__func__]++;
   test::mock::stack_acl::ACL_RegisterClient(callbacks);
@@ -681,6 +696,14 @@ void btm_read_tx_power_timeout(UNUSED_ATTR void* data) {
   mock_function_count_map[__func__]++;
   test::mock::stack_acl::btm_read_tx_power_timeout(data);
 }
+void btm_ble_transmit_power_reporting_evt(uint8_t* p, uint8_t evt_len) {
+  mock_function_count_map[__func__]++;
+  test::mock::stack_acl::btm_ble_transmit_power_reporting_evt(p, evt_len);
+}
+void btm_ble_path_loss_threshold_evt(uint8_t* p, uint8_t evt_len) {
+  mock_function_count_map[__func__]++;
+  test::mock::stack_acl::btm_ble_path_loss_threshold_evt(p, evt_len);
+}
 void btm_rejectlist_role_change_device(const RawAddress& bd_addr,
                                        uint8_t hci_status) {
   mock_function_count_map[__func__]++;
@@ -440,6 +440,20 @@ struct BTM_ReadTxPower {
   };
 };
 extern struct BTM_ReadTxPower BTM_ReadTxPower;
+// Name: BTM_BleSetPowerReportCallbacks
+// Params: tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb, tBTM_BLE_PATH_LOSS_CB*
+// p_path_loss_cb Returns: void
+struct BTM_BleSetPowerReportCallbacks {
+  std::function<void(tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb,
+                     tBTM_BLE_PATH_LOSS_CB* p_path_loss_cb)>
+      body{[](tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb,
+              tBTM_BLE_PATH_LOSS_CB* p_path_loss_cb) { ; }};
+  void operator()(tBTM_BLE_TX_POWER_REPORT_CB* p_tx_power_cb,
+                  tBTM_BLE_PATH_LOSS_CB* p_path_loss_cb) {
+    body(p_tx_power_cb, p_path_loss_cb);
+  };
+};
+extern struct BTM_BleSetPowerReportCallbacks BTM_BleSetPowerReportCallbacks;
 // Name: BTM_SetLinkSuperTout
 // Params: const RawAddress& remote_bda, uint16_t timeout
 // Returns: tBTM_STATUS
@@ -570,6 +584,15 @@ struct BTM_ReadRemoteFeatures {
   uint8_t* operator()(const RawAddress& addr) { return body(addr); };
 };
 extern struct BTM_ReadRemoteFeatures BTM_ReadRemoteFeatures;
+// Name: BTM_ReadRemoteLeFeatures
+// Params: const RawAddress& addr
+// Returns: const uint8_t*
+struct BTM_ReadRemoteLeFeatures {
+  std::function<const uint8_t*(const RawAddress& addr)> body{
+      [](const RawAddress& addr) { return nullptr; }};
+  const uint8_t* operator()(const RawAddress& addr) { return body(addr); };
+};
+extern struct BTM_ReadRemoteLeFeatures BTM_ReadRemoteLeFeatures;
 // Name: ACL_RegisterClient
 // Params: struct acl_client_callback_s* callbacks
 // Returns: void
@@ -1211,6 +1234,25 @@ struct btm_read_tx_power_complete {
   void operator()(uint8_t* p, bool is_ble) { body(p, is_ble); };
 };
 extern struct btm_read_tx_power_complete btm_read_tx_power_complete;
+// Name: btm_ble_transmit_power_reporting_evt
+// Params: uint8_t* p, uint8_t evt_len
+// Returns: void
+struct btm_ble_transmit_power_reporting_evt {
+  std::function<void(uint8_t* p, uint8_t evt_len)> body{
+      [](uint8_t* p, uint8_t evt_len) { ; }};
+  void operator()(uint8_t* p, uint8_t evt_len) { body(p, evt_len); };
+};
+extern struct btm_ble_transmit_power_reporting_evt
+    btm_ble_transmit_power_reporting_evt;
+// Name: btm_ble_path_loss_threshold_evt
+// Params: uint8_t* p, uint8_t evt_len
+// Returns: void
+struct btm_ble_path_loss_threshold_evt {
+  std::function<void(uint8_t* p, uint8_t evt_len)> body{
+      [](uint8_t* p, uint8_t evt_len) { ; }};
+  void operator()(uint8_t* p, uint8_t evt_len) { body(p, evt_len); };
+};
+extern struct btm_ble_path_loss_threshold_evt btm_ble_path_loss_threshold_evt;
 // Name: btm_read_tx_power_timeout
 // Params: UNUSED_ATTR void* data
 // Returns: void
@@ -70,6 +70,10 @@ bool BTM_BleConfigPrivacy(bool privacy_mode) {
   mock_function_count_map[__func__]++;
   return false;
 }
+bool BTM_BleConfigStaticAddress(const RawAddress& static_addr) {
+  mock_function_count_map[__func__]++;
+  return false;
+}
 bool BTM_BleLocalPrivacyEnabled(void) {
   mock_function_count_map[__func__]++;
   return false;
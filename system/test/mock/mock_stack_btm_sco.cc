@@ -53,6 +53,10 @@ const RawAddress* BTM_ReadScoBdAddr(uint16_t sco_inx) {
   mock_function_count_map[__func__]++;
   return nullptr;
 }
+uint16_t BTM_ReadScoHandle(const RawAddress& bda) {
+  mock_function_count_map[__func__]++;
+  return HCI_INVALID_HANDLE;
+}
 tBTM_STATUS BTM_CreateSco(const RawAddress* remote_bda, bool is_orig,
                           uint16_t pkt_types, uint16_t* p_sco_inx,
                           tBTM_SCO_CB* p_conn_cb, tBTM_SCO_CB* p_disc_cb) {
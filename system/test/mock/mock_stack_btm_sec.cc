@@ -236,6 +236,17 @@ void BTM_RemoteOobDataReply(tBTM_STATUS res, const RawAddress& bd_addr,
 void BTM_SetPinType(uint8_t pin_type, PIN_CODE pin_code, uint8_t pin_code_len) {
   mock_function_count_map[__func__]++;
 }
+void BTM_SetSecureConnectionsOnly(bool enabled) {
+  mock_function_count_map[__func__]++;
+}
+void BTM_SetMinEncryptionKeySize(uint8_t key_size) {
+  mock_function_count_map[__func__]++;
+}
+uint8_t BTM_GetEncryptionKeySize(const RawAddress& bd_addr,
+                                 tBT_TRANSPORT transport) {
+  mock_function_count_map[__func__]++;
+  return 0;
+}
 void NotifyBondingCanceled(tBTM_STATUS btm_status) {
   mock_function_count_map[__func__]++;
 }
@@ -286,6 +297,12 @@ void btm_sec_encrypt_change(uint16_t handle, tHCI_STATUS status,
                             uint8_t encr_enable) {
   mock_function_count_map[__func__]++;
 }
+bool btm_sec_check_encryption_key_size(const RawAddress& bd_addr,
+                                       tBT_TRANSPORT transport,
+                                       uint8_t key_size) {
+  mock_function_count_map[__func__]++;
+  return true;
+}
 void btm_sec_link_key_notification(const RawAddress& p_bda,
                                    const Octet16& link_key, uint8_t key_type) {
   mock_function_count_map[__func__]++;
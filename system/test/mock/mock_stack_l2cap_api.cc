@@ -88,6 +88,7 @@ struct L2CA_SetAclPriority L2CA_SetAclPriority;
 struct L2CA_SetAclLatency L2CA_SetAclLatency;
 struct L2CA_SetTxPriority L2CA_SetTxPriority;
 struct L2CA_GetPeerFeatures L2CA_GetPeerFeatures;
+struct L2CA_SendEcho L2CA_SendEcho;
 struct L2CA_RegisterFixedChannel L2CA_RegisterFixedChannel;
 struct L2CA_ConnectFixedChnl L2CA_ConnectFixedChnl;
 struct L2CA_SendFixedChnlData L2CA_SendFixedChnlData;
@@ -235,6 +236,12 @@ bool L2CA_GetPeerFeatures(const RawAddress& bd_addr, uint32_t* p_ext_feat,
   return test::mock::stack_l2cap_api::L2CA_GetPeerFeatures(bd_addr, p_ext_feat,
                                                            p_chnl_mask);
 }
+bool L2CA_SendEcho(const RawAddress& bd_addr, const uint8_t* p_data,
+                   uint16_t data_len, tL2CA_ECHO_RSP_CB* p_cb) {
+  mock_function_count_map[__func__]++;
+  return test::mock::stack_l2cap_api::L2CA_SendEcho(bd_addr, p_data, data_len,
+                                                    p_cb);
+}
 bool L2CA_RegisterFixedChannel(uint16_t fixed_cid,
                                tL2CAP_FIXED_CHNL_REG* p_freg) {
   mock_function_count_map[__func__]++;
@@ -362,6 +362,21 @@ struct L2CA_GetPeerFeatures {
   };
 };
 extern struct L2CA_GetPeerFeatures L2CA_GetPeerFeatures;
+// Name: L2CA_SendEcho
+// Params: const RawAddress& bd_addr, const uint8_t* p_data, uint16_t data_len,
+// tL2CA_ECHO_RSP_CB* p_cb
+// Returns: bool
+struct L2CA_SendEcho {
+  std::function<bool(const RawAddress& bd_addr, const uint8_t* p_data,
+                     uint16_t data_len, tL2CA_ECHO_RSP_CB* p_cb)>
+      body{[](const RawAddress& bd_addr, const uint8_t* p_data,
+              uint16_t data_len, tL2CA_ECHO_RSP_CB* p_cb) { return false; }};
+  bool operator()(const RawAddress& bd_addr, const uint8_t* p_data,
+                  uint16_t data_len, tL2CA_ECHO_RSP_CB* p_cb) {
+    return body(bd_addr, p_data, data_len, p_cb);
+  };
+};
+extern struct L2CA_SendEcho L2CA_SendEcho;
 // Name: L2CA_RegisterFixedChannel
 // Params: uint16_t fixed_cid, tL2CAP_FIXED_CHNL_REG* p_freg
 // Returns: bool
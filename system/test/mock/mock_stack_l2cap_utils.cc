@@ -259,6 +259,11 @@ void l2cu_send_peer_disc_rsp(tL2C_LCB* p_lcb, uint8_t remote_id,
                              uint16_t local_cid, uint16_t remote_cid) {
   mock_function_count_map[__func__]++;
 }
+bool l2cu_send_peer_echo_req(tL2C_LCB* p_lcb, const uint8_t* p_data,
+                             uint16_t data_len) {
+  mock_function_count_map[__func__]++;
+  return false;
+}
 void l2cu_send_peer_echo_rsp(tL2C_LCB* p_lcb, uint8_t signal_id,
                              uint8_t* p_data, uint16_t data_len) {
   mock_function_count_map[__func__]++;